@@ -1,15 +1,21 @@
 use thiserror::Error;
 use tonic::{Code, Status};
 use spacepanda_core::core_space::{ChannelError, SpaceError, MembershipError, InviteError};
+use spacepanda_core::core_mvp::errors::MvpError;
+
+use crate::token::TokenError;
 
 #[derive(Debug, Error)]
 pub enum ApiError {
     #[error("Authentication failed: {0}")]
     AuthenticationFailed(String),
 
-    #[error("Invalid session token")]
+    #[error("Session has ended, log in again")]
     InvalidSession,
 
+    #[error("{0}")]
+    Token(#[from] TokenError),
+
     #[error("Space not found: {0}")]
     SpaceNotFound(String),
 
@@ -31,6 +37,9 @@ pub enum ApiError {
     #[error("Invite error: {0}")]
     InviteError(#[from] InviteError),
 
+    #[error("{0}")]
+    Messaging(#[from] MvpError),
+
     #[error("Internal error: {0}")]
     Internal(#[from] anyhow::Error),
 
@@ -48,8 +57,9 @@ impl From<ApiError> for Status {
                 Status::new(Code::Unauthenticated, msg)
             }
             ApiError::InvalidSession => {
-                Status::new(Code::Unauthenticated, "Invalid session token")
+                Status::new(Code::Unauthenticated, "Session has ended, log in again")
             }
+            ApiError::Token(e) => Status::from(e),
             ApiError::SpaceNotFound(id) => {
                 Status::new(Code::NotFound, format!("Space not found: {}", id))
             }
@@ -61,6 +71,7 @@ impl From<ApiError> for Status {
             ApiError::ChannelError(e) => Status::new(Code::Internal, e.to_string()),
             ApiError::MembershipError(e) => Status::new(Code::Internal, e.to_string()),
             ApiError::InviteError(e) => Status::new(Code::Internal, e.to_string()),
+            ApiError::Messaging(e) => Status::new(messaging_code(&e), e.to_string()),
             ApiError::Internal(e) => Status::new(Code::Internal, e.to_string()),
             ApiError::IoError(e) => Status::new(Code::Internal, e.to_string()),
             ApiError::JsonError(e) => Status::new(Code::Internal, e.to_string()),
@@ -68,4 +79,30 @@ impl From<ApiError> for Status {
     }
 }
 
+/// Status code a client should see for a channel manager error
+fn messaging_code(err: &MvpError) -> Code {
+    match err {
+        MvpError::ChannelNotFound(_)
+        | MvpError::MemberNotFound { .. }
+        | MvpError::MessageNotFound(_)
+        | MvpError::WebhookNotFound(_)
+        | MvpError::DeviceNotFound(_) => Code::NotFound,
+        MvpError::ChannelExists(_) => Code::AlreadyExists,
+        MvpError::PermissionDenied { .. } | MvpError::Unauthorized(_) => Code::PermissionDenied,
+        MvpError::InvalidInvite(_)
+        | MvpError::InvalidKeyPackage(_)
+        | MvpError::InvalidMessage(_)
+        | MvpError::PayloadTooLarge { .. } => Code::InvalidArgument,
+        MvpError::InviteExpired(_)
+        | MvpError::NoKeyPackages(_)
+        | MvpError::ChannelQuarantined(_)
+        | MvpError::InvalidOperation(_) => Code::FailedPrecondition,
+        MvpError::RateLimited(_) | MvpError::InsufficientStorage { .. } => Code::ResourceExhausted,
+        MvpError::Dht(_) | MvpError::NetworkError(_) | MvpError::ChunkUnavailable { .. } => {
+            Code::Unavailable
+        }
+        _ => Code::Internal,
+    }
+}
+
 pub type ApiResult<T> = Result<T, ApiError>;
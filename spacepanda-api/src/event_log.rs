@@ -0,0 +1,165 @@
+//! Numbered log of a session's recent channel events
+//!
+//! Streams hand each event's sequence number to the client, which passes the
+//! last one it got back when it reconnects. The log keeps the most recent
+//! events so a reconnecting client gets everything it missed in between,
+//! without the manager's broadcast channel having to hold them.
+
+use spacepanda_core::core_mvp::events::ChannelEvent;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+/// Events kept for reconnecting clients
+pub const EVENT_LOG_CAPACITY: usize = 1024;
+
+/// An event and its position in the session's stream
+pub type SequencedEvent = (u64, ChannelEvent);
+
+/// Why a stream can't resume where the client asked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResumeError {
+    /// Events after the token have already been dropped from the log
+    Expired { oldest: u64 },
+
+    /// The token is ahead of the log, e.g. from an earlier session
+    Unknown { latest: u64 },
+}
+
+struct LogState {
+    /// Sequence of the newest event, 0 before the first
+    latest: u64,
+    entries: VecDeque<SequencedEvent>,
+}
+
+/// The session's recent events, numbered from 1
+pub struct EventLog {
+    state: Mutex<LogState>,
+    live: broadcast::Sender<SequencedEvent>,
+    capacity: usize,
+}
+
+impl EventLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            state: Mutex::new(LogState { latest: 0, entries: VecDeque::with_capacity(capacity) }),
+            live: broadcast::channel(capacity).0,
+            capacity,
+        }
+    }
+
+    /// Number and keep an event, then pass it to live subscribers
+    pub fn record(&self, event: ChannelEvent) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        state.latest += 1;
+        let sequence = state.latest;
+        if state.entries.len() == self.capacity {
+            state.entries.pop_front();
+        }
+        state.entries.push_back((sequence, event.clone()));
+
+        // Sent under the lock, so a subscriber sees each event once
+        let _ = self.live.send((sequence, event));
+        sequence
+    }
+
+    /// Events after `resume_after`, and a receiver for the ones after those
+    ///
+    /// With `resume_after` 0, nothing is replayed.
+    pub fn subscribe_after(
+        &self,
+        resume_after: u64,
+    ) -> Result<(Vec<SequencedEvent>, broadcast::Receiver<SequencedEvent>), ResumeError> {
+        let state = self.state.lock().unwrap();
+        if resume_after > state.latest {
+            return Err(ResumeError::Unknown { latest: state.latest });
+        }
+
+        let replay = if resume_after == 0 {
+            Vec::new()
+        } else {
+            let oldest = state.entries.front().map_or(state.latest + 1, |(sequence, _)| *sequence);
+            if resume_after + 1 < oldest {
+                return Err(ResumeError::Expired { oldest });
+            }
+            state
+                .entries
+                .iter()
+                .filter(|(sequence, _)| *sequence > resume_after)
+                .cloned()
+                .collect()
+        };
+        Ok((replay, self.live.subscribe()))
+    }
+
+    /// Record every event from `events` until the sender goes away
+    pub fn spawn_recorder(
+        self: Arc<Self>,
+        mut events: broadcast::Receiver<ChannelEvent>,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => {
+                        self.record(event);
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!(skipped, "Event log fell behind, events were not recorded");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spacepanda_core::core_mvp::events::ChannelEventKind;
+    use spacepanda_core::core_store::model::types::{ChannelId, Timestamp, UserId};
+
+    fn typing(name: &str) -> ChannelEvent {
+        ChannelEvent {
+            channel_id: ChannelId("general".to_string()),
+            epoch: 1,
+            seq: 0,
+            actor: Some(UserId(name.to_string())),
+            timestamp: Timestamp::now(),
+            kind: ChannelEventKind::Typing(UserId(name.to_string())),
+        }
+    }
+
+    #[test]
+    fn test_resume_replays_missed_events() {
+        let log = EventLog::new(3);
+        for name in ["a", "b", "c", "d"] {
+            log.record(typing(name));
+        }
+
+        let (replay, _) = log.subscribe_after(2).unwrap();
+        let sequences: Vec<u64> = replay.iter().map(|(sequence, _)| *sequence).collect();
+        assert_eq!(sequences, vec![3, 4]);
+
+        // Live only
+        let (replay, mut live) = log.subscribe_after(0).unwrap();
+        assert!(replay.is_empty());
+        log.record(typing("e"));
+        assert_eq!(live.try_recv().unwrap().0, 5);
+    }
+
+    #[test]
+    fn test_resume_outside_the_log_fails() {
+        let log = EventLog::new(2);
+        for name in ["a", "b", "c"] {
+            log.record(typing(name));
+        }
+
+        // Event 2 is the oldest kept, so resuming after 1 still works
+        assert!(log.subscribe_after(1).is_ok());
+        assert_eq!(log.record(typing("d")), 4);
+        assert_eq!(log.subscribe_after(1).unwrap_err(), ResumeError::Expired { oldest: 3 });
+        assert_eq!(log.subscribe_after(9).unwrap_err(), ResumeError::Unknown { latest: 4 });
+    }
+}
@@ -0,0 +1,147 @@
+//! Accepting connections for the gRPC server
+//!
+//! Connections are accepted here rather than by tonic so their number can be
+//! capped and TLS handshakes can use whichever certificate is current. At most
+//! `max_connections` are open at once; further clients wait in the listen
+//! backlog until one closes. Handshakes run on their own tasks, so a slow
+//! client doesn't hold up the ones behind it.
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, OwnedSemaphorePermit, Semaphore};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::server::Connected;
+use tracing::{debug, warn};
+
+/// A connection counted against the limit until it's dropped
+pub struct Limited<IO> {
+    io: IO,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl<IO: AsyncRead + Unpin> AsyncRead for Limited<IO> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_read(cx, buf)
+    }
+}
+
+impl<IO: AsyncWrite + Unpin> AsyncWrite for Limited<IO> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_shutdown(cx)
+    }
+}
+
+impl<IO: Connected> Connected for Limited<IO> {
+    type ConnectInfo = IO::ConnectInfo;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        self.io.connect_info()
+    }
+}
+
+/// Accept connections on `listener` and pass each through `handshake`
+///
+/// Handshakes that fail or take longer than `handshake_timeout` are dropped.
+/// The returned stream is meant for `serve_with_incoming`; accepting stops
+/// when it's dropped.
+pub fn accept<IO, F, Fut>(
+    listener: TcpListener,
+    max_connections: usize,
+    handshake_timeout: Duration,
+    handshake: F,
+) -> ReceiverStream<io::Result<Limited<IO>>>
+where
+    IO: Send + 'static,
+    F: Fn(TcpStream) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = io::Result<IO>> + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(16);
+    let limit = Arc::new(Semaphore::new(max_connections));
+    let handshake = Arc::new(handshake);
+
+    tokio::spawn(async move {
+        loop {
+            let permit = tokio::select! {
+                _ = tx.closed() => break,
+                permit = limit.clone().acquire_owned() => permit.expect("semaphore is never closed"),
+            };
+            let (stream, peer) = tokio::select! {
+                _ = tx.closed() => break,
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        // Usually out of file descriptors; give some a chance to close
+                        warn!("Failed to accept connection: {}", e);
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                },
+            };
+
+            let tx = tx.clone();
+            let handshake = handshake.clone();
+            tokio::spawn(async move {
+                match tokio::time::timeout(handshake_timeout, handshake(stream)).await {
+                    Ok(Ok(io)) => {
+                        let _ = tx.send(Ok(Limited { io, _permit: permit })).await;
+                    }
+                    Ok(Err(e)) => debug!("Handshake with {} failed: {}", peer, e),
+                    Err(_) => debug!("Handshake with {} timed out", peer),
+                }
+            });
+        }
+    });
+
+    ReceiverStream::new(rx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio_stream::StreamExt;
+
+    #[tokio::test]
+    async fn test_connections_are_capped() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut incoming = accept(listener, 1, Duration::from_secs(5), |s| async move { Ok(s) });
+
+        let first = TcpStream::connect(addr).await.unwrap();
+        let mut accepted = incoming.next().await.unwrap().unwrap();
+
+        // The second client waits until the first one is gone
+        let _second = TcpStream::connect(addr).await.unwrap();
+        let waiting = tokio::time::timeout(Duration::from_millis(200), incoming.next()).await;
+        assert!(waiting.is_err());
+
+        drop(first);
+        let mut buf = [0u8; 1];
+        assert_eq!(accepted.read(&mut buf).await.unwrap(), 0);
+        drop(accepted);
+        let next = tokio::time::timeout(Duration::from_secs(5), incoming.next()).await;
+        assert!(matches!(next, Ok(Some(Ok(_)))));
+    }
+}
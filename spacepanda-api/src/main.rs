@@ -1,15 +1,22 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use tokio::net::TcpListener;
 use tonic::transport::Server;
-use tracing::{info, Level};
+use tracing::{info, warn, Level};
 use tracing_subscriber;
 
 mod auth;
 mod error;
+mod event_log;
+mod listener;
 mod proto;
 mod services;
 mod session;
+mod tls;
+mod token;
 
 use services::{AuthServiceImpl, MessageServiceImpl, NetworkServiceImpl, SpaceServiceImpl};
+use spacepanda_core::shutdown::{install_signal_handlers, ShutdownCoordinator};
+use std::sync::Arc;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -18,42 +25,145 @@ async fn main() -> Result<()> {
         .with_max_level(Level::DEBUG)
         .init();
 
-    // Get port from environment or command line argument
-    let port = std::env::args()
-        .nth(1)
-        .and_then(|arg| if arg == "--port" { std::env::args().nth(2) } else { Some(arg) })
-        .or_else(|| std::env::var("GRPC_PORT").ok())
-        .unwrap_or_else(|| "50051".to_string());
+    // `--config <file>` or SPACEPANDA_CONFIG names a TOML file; otherwise
+    // settings come from SPACEPANDA_* variables. Bad settings fail here.
+    let args = Args::parse();
+    let config = match &args.config {
+        Some(path) => spacepanda_core::config::Config::from_file(path)
+            .with_context(|| format!("Failed to load config from {}", path))?,
+        None => spacepanda_core::config::Config::from_env()?,
+    };
 
-    let addr = format!("127.0.0.1:{}", port).parse()?;
-    
-    info!("🐼 SpacePanda gRPC API Server starting on {}", addr);
+    // `--port` or GRPC_PORT overrides just the port of the bind address
+    let mut addr = config.server.bind_address;
+    if let Some(port) = &args.port {
+        addr.set_port(port.parse().with_context(|| format!("Invalid port: {}", port))?);
+    }
+
+    // Unreadable or mismatched certificate files stop the server here
+    let tls = match (&config.server.tls_cert_path, &config.server.tls_key_path) {
+        (Some(cert), Some(key)) if config.server.enable_tls => {
+            let tls = tls::ServerTls::load(cert, key)?;
+            #[cfg(unix)]
+            tls.reload_on_sighup()?;
+            Some(tls)
+        }
+        _ => None,
+    };
+
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind {}", addr))?;
+    info!(
+        "🐼 SpacePanda gRPC API Server starting on {} ({})",
+        addr,
+        if tls.is_some() { "TLS" } else { "plaintext" }
+    );
+
+    // Ctrl+C or SIGTERM ends open streams, then stops the server
+    let shutdown_timeout = config.server.shutdown_timeout;
+    let shutdown = Arc::new(ShutdownCoordinator::new(shutdown_timeout));
 
     // Initialize SHARED session manager for all services
-    let session_manager = std::sync::Arc::new(session::SessionManager::new());
+    let session_manager = session::SessionManager::new(config.store.mls_backend.clone())
+        .with_session_limits(config.server.session_ttl, config.server.session_idle_timeout);
+    let session_manager = std::sync::Arc::new(session_manager);
+    session_manager.spawn_sweeper();
+    let flushed = session_manager.flush_on_shutdown(&shutdown);
+    install_signal_handlers(shutdown.clone());
+    let interceptor = session_manager.interceptor();
 
     // Initialize services with shared session manager
     let auth_service = AuthServiceImpl::new(session_manager.clone());
     let space_service = SpaceServiceImpl::new(session_manager.clone());
-    let message_service = MessageServiceImpl::new(session_manager.clone());
+    let message_service = MessageServiceImpl::new(session_manager.clone(), shutdown.clone());
     let network_service = NetworkServiceImpl::new(session_manager.clone());
 
     // Build and start server
-    Server::builder()
-        .add_service(proto::auth_service_server::AuthServiceServer::new(
+    let router = Server::builder()
+        .add_service(proto::auth_service_server::AuthServiceServer::with_interceptor(
             auth_service,
+            interceptor.clone(),
         ))
-        .add_service(proto::space_service_server::SpaceServiceServer::new(
+        .add_service(proto::space_service_server::SpaceServiceServer::with_interceptor(
             space_service,
+            interceptor.clone(),
         ))
-        .add_service(proto::message_service_server::MessageServiceServer::new(
+        .add_service(proto::message_service_server::MessageServiceServer::with_interceptor(
             message_service,
+            interceptor.clone(),
         ))
-        .add_service(proto::network_service_server::NetworkServiceServer::new(
+        .add_service(proto::network_service_server::NetworkServiceServer::with_interceptor(
             network_service,
-        ))
-        .serve(addr)
-        .await?;
+            interceptor,
+        ));
+
+    let max_connections = config.server.max_connections;
+    let handshake_timeout = config.server.connection_timeout;
+    let stop = {
+        let shutdown = shutdown.clone();
+        async move { shutdown.wait_for_shutdown().await }
+    };
+    let server = async move {
+        match tls {
+            Some(tls) => {
+                let handshake = move |stream| {
+                    let tls = tls.clone();
+                    async move { tls.handshake(stream).await }
+                };
+                let incoming =
+                    listener::accept(listener, max_connections, handshake_timeout, handshake);
+                router.serve_with_incoming_shutdown(incoming, stop).await
+            }
+            None => {
+                let handshake = |stream| async move { Ok(stream) };
+                let incoming =
+                    listener::accept(listener, max_connections, handshake_timeout, handshake);
+                router.serve_with_incoming_shutdown(incoming, stop).await
+            }
+        }
+    };
+
+    // On SIGTERM the server stops accepting connections and tells HTTP/2
+    // clients to go away; open streams end with a status asking clients to
+    // resume later, and sessions save their state. Clients that haven't
+    // disconnected by the shutdown timeout are cut off.
+    tokio::select! {
+        result = server => result?,
+        _ = async {
+            shutdown.wait_for_shutdown().await;
+            tokio::time::sleep(shutdown_timeout).await;
+        } => warn!("Connections still open after {:?}, stopping anyway", shutdown_timeout),
+    }
+    if tokio::time::timeout(shutdown_timeout, flushed).await.is_err() {
+        warn!("Sessions not saved after {:?}, stopping anyway", shutdown_timeout);
+    }
 
     Ok(())
 }
+
+/// Command line options
+#[derive(Default)]
+struct Args {
+    config: Option<String>,
+    port: Option<String>,
+}
+
+impl Args {
+    /// `[--config <file>] [--port <port> | <port>]`, falling back to
+    /// SPACEPANDA_CONFIG and GRPC_PORT
+    fn parse() -> Self {
+        let mut args = Self::default();
+        let mut argv = std::env::args().skip(1);
+        while let Some(arg) = argv.next() {
+            match arg.as_str() {
+                "--config" => args.config = argv.next(),
+                "--port" => args.port = argv.next(),
+                _ => args.port = Some(arg),
+            }
+        }
+        args.config = args.config.or_else(|| std::env::var("SPACEPANDA_CONFIG").ok());
+        args.port = args.port.or_else(|| std::env::var("GRPC_PORT").ok());
+        args
+    }
+}
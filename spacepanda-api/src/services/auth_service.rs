@@ -40,7 +40,7 @@ impl auth_service_server::AuthService for AuthServiceImpl {
             .await
             .map_err(|e| Status::from(e))?;
 
-        let session_token = self
+        let issued = self
             .session_manager
             .create_session(&profile)
             .await
@@ -55,8 +55,9 @@ impl auth_service_server::AuthService for AuthServiceImpl {
         };
 
         Ok(Response::new(UnlockResponse {
-            session_token,
+            session_token: issued.token,
             user: Some(user),
+            expires_at: issued.expires_at as i64,
         }))
     }
 
@@ -79,7 +80,7 @@ impl auth_service_server::AuthService for AuthServiceImpl {
             .await
             .map_err(|e| Status::from(e))?;
 
-        let session_token = self
+        let issued = self
             .session_manager
             .create_session(&profile)
             .await
@@ -94,8 +95,9 @@ impl auth_service_server::AuthService for AuthServiceImpl {
         };
 
         Ok(Response::new(CreateProfileResponse {
-            session_token,
+            session_token: issued.token,
             user: Some(user),
+            expires_at: issued.expires_at as i64,
         }))
     }
 
@@ -103,10 +105,42 @@ impl auth_service_server::AuthService for AuthServiceImpl {
         let req = request.into_inner();
 
         self.session_manager
-            .remove_session(&req.session_token)
+            .end_session(&req.session_token)
             .await
             .map_err(|e| Status::from(e))?;
 
         Ok(Response::new(LockResponse { success: true }))
     }
+
+    async fn refresh_token(
+        &self,
+        request: Request<RefreshTokenRequest>,
+    ) -> Result<Response<RefreshTokenResponse>, Status> {
+        let req = request.into_inner();
+
+        let issued = self
+            .session_manager
+            .refresh_session(&req.session_token)
+            .await
+            .map_err(|e| Status::from(e))?;
+
+        Ok(Response::new(RefreshTokenResponse {
+            session_token: issued.token,
+            expires_at: issued.expires_at as i64,
+        }))
+    }
+
+    async fn logout(
+        &self,
+        request: Request<LogoutRequest>,
+    ) -> Result<Response<LogoutResponse>, Status> {
+        let req = request.into_inner();
+
+        self.session_manager
+            .end_session(&req.session_token)
+            .await
+            .map_err(|e| Status::from(e))?;
+
+        Ok(Response::new(LogoutResponse { success: true }))
+    }
 }
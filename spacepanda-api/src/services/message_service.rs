@@ -1,17 +1,31 @@
+use std::collections::HashSet;
 use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
 use tonic::{Request, Response, Status};
 
+use super::{mvp_channel_id, parse_channel_id};
+use crate::error::ApiError;
+use crate::event_log::ResumeError;
 use crate::proto::*;
 use crate::session::SessionManager;
+use crate::token::TokenError;
+use spacepanda_core::core_mvp::events::{ChannelEvent, ChannelEventKind};
+use spacepanda_core::core_mvp::transform::detect_language;
+use spacepanda_core::core_mvp::types::ChatMessage;
+use spacepanda_core::core_store::model::types::MessageId;
+use spacepanda_core::shutdown::ShutdownCoordinator;
 
 pub struct MessageServiceImpl {
     session_manager: Arc<SessionManager>,
+    /// Ends open message streams when the server shuts down
+    shutdown: Arc<ShutdownCoordinator>,
 }
 
 impl MessageServiceImpl {
-    pub fn new(session_manager: Arc<SessionManager>) -> Self {
+    pub fn new(session_manager: Arc<SessionManager>, shutdown: Arc<ShutdownCoordinator>) -> Self {
         Self {
             session_manager,
+            shutdown,
         }
     }
 }
@@ -29,69 +43,18 @@ impl message_service_server::MessageService for MessageServiceImpl {
             .await
             .map_err(|e| Status::from(e))?;
 
-        let channel_id_bytes = hex::decode(&req.channel_id)
-            .map_err(|_| Status::invalid_argument("Invalid channel ID format"))?;
-        let channel_id = if channel_id_bytes.len() == 32 {
-            let mut arr = [0u8; 32];
-            arr.copy_from_slice(&channel_id_bytes);
-            spacepanda_core::core_space::ChannelId::from_bytes(arr)
-        } else {
-            return Err(Status::invalid_argument("Invalid channel ID length"));
-        };
-
-        // Load messages from storage
-        let limit = if req.limit > 0 { req.limit.min(100) } else { 50 } as i64;
-        let offset = 0i64; // TODO: Implement cursor-based pagination with `before` field
+        let channel_id = mvp_channel_id(&parse_channel_id(&req.channel_id)?);
+        let limit = if req.limit > 0 { req.limit.min(100) } else { 50 } as usize;
+        let before = (!req.before.is_empty()).then(|| MessageId(req.before));
 
-        let stored_messages = session
-            .manager
-            .load_messages(&channel_id, limit, offset)
+        let messages = session
+            .channels
+            .get_messages(&channel_id, limit, before)
             .await
-            .map_err(|e| Status::internal(format!("Failed to load messages: {}", e)))?;
-
-        // Convert stored messages to proto Messages
-        let mut messages = Vec::new();
-        for (message_id_bytes, encrypted_content, sender_hash, sequence, _processed, plaintext_content) in stored_messages {
-            // Check if we have plaintext (sent message) or need to decrypt (received message)
-            let content = if let Some(plaintext) = plaintext_content {
-                // This is a sent message, use stored plaintext
-                String::from_utf8(plaintext)
-                    .unwrap_or_else(|_| "[Binary content]".to_string())
-            } else {
-                // This is a received message, decrypt it
-                let decrypted_content = session
-                    .manager
-                    .receive_channel_message(&channel_id, &encrypted_content)
-                    .await
-                    .map_err(|e| Status::internal(format!("Failed to decrypt message: {}", e)))?;
-
-                String::from_utf8(decrypted_content)
-                    .unwrap_or_else(|_| "[Binary content]".to_string())
-            };
-
-            // Parse message ID from bytes
-            let message_id = if message_id_bytes.len() == 16 {
-                uuid::Uuid::from_slice(&message_id_bytes)
-                    .map(|id| id.to_string())
-                    .unwrap_or_else(|_| hex::encode(&message_id_bytes))
-            } else {
-                hex::encode(&message_id_bytes)
-            };
-
-            // Parse sender ID from hash
-            let sender_id = String::from_utf8(sender_hash.clone())
-                .unwrap_or_else(|_| hex::encode(&sender_hash));
-
-            messages.push(Message {
-                id: message_id,
-                channel_id: req.channel_id.clone(),
-                sender_id,
-                content,
-                timestamp: sequence,
-                is_e2ee: true,
-                attachments: vec![],
-            });
-        }
+            .map_err(ApiError::from)?
+            .into_iter()
+            .map(proto_message)
+            .collect();
 
         Ok(Response::new(GetMessagesResponse { messages }))
     }
@@ -107,79 +70,493 @@ impl message_service_server::MessageService for MessageServiceImpl {
             .await
             .map_err(|e| Status::from(e))?;
 
-        let channel_id_bytes = hex::decode(&req.channel_id)
-            .map_err(|_| Status::invalid_argument("Invalid channel ID format"))?;
-        let channel_id = if channel_id_bytes.len() == 32 {
-            let mut arr = [0u8; 32];
-            arr.copy_from_slice(&channel_id_bytes);
-            spacepanda_core::core_space::ChannelId::from_bytes(arr)
-        } else {
-            return Err(Status::invalid_argument("Invalid channel ID length"));
-        };
+        let channel_id = mvp_channel_id(&parse_channel_id(&req.channel_id)?);
 
-        // Send message via AsyncSpaceManager
-        // This will:
-        // 1. Encrypt the message with MLS
-        // 2. Save it to the sender's local database
-        // 3. Broadcast to peers via P2P (if network layer is configured)
-        let _encrypted_content = session
-            .manager
-            .send_channel_message(&channel_id, &session.user_id, req.content.as_bytes())
+        // Encrypts, stores and broadcasts to the channel's members
+        let ciphertext = session
+            .channels
+            .send_message(&channel_id, req.content.as_bytes())
             .await
-            .map_err(|e| Status::internal(e.to_string()))?;
+            .map_err(ApiError::from)?;
 
-        // Retrieve the just-saved message to get the actual stored message ID
-        let messages = session
-            .manager
-            .load_messages(&channel_id, 1, 0)
+        // Stored under the ID every member derives from the ciphertext
+        let mut message =
+            ChatMessage::new(channel_id, session.user_id.clone(), req.content.into_bytes());
+        message.message_id = MessageId::from_ciphertext(&ciphertext);
+
+        Ok(Response::new(proto_message(message)))
+    }
+
+    type StreamMessagesStream =
+        tokio_stream::wrappers::ReceiverStream<Result<MessageEvent, Status>>;
+
+    async fn stream_messages(
+        &self,
+        request: Request<StreamMessagesRequest>,
+    ) -> Result<Response<Self::StreamMessagesStream>, Status> {
+        let req = request.into_inner();
+        let session = self
+            .session_manager
+            .get_session(&req.session_token)
             .await
-            .map_err(|e| Status::internal(format!("Failed to load sent message: {}", e)))?;
+            .map_err(|e| Status::from(e))?;
 
-        if messages.is_empty() {
-            return Err(Status::internal("Message was sent but not found in storage"));
+        // Only channels the session's user is in
+        let mut channel_ids = HashSet::new();
+        for channel_id in &req.channel_ids {
+            let channel_id = mvp_channel_id(&parse_channel_id(channel_id)?);
+            session.channels.get_channel(&channel_id).await.map_err(ApiError::from)?;
+            channel_ids.insert(channel_id.0);
         }
 
-        let (message_id_bytes, _encrypted, sender_hash, sequence, _processed, _plaintext) = &messages[0];
+        let (replay, mut live) =
+            session.events.subscribe_after(req.resume_after).map_err(|e| match e {
+                ResumeError::Expired { oldest } => Status::out_of_range(format!(
+                    "Resume token expired, the oldest event kept is {}; reload with GetMessages",
+                    oldest
+                )),
+                ResumeError::Unknown { latest } => Status::out_of_range(format!(
+                    "Unknown resume token, the latest event is {}",
+                    latest
+                )),
+            })?;
+
+        // The stream only waits on its own buffer: a client that can't keep
+        // up makes the forwarder lag behind the session's events and get an
+        // error telling it to resume, rather than holding up processing.
+        //
+        // A stream lasts only as long as the token it was opened with, and
+        // ends with an error when that expires or the session ends; the
+        // client resumes with a refreshed token. Shutdown waits for streams
+        // to tell their clients to reconnect elsewhere or later.
+        let mut shutdown = self.shutdown.subscribe();
+        let guard = self.shutdown.register("message stream");
+        let mut ended = session.ended();
+        let expiry = tokio::time::sleep(session.token_remaining());
+        let (tx, rx) = tokio::sync::mpsc::channel(STREAM_BUFFER);
+        tokio::spawn(async move {
+            let _guard = guard;
+            tokio::pin!(expiry);
+            let mut replay = replay.into_iter();
+            loop {
+                let received = match replay.next() {
+                    Some(event) => Ok(event),
+                    None => tokio::select! {
+                        received = live.recv() => received,
+                        _ = &mut expiry => {
+                            let _ = tx.send(Err(TokenError::Expired.into())).await;
+                            break;
+                        }
+                        _ = ended.changed() => {
+                            let _ = tx.send(Err(ApiError::InvalidSession.into())).await;
+                            break;
+                        }
+                        _ = tx.closed() => break,
+                        _ = shutdown.recv() => {
+                            let _ = tx.try_send(Err(shutting_down()));
+                            break;
+                        }
+                    },
+                };
+                let item = match received {
+                    Ok((sequence, event)) => match message_event(sequence, event, &channel_ids) {
+                        Some(item) => Ok(item),
+                        None => continue,
+                    },
+                    Err(RecvError::Lagged(skipped)) => Err(Status::data_loss(format!(
+                        "Stream fell behind by {} events; resume after the last sequence received",
+                        skipped
+                    ))),
+                    Err(RecvError::Closed) => break,
+                };
+
+                let ends_stream = item.is_err();
+                tokio::select! {
+                    sent = tx.send(item) => {
+                        // An error means the client went away
+                        if sent.is_err() || ends_stream {
+                            break;
+                        }
+                    }
+                    _ = shutdown.recv() => {
+                        let _ = tx.try_send(Err(shutting_down()));
+                        break;
+                    }
+                }
+            }
+        });
 
-        // Parse message ID from bytes
-        let message_id = if message_id_bytes.len() == 16 {
-            uuid::Uuid::from_slice(message_id_bytes)
-                .map(|id| id.to_string())
-                .unwrap_or_else(|_| hex::encode(message_id_bytes))
-        } else {
-            hex::encode(message_id_bytes)
+        Ok(Response::new(tokio_stream::wrappers::ReceiverStream::new(rx)))
+    }
+}
+
+/// Messages buffered for a stream's client before it counts as slow
+const STREAM_BUFFER: usize = 128;
+
+/// The last item of a stream closed by shutdown, unless its buffer is full
+fn shutting_down() -> Status {
+    Status::unavailable("Server is shutting down; resume after the last sequence received")
+}
+
+/// The stream item for a received message in one of `channel_ids` (any if empty)
+fn message_event(
+    sequence: u64,
+    event: ChannelEvent,
+    channel_ids: &HashSet<String>,
+) -> Option<MessageEvent> {
+    if !channel_ids.is_empty() && !channel_ids.contains(&event.channel_id.0) {
+        return None;
+    }
+    let ChannelEventKind::MessageReceived(message) = event.kind else {
+        return None;
+    };
+
+    Some(MessageEvent { sequence, message: Some(proto_message(message)) })
+}
+
+/// A stored or received message as sent to clients
+fn proto_message(message: ChatMessage) -> Message {
+    let content =
+        String::from_utf8(message.body).unwrap_or_else(|_| "[Binary content]".to_string());
+    Message {
+        id: message.message_id.0,
+        channel_id: message.channel_id.0,
+        sender_id: message.sender.0,
+        language: message.language.or_else(|| detect_language(&content)).unwrap_or_default(),
+        content,
+        timestamp: message.timestamp.as_millis() as i64,
+        is_e2ee: true,
+        attachments: vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::UserProfile;
+    use crate::services::SpaceServiceImpl;
+    use message_service_client::MessageServiceClient;
+    use space_service_client::SpaceServiceClient;
+    use std::time::Duration;
+    use tempfile::TempDir;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::Server;
+    use tonic::Streaming;
+
+    /// Serve the space and message services on a free port
+    async fn start_server(
+        session_manager: Arc<SessionManager>,
+        shutdown: Arc<ShutdownCoordinator>,
+    ) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+
+        let interceptor = session_manager.interceptor();
+        let spaces = SpaceServiceImpl::new(session_manager.clone());
+        let messages = MessageServiceImpl::new(session_manager, shutdown.clone());
+        tokio::spawn(async move {
+            Server::builder()
+                .add_service(space_service_server::SpaceServiceServer::with_interceptor(
+                    spaces,
+                    interceptor.clone(),
+                ))
+                .add_service(message_service_server::MessageServiceServer::with_interceptor(
+                    messages,
+                    interceptor,
+                ))
+                .serve_with_incoming_shutdown(TcpListenerStream::new(listener), async move {
+                    shutdown.wait_for_shutdown().await
+                })
+                .await
+                .unwrap();
+        });
+        endpoint
+    }
+
+    /// Open a session for a new user keeping its data in `dir`
+    async fn login(session_manager: &SessionManager, name: &str, dir: &TempDir) -> String {
+        let profile = UserProfile {
+            id: format!("{}-{}", name, uuid::Uuid::new_v4()),
+            username: name.to_string(),
+            password_hash: String::new(),
+            data_dir: dir.path().to_path_buf(),
         };
+        session_manager.create_session(&profile).await.unwrap().token
+    }
 
-        let message = Message {
-            id: message_id,
-            channel_id: hex::encode(channel_id.as_bytes()),
-            sender_id: String::from_utf8(sender_hash.clone())
-                .unwrap_or_else(|_| hex::encode(sender_hash)),
-            content: req.content,
-            timestamp: *sequence,
-            is_e2ee: true,
-            attachments: vec![],
+    /// Alice creates a channel, invites Bob and Bob joins
+    async fn share_channel(
+        spaces: &mut SpaceServiceClient<tonic::transport::Channel>,
+        alice: &str,
+        bob: &str,
+    ) -> Channel {
+        let request = CreateSpaceRequest {
+            session_token: alice.to_string(),
+            name: "team".to_string(),
+            ..Default::default()
+        };
+        let space = spaces.create_space(request).await.unwrap().into_inner().space.unwrap();
+        let request = CreateChannelRequest {
+            session_token: alice.to_string(),
+            space_id: space.id,
+            name: "general".to_string(),
+            visibility: ChannelVisibility::Private as i32,
+            ..Default::default()
         };
+        let channel = spaces.create_channel(request).await.unwrap().into_inner().channel.unwrap();
 
-        Ok(Response::new(message))
+        let request = GenerateKeyPackageRequest { session_token: bob.to_string() };
+        let key_package = spaces.generate_key_package(request).await.unwrap().into_inner();
+        let request = CreateChannelInviteRequest {
+            session_token: alice.to_string(),
+            channel_id: channel.id.clone(),
+            key_package: key_package.key_package,
+        };
+        let invite = spaces.create_channel_invite(request).await.unwrap().into_inner();
+        let request = JoinChannelRequest {
+            session_token: bob.to_string(),
+            invite_token: invite.invite_token,
+            ratchet_tree: invite.ratchet_tree,
+            space_id: invite.space_id,
+            channel_name: invite.channel_name,
+            channel_id: invite.channel_id,
+        };
+        let joined = spaces.join_channel(request).await.unwrap().into_inner();
+        assert_eq!(joined.channel_id, channel.id);
+        channel
     }
 
-    type StreamMessagesStream =
-        tokio_stream::wrappers::ReceiverStream<Result<Message, Status>>;
+    async fn next_event(stream: &mut Streaming<MessageEvent>) -> Option<MessageEvent> {
+        tokio::time::timeout(Duration::from_secs(10), stream.message())
+            .await
+            .expect("timed out waiting for the stream")
+            .unwrap()
+    }
 
-    async fn stream_messages(
-        &self,
-        request: Request<StreamMessagesRequest>,
-    ) -> Result<Response<Self::StreamMessagesStream>, Status> {
-        let _req = request.into_inner();
+    #[tokio::test]
+    async fn test_stream_delivers_messages_from_another_session() {
+        let dirs: Vec<TempDir> = (0..2).map(|_| TempDir::new().unwrap()).collect();
+        let session_manager = Arc::new(SessionManager::new(None));
+        let shutdown = Arc::new(ShutdownCoordinator::new(Duration::from_millis(10)));
+        let endpoint = start_server(session_manager.clone(), shutdown.clone()).await;
+        let alice = login(&session_manager, "alice", &dirs[0]).await;
+        let bob = login(&session_manager, "bob", &dirs[1]).await;
+
+        let mut spaces = SpaceServiceClient::connect(endpoint.clone()).await.unwrap();
+        let mut messages = MessageServiceClient::connect(endpoint).await.unwrap();
+
+        let channel = share_channel(&mut spaces, &alice, &bob).await;
+
+        let request = StreamMessagesRequest {
+            session_token: bob.clone(),
+            channel_ids: vec![channel.id.clone()],
+            resume_after: 0,
+        };
+        let mut stream = messages.stream_messages(request).await.unwrap().into_inner();
+
+        for content in ["one", "two", "three"] {
+            let request = SendMessageRequest {
+                session_token: alice.clone(),
+                channel_id: channel.id.clone(),
+                content: content.to_string(),
+            };
+            messages.send_message(request).await.unwrap();
+        }
+
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            received.push(next_event(&mut stream).await.unwrap());
+        }
+        let contents: Vec<_> = received
+            .iter()
+            .map(|event| event.message.as_ref().unwrap().content.as_str())
+            .collect();
+        assert_eq!(contents, ["one", "two", "three"]);
+        assert!(received
+            .iter()
+            .all(|event| event.message.as_ref().unwrap().channel_id == channel.id));
+        assert!(received.windows(2).all(|pair| pair[0].sequence < pair[1].sequence));
+
+        // Reconnecting with the first message's sequence gets the rest again
+        let request = StreamMessagesRequest {
+            session_token: bob.clone(),
+            channel_ids: vec![channel.id.clone()],
+            resume_after: received[0].sequence,
+        };
+        let mut resumed = messages.stream_messages(request).await.unwrap().into_inner();
+        for expected in &received[1..] {
+            assert_eq!(next_event(&mut resumed).await.as_ref(), Some(expected));
+        }
+
+        // Tokens from the future and strangers' sessions are refused
+        let request = StreamMessagesRequest {
+            session_token: bob.clone(),
+            channel_ids: vec![],
+            resume_after: received[2].sequence + 100,
+        };
+        let status = messages.stream_messages(request).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::OutOfRange);
+        let request = StreamMessagesRequest {
+            session_token: "not-a-session".to_string(),
+            channel_ids: vec![channel.id.clone()],
+            resume_after: 0,
+        };
+        let status = messages.stream_messages(request).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+
+        // Shutting down tells open streams to resume later, then ends them
+        shutdown.shutdown().await;
+        for stream in [&mut stream, &mut resumed] {
+            let status = stream_error(stream).await;
+            assert_eq!(status.code(), tonic::Code::Unavailable);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_two_clients_exchange_a_message() {
+        let dirs: Vec<TempDir> = (0..2).map(|_| TempDir::new().unwrap()).collect();
+        let session_manager = Arc::new(SessionManager::new(None));
+        let shutdown = Arc::new(ShutdownCoordinator::new(Duration::from_millis(10)));
+        let endpoint = start_server(session_manager.clone(), shutdown.clone()).await;
+        let alice = login(&session_manager, "alice", &dirs[0]).await;
+        let bob = login(&session_manager, "bob", &dirs[1]).await;
+
+        // Each user has their own client
+        let mut alice_spaces = SpaceServiceClient::connect(endpoint.clone()).await.unwrap();
+        let mut alice_messages = MessageServiceClient::connect(endpoint.clone()).await.unwrap();
+        let mut bob_messages = MessageServiceClient::connect(endpoint).await.unwrap();
+
+        let channel = share_channel(&mut alice_spaces, &alice, &bob).await;
+        let request = StreamMessagesRequest {
+            session_token: bob.clone(),
+            channel_ids: vec![channel.id.clone()],
+            resume_after: 0,
+        };
+        let mut stream = bob_messages.stream_messages(request).await.unwrap().into_inner();
+
+        let request = SendMessageRequest {
+            session_token: alice.clone(),
+            channel_id: channel.id.clone(),
+            content: "hello bob".to_string(),
+        };
+        let sent = alice_messages.send_message(request).await.unwrap().into_inner();
+        assert_eq!(sent.channel_id, channel.id);
+
+        // Bob gets the message under the ID Alice was given
+        let received = next_event(&mut stream).await.unwrap().message.unwrap();
+        assert_eq!(received.id, sent.id);
+        assert_eq!(received.content, "hello bob");
+        assert_eq!(received.sender_id, sent.sender_id);
+
+        let request = GetMessagesRequest {
+            session_token: bob.clone(),
+            channel_id: channel.id.clone(),
+            ..Default::default()
+        };
+        let history = bob_messages.get_messages(request).await.unwrap().into_inner().messages;
+        let ids: Vec<_> = history.iter().map(|message| message.id.as_str()).collect();
+        assert_eq!(ids, [sent.id.as_str()]);
+
+        // Channel manager errors keep their meaning
+        let request = SendMessageRequest {
+            session_token: alice.clone(),
+            channel_id: hex::encode([7u8; 32]),
+            content: "anyone?".to_string(),
+        };
+        let status = alice_messages.send_message(request).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+
+        shutdown.shutdown_immediately().await;
+    }
+
+    /// The error a stream ended with
+    async fn stream_error(stream: &mut Streaming<MessageEvent>) -> Status {
+        tokio::time::timeout(Duration::from_secs(10), stream.message())
+            .await
+            .expect("timed out waiting for the stream")
+            .unwrap_err()
+    }
+
+    fn stream_request(session_token: &str) -> StreamMessagesRequest {
+        StreamMessagesRequest {
+            session_token: session_token.to_string(),
+            channel_ids: vec![],
+            resume_after: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_ends_when_the_token_expires() {
+        let dir = TempDir::new().unwrap();
+        let session_manager = SessionManager::new(None)
+            .with_session_limits(Duration::from_secs(2), Duration::from_secs(60));
+        let session_manager = Arc::new(session_manager);
+        let shutdown = Arc::new(ShutdownCoordinator::new(Duration::from_millis(10)));
+        let endpoint = start_server(session_manager.clone(), shutdown.clone()).await;
+        let alice = login(&session_manager, "alice", &dir).await;
+        let mut messages = MessageServiceClient::connect(endpoint).await.unwrap();
+
+        // Refreshing doesn't extend a stream already open
+        let mut stream =
+            messages.stream_messages(stream_request(&alice)).await.unwrap().into_inner();
+        let refreshed = session_manager.refresh_session(&alice).await.unwrap().token;
+        let status = stream_error(&mut stream).await;
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+        assert_eq!(status.message(), "Session token expired");
+
+        // The old token is dead, the new one works until it expires too
+        let status = messages.stream_messages(stream_request(&alice)).await.unwrap_err();
+        assert_eq!(status.message(), "Session token was replaced by a refresh");
+        let mut stream =
+            messages.stream_messages(stream_request(&refreshed)).await.unwrap().into_inner();
+        assert_eq!(stream_error(&mut stream).await.message(), "Session token expired");
+        let status = messages.stream_messages(stream_request(&refreshed)).await.unwrap_err();
+        assert_eq!(status.message(), "Session token expired");
+        let status = session_manager.refresh_session(&refreshed).await.unwrap_err();
+        assert_eq!(Status::from(status).message(), "Session token expired");
+
+        shutdown.shutdown_immediately().await;
+    }
+
+    #[tokio::test]
+    async fn test_bad_tokens_are_refused() {
+        let dir = TempDir::new().unwrap();
+        let session_manager = Arc::new(SessionManager::new(None));
+        let shutdown = Arc::new(ShutdownCoordinator::new(Duration::from_millis(10)));
+        let endpoint = start_server(session_manager.clone(), shutdown.clone()).await;
+        let alice = login(&session_manager, "alice", &dir).await;
+        let mut messages = MessageServiceClient::connect(endpoint).await.unwrap();
+
+        let mut forged = alice.clone();
+        let last = forged.pop().unwrap();
+        forged.push(if last == '0' { '1' } else { '0' });
+
+        let cases = [
+            ("", "Missing session token"),
+            ("alice", "Malformed session token"),
+            (forged.as_str(), "Session token signature is invalid"),
+        ];
+        for (token, expected) in cases {
+            let status = messages.stream_messages(stream_request(token)).await.unwrap_err();
+            assert_eq!(status.code(), tonic::Code::Unauthenticated);
+            assert_eq!(status.message(), expected);
+        }
+
+        // A forged token in the metadata is refused even with a good one in the request
+        let mut request = Request::new(stream_request(&alice));
+        let bearer = format!("Bearer {}", forged).parse().unwrap();
+        request.metadata_mut().insert("authorization", bearer);
+        let status = messages.stream_messages(request).await.unwrap_err();
+        assert_eq!(status.message(), "Session token signature is invalid");
 
-        // TODO: Implement real-time message streaming
-        // For now, return empty stream
-        let (tx, rx) = tokio::sync::mpsc::channel(128);
-        drop(tx); // Close immediately
+        // Logging out ends open streams
+        let mut stream =
+            messages.stream_messages(stream_request(&alice)).await.unwrap().into_inner();
+        session_manager.end_session(&alice).await.unwrap();
+        assert_eq!(stream_error(&mut stream).await.message(), "Session has ended, log in again");
+        let status = messages.stream_messages(stream_request(&alice)).await.unwrap_err();
+        assert_eq!(status.message(), "Session has ended, log in again");
 
-        Ok(Response::new(
-            tokio_stream::wrappers::ReceiverStream::new(rx),
-        ))
+        shutdown.shutdown_immediately().await;
     }
 }
@@ -7,3 +7,25 @@ pub use auth_service::AuthServiceImpl;
 pub use message_service::MessageServiceImpl;
 pub use network_service::NetworkServiceImpl;
 pub use space_service::SpaceServiceImpl;
+
+use spacepanda_core::core_space::ChannelId;
+use spacepanda_core::core_store::model::types;
+use tonic::Status;
+
+/// Parse a hex channel ID from a request
+pub(crate) fn parse_channel_id(channel_id: &str) -> Result<ChannelId, Status> {
+    let bytes = hex::decode(channel_id)
+        .map_err(|_| Status::invalid_argument("Invalid channel ID format"))?;
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| Status::invalid_argument("Invalid channel ID length"))?;
+    Ok(ChannelId::from_bytes(bytes))
+}
+
+/// The channel manager's ID for a Space channel
+///
+/// Channels are created in the user's channel manager under the hex of
+/// their Space channel ID, which is also their ID on the network.
+pub(crate) fn mvp_channel_id(channel_id: &ChannelId) -> types::ChannelId {
+    types::ChannelId(hex::encode(channel_id.as_bytes()))
+}
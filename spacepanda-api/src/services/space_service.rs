@@ -1,8 +1,13 @@
 use std::sync::Arc;
 use tonic::{Request, Response, Status};
 
+use super::{mvp_channel_id, parse_channel_id};
+use crate::error::ApiError;
 use crate::proto::*;
 use crate::session::SessionManager;
+use spacepanda_core::core_mls::types::GroupId;
+use spacepanda_core::core_mvp::types::InviteToken;
+use spacepanda_core::core_store::model::Permission;
 use spacepanda_core::core_store::UserId;
 
 pub struct SpaceServiceImpl {
@@ -237,12 +242,21 @@ impl space_service_server::SpaceService for SpaceServiceImpl {
             }
         };
 
-        // Create channel using AsyncSpaceManager
+        // List the channel in the Space, then create it under the same ID
         let core_channel = session
             .manager
-            .create_channel(space_id, req.name, session.user_id.clone(), visibility)
+            .add_channel_record(space_id, req.name, session.user_id.clone(), visibility)
             .await
             .map_err(|e| Status::internal(e.to_string()))?;
+        session
+            .channels
+            .create_channel_as(
+                mvp_channel_id(&core_channel.id),
+                core_channel.name.clone(),
+                visibility == spacepanda_core::core_space::ChannelVisibility::Public,
+            )
+            .await
+            .map_err(ApiError::from)?;
 
         // Register channel creator with P2P network
         session
@@ -300,6 +314,13 @@ impl space_service_server::SpaceService for SpaceServiceImpl {
             return Err(Status::invalid_argument("Invalid channel ID length"));
         };
 
+        // Caller must be allowed to invite in the channel's Space
+        session
+            .manager
+            .require_channel_permission(&channel_id, &session.user_id, Permission::Invite)
+            .await
+            .map_err(|e| Status::permission_denied(format!("Cannot add member: {}", e)))?;
+
         // Create UserId for the member to add
         let member_user_id = UserId(req.user_id.clone());
 
@@ -338,15 +359,19 @@ impl space_service_server::SpaceService for SpaceServiceImpl {
             return Err(Status::invalid_argument("Invalid channel ID length"));
         };
 
-        // Create UserId for the member to remove
-        let member_user_id = UserId(req.user_id);
+        // Caller must be allowed to kick in the channel's Space
+        session
+            .manager
+            .require_channel_permission(&channel_id, &session.user_id, Permission::Kick)
+            .await
+            .map_err(|e| Status::permission_denied(format!("Cannot remove member: {}", e)))?;
 
         // Remove member from channel
         session
-            .manager
-            .remove_member_from_channel(&channel_id, &member_user_id)
+            .channels
+            .remove_member(&mvp_channel_id(&channel_id), req.user_id.as_bytes())
             .await
-            .map_err(|e| Status::internal(format!("Failed to remove member: {}", e)))?;
+            .map_err(ApiError::from)?;
 
         Ok(Response::new(RemoveMemberFromChannelResponse {
             success: true,
@@ -366,11 +391,7 @@ impl space_service_server::SpaceService for SpaceServiceImpl {
             .map_err(|e| Status::from(e))?;
 
         // Generate key package for this user
-        let key_package = session
-            .manager
-            .generate_key_package()
-            .await
-            .map_err(|e| Status::internal(format!("Failed to generate key package: {}", e)))?;
+        let key_package = session.channels.generate_key_package().await.map_err(ApiError::from)?;
 
         Ok(Response::new(GenerateKeyPackageResponse { key_package }))
     }
@@ -397,38 +418,25 @@ impl space_service_server::SpaceService for SpaceServiceImpl {
             return Err(Status::invalid_argument("Invalid channel ID length"));
         };
 
-        // Create invite with provided key package
-        let (invite_token, commit, ratchet_tree) = session
-            .manager
-            .create_channel_invite(&channel_id, req.key_package)
-            .await
-            .map_err(|e| Status::internal(format!("Failed to create invite: {}", e)))?;
-
-        // Broadcast the commit to all existing members for MLS state sync
-        if let Some(commit_data) = &commit {
-            if let Some(network_layer) = session.manager.network_layer() {
-                eprintln!("[P2P] Broadcasting MLS commit for channel {} after creating invite", req.channel_id);
-                let network_channel_id = spacepanda_core::core_store::model::types::ChannelId(req.channel_id.clone());
-                if let Err(e) = network_layer.broadcast_commit(&network_channel_id, commit_data.clone()).await {
-                    eprintln!("[P2P] Warning: Failed to broadcast commit: {}", e);
-                    // Continue anyway - the commit is included in the response
-                } else {
-                    eprintln!("[P2P] ✓ Commit broadcasted to existing members");
-                }
-            }
-        }
-
         // Get channel metadata to include in invite
         let channel = session
             .manager
             .get_channel(&channel_id)
             .await
-            .map_err(|e| Status::internal(format!("Failed to get channel metadata: {}", e)))?;
+            .map_err(|e| Status::not_found(format!("Channel not found: {}", e)))?;
+
+        // The commit adding the invitee is broadcast to existing members
+        let (invite, commit) = session
+            .channels
+            .create_invite(&mvp_channel_id(&channel_id), req.key_package)
+            .await
+            .map_err(ApiError::from)?;
+        let invite_token = serde_json::to_vec(&invite).map_err(ApiError::from)?;
 
         Ok(Response::new(CreateChannelInviteResponse {
             invite_token,
             commit: commit.unwrap_or_default(),
-            ratchet_tree: ratchet_tree.unwrap_or_default(),
+            ratchet_tree: invite.ratchet_tree.unwrap_or_default(),
             space_id: hex::encode(channel.space_id.as_bytes()),
             channel_name: channel.name,
             channel_id: req.channel_id.clone(), // Pass through original channel ID
@@ -446,12 +454,9 @@ impl space_service_server::SpaceService for SpaceServiceImpl {
             .await
             .map_err(|e| Status::from(e))?;
 
-        // Join channel from invite token
-        let ratchet_tree = if req.ratchet_tree.is_empty() {
-            None
-        } else {
-            Some(req.ratchet_tree)
-        };
+        // The token carries the ratchet tree and the channel's ID
+        let invite: InviteToken = serde_json::from_slice(&req.invite_token)
+            .map_err(|_| Status::invalid_argument("Invalid invite token"))?;
 
         // Parse space ID from request
         let space_id_bytes = hex::decode(&req.space_id)
@@ -463,34 +468,22 @@ impl space_service_server::SpaceService for SpaceServiceImpl {
         } else {
             return Err(Status::invalid_argument("Invalid space ID length"));
         };
+        let channel_id = parse_channel_id(&invite.channel_id.0)?;
 
-        // Parse original channel ID from invite if provided
-        let original_channel_id = if !req.channel_id.is_empty() {
-            let channel_id_bytes = hex::decode(&req.channel_id)
-                .map_err(|_| Status::invalid_argument("Invalid channel ID format"))?;
-            if channel_id_bytes.len() == 32 {
-                let mut arr = [0u8; 32];
-                arr.copy_from_slice(&channel_id_bytes);
-                Some(spacepanda_core::core_space::ChannelId::from_bytes(arr))
-            } else {
-                return Err(Status::invalid_argument("Invalid channel ID length"));
-            }
-        } else {
-            None
-        };
+        let joined = session.channels.join_channel(&invite).await.map_err(ApiError::from)?;
 
-        let channel_id = session
+        // List the channel in the user's copy of the Space
+        session
             .manager
-            .join_channel_from_invite(
-                req.invite_token,
-                ratchet_tree,
-                &session.user_id,
+            .record_joined_channel(
                 &space_id,
-                &req.channel_name,
-                original_channel_id, // Pass original channel ID for P2P consistency
+                &channel_id,
+                &invite.channel_name,
+                &session.user_id,
+                GroupId::new(joined.0.as_bytes().to_vec()),
             )
             .await
-            .map_err(|e| Status::internal(format!("Failed to join channel: {}", e)))?;
+            .map_err(|e| Status::internal(format!("Failed to record joined channel: {}", e)))?;
 
         // Register new member with P2P network
         session
@@ -505,4 +498,57 @@ impl space_service_server::SpaceService for SpaceServiceImpl {
             message: "Successfully joined channel".to_string(),
         }))
     }
+
+    async fn get_channel_privacy(
+        &self,
+        request: Request<GetChannelPrivacyRequest>,
+    ) -> Result<Response<ChannelPrivacyReport>, Status> {
+        let req = request.into_inner();
+        let session = self
+            .session_manager
+            .get_session(&req.session_token)
+            .await
+            .map_err(|e| Status::from(e))?;
+
+        // Parse channel ID
+        let channel_id_bytes = hex::decode(&req.channel_id)
+            .map_err(|_| Status::invalid_argument("Invalid channel ID format"))?;
+        let channel_id = if channel_id_bytes.len() == 32 {
+            let mut arr = [0u8; 32];
+            arr.copy_from_slice(&channel_id_bytes);
+            spacepanda_core::core_space::ChannelId::from_bytes(arr)
+        } else {
+            return Err(Status::invalid_argument("Invalid channel ID length"));
+        };
+
+        // Only members may look at a channel's settings
+        let channel = session
+            .manager
+            .get_channel(&channel_id)
+            .await
+            .map_err(|e| Status::not_found(format!("Channel not found: {}", e)))?;
+        if !channel.members.contains(&session.user_id) {
+            return Err(Status::permission_denied("Not a member of this channel"));
+        }
+
+        let report = session
+            .manager
+            .channel_privacy_report(&channel_id)
+            .await
+            .map_err(|e| Status::internal(format!("Failed to build privacy report: {}", e)))?;
+
+        Ok(Response::new(ChannelPrivacyReport {
+            channel_id: report.channel_id,
+            discoverable: report.discoverable,
+            sealed_sender: report.sealed_sender,
+            padding_bucket: report.padding_bucket.unwrap_or(0) as u32,
+            presence: report.presence,
+            retention_secs: report.retention.map_or(0, |d| d.as_secs()),
+            member_list_visible_to: report.member_list.to_string(),
+            membership_batch_secs: report.membership_batch_interval.map_or(0, |d| d.as_secs()),
+            score: report.score as u32,
+            rating: report.rating.to_string(),
+            recommendations: report.recommendations,
+        }))
+    }
 }
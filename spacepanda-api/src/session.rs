@@ -1,4 +1,4 @@
-use spacepanda_core::config::Config;
+use spacepanda_core::config::{Config, ServerConfig, StorageBackend};
 use spacepanda_core::core_space::{AsyncSpaceManager, SpaceSqlStore};
 use spacepanda_core::core_mls::service::MlsService;
 use spacepanda_core::core_store::model::{UserId, types};
@@ -6,26 +6,81 @@ use spacepanda_core::shutdown::ShutdownCoordinator;
 use spacepanda_core::core_router::router_handle::RouterHandle;
 use spacepanda_core::core_router::session_manager::PeerId;
 use spacepanda_core::core_mvp::network::NetworkLayer;
+use spacepanda_core::core_mvp::{ChannelManager, Identity};
+use spacepanda_core::core_store::store::{LocalStore, LocalStoreConfig};
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
 use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::{watch, RwLock};
 use tokio::task::JoinHandle;
 use uuid::Uuid;
 
 use crate::auth::UserProfile;
 use crate::error::{ApiError, ApiResult};
+use crate::event_log::{EventLog, EVENT_LOG_CAPACITY};
+use crate::token::{Claims, TokenError, TokenInterceptor, TokenSigner};
 
-/// Session token -> User session data
+/// Session ID -> User session data
 #[derive(Clone)]
 pub struct Session {
-    pub token: String,
+    pub id: String,
+    /// Generation of the session's current token; older ones are refused
+    pub token_generation: u64,
+    /// Expiry of the session's current token, in milliseconds since the epoch
+    pub expires_at: u64,
     pub user_id: UserId,
     pub username: String,
     pub manager: Arc<AsyncSpaceManager>,
+    /// The user's channels, kept under their data directory
+    pub channels: Arc<ChannelManager>,
     pub network_task: Arc<Option<JoinHandle<()>>>,
+    /// Shared with `manager` and `channels`
+    mls: Arc<MlsService>,
     pub peer_id: PeerId,
+    /// Recent channel events, for streams to resume from
+    pub events: Arc<EventLog>,
+    /// When the session was last used
+    last_active: Arc<std::sync::Mutex<Instant>>,
+    /// Set once the session is logged out or swept
+    ended: Arc<watch::Sender<bool>>,
+}
+
+impl Session {
+    /// Time left before the session's current token expires
+    pub fn token_remaining(&self) -> Duration {
+        Claims {
+            session_id: self.id.clone(),
+            generation: self.token_generation,
+            expires_at: self.expires_at,
+        }
+        .remaining()
+    }
+
+    /// Changes to true when the session ends
+    pub fn ended(&self) -> watch::Receiver<bool> {
+        self.ended.subscribe()
+    }
+
+    /// Stop taking in network messages and save the MLS group state
+    async fn flush(&self) {
+        if let Some(task) = self.network_task.as_ref() {
+            task.abort();
+        }
+        for group_id in self.mls.list_groups().await {
+            if let Err(e) = self.mls.save_group(&group_id).await {
+                tracing::warn!("Failed to save group {} of session {}: {}", group_id, self.id, e);
+            }
+        }
+    }
+}
+
+/// A token handed to a client
+pub struct IssuedToken {
+    pub token: String,
+    /// Milliseconds since the Unix epoch
+    pub expires_at: u64,
 }
 
 /// Manages active user sessions
@@ -37,10 +92,18 @@ pub struct SessionManager {
     shared_channel_members: Arc<RwLock<HashMap<types::ChannelId, HashMap<UserId, PeerId>>>>,
     /// Counter for generating unique peer IDs
     peer_id_counter: Arc<tokio::sync::Mutex<u64>>,
+    /// MLS storage backend, scoped per user (SQLite in the user's data dir if unset)
+    mls_backend: Option<StorageBackend>,
+    /// Signs and checks session tokens
+    signer: Arc<TokenSigner>,
+    /// How long a token is valid before it must be refreshed
+    token_ttl: Duration,
+    /// Sessions unused for this long are swept
+    idle_timeout: Duration,
 }
 
 impl SessionManager {
-    pub fn new() -> Self {
+    pub fn new(mls_backend: Option<StorageBackend>) -> Self {
         // Create a single shared router for all sessions
         let (router_handle, _router_task) = RouterHandle::new();
         
@@ -65,11 +128,27 @@ impl SessionManager {
             shared_router: Arc::new(router_handle),
             shared_channel_members,
             peer_id_counter: Arc::new(tokio::sync::Mutex::new(0)),
+            mls_backend,
+            signer: Arc::new(TokenSigner::new()),
+            token_ttl: ServerConfig::default().session_ttl,
+            idle_timeout: ServerConfig::default().session_idle_timeout,
         }
     }
 
-    pub async fn create_session(&self, profile: &UserProfile) -> ApiResult<String> {
-        let token = Uuid::new_v4().to_string();
+    /// Set how long tokens last and how long a session may sit unused
+    pub fn with_session_limits(mut self, token_ttl: Duration, idle_timeout: Duration) -> Self {
+        self.token_ttl = token_ttl;
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Interceptor checking tokens sent as `authorization` metadata
+    pub fn interceptor(&self) -> TokenInterceptor {
+        TokenInterceptor::new(self.signer.clone())
+    }
+
+    pub async fn create_session(&self, profile: &UserProfile) -> ApiResult<IssuedToken> {
+        let session_id = Uuid::new_v4().to_string();
 
         // Initialize storage and MLS service for this user
         let db_path = profile.data_dir.join("spaces.db");
@@ -80,12 +159,20 @@ impl SessionManager {
         let store = SpaceSqlStore::new(pool)
             .map_err(|e| ApiError::Internal(anyhow::anyhow!("Failed to create store: {}", e)))?;
         
-        // Create minimal config for MLS service with SQL storage
-        let config = Config::default();
+        // Channel state lives in the user's data directory
+        let mut config = Config::default();
+        config.store.data_dir = profile.data_dir.join("channels");
+        let config = Arc::new(config);
         let shutdown = Arc::new(ShutdownCoordinator::new(std::time::Duration::from_secs(30)));
         let mls_storage_dir = profile.data_dir.join("mls");
+        let mls_service = match &self.mls_backend {
+            Some(backend) => {
+                MlsService::with_backend(&config, shutdown, backend.scoped(&profile.id))
+            }
+            None => MlsService::with_storage(&config, shutdown, mls_storage_dir),
+        };
         let mls_service = Arc::new(
-            MlsService::with_storage(&config, shutdown, mls_storage_dir)
+            mls_service
                 .map_err(|e| ApiError::Internal(anyhow::anyhow!("Failed to create MLS service: {}", e)))?
         );
 
@@ -110,7 +197,7 @@ impl SessionManager {
         let router_handle = self.shared_router.as_ref().clone();
         
         // Create network layer with SHARED channel members map
-        let (network_layer, mut incoming_rx, mut commits_rx) = NetworkLayer::with_shared_members(
+        let (network_layer, incoming_rx, commits_rx) = NetworkLayer::with_shared_members(
             router_handle,
             peer_id.clone(),
             self.shared_channel_members.clone(),
@@ -123,167 +210,272 @@ impl SessionManager {
         let _event_processor_task = network_layer.clone().spawn_event_processor();
         eprintln!("[P2P] Event processor spawned for peer {:?}", peer_id);
         
-        // Create space manager WITH network layer (enables P2P distribution)
+        // Spaces and their directories stay in the space manager; channel
+        // encryption and messaging go through the channel manager
         let manager = AsyncSpaceManager::with_network(
             store,
-            mls_service,
+            mls_service.clone(),
             network_layer.clone(),
         );
         let manager = Arc::new(manager);
-        
-        // Spawn background task to handle incoming MLS commits
-        let manager_for_commits = manager.clone();
-        let user_id_for_commits = UserId(profile.id.clone());
-        let local_peer_for_commits = peer_id.clone();
-        let _commits_task = tokio::spawn(async move {
-            eprintln!("[P2P] Commit processor started for user: {}", user_id_for_commits.0);
-            while let Some(incoming_commit) = commits_rx.recv().await {
-                // Skip commits from ourselves
-                if incoming_commit.sender_peer_id == local_peer_for_commits {
-                    eprintln!("[P2P] Skipping own commit (sender == self)");
-                    continue;
-                }
-                
-                eprintln!("[P2P] Received commit from peer {:?} for channel {}", 
-                    incoming_commit.sender_peer_id, incoming_commit.channel_id.0);
-                
-                // Decode hex channel ID to bytes
-                let channel_id_bytes = match hex::decode(&incoming_commit.channel_id.0) {
-                    Ok(bytes) if bytes.len() == 32 => {
-                        let mut arr = [0u8; 32];
-                        arr.copy_from_slice(&bytes);
-                        spacepanda_core::core_space::ChannelId::from_bytes(arr)
-                    }
-                    Ok(_) => {
-                        eprintln!("[P2P] Invalid commit channel ID length");
-                        continue;
-                    }
-                    Err(e) => {
-                        eprintln!("[P2P] Failed to decode commit channel ID: {}", e);
-                        continue;
-                    }
-                };
-                
-                // Process the commit
-                if let Err(e) = manager_for_commits.handle_incoming_commit(
-                    &channel_id_bytes,
-                    &incoming_commit.commit_data
-                ).await {
-                    eprintln!("[P2P] Error processing commit: {}", e);
-                }
-            }
-            eprintln!("[P2P] Commit processor ended for user: {}", user_id_for_commits.0);
-        });
-        
-        // Spawn background task to handle incoming P2P messages
-        let manager_clone = manager.clone();
-        let user_id_for_task = UserId(profile.id.clone());
-        let local_peer_id = peer_id.clone();
-        let network_task = tokio::spawn(async move {
-            eprintln!("[P2P] Background task started for user: {}", user_id_for_task.0);
-            while let Some(incoming) = incoming_rx.recv().await {
-                // Skip messages from ourselves (broadcast loopback)
-                if incoming.sender_peer_id == local_peer_id {
-                    eprintln!("[P2P] Skipping own message (sender == self)");
-                    continue;
-                }
-                
-                eprintln!("[P2P] Received message from peer {:?} for channel {}", 
-                    incoming.sender_peer_id, incoming.channel_id.0);
-                
-                // Decode hex channel ID to bytes
-                eprintln!("[P2P] Decoding channel ID: {}", incoming.channel_id.0);
-                let channel_id_bytes = match hex::decode(&incoming.channel_id.0) {
-                    Ok(bytes) if bytes.len() == 32 => {
-                        let mut arr = [0u8; 32];
-                        arr.copy_from_slice(&bytes);
-                        let decoded_id = spacepanda_core::core_space::ChannelId::from_bytes(arr);
-                        eprintln!("[P2P] Decoded channel ID to: {}", hex::encode(decoded_id.as_bytes()));
-                        decoded_id
-                    }
-                    Ok(bytes) => {
-                        eprintln!("[P2P] Invalid channel ID length: {} bytes (expected 32)", bytes.len());
-                        continue;
-                    }
-                    Err(e) => {
-                        eprintln!("[P2P] Failed to decode hex channel ID: {}", e);
-                        continue;
-                    }
-                };
-                
-                // Extract sender user ID from peer ID
-                // Note: In production, maintain a PeerId -> UserId mapping
-                // For now, we use a simple conversion since both are generated from UUIDs
-                let sender_id = UserId(format!("peer:{}", hex::encode(&incoming.sender_peer_id.as_bytes()[..8])));
-                
-                eprintln!("[P2P] Processing message for user {} from sender {}", 
-                    user_id_for_task.0, sender_id.0);
-                
-                // Handle incoming message from P2P network
-                match manager_clone.handle_incoming_message(
-                    &channel_id_bytes,
-                    &sender_id,
-                    &incoming.ciphertext
-                ).await {
-                    Ok(_) => {
-                        eprintln!("[P2P] ✓ Successfully processed incoming message");
-                    }
-                    Err(e) => {
-                        let error_str = e.to_string();
-                        // Categorize errors for better debugging
-                        if error_str.contains("CannotDecryptOwnMessage") {
-                            eprintln!("[P2P] Note: MLS detected own message (duplicate detection)");
-                        } else if error_str.contains("WrongEpoch") {
-                            eprintln!("[P2P] Warning: Message from wrong epoch (out of sync): {}", error_str);
-                        } else if error_str.contains("TooDistantInThePast") {
-                            eprintln!("[P2P] Warning: Message too old (out of order delivery): {}", error_str);
-                        } else if error_str.contains("UnableToDecrypt") {
-                            eprintln!("[P2P] Warning: Unable to decrypt message: {}", error_str);
-                        } else {
-                            eprintln!("[P2P] Error handling incoming P2P message: {}", e);
-                        }
-                    }
-                }
-            }
-            eprintln!("[P2P] Background task ended for user: {}", user_id_for_task.0);
-        });
+
+        let store_config = LocalStoreConfig {
+            data_dir: config.store.data_dir.clone(),
+            enable_encryption: false,
+            ..Default::default()
+        };
+        let channel_store = LocalStore::new(store_config)
+            .map_err(|e| ApiError::Internal(anyhow::anyhow!("Failed to create channel store: {}", e)))?;
+        let identity = Identity::new(
+            UserId(profile.id.clone()),
+            profile.username.clone(),
+            hex::encode(peer_id.as_bytes()),
+        );
+        let channels = ChannelManager::new(
+            mls_service.clone(),
+            Arc::new(channel_store),
+            Arc::new(identity),
+            config,
+        )
+        .with_network(network_layer.clone());
+        let channels = Arc::new(channels);
+
+        // Number the channel manager's events for StreamMessages
+        let events = Arc::new(EventLog::new(EVENT_LOG_CAPACITY));
+        events.clone().spawn_recorder(channels.subscribe_events());
+
+        channels.clone().spawn_commit_processor(commits_rx);
+        let network_task = channels.clone().spawn_message_processor(incoming_rx);
 
         let user_id = UserId(profile.id.clone());
 
+        let (token, claims) = self.signer.issue(&session_id, 0, self.token_ttl);
         let session = Session {
-            token: token.clone(),
+            id: session_id.clone(),
+            token_generation: claims.generation,
+            expires_at: claims.expires_at,
             user_id,
             username: profile.username.clone(),
             manager,
+            channels,
             network_task: Arc::new(Some(network_task)),
+            mls: mls_service,
             peer_id: peer_id.clone(),
+            events,
+            last_active: Arc::new(std::sync::Mutex::new(Instant::now())),
+            ended: Arc::new(watch::channel(false).0),
         };
 
         self.sessions
             .write()
             .await
-            .insert(token.clone(), session);
+            .insert(session_id, session);
 
-        Ok(token)
+        Ok(IssuedToken { token, expires_at: claims.expires_at })
     }
 
+    /// The session a token is for, if the token and session are still good
     pub async fn get_session(&self, token: &str) -> ApiResult<Session> {
-        self.sessions
+        let claims = self.signer.verify(token)?;
+        let sessions = self.sessions.read().await;
+        let session = sessions.get(&claims.session_id).ok_or(ApiError::InvalidSession)?;
+        if claims.generation != session.token_generation {
+            return Err(TokenError::Superseded.into());
+        }
+
+        *session.last_active.lock().unwrap() = Instant::now();
+        Ok(session.clone())
+    }
+
+    /// Swap a valid token for one that expires later
+    ///
+    /// The new token is of the next generation, so the old one stops working
+    /// even when both are issued within the same millisecond.
+    pub async fn refresh_session(&self, token: &str) -> ApiResult<IssuedToken> {
+        let claims = self.signer.verify(token)?;
+        let mut sessions = self.sessions.write().await;
+        let session = sessions.get_mut(&claims.session_id).ok_or(ApiError::InvalidSession)?;
+        if claims.generation != session.token_generation {
+            return Err(TokenError::Superseded.into());
+        }
+
+        let (token, claims) =
+            self.signer.issue(&session.id, session.token_generation + 1, self.token_ttl);
+        session.token_generation = claims.generation;
+        session.expires_at = claims.expires_at;
+        *session.last_active.lock().unwrap() = Instant::now();
+        Ok(IssuedToken { token, expires_at: claims.expires_at })
+    }
+
+    /// End the session a token is for
+    pub async fn end_session(&self, token: &str) -> ApiResult<()> {
+        let session = self.get_session(token).await?;
+        self.remove(&session.id).await;
+        Ok(())
+    }
+
+    /// Close sessions left unused or whose token has expired
+    ///
+    /// # Returns
+    ///
+    /// Number of sessions closed
+    pub async fn sweep(&self) -> usize {
+        let stale: Vec<String> = self
+            .sessions
             .read()
             .await
-            .get(token)
-            .cloned()
-            .ok_or(ApiError::InvalidSession)
+            .values()
+            .filter(|session| {
+                let idle = session.last_active.lock().unwrap().elapsed();
+                idle >= self.idle_timeout || session.token_remaining().is_zero()
+            })
+            .map(|session| session.id.clone())
+            .collect();
+
+        for session_id in &stale {
+            tracing::info!(session_id = %session_id, "Closing idle or expired session");
+            self.remove(session_id).await;
+        }
+        stale.len()
     }
 
-    pub async fn remove_session(&self, token: &str) -> ApiResult<()> {
-        self.sessions.write().await.remove(token);
-        Ok(())
+    /// Sweep sessions periodically until the manager is dropped
+    pub fn spawn_sweeper(self: &Arc<Self>) -> JoinHandle<()> {
+        let manager: Weak<Self> = Arc::downgrade(self);
+        let period = (self.idle_timeout / 4).max(Duration::from_millis(10));
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                let Some(manager) = manager.upgrade() else {
+                    break;
+                };
+                manager.sweep().await;
+            }
+        })
+    }
+
+    /// Flush every session once `shutdown` begins
+    ///
+    /// Registers with the coordinator, so shutdown waits for the flush to
+    /// finish. Sessions stay open meanwhile for requests still in flight.
+    pub fn flush_on_shutdown(self: &Arc<Self>, shutdown: &ShutdownCoordinator) -> JoinHandle<()> {
+        let mut signal = shutdown.subscribe();
+        let guard = shutdown.register("sessions");
+        let manager = self.clone();
+        tokio::spawn(async move {
+            let _ = signal.recv().await;
+            let sessions: Vec<Session> = manager.sessions.read().await.values().cloned().collect();
+            for session in &sessions {
+                session.flush().await;
+            }
+            tracing::info!("Flushed {} sessions for shutdown", sessions.len());
+            guard.done();
+        })
+    }
+
+    async fn remove(&self, session_id: &str) {
+        if let Some(session) = self.sessions.write().await.remove(session_id) {
+            session.ended.send_replace(true);
+        }
     }
 }
 
 impl Default for SessionManager {
     fn default() -> Self {
-        Self::new()
+        Self::new(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_idle_sessions_are_swept() {
+        let dir = TempDir::new().unwrap();
+        let manager = SessionManager::new(None)
+            .with_session_limits(Duration::from_secs(60), Duration::from_millis(200));
+        let manager = Arc::new(manager);
+        let profile = UserProfile {
+            id: Uuid::new_v4().to_string(),
+            username: "alice".to_string(),
+            password_hash: String::new(),
+            data_dir: dir.path().to_path_buf(),
+        };
+        let token = manager.create_session(&profile).await.unwrap().token;
+        let mut ended = manager.get_session(&token).await.unwrap().ended();
+
+        // Using the session keeps it open
+        assert_eq!(manager.sweep().await, 0);
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        manager.get_session(&token).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        assert_eq!(manager.sweep().await, 0);
+
+        // Left alone, the sweeper closes it
+        manager.spawn_sweeper();
+        tokio::time::timeout(Duration::from_secs(5), ended.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(*ended.borrow());
+        assert!(matches!(manager.get_session(&token).await, Err(ApiError::InvalidSession)));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_retires_the_old_token_at_once() {
+        let dir = TempDir::new().unwrap();
+        let manager = SessionManager::new(None);
+        let profile = UserProfile {
+            id: Uuid::new_v4().to_string(),
+            username: "alice".to_string(),
+            password_hash: String::new(),
+            data_dir: dir.path().to_path_buf(),
+        };
+        let first = manager.create_session(&profile).await.unwrap().token;
+
+        // Back-to-back refreshes usually land in the same millisecond
+        let second = manager.refresh_session(&first).await.unwrap().token;
+        let third = manager.refresh_session(&second).await.unwrap().token;
+
+        for stale in [&first, &second] {
+            assert!(matches!(
+                manager.get_session(stale).await,
+                Err(ApiError::Token(TokenError::Superseded))
+            ));
+            assert!(manager.refresh_session(stale).await.is_err());
+        }
+        assert_eq!(manager.get_session(&third).await.unwrap().token_generation, 2);
+    }
+
+    #[tokio::test]
+    async fn test_sessions_flush_before_shutdown_completes() {
+        let dir = TempDir::new().unwrap();
+        let manager = Arc::new(SessionManager::new(None));
+        let profile = UserProfile {
+            id: Uuid::new_v4().to_string(),
+            username: "alice".to_string(),
+            password_hash: String::new(),
+            data_dir: dir.path().to_path_buf(),
+        };
+        let token = manager.create_session(&profile).await.unwrap().token;
+        let session = manager.get_session(&token).await.unwrap();
+
+        let shutdown = ShutdownCoordinator::new(Duration::from_secs(30));
+        let flush = manager.flush_on_shutdown(&shutdown);
+        assert_eq!(shutdown.pending_components(), ["sessions"]);
+
+        // Shutdown returns as soon as the flush is done, well before its timeout
+        let started = Instant::now();
+        shutdown.shutdown().await;
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(shutdown.pending_components().is_empty());
+        flush.await.unwrap();
+
+        // The session is still usable by requests in flight
+        assert_eq!(manager.get_session(&token).await.unwrap().id, session.id);
     }
 }
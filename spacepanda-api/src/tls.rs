@@ -0,0 +1,222 @@
+//! TLS for the gRPC server
+//!
+//! The certificate and key are read from the paths in `ServerConfig` at
+//! startup, where any problem with them stops the server, and again on
+//! SIGHUP, where a problem is logged and the previous certificate stays in
+//! use. Connections already open keep the certificate they were made with.
+
+use anyhow::{anyhow, Context, Result};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tokio::net::TcpStream;
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+use tracing::{error, info};
+
+/// The server's TLS settings, reloadable while it runs
+#[derive(Clone)]
+pub struct ServerTls {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    acceptor: Arc<RwLock<TlsAcceptor>>,
+}
+
+impl ServerTls {
+    /// Load the certificate chain and private key, both PEM
+    pub fn load(cert_path: &Path, key_path: &Path) -> Result<Self> {
+        let acceptor = acceptor(cert_path, key_path)?;
+        Ok(Self {
+            cert_path: cert_path.to_path_buf(),
+            key_path: key_path.to_path_buf(),
+            acceptor: Arc::new(RwLock::new(acceptor)),
+        })
+    }
+
+    /// Read the certificate and key again, keeping the current ones on failure
+    pub fn reload(&self) -> Result<()> {
+        let acceptor = acceptor(&self.cert_path, &self.key_path)?;
+        *self.acceptor.write().unwrap_or_else(|e| e.into_inner()) = acceptor;
+        Ok(())
+    }
+
+    /// Reload whenever the process receives SIGHUP
+    #[cfg(unix)]
+    pub fn reload_on_sighup(&self) -> Result<()> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut hangup =
+            signal(SignalKind::hangup()).context("Failed to install SIGHUP handler")?;
+        let tls = self.clone();
+        tokio::spawn(async move {
+            while hangup.recv().await.is_some() {
+                match tls.reload() {
+                    Ok(()) => info!("Reloaded TLS certificate from {}", tls.cert_path.display()),
+                    Err(e) => error!("Keeping the current TLS certificate: {:#}", e),
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Run the server side of the handshake with the current certificate
+    pub async fn handshake(&self, stream: TcpStream) -> std::io::Result<TlsStream<TcpStream>> {
+        let acceptor = self.acceptor.read().unwrap_or_else(|e| e.into_inner()).clone();
+        acceptor.accept(stream).await
+    }
+}
+
+fn acceptor(cert_path: &Path, key_path: &Path) -> Result<TlsAcceptor> {
+    let certs = read_certs(cert_path)?;
+    let key = read_key(key_path)?;
+
+    // Pinned rather than left to the process default, which is ambiguous when
+    // more than one rustls backend is linked in
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut config = rustls::ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .context("Failed to set up TLS")?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| {
+            anyhow!(
+                "TLS key {} doesn't match certificate {}: {}",
+                key_path.display(),
+                cert_path.display(),
+                e
+            )
+        })?;
+    config.alpn_protocols = vec![b"h2".to_vec()];
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+fn read_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to read TLS certificate {}", path.display()))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Failed to parse TLS certificate {}", path.display()))?;
+    if certs.is_empty() {
+        return Err(anyhow!("No certificates found in {}", path.display()));
+    }
+    Ok(certs)
+}
+
+fn read_key(path: &Path) -> Result<PrivateKeyDer<'static>> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to read TLS key {}", path.display()))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .with_context(|| format!("Failed to parse TLS key {}", path.display()))?
+        .ok_or_else(|| anyhow!("No private key found in {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::listener;
+    use crate::proto::space_service_client::SpaceServiceClient;
+    use crate::proto::space_service_server::SpaceServiceServer;
+    use crate::proto::ListSpacesRequest;
+    use crate::services::SpaceServiceImpl;
+    use crate::session::SessionManager;
+    use std::time::Duration;
+    use tempfile::TempDir;
+    use tokio::net::TcpListener;
+    use tonic::transport::{Certificate, ClientTlsConfig, Endpoint, Server};
+
+    /// A self-signed certificate for localhost, written to `dir`
+    fn write_cert(dir: &TempDir, name: &str) -> (PathBuf, PathBuf, String) {
+        let generated = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let cert_pem = generated.cert.pem();
+        let cert_path = dir.path().join(format!("{}.crt", name));
+        let key_path = dir.path().join(format!("{}.key", name));
+        std::fs::write(&cert_path, &cert_pem).unwrap();
+        std::fs::write(&key_path, generated.key_pair.serialize_pem()).unwrap();
+        (cert_path, key_path, cert_pem)
+    }
+
+    async fn start_server(tls: ServerTls) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let incoming = listener::accept(listener, 16, Duration::from_secs(5), move |stream| {
+            let tls = tls.clone();
+            async move { tls.handshake(stream).await }
+        });
+        let spaces = SpaceServiceImpl::new(Arc::new(SessionManager::new(None)));
+        tokio::spawn(async move {
+            Server::builder()
+                .add_service(SpaceServiceServer::new(spaces))
+                .serve_with_incoming(incoming)
+                .await
+                .unwrap();
+        });
+        format!("https://localhost:{}", port)
+    }
+
+    /// Whether a client trusting only `ca_pem` gets a response from `url`
+    async fn reaches(url: &str, ca_pem: &str) -> bool {
+        let tls = ClientTlsConfig::new()
+            .ca_certificate(Certificate::from_pem(ca_pem))
+            .domain_name("localhost");
+        let channel = match Endpoint::from_shared(url.to_string())
+            .unwrap()
+            .tls_config(tls)
+            .unwrap()
+            .connect()
+            .await
+        {
+            Ok(channel) => channel,
+            Err(_) => return false,
+        };
+
+        // No session, but getting that far means the handshake worked
+        let status = SpaceServiceClient::new(channel)
+            .list_spaces(ListSpacesRequest { session_token: String::new() })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+        true
+    }
+
+    #[tokio::test]
+    async fn test_tls_client_connects() {
+        let dir = TempDir::new().unwrap();
+        let (cert_path, key_path, cert_pem) = write_cert(&dir, "server");
+        let tls = ServerTls::load(&cert_path, &key_path).unwrap();
+        let url = start_server(tls.clone()).await;
+
+        assert!(reaches(&url, &cert_pem).await);
+
+        // A reload picks up a replaced certificate for new connections
+        let (new_cert, new_key, new_pem) = write_cert(&dir, "renewed");
+        std::fs::copy(&new_cert, &cert_path).unwrap();
+        std::fs::copy(&new_key, &key_path).unwrap();
+        tls.reload().unwrap();
+        assert!(reaches(&url, &new_pem).await);
+        assert!(!reaches(&url, &cert_pem).await);
+    }
+
+    #[test]
+    fn test_bad_files_are_reported() {
+        let dir = TempDir::new().unwrap();
+        let (cert_path, key_path, _) = write_cert(&dir, "server");
+        let (_, other_key, _) = write_cert(&dir, "other");
+        let missing = dir.path().join("missing.pem");
+
+        let err = ServerTls::load(&missing, &key_path).err().unwrap().to_string();
+        assert!(err.starts_with("Failed to read TLS certificate"), "{}", err);
+        let err = ServerTls::load(&cert_path, &missing).err().unwrap().to_string();
+        assert!(err.starts_with("Failed to read TLS key"), "{}", err);
+        let err = ServerTls::load(&key_path, &key_path).err().unwrap().to_string();
+        assert!(err.starts_with("No certificates found"), "{}", err);
+        let err = ServerTls::load(&cert_path, &other_key).err().unwrap().to_string();
+        assert!(err.contains("doesn't match certificate"), "{}", err);
+
+        // A failed reload leaves the working certificate in place
+        let tls = ServerTls::load(&cert_path, &key_path).unwrap();
+        std::fs::write(&key_path, "").unwrap();
+        assert!(tls.reload().is_err());
+    }
+}
@@ -0,0 +1,186 @@
+//! Signed session tokens
+//!
+//! A token is `<session id>.<generation>.<expiry>.<signature>`: the
+//! session's token generation, which goes up by one on every refresh, the
+//! expiry in milliseconds since the Unix epoch, and an HMAC-SHA256 over the
+//! first three parts under a key the server generates when it starts. A
+//! session only accepts tokens of its current generation, so a refresh
+//! always retires the old token, however soon it follows. A token can be
+//! checked without looking its session up, so forged and expired ones are
+//! turned away before any work is done; tokens from an earlier run of the
+//! server fail the check, as their sessions are gone too.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tonic::service::Interceptor;
+use tonic::{Request, Status};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Why a token was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum TokenError {
+    #[error("Missing session token")]
+    Missing,
+
+    #[error("Malformed session token")]
+    Malformed,
+
+    #[error("Session token signature is invalid")]
+    Forged,
+
+    #[error("Session token expired")]
+    Expired,
+
+    #[error("Session token was replaced by a refresh")]
+    Superseded,
+}
+
+impl From<TokenError> for Status {
+    fn from(err: TokenError) -> Self {
+        Status::unauthenticated(err.to_string())
+    }
+}
+
+/// What a valid token says
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Claims {
+    pub session_id: String,
+    /// Token generation of the session, bumped by each refresh
+    pub generation: u64,
+    /// Milliseconds since the Unix epoch
+    pub expires_at: u64,
+}
+
+impl Claims {
+    /// Time left before the token expires
+    pub fn remaining(&self) -> Duration {
+        Duration::from_millis(self.expires_at.saturating_sub(now_millis()))
+    }
+}
+
+/// Issues and checks session tokens
+pub struct TokenSigner {
+    key: [u8; 32],
+}
+
+impl TokenSigner {
+    /// A signer with a fresh random key
+    pub fn new() -> Self {
+        Self { key: rand::random() }
+    }
+
+    /// A token of `generation` for `session_id` valid for `ttl`
+    pub fn issue(&self, session_id: &str, generation: u64, ttl: Duration) -> (String, Claims) {
+        let expires_at = now_millis() + ttl.as_millis() as u64;
+        let payload = format!("{}.{}.{}", session_id, generation, expires_at);
+        let signature = hex::encode(self.mac(&payload).finalize().into_bytes());
+        let claims = Claims { session_id: session_id.to_string(), generation, expires_at };
+        (format!("{}.{}", payload, signature), claims)
+    }
+
+    /// The claims of a token signed by this signer that hasn't expired
+    pub fn verify(&self, token: &str) -> Result<Claims, TokenError> {
+        if token.is_empty() {
+            return Err(TokenError::Missing);
+        }
+        let (payload, signature) = token.rsplit_once('.').ok_or(TokenError::Malformed)?;
+        let (rest, expires_at) = payload.rsplit_once('.').ok_or(TokenError::Malformed)?;
+        let (session_id, generation) = rest.rsplit_once('.').ok_or(TokenError::Malformed)?;
+        let generation: u64 = generation.parse().map_err(|_| TokenError::Malformed)?;
+        let expires_at: u64 = expires_at.parse().map_err(|_| TokenError::Malformed)?;
+        let signature = hex::decode(signature).map_err(|_| TokenError::Malformed)?;
+
+        self.mac(payload).verify_slice(&signature).map_err(|_| TokenError::Forged)?;
+        if now_millis() >= expires_at {
+            return Err(TokenError::Expired);
+        }
+        Ok(Claims { session_id: session_id.to_string(), generation, expires_at })
+    }
+
+    fn mac(&self, payload: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC takes any key length");
+        mac.update(payload.as_bytes());
+        mac
+    }
+}
+
+impl Default for TokenSigner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Checks the token in a request's `authorization` metadata
+///
+/// Requests without one pass through; the token in their body is checked
+/// when their session is looked up. A bad token is refused here, before the
+/// request is decoded, and a good one's claims are added to the request's
+/// extensions.
+#[derive(Clone)]
+pub struct TokenInterceptor {
+    signer: std::sync::Arc<TokenSigner>,
+}
+
+impl TokenInterceptor {
+    pub fn new(signer: std::sync::Arc<TokenSigner>) -> Self {
+        Self { signer }
+    }
+}
+
+impl Interceptor for TokenInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let Some(value) = request.metadata().get("authorization") else {
+            return Ok(request);
+        };
+        let token = value
+            .to_str()
+            .ok()
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(TokenError::Malformed)?;
+        let claims = self.signer.verify(token)?;
+        request.extensions_mut().insert(claims);
+        Ok(request)
+    }
+}
+
+/// Milliseconds since the Unix epoch
+pub fn now_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_are_checked() {
+        let signer = TokenSigner::new();
+        let (token, claims) = signer.issue("session-1", 0, Duration::from_secs(60));
+        assert_eq!(signer.verify(&token), Ok(claims));
+
+        // Changing any part breaks the signature
+        let longer = token.replacen(".", "0.", 1);
+        assert_eq!(signer.verify(&longer), Err(TokenError::Forged));
+        let other = token.replacen("session-1", "session-2", 1);
+        assert_eq!(signer.verify(&other), Err(TokenError::Forged));
+        let (foreign, _) = TokenSigner::new().issue("session-1", 0, Duration::from_secs(60));
+        assert_eq!(signer.verify(&foreign), Err(TokenError::Forged));
+        let bumped = token.replacen("session-1.0.", "session-1.1.", 1);
+        assert_eq!(signer.verify(&bumped), Err(TokenError::Forged));
+
+        assert_eq!(signer.verify(""), Err(TokenError::Missing));
+        assert_eq!(signer.verify("session-1"), Err(TokenError::Malformed));
+        assert_eq!(signer.verify("session-1.0.soon.00"), Err(TokenError::Malformed));
+        assert_eq!(signer.verify("session-1.1000.00"), Err(TokenError::Malformed));
+    }
+
+    #[test]
+    fn test_tokens_expire() {
+        let signer = TokenSigner::new();
+        let (token, _) = signer.issue("session-1", 0, Duration::ZERO);
+        assert_eq!(signer.verify(&token), Err(TokenError::Expired));
+    }
+}
@@ -0,0 +1,55 @@
+//! Startup integrity attestation
+//!
+//! With `store.attestation` enabled, every command that opens the data
+//! directory first checks it against its signed startup history and then
+//! records the new startup. A directory restored from an older copy, or
+//! opened by an older binary, is refused unless `--accept-rollback` is
+//! given; accepting it raises a security event.
+
+use anyhow::{Context, Result};
+use spacepanda_core::{
+    core_identity::MasterKey, core_mvp::SecurityEvent, core_store::store::StartupAttestation,
+};
+use std::path::Path;
+use std::sync::OnceLock;
+use tracing::{debug, error};
+
+/// Whether `--accept-rollback` was given
+static ACCEPT_ROLLBACK: OnceLock<bool> = OnceLock::new();
+
+/// Start even if the data directory fails its attestation
+pub fn set_accept_rollback(accept: bool) {
+    let _ = ACCEPT_ROLLBACK.set(accept);
+}
+
+/// Check the data directory and record this startup
+pub fn attest(data_dir: &Path) -> Result<()> {
+    let signer = signing_key(data_dir)?;
+    let accept_rollback = ACCEPT_ROLLBACK.get().copied().unwrap_or(false);
+
+    let outcome = StartupAttestation::new(data_dir, env!("CARGO_PKG_VERSION"))
+        .attest(signer.keypair(), accept_rollback)
+        .with_context(|| {
+            "Data directory failed its integrity attestation \
+             (run with --accept-rollback if this is expected)"
+        })?;
+    debug!("Attested startup {}", outcome.attestation.record.counter);
+
+    if !outcome.anomalies.is_empty() {
+        let event = SecurityEvent::IntegrityRollbackAccepted {
+            anomalies: outcome.anomalies.iter().map(|a| a.to_string()).collect(),
+        };
+        error!("{}", event);
+        eprintln!("🚨 {}", event);
+    }
+    Ok(())
+}
+
+/// Key attestations are signed with: the master key, or a linked device's key
+fn signing_key(data_dir: &Path) -> Result<MasterKey> {
+    match super::device::load(data_dir)? {
+        Some(linked) => MasterKey::from_keypair(super::device::load_key(data_dir, &linked)?)
+            .map_err(anyhow::Error::msg),
+        None => super::load_master_key(data_dir),
+    }
+}
@@ -0,0 +1,254 @@
+//! Linked devices
+//!
+//! A user can run SpacePanda on several devices. The first device holds the
+//! identity master key. Each further device gets its own device key, which
+//! the master key authorizes with a signed binding, and joins the user's
+//! channels as another leaf of the same member. Device keys stay in each
+//! device's keystore; device.json records the binding.
+
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use spacepanda_core::{
+    config::StoreConfig,
+    core_identity::{
+        DeviceBundle, DeviceId, DeviceKeyBinding, DeviceMetadata, IdentityBundle, KeyType, Keypair,
+        Keystore, KeystoreError,
+    },
+    core_mls::service::MlsService,
+    core_mvp::{DeviceLinkApproval, DeviceLinkRequest},
+    core_store::{model::types::UserId, store::local_store::LocalStore},
+    shutdown::ShutdownCoordinator,
+    ChannelManager, Identity,
+};
+use std::io::BufRead;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// File in the data directory that marks a linked device
+const LINK_FILE: &str = "device.json";
+
+/// This device's link to an identity held on another device
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkedDevice {
+    /// This device's ID; its key is in the keystore under it
+    pub device_id: DeviceId,
+
+    /// Identity master public key that authorized this device
+    pub identity_public_key: Vec<u8>,
+
+    /// The master key's authorization of this device
+    pub binding: DeviceKeyBinding,
+}
+
+/// This device's link, if it is a linked device
+pub fn load(data_dir: &Path) -> Result<Option<LinkedDevice>> {
+    let path = data_dir.join(LINK_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let json =
+        std::fs::read_to_string(&path).with_context(|| format!("Failed to read {:?}", path))?;
+    Ok(Some(
+        serde_json::from_str(&json).with_context(|| format!("Invalid {:?}", path))?,
+    ))
+}
+
+/// Whether the data directory belongs to a linked device
+pub fn is_linked(data_dir: &Path) -> bool {
+    data_dir.join(LINK_FILE).exists()
+}
+
+/// Unlock this device's key
+pub fn load_key(data_dir: &Path, linked: &LinkedDevice) -> Result<Keypair> {
+    super::open_keystore(data_dir)?
+        .load_device_keypair(&linked.device_id)
+        .map_err(|e| {
+            let context = match e {
+                KeystoreError::InvalidPassword => "Wrong keystore passphrase",
+                _ => "Failed to load device key from keystore",
+            };
+            anyhow::Error::new(e).context(context)
+        })
+}
+
+/// Ask to be linked to an existing identity, then join its channels
+///
+/// Prints a link request for the device holding the identity key and waits
+/// for the approval code it prints in return. The key packages in the
+/// request only exist in this process, so the approval must be pasted into
+/// the same run.
+pub async fn link_request(
+    data_dir: &Path,
+    user_id: &str,
+    name: &str,
+    key_packages: usize,
+) -> Result<()> {
+    std::fs::create_dir_all(data_dir)
+        .with_context(|| format!("Failed to create directory {:?}", data_dir))?;
+    let identity_path = data_dir.join("identity.json");
+    if identity_path.exists() {
+        anyhow::bail!(
+            "Identity already exists at {:?}. Link from a new data directory.",
+            identity_path
+        );
+    }
+    if key_packages == 0 {
+        anyhow::bail!("A link request needs at least one key package");
+    }
+
+    // The device key goes into a new keystore before anything else is written
+    let device_kp = Keypair::generate(KeyType::Ed25519);
+    let device_id = DeviceId::from_pubkey(device_kp.public_key());
+    let passphrase = super::passphrase::new_keystore()?;
+    let keystore = super::open_keystore_with(data_dir, Some(&passphrase))?;
+    keystore
+        .save_device_keypair(&device_id, &device_kp)
+        .with_context(|| "Failed to save device key")?;
+
+    let mut identity =
+        Identity::new(UserId(user_id.to_string()), String::new(), uuid::Uuid::new_v4().to_string());
+
+    // Key packages belong to this MLS service, which must also do the joining
+    let config = Arc::new(super::load_config(data_dir)?);
+    let shutdown = Arc::new(ShutdownCoordinator::new(Duration::from_secs(30)));
+    let mls_service = Arc::new(
+        MlsService::with_storage(&config, shutdown, data_dir.join("mls_groups"))
+            .and_then(|service| service.with_recovery_keystore(&keystore))
+            .with_context(|| "Failed to initialize MLS service with storage")?,
+    );
+    let mut packages = Vec::with_capacity(key_packages);
+    for _ in 0..key_packages {
+        packages.push(mls_service.generate_key_package(identity.as_bytes()).await?);
+    }
+
+    let metadata = DeviceMetadata::new(device_id.clone(), name.to_string(), &identity.node_id);
+    let bundle = DeviceBundle::for_new_device(&device_kp, metadata);
+    let request = DeviceLinkRequest::new(&device_kp, bundle, packages);
+
+    println!("✅ Link request created for device {}", device_id);
+    println!("\nOn a device that holds the identity key, run:");
+    println!("  spacepanda device approve {}", STANDARD.encode(serde_json::to_vec(&request)?));
+    println!("\nThen paste the approval code here:");
+
+    let mut code = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut code)
+        .with_context(|| "Failed to read approval code")?;
+    let approval: DeviceLinkApproval = serde_json::from_slice(
+        &STANDARD
+            .decode(code.trim())
+            .with_context(|| "Invalid approval code (not valid base64)")?,
+    )
+    .with_context(|| "Invalid approval format")?;
+
+    if approval.user_id != identity.user_id {
+        anyhow::bail!("Approval is for user {}, not {}", approval.user_id, identity.user_id);
+    }
+    if !approval.authorizes(device_kp.public_key()) {
+        anyhow::bail!("Approval does not authorize this device");
+    }
+
+    let linked = LinkedDevice {
+        device_id,
+        identity_public_key: approval.identity_public_key,
+        binding: approval.binding,
+    };
+    std::fs::write(data_dir.join(LINK_FILE), serde_json::to_string_pretty(&linked)?)?;
+
+    identity.display_name = approval.display_name;
+    std::fs::write(&identity_path, serde_json::to_string_pretty(&identity)?)?;
+
+    // Initialize local store, encrypted at rest with the device key
+    let key = super::store_key(data_dir, false)?;
+    LocalStore::open_encrypted(super::store_config(data_dir, &StoreConfig::default()), &key)
+        .with_context(|| "Failed to initialize local store")?;
+
+    let manager = super::assemble_manager(data_dir, config, mls_service, identity.clone())?;
+    let mut joined = 0;
+    for invite in &approval.invites {
+        match manager.join_channel(invite).await {
+            Ok(_) => joined += 1,
+            Err(e) => println!("⚠️  Could not join {}: {}", invite.channel_name, e),
+        }
+    }
+
+    println!("✅ Device linked to {} ({})", identity.display_name, identity.user_id);
+    println!("   Data directory: {:?}", data_dir);
+    println!("   Channels joined: {}/{}", joined, approval.invites.len());
+
+    Ok(())
+}
+
+/// Authorize a new device and add it to this identity's channels
+pub async fn approve(data_dir: &Path, manager: Arc<ChannelManager>, code: &str) -> Result<()> {
+    if is_linked(data_dir) {
+        anyhow::bail!(
+            "This is a linked device; approve from the device that holds the identity key"
+        );
+    }
+
+    let mut request: DeviceLinkRequest = serde_json::from_slice(
+        &STANDARD
+            .decode(code.trim())
+            .with_context(|| "Invalid link request (not valid base64)")?,
+    )
+    .with_context(|| "Invalid link request format")?;
+
+    let master_key = super::load_master_key(data_dir)?;
+    let identity = manager.identity();
+    let mut identity_bundle = IdentityBundle::new(
+        identity.as_bytes(),
+        master_key.public_key().to_vec(),
+        Vec::new(),
+        master_key.keypair(),
+    );
+    let binding = identity_bundle
+        .authorize_device(&master_key, &request.device_bundle)
+        .with_context(|| "Link request has an invalid device bundle")?;
+
+    request.device_bundle = request.device_bundle.with_binding(binding.clone());
+    let linked = manager.link_device(&request, master_key.public_key()).await?;
+
+    let approval = DeviceLinkApproval {
+        user_id: identity.user_id.clone(),
+        display_name: identity.display_name.clone(),
+        identity_public_key: master_key.public_key().to_vec(),
+        binding,
+        invites: linked.invites,
+    };
+
+    println!("✅ Device {} approved", linked.device_id);
+    println!("   Added to {} channel(s)", approval.invites.len());
+    for channel_id in &linked.skipped {
+        println!("   ⚠️  Not added to {} (invite it separately)", channel_id);
+    }
+    println!("\nApproval code (paste it on the new device):");
+    println!("{}", STANDARD.encode(serde_json::to_vec(&approval)?));
+
+    Ok(())
+}
+
+/// Revoke a linked device and remove it from this identity's channels
+pub async fn revoke(data_dir: &Path, manager: Arc<ChannelManager>, device_id: &str) -> Result<()> {
+    if is_linked(data_dir) {
+        anyhow::bail!(
+            "This is a linked device; revoke from the device that holds the identity key"
+        );
+    }
+    let device_id = DeviceId::from_string(device_id.trim()).map_err(anyhow::Error::msg)?;
+
+    // Only the holder of the identity key may revoke its devices
+    super::load_master_key(data_dir)?;
+    let revoked = manager.revoke_device(&device_id).await?;
+
+    println!("✅ Device {} revoked", revoked.device_id);
+    println!("   Removed from {} channel(s)", revoked.commits.len());
+    for channel_id in &revoked.skipped {
+        println!("   ⚠️  Could not remove it from {} (remove it there separately)", channel_id);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,849 @@
+//! DHT status and debugging
+//!
+//! `spacepanda dht status` bootstraps the DHT the way a starting node does:
+//! through the peers remembered from earlier runs first, then the bootstrap
+//! peers in config.toml. Plain TCP only tells whether something is
+//! listening, so a peer counts as answering when it accepts a connection,
+//! and a bootstrap peer seen for the first time is keyed by its address
+//! until the DHT learns its node ID.
+//!
+//! `put`, `get`, `peers` and `stats` are for poking at the DHT by hand. They
+//! go through the control socket of a node running `spacepanda dht serve`
+//! in the same data directory if there is one. Otherwise they bootstrap a
+//! transient node for the one command, pinging peers over the DHT wire
+//! protocol so they're keyed by their real node IDs. On the wire, each
+//! message is a 4-byte big-endian length followed by the message as JSON.
+//! Keys are hashed into DHT keys and values are stored as UTF-8 text.
+//!
+//! A key's namespace is the part before its first `:`. `get` looks up keys
+//! in the namespaces listed under `dht.private_lookups` in config.toml
+//! through relays, so the nodes holding them don't learn who asked.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use spacepanda_core::{
+    config::Config,
+    core_dht::{
+        ConsistencyLevel, DhtClient, DhtConfig, DhtKey, DhtLookupTransport,
+        DhtMessageNew as DhtMessage, DhtServer, DhtStorage, DhtTransport, DhtValue, LookupMode,
+        PeerBootstrap, PeerProber, PrivateLookup, RoutingTable, UsageReport,
+    },
+    core_router::RouterHandle,
+    core_store::store::local_store::LocalStore,
+    metrics::record_histogram,
+    shutdown::{install_signal_handlers, ShutdownCoordinator},
+};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UnixListener, UnixStream};
+use tokio::sync::{mpsc, Mutex};
+use tracing::{debug, warn};
+
+/// Control socket of a serving node, in its data directory
+const CONTROL_SOCKET: &str = "dht.sock";
+
+/// Largest message read from a peer or the control socket
+const MAX_FRAME: usize = 8 * 1024 * 1024;
+
+/// Round trips remembered for `stats`
+const MAX_LATENCY_SAMPLES: usize = 1024;
+
+/// Timeout for each connection attempt
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Counts a peer as answering if it accepts a TCP connection
+struct TcpProber;
+
+#[async_trait]
+impl PeerProber for TcpProber {
+    async fn ping(&self, address: &str, expected: Option<DhtKey>) -> Result<DhtKey, String> {
+        TcpStream::connect(address).await.map_err(|e| e.to_string())?;
+        Ok(expected.unwrap_or_else(|| DhtKey::hash_string(address)))
+    }
+}
+
+/// Bootstrap and show how it went, then list the peers now known
+pub async fn status(store: LocalStore, config: &Config, node_id: &str) -> Result<()> {
+    let table = RoutingTable::new(DhtKey::hash_string(node_id), config.dht.bucket_size);
+    let bootstrap_peers = config.dht.bootstrap_peers.iter().map(|a| a.to_string()).collect();
+    let peers = Arc::new(
+        PeerBootstrap::new(
+            Arc::new(Mutex::new(table)),
+            Arc::new(store),
+            Arc::new(TcpProber),
+            bootstrap_peers,
+        )
+        .with_rpc_timeout(PING_TIMEOUT),
+    );
+
+    let summary = peers.bootstrap().await;
+    println!("🌐 DHT status");
+    println!("   Remembered peers: {}", summary.restored);
+    println!("   Contacted: {}", summary.contacted);
+    println!("   Responded: {}", summary.responded);
+    if summary.used_bootstrap_peers {
+        println!("   (no remembered peer answered; used the configured bootstrap peers)");
+    }
+    println!("   Routing table size: {}", summary.table_size);
+
+    let mut known = peers.routing_table().lock().await.all_peers();
+    known.sort_by(|a, b| b.reliability.total_cmp(&a.reliability));
+    if !known.is_empty() {
+        println!();
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    for peer in &known {
+        let seen = now.saturating_sub(peer.last_seen);
+        println!(
+            "   {}  reliability {:.2}, seen {} ago{}",
+            peer.address,
+            peer.reliability,
+            ago(seen),
+            if peer.failed_rpcs > 0 {
+                ", not answering"
+            } else {
+                ""
+            }
+        );
+    }
+
+    peers.persist().await?;
+    Ok(())
+}
+
+/// Rough age, e.g. "3h"
+fn ago(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
+/// Something to do against the DHT
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ControlRequest {
+    /// Store `value` under `key` for `ttl_secs`
+    Put { key: String, value: String, ttl_secs: u64 },
+    /// Look up the value under `key`
+    Get { key: String },
+    /// Dump the routing table
+    Peers,
+    /// Storage usage and RPC latencies
+    Stats,
+}
+
+/// What came of a `ControlRequest`
+#[derive(Debug, Serialize, Deserialize)]
+enum ControlResponse {
+    Stored(Stored),
+    Value(Option<Found>),
+    Peers(Vec<Peer>),
+    Stats(Stats),
+    Error(String),
+}
+
+/// Where a value was stored
+#[derive(Debug, Serialize, Deserialize)]
+struct Stored {
+    key: String,
+    stored_on: Vec<String>,
+}
+
+/// A value found in the DHT
+#[derive(Debug, Serialize, Deserialize)]
+struct Found {
+    key: String,
+    value: String,
+    sequence: u64,
+    expires_in_secs: Option<u64>,
+}
+
+/// A routing table entry
+#[derive(Debug, Serialize, Deserialize)]
+struct Peer {
+    id: String,
+    address: String,
+    last_seen_secs_ago: u64,
+    rtt_ms: Option<f64>,
+    reliability: f64,
+    failed_rpcs: u32,
+    /// From 0.0 (misbehaving) to 1.0 (well-behaved); 0.5 for a peer with no record
+    reputation: f64,
+    /// Seconds left in quarantine, if the peer is in it
+    quarantined_secs: Option<u64>,
+}
+
+/// How a node is doing
+#[derive(Debug, Serialize, Deserialize)]
+struct Stats {
+    node_id: String,
+    peers: usize,
+    /// What the node holds for others; transient nodes hold nothing
+    storage: Option<StorageStats>,
+    rpc_latency: LatencySummary,
+}
+
+/// What a node holds for others
+#[derive(Debug, Serialize, Deserialize)]
+struct StorageStats {
+    records: usize,
+    bytes: u64,
+    max_bytes: u64,
+    evictions: u64,
+    rejections: u64,
+    /// Heaviest publishers first
+    publishers: Vec<PublisherUsage>,
+}
+
+impl From<UsageReport> for StorageStats {
+    fn from(report: UsageReport) -> Self {
+        let publishers = report
+            .publishers
+            .into_iter()
+            .map(|(id, usage)| PublisherUsage {
+                id: id.to_string(),
+                records: usage.records,
+                bytes: usage.bytes,
+            })
+            .collect();
+        StorageStats {
+            records: report.total.records,
+            bytes: report.total.bytes,
+            max_bytes: report.quota.max_bytes,
+            evictions: report.evictions,
+            rejections: report.rejections,
+            publishers,
+        }
+    }
+}
+
+/// Storage held for one publisher
+#[derive(Debug, Serialize, Deserialize)]
+struct PublisherUsage {
+    id: String,
+    records: usize,
+    bytes: u64,
+}
+
+/// Round trips of recent DHT RPCs
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LatencySummary {
+    samples: usize,
+    p50_ms: Option<f64>,
+    p95_ms: Option<f64>,
+    max_ms: Option<f64>,
+}
+
+/// The latest RPC round trips
+#[derive(Default)]
+struct Latencies(std::sync::Mutex<VecDeque<Duration>>);
+
+impl Latencies {
+    fn record(&self, rtt: Duration) {
+        record_histogram("dht.rpc_latency_ms", rtt.as_secs_f64() * 1000.0);
+        let mut samples = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if samples.len() == MAX_LATENCY_SAMPLES {
+            samples.pop_front();
+        }
+        samples.push_back(rtt);
+    }
+
+    fn summary(&self) -> LatencySummary {
+        let mut samples: Vec<Duration> =
+            self.0.lock().unwrap_or_else(|e| e.into_inner()).iter().copied().collect();
+        if samples.is_empty() {
+            return LatencySummary::default();
+        }
+        samples.sort();
+        let ms = |rtt: &Duration| rtt.as_secs_f64() * 1000.0;
+        let at = |q: f64| ms(&samples[((samples.len() - 1) as f64 * q).round() as usize]);
+        LatencySummary {
+            samples: samples.len(),
+            p50_ms: Some(at(0.5)),
+            p95_ms: Some(at(0.95)),
+            max_ms: samples.last().map(ms),
+        }
+    }
+}
+
+/// Write `message` as one length-prefixed JSON frame
+async fn write_frame<T: Serialize>(
+    stream: &mut (impl AsyncWrite + Unpin),
+    message: &T,
+) -> std::io::Result<()> {
+    let bytes = serde_json::to_vec(message)?;
+    stream.write_u32(bytes.len() as u32).await?;
+    stream.write_all(&bytes).await?;
+    stream.flush().await
+}
+
+/// Read one length-prefixed JSON frame
+async fn read_frame<T: DeserializeOwned>(
+    stream: &mut (impl AsyncRead + Unpin),
+) -> std::io::Result<T> {
+    let len = stream.read_u32().await? as usize;
+    if len > MAX_FRAME {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{} byte frame is over the {} byte limit", len, MAX_FRAME),
+        ));
+    }
+    let mut bytes = vec![0; len];
+    stream.read_exact(&mut bytes).await?;
+    Ok(serde_json::from_slice(&bytes)?)
+}
+
+/// Send one DHT request to `address` and wait for the response
+async fn exchange(address: &str, request: &DhtMessage) -> Result<DhtMessage, String> {
+    let mut stream = TcpStream::connect(address).await.map_err(|e| e.to_string())?;
+    write_frame(&mut stream, request).await.map_err(|e| e.to_string())?;
+    read_frame(&mut stream).await.map_err(|e| e.to_string())
+}
+
+/// Counts a peer as answering if it returns a DHT PONG, which names its node ID
+/// and lookup key
+struct DhtProber {
+    local_id: DhtKey,
+    latencies: Arc<Latencies>,
+    lookup_keys: std::sync::Mutex<HashMap<DhtKey, [u8; 32]>>,
+}
+
+#[async_trait]
+impl PeerProber for DhtProber {
+    async fn ping(&self, address: &str, _expected: Option<DhtKey>) -> Result<DhtKey, String> {
+        let started = Instant::now();
+        let response = exchange(address, &DhtMessage::new_ping(self.local_id)).await?;
+        self.latencies.record(started.elapsed());
+        match response {
+            DhtMessage::Pong { sender_id, lookup_key, .. } => {
+                if let Some(lookup_key) = lookup_key {
+                    let mut lookup_keys =
+                        self.lookup_keys.lock().unwrap_or_else(|e| e.into_inner());
+                    lookup_keys.insert(sender_id, lookup_key);
+                }
+                Ok(sender_id)
+            }
+            other => Err(format!("Expected a Pong, got {}", other.message_type())),
+        }
+    }
+
+    fn lookup_key(&self, id: &DhtKey) -> Option<[u8; 32]> {
+        self.lookup_keys.lock().unwrap_or_else(|e| e.into_inner()).get(id).copied()
+    }
+}
+
+/// Sends DHT requests to peers at their routing table address, timing each
+struct TcpTransport {
+    routing_table: Arc<Mutex<RoutingTable>>,
+    latencies: Arc<Latencies>,
+}
+
+#[async_trait]
+impl DhtTransport for TcpTransport {
+    async fn request(&self, peer: DhtKey, request: DhtMessage) -> Result<DhtMessage, String> {
+        let contact = self.routing_table.lock().await.get(&peer);
+        let address = contact.ok_or_else(|| format!("No address for peer {}", peer))?.address;
+        let started = Instant::now();
+        let response = exchange(&address, &request).await?;
+        let rtt = started.elapsed();
+        self.routing_table.lock().await.record_rtt(&peer, rtt);
+        self.latencies.record(rtt);
+        Ok(response)
+    }
+}
+
+/// A DHT node as far as the `dht` commands are concerned
+struct Node {
+    local_id: DhtKey,
+    client: DhtClient,
+    routing_table: Arc<Mutex<RoutingTable>>,
+    /// Values held for others; transient nodes hold none
+    storage: Option<DhtStorage>,
+    /// Answers and relays private lookups, and makes this node's own
+    private_lookup: Arc<PrivateLookup>,
+    latencies: Arc<Latencies>,
+}
+
+impl Node {
+    /// A node reachable at `address` that bootstraps through `store` and config.toml
+    fn new(
+        store: LocalStore,
+        config: &Config,
+        node_id: &str,
+        address: String,
+        storage: Option<DhtStorage>,
+    ) -> (Arc<PeerBootstrap>, Self) {
+        let dht_config = dht_config(config);
+        let local_id = DhtKey::hash_string(node_id);
+        let routing_table =
+            Arc::new(Mutex::new(RoutingTable::new(local_id, config.dht.bucket_size)));
+        let latencies = Arc::new(Latencies::default());
+        let bootstrap_peers = config.dht.bootstrap_peers.iter().map(|a| a.to_string()).collect();
+        let prober =
+            DhtProber { local_id, latencies: latencies.clone(), lookup_keys: Default::default() };
+        let peers = Arc::new(
+            PeerBootstrap::new(
+                routing_table.clone(),
+                Arc::new(store),
+                Arc::new(prober),
+                bootstrap_peers,
+            )
+            .with_rpc_timeout(PING_TIMEOUT),
+        );
+
+        let transport = Arc::new(TcpTransport {
+            routing_table: routing_table.clone(),
+            latencies: latencies.clone(),
+        });
+        let private_lookup = Arc::new(PrivateLookup::new(
+            local_id,
+            dht_config,
+            storage.clone().unwrap_or_default(),
+            routing_table.clone(),
+            Arc::new(DhtLookupTransport::new(local_id, transport.clone())),
+        ));
+        let (router, _router_task) = RouterHandle::new();
+        let client =
+            DhtClient::new(local_id, Arc::new(router), routing_table.clone(), PING_TIMEOUT)
+                .with_transport(transport)
+                .with_address(address)
+                .with_private_lookup(private_lookup.clone());
+        let node = Node { local_id, client, routing_table, storage, private_lookup, latencies };
+        (peers, node)
+    }
+
+    async fn handle(&self, request: ControlRequest) -> ControlResponse {
+        let result = match request {
+            ControlRequest::Put { key, value, ttl_secs } => self
+                .put(&key, value, Duration::from_secs(ttl_secs))
+                .await
+                .map(ControlResponse::Stored),
+            ControlRequest::Get { key } => self.get(&key).await.map(ControlResponse::Value),
+            ControlRequest::Peers => Ok(ControlResponse::Peers(self.peers().await)),
+            ControlRequest::Stats => Ok(ControlResponse::Stats(self.stats().await)),
+        };
+        result.unwrap_or_else(ControlResponse::Error)
+    }
+
+    /// Store on this node, if it holds values, and the closest peers
+    async fn put(&self, key: &str, value: String, ttl: Duration) -> Result<Stored, String> {
+        let dht_key = DhtKey::hash_string(key);
+        // Later puts of a key win over earlier ones
+        let sequence =
+            SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+        let value = DhtValue::new(value.into_bytes()).with_sequence(sequence);
+
+        let mut stored_on = Vec::new();
+        if let Some(storage) = &self.storage {
+            storage.put(dht_key, value.clone().with_ttl_duration(ttl))?;
+            stored_on.push(self.local_id.to_string());
+        }
+        match self.client.put(dht_key, value, ttl).await {
+            Ok(peers) => stored_on.extend(peers.iter().map(ToString::to_string)),
+            Err(e) if stored_on.is_empty() => return Err(e),
+            Err(e) => debug!("Stored locally only: {}", e),
+        }
+        Ok(Stored { key: dht_key.to_string(), stored_on })
+    }
+
+    /// Look in this node's storage, then ask the closest peers
+    async fn get(&self, key: &str) -> Result<Option<Found>, String> {
+        let dht_key = DhtKey::hash_string(key);
+        let namespace = key.split_once(':').map_or("", |(namespace, _)| namespace);
+        let local = self.storage.as_ref().and_then(|storage| storage.get(&dht_key).ok());
+        let value = match local {
+            Some(value) => Some(value),
+            None => match self.client.get_in(namespace, dht_key, ConsistencyLevel::Quorum).await {
+                Ok(value) => value,
+                // With nobody to ask, this node's storage is the whole DHT
+                Err(e) if self.storage.is_some() => {
+                    debug!("Lookup failed: {}", e);
+                    None
+                }
+                Err(e) => return Err(e),
+            },
+        };
+        Ok(value.map(|value| Found {
+            key: dht_key.to_string(),
+            value: String::from_utf8_lossy(&value.data).into_owned(),
+            sequence: value.sequence,
+            expires_in_secs: value.time_remaining(),
+        }))
+    }
+
+    /// The routing table, best reputation first
+    async fn peers(&self) -> Vec<Peer> {
+        let table = self.routing_table.lock().await;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let mut known: Vec<Peer> = table
+            .all_peers()
+            .into_iter()
+            .map(|peer| Peer {
+                reputation: table.reputation(&peer.id).unwrap_or(0.5),
+                quarantined_secs: peer.reputation.quarantine_remaining().map(|d| d.as_secs()),
+                id: peer.id.to_string(),
+                address: peer.address,
+                last_seen_secs_ago: now.saturating_sub(peer.last_seen),
+                rtt_ms: peer.rtt.map(|rtt| rtt.as_secs_f64() * 1000.0),
+                reliability: peer.reliability,
+                failed_rpcs: peer.failed_rpcs,
+            })
+            .collect();
+        known.sort_by(|a, b| b.reputation.total_cmp(&a.reputation));
+        known
+    }
+
+    async fn stats(&self) -> Stats {
+        Stats {
+            node_id: self.local_id.to_string(),
+            peers: self.routing_table.lock().await.size(),
+            storage: self
+                .storage
+                .as_ref()
+                .and_then(|storage| storage.usage_report().ok())
+                .map(StorageStats::from),
+            rpc_latency: self.latencies.summary(),
+        }
+    }
+}
+
+/// Run `request` through the serving node's control socket, or a transient node
+pub async fn run(
+    store: LocalStore,
+    config: &Config,
+    node_id: &str,
+    data_dir: &Path,
+    request: ControlRequest,
+    json: bool,
+) -> Result<()> {
+    let socket = data_dir.join(CONTROL_SOCKET);
+    let response = match UnixStream::connect(&socket).await {
+        Ok(mut stream) => {
+            debug!("Attached to the node serving {:?}", socket);
+            write_frame(&mut stream, &request).await?;
+            read_frame(&mut stream).await.context("Serving node hung up")?
+        }
+        Err(e) => {
+            if socket.exists() {
+                warn!("Control socket {:?} isn't answering ({}), starting a node", socket, e);
+            }
+            let (peers, node) = Node::new(store, config, node_id, String::new(), None);
+            let summary = peers.bootstrap().await;
+            debug!("Bootstrapped: {} of {} peers answered", summary.responded, summary.contacted);
+            let response = node.handle(request).await;
+            peers.persist().await?;
+            response
+        }
+    };
+    print_response(response, json)
+}
+
+/// Serve the DHT to peers on `listen_addr` and the `dht` commands on the control socket
+pub async fn serve(
+    store: LocalStore,
+    config: &Config,
+    node_id: &str,
+    data_dir: &Path,
+    listen_addr: &str,
+) -> Result<()> {
+    let shutdown = Arc::new(ShutdownCoordinator::new(Duration::from_secs(1)));
+    install_signal_handlers(shutdown.clone());
+    let mut shutdown_rx = shutdown.subscribe();
+
+    let listener = TcpListener::bind(listen_addr)
+        .await
+        .with_context(|| format!("Failed to listen on {}", listen_addr))?;
+    let address = listener.local_addr()?.to_string();
+    let socket = data_dir.join(CONTROL_SOCKET);
+    if UnixStream::connect(&socket).await.is_ok() {
+        anyhow::bail!("Another node is already serving this data directory");
+    }
+    let _ = std::fs::remove_file(&socket);
+    let control = UnixListener::bind(&socket)
+        .with_context(|| format!("Failed to bind control socket {:?}", socket))?;
+
+    let dht_config = dht_config(config);
+    let storage = DhtStorage::new()
+        .with_max_ttl(dht_config.value_expiration)
+        .with_quota(dht_config.storage_quota);
+    let (peers, node) = Node::new(store, config, node_id, address.clone(), Some(storage.clone()));
+    let summary = peers.bootstrap().await;
+
+    let server = dht_server(&node, &dht_config, storage.clone());
+    let node = Arc::new(node);
+
+    let sweep_interval = dht_config.sweep_interval;
+    let tasks = [
+        tokio::spawn(serve_peers(listener, server.clone())),
+        tokio::spawn(serve_control(control, node.clone())),
+        server.spawn_provider_sweeper(),
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(sweep_interval);
+            loop {
+                tick.tick().await;
+                if let Err(e) = storage.cleanup_expired() {
+                    warn!("Failed to sweep expired DHT values: {}", e);
+                }
+            }
+        }),
+        peers.spawn_persist(Duration::from_secs(300)),
+    ];
+
+    println!("🌐 Serving the DHT as {}", node.local_id);
+    println!("   Accepting peers on {}", address);
+    println!(
+        "   Joined through {} of {} peers contacted",
+        summary.responded, summary.contacted
+    );
+    println!("   Press Ctrl+C to stop.");
+    let _ = shutdown_rx.recv().await;
+
+    for task in tasks {
+        task.abort();
+    }
+    let _ = std::fs::remove_file(&socket);
+    peers.persist().await?;
+    println!("\nStopped serving.");
+    Ok(())
+}
+
+/// DHT settings from config.toml
+fn dht_config(config: &Config) -> DhtConfig {
+    let mut dht_config = DhtConfig::default().with_bucket_size(config.dht.bucket_size);
+    for (namespace, relay_hops) in &config.dht.private_lookups {
+        dht_config =
+            dht_config.with_lookup_mode(namespace, LookupMode::Private { relay_hops: *relay_hops });
+    }
+    dht_config
+}
+
+/// A server answering peers from `node`'s routing table and `storage`
+fn dht_server(node: &Node, dht_config: &DhtConfig, storage: DhtStorage) -> Arc<DhtServer> {
+    let (router, _router_task) = RouterHandle::new();
+    let (event_tx, _) = mpsc::channel(1);
+    Arc::new(
+        DhtServer::new(
+            node.local_id,
+            dht_config.clone(),
+            Arc::new(router),
+            storage,
+            node.routing_table.clone(),
+            event_tx,
+        )
+        .with_private_lookup(node.private_lookup.clone()),
+    )
+}
+
+/// Answer DHT requests from peers, any number per connection
+async fn serve_peers(listener: TcpListener, server: Arc<DhtServer>) {
+    loop {
+        let mut stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                warn!("Failed to accept a DHT peer: {}", e);
+                continue;
+            }
+        };
+        let server = server.clone();
+        tokio::spawn(async move {
+            while let Ok(request) = read_frame::<DhtMessage>(&mut stream).await {
+                let from = request.sender_id();
+                let Some(response) = server.respond(from, request).await else {
+                    break;
+                };
+                if write_frame(&mut stream, &response).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+/// Answer `dht` commands run against this node
+async fn serve_control(listener: UnixListener, node: Arc<Node>) {
+    loop {
+        let mut stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                warn!("Failed to accept a control connection: {}", e);
+                continue;
+            }
+        };
+        let node = node.clone();
+        tokio::spawn(async move {
+            if let Ok(request) = read_frame::<ControlRequest>(&mut stream).await {
+                let response = node.handle(request).await;
+                let _ = write_frame(&mut stream, &response).await;
+            }
+        });
+    }
+}
+
+/// Print a response for people, or as JSON for scripts
+///
+/// A failed request, or a key with no value, makes the command fail too.
+fn print_response(response: ControlResponse, json: bool) -> Result<()> {
+    match response {
+        ControlResponse::Error(e) => anyhow::bail!(e),
+        ControlResponse::Stored(stored) if json => print_json(&stored)?,
+        ControlResponse::Stored(stored) => {
+            println!("✅ Stored {} on {} node(s)", stored.key, stored.stored_on.len());
+            for id in &stored.stored_on {
+                println!("   {}", id);
+            }
+        }
+        ControlResponse::Value(found) => {
+            match (&found, json) {
+                (_, true) => print_json(&found)?,
+                (Some(found), false) => println!("{}", found.value),
+                (None, false) => {}
+            }
+            if found.is_none() {
+                anyhow::bail!("No value found");
+            }
+        }
+        ControlResponse::Peers(peers) if json => print_json(&peers)?,
+        ControlResponse::Peers(peers) => {
+            println!("🌐 {} peer(s)", peers.len());
+            for peer in &peers {
+                let rtt = peer.rtt_ms.map(|ms| format!("{:.0}ms", ms));
+                let quarantined = peer
+                    .quarantined_secs
+                    .map(|secs| format!(", quarantined for {}", ago(secs)))
+                    .unwrap_or_default();
+                println!(
+                    "   {}  {}  seen {} ago, rtt {}, reliability {:.2}, reputation {:.2}{}{}",
+                    peer.id,
+                    peer.address,
+                    ago(peer.last_seen_secs_ago),
+                    rtt.as_deref().unwrap_or("-"),
+                    peer.reliability,
+                    peer.reputation,
+                    if peer.failed_rpcs > 0 {
+                        ", not answering"
+                    } else {
+                        ""
+                    },
+                    quarantined
+                );
+            }
+        }
+        ControlResponse::Stats(stats) if json => print_json(&stats)?,
+        ControlResponse::Stats(stats) => print_stats(&stats),
+    }
+    Ok(())
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+fn print_stats(stats: &Stats) {
+    println!("📊 DHT node {}", stats.node_id);
+    println!("   Peers: {}", stats.peers);
+    match &stats.storage {
+        Some(storage) => {
+            println!(
+                "   Storage: {} record(s), {} of {} bytes",
+                storage.records, storage.bytes, storage.max_bytes
+            );
+            println!("   Evicted {}, refused {} over quota", storage.evictions, storage.rejections);
+            for publisher in storage.publishers.iter().take(5) {
+                println!(
+                    "     {}  {} record(s), {} bytes",
+                    publisher.id, publisher.records, publisher.bytes
+                );
+            }
+        }
+        None => println!("   Storage: none (transient node)"),
+    }
+    let latency = &stats.rpc_latency;
+    match (latency.p50_ms, latency.p95_ms, latency.max_ms) {
+        (Some(p50), Some(p95), Some(max)) => println!(
+            "   RPC latency over {} call(s): p50 {:.0}ms, p95 {:.0}ms, max {:.0}ms",
+            latency.samples, p50, p95, max
+        ),
+        _ => println!("   RPC latency: no calls yet"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spacepanda_core::core_store::store::local_store::LocalStoreConfig;
+    use tempfile::TempDir;
+
+    fn store(dir: &TempDir, name: &str) -> LocalStore {
+        LocalStore::new(LocalStoreConfig {
+            data_dir: dir.path().join(name),
+            enable_encryption: false,
+            snapshot_interval: 1000,
+            max_log_size: 10_000_000,
+            enable_compaction: false,
+            enable_wal: true,
+            require_signatures: false,
+            authorized_keys: Vec::new(),
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_transient_node_puts_and_gets_through_a_served_node() {
+        let dir = TempDir::new().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let storage = DhtStorage::new();
+        let config = Config::default();
+        let (_, served) =
+            Node::new(store(&dir, "served"), &config, "served", address.to_string(), None);
+        let server = dht_server(&served, &DhtConfig::default(), storage.clone());
+        tokio::spawn(serve_peers(listener, server));
+
+        let mut config = Config::default();
+        config.dht.bootstrap_peers = vec![address];
+        let (peers, transient) =
+            Node::new(store(&dir, "transient"), &config, "transient", String::new(), None);
+        let summary = peers.bootstrap().await;
+        assert_eq!(summary.responded, 1);
+
+        // The bootstrap PONG keyed the peer by its real node ID
+        let put = ControlRequest::Put { key: "k".into(), value: "v".into(), ttl_secs: 60 };
+        let ControlResponse::Stored(stored) = transient.handle(put).await else {
+            panic!("put failed");
+        };
+        assert_eq!(stored.stored_on, vec![served.local_id.to_string()]);
+        assert_eq!(storage.size().unwrap(), 1);
+
+        let ControlResponse::Value(Some(found)) =
+            transient.handle(ControlRequest::Get { key: "k".into() }).await
+        else {
+            panic!("get failed");
+        };
+        assert_eq!(found.value, "v");
+        assert!(matches!(
+            transient.handle(ControlRequest::Get { key: "missing".into() }).await,
+            ControlResponse::Value(None)
+        ));
+
+        let ControlResponse::Peers(known) = transient.handle(ControlRequest::Peers).await else {
+            panic!("peers failed");
+        };
+        assert_eq!(known.len(), 1);
+        assert_eq!(known[0].address, address.to_string());
+        assert!(known[0].rtt_ms.is_some());
+
+        let ControlResponse::Stats(stats) = transient.handle(ControlRequest::Stats).await else {
+            panic!("stats failed");
+        };
+        assert!(stats.storage.is_none());
+        // The bootstrap ping, the store and two lookups
+        assert_eq!(stats.rpc_latency.samples, 4);
+    }
+}
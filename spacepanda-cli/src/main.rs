@@ -1,17 +1,59 @@
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use spacepanda_core::{
-    config::Config,
-    core_mls::service::MlsService,
-    core_store::store::local_store::{LocalStore, LocalStoreConfig},
+    config::{Config, StoreConfig},
+    core_dht::DhtStorage,
+    core_identity::{
+        keystore::{file_keystore::FileKeystore, Keystore, KeystoreError},
+        master_key::MasterKey,
+        KeyType, Keypair,
+    },
+    core_mls::{channel_metadata::RetentionPolicy, service::MlsService},
+    core_mvp::{
+        account_export::{self, AccountImporter, ExportManifest, ExportSection},
+        acks::ACK_INTERVAL, delivery::DELIVERY_REPORT_FILE, device_link::DEFAULT_LINK_KEY_PACKAGES,
+        ephemeral::PRESENCE_INTERVAL,
+        network::{NetworkLayer, NETWORK_STATE_FILE},
+        notifications::DIGEST_STATE_FILE,
+        AttachmentManifest, AttachmentStore, ChannelEventKind, ChatMessage, DeliveryReport,
+        DeliverySummary, DeliveryTracker, DhtMailbox, Notification, NotificationRules,
+        TranscriptFormat, TranscriptOptions, WebhookSink,
+    },
+    core_router::{PeerId, RouterHandle},
+    core_space::{archive, ChannelId as SpaceChannelId},
+    core_store::{
+        backup::{RemoteBackup, S3Target},
+        model::types::Timestamp,
+        query::persistent_index::{PersistentSearchIndex, DEFAULT_SEGMENT_SIZE},
+        store::{
+            attachment_cache::AttachmentCache,
+            encryption::StoreKey,
+            local_store::{LocalStore, LocalStoreConfig},
+            preflight::StoragePreflight,
+            DeliveryState,
+        },
+        sync::{
+            serve_sync, spawn_anti_entropy, AntiEntropyConfig, AntiEntropyManager, CausalStability,
+            DeltaStateConfig, StabilityConfig, SyncDeltas, SyncReplicas, STABILITY_FILE,
+            SYNC_METHOD,
+        },
+    },
     logging::{init_logging_with_config, LogConfig, LogLevel},
-    shutdown::ShutdownCoordinator,
+    metrics::{MetricsHistory, MetricsService},
+    shutdown::{install_signal_handlers, ShutdownCoordinator},
     ChannelManager, Identity,
 };
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::{debug, info, warn};
 
+mod attestation;
+mod device;
+mod dht;
+mod passphrase;
+mod profile;
+
 #[derive(Parser, Debug)]
 #[command(name = "spacepanda")]
 #[command(author, version, about = "Privacy-first encrypted chat", long_about = None)]
@@ -28,6 +70,18 @@ struct Args {
     #[arg(short, long, default_value = "~/.spacepanda")]
     data_dir: String,
 
+    /// Use the named profile in <data-dir>/profiles/<name>
+    #[arg(short, long)]
+    profile: Option<String>,
+
+    /// Read the keystore passphrase from this file instead of prompting
+    #[arg(long, global = true)]
+    passphrase_file: Option<PathBuf>,
+
+    /// Start even if the data directory fails its integrity attestation
+    #[arg(long, global = true)]
+    accept_rollback: bool,
+
     /// Subcommand to execute
     #[command(subcommand)]
     command: Command,
@@ -55,10 +109,428 @@ enum Command {
         message: String,
     },
 
+    /// Edit a message you sent
+    Edit {
+        /// Channel the message is in
+        channel_id: String,
+
+        /// ID of the message (shown by `history`)
+        message_id: String,
+
+        /// New message text
+        message: String,
+    },
+
+    /// Delete a message (your own, or anyone's as a channel admin)
+    Delete {
+        /// Channel the message is in
+        channel_id: String,
+
+        /// ID of the message (shown by `history`)
+        message_id: String,
+    },
+
+    /// Send a file, encrypted in chunks stored in the DHT
+    SendFile {
+        /// Channel ID to send to
+        channel_id: String,
+
+        /// File to send
+        path: PathBuf,
+    },
+
+    /// Download a file sent to a channel
+    Download {
+        /// Channel the file was sent to
+        channel_id: String,
+
+        /// ID of the message with the file (shown by `history`)
+        message_id: String,
+
+        /// Where to save the file
+        dest: PathBuf,
+    },
+
+    /// Show message history for a channel
+    History {
+        /// Channel ID to read
+        channel_id: String,
+
+        /// Maximum number of messages to show
+        #[arg(short = 'n', long, default_value_t = 50)]
+        limit: usize,
+
+        /// Only show messages older than this message ID
+        #[arg(long)]
+        before: Option<String>,
+    },
+
+    /// List sent messages still waiting to reach the network
+    Outbox,
+
     /// Listen for incoming messages (interactive mode)
     Listen {
         /// Channel ID to listen on
         channel_id: String,
+
+        /// Address to accept peer connections on
+        #[arg(long, default_value = "0.0.0.0:7100")]
+        listen_addr: String,
+
+        /// Peer address to connect to (may be repeated)
+        #[arg(long = "peer")]
+        peers: Vec<String>,
+    },
+
+    /// Key packages for receiving invites
+    #[command(subcommand)]
+    Keypackage(KeyPackageCommand),
+
+    /// Inspect Space archives
+    #[command(subcommand)]
+    Archive(ArchiveCommand),
+
+    /// Manage secrets referenced from config.toml as "keystore:<name>"
+    #[command(subcommand)]
+    Secret(SecretCommand),
+
+    /// Inspect and manage local storage
+    #[command(subcommand)]
+    Storage(StorageCommand),
+
+    /// Manage at-rest encryption of the local store
+    #[command(subcommand)]
+    Store(StoreCommand),
+
+    /// Back up the data directory
+    #[command(subcommand)]
+    Backup(BackupCommand),
+
+    /// Write the whole account to an encrypted file, to move it to another machine
+    Export {
+        /// File to write
+        #[arg(long)]
+        out: PathBuf,
+
+        /// Passphrase to seal the export with (prompted for if not given here
+        /// or in SPACEPANDA_BACKUP_PASSPHRASE)
+        #[arg(long)]
+        passphrase: Option<String>,
+    },
+
+    /// Restore an account export into an empty data directory
+    Import {
+        /// Export file written by `spacepanda export`
+        file: PathBuf,
+
+        /// Passphrase the export was sealed with
+        #[arg(long)]
+        passphrase: Option<String>,
+
+        /// Import even if the free disk space check fails
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Manage the local message search index
+    #[command(subcommand)]
+    Search(SearchCommand),
+
+    /// Show locally recorded statistics
+    #[command(subcommand)]
+    Stats(StatsCommand),
+
+    /// Manage named profiles (separate identities on one machine)
+    #[command(subcommand)]
+    Profile(ProfileCommand),
+
+    /// Manage the identity keystore
+    #[command(subcommand)]
+    Identity(IdentityCommand),
+
+    /// Link more devices to your identity
+    #[command(subcommand)]
+    Device(DeviceCommand),
+
+    /// Tools for investigating sync problems
+    #[command(subcommand)]
+    Debug(DebugCommand),
+
+    /// Inspect and debug the DHT
+    #[command(subcommand)]
+    Dht(DhtCommand),
+}
+
+#[derive(Subcommand, Debug)]
+enum DhtCommand {
+    /// Bootstrap from remembered and configured peers and show the result
+    Status,
+
+    /// Store a text value under a key
+    Put {
+        /// Key, hashed into a DHT key
+        key: String,
+
+        /// Value to store
+        value: String,
+
+        /// How long peers keep the value, in seconds
+        #[arg(long, default_value_t = 3600)]
+        ttl: u64,
+
+        /// Print the result as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Look up the value under a key
+    Get {
+        /// Key, hashed into a DHT key
+        key: String,
+
+        /// Print the result as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// List the routing table with last-seen times and round-trip times
+    Peers {
+        /// Print the result as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Show storage usage and RPC latencies
+    Stats {
+        /// Print the result as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Run a DHT node that peers and the other `dht` commands can reach
+    Serve {
+        /// Address to accept DHT peers on
+        #[arg(long, default_value = "0.0.0.0:7200")]
+        listen_addr: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum DebugCommand {
+    /// Export a channel's operation log as JSON Lines
+    ExportOplog {
+        /// Channel ID
+        channel_id: String,
+
+        /// File to write
+        #[arg(short, long)]
+        out: PathBuf,
+
+        /// Include operation payloads (message bodies included) instead of only their hashes
+        #[arg(long)]
+        payloads: bool,
+    },
+
+    /// Compare two exported operation logs of the same channel
+    DiffOplog {
+        /// First export
+        a: PathBuf,
+
+        /// Second export
+        b: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum DeviceCommand {
+    /// On a new device: ask to be linked to an existing identity
+    LinkRequest {
+        /// User ID to link to (shown by 'init' on the existing device)
+        user_id: String,
+
+        /// Name of this device
+        #[arg(short, long, default_value = "New device")]
+        name: String,
+
+        /// Key packages to include; the device can join this many channels
+        #[arg(long, default_value_t = DEFAULT_LINK_KEY_PACKAGES)]
+        key_packages: usize,
+    },
+
+    /// On the device holding the identity key: approve a link request
+    Approve {
+        /// Link request code printed by 'device link-request'
+        code: String,
+    },
+
+    /// On the device holding the identity key: cut off a lost or stolen device
+    Revoke {
+        /// Device ID printed by 'device approve'
+        device_id: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum IdentityCommand {
+    /// Re-encrypt the keystore under a new passphrase
+    ChangePassphrase {
+        /// Read the new passphrase from this file instead of prompting
+        #[arg(long)]
+        new_passphrase_file: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ProfileCommand {
+    /// List profiles and their identities
+    List,
+
+    /// Create a profile with a new identity
+    Create {
+        /// Profile name (letters, digits, '-', '_')
+        profile: String,
+
+        /// Display name for the profile's identity
+        #[arg(short, long)]
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum KeyPackageCommand {
+    /// Generate a fresh key package and print it as base64
+    Export,
+}
+
+#[derive(Subcommand, Debug)]
+enum BackupCommand {
+    /// Encrypted backups on the remote configured in [store.remote_backup]
+    #[command(subcommand)]
+    Remote(RemoteBackupCommand),
+}
+
+#[derive(Subcommand, Debug)]
+enum RemoteBackupCommand {
+    /// Upload a new snapshot and apply the retention policy
+    Push,
+
+    /// Restore a snapshot into an empty directory
+    Pull {
+        /// Snapshot ID to restore (defaults to the latest)
+        #[arg(long)]
+        snapshot: Option<String>,
+
+        /// Directory to restore into
+        #[arg(long)]
+        into: PathBuf,
+
+        /// Restore even if the free disk space check fails
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// List snapshots on the remote
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+enum StorageCommand {
+    /// Show attachment cache usage and pinned items
+    Attachments {
+        /// Evict all unpinned attachments (re-downloaded on next access)
+        #[arg(long)]
+        evict: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum StoreCommand {
+    /// Encrypt an existing unencrypted store in place (one-time migration)
+    Encrypt,
+
+    /// Copy MLS groups and keys from an older storage backend
+    Migrate {
+        /// Backend to migrate to
+        #[arg(long, value_enum)]
+        to: MigrateTarget,
+
+        /// Directory of the file store to migrate from
+        #[arg(long)]
+        from: PathBuf,
+
+        /// File holding the password the file store is encrypted with
+        #[arg(long)]
+        password_file: Option<PathBuf>,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum MigrateTarget {
+    /// The SQLite database the MLS service uses
+    Sql,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum TranscriptFormatArg {
+    /// One JSON document, for other tools
+    Json,
+    /// Markdown, for reading
+    Md,
+}
+
+#[derive(Subcommand, Debug)]
+enum SearchCommand {
+    /// Rebuild the search index from the full message history
+    Rebuild,
+}
+
+#[derive(Subcommand, Debug)]
+enum StatsCommand {
+    /// System metrics history recorded while 'listen' runs
+    System {
+        /// How far back to show (e.g. 1h, 24h, 7d)
+        #[arg(long, default_value = "24h", value_parser = parse_duration)]
+        last: Duration,
+    },
+
+    /// Delivery latency percentiles and SLO state recorded while 'listen' runs
+    Delivery {
+        /// Channel ID
+        channel_id: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ArchiveCommand {
+    /// Verify an archive's integrity (no keys required)
+    Verify {
+        /// Path to the archive file
+        file: PathBuf,
+    },
+
+    /// Extract a single channel into a new archive
+    Extract {
+        /// Path to the archive file
+        file: PathBuf,
+
+        /// Hex-encoded channel ID to extract
+        channel_id: String,
+
+        /// Output archive path
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SecretCommand {
+    /// Store a secret in the local keystore
+    Set {
+        /// Secret name (letters, digits, '_', '-', '.')
+        name: String,
+
+        /// Secret value (read from stdin if omitted)
+        value: Option<String>,
     },
 }
 
@@ -77,17 +549,155 @@ enum ChannelCommand {
     /// Join a channel from an invite code
     Join {
         /// Base64-encoded invite token
-        invite: String,
+        #[arg(required_unless_present = "offline")]
+        invite: Option<String>,
+
+        /// Join from an offline invite file instead, without any network
+        /// (passphrase from SPACEPANDA_INVITE_PASSPHRASE)
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["invite", "force"])]
+        offline: Option<PathBuf>,
+
+        /// Join even if the free disk space check fails
+        #[arg(long)]
+        force: bool,
     },
 
     /// Generate an invite code for a channel
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
     Invite {
+        #[command(subcommand)]
+        action: Option<InviteCommand>,
+
         /// Channel ID to create invite for
-        channel_id: String,
+        #[arg(required = true)]
+        channel_id: Option<String>,
+
+        /// Invitee's base64 key package (from 'spacepanda keypackage export')
+        #[arg(required = true)]
+        key_package: Option<String>,
+
+        /// How long the invite stays valid (e.g. 30m, 24h, 7d)
+        #[arg(long, value_parser = parse_duration)]
+        expires: Option<Duration>,
+
+        /// Write a self-contained invite to FILE for joining without any
+        /// network (passphrase from SPACEPANDA_INVITE_PASSPHRASE)
+        #[arg(long, value_name = "FILE")]
+        offline: Option<PathBuf>,
     },
 
     /// List all your channels
     List,
+
+    /// Remove a member from a channel
+    Kick {
+        /// Channel ID to remove the member from
+        channel_id: String,
+
+        /// User ID of the member to remove
+        user_id: String,
+    },
+
+    /// Leave a channel and delete it locally
+    Leave {
+        /// Channel ID to leave
+        channel_id: String,
+    },
+
+    /// List a channel's members and their devices
+    Members {
+        /// Channel ID
+        channel_id: String,
+    },
+
+    /// Show what metadata a channel exposes, with recommendations
+    Privacy {
+        /// Channel ID
+        channel_id: String,
+    },
+
+    /// Set a channel's topic for every member (admin only)
+    SetTopic {
+        /// Channel ID
+        channel_id: String,
+
+        /// New topic (an empty string clears it)
+        topic: String,
+    },
+
+    /// Set how long members keep a channel's messages (admin only)
+    SetRetention {
+        /// Channel ID
+        channel_id: String,
+
+        /// "forever", a number of days like "7d", or of messages like "500msgs"
+        #[arg(value_parser = parse_retention)]
+        policy: RetentionPolicy,
+    },
+
+    /// Export a channel's history as a JSON or Markdown transcript
+    Export {
+        /// Channel ID
+        channel_id: String,
+
+        /// Transcript format
+        #[arg(long, value_enum, default_value = "md")]
+        format: TranscriptFormatArg,
+
+        /// File to write the transcript to
+        #[arg(long, value_name = "FILE")]
+        out: PathBuf,
+
+        /// Leave out messages with attachments
+        #[arg(long)]
+        no_attachments: bool,
+
+        /// Leave out a sender's messages and reactions (repeatable)
+        #[arg(long, value_name = "USER_ID")]
+        exclude_sender: Vec<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum InviteCommand {
+    /// Revoke an invite before it is used
+    Revoke {
+        /// Invite ID (printed when the invite was created)
+        invite_id: String,
+    },
+}
+
+/// Parse a duration like "90s", "30m", "24h" or "7d"
+fn parse_duration(value: &str) -> std::result::Result<Duration, String> {
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let amount: u64 = amount.parse().map_err(|_| format!("invalid duration '{}'", value))?;
+    let seconds = match unit {
+        "s" | "" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("unknown duration unit '{}' (use s, m, h or d)", unit)),
+    };
+    Ok(Duration::from_secs(amount * seconds))
+}
+
+/// Parse a retention policy like "forever", "7d" or "500msgs"
+fn parse_retention(value: &str) -> std::result::Result<RetentionPolicy, String> {
+    if value == "forever" {
+        return Ok(RetentionPolicy::KeepForever);
+    }
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let amount: u32 = amount.parse().map_err(|_| format!("invalid retention '{}'", value))?;
+    if amount == 0 {
+        return Err("retention must keep at least one day or message".to_string());
+    }
+    match unit {
+        "d" => Ok(RetentionPolicy::KeepDays(amount)),
+        "msgs" => Ok(RetentionPolicy::KeepMessages(amount)),
+        _ => Err(format!("unknown retention unit '{}' (use d or msgs, or 'forever')", unit)),
+    }
 }
 
 #[tokio::main]
@@ -106,9 +716,15 @@ async fn main() -> Result<()> {
 
     info!("SpacePanda CLI started");
 
-    // Expand tilde in data dir
+    // Expand tilde in data dir, then select the profile within it
     let data_dir = shellexpand::tilde(&args.data_dir).to_string();
-    let data_path = PathBuf::from(&data_dir);
+    let root_path = PathBuf::from(&data_dir);
+    let profile = args.profile.as_deref();
+    let data_path = profile::resolve(&root_path, profile)?;
+    if let Some(path) = &args.passphrase_file {
+        passphrase::set_file(path.clone());
+    }
+    attestation::set_accept_rollback(args.accept_rollback);
 
     // Execute command
     match args.command {
@@ -116,34 +732,219 @@ async fn main() -> Result<()> {
             cmd_init(&data_path, &name).await?;
         }
         Command::Channel(channel_cmd) => {
-            let manager = load_manager(&data_path).await?;
+            let manager = load_manager(&root_path, profile).await?;
             match channel_cmd {
                 ChannelCommand::Create { name, public } => {
                     cmd_channel_create(manager, &name, public).await?;
                 }
-                ChannelCommand::Join { invite } => {
-                    cmd_channel_join(manager, &invite).await?;
-                }
-                ChannelCommand::Invite { channel_id } => {
-                    cmd_channel_invite(manager, &channel_id).await?;
+                ChannelCommand::Join { invite, offline, force } => match (invite, offline) {
+                    (_, Some(file)) => cmd_channel_join_offline(manager, &file).await?,
+                    (Some(invite), None) => cmd_channel_join(manager, &invite, force).await?,
+                    (None, None) => anyhow::bail!("Usage: spacepanda channel join <invite-code>"),
+                },
+                ChannelCommand::Invite { action, channel_id, key_package, expires, offline } => {
+                    match (action, channel_id, key_package) {
+                        (Some(InviteCommand::Revoke { invite_id }), _, _) => {
+                            cmd_channel_invite_revoke(manager, &invite_id).await?;
+                        }
+                        (None, Some(channel_id), Some(key_package)) => match offline {
+                            Some(file) => {
+                                cmd_channel_invite_offline(
+                                    manager,
+                                    &channel_id,
+                                    &key_package,
+                                    expires,
+                                    &file,
+                                )
+                                .await?;
+                            }
+                            None => {
+                                cmd_channel_invite(manager, &channel_id, &key_package, expires)
+                                    .await?;
+                            }
+                        },
+                        _ => anyhow::bail!(
+                            "Usage: spacepanda channel invite <channel-id> <key-package>"
+                        ),
+                    }
                 }
                 ChannelCommand::List => {
                     cmd_channel_list(manager).await?;
                 }
+                ChannelCommand::Kick { channel_id, user_id } => {
+                    cmd_channel_kick(manager, &channel_id, &user_id).await?;
+                }
+                ChannelCommand::Leave { channel_id } => {
+                    cmd_channel_leave(manager, &channel_id).await?;
+                }
+                ChannelCommand::Members { channel_id } => {
+                    cmd_channel_members(manager, &channel_id).await?;
+                }
+                ChannelCommand::Privacy { channel_id } => {
+                    cmd_channel_privacy(manager, &channel_id).await?;
+                }
+                ChannelCommand::SetTopic { channel_id, topic } => {
+                    cmd_channel_set_topic(manager, &channel_id, &topic).await?;
+                }
+                ChannelCommand::SetRetention { channel_id, policy } => {
+                    cmd_channel_set_retention(manager, &channel_id, policy).await?;
+                }
+                ChannelCommand::Export {
+                    channel_id,
+                    format,
+                    out,
+                    no_attachments,
+                    exclude_sender,
+                } => {
+                    cmd_channel_export(
+                        manager,
+                        &channel_id,
+                        format,
+                        &out,
+                        no_attachments,
+                        &exclude_sender,
+                    )
+                    .await?;
+                }
             }
         }
         Command::Send { channel_id, message } => {
-            let manager = load_manager(&data_path).await?;
+            let manager = load_manager(&root_path, profile).await?;
             cmd_send(manager, &channel_id, &message).await?;
         }
-        Command::Listen { channel_id } => {
-            let manager = load_manager(&data_path).await?;
-            cmd_listen(manager, &channel_id).await?;
+        Command::Edit { channel_id, message_id, message } => {
+            let manager = load_manager(&root_path, profile).await?;
+            cmd_edit(manager, &channel_id, &message_id, &message).await?;
         }
-    }
-
-    info!("SpacePanda CLI finished");
-    Ok(())
+        Command::Delete { channel_id, message_id } => {
+            let manager = load_manager(&root_path, profile).await?;
+            cmd_delete(manager, &channel_id, &message_id).await?;
+        }
+        Command::SendFile { channel_id, path } => {
+            let manager = load_manager(&root_path, profile).await?;
+            cmd_send_file(manager, &channel_id, &path).await?;
+        }
+        Command::Download { channel_id, message_id, dest } => {
+            let manager = load_manager(&root_path, profile).await?;
+            cmd_download(manager, &channel_id, &message_id, &dest).await?;
+        }
+        Command::History { channel_id, limit, before } => {
+            let manager = load_manager(&root_path, profile).await?;
+            cmd_history(manager, &channel_id, limit, before.as_deref()).await?;
+        }
+        Command::Outbox => {
+            let manager = load_manager(&root_path, profile).await?;
+            cmd_outbox(manager)?;
+        }
+        Command::Listen { channel_id, listen_addr, peers } => {
+            cmd_listen(&data_path, &channel_id, &listen_addr, &peers).await?;
+        }
+        Command::Keypackage(KeyPackageCommand::Export) => {
+            let manager = load_manager(&root_path, profile).await?;
+            cmd_keypackage_export(manager).await?;
+        }
+        Command::Archive(archive_cmd) => match archive_cmd {
+            ArchiveCommand::Verify { file } => {
+                cmd_archive_verify(&file)?;
+            }
+            ArchiveCommand::Extract { file, channel_id, output } => {
+                cmd_archive_extract(&file, &channel_id, &output)?;
+            }
+        },
+        Command::Secret(secret_cmd) => match secret_cmd {
+            SecretCommand::Set { name, value } => {
+                cmd_secret_set(&data_path, &name, value)?;
+            }
+        },
+        Command::Storage(storage_cmd) => match storage_cmd {
+            StorageCommand::Attachments { evict } => {
+                cmd_storage_attachments(&data_path, evict)?;
+            }
+        },
+        Command::Store(StoreCommand::Encrypt) => {
+            cmd_store_encrypt(&data_path)?;
+        }
+        Command::Store(StoreCommand::Migrate { to: MigrateTarget::Sql, from, password_file }) => {
+            cmd_store_migrate(&data_path, &from, password_file.as_deref()).await?;
+        }
+        Command::Backup(BackupCommand::Remote(remote_cmd)) => match remote_cmd {
+            RemoteBackupCommand::Push => {
+                cmd_backup_remote_push(&data_path).await?;
+            }
+            RemoteBackupCommand::Pull { snapshot, into, force } => {
+                cmd_backup_remote_pull(&data_path, snapshot.as_deref(), &into, force).await?;
+            }
+            RemoteBackupCommand::List => {
+                cmd_backup_remote_list(&data_path).await?;
+            }
+        },
+        Command::Export { out, passphrase } => {
+            cmd_export(&root_path, profile, &out, passphrase).await?;
+        }
+        Command::Import { file, passphrase, force } => {
+            cmd_import(&data_path, &file, passphrase, force)?;
+        }
+        Command::Search(SearchCommand::Rebuild) => {
+            cmd_search_rebuild(&data_path)?;
+        }
+        Command::Stats(StatsCommand::System { last }) => {
+            cmd_stats_system(&data_path, last)?;
+        }
+        Command::Stats(StatsCommand::Delivery { channel_id }) => {
+            cmd_stats_delivery(&data_path, &channel_id)?;
+        }
+        Command::Profile(profile_cmd) => match profile_cmd {
+            ProfileCommand::List => {
+                cmd_profile_list(&root_path)?;
+            }
+            ProfileCommand::Create { profile, name } => {
+                profile::create(&root_path, &profile, &name).await?;
+            }
+        },
+        Command::Identity(IdentityCommand::ChangePassphrase { new_passphrase_file }) => {
+            cmd_identity_change_passphrase(&data_path, new_passphrase_file.as_deref())?;
+        }
+        Command::Debug(DebugCommand::ExportOplog { channel_id, out, payloads }) => {
+            cmd_debug_export_oplog(&data_path, &channel_id, &out, payloads)?;
+        }
+        Command::Debug(DebugCommand::DiffOplog { a, b }) => {
+            cmd_debug_diff_oplog(&a, &b)?;
+        }
+        Command::Dht(DhtCommand::Status) => {
+            cmd_dht_status(&data_path).await?;
+        }
+        Command::Dht(DhtCommand::Put { key, value, ttl, json }) => {
+            let request = dht::ControlRequest::Put { key, value, ttl_secs: ttl };
+            cmd_dht(&data_path, request, json).await?;
+        }
+        Command::Dht(DhtCommand::Get { key, json }) => {
+            cmd_dht(&data_path, dht::ControlRequest::Get { key }, json).await?;
+        }
+        Command::Dht(DhtCommand::Peers { json }) => {
+            cmd_dht(&data_path, dht::ControlRequest::Peers, json).await?;
+        }
+        Command::Dht(DhtCommand::Stats { json }) => {
+            cmd_dht(&data_path, dht::ControlRequest::Stats, json).await?;
+        }
+        Command::Dht(DhtCommand::Serve { listen_addr }) => {
+            let (store, config, node_id) = dht_context(&data_path)?;
+            dht::serve(store, &config, &node_id, &data_path, &listen_addr).await?;
+        }
+        Command::Device(DeviceCommand::LinkRequest { user_id, name, key_packages }) => {
+            device::link_request(&data_path, &user_id, &name, key_packages).await?;
+        }
+        Command::Device(DeviceCommand::Approve { code }) => {
+            let manager = load_manager(&root_path, profile).await?;
+            device::approve(&data_path, manager, &code).await?;
+        }
+        Command::Device(DeviceCommand::Revoke { device_id }) => {
+            let manager = load_manager(&root_path, profile).await?;
+            device::revoke(&data_path, manager, &device_id).await?;
+        }
+    }
+
+    info!("SpacePanda CLI finished");
+    Ok(())
 }
 
 /// Initialize SpacePanda (create identity and local storage)
@@ -163,7 +964,20 @@ async fn cmd_init(data_dir: &PathBuf, name: &str) -> Result<()> {
         ));
     }
 
-    // Generate new identity
+    // A leftover master key may still be needed to decrypt existing data
+    let keystore_dir = data_dir.join("keystore");
+    let existing = FileKeystore::new(keystore_dir.clone(), None)?.load_identity_keypair();
+    if !matches!(existing, Err(KeystoreError::NotFound(_))) {
+        return Err(anyhow::anyhow!(
+            "An identity master key already exists in {:?}. Remove it to reinitialize.",
+            keystore_dir
+        ));
+    }
+
+    // Generate the master key under a new passphrase before writing anything else
+    create_master_key(data_dir)?;
+
+    // Generate new identity (public details only; the keys stay in the keystore)
     let identity = Identity::new(
         spacepanda_core::core_store::model::types::UserId(uuid::Uuid::new_v4().to_string()),
         name.to_string(),
@@ -174,24 +988,18 @@ async fn cmd_init(data_dir: &PathBuf, name: &str) -> Result<()> {
     let identity_json = serde_json::to_string_pretty(&identity)?;
     std::fs::write(&identity_path, identity_json)?;
 
-    // Initialize local store
-    let store_config = LocalStoreConfig {
-        data_dir: data_dir.clone(),
-        enable_encryption: false,
-        snapshot_interval: 1000,
-        max_log_size: 10_000_000,
-        enable_compaction: false,
-        require_signatures: false,
-        authorized_keys: Vec::new(),
-    };
-
-    let _store =
-        LocalStore::new(store_config).with_context(|| "Failed to initialize local store")?;
+    // Initialize local store, encrypted at rest
+    let key = store_key(data_dir, false)?;
+    let config = store_config(data_dir, &StoreConfig::default());
+    let _store = LocalStore::open_encrypted(config, &key)
+        .with_context(|| "Failed to initialize local store")?;
 
     println!("✅ SpacePanda initialized successfully!");
     println!("   Data directory: {:?}", data_dir);
     println!("   User ID: {}", identity.user_id);
     println!("   Display name: {}", identity.display_name);
+    println!("   Keystore: passphrase-protected ({:?})", keystore_dir);
+    println!("   Local store: encrypted ({})", key_description(&key));
     println!("\nNext steps:");
     println!("  - Create a channel: spacepanda channel create <name>");
     println!("  - Join a channel: spacepanda channel join <invite-code>");
@@ -199,8 +1007,459 @@ async fn cmd_init(data_dir: &PathBuf, name: &str) -> Result<()> {
     Ok(())
 }
 
-/// Load ChannelManager from data directory
-async fn load_manager(data_dir: &PathBuf) -> Result<Arc<ChannelManager>> {
+/// Verify a Space archive offline
+fn cmd_archive_verify(file: &Path) -> Result<()> {
+    let reader = std::io::BufReader::new(
+        std::fs::File::open(file).with_context(|| format!("Failed to open {:?}", file))?,
+    );
+    let summary = archive::verify_archive(reader)
+        .with_context(|| format!("Archive {:?} failed verification", file))?;
+
+    println!("✅ Archive verified");
+    println!("   Space: {}", summary.space_id);
+    println!("   Exported at: {} ms", summary.exported_at.as_millis());
+    println!("   Channels: {}", summary.channel_count);
+    println!("   Messages: {}", summary.message_count);
+    println!("   Attachments: {}", summary.attachment_count);
+    println!("   Attachment blobs: {}", summary.blob_count);
+    println!("   SHA-256: {}", summary.manifest.sha256);
+
+    Ok(())
+}
+
+/// Extract a single channel from a Space archive
+fn cmd_archive_extract(file: &Path, channel_id: &str, output: &Path) -> Result<()> {
+    let channel_bytes: [u8; 32] = hex::decode(channel_id)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow::anyhow!("Channel ID must be 64 hex characters"))?;
+
+    let reader = std::io::BufReader::new(
+        std::fs::File::open(file).with_context(|| format!("Failed to open {:?}", file))?,
+    );
+    let mut extracted = Vec::new();
+    let summary = archive::extract_channel(
+        reader,
+        &SpaceChannelId::from_bytes(channel_bytes),
+        &mut extracted,
+    )
+    .with_context(|| format!("Failed to extract channel from {:?}", file))?;
+
+    // Only write the output once the source archive has been verified
+    std::fs::write(output, extracted).with_context(|| format!("Failed to write {:?}", output))?;
+
+    println!("✅ Extracted channel {} ({} messages)", channel_id, summary.message_count);
+    println!("   Output: {:?}", output);
+
+    Ok(())
+}
+
+/// Show attachment cache usage, optionally evicting unpinned blobs
+fn cmd_storage_attachments(data_dir: &Path, evict: bool) -> Result<()> {
+    let config = load_config(data_dir)?;
+    let cache = open_attachment_cache(data_dir, &config)?;
+
+    if evict {
+        let before = cache.usage().used_bytes;
+        let evicted = cache.evict_all().with_context(|| "Failed to evict attachments")?;
+        let freed = before.saturating_sub(cache.usage().used_bytes);
+        println!("🧹 Evicted {} attachment(s), freed {} bytes", evicted.len(), freed);
+    }
+
+    let usage = cache.usage();
+    println!("📎 Attachment cache");
+    println!("   Used: {} / {} bytes", usage.used_bytes, usage.quota_bytes);
+    println!("   Cached attachments: {}", usage.entries);
+    println!("   Awaiting delivery: {}", usage.pending_delivery);
+
+    if usage.pinned.is_empty() {
+        println!("   Pinned: none");
+    } else {
+        println!("   Pinned:");
+        for item in &usage.pinned {
+            println!(
+                "     {} ({} bytes) from message {}",
+                item.attachment.filename, item.size_bytes, item.message_id
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Open the attachment cache in the data directory
+fn open_attachment_cache(data_dir: &Path, config: &Config) -> Result<AttachmentCache> {
+    let cache = AttachmentCache::open(
+        &data_dir.join("attachments"),
+        config.store.attachment_cache_quota,
+        config.dht.replication_factor,
+    )
+    .with_context(|| "Failed to open attachment cache")?;
+    Ok(cache.with_storage_preflight(StoragePreflight::new(config.store.free_space_margin)))
+}
+
+/// Environment variable holding the backup passphrase
+const BACKUP_PASSPHRASE_ENV: &str = "SPACEPANDA_BACKUP_PASSPHRASE";
+
+/// Open the backup repository on the configured remote
+async fn open_remote_backup(data_dir: &Path) -> Result<(RemoteBackup, usize)> {
+    let config = load_config(data_dir)?;
+    let remote = config.store.remote_backup.as_ref().ok_or_else(|| {
+        anyhow::anyhow!("No remote configured; add a [store.remote_backup] section to config.toml")
+    })?;
+    let passphrase = std::env::var(BACKUP_PASSPHRASE_ENV)
+        .with_context(|| format!("Set {} to the backup passphrase", BACKUP_PASSPHRASE_ENV))?;
+
+    let target = S3Target::from_config(remote)?;
+    let backup = RemoteBackup::open(Arc::new(target), &passphrase)
+        .await
+        .with_context(|| "Failed to open remote backup")?;
+    Ok((backup, remote.keep_last))
+}
+
+/// Push a snapshot of the data directory to the remote
+async fn cmd_backup_remote_push(data_dir: &Path) -> Result<()> {
+    let (mut backup, keep_last) = open_remote_backup(data_dir).await?;
+    println!("☁️  Pushing to {}", backup.target().describe());
+
+    let report = backup
+        .push(data_dir)
+        .await
+        .with_context(|| "Push interrupted; run it again to resume (uploaded chunks are kept)")?;
+    println!("✅ Snapshot {} ({} files)", report.snapshot_id, report.files);
+    println!(
+        "   Uploaded {} chunk(s), {} bytes; {} unchanged",
+        report.chunks_uploaded, report.bytes_uploaded, report.chunks_reused
+    );
+
+    let pruned = backup.prune(keep_last).await.with_context(|| "Failed to apply retention")?;
+    if !pruned.snapshots_removed.is_empty() {
+        println!(
+            "🧹 Removed {} old snapshot(s) and {} chunk(s), keeping the last {}",
+            pruned.snapshots_removed.len(),
+            pruned.chunks_removed,
+            keep_last
+        );
+    }
+
+    Ok(())
+}
+
+/// Restore a snapshot from the remote
+async fn cmd_backup_remote_pull(
+    data_dir: &Path,
+    snapshot: Option<&str>,
+    into: &Path,
+    force: bool,
+) -> Result<()> {
+    if into.exists() && std::fs::read_dir(into)?.next().is_some() {
+        return Err(anyhow::anyhow!("Refusing to restore into non-empty directory {:?}", into));
+    }
+
+    let (mut backup, _) = open_remote_backup(data_dir).await?;
+    if !force {
+        let margin = load_config(data_dir)?.store.free_space_margin;
+        backup = backup.with_storage_preflight(StoragePreflight::new(margin));
+    }
+    let manifest = backup.pull(snapshot, into).await.with_context(|| "Restore failed")?;
+
+    println!("✅ Restored snapshot {} into {:?}", manifest.snapshot_id, into);
+    println!("   {} files, {} bytes", manifest.files.len(), manifest.total_size());
+    println!("\nUse it with:");
+    println!("  spacepanda --data-dir {} channel list", into.display());
+
+    Ok(())
+}
+
+/// List snapshots on the remote
+async fn cmd_backup_remote_list(data_dir: &Path) -> Result<()> {
+    let (backup, _) = open_remote_backup(data_dir).await?;
+    let snapshots = backup.list().await?;
+
+    if snapshots.is_empty() {
+        println!("No snapshots on {}", backup.target().describe());
+        return Ok(());
+    }
+
+    println!("☁️  Snapshots on {}:", backup.target().describe());
+    for snapshot in &snapshots {
+        println!(
+            "  {}  {} files, {} bytes",
+            snapshot.snapshot_id, snapshot.files, snapshot.total_size
+        );
+    }
+
+    Ok(())
+}
+
+/// Passphrase for an account export: given, from the environment, or prompted for
+///
+/// A new export's passphrase is prompted for twice.
+fn export_passphrase(given: Option<String>, confirm: bool) -> Result<String> {
+    if let Some(passphrase) = given.or_else(|| std::env::var(BACKUP_PASSPHRASE_ENV).ok()) {
+        if passphrase.is_empty() {
+            anyhow::bail!("Export passphrase must not be empty");
+        }
+        return Ok(passphrase);
+    }
+
+    let passphrase = rpassword::prompt_password("Export passphrase: ")
+        .with_context(|| format!("Failed to read passphrase (or set {})", BACKUP_PASSPHRASE_ENV))?;
+    if passphrase.is_empty() {
+        anyhow::bail!("Export passphrase must not be empty");
+    }
+    if confirm {
+        let confirmation = rpassword::prompt_password("Repeat passphrase: ")
+            .with_context(|| "Failed to read passphrase")?;
+        if passphrase != confirmation {
+            anyhow::bail!("Passphrases do not match");
+        }
+    }
+    Ok(passphrase)
+}
+
+/// Write the account to an encrypted export file
+async fn cmd_export(
+    root_path: &Path,
+    profile: Option<&str>,
+    out: &Path,
+    passphrase: Option<String>,
+) -> Result<()> {
+    let data_dir = profile::resolve(root_path, profile)?;
+    let manager = load_manager(root_path, profile).await?;
+
+    // Group state kept outside the data directory wouldn't be in the export
+    let config = load_config(&data_dir)?;
+    if let Some(dir) = config.store.mls_backend.as_ref().and_then(|backend| backend.dir()) {
+        let inside = match (std::fs::canonicalize(dir), std::fs::canonicalize(&data_dir)) {
+            (Ok(dir), Ok(data_dir)) => dir.starts_with(data_dir),
+            _ => false,
+        };
+        if !inside {
+            anyhow::bail!(
+                "MLS state is stored in {:?}, outside the data directory; move it under {:?} first",
+                dir,
+                data_dir
+            );
+        }
+    }
+
+    let passphrase = export_passphrase(passphrase, true)?;
+    let channels = manager.list_channels().await?;
+
+    let tmp = out.with_extension("tmp");
+    let file = std::fs::File::create(&tmp).with_context(|| format!("Failed to create {:?}", tmp))?;
+    let mut writer = std::io::BufWriter::new(file);
+    let manifest = account_export::export_account(
+        &data_dir,
+        manager.identity(),
+        channels,
+        &passphrase,
+        &mut writer,
+    );
+    let manifest = match manifest {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            drop(writer);
+            let _ = std::fs::remove_file(&tmp);
+            return Err(e).with_context(|| "Export failed");
+        }
+    };
+    writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    std::fs::rename(&tmp, out).with_context(|| format!("Failed to write {:?}", out))?;
+
+    println!("✅ Exported {} ({}) to {:?}", manifest.display_name, manifest.user_id, out);
+    print_export_summary(&manifest);
+    println!("\nImport it on the new machine with:");
+    println!("  spacepanda import {}", out.display());
+    println!("Keep the file safe: anyone with it and the passphrase can act as you.");
+
+    Ok(())
+}
+
+/// Restore an account export into the data directory
+fn cmd_import(data_dir: &Path, file: &Path, passphrase: Option<String>, force: bool) -> Result<()> {
+    let passphrase = export_passphrase(passphrase, false)?;
+    let mut importer = AccountImporter::new(passphrase);
+    if !force {
+        let margin = load_config(data_dir)?.store.free_space_margin;
+        importer = importer.with_storage_preflight(StoragePreflight::new(margin));
+    }
+
+    let export = std::fs::File::open(file).with_context(|| format!("Failed to open {:?}", file))?;
+    let manifest = importer
+        .import(&mut std::io::BufReader::new(export), data_dir)
+        .with_context(|| "Import failed")?;
+
+    println!("✅ Imported {} ({}) into {:?}", manifest.display_name, manifest.user_id, data_dir);
+    print_export_summary(&manifest);
+    println!(
+        "\n⚠️  Stop using this account on the old machine. Both copies share the same \
+         keys; if both stay active, `listen` warns that the identity is in use elsewhere."
+    );
+
+    Ok(())
+}
+
+/// Files and bytes per section of an account export
+fn print_export_summary(manifest: &ExportManifest) {
+    for (label, section) in [
+        ("Identity", ExportSection::Identity),
+        ("MLS state", ExportSection::MlsState),
+        ("Config", ExportSection::Config),
+        ("Store", ExportSection::Store),
+    ] {
+        let (files, bytes) = manifest.section_size(section);
+        println!("   {:<10} {} files, {} bytes", label, files, bytes);
+    }
+    println!("   {} channel(s)", manifest.channels.len());
+}
+
+/// Open the keystore holding the identity master key and configuration secrets
+///
+/// Asks for the passphrase when the keystore is protected.
+fn open_keystore(data_dir: &Path) -> Result<FileKeystore> {
+    let passphrase = if FileKeystore::is_password_protected(&data_dir.join("keystore")) {
+        Some(passphrase::current()?)
+    } else {
+        passphrase::provided()?
+    };
+    open_keystore_with(data_dir, passphrase.as_deref())
+}
+
+/// Open the keystore with the given passphrase
+fn open_keystore_with(data_dir: &Path, passphrase: Option<&str>) -> Result<FileKeystore> {
+    FileKeystore::new(data_dir.join("keystore"), passphrase)
+        .with_context(|| "Failed to open keystore")
+}
+
+/// Unlock the identity master key
+///
+/// A wrong passphrase fails with [`KeystoreError::InvalidPassword`].
+fn load_master_key(data_dir: &Path) -> Result<MasterKey> {
+    let keypair = open_keystore(data_dir)?.load_identity_keypair().map_err(|e| {
+        let context = match e {
+            KeystoreError::InvalidPassword => "Wrong keystore passphrase",
+            _ => "Failed to load identity master key from keystore",
+        };
+        anyhow::Error::new(e).context(context)
+    })?;
+    MasterKey::from_keypair(keypair).map_err(anyhow::Error::msg)
+}
+
+/// Generate an identity master key and save it under a new passphrase
+fn create_master_key(data_dir: &Path) -> Result<MasterKey> {
+    let passphrase = passphrase::new_keystore()?;
+    let master_key = MasterKey::generate();
+    open_keystore_with(data_dir, Some(&passphrase))?
+        .save_identity_keypair(master_key.keypair())
+        .with_context(|| "Failed to save identity master key")?;
+    Ok(master_key)
+}
+
+/// Whether an error is a keystore lookup that found nothing
+fn is_not_found(error: &anyhow::Error) -> bool {
+    matches!(error.downcast_ref(), Some(KeystoreError::NotFound(_)))
+}
+
+/// Re-encrypt the keystore under a new passphrase
+fn cmd_identity_change_passphrase(
+    data_dir: &Path,
+    new_passphrase_file: Option<&Path>,
+) -> Result<()> {
+    let protected = FileKeystore::is_password_protected(&data_dir.join("keystore"));
+
+    // Unlocking first checks the current passphrase before asking for a new one
+    load_master_key(data_dir)?;
+    let keystore = open_keystore(data_dir)?;
+
+    let new_passphrase = passphrase::choose(new_passphrase_file)?;
+    keystore
+        .rotate_master_key(&new_passphrase)
+        .with_context(|| "Failed to re-encrypt keystore")?;
+
+    println!("✅ Keystore passphrase {}", if protected { "changed" } else { "set" });
+    if std::env::var(passphrase::PASSPHRASE_ENV).is_ok() {
+        println!("   Update {} to the new passphrase", passphrase::PASSPHRASE_ENV);
+    }
+
+    Ok(())
+}
+
+/// Load config.toml from the data directory, resolving keystore references
+fn load_config(data_dir: &Path) -> Result<Config> {
+    let config_path = data_dir.join("config.toml");
+    if !config_path.exists() {
+        return Ok(Config::default());
+    }
+
+    let keystore = open_keystore(data_dir)?;
+    let config = Config::from_file_with_keystore(&config_path, &keystore)
+        .with_context(|| format!("Failed to load config from {:?}", config_path))?;
+    debug!("Loaded config: {:?}", config);
+    Ok(config)
+}
+
+/// Store a secret in the keystore
+fn cmd_secret_set(data_dir: &Path, name: &str, value: Option<String>) -> Result<()> {
+    let value = match value {
+        Some(value) => value,
+        None => {
+            let mut input = String::new();
+            std::io::stdin().read_line(&mut input)?;
+            input.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+
+    let keystore = open_keystore(data_dir)?;
+    keystore
+        .put_secret(name, value.as_bytes())
+        .with_context(|| format!("Failed to store secret '{}'", name))?;
+
+    println!("✅ Secret '{}' stored", name);
+    println!("   Reference it in config.toml as \"keystore:{}\"", name);
+
+    Ok(())
+}
+
+/// Load ChannelManager for the selected profile (or the data directory)
+///
+/// Refuses to run for a profile that hasn't been created, rather than
+/// creating empty state for it.
+async fn load_manager(data_dir: &Path, profile: Option<&str>) -> Result<Arc<ChannelManager>> {
+    let dir = profile::resolve(data_dir, profile)?;
+    profile::require_identity(&dir, profile)?;
+
+    let shutdown = Arc::new(ShutdownCoordinator::new(std::time::Duration::from_secs(30)));
+    Ok(Arc::new(build_manager(&dir, shutdown).await?))
+}
+
+/// List profiles under the data directory
+fn cmd_profile_list(data_dir: &Path) -> Result<()> {
+    let profiles = profile::list(data_dir)?;
+    if profiles.is_empty() {
+        println!("No profiles. Create one with: spacepanda profile create <name> --name <display>");
+        return Ok(());
+    }
+
+    println!("Profiles in {:?}:", data_dir.join("profiles"));
+    for entry in profiles {
+        match entry.identity {
+            Some(identity) => {
+                println!("  {:<16} {} ({})", entry.name, identity.display_name, identity.user_id)
+            }
+            None => println!("  {:<16} <no identity>", entry.name),
+        }
+    }
+
+    Ok(())
+}
+
+/// Build a ChannelManager from the data directory
+async fn build_manager(
+    data_dir: &Path,
+    shutdown: Arc<ShutdownCoordinator>,
+) -> Result<ChannelManager> {
     // Load identity
     let identity_path = data_dir.join("identity.json");
     if !identity_path.exists() {
@@ -210,14 +1469,32 @@ async fn load_manager(data_dir: &PathBuf) -> Result<Arc<ChannelManager>> {
     let identity_json = std::fs::read_to_string(&identity_path)?;
     let identity: Identity = serde_json::from_str(&identity_json)?;
 
+    // Unlock the keystore up front so a wrong passphrase fails clearly
+    match load_master_key(data_dir) {
+        // A linked device holds a device key instead
+        Err(e) if is_not_found(&e) && device::is_linked(data_dir) => {}
+        Err(e) if is_not_found(&e) => {
+            warn!("No identity master key in the keystore; run 'spacepanda store encrypt'")
+        }
+        result => {
+            result?;
+        }
+    }
+
     // Initialize services
-    let config = Arc::new(Config::default());
-    let shutdown = Arc::new(ShutdownCoordinator::new(std::time::Duration::from_secs(30)));
+    let config = Arc::new(load_config(data_dir)?);
+    if config.store.attestation {
+        attestation::attest(data_dir)?;
+    }
 
-    // Create MLS service with storage persistence
+    // Create MLS service with storage persistence, in the configured backend
+    // or else SQLite under mls_groups, sealing recovery snapshots with a key
+    // from the keystore
     let mls_storage_dir = data_dir.join("mls_groups");
+    let keystore = open_keystore(data_dir)?;
     let mls_service = Arc::new(
         MlsService::with_storage(&config, shutdown, mls_storage_dir)
+            .and_then(|service| service.with_recovery_keystore(&keystore))
             .with_context(|| "Failed to initialize MLS service with storage")?,
     );
 
@@ -234,30 +1511,438 @@ async fn load_manager(data_dir: &PathBuf) -> Result<Arc<ChannelManager>> {
         }
     }
 
-    // Initialize store
-    let store_config = LocalStoreConfig {
-        data_dir: data_dir.clone(),
-        enable_encryption: false,
+    assemble_manager(data_dir, config, mls_service, identity)
+}
+
+/// Create a ChannelManager on the data directory's store with a ready MLS service
+fn assemble_manager(
+    data_dir: &Path,
+    config: Arc<Config>,
+    mls_service: Arc<MlsService>,
+    identity: Identity,
+) -> Result<ChannelManager> {
+    let store = Arc::new(open_store(data_dir, &config.store)?);
+    attach_search_index(data_dir, &store);
+
+    let attachments = Arc::new(open_attachment_cache(data_dir, &config)?);
+    let chunks = Arc::new(AttachmentStore::new(DhtStorage::new()));
+
+    // Create manager
+    let manager = ChannelManager::new(mls_service, store, Arc::new(identity), config)
+        .with_attachment_cache(attachments)
+        .with_attachment_store(chunks);
+
+    Ok(manager)
+}
+
+/// Environment variable holding a passphrase to encrypt the store with
+const STORE_PASSPHRASE_ENV: &str = "SPACEPANDA_STORE_PASSPHRASE";
+
+/// Local store configuration for the data directory
+fn store_config(data_dir: &Path, store: &StoreConfig) -> LocalStoreConfig {
+    LocalStoreConfig {
+        data_dir: data_dir.to_path_buf(),
+        enable_encryption: true,
         snapshot_interval: 1000,
         max_log_size: 10_000_000,
-        enable_compaction: false,
+        enable_compaction: true,
+        enable_wal: store.enable_wal,
         require_signatures: false,
         authorized_keys: Vec::new(),
+    }
+}
+
+/// Key the local store is encrypted with
+///
+/// A passphrase from SPACEPANDA_STORE_PASSPHRASE if set, otherwise a key
+/// derived from the identity master key in the keystore (or, on a linked
+/// device, from its device key). With `create`, a missing master key is
+/// generated and saved under a new passphrase.
+fn store_key(data_dir: &Path, create: bool) -> Result<StoreKey> {
+    if let Ok(passphrase) = std::env::var(STORE_PASSPHRASE_ENV) {
+        return Ok(StoreKey::passphrase(&passphrase));
+    }
+
+    // A linked device has no master key; its store key comes from its device key
+    if let Some(linked) = device::load(data_dir)? {
+        let device_key = MasterKey::from_keypair(device::load_key(data_dir, &linked)?)
+            .map_err(anyhow::Error::msg)?;
+        return Ok(StoreKey::Derived(device_key.derive_store_key()));
+    }
+
+    let master_key = match load_master_key(data_dir) {
+        Err(e) if create && is_not_found(&e) => create_master_key(data_dir)?,
+        result => result?,
     };
 
-    let store = Arc::new(LocalStore::new(store_config)?);
+    Ok(StoreKey::Derived(master_key.derive_store_key()))
+}
+
+/// Short description of where a store key comes from
+fn key_description(key: &StoreKey) -> &'static str {
+    match key {
+        StoreKey::Derived(_) => "identity master key",
+        StoreKey::Passphrase(_) => STORE_PASSPHRASE_ENV,
+    }
+}
+
+/// Open the local store in the data directory and load persisted state
+fn open_store(data_dir: &Path, store: &StoreConfig) -> Result<LocalStore> {
+    let store = if LocalStore::is_encrypted(data_dir) {
+        let hint = format!("set {} if it was encrypted with a passphrase", STORE_PASSPHRASE_ENV);
+        let key = store_key(data_dir, false)
+            .with_context(|| format!("No key for the encrypted store ({})", hint))?;
+        LocalStore::open_encrypted(store_config(data_dir, store), &key).with_context(|| {
+            format!("Failed to open encrypted store with the {} ({})", key_description(&key), hint)
+        })?
+    } else {
+        warn!("Local store is not encrypted; run 'spacepanda store encrypt' to encrypt it");
+        LocalStore::new(LocalStoreConfig {
+            enable_encryption: false,
+            ..store_config(data_dir, store)
+        })?
+    };
+
+    // Load existing channel state from snapshots
+    if let Err(e) = store.load() {
+        warn!("Failed to load persisted channel state: {}", e);
+    } else {
+        debug!("Successfully loaded channel state from storage");
+    }
+
+    Ok(store)
+}
+
+/// Bootstrap the DHT and report on its peers
+async fn cmd_dht_status(data_dir: &Path) -> Result<()> {
+    let (store, config, node_id) = dht_context(data_dir)?;
+    dht::status(store, &config, &node_id).await
+}
+
+/// Run a `dht` debugging command and print the result
+async fn cmd_dht(data_dir: &Path, request: dht::ControlRequest, json: bool) -> Result<()> {
+    let (store, config, node_id) = dht_context(data_dir)?;
+    dht::run(store, &config, &node_id, data_dir, request, json).await
+}
+
+/// The store, config and node ID the `dht` commands run with
+fn dht_context(data_dir: &Path) -> Result<(LocalStore, Config, String)> {
+    let identity_path = data_dir.join("identity.json");
+    if !identity_path.exists() {
+        return Err(anyhow::anyhow!("Identity not found. Run 'spacepanda init' first."));
+    }
+    let identity: Identity = serde_json::from_str(&std::fs::read_to_string(&identity_path)?)?;
+
+    let config = load_config(data_dir)?;
+    let store = open_store(data_dir, &config.store)?;
+    Ok((store, config, identity.node_id))
+}
+
+/// Keep the on-disk search index up to date with the store
+///
+/// Catches up on messages committed since the last run. The index is
+/// derived state, so failures are logged rather than fatal.
+fn attach_search_index(data_dir: &Path, store: &LocalStore) {
+    let index = match PersistentSearchIndex::open(
+        data_dir.join("search_index"),
+        store.encryption(),
+        DEFAULT_SEGMENT_SIZE,
+    ) {
+        Ok(index) => Arc::new(index),
+        Err(e) => {
+            warn!("Failed to open search index: {}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = index.attach(store) {
+        warn!("Failed to update search index: {}", e);
+        return;
+    }
+    index.spawn_maintenance(SEARCH_MAINTENANCE_INTERVAL, SEARCH_MAX_SEGMENTS);
+}
+
+/// How often buffered search index changes are flushed
+const SEARCH_MAINTENANCE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Segment count above which the search index is merged
+const SEARCH_MAX_SEGMENTS: usize = 8;
+
+/// Encrypt an existing unencrypted store in place
+///
+/// The search index was written unencrypted too, so it is rebuilt under
+/// the store key afterwards.
+fn cmd_store_encrypt(data_dir: &Path) -> Result<()> {
+    if !data_dir.join("identity.json").exists() {
+        return Err(anyhow::anyhow!("Identity not found. Run 'spacepanda init' first."));
+    }
+    if LocalStore::is_encrypted(data_dir) {
+        println!("✅ Local store is already encrypted");
+        return Ok(());
+    }
+
+    let key = store_key(data_dir, true)?;
+    println!("🔒 Encrypting local store with the {}...", key_description(&key));
+    let config = store_config(data_dir, &StoreConfig::default());
+    let entries = LocalStore::encrypt_in_place(config, &key)
+        .with_context(|| "Failed to encrypt local store")?;
+    println!("✅ Encrypted {} log entries and the current snapshot", entries);
+
+    let index_dir = data_dir.join("search_index");
+    if index_dir.exists() {
+        std::fs::remove_dir_all(&index_dir)
+            .with_context(|| format!("Failed to remove {:?}", index_dir))?;
+    }
+    cmd_search_rebuild(data_dir)
+}
+
+/// Copy the MLS groups and keys of a file store into the SQL store
+async fn cmd_store_migrate(
+    data_dir: &Path,
+    from: &Path,
+    password_file: Option<&Path>,
+) -> Result<()> {
+    use spacepanda_core::config::StorageBackend;
+    use spacepanda_core::core_mls::storage::{
+        is_migrated, migrate_provider, FileStorageProvider, SqlStorageProvider,
+    };
+
+    if !data_dir.join("identity.json").exists() {
+        return Err(anyhow::anyhow!("Identity not found. Run 'spacepanda init' first."));
+    }
+    if !from.is_dir() {
+        return Err(anyhow::anyhow!("No file store found at {:?}", from));
+    }
+
+    let password = password_file.map(passphrase::read_file).transpose()?;
+    let src = FileStorageProvider::new(from.to_path_buf(), password.as_deref())
+        .with_context(|| format!("Failed to open file store at {:?}", from))?;
+    let db_path = match load_config(data_dir)?.store.mls_backend {
+        Some(StorageBackend::Sqlite { path }) => path,
+        _ => data_dir.join("mls_groups").join("mls_state.db"),
+    };
+    if let Some(dir) = db_path.parent() {
+        std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    }
+    let dst = SqlStorageProvider::new(&db_path)
+        .with_context(|| format!("Failed to open SQL store at {:?}", db_path))?;
+
+    if is_migrated(&src).await? {
+        println!("ℹ️  {:?} was migrated before; copying anything missing", from);
+    }
+    let report = migrate_provider(&src, &dst).await.with_context(|| "Migration failed")?;
+    if !report.is_migrated() {
+        println!("❌ The SQL store already holds different data for:");
+        for conflict in &report.conflicts {
+            println!("   {}", conflict);
+        }
+        return Err(anyhow::anyhow!("Migration aborted; nothing was written"));
+    }
+
+    println!("✅ Migrated {} group(s) and {} key blob(s) to SQL", report.groups, report.blobs);
+    Ok(())
+}
+
+/// Export a channel's operation log for comparison with another replica
+fn cmd_debug_export_oplog(
+    data_dir: &Path,
+    channel_id_str: &str,
+    out: &Path,
+    payloads: bool,
+) -> Result<()> {
+    use spacepanda_core::core_store::model::types::ChannelId;
+
+    if !data_dir.join("identity.json").exists() {
+        return Err(anyhow::anyhow!("Identity not found. Run 'spacepanda init' first."));
+    }
+
+    let store = open_store(data_dir, &StoreConfig::default())?;
+    let channel_id = ChannelId(channel_id_str.to_string());
+    let file = std::fs::File::create(out).with_context(|| format!("Failed to create {:?}", out))?;
+    let count = store
+        .export_oplog(&channel_id, payloads, std::io::BufWriter::new(file))
+        .with_context(|| format!("Failed to export the oplog of {}", channel_id_str))?;
+
+    println!("✅ Exported {} operation(s) to {:?}", count, out);
+    if !payloads {
+        println!("   Payloads are redacted to hashes; use --payloads to include them");
+    }
+
+    Ok(())
+}
+
+/// Report how two exported operation logs differ
+fn cmd_debug_diff_oplog(a: &Path, b: &Path) -> Result<()> {
+    use spacepanda_core::core_store::store::{OplogDiffer, OplogFinding, OplogSide};
+
+    let name = |side| match side {
+        OplogSide::A => "A",
+        OplogSide::B => "B",
+    };
+    let summary = OplogDiffer::new(std::env::temp_dir())
+        .diff(a, b, |finding| match finding {
+            OplogFinding::Missing { side, index, op } => println!(
+                "only in {} #{:<8} {} {} {} by {} at {}",
+                name(side),
+                index,
+                op.op_id,
+                op.op_type,
+                op.target,
+                op.author,
+                op.timestamp
+            ),
+            OplogFinding::Duplicate { side, index, op_id } => {
+                println!("⚠️  {} #{}: duplicate of an earlier {}", name(side), index, op_id)
+            }
+            OplogFinding::ClockRegression { side, index, op_id, node, previous, current } => {
+                println!(
+                    "⚠️  {} #{}: {} committed with clock {}={} after {}={}",
+                    name(side),
+                    index,
+                    op_id,
+                    node,
+                    current,
+                    node,
+                    previous
+                )
+            }
+        })
+        .with_context(|| "Failed to diff oplogs")?;
+
+    println!("\nA: {} operation(s), B: {} operation(s)", summary.ops_a, summary.ops_b);
+    println!("   Only in A: {}", summary.only_in_a);
+    println!("   Only in B: {}", summary.only_in_b);
+    println!("   Anomalies: {}", summary.anomalies);
+    if summary.is_converged() {
+        println!("✅ Both logs hold the same operations");
+    } else {
+        println!("❌ Logs diverge after {} common operation(s)", summary.common_prefix);
+        if let Some(index) = summary.first_missing_from_b {
+            println!("   Earliest operation B lacks: A #{}", index);
+        }
+        if let Some(index) = summary.first_missing_from_a {
+            println!("   Earliest operation A lacks: B #{}", index);
+        }
+    }
+
+    Ok(())
+}
+
+/// Rebuild the on-disk search index from the commit log
+fn cmd_search_rebuild(data_dir: &Path) -> Result<()> {
+    if !data_dir.join("identity.json").exists() {
+        return Err(anyhow::anyhow!("Identity not found. Run 'spacepanda init' first."));
+    }
+
+    let store = open_store(data_dir, &StoreConfig::default())?;
+    let index = PersistentSearchIndex::open(
+        data_dir.join("search_index"),
+        store.encryption(),
+        DEFAULT_SEGMENT_SIZE,
+    )
+    .with_context(|| "Failed to open search index")?;
+
+    println!("🔎 Rebuilding search index...");
+    index
+        .rebuild(&store, &|done, total| {
+            print!("\r   {} / {} log entries", done, total);
+            let _ = std::io::Write::flush(&mut std::io::stdout());
+        })
+        .with_context(|| "Failed to rebuild search index")?;
+    println!();
+
+    let stats = index
+        .index()
+        .read()
+        .map_err(|_| anyhow::anyhow!("Search index lock poisoned"))?
+        .stats();
+    println!("✅ Search index rebuilt");
+    println!("   Messages indexed: {}", stats.total_messages);
+    println!("   Segments: {}", index.segment_count());
+
+    Ok(())
+}
+
+/// Points per sparkline in 'stats system'
+const SPARKLINE_WIDTH: u32 = 48;
+
+/// Print a sparkline and summary for each recorded metric
+fn cmd_stats_system(data_dir: &Path, last: Duration) -> Result<()> {
+    let history = MetricsHistory::open(data_dir.join("metrics"))
+        .with_context(|| "Failed to open metrics history")?;
+    let step = (last / SPARKLINE_WIDTH).max(Duration::from_secs(1));
+
+    println!("📈 System metrics, last {}s:\n", last.as_secs());
+    for name in history.series() {
+        let points = history
+            .query(name, last, step)
+            .with_context(|| format!("Failed to read history of {}", name))?;
+        let Some(latest) = points.last() else {
+            println!("  {:<28} (no data)", name);
+            continue;
+        };
+
+        let values: Vec<f64> = points.iter().map(|p| p.value).collect();
+        let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        println!(
+            "  {:<28} {}  min {:.1}  max {:.1}  now {:.1}",
+            name,
+            sparkline(&values, min, max),
+            min,
+            max,
+            latest.value
+        );
+    }
+
+    Ok(())
+}
+
+/// Render values as a row of block characters scaled to [min, max]
+fn sparkline(values: &[f64], min: f64, max: f64) -> String {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    values
+        .iter()
+        .map(|value| {
+            let level = if max > min {
+                (value - min) / (max - min)
+            } else {
+                0.0
+            };
+            BLOCKS[((level * 7.0).round() as usize).min(7)]
+        })
+        .collect()
+}
 
-    // Load existing channel state from snapshots
-    if let Err(e) = store.load() {
-        warn!("Failed to load persisted channel state: {}", e);
-    } else {
-        debug!("Successfully loaded channel state from storage");
+/// Print a channel's delivery latency against the SLO
+fn cmd_stats_delivery(data_dir: &Path, channel_id_str: &str) -> Result<()> {
+    use spacepanda_core::core_store::model::types::ChannelId;
+
+    let path = data_dir.join(DELIVERY_REPORT_FILE);
+    if !path.exists() {
+        anyhow::bail!("No delivery stats yet. They are recorded while 'spacepanda listen' runs.");
     }
+    let report = DeliveryReport::load(&path)
+        .with_context(|| format!("Failed to read delivery stats from {:?}", path))?;
+    let Some(stats) = report.channel(&ChannelId(channel_id_str.to_string())) else {
+        anyhow::bail!("No delivery receipts recorded for channel {}", channel_id_str);
+    };
 
-    // Create manager
-    let manager = Arc::new(ChannelManager::new(mls_service, store, Arc::new(identity), config));
+    println!("📬 Delivery latency for {}:", stats.channel_id);
+    println!("   Receipts in window: {}", stats.samples);
+    println!("   p50: {} ms", stats.p50_ms);
+    println!("   p95: {} ms", stats.p95_ms);
+    println!("   p99: {} ms", stats.p99_ms);
+    println!(
+        "   Within {} ms: {:.1}% (objective {:.1}%)",
+        report.target_latency_ms,
+        stats.compliance * 100.0,
+        report.objective * 100.0
+    );
+    println!("   Unacknowledged: {}", stats.unacknowledged);
+    println!("   SLO state: {:?}", stats.state);
 
-    Ok(manager)
+    Ok(())
 }
 
 /// Create a new encrypted channel
@@ -271,13 +1956,17 @@ async fn cmd_channel_create(manager: Arc<ChannelManager>, name: &str, public: bo
     println!("   Name: {}", name);
     println!("   Public: {}", if public { "yes" } else { "no" });
     println!("\nTo invite others:");
-    println!("  spacepanda channel invite {}", channel_id);
+    println!("  spacepanda channel invite {} <key-package>", channel_id);
 
     Ok(())
 }
 
 /// Join a channel from an invite code
-async fn cmd_channel_join(manager: Arc<ChannelManager>, invite_b64: &str) -> Result<()> {
+async fn cmd_channel_join(
+    manager: Arc<ChannelManager>,
+    invite_b64: &str,
+    force: bool,
+) -> Result<()> {
     use base64::{engine::general_purpose::STANDARD, Engine};
 
     info!("Joining channel from invite");
@@ -290,7 +1979,11 @@ async fn cmd_channel_join(manager: Arc<ChannelManager>, invite_b64: &str) -> Res
     let invite: spacepanda_core::core_mvp::types::InviteToken =
         serde_json::from_slice(&invite_bytes).with_context(|| "Invalid invite format")?;
 
-    let channel_id = manager.join_channel(&invite).await?;
+    let channel_id = if force {
+        manager.join_channel_forced(&invite).await?
+    } else {
+        manager.join_channel(&invite).await?
+    };
 
     println!("✅ Successfully joined channel!");
     println!("   Channel ID: {}", channel_id);
@@ -301,33 +1994,355 @@ async fn cmd_channel_join(manager: Arc<ChannelManager>, invite_b64: &str) -> Res
     Ok(())
 }
 
+/// Generate a key package for receiving invites
+async fn cmd_keypackage_export(manager: Arc<ChannelManager>) -> Result<()> {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    let key_package = manager.generate_key_package().await?;
+
+    println!("✅ Key package generated!");
+    println!("\nKey package:");
+    println!("{}", STANDARD.encode(&key_package));
+    println!("\nSend this to a channel member. They can invite you with:");
+    println!("  spacepanda channel invite <channel-id> <key-package>");
+
+    Ok(())
+}
+
 /// Generate an invite code for a channel
-async fn cmd_channel_invite(manager: Arc<ChannelManager>, channel_id_str: &str) -> Result<()> {
+async fn cmd_channel_invite(
+    manager: Arc<ChannelManager>,
+    channel_id_str: &str,
+    key_package_b64: &str,
+    expires: Option<Duration>,
+) -> Result<()> {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use spacepanda_core::core_mvp::InviteOptions;
     use spacepanda_core::core_store::model::types::ChannelId;
 
     info!("Creating invite for channel: {}", channel_id_str);
 
     let channel_id = ChannelId(channel_id_str.to_string());
 
-    // Generate a key package for the invitee (they'll need to import this)
-    // For MVP, we create a temporary key package
-    // TODO: In production, the invitee should generate their own key package
-    warn!("Generating temporary key package (MVP limitation)");
-    let key_package = manager.generate_key_package().await?;
+    let key_package = STANDARD
+        .decode(key_package_b64.trim())
+        .with_context(|| "Invalid key package (not valid base64)")?;
 
-    let (invite, _commit) = manager.create_invite(&channel_id, key_package).await?;
+    let mut options = InviteOptions::default();
+    if let Some(ttl) = expires {
+        options = options.with_ttl(ttl);
+    }
+    let (invite, _commit) =
+        manager.create_invite_with_options(&channel_id, key_package, options).await?;
 
     // Encode invite as base64
-    use base64::{engine::general_purpose::STANDARD, Engine};
     let invite_bytes = serde_json::to_vec(&invite)?;
     let invite_b64 = STANDARD.encode(&invite_bytes);
 
     println!("✅ Invite created successfully!");
+    println!("   Invite ID: {}", invite.invite_id);
+    if let Some(expires_at) = invite.expires_at {
+        println!("   Expires: {}", expires_at);
+    }
     println!("\nInvite code:");
     println!("{}", invite_b64);
     println!("\nShare this with the person you want to invite.");
+    println!("Only the device that exported the key package can use it.");
     println!("They can join with:");
     println!("  spacepanda channel join <invite-code>");
+    println!("\nTo revoke it before it is used:");
+    println!("  spacepanda channel invite revoke {}", invite.invite_id);
+
+    Ok(())
+}
+
+/// Environment variable holding the passphrase of offline invite files
+const INVITE_PASSPHRASE_ENV: &str = "SPACEPANDA_INVITE_PASSPHRASE";
+
+/// Passphrase for sealing and opening offline invite files
+fn invite_passphrase() -> Result<String> {
+    std::env::var(INVITE_PASSPHRASE_ENV)
+        .with_context(|| format!("Set {} to the offline invite passphrase", INVITE_PASSPHRASE_ENV))
+}
+
+/// Write a self-contained invite file for joining without any network
+async fn cmd_channel_invite_offline(
+    manager: Arc<ChannelManager>,
+    channel_id_str: &str,
+    key_package_b64: &str,
+    expires: Option<Duration>,
+    file: &Path,
+) -> Result<()> {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use spacepanda_core::core_mvp::InviteOptions;
+    use spacepanda_core::core_store::model::types::ChannelId;
+
+    let passphrase = invite_passphrase()?;
+    let channel_id = ChannelId(channel_id_str.to_string());
+    let key_package = STANDARD
+        .decode(key_package_b64.trim())
+        .with_context(|| "Invalid key package (not valid base64)")?;
+
+    let mut options = InviteOptions::default();
+    if let Some(ttl) = expires {
+        options = options.with_ttl(ttl);
+    }
+    let (bundle, _commit) =
+        manager.create_offline_invite(&channel_id, key_package, options).await?;
+
+    let sealed = bundle.seal(&passphrase)?;
+    std::fs::write(file, &sealed)
+        .with_context(|| format!("Failed to write offline invite to {}", file.display()))?;
+
+    println!("✅ Offline invite written to {}", file.display());
+    println!("   Invite ID: {}", bundle.invite.invite_id);
+    println!("   Size: {} bytes, {} members", sealed.len(), bundle.member_count);
+    if let Some(expires_at) = bundle.invite.expires_at {
+        println!("   Expires: {}", expires_at);
+    }
+    if let Some(warning) = bundle.size_warning(sealed.len()) {
+        println!("⚠️  {}", warning);
+    }
+    println!("\nCarry the file over and pass on the passphrase separately.");
+    println!("They can join with:");
+    println!("  spacepanda channel join --offline {}", file.display());
+
+    Ok(())
+}
+
+/// Join a channel from an offline invite file
+async fn cmd_channel_join_offline(manager: Arc<ChannelManager>, file: &Path) -> Result<()> {
+    use spacepanda_core::core_mvp::OfflineInvite;
+
+    let passphrase = invite_passphrase()?;
+    let sealed = std::fs::read(file)
+        .with_context(|| format!("Failed to read offline invite {}", file.display()))?;
+    let bundle = OfflineInvite::open(&sealed, &passphrase)?;
+    if let Some(warning) = bundle.size_warning(sealed.len()) {
+        println!("⚠️  {}", warning);
+    }
+
+    let channel_id = manager.join_offline(&bundle).await?;
+
+    println!("✅ Joined channel without a network!");
+    println!("   Channel ID: {}", channel_id);
+    println!("   Name: {}", bundle.invite.channel_name);
+    println!("   Invited by: {}", bundle.inviter.display_name);
+    if !bundle.bootstrap_peers.is_empty() {
+        println!("   Bootstrap peers: {}", bundle.bootstrap_peers.join(", "));
+    }
+    println!("\nMessages you send are queued until you next run:");
+    println!("  spacepanda listen {}", channel_id);
+
+    Ok(())
+}
+
+/// Revoke an invite
+async fn cmd_channel_invite_revoke(manager: Arc<ChannelManager>, invite_id: &str) -> Result<()> {
+    info!("Revoking invite: {}", invite_id);
+
+    // Invite IDs are unique, so find the channel that issued it
+    let mut channel_id = None;
+    for channel in manager.list_channels().await? {
+        let invites = manager.list_invites(&channel.channel_id).await?;
+        if invites.iter().any(|invite| invite.invite_id == invite_id) {
+            channel_id = Some(channel.channel_id);
+            break;
+        }
+    }
+    let channel_id =
+        channel_id.with_context(|| format!("No invite with ID {} was issued here", invite_id))?;
+
+    let commit = manager.revoke_invite(&channel_id, invite_id).await?;
+
+    println!("✅ Revoked invite {}", invite_id);
+    println!("   Channel: {}", channel_id);
+    if let Some(commit) = commit {
+        println!("   Removal commit: {} bytes", commit.len());
+        println!("\nThe invite's Welcome can no longer be used to read the channel.");
+    }
+
+    Ok(())
+}
+
+/// Remove a member from a channel
+async fn cmd_channel_kick(
+    manager: Arc<ChannelManager>,
+    channel_id_str: &str,
+    user_id: &str,
+) -> Result<()> {
+    use spacepanda_core::core_store::model::types::ChannelId;
+
+    info!("Removing {} from channel: {}", user_id, channel_id_str);
+
+    let channel_id = ChannelId(channel_id_str.to_string());
+    let commit = manager.remove_member(&channel_id, user_id.as_bytes()).await?;
+
+    println!("✅ Removed {} from channel {}", user_id, channel_id_str);
+    println!("   Removal commit: {} bytes", commit.len());
+    println!("\nRemaining members must process this commit to stay in sync.");
+
+    Ok(())
+}
+
+/// Leave a channel
+async fn cmd_channel_leave(manager: Arc<ChannelManager>, channel_id_str: &str) -> Result<()> {
+    use spacepanda_core::core_store::model::types::ChannelId;
+
+    info!("Leaving channel: {}", channel_id_str);
+
+    let channel_id = ChannelId(channel_id_str.to_string());
+    let proposal = manager.leave_channel(&channel_id).await?;
+
+    println!("✅ Left channel {}", channel_id_str);
+    println!("   Leave proposal: {} bytes", proposal.len());
+    println!("\nA remaining member must commit the proposal to complete the removal.");
+
+    Ok(())
+}
+
+/// List a channel's members
+async fn cmd_channel_members(manager: Arc<ChannelManager>, channel_id_str: &str) -> Result<()> {
+    use spacepanda_core::core_store::model::types::ChannelId;
+
+    let channel_id = ChannelId(channel_id_str.to_string());
+    let members = manager.list_members(&channel_id).await?;
+
+    println!("👥 {} member device(s) in {}:\n", members.len(), channel_id_str);
+    for member in members {
+        println!("  👤 {} (device {})", member.user_id, member.device_id);
+        println!("     Joined: {}", member.joined_at);
+    }
+
+    Ok(())
+}
+
+/// Set a channel's topic
+async fn cmd_channel_set_topic(
+    manager: Arc<ChannelManager>,
+    channel_id_str: &str,
+    topic: &str,
+) -> Result<()> {
+    use spacepanda_core::core_store::model::types::ChannelId;
+
+    info!("Setting topic of channel: {}", channel_id_str);
+
+    let channel_id = ChannelId(channel_id_str.to_string());
+    let topic = Some(topic.to_string()).filter(|topic| !topic.is_empty());
+    let commit = manager.set_channel_topic(&channel_id, topic.clone()).await?;
+
+    match topic {
+        Some(topic) => println!("✅ Set topic of {} to \"{}\"", channel_id_str, topic),
+        None => println!("✅ Cleared topic of {}", channel_id_str),
+    }
+    println!("   Metadata commit: {} bytes", commit.len());
+    println!("\nMembers see the new topic once they process this commit.");
+
+    Ok(())
+}
+
+/// Set a channel's retention policy
+async fn cmd_channel_set_retention(
+    manager: Arc<ChannelManager>,
+    channel_id_str: &str,
+    policy: RetentionPolicy,
+) -> Result<()> {
+    use spacepanda_core::core_store::model::types::ChannelId;
+
+    info!("Setting retention of channel {} to {}", channel_id_str, policy);
+
+    let channel_id = ChannelId(channel_id_str.to_string());
+    let commit = manager.set_retention(&channel_id, policy).await?;
+    let pruned = manager.prune_channel(&channel_id).await?;
+
+    println!("✅ Set retention of {} to {}", channel_id_str, policy);
+    println!("   Metadata commit: {} bytes", commit.len());
+    if pruned > 0 {
+        println!("   Pruned {} expired message(s) locally", pruned);
+    }
+    println!("\nMembers prune their copies once they process this commit.");
+
+    Ok(())
+}
+
+/// Write a channel transcript to a file
+async fn cmd_channel_export(
+    manager: Arc<ChannelManager>,
+    channel_id_str: &str,
+    format: TranscriptFormatArg,
+    out: &Path,
+    no_attachments: bool,
+    exclude_senders: &[String],
+) -> Result<()> {
+    use spacepanda_core::core_store::model::types::{ChannelId, UserId};
+
+    info!("Exporting transcript of channel {}", channel_id_str);
+
+    let format = match format {
+        TranscriptFormatArg::Json => TranscriptFormat::Json,
+        TranscriptFormatArg::Md => TranscriptFormat::Markdown,
+    };
+    let mut options = TranscriptOptions::new(format);
+    if no_attachments {
+        options = options.with_attachments_excluded();
+    }
+    for sender in exclude_senders {
+        options = options.with_excluded_sender(UserId(sender.clone()));
+    }
+
+    let channel_id = ChannelId(channel_id_str.to_string());
+
+    // Don't leave a partial transcript behind if the export fails
+    let tmp = out.with_extension("tmp");
+    let file = std::fs::File::create(&tmp).with_context(|| format!("Failed to create {:?}", tmp))?;
+    let writer = std::io::BufWriter::new(file);
+    let report = match manager.export_channel(&channel_id, options, writer).await {
+        Ok(report) => report,
+        Err(e) => {
+            let _ = std::fs::remove_file(&tmp);
+            return Err(e).with_context(|| "Export failed");
+        }
+    };
+    std::fs::rename(&tmp, out).with_context(|| format!("Failed to write {:?}", out))?;
+
+    println!("✅ Exported {} message(s) of {} to {:?}", report.messages, channel_id_str, out);
+    if report.redacted > 0 {
+        println!("   Left out {} redacted message(s)", report.redacted);
+    }
+
+    Ok(())
+}
+
+/// Show a channel's privacy report
+async fn cmd_channel_privacy(manager: Arc<ChannelManager>, channel_id_str: &str) -> Result<()> {
+    use spacepanda_core::core_store::model::types::ChannelId;
+
+    let report = manager.privacy_report(&ChannelId(channel_id_str.to_string())).await?;
+    let yes_no = |value: bool| if value { "yes" } else { "no" };
+
+    println!("🔒 Privacy of {}: {}/100 ({})\n", channel_id_str, report.score, report.rating);
+    println!("  Publicly discoverable: {}", yes_no(report.discoverable));
+    println!("  Sealed sender:         {}", yes_no(report.sealed_sender));
+    match report.padding_bucket {
+        Some(bucket) => println!("  Padding bucket:        {} bytes", bucket),
+        None => println!("  Padding bucket:        none"),
+    }
+    println!("  Presence:              {}", if report.presence { "on" } else { "off" });
+    match report.retention {
+        Some(retention) => println!("  Retention:             {}s", retention.as_secs()),
+        None => println!("  Retention:             until you leave"),
+    }
+    println!("  Member list seen by:   {}", report.member_list);
+    if let Some(interval) = report.membership_batch_interval {
+        println!("  Broadcast batching:    every {}s", interval.as_secs());
+    }
+
+    if !report.recommendations.is_empty() {
+        println!("\nRecommendations:");
+        for recommendation in &report.recommendations {
+            println!("  • {}", recommendation);
+        }
+    }
 
     Ok(())
 }
@@ -369,20 +2384,452 @@ async fn cmd_send(manager: Arc<ChannelManager>, channel_id_str: &str, message: &
     Ok(())
 }
 
+/// Edit a message we sent
+async fn cmd_edit(
+    manager: Arc<ChannelManager>,
+    channel_id_str: &str,
+    message_id: &str,
+    message: &str,
+) -> Result<()> {
+    use spacepanda_core::core_store::model::types::{ChannelId, MessageId};
+
+    let channel_id = ChannelId(channel_id_str.to_string());
+    manager
+        .edit_message(&channel_id, MessageId(message_id.to_string()), message.as_bytes())
+        .await?;
+
+    println!("✅ Message edited");
+
+    Ok(())
+}
+
+/// Delete a message
+async fn cmd_delete(
+    manager: Arc<ChannelManager>,
+    channel_id_str: &str,
+    message_id: &str,
+) -> Result<()> {
+    use spacepanda_core::core_store::model::types::{ChannelId, MessageId};
+
+    let channel_id = ChannelId(channel_id_str.to_string());
+    manager.delete_message(&channel_id, MessageId(message_id.to_string())).await?;
+
+    println!("✅ Message deleted");
+
+    Ok(())
+}
+
+/// Send a file
+async fn cmd_send_file(
+    manager: Arc<ChannelManager>,
+    channel_id_str: &str,
+    path: &Path,
+) -> Result<()> {
+    use spacepanda_core::core_store::model::types::ChannelId;
+
+    let channel_id = ChannelId(channel_id_str.to_string());
+
+    info!("Sending {} to channel: {}", path.display(), channel_id);
+
+    manager.send_attachment(&channel_id, path).await?;
+
+    println!("✅ File sent: {}", path.display());
+
+    Ok(())
+}
+
+/// Download a file sent to a channel
+async fn cmd_download(
+    manager: Arc<ChannelManager>,
+    channel_id_str: &str,
+    message_id: &str,
+    dest: &Path,
+) -> Result<()> {
+    use spacepanda_core::core_store::model::types::{ChannelId, MessageId};
+
+    let channel_id = ChannelId(channel_id_str.to_string());
+    let attachment = manager
+        .download_attachment(&channel_id, MessageId(message_id.to_string()), dest)
+        .await
+        .with_context(|| "Download failed; run the command again to resume")?;
+
+    println!(
+        "✅ Saved {} ({} bytes, {}) to {}",
+        attachment.filename,
+        attachment.size_bytes,
+        attachment.mime_type,
+        dest.display()
+    );
+
+    Ok(())
+}
+
+/// Show message history, oldest of the page first
+async fn cmd_history(
+    manager: Arc<ChannelManager>,
+    channel_id_str: &str,
+    limit: usize,
+    before: Option<&str>,
+) -> Result<()> {
+    use spacepanda_core::core_store::model::types::{ChannelId, MessageId};
+
+    let channel_id = ChannelId(channel_id_str.to_string());
+    let before = before.map(|id| MessageId(id.to_string()));
+
+    let messages = manager.get_messages(&channel_id, limit, before).await?;
+    if messages.is_empty() {
+        println!("No messages.");
+        return Ok(());
+    }
+
+    for message in messages.iter().rev() {
+        print_message(message);
+    }
+
+    if messages.len() == limit {
+        let oldest = &messages[messages.len() - 1].message_id;
+        println!("\nOlder messages:");
+        println!("  spacepanda history {} --before {}", channel_id_str, oldest);
+    }
+
+    Ok(())
+}
+
+/// List sent messages still waiting to reach the network, oldest first
+///
+/// Messages are queued when the network can't be reached, or when the
+/// channel was joined offline; `listen` retries them in the background.
+fn cmd_outbox(manager: Arc<ChannelManager>) -> Result<()> {
+    use spacepanda_core::core_store::model::types::MessageId;
+
+    let queued = manager.outbox_messages();
+    if queued.is_empty() {
+        println!("Outbox is empty.");
+        return Ok(());
+    }
+
+    println!("📤 {} message(s) waiting to be sent:", queued.len());
+    for envelope in &queued {
+        let deferred =
+            if manager.is_join_deferred(&envelope.channel_id) { " (join pending)" } else { "" };
+        println!(
+            "  [{}] {}  {}  {} bytes, {} attempt(s){}",
+            envelope.queued_at,
+            envelope.channel_id,
+            MessageId::from_ciphertext(&envelope.ciphertext),
+            envelope.ciphertext.len(),
+            envelope.attempts,
+            deferred
+        );
+    }
+
+    Ok(())
+}
+
+/// How often `listen` checks the DHT mailbox for Welcomes and commits
+const MAILBOX_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// First and longest delay between `listen`'s retries of the outbox
+const OUTBOX_RETRY: std::time::Duration = std::time::Duration::from_secs(5);
+const OUTBOX_MAX_RETRY: std::time::Duration = std::time::Duration::from_secs(300);
+
+/// How often `listen` prunes messages past their channel's retention
+const RETENTION_TICK: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// How often `listen` looks for peers due a store sync session
+const SYNC_TICK: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// How often `listen` looks for delivery paths due a keepalive probe
+const PATH_PROBE_TICK: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// How often `listen` checks whether the notification digest is due
+const DIGEST_TICK: std::time::Duration = std::time::Duration::from_secs(60);
+
 /// Listen for incoming messages (interactive mode)
-async fn cmd_listen(_manager: Arc<ChannelManager>, channel_id: &str) -> Result<()> {
+///
+/// Messages that were queued while the client was offline are printed
+/// first, then new messages as they arrive. Commits received in the
+/// meantime are applied in the background, so messages keep decrypting
+/// across member adds and removals. Invites and commits left in the DHT
+/// mailbox are picked up periodically. Other members typing is shown as
+/// it happens, and a presence heartbeat is sent to the channel while
+/// listening. Messages still in the outbox are retried in the background,
+/// straight away whenever a peer connects. Received messages are
+/// acknowledged to their senders in batches, and messages past their
+/// channel's retention policy are pruned. Idle delivery paths to peers are
+/// re-measured with keepalive probes, and frames peers left in our mailbox
+/// inbox are read. Nodes with an identity master key relay onion circuits
+/// for others. Messages in other channels are notified by the notification
+/// policy, and the digest is shown when due, straight away if it fell due
+/// while the client was stopped; both go to the notification webhook if one
+/// is configured.
+async fn cmd_listen(
+    data_dir: &Path,
+    channel_id_str: &str,
+    listen_addr: &str,
+    peers: &[String],
+) -> Result<()> {
+    use spacepanda_core::core_store::model::types::ChannelId;
+
+    let channel_id = ChannelId(channel_id_str.to_string());
+
+    let shutdown = Arc::new(ShutdownCoordinator::new(std::time::Duration::from_secs(1)));
+    install_signal_handlers(shutdown.clone());
+    let mut shutdown_rx = shutdown.subscribe();
+
+    let manager = build_manager(data_dir, shutdown.clone()).await?;
+
+    // Record metrics history for 'stats system'
+    let config = load_config(data_dir)?;
+    let history = MetricsHistory::open(data_dir.join("metrics"))
+        .with_context(|| "Failed to open metrics history")?;
+    let metrics = Arc::new(
+        MetricsService::new(config.metrics.collection_interval).with_history(Arc::new(history)),
+    );
+
+    // Start the network layer, relaying onion circuits with the identity's
+    // onion key and signing relay and mailbox frames with its keypair;
+    // linked devices hold no master key, only send through circuits and sign
+    // with a key of their own
+    let master_key = load_master_key(data_dir).ok();
+    let (router, router_task) = match &master_key {
+        Some(master_key) => RouterHandle::with_onion_key(master_key.onion_key()),
+        None => RouterHandle::new(),
+    };
+    let signing_key = match &master_key {
+        Some(master_key) => master_key.keypair().clone(),
+        None => Keypair::generate(KeyType::Ed25519),
+    };
+    let peer_id = PeerId::from_bytes(manager.identity().node_id.as_bytes().to_vec());
+    let (network, messages_rx, commits_rx) = NetworkLayer::new(router.clone(), peer_id);
+    let delivery = Arc::new(DeliveryTracker::new(config.metrics.delivery_slo.clone()));
+    let mut slo_events = delivery.subscribe();
+    let network = Arc::new(
+        network
+            .with_delivery_tracker(delivery.clone())
+            .with_mailbox(Arc::new(DhtMailbox::new(DhtStorage::new())))
+            .with_signing_key(signing_key)
+            .with_state_file(&data_dir.join(NETWORK_STATE_FILE))?,
+    );
+
+    network.listen(listen_addr).await?;
+    for peer in peers {
+        if let Err(e) = network.dial(peer).await {
+            warn!("Failed to connect to peer {}: {}", peer, e);
+        }
+    }
+
+    // Reconcile the channel's store state with peers as they connect
+    let node_id = manager.identity().node_id.clone();
+    let replicas = SyncReplicas {
+        stability: Arc::new(std::sync::Mutex::new(CausalStability::open(
+            StabilityConfig::from_store_config(&config.store),
+            manager.store().data_dir().join(STABILITY_FILE),
+        )?)),
+        deltas: Arc::new(std::sync::Mutex::new(SyncDeltas::new(
+            DeltaStateConfig::from_store_config(&config.store),
+        ))),
+    };
+    let (sync_tx, sync_server) =
+        serve_sync(manager.store().clone(), node_id.clone(), replicas.clone());
+    router
+        .register_rpc_handler(SYNC_METHOD.to_string(), sync_tx)
+        .await
+        .map_err(anyhow::Error::msg)?;
+    let mut anti_entropy = AntiEntropyManager::new(AntiEntropyConfig::default());
+    anti_entropy.add_target(channel_id.0.clone(), Default::default());
+    let anti_entropy = spawn_anti_entropy(
+        router,
+        manager.store().clone(),
+        Arc::new(std::sync::Mutex::new(anti_entropy)),
+        replicas,
+        node_id,
+        network.peer_connections(),
+        SYNC_TICK,
+    );
+
+    let manager = Arc::new(manager.with_network(network.clone()));
+    let mut channel_events = manager.subscribe_events();
+    let mut notifications = NotificationRules::open(
+        config.notifications.clone(),
+        manager.identity(),
+        &data_dir.join(DIGEST_STATE_FILE),
+        Timestamp::now(),
+    )?;
+    let webhook = config.notifications.webhook_url.clone().map(WebhookSink::new);
+    let mut security_events = manager.subscribe_security_events();
+
+    let tasks = [
+        router_task,
+        network.clone().spawn_event_processor(),
+        network.clone().spawn_path_prober(PATH_PROBE_TICK),
+        network.clone().spawn_mailbox_poller(MAILBOX_POLL_INTERVAL),
+        manager.clone().spawn_commit_processor(commits_rx),
+        manager.clone().spawn_message_processor(messages_rx),
+        manager.clone().spawn_ack_sender(ACK_INTERVAL),
+        manager.clone().spawn_retention_pruning(shutdown.clone(), RETENTION_TICK),
+        manager.clone().spawn_outbox_flusher(
+            network.peer_connections(),
+            OUTBOX_RETRY,
+            OUTBOX_MAX_RETRY,
+        ),
+        sync_server,
+        anti_entropy,
+        tokio::spawn(metrics.run()),
+        delivery.spawn_evaluator(
+            config.metrics.collection_interval,
+            Some(data_dir.join(DELIVERY_REPORT_FILE)),
+        ),
+    ];
+
     println!("🎧 Listening on channel: {}", channel_id);
-    println!("   (Interactive message receiving not yet implemented)");
+    println!("   Accepting peers on {}", listen_addr);
     println!("   Press Ctrl+C to stop.");
 
-    // TODO: Implement message receiving loop
-    // This requires:
-    // 1. Network layer integration
-    // 2. Message queue/channel for incoming messages
-    // 3. Decryption and display
+    // Catch up on messages received while offline before going live
+    for message in manager.process_pending(&channel_id).await? {
+        print_message(&message);
+    }
+
+    // Finish offline joins and send what was queued without a network
+    match manager.resume_online().await {
+        Ok(resumed) => {
+            for message in resumed.received.iter().filter(|m| m.channel_id == channel_id) {
+                print_message(message);
+            }
+            if resumed.delivered > 0 || !resumed.joins_completed.is_empty() {
+                println!(
+                    "📤 Sent {} queued message(s), finished {} offline join(s)",
+                    resumed.delivered,
+                    resumed.joins_completed.len()
+                );
+            }
+        }
+        Err(e) => warn!("Failed to resume offline work: {}", e),
+    }
+
+    let mut mailbox_poll = tokio::time::interval(MAILBOX_POLL_INTERVAL);
+    let mut heartbeat = tokio::time::interval(PRESENCE_INTERVAL);
+    let mut digest_poll = tokio::time::interval(DIGEST_TICK);
+    loop {
+        tokio::select! {
+            received = channel_events.recv() => match received {
+                Ok(event) => {
+                    if let Some(notification) = notifications.handle(&event) {
+                        // Messages in this channel are printed anyway
+                        if event.channel_id != channel_id {
+                            println!("🔔 {}", notification);
+                        }
+                        forward_notification(&webhook, Notification::Message(notification));
+                    }
+                    if event.channel_id == channel_id {
+                        print_event(event.kind);
+                    }
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("Display fell behind, {} event(s) not shown", skipped);
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            },
+            Ok(event) = slo_events.recv() => println!("⚠️  {}", event),
+            Ok(event) = security_events.recv() => println!("🚨 {}", event),
+            _ = mailbox_poll.tick() => match manager.poll_mailbox().await {
+                Ok(joined) => {
+                    for joined_id in joined {
+                        println!("📬 Joined channel {} from an invite in the mailbox", joined_id);
+                    }
+                }
+                Err(e) => warn!("Failed to poll mailbox: {}", e),
+            },
+            _ = heartbeat.tick() => {
+                if let Err(e) = manager.send_presence(&channel_id).await {
+                    debug!("Failed to send presence heartbeat: {}", e);
+                }
+            }
+            _ = digest_poll.tick() => {
+                if let Some(digest) = notifications.poll_digest(Timestamp::now()) {
+                    println!("📰 {}", digest);
+                    forward_notification(&webhook, Notification::Digest(digest));
+                }
+            }
+            _ = shutdown_rx.recv() => break,
+        }
+    }
 
-    tokio::signal::ctrl_c().await?;
+    for task in tasks {
+        task.abort();
+    }
     println!("\nStopped listening.");
 
     Ok(())
 }
+
+/// Print what happened in the channel being listened to
+fn print_event(kind: ChannelEventKind) {
+    match kind {
+        ChannelEventKind::MessageReceived(message) => print_message(&message),
+        ChannelEventKind::MemberJoined(user) => println!("➕ {} joined", user),
+        ChannelEventKind::MemberLeft(user) => println!("➖ {} left", user),
+        ChannelEventKind::Typing(user) => println!("✏️  {} is typing…", user),
+        ChannelEventKind::ChannelMetadataChanged(metadata) => match metadata.topic {
+            Some(topic) => println!("📝 Channel is now {} ({})", metadata.name, topic),
+            None => println!("📝 Channel is now {}", metadata.name),
+        },
+        ChannelEventKind::MessageDelivered { message_id } => {
+            println!("📤 Queued message {} sent", message_id);
+        }
+        ChannelEventKind::MessageEvicted { message_id } => {
+            println!("⚠️  Outbox full, dropped unsent message {}", message_id);
+        }
+        ChannelEventKind::DeliveryUpdated { message_id, summary } => {
+            println!("📬 {}{}", message_id, delivery_mark(summary));
+        }
+        ChannelEventKind::DecryptionFailed { error } => {
+            println!("⚠️  A message could not be decrypted: {}", error);
+        }
+        ChannelEventKind::MessagesPruned { message_ids } => {
+            println!("🧹 {} expired message(s) pruned", message_ids.len());
+        }
+        ChannelEventKind::Presence(_)
+        | ChannelEventKind::EpochAdvanced(_)
+        | ChannelEventKind::InviteAccepted { .. } => {}
+    }
+}
+
+/// Send a notification to the webhook in the background, if there is one
+fn forward_notification(webhook: &Option<WebhookSink>, notification: Notification) {
+    let Some(webhook) = webhook.clone() else {
+        return;
+    };
+    tokio::spawn(async move {
+        if let Err(e) = webhook.forward(&notification).await {
+            warn!("Failed to forward notification to webhook: {}", e);
+        }
+    });
+}
+
+/// Print a decrypted message with sender and timestamp
+fn print_message(message: &ChatMessage) {
+    let body = if message.deleted {
+        "(deleted)".into()
+    } else if let Some(manifest) = AttachmentManifest::decode(&message.body) {
+        format!("📎 {} ({} bytes)", manifest.filename, manifest.size).into()
+    } else {
+        String::from_utf8_lossy(&message.body)
+    };
+    let edited = if message.edits.is_empty() { "" } else { " (edited)" };
+    let delivery = message.delivery.map(delivery_mark).unwrap_or_default();
+    println!(
+        "[{}] {}: {}{}{}  <{}>",
+        message.timestamp, message.sender, body, edited, delivery, message.message_id
+    );
+}
+
+/// How far one of our messages got, e.g. " ✓ 2/3"
+fn delivery_mark(summary: DeliverySummary) -> String {
+    match summary.state {
+        DeliveryState::Read => " ✓✓".to_string(),
+        DeliveryState::Delivered => " ✓".to_string(),
+        DeliveryState::Pending => format!(" ✓ {}/{}", summary.delivered, summary.recipients),
+    }
+}
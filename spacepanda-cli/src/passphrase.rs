@@ -0,0 +1,97 @@
+//! Keystore passphrase
+//!
+//! The keystore holds the identity master key (and config secrets) encrypted
+//! under a passphrase. It is read at most once per run, from the first of:
+//!
+//! - the file given with `--passphrase-file`
+//! - the SPACEPANDA_KEYSTORE_PASSPHRASE environment variable
+//! - an interactive prompt
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Environment variable holding the keystore passphrase
+pub const PASSPHRASE_ENV: &str = "SPACEPANDA_KEYSTORE_PASSPHRASE";
+
+/// File given with `--passphrase-file`
+static PASSPHRASE_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Passphrase already read this run
+static PASSPHRASE: OnceLock<String> = OnceLock::new();
+
+/// Read the passphrase from this file instead of prompting
+pub fn set_file(path: PathBuf) {
+    let _ = PASSPHRASE_FILE.set(path);
+}
+
+/// Read a passphrase file, ignoring a trailing newline
+pub fn read_file(path: &Path) -> Result<String> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read passphrase file {:?}", path))?;
+    let passphrase = contents.trim_end_matches(['\r', '\n']).to_string();
+    if passphrase.is_empty() {
+        anyhow::bail!("Passphrase file {:?} is empty", path);
+    }
+    Ok(passphrase)
+}
+
+/// Passphrase from `--passphrase-file` or the environment, if either is set
+fn non_interactive() -> Result<Option<String>> {
+    if let Some(path) = PASSPHRASE_FILE.get() {
+        return read_file(path).map(Some);
+    }
+    Ok(std::env::var(PASSPHRASE_ENV).ok().filter(|passphrase| !passphrase.is_empty()))
+}
+
+/// Passphrase given without prompting, if any
+pub fn provided() -> Result<Option<String>> {
+    match PASSPHRASE.get() {
+        Some(passphrase) => Ok(Some(passphrase.clone())),
+        None => non_interactive(),
+    }
+}
+
+/// Passphrase of an existing keystore, prompting if none was provided
+pub fn current() -> Result<String> {
+    if let Some(passphrase) = provided()? {
+        return Ok(passphrase);
+    }
+    let passphrase = rpassword::prompt_password("Keystore passphrase: ")
+        .with_context(|| format!("Failed to read passphrase (or set {})", PASSPHRASE_ENV))?;
+    Ok(remember(passphrase))
+}
+
+/// Passphrase for a new keystore: provided, or prompted for twice
+pub fn new_keystore() -> Result<String> {
+    let passphrase = match non_interactive()? {
+        Some(passphrase) => passphrase,
+        None => choose(None)?,
+    };
+    Ok(remember(passphrase))
+}
+
+/// A new passphrase from a file, or prompted for and confirmed
+pub fn choose(file: Option<&Path>) -> Result<String> {
+    if let Some(path) = file {
+        return read_file(path);
+    }
+
+    let passphrase = rpassword::prompt_password("New keystore passphrase: ")
+        .with_context(|| "Failed to read passphrase")?;
+    if passphrase.is_empty() {
+        anyhow::bail!("Passphrase must not be empty");
+    }
+    let confirmation = rpassword::prompt_password("Repeat passphrase: ")
+        .with_context(|| "Failed to read passphrase")?;
+    if passphrase != confirmation {
+        anyhow::bail!("Passphrases do not match");
+    }
+    Ok(passphrase)
+}
+
+/// Use this passphrase for the rest of the run
+fn remember(passphrase: String) -> String {
+    let _ = PASSPHRASE.set(passphrase.clone());
+    passphrase
+}
@@ -0,0 +1,173 @@
+//! Named profiles
+//!
+//! A profile is a complete, isolated data directory under
+//! `<data-dir>/profiles/<name>/`: its own identity.json, MLS state
+//! (mls_groups) and LocalStore. Selecting one with `--profile` lets several
+//! users run side by side on one machine.
+
+use anyhow::{Context, Result};
+use spacepanda_core::Identity;
+use std::path::{Path, PathBuf};
+
+/// Subdirectory of the data directory that holds profiles
+const PROFILES_DIR: &str = "profiles";
+
+/// Check a profile name is safe to use as a directory name
+fn validate_name(name: &str) -> Result<()> {
+    let valid =
+        !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        anyhow::bail!("Invalid profile name '{}' (use letters, digits, '-' and '_')", name);
+    }
+    Ok(())
+}
+
+/// Directory of a named profile
+pub fn profile_dir(data_dir: &Path, name: &str) -> Result<PathBuf> {
+    validate_name(name)?;
+    Ok(data_dir.join(PROFILES_DIR).join(name))
+}
+
+/// Directory to operate on: the selected profile's, or the data directory
+pub fn resolve(data_dir: &Path, profile: Option<&str>) -> Result<PathBuf> {
+    match profile {
+        Some(name) => profile_dir(data_dir, name),
+        None => Ok(data_dir.to_path_buf()),
+    }
+}
+
+/// Fail unless the directory has an identity
+///
+/// Without this, opening a manager on a new profile directory would
+/// silently create empty state for it.
+pub fn require_identity(dir: &Path, profile: Option<&str>) -> Result<()> {
+    if dir.join("identity.json").exists() {
+        return Ok(());
+    }
+    match profile {
+        Some(name) => Err(anyhow::anyhow!(
+            "Profile '{}' has no identity. Run 'spacepanda profile create {} --name <display>' first.",
+            name,
+            name
+        )),
+        None => Err(anyhow::anyhow!("Identity not found. Run 'spacepanda init' first.")),
+    }
+}
+
+/// Create a profile with a fresh identity
+pub async fn create(data_dir: &Path, name: &str, display_name: &str) -> Result<PathBuf> {
+    let dir = profile_dir(data_dir, name)?;
+    if dir.join("identity.json").exists() {
+        anyhow::bail!("Profile '{}' already exists at {:?}", name, dir);
+    }
+
+    super::cmd_init(&dir, display_name).await?;
+    println!("   Use it with: spacepanda --profile {} <command>", name);
+
+    Ok(dir)
+}
+
+/// A profile found on disk
+#[derive(Debug)]
+pub struct ProfileEntry {
+    /// Profile name
+    pub name: String,
+
+    /// Identity, if the profile has been initialized
+    pub identity: Option<Identity>,
+}
+
+/// Profiles under the data directory, sorted by name
+pub fn list(data_dir: &Path) -> Result<Vec<ProfileEntry>> {
+    let root = data_dir.join(PROFILES_DIR);
+    if !root.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut profiles = Vec::new();
+    for entry in std::fs::read_dir(&root).with_context(|| format!("Failed to read {:?}", root))? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let identity = std::fs::read_to_string(entry.path().join("identity.json"))
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok());
+        profiles.push(ProfileEntry { name, identity });
+    }
+    profiles.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(profiles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spacepanda_core::core_mvp::network::IncomingMessage;
+    use spacepanda_core::core_router::PeerId;
+    use spacepanda_core::shutdown::ShutdownCoordinator;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_profile_names_cannot_escape() {
+        let root = Path::new("/data");
+        assert_eq!(profile_dir(root, "alice").unwrap(), root.join("profiles").join("alice"));
+        for name in ["", "..", "../bob", "a/b", "alice bob"] {
+            assert!(profile_dir(root, name).is_err(), "{:?} accepted", name);
+        }
+        assert_eq!(resolve(root, None).unwrap(), root);
+    }
+
+    #[tokio::test]
+    async fn test_two_profiles_exchange_messages() {
+        let data_dir = TempDir::new().unwrap();
+        let root = data_dir.path();
+        std::env::set_var(crate::passphrase::PASSPHRASE_ENV, "correct horse battery staple");
+
+        // An uncreated profile is refused rather than initialized empty
+        let missing = resolve(root, Some("alice")).unwrap();
+        assert!(require_identity(&missing, Some("alice")).is_err());
+
+        let alice_dir = create(root, "alice", "Alice").await.unwrap();
+        let bob_dir = create(root, "bob", "Bob").await.unwrap();
+        assert!(create(root, "bob", "Bob again").await.is_err());
+
+        let names: Vec<String> = list(root).unwrap().into_iter().map(|p| p.name).collect();
+        assert_eq!(names, vec!["alice", "bob"]);
+
+        let shutdown = Arc::new(ShutdownCoordinator::new(Duration::from_secs(30)));
+        let alice = super::super::build_manager(&alice_dir, shutdown.clone()).await.unwrap();
+        let bob = super::super::build_manager(&bob_dir, shutdown).await.unwrap();
+        assert_ne!(alice.identity().user_id, bob.identity().user_id);
+
+        // Each profile has its own MLS provider directory
+        assert_ne!(alice_dir.join("mls_groups"), bob_dir.join("mls_groups"));
+        assert!(alice_dir.join("mls_groups").is_dir());
+        assert!(bob_dir.join("mls_groups").is_dir());
+
+        let channel_id = alice.create_channel("pair".to_string(), false).await.unwrap();
+        let key_package = bob.generate_key_package().await.unwrap();
+        let (invite, _) = alice.create_invite(&channel_id, key_package).await.unwrap();
+        bob.join_channel(&invite).await.unwrap();
+
+        for (sender, receiver, text) in
+            [(&alice, &bob, b"hi bob".as_slice()), (&bob, &alice, b"hi alice".as_slice())]
+        {
+            let ciphertext = sender.send_message(&channel_id, text).await.unwrap();
+            let message = receiver
+                .handle_incoming_message(IncomingMessage {
+                    channel_id: channel_id.clone(),
+                    ciphertext,
+                    sender_id: sender.identity().user_id.clone(),
+                    sender_peer_id: PeerId(vec![1]),
+                })
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(message.body, text);
+        }
+    }
+}
@@ -58,6 +58,7 @@ impl TestActor {
             snapshot_interval: 1000,
             max_log_size: 10_000_000,
             enable_compaction: false,
+            enable_wal: true,
             require_signatures: false,
             authorized_keys: Vec::new(),
         };
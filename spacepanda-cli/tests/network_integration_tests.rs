@@ -65,6 +65,7 @@ impl NetworkedActor {
             snapshot_interval: 1000,
             max_log_size: 10_000_000,
             enable_compaction: false,
+            enable_wal: true,
             require_signatures: false,
             authorized_keys: Vec::new(),
         };
@@ -111,6 +112,7 @@ impl NetworkedActor {
                     RouterEvent::PeerDisconnected(peer_id) => {
                         println!("[{}] Peer disconnected: {:?}", name_clone, peer_id);
                     }
+                    RouterEvent::PeerReputationChanged(..) | RouterEvent::PeerQuarantined(..) => {}
                 }
             }
         });
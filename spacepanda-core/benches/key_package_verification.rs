@@ -0,0 +1,62 @@
+//! Key package verification for invite batches
+//!
+//! Compares verifying 100 key packages one at a time without a cache (the
+//! old path), as one parallel batch, and again with every verdict cached
+//! (a package re-checked at commit time).
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use openmls_rust_crypto::OpenMlsRustCrypto;
+use openmls_traits::OpenMlsProvider;
+use spacepanda_core::config::Config;
+use spacepanda_core::core_mls::key_package_cache::KeyPackageVerifier;
+use spacepanda_core::core_mls::service::MlsService;
+use spacepanda_core::shutdown::ShutdownCoordinator;
+use std::sync::Arc;
+use std::time::Duration;
+
+const BATCH_SIZE: usize = 100;
+
+fn key_packages(n: usize) -> Vec<Vec<u8>> {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let shutdown = Arc::new(ShutdownCoordinator::new(Duration::from_secs(30)));
+    let service = MlsService::new(&Config::default(), shutdown);
+    runtime.block_on(async {
+        let mut packages = Vec::with_capacity(n);
+        for i in 0..n {
+            let identity = format!("user-{}@spacepanda.local", i).into_bytes();
+            packages.push(service.generate_key_package(identity).await.unwrap());
+        }
+        packages
+    })
+}
+
+fn bench_batch_verification(c: &mut Criterion) {
+    let provider = OpenMlsRustCrypto::default();
+    let packages = key_packages(BATCH_SIZE);
+
+    let mut group = c.benchmark_group("key_package_verification");
+    group.measurement_time(Duration::from_secs(10));
+    group.throughput(Throughput::Elements(BATCH_SIZE as u64));
+
+    group.bench_with_input(BenchmarkId::new("serial_uncached", BATCH_SIZE), &packages, |b, kps| {
+        b.iter(|| {
+            for kp in kps {
+                black_box(KeyPackageVerifier::default().verify(provider.crypto(), kp).unwrap());
+            }
+        });
+    });
+
+    group.bench_with_input(BenchmarkId::new("batch_uncached", BATCH_SIZE), &packages, |b, kps| {
+        b.iter(|| black_box(KeyPackageVerifier::default().verify_batch(provider.crypto(), kps)));
+    });
+
+    let warm = KeyPackageVerifier::default();
+    warm.verify_batch(provider.crypto(), &packages);
+    group.bench_with_input(BenchmarkId::new("batch_cached", BATCH_SIZE), &packages, |b, kps| {
+        b.iter(|| black_box(warm.verify_batch(provider.crypto(), kps)));
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_batch_verification);
+criterion_main!(benches);
@@ -21,4 +21,10 @@ pub enum ConfigError {
 
     #[error("Configuration validation failed: {0}")]
     ValidationFailed(String),
+
+    #[error("Referenced secret not found in keystore: {0}")]
+    MissingSecret(String),
+
+    #[error("Failed to resolve secret: {0}")]
+    SecretError(String),
 }
@@ -25,6 +25,10 @@ pub struct FeatureFlags {
     /// Enable circuit breaker
     pub circuit_breaker: bool,
 
+    /// Route DHT puts through onion circuits, hiding which node wrote them
+    #[serde(default)]
+    pub anonymity_mode: bool,
+
     /// Custom feature flags (key-value pairs)
     pub custom: HashMap<String, bool>,
 }
@@ -38,6 +42,7 @@ impl Default for FeatureFlags {
             compression: true,
             rate_limiting: true,
             circuit_breaker: true,
+            anonymity_mode: false,
             custom: HashMap::new(),
         }
     }
@@ -90,6 +95,11 @@ impl FeatureManager {
         self.flags.read().unwrap().circuit_breaker
     }
 
+    /// Check if anonymity mode is enabled
+    pub fn is_anonymity_mode_enabled(&self) -> bool {
+        self.flags.read().unwrap().anonymity_mode
+    }
+
     /// Check a custom feature flag
     pub fn is_enabled(&self, feature: &str) -> bool {
         self.flags.read().unwrap().custom.get(feature).copied().unwrap_or(false)
@@ -105,6 +115,7 @@ impl FeatureManager {
             "compression" => flags.compression = true,
             "rate_limiting" => flags.rate_limiting = true,
             "circuit_breaker" => flags.circuit_breaker = true,
+            "anonymity_mode" => flags.anonymity_mode = true,
             _ => {
                 flags.custom.insert(feature.to_string(), true);
             }
@@ -121,6 +132,7 @@ impl FeatureManager {
             "compression" => flags.compression = false,
             "rate_limiting" => flags.rate_limiting = false,
             "circuit_breaker" => flags.circuit_breaker = false,
+            "anonymity_mode" => flags.anonymity_mode = false,
             _ => {
                 flags.custom.insert(feature.to_string(), false);
             }
@@ -167,6 +179,11 @@ mod tests {
 
         manager.disable("experimental");
         assert!(!manager.is_experimental_enabled());
+
+        assert!(!manager.is_anonymity_mode_enabled());
+        manager.enable("anonymity_mode");
+        assert!(manager.is_anonymity_mode_enabled());
+        assert!(!manager.is_enabled("anonymity_mode"));
     }
 
     #[test]
@@ -3,20 +3,28 @@
 //! This module provides environment-based configuration management with
 //! support for defaults, validation, and feature flags.
 
+use crate::core_mls::padding::PaddingStrategy;
+use chrono::NaiveTime;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::env;
 use std::net::SocketAddr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 mod error;
 mod feature_flags;
+mod secrets;
 
 pub use error::ConfigError;
 pub use feature_flags::{FeatureFlags, FeatureManager};
+pub use secrets::{parse_secret_ref, REDACTED, SECRET_REF_PREFIX, SECRET_SETTINGS};
 
 /// Main application configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// String values may reference keystore secrets (`"keystore:<name>"`); see
+/// [`Config::resolve_secrets`].
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Config {
     /// Server configuration
     pub server: ServerConfig,
@@ -35,6 +43,35 @@ pub struct Config {
 
     /// Feature flags
     pub features: FeatureFlags,
+
+    /// Metadata privacy settings
+    #[serde(default)]
+    pub privacy: PrivacyConfig,
+
+    /// Notification policy
+    #[serde(default)]
+    pub notifications: NotificationConfig,
+
+    /// `(setting path, secret name)` of values resolved from the keystore
+    #[serde(skip)]
+    resolved_secrets: Vec<(String, String)>,
+}
+
+impl std::fmt::Debug for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Debug output ends up in logs, so it must never show resolved secrets
+        let redacted = self.redacted();
+        f.debug_struct("Config")
+            .field("server", &redacted.server)
+            .field("dht", &redacted.dht)
+            .field("store", &redacted.store)
+            .field("logging", &redacted.logging)
+            .field("metrics", &redacted.metrics)
+            .field("features", &redacted.features)
+            .field("privacy", &redacted.privacy)
+            .field("notifications", &redacted.notifications)
+            .finish()
+    }
 }
 
 /// Server configuration
@@ -62,6 +99,14 @@ pub struct ServerConfig {
 
     /// TLS key path (if TLS enabled)
     pub tls_key_path: Option<PathBuf>,
+
+    /// How long an API session token is valid before it must be refreshed
+    #[serde(default = "default_session_ttl", with = "humantime_serde")]
+    pub session_ttl: Duration,
+
+    /// API sessions unused for this long are closed
+    #[serde(default = "default_session_idle_timeout", with = "humantime_serde")]
+    pub session_idle_timeout: Duration,
 }
 
 /// DHT configuration
@@ -86,6 +131,11 @@ pub struct DhtConfig {
 
     /// Bootstrap peers
     pub bootstrap_peers: Vec<SocketAddr>,
+
+    /// Key namespaces looked up privately, with how many relays (1 or 2) each
+    /// lookup goes through; other namespaces are looked up directly
+    #[serde(default)]
+    pub private_lookups: BTreeMap<String, usize>,
 }
 
 /// Store configuration
@@ -110,6 +160,202 @@ pub struct StoreConfig {
     /// Tombstone cleanup interval
     #[serde(with = "humantime_serde")]
     pub tombstone_cleanup_interval: Duration,
+
+    /// How long a replica may go without a completed sync before tombstone
+    /// cleanup stops waiting for it; its writes are refused from then on
+    #[serde(default = "default_replica_silence_horizon", with = "humantime_serde")]
+    pub replica_silence_horizon: Duration,
+
+    /// Byte quota for downloaded attachment blobs
+    #[serde(default = "default_attachment_cache_quota")]
+    pub attachment_cache_quota: u64,
+
+    /// Largest file that can be sent or downloaded as an attachment
+    #[serde(default = "default_max_attachment_size")]
+    pub max_attachment_size: u64,
+
+    /// Free space to leave on top of the estimate before joins, attachment
+    /// downloads and restores
+    #[serde(default = "default_free_space_margin")]
+    pub free_space_margin: u64,
+
+    /// Remote backup target (disabled if absent)
+    #[serde(default)]
+    pub remote_backup: Option<RemoteBackupConfig>,
+
+    /// Attest the binary and data directory pairing on every startup
+    #[serde(default)]
+    pub attestation: bool,
+
+    /// Where the MLS service keeps group state (default: SQLite in the
+    /// service's storage directory)
+    #[serde(default)]
+    pub mls_backend: Option<StorageBackend>,
+}
+
+/// Storage backend for MLS group state
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// Snapshot files in a directory
+    File { dir: PathBuf },
+    /// SQLite database
+    Sqlite { path: PathBuf },
+    /// Kept in memory only; nothing survives a restart
+    Memory,
+}
+
+impl StorageBackend {
+    /// Directory the backend writes to, if any
+    pub fn dir(&self) -> Option<&Path> {
+        match self {
+            StorageBackend::File { dir } => Some(dir),
+            StorageBackend::Sqlite { path } => {
+                Some(path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new(".")))
+            }
+            StorageBackend::Memory => None,
+        }
+    }
+
+    /// The same kind of backend, with its files under `dir/name`
+    ///
+    /// For servers that keep the state of several identities apart.
+    pub fn scoped(&self, name: &str) -> Self {
+        match self {
+            StorageBackend::File { dir } => StorageBackend::File { dir: dir.join(name) },
+            StorageBackend::Sqlite { path } => {
+                let file_name = path.file_name().unwrap_or("mls_state.db".as_ref());
+                let dir = self.dir().unwrap_or(Path::new("."));
+                StorageBackend::Sqlite { path: dir.join(name).join(file_name) }
+            }
+            StorageBackend::Memory => StorageBackend::Memory,
+        }
+    }
+
+    /// Check the backend can be written to, creating its directory
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let Some(dir) = self.dir() else {
+            return Ok(());
+        };
+        let unwritable = |e: std::io::Error| {
+            ConfigError::ValidationFailed(format!(
+                "store.mls_backend: {:?} is not writable: {}",
+                dir, e
+            ))
+        };
+
+        std::fs::create_dir_all(dir).map_err(unwritable)?;
+        let probe = dir.join(".spacepanda-write-test");
+        std::fs::write(&probe, b"").map_err(unwritable)?;
+        let _ = std::fs::remove_file(&probe);
+
+        if let StorageBackend::Sqlite { path } = self {
+            if path.exists() {
+                std::fs::OpenOptions::new().append(true).open(path).map_err(|e| {
+                    ConfigError::ValidationFailed(format!(
+                        "store.mls_backend: {:?} is not writable: {}",
+                        path, e
+                    ))
+                })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for StorageBackend {
+    type Err = String;
+
+    /// Parses `memory`, `file:<dir>` or `sqlite:<path>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "memory" {
+            return Ok(StorageBackend::Memory);
+        }
+        match s.split_once(':') {
+            Some(("file", dir)) if !dir.is_empty() => Ok(StorageBackend::File { dir: dir.into() }),
+            Some(("sqlite", path)) if !path.is_empty() => {
+                Ok(StorageBackend::Sqlite { path: path.into() })
+            }
+            _ => Err(format!("expected memory, file:<dir> or sqlite:<path>, got '{}'", s)),
+        }
+    }
+}
+
+fn default_session_ttl() -> Duration {
+    Duration::from_secs(3600)
+}
+
+fn default_session_idle_timeout() -> Duration {
+    Duration::from_secs(30 * 60)
+}
+
+fn default_attachment_cache_quota() -> u64 {
+    1024 * 1024 * 1024 // 1 GB
+}
+
+fn default_max_attachment_size() -> u64 {
+    100 * 1024 * 1024 // 100 MB
+}
+
+fn default_free_space_margin() -> u64 {
+    64 * 1024 * 1024 // 64 MB
+}
+
+fn default_replica_silence_horizon() -> Duration {
+    Duration::from_secs(14 * 24 * 3600) // 14 days
+}
+
+/// S3-compatible remote backup settings
+///
+/// Credentials are best given as keystore references, e.g.
+/// `secret_access_key = "keystore:backup_secret_key"`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RemoteBackupConfig {
+    /// Service endpoint, e.g. `https://s3.eu-west-1.amazonaws.com`
+    pub endpoint: String,
+
+    /// Signing region
+    #[serde(default = "default_backup_region")]
+    pub region: String,
+
+    /// Bucket name
+    pub bucket: String,
+
+    /// Key prefix inside the bucket
+    #[serde(default)]
+    pub prefix: String,
+
+    /// Access key ID
+    pub access_key_id: String,
+
+    /// Secret access key
+    pub secret_access_key: String,
+
+    /// Number of snapshots kept on the remote after a push
+    #[serde(default = "default_backup_keep_last")]
+    pub keep_last: usize,
+}
+
+impl std::fmt::Debug for RemoteBackupConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteBackupConfig")
+            .field("endpoint", &self.endpoint)
+            .field("region", &self.region)
+            .field("bucket", &self.bucket)
+            .field("prefix", &self.prefix)
+            .field("access_key_id", &self.access_key_id)
+            .field("secret_access_key", &REDACTED)
+            .field("keep_last", &self.keep_last)
+            .finish()
+    }
+}
+
+fn default_backup_region() -> String {
+    "us-east-1".to_string()
+}
+
+fn default_backup_keep_last() -> usize {
+    7
 }
 
 /// Logging configuration
@@ -152,6 +398,201 @@ pub struct MetricsConfig {
 
     /// OpenTelemetry endpoint
     pub otlp_endpoint: Option<String>,
+
+    /// Message delivery latency objective
+    #[serde(default)]
+    pub delivery_slo: DeliverySloConfig,
+}
+
+/// Metadata privacy settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrivacyConfig {
+    /// Smallest size messages are padded to; one of the padding buckets
+    pub padding_bucket: usize,
+
+    /// How messages in channels created here are padded
+    pub padding: PaddingStrategy,
+}
+
+impl Default for PrivacyConfig {
+    fn default() -> Self {
+        Self {
+            padding_bucket: crate::core_mls::padding::PADDING_BUCKETS[0],
+            padding: PaddingStrategy::default(),
+        }
+    }
+}
+
+impl PrivacyConfig {
+    /// Padding strategy with `padding_bucket` as the smallest bucket
+    pub fn padding_strategy(&self) -> PaddingStrategy {
+        self.padding.clone().with_min_bucket(self.padding_bucket)
+    }
+}
+
+/// How a channel's messages are notified
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationMode {
+    /// A notification for every message
+    #[default]
+    All,
+
+    /// Only messages mentioning us
+    MentionsOnly,
+
+    /// Messages are counted into the daily digest instead
+    Digest,
+
+    /// No notifications at all
+    Mute,
+}
+
+/// Notification policy
+///
+/// `webhook_url` may carry a token and is best given as a keystore reference.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationConfig {
+    /// Mode of channels not listed in `channels`
+    pub default_mode: NotificationMode,
+
+    /// Per-channel modes, by channel ID
+    pub channels: BTreeMap<String, NotificationMode>,
+
+    /// Local time of day the digest is sent, as `HH:MM`
+    pub digest_at: String,
+
+    /// URL notifications and digests are POSTed to as JSON
+    pub webhook_url: Option<String>,
+}
+
+impl std::fmt::Debug for NotificationConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NotificationConfig")
+            .field("default_mode", &self.default_mode)
+            .field("channels", &self.channels)
+            .field("digest_at", &self.digest_at)
+            .field("webhook_url", &self.webhook_url.as_ref().map(|_| REDACTED))
+            .finish()
+    }
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            default_mode: NotificationMode::All,
+            channels: BTreeMap::new(),
+            digest_at: "09:00".to_string(),
+            webhook_url: None,
+        }
+    }
+}
+
+impl NotificationConfig {
+    /// Mode of a channel
+    pub fn mode_for(&self, channel_id: &str) -> NotificationMode {
+        self.channels.get(channel_id).copied().unwrap_or(self.default_mode)
+    }
+
+    /// Time of day the digest is sent
+    pub fn digest_time(&self) -> Result<NaiveTime, ConfigError> {
+        NaiveTime::parse_from_str(&self.digest_at, "%H:%M").map_err(|e| {
+            ConfigError::ValidationFailed(format!(
+                "notifications.digest_at must be HH:MM, got '{}': {}",
+                self.digest_at, e
+            ))
+        })
+    }
+}
+
+/// Delivery latency service-level objective
+///
+/// The objective is met while at least `objective` of the deliveries in the
+/// rolling `window` took no longer than `target_latency` (the defaults read
+/// "p95 < 5s"). A channel is flagged after missing it for `sustain`, and
+/// cleared once it has reached `recovery_objective` for `sustain` again.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeliverySloConfig {
+    /// Latency a delivery must stay within
+    #[serde(with = "humantime_serde")]
+    pub target_latency: Duration,
+
+    /// Share of deliveries that must meet the target (e.g. 0.95 for p95)
+    pub objective: f64,
+
+    /// Share needed to clear an alert; above `objective` so it doesn't flap
+    pub recovery_objective: f64,
+
+    /// How long compliance must stay past a threshold before the state changes
+    #[serde(with = "humantime_serde")]
+    pub sustain: Duration,
+
+    /// Rolling window latencies are kept for
+    #[serde(with = "humantime_serde")]
+    pub window: Duration,
+
+    /// Deliveries needed in the window before compliance is judged
+    pub min_samples: usize,
+
+    /// How long to wait for a delivery receipt before giving up on it
+    #[serde(with = "humantime_serde")]
+    pub receipt_timeout: Duration,
+}
+
+impl Default for DeliverySloConfig {
+    fn default() -> Self {
+        Self {
+            target_latency: Duration::from_secs(5),
+            objective: 0.95,
+            recovery_objective: 0.98,
+            sustain: Duration::from_secs(5 * 60),
+            window: Duration::from_secs(15 * 60),
+            min_samples: 20,
+            receipt_timeout: Duration::from_secs(2 * 60),
+        }
+    }
+}
+
+impl DeliverySloConfig {
+    /// Set the latency target and the share of deliveries that must meet it
+    pub fn with_target(mut self, target_latency: Duration, objective: f64) -> Self {
+        self.target_latency = target_latency;
+        self.objective = objective;
+        self
+    }
+
+    /// Set the share needed to clear an alert
+    pub fn with_recovery_objective(mut self, recovery_objective: f64) -> Self {
+        self.recovery_objective = recovery_objective;
+        self
+    }
+
+    /// Set how long a breach or recovery must last
+    pub fn with_sustain(mut self, sustain: Duration) -> Self {
+        self.sustain = sustain;
+        self
+    }
+
+    /// Set the rolling window
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Set the deliveries needed before compliance is judged
+    pub fn with_min_samples(mut self, min_samples: usize) -> Self {
+        self.min_samples = min_samples;
+        self
+    }
+
+    /// Set the receipt timeout
+    pub fn with_receipt_timeout(mut self, receipt_timeout: Duration) -> Self {
+        self.receipt_timeout = receipt_timeout;
+        self
+    }
 }
 
 impl Default for Config {
@@ -163,6 +604,9 @@ impl Default for Config {
             logging: LoggingConfig::default(),
             metrics: MetricsConfig::default(),
             features: FeatureFlags::default(),
+            privacy: PrivacyConfig::default(),
+            notifications: NotificationConfig::default(),
+            resolved_secrets: Vec::new(),
         }
     }
 }
@@ -177,6 +621,8 @@ impl Default for ServerConfig {
             enable_tls: false,
             tls_cert_path: None,
             tls_key_path: None,
+            session_ttl: default_session_ttl(),
+            session_idle_timeout: default_session_idle_timeout(),
         }
     }
 }
@@ -190,6 +636,7 @@ impl Default for DhtConfig {
             sync_interval: Duration::from_secs(60),
             peer_timeout: Duration::from_secs(300),
             bootstrap_peers: vec![],
+            private_lookups: BTreeMap::new(),
         }
     }
 }
@@ -203,6 +650,13 @@ impl Default for StoreConfig {
             max_snapshot_size: 100 * 1024 * 1024, // 100 MB
             enable_compression: true,
             tombstone_cleanup_interval: Duration::from_secs(3600),
+            replica_silence_horizon: default_replica_silence_horizon(),
+            attachment_cache_quota: default_attachment_cache_quota(),
+            max_attachment_size: default_max_attachment_size(),
+            free_space_margin: default_free_space_margin(),
+            remote_backup: None,
+            attestation: false,
+            mls_backend: None,
         }
     }
 }
@@ -228,6 +682,7 @@ impl Default for MetricsConfig {
             enable_prometheus: true,
             enable_opentelemetry: false,
             otlp_endpoint: None,
+            delivery_slo: DeliverySloConfig::default(),
         }
     }
 }
@@ -237,7 +692,31 @@ impl Config {
     ///
     /// Environment variables follow the pattern: SPACEPANDA_<SECTION>_<KEY>
     /// Example: SPACEPANDA_SERVER_BIND_ADDRESS=0.0.0.0:8080
+    ///
+    /// # Errors
+    ///
+    /// `SecretError` if a value references a keystore secret; load those
+    /// with [`Config::from_env_with_keystore`]
     pub fn from_env() -> Result<Self, ConfigError> {
+        let config = Self::read_env()?;
+        config.reject_secret_references()?;
+        Ok(config)
+    }
+
+    /// Load configuration from file
+    ///
+    /// # Errors
+    ///
+    /// `SecretError` if a value references a keystore secret; load those
+    /// with [`Config::from_file_with_keystore`]
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Self, ConfigError> {
+        let config = Self::read_file(path)?;
+        config.reject_secret_references()?;
+        Ok(config)
+    }
+
+    /// Settings from environment variables, keystore references unresolved
+    fn read_env() -> Result<Self, ConfigError> {
         let mut config = Self::default();
 
         // Server config
@@ -256,6 +735,22 @@ impl Config {
                 .parse()
                 .map_err(|e| ConfigError::InvalidValue(format!("Invalid TLS flag: {}", e)))?;
         }
+        if let Ok(path) = env::var("SPACEPANDA_SERVER_TLS_CERT_PATH") {
+            config.server.tls_cert_path = Some(PathBuf::from(path));
+        }
+        if let Ok(path) = env::var("SPACEPANDA_SERVER_TLS_KEY_PATH") {
+            config.server.tls_key_path = Some(PathBuf::from(path));
+        }
+        if let Ok(ttl) = env::var("SPACEPANDA_SERVER_SESSION_TTL") {
+            config.server.session_ttl = humantime_serde::re::humantime::parse_duration(&ttl)
+                .map_err(|e| ConfigError::InvalidValue(format!("Invalid session TTL: {}", e)))?;
+        }
+        if let Ok(idle) = env::var("SPACEPANDA_SERVER_SESSION_IDLE_TIMEOUT") {
+            config.server.session_idle_timeout =
+                humantime_serde::re::humantime::parse_duration(&idle).map_err(|e| {
+                    ConfigError::InvalidValue(format!("Invalid session idle timeout: {}", e))
+                })?;
+        }
 
         // DHT config
         if let Ok(bucket_size) = env::var("SPACEPANDA_DHT_BUCKET_SIZE") {
@@ -278,6 +773,16 @@ impl Config {
                 .parse()
                 .map_err(|e| ConfigError::InvalidValue(format!("Invalid WAL flag: {}", e)))?;
         }
+        if let Ok(attestation) = env::var("SPACEPANDA_STORE_ATTESTATION") {
+            config.store.attestation = attestation.parse().map_err(|e| {
+                ConfigError::InvalidValue(format!("Invalid attestation flag: {}", e))
+            })?;
+        }
+        if let Ok(backend) = env::var("SPACEPANDA_STORE_MLS_BACKEND") {
+            config.store.mls_backend = Some(backend.parse().map_err(|e| {
+                ConfigError::InvalidValue(format!("Invalid MLS storage backend: {}", e))
+            })?);
+        }
 
         // Logging config
         if let Ok(level) = env::var("SPACEPANDA_LOG_LEVEL") {
@@ -300,13 +805,23 @@ impl Config {
                 ConfigError::InvalidValue(format!("Invalid metrics address: {}", e))
             })?;
         }
+        if let Ok(endpoint) = env::var("SPACEPANDA_METRICS_OTLP_ENDPOINT") {
+            config.metrics.otlp_endpoint = Some(endpoint);
+        }
+
+        // Privacy config
+        if let Ok(bucket) = env::var("SPACEPANDA_PRIVACY_PADDING_BUCKET") {
+            config.privacy.padding_bucket = bucket
+                .parse()
+                .map_err(|e| ConfigError::InvalidValue(format!("Invalid padding bucket: {}", e)))?;
+        }
 
         config.validate()?;
         Ok(config)
     }
 
-    /// Load configuration from file
-    pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Self, ConfigError> {
+    /// Settings from a file, keystore references unresolved
+    fn read_file(path: impl AsRef<std::path::Path>) -> Result<Self, ConfigError> {
         let contents =
             std::fs::read_to_string(path).map_err(|e| ConfigError::FileReadError(e.to_string()))?;
 
@@ -326,6 +841,12 @@ impl Config {
             ));
         }
 
+        if self.server.session_ttl.is_zero() || self.server.session_idle_timeout.is_zero() {
+            return Err(ConfigError::ValidationFailed(
+                "session_ttl and session_idle_timeout must be greater than 0".to_string(),
+            ));
+        }
+
         if self.server.enable_tls {
             if self.server.tls_cert_path.is_none() || self.server.tls_key_path.is_none() {
                 return Err(ConfigError::ValidationFailed(
@@ -347,6 +868,15 @@ impl Config {
             ));
         }
 
+        for (namespace, relay_hops) in &self.dht.private_lookups {
+            if !(1..=2).contains(relay_hops) {
+                return Err(ConfigError::ValidationFailed(format!(
+                    "private lookups for '{}' must use 1 or 2 relays",
+                    namespace
+                )));
+            }
+        }
+
         // Validate store config
         if self.store.max_snapshot_size == 0 {
             return Err(ConfigError::ValidationFailed(
@@ -354,6 +884,49 @@ impl Config {
             ));
         }
 
+        if let Some(backend) = &self.store.mls_backend {
+            backend.validate()?;
+        }
+
+        if let Some(backup) = &self.store.remote_backup {
+            if backup.keep_last == 0 {
+                return Err(ConfigError::ValidationFailed(
+                    "remote_backup.keep_last must be greater than 0".to_string(),
+                ));
+            }
+        }
+
+        // Validate metrics config
+        let slo = &self.metrics.delivery_slo;
+        if !(slo.objective > 0.0 && slo.objective <= 1.0) {
+            return Err(ConfigError::ValidationFailed(
+                "delivery_slo.objective must be in (0, 1]".to_string(),
+            ));
+        }
+
+        if !(slo.objective..=1.0).contains(&slo.recovery_objective) {
+            return Err(ConfigError::ValidationFailed(
+                "delivery_slo.recovery_objective must be between objective and 1".to_string(),
+            ));
+        }
+
+        // Validate privacy config
+        self.privacy
+            .padding
+            .validate()
+            .map_err(|e| ConfigError::ValidationFailed(format!("privacy.padding: {}", e)))?;
+        if let PaddingStrategy::Buckets { ref sizes } = self.privacy.padding {
+            if !sizes.contains(&self.privacy.padding_bucket) {
+                return Err(ConfigError::ValidationFailed(format!(
+                    "privacy.padding_bucket must be one of {:?}",
+                    sizes
+                )));
+            }
+        }
+
+        // Validate notification config
+        self.notifications.digest_time()?;
+
         // Validate logging config
         let valid_levels = ["trace", "debug", "info", "warn", "error"];
         if !valid_levels.contains(&self.logging.level.as_str()) {
@@ -367,9 +940,11 @@ impl Config {
     }
 
     /// Save configuration to file
+    ///
+    /// Values resolved from the keystore are written back as their references.
     pub fn save_to_file(&self, path: impl AsRef<std::path::Path>) -> Result<(), ConfigError> {
-        let contents =
-            toml::to_string_pretty(self).map_err(|e| ConfigError::SerializeError(e.to_string()))?;
+        let contents = toml::to_string_pretty(&self.unresolved())
+            .map_err(|e| ConfigError::SerializeError(e.to_string()))?;
 
         std::fs::write(path, contents).map_err(|e| ConfigError::FileWriteError(e.to_string()))?;
 
@@ -404,6 +979,77 @@ mod tests {
         config = Config::default();
         config.dht.bucket_size = 0;
         assert!(config.validate().is_err());
+
+        // Test padding bucket that isn't a bucket size
+        config = Config::default();
+        config.privacy.padding_bucket = 1000;
+        assert!(config.validate().is_err());
+        config.privacy.padding_bucket = 1024;
+        assert!(config.validate().is_ok());
+
+        // Test digest time that isn't HH:MM
+        config = Config::default();
+        config.notifications.digest_at = "9am".to_string();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_mls_backend_validated_on_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+
+        let mut config = Config::default();
+        config.store.mls_backend =
+            Some(StorageBackend::Sqlite { path: dir.path().join("mls").join("mls_state.db") });
+        config.save_to_file(&path).unwrap();
+        let loaded = Config::from_file(&path).unwrap();
+        assert_eq!(loaded.store.mls_backend, config.store.mls_backend);
+        assert!(dir.path().join("mls").is_dir());
+
+        // A directory that can't be created fails at load, not on first use
+        let blocker = dir.path().join("not-a-dir");
+        std::fs::write(&blocker, b"").unwrap();
+        config.store.mls_backend = Some(StorageBackend::File { dir: blocker.join("mls") });
+        config.save_to_file(&path).unwrap();
+        assert!(matches!(Config::from_file(&path), Err(ConfigError::ValidationFailed(_))));
+    }
+
+    #[test]
+    fn test_parse_mls_backend() {
+        assert_eq!("memory".parse::<StorageBackend>().unwrap(), StorageBackend::Memory);
+        assert_eq!(
+            "sqlite:/var/lib/spacepanda/mls.db".parse::<StorageBackend>().unwrap(),
+            StorageBackend::Sqlite { path: "/var/lib/spacepanda/mls.db".into() }
+        );
+        assert_eq!(
+            "file:mls".parse::<StorageBackend>().unwrap().scoped("alice"),
+            StorageBackend::File { dir: "mls/alice".into() }
+        );
+        assert!("sqlite:".parse::<StorageBackend>().is_err());
+        assert!("postgres:db".parse::<StorageBackend>().is_err());
+    }
+
+    #[test]
+    fn test_padding_validated_on_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+
+        let mut config = Config::default();
+        config.privacy.padding = PaddingStrategy::Buckets { sizes: vec![512, 2048] };
+        config.privacy.padding_bucket = 512;
+        config.save_to_file(&path).unwrap();
+        let loaded = Config::from_file(&path).unwrap();
+        assert_eq!(loaded.privacy.padding, config.privacy.padding);
+
+        // Sizes out of order are rejected when the file is loaded
+        config.privacy.padding = PaddingStrategy::Buckets { sizes: vec![2048, 512] };
+        config.save_to_file(&path).unwrap();
+        assert!(matches!(Config::from_file(&path), Err(ConfigError::ValidationFailed(_))));
+
+        // The smallest bucket only applies to bucketed padding
+        config.privacy.padding = PaddingStrategy::Padme;
+        config.privacy.padding_bucket = 1000;
+        assert!(config.validate().is_ok());
     }
 
     #[test]
@@ -416,4 +1062,125 @@ mod tests {
         config.logging.level = "debug".to_string();
         assert!(config.validate().is_ok());
     }
+
+    #[test]
+    fn test_resolve_secret_references() {
+        use crate::core_identity::keystore::{memory_keystore::MemoryKeystore, Keystore};
+
+        let keystore = MemoryKeystore::new();
+        keystore.put_secret("otlp_endpoint", b"https://user:pw@otlp.example").unwrap();
+
+        let mut config = Config::default();
+        config.metrics.otlp_endpoint = Some("keystore:otlp_endpoint".to_string());
+
+        let refs = config.secret_references().unwrap();
+        assert_eq!(refs, vec![("metrics.otlp_endpoint".to_string(), "otlp_endpoint".to_string())]);
+
+        config.resolve_secrets(&keystore).unwrap();
+        assert_eq!(config.metrics.otlp_endpoint.as_deref(), Some("https://user:pw@otlp.example"));
+        assert_eq!(config.resolved_secret_paths(), ["metrics.otlp_endpoint".to_string()]);
+        assert!(config.secret_references().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_missing_secret_reference() {
+        use crate::core_identity::keystore::memory_keystore::MemoryKeystore;
+
+        let keystore = MemoryKeystore::new();
+        let mut config = Config::default();
+        config.metrics.otlp_endpoint = Some("keystore:does_not_exist".to_string());
+
+        let err = config.resolve_secrets(&keystore).unwrap_err();
+        match err {
+            ConfigError::MissingSecret(msg) => {
+                assert!(msg.contains("does_not_exist"));
+                assert!(msg.contains("metrics.otlp_endpoint"));
+            }
+            other => panic!("Expected MissingSecret, got {:?}", other),
+        }
+
+        // Config is left untouched on failure
+        assert_eq!(config.metrics.otlp_endpoint.as_deref(), Some("keystore:does_not_exist"));
+    }
+
+    #[test]
+    fn test_resolved_secrets_are_redacted() {
+        use crate::core_identity::keystore::{memory_keystore::MemoryKeystore, Keystore};
+
+        let keystore = MemoryKeystore::new();
+        keystore.put_secret("otlp", b"super-secret-token").unwrap();
+
+        let mut config = Config::default();
+        config.metrics.otlp_endpoint = Some("keystore:otlp".to_string());
+        config.resolve_secrets(&keystore).unwrap();
+
+        let redacted = config.redacted();
+        assert_eq!(redacted.metrics.otlp_endpoint.as_deref(), Some(REDACTED));
+        assert_eq!(redacted.server.bind_address, config.server.bind_address);
+
+        let debug_output = format!("{:?}", config);
+        assert!(!debug_output.contains("super-secret-token"));
+        assert!(debug_output.contains(REDACTED));
+    }
+
+    #[test]
+    fn test_from_file_with_keystore() {
+        use crate::core_identity::keystore::{memory_keystore::MemoryKeystore, Keystore};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+
+        let mut config = Config::default();
+        config.metrics.otlp_endpoint = Some("keystore:otlp".to_string());
+        config.save_to_file(&path).unwrap();
+
+        // The file on disk only holds the reference
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("keystore:otlp"));
+
+        let keystore = MemoryKeystore::new();
+        keystore.put_secret("otlp", b"http://collector:4317").unwrap();
+
+        let loaded = Config::from_file_with_keystore(&path, &keystore).unwrap();
+        assert_eq!(loaded.metrics.otlp_endpoint.as_deref(), Some("http://collector:4317"));
+
+        // Loading without a keystore never hands out the bare reference
+        assert!(matches!(Config::from_file(&path), Err(ConfigError::SecretError(_))));
+    }
+
+    #[test]
+    fn test_resolved_config_saves_references() {
+        use crate::core_identity::keystore::{memory_keystore::MemoryKeystore, Keystore};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+
+        let keystore = MemoryKeystore::new();
+        keystore.put_secret("webhook", b"https://hooks.example/T0KEN").unwrap();
+
+        let mut config = Config::default();
+        config.notifications.webhook_url = Some("keystore:webhook".to_string());
+        config.save_to_file(&path).unwrap();
+
+        let loaded = Config::from_file_with_keystore(&path, &keystore).unwrap();
+        assert_eq!(
+            loaded.notifications.webhook_url.as_deref(),
+            Some("https://hooks.example/T0KEN")
+        );
+
+        loaded.save_to_file(&path).unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains("T0KEN"));
+        assert!(contents.contains("keystore:webhook"));
+    }
+
+    #[test]
+    fn test_literal_webhook_url_is_redacted() {
+        let mut config = Config::default();
+        config.notifications.webhook_url = Some("https://hooks.example/T0KEN".to_string());
+
+        assert_eq!(config.redacted().notifications.webhook_url.as_deref(), Some(REDACTED));
+        assert!(!format!("{:?}", config).contains("T0KEN"));
+        assert!(!format!("{:?}", config.notifications).contains("T0KEN"));
+    }
 }
@@ -0,0 +1,241 @@
+//! Secret references in configuration values
+//!
+//! Any string-valued setting may be written as `"keystore:<name>"` instead of
+//! a literal value. References are resolved against the keystore's generic
+//! secrets store when the configuration is loaded, so the TOML file and the
+//! environment never carry the plaintext:
+//!
+//! ```toml
+//! [metrics]
+//! otlp_endpoint = "keystore:otlp_endpoint"
+//! ```
+//!
+//! Only the `*_with_keystore` loaders resolve references; [`Config::from_file`]
+//! and [`Config::from_env`] refuse a configuration that has any, so a
+//! reference is never used as if it were the value.
+//!
+//! Values that were resolved from the keystore are remembered so that
+//! [`Config::redacted`] (and the `Debug` output of [`Config`]) can mask them,
+//! and so that [`Config::save_to_file`] writes the reference back instead of
+//! the plaintext. Settings listed in [`SECRET_SETTINGS`] are masked even when
+//! they are given literally.
+
+use super::{Config, ConfigError};
+use crate::core_identity::keystore::{Keystore, KeystoreError};
+use toml::Value;
+
+/// Prefix marking a configuration value as a keystore reference
+pub const SECRET_REF_PREFIX: &str = "keystore:";
+
+/// Placeholder shown instead of resolved secret values
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Settings that always hold credentials, masked even when given literally
+pub const SECRET_SETTINGS: &[&str] =
+    &["store.remote_backup.secret_access_key", "notifications.webhook_url"];
+
+/// Extract the secret name from a `keystore:<name>` reference
+pub fn parse_secret_ref(value: &str) -> Option<&str> {
+    value.strip_prefix(SECRET_REF_PREFIX)
+}
+
+impl Config {
+    /// Load configuration from file and resolve keystore references
+    pub fn from_file_with_keystore(
+        path: impl AsRef<std::path::Path>,
+        keystore: &dyn Keystore,
+    ) -> Result<Self, ConfigError> {
+        let mut config = Self::read_file(path)?;
+        config.resolve_secrets(keystore)?;
+        Ok(config)
+    }
+
+    /// Load configuration from environment variables and resolve keystore references
+    pub fn from_env_with_keystore(keystore: &dyn Keystore) -> Result<Self, ConfigError> {
+        let mut config = Self::read_env()?;
+        config.resolve_secrets(keystore)?;
+        Ok(config)
+    }
+
+    /// List all unresolved secret references as `(setting path, secret name)` pairs
+    pub fn secret_references(&self) -> Result<Vec<(String, String)>, ConfigError> {
+        let value = self.to_value()?;
+        let mut refs = Vec::new();
+        visit_strings(&value, &mut Vec::new(), &mut |path, s| {
+            if let Some(name) = parse_secret_ref(s) {
+                refs.push((path.join("."), name.to_string()));
+            }
+        });
+        Ok(refs)
+    }
+
+    /// Fail if any value is still a keystore reference
+    pub(super) fn reject_secret_references(&self) -> Result<(), ConfigError> {
+        let refs = self.secret_references()?;
+        if refs.is_empty() {
+            return Ok(());
+        }
+
+        let paths: Vec<String> = refs.into_iter().map(|(path, _)| path).collect();
+        Err(ConfigError::SecretError(format!(
+            "{} reference keystore secrets; load the configuration with a keystore",
+            paths.join(", ")
+        )))
+    }
+
+    /// Replace every `keystore:<name>` value with the secret stored under `name`
+    ///
+    /// All references are checked before anything is substituted, so a missing
+    /// secret leaves the configuration untouched and the error names every
+    /// missing entry at once.
+    pub fn resolve_secrets(&mut self, keystore: &dyn Keystore) -> Result<(), ConfigError> {
+        let refs = self.secret_references()?;
+        if refs.is_empty() {
+            return Ok(());
+        }
+
+        let mut resolved = Vec::with_capacity(refs.len());
+        let mut missing = Vec::new();
+        for (path, name) in &refs {
+            match keystore.get_secret(name) {
+                Ok(bytes) => {
+                    let secret = String::from_utf8(bytes).map_err(|_| {
+                        ConfigError::SecretError(format!("Secret '{}' is not valid UTF-8", name))
+                    })?;
+                    resolved.push((path.clone(), secret));
+                }
+                Err(KeystoreError::NotFound(_)) => {
+                    missing.push(format!("{} (referenced by {})", name, path));
+                }
+                Err(e) => {
+                    return Err(ConfigError::SecretError(format!(
+                        "Failed to read secret '{}': {}",
+                        name, e
+                    )));
+                }
+            }
+        }
+
+        if !missing.is_empty() {
+            return Err(ConfigError::MissingSecret(missing.join(", ")));
+        }
+
+        let mut value = self.to_value()?;
+        for (path, secret) in &resolved {
+            set_string(&mut value, path, secret.clone());
+        }
+
+        let mut resolved_refs = std::mem::take(&mut self.resolved_secrets);
+        resolved_refs.extend(refs);
+
+        *self = Self::from_value(value)?;
+        self.resolved_secrets = resolved_refs;
+        self.validate()
+    }
+
+    /// Setting paths whose values were resolved from the keystore
+    pub fn resolved_secret_paths(&self) -> Vec<String> {
+        self.resolved_secrets.iter().map(|(path, _)| path.clone()).collect()
+    }
+
+    /// Copy of this configuration with all secret values masked
+    ///
+    /// Masks keystore-resolved values and every setting in [`SECRET_SETTINGS`].
+    /// Use this for anything that leaves the process: diagnostics, config
+    /// dumps, or API responses.
+    pub fn redacted(&self) -> Self {
+        let paths = self
+            .resolved_secrets
+            .iter()
+            .map(|(path, _)| path.as_str())
+            .chain(SECRET_SETTINGS.iter().copied());
+        self.with_values(paths.map(|path| (path, REDACTED.to_string())))
+    }
+
+    /// Copy of this configuration with resolved values turned back into references
+    ///
+    /// This is what gets written to disk, so a configuration loaded through a
+    /// keystore never persists the plaintext it was resolved to.
+    pub(super) fn unresolved(&self) -> Self {
+        let refs = self
+            .resolved_secrets
+            .iter()
+            .map(|(path, name)| (path.as_str(), format!("{}{}", SECRET_REF_PREFIX, name)));
+        self.with_values(refs)
+    }
+
+    /// Copy of this configuration with the strings at the given paths replaced
+    fn with_values<'a>(&self, values: impl Iterator<Item = (&'a str, String)>) -> Self {
+        let replaced = self.to_value().ok().and_then(|mut value| {
+            for (path, new_value) in values {
+                set_string(&mut value, path, new_value);
+            }
+            Self::from_value(value).ok()
+        });
+
+        match replaced {
+            Some(mut config) => {
+                config.resolved_secrets = self.resolved_secrets.clone();
+                config
+            }
+            // Never fall back to the config holding the plaintext
+            None => Self::default(),
+        }
+    }
+
+    fn to_value(&self) -> Result<Value, ConfigError> {
+        Value::try_from(self).map_err(|e| ConfigError::SerializeError(e.to_string()))
+    }
+
+    fn from_value(value: Value) -> Result<Self, ConfigError> {
+        value
+            .try_into()
+            .map_err(|e: toml::de::Error| ConfigError::ParseError(e.to_string()))
+    }
+}
+
+/// Walk every string leaf in a TOML value, passing its dotted path
+fn visit_strings(value: &Value, path: &mut Vec<String>, f: &mut dyn FnMut(&[String], &str)) {
+    match value {
+        Value::String(s) => f(path, s),
+        Value::Table(table) => {
+            for (key, child) in table {
+                path.push(key.clone());
+                visit_strings(child, path, f);
+                path.pop();
+            }
+        }
+        Value::Array(items) => {
+            for (index, child) in items.iter().enumerate() {
+                path.push(index.to_string());
+                visit_strings(child, path, f);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Overwrite the string at a dotted path (no-op if the path does not exist)
+fn set_string(value: &mut Value, path: &str, new_value: String) {
+    let mut current = value;
+    for segment in path.split('.') {
+        current = match current {
+            Value::Table(table) => match table.get_mut(segment) {
+                Some(child) => child,
+                None => return,
+            },
+            Value::Array(items) => {
+                match segment.parse::<usize>().ok().and_then(|i| items.get_mut(i)) {
+                    Some(child) => child,
+                    None => return,
+                }
+            }
+            _ => return,
+        };
+    }
+
+    if let Value::String(s) = current {
+        *s = new_value;
+    }
+}
@@ -0,0 +1,316 @@
+/*
+    Bootstrap - rejoining the DHT after a restart.
+
+    Responsibilities:
+    `bootstrap.rs` keeps the routing table's known-good peers in the local store and
+    uses them to rejoin the network on the next start, before falling back to the
+    configured bootstrap peers. A node whose bootstrap peers are all gone can still
+    get back in through anyone it knew.
+
+    Workflow:
+    1. Seed the routing table with every remembered peer
+    2. Ping the remembered peers seen recently, most reliable first
+    3. If none of them answered, ping the configured bootstrap peers
+    4. In the background, ping the remembered peers not seen for a while and evict
+       those that don't answer, so a long list of dead peers never delays startup
+    5. Copy the routing table back to the store on a timer and during shutdown
+
+    Inputs:
+    - peers remembered in the local store
+    - configured bootstrap addresses
+    - ping results from the PeerProber
+
+    Outputs:
+    - routing table entries
+    - the remembered peer list in the local store
+    - a BootstrapSummary for status reporting
+*/
+
+use async_trait::async_trait;
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+use tracing::{debug, info, warn};
+
+use super::routing_table::PeerContact;
+use super::{DhtKey, RoutingTable};
+use crate::core_store::store::errors::StoreResult;
+use crate::core_store::store::{KnownPeer, LocalStore};
+use crate::shutdown::ShutdownCoordinator;
+
+/// Peers that failed this many RPCs in a row aren't remembered
+const MAX_FAILED_RPCS: u32 = 3;
+
+/// Remembered peers unseen for longer than this are checked in the background
+const DEFAULT_STALE_AFTER: Duration = Duration::from_secs(24 * 3600);
+
+/// Pings peers by address
+#[async_trait]
+pub trait PeerProber: Send + Sync {
+    /// Contact `address` and return the node ID of whoever answered
+    ///
+    /// `expected` is the ID the peer had when last seen, if it is known.
+    async fn ping(&self, address: &str, expected: Option<DhtKey>) -> Result<DhtKey, String>;
+
+    /// Lookup key `id` gave in its last answer, if any
+    fn lookup_key(&self, _id: &DhtKey) -> Option<[u8; 32]> {
+        None
+    }
+}
+
+/// Outcome of a bootstrap
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BootstrapSummary {
+    /// Peers restored from the local store
+    pub restored: usize,
+    /// Peers pinged
+    pub contacted: usize,
+    /// Peers that answered
+    pub responded: usize,
+    /// Whether the configured bootstrap peers had to be used
+    pub used_bootstrap_peers: bool,
+    /// Peers in the routing table afterwards
+    pub table_size: usize,
+}
+
+/// Restores, refreshes and persists the routing table's peers
+pub struct PeerBootstrap {
+    /// Routing table shared with the DHT client and server
+    routing_table: Arc<Mutex<RoutingTable>>,
+    /// Store the peers are remembered in
+    store: Arc<LocalStore>,
+    /// Transport for pings
+    prober: Arc<dyn PeerProber>,
+    /// Configured bootstrap addresses
+    bootstrap_peers: Vec<String>,
+    /// Remembered peers unseen for longer than this are checked lazily
+    stale_after: Duration,
+    /// Timeout for each ping
+    rpc_timeout: Duration,
+}
+
+impl PeerBootstrap {
+    /// Create a bootstrapper for `routing_table`
+    pub fn new(
+        routing_table: Arc<Mutex<RoutingTable>>,
+        store: Arc<LocalStore>,
+        prober: Arc<dyn PeerProber>,
+        bootstrap_peers: Vec<String>,
+    ) -> Self {
+        PeerBootstrap {
+            routing_table,
+            store,
+            prober,
+            bootstrap_peers,
+            stale_after: DEFAULT_STALE_AFTER,
+            rpc_timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// Builder: set how long a remembered peer can go unseen before it's only checked lazily
+    pub fn with_stale_after(mut self, stale_after: Duration) -> Self {
+        self.stale_after = stale_after;
+        self
+    }
+
+    /// Builder: set the timeout for each ping
+    pub fn with_rpc_timeout(mut self, rpc_timeout: Duration) -> Self {
+        self.rpc_timeout = rpc_timeout;
+        self
+    }
+
+    /// Get the routing table the peers go into
+    pub fn routing_table(&self) -> Arc<Mutex<RoutingTable>> {
+        self.routing_table.clone()
+    }
+
+    /// Seed the routing table with the remembered peers, returning how many were added
+    pub async fn restore(&self) -> usize {
+        let mut table = self.routing_table.lock().await;
+        let mut restored = 0;
+        for known in self.store.known_peers().all() {
+            let contact = PeerContact {
+                id: DhtKey::from_bytes(known.node_id),
+                address: known.address,
+                last_seen: known.last_seen,
+                failed_rpcs: 0,
+                reliability: known.reliability,
+                rtt: None,
+                reputation: Default::default(),
+                lookup_key: None,
+            };
+            if table.get(&contact.id).is_none() && table.insert(contact).is_ok() {
+                restored += 1;
+            }
+        }
+        restored
+    }
+
+    /// Rejoin the network through remembered peers, or else the bootstrap peers
+    ///
+    /// Returns once the recently seen peers have been pinged. Peers not seen for a
+    /// while stay in the table and are checked by a background task.
+    pub async fn bootstrap(self: &Arc<Self>) -> BootstrapSummary {
+        let restored = self.restore().await;
+        let (fresh, stale) = self.remembered().await;
+
+        let mut summary = BootstrapSummary { restored, ..Default::default() };
+        let pings = fresh.iter().map(|p| self.ping(&p.address, Some(p.id)));
+        summary.contacted = fresh.len();
+        summary.responded = join_all(pings).await.into_iter().flatten().count();
+
+        if summary.responded == 0 && !self.bootstrap_peers.is_empty() {
+            summary.used_bootstrap_peers = true;
+            let pings = self.bootstrap_peers.iter().map(|address| self.ping(address, None));
+            summary.contacted += self.bootstrap_peers.len();
+            summary.responded += join_all(pings).await.into_iter().flatten().count();
+        }
+
+        if !stale.is_empty() {
+            let this = self.clone();
+            tokio::spawn(async move { this.check(stale).await });
+        }
+
+        summary.table_size = self.routing_table.lock().await.size();
+        info!(
+            restored = summary.restored,
+            contacted = summary.contacted,
+            responded = summary.responded,
+            table_size = summary.table_size,
+            "DHT bootstrap finished"
+        );
+        summary
+    }
+
+    /// Ping remembered peers not seen for a while, evicting those that don't answer
+    ///
+    /// Returns how many were evicted.
+    pub async fn check_stale(&self) -> usize {
+        let (_, stale) = self.remembered().await;
+        self.check(stale).await
+    }
+
+    /// Copy the known-good peers in the routing table to the store
+    pub async fn persist(&self) -> StoreResult<usize> {
+        let peers: Vec<KnownPeer> = self
+            .routing_table
+            .lock()
+            .await
+            .all_peers()
+            .into_iter()
+            .filter(|p| p.failed_rpcs < MAX_FAILED_RPCS)
+            .map(|p| KnownPeer {
+                node_id: *p.id.as_bytes(),
+                address: p.address,
+                last_seen: p.last_seen,
+                reliability: p.reliability,
+            })
+            .collect();
+        let count = peers.len();
+        self.store.known_peers().replace(peers)?;
+        debug!("Remembered {} DHT peers", count);
+        Ok(count)
+    }
+
+    /// Persist the peers every `period`
+    pub fn spawn_persist(self: &Arc<Self>, period: Duration) -> JoinHandle<()> {
+        let this = self.clone();
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(period);
+            tick.tick().await;
+            loop {
+                tick.tick().await;
+                if let Err(e) = this.persist().await {
+                    warn!("Failed to remember DHT peers: {}", e);
+                }
+            }
+        })
+    }
+
+    /// Persist the peers once shutdown starts
+    ///
+    /// Registers with the coordinator, so shutdown waits for the write.
+    pub fn persist_on_shutdown(self: &Arc<Self>, shutdown: &ShutdownCoordinator) -> JoinHandle<()> {
+        let mut signal = shutdown.subscribe();
+        let guard = shutdown.register("dht peers");
+        let this = self.clone();
+        tokio::spawn(async move {
+            let _ = signal.recv().await;
+            if let Err(e) = this.persist().await {
+                warn!("Failed to remember DHT peers: {}", e);
+            }
+            guard.done();
+        })
+    }
+
+    /// Remembered peers in the table, split into recently seen (most reliable
+    /// first) and stale
+    async fn remembered(&self) -> (Vec<PeerContact>, Vec<PeerContact>) {
+        let stale_after = self.stale_after.as_secs();
+        let mut peers = self.routing_table.lock().await.all_peers();
+        peers.sort_by(|a, b| b.reliability.total_cmp(&a.reliability));
+        peers.into_iter().partition(|p| !p.is_stale(stale_after))
+    }
+
+    async fn check(&self, stale: Vec<PeerContact>) -> usize {
+        let pings = stale.iter().map(|p| self.ping(&p.address, Some(p.id)));
+        let results = join_all(pings).await;
+
+        let mut table = self.routing_table.lock().await;
+        let mut evicted = 0;
+        for (peer, result) in stale.iter().zip(results) {
+            if result.is_none() && table.remove(&peer.id) {
+                evicted += 1;
+            }
+        }
+        drop(table);
+
+        if evicted > 0 {
+            debug!("Evicted {} unreachable DHT peers", evicted);
+            if let Err(e) = self.persist().await {
+                warn!("Failed to remember DHT peers: {}", e);
+            }
+        }
+        evicted
+    }
+
+    /// Ping an address, recording the result in the routing table
+    async fn ping(&self, address: &str, expected: Option<DhtKey>) -> Option<DhtKey> {
+        let result = match timeout(self.rpc_timeout, self.prober.ping(address, expected)).await {
+            Ok(result) => result,
+            Err(_) => Err("RPC timeout".to_string()),
+        };
+
+        let mut table = self.routing_table.lock().await;
+        match result {
+            Ok(id) => {
+                // Someone else answers at this address now
+                if let Some(expected) = expected.filter(|expected| *expected != id) {
+                    table.remove(&expected);
+                }
+                if table.get(&id).is_some() {
+                    table.touch(&id);
+                } else {
+                    let mut contact = PeerContact::new(id, address.to_string());
+                    contact.touch();
+                    let _ = table.insert(contact);
+                }
+                if let Some(lookup_key) = self.prober.lookup_key(&id) {
+                    table.set_lookup_key(&id, lookup_key);
+                }
+                Some(id)
+            }
+            Err(e) => {
+                debug!("DHT peer {} didn't answer: {}", address, e);
+                if let Some(expected) = expected {
+                    table.mark_failed(&expected);
+                }
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,216 @@
+/*
+    ChannelFeed - per-channel log of application messages
+
+    Responsibilities:
+    `channel_feed.rs` lets every member of an MLS group append encrypted messages to a shared
+    log in the DHT, and lets members that were offline or on another machine catch up by
+    reading it from where they left off. Payloads are opaque here; callers store ciphertext.
+
+    Layout (per group):
+    - one entry per message, keyed by group ID and sequence number
+    - a head entry whose DHT sequence number is the next free slot
+
+    Writers claim a slot by storing into it only if it is still empty, so concurrent writers
+    end up in different slots. The head is only a hint: it can lag behind a writer that
+    stopped between claiming a slot and moving the head, and appends probe forward from it.
+    Entries carry the sender's message ID, so readers can drop an envelope that was stored
+    twice (e.g. an append retried after a timeout).
+
+    Inputs:
+    - append(group, message_id, payload)
+    - fetch(group, from_seq)
+
+    Outputs:
+    - entries in sequence order; missing slots are left out so readers can spot gaps
+*/
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::{DhtKey, DhtStorage, DhtValue};
+use crate::core_mls::types::GroupId;
+
+/// Namespace for channel feeds
+pub const CHANNEL_FEED_NAMESPACE: &str = "channel_feed";
+
+/// Default time an entry stays in the feed
+pub const DEFAULT_FEED_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Slots probed past the head before an append gives up
+const MAX_CLAIM_ATTEMPTS: u64 = 64;
+
+/// A message in a channel feed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelFeedEntry {
+    /// Position in the feed
+    pub seq: u64,
+    /// Sender-assigned message ID, the same for every copy of the message
+    pub message_id: String,
+    /// Payload as appended (ciphertext)
+    pub payload: Vec<u8>,
+}
+
+/// Per-channel message feeds stored in the DHT
+pub struct ChannelFeed {
+    storage: DhtStorage,
+    ttl: Duration,
+    /// Serializes local appends so they don't race each other for slots
+    append_lock: Mutex<()>,
+}
+
+impl ChannelFeed {
+    /// Create feeds backed by the given DHT storage
+    pub fn new(storage: DhtStorage) -> Self {
+        Self { storage, ttl: DEFAULT_FEED_TTL, append_lock: Mutex::new(()) }
+    }
+
+    /// Set how long entries stay in the feed
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// DHT key of a group's feed head, which also names the feed
+    pub fn head_key(group_id: &GroupId) -> DhtKey {
+        let name = format!("{}-head:{}", CHANNEL_FEED_NAMESPACE, group_id.to_hex());
+        DhtKey::hash(name.as_bytes())
+    }
+
+    fn entry_key(group_id: &GroupId, seq: u64) -> DhtKey {
+        let name = format!("{}:{}:{}", CHANNEL_FEED_NAMESPACE, group_id.to_hex(), seq);
+        DhtKey::hash(name.as_bytes())
+    }
+
+    /// Sequence number past the last entry known to be appended
+    pub fn head(&self, group_id: &GroupId) -> u64 {
+        self.storage
+            .get(&Self::head_key(group_id))
+            .map(|value| value.sequence)
+            .unwrap_or(0)
+    }
+
+    /// Append a message to a group's feed
+    ///
+    /// Returns the entry's sequence number.
+    pub fn append(
+        &self,
+        group_id: &GroupId,
+        message_id: &str,
+        payload: Vec<u8>,
+    ) -> Result<u64, String> {
+        self.append_with_ttl(group_id, message_id, payload, self.ttl)
+    }
+
+    /// Append a message that expires from the feed after `ttl`
+    ///
+    /// The head keeps the feed's own TTL, so a short-lived entry doesn't
+    /// take the rest of the feed with it.
+    pub fn append_with_ttl(
+        &self,
+        group_id: &GroupId,
+        message_id: &str,
+        payload: Vec<u8>,
+        ttl: Duration,
+    ) -> Result<u64, String> {
+        let _guard = self.append_lock.lock().unwrap_or_else(|e| e.into_inner());
+
+        let start = self.head(group_id);
+        for seq in start..start + MAX_CLAIM_ATTEMPTS {
+            let entry = ChannelFeedEntry {
+                seq,
+                message_id: message_id.to_string(),
+                payload: payload.clone(),
+            };
+            let data = bincode::serialize(&entry)
+                .map_err(|e| format!("Failed to serialize feed entry: {}", e))?;
+
+            // Every entry has the same DHT sequence number, so a store into a
+            // taken slot is rejected as stale and we move on to the next one
+            let value = DhtValue::new(data).with_ttl_duration(ttl);
+            if self.storage.put(Self::entry_key(group_id, seq), value).is_err() {
+                continue;
+            }
+
+            // Another writer may have moved the head further already
+            let head = DhtValue::new(Vec::new()).with_ttl_duration(self.ttl).with_sequence(seq + 1);
+            let _ = self.storage.put(Self::head_key(group_id), head);
+            return Ok(seq);
+        }
+
+        Err(format!("No free feed slot within {} of the head", MAX_CLAIM_ATTEMPTS))
+    }
+
+    /// Entries with `seq >= from_seq`, in sequence order
+    ///
+    /// Slots that are empty, expired or unreadable are left out.
+    pub fn fetch(&self, group_id: &GroupId, from_seq: u64) -> Vec<ChannelFeedEntry> {
+        (from_seq..self.head(group_id))
+            .filter_map(|seq| {
+                let value = self.storage.get(&Self::entry_key(group_id, seq)).ok()?;
+                bincode::deserialize(&value.data).ok()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_and_fetch() {
+        let feed = ChannelFeed::new(DhtStorage::new());
+        let group = GroupId::random();
+        let other = GroupId::random();
+
+        assert_eq!(feed.append(&group, "m0", b"zero".to_vec()).unwrap(), 0);
+        assert_eq!(feed.append(&group, "m1", b"one".to_vec()).unwrap(), 1);
+        feed.append(&other, "m9", b"other".to_vec()).unwrap();
+
+        let entries = feed.fetch(&group, 0);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].message_id, "m1");
+        assert_eq!(entries[1].payload, b"one");
+        assert_eq!(feed.fetch(&group, 1).len(), 1);
+        assert!(feed.fetch(&group, 2).is_empty());
+    }
+
+    #[test]
+    fn test_writers_sharing_storage_get_distinct_slots() {
+        let storage = DhtStorage::new();
+        let alice = ChannelFeed::new(storage.clone());
+        let bob = ChannelFeed::new(storage);
+        let group = GroupId::random();
+
+        alice.append(&group, "a", b"from alice".to_vec()).unwrap();
+        // Bob's head hint lags, as if he hadn't seen Alice's head update yet
+        let _ = bob.storage.delete(&ChannelFeed::head_key(&group));
+        assert_eq!(bob.append(&group, "b", b"from bob".to_vec()).unwrap(), 1);
+
+        let ids: Vec<_> = alice.fetch(&group, 0).into_iter().map(|e| e.message_id).collect();
+        assert_eq!(ids, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_expired_entries_are_skipped() {
+        let feed = ChannelFeed::new(DhtStorage::new()).with_ttl(Duration::ZERO);
+        let group = GroupId::random();
+        feed.append(&group, "m0", b"stale".to_vec()).unwrap();
+        assert!(feed.fetch(&group, 0).is_empty());
+    }
+
+    #[test]
+    fn test_short_lived_entries_keep_the_head() {
+        let feed = ChannelFeed::new(DhtStorage::new());
+        let group = GroupId::random();
+        feed.append_with_ttl(&group, "typing", b"gone".to_vec(), Duration::ZERO)
+            .unwrap();
+        feed.append(&group, "m1", b"kept".to_vec()).unwrap();
+
+        let ids: Vec<_> = feed.fetch(&group, 0).into_iter().map(|e| e.message_id).collect();
+        assert_eq!(ids, vec!["m1"]);
+        assert_eq!(feed.head(&group), 2);
+    }
+}
@@ -10,6 +10,8 @@
     ```
 
     it handles: timeouts, retries, and updating routing table with response quality.
+    Responses that don't fit the request count against the peer's reputation as invalid
+    messages, rather than as plain failures.
 
     Inputs:
     - API calls from dht_node
@@ -18,18 +20,75 @@
     outputs:
     - request to router
     - resolved responses -> search engine
+
+    Reads ask several replicas at once and wait for as many answers as the
+    caller's consistency level needs. The newest value wins, and replicas that
+    answered with nothing or an older value get the winner written back in the
+    background (read repair).
+
+    Reads in a namespace configured for private lookups go through PrivateLookup
+    instead, so the replicas never learn who asked. Those replicas are not
+    repaired, since writing back would tell them.
+
+    Blobs too large to store at the k closest nodes are announced instead:
+    `provide` leaves provider records there pointing back at this node, and
+    `find_providers` collects them so the blob can be fetched from a provider
+    directly. Records expire, so providers re-announce on a timer.
 */
 
+use async_trait::async_trait;
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::Mutex;
-use tokio::time::timeout;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, timeout};
+use tracing::debug;
 
 use super::message::{DhtMessage, FindValueResult, PeerInfo};
-use super::{DhtKey, DhtValue, RoutingTable};
+use super::private_lookup::{LookupPeer, PrivateLookup};
+use super::{DhtKey, DhtValue, LookupMode, RoutingTable};
 use crate::core_router::RouterHandle;
+use crate::metrics::record_counter;
+
+/// Replicas asked by a read unless configured otherwise
+const DEFAULT_READ_REPLICAS: usize = 3;
+
+/// Provider record lifetime unless configured otherwise (24 hours)
+const DEFAULT_PROVIDER_TTL: Duration = Duration::from_secs(86400);
+
+/// Carries requests to peers and brings back their responses
+#[async_trait]
+pub trait DhtTransport: Send + Sync {
+    /// Send `request` to `peer` and wait for its response
+    async fn request(&self, peer: DhtKey, request: DhtMessage) -> Result<DhtMessage, String>;
+}
+
+/// How many replicas must answer a read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsistencyLevel {
+    /// The first answer, for latency-sensitive reads like presence
+    One,
+    /// A majority of the replicas asked
+    Quorum,
+    /// Every replica asked
+    All,
+}
+
+impl ConsistencyLevel {
+    /// Answers needed out of `replicas`
+    pub fn required(self, replicas: usize) -> usize {
+        match self {
+            ConsistencyLevel::One => replicas.min(1),
+            ConsistencyLevel::Quorum => replicas / 2 + 1,
+            ConsistencyLevel::All => replicas,
+        }
+    }
+}
 
 /// DHT client for outbound RPC calls
+#[derive(Clone)]
 pub struct DhtClient {
     /// Local node ID
     local_id: DhtKey,
@@ -41,6 +100,18 @@ pub struct DhtClient {
     rpc_timeout: Duration,
     /// Request ID counter
     request_id_counter: Arc<Mutex<u64>>,
+    /// Replicas asked by each read
+    read_replicas: usize,
+    /// Transport for requests, in place of the router
+    transport: Option<Arc<dyn DhtTransport>>,
+    /// Address other peers reach this node at, given out in provider records
+    address: String,
+    /// How long provider records announced by this node live
+    provider_ttl: Duration,
+    /// Blobs this node provides, re-announced until dropped
+    provided: Arc<Mutex<HashSet<DhtKey>>>,
+    /// Private lookups, for namespaces configured to use them
+    private_lookup: Option<Arc<PrivateLookup>>,
 }
 
 impl DhtClient {
@@ -57,9 +128,45 @@ impl DhtClient {
             routing_table,
             rpc_timeout,
             request_id_counter: Arc::new(Mutex::new(0)),
+            read_replicas: DEFAULT_READ_REPLICAS,
+            transport: None,
+            address: local_id.to_string(),
+            provider_ttl: DEFAULT_PROVIDER_TTL,
+            provided: Arc::new(Mutex::new(HashSet::new())),
+            private_lookup: None,
         }
     }
 
+    /// Builder: set how many replicas each read asks
+    pub fn with_read_replicas(mut self, read_replicas: usize) -> Self {
+        self.read_replicas = read_replicas.max(1);
+        self
+    }
+
+    /// Builder: send requests through `transport` instead of the router
+    pub fn with_transport(mut self, transport: Arc<dyn DhtTransport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Builder: set the address advertised in provider records
+    pub fn with_address(mut self, address: String) -> Self {
+        self.address = address;
+        self
+    }
+
+    /// Builder: set how long announced provider records live
+    pub fn with_provider_ttl(mut self, ttl: Duration) -> Self {
+        self.provider_ttl = ttl;
+        self
+    }
+
+    /// Builder: read namespaces configured as private through `private_lookup`
+    pub fn with_private_lookup(mut self, private_lookup: Arc<PrivateLookup>) -> Self {
+        self.private_lookup = Some(private_lookup);
+        self
+    }
+
     /// Get next request ID
     async fn next_request_id(&self) -> u64 {
         let mut counter = self.request_id_counter.lock().await;
@@ -112,13 +219,20 @@ impl DhtClient {
 
         match result {
             Ok(Ok(response)) => {
-                if let DhtMessage::FindNodeResponse { nodes, .. } = response {
-                    // Update routing table on success
-                    self.routing_table.lock().await.touch(&peer_id);
-                    Ok(nodes)
-                } else {
-                    self.routing_table.lock().await.mark_failed(&peer_id);
-                    Err("Unexpected response type".to_string())
+                let mut table = self.routing_table.lock().await;
+                match response {
+                    // At most k nodes, answering this request
+                    DhtMessage::FindNodeResponse { nodes, request_id: answered, .. }
+                        if answered == request_id && nodes.len() <= table.k() =>
+                    {
+                        // Update routing table on success
+                        table.touch(&peer_id);
+                        Ok(nodes)
+                    }
+                    other => {
+                        table.mark_invalid(&peer_id);
+                        Err(format!("Invalid FIND_NODE response: {}", other.message_type()))
+                    }
                 }
             }
             Ok(Err(e)) => {
@@ -149,7 +263,7 @@ impl DhtClient {
                     self.routing_table.lock().await.touch(&peer_id);
                     Ok(result)
                 } else {
-                    self.routing_table.lock().await.mark_failed(&peer_id);
+                    self.routing_table.lock().await.mark_invalid(&peer_id);
                     Err("Unexpected response type".to_string())
                 }
             }
@@ -173,16 +287,20 @@ impl DhtClient {
 
         match result {
             Ok(Ok(response)) => {
-                if let DhtMessage::StoreAck { success, error, .. } = response {
+                if let DhtMessage::StoreAck { success, error, code, .. } = response {
                     if success {
                         self.routing_table.lock().await.touch(&peer_id);
                         Ok(())
+                    } else if code.is_some() {
+                        // A peer refusing over quota is alive, just full
+                        self.routing_table.lock().await.touch(&peer_id);
+                        Err(error.unwrap_or_else(|| "Store refused".to_string()))
                     } else {
                         self.routing_table.lock().await.mark_failed(&peer_id);
                         Err(error.unwrap_or_else(|| "Store failed".to_string()))
                     }
                 } else {
-                    self.routing_table.lock().await.mark_failed(&peer_id);
+                    self.routing_table.lock().await.mark_invalid(&peer_id);
                     Err("Unexpected response type".to_string())
                 }
             }
@@ -197,14 +315,365 @@ impl DhtClient {
         }
     }
 
+    /// Store `value` under `key` on the k closest known peers, for `ttl`
+    ///
+    /// Peers cap the TTL at their own `value_expiration`. Returns the peers that
+    /// stored it.
+    pub async fn put(
+        &self,
+        key: DhtKey,
+        value: DhtValue,
+        ttl: Duration,
+    ) -> Result<Vec<DhtKey>, String> {
+        let value = value.with_ttl_duration(ttl);
+        let closest = {
+            let table = self.routing_table.lock().await;
+            table.find_closest(&key, table.k())
+        };
+        if closest.is_empty() {
+            return Err("No peers to store on".to_string());
+        }
+
+        let mut stored = Vec::new();
+        let mut last_error = String::new();
+        for peer in closest {
+            match self.store(peer.id, key, value.clone()).await {
+                Ok(()) => stored.push(peer.id),
+                Err(e) => last_error = e,
+            }
+        }
+
+        if stored.is_empty() {
+            return Err(format!("No peer stored the value: {}", last_error));
+        }
+        Ok(stored)
+    }
+
+    /// Read the value under `key` from its closest replicas
+    ///
+    /// Asks up to `read_replicas` of them at once and waits for as many answers as
+    /// `consistency` needs, then returns the newest value among them. Expired copies
+    /// count as missing, even if the peer holding one hasn't swept it yet. Replicas
+    /// that answered with nothing or an older value are repaired in the background.
+    pub async fn get(
+        &self,
+        key: DhtKey,
+        consistency: ConsistencyLevel,
+    ) -> Result<Option<DhtValue>, String> {
+        self.read(key, consistency, None).await
+    }
+
+    /// Read the value under `key` in `namespace` from its closest replicas
+    ///
+    /// Like [`get`](Self::get), except that if the namespace is configured for
+    /// private lookups each replica is asked through relays. Only replicas and
+    /// relays whose lookup key is known take part.
+    pub async fn get_in(
+        &self,
+        namespace: &str,
+        key: DhtKey,
+        consistency: ConsistencyLevel,
+    ) -> Result<Option<DhtValue>, String> {
+        match &self.private_lookup {
+            Some(lookup) if lookup.lookup_mode(namespace) != LookupMode::Direct => {
+                self.read(key, consistency, Some((lookup, namespace))).await
+            }
+            _ => self.get(key, consistency).await,
+        }
+    }
+
+    /// Read `key` from its closest replicas, directly or through a private lookup
+    async fn read(
+        &self,
+        key: DhtKey,
+        consistency: ConsistencyLevel,
+        private: Option<(&PrivateLookup, &str)>,
+    ) -> Result<Option<DhtValue>, String> {
+        let (replicas, relays) = {
+            let table = self.routing_table.lock().await;
+            match private {
+                None => (table.find_closest(&key, self.read_replicas), Vec::new()),
+                Some(_) => {
+                    let replicas = table
+                        .find_closest(&key, table.k())
+                        .into_iter()
+                        .filter(|contact| contact.lookup_key.is_some())
+                        .take(self.read_replicas)
+                        .collect();
+                    let relays = table
+                        .all_peers()
+                        .into_iter()
+                        .filter_map(|contact| {
+                            Some(LookupPeer { id: contact.id, public_key: contact.lookup_key? })
+                        })
+                        .collect();
+                    (replicas, relays)
+                }
+            }
+        };
+        if replicas.is_empty() {
+            return Err("No peers to read from".to_string());
+        }
+        let needed = consistency.required(replicas.len());
+
+        let relays = &relays;
+        let mut pending: FuturesUnordered<_> = replicas
+            .iter()
+            .map(|peer| async move {
+                let result = match (private, peer.lookup_key) {
+                    (Some((lookup, namespace)), Some(public_key)) => {
+                        let storing_node = LookupPeer { id: peer.id, public_key };
+                        let lookup = lookup.lookup(namespace, key, &storing_node, relays);
+                        match timeout(self.rpc_timeout, lookup).await {
+                            Ok(result) => result,
+                            Err(_) => Err("RPC timeout".to_string()),
+                        }
+                    }
+                    _ => self.find_value(peer.id, key).await.map(|result| match result {
+                        FindValueResult::Found(value) => Some(value),
+                        FindValueResult::NotFound { .. } => None,
+                    }),
+                };
+                (peer.id, result)
+            })
+            .collect();
+        let mut answers: Vec<(DhtKey, Option<DhtValue>)> = Vec::new();
+        let mut last_error = String::new();
+
+        while let Some((peer, result)) = pending.next().await {
+            match result {
+                Ok(Some(value)) if !value.is_expired() => answers.push((peer, Some(value))),
+                Ok(_) => answers.push((peer, None)),
+                Err(e) => last_error = e,
+            }
+            // A level of One keeps waiting while every answer so far is empty
+            let found = answers.iter().any(|(_, value)| value.is_some());
+            if answers.len() >= needed && (found || consistency != ConsistencyLevel::One) {
+                break;
+            }
+        }
+
+        if answers.len() < needed {
+            return Err(format!(
+                "Only {} of {} replicas answered: {}",
+                answers.len(),
+                needed,
+                last_error
+            ));
+        }
+
+        let newest = answers
+            .iter()
+            .filter_map(|(_, value)| value.as_ref())
+            .max_by_key(|value| (value.sequence, value.timestamp))
+            .cloned();
+
+        if let Some(newest) = &newest {
+            let stale: Vec<DhtKey> = answers
+                .iter()
+                .filter(|(_, value)| value.as_ref() != Some(newest))
+                .map(|(peer, _)| *peer)
+                .collect();
+            if !stale.is_empty() && private.is_none() {
+                let client = self.clone();
+                let newest = newest.clone();
+                tokio::spawn(async move { client.repair(key, newest, stale).await });
+            }
+        }
+
+        Ok(newest)
+    }
+
+    /// Write `value` back to replicas that returned a stale copy or none
+    async fn repair(&self, key: DhtKey, value: DhtValue, stale: Vec<DhtKey>) {
+        for peer in stale {
+            match self.store(peer, key, value.clone()).await {
+                Ok(()) => record_counter("dht.read_repairs", 1),
+                Err(e) => debug!("Read repair of {} on {} failed: {}", key, peer, e),
+            }
+        }
+    }
+
+    /// Send ADD_PROVIDER request, announcing this node as a provider of `key`
+    pub async fn add_provider(&self, peer_id: DhtKey, key: DhtKey) -> Result<(), String> {
+        let request_id = self.next_request_id().await;
+        let msg = DhtMessage::AddProvider {
+            sender_id: self.local_id,
+            key,
+            provider: PeerInfo::new(self.local_id, self.address.clone()),
+            ttl: self.provider_ttl.as_secs(),
+            request_id,
+        };
+
+        let result = timeout(self.rpc_timeout, self.send_and_receive(peer_id, msg)).await;
+
+        match result {
+            Ok(Ok(DhtMessage::StoreAck { success: true, .. })) => {
+                self.routing_table.lock().await.touch(&peer_id);
+                Ok(())
+            }
+            Ok(Ok(DhtMessage::StoreAck { error, .. })) => {
+                Err(error.unwrap_or_else(|| "Add provider failed".to_string()))
+            }
+            Ok(Ok(_)) => {
+                self.routing_table.lock().await.mark_invalid(&peer_id);
+                Err("Unexpected response type".to_string())
+            }
+            Ok(Err(e)) => {
+                self.routing_table.lock().await.mark_failed(&peer_id);
+                Err(e)
+            }
+            Err(_) => {
+                self.routing_table.lock().await.mark_failed(&peer_id);
+                Err("RPC timeout".to_string())
+            }
+        }
+    }
+
+    /// Send GET_PROVIDERS request, returning the peer's providers of `key`
+    pub async fn get_providers(
+        &self,
+        peer_id: DhtKey,
+        key: DhtKey,
+    ) -> Result<Vec<PeerInfo>, String> {
+        let request_id = self.next_request_id().await;
+        let msg = DhtMessage::GetProviders { sender_id: self.local_id, key, request_id };
+
+        let result = timeout(self.rpc_timeout, self.send_and_receive(peer_id, msg)).await;
+
+        match result {
+            Ok(Ok(DhtMessage::GetProvidersResponse { providers, .. })) => {
+                self.routing_table.lock().await.touch(&peer_id);
+                Ok(providers)
+            }
+            Ok(Ok(_)) => {
+                self.routing_table.lock().await.mark_invalid(&peer_id);
+                Err("Unexpected response type".to_string())
+            }
+            Ok(Err(e)) => {
+                self.routing_table.lock().await.mark_failed(&peer_id);
+                Err(e)
+            }
+            Err(_) => {
+                self.routing_table.lock().await.mark_failed(&peer_id);
+                Err("RPC timeout".to_string())
+            }
+        }
+    }
+
+    /// Announce that this node can serve the blob hashed to `content_hash`
+    ///
+    /// Leaves a provider record on the k closest known peers and keeps the blob
+    /// on the re-announce list. Returns the peers that took the record.
+    pub async fn provide(&self, content_hash: DhtKey) -> Result<Vec<DhtKey>, String> {
+        self.provided.lock().await.insert(content_hash);
+        self.announce(content_hash).await
+    }
+
+    /// Stop re-announcing `content_hash`; existing records lapse at their TTL
+    pub async fn stop_providing(&self, content_hash: &DhtKey) {
+        self.provided.lock().await.remove(content_hash);
+    }
+
+    /// Announce every provided blob again, refreshing their records
+    pub async fn reannounce(&self) {
+        let provided: Vec<DhtKey> = self.provided.lock().await.iter().copied().collect();
+        for content_hash in provided {
+            if let Err(e) = self.announce(content_hash).await {
+                debug!("Re-announcing provider of {} failed: {}", content_hash, e);
+            }
+        }
+    }
+
+    /// Re-announce provided blobs every `period`, well inside the provider TTL
+    pub fn spawn_reannouncer(&self, period: Duration) -> JoinHandle<()> {
+        let client = self.clone();
+        tokio::spawn(async move {
+            let mut tick = interval(period);
+            // The first tick fires immediately; provide() already announced
+            tick.tick().await;
+            loop {
+                tick.tick().await;
+                client.reannounce().await;
+            }
+        })
+    }
+
+    /// Providers of the blob hashed to `content_hash`
+    ///
+    /// Asks the k closest known peers at once and merges their answers.
+    pub async fn find_providers(&self, content_hash: DhtKey) -> Result<Vec<PeerInfo>, String> {
+        let closest = {
+            let table = self.routing_table.lock().await;
+            table.find_closest(&content_hash, table.k())
+        };
+        if closest.is_empty() {
+            return Err("No peers to ask for providers".to_string());
+        }
+
+        let mut pending: FuturesUnordered<_> =
+            closest.iter().map(|peer| self.get_providers(peer.id, content_hash)).collect();
+        let mut providers: Vec<PeerInfo> = Vec::new();
+        let mut answered = 0;
+        let mut last_error = String::new();
+
+        while let Some(result) = pending.next().await {
+            match result {
+                Ok(found) => {
+                    answered += 1;
+                    for provider in found {
+                        if !providers.iter().any(|p| p.id == provider.id) {
+                            providers.push(provider);
+                        }
+                    }
+                }
+                Err(e) => last_error = e,
+            }
+        }
+
+        if answered == 0 {
+            return Err(format!("No peer answered: {}", last_error));
+        }
+        Ok(providers)
+    }
+
+    /// Leave a provider record for `content_hash` on the k closest known peers
+    async fn announce(&self, content_hash: DhtKey) -> Result<Vec<DhtKey>, String> {
+        let closest = {
+            let table = self.routing_table.lock().await;
+            table.find_closest(&content_hash, table.k())
+        };
+        if closest.is_empty() {
+            return Err("No peers to announce to".to_string());
+        }
+
+        let mut announced = Vec::new();
+        let mut last_error = String::new();
+        for peer in closest {
+            match self.add_provider(peer.id, content_hash).await {
+                Ok(()) => announced.push(peer.id),
+                Err(e) => last_error = e,
+            }
+        }
+
+        if announced.is_empty() {
+            return Err(format!("No peer took the provider record: {}", last_error));
+        }
+        Ok(announced)
+    }
+
     /// Helper: send message and receive response
     /// Note: This is a simplified implementation. In production, you'd use the RPC protocol
     /// from the router layer to handle request/response matching.
     async fn send_and_receive(
         &self,
-        _peer_id: DhtKey,
-        _msg: DhtMessage,
+        peer_id: DhtKey,
+        msg: DhtMessage,
     ) -> Result<DhtMessage, String> {
+        if let Some(transport) = &self.transport {
+            return transport.request(peer_id, msg).await;
+        }
+
         // In production, we'd serialize, send via router, and wait for response
         // For now, return an error indicating this needs RPC integration
         Err("Response handling requires RPC protocol integration".to_string())
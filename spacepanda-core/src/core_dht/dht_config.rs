@@ -15,8 +15,11 @@
 */
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::time::Duration;
 
+use super::dht_storage::StorageQuota;
+
 /// Replication strategy for DHT values
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ReplicationStrategy {
@@ -28,6 +31,15 @@ pub enum ReplicationStrategy {
     Hybrid,
 }
 
+/// How lookups for a key namespace reach the storing node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LookupMode {
+    /// Query the storing node directly (it sees our node ID)
+    Direct,
+    /// Proxy the query through 1-2 relay peers so the storing node only sees the last relay
+    Private { relay_hops: usize },
+}
+
 /// Configuration for DHT behavior
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DhtConfig {
@@ -46,9 +58,14 @@ pub struct DhtConfig {
     /// Value expiration time - how long values live without republish
     pub value_expiration: Duration,
 
-    /// Bucket refresh interval - how often to refresh buckets
+    /// Bucket refresh interval - buckets with no lookup in this long get a refresh lookup
     pub bucket_refresh_interval: Duration,
 
+    /// How long the least-recently-seen peer of a full bucket has to answer a ping
+    /// before a newcomer takes its place
+    #[serde(default = "default_peer_ping_timeout")]
+    pub peer_ping_timeout: Duration,
+
     /// Replication strategy
     pub replication_strategy: ReplicationStrategy,
 
@@ -66,6 +83,50 @@ pub struct DhtConfig {
 
     /// Number of buckets (typically 256 for 256-bit keyspace)
     pub num_buckets: usize,
+
+    /// Per-namespace lookup mode; namespaces not listed use direct lookups
+    #[serde(default)]
+    pub lookup_modes: HashMap<String, LookupMode>,
+
+    /// How often the routing table's peers are saved for the next start
+    #[serde(default = "default_peer_persist_interval")]
+    pub peer_persist_interval: Duration,
+
+    /// How often expired records are dropped from storage
+    #[serde(default = "default_sweep_interval")]
+    pub sweep_interval: Duration,
+
+    /// How long a provider record lives without being announced again
+    #[serde(default = "default_provider_ttl")]
+    pub provider_ttl: Duration,
+
+    /// How often a node re-announces the blobs it provides
+    #[serde(default = "default_provider_reannounce_interval")]
+    pub provider_reannounce_interval: Duration,
+
+    /// How much storage peers may use, in total and each
+    #[serde(default)]
+    pub storage_quota: StorageQuota,
+}
+
+fn default_peer_persist_interval() -> Duration {
+    Duration::from_secs(300)
+}
+
+fn default_peer_ping_timeout() -> Duration {
+    Duration::from_secs(2)
+}
+
+fn default_sweep_interval() -> Duration {
+    Duration::from_secs(60)
+}
+
+fn default_provider_ttl() -> Duration {
+    Duration::from_secs(86400)
+}
+
+fn default_provider_reannounce_interval() -> Duration {
+    Duration::from_secs(43200)
 }
 
 impl Default for DhtConfig {
@@ -77,12 +138,19 @@ impl Default for DhtConfig {
             republish_interval: Duration::from_secs(3600),      // 1 hour
             value_expiration: Duration::from_secs(86400),       // 24 hours
             bucket_refresh_interval: Duration::from_secs(3600), // 1 hour
+            peer_ping_timeout: default_peer_ping_timeout(),     // 2 seconds
             replication_strategy: ReplicationStrategy::Hybrid,
             rpc_timeout: Duration::from_secs(5),
             max_lookup_hops: 8,
             require_signatures: false,
             max_value_size: 1024 * 1024, // 1 MB
             num_buckets: 256,            // 256-bit keyspace
+            lookup_modes: HashMap::new(),
+            peer_persist_interval: default_peer_persist_interval(), // 5 minutes
+            sweep_interval: default_sweep_interval(),               // 1 minute
+            provider_ttl: default_provider_ttl(),                   // 24 hours
+            provider_reannounce_interval: default_provider_reannounce_interval(), // 12 hours
+            storage_quota: StorageQuota::default(),                 // 256 MB, 16 MB per publisher
         }
     }
 }
@@ -123,6 +191,18 @@ impl DhtConfig {
         self
     }
 
+    /// Builder: set how long a bucket may go without a lookup before it is refreshed
+    pub fn with_bucket_refresh_interval(mut self, interval: Duration) -> Self {
+        self.bucket_refresh_interval = interval;
+        self
+    }
+
+    /// Builder: set how long a full bucket's oldest peer has to answer a ping
+    pub fn with_peer_ping_timeout(mut self, timeout: Duration) -> Self {
+        self.peer_ping_timeout = timeout;
+        self
+    }
+
     /// Builder: set replication strategy
     pub fn with_replication_strategy(mut self, strategy: ReplicationStrategy) -> Self {
         self.replication_strategy = strategy;
@@ -135,12 +215,64 @@ impl DhtConfig {
         self
     }
 
+    /// Builder: set how often peers are saved for the next start
+    pub fn with_peer_persist_interval(mut self, interval: Duration) -> Self {
+        self.peer_persist_interval = interval;
+        self
+    }
+
+    /// Builder: set how often expired records are swept
+    pub fn with_sweep_interval(mut self, interval: Duration) -> Self {
+        self.sweep_interval = interval;
+        self
+    }
+
+    /// Builder: set how long provider records live without re-announcement
+    pub fn with_provider_ttl(mut self, ttl: Duration) -> Self {
+        self.provider_ttl = ttl;
+        self
+    }
+
+    /// Builder: set how often provided blobs are re-announced
+    pub fn with_provider_reannounce_interval(mut self, interval: Duration) -> Self {
+        self.provider_reannounce_interval = interval;
+        self
+    }
+
+    /// Builder: set how much storage peers may use
+    pub fn with_storage_quota(mut self, quota: StorageQuota) -> Self {
+        self.storage_quota = quota;
+        self
+    }
+
     /// Builder: enable signature verification
     pub fn with_signatures(mut self, enabled: bool) -> Self {
         self.require_signatures = enabled;
         self
     }
 
+    /// Builder: set the lookup mode for a key namespace
+    ///
+    /// Private lookups add a round trip per relay hop, so only sensitive
+    /// namespaces (key packages, channel listings) should use them.
+    pub fn with_lookup_mode(mut self, namespace: &str, mode: LookupMode) -> Self {
+        if let LookupMode::Private { relay_hops } = mode {
+            tracing::warn!(
+                namespace,
+                relay_hops,
+                "Private DHT lookups add {} relay round trip(s) of latency per query",
+                relay_hops
+            );
+        }
+        self.lookup_modes.insert(namespace.to_string(), mode);
+        self
+    }
+
+    /// Lookup mode for a key namespace
+    pub fn lookup_mode(&self, namespace: &str) -> LookupMode {
+        self.lookup_modes.get(namespace).copied().unwrap_or(LookupMode::Direct)
+    }
+
     /// Validate configuration
     pub fn validate(&self) -> Result<(), String> {
         if self.bucket_size == 0 {
@@ -163,10 +295,44 @@ impl DhtConfig {
             return Err("Max value size must be greater than 0".to_string());
         }
 
+        if self.bucket_refresh_interval.is_zero() {
+            return Err("Bucket refresh interval must be greater than 0".to_string());
+        }
+
+        if self.peer_ping_timeout.is_zero() {
+            return Err("Peer ping timeout must be greater than 0".to_string());
+        }
+
+        if self.sweep_interval.is_zero() {
+            return Err("Sweep interval must be greater than 0".to_string());
+        }
+
+        if self.provider_reannounce_interval.is_zero()
+            || self.provider_reannounce_interval >= self.provider_ttl
+        {
+            return Err(
+                "Provider re-announce interval must be non-zero and shorter than provider TTL"
+                    .to_string(),
+            );
+        }
+
+        self.storage_quota.validate()?;
+
         if self.num_buckets != 256 {
             return Err("Number of buckets must be 256 for 256-bit keyspace".to_string());
         }
 
+        for (namespace, mode) in &self.lookup_modes {
+            if let LookupMode::Private { relay_hops } = mode {
+                if !(1..=2).contains(relay_hops) {
+                    return Err(format!(
+                        "Private lookups for '{}' must use 1 or 2 relay hops",
+                        namespace
+                    ));
+                }
+            }
+        }
+
         Ok(())
     }
 
@@ -180,12 +346,24 @@ impl DhtConfig {
             republish_interval: Duration::from_secs(10),
             value_expiration: Duration::from_secs(30),
             bucket_refresh_interval: Duration::from_secs(10),
+            peer_ping_timeout: Duration::from_millis(100),
             replication_strategy: ReplicationStrategy::Push,
             rpc_timeout: Duration::from_millis(100),
             max_lookup_hops: 4,
             require_signatures: false,
             max_value_size: 1024,
             num_buckets: 256,
+            lookup_modes: HashMap::new(),
+            peer_persist_interval: Duration::from_secs(10),
+            sweep_interval: Duration::from_secs(1),
+            provider_ttl: Duration::from_secs(30),
+            provider_reannounce_interval: Duration::from_secs(10),
+            storage_quota: StorageQuota {
+                max_bytes: 64 * 1024,
+                max_records: 256,
+                max_publisher_bytes: 16 * 1024,
+                max_publisher_records: 64,
+            },
         }
     }
 }
@@ -299,6 +477,22 @@ mod tests {
         assert_eq!(config.alpha, deserialized.alpha);
     }
 
+    #[test]
+    fn test_dht_config_lookup_modes() {
+        let config =
+            DhtConfig::new().with_lookup_mode("key_package", LookupMode::Private { relay_hops: 2 });
+
+        assert_eq!(config.lookup_mode("key_package"), LookupMode::Private { relay_hops: 2 });
+        assert_eq!(config.lookup_mode("profile"), LookupMode::Direct);
+        assert!(config.validate().is_ok());
+
+        let config =
+            DhtConfig::new().with_lookup_mode("channel", LookupMode::Private { relay_hops: 3 });
+        let result = config.validate();
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("relay hops"));
+    }
+
     #[test]
     fn test_dht_config_with_signatures() {
         let config = DhtConfig::new().with_signatures(true);
@@ -101,6 +101,24 @@ impl DhtKey {
         }
     }
 
+    /// A random key that falls in bucket `index` relative to this key
+    ///
+    /// Used to refresh a bucket: its distance from this key has bit `index` as the
+    /// highest set bit, and random bits below it.
+    pub fn random_in_bucket(&self, index: usize) -> DhtKey {
+        use rand::Rng;
+        let index = index.min(255);
+        let mut distance = [0u8; 32];
+        rand::rng().fill(&mut distance[..]);
+
+        let byte = 31 - index / 8;
+        let bit = 1u8 << (index % 8);
+        distance[..byte].fill(0);
+        distance[byte] = (distance[byte] & (bit - 1)) | bit;
+
+        DhtKey(distance).distance(self)
+    }
+
     /// Check if this key is closer to a target than another key
     pub fn is_closer(&self, other: &DhtKey, target: &DhtKey) -> bool {
         let dist_self = self.distance(target);
@@ -255,6 +273,16 @@ mod tests {
         assert_eq!(key2.bucket_index(&reference), 0);
     }
 
+    #[test]
+    fn test_dht_key_random_in_bucket() {
+        let local = DhtKey::hash(b"local");
+        for index in [0, 7, 8, 100, 254, 255] {
+            for _ in 0..20 {
+                assert_eq!(local.random_in_bucket(index).bucket_index(&local), index);
+            }
+        }
+    }
+
     #[test]
     fn test_dht_key_is_closer() {
         let target = DhtKey::from_bytes([0xFF; 32]);
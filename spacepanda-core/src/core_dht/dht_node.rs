@@ -30,15 +30,22 @@
 */
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
 use tokio::sync::{mpsc, oneshot, Mutex};
-use tokio::time::interval;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, timeout};
 
+use super::bootstrap::{BootstrapSummary, PeerBootstrap, PeerProber};
 use super::dht_config::DhtConfig;
 use super::dht_key::DhtKey;
+use super::dht_storage::DhtStorage;
 use super::dht_value::DhtValue;
+use super::kad_search::{KadSearch, SearchResult, SearchStats, SearchTransport, SearchType};
+use super::message::{FindValueResult, PeerInfo};
+use super::replication::ReplicationManager;
+use super::routing_table::PeerContact;
+use crate::metrics::record_counter;
 
 /// K-bucket entry representing a known peer
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -66,41 +73,120 @@ impl BucketEntry {
     }
 }
 
+/// A k-bucket, least recently seen peer first
+#[derive(Debug, Clone)]
+struct Bucket {
+    entries: Vec<BucketEntry>,
+    /// When a lookup last targeted this bucket's range
+    last_refresh: Instant,
+    /// Peers evicted after failing to answer a ping
+    evictions: u64,
+}
+
+impl Bucket {
+    fn new() -> Self {
+        Bucket { entries: Vec::new(), last_refresh: Instant::now(), evictions: 0 }
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+/// Statistics for one k-bucket, for the health system
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BucketStats {
+    /// Bucket index (highest differing bit from the local ID)
+    pub index: usize,
+    /// Peers in the bucket
+    pub entries: usize,
+    /// Time since a lookup last targeted the bucket's range
+    pub since_refresh: Duration,
+    /// Peers evicted after failing to answer a ping
+    pub evictions: u64,
+}
+
+/// Outcome of offering a node to the routing table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Insertion {
+    /// New entry
+    Added,
+    /// Already known, now most recently seen
+    Updated,
+    /// The bucket is full; `oldest` is its least recently seen peer
+    Full { oldest: DhtKey },
+    /// The local node itself
+    Rejected,
+}
+
 /// Routing table using Kademlia k-buckets
 #[derive(Debug)]
 pub struct RoutingTable {
     local_id: DhtKey,
-    buckets: Vec<Vec<BucketEntry>>,
+    buckets: Vec<Bucket>,
     bucket_size: usize,
 }
 
 impl RoutingTable {
     fn new(local_id: DhtKey, bucket_size: usize, num_buckets: usize) -> Self {
-        RoutingTable { local_id, buckets: vec![Vec::new(); num_buckets], bucket_size }
+        RoutingTable { local_id, buckets: vec![Bucket::new(); num_buckets], bucket_size }
     }
 
-    /// Add or update a node in the routing table
-    fn add_node(&mut self, node_id: DhtKey) -> bool {
+    /// Add or update a node, moving it to the most recently seen end of its bucket
+    fn insert(&mut self, node_id: DhtKey) -> Insertion {
         if node_id == self.local_id {
-            return false; // Don't add self
+            return Insertion::Rejected; // Don't add self
         }
 
         let bucket_idx = node_id.bucket_index(&self.local_id);
         let bucket = &mut self.buckets[bucket_idx];
 
         // Check if already exists
-        if let Some(entry) = bucket.iter_mut().find(|e| e.node_id == node_id) {
+        if let Some(pos) = bucket.entries.iter().position(|e| e.node_id == node_id) {
+            let mut entry = bucket.entries.remove(pos);
             entry.update_seen();
-            return true;
+            bucket.entries.push(entry);
+            return Insertion::Updated;
         }
 
         // Add if bucket has space
         if bucket.len() < self.bucket_size {
-            bucket.push(BucketEntry::new(node_id));
-            return true;
+            bucket.entries.push(BucketEntry::new(node_id));
+            return Insertion::Added;
         }
 
-        false
+        Insertion::Full { oldest: bucket.entries[0].node_id }
+    }
+
+    /// Add or update a node in the routing table, returning false if its bucket is full
+    fn add_node(&mut self, node_id: DhtKey) -> bool {
+        matches!(self.insert(node_id), Insertion::Added | Insertion::Updated)
+    }
+
+    /// Replace `stale` with `replacement`, if `stale` is still in its bucket
+    fn evict(&mut self, stale: &DhtKey, replacement: DhtKey) -> bool {
+        if !self.remove_node(stale) {
+            return false;
+        }
+        self.buckets[stale.bucket_index(&self.local_id)].evictions += 1;
+        self.add_node(replacement);
+        true
+    }
+
+    /// Note that a lookup targeted `target`, which refreshes its bucket
+    fn mark_refreshed(&mut self, target: &DhtKey) {
+        let bucket_idx = target.bucket_index(&self.local_id);
+        self.buckets[bucket_idx].last_refresh = Instant::now();
+    }
+
+    /// Occupied buckets that no lookup has targeted for `interval`
+    fn stale_buckets(&self, interval: Duration) -> Vec<usize> {
+        self.buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, bucket)| bucket.len() > 0 && bucket.last_refresh.elapsed() >= interval)
+            .map(|(index, _)| index)
+            .collect()
     }
 
     /// Find the k closest nodes to a target key
@@ -108,7 +194,7 @@ impl RoutingTable {
         let mut all_nodes: Vec<_> = self
             .buckets
             .iter()
-            .flat_map(|bucket| bucket.iter())
+            .flat_map(|bucket| bucket.entries.iter())
             .map(|entry| entry.node_id)
             .collect();
 
@@ -126,7 +212,7 @@ impl RoutingTable {
     fn all_nodes(&self) -> Vec<DhtKey> {
         self.buckets
             .iter()
-            .flat_map(|bucket| bucket.iter())
+            .flat_map(|bucket| bucket.entries.iter())
             .map(|entry| entry.node_id)
             .collect()
     }
@@ -136,8 +222,8 @@ impl RoutingTable {
         let bucket_idx = node_id.bucket_index(&self.local_id);
         let bucket = &mut self.buckets[bucket_idx];
 
-        if let Some(pos) = bucket.iter().position(|e| &e.node_id == node_id) {
-            bucket.remove(pos);
+        if let Some(pos) = bucket.entries.iter().position(|e| &e.node_id == node_id) {
+            bucket.entries.remove(pos);
             return true;
         }
 
@@ -210,8 +296,16 @@ pub struct DhtNode {
     config: DhtConfig,
     local_id: DhtKey,
     routing_table: Arc<Mutex<RoutingTable>>,
-    storage: Arc<Mutex<HashMap<DhtKey, DhtValue>>>,
+    storage: DhtStorage,
     event_tx: mpsc::Sender<DhtEvent>,
+    /// Remembers peers across restarts, if set up
+    peers: Option<Arc<PeerBootstrap>>,
+    /// Republishes the values we put, if set up
+    replication: Option<Arc<ReplicationManager>>,
+    /// Pings full buckets' oldest peers before evicting them, if set up
+    prober: Option<Arc<dyn PeerProber>>,
+    /// Carries bucket refresh lookups, if set up
+    search_transport: Option<Arc<dyn SearchTransport>>,
 }
 
 impl DhtNode {
@@ -229,9 +323,62 @@ impl DhtNode {
             config.num_buckets,
         )));
 
-        let storage = Arc::new(Mutex::new(HashMap::new()));
+        let storage = DhtStorage::new()
+            .with_max_ttl(config.value_expiration)
+            .with_quota(config.storage_quota);
 
-        Ok(DhtNode { config, local_id, routing_table, storage, event_tx })
+        Ok(DhtNode {
+            config,
+            local_id,
+            routing_table,
+            storage,
+            event_tx,
+            peers: None,
+            replication: None,
+            prober: None,
+            search_transport: None,
+        })
+    }
+
+    /// Builder: keep values in `storage`, shared with feeds and mailboxes
+    pub fn with_storage(mut self, storage: DhtStorage) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    /// Builder: republish the values put through this node with `replication`
+    ///
+    /// The node switches to the manager's storage, so both see the same values.
+    pub fn with_replication(mut self, replication: Arc<ReplicationManager>) -> Self {
+        self.storage = replication.storage().clone();
+        self.replication = Some(replication);
+        self
+    }
+
+    /// Get the storage holding this node's values
+    pub fn storage(&self) -> &DhtStorage {
+        &self.storage
+    }
+
+    /// Builder: restore peers from, and save them to, the local store
+    pub fn with_peer_bootstrap(mut self, peers: Arc<PeerBootstrap>) -> Self {
+        self.peers = Some(peers);
+        self
+    }
+
+    /// Builder: ping a full bucket's least recently seen peer before a newcomer
+    /// replaces it
+    ///
+    /// Without a prober, newcomers to a full bucket are dropped.
+    pub fn with_prober(mut self, prober: Arc<dyn PeerProber>) -> Self {
+        self.prober = Some(prober);
+        self
+    }
+
+    /// Builder: refresh idle buckets with lookups sent through `transport`
+    pub fn with_search_transport(mut self, transport: Arc<dyn SearchTransport>) -> Self {
+        self.search_transport = Some(transport);
+        self
     }
 
     /// Get the local node ID
@@ -241,15 +388,21 @@ impl DhtNode {
 
     /// Start the DHT node event loop
     pub async fn run(self: Arc<Self>, mut command_rx: mpsc::Receiver<DhtCommand>) {
-        // Spawn maintenance task
+        // Spawn maintenance task; checking twice per refresh interval keeps an idle
+        // bucket from going much longer than the interval without a refresh
         let node = self.clone();
-        tokio::spawn(async move {
-            let mut tick = interval(node.config.bucket_refresh_interval);
+        let maintenance = tokio::spawn(async move {
+            let period = (node.config.bucket_refresh_interval / 2).max(Duration::from_millis(1));
+            let mut tick = interval(period);
             loop {
                 tick.tick().await;
                 node.perform_maintenance().await;
             }
         });
+        let persist =
+            self.peers.as_ref().map(|p| p.spawn_persist(self.config.peer_persist_interval));
+        let sweeper = self.spawn_sweeper();
+        let replication = self.replication.clone().map(|r| tokio::spawn(r.run()));
 
         loop {
             tokio::select! {
@@ -277,12 +430,195 @@ impl DhtNode {
                             self.perform_maintenance().await;
                         }
                         DhtCommand::Shutdown => {
+                            if let Some(peers) = &self.peers {
+                                if let Err(e) = peers.persist().await {
+                                    tracing::warn!("Failed to remember DHT peers: {}", e);
+                                }
+                            }
                             break;
                         }
                     }
                 }
             }
         }
+
+        if let Some(persist) = persist {
+            persist.abort();
+        }
+        if let Some(replication) = replication {
+            replication.abort();
+        }
+        sweeper.abort();
+        maintenance.abort();
+    }
+
+    /// Drop expired values every `sweep_interval`
+    ///
+    /// Reads never return expired values anyway; this only frees the space.
+    pub fn spawn_sweeper(&self) -> JoinHandle<()> {
+        let storage = self.storage.clone();
+        let period = self.config.sweep_interval;
+        tokio::spawn(async move {
+            let mut tick = interval(period);
+            loop {
+                tick.tick().await;
+                match storage.cleanup_expired() {
+                    Ok(0) => {}
+                    Ok(removed) => tracing::debug!("Swept {} expired DHT values", removed),
+                    Err(e) => tracing::warn!("Failed to sweep DHT values: {}", e),
+                }
+            }
+        })
+    }
+
+    /// Rejoin the network through remembered peers, or else the bootstrap peers
+    ///
+    /// Every peer that ends up in the bootstrap routing table is added here too.
+    pub async fn bootstrap(&self) -> Result<BootstrapSummary, String> {
+        let peers = self.peers.as_ref().ok_or("No peer bootstrap configured")?;
+        let mut summary = peers.bootstrap().await;
+
+        let contacts = peers.routing_table().lock().await.all_peers();
+        for contact in contacts {
+            if self.add_peer(contact.id).await {
+                let _ = self.event_tx.send(DhtEvent::NodeDiscovered { node_id: contact.id }).await;
+            }
+        }
+        summary.table_size = self.routing_table.lock().await.node_count();
+
+        Ok(summary)
+    }
+
+    /// Add a peer to the routing table, returning false if there was no room for it
+    ///
+    /// When the peer's bucket is full, its least recently seen entry is pinged first:
+    /// an entry that answers stays and the newcomer is dropped, one that times out
+    /// is evicted to make room.
+    pub async fn add_peer(&self, node_id: DhtKey) -> bool {
+        let oldest = match self.routing_table.lock().await.insert(node_id) {
+            Insertion::Added | Insertion::Updated => return true,
+            Insertion::Rejected => return false,
+            Insertion::Full { oldest } => oldest,
+        };
+        let Some(prober) = &self.prober else {
+            return false;
+        };
+
+        let address = oldest.to_string();
+        let ping = prober.ping(&address, Some(oldest));
+        let alive = matches!(
+            timeout(self.config.peer_ping_timeout, ping).await,
+            Ok(Ok(answered)) if answered == oldest
+        );
+
+        let evicted = {
+            let mut table = self.routing_table.lock().await;
+            if alive {
+                // Seen just now, so it moves to the back of the bucket
+                table.add_node(oldest);
+                return false;
+            }
+            table.evict(&oldest, node_id)
+        };
+        if !evicted {
+            // Someone else already made room, or took it
+            return self.routing_table.lock().await.add_node(node_id);
+        }
+
+        tracing::debug!("Evicted unresponsive DHT peer {} for {}", oldest, node_id);
+        record_counter("dht.bucket_evictions", 1);
+        let _ = self.event_tx.send(DhtEvent::NodeRemoved { node_id: oldest }).await;
+        true
+    }
+
+    /// Look up a random ID in every occupied bucket that no lookup has targeted
+    /// for `bucket_refresh_interval`, returning how many buckets were refreshed
+    ///
+    /// Does nothing without a search transport.
+    pub async fn refresh_buckets(&self) -> usize {
+        let Some(transport) = self.search_transport.clone() else {
+            return 0;
+        };
+        let stale = self
+            .routing_table
+            .lock()
+            .await
+            .stale_buckets(self.config.bucket_refresh_interval);
+
+        for &index in &stale {
+            let target = self.local_id.random_in_bucket(index);
+            let (_, stats) = self.lookup(target, SearchType::FindNode, transport.as_ref()).await;
+            tracing::debug!(
+                "Refreshed bucket {} with {} queries over {} rounds",
+                index,
+                stats.queried,
+                stats.rounds
+            );
+        }
+        record_counter("dht.bucket_refreshes", stale.len() as u64);
+        stale.len()
+    }
+
+    /// Store a value on this node only, as a STORE from a peer would
+    pub async fn store_local(&self, key: DhtKey, value: DhtValue) -> Result<(), String> {
+        value.validate(self.config.max_value_size, self.config.require_signatures)?;
+        self.storage.put(key, value)
+    }
+
+    /// Answer a FIND_NODE or FIND_VALUE query from a peer
+    ///
+    /// The routing table doesn't track addresses yet, so peers are named by ID.
+    pub async fn answer_query(&self, target: DhtKey, search_type: SearchType) -> FindValueResult {
+        if search_type == SearchType::FindValue {
+            if let Ok(value) = self.storage.get(&target) {
+                return FindValueResult::Found(value);
+            }
+        }
+
+        let closest =
+            self.routing_table.lock().await.find_closest(&target, self.config.bucket_size);
+        FindValueResult::NotFound {
+            closest_nodes: closest
+                .into_iter()
+                .map(|id| PeerInfo::new(id, id.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Look `target` up across the network with an alpha-parallel iterative search
+    ///
+    /// A value stored here is returned without any queries. Peers that answered are
+    /// added to the routing table.
+    pub async fn lookup(
+        &self,
+        target: DhtKey,
+        search_type: SearchType,
+        transport: &dyn SearchTransport,
+    ) -> (SearchResult, SearchStats) {
+        if let FindValueResult::Found(value) = self.answer_query(target, search_type).await {
+            return (SearchResult::Value(value), SearchStats::default());
+        }
+
+        let initial: Vec<PeerContact> = self
+            .routing_table
+            .lock()
+            .await
+            .find_closest(&target, self.config.bucket_size)
+            .into_iter()
+            .map(|id| PeerContact::new(id, id.to_string()))
+            .collect();
+        let search = KadSearch::from_config(target, search_type, initial, &self.config)
+            .with_local_id(self.local_id);
+        self.routing_table.lock().await.mark_refreshed(&target);
+        let (result, stats) = search.run(transport).await;
+
+        if let SearchResult::Nodes(nodes) = &result {
+            for node in nodes {
+                self.add_peer(node.id).await;
+            }
+        }
+
+        (result, stats)
     }
 
     /// Handle PUT operation
@@ -298,7 +634,10 @@ impl DhtNode {
             .find_closest(&key, self.config.replication_factor);
 
         // Store locally (we're always one of the nodes responsible for storage)
-        self.storage.lock().await.insert(key, value.clone());
+        self.storage.put(key, value)?;
+        if let Some(replication) = &self.replication {
+            replication.mark_original(key).await;
+        }
 
         // TODO: Send STORE messages to closest nodes via router
         // For now, just emit success event
@@ -309,13 +648,10 @@ impl DhtNode {
 
     /// Handle GET operation
     async fn handle_get(&self, key: DhtKey) -> Result<Option<DhtValue>, String> {
-        // Check local storage first
-        if let Some(value) = self.storage.lock().await.get(&key) {
-            if !value.is_expired() {
-                let _ =
-                    self.event_tx.send(DhtEvent::ValueFound { key, value: value.clone() }).await;
-                return Ok(Some(value.clone()));
-            }
+        // Check local storage first; expired values are never returned
+        if let Ok(value) = self.storage.get(&key) {
+            let _ = self.event_tx.send(DhtEvent::ValueFound { key, value: value.clone() }).await;
+            return Ok(Some(value));
         }
 
         // TODO: Perform iterative lookup via router
@@ -336,7 +672,7 @@ impl DhtNode {
     /// Handle incoming DHT message
     async fn handle_message(&self, from: DhtKey, message: DhtMessage) {
         // Add sender to routing table
-        self.routing_table.lock().await.add_node(from);
+        self.add_peer(from).await;
 
         match message {
             DhtMessage::Ping { .. } => {
@@ -356,12 +692,12 @@ impl DhtNode {
                     .validate(self.config.max_value_size, self.config.require_signatures)
                     .is_ok()
                 {
-                    self.storage.lock().await.insert(key, value);
+                    let _ = self.storage.put_from(key, value, from);
                     // TODO: Send StoreAck via router
                 }
             }
             DhtMessage::GetValue { key, .. } => {
-                let _stored_value = self.storage.lock().await.get(&key).cloned();
+                let _stored_value = self.storage.get(&key).ok();
                 let _closest =
                     self.routing_table.lock().await.find_closest(&key, self.config.bucket_size);
                 // TODO: Send GetValueResponse via router
@@ -374,7 +710,7 @@ impl DhtNode {
 
     /// Handle bootstrap
     async fn handle_bootstrap(&self, node_id: DhtKey) {
-        self.routing_table.lock().await.add_node(node_id);
+        self.add_peer(node_id).await;
 
         // TODO: Perform FIND_NODE for self.local_id to populate routing table
         let _ = self.event_tx.send(DhtEvent::NodeDiscovered { node_id }).await;
@@ -383,10 +719,9 @@ impl DhtNode {
     /// Perform periodic maintenance
     async fn perform_maintenance(&self) {
         // Remove expired values
-        let mut storage = self.storage.lock().await;
-        storage.retain(|_, value| !value.is_expired());
+        let _ = self.storage.cleanup_expired();
 
-        // TODO: Refresh buckets, republish values, etc.
+        self.refresh_buckets().await;
     }
 
     /// Get routing table statistics
@@ -397,11 +732,27 @@ impl DhtNode {
         (total, per_bucket)
     }
 
+    /// Per-bucket statistics for every bucket holding peers or having evicted some
+    pub async fn routing_stats(&self) -> Vec<BucketStats> {
+        let table = self.routing_table.lock().await;
+        table
+            .buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, bucket)| bucket.len() > 0 || bucket.evictions > 0)
+            .map(|(index, bucket)| BucketStats {
+                index,
+                entries: bucket.len(),
+                since_refresh: bucket.last_refresh.elapsed(),
+                evictions: bucket.evictions,
+            })
+            .collect()
+    }
+
     /// Get storage statistics
     pub async fn storage_stats(&self) -> (usize, usize) {
-        let storage = self.storage.lock().await;
-        let total = storage.len();
-        let expired = storage.values().filter(|v| v.is_expired()).count();
+        let total = self.storage.size().unwrap_or(0);
+        let expired = total - self.storage.active_keys().map(|k| k.len()).unwrap_or(0);
         (total, expired)
     }
 }
@@ -639,7 +990,7 @@ mod tests {
         // Add some values
         let key1 = DhtKey::hash_string("key1");
         let value1 = DhtValue::new(vec![1, 2, 3]);
-        node.storage.lock().await.insert(key1, value1);
+        node.storage.put(key1, value1).unwrap();
 
         let (total, _expired) = node.storage_stats().await;
         assert_eq!(total, 1);
@@ -13,6 +13,11 @@
 
     The storage must also keep which peers store replicas.
 
+    To resist flooding, every record counts against a global quota and
+    against the quota of the peer that published it. A publisher over its
+    quota is refused; a full store evicts its least valuable records
+    (shortest remaining TTL, then least recently requested) to make room.
+
     Inputs:
     - requests: store(key, value), get(key), delete(key)
     - load value(key)
@@ -22,12 +27,14 @@
     - stored values
     - deletion notifications
     - expiration scans
+    - usage reports, in total and per publisher
 */
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
 use std::sync::{Arc, RwLock};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Get current Unix timestamp in seconds
 /// Returns 0 if system clock is before UNIX epoch (should never happen on modern systems)
@@ -37,6 +44,115 @@ fn current_timestamp() -> u64 {
 
 use super::dht_key::DhtKey;
 use super::dht_value::DhtValue;
+use crate::metrics::record_counter;
+
+/// How much a store may hold, in total and per publisher
+///
+/// The limits are "sloppy": expired records still count until they're swept,
+/// and a full store makes room by evicting its least valuable records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageQuota {
+    /// Total bytes held
+    pub max_bytes: u64,
+    /// Total records held
+    pub max_records: usize,
+    /// Bytes held for any one publisher
+    pub max_publisher_bytes: u64,
+    /// Records held for any one publisher
+    pub max_publisher_records: usize,
+}
+
+impl StorageQuota {
+    /// No limits at all
+    pub fn unlimited() -> Self {
+        StorageQuota {
+            max_bytes: u64::MAX,
+            max_records: usize::MAX,
+            max_publisher_bytes: u64::MAX,
+            max_publisher_records: usize::MAX,
+        }
+    }
+
+    /// Check every limit is non-zero
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_bytes == 0 || self.max_records == 0 {
+            return Err("storage quota must allow at least one record".to_string());
+        }
+        if self.max_publisher_bytes == 0 || self.max_publisher_records == 0 {
+            return Err("per-publisher storage quota must allow at least one record".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl Default for StorageQuota {
+    fn default() -> Self {
+        StorageQuota {
+            max_bytes: 256 * 1024 * 1024,
+            max_records: 100_000,
+            max_publisher_bytes: 16 * 1024 * 1024,
+            max_publisher_records: 5_000,
+        }
+    }
+}
+
+/// Bytes and records held
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    pub bytes: u64,
+    pub records: usize,
+}
+
+impl Usage {
+    fn add(&mut self, bytes: u64) {
+        self.bytes += bytes;
+        self.records += 1;
+    }
+
+    fn sub(&mut self, bytes: u64) {
+        self.bytes = self.bytes.saturating_sub(bytes);
+        self.records = self.records.saturating_sub(1);
+    }
+}
+
+/// Snapshot of what a store holds and for whom
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageReport {
+    /// Everything held, expired records included until swept
+    pub total: Usage,
+    /// The limits in force
+    pub quota: StorageQuota,
+    /// Usage per publisher, heaviest first; records stored locally aren't attributed
+    pub publishers: Vec<(DhtKey, Usage)>,
+    /// Records evicted to make room since the store was created
+    pub evictions: u64,
+    /// Puts refused for being over quota since the store was created
+    pub rejections: u64,
+}
+
+/// Why a put was refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PutError {
+    /// The publisher, or the store as a whole, has no room for the value
+    QuotaExceeded(String),
+    /// Anything else: a stale sequence number, a poisoned lock
+    Other(String),
+}
+
+impl fmt::Display for PutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PutError::QuotaExceeded(reason) => write!(f, "Quota exceeded: {}", reason),
+            PutError::Other(reason) => f.write_str(reason),
+        }
+    }
+}
+
+impl From<PutError> for String {
+    fn from(err: PutError) -> Self {
+        err.to_string()
+    }
+}
 
 /// Entry in the storage with metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,20 +161,39 @@ struct StorageEntry {
     value: DhtValue,
     /// When this entry was stored locally (Unix timestamp)
     stored_at: u64,
+    /// When this entry expires (Unix timestamp): the publisher's TTL, capped by the store
+    expires_at: u64,
     /// Peer IDs that store replicas of this value
     replica_peers: Vec<DhtKey>,
+    /// Peer whose STORE put this entry here; None for local writes
+    publisher: Option<DhtKey>,
+    /// When this entry was last read (Unix timestamp)
+    last_requested: u64,
 }
 
 impl StorageEntry {
-    fn new(value: DhtValue) -> Self {
+    fn new(value: DhtValue, max_ttl: u64, publisher: Option<DhtKey>) -> Self {
         let now = current_timestamp();
-
-        StorageEntry { value, stored_at: now, replica_peers: Vec::new() }
+        let expires_at = value.timestamp.saturating_add(value.ttl.min(max_ttl));
+
+        StorageEntry {
+            value,
+            stored_at: now,
+            expires_at,
+            replica_peers: Vec::new(),
+            publisher,
+            last_requested: now,
+        }
     }
 
     /// Check if this entry has expired
     fn is_expired(&self) -> bool {
-        self.value.is_expired()
+        current_timestamp() >= self.expires_at
+    }
+
+    /// Bytes this entry counts against quotas
+    fn footprint(&self) -> u64 {
+        footprint(&self.value)
     }
 
     /// Add a replica peer
@@ -74,48 +209,247 @@ impl StorageEntry {
     }
 }
 
+/// Bytes a value counts against quotas
+fn footprint(value: &DhtValue) -> u64 {
+    let signature = value.signature.as_ref().map_or(0, Vec::len);
+    let metadata = value.crdt_metadata.as_ref().map_or(0, Vec::len);
+    (value.data.len() + signature + metadata) as u64
+}
+
+/// The entries, with usage kept in step with every insert and removal
+#[derive(Default)]
+struct Records {
+    entries: HashMap<DhtKey, StorageEntry>,
+    total: Usage,
+    publishers: HashMap<DhtKey, Usage>,
+    evictions: u64,
+    rejections: u64,
+}
+
+impl Records {
+    fn insert(&mut self, key: DhtKey, entry: StorageEntry) {
+        self.remove(&key);
+        let bytes = entry.footprint();
+        self.total.add(bytes);
+        if let Some(publisher) = entry.publisher {
+            self.publishers.entry(publisher).or_default().add(bytes);
+        }
+        self.entries.insert(key, entry);
+    }
+
+    fn remove(&mut self, key: &DhtKey) -> Option<StorageEntry> {
+        let entry = self.entries.remove(key)?;
+        let bytes = entry.footprint();
+        self.total.sub(bytes);
+        if let Some(publisher) = entry.publisher {
+            if let Some(usage) = self.publishers.get_mut(&publisher) {
+                usage.sub(bytes);
+                if usage.records == 0 {
+                    self.publishers.remove(&publisher);
+                }
+            }
+        }
+        Some(entry)
+    }
+
+    /// Remove every entry failing `keep`, returning how many went
+    fn retain(&mut self, mut keep: impl FnMut(&StorageEntry) -> bool) -> usize {
+        let doomed: Vec<DhtKey> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| !keep(entry))
+            .map(|(key, _)| *key)
+            .collect();
+        for key in &doomed {
+            self.remove(key);
+        }
+        doomed.len()
+    }
+
+    /// Usage once the entry under `key`, if any, is gone
+    fn usage_without(&self, key: &DhtKey, publisher: Option<DhtKey>) -> Usage {
+        let mut usage = match publisher {
+            Some(publisher) => self.publishers.get(&publisher).copied().unwrap_or_default(),
+            None => self.total,
+        };
+        if let Some(existing) = self.entries.get(key) {
+            if publisher.is_none() || existing.publisher == publisher {
+                usage.sub(existing.footprint());
+            }
+        }
+        usage
+    }
+
+    /// Make room for `bytes` more under `key`, evicting the least valuable
+    /// records: soonest to expire first, then least recently requested
+    ///
+    /// Records worth keeping longer than the newcomer are never evicted for it.
+    fn make_room(
+        &mut self,
+        key: &DhtKey,
+        bytes: u64,
+        expires_at: u64,
+        quota: &StorageQuota,
+    ) -> Result<(), String> {
+        let fits = |usage: Usage| {
+            usage.bytes.saturating_add(bytes) <= quota.max_bytes
+                && usage.records < quota.max_records
+        };
+        if fits(self.usage_without(key, None)) {
+            return Ok(());
+        }
+
+        self.retain(|entry| !entry.is_expired());
+        let mut candidates: Vec<(u64, u64, DhtKey)> = self
+            .entries
+            .iter()
+            .filter(|(candidate, _)| *candidate != key)
+            .map(|(candidate, entry)| (entry.expires_at, entry.last_requested, *candidate))
+            .collect();
+        candidates.sort_unstable();
+
+        let mut evicted = 0;
+        for (candidate_expiry, _, candidate) in candidates {
+            if fits(self.usage_without(key, None)) {
+                break;
+            }
+            if candidate_expiry > expires_at {
+                break;
+            }
+            self.remove(&candidate);
+            evicted += 1;
+        }
+        self.evictions += evicted;
+        record_counter("dht.storage_evictions", evicted);
+
+        if fits(self.usage_without(key, None)) {
+            Ok(())
+        } else {
+            Err("storage is full of longer-lived records".to_string())
+        }
+    }
+}
+
 /// Simple in-memory DHT storage
 #[derive(Clone)]
 pub struct DhtStorage {
-    /// Storage map: key -> entry
-    store: Arc<RwLock<HashMap<DhtKey, StorageEntry>>>,
+    /// Storage map: key -> entry, with usage
+    store: Arc<RwLock<Records>>,
+    /// Longest TTL honored, in seconds; longer ones are cut short
+    max_ttl: u64,
+    /// Limits on what the store holds
+    quota: StorageQuota,
 }
 
 impl DhtStorage {
     /// Create a new DHT storage
     pub fn new() -> Self {
-        DhtStorage { store: Arc::new(RwLock::new(HashMap::new())) }
+        DhtStorage {
+            store: Arc::new(RwLock::new(Records::default())),
+            max_ttl: u64::MAX,
+            quota: StorageQuota::unlimited(),
+        }
+    }
+
+    /// Builder: cap the TTL of every stored value at `max_ttl`
+    pub fn with_max_ttl(mut self, max_ttl: Duration) -> Self {
+        self.max_ttl = max_ttl.as_secs();
+        self
+    }
+
+    /// Builder: limit what the store holds, in total and per publisher
+    pub fn with_quota(mut self, quota: StorageQuota) -> Self {
+        self.quota = quota;
+        self
     }
 
-    /// Store a value
+    /// Store a value written locally
+    ///
+    /// Local writes count against the global quota but no publisher's.
     pub fn put(&self, key: DhtKey, value: DhtValue) -> Result<(), String> {
-        let mut store =
-            self.store.write().map_err(|e| format!("Failed to acquire write lock: {}", e))?;
+        self.put_entry(key, value, None).map_err(String::from)
+    }
+
+    /// Store a value on behalf of the peer that sent the STORE
+    pub fn put_from(
+        &self,
+        key: DhtKey,
+        value: DhtValue,
+        publisher: DhtKey,
+    ) -> Result<(), PutError> {
+        self.put_entry(key, value, Some(publisher))
+    }
+
+    fn put_entry(
+        &self,
+        key: DhtKey,
+        value: DhtValue,
+        publisher: Option<DhtKey>,
+    ) -> Result<(), PutError> {
+        let mut store = self
+            .store
+            .write()
+            .map_err(|e| PutError::Other(format!("Failed to acquire write lock: {}", e)))?;
 
         // Check if we already have this key
-        if let Some(existing) = store.get(&key) {
+        if let Some(existing) = store.entries.get(&key).filter(|existing| !existing.is_expired()) {
             // Only update if new value has higher sequence number
             if value.sequence <= existing.value.sequence {
-                return Err(format!(
+                return Err(PutError::Other(format!(
                     "Stale value: existing sequence {} >= new sequence {}",
                     existing.value.sequence, value.sequence
-                ));
+                )));
             }
         }
 
-        store.insert(key, StorageEntry::new(value));
+        let entry = StorageEntry::new(value, self.max_ttl, publisher);
+        let room = self
+            .check_quota(&store, &key, &entry)
+            .and_then(|()| store.make_room(&key, entry.footprint(), entry.expires_at, &self.quota));
+        if let Err(reason) = room {
+            store.rejections += 1;
+            record_counter("dht.storage_rejections", 1);
+            return Err(PutError::QuotaExceeded(reason));
+        }
+
+        store.insert(key, entry);
+        Ok(())
+    }
+
+    /// Check `entry` fits the publisher's quota and could fit the store at all
+    fn check_quota(
+        &self,
+        store: &Records,
+        key: &DhtKey,
+        entry: &StorageEntry,
+    ) -> Result<(), String> {
+        let bytes = entry.footprint();
+        if bytes > self.quota.max_bytes.min(self.quota.max_publisher_bytes) {
+            return Err(format!("value of {} bytes is larger than the storage quota", bytes));
+        }
+        let Some(publisher) = entry.publisher else {
+            return Ok(());
+        };
+        let usage = store.usage_without(key, Some(publisher));
+        if usage.bytes.saturating_add(bytes) > self.quota.max_publisher_bytes
+            || usage.records >= self.quota.max_publisher_records
+        {
+            return Err(format!("publisher {} is over its storage quota", publisher));
+        }
         Ok(())
     }
 
     /// Get a value
     pub fn get(&self, key: &DhtKey) -> Result<DhtValue, String> {
-        let store = self.store.read().map_err(|e| format!("Failed to acquire read lock: {}", e))?;
+        let mut store =
+            self.store.write().map_err(|e| format!("Failed to acquire write lock: {}", e))?;
 
-        match store.get(key) {
+        match store.entries.get_mut(key) {
             Some(entry) => {
                 if entry.is_expired() {
                     Err("Value has expired".to_string())
                 } else {
+                    entry.last_requested = current_timestamp();
                     Ok(entry.value.clone())
                 }
             }
@@ -140,29 +474,41 @@ impl DhtStorage {
         let mut store =
             self.store.write().map_err(|e| format!("Failed to acquire write lock: {}", e))?;
 
-        match store.get_mut(key) {
+        match store.entries.get_mut(key) {
             Some(entry) => {
                 entry.value.ttl = new_ttl;
                 entry.value.timestamp = SystemTime::now()
                     .duration_since(UNIX_EPOCH)
                     .expect("System clock is before UNIX epoch")
                     .as_secs();
+                entry.expires_at = entry.value.timestamp.saturating_add(new_ttl.min(self.max_ttl));
                 Ok(())
             }
             None => Err("Key not found".to_string()),
         }
     }
 
+    /// Get when a key expires (Unix timestamp)
+    pub fn expires_at(&self, key: &DhtKey) -> Result<u64, String> {
+        let store = self.store.read().map_err(|e| format!("Failed to acquire read lock: {}", e))?;
+
+        match store.entries.get(key) {
+            Some(entry) => Ok(entry.expires_at),
+            None => Err("Key not found".to_string()),
+        }
+    }
+
     /// Get all keys in storage
     pub fn keys(&self) -> Result<Vec<DhtKey>, String> {
         let store = self.store.read().map_err(|e| format!("Failed to acquire read lock: {}", e))?;
-        Ok(store.keys().copied().collect())
+        Ok(store.entries.keys().copied().collect())
     }
 
     /// Get all non-expired keys
     pub fn active_keys(&self) -> Result<Vec<DhtKey>, String> {
         let store = self.store.read().map_err(|e| format!("Failed to acquire read lock: {}", e))?;
         Ok(store
+            .entries
             .iter()
             .filter(|(_, entry)| !entry.is_expired())
             .map(|(key, _)| *key)
@@ -173,11 +519,7 @@ impl DhtStorage {
     pub fn cleanup_expired(&self) -> Result<usize, String> {
         let mut store =
             self.store.write().map_err(|e| format!("Failed to acquire write lock: {}", e))?;
-        let before_count = store.len();
-
-        store.retain(|_, entry| !entry.is_expired());
-
-        Ok(before_count - store.len())
+        Ok(store.retain(|entry| !entry.is_expired()))
     }
 
     /// Add a replica peer for a key
@@ -185,7 +527,7 @@ impl DhtStorage {
         let mut store =
             self.store.write().map_err(|e| format!("Failed to acquire write lock: {}", e))?;
 
-        match store.get_mut(key) {
+        match store.entries.get_mut(key) {
             Some(entry) => {
                 entry.add_replica(peer);
                 Ok(())
@@ -199,7 +541,7 @@ impl DhtStorage {
         let mut store =
             self.store.write().map_err(|e| format!("Failed to acquire write lock: {}", e))?;
 
-        match store.get_mut(key) {
+        match store.entries.get_mut(key) {
             Some(entry) => {
                 entry.remove_replica(peer);
                 Ok(())
@@ -212,7 +554,7 @@ impl DhtStorage {
     pub fn get_replicas(&self, key: &DhtKey) -> Result<Vec<DhtKey>, String> {
         let store = self.store.read().map_err(|e| format!("Failed to acquire read lock: {}", e))?;
 
-        match store.get(key) {
+        match store.entries.get(key) {
             Some(entry) => Ok(entry.replica_peers.clone()),
             None => Err("Key not found".to_string()),
         }
@@ -224,22 +566,39 @@ impl DhtStorage {
             .store
             .read()
             .map_err(|e| format!("Failed to acquire read lock: {}", e))?
+            .entries
             .len())
     }
 
+    /// Report what the store holds, in total and per publisher
+    pub fn usage_report(&self) -> Result<UsageReport, String> {
+        let store = self.store.read().map_err(|e| format!("Failed to acquire read lock: {}", e))?;
+        let mut publishers: Vec<(DhtKey, Usage)> =
+            store.publishers.iter().map(|(publisher, usage)| (*publisher, *usage)).collect();
+        publishers.sort_by(|a, b| b.1.bytes.cmp(&a.1.bytes).then(a.0.cmp(&b.0)));
+        Ok(UsageReport {
+            total: store.total,
+            quota: self.quota,
+            publishers,
+            evictions: store.evictions,
+            rejections: store.rejections,
+        })
+    }
+
     /// Clear all entries
     pub fn clear(&self) -> Result<(), String> {
-        self.store
-            .write()
-            .map_err(|e| format!("Failed to acquire write lock: {}", e))?
-            .clear();
+        let mut store =
+            self.store.write().map_err(|e| format!("Failed to acquire write lock: {}", e))?;
+        store.entries.clear();
+        store.publishers.clear();
+        store.total = Usage::default();
         Ok(())
     }
 
     /// Get all entries (for debugging/testing)
     pub fn entries(&self) -> Result<Vec<(DhtKey, DhtValue)>, String> {
         let store = self.store.read().map_err(|e| format!("Failed to acquire read lock: {}", e))?;
-        Ok(store.iter().map(|(key, entry)| (*key, entry.value.clone())).collect())
+        Ok(store.entries.iter().map(|(key, entry)| (*key, entry.value.clone())).collect())
     }
 }
 
@@ -394,6 +753,26 @@ mod tests {
         assert!(after.timestamp >= before_timestamp);
     }
 
+    #[test]
+    fn test_storage_caps_ttl() {
+        let storage = DhtStorage::new().with_max_ttl(Duration::from_secs(60));
+        let key = DhtKey::hash(b"test_key");
+        let value = DhtValue::new(b"test_data".to_vec()).with_ttl(3600);
+
+        storage.put(key, value.clone()).unwrap();
+        assert_eq!(storage.expires_at(&key).unwrap(), value.timestamp + 60);
+
+        storage.refresh(&key, 30).unwrap();
+        let refreshed = storage.get(&key).unwrap();
+        assert_eq!(storage.expires_at(&key).unwrap(), refreshed.timestamp + 30);
+
+        // An expired record can be replaced by any sequence
+        storage.refresh(&key, 0).unwrap();
+        assert!(storage.get(&key).is_err());
+        storage.put(key, DhtValue::new(b"again".to_vec())).unwrap();
+        assert_eq!(storage.get(&key).unwrap().data, b"again");
+    }
+
     #[test]
     fn test_storage_replicas() {
         let storage = DhtStorage::new();
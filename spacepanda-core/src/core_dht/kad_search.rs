@@ -15,15 +15,26 @@
     - final value or final closest nodes
     - search logs
     - errors
+
+    Lookups follow the standard alpha-parallel procedure: the shortlist holds the k closest
+    live peers seen so far, up to alpha queries are in flight at once, and each reply adds the
+    closer peers it names. The search ends once the k closest live peers have all answered,
+    or a value is found. A query that times out only frees its slot, so a slow peer never
+    holds up the others.
 */
 
+use async_trait::async_trait;
+use futures::stream::{FuturesUnordered, StreamExt};
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use tokio::time::Duration;
+use tokio::time::{timeout, Duration};
+use tracing::debug;
 
+use super::dht_config::DhtConfig;
 use super::dht_key::DhtKey;
 use super::dht_value::DhtValue;
+use super::message::FindValueResult;
 use super::routing_table::PeerContact;
 
 /// Search state for a single peer query
@@ -59,6 +70,32 @@ pub enum SearchType {
     FindNode,
 }
 
+/// Sends the queries of a search to peers
+#[async_trait]
+pub trait SearchTransport: Send + Sync {
+    /// Ask `peer` about `target`
+    ///
+    /// FIND_NODE queries are answered with `FindValueResult::NotFound` and the peer's
+    /// closest nodes.
+    async fn query(
+        &self,
+        peer: &PeerContact,
+        target: DhtKey,
+        search_type: SearchType,
+    ) -> Result<FindValueResult, String>;
+}
+
+/// How a search went
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    /// Queries sent
+    pub queried: usize,
+    /// Queries that failed or timed out
+    pub failed: usize,
+    /// Longest chain of referrals followed, counting the initial peers as round 1
+    pub rounds: usize,
+}
+
 /// Manages a single Kademlia search operation
 pub struct KadSearch {
     /// Target key we're searching for
@@ -79,6 +116,12 @@ pub struct KadSearch {
     complete: bool,
     /// RPC timeout
     rpc_timeout: Duration,
+    /// Round each peer was learned in
+    rounds: HashMap<DhtKey, usize>,
+    /// Queries sent but not answered yet
+    in_flight: usize,
+    /// Running statistics
+    stats: SearchStats,
 }
 
 impl KadSearch {
@@ -100,6 +143,9 @@ impl KadSearch {
             found_value: None,
             complete: false,
             rpc_timeout: Duration::from_secs(5),
+            rounds: HashMap::new(),
+            in_flight: 0,
+            stats: SearchStats::default(),
         };
 
         // Add initial peers
@@ -110,23 +156,60 @@ impl KadSearch {
         search
     }
 
+    /// Create a search using the alpha, k and RPC timeout from `config`
+    pub fn from_config(
+        target: DhtKey,
+        search_type: SearchType,
+        initial_peers: Vec<PeerContact>,
+        config: &DhtConfig,
+    ) -> Self {
+        Self::new(target, search_type, initial_peers, config.alpha, config.bucket_size)
+            .with_rpc_timeout(config.rpc_timeout)
+    }
+
+    /// Builder: set the timeout for each query
+    pub fn with_rpc_timeout(mut self, rpc_timeout: Duration) -> Self {
+        self.rpc_timeout = rpc_timeout;
+        self
+    }
+
+    /// Builder: never query or return the local node, however peers name it
+    pub fn with_local_id(mut self, local_id: DhtKey) -> Self {
+        self.seen.insert(local_id);
+        self.peers.retain(|(peer, _)| peer.id != local_id);
+        self
+    }
+
     /// Add a peer to the search
     fn add_peer(&mut self, peer: PeerContact) {
+        self.add_peer_in_round(peer, 1);
+    }
+
+    /// Add a peer learned in `round`
+    fn add_peer_in_round(&mut self, peer: PeerContact, round: usize) {
         // Don't add duplicates
         if self.seen.contains(&peer.id) {
             return;
         }
 
         self.seen.insert(peer.id);
+        self.rounds.insert(peer.id, round);
         self.peers.push((peer.clone(), PeerState::Pending));
 
         // Sort by distance to target
         self.peers.sort_by_key(|(p, _)| p.id.distance(&self.target));
 
-        // Keep only k closest
-        if self.peers.len() > self.k {
-            self.peers.truncate(self.k);
-        }
+        // Keep only the k closest live peers still to be queried; peers already queried
+        // stay for the result
+        let k = self.k;
+        let mut live = 0;
+        self.peers.retain(|(_, state)| {
+            if *state == PeerState::Failed {
+                return true;
+            }
+            live += 1;
+            live <= k || *state != PeerState::Pending
+        });
     }
 
     /// Add multiple peers
@@ -136,43 +219,42 @@ impl KadSearch {
         }
     }
 
-    /// Get the next peers to query (up to alpha)
+    /// Get the next peers to query, closest first, keeping at most alpha queries in flight
     pub fn get_next_queries(&mut self) -> Vec<PeerContact> {
         let mut queries = Vec::new();
+        let available = self.alpha.saturating_sub(self.in_flight);
 
         for (peer, state) in &mut self.peers {
-            if *state == PeerState::Pending && queries.len() < self.alpha {
+            if *state == PeerState::Pending && queries.len() < available {
                 *state = PeerState::Querying;
                 queries.push(peer.clone());
             }
         }
 
+        self.in_flight += queries.len();
+        self.stats.queried += queries.len();
+        for peer in &queries {
+            let round = self.rounds.get(&peer.id).copied().unwrap_or(1);
+            self.stats.rounds = self.stats.rounds.max(round);
+        }
+
         queries
     }
 
     /// Mark a peer as responded with new peers
     pub fn mark_responded(&mut self, peer_id: &DhtKey, new_peers: Vec<PeerContact>) {
-        // Update state
-        for (peer, state) in &mut self.peers {
-            if &peer.id == peer_id {
-                *state = PeerState::Responded;
-                break;
-            }
-        }
+        self.set_state(peer_id, PeerState::Responded);
 
-        // Add new peers
-        self.add_peers(new_peers);
+        // Peers it named are one round further out
+        let round = self.rounds.get(peer_id).copied().unwrap_or(1) + 1;
+        for peer in new_peers {
+            self.add_peer_in_round(peer, round);
+        }
     }
 
     /// Mark a peer as responded with a value
     pub fn mark_value_found(&mut self, peer_id: &DhtKey, value: DhtValue) {
-        // Update state
-        for (peer, state) in &mut self.peers {
-            if &peer.id == peer_id {
-                *state = PeerState::Responded;
-                break;
-            }
-        }
+        self.set_state(peer_id, PeerState::Responded);
 
         self.found_value = Some(value);
         self.complete = true;
@@ -180,12 +262,62 @@ impl KadSearch {
 
     /// Mark a peer as failed
     pub fn mark_failed(&mut self, peer_id: &DhtKey) {
-        for (peer, state) in &mut self.peers {
-            if &peer.id == peer_id {
-                *state = PeerState::Failed;
+        self.set_state(peer_id, PeerState::Failed);
+        self.stats.failed += 1;
+    }
+
+    fn set_state(&mut self, peer_id: &DhtKey, new_state: PeerState) {
+        if let Some((_, state)) = self.peers.iter_mut().find(|(peer, _)| &peer.id == peer_id) {
+            if *state == PeerState::Querying {
+                self.in_flight -= 1;
+            }
+            *state = new_state;
+        }
+    }
+
+    /// Run the search to completion, sending queries through `transport`
+    pub async fn run(mut self, transport: &dyn SearchTransport) -> (SearchResult, SearchStats) {
+        let mut queries = FuturesUnordered::new();
+
+        while !self.complete {
+            for peer in self.get_next_queries() {
+                let (target, search_type, rpc_timeout) =
+                    (self.target, self.search_type, self.rpc_timeout);
+                queries.push(async move {
+                    let reply = timeout(rpc_timeout, transport.query(&peer, target, search_type))
+                        .await
+                        .unwrap_or_else(|_| Err("RPC timeout".to_string()));
+                    (peer.id, reply)
+                });
+            }
+
+            // Nothing in flight and nothing left to ask
+            let Some((peer_id, reply)) = queries.next().await else {
                 break;
+            };
+
+            match reply {
+                Ok(FindValueResult::Found(value)) if self.search_type == SearchType::FindValue => {
+                    self.mark_value_found(&peer_id, value);
+                }
+                Ok(FindValueResult::Found(_)) => self.mark_responded(&peer_id, Vec::new()),
+                Ok(FindValueResult::NotFound { closest_nodes }) => {
+                    let peers = closest_nodes
+                        .into_iter()
+                        .map(|info| {
+                            PeerContact::new(info.id, info.address).with_lookup_key(info.lookup_key)
+                        })
+                        .collect();
+                    self.mark_responded(&peer_id, peers);
+                }
+                Err(e) => {
+                    debug!("Search query to {} failed: {}", peer_id, e);
+                    self.mark_failed(&peer_id);
+                }
             }
         }
+
+        (self.result(), self.stats)
     }
 
     /// Check if search is complete
@@ -244,6 +376,11 @@ impl KadSearch {
     pub fn search_type(&self) -> SearchType {
         self.search_type
     }
+
+    /// Get the statistics so far
+    pub fn stats(&self) -> SearchStats {
+        self.stats
+    }
 }
 
 /// Search manager coordinating multiple searches
@@ -383,8 +520,13 @@ mod tests {
         let queries = search.get_next_queries();
         assert_eq!(queries.len(), 2); // Should return alpha peers
 
+        // Both slots are taken until one of them answers
+        assert!(search.get_next_queries().is_empty());
+        search.mark_responded(&queries[0].id, vec![]);
+
         let queries2 = search.get_next_queries();
         assert_eq!(queries2.len(), 1); // Only 1 remaining peer
+        assert_eq!(search.stats().queried, 3);
     }
 
     #[test]
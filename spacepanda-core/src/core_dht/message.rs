@@ -9,12 +9,17 @@
     - FIND_NODE(target_id)
     - FIND_VALUE(key)
     - STORE_VALUE(key, value)
+    - ADD_PROVIDER(content_hash, provider)
+    - GET_PROVIDERS(content_hash)
+    - LOOKUP(onion layer of a private lookup)
     - PING
 
     Response messages:
     - NODES(list of closest nodes)
     - VALUE(value)
-    - PONG
+    - PROVIDERS(providers, closest nodes)
+    - LOOKUP_RESULT(sealed answer)
+    - PONG(with the sender's lookup key)
 
     Serialization is done with CBOR or bincode.
 
@@ -35,14 +40,28 @@ fn current_timestamp() -> u64 {
     SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
 }
 
+/// Why a STORE was refused, for refusals the sender should act on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StoreErrorCode {
+    /// The sender, or the node as a whole, is out of storage quota
+    QuotaExceeded,
+    /// The sender went over quota too often and is refused for a while
+    Throttled,
+}
+
 /// DHT RPC message types
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum DhtMessage {
     /// Ping request - check if peer is alive
     Ping { sender_id: DhtKey, timestamp: u64 },
 
-    /// Pong response - acknowledge ping
-    Pong { sender_id: DhtKey, timestamp: u64 },
+    /// Pong response - acknowledge ping, giving the key private lookups are sealed to
+    Pong {
+        sender_id: DhtKey,
+        timestamp: u64,
+        #[serde(default)]
+        lookup_key: Option<[u8; 32]>,
+    },
 
     /// Find node request - lookup k closest nodes to target
     FindNode { sender_id: DhtKey, target: DhtKey, request_id: u64 },
@@ -59,8 +78,35 @@ pub enum DhtMessage {
     /// Store value request - store key-value pair
     Store { sender_id: DhtKey, key: DhtKey, value: DhtValue, request_id: u64 },
 
-    /// Store acknowledgment - confirm storage
-    StoreAck { sender_id: DhtKey, success: bool, request_id: u64, error: Option<String> },
+    /// Store acknowledgment - confirm storage, or say why it was refused
+    StoreAck {
+        sender_id: DhtKey,
+        success: bool,
+        request_id: u64,
+        error: Option<String>,
+        #[serde(default)]
+        code: Option<StoreErrorCode>,
+    },
+
+    /// Add provider request - sender can serve the blob for `ttl` seconds (acked by StoreAck)
+    AddProvider { sender_id: DhtKey, key: DhtKey, provider: PeerInfo, ttl: u64, request_id: u64 },
+
+    /// Get providers request - lookup peers serving a blob
+    GetProviders { sender_id: DhtKey, key: DhtKey, request_id: u64 },
+
+    /// Get providers response - known providers and closest known nodes
+    GetProvidersResponse {
+        sender_id: DhtKey,
+        request_id: u64,
+        providers: Vec<PeerInfo>,
+        closest_nodes: Vec<PeerInfo>,
+    },
+
+    /// Private lookup request - one onion layer of an oblivious lookup
+    Lookup { sender_id: DhtKey, payload: Vec<u8>, request_id: u64 },
+
+    /// Private lookup response - the sealed answer, or why there is none
+    LookupResponse { sender_id: DhtKey, request_id: u64, result: Result<Vec<u8>, String> },
 }
 
 /// Result of FindValue RPC
@@ -79,11 +125,20 @@ pub struct PeerInfo {
     pub id: DhtKey,
     /// Network address (e.g., "127.0.0.1:8080")
     pub address: String,
+    /// Key private lookups are sealed to, if the peer serves them
+    #[serde(default)]
+    pub lookup_key: Option<[u8; 32]>,
 }
 
 impl PeerInfo {
     pub fn new(id: DhtKey, address: String) -> Self {
-        PeerInfo { id, address }
+        PeerInfo { id, address, lookup_key: None }
+    }
+
+    /// Builder: set the peer's lookup key
+    pub fn with_lookup_key(mut self, lookup_key: Option<[u8; 32]>) -> Self {
+        self.lookup_key = lookup_key;
+        self
     }
 }
 
@@ -99,6 +154,11 @@ impl DhtMessage {
             DhtMessage::FindValueResponse { sender_id, .. } => *sender_id,
             DhtMessage::Store { sender_id, .. } => *sender_id,
             DhtMessage::StoreAck { sender_id, .. } => *sender_id,
+            DhtMessage::AddProvider { sender_id, .. } => *sender_id,
+            DhtMessage::GetProviders { sender_id, .. } => *sender_id,
+            DhtMessage::GetProvidersResponse { sender_id, .. } => *sender_id,
+            DhtMessage::Lookup { sender_id, .. } => *sender_id,
+            DhtMessage::LookupResponse { sender_id, .. } => *sender_id,
         }
     }
 
@@ -111,6 +171,11 @@ impl DhtMessage {
             DhtMessage::FindValueResponse { request_id, .. } => Some(*request_id),
             DhtMessage::Store { request_id, .. } => Some(*request_id),
             DhtMessage::StoreAck { request_id, .. } => Some(*request_id),
+            DhtMessage::AddProvider { request_id, .. } => Some(*request_id),
+            DhtMessage::GetProviders { request_id, .. } => Some(*request_id),
+            DhtMessage::GetProvidersResponse { request_id, .. } => Some(*request_id),
+            DhtMessage::Lookup { request_id, .. } => Some(*request_id),
+            DhtMessage::LookupResponse { request_id, .. } => Some(*request_id),
             _ => None,
         }
     }
@@ -123,6 +188,9 @@ impl DhtMessage {
                 | DhtMessage::FindNode { .. }
                 | DhtMessage::FindValue { .. }
                 | DhtMessage::Store { .. }
+                | DhtMessage::AddProvider { .. }
+                | DhtMessage::GetProviders { .. }
+                | DhtMessage::Lookup { .. }
         )
     }
 
@@ -134,6 +202,8 @@ impl DhtMessage {
                 | DhtMessage::FindNodeResponse { .. }
                 | DhtMessage::FindValueResponse { .. }
                 | DhtMessage::StoreAck { .. }
+                | DhtMessage::GetProvidersResponse { .. }
+                | DhtMessage::LookupResponse { .. }
         )
     }
 
@@ -148,6 +218,11 @@ impl DhtMessage {
             DhtMessage::FindValueResponse { .. } => "FindValueResponse",
             DhtMessage::Store { .. } => "Store",
             DhtMessage::StoreAck { .. } => "StoreAck",
+            DhtMessage::AddProvider { .. } => "AddProvider",
+            DhtMessage::GetProviders { .. } => "GetProviders",
+            DhtMessage::GetProvidersResponse { .. } => "GetProvidersResponse",
+            DhtMessage::Lookup { .. } => "Lookup",
+            DhtMessage::LookupResponse { .. } => "LookupResponse",
         }
     }
 
@@ -157,10 +232,10 @@ impl DhtMessage {
         DhtMessage::Ping { sender_id, timestamp }
     }
 
-    /// Create a pong response
-    pub fn new_pong(sender_id: DhtKey) -> Self {
+    /// Create a pong response, carrying the sender's lookup key if it has one
+    pub fn new_pong(sender_id: DhtKey, lookup_key: Option<[u8; 32]>) -> Self {
         let timestamp = current_timestamp();
-        DhtMessage::Pong { sender_id, timestamp }
+        DhtMessage::Pong { sender_id, timestamp, lookup_key }
     }
 }
 
@@ -172,7 +247,7 @@ mod tests {
     fn test_ping_pong() {
         let sender = DhtKey::hash(b"sender");
         let ping = DhtMessage::new_ping(sender);
-        let pong = DhtMessage::new_pong(sender);
+        let pong = DhtMessage::new_pong(sender, None);
 
         assert!(ping.is_request());
         assert!(!ping.is_response());
@@ -273,8 +348,13 @@ mod tests {
     fn test_store_ack_success() {
         let sender = DhtKey::hash(b"sender");
 
-        let msg =
-            DhtMessage::StoreAck { sender_id: sender, success: true, request_id: 789, error: None };
+        let msg = DhtMessage::StoreAck {
+            sender_id: sender,
+            success: true,
+            request_id: 789,
+            error: None,
+            code: None,
+        };
 
         assert!(msg.is_response());
 
@@ -293,14 +373,41 @@ mod tests {
             success: false,
             request_id: 789,
             error: Some("storage full".to_string()),
+            code: Some(StoreErrorCode::QuotaExceeded),
         };
 
-        if let DhtMessage::StoreAck { success, error, .. } = msg {
+        if let DhtMessage::StoreAck { success, error, code, .. } = msg {
             assert!(!success);
             assert_eq!(error.unwrap(), "storage full");
+            assert_eq!(code, Some(StoreErrorCode::QuotaExceeded));
         }
     }
 
+    #[test]
+    fn test_provider_messages() {
+        let sender = DhtKey::hash(b"sender");
+        let key = DhtKey::hash(b"blob");
+        let provider = PeerInfo::new(sender, "127.0.0.1:8001".to_string());
+
+        let add =
+            DhtMessage::AddProvider { sender_id: sender, key, provider, ttl: 60, request_id: 7 };
+        assert!(add.is_request());
+        assert_eq!(add.request_id(), Some(7));
+        assert_eq!(add.message_type(), "AddProvider");
+
+        let get = DhtMessage::GetProviders { sender_id: sender, key, request_id: 8 };
+        assert!(get.is_request());
+
+        let response = DhtMessage::GetProvidersResponse {
+            sender_id: sender,
+            request_id: 8,
+            providers: Vec::new(),
+            closest_nodes: Vec::new(),
+        };
+        assert!(response.is_response());
+        assert_eq!(response.sender_id(), sender);
+    }
+
     #[test]
     fn test_message_type_consistency() {
         let sender = DhtKey::hash(b"sender");
@@ -1,3 +1,5 @@
+pub mod bootstrap;
+pub mod channel_feed;
 pub mod client;
 pub mod dht_config;
 pub mod dht_key;
@@ -7,6 +9,9 @@ pub mod dht_value;
 pub mod events;
 pub mod kad_search;
 pub mod message;
+pub mod private_lookup;
+pub mod provider_store;
+pub mod recipient_mailbox;
 pub mod replication;
 pub mod routing_table;
 pub mod server;
@@ -14,15 +19,22 @@ pub mod server;
 #[cfg(test)]
 pub mod tests;
 
-pub use client::DhtClient;
-pub use dht_config::{DhtConfig, ReplicationStrategy};
+pub use bootstrap::{BootstrapSummary, PeerBootstrap, PeerProber};
+pub use channel_feed::{ChannelFeed, ChannelFeedEntry};
+pub use client::{ConsistencyLevel, DhtClient, DhtTransport};
+pub use dht_config::{DhtConfig, LookupMode, ReplicationStrategy};
 pub use dht_key::DhtKey;
-pub use dht_node::{BucketEntry, DhtCommand, DhtEvent, DhtMessage, DhtNode};
-pub use dht_storage::DhtStorage;
+pub use dht_node::{BucketEntry, BucketStats, DhtCommand, DhtEvent, DhtMessage, DhtNode};
+pub use dht_storage::{DhtStorage, PutError, StorageQuota, Usage, UsageReport};
 pub use dht_value::DhtValue;
 pub use events::DhtEvent as DhtEventNew;
-pub use kad_search::{KadSearch, SearchManager, SearchResult, SearchType};
-pub use message::{DhtMessage as DhtMessageNew, FindValueResult, PeerInfo};
+pub use kad_search::{
+    KadSearch, SearchManager, SearchResult, SearchStats, SearchTransport, SearchType,
+};
+pub use message::{DhtMessage as DhtMessageNew, FindValueResult, PeerInfo, StoreErrorCode};
+pub use private_lookup::{DhtLookupTransport, LookupPeer, LookupTransport, PrivateLookup};
+pub use provider_store::ProviderStore;
+pub use recipient_mailbox::{RecipientMailbox, RecipientMailboxEntry};
 pub use replication::{ReplicationEvent, ReplicationManager, ReplicationStats};
 pub use routing_table::{PeerContact, RoutingTable};
 pub use server::DhtServer;
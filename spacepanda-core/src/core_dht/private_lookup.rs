@@ -0,0 +1,491 @@
+/*
+    PrivateLookup - oblivious DHT lookups through relay peers.
+
+    Responsibilities:
+    `private_lookup.rs` hides which keys a node is interested in from the nodes storing them.
+    Lookups for namespaces configured as `LookupMode::Private` are proxied through 1-2
+    randomly selected relay peers, so the storing node only ever sees the last relay.
+
+    Workflow (requester -> R1 -> R2 -> storing node):
+    1. Requester picks relay_hops relays from its relay pool
+    2. Query is onion-sealed: sealed to the storing node's X25519 key, then once per relay,
+       innermost first, together with the hop after it (each layer: ephemeral ECDH + HKDF +
+       ChaCha20-Poly1305)
+    3. Each relay opens its layer and forwards the rest; it learns the previous and next hop,
+       never the rest of the path, the queried key or the response. Relays only forward to
+       nodes in their routing table, and only while the hop count allows: it starts at
+       relay_hops, drops by one at each relay and may not exceed MAX_RELAY_HOPS
+    4. Storing node opens the query, pads the response to a fixed bucket size and seals it with
+       a key derived from its layer's shared secret
+    5. Response travels back along the same path, each relay sealing it again with its own
+       layer's key; only the requester can open it
+
+    Nodes hand out their lookup key in PONGs and in the routing table entries they share
+    (PeerInfo::lookup_key). It is made fresh each time the process starts.
+
+    Inputs:
+    - lookups from the application (namespace, key, storing node)
+    - inbound lookup messages from peers
+
+    Outputs:
+    - lookup results (same as a direct lookup)
+    - outbound lookup messages via LookupTransport
+*/
+
+use async_trait::async_trait;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Nonce,
+};
+use rand::seq::SliceRandom;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use super::client::DhtTransport;
+use super::dht_config::LookupMode;
+use super::message::DhtMessage;
+use super::{DhtConfig, DhtKey, DhtStorage, DhtValue, RoutingTable};
+
+/// Namespace for MLS key packages
+pub const KEY_PACKAGE_NAMESPACE: &str = "key_package";
+
+/// Namespace for channel listings
+pub const CHANNEL_LISTING_NAMESPACE: &str = "channel_listing";
+
+/// Namespace for public channels' GroupInfo
+pub const PUBLIC_GROUP_NAMESPACE: &str = "public_group";
+
+/// Namespace for ratchet trees shipped outside invites
+pub const RATCHET_TREE_NAMESPACE: &str = "ratchet_tree";
+
+/// Most relays a private lookup may pass through
+pub const MAX_RELAY_HOPS: u8 = 2;
+
+/// Response plaintexts are padded up to the smallest of these sizes that fits
+const PADDING_BUCKETS: [usize; 5] = [1024, 4096, 16 * 1024, 64 * 1024, 256 * 1024];
+
+const NONCE_SIZE: usize = 12;
+const REQUEST_KEY_INFO: &[u8] = b"spacepanda dht lookup request";
+const RESPONSE_KEY_INFO: &[u8] = b"spacepanda dht lookup response";
+
+/// Sends lookup messages to peers and returns their reply
+///
+/// The receiving peer only learns the identity of the node that called `request`.
+#[async_trait]
+pub trait LookupTransport: Send + Sync {
+    async fn request(&self, peer: DhtKey, payload: Vec<u8>) -> Result<Vec<u8>, String>;
+}
+
+/// Carries lookup messages as DHT LOOKUP requests
+pub struct DhtLookupTransport {
+    local_id: DhtKey,
+    transport: Arc<dyn DhtTransport>,
+    request_id_counter: AtomicU64,
+}
+
+impl DhtLookupTransport {
+    pub fn new(local_id: DhtKey, transport: Arc<dyn DhtTransport>) -> Self {
+        DhtLookupTransport { local_id, transport, request_id_counter: AtomicU64::new(0) }
+    }
+}
+
+#[async_trait]
+impl LookupTransport for DhtLookupTransport {
+    async fn request(&self, peer: DhtKey, payload: Vec<u8>) -> Result<Vec<u8>, String> {
+        let request_id = self.request_id_counter.fetch_add(1, Ordering::Relaxed) + 1;
+        let request = DhtMessage::Lookup { sender_id: self.local_id, payload, request_id };
+        match self.transport.request(peer, request).await? {
+            DhtMessage::LookupResponse { request_id: answered, result, .. }
+                if answered == request_id =>
+            {
+                result
+            }
+            other => Err(format!("Invalid LOOKUP response: {}", other.message_type())),
+        }
+    }
+}
+
+/// A node together with its lookup encryption key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LookupPeer {
+    pub id: DhtKey,
+    pub public_key: [u8; 32],
+}
+
+/// Wire messages exchanged between requester, relays and storing node
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum LookupMessage {
+    /// Plain lookup; the storing node sees who asked
+    Direct { key: DhtKey },
+    /// Onion layer sealed to the receiving node, which may forward `hops_left` more times
+    Onion { hops_left: u8, layer: Vec<u8> },
+}
+
+/// Plaintext of an onion layer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Layer {
+    /// Forward `inner` to `next_hop`
+    Relay { next_hop: DhtKey, inner: Vec<u8> },
+    /// Look `key` up here
+    Query { key: DhtKey },
+}
+
+/// Serves and performs DHT lookups, privately for sensitive namespaces
+pub struct PrivateLookup {
+    /// Local node ID
+    local_id: DhtKey,
+    /// DHT configuration (lookup modes per namespace)
+    config: DhtConfig,
+    /// Secret used to open sealed queries addressed to us
+    secret: StaticSecret,
+    /// Local storage answering lookups
+    storage: DhtStorage,
+    /// Known nodes; lookups are only relayed to these
+    routing_table: Arc<Mutex<RoutingTable>>,
+    /// Transport for outbound lookup messages
+    transport: Arc<dyn LookupTransport>,
+}
+
+impl PrivateLookup {
+    /// Create a lookup service with a fresh lookup encryption key
+    pub fn new(
+        local_id: DhtKey,
+        config: DhtConfig,
+        storage: DhtStorage,
+        routing_table: Arc<Mutex<RoutingTable>>,
+        transport: Arc<dyn LookupTransport>,
+    ) -> Self {
+        let mut secret_bytes = [0u8; 32];
+        rand::rng().fill_bytes(&mut secret_bytes);
+        PrivateLookup {
+            local_id,
+            config,
+            secret: StaticSecret::from(secret_bytes),
+            storage,
+            routing_table,
+            transport,
+        }
+    }
+
+    /// Get local node ID
+    pub fn local_id(&self) -> DhtKey {
+        self.local_id
+    }
+
+    /// Public key other nodes seal queries to
+    pub fn public_key(&self) -> [u8; 32] {
+        *PublicKey::from(&self.secret).as_bytes()
+    }
+
+    /// This node as a lookup target
+    pub fn as_peer(&self) -> LookupPeer {
+        LookupPeer { id: self.local_id, public_key: self.public_key() }
+    }
+
+    /// Lookup mode configured for `namespace`
+    pub fn lookup_mode(&self, namespace: &str) -> LookupMode {
+        self.config.lookup_mode(namespace)
+    }
+
+    /// Look up `key` on `storing_node` using the mode configured for `namespace`
+    ///
+    /// `relays` is the pool private lookups pick their relays from; the storing
+    /// node and this node are never picked.
+    pub async fn lookup(
+        &self,
+        namespace: &str,
+        key: DhtKey,
+        storing_node: &LookupPeer,
+        relays: &[LookupPeer],
+    ) -> Result<Option<DhtValue>, String> {
+        match self.config.lookup_mode(namespace) {
+            LookupMode::Direct => {
+                let response = self
+                    .transport
+                    .request(storing_node.id, encode(&LookupMessage::Direct { key })?)
+                    .await?;
+                bincode::deserialize(&response)
+                    .map_err(|e| format!("Failed to decode lookup response: {}", e))
+            }
+            LookupMode::Private { relay_hops } => {
+                self.private_lookup(key, storing_node, relays, relay_hops).await
+            }
+        }
+    }
+
+    /// Send an onion-sealed query through `relay_hops` random relays
+    async fn private_lookup(
+        &self,
+        key: DhtKey,
+        storing_node: &LookupPeer,
+        relays: &[LookupPeer],
+        relay_hops: usize,
+    ) -> Result<Option<DhtValue>, String> {
+        let hops_left = u8::try_from(relay_hops)
+            .map_err(|_| format!("Private lookups can't use {} relay hops", relay_hops))?;
+        let mut candidates: Vec<LookupPeer> = relays
+            .iter()
+            .copied()
+            .filter(|peer| peer.id != self.local_id && peer.id != storing_node.id)
+            .collect();
+        if candidates.len() < relay_hops {
+            return Err(format!(
+                "Private lookup needs {} relay(s), only {} available",
+                relay_hops,
+                candidates.len()
+            ));
+        }
+        candidates.shuffle(&mut rand::rng());
+        candidates.truncate(relay_hops);
+
+        let (layer, response_keys) = build_onion(&candidates, storing_node, key)?;
+        let first_hop = candidates.first().map_or(storing_node.id, |relay| relay.id);
+        let mut response = self
+            .transport
+            .request(first_hop, encode(&LookupMessage::Onion { hops_left, layer })?)
+            .await?;
+
+        // Peel the layer each hop sealed on the way back, first hop first
+        for response_key in &response_keys {
+            response = decrypt(response_key, &response)?;
+        }
+        bincode::deserialize(&unpad(&response)?)
+            .map_err(|e| format!("Failed to decode lookup response: {}", e))
+    }
+
+    /// Handle an inbound lookup message from `from` and return the reply
+    pub async fn handle_request(&self, from: DhtKey, data: &[u8]) -> Result<Vec<u8>, String> {
+        let message: LookupMessage = bincode::deserialize(data)
+            .map_err(|e| format!("Failed to decode lookup message from {}: {}", from, e))?;
+
+        let (hops_left, layer) = match message {
+            LookupMessage::Direct { key } => return encode(&self.storage.get(&key).ok()),
+            LookupMessage::Onion { hops_left, layer } => (hops_left, layer),
+        };
+        if hops_left > MAX_RELAY_HOPS {
+            return Err(format!(
+                "Lookup from {} asks for {} relay hops, at most {} are allowed",
+                from, hops_left, MAX_RELAY_HOPS
+            ));
+        }
+
+        let (layer, response_key) = open_layer(&self.secret, &layer)?;
+        let layer: Layer = bincode::deserialize(&layer)
+            .map_err(|e| format!("Failed to decode onion layer: {}", e))?;
+        let response = match layer {
+            Layer::Query { key } => pad(&encode(&self.storage.get(&key).ok())?),
+            Layer::Relay { next_hop, inner } => {
+                let hops_left = hops_left
+                    .checked_sub(1)
+                    .ok_or_else(|| format!("Lookup from {} has no relay hops left", from))?;
+                let known = self.routing_table.lock().await.get(&next_hop).is_some();
+                if !known {
+                    return Err(format!("Next hop {} is not a known node", next_hop));
+                }
+                let next = LookupMessage::Onion { hops_left, layer: inner };
+                self.transport.request(next_hop, encode(&next)?).await?
+            }
+        };
+        // Only the requester can open the reply; seal it once more for the way back
+        encrypt(&response_key, &response)
+    }
+}
+
+/// Seal a query for `key` to `storing_node`, then wrap it once per relay in `path`
+///
+/// Returns the outermost layer, for `path[0]`, and the keys the response is sealed
+/// with on its way back, in the order they have to be removed.
+fn build_onion(
+    path: &[LookupPeer],
+    storing_node: &LookupPeer,
+    key: DhtKey,
+) -> Result<(Vec<u8>, Vec<[u8; 32]>), String> {
+    let (mut layer, response_key) =
+        seal_layer(&storing_node.public_key, &encode(&Layer::Query { key })?)?;
+    let mut response_keys = vec![response_key];
+    let mut next_hop = storing_node.id;
+    for relay in path.iter().rev() {
+        let relay_layer = Layer::Relay { next_hop, inner: layer };
+        let (sealed, response_key) = seal_layer(&relay.public_key, &encode(&relay_layer)?)?;
+        layer = sealed;
+        response_keys.push(response_key);
+        next_hop = relay.id;
+    }
+    response_keys.reverse();
+    Ok((layer, response_keys))
+}
+
+fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, String> {
+    bincode::serialize(value).map_err(|e| format!("Failed to encode lookup message: {}", e))
+}
+
+/// Derive the request and response keys from an ECDH shared secret
+fn derive_keys(shared_secret: &[u8]) -> Result<([u8; 32], [u8; 32]), String> {
+    let hk = hkdf::Hkdf::<sha2::Sha256>::new(None, shared_secret);
+    let mut request_key = [0u8; 32];
+    let mut response_key = [0u8; 32];
+    hk.expand(REQUEST_KEY_INFO, &mut request_key)
+        .map_err(|e| format!("HKDF expand failed: {}", e))?;
+    hk.expand(RESPONSE_KEY_INFO, &mut response_key)
+        .map_err(|e| format!("HKDF expand failed: {}", e))?;
+    Ok((request_key, response_key))
+}
+
+/// Seal an onion layer to one hop: ephemeral_pk (32) || nonce (12) || ciphertext
+///
+/// Returns the sealed layer and the key that hop seals the response with.
+fn seal_layer(recipient: &[u8; 32], plaintext: &[u8]) -> Result<(Vec<u8>, [u8; 32]), String> {
+    let mut ephemeral_bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut ephemeral_bytes);
+    let ephemeral_sk = StaticSecret::from(ephemeral_bytes);
+    let ephemeral_pk = PublicKey::from(&ephemeral_sk);
+
+    let shared_secret = ephemeral_sk.diffie_hellman(&PublicKey::from(*recipient));
+    let (request_key, response_key) = derive_keys(shared_secret.as_bytes())?;
+
+    let mut sealed = ephemeral_pk.as_bytes().to_vec();
+    sealed.extend(encrypt(&request_key, plaintext)?);
+    Ok((sealed, response_key))
+}
+
+/// Open a layer sealed to `secret`, returning it with the response key
+fn open_layer(secret: &StaticSecret, sealed: &[u8]) -> Result<(Vec<u8>, [u8; 32]), String> {
+    if sealed.len() < 32 {
+        return Err("Sealed layer too short".to_string());
+    }
+    let mut ephemeral_pk = [0u8; 32];
+    ephemeral_pk.copy_from_slice(&sealed[..32]);
+
+    let shared_secret = secret.diffie_hellman(&PublicKey::from(ephemeral_pk));
+    let (request_key, response_key) = derive_keys(shared_secret.as_bytes())?;
+
+    Ok((decrypt(&request_key, &sealed[32..])?, response_key))
+}
+
+/// nonce (12) || ciphertext
+fn encrypt(key: &[u8; 32], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let mut nonce_bytes = [0u8; NONCE_SIZE];
+    rand::rng().fill_bytes(&mut nonce_bytes);
+
+    let cipher = ChaCha20Poly1305::new(key.into());
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce_bytes), plaintext)
+        .map_err(|e| format!("Encryption failed: {}", e))?;
+
+    let mut result = nonce_bytes.to_vec();
+    result.extend(ciphertext);
+    Ok(result)
+}
+
+fn decrypt(key: &[u8; 32], data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < NONCE_SIZE {
+        return Err("Ciphertext too short".to_string());
+    }
+    let cipher = ChaCha20Poly1305::new(key.into());
+    cipher
+        .decrypt(Nonce::from_slice(&data[..NONCE_SIZE]), &data[NONCE_SIZE..])
+        .map_err(|e| format!("Decryption failed: {}", e))
+}
+
+/// Length-prefix `data` and zero-pad it to the smallest fitting bucket
+///
+/// Payloads larger than the biggest bucket are padded to a multiple of it.
+fn pad(data: &[u8]) -> Vec<u8> {
+    let needed = data.len() + 4;
+    let largest = PADDING_BUCKETS[PADDING_BUCKETS.len() - 1];
+    let size = PADDING_BUCKETS
+        .iter()
+        .copied()
+        .find(|bucket| *bucket >= needed)
+        .unwrap_or_else(|| needed.div_ceil(largest) * largest);
+
+    let mut padded = Vec::with_capacity(size);
+    padded.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    padded.extend_from_slice(data);
+    padded.resize(size, 0);
+    padded
+}
+
+fn unpad(padded: &[u8]) -> Result<Vec<u8>, String> {
+    if padded.len() < 4 {
+        return Err("Padded response too short".to_string());
+    }
+    let len = u32::from_le_bytes([padded[0], padded[1], padded[2], padded[3]]) as usize;
+    padded
+        .get(4..4 + len)
+        .map(|data| data.to_vec())
+        .ok_or_else(|| "Padded response length out of range".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pad_to_fixed_buckets() {
+        assert_eq!(pad(b"").len(), 1024);
+        assert_eq!(pad(&[7u8; 1020]).len(), 1024);
+        assert_eq!(pad(&[7u8; 1021]).len(), 4096);
+        assert_eq!(pad(&[7u8; 300 * 1024]).len(), 512 * 1024);
+
+        let data = b"key package bytes".to_vec();
+        assert_eq!(unpad(&pad(&data)).unwrap(), data);
+    }
+
+    #[test]
+    fn test_unpad_rejects_bad_length() {
+        let mut padded = pad(b"abc");
+        padded[..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(unpad(&padded).is_err());
+    }
+
+    #[test]
+    fn test_sealed_layer_roundtrip() {
+        let secret = StaticSecret::from([9u8; 32]);
+        let public = *PublicKey::from(&secret).as_bytes();
+
+        let (sealed, response_key) = seal_layer(&public, b"query").unwrap();
+        let (query, opened_key) = open_layer(&secret, &sealed).unwrap();
+
+        assert_eq!(query, b"query");
+        assert_eq!(response_key, opened_key);
+
+        // A different node cannot open it
+        assert!(open_layer(&StaticSecret::from([1u8; 32]), &sealed).is_err());
+    }
+
+    #[test]
+    fn test_each_layer_only_names_the_next_hop() {
+        let secrets: Vec<StaticSecret> = (1..=3).map(|i| StaticSecret::from([i; 32])).collect();
+        let peers: Vec<LookupPeer> = secrets
+            .iter()
+            .enumerate()
+            .map(|(i, secret)| LookupPeer {
+                id: DhtKey::hash(format!("node{}", i).as_bytes()),
+                public_key: *PublicKey::from(secret).as_bytes(),
+            })
+            .collect();
+        let key = DhtKey::hash(b"key_package:bob");
+
+        let (mut layer, response_keys) = build_onion(&peers[..2], &peers[2], key).unwrap();
+        assert_eq!(response_keys.len(), 3);
+        for (i, secret) in secrets.iter().enumerate() {
+            let (plaintext, response_key) = open_layer(secret, &layer).unwrap();
+            assert_eq!(response_key, response_keys[i]);
+            match bincode::deserialize(&plaintext).unwrap() {
+                Layer::Relay { next_hop, inner } => {
+                    assert_eq!(next_hop, peers[i + 1].id);
+                    layer = inner;
+                }
+                Layer::Query { key: queried } => {
+                    assert_eq!(i, 2);
+                    assert_eq!(queried, key);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,171 @@
+/*
+    ProviderStore - who can serve which content-addressed blob
+
+    Responsibilities:
+    `provider_store.rs` keeps provider records: "peer P can serve the blob whose
+    hash is H". Nodes close to H hold the records instead of the blob itself,
+    so large attachment chunks never land in `DhtStorage`; the blob is fetched
+    from a provider directly.
+
+    Records expire after their TTL unless the provider announces again, so a
+    provider that goes away drops out of the results on its own.
+
+    Inputs:
+    - add(content_hash, provider, ttl) from ADD_PROVIDER requests
+    - providers(content_hash) for GET_PROVIDERS requests
+
+    Outputs:
+    - live provider records
+    - expiration scans
+*/
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::dht_key::DhtKey;
+use super::message::PeerInfo;
+
+/// Get current Unix timestamp in seconds
+fn current_timestamp() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// A provider of one blob, until `expires_at`
+#[derive(Debug, Clone)]
+struct ProviderRecord {
+    provider: PeerInfo,
+    /// When this record expires (Unix timestamp)
+    expires_at: u64,
+}
+
+/// In-memory provider records, keyed by content hash
+#[derive(Clone)]
+pub struct ProviderStore {
+    /// content hash -> provider ID -> record
+    records: Arc<RwLock<HashMap<DhtKey, HashMap<DhtKey, ProviderRecord>>>>,
+    /// Longest TTL honored, in seconds; longer ones are cut short
+    max_ttl: u64,
+}
+
+impl ProviderStore {
+    /// Create an empty provider store
+    pub fn new() -> Self {
+        ProviderStore { records: Arc::new(RwLock::new(HashMap::new())), max_ttl: u64::MAX }
+    }
+
+    /// Builder: cap the TTL of every record at `max_ttl`
+    pub fn with_max_ttl(mut self, max_ttl: Duration) -> Self {
+        self.max_ttl = max_ttl.as_secs();
+        self
+    }
+
+    /// Record that `provider` can serve `content_hash` for the next `ttl` seconds
+    ///
+    /// Announcing again replaces the provider's previous record.
+    pub fn add(&self, content_hash: DhtKey, provider: PeerInfo, ttl: u64) -> Result<(), String> {
+        let mut records = self
+            .records
+            .write()
+            .map_err(|e| format!("Failed to acquire write lock: {}", e))?;
+        let expires_at = current_timestamp().saturating_add(ttl.min(self.max_ttl));
+        records
+            .entry(content_hash)
+            .or_default()
+            .insert(provider.id, ProviderRecord { provider, expires_at });
+        Ok(())
+    }
+
+    /// Live providers of `content_hash`
+    pub fn providers(&self, content_hash: &DhtKey) -> Vec<PeerInfo> {
+        let Ok(records) = self.records.read() else {
+            return Vec::new();
+        };
+        let now = current_timestamp();
+        records
+            .get(content_hash)
+            .map(|providers| {
+                providers
+                    .values()
+                    .filter(|record| record.expires_at > now)
+                    .map(|record| record.provider.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Drop expired records, returning how many were removed
+    pub fn cleanup_expired(&self) -> usize {
+        let Ok(mut records) = self.records.write() else {
+            return 0;
+        };
+        let now = current_timestamp();
+        let mut removed = 0;
+        records.retain(|_, providers| {
+            let before = providers.len();
+            providers.retain(|_, record| record.expires_at > now);
+            removed += before - providers.len();
+            !providers.is_empty()
+        });
+        removed
+    }
+
+    /// Number of records held, live or not yet swept
+    pub fn len(&self) -> usize {
+        self.records.read().map(|r| r.values().map(HashMap::len).sum()).unwrap_or(0)
+    }
+
+    /// Whether no records are held
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for ProviderStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer(name: &str) -> PeerInfo {
+        PeerInfo::new(DhtKey::hash_string(name), format!("{}:7000", name))
+    }
+
+    #[test]
+    fn test_add_and_list_providers() {
+        let store = ProviderStore::new();
+        let blob = DhtKey::hash(b"blob");
+
+        store.add(blob, peer("alice"), 60).unwrap();
+        store.add(blob, peer("bob"), 60).unwrap();
+        // Re-announcing doesn't duplicate
+        store.add(blob, peer("alice"), 60).unwrap();
+
+        let mut ids: Vec<DhtKey> = store.providers(&blob).iter().map(|p| p.id).collect();
+        ids.sort();
+        let mut expected = vec![DhtKey::hash_string("alice"), DhtKey::hash_string("bob")];
+        expected.sort();
+        assert_eq!(ids, expected);
+        assert!(store.providers(&DhtKey::hash(b"other")).is_empty());
+    }
+
+    #[test]
+    fn test_expired_providers_are_hidden_and_swept() {
+        let store = ProviderStore::new().with_max_ttl(Duration::from_secs(3600));
+        let blob = DhtKey::hash(b"blob");
+
+        store.add(blob, peer("gone"), 0).unwrap();
+        store.add(blob, peer("here"), u64::MAX).unwrap();
+
+        let providers = store.providers(&blob);
+        assert_eq!(providers.len(), 1);
+        assert_eq!(providers[0].id, DhtKey::hash_string("here"));
+
+        assert_eq!(store.cleanup_expired(), 1);
+        assert_eq!(store.len(), 1);
+    }
+}
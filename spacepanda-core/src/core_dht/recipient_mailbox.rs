@@ -0,0 +1,183 @@
+/*
+    RecipientMailbox - store-and-forward deliveries addressed to one identity
+
+    Responsibilities:
+    `recipient_mailbox.rs` holds payloads for a recipient who may be offline, under keys
+    derived from the hash of the recipient's identity key. Payloads are opaque here; callers
+    seal them to the recipient before depositing.
+
+    Layout (per recipient):
+    - a head entry whose data is the next sequence number
+    - one entry per deposit, keyed by recipient and sequence number
+
+    Every entry expires after the mailbox TTL. The recipient removes entries once it has
+    processed them, so polling again doesn't deliver them twice.
+
+    Inputs:
+    - deposit(recipient, payload)
+    - fetch(recipient), remove(recipient, seq)
+
+    Outputs:
+    - pending entries in deposit order
+*/
+
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::{DhtKey, DhtStorage, DhtValue};
+
+/// Namespace for recipient mailboxes
+pub const RECIPIENT_MAILBOX_NAMESPACE: &str = "recipient_mailbox";
+
+/// Default time a deposit waits for its recipient
+pub const DEFAULT_MAILBOX_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// A payload waiting in a recipient's mailbox
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecipientMailboxEntry {
+    /// Position in the mailbox, used to remove the entry
+    pub seq: u64,
+    /// Payload as deposited (sealed by the sender)
+    pub payload: Vec<u8>,
+}
+
+/// Per-recipient mailboxes stored in the DHT
+pub struct RecipientMailbox {
+    storage: DhtStorage,
+    ttl: Duration,
+    /// Serializes deposits so sequence numbers are unique
+    deposit_lock: Mutex<()>,
+}
+
+impl RecipientMailbox {
+    /// Create mailboxes backed by the given DHT storage
+    pub fn new(storage: DhtStorage) -> Self {
+        Self { storage, ttl: DEFAULT_MAILBOX_TTL, deposit_lock: Mutex::new(()) }
+    }
+
+    /// Set how long deposits wait for their recipient
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Mailbox address of the holder of an identity key
+    pub fn recipient(identity_public_key: &[u8]) -> DhtKey {
+        DhtKey::hash(identity_public_key)
+    }
+
+    fn head_key(recipient: &DhtKey) -> DhtKey {
+        let name =
+            format!("{}-head:{}", RECIPIENT_MAILBOX_NAMESPACE, hex::encode(recipient.as_bytes()));
+        DhtKey::hash(name.as_bytes())
+    }
+
+    fn entry_key(recipient: &DhtKey, seq: u64) -> DhtKey {
+        let name = format!(
+            "{}:{}:{}",
+            RECIPIENT_MAILBOX_NAMESPACE,
+            hex::encode(recipient.as_bytes()),
+            seq
+        );
+        DhtKey::hash(name.as_bytes())
+    }
+
+    /// Next sequence number to be deposited
+    fn head(&self, recipient: &DhtKey) -> u64 {
+        self.storage
+            .get(&Self::head_key(recipient))
+            .ok()
+            .and_then(|value| value.data.try_into().ok())
+            .map(u64::from_le_bytes)
+            .unwrap_or(0)
+    }
+
+    /// Leave a payload for a recipient
+    ///
+    /// Returns the entry's sequence number.
+    pub fn deposit(&self, recipient: &DhtKey, payload: Vec<u8>) -> Result<u64, String> {
+        let _guard = self.deposit_lock.lock().unwrap_or_else(|e| e.into_inner());
+
+        let seq = self.head(recipient);
+        self.put(Self::entry_key(recipient, seq), payload)?;
+        self.put(Self::head_key(recipient), (seq + 1).to_le_bytes().to_vec())?;
+        Ok(seq)
+    }
+
+    /// Pending entries for a recipient, in deposit order
+    ///
+    /// Removed and expired entries are skipped.
+    pub fn fetch(&self, recipient: &DhtKey) -> Vec<RecipientMailboxEntry> {
+        (0..self.head(recipient))
+            .filter_map(|seq| {
+                let value = self.storage.get(&Self::entry_key(recipient, seq)).ok()?;
+                Some(RecipientMailboxEntry { seq, payload: value.data })
+            })
+            .collect()
+    }
+
+    /// Remove a processed entry
+    ///
+    /// Removing an entry that is already gone is not an error.
+    pub fn remove(&self, recipient: &DhtKey, seq: u64) {
+        let _ = self.storage.delete(&Self::entry_key(recipient, seq));
+    }
+
+    fn put(&self, key: DhtKey, data: Vec<u8>) -> Result<(), String> {
+        // Sequence by write time; an expired entry may still hold the key
+        let previous = self.storage.get(&key).ok().map(|value| value.sequence);
+        let sequence = now_millis().max(previous.map_or(0, |seq| seq + 1));
+        let value = DhtValue::new(data).with_ttl_duration(self.ttl).with_sequence(sequence);
+
+        match self.storage.put(key, value.clone()) {
+            Err(_) if previous.is_none() => {
+                let _ = self.storage.delete(&key);
+                self.storage.put(key, value)
+            }
+            result => result,
+        }
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deposit_fetch_remove() {
+        let mailbox = RecipientMailbox::new(DhtStorage::new());
+        let bob = RecipientMailbox::recipient(b"bob identity key");
+        let carol = RecipientMailbox::recipient(b"carol identity key");
+
+        assert_eq!(mailbox.deposit(&bob, b"welcome".to_vec()).unwrap(), 0);
+        assert_eq!(mailbox.deposit(&bob, b"commit".to_vec()).unwrap(), 1);
+        mailbox.deposit(&carol, b"other".to_vec()).unwrap();
+
+        let pending = mailbox.fetch(&bob);
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].payload, b"welcome");
+
+        mailbox.remove(&bob, 0);
+        mailbox.remove(&bob, 0);
+        assert_eq!(
+            mailbox.fetch(&bob),
+            vec![RecipientMailboxEntry { seq: 1, payload: b"commit".to_vec() }]
+        );
+        assert_eq!(mailbox.fetch(&carol).len(), 1);
+    }
+
+    #[test]
+    fn test_expired_entries_are_skipped() {
+        let mailbox = RecipientMailbox::new(DhtStorage::new()).with_ttl(Duration::ZERO);
+        let bob = RecipientMailbox::recipient(b"bob identity key");
+        mailbox.deposit(&bob, b"stale".to_vec()).unwrap();
+        assert!(mailbox.fetch(&bob).is_empty());
+    }
+}
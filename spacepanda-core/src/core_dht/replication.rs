@@ -73,6 +73,16 @@ impl ReplicationState {
         (now - self.last_replicated) > interval_secs
     }
 
+    /// Whether an original value expiring at `expires_at` must be republished now
+    ///
+    /// Republishing is due once the value would otherwise expire within the next
+    /// two rounds, so one late round doesn't let it lapse.
+    fn needs_republish(&self, expires_at: u64, interval_secs: u64) -> bool {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+
+        self.is_original && expires_at.saturating_sub(now) <= interval_secs.saturating_mul(2)
+    }
+
     fn mark_replicated(&mut self) {
         self.last_replicated =
             SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
@@ -140,11 +150,21 @@ impl ReplicationManager {
         let keys = self.storage.active_keys()?;
 
         for key in keys {
-            if let Ok(value) = self.storage.get(&key) {
+            if let Ok(mut value) = self.storage.get(&key) {
                 // Check if this key needs replication
                 let mut states = self.replication_state.lock().await;
                 let state = states.entry(key).or_insert_with(|| ReplicationState::new(false));
 
+                // Refresh values we published before they expire, and push the
+                // refreshed copy to every replica again
+                let expires_at = self.storage.expires_at(&key)?;
+                if state.needs_republish(expires_at, self.config.republish_interval.as_secs()) {
+                    self.storage.refresh(&key, value.ttl)?;
+                    value = self.storage.get(&key)?;
+                    state.replica_peers.clear();
+                    state.last_replicated = 0;
+                }
+
                 let interval = if state.is_original {
                     self.config.republish_interval.as_secs()
                 } else {
@@ -205,6 +225,11 @@ impl ReplicationManager {
         Ok(())
     }
 
+    /// Get the storage this manager replicates
+    pub fn storage(&self) -> &DhtStorage {
+        &self.storage
+    }
+
     /// Mark a key as original (we published it)
     pub async fn mark_original(&self, key: DhtKey) {
         let mut states = self.replication_state.lock().await;
@@ -13,11 +13,17 @@
     Outputs:
     - list of closest K peers for a given key
     - bucket refresh events
+
+    Every peer carries a reputation (see core_router::reputation), fed by the outcome of
+    RPCs to it and by invalid messages from it. Quarantined peers are left out of the
+    closest peers, so lookups neither ask them nor hand them out, and they are the first
+    to go when a full bucket needs room. Peers with a poor but unquarantined reputation
+    are only used once the better ones run out.
 */
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Get current Unix timestamp in seconds
 /// Returns 0 if system clock is before UNIX epoch (should never happen on modern systems)
@@ -26,6 +32,10 @@ fn current_timestamp() -> u64 {
 }
 
 use super::dht_key::DhtKey;
+use crate::core_router::{Behaviour, Reputation, ReputationConfig};
+
+/// Reputation below which a peer is only used when better ones run out
+const LOW_REPUTATION: f64 = 0.35;
 
 /// Peer information stored in routing table
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -38,24 +48,74 @@ pub struct PeerContact {
     pub last_seen: u64,
     /// Number of failed RPC attempts
     pub failed_rpcs: u32,
+    /// Running score of how reliably the peer answers, from 0.0 to 1.0
+    #[serde(default = "default_reliability")]
+    pub reliability: f64,
+    /// Smoothed round-trip time of RPCs to the peer, once one has been timed
+    #[serde(default)]
+    pub rtt: Option<Duration>,
+    /// How well the peer has behaved lately
+    #[serde(default)]
+    pub reputation: Reputation,
+    /// Key private lookups are sealed to, once the peer has given it
+    #[serde(default)]
+    pub lookup_key: Option<[u8; 32]>,
+}
+
+/// Score of a peer with no track record
+const DEFAULT_RELIABILITY: f64 = 0.5;
+
+/// Weight of the latest RPC in the reliability score
+const RELIABILITY_WEIGHT: f64 = 0.2;
+
+/// The latest sample counts for 1/RTT_SMOOTHING of the smoothed round-trip time
+const RTT_SMOOTHING: u32 = 8;
+
+fn default_reliability() -> f64 {
+    DEFAULT_RELIABILITY
 }
 
 impl PeerContact {
     pub fn new(id: DhtKey, address: String) -> Self {
         let now = current_timestamp();
 
-        PeerContact { id, address, last_seen: now, failed_rpcs: 0 }
+        PeerContact {
+            id,
+            address,
+            last_seen: now,
+            failed_rpcs: 0,
+            reliability: DEFAULT_RELIABILITY,
+            rtt: None,
+            reputation: Reputation::default(),
+            lookup_key: None,
+        }
+    }
+
+    /// Builder: set the peer's lookup key
+    pub fn with_lookup_key(mut self, lookup_key: Option<[u8; 32]>) -> Self {
+        self.lookup_key = lookup_key;
+        self
     }
 
     /// Update last seen timestamp
     pub fn touch(&mut self) {
         self.last_seen = current_timestamp();
         self.failed_rpcs = 0;
+        self.reliability += (1.0 - self.reliability) * RELIABILITY_WEIGHT;
     }
 
     /// Mark a failed RPC
     pub fn mark_failed(&mut self) {
         self.failed_rpcs += 1;
+        self.reliability -= self.reliability * RELIABILITY_WEIGHT;
+    }
+
+    /// Fold a timed RPC into the smoothed round-trip time
+    pub fn record_rtt(&mut self, sample: Duration) {
+        self.rtt = Some(match self.rtt {
+            Some(rtt) => (rtt * (RTT_SMOOTHING - 1) + sample) / RTT_SMOOTHING,
+            None => sample,
+        });
     }
 
     /// Check if peer is considered stale (no response in threshold seconds)
@@ -84,7 +144,7 @@ impl KBucket {
 
     /// Try to insert a peer into the bucket
     fn insert(&mut self, peer: PeerContact) -> Result<(), String> {
-        // If peer already exists, update it
+        // If peer already exists, update it; a quarantined peer's record stands
         if let Some(existing) = self.peers.iter_mut().find(|p| p.id == peer.id) {
             existing.address = peer.address;
             existing.touch();
@@ -96,8 +156,13 @@ impl KBucket {
             self.peers.push(peer);
             Ok(())
         } else {
-            // Bucket full - try to replace a stale peer
-            if let Some(stale_idx) = self.peers.iter().position(|p| p.is_stale(3600)) {
+            // Bucket full - try to replace a quarantined peer, then a stale one
+            let evictable = self
+                .peers
+                .iter()
+                .position(|p| p.reputation.is_quarantined())
+                .or_else(|| self.peers.iter().position(|p| p.is_stale(3600)));
+            if let Some(stale_idx) = evictable {
                 self.peers[stale_idx] = peer;
                 Ok(())
             } else {
@@ -142,12 +207,25 @@ pub struct RoutingTable {
     buckets: HashMap<usize, KBucket>,
     /// Bucket size (k parameter)
     k: usize,
+    /// How peer reputations are scored and when peers are quarantined
+    reputation_config: ReputationConfig,
 }
 
 impl RoutingTable {
     /// Create a new routing table
     pub fn new(local_id: DhtKey, k: usize) -> Self {
-        RoutingTable { local_id, buckets: HashMap::new(), k }
+        RoutingTable {
+            local_id,
+            buckets: HashMap::new(),
+            k,
+            reputation_config: ReputationConfig::default(),
+        }
+    }
+
+    /// Builder: tune how reputation is scored and when peers are quarantined
+    pub fn with_reputation_config(mut self, config: ReputationConfig) -> Self {
+        self.reputation_config = config;
+        self
     }
 
     /// Get the bucket index for a given peer ID
@@ -189,38 +267,93 @@ impl RoutingTable {
             .cloned()
     }
 
-    /// Update a peer's last seen timestamp
+    /// Update a peer's last seen timestamp after it answered
     pub fn touch(&mut self, id: &DhtKey) {
-        let idx = self.bucket_index(id);
+        let config = self.reputation_config.clone();
+        if let Some(peer) = self.peer_mut(id) {
+            peer.touch();
+            let latency = peer.rtt;
+            peer.reputation.record(Behaviour::RpcSucceeded { latency }, &config);
+        }
+    }
 
-        if let Some(bucket) = self.buckets.get_mut(&idx) {
-            if let Some(peer) = bucket.peers.iter_mut().find(|p| &p.id == id) {
-                peer.touch();
-            }
+    /// Record the key a peer seals private lookups to
+    pub fn set_lookup_key(&mut self, id: &DhtKey, lookup_key: [u8; 32]) {
+        if let Some(peer) = self.peer_mut(id) {
+            peer.lookup_key = Some(lookup_key);
+        }
+    }
+
+    /// Record how long an RPC to a peer took
+    pub fn record_rtt(&mut self, id: &DhtKey, rtt: Duration) {
+        if let Some(peer) = self.peer_mut(id) {
+            peer.record_rtt(rtt);
         }
     }
 
     /// Mark a peer as having failed an RPC
     pub fn mark_failed(&mut self, id: &DhtKey) {
-        let idx = self.bucket_index(id);
+        self.record(id, Behaviour::RpcFailed);
+        if let Some(peer) = self.peer_mut(id) {
+            peer.mark_failed();
+        }
+    }
 
-        if let Some(bucket) = self.buckets.get_mut(&idx) {
-            if let Some(peer) = bucket.peers.iter_mut().find(|p| &p.id == id) {
-                peer.mark_failed();
-            }
+    /// Mark a peer as having sent a malformed, unexpected or wrongly signed message
+    ///
+    /// Returns true if this put the peer in quarantine.
+    pub fn mark_invalid(&mut self, id: &DhtKey) -> bool {
+        self.record(id, Behaviour::InvalidMessage)
+    }
+
+    /// Fold a behaviour into a peer's reputation; returns true if it put the peer in
+    /// quarantine
+    pub fn record(&mut self, id: &DhtKey, behaviour: Behaviour) -> bool {
+        let config = self.reputation_config.clone();
+        let Some(peer) = self.peer_mut(id) else {
+            return false;
+        };
+        let change = peer.reputation.record(behaviour, &config);
+        if change.quarantined {
+            tracing::warn!("DHT peer {} quarantined, reputation {:.2}", id, change.score);
+            crate::core_router::metrics::peer_quarantined();
         }
+        change.quarantined
+    }
+
+    /// Reputation score of a peer, if it is in the table
+    pub fn reputation(&self, id: &DhtKey) -> Option<f64> {
+        self.get(id).map(|peer| peer.reputation.score(&self.reputation_config))
+    }
+
+    /// Whether a peer in the table is quarantined
+    pub fn is_quarantined(&self, id: &DhtKey) -> bool {
+        self.get(id).is_some_and(|peer| peer.reputation.is_quarantined())
+    }
+
+    fn peer_mut(&mut self, id: &DhtKey) -> Option<&mut PeerContact> {
+        let idx = self.bucket_index(id);
+        self.buckets.get_mut(&idx)?.peers.iter_mut().find(|p| &p.id == id)
     }
 
     /// Find the k closest peers to a target key
+    ///
+    /// Quarantined peers are left out, and peers with a low reputation come after the
+    /// others.
     pub fn find_closest(&self, target: &DhtKey, count: usize) -> Vec<PeerContact> {
         let mut all_peers: Vec<PeerContact> = self
             .buckets
             .values()
-            .flat_map(|bucket| bucket.peers().iter().cloned())
+            .flat_map(|bucket| bucket.peers().iter())
+            .filter(|peer| !peer.reputation.is_quarantined())
+            .cloned()
             .collect();
 
-        // Sort by XOR distance to target
-        all_peers.sort_by_key(|peer| peer.id.distance(target));
+        // Sort by XOR distance to target, well-behaved peers first
+        all_peers.sort_by_key(|peer| {
+            let low = peer.reputation.score(&self.reputation_config) < LOW_REPUTATION;
+            (low, peer.id.distance(target))
+        });
 
         // Return up to count closest peers
         all_peers.into_iter().take(count).collect()
@@ -272,6 +405,11 @@ impl RoutingTable {
     pub fn local_id(&self) -> DhtKey {
         self.local_id
     }
+
+    /// Get the bucket size (k)
+    pub fn k(&self) -> usize {
+        self.k
+    }
 }
 
 #[cfg(test)]
@@ -312,6 +450,7 @@ mod tests {
 
         peer.mark_failed();
         assert_eq!(peer.failed_rpcs, 1);
+        assert!(peer.reliability < 0.5);
 
         peer.mark_failed();
         peer.mark_failed();
@@ -533,4 +672,20 @@ mod tests {
         assert_eq!(removed, 2);
         assert_eq!(table.size(), 3);
     }
+
+    #[test]
+    fn test_record_rtt_smooths_samples() {
+        let local_id = DhtKey::hash(b"local");
+        let mut table = RoutingTable::new(local_id, 20);
+        let peer_id = DhtKey::hash(b"peer");
+        table.insert(PeerContact::new(peer_id, "127.0.0.1:8000".to_string())).unwrap();
+        assert_eq!(table.get(&peer_id).unwrap().rtt, None);
+
+        table.record_rtt(&peer_id, Duration::from_millis(80));
+        assert_eq!(table.get(&peer_id).unwrap().rtt, Some(Duration::from_millis(80)));
+
+        // One slow RPC only nudges the estimate
+        table.record_rtt(&peer_id, Duration::from_millis(880));
+        assert_eq!(table.get(&peer_id).unwrap().rtt, Some(Duration::from_millis(180)));
+    }
 }
@@ -13,6 +13,7 @@
       - on_find_node
       - on_find_value
       - on_store_value
+      - on_add_provider / on_get_providers
       - on_ping
     - respond through router
 
@@ -28,11 +29,16 @@
 
 use std::sync::Arc;
 use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
 
+use super::dht_storage::PutError;
 use super::events::DhtEvent;
-use super::message::{DhtMessage, FindValueResult, PeerInfo};
+use super::message::{DhtMessage, FindValueResult, PeerInfo, StoreErrorCode};
+use super::private_lookup::PrivateLookup;
+use super::provider_store::ProviderStore;
+use super::routing_table::PeerContact;
 use super::{DhtConfig, DhtKey, DhtStorage, DhtValue, RoutingTable};
-use crate::core_router::RouterHandle;
+use crate::core_router::{Behaviour, PeerId, RateLimitResult, RateLimiter, RouterHandle};
 
 /// DHT server for handling inbound RPC requests
 pub struct DhtServer {
@@ -44,10 +50,16 @@ pub struct DhtServer {
     router: Arc<RouterHandle>,
     /// Local storage
     storage: DhtStorage,
+    /// Provider records for blobs held elsewhere
+    providers: ProviderStore,
     /// Routing table
     routing_table: Arc<Mutex<RoutingTable>>,
     /// Event channel
     event_tx: mpsc::Sender<DhtEvent>,
+    /// Throttles peers that keep storing over quota
+    rate_limiter: Option<Arc<RateLimiter>>,
+    /// Answers and relays private lookups
+    private_lookup: Option<Arc<PrivateLookup>>,
 }
 
 impl DhtServer {
@@ -60,7 +72,58 @@ impl DhtServer {
         routing_table: Arc<Mutex<RoutingTable>>,
         event_tx: mpsc::Sender<DhtEvent>,
     ) -> Self {
-        DhtServer { local_id, config, router, storage, routing_table, event_tx }
+        let providers = ProviderStore::new().with_max_ttl(config.provider_ttl);
+        DhtServer {
+            local_id,
+            config,
+            router,
+            storage,
+            providers,
+            routing_table,
+            event_tx,
+            rate_limiter: None,
+            private_lookup: None,
+        }
+    }
+
+    /// Builder: count over-quota STOREs against peers in `rate_limiter`
+    ///
+    /// Once a peer's circuit opens, its STOREs are refused outright until the
+    /// breaker lets it try again.
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Builder: answer and relay private lookups with `private_lookup`
+    ///
+    /// Its lookup key is handed out in every PONG.
+    pub fn with_private_lookup(mut self, private_lookup: Arc<PrivateLookup>) -> Self {
+        self.private_lookup = Some(private_lookup);
+        self
+    }
+
+    /// Provider records this server holds
+    pub fn providers(&self) -> &ProviderStore {
+        &self.providers
+    }
+
+    /// Drop expired provider records every `sweep_interval`
+    ///
+    /// Lookups never return expired providers anyway; this only frees the space.
+    pub fn spawn_provider_sweeper(&self) -> JoinHandle<()> {
+        let providers = self.providers.clone();
+        let period = self.config.sweep_interval;
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(period);
+            loop {
+                tick.tick().await;
+                let removed = providers.cleanup_expired();
+                if removed > 0 {
+                    tracing::debug!("Swept {} expired provider records", removed);
+                }
+            }
+        })
     }
 
     /// Handle incoming DHT message
@@ -69,7 +132,7 @@ impl DhtServer {
         // For now, just acknowledge receipt and update routing table
 
         // Create PeerContact and add to routing table
-        let peer = super::routing_table::PeerContact::new(from, format!("unknown:{}", from));
+        let peer = PeerContact::new(from, format!("unknown:{}", from));
         let _ = self.routing_table.lock().await.insert(peer);
 
         // Emit peer discovered event
@@ -79,6 +142,69 @@ impl DhtServer {
         Ok(())
     }
 
+    /// Answer a request, returning the response to send back
+    ///
+    /// Responses and other non-requests get no answer.
+    pub async fn respond(&self, from: DhtKey, request: DhtMessage) -> Option<DhtMessage> {
+        let sender_id = self.local_id;
+        match request {
+            DhtMessage::Ping { .. } => {
+                let _ = self.handle_ping(from).await;
+                let lookup_key = self.private_lookup.as_ref().map(|lookup| lookup.public_key());
+                Some(DhtMessage::new_pong(sender_id, lookup_key))
+            }
+            DhtMessage::FindNode { target, request_id, .. } => {
+                let nodes = self.handle_find_node(from, target, request_id).await;
+                Some(DhtMessage::FindNodeResponse { sender_id, nodes, request_id })
+            }
+            DhtMessage::FindValue { key, request_id, .. } => {
+                let result = self.handle_find_value(from, key, request_id).await;
+                Some(DhtMessage::FindValueResponse { sender_id, request_id, result })
+            }
+            DhtMessage::Store { key, value, request_id, .. } => {
+                let (error, code) = match self.throttled_store(from, key, value, request_id).await {
+                    Ok(()) => (None, None),
+                    Err((error, code)) => (Some(error), code),
+                };
+                Some(DhtMessage::StoreAck {
+                    sender_id,
+                    success: error.is_none(),
+                    request_id,
+                    error,
+                    code,
+                })
+            }
+            DhtMessage::AddProvider { key, provider, ttl, request_id, .. } => {
+                let result = self.handle_add_provider(from, key, provider, ttl);
+                Some(DhtMessage::StoreAck {
+                    sender_id,
+                    success: result.is_ok(),
+                    request_id,
+                    error: result.err(),
+                    code: None,
+                })
+            }
+            DhtMessage::GetProviders { key, request_id, .. } => {
+                let providers = self.providers.providers(&key);
+                let closest_nodes = self.handle_find_node(from, key, request_id).await;
+                Some(DhtMessage::GetProvidersResponse {
+                    sender_id,
+                    request_id,
+                    providers,
+                    closest_nodes,
+                })
+            }
+            DhtMessage::Lookup { payload, request_id, .. } => {
+                let result = match &self.private_lookup {
+                    Some(lookup) => lookup.handle_request(from, &payload).await,
+                    None => Err("Private lookups are not served here".to_string()),
+                };
+                Some(DhtMessage::LookupResponse { sender_id, request_id, result })
+            }
+            _ => None,
+        }
+    }
+
     /// Handle PING request
     async fn handle_ping(&self, _from: DhtKey) -> Result<(), String> {
         // In production, send pong response via router
@@ -91,19 +217,13 @@ impl DhtServer {
         _from: DhtKey,
         target: DhtKey,
         _request_id: u64,
-    ) -> Result<(), String> {
+    ) -> Vec<PeerInfo> {
         // Find k closest nodes to target
         let routing_table = self.routing_table.lock().await;
         let closest = routing_table.find_closest(&target, self.config.bucket_size);
 
         // Convert PeerContact to PeerInfo
-        let _nodes: Vec<PeerInfo> = closest
-            .iter()
-            .map(|contact| PeerInfo::new(contact.id, contact.address.clone()))
-            .collect();
-
-        // In production, send response via router
-        Ok(())
+        closest.iter().map(peer_info).collect()
     }
 
     /// Handle FIND_VALUE request
@@ -112,9 +232,9 @@ impl DhtServer {
         _from: DhtKey,
         key: DhtKey,
         _request_id: u64,
-    ) -> Result<(), String> {
+    ) -> FindValueResult {
         // Try to get value from local storage
-        let _result = match self.storage.get(&key) {
+        match self.storage.get(&key) {
             Ok(value) => {
                 // Value found, emit event
                 let _ =
@@ -127,27 +247,70 @@ impl DhtServer {
                 let routing_table = self.routing_table.lock().await;
                 let closest = routing_table.find_closest(&key, self.config.bucket_size);
 
-                let nodes: Vec<PeerInfo> = closest
-                    .iter()
-                    .map(|contact| PeerInfo::new(contact.id, contact.address.clone()))
-                    .collect();
+                let nodes: Vec<PeerInfo> = closest.iter().map(peer_info).collect();
 
                 FindValueResult::NotFound { closest_nodes: nodes }
             }
+        }
+    }
+
+    /// Handle ADD_PROVIDER request
+    ///
+    /// Peers may only announce themselves, so nobody can point others at a stranger.
+    fn handle_add_provider(
+        &self,
+        from: DhtKey,
+        key: DhtKey,
+        provider: PeerInfo,
+        ttl: u64,
+    ) -> Result<(), String> {
+        if provider.id != from {
+            return Err(format!("Peer {} can't announce {} as a provider", from, provider.id));
+        }
+        self.providers.add(key, provider, ttl)
+    }
+
+    /// Handle STORE request, refusing peers the rate limiter has throttled
+    ///
+    /// Over-quota STOREs count as failures against the peer, so a peer that
+    /// keeps flooding trips its circuit breaker.
+    async fn throttled_store(
+        &self,
+        from: DhtKey,
+        key: DhtKey,
+        value: DhtValue,
+        request_id: u64,
+    ) -> Result<(), (String, Option<StoreErrorCode>)> {
+        let Some(rate_limiter) = &self.rate_limiter else {
+            return self.handle_store(from, key, value, request_id).await.map_err(store_error);
         };
 
-        // In production, send response via router
-        Ok(())
+        let peer_id = PeerId::from_bytes(from.to_vec());
+        if rate_limiter.check_request(&peer_id).await != RateLimitResult::Allowed {
+            crate::metrics::record_counter("dht.store_throttled", 1);
+            self.routing_table.lock().await.record(&from, Behaviour::RateLimited);
+            return Err((format!("Peer {} is throttled", from), Some(StoreErrorCode::Throttled)));
+        }
+        match self.handle_store(from, key, value, request_id).await {
+            Err(PutError::QuotaExceeded(reason)) => {
+                rate_limiter.record_failure(&peer_id).await;
+                Err(store_error(PutError::QuotaExceeded(reason)))
+            }
+            result => {
+                rate_limiter.record_success(&peer_id).await;
+                result.map_err(store_error)
+            }
+        }
     }
 
     /// Handle STORE request
     async fn handle_store(
         &self,
-        _from: DhtKey,
+        from: DhtKey,
         key: DhtKey,
         value: DhtValue,
         _request_id: u64,
-    ) -> Result<(), String> {
+    ) -> Result<(), PutError> {
         // Validate value
         let validation_result =
             value.validate(self.config.max_value_size, self.config.require_signatures);
@@ -155,7 +318,7 @@ impl DhtServer {
         match validation_result {
             Ok(_) => {
                 // Store value
-                match self.storage.put(key, value) {
+                match self.storage.put_from(key, value, from) {
                     Ok(_) => {
                         // Emit event
                         let _ = self.event_tx.send(DhtEvent::ValueStored { key }).await;
@@ -165,19 +328,33 @@ impl DhtServer {
                 }
             }
             Err(e) => {
-                // Validation failed
+                // Validation failed, which counts against the peer's reputation
+                self.routing_table.lock().await.mark_invalid(&from);
                 let _ =
                     self.event_tx.send(DhtEvent::ValidationFailed { key, reason: e.clone() }).await;
-                Err(e)
+                Err(PutError::Other(e))
             }
         }
     }
 }
 
+/// The error text and wire code a refused STORE is acked with
+fn store_error(err: PutError) -> (String, Option<StoreErrorCode>) {
+    let code = match err {
+        PutError::QuotaExceeded(_) => Some(StoreErrorCode::QuotaExceeded),
+        PutError::Other(_) => None,
+    };
+    (err.to_string(), code)
+}
+
+/// A routing table entry as handed out to peers
+fn peer_info(contact: &PeerContact) -> PeerInfo {
+    PeerInfo::new(contact.id, contact.address.clone()).with_lookup_key(contact.lookup_key)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core_dht::PeerContact;
 
     fn create_test_server() -> DhtServer {
         let local_id = DhtKey::hash(b"local");
@@ -307,7 +484,7 @@ mod tests {
         assert!(ping.is_request());
         assert_eq!(ping.message_type(), "Ping");
 
-        let pong = DhtMessage::new_pong(DhtKey::hash(b"test"));
+        let pong = DhtMessage::new_pong(DhtKey::hash(b"test"), None);
         assert!(pong.is_response());
         assert_eq!(pong.message_type(), "Pong");
     }
@@ -0,0 +1,211 @@
+/*
+    bootstrap_tests.rs - Rejoining the DHT after a restart
+
+    - Peers remembered by one run are used by the next, before bootstrap peers
+    - Bootstrap peers are used when no remembered peer answers
+    - Stale remembered peers don't delay startup and are evicted if gone
+    - Peers are saved during shutdown
+*/
+
+use crate::core_dht::{
+    DhtConfig, DhtKey, DhtNode, PeerBootstrap, PeerContact, PeerProber, RoutingTable,
+};
+use crate::core_store::store::{KnownPeer, LocalStore, LocalStoreConfig};
+use crate::shutdown::ShutdownCoordinator;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+use tokio::sync::{mpsc, Mutex};
+
+/// Answers for the addresses it knows; hangs on the ones marked slow
+#[derive(Default)]
+struct FakeProber {
+    up: HashMap<String, DhtKey>,
+    slow: Vec<String>,
+}
+
+impl FakeProber {
+    fn up(mut self, address: &str) -> Self {
+        self.up.insert(address.to_string(), DhtKey::hash_string(address));
+        self
+    }
+
+    fn slow(mut self, address: &str) -> Self {
+        self.slow.push(address.to_string());
+        self
+    }
+}
+
+#[async_trait]
+impl PeerProber for FakeProber {
+    async fn ping(&self, address: &str, _expected: Option<DhtKey>) -> Result<DhtKey, String> {
+        if self.slow.iter().any(|a| a == address) {
+            tokio::time::sleep(Duration::from_secs(3600)).await;
+        }
+        self.up.get(address).copied().ok_or_else(|| "connection refused".to_string())
+    }
+}
+
+fn open_store(dir: &Path) -> Arc<LocalStore> {
+    let config = LocalStoreConfig {
+        data_dir: dir.to_path_buf(),
+        enable_encryption: false,
+        ..Default::default()
+    };
+    Arc::new(LocalStore::new(config).unwrap())
+}
+
+fn bootstrapper(
+    store: Arc<LocalStore>,
+    prober: FakeProber,
+    bootstrap_peers: &[&str],
+) -> Arc<PeerBootstrap> {
+    let table = Arc::new(Mutex::new(RoutingTable::new(DhtKey::hash_string("local"), 20)));
+    let bootstrap_peers = bootstrap_peers.iter().map(|a| a.to_string()).collect();
+    Arc::new(
+        PeerBootstrap::new(table, store, Arc::new(prober), bootstrap_peers)
+            .with_rpc_timeout(Duration::from_millis(200)),
+    )
+}
+
+fn known(address: &str, last_seen: u64) -> KnownPeer {
+    KnownPeer {
+        node_id: *DhtKey::hash_string(address).as_bytes(),
+        address: address.to_string(),
+        last_seen,
+        reliability: 0.5,
+    }
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[tokio::test]
+async fn test_remembered_peers_are_used_before_bootstrap_peers() {
+    let dir = TempDir::new().unwrap();
+
+    // First run: learn two peers and save them
+    {
+        let peers = bootstrapper(open_store(dir.path()), FakeProber::default(), &[]);
+        let table = peers.routing_table();
+        for address in ["10.0.0.1:7000", "10.0.0.2:7000"] {
+            let contact = PeerContact::new(DhtKey::hash_string(address), address.to_string());
+            table.lock().await.insert(contact).unwrap();
+        }
+        assert_eq!(peers.persist().await.unwrap(), 2);
+    }
+
+    // Second run: the remembered peers answer, so the bootstrap peer isn't needed
+    let prober = FakeProber::default().up("10.0.0.1:7000").up("10.0.0.2:7000").up("boot:7000");
+    let peers = bootstrapper(open_store(dir.path()), prober, &["boot:7000"]);
+    let summary = peers.bootstrap().await;
+
+    assert_eq!(summary.restored, 2);
+    assert_eq!(summary.contacted, 2);
+    assert_eq!(summary.responded, 2);
+    assert!(!summary.used_bootstrap_peers);
+    assert_eq!(summary.table_size, 2);
+}
+
+#[tokio::test]
+async fn test_bootstrap_peers_are_used_when_no_remembered_peer_answers() {
+    let dir = TempDir::new().unwrap();
+    let store = open_store(dir.path());
+    store.known_peers().replace(vec![known("10.0.0.1:7000", now())]).unwrap();
+
+    let peers = bootstrapper(store.clone(), FakeProber::default().up("boot:7000"), &["boot:7000"]);
+    let summary = peers.bootstrap().await;
+
+    assert_eq!(summary.restored, 1);
+    assert_eq!(summary.contacted, 2);
+    assert_eq!(summary.responded, 1);
+    assert!(summary.used_bootstrap_peers);
+
+    // The dead peer failed once and is kept for now; the bootstrap peer joined it
+    let table = peers.routing_table();
+    let dead = table.lock().await.get(&DhtKey::hash_string("10.0.0.1:7000")).unwrap();
+    assert_eq!(dead.failed_rpcs, 1);
+    assert!(dead.reliability < 0.5);
+    assert!(table.lock().await.get(&DhtKey::hash_string("boot:7000")).is_some());
+}
+
+#[tokio::test]
+async fn test_stale_peers_are_checked_lazily() {
+    let dir = TempDir::new().unwrap();
+    let store = open_store(dir.path());
+    let week_ago = now() - 7 * 24 * 3600;
+    store
+        .known_peers()
+        .replace(vec![
+            known("10.0.0.1:7000", now()),
+            known("10.0.0.2:7000", week_ago),
+            known("10.0.0.3:7000", week_ago),
+        ])
+        .unwrap();
+
+    // A stale peer that never answers must not hold up startup
+    let prober = FakeProber::default()
+        .up("10.0.0.1:7000")
+        .up("10.0.0.3:7000")
+        .slow("10.0.0.2:7000");
+    let peers = bootstrapper(store.clone(), prober, &[]);
+    let started = Instant::now();
+    let summary = peers.bootstrap().await;
+    assert!(started.elapsed() < Duration::from_secs(1));
+    assert_eq!(summary.contacted, 1);
+    assert_eq!(summary.responded, 1);
+    assert_eq!(summary.table_size, 3);
+
+    // The dead one goes once the background check times out on it
+    let table = peers.routing_table();
+    let dead = DhtKey::hash_string("10.0.0.2:7000");
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while table.lock().await.get(&dead).is_some() || store.known_peers().len() != 2 {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert!(table.lock().await.get(&DhtKey::hash_string("10.0.0.3:7000")).is_some());
+
+    let remembered: Vec<_> = store.known_peers().all().into_iter().map(|p| p.address).collect();
+    assert!(!remembered.contains(&"10.0.0.2:7000".to_string()));
+
+    // Nothing stale is left to evict
+    assert_eq!(peers.check_stale().await, 0);
+}
+
+#[tokio::test]
+async fn test_node_bootstraps_and_saves_peers_on_shutdown() {
+    let dir = TempDir::new().unwrap();
+    let store = open_store(dir.path());
+
+    let peers = bootstrapper(store.clone(), FakeProber::default().up("boot:7000"), &["boot:7000"]);
+    let (event_tx, _event_rx) = mpsc::channel(100);
+    let node = DhtNode::new(DhtKey::hash_string("local"), DhtConfig::test_config(), event_tx)
+        .unwrap()
+        .with_peer_bootstrap(peers.clone());
+
+    let summary = node.bootstrap().await.unwrap();
+    assert_eq!(summary.responded, 1);
+    assert_eq!(summary.table_size, 1);
+    assert_eq!(node.routing_table_stats().await.0, 1);
+    assert!(store.known_peers().is_empty());
+
+    let shutdown = ShutdownCoordinator::new(Duration::from_secs(10));
+    let saved = peers.persist_on_shutdown(&shutdown);
+    shutdown.shutdown().await;
+    saved.await.unwrap();
+
+    let remembered = store.known_peers().all();
+    assert_eq!(remembered.len(), 1);
+    assert_eq!(remembered[0].address, "boot:7000");
+    assert!(remembered[0].reliability > 0.5);
+}
@@ -0,0 +1,221 @@
+/*
+    bucket_tests.rs - Bucket liveness and refresh
+
+    - A newcomer to a full bucket replaces the least recently seen peer only if
+      that peer fails a ping; dead peers are evicted, responsive ones stay
+    - Buckets no lookup has touched for the refresh interval get a lookup for a
+      random ID in their range
+    - routing_stats reports entries, refresh age and evictions per bucket
+*/
+
+use crate::core_dht::{
+    DhtConfig, DhtEvent, DhtKey, DhtNode, FindValueResult, PeerContact, PeerProber,
+    SearchTransport, SearchType,
+};
+use async_trait::async_trait;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Answers pings for every peer except the dead ones
+#[derive(Default)]
+struct Liveness {
+    /// Peers that never answer
+    silent: HashSet<DhtKey>,
+    /// Peers whose connection is refused
+    refused: HashSet<DhtKey>,
+    pings: AtomicUsize,
+}
+
+#[async_trait]
+impl PeerProber for Liveness {
+    async fn ping(&self, _address: &str, expected: Option<DhtKey>) -> Result<DhtKey, String> {
+        self.pings.fetch_add(1, Ordering::SeqCst);
+        let id = expected.ok_or("no expected ID")?;
+        if self.silent.contains(&id) {
+            tokio::time::sleep(Duration::from_secs(3600)).await;
+        }
+        if self.refused.contains(&id) {
+            return Err("connection refused".to_string());
+        }
+        Ok(id)
+    }
+}
+
+/// Records lookup targets and knows no other nodes
+#[derive(Default)]
+struct Recorder {
+    targets: Mutex<Vec<DhtKey>>,
+}
+
+#[async_trait]
+impl SearchTransport for Recorder {
+    async fn query(
+        &self,
+        _peer: &PeerContact,
+        target: DhtKey,
+        _search_type: SearchType,
+    ) -> Result<FindValueResult, String> {
+        self.targets.lock().unwrap().push(target);
+        Ok(FindValueResult::NotFound { closest_nodes: Vec::new() })
+    }
+}
+
+fn local_id() -> DhtKey {
+    DhtKey::hash_string("local")
+}
+
+/// `n` distinct peers in bucket `index`
+fn peers_in_bucket(index: usize, n: usize) -> Vec<DhtKey> {
+    let mut peers = HashSet::new();
+    while peers.len() < n {
+        peers.insert(local_id().random_in_bucket(index));
+    }
+    peers.into_iter().collect()
+}
+
+/// The peers `node` would hand out for `target`
+async fn known(node: &DhtNode, target: &DhtKey) -> HashSet<DhtKey> {
+    match node.answer_query(*target, SearchType::FindNode).await {
+        FindValueResult::NotFound { closest_nodes } => closest_nodes.iter().map(|p| p.id).collect(),
+        FindValueResult::Found(_) => panic!("FIND_NODE returned a value"),
+    }
+}
+
+#[tokio::test]
+async fn test_dead_peers_are_evicted_for_newcomers() {
+    let config = DhtConfig::test_config();
+    let k = config.bucket_size;
+    let peers = peers_in_bucket(255, k + 3);
+    let (members, newcomers) = peers.split_at(k);
+
+    // The two least recently seen members are dead: one hangs, one refuses
+    let liveness = Liveness {
+        silent: HashSet::from([members[0]]),
+        refused: HashSet::from([members[1]]),
+        ..Default::default()
+    };
+    let liveness = Arc::new(liveness);
+    let (event_tx, mut event_rx) = mpsc::channel(100);
+    let node = DhtNode::new(local_id(), config, event_tx)
+        .unwrap()
+        .with_prober(liveness.clone());
+
+    for member in members {
+        assert!(node.add_peer(*member).await);
+    }
+
+    // Each of the first two newcomers takes a dead peer's place
+    assert!(node.add_peer(newcomers[0]).await);
+    assert!(node.add_peer(newcomers[1]).await);
+    // The bucket is now all responsive peers, so the third is turned away
+    assert!(!node.add_peer(newcomers[2]).await);
+    assert_eq!(liveness.pings.load(Ordering::SeqCst), 3);
+
+    let expected: HashSet<DhtKey> = members[2..].iter().chain(&newcomers[..2]).copied().collect();
+    assert_eq!(known(&node, &newcomers[0]).await, expected);
+
+    let stats = node.routing_stats().await;
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].index, 255);
+    assert_eq!(stats[0].entries, k);
+    assert_eq!(stats[0].evictions, 2);
+
+    let mut removed = HashSet::new();
+    while let Ok(event) = event_rx.try_recv() {
+        if let DhtEvent::NodeRemoved { node_id } = event {
+            removed.insert(node_id);
+        }
+    }
+    assert_eq!(removed, HashSet::from([members[0], members[1]]));
+}
+
+#[tokio::test]
+async fn test_responsive_peers_survive_a_full_bucket() {
+    let config = DhtConfig::test_config();
+    let k = config.bucket_size;
+    let peers = peers_in_bucket(254, k + 10);
+    let (members, newcomers) = peers.split_at(k);
+
+    let liveness = Arc::new(Liveness::default());
+    let (event_tx, _event_rx) = mpsc::channel(100);
+    let node = DhtNode::new(local_id(), config, event_tx)
+        .unwrap()
+        .with_prober(liveness.clone());
+
+    for member in members {
+        node.add_peer(*member).await;
+    }
+    for newcomer in newcomers {
+        assert!(!node.add_peer(*newcomer).await);
+    }
+
+    // Every newcomer cost one ping, each of a different (least recently seen) member
+    assert_eq!(liveness.pings.load(Ordering::SeqCst), newcomers.len());
+    assert_eq!(known(&node, &members[0]).await, members.iter().copied().collect());
+
+    let stats = node.routing_stats().await;
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].entries, k);
+    assert_eq!(stats[0].evictions, 0);
+}
+
+#[tokio::test]
+async fn test_full_bucket_without_prober_keeps_old_peers() {
+    let config = DhtConfig::test_config();
+    let k = config.bucket_size;
+    let peers = peers_in_bucket(255, k + 1);
+    let (event_tx, _event_rx) = mpsc::channel(100);
+    let node = DhtNode::new(local_id(), config, event_tx).unwrap();
+
+    for peer in &peers[..k] {
+        assert!(node.add_peer(*peer).await);
+    }
+    assert!(!node.add_peer(peers[k]).await);
+    assert_eq!(node.routing_stats().await[0].evictions, 0);
+}
+
+#[tokio::test]
+async fn test_idle_buckets_are_refreshed() {
+    let config = DhtConfig::test_config().with_bucket_refresh_interval(Duration::from_millis(200));
+    let recorder = Arc::new(Recorder::default());
+    let (event_tx, _event_rx) = mpsc::channel(100);
+    let node = DhtNode::new(local_id(), config, event_tx)
+        .unwrap()
+        .with_search_transport(recorder.clone());
+
+    for index in [255, 250, 240] {
+        for peer in peers_in_bucket(index, 2) {
+            node.add_peer(peer).await;
+        }
+    }
+
+    // Nothing is idle yet
+    assert_eq!(node.refresh_buckets().await, 0);
+    tokio::time::sleep(Duration::from_millis(150)).await;
+
+    // A lookup into bucket 250's range counts as touching it
+    let target = local_id().random_in_bucket(250);
+    node.lookup(target, SearchType::FindNode, recorder.as_ref()).await;
+    recorder.targets.lock().unwrap().clear();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert_eq!(node.refresh_buckets().await, 2);
+    let refreshed: HashSet<usize> = recorder
+        .targets
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|target| target.bucket_index(&local_id()))
+        .collect();
+    assert_eq!(refreshed, HashSet::from([255, 240]));
+
+    // Refreshed buckets aren't due again until the interval passes
+    assert_eq!(node.refresh_buckets().await, 0);
+    for bucket in node.routing_stats().await {
+        assert!(bucket.since_refresh < Duration::from_millis(200));
+        assert_eq!(bucket.entries, 2);
+    }
+}
@@ -0,0 +1,115 @@
+/*
+    expiry_tests.rs - Record expiry and republishing
+
+    - Values disappear once their TTL runs out, and the sweeper frees them
+    - TTLs longer than value_expiration are cut short
+    - Values the node published are republished before they expire
+*/
+
+use crate::core_dht::{
+    DhtCommand, DhtConfig, DhtKey, DhtNode, DhtStorage, DhtValue, FindValueResult,
+    ReplicationManager, RoutingTable, SearchType,
+};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, Mutex};
+
+fn config() -> DhtConfig {
+    DhtConfig::test_config().with_sweep_interval(Duration::from_millis(200))
+}
+
+/// Start a node, returning its command channel
+fn start(node: DhtNode) -> (Arc<DhtNode>, mpsc::Sender<DhtCommand>) {
+    let node = Arc::new(node);
+    let (command_tx, command_rx) = mpsc::channel(10);
+    tokio::spawn(node.clone().run(command_rx));
+    (node, command_tx)
+}
+
+async fn get(commands: &mpsc::Sender<DhtCommand>, key: DhtKey) -> Option<DhtValue> {
+    let (response_tx, response_rx) = oneshot::channel();
+    commands.send(DhtCommand::Get { key, response_tx }).await.unwrap();
+    response_rx.await.unwrap().unwrap()
+}
+
+#[tokio::test]
+async fn test_expired_values_are_swept() {
+    let (event_tx, _event_rx) = mpsc::channel(100);
+    let node = DhtNode::new(DhtKey::hash_string("local"), config(), event_tx).unwrap();
+    let (node, commands) = start(node);
+
+    let short = DhtKey::hash_string("short");
+    let long = DhtKey::hash_string("long");
+    node.store_local(short, DhtValue::new(b"short".to_vec()).with_ttl(1))
+        .await
+        .unwrap();
+    node.store_local(long, DhtValue::new(b"long".to_vec()).with_ttl(3600))
+        .await
+        .unwrap();
+    assert!(get(&commands, short).await.is_some());
+
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+
+    assert!(get(&commands, short).await.is_none());
+    assert!(matches!(
+        node.answer_query(short, SearchType::FindValue).await,
+        FindValueResult::NotFound { .. }
+    ));
+    assert_eq!(node.storage_stats().await, (1, 0));
+    assert!(get(&commands, long).await.is_some());
+
+    let _ = commands.send(DhtCommand::Shutdown).await;
+}
+
+#[tokio::test]
+async fn test_ttl_is_capped_by_config() {
+    let config = config().with_value_expiration(Duration::from_secs(1));
+    let (event_tx, _event_rx) = mpsc::channel(100);
+    let node = DhtNode::new(DhtKey::hash_string("local"), config, event_tx).unwrap();
+
+    let key = DhtKey::hash_string("greedy");
+    let value = DhtValue::new(b"forever".to_vec()).with_ttl(u64::MAX / 2);
+    node.store_local(key, value.clone()).await.unwrap();
+    assert_eq!(node.storage().expires_at(&key).unwrap(), value.timestamp + 1);
+
+    tokio::time::sleep(Duration::from_millis(2100)).await;
+    assert!(node.storage().get(&key).is_err());
+}
+
+#[tokio::test]
+async fn test_owned_values_are_republished_before_expiry() {
+    let config = config().with_republish_interval(Duration::from_secs(1));
+    let local_id = DhtKey::hash_string("local");
+    let routing_table = Arc::new(Mutex::new(RoutingTable::new(local_id, 20)));
+    let (replication_tx, _replication_rx) = mpsc::channel(100);
+    let replication = Arc::new(ReplicationManager::new(
+        config.clone(),
+        DhtStorage::new().with_max_ttl(config.value_expiration),
+        routing_table,
+        replication_tx,
+    ));
+
+    let (event_tx, _event_rx) = mpsc::channel(100);
+    let node = DhtNode::new(local_id, config, event_tx).unwrap().with_replication(replication);
+    let (node, commands) = start(node);
+
+    // One value put through the node, one stored for someone else
+    let owned = DhtKey::hash_string("owned");
+    let (response_tx, response_rx) = oneshot::channel();
+    let value = DhtValue::new(b"mine".to_vec()).with_ttl(2);
+    commands.send(DhtCommand::Put { key: owned, value, response_tx }).await.unwrap();
+    response_rx.await.unwrap().unwrap();
+
+    let cached = DhtKey::hash_string("cached");
+    node.store_local(cached, DhtValue::new(b"theirs".to_vec()).with_ttl(2))
+        .await
+        .unwrap();
+
+    // Well past both TTLs
+    tokio::time::sleep(Duration::from_secs(4)).await;
+
+    assert_eq!(get(&commands, owned).await.unwrap().data, b"mine");
+    assert!(get(&commands, cached).await.is_none());
+
+    let _ = commands.send(DhtCommand::Shutdown).await;
+}
@@ -0,0 +1,199 @@
+/*
+    kad_search_tests.rs - Iterative lookups across a simulated network
+
+    50 in-process DhtNodes, each knowing only part of the network through
+    its k-buckets, answer each other's queries directly:
+    - FIND_VALUE finds values stored at the nodes closest to the key
+    - FIND_NODE converges on the true closest nodes
+    - Lookups finish in a bounded number of rounds
+    - Never more than alpha queries are in flight
+    - Slow and dead peers don't stall a lookup
+*/
+
+use crate::core_dht::{
+    DhtConfig, DhtKey, DhtNode, DhtValue, FindValueResult, PeerContact, SearchResult,
+    SearchTransport, SearchType,
+};
+use async_trait::async_trait;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+const NODES: usize = 50;
+
+/// ceil(log2(50)) + 1 rounds of slack
+const MAX_ROUNDS: usize = 7;
+
+/// Delivers queries straight to the target node
+#[derive(Default)]
+struct Network {
+    nodes: HashMap<DhtKey, Arc<DhtNode>>,
+    dead: HashSet<DhtKey>,
+    slow: HashSet<DhtKey>,
+    latency: Duration,
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+}
+
+#[async_trait]
+impl SearchTransport for Network {
+    async fn query(
+        &self,
+        peer: &PeerContact,
+        target: DhtKey,
+        search_type: SearchType,
+    ) -> Result<FindValueResult, String> {
+        let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_in_flight.fetch_max(now, Ordering::SeqCst);
+
+        let reply = async {
+            if self.dead.contains(&peer.id) {
+                return Err("connection refused".to_string());
+            }
+            if self.slow.contains(&peer.id) {
+                tokio::time::sleep(Duration::from_secs(3600)).await;
+            }
+            tokio::time::sleep(self.latency).await;
+            let node = self.nodes.get(&peer.id).ok_or("unknown peer")?;
+            Ok(node.answer_query(target, search_type).await)
+        };
+        let result = reply.await;
+
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+        result
+    }
+}
+
+fn node_id(i: usize) -> DhtKey {
+    DhtKey::hash_string(&format!("node{}", i))
+}
+
+/// Build the network, letting every node fill its k-buckets from the others
+async fn network(config: &DhtConfig) -> Network {
+    let mut network = Network::default();
+    for i in 0..NODES {
+        // Nothing listens for events here
+        let (event_tx, _) = mpsc::channel(1);
+        let node = DhtNode::new(node_id(i), config.clone(), event_tx).unwrap();
+        network.nodes.insert(node_id(i), Arc::new(node));
+    }
+    for node in network.nodes.values() {
+        for i in 0..NODES {
+            node.add_peer(node_id(i)).await;
+        }
+    }
+    network
+}
+
+/// The nodes closest to `target`, nearest first
+fn closest(target: &DhtKey, among: impl Iterator<Item = DhtKey>) -> Vec<DhtKey> {
+    let mut ids: Vec<DhtKey> = among.collect();
+    ids.sort_by_key(|id| id.distance(target));
+    ids
+}
+
+#[tokio::test]
+async fn test_find_value_across_network() {
+    let config = DhtConfig::test_config();
+    let network = network(&config).await;
+
+    // Store each value at the replication_factor nodes closest to its key
+    let keys: Vec<DhtKey> = (0..5).map(|i| DhtKey::hash_string(&format!("key{}", i))).collect();
+    for key in &keys {
+        let holders = closest(key, (0..NODES).map(node_id));
+        for holder in &holders[..config.replication_factor] {
+            let value = DhtValue::new(key.to_string().into_bytes());
+            network.nodes[holder].store_local(*key, value).await.unwrap();
+        }
+    }
+
+    for key in &keys {
+        for origin in (0..NODES).step_by(7).map(node_id) {
+            let (result, stats) =
+                network.nodes[&origin].lookup(*key, SearchType::FindValue, &network).await;
+
+            match result {
+                SearchResult::Value(value) => assert_eq!(value.data, key.to_string().into_bytes()),
+                other => panic!("lookup of {} from {} failed: {:?}", key, origin, other),
+            }
+            assert!(stats.rounds <= MAX_ROUNDS, "took {} rounds", stats.rounds);
+            assert!(stats.queried < NODES, "queried {} peers", stats.queried);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_find_node_converges_on_closest_nodes() {
+    let config = DhtConfig::test_config();
+    let network = network(&config).await;
+
+    for t in 0..10 {
+        let target = DhtKey::hash_string(&format!("target{}", t));
+        let origin = node_id(t * 3);
+
+        let (result, stats) =
+            network.nodes[&origin].lookup(target, SearchType::FindNode, &network).await;
+        let SearchResult::Nodes(found) = result else {
+            panic!("FIND_NODE returned no nodes");
+        };
+
+        let expected = closest(&target, (0..NODES).map(node_id).filter(|id| *id != origin));
+        assert_eq!(found.len(), config.bucket_size);
+        assert_eq!(found[0].id, expected[0]);
+        let found: HashSet<DhtKey> = found.iter().map(|p| p.id).collect();
+        let expected: HashSet<DhtKey> = expected[..config.bucket_size].iter().copied().collect();
+        assert_eq!(found, expected);
+        assert!(stats.rounds <= MAX_ROUNDS, "took {} rounds", stats.rounds);
+    }
+}
+
+#[tokio::test]
+async fn test_lookups_keep_alpha_queries_in_flight() {
+    let mut config = DhtConfig::test_config();
+    config.alpha = 3;
+    let mut network = network(&config).await;
+    network.latency = Duration::from_millis(5);
+
+    let target = DhtKey::hash_string("missing");
+    let (result, stats) =
+        network.nodes[&node_id(0)].lookup(target, SearchType::FindValue, &network).await;
+
+    assert!(matches!(result, SearchResult::Nodes(_)));
+    assert!(stats.queried > config.alpha);
+    assert_eq!(network.max_in_flight.load(Ordering::SeqCst), config.alpha);
+}
+
+#[tokio::test]
+async fn test_slow_and_dead_peers_do_not_stall_lookups() {
+    let config = DhtConfig::test_config();
+    let mut network = network(&config).await;
+
+    let key = DhtKey::hash_string("resilient");
+    let by_distance = closest(&key, (0..NODES).map(node_id));
+
+    // The value lives on the closest node; the next few are slow or dead
+    let value = DhtValue::new(b"still here".to_vec());
+    network.nodes[&by_distance[0]].store_local(key, value).await.unwrap();
+    network.slow.extend(by_distance[1..3].iter().copied());
+    network.dead.extend(by_distance[3..6].iter().copied());
+    for id in by_distance[10..20].iter() {
+        network.slow.insert(*id);
+    }
+
+    let started = Instant::now();
+    for origin in by_distance[20..].iter().step_by(5) {
+        let (result, stats) =
+            network.nodes[origin].lookup(key, SearchType::FindValue, &network).await;
+
+        match result {
+            SearchResult::Value(value) => assert_eq!(value.data, b"still here"),
+            other => panic!("lookup from {} failed: {:?}", origin, other),
+        }
+        assert!(stats.rounds <= MAX_ROUNDS, "took {} rounds", stats.rounds);
+    }
+
+    // Each slow peer costs at most one RPC timeout
+    assert!(started.elapsed() < Duration::from_secs(10));
+}
@@ -2,4 +2,13 @@
     DHT subsystem tests
 */
 
+pub mod bootstrap_tests;
+pub mod bucket_tests;
+pub mod expiry_tests;
+pub mod kad_search_tests;
+pub mod private_lookup_tests;
+pub mod provider_tests;
+pub mod quorum_tests;
+pub mod quota_tests;
+pub mod reputation_tests;
 pub mod resilience_tests;
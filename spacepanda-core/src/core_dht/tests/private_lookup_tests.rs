@@ -0,0 +1,339 @@
+/*
+    private_lookup_tests.rs - Oblivious DHT lookup tests
+
+    Nodes are wired together through an in-memory transport that records
+    which node each request was received from. These tests validate:
+    - The storing node never sees the requester in private mode
+    - Private lookups return the same results as direct lookups
+    - Namespaces without a private mode keep using direct lookups
+    - Relays only forward to nodes they know, and only so many times
+    - DhtClient reads private namespaces through relays, using the lookup
+      keys nodes hand out in their PONGs
+*/
+
+use crate::core_dht::private_lookup::{CHANNEL_LISTING_NAMESPACE, KEY_PACKAGE_NAMESPACE};
+use crate::core_dht::{
+    ConsistencyLevel, DhtClient, DhtConfig, DhtKey, DhtLookupTransport, DhtMessageNew, DhtServer,
+    DhtStorage, DhtTransport, DhtValue, LookupMode, LookupPeer, LookupTransport, PeerContact,
+    PrivateLookup, RoutingTable,
+};
+use crate::core_router::RouterHandle;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+/// In-memory network delivering lookup messages between nodes
+#[derive(Default)]
+struct LoopbackNetwork {
+    nodes: Mutex<HashMap<DhtKey, Weak<PrivateLookup>>>,
+    /// (from, to) for every delivered request
+    observed: Mutex<Vec<(DhtKey, DhtKey)>>,
+}
+
+impl LoopbackNetwork {
+    /// Senders the given node has received requests from
+    fn seen_by(&self, node: DhtKey) -> Vec<DhtKey> {
+        self.observed
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, to)| *to == node)
+            .map(|(from, _)| *from)
+            .collect()
+    }
+}
+
+/// Transport bound to one node of the loopback network
+struct LoopbackTransport {
+    local_id: DhtKey,
+    network: Arc<LoopbackNetwork>,
+}
+
+#[async_trait]
+impl LookupTransport for LoopbackTransport {
+    async fn request(&self, peer: DhtKey, payload: Vec<u8>) -> Result<Vec<u8>, String> {
+        let node = self
+            .network
+            .nodes
+            .lock()
+            .unwrap()
+            .get(&peer)
+            .and_then(Weak::upgrade)
+            .ok_or_else(|| format!("Unknown peer {}", peer))?;
+        self.network.observed.lock().unwrap().push((self.local_id, peer));
+        node.handle_request(self.local_id, &payload).await
+    }
+}
+
+fn private_config(relay_hops: usize) -> DhtConfig {
+    DhtConfig::default()
+        .with_lookup_mode(KEY_PACKAGE_NAMESPACE, LookupMode::Private { relay_hops })
+        .with_lookup_mode(CHANNEL_LISTING_NAMESPACE, LookupMode::Private { relay_hops })
+}
+
+fn node_id(i: usize) -> DhtKey {
+    DhtKey::hash(format!("node{}", i).as_bytes())
+}
+
+/// Create `count` nodes sharing `config`, along with their storages
+///
+/// Every node knows every other one.
+fn create_network(
+    count: usize,
+    config: DhtConfig,
+) -> (Arc<LoopbackNetwork>, Vec<Arc<PrivateLookup>>, Vec<DhtStorage>) {
+    create_network_with(count, config, |_, _| true)
+}
+
+/// Create `count` nodes sharing `config`, where node i has node j in its
+/// routing table if `knows(i, j)`
+fn create_network_with(
+    count: usize,
+    config: DhtConfig,
+    knows: impl Fn(usize, usize) -> bool,
+) -> (Arc<LoopbackNetwork>, Vec<Arc<PrivateLookup>>, Vec<DhtStorage>) {
+    let network = Arc::new(LoopbackNetwork::default());
+    let storages: Vec<_> = (0..count).map(|_| DhtStorage::new()).collect();
+    let nodes: Vec<_> = storages
+        .iter()
+        .enumerate()
+        .map(|(i, storage)| {
+            let id = node_id(i);
+            let mut table = RoutingTable::new(id, 20);
+            for j in (0..count).filter(|j| *j != i && knows(i, *j)) {
+                table.insert(PeerContact::new(node_id(j), format!("node{}", j))).unwrap();
+            }
+            let table = Arc::new(tokio::sync::Mutex::new(table));
+            let transport = Arc::new(LoopbackTransport { local_id: id, network: network.clone() });
+            Arc::new(PrivateLookup::new(id, config.clone(), storage.clone(), table, transport))
+        })
+        .collect();
+
+    for node in &nodes {
+        network.nodes.lock().unwrap().insert(node.local_id(), Arc::downgrade(node));
+    }
+    (network, nodes, storages)
+}
+
+/// Node 0 requests, node 1 stores, the rest are relays
+fn roles(nodes: &[Arc<PrivateLookup>]) -> (&PrivateLookup, LookupPeer, Vec<LookupPeer>) {
+    let relays = nodes[2..].iter().map(|n| n.as_peer()).collect();
+    (&nodes[0], nodes[1].as_peer(), relays)
+}
+
+#[tokio::test]
+async fn test_storing_node_never_sees_requester() {
+    for relay_hops in [1, 2] {
+        let (network, nodes, storages) = create_network(6, private_config(relay_hops));
+        let (requester, storing_node, relays) = roles(&nodes);
+
+        let key = DhtKey::hash(b"key_package:bob");
+        storages[1].put(key, DhtValue::new(b"bob's key package".to_vec())).unwrap();
+
+        let value = requester
+            .lookup(KEY_PACKAGE_NAMESPACE, key, &storing_node, &relays)
+            .await
+            .unwrap()
+            .expect("value should be found");
+        assert_eq!(value.data, b"bob's key package");
+
+        let seen = network.seen_by(storing_node.id);
+        assert_eq!(seen.len(), 1, "storing node should receive exactly one request");
+        assert_ne!(seen[0], requester.local_id(), "storing node saw the requester");
+        assert!(relays.iter().any(|relay| relay.id == seen[0]));
+
+        // The request crossed exactly relay_hops relays
+        assert_eq!(network.observed.lock().unwrap().len(), relay_hops + 1);
+    }
+}
+
+#[tokio::test]
+async fn test_private_lookup_matches_direct_lookup() {
+    let (_, private_nodes, private_storages) = create_network(5, private_config(2));
+    let (_, direct_nodes, direct_storages) = create_network(5, DhtConfig::default());
+
+    let found = DhtKey::hash(b"channel_listing:rust");
+    let missing = DhtKey::hash(b"channel_listing:nothing");
+    for storages in [&private_storages, &direct_storages] {
+        storages[1].put(found, DhtValue::new(b"#rust, #general".to_vec())).unwrap();
+    }
+
+    for key in [found, missing] {
+        let (requester, storing_node, relays) = roles(&private_nodes);
+        let private = requester
+            .lookup(CHANNEL_LISTING_NAMESPACE, key, &storing_node, &relays)
+            .await
+            .unwrap();
+
+        let (requester, storing_node, relays) = roles(&direct_nodes);
+        let direct = requester
+            .lookup(CHANNEL_LISTING_NAMESPACE, key, &storing_node, &relays)
+            .await
+            .unwrap();
+
+        assert_eq!(private.map(|v| v.data), direct.map(|v| v.data));
+    }
+}
+
+#[tokio::test]
+async fn test_unconfigured_namespace_uses_direct_lookup() {
+    let (network, nodes, storages) = create_network(4, private_config(1));
+    let (requester, storing_node, relays) = roles(&nodes);
+
+    let key = DhtKey::hash(b"profile:alice");
+    storages[1].put(key, DhtValue::new(b"alice".to_vec())).unwrap();
+
+    let value = requester.lookup("profile", key, &storing_node, &relays).await.unwrap();
+    assert_eq!(value.unwrap().data, b"alice");
+    assert_eq!(network.seen_by(storing_node.id), vec![requester.local_id()]);
+}
+
+#[tokio::test]
+async fn test_private_lookup_requires_enough_relays() {
+    let (network, nodes, _) = create_network(3, private_config(2));
+    let (requester, storing_node, relays) = roles(&nodes);
+
+    let result = requester
+        .lookup(KEY_PACKAGE_NAMESPACE, DhtKey::hash(b"k"), &storing_node, &relays)
+        .await;
+    assert!(result.unwrap_err().contains("relay"));
+
+    // Nothing was sent rather than falling back to a direct lookup
+    assert!(network.observed.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_relays_only_forward_to_known_nodes() {
+    // The relays don't have the storing node in their routing tables
+    let (network, nodes, storages) =
+        create_network_with(4, private_config(1), |i, j| !(i >= 2 && j == 1));
+    let (requester, storing_node, relays) = roles(&nodes);
+
+    let key = DhtKey::hash(b"key_package:bob");
+    storages[1].put(key, DhtValue::new(b"bob's key package".to_vec())).unwrap();
+
+    let result = requester.lookup(KEY_PACKAGE_NAMESPACE, key, &storing_node, &relays).await;
+    assert!(result.unwrap_err().contains("not a known node"));
+    assert!(network.seen_by(storing_node.id).is_empty());
+}
+
+#[tokio::test]
+async fn test_relay_hops_are_bounded() {
+    let (network, nodes, _) = create_network(6, private_config(3));
+    let (requester, storing_node, relays) = roles(&nodes);
+
+    let result = requester
+        .lookup(KEY_PACKAGE_NAMESPACE, DhtKey::hash(b"k"), &storing_node, &relays)
+        .await;
+    assert!(result.unwrap_err().contains("relay hops"));
+
+    // The first relay refused it rather than passing it on
+    assert_eq!(network.observed.lock().unwrap().len(), 1);
+    assert!(network.seen_by(storing_node.id).is_empty());
+}
+
+/// In-memory network delivering DHT messages to each node's server
+#[derive(Default)]
+struct ServerNetwork {
+    servers: Mutex<HashMap<DhtKey, Arc<DhtServer>>>,
+    /// (from, to) for every delivered request
+    observed: Mutex<Vec<(DhtKey, DhtKey)>>,
+}
+
+/// DHT transport bound to one node of the server network
+struct ServerTransport {
+    local_id: DhtKey,
+    network: Arc<ServerNetwork>,
+}
+
+#[async_trait]
+impl DhtTransport for ServerTransport {
+    async fn request(&self, peer: DhtKey, request: DhtMessageNew) -> Result<DhtMessageNew, String> {
+        let server = self
+            .network
+            .servers
+            .lock()
+            .unwrap()
+            .get(&peer)
+            .cloned()
+            .ok_or_else(|| format!("Unknown peer {}", peer))?;
+        self.network.observed.lock().unwrap().push((self.local_id, peer));
+        server
+            .respond(self.local_id, request)
+            .await
+            .ok_or_else(|| "No response".to_string())
+    }
+}
+
+#[tokio::test]
+async fn test_client_reads_private_namespaces_through_relays() {
+    let config = private_config(2);
+    let network = Arc::new(ServerNetwork::default());
+    let mut clients = Vec::new();
+    let mut tables = Vec::new();
+    let key = DhtKey::hash(b"key_package:bob");
+    for i in 0..6 {
+        let id = node_id(i);
+        let table = Arc::new(tokio::sync::Mutex::new(RoutingTable::new(id, 20)));
+        let storage = DhtStorage::new();
+        storage.put(key, DhtValue::new(b"bob's key package".to_vec())).unwrap();
+        let transport = Arc::new(ServerTransport { local_id: id, network: network.clone() });
+        let lookup = Arc::new(PrivateLookup::new(
+            id,
+            config.clone(),
+            storage.clone(),
+            table.clone(),
+            Arc::new(DhtLookupTransport::new(id, transport.clone())),
+        ));
+        let (router, _) = RouterHandle::new();
+        let router = Arc::new(router);
+        let (event_tx, _) = tokio::sync::mpsc::channel(16);
+        let server =
+            DhtServer::new(id, config.clone(), router.clone(), storage, table.clone(), event_tx)
+                .with_private_lookup(lookup.clone());
+        network.servers.lock().unwrap().insert(id, Arc::new(server));
+        clients.push(
+            DhtClient::new(id, router, table.clone(), Duration::from_secs(5))
+                .with_transport(transport)
+                .with_private_lookup(lookup)
+                .with_read_replicas(1),
+        );
+        tables.push(table);
+    }
+
+    // Every node pings every other, learning its lookup key from the PONG
+    for (i, table) in tables.iter().enumerate() {
+        let transport = ServerTransport { local_id: node_id(i), network: network.clone() };
+        for j in (0..6).filter(|j| *j != i) {
+            let pong = transport.request(node_id(j), DhtMessageNew::new_ping(node_id(i))).await;
+            let Ok(DhtMessageNew::Pong { lookup_key, .. }) = pong else {
+                panic!("expected a Pong");
+            };
+            let contact = PeerContact::new(node_id(j), format!("node{}", j));
+            table.lock().await.insert(contact.with_lookup_key(lookup_key)).unwrap();
+        }
+    }
+    network.observed.lock().unwrap().clear();
+
+    let requester = node_id(0);
+    let replica = tables[0].lock().await.find_closest(&key, 1)[0].id;
+    let value = clients[0]
+        .get_in(KEY_PACKAGE_NAMESPACE, key, ConsistencyLevel::One)
+        .await
+        .unwrap()
+        .expect("value should be found");
+    assert_eq!(value.data, b"bob's key package");
+
+    // The replica heard from a relay only, after two relays
+    let observed = network.observed.lock().unwrap().clone();
+    assert_eq!(observed.len(), 3);
+    assert_ne!(observed[0].1, replica);
+    assert!(observed.contains(&(observed[1].1, replica)));
+    assert!(!observed.contains(&(requester, replica)));
+
+    // Other namespaces are still read directly
+    network.observed.lock().unwrap().clear();
+    clients[0].get_in("profile", key, ConsistencyLevel::One).await.unwrap();
+    assert_eq!(network.observed.lock().unwrap().clone(), vec![(requester, replica)]);
+}
@@ -0,0 +1,232 @@
+/*
+    provider_tests.rs - Provider records and direct blob transfer
+
+    Three DHT nodes hold provider records; two clients, A and B, reach them
+    through an in-process transport and talk to each other over a pair of
+    wired-up RpcProtocols:
+    - A provides a 1MB blob, B finds A and fetches the blob from it directly,
+      and the blob never touches any node's DhtStorage
+    - Provider records lapse unless re-announced
+    - Peers can't announce someone else as a provider
+*/
+
+use crate::core_dht::message::DhtMessage;
+use crate::core_dht::{
+    DhtClient, DhtConfig, DhtKey, DhtServer, DhtStorage, DhtTransport, PeerContact, PeerInfo,
+    RoutingTable,
+};
+use crate::core_router::{
+    fetch_blob, serve_blobs, BlobSource, PeerId, RouterHandle, RpcCommand, RpcError, RpcProtocol,
+    SessionCommand, SessionEvent, FETCH_BLOB_METHOD,
+};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, Mutex};
+
+/// Hands requests straight to the target node's server
+struct Nodes {
+    servers: HashMap<DhtKey, Arc<DhtServer>>,
+}
+
+#[async_trait]
+impl DhtTransport for Nodes {
+    async fn request(&self, peer: DhtKey, request: DhtMessage) -> Result<DhtMessage, String> {
+        let server = self.servers.get(&peer).ok_or("unknown peer")?;
+        let from = request.sender_id();
+        server.respond(from, request).await.ok_or_else(|| "no response".to_string())
+    }
+}
+
+/// Blobs held in memory, keyed by hash
+struct Blobs(HashMap<[u8; 32], Arc<[u8]>>);
+
+impl BlobSource for Blobs {
+    fn blob(&self, hash: &[u8; 32]) -> Option<Arc<[u8]>> {
+        self.0.get(hash).cloned()
+    }
+}
+
+struct Setup {
+    servers: Vec<(Arc<DhtServer>, DhtStorage)>,
+    nodes: Arc<Nodes>,
+}
+
+/// Three DHT nodes
+fn setup() -> Setup {
+    let mut nodes = Nodes { servers: HashMap::new() };
+    let mut servers = Vec::new();
+    for i in 0..3 {
+        let id = DhtKey::hash_string(&format!("dht{}", i));
+        let storage = DhtStorage::new();
+        let (router, _handle) = RouterHandle::new();
+        let (event_tx, _) = mpsc::channel(1);
+        let server = Arc::new(DhtServer::new(
+            id,
+            DhtConfig::default(),
+            Arc::new(router),
+            storage.clone(),
+            Arc::new(Mutex::new(RoutingTable::new(id, 20))),
+            event_tx,
+        ));
+        nodes.servers.insert(id, server.clone());
+        servers.push((server, storage));
+    }
+    Setup { servers, nodes: Arc::new(nodes) }
+}
+
+/// A client named `name` that knows every DHT node
+async fn client(name: &str, setup: &Setup) -> DhtClient {
+    let local_id = DhtKey::hash_string(name);
+    let routing_table = Arc::new(Mutex::new(RoutingTable::new(local_id, 20)));
+    for (i, id) in setup.nodes.servers.keys().enumerate() {
+        let contact = PeerContact::new(*id, format!("dht{}:7000", i));
+        routing_table.lock().await.insert(contact).unwrap();
+    }
+    let (router, _handle) = RouterHandle::new();
+    DhtClient::new(local_id, Arc::new(router), routing_table, Duration::from_secs(1))
+        .with_transport(setup.nodes.clone())
+        .with_address(format!("{}:9000", name))
+}
+
+/// Router identity of a DHT peer
+fn peer_id(peer: &PeerInfo) -> PeerId {
+    PeerId::from_bytes(peer.id.to_vec())
+}
+
+/// Deliver everything `from` sends to `to`, as frames from `from_id`
+fn wire(from_id: PeerId, mut outbox: mpsc::Receiver<SessionCommand>, to: Arc<RpcProtocol>) {
+    tokio::spawn(async move {
+        while let Some(command) = outbox.recv().await {
+            if let SessionCommand::SendPlaintext(_, bytes) = command {
+                let frame = SessionEvent::PlaintextFrame(from_id.clone(), bytes);
+                let _ = to.handle_session_event(frame).await;
+            }
+        }
+    });
+}
+
+/// Make an RPC call through `rpc`
+async fn call(
+    rpc: Arc<RpcProtocol>,
+    peer_id: PeerId,
+    params: serde_json::Value,
+) -> Result<serde_json::Value, RpcError> {
+    let (response_tx, response_rx) = oneshot::channel();
+    let method = FETCH_BLOB_METHOD.to_string();
+    rpc.handle_command(RpcCommand::Call { peer_id, method, params, response_tx })
+        .await
+        .map_err(|e| RpcError::internal_error(&e))?;
+    response_rx.await.map_err(|_| RpcError::internal_error("call dropped"))?
+}
+
+#[tokio::test]
+async fn test_blob_is_found_through_providers_and_fetched_directly() {
+    let setup = setup();
+    let alice = client("alice", &setup).await;
+    let bob = client("bob", &setup).await;
+
+    let blob: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+    let hash = *blake3::hash(&blob).as_bytes();
+    let content_hash = DhtKey::from_bytes(hash);
+
+    // Alice serves the blob over RPC and announces it in the DHT
+    let (alice_tx, alice_outbox) = mpsc::channel(100);
+    let (bob_tx, bob_outbox) = mpsc::channel(100);
+    let alice_rpc = Arc::new(RpcProtocol::new(alice_tx));
+    let bob_rpc = Arc::new(RpcProtocol::new(bob_tx));
+    let alice_peer = PeerId::from_bytes(DhtKey::hash_string("alice").to_vec());
+    let bob_peer = PeerId::from_bytes(DhtKey::hash_string("bob").to_vec());
+    wire(alice_peer, alice_outbox, bob_rpc.clone());
+    wire(bob_peer, bob_outbox, alice_rpc.clone());
+
+    let blobs = Blobs(HashMap::from([(hash, Arc::from(blob.clone()))]));
+    let (handler_tx, _server) = serve_blobs(Arc::new(blobs));
+    let method = FETCH_BLOB_METHOD.to_string();
+    alice_rpc
+        .handle_command(RpcCommand::RegisterHandler { method, handler_tx })
+        .await
+        .unwrap();
+
+    let announced = alice.provide(content_hash).await.unwrap();
+    assert_eq!(announced.len(), 3);
+
+    // Bob finds Alice and pulls the blob from her
+    let providers = bob.find_providers(content_hash).await.unwrap();
+    assert_eq!(providers.len(), 1);
+    assert_eq!(providers[0].id, DhtKey::hash_string("alice"));
+    assert_eq!(providers[0].address, "alice:9000");
+
+    let provider = peer_id(&providers[0]);
+    let fetched =
+        fetch_blob(hash, 2 * 1024 * 1024, |params| call(bob_rpc.clone(), provider.clone(), params))
+            .await
+            .unwrap();
+    assert_eq!(fetched, blob);
+
+    // The DHT only ever held the provider records
+    for (server, storage) in &setup.servers {
+        assert_eq!(storage.size().unwrap(), 0);
+        assert_eq!(server.providers().len(), 1);
+    }
+
+    // A provider that doesn't have the blob says so
+    let unknown = [7u8; 32];
+    let err = fetch_blob(unknown, 1024, |params| call(bob_rpc.clone(), provider.clone(), params))
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("Blob not found"), "{}", err.message);
+}
+
+#[tokio::test]
+async fn test_provider_records_lapse_unless_reannounced() {
+    let setup = setup();
+    let content_hash = DhtKey::hash(b"chunk");
+    let lapsing = client("lapsing", &setup).await.with_provider_ttl(Duration::from_secs(2));
+    let steady = client("steady", &setup).await.with_provider_ttl(Duration::from_secs(2));
+    let reader = client("reader", &setup).await;
+
+    lapsing.provide(content_hash).await.unwrap();
+    steady.provide(content_hash).await.unwrap();
+    let reannouncer = steady.spawn_reannouncer(Duration::from_millis(300));
+    assert_eq!(reader.find_providers(content_hash).await.unwrap().len(), 2);
+
+    tokio::time::sleep(Duration::from_secs(3)).await;
+
+    let providers = reader.find_providers(content_hash).await.unwrap();
+    assert_eq!(providers.len(), 1);
+    assert_eq!(providers[0].id, DhtKey::hash_string("steady"));
+
+    // Once it stops, its records lapse too
+    steady.stop_providing(&content_hash).await;
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    assert!(reader.find_providers(content_hash).await.unwrap().is_empty());
+    for (server, _) in &setup.servers {
+        server.providers().cleanup_expired();
+        assert!(server.providers().is_empty());
+    }
+    reannouncer.abort();
+}
+
+#[tokio::test]
+async fn test_peers_cannot_announce_others() {
+    let setup = setup();
+    let (server, _) = &setup.servers[0];
+    let mallory = DhtKey::hash_string("mallory");
+    let victim = PeerInfo::new(DhtKey::hash_string("victim"), "victim:9000".to_string());
+    let content_hash = DhtKey::hash(b"popular");
+
+    let request = DhtMessage::AddProvider {
+        sender_id: mallory,
+        key: content_hash,
+        provider: victim,
+        ttl: 3600,
+        request_id: 1,
+    };
+    let Some(DhtMessage::StoreAck { success, .. }) = server.respond(mallory, request).await else {
+        panic!("expected a StoreAck");
+    };
+    assert!(!success);
+    assert!(server.providers().providers(&content_hash).is_empty());
+}
@@ -0,0 +1,171 @@
+/*
+    quorum_tests.rs - Consistency levels and read repair
+
+    Three replicas, each a DhtServer with its own storage, reached through an
+    in-process transport:
+    - A quorum read returns the newest value even when one replica is stale
+    - The stale replica is repaired in the background
+    - One answers from a single replica; All needs every replica up
+*/
+
+use crate::core_dht::message::DhtMessage;
+use crate::core_dht::{
+    ConsistencyLevel, DhtClient, DhtConfig, DhtKey, DhtServer, DhtStorage, DhtTransport, DhtValue,
+    PeerContact, RoutingTable,
+};
+use crate::core_router::RouterHandle;
+use async_trait::async_trait;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex};
+
+/// Hands requests straight to the target replica's server
+struct Replicas {
+    servers: HashMap<DhtKey, DhtServer>,
+    down: HashSet<DhtKey>,
+    slow: HashSet<DhtKey>,
+}
+
+#[async_trait]
+impl DhtTransport for Replicas {
+    async fn request(&self, peer: DhtKey, request: DhtMessage) -> Result<DhtMessage, String> {
+        if self.down.contains(&peer) {
+            return Err("connection refused".to_string());
+        }
+        if self.slow.contains(&peer) {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+        let server = self.servers.get(&peer).ok_or("unknown peer")?;
+        let from = request.sender_id();
+        server.respond(from, request).await.ok_or_else(|| "no response".to_string())
+    }
+}
+
+struct Setup {
+    client: DhtClient,
+    storages: Vec<(DhtKey, DhtStorage)>,
+}
+
+/// A client and three replicas, with `down` unreachable and `slow` answering last
+async fn setup(down: &[usize], slow: &[usize]) -> Setup {
+    let local_id = DhtKey::hash_string("reader");
+    let routing_table = Arc::new(Mutex::new(RoutingTable::new(local_id, 20)));
+    let mut replicas =
+        Replicas { servers: HashMap::new(), down: HashSet::new(), slow: HashSet::new() };
+    let mut storages = Vec::new();
+
+    for i in 0..3 {
+        let id = DhtKey::hash_string(&format!("replica{}", i));
+        let storage = DhtStorage::new();
+        let (router, _handle) = RouterHandle::new();
+        let (event_tx, _) = mpsc::channel(1);
+        let server = DhtServer::new(
+            id,
+            DhtConfig::default(),
+            Arc::new(router),
+            storage.clone(),
+            Arc::new(Mutex::new(RoutingTable::new(id, 20))),
+            event_tx,
+        );
+        replicas.servers.insert(id, server);
+        if down.contains(&i) {
+            replicas.down.insert(id);
+        }
+        if slow.contains(&i) {
+            replicas.slow.insert(id);
+        }
+        let contact = PeerContact::new(id, format!("replica{}:7000", i));
+        routing_table.lock().await.insert(contact).unwrap();
+        storages.push((id, storage));
+    }
+
+    let (router, _handle) = RouterHandle::new();
+    let client = DhtClient::new(local_id, Arc::new(router), routing_table, Duration::from_secs(1))
+        .with_transport(Arc::new(replicas));
+    Setup { client, storages }
+}
+
+fn value(data: &[u8], sequence: u64) -> DhtValue {
+    DhtValue::new(data.to_vec()).with_ttl(3600).with_sequence(sequence)
+}
+
+#[tokio::test]
+async fn test_quorum_read_returns_newest_and_repairs_laggard() {
+    // The quorum is the laggard and one up-to-date replica
+    let Setup { client, storages } = setup(&[], &[2]).await;
+    let key = DhtKey::hash_string("presence/alice");
+
+    // Two replicas have the update, one missed it
+    storages[0].1.put(key, value(b"old", 1)).unwrap();
+    storages[1].1.put(key, value(b"new", 2)).unwrap();
+    storages[2].1.put(key, value(b"new", 2)).unwrap();
+
+    let read = client.get(key, ConsistencyLevel::Quorum).await.unwrap().unwrap();
+    assert_eq!(read.data, b"new");
+    assert_eq!(read.sequence, 2);
+
+    let laggard = &storages[0].1;
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while laggard.get(&key).unwrap().sequence != 2 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("stale replica wasn't repaired");
+
+    // Everyone agrees now
+    let read = client.get(key, ConsistencyLevel::All).await.unwrap().unwrap();
+    assert_eq!(read.data, b"new");
+}
+
+#[tokio::test]
+async fn test_quorum_read_repairs_missing_copy() {
+    let Setup { client, storages } = setup(&[], &[2]).await;
+    let key = DhtKey::hash_string("key-package/bob");
+
+    storages[1].1.put(key, value(b"kp", 1)).unwrap();
+    storages[2].1.put(key, value(b"kp", 1)).unwrap();
+
+    let read = client.get(key, ConsistencyLevel::Quorum).await.unwrap().unwrap();
+    assert_eq!(read.data, b"kp");
+
+    let missing = &storages[0].1;
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while missing.get(&key).is_err() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("missing replica wasn't repaired");
+}
+
+#[tokio::test]
+async fn test_consistency_levels_with_replica_down() {
+    let Setup { client, storages } = setup(&[2], &[]).await;
+    let key = DhtKey::hash_string("channel/feed");
+    for (_, storage) in &storages {
+        storage.put(key, value(b"feed", 1)).unwrap();
+    }
+
+    let read = client.get(key, ConsistencyLevel::One).await.unwrap();
+    assert_eq!(read.unwrap().data, b"feed");
+    let read = client.get(key, ConsistencyLevel::Quorum).await.unwrap();
+    assert_eq!(read.unwrap().data, b"feed");
+
+    let err = client.get(key, ConsistencyLevel::All).await.unwrap_err();
+    assert!(err.contains("Only 2 of 3 replicas answered"), "{}", err);
+
+    // Nothing stored anywhere reads as missing
+    let absent = DhtKey::hash_string("nobody");
+    assert!(client.get(absent, ConsistencyLevel::Quorum).await.unwrap().is_none());
+}
+
+#[test]
+fn test_required_answers() {
+    assert_eq!(ConsistencyLevel::One.required(3), 1);
+    assert_eq!(ConsistencyLevel::Quorum.required(3), 2);
+    assert_eq!(ConsistencyLevel::Quorum.required(4), 3);
+    assert_eq!(ConsistencyLevel::All.required(3), 3);
+    assert_eq!(ConsistencyLevel::One.required(0), 0);
+}
@@ -0,0 +1,208 @@
+/*
+    quota_tests.rs - Storage quotas under flooding
+
+    - One publisher flooding a node hits its own quota; other publishers'
+      records survive and can still be stored
+    - A full store evicts its least valuable records, soonest to expire first
+      and least recently requested next, but never for a shorter-lived newcomer
+    - Refusals carry a quota error code, and a peer that keeps flooding is
+      throttled through the rate limiter
+*/
+
+use crate::core_dht::message::DhtMessage;
+use crate::core_dht::{
+    DhtConfig, DhtKey, DhtServer, DhtStorage, DhtValue, PutError, RoutingTable, StorageQuota,
+    StoreErrorCode,
+};
+use crate::core_router::{RateLimiter, RateLimiterConfig, RouterHandle};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex};
+
+fn quota() -> StorageQuota {
+    StorageQuota {
+        max_bytes: 10_000,
+        max_records: 100,
+        max_publisher_bytes: 2_000,
+        max_publisher_records: 20,
+    }
+}
+
+fn value(len: usize, ttl: u64) -> DhtValue {
+    DhtValue::new(vec![7; len]).with_ttl(ttl)
+}
+
+fn key(publisher: &str, i: usize) -> DhtKey {
+    DhtKey::hash_string(&format!("{}/{}", publisher, i))
+}
+
+#[test]
+fn test_flooding_publisher_cannot_crowd_out_others() {
+    let storage = DhtStorage::new().with_quota(quota());
+    let flooder = DhtKey::hash_string("flooder");
+    let honest = DhtKey::hash_string("honest");
+
+    storage.put_from(key("honest", 0), value(100, 3600), honest).unwrap();
+
+    // 100 bytes each: the flooder gets 20 records, then is refused
+    let mut refused = 0;
+    for i in 0..500 {
+        match storage.put_from(key("flooder", i), value(100, 3600), flooder) {
+            Ok(()) => {}
+            Err(PutError::QuotaExceeded(_)) => refused += 1,
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+    }
+    assert_eq!(refused, 480);
+
+    // Everyone else is unaffected
+    assert!(storage.get(&key("honest", 0)).is_ok());
+    storage.put_from(key("honest", 1), value(100, 3600), honest).unwrap();
+
+    let report = storage.usage_report().unwrap();
+    assert_eq!(report.total.records, 22);
+    assert_eq!(report.total.bytes, 2_200);
+    assert_eq!(report.rejections, 480);
+    assert_eq!(report.evictions, 0);
+    assert_eq!(report.publishers[0].0, flooder);
+    assert_eq!(report.publishers[0].1.records, 20);
+    assert_eq!(report.publishers[1].0, honest);
+    assert_eq!(report.publishers[1].1.records, 2);
+}
+
+#[test]
+fn test_replacing_a_record_reuses_its_quota() {
+    let storage = DhtStorage::new().with_quota(quota());
+    let publisher = DhtKey::hash_string("publisher");
+    let k = key("publisher", 0);
+
+    for sequence in 1..100 {
+        storage
+            .put_from(k, value(1_500, 3600).with_sequence(sequence), publisher)
+            .unwrap();
+    }
+    let report = storage.usage_report().unwrap();
+    assert_eq!(report.total.records, 1);
+    assert_eq!(report.total.bytes, 1_500);
+
+    // Deleting frees the publisher's quota too
+    storage.delete(&k).unwrap();
+    assert!(storage.usage_report().unwrap().publishers.is_empty());
+    storage.put_from(key("publisher", 1), value(2_000, 3600), publisher).unwrap();
+}
+
+#[test]
+fn test_full_store_evicts_lowest_value_records() {
+    let storage = DhtStorage::new().with_quota(quota());
+
+    // Fill the store from many publishers, the first with short TTLs
+    for p in 0..10 {
+        let publisher = DhtKey::hash_string(&format!("p{}", p));
+        let ttl = if p == 0 { 600 } else { 3600 };
+        for i in 0..10 {
+            storage.put_from(key(&p.to_string(), i), value(100, ttl), publisher).unwrap();
+        }
+    }
+    assert_eq!(storage.size().unwrap(), 100);
+
+    // A newcomer pushes out the shortest-lived records first
+    let newcomer = DhtKey::hash_string("newcomer");
+    for i in 0..5 {
+        storage.put_from(key("newcomer", i), value(100, 3600), newcomer).unwrap();
+    }
+    let report = storage.usage_report().unwrap();
+    assert_eq!(report.total.records, 100);
+    assert_eq!(report.evictions, 5);
+    let short_lived = report
+        .publishers
+        .iter()
+        .find(|(publisher, _)| *publisher == DhtKey::hash_string("p0"))
+        .unwrap();
+    assert_eq!(short_lived.1.records, 5);
+    for p in 1..10 {
+        for i in 0..10 {
+            assert!(storage.get(&key(&p.to_string(), i)).is_ok());
+        }
+    }
+
+    // A record shorter-lived than everything held isn't worth an eviction
+    let err = storage.put_from(key("newcomer", 5), value(100, 60), newcomer).unwrap_err();
+    assert!(matches!(err, PutError::QuotaExceeded(_)));
+    assert_eq!(storage.usage_report().unwrap().evictions, 5);
+}
+
+#[test]
+fn test_eviction_spares_recently_requested_records() {
+    let storage = DhtStorage::new().with_quota(StorageQuota { max_records: 3, ..quota() });
+    let publishers: Vec<DhtKey> = (0..4).map(|p| DhtKey::hash_string(&format!("p{}", p))).collect();
+
+    // Same expiry for all; only the last one stored hasn't been read
+    let mut v = value(10, 3600);
+    v.timestamp -= 5;
+    for (i, publisher) in publishers[..3].iter().enumerate() {
+        storage.put_from(key("k", i), v.clone(), *publisher).unwrap();
+    }
+    std::thread::sleep(Duration::from_millis(1100));
+    storage.get(&key("k", 0)).unwrap();
+    storage.get(&key("k", 1)).unwrap();
+
+    storage.put_from(key("k", 3), value(10, 3600), publishers[3]).unwrap();
+    assert!(storage.get(&key("k", 0)).is_ok());
+    assert!(storage.get(&key("k", 1)).is_ok());
+    assert!(storage.get(&key("k", 2)).is_err());
+    assert!(storage.get(&key("k", 3)).is_ok());
+}
+
+#[tokio::test]
+async fn test_flooding_peer_is_refused_then_throttled() {
+    let local_id = DhtKey::hash_string("node");
+    let storage = DhtStorage::new().with_quota(quota());
+    let (router, _handle) = RouterHandle::new();
+    let (event_tx, _event_rx) = mpsc::channel(1000);
+    let rate_limiter = Arc::new(RateLimiter::new_with_config(RateLimiterConfig {
+        circuit_breaker_threshold: 5,
+        ..Default::default()
+    }));
+    let server = DhtServer::new(
+        local_id,
+        DhtConfig::default(),
+        Arc::new(router),
+        storage.clone(),
+        Arc::new(Mutex::new(RoutingTable::new(local_id, 20))),
+        event_tx,
+    )
+    .with_rate_limiter(rate_limiter);
+
+    let store = |sender: DhtKey, i: usize| DhtMessage::Store {
+        sender_id: sender,
+        key: key(&sender.to_string(), i),
+        value: value(100, 3600),
+        request_id: i as u64,
+    };
+    let flooder = DhtKey::hash_string("flooder");
+    let honest = DhtKey::hash_string("honest");
+
+    let mut codes = Vec::new();
+    for i in 0..30 {
+        let Some(DhtMessage::StoreAck { success, code, .. }) =
+            server.respond(flooder, store(flooder, i)).await
+        else {
+            panic!("expected a StoreAck");
+        };
+        assert_eq!(success, code.is_none());
+        codes.push(code);
+    }
+
+    // 20 stored, 5 refused over quota, then the circuit opens
+    assert!(codes[..20].iter().all(Option::is_none));
+    assert!(codes[20..25].iter().all(|c| *c == Some(StoreErrorCode::QuotaExceeded)));
+    assert!(codes[25..].iter().all(|c| *c == Some(StoreErrorCode::Throttled)));
+
+    // Other peers store as usual
+    let Some(DhtMessage::StoreAck { success, .. }) = server.respond(honest, store(honest, 0)).await
+    else {
+        panic!("expected a StoreAck");
+    };
+    assert!(success);
+    assert_eq!(storage.size().unwrap(), 21);
+}
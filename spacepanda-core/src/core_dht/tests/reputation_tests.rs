@@ -0,0 +1,118 @@
+/*
+    reputation_tests.rs - Peer reputation and quarantine
+
+    A client looks nodes up through honest peers and one that answers FIND_NODE
+    with garbage, reached through an in-process transport:
+    - The garbage peer ends up quarantined and out of lookups
+    - Honest peers keep a good reputation and stay in lookups
+*/
+
+use crate::core_dht::message::{DhtMessage, PeerInfo};
+use crate::core_dht::{DhtClient, DhtKey, DhtTransport, PeerContact, RoutingTable};
+use crate::core_router::RouterHandle;
+use async_trait::async_trait;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+/// Honest peers answer FIND_NODE properly; the liars answer with a mix of garbage
+struct Peers {
+    liars: HashSet<DhtKey>,
+    calls: std::sync::Mutex<u64>,
+}
+
+#[async_trait]
+impl DhtTransport for Peers {
+    async fn request(&self, peer: DhtKey, request: DhtMessage) -> Result<DhtMessage, String> {
+        let DhtMessage::FindNode { target, request_id, .. } = request else {
+            return Err("unexpected request".to_string());
+        };
+        let node = PeerInfo::new(target, "10.0.0.1:7000".to_string());
+        if !self.liars.contains(&peer) {
+            return Ok(DhtMessage::FindNodeResponse {
+                sender_id: peer,
+                nodes: vec![node],
+                request_id,
+            });
+        }
+
+        let mut calls = self.calls.lock().unwrap();
+        *calls += 1;
+        Ok(match *calls % 3 {
+            // The wrong kind of answer
+            0 => DhtMessage::new_pong(peer, None),
+            // An answer to some other request
+            1 => DhtMessage::FindNodeResponse {
+                sender_id: peer,
+                nodes: vec![node],
+                request_id: request_id + 1,
+            },
+            // A flood of made-up nodes
+            _ => {
+                DhtMessage::FindNodeResponse { sender_id: peer, nodes: vec![node; 100], request_id }
+            }
+        })
+    }
+}
+
+fn peer_id(i: usize) -> DhtKey {
+    DhtKey::hash_string(&format!("peer{}", i))
+}
+
+/// A client knowing five peers, the first of them a liar
+async fn setup() -> (DhtClient, Arc<Mutex<RoutingTable>>) {
+    let local_id = DhtKey::hash_string("searcher");
+    let routing_table = Arc::new(Mutex::new(RoutingTable::new(local_id, 20)));
+    for i in 0..5 {
+        let contact = PeerContact::new(peer_id(i), format!("peer{}:7000", i));
+        routing_table.lock().await.insert(contact).unwrap();
+    }
+
+    let peers = Peers { liars: HashSet::from([peer_id(0)]), calls: std::sync::Mutex::new(0) };
+    let (router, _handle) = RouterHandle::new();
+    let client =
+        DhtClient::new(local_id, Arc::new(router), routing_table.clone(), Duration::from_secs(1))
+            .with_transport(Arc::new(peers));
+    (client, routing_table)
+}
+
+#[tokio::test]
+async fn test_garbage_find_node_responses_quarantine_peer() {
+    let (client, routing_table) = setup().await;
+    let target = DhtKey::hash_string("target");
+
+    for _ in 0..3 {
+        for i in 0..5 {
+            let result = client.find_node(peer_id(i), target).await;
+            assert_eq!(result.is_ok(), i != 0, "peer{}: {:?}", i, result);
+        }
+    }
+
+    let table = routing_table.lock().await;
+    assert!(table.is_quarantined(&peer_id(0)));
+    assert!(table.reputation(&peer_id(0)).unwrap() < 0.2);
+
+    let closest: Vec<DhtKey> = table.find_closest(&target, 20).iter().map(|p| p.id).collect();
+    assert!(!closest.contains(&peer_id(0)), "quarantined peer offered for lookups");
+    for i in 1..5 {
+        assert!(!table.is_quarantined(&peer_id(i)));
+        assert!(table.reputation(&peer_id(i)).unwrap() > 0.5);
+        assert!(closest.contains(&peer_id(i)));
+    }
+}
+
+#[tokio::test]
+async fn test_low_reputation_peers_are_asked_last() {
+    let (client, routing_table) = setup().await;
+    let target = DhtKey::hash_string("target");
+
+    // One bad answer: not quarantined yet, but no longer trusted first
+    assert!(client.find_node(peer_id(0), target).await.is_err());
+    let table = routing_table.lock().await;
+    assert!(!table.is_quarantined(&peer_id(0)));
+
+    let closest = table.find_closest(&target, 20);
+    assert_eq!(closest.len(), 5);
+    assert_eq!(closest.last().map(|p| p.id), Some(peer_id(0)));
+}
@@ -3,8 +3,11 @@
 //! Create MLS KeyPackage and identity bundles for publication.
 
 use crate::core_identity::device_id::DeviceId;
+use crate::core_identity::device_key::DeviceKeyBinding;
 use crate::core_identity::keypair::Keypair;
+use crate::core_identity::master_key::MasterKey;
 use crate::core_identity::metadata::DeviceMetadata;
+use crate::core_identity::validation::ValidationError;
 use blake3;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -12,6 +15,9 @@ use std::collections::HashMap;
 /// Hash type for content addressing
 pub type Hash = Vec<u8>;
 
+/// Extension carrying a serialized MLS key package
+const MLS_KEY_PACKAGE_EXTENSION: &str = "mls_key_package";
+
 /// MLS KeyPackage for device authentication and group joining
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KeyPackage {
@@ -80,6 +86,39 @@ impl KeyPackage {
         }
     }
 
+    /// Wrap a serialized MLS key package, signed by the identity key
+    ///
+    /// Used to publish MLS key packages in the DHT: whoever fetches one can
+    /// check that the identity key vouches for it before inviting with it.
+    pub fn for_mls(identity_kp: &Keypair, mls_key_package: Vec<u8>) -> Self {
+        let init_key = identity_kp.public_key().to_vec();
+        let credential = Self::create_credential(identity_kp, identity_kp);
+        let extensions = vec![Extension {
+            extension_type: MLS_KEY_PACKAGE_EXTENSION.to_string(),
+            data: mls_key_package,
+        }];
+
+        let body = Self::package_body(&init_key, &credential, &extensions);
+        let signature = identity_kp.sign(&body);
+
+        KeyPackage {
+            cipher_suite: "MLS_128_DHKEMX25519_AES128GCM_SHA256_Ed25519".to_string(),
+            init_key,
+            leaf_secret_encryption: None,
+            credential,
+            extensions,
+            signature,
+        }
+    }
+
+    /// The MLS key package wrapped by [`for_mls`](Self::for_mls), if any
+    pub fn mls_key_package(&self) -> Option<&[u8]> {
+        self.extensions
+            .iter()
+            .find(|ext| ext.extension_type == MLS_KEY_PACKAGE_EXTENSION)
+            .map(|ext| ext.data.as_slice())
+    }
+
     /// Create credential signed by identity key
     fn create_credential(device_kp: &Keypair, identity_kp: &Keypair) -> Vec<u8> {
         let mut cred = Vec::new();
@@ -130,6 +169,10 @@ impl KeyPackage {
 }
 
 /// Device bundle containing key package and metadata
+///
+/// Signed either by the identity key, or, for a linked device, by the
+/// device's own key with a [`DeviceKeyBinding`] from the identity key
+/// vouching for it.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeviceBundle {
     /// The key package
@@ -138,6 +181,9 @@ pub struct DeviceBundle {
     pub device_metadata: DeviceMetadata,
     /// Signature over bundle
     pub signature: Vec<u8>,
+    /// Identity key's authorization of a linked device
+    #[serde(default)]
+    pub binding: Option<DeviceKeyBinding>,
 }
 
 impl DeviceBundle {
@@ -153,7 +199,28 @@ impl DeviceBundle {
 
         let signature = identity_kp.sign(&body);
 
-        DeviceBundle { key_package, device_metadata, signature }
+        DeviceBundle { key_package, device_metadata, signature, binding: None }
+    }
+
+    /// Create a bundle for a new device, signed with its own key
+    ///
+    /// The bundle proves possession of the device key; it is only accepted
+    /// once the identity key authorizes it (see
+    /// [`IdentityBundle::authorize_device`]).
+    pub fn for_new_device(device_kp: &Keypair, device_metadata: DeviceMetadata) -> Self {
+        let key_package = KeyPackage::new(device_kp, device_kp, &device_metadata);
+        Self::new(key_package, device_metadata, device_kp)
+    }
+
+    /// Public key of the device this bundle describes
+    pub fn device_public_key(&self) -> &[u8] {
+        &self.key_package.init_key
+    }
+
+    /// Attach the identity key's authorization
+    pub fn with_binding(mut self, binding: DeviceKeyBinding) -> Self {
+        self.binding = Some(binding);
+        self
     }
 
     /// Verify this bundle
@@ -187,29 +254,63 @@ impl IdentityBundle {
         devices: Vec<DeviceId>,
         identity_kp: &Keypair,
     ) -> Self {
-        let mut body = Vec::new();
-        body.extend_from_slice(&user_id);
-        body.extend_from_slice(&public_key);
-        for device in &devices {
-            body.extend_from_slice(device.as_bytes());
-        }
-
-        let signature = identity_kp.sign(&body);
+        let signature = identity_kp.sign(&Self::body(&user_id, &public_key, &devices));
 
         IdentityBundle { user_id, public_key, devices, signature }
     }
 
-    /// Verify this bundle
-    pub fn verify(&self) -> bool {
+    /// Create bundle body for signing
+    fn body(user_id: &[u8], public_key: &[u8], devices: &[DeviceId]) -> Vec<u8> {
         let mut body = Vec::new();
-        body.extend_from_slice(&self.user_id);
-        body.extend_from_slice(&self.public_key);
-        for device in &self.devices {
+        body.extend_from_slice(user_id);
+        body.extend_from_slice(public_key);
+        for device in devices {
             body.extend_from_slice(device.as_bytes());
         }
+        body
+    }
 
+    /// Verify this bundle
+    pub fn verify(&self) -> bool {
+        let body = Self::body(&self.user_id, &self.public_key, &self.devices);
         Keypair::verify(&self.public_key, &body, &self.signature)
     }
+
+    /// Authorize a new device for this identity
+    ///
+    /// Checks the device bundle's self-signature (proof that the requester
+    /// holds the device key), adds the device to this bundle and re-signs
+    /// it, and returns the binding that vouches for the device.
+    pub fn authorize_device(
+        &mut self,
+        master_key: &MasterKey,
+        device_bundle: &DeviceBundle,
+    ) -> Result<DeviceKeyBinding, ValidationError> {
+        if master_key.public_key() != self.public_key.as_slice() {
+            return Err(ValidationError::BadFormat(
+                "Master key doesn't match identity bundle".to_string(),
+            ));
+        }
+
+        let device_public_key = device_bundle.device_public_key();
+        if !device_bundle.verify(device_public_key)
+            || !device_bundle.key_package.verify(device_public_key)
+        {
+            return Err(ValidationError::InvalidSignature);
+        }
+
+        let device_id = device_bundle.device_metadata.device_id.clone();
+        let binding =
+            DeviceKeyBinding::new(master_key, device_id.clone(), 1, device_public_key.to_vec());
+
+        if !self.devices.contains(&device_id) {
+            self.devices.push(device_id);
+        }
+        self.signature =
+            master_key.sign(&Self::body(&self.user_id, &self.public_key, &self.devices));
+
+        Ok(binding)
+    }
 }
 
 #[cfg(test)]
@@ -283,4 +384,38 @@ mod tests {
 
         assert!(bundle.verify());
     }
+
+    #[test]
+    fn test_authorize_device() {
+        let master_key = MasterKey::generate();
+        let mut identity = IdentityBundle::new(
+            vec![1, 2, 3, 4],
+            master_key.public_key().to_vec(),
+            Vec::new(),
+            master_key.keypair(),
+        );
+
+        let device_kp = Keypair::generate(KeyType::Ed25519);
+        let device_meta = DeviceMetadata::new(DeviceId::generate(), "Laptop".to_string(), "node2");
+        let request = DeviceBundle::for_new_device(&device_kp, device_meta.clone());
+
+        let binding = identity.authorize_device(&master_key, &request).unwrap();
+        assert!(binding.verify(master_key.public_key()));
+        assert_eq!(binding.device_id, device_meta.device_id);
+        assert_eq!(binding.device_public_key, device_kp.public_key());
+        assert_eq!(identity.devices, vec![device_meta.device_id.clone()]);
+        assert!(identity.verify());
+
+        // Only the identity's own master key can authorize
+        let other = MasterKey::generate();
+        assert!(identity.authorize_device(&other, &request).is_err());
+
+        // A bundle the requester can't sign for is refused
+        let mut forged = request.clone();
+        forged.key_package.init_key = Keypair::generate(KeyType::Ed25519).public_key().to_vec();
+        assert!(matches!(
+            identity.authorize_device(&master_key, &forged),
+            Err(ValidationError::InvalidSignature)
+        ));
+    }
 }
@@ -145,6 +145,19 @@ impl Keypair {
         }
     }
 
+    /// X25519 public key matching an Ed25519 public key
+    ///
+    /// The counterpart of [`derive_x25519_from_ed25519`](Self::derive_x25519_from_ed25519)
+    /// for the other party: lets anyone encrypt to the holder of an Ed25519
+    /// key knowing only its public half.
+    pub fn x25519_public_from_ed25519(pubkey: &[u8]) -> Result<Vec<u8>, String> {
+        let bytes: &[u8; 32] =
+            pubkey.try_into().map_err(|_| "Public key must be 32 bytes".to_string())?;
+        let verifying_key = VerifyingKey::from_bytes(bytes)
+            .map_err(|e| format!("Invalid Ed25519 public key: {}", e))?;
+        Ok(verifying_key.to_montgomery().to_bytes().to_vec())
+    }
+
     /// Serialize to bytes (suitable for keystore)
     pub fn serialize(&self) -> Vec<u8> {
         bincode::serialize(self).expect("Failed to serialize keypair")
@@ -241,6 +254,13 @@ mod tests {
         assert_eq!(x25519_kp.key_type, KeyType::X25519);
     }
 
+    #[test]
+    fn test_x25519_public_matches_derived_key() {
+        let ed = Keypair::generate(KeyType::Ed25519);
+        let x = Keypair::derive_x25519_from_ed25519(&ed);
+        assert_eq!(Keypair::x25519_public_from_ed25519(ed.public_key()).unwrap(), x.public_key());
+    }
+
     #[test]
     fn test_secret_zeroized_on_drop() {
         // Create a keypair and get a pointer to its secret bytes
@@ -9,7 +9,7 @@
 //! [Ciphertext + AEAD tag: variable]
 //! ```
 
-use super::{Keystore, KeystoreError};
+use super::{validate_secret_name, Keystore, KeystoreError};
 use crate::core_identity::device_id::DeviceId;
 use crate::core_identity::keypair::Keypair;
 use aes_gcm::{
@@ -19,7 +19,8 @@ use aes_gcm::{
 use argon2::{Argon2, Params};
 use rand::RngCore;
 use std::fs;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use zeroize::Zeroizing;
 
 /// Magic header for encrypted keystore files
@@ -37,6 +38,19 @@ const NONCE_LEN: usize = 12;
 /// Header size: magic(8) + version(1) + salt(16) + nonce(12) = 37 bytes
 const HEADER_SIZE: usize = 8 + 1 + SALT_LEN + NONCE_LEN;
 
+/// File name of the identity keypair
+const IDENTITY_FILE: &str = "identity.bin.enc";
+
+/// Suffix of every keystore entry file
+const ENTRY_SUFFIX: &str = ".bin.enc";
+
+/// Extension of entries re-encrypted by a rotation but not yet swapped in
+const ROTATING_EXTENSION: &str = "rotating";
+
+/// Marker written once every entry is staged; from then on a rotation
+/// is completed rather than discarded
+const ROTATION_MARKER: &str = "rotation.commit";
+
 /// File-based encrypted keystore
 pub struct FileKeystore {
     /// Directory where keys are stored
@@ -50,13 +64,79 @@ impl FileKeystore {
     pub fn new(base_path: PathBuf, password: Option<&str>) -> Result<Self, KeystoreError> {
         // Create directory if it doesn't exist
         fs::create_dir_all(&base_path)?;
+        Self::recover_rotation(&base_path)?;
 
         Ok(FileKeystore { base_path, password: password.map(|s| Zeroizing::new(s.to_string())) })
     }
 
+    /// Whether the keystore at `base_path` holds a passphrase-protected identity keypair
+    pub fn is_password_protected(base_path: &Path) -> bool {
+        fs::read(base_path.join(IDENTITY_FILE))
+            .map(|data| data.starts_with(MAGIC_HEADER))
+            .unwrap_or(false)
+    }
+
     /// Get path for identity keypair
     fn identity_path(&self) -> PathBuf {
-        self.base_path.join("identity.bin.enc")
+        self.base_path.join(IDENTITY_FILE)
+    }
+
+    /// Paths of every stored entry
+    fn entry_paths(&self) -> Result<Vec<PathBuf>, KeystoreError> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(&self.base_path)? {
+            let path = entry?.path();
+            let is_entry = path
+                .file_name()
+                .map(|name| name.to_string_lossy().ends_with(ENTRY_SUFFIX))
+                .unwrap_or(false);
+            if is_entry {
+                paths.push(path);
+            }
+        }
+        Ok(paths)
+    }
+
+    /// Re-encrypt every entry under `password` into a staging file next to it
+    ///
+    /// Covers the identity keypair, every device keypair and the secrets.
+    fn stage_rotation(&self, password: &str) -> Result<(), KeystoreError> {
+        let rotated = FileKeystore {
+            base_path: self.base_path.clone(),
+            password: Some(Zeroizing::new(password.to_string())),
+        };
+
+        for path in self.entry_paths()? {
+            let plaintext = Zeroizing::new(self.decrypt(&fs::read(&path)?)?);
+            write_synced(&path.with_extension(ROTATING_EXTENSION), &rotated.encrypt(&plaintext)?)?;
+        }
+        Ok(())
+    }
+
+    /// Finish or undo a rotation that was interrupted
+    ///
+    /// Without the commit marker, staged entries are discarded and the old
+    /// password stays valid; with it, they are all swapped in. Either way no
+    /// mix of old and new entries survives.
+    fn recover_rotation(base_path: &Path) -> Result<(), KeystoreError> {
+        let marker = base_path.join(ROTATION_MARKER);
+        let committed = marker.exists();
+
+        for entry in fs::read_dir(base_path)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == ROTATING_EXTENSION) {
+                if committed {
+                    fs::rename(&path, path.with_extension("enc"))?;
+                } else {
+                    fs::remove_file(&path)?;
+                }
+            }
+        }
+
+        if committed {
+            fs::remove_file(marker)?;
+        }
+        Ok(())
     }
 
     /// Get path for device keypair
@@ -64,6 +144,11 @@ impl FileKeystore {
         self.base_path.join(format!("device-{}.bin.enc", device_id.to_string()))
     }
 
+    /// Get path for a named secret
+    fn secret_path(&self, name: &str) -> PathBuf {
+        self.base_path.join(format!("secret-{}.bin.enc", name))
+    }
+
     /// Encrypt data with AEAD (AES-256-GCM)
     ///
     /// Returns: [magic][version][salt][nonce][ciphertext+tag]
@@ -245,14 +330,53 @@ impl Keystore for FileKeystore {
         Ok(devices)
     }
 
-    fn rotate_master_key(&self, _password: &str) -> Result<(), KeystoreError> {
-        // TODO: Implement key rotation
-        // 1. Load all keys with old master key
-        // 2. Derive new master key from new password
-        // 3. Re-encrypt all keys with new master key
-        // 4. Write back to disk atomically
-        Err(KeystoreError::Encryption("Key rotation not yet implemented".to_string()))
+    fn get_secret(&self, name: &str) -> Result<Vec<u8>, KeystoreError> {
+        validate_secret_name(name)?;
+        let path = self.secret_path(name);
+        if !path.exists() {
+            return Err(KeystoreError::NotFound(format!("Secret not found: {}", name)));
+        }
+
+        let encrypted = fs::read(&path)?;
+        self.decrypt(&encrypted)
     }
+
+    fn put_secret(&self, name: &str, value: &[u8]) -> Result<(), KeystoreError> {
+        validate_secret_name(name)?;
+        let encrypted = self.encrypt(value)?;
+        let path = self.secret_path(name);
+        self.write_atomic(&path, &encrypted)
+    }
+
+    /// Re-encrypt every entry under a new password
+    ///
+    /// Each entry gets a fresh salt and nonce. All entries are staged before
+    /// any is replaced, and a commit marker decides whether an interrupted
+    /// rotation is rolled back or completed (see [`FileKeystore::new`]), so
+    /// the keystore is never left half-rotated. This keystore keeps the old
+    /// password; open a new one to use the rotated entries.
+    fn rotate_master_key(&self, password: &str) -> Result<(), KeystoreError> {
+        if password.is_empty() {
+            return Err(KeystoreError::Other("New password must not be empty".to_string()));
+        }
+
+        // A wrong old password or any failure here discards what was staged
+        if let Err(e) = self.stage_rotation(password) {
+            let _ = Self::recover_rotation(&self.base_path);
+            return Err(e);
+        }
+
+        write_synced(&self.base_path.join(ROTATION_MARKER), &[])?;
+        Self::recover_rotation(&self.base_path)
+    }
+}
+
+/// Write a file and flush it to disk
+fn write_synced(path: &Path, data: &[u8]) -> Result<(), KeystoreError> {
+    let mut file = fs::File::create(path)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    Ok(())
 }
 
 /// Derive 256-bit encryption key from password using Argon2id
@@ -585,4 +709,141 @@ mod tests {
             _ => panic!("Expected error when encrypted keystore loads unencrypted file"),
         }
     }
+
+    #[test]
+    fn test_secret_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let keystore =
+            FileKeystore::new(temp_dir.path().to_path_buf(), Some("password123")).unwrap();
+
+        keystore.put_secret("otlp_token", b"token-value").unwrap();
+        assert_eq!(keystore.get_secret("otlp_token").unwrap(), b"token-value");
+
+        // Secrets are encrypted at rest like keypairs
+        let raw = fs::read(keystore.secret_path("otlp_token")).unwrap();
+        assert_eq!(&raw[0..8], MAGIC_HEADER);
+
+        // Wrong password cannot read it
+        let other = FileKeystore::new(temp_dir.path().to_path_buf(), Some("wrong")).unwrap();
+        assert!(matches!(other.get_secret("otlp_token"), Err(KeystoreError::InvalidPassword)));
+    }
+
+    #[test]
+    fn test_rotate_master_key() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().to_path_buf();
+        let keystore = FileKeystore::new(path.clone(), Some("old password")).unwrap();
+
+        let kp = Keypair::generate(KeyType::Ed25519);
+        keystore.save_identity_keypair(&kp).unwrap();
+        keystore.put_secret("otlp_token", b"token").unwrap();
+        assert!(FileKeystore::is_password_protected(&path));
+
+        // A wrong current password changes nothing
+        let wrong = FileKeystore::new(path.clone(), Some("wrong")).unwrap();
+        assert!(matches!(
+            wrong.rotate_master_key("new password"),
+            Err(KeystoreError::InvalidPassword)
+        ));
+        assert_eq!(keystore.get_secret("otlp_token").unwrap(), b"token");
+
+        keystore.rotate_master_key("new password").unwrap();
+        assert!(matches!(keystore.load_identity_keypair(), Err(KeystoreError::InvalidPassword)));
+
+        let rotated = FileKeystore::new(path.clone(), Some("new password")).unwrap();
+        assert_eq!(rotated.load_identity_keypair().unwrap().public_key(), kp.public_key());
+        assert_eq!(rotated.get_secret("otlp_token").unwrap(), b"token");
+        assert_eq!(leftover_rotation_files(&path), 0);
+    }
+
+    /// Staged entries and commit markers left in a keystore directory
+    fn leftover_rotation_files(path: &Path) -> usize {
+        fs::read_dir(path)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                path.extension().is_some_and(|ext| ext == ROTATING_EXTENSION)
+                    || path.ends_with(ROTATION_MARKER)
+            })
+            .count()
+    }
+
+    /// Keystore with an identity, two devices and a secret under `password`
+    fn populated_keystore(path: &Path, password: &str) -> (FileKeystore, Vec<DeviceId>) {
+        let keystore = FileKeystore::new(path.to_path_buf(), Some(password)).unwrap();
+        keystore.save_identity_keypair(&Keypair::generate(KeyType::Ed25519)).unwrap();
+        let devices = vec![DeviceId::generate(), DeviceId::generate()];
+        for device_id in &devices {
+            let kp = Keypair::generate(KeyType::Ed25519);
+            keystore.save_device_keypair(device_id, &kp).unwrap();
+        }
+        keystore.put_secret("otlp_token", b"token").unwrap();
+        (keystore, devices)
+    }
+
+    /// Every entry opens with the password
+    fn opens_with(path: &Path, password: &str, devices: &[DeviceId]) -> bool {
+        let keystore = FileKeystore::new(path.to_path_buf(), Some(password)).unwrap();
+        keystore.load_identity_keypair().is_ok()
+            && devices.iter().all(|id| keystore.load_device_keypair(id).is_ok())
+            && keystore.get_secret("otlp_token").is_ok()
+    }
+
+    #[test]
+    fn test_rotation_interrupted_before_commit_rolls_back() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        let (keystore, devices) = populated_keystore(path, "old password");
+
+        // Power lost after staging, before the commit marker was written
+        keystore.stage_rotation("new password").unwrap();
+        assert_eq!(leftover_rotation_files(path), 4);
+        drop(keystore);
+
+        assert!(opens_with(path, "old password", &devices));
+        assert!(!opens_with(path, "new password", &devices));
+        assert_eq!(leftover_rotation_files(path), 0);
+    }
+
+    #[test]
+    fn test_rotation_interrupted_after_commit_rolls_forward() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        let (keystore, devices) = populated_keystore(path, "old password");
+
+        // Power lost after the marker and the first swap
+        keystore.stage_rotation("new password").unwrap();
+        write_synced(&path.join(ROTATION_MARKER), &[]).unwrap();
+        let staged = path.join("identity.bin.rotating");
+        fs::rename(&staged, staged.with_extension("enc")).unwrap();
+        drop(keystore);
+
+        assert!(opens_with(path, "new password", &devices));
+        assert!(!opens_with(path, "old password", &devices));
+        assert_eq!(leftover_rotation_files(path), 0);
+    }
+
+    #[test]
+    fn test_rotate_protects_unencrypted_keystore() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().to_path_buf();
+        let keystore = FileKeystore::new(path.clone(), None).unwrap();
+        keystore.save_identity_keypair(&Keypair::generate(KeyType::Ed25519)).unwrap();
+        assert!(!FileKeystore::is_password_protected(&path));
+
+        keystore.rotate_master_key("passphrase").unwrap();
+        assert!(FileKeystore::is_password_protected(&path));
+        let rotated = FileKeystore::new(path, Some("passphrase")).unwrap();
+        assert!(rotated.load_identity_keypair().is_ok());
+    }
+
+    #[test]
+    fn test_secret_not_found_and_invalid_name() {
+        let temp_dir = TempDir::new().unwrap();
+        let keystore = FileKeystore::new(temp_dir.path().to_path_buf(), None).unwrap();
+
+        assert!(matches!(keystore.get_secret("missing"), Err(KeystoreError::NotFound(_))));
+        assert!(keystore.put_secret("../../etc/passwd", b"x").is_err());
+        assert!(keystore.put_secret("", b"x").is_err());
+    }
 }
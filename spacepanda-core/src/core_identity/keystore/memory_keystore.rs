@@ -1,6 +1,6 @@
 //! In-memory keystore for testing
 
-use super::{Keystore, KeystoreError};
+use super::{validate_secret_name, Keystore, KeystoreError};
 use crate::core_identity::device_id::DeviceId;
 use crate::core_identity::keypair::Keypair;
 use std::collections::HashMap;
@@ -16,6 +16,7 @@ fn handle_poison<T>(_err: PoisonError<T>) -> KeystoreError {
 pub struct MemoryKeystore {
     identity: Arc<RwLock<Option<Keypair>>>,
     devices: Arc<RwLock<HashMap<DeviceId, Keypair>>>,
+    secrets: Arc<RwLock<HashMap<String, Vec<u8>>>>,
 }
 
 impl MemoryKeystore {
@@ -24,6 +25,7 @@ impl MemoryKeystore {
         MemoryKeystore {
             identity: Arc::new(RwLock::new(None)),
             devices: Arc::new(RwLock::new(HashMap::new())),
+            secrets: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 }
@@ -70,6 +72,25 @@ impl Keystore for MemoryKeystore {
     fn list_devices(&self) -> Result<Vec<DeviceId>, KeystoreError> {
         Ok(self.devices.read().map_err(handle_poison)?.keys().cloned().collect())
     }
+
+    fn get_secret(&self, name: &str) -> Result<Vec<u8>, KeystoreError> {
+        validate_secret_name(name)?;
+        self.secrets
+            .read()
+            .map_err(handle_poison)?
+            .get(name)
+            .cloned()
+            .ok_or_else(|| KeystoreError::NotFound(format!("Secret not found: {}", name)))
+    }
+
+    fn put_secret(&self, name: &str, value: &[u8]) -> Result<(), KeystoreError> {
+        validate_secret_name(name)?;
+        self.secrets
+            .write()
+            .map_err(handle_poison)?
+            .insert(name.to_string(), value.to_vec());
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -107,4 +128,15 @@ mod tests {
         let loaded1 = keystore.load_device_keypair(&device1).unwrap();
         assert_eq!(kp1.public_key(), loaded1.public_key());
     }
+
+    #[test]
+    fn test_memory_keystore_secrets() {
+        let keystore = MemoryKeystore::new();
+
+        keystore.put_secret("webhook_token", b"s3cr3t").unwrap();
+        assert_eq!(keystore.get_secret("webhook_token").unwrap(), b"s3cr3t");
+
+        assert!(matches!(keystore.get_secret("missing"), Err(KeystoreError::NotFound(_))));
+        assert!(keystore.put_secret("../escape", b"x").is_err());
+    }
 }
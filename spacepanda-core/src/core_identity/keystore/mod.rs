@@ -34,6 +34,23 @@ pub enum KeystoreError {
     Other(String),
 }
 
+/// Validate a secret name
+///
+/// Names end up in file names, so only `[A-Za-z0-9_.-]` is accepted and
+/// names may not start with a dot.
+pub fn validate_secret_name(name: &str) -> Result<(), KeystoreError> {
+    let valid = !name.is_empty()
+        && name.len() <= 128
+        && !name.starts_with('.')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+
+    if valid {
+        Ok(())
+    } else {
+        Err(KeystoreError::Other(format!("Invalid secret name: {:?}", name)))
+    }
+}
+
 /// Abstract keystore trait
 pub trait Keystore: Send + Sync {
     /// Load identity keypair
@@ -51,6 +68,12 @@ pub trait Keystore: Send + Sync {
     /// List all device IDs
     fn list_devices(&self) -> Result<Vec<DeviceId>, KeystoreError>;
 
+    /// Load a named secret from the generic secrets store
+    fn get_secret(&self, name: &str) -> Result<Vec<u8>, KeystoreError>;
+
+    /// Save a named secret to the generic secrets store (overwrites existing)
+    fn put_secret(&self, name: &str, value: &[u8]) -> Result<(), KeystoreError>;
+
     /// Rotate master key (optional, for password change)
     fn rotate_master_key(&self, password: &str) -> Result<(), KeystoreError> {
         let _ = password;
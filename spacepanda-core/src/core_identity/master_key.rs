@@ -11,6 +11,7 @@ use crate::core_identity::keypair::{KeyType, Keypair};
 use hkdf::Hkdf;
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
+use x25519_dalek::StaticSecret;
 
 /// Master identity key - the user's long-term identity
 #[derive(Clone, Serialize, Deserialize)]
@@ -63,6 +64,44 @@ impl MasterKey {
         okm
     }
 
+    /// Derive the key that encrypts the local store at rest
+    ///
+    /// Deterministic, so the store can be reopened with the same identity,
+    /// and independent of every pseudonym.
+    pub fn derive_store_key(&self) -> [u8; 32] {
+        let hk = Hkdf::<Sha256>::new(Some(b"spacepanda-store-key-v1"), self.keypair.secret_key());
+
+        let mut okm = [0u8; 32];
+        hk.expand(b"local-store", &mut okm).expect("HKDF expand failed");
+
+        okm
+    }
+
+    /// Onion key for relaying circuits
+    ///
+    /// The X25519 form of the identity key, so peers can compute our
+    /// PeerInfo::overlay_pubkey from the public key alone.
+    pub fn onion_key(&self) -> StaticSecret {
+        let x25519 = Keypair::derive_x25519_from_ed25519(&self.keypair);
+        let secret: [u8; 32] =
+            x25519.secret_key().try_into().expect("X25519 secret key must be 32 bytes");
+        StaticSecret::from(secret)
+    }
+
+    /// Wrap a keypair loaded from a keystore
+    pub fn from_keypair(keypair: Keypair) -> Result<Self, String> {
+        if keypair.key_type != KeyType::Ed25519 {
+            return Err("Master key must be Ed25519".to_string());
+        }
+
+        Ok(MasterKey { keypair })
+    }
+
+    /// Underlying keypair (for keystore)
+    pub fn keypair(&self) -> &Keypair {
+        &self.keypair
+    }
+
     /// Serialize to bytes (for keystore)
     pub fn to_bytes(&self) -> Vec<u8> {
         self.keypair.serialize()
@@ -149,6 +188,27 @@ mod tests {
         assert_ne!(p1, p2);
     }
 
+    #[test]
+    fn test_store_key_deterministic() {
+        let mk = MasterKey::generate();
+        let restored = MasterKey::from_keypair(mk.keypair().clone()).unwrap();
+
+        assert_eq!(mk.derive_store_key(), restored.derive_store_key());
+        assert_ne!(mk.derive_store_key(), MasterKey::generate().derive_store_key());
+        assert_ne!(mk.derive_store_key().to_vec(), mk.derive_pseudonym("local-store"));
+    }
+
+    #[test]
+    fn test_onion_key_matches_public_key() {
+        let mk = MasterKey::generate();
+        let onion_pub = x25519_dalek::PublicKey::from(&mk.onion_key());
+
+        assert_eq!(
+            onion_pub.as_bytes().to_vec(),
+            Keypair::x25519_public_from_ed25519(mk.public_key()).unwrap()
+        );
+    }
+
     #[test]
     fn test_export_import_roundtrip() {
         let mk = MasterKey::generate();
@@ -35,6 +35,9 @@ pub enum ValidationError {
 /// Maximum allowed timestamp skew (1 hour in seconds)
 const MAX_TIMESTAMP_SKEW: u64 = 3600;
 
+/// Granularity of device `last_seen` timestamps (1 day in seconds)
+const LAST_SEEN_GRANULARITY: u64 = 24 * 3600;
+
 /// Validate a key package
 pub fn validate_keypackage(kp_bytes: &[u8]) -> Result<KeyPackage, ValidationError> {
     let kp = KeyPackage::from_bytes(kp_bytes)
@@ -60,24 +63,46 @@ pub fn validate_keypackage(kp_bytes: &[u8]) -> Result<KeyPackage, ValidationErro
 }
 
 /// Validate a device bundle
+///
+/// A bundle with a binding is from a linked device: the binding must be
+/// signed by the identity key and name this device's key, and the bundle
+/// itself is signed by the device key.
 pub fn validate_device_bundle(
     bundle: &DeviceBundle,
     expected_user: &UserId,
     identity_pubkey: &[u8],
 ) -> Result<(), ValidationError> {
+    let signer = match &bundle.binding {
+        Some(binding) => {
+            if !binding.verify(identity_pubkey) {
+                return Err(ValidationError::InvalidSignature);
+            }
+            if binding.device_id != bundle.device_metadata.device_id
+                || binding.device_public_key != bundle.device_public_key()
+            {
+                return Err(ValidationError::CredentialVerificationFailed);
+            }
+            binding.device_public_key.as_slice()
+        }
+        None => identity_pubkey,
+    };
+
     // Verify bundle signature
-    if !bundle.verify(identity_pubkey) {
+    if !bundle.verify(signer) {
         return Err(ValidationError::InvalidSignature);
     }
 
     // Verify key package signature
-    if !bundle.key_package.verify(identity_pubkey) {
+    if !bundle.key_package.verify(signer) {
         return Err(ValidationError::InvalidSignature);
     }
 
-    // Verify device metadata is recent
+    // Verify device metadata is recent (last_seen is rounded down to the day)
     if let Some(last_seen) = bundle.device_metadata.last_seen.get() {
-        validate_timestamp(last_seen.as_millis() / 1000)?;
+        validate_timestamp_within(
+            last_seen.as_millis() / 1000,
+            LAST_SEEN_GRANULARITY + MAX_TIMESTAMP_SKEW,
+        )?;
     }
 
     Ok(())
@@ -119,6 +144,11 @@ pub fn validate_signature(sig: &IdentitySignature, pubkey: &[u8]) -> Result<(),
 
 /// Validate timestamp is within acceptable range
 pub fn validate_timestamp(timestamp: u64) -> Result<(), ValidationError> {
+    validate_timestamp_within(timestamp, MAX_TIMESTAMP_SKEW)
+}
+
+/// Validate timestamp is at most `max_age` seconds old and not in the future
+fn validate_timestamp_within(timestamp: u64, max_age: u64) -> Result<(), ValidationError> {
     use std::time::{SystemTime, UNIX_EPOCH};
 
     let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
@@ -131,7 +161,7 @@ pub fn validate_timestamp(timestamp: u64) -> Result<(), ValidationError> {
     }
 
     // Check if timestamp is too far in the past
-    if timestamp + MAX_TIMESTAMP_SKEW < now {
+    if timestamp + max_age < now {
         return Err(ValidationError::TimestampOutOfRange("Timestamp too old".to_string()));
     }
 
@@ -171,7 +201,9 @@ mod tests {
     use super::*;
     use crate::core_identity::bundles::KeyPackage;
     use crate::core_identity::device_id::DeviceId;
+    use crate::core_identity::device_key::DeviceKeyBinding;
     use crate::core_identity::keypair::{KeyType, Keypair};
+    use crate::core_identity::master_key::MasterKey;
     use crate::core_identity::metadata::DeviceMetadata;
     use crate::core_identity::signatures::IdentitySignature;
 
@@ -189,6 +221,66 @@ mod tests {
         assert!(validated.is_ok());
     }
 
+    #[test]
+    fn test_validate_linked_device_bundle() {
+        let master_key = MasterKey::generate();
+        let user_id = UserId::from_public_key(master_key.public_key());
+        let mut identity = IdentityBundle::new(
+            user_id.as_bytes().to_vec(),
+            master_key.public_key().to_vec(),
+            Vec::new(),
+            master_key.keypair(),
+        );
+
+        let device_kp = Keypair::generate(KeyType::Ed25519);
+        let device_meta = DeviceMetadata::new(DeviceId::generate(), "Laptop".to_string(), "node2");
+        let request = DeviceBundle::for_new_device(&device_kp, device_meta);
+
+        // Self-signed only: not authorized by the identity
+        assert!(validate_device_bundle(&request, &user_id, master_key.public_key()).is_err());
+
+        let binding = identity.authorize_device(&master_key, &request).unwrap();
+        let linked = request.clone().with_binding(binding.clone());
+        assert!(validate_device_bundle(&linked, &user_id, master_key.public_key()).is_ok());
+
+        // A binding signed by someone else's master key
+        let intruder = MasterKey::generate();
+        let forged = DeviceKeyBinding::new(
+            &intruder,
+            binding.device_id.clone(),
+            binding.key_version,
+            binding.device_public_key.clone(),
+        );
+        let unauthorized = request.clone().with_binding(forged);
+        assert!(matches!(
+            validate_device_bundle(&unauthorized, &user_id, master_key.public_key()),
+            Err(ValidationError::InvalidSignature)
+        ));
+
+        // A tampered signature over the binding
+        let mut tampered = binding;
+        tampered.master_signature[0] ^= 0xff;
+        let unauthorized = request.clone().with_binding(tampered);
+        assert!(matches!(
+            validate_device_bundle(&unauthorized, &user_id, master_key.public_key()),
+            Err(ValidationError::InvalidSignature)
+        ));
+
+        // A genuine binding, but for another device
+        let other_kp = Keypair::generate(KeyType::Ed25519);
+        let other_meta = DeviceMetadata::new(DeviceId::generate(), "Phone".to_string(), "node3");
+        let other = DeviceBundle::for_new_device(&other_kp, other_meta);
+        let other_binding = identity.authorize_device(&master_key, &other).unwrap();
+        assert!(matches!(
+            validate_device_bundle(
+                &request.with_binding(other_binding),
+                &user_id,
+                master_key.public_key()
+            ),
+            Err(ValidationError::CredentialVerificationFailed)
+        ));
+    }
+
     #[test]
     fn test_validate_signature() {
         let identity_kp = Keypair::generate(KeyType::Ed25519);
@@ -0,0 +1,202 @@
+//! Channel metadata carried in the group context
+//!
+//! A channel's name, topic, avatar hash and retention policy travel in a
+//! custom GroupContext extension, so every member sees the same values and
+//! a change only takes effect through a commit. Members advertise support
+//! for the extension in their leaf capabilities ([`capabilities`]); OpenMLS
+//! refuses a GroupContextExtensions proposal carrying an extension that
+//! some member doesn't support.
+//!
+//! Only owners and admins may commit a change; commits from anyone else
+//! are rejected when they are processed (see
+//! [`commit_policy`](crate::core_mls::commit_policy)).
+
+use crate::core_mls::commit_policy::CHANNEL_ROLES_EXTENSION_TYPE;
+use crate::core_mls::errors::{MlsError, MlsResult};
+use crate::core_store::model::Timestamp;
+use openmls::prelude::{Capabilities, Extension, ExtensionType, Extensions, UnknownExtension};
+use serde::{Deserialize, Serialize};
+
+/// Extension type of the channel metadata (private-use range)
+pub const CHANNEL_METADATA_EXTENSION_TYPE: u16 = 0xff0a;
+
+/// Longest channel name, in bytes
+pub const MAX_NAME_LEN: usize = 128;
+
+/// Longest channel topic, in bytes
+pub const MAX_TOPIC_LEN: usize = 1024;
+
+const MILLIS_PER_DAY: u64 = 24 * 60 * 60 * 1000;
+
+/// How long members keep a channel's messages
+///
+/// Every member prunes its own replica; see
+/// [`ChannelManager::prune_expired`](crate::core_mvp::channel_manager::ChannelManager::prune_expired).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RetentionPolicy {
+    /// Never prune
+    #[default]
+    KeepForever,
+
+    /// Prune messages older than this many days
+    KeepDays(u32),
+
+    /// Keep only this many of the newest messages
+    KeepMessages(u32),
+}
+
+impl RetentionPolicy {
+    /// Messages sent before this are expired, for an age-based policy
+    pub fn cutoff(&self, now: Timestamp) -> Option<Timestamp> {
+        match self {
+            RetentionPolicy::KeepDays(days) => Some(Timestamp::from_millis(
+                now.as_millis().saturating_sub(u64::from(*days) * MILLIS_PER_DAY),
+            )),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for RetentionPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RetentionPolicy::KeepForever => write!(f, "keep forever"),
+            RetentionPolicy::KeepDays(days) => write!(f, "keep {} day(s)", days),
+            RetentionPolicy::KeepMessages(count) => write!(f, "keep {} message(s)", count),
+        }
+    }
+}
+
+/// Name, topic, avatar and retention of a channel, as agreed by the group
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelMetadata {
+    /// Channel name
+    pub name: String,
+
+    /// Channel topic
+    pub topic: Option<String>,
+
+    /// SHA-256 of the channel avatar
+    pub avatar_hash: Option<[u8; 32]>,
+
+    /// How long members keep messages
+    #[serde(default)]
+    pub retention: RetentionPolicy,
+}
+
+impl ChannelMetadata {
+    /// Metadata with just a name
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into(), ..Default::default() }
+    }
+
+    /// Check the field lengths
+    ///
+    /// # Errors
+    ///
+    /// `InvalidInput` for an empty or overlong name, an overlong topic, or
+    /// a retention policy keeping nothing
+    pub fn validate(&self) -> MlsResult<()> {
+        if self.name.is_empty() || self.name.len() > MAX_NAME_LEN {
+            return Err(MlsError::InvalidInput(format!(
+                "Channel name must be 1 to {} bytes",
+                MAX_NAME_LEN
+            )));
+        }
+        if self.topic.as_ref().is_some_and(|topic| topic.len() > MAX_TOPIC_LEN) {
+            return Err(MlsError::InvalidInput(format!(
+                "Channel topic must be at most {} bytes",
+                MAX_TOPIC_LEN
+            )));
+        }
+        if matches!(self.retention, RetentionPolicy::KeepDays(0) | RetentionPolicy::KeepMessages(0))
+        {
+            return Err(MlsError::InvalidInput(
+                "Retention must keep at least one day or message".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Encode as a GroupContext extension
+    pub fn to_extension(&self) -> MlsResult<Extension> {
+        Ok(Extension::Unknown(
+            CHANNEL_METADATA_EXTENSION_TYPE,
+            UnknownExtension(bincode::serialize(self)?),
+        ))
+    }
+
+    /// Metadata in a set of extensions, if present
+    pub fn from_extensions(extensions: &Extensions) -> MlsResult<Option<Self>> {
+        extensions
+            .unknown(CHANNEL_METADATA_EXTENSION_TYPE)
+            .map(|extension| bincode::deserialize(&extension.0).map_err(MlsError::from))
+            .transpose()
+    }
+}
+
+/// Leaf capabilities advertising support for the channel extensions
+///
+/// Covers the metadata and the roles extension.
+pub fn capabilities() -> Capabilities {
+    Capabilities::new(
+        None,
+        None,
+        Some(&[
+            ExtensionType::Unknown(CHANNEL_METADATA_EXTENSION_TYPE),
+            ExtensionType::Unknown(CHANNEL_ROLES_EXTENSION_TYPE),
+        ]),
+        None,
+        None,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extension_roundtrip() {
+        let metadata = ChannelMetadata {
+            name: "general".to_string(),
+            topic: Some("release planning".to_string()),
+            avatar_hash: Some([7; 32]),
+            retention: RetentionPolicy::KeepDays(7),
+        };
+
+        let extensions = Extensions::single(metadata.to_extension().unwrap());
+        assert_eq!(ChannelMetadata::from_extensions(&extensions).unwrap(), Some(metadata));
+        assert_eq!(ChannelMetadata::from_extensions(&Extensions::empty()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(ChannelMetadata::new("general").validate().is_ok());
+        assert!(ChannelMetadata::new("").validate().is_err());
+        assert!(ChannelMetadata::new("x".repeat(MAX_NAME_LEN + 1)).validate().is_err());
+
+        let metadata = ChannelMetadata {
+            topic: Some("x".repeat(MAX_TOPIC_LEN + 1)),
+            ..ChannelMetadata::new("general")
+        };
+        assert!(matches!(metadata.validate(), Err(MlsError::InvalidInput(_))));
+
+        let metadata = ChannelMetadata {
+            retention: RetentionPolicy::KeepMessages(0),
+            ..ChannelMetadata::new("general")
+        };
+        assert!(metadata.validate().is_err());
+    }
+
+    #[test]
+    fn test_retention_cutoff() {
+        let now = Timestamp::from_millis(10 * MILLIS_PER_DAY);
+        assert_eq!(
+            RetentionPolicy::KeepDays(7).cutoff(now),
+            Some(Timestamp::from_millis(3 * MILLIS_PER_DAY))
+        );
+        assert_eq!(RetentionPolicy::KeepDays(30).cutoff(now), Some(Timestamp::from_millis(0)));
+        assert_eq!(RetentionPolicy::KeepMessages(5).cutoff(now), None);
+        assert_eq!(RetentionPolicy::KeepForever.cutoff(now), None);
+    }
+}
@@ -0,0 +1,146 @@
+//! Log of processed commits
+//!
+//! Commit envelopes reach a member more than once: DHT replication and
+//! gossip both redeliver. OpenMLS only knows the current epoch, so a
+//! second copy of an already merged commit fails as a wrong-epoch message.
+//! The service keeps the hashes of the last [`MAX_PROCESSED_COMMITS`]
+//! commits of each group, with the epoch each one was merged in, and
+//! answers a known commit without handing it to OpenMLS again. A different
+//! commit for an epoch that already has one is a fork, resolved as
+//! described in [`fork`](crate::core_mls::fork).
+//!
+//! The log is persisted next to the group state so a restart doesn't
+//! forget it.
+
+use crate::core_mls::errors::MlsResult;
+use crate::core_mls::types::GroupId;
+use openmls::prelude::tls_codec::Deserialize as _;
+use openmls::prelude::{ContentType, MlsMessageBodyIn, MlsMessageIn, ProtocolMessage};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+
+/// Commits remembered per group
+pub const MAX_PROCESSED_COMMITS: usize = 64;
+
+/// Hash identifying a commit envelope
+pub type CommitHash = [u8; 32];
+
+/// Hash of a commit envelope as delivered
+pub fn commit_hash(message_bytes: &[u8]) -> CommitHash {
+    Sha256::digest(message_bytes).into()
+}
+
+/// Epoch a commit envelope for `group_id` applies to
+///
+/// `None` for anything but a commit to that group. Only reads the
+/// unencrypted framing; nothing is verified.
+pub fn commit_epoch(group_id: &GroupId, message_bytes: &[u8]) -> Option<u64> {
+    let message = MlsMessageIn::tls_deserialize_exact(message_bytes).ok()?;
+    let protocol_message: ProtocolMessage = match message.extract() {
+        MlsMessageBodyIn::PrivateMessage(pm) => pm.into(),
+        MlsMessageBodyIn::PublicMessage(pm) => pm.into(),
+        _ => return None,
+    };
+    let is_commit = protocol_message.content_type() == ContentType::Commit;
+    let for_group = protocol_message.group_id().as_slice() == group_id.as_bytes();
+    (is_commit && for_group).then(|| protocol_message.epoch().as_u64())
+}
+
+/// Recently merged commits of one group, oldest first
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcessedCommits {
+    entries: VecDeque<(u64, CommitHash)>,
+}
+
+impl ProcessedCommits {
+    /// Storage key of a group's log
+    pub fn storage_key(group_id: &GroupId) -> String {
+        format!("processed_commits:{}", group_id.to_hex())
+    }
+
+    /// Decode a persisted log
+    pub fn from_bytes(bytes: &[u8]) -> MlsResult<Self> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+
+    /// Encode for persistence
+    pub fn to_bytes(&self) -> MlsResult<Vec<u8>> {
+        Ok(bincode::serialize(self)?)
+    }
+
+    /// Whether the commit was already merged
+    pub fn contains(&self, hash: &CommitHash) -> bool {
+        self.entries.iter().any(|(_, seen)| seen == hash)
+    }
+
+    /// Hash of the commit merged for `epoch`, if it is still remembered
+    pub fn commit_for(&self, epoch: u64) -> Option<&CommitHash> {
+        self.entries.iter().find(|(seen, _)| *seen == epoch).map(|(_, hash)| hash)
+    }
+
+    /// Remember a merged commit, forgetting the oldest beyond the limit
+    ///
+    /// Replaces the commit recorded for the same epoch, which a resolved
+    /// fork has undone.
+    pub fn record(&mut self, epoch: u64, hash: CommitHash) {
+        if self.contains(&hash) {
+            return;
+        }
+        self.entries.retain(|(seen, _)| *seen != epoch);
+        self.entries.push_back((epoch, hash));
+        while self.entries.len() > MAX_PROCESSED_COMMITS {
+            self.entries.pop_front();
+        }
+    }
+
+    /// Number of remembered commits
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no commit is remembered
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_lookup() {
+        let mut log = ProcessedCommits::default();
+        let first = commit_hash(b"commit 1");
+        log.record(1, first);
+        log.record(1, first);
+
+        assert_eq!(log.len(), 1);
+        assert!(log.contains(&first));
+        assert!(!log.contains(&commit_hash(b"commit 1'")));
+        assert_eq!(log.commit_for(1), Some(&first));
+        assert_eq!(log.commit_for(2), None);
+
+        let winner = commit_hash(b"commit 1'");
+        log.record(1, winner);
+        assert_eq!(log.len(), 1);
+        assert!(!log.contains(&first));
+        assert_eq!(log.commit_for(1), Some(&winner));
+    }
+
+    #[test]
+    fn test_oldest_commits_are_forgotten() {
+        let mut log = ProcessedCommits::default();
+        for epoch in 0..(MAX_PROCESSED_COMMITS as u64 + 2) {
+            log.record(epoch, commit_hash(&epoch.to_be_bytes()));
+        }
+
+        assert_eq!(log.len(), MAX_PROCESSED_COMMITS);
+        assert!(!log.contains(&commit_hash(&0u64.to_be_bytes())));
+        assert!(log.commit_for(2).is_some());
+
+        let restored = ProcessedCommits::from_bytes(&log.to_bytes().unwrap()).unwrap();
+        assert_eq!(restored, log);
+    }
+}
@@ -0,0 +1,205 @@
+//! Commit authorization policy
+//!
+//! OpenMLS accepts any well-formed commit from any member. On top of that,
+//! every member checks who committed what before merging:
+//!
+//! - Add and Remove proposals need an owner or admin, except that members
+//!   may add and remove leaves of their own identity (linked devices) and
+//!   a Remove that the removed member proposed itself (leaving) can be
+//!   committed by anyone
+//! - Changing the channel metadata needs an owner or admin
+//! - Changing the roles themselves needs the owner
+//!
+//! Roles are kept by identity in a custom GroupContext extension
+//! ([`ChannelRoles`]), so they are agreed by the whole group and only
+//! change through a commit. A group without the extension is owned by the
+//! member at leaf 0, its creator.
+//!
+//! A commit that fails the policy is rejected before it is merged, so it
+//! doesn't move honest members to the next epoch.
+
+use crate::core_mls::channel_metadata::ChannelMetadata;
+use crate::core_mls::errors::{MlsError, MlsResult};
+use openmls::prelude::{
+    Extension, Extensions, LeafNodeIndex, MlsGroup, Proposal, Sender, StagedCommit,
+    UnknownExtension,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Extension type of the channel roles (private-use range)
+pub const CHANNEL_ROLES_EXTENSION_TYPE: u16 = 0xff0b;
+
+/// Owner and admins of a channel, by MLS identity
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelRoles {
+    /// Identity of the channel owner
+    pub owner: Vec<u8>,
+
+    /// Identities of the admins (not including the owner)
+    pub admins: BTreeSet<Vec<u8>>,
+}
+
+impl ChannelRoles {
+    /// Roles with just an owner
+    pub fn new(owner: Vec<u8>) -> Self {
+        Self { owner, admins: BTreeSet::new() }
+    }
+
+    /// Whether `identity` owns the channel
+    pub fn is_owner(&self, identity: &[u8]) -> bool {
+        self.owner == identity
+    }
+
+    /// Whether `identity` may manage members (the owner or an admin)
+    pub fn is_admin(&self, identity: &[u8]) -> bool {
+        self.is_owner(identity) || self.admins.contains(identity)
+    }
+
+    /// Make `identity` an admin; `false` if it already manages members
+    pub fn promote(&mut self, identity: &[u8]) -> bool {
+        !self.is_owner(identity) && self.admins.insert(identity.to_vec())
+    }
+
+    /// Take admin rights from `identity`; `false` if it wasn't an admin
+    pub fn demote(&mut self, identity: &[u8]) -> bool {
+        self.admins.remove(identity)
+    }
+
+    /// Encode as a GroupContext extension
+    pub fn to_extension(&self) -> MlsResult<Extension> {
+        Ok(Extension::Unknown(
+            CHANNEL_ROLES_EXTENSION_TYPE,
+            UnknownExtension(bincode::serialize(self)?),
+        ))
+    }
+
+    /// Roles in a set of extensions, if present
+    pub fn from_extensions(extensions: &Extensions) -> MlsResult<Option<Self>> {
+        extensions
+            .unknown(CHANNEL_ROLES_EXTENSION_TYPE)
+            .map(|extension| bincode::deserialize(&extension.0).map_err(MlsError::from))
+            .transpose()
+    }
+
+    /// Roles of a group, defaulting to its creator as the owner
+    pub fn of_group(group: &MlsGroup) -> MlsResult<Self> {
+        if let Some(roles) = Self::from_extensions(group.extensions())? {
+            return Ok(roles);
+        }
+        let creator = identity_at(group, LeafNodeIndex::new(0)).unwrap_or_default();
+        Ok(Self::new(creator))
+    }
+}
+
+/// Identity of the member at a leaf
+fn identity_at(group: &MlsGroup, leaf: LeafNodeIndex) -> Option<Vec<u8>> {
+    group
+        .members()
+        .find(|member| member.index == leaf)
+        .map(|member| member.credential.serialized_content().to_vec())
+}
+
+/// Check a staged commit against the policy before merging it
+///
+/// `group` is the state the commit applies to. Only commits by members
+/// are checked; whether external commits are accepted at all is up to the
+/// caller.
+///
+/// # Errors
+///
+/// `PermissionDenied` naming the first change the committer isn't allowed
+/// to make
+pub fn check_commit(
+    group: &MlsGroup,
+    sender: &Sender,
+    staged_commit: &StagedCommit,
+) -> MlsResult<()> {
+    let Sender::Member(committer_leaf) = sender else {
+        return Ok(());
+    };
+    let committer = identity_at(group, *committer_leaf).unwrap_or_default();
+    let roles = ChannelRoles::of_group(group)?;
+    let authorized = roles.is_admin(&committer);
+
+    for queued in staged_commit.queued_proposals() {
+        match queued.proposal() {
+            Proposal::Add(add) => {
+                let invitee = add.key_package().leaf_node().credential().serialized_content();
+                if !authorized && invitee != committer.as_slice() {
+                    return Err(denied("Adding members", "an admin"));
+                }
+            }
+            Proposal::Remove(remove) => {
+                let removed = remove.removed();
+                let leaving = matches!(queued.sender(), Sender::Member(leaf) if *leaf == removed);
+                let own_leaf = identity_at(group, removed).is_some_and(|id| id == committer);
+                if !authorized && !leaving && !own_leaf {
+                    return Err(denied("Removing members", "an admin"));
+                }
+            }
+            Proposal::GroupContextExtensions(proposal) => {
+                let (current, proposed) = (group.extensions(), proposal.extensions());
+                if !authorized && changed(ChannelMetadata::from_extensions, current, proposed) {
+                    return Err(denied("Changing the channel metadata", "an admin"));
+                }
+                if !roles.is_owner(&committer)
+                    && changed(ChannelRoles::from_extensions, current, proposed)
+                {
+                    return Err(denied("Changing roles", "the owner"));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether an extension decoded by `decode` differs between two sets
+///
+/// A proposed value that doesn't decode counts as a change.
+fn changed<T: PartialEq>(
+    decode: fn(&Extensions) -> MlsResult<Option<T>>,
+    current: &Extensions,
+    proposed: &Extensions,
+) -> bool {
+    let current = decode(current).ok().flatten();
+    !matches!(decode(proposed), Ok(proposed) if proposed == current)
+}
+
+fn denied(change: &str, role: &str) -> MlsError {
+    MlsError::PermissionDenied(format!("{} is reserved for {} of the channel", change, role))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_promote_and_demote() {
+        let mut roles = ChannelRoles::new(b"alice".to_vec());
+        assert!(roles.is_owner(b"alice") && roles.is_admin(b"alice"));
+        assert!(!roles.is_admin(b"bob"));
+
+        assert!(roles.promote(b"bob"));
+        assert!(!roles.promote(b"bob"));
+        assert!(!roles.promote(b"alice"));
+        assert!(roles.is_admin(b"bob") && !roles.is_owner(b"bob"));
+
+        assert!(roles.demote(b"bob"));
+        assert!(!roles.demote(b"bob"));
+        assert!(!roles.demote(b"alice"));
+        assert!(roles.is_owner(b"alice"));
+    }
+
+    #[test]
+    fn test_extension_roundtrip() {
+        let mut roles = ChannelRoles::new(b"alice".to_vec());
+        roles.promote(b"bob");
+
+        let extensions = Extensions::single(roles.to_extension().unwrap());
+        assert_eq!(ChannelRoles::from_extensions(&extensions).unwrap(), Some(roles));
+        assert_eq!(ChannelRoles::from_extensions(&Extensions::empty()).unwrap(), None);
+    }
+}
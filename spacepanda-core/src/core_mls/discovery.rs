@@ -15,6 +15,10 @@
 //! - Creator signature
 //!
 //! Members can discover groups without being in them.
+//!
+//! Groups created with OpenMLS have no `MlsTree`; their info is built with
+//! [`GroupPublicInfo::from_group_info`] and carries the signed MLS
+//! GroupInfo instead, so anyone who finds it can join by external commit.
 
 use super::errors::{MlsError, MlsResult};
 use super::tree::MlsTree;
@@ -22,6 +26,7 @@ use super::types::{GroupId, GroupMetadata};
 use super::welcome::TreeSnapshot;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 
 /// Public group information for discovery
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -42,6 +47,10 @@ pub struct GroupPublicInfo {
     pub updated_at: u64,
     /// Signature over the public info (creator signs)
     pub signature: Vec<u8>,
+    /// Serialized MLS GroupInfo with the external_pub extension, for
+    /// joining by external commit (empty if the group takes none)
+    #[serde(default)]
+    pub group_info: Vec<u8>,
 }
 
 impl GroupPublicInfo {
@@ -63,6 +72,7 @@ impl GroupPublicInfo {
             created_at: metadata.created_at,
             updated_at: metadata.updated_at,
             signature: vec![],
+            group_info: vec![],
         };
 
         // Sign the canonical bytes
@@ -72,6 +82,46 @@ impl GroupPublicInfo {
         info
     }
 
+    /// Create public info for an OpenMLS group from its exported GroupInfo
+    ///
+    /// The tree snapshot only records the leaf count: the full public tree
+    /// travels inside `group_info`.
+    pub fn from_group_info(
+        group_id: GroupId,
+        metadata: &GroupMetadata,
+        group_info: Vec<u8>,
+        sign_fn: impl FnOnce(&[u8]) -> Vec<u8>,
+    ) -> Self {
+        let tree_snapshot = TreeSnapshot {
+            leaf_count: metadata.members.len() as u32,
+            public_nodes: HashMap::new(),
+            node_hashes: HashMap::new(),
+            ratchet_tree: Vec::new(),
+        };
+
+        let mut info = Self {
+            group_id,
+            name: metadata.name.clone(),
+            epoch: metadata.epoch,
+            member_count: metadata.members.len(),
+            tree_snapshot,
+            created_at: metadata.created_at,
+            updated_at: metadata.updated_at,
+            signature: vec![],
+            group_info,
+        };
+
+        let bytes = info.to_bytes_for_signature();
+        info.signature = sign_fn(&bytes);
+
+        info
+    }
+
+    /// Whether newcomers can join this group by external commit
+    pub fn accepts_external_commits(&self) -> bool {
+        !self.group_info.is_empty()
+    }
+
     /// Verify signature
     pub fn verify(&self, verify_fn: impl FnOnce(&[u8], &[u8]) -> bool) -> MlsResult<()> {
         let mut info_copy = self.clone();
@@ -107,6 +157,11 @@ impl GroupPublicInfo {
             bytes.extend_from_slice(&hasher.finalize());
         }
 
+        // Empty for tree-based info, so their signatures are unchanged
+        if !self.group_info.is_empty() {
+            bytes.extend_from_slice(&Sha256::digest(&self.group_info));
+        }
+
         bytes
     }
 
@@ -130,6 +185,7 @@ impl GroupPublicInfo {
             self.tree_snapshot = other.tree_snapshot.clone();
             self.updated_at = other.updated_at;
             self.signature = other.signature.clone();
+            self.group_info = other.group_info.clone();
         }
 
         Ok(())
@@ -358,6 +414,29 @@ mod tests {
         assert_eq!(info1.updated_at, info2.updated_at);
     }
 
+    #[test]
+    fn test_group_info_is_signed() {
+        let group = test_group();
+
+        let info = GroupPublicInfo::from_group_info(
+            group.group_id.clone(),
+            &group.metadata,
+            vec![7; 32],
+            sign_fn,
+        );
+        assert!(info.accepts_external_commits());
+        assert_eq!(info.tree_snapshot.leaf_count, 1);
+        assert!(info.verify(verify_fn).is_ok());
+
+        let mut tampered = info.clone();
+        tampered.group_info[0] = 8;
+        assert!(tampered.verify(verify_fn).is_err());
+
+        let legacy =
+            GroupPublicInfo::from_metadata(group.group_id, &group.metadata, &group.tree, sign_fn);
+        assert!(!legacy.accepts_external_commits());
+    }
+
     #[test]
     fn test_merge_different_groups_fails() {
         let group1 = test_group();
@@ -4,6 +4,8 @@
 //! using OpenMLS primitives.
 
 use crate::core_mls::{
+    channel_metadata::ChannelMetadata,
+    commit_policy::ChannelRoles,
     errors::{MlsError, MlsResult},
     events::MlsEvent,
 };
@@ -35,6 +37,23 @@ pub trait GroupOperations {
 
     /// Commit pending proposals
     async fn commit_pending(&self) -> MlsResult<CommitResult>;
+
+    /// Refresh our own leaf key material with an update commit
+    ///
+    /// Moves the group to a new epoch whose secrets no longer depend on
+    /// our old leaf keys (post-compromise security).
+    async fn self_update(&self) -> MlsResult<CommitResult>;
+
+    /// Export a signed GroupInfo that newcomers can join by external commit
+    async fn export_group_info(&self) -> MlsResult<Vec<u8>>;
+
+    /// Register an external pre-shared key (ID + secret) with this member
+    async fn inject_psk(&self, psk_id: &[u8], secret: &[u8]) -> MlsResult<()>;
+
+    /// Propose mixing an injected PSK into the key schedule
+    ///
+    /// Returns the serialized proposal; the next commit includes it.
+    async fn propose_psk(&self, psk_id: &[u8]) -> MlsResult<Vec<u8>>;
 }
 
 /// Result of processing a message
@@ -71,87 +90,7 @@ impl<P: openmls_traits::OpenMlsProvider + 'static> GroupOperations for OpenMlsEn
         &self,
         key_packages: Vec<Vec<u8>>,
     ) -> MlsResult<(Vec<u8>, Option<Vec<u8>>)> {
-        let mut group = self.group.write().await;
-
-        // Parse key packages using TlsDeserialize trait
-        let parsed_packages: Vec<KeyPackage> = key_packages
-            .iter()
-            .map(|bytes| {
-                // First deserialize to KeyPackageIn, then convert to KeyPackage
-                let kp_in = KeyPackageIn::tls_deserialize(&mut bytes.as_slice()).map_err(|e| {
-                    MlsError::InvalidMessage(format!("Invalid key package: {:?}", e))
-                })?;
-                kp_in
-                    .validate(self.provider().crypto(), ProtocolVersion::default())
-                    .map_err(|e| {
-                        MlsError::InvalidMessage(format!("Key package validation failed: {:?}", e))
-                    })
-            })
-            .collect::<Result<Vec<_>, _>>()?;
-
-        // Add members (creates proposals and commits them)
-        let (commit_msg, welcome_msg, _group_info) = group
-            .add_members(self.provider(), self.signature_keys(), &parsed_packages)
-            .map_err(|e| MlsError::InvalidMessage(format!("Failed to add members: {:?}", e)))?;
-
-        // Get group info for events
-        let group_id = group.group_id().as_slice().to_vec();
-        let new_epoch = group.epoch().as_u64();
-
-        // Merge pending commit (CRITICAL!)
-        group
-            .merge_pending_commit(self.provider())
-            .map_err(|e| MlsError::InvalidMessage(format!("Failed to merge commit: {:?}", e)))?;
-
-        // Record join times for new members
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-
-        // Find leaf indices of newly added members
-        let mut new_member_indices = Vec::new();
-        for member in group.members() {
-            let member_identity = member.credential.serialized_content();
-            for kp in &parsed_packages {
-                let kp_identity = kp.leaf_node().credential().serialized_content();
-                if member_identity == kp_identity {
-                    new_member_indices.push(member.index.u32());
-                    break;
-                }
-            }
-        }
-
-        // Drop the write lock before accessing member_join_times
-        drop(group);
-
-        // Record join times
-        for leaf_index in &new_member_indices {
-            self.record_join_time(*leaf_index, now).await;
-        }
-
-        // Emit MemberAdded events for each new member
-        for key_package in &parsed_packages {
-            // Extract member identity from credential
-            let member_id = key_package.leaf_node().credential().serialized_content().to_vec();
-            self.events().emit(MlsEvent::MemberAdded {
-                group_id: group_id.clone(),
-                member_id,
-                epoch: new_epoch,
-            });
-        }
-
-        // Serialize commit for transport
-        let commit_bytes = commit_msg.tls_serialize_detached().map_err(|e| {
-            MlsError::SerializationError(format!("Failed to serialize commit: {:?}", e))
-        })?;
-
-        // Serialize Welcome message - OpenMLS always returns one when adding members
-        let welcome_bytes = welcome_msg.tls_serialize_detached().map_err(|e| {
-            MlsError::SerializationError(format!("Failed to serialize Welcome: {:?}", e))
-        })?;
-
-        Ok((commit_bytes, Some(welcome_bytes)))
+        self.add_members_with_psk(key_packages, None).await
     }
 
     /// Remove members from the group
@@ -180,6 +119,7 @@ impl<P: openmls_traits::OpenMlsProvider + 'static> GroupOperations for OpenMlsEn
         group
             .merge_pending_commit(self.provider())
             .map_err(|e| MlsError::InvalidMessage(format!("Failed to merge commit: {:?}", e)))?;
+        self.after_merge(&group);
 
         // Drop the write lock before emitting events
         drop(group);
@@ -253,14 +193,24 @@ impl<P: openmls_traits::OpenMlsProvider + 'static> GroupOperations for OpenMlsEn
             .process_message(self.provider(), protocol_message)
             .map_err(|e| MlsError::InvalidMessage(format!("Failed to process message: {:?}", e)))?;
 
+        // External commits are only taken through the engine's opt-in path
+        if matches!(processed.sender(), Sender::NewMemberCommit) {
+            return Err(MlsError::PermissionDenied(
+                "External commits are not accepted in this group".to_string(),
+            ));
+        }
+
         // Handle based on content type
         let result = match processed.into_content() {
             ProcessedMessageContent::ApplicationMessage(app_msg) => {
                 let plaintext = app_msg.into_bytes();
                 ProcessedMessage::Application(plaintext)
             }
-            ProcessedMessageContent::ProposalMessage(_proposal) => {
-                // Proposal stored automatically in group's proposal store
+            ProcessedMessageContent::ProposalMessage(proposal) => {
+                // Queue the proposal so a later commit can include it
+                group.store_pending_proposal(self.provider().storage(), *proposal).map_err(
+                    |e| MlsError::InvalidMessage(format!("Failed to store proposal: {:?}", e)),
+                )?;
                 ProcessedMessage::Proposal
             }
             ProcessedMessageContent::StagedCommitMessage(staged_commit) => {
@@ -268,6 +218,7 @@ impl<P: openmls_traits::OpenMlsProvider + 'static> GroupOperations for OpenMlsEn
                 group.merge_staged_commit(self.provider(), *staged_commit).map_err(|e| {
                     MlsError::InvalidMessage(format!("Failed to merge staged commit: {:?}", e))
                 })?;
+                self.after_merge(&group);
 
                 let new_epoch = group.epoch().as_u64();
                 ProcessedMessage::Commit { new_epoch }
@@ -299,6 +250,7 @@ impl<P: openmls_traits::OpenMlsProvider + 'static> GroupOperations for OpenMlsEn
         group
             .merge_pending_commit(self.provider())
             .map_err(|e| MlsError::InvalidMessage(format!("Failed to merge commit: {:?}", e)))?;
+        self.after_merge(&group);
 
         let new_epoch = group.epoch().as_u64();
 
@@ -317,6 +269,260 @@ impl<P: openmls_traits::OpenMlsProvider + 'static> GroupOperations for OpenMlsEn
 
         Ok(CommitResult { commit_message, welcome_message, new_epoch })
     }
+
+    /// Refresh our own leaf key material with an update commit
+    ///
+    /// MLS doesn't let a member commit its own Update proposal, so the
+    /// update travels as the commit's path: a fresh leaf (encryption key
+    /// and signature over it) and new path secrets up to the root.
+    ///
+    /// # Returns
+    /// Commit result with the commit to broadcast (never a Welcome)
+    async fn self_update(&self) -> MlsResult<CommitResult> {
+        let mut group = self.group.write().await;
+
+        let bundle = group
+            .self_update(self.provider(), self.signature_keys(), LeafNodeParameters::default())
+            .map_err(|e| MlsError::InvalidMessage(format!("Failed to update own leaf: {:?}", e)))?;
+
+        group
+            .merge_pending_commit(self.provider())
+            .map_err(|e| MlsError::InvalidMessage(format!("Failed to merge commit: {:?}", e)))?;
+        self.after_merge(&group);
+
+        let new_epoch = group.epoch().as_u64();
+        let commit_message = bundle
+            .commit()
+            .tls_serialize_detached()
+            .map_err(|e| MlsError::Serialization(format!("Failed to serialize commit: {}", e)))?;
+
+        Ok(CommitResult { commit_message, welcome_message: None, new_epoch })
+    }
+
+    /// Export a signed GroupInfo that newcomers can join by external commit
+    ///
+    /// # Returns
+    /// Serialized GroupInfo with the ratchet tree and external_pub extension
+    async fn export_group_info(&self) -> MlsResult<Vec<u8>> {
+        OpenMlsEngine::export_group_info(self).await
+    }
+
+    /// Register an external pre-shared key with this member
+    ///
+    /// # Arguments
+    /// * `psk_id` - Application-chosen PSK ID
+    /// * `secret` - The shared secret
+    async fn inject_psk(&self, psk_id: &[u8], secret: &[u8]) -> MlsResult<()> {
+        OpenMlsEngine::store_external_psk(self.provider(), psk_id, secret)
+    }
+
+    /// Propose mixing an injected PSK into the key schedule
+    ///
+    /// # Returns
+    /// Serialized proposal message for broadcast
+    async fn propose_psk(&self, psk_id: &[u8]) -> MlsResult<Vec<u8>> {
+        OpenMlsEngine::propose_external_psk(self, psk_id).await
+    }
+}
+
+impl<P: openmls_traits::OpenMlsProvider + 'static> OpenMlsEngine<P> {
+    /// Add members to the group, optionally requiring an external PSK
+    ///
+    /// With `psk_id`, a PreSharedKey proposal goes into the same commit as
+    /// the adds. The PSK is mixed into the new epoch's key schedule and
+    /// listed in the Welcome, so joiners need it (stored with
+    /// [`store_external_psk`](Self::store_external_psk)) on top of the
+    /// Welcome, as do the members processing the commit.
+    ///
+    /// # Arguments
+    /// * `key_packages` - Serialized KeyPackages for new members
+    /// * `psk_id` - ID of a stored external PSK to require
+    ///
+    /// # Returns
+    /// Tuple of (serialized commit message, optional serialized Welcome message)
+    pub async fn add_members_with_psk(
+        &self,
+        key_packages: Vec<Vec<u8>>,
+        psk_id: Option<&[u8]>,
+    ) -> MlsResult<(Vec<u8>, Option<Vec<u8>>)> {
+        let mut group = self.group.write().await;
+
+        // Parse key packages using TlsDeserialize trait
+        let parsed_packages: Vec<KeyPackage> = key_packages
+            .iter()
+            .map(|bytes| {
+                // First deserialize to KeyPackageIn, then convert to KeyPackage
+                let kp_in = KeyPackageIn::tls_deserialize(&mut bytes.as_slice()).map_err(|e| {
+                    MlsError::InvalidMessage(format!("Invalid key package: {:?}", e))
+                })?;
+                kp_in
+                    .validate(self.provider().crypto(), ProtocolVersion::default())
+                    .map_err(|e| {
+                        MlsError::InvalidMessage(format!("Key package validation failed: {:?}", e))
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // The PSK proposal goes into the commit by value, so members who
+        // never saw it as a standalone proposal can still process the commit
+        let psk_proposals = psk_id
+            .map(|psk_id| Self::external_psk_id(self.provider(), psk_id))
+            .transpose()?
+            .map(|psk_id| Proposal::PreSharedKey(Box::new(PreSharedKeyProposal::new(psk_id))));
+
+        // Add members (creates proposals and commits them)
+        let bundle = group
+            .commit_builder()
+            .propose_adds(parsed_packages.iter().cloned())
+            .add_proposals(psk_proposals)
+            .force_self_update(true)
+            .load_psks(self.provider().storage())
+            .map_err(|e| MlsError::InvalidMessage(format!("Failed to load PSKs: {:?}", e)))?
+            .build(self.provider().rand(), self.provider().crypto(), self.signature_keys(), |_| {
+                true
+            })
+            .map_err(|e| MlsError::InvalidMessage(format!("Failed to add members: {:?}", e)))?
+            .stage_commit(self.provider())
+            .map_err(|e| MlsError::InvalidMessage(format!("Failed to add members: {:?}", e)))?;
+        let welcome_msg = bundle.to_welcome_msg().ok_or_else(|| {
+            MlsError::Internal("Commit with adds produced no Welcome".to_string())
+        })?;
+        let commit_msg = bundle.into_commit();
+
+        // Get group info for events
+        let group_id = group.group_id().as_slice().to_vec();
+        let new_epoch = group.epoch().as_u64();
+
+        // Merge pending commit (CRITICAL!)
+        group
+            .merge_pending_commit(self.provider())
+            .map_err(|e| MlsError::InvalidMessage(format!("Failed to merge commit: {:?}", e)))?;
+        self.after_merge(&group);
+
+        // Record join times for new members
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        // Find leaf indices of newly added members
+        let mut new_member_indices = Vec::new();
+        for member in group.members() {
+            let member_identity = member.credential.serialized_content();
+            for kp in &parsed_packages {
+                let kp_identity = kp.leaf_node().credential().serialized_content();
+                if member_identity == kp_identity {
+                    new_member_indices.push(member.index.u32());
+                    break;
+                }
+            }
+        }
+
+        // Drop the write lock before accessing member_join_times
+        drop(group);
+
+        // Record join times
+        for leaf_index in &new_member_indices {
+            self.record_join_time(*leaf_index, now).await;
+        }
+
+        // Emit MemberAdded events for each new member
+        for key_package in &parsed_packages {
+            // Extract member identity from credential
+            let member_id = key_package.leaf_node().credential().serialized_content().to_vec();
+            self.events().emit(MlsEvent::MemberAdded {
+                group_id: group_id.clone(),
+                member_id,
+                epoch: new_epoch,
+            });
+        }
+
+        // Serialize commit for transport
+        let commit_bytes = commit_msg.tls_serialize_detached().map_err(|e| {
+            MlsError::SerializationError(format!("Failed to serialize commit: {:?}", e))
+        })?;
+
+        // Serialize Welcome message - OpenMLS always returns one when adding members
+        let welcome_bytes = welcome_msg.tls_serialize_detached().map_err(|e| {
+            MlsError::SerializationError(format!("Failed to serialize Welcome: {:?}", e))
+        })?;
+
+        Ok((commit_bytes, Some(welcome_bytes)))
+    }
+
+    /// Replace the channel metadata in the group context
+    ///
+    /// Members only merge the commit from an owner or admin (see
+    /// [`commit_policy`](crate::core_mls::commit_policy)), so a commit from
+    /// anyone else forks them off the group.
+    ///
+    /// # Returns
+    /// Serialized commit message for broadcast
+    pub async fn update_channel_metadata(&self, metadata: &ChannelMetadata) -> MlsResult<Vec<u8>> {
+        metadata.validate()?;
+        self.commit_context_extension(metadata.to_extension()?).await
+    }
+
+    /// Replace the owner and admins in the group context
+    ///
+    /// Members only merge the commit from the current owner.
+    ///
+    /// # Returns
+    /// Serialized commit message for broadcast
+    pub async fn update_channel_roles(&self, roles: &ChannelRoles) -> MlsResult<Vec<u8>> {
+        self.commit_context_extension(roles.to_extension()?).await
+    }
+
+    /// Commit a GroupContextExtensions proposal that swaps in `extension`
+    ///
+    /// Every other extension is kept; one of the same type is replaced.
+    /// OpenMLS only accepts a non-default extension in the group context if
+    /// it is listed in the required capabilities, so its type is added there
+    /// too. Every member advertises the channel extensions in its leaf
+    /// capabilities, so requiring them excludes no one.
+    async fn commit_context_extension(&self, extension: Extension) -> MlsResult<Vec<u8>> {
+        let mut group = self.group.write().await;
+
+        let extension_type = extension.extension_type();
+        let required = group.extensions().required_capabilities().cloned().unwrap_or_default();
+        let mut required_types = required.extension_types().to_vec();
+        if !required_types.contains(&extension_type) {
+            required_types.push(extension_type);
+        }
+
+        let mut extensions: Vec<Extension> = group
+            .extensions()
+            .iter()
+            .filter(|existing| {
+                existing.extension_type() != extension_type
+                    && existing.extension_type() != ExtensionType::RequiredCapabilities
+            })
+            .cloned()
+            .collect();
+        extensions.push(Extension::RequiredCapabilities(RequiredCapabilitiesExtension::new(
+            &required_types,
+            required.proposal_types(),
+            required.credential_types(),
+        )));
+        extensions.push(extension);
+        let extensions = Extensions::from_vec(extensions)
+            .map_err(|e| MlsError::InvalidInput(format!("Invalid extensions: {:?}", e)))?;
+
+        let (commit, _, _) = group
+            .update_group_context_extensions(self.provider(), extensions, self.signature_keys())
+            .map_err(|e| {
+                MlsError::InvalidMessage(format!("Failed to update group context: {:?}", e))
+            })?;
+
+        group
+            .merge_pending_commit(self.provider())
+            .map_err(|e| MlsError::InvalidMessage(format!("Failed to merge commit: {:?}", e)))?;
+        self.after_merge(&group);
+
+        commit
+            .tls_serialize_detached()
+            .map_err(|e| MlsError::Serialization(format!("Failed to serialize commit: {}", e)))
+    }
 }
 
 #[cfg(test)]
@@ -4,16 +4,24 @@
 //! OpenMLS for all cryptographic operations and state management.
 
 use crate::core_mls::{
+    channel_metadata::{self, ChannelMetadata},
+    commit_policy::{self, ChannelRoles},
     errors::{MlsError, MlsResult},
     events::{EventBroadcaster, MlsEvent},
+    sealed_sender::{self, SenderKeyRing, SENDER_KEY_LABEL},
     state::GroupSnapshot,
     types::{GroupId, GroupMetadata, MemberInfo, MlsConfig},
 };
 
 use openmls::prelude::*;
+use openmls::schedule::{ExternalPsk, PreSharedKeyId, Psk};
 use openmls_basic_credential::SignatureKeyPair;
+use openmls_traits::storage::{
+    traits as storage_traits, Entity, StorageProvider as _, CURRENT_VERSION,
+};
 use openmls_traits::OpenMlsProvider;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tls_codec::{Deserialize as TlsDeserialize, Serialize as TlsSerialize};
 use tokio::sync::RwLock;
@@ -45,6 +53,12 @@ pub struct OpenMlsEngine<P: OpenMlsProvider> {
 
     /// Track when each member joined (leaf_index -> unix timestamp)
     member_join_times: Arc<RwLock<HashMap<u32, u64>>>,
+
+    /// Epochs below this one have had their key material purged
+    purged_before: AtomicU64,
+
+    /// Sealed-sender keys of the current and previous epoch
+    sender_keys: std::sync::Mutex<SenderKeyRing>,
 }
 
 impl<P: OpenMlsProvider + 'static> OpenMlsEngine<P> {
@@ -92,6 +106,8 @@ impl<P: OpenMlsProvider + 'static> OpenMlsEngine<P> {
         let mls_group_config = MlsGroupCreateConfig::builder()
             .wire_format_policy(PURE_CIPHERTEXT_WIRE_FORMAT_POLICY)
             .ciphersuite(ciphersuite)
+            .max_past_epochs(config.past_epoch_retention)
+            .capabilities(channel_metadata::capabilities())
             .build();
 
         // Convert our GroupId to OpenMLS GroupId
@@ -117,7 +133,9 @@ impl<P: OpenMlsProvider + 'static> OpenMlsEngine<P> {
             .unwrap()
             .as_secs();
         join_times.insert(0u32, now); // Creator is always at leaf index 0
+        let epoch = group.epoch().as_u64();
 
+        let sender_keys = Self::sender_key_ring(&*provider, &group);
         let engine = Self {
             group: Arc::new(RwLock::new(group)),
             provider,
@@ -126,6 +144,8 @@ impl<P: OpenMlsProvider + 'static> OpenMlsEngine<P> {
             credential: credential_bundle.clone(),
             event_broadcaster: event_broadcaster.clone(),
             member_join_times: Arc::new(RwLock::new(join_times)),
+            purged_before: AtomicU64::new(epoch),
+            sender_keys: std::sync::Mutex::new(sender_keys),
         };
 
         // Emit GroupCreated event
@@ -148,6 +168,8 @@ impl<P: OpenMlsProvider + 'static> OpenMlsEngine<P> {
         signature_keys: SignatureKeyPair,
         credential: CredentialWithKey,
     ) -> Self {
+        let epoch = group.epoch().as_u64();
+        let sender_keys = Self::sender_key_ring(&*provider, &group);
         Self {
             group: Arc::new(RwLock::new(group)),
             provider,
@@ -156,6 +178,8 @@ impl<P: OpenMlsProvider + 'static> OpenMlsEngine<P> {
             credential,
             event_broadcaster: EventBroadcaster::default(),
             member_join_times: Arc::new(RwLock::new(HashMap::new())),
+            purged_before: AtomicU64::new(epoch),
+            sender_keys: std::sync::Mutex::new(sender_keys),
         }
     }
 
@@ -251,6 +275,7 @@ impl<P: OpenMlsProvider + 'static> OpenMlsEngine<P> {
         // Create join config
         let join_config = MlsGroupJoinConfig::builder()
             .wire_format_policy(PURE_CIPHERTEXT_WIRE_FORMAT_POLICY)
+            .max_past_epochs(config.past_epoch_retention)
             .build();
 
         // Stage the welcome (validates and prepares group state)
@@ -276,6 +301,7 @@ impl<P: OpenMlsProvider + 'static> OpenMlsEngine<P> {
         // Initialize member join times - we'll populate from group state
         let join_times = HashMap::new();
 
+        let sender_keys = Self::sender_key_ring(&*provider, &group);
         let engine = Self {
             group: Arc::new(RwLock::new(group)),
             provider,
@@ -284,6 +310,8 @@ impl<P: OpenMlsProvider + 'static> OpenMlsEngine<P> {
             credential: credential_bundle,
             event_broadcaster: event_broadcaster.clone(),
             member_join_times: Arc::new(RwLock::new(join_times)),
+            purged_before: AtomicU64::new(epoch),
+            sender_keys: std::sync::Mutex::new(sender_keys),
         };
 
         // Emit GroupJoined event
@@ -296,21 +324,288 @@ impl<P: OpenMlsProvider + 'static> OpenMlsEngine<P> {
         Ok(engine)
     }
 
+    /// Join a group by external commit (RFC 9420 §12.4.3.2)
+    ///
+    /// Builds and merges a commit adding ourselves from the group's
+    /// published GroupInfo, so no member has to invite us. The GroupInfo
+    /// must carry the ratchet tree and the external_pub extension (see
+    /// [`export_group_info`](Self::export_group_info)).
+    ///
+    /// # Arguments
+    /// * `group_info_bytes` - Serialized GroupInfo message
+    /// * `identity` - Member identity (username/user ID)
+    /// * `config` - Group configuration
+    /// * `provider` - Shared crypto provider (for key continuity)
+    ///
+    /// # Returns
+    /// The engine and the serialized commit to broadcast to existing members
+    pub async fn join_by_external_commit(
+        group_info_bytes: &[u8],
+        identity: Vec<u8>,
+        config: MlsConfig,
+        provider: Arc<P>,
+    ) -> MlsResult<(Self, Vec<u8>)> {
+        let mls_message = MlsMessageIn::tls_deserialize_exact(group_info_bytes).map_err(|e| {
+            MlsError::InvalidMessage(format!("Failed to parse group info: {:?}", e))
+        })?;
+        let group_info = match mls_message.extract() {
+            MlsMessageBodyIn::GroupInfo(group_info) => group_info,
+            _ => return Err(MlsError::InvalidMessage("Expected GroupInfo message".to_string())),
+        };
+
+        let ciphersuite = Ciphersuite::MLS_128_DHKEMX25519_AES128GCM_SHA256_Ed25519;
+
+        // Fresh signature keys for our new leaf
+        let signature_keys =
+            SignatureKeyPair::new(ciphersuite.signature_algorithm()).map_err(|e| {
+                MlsError::CryptoError(format!("Failed to generate signature keys: {:?}", e))
+            })?;
+        signature_keys.store(provider.storage()).map_err(|e| {
+            MlsError::CryptoError(format!("Failed to store signature keys: {:?}", e))
+        })?;
+
+        let credential_bundle = CredentialWithKey {
+            credential: BasicCredential::new(identity).into(),
+            signature_key: signature_keys.public().into(),
+        };
+
+        let join_config = MlsGroupJoinConfig::builder()
+            .wire_format_policy(PURE_CIPHERTEXT_WIRE_FORMAT_POLICY)
+            .max_past_epochs(config.past_epoch_retention)
+            .build();
+
+        // External commits are always sent as public messages; OpenMLS
+        // verifies the GroupInfo signature against the tree it carries
+        let (mut group, bundle) = MlsGroup::external_commit_builder()
+            .with_config(join_config)
+            .build_group(&*provider, group_info, credential_bundle.clone())
+            .map_err(|e| MlsError::InvalidMessage(format!("Failed to join group: {:?}", e)))?
+            .leaf_node_parameters(
+                LeafNodeParameters::builder()
+                    .with_capabilities(channel_metadata::capabilities())
+                    .build(),
+            )
+            .load_psks(provider.storage())
+            .map_err(|e| MlsError::InvalidMessage(format!("Failed to load PSKs: {:?}", e)))?
+            .build(provider.rand(), provider.crypto(), &signature_keys, |_| true)
+            .map_err(|e| {
+                MlsError::InvalidMessage(format!("Failed to build external commit: {:?}", e))
+            })?
+            .finalize(&*provider)
+            .map_err(|e| {
+                MlsError::InvalidMessage(format!("Failed to finalize external commit: {:?}", e))
+            })?;
+
+        group
+            .merge_pending_commit(&*provider)
+            .map_err(|e| MlsError::Internal(format!("Failed to merge commit: {:?}", e)))?;
+
+        let commit_bytes = bundle
+            .commit()
+            .tls_serialize_detached()
+            .map_err(|e| MlsError::Internal(format!("Failed to serialize commit: {:?}", e)))?;
+
+        let group_id = GroupId::new(group.group_id().as_slice().to_vec());
+        let epoch = group.epoch().as_u64();
+        let member_count = group.members().count();
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let mut join_times = HashMap::new();
+        join_times.insert(group.own_leaf_index().u32(), now);
+
+        let event_broadcaster = EventBroadcaster::default();
+        let sender_keys = Self::sender_key_ring(&*provider, &group);
+        let engine = Self {
+            group: Arc::new(RwLock::new(group)),
+            provider,
+            config,
+            signature_keys,
+            credential: credential_bundle,
+            event_broadcaster: event_broadcaster.clone(),
+            member_join_times: Arc::new(RwLock::new(join_times)),
+            purged_before: AtomicU64::new(epoch),
+            sender_keys: std::sync::Mutex::new(sender_keys),
+        };
+
+        event_broadcaster.emit(MlsEvent::GroupJoined {
+            group_id: group_id.as_bytes().to_vec(),
+            epoch,
+            member_count,
+        });
+
+        Ok((engine, commit_bytes))
+    }
+
     /// Get the group ID
     pub async fn group_id(&self) -> GroupId {
         let group = self.group.read().await;
         GroupId::new(group.group_id().as_slice().to_vec())
     }
 
+    /// Configuration the group was created or joined with
+    pub fn config(&self) -> &MlsConfig {
+        &self.config
+    }
+
     /// Get the current epoch
     pub async fn epoch(&self) -> u64 {
         let group = self.group.read().await;
         group.epoch().as_u64()
     }
 
+    /// Delete key material of epochs outside the retention window
+    ///
+    /// Keeps the current epoch and the `past_epoch_retention` epochs before
+    /// it; OpenMLS trims its past-epoch message secrets to the same window
+    /// when a commit is merged. Encryption keys and resumption secrets of
+    /// older epochs are removed from the provider's storage.
+    ///
+    /// # Returns
+    /// Number of epochs purged
+    pub async fn purge_old_epochs(&self) -> MlsResult<u64> {
+        let group = self.group.read().await;
+        self.purge_epochs(&group)
+    }
+
+    fn purge_epochs(&self, group: &MlsGroup) -> MlsResult<u64> {
+        let retention = self.config.past_epoch_retention as u64;
+        let keep_from = group.epoch().as_u64().saturating_sub(retention);
+        let from = self.purged_before.load(Ordering::Acquire);
+        if from >= keep_from {
+            return Ok(0);
+        }
+
+        let leaf_index = group.own_leaf_index().u32();
+        for epoch in from..keep_from {
+            self.provider
+                .storage()
+                .delete_encryption_epoch_key_pairs(
+                    group.group_id(),
+                    &GroupEpoch::from(epoch),
+                    leaf_index,
+                )
+                .map_err(|e| {
+                    MlsError::Storage(format!("Failed to delete keys of epoch {}: {:?}", epoch, e))
+                })?;
+        }
+
+        self.purge_resumption_psks(group, keep_from)?;
+
+        self.purged_before.store(keep_from, Ordering::Release);
+        Ok(keep_from - from)
+    }
+
+    /// Drop the resumption secrets of epochs before `keep_from`
+    ///
+    /// A group rewrites its whole store on every merge, so this runs after
+    /// each one rather than once per epoch.
+    fn purge_resumption_psks(&self, group: &MlsGroup, keep_from: u64) -> MlsResult<()> {
+        let storage = self.provider.storage();
+        let stored: Option<ResumptionPsks> = storage
+            .resumption_psk_store(group.group_id())
+            .map_err(|e| MlsError::Storage(format!("Failed to read resumption PSKs: {:?}", e)))?;
+        let Some(mut psks) = stored else {
+            return Ok(());
+        };
+
+        let before = psks.resumption_psk.len();
+        psks.resumption_psk.retain(|(epoch, _)| *epoch >= keep_from);
+        if psks.resumption_psk.len() == before {
+            return Ok(());
+        }
+        // Entries are appended again until the store is full
+        psks.cursor = psks.resumption_psk.len();
+        storage
+            .write_resumption_psk_store(group.group_id(), &psks)
+            .map_err(|e| MlsError::Storage(format!("Failed to write resumption PSKs: {:?}", e)))
+    }
+
+    /// Housekeeping after a merge; the merge itself already succeeded
+    ///
+    /// Moves the sealed-sender key to the new epoch and purges epochs
+    /// outside the retention window.
+    pub(crate) fn after_merge(&self, group: &MlsGroup) {
+        match Self::epoch_sender_key(&*self.provider, group) {
+            Ok(key) => self.lock_sender_keys().advance(group.epoch().as_u64(), key),
+            Err(e) => tracing::warn!("Failed to derive sender key: {}", e),
+        }
+        if let Err(e) = self.purge_epochs(group) {
+            tracing::warn!("Failed to purge old epochs: {}", e);
+        }
+    }
+
+    /// Sealed-sender key of the group's current epoch
+    fn epoch_sender_key(provider: &P, group: &MlsGroup) -> MlsResult<[u8; 32]> {
+        let secret = group
+            .export_secret(provider.crypto(), SENDER_KEY_LABEL, b"", 32)
+            .map_err(|e| MlsError::CryptoError(format!("Failed to export secret: {:?}", e)))?;
+        Ok(sealed_sender::derive_sender_key(&secret))
+    }
+
+    /// Key ring starting at the group's current epoch
+    ///
+    /// Left empty if the key can't be derived yet; [`Self::sender_key`]
+    /// retries.
+    fn sender_key_ring(provider: &P, group: &MlsGroup) -> SenderKeyRing {
+        let mut ring = SenderKeyRing::default();
+        match Self::epoch_sender_key(provider, group) {
+            Ok(key) => ring.advance(group.epoch().as_u64(), key),
+            Err(e) => tracing::warn!("Failed to derive sender key: {}", e),
+        }
+        ring
+    }
+
+    fn lock_sender_keys(&self) -> std::sync::MutexGuard<'_, SenderKeyRing> {
+        self.sender_keys.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Epoch and sealed-sender key to seal outgoing envelopes with
+    pub async fn sender_key(&self) -> MlsResult<(u64, [u8; 32])> {
+        let group = self.group.read().await;
+        let epoch = group.epoch().as_u64();
+        let mut keys = self.lock_sender_keys();
+        if let Some((current, key)) = keys.current().filter(|&(current, _)| current == epoch) {
+            return Ok((current, key));
+        }
+
+        let key = Self::epoch_sender_key(&*self.provider, &group)?;
+        keys.advance(epoch, key);
+        Ok((epoch, key))
+    }
+
+    /// Sealed-sender key for envelopes sealed in `epoch`
+    ///
+    /// Only the current epoch and the one before it have keys; the previous
+    /// one is for envelopes that raced a commit.
+    ///
+    /// # Errors
+    ///
+    /// `EpochTooOld` for older epochs, `EpochMismatch` for later ones
+    pub async fn sender_key_for(&self, epoch: u64) -> MlsResult<[u8; 32]> {
+        let (current, key) = self.sender_key().await?;
+        if epoch == current {
+            return Ok(key);
+        }
+        self.lock_sender_keys().get(epoch)
+    }
+
+    /// Unseal the sender of an envelope sealed in `epoch`
+    pub async fn unseal_sender(
+        &self,
+        sealed: &sealed_sender::SealedSender,
+        epoch: u64,
+    ) -> MlsResult<Vec<u8>> {
+        let key = self.sender_key_for(epoch).await?;
+        sealed_sender::unseal_sender(sealed, &key, epoch)
+    }
+
     /// Get group metadata
     pub async fn metadata(&self) -> MlsResult<GroupMetadata> {
         let group = self.group.read().await;
+        let roles = ChannelRoles::of_group(&group)?;
 
         // Extract member information from the tree
         let members: Vec<MemberInfo> = group
@@ -318,8 +613,8 @@ impl<P: OpenMlsProvider + 'static> OpenMlsEngine<P> {
             .map(|member| {
                 // Extract identity from credential - use serialized credential as identity
                 let identity = member.credential.serialized_content().to_vec();
-                // Creator (first member, leaf index 0) is admin, others are regular members
-                let role = if member.index.u32() == 0 {
+                // The owner and admins (see ChannelRoles) are admins, others regular members
+                let role = if roles.is_admin(&identity) {
                     super::super::types::MemberRole::Admin
                 } else {
                     super::super::types::MemberRole::Member
@@ -329,13 +624,17 @@ impl<P: OpenMlsProvider + 'static> OpenMlsEngine<P> {
                     leaf_index: member.index.u32(),
                     joined_at: 0, // OpenMLS doesn't track join time by default
                     role,
+                    signature_key: member.signature_key.clone(),
                 }
             })
             .collect();
 
         Ok(GroupMetadata {
             group_id: GroupId::new(group.group_id().as_slice().to_vec()),
-            name: None, // Can be added via group context extensions
+            name: ChannelMetadata::from_extensions(group.extensions())
+                .ok()
+                .flatten()
+                .map(|metadata| metadata.name),
             epoch: group.epoch().as_u64(),
             members,
             created_at: 0, // Would need custom storage
@@ -343,6 +642,18 @@ impl<P: OpenMlsProvider + 'static> OpenMlsEngine<P> {
         })
     }
 
+    /// Channel metadata in the group context, if any has been set
+    pub async fn channel_metadata(&self) -> MlsResult<Option<ChannelMetadata>> {
+        let group = self.group.read().await;
+        ChannelMetadata::from_extensions(group.extensions())
+    }
+
+    /// Owner and admins of the group
+    pub async fn channel_roles(&self) -> MlsResult<ChannelRoles> {
+        let group = self.group.read().await;
+        ChannelRoles::of_group(&group)
+    }
+
     /// Get reference to provider for use in operations
     pub(crate) fn provider(&self) -> &P {
         &self.provider
@@ -426,6 +737,7 @@ impl<P: OpenMlsProvider + 'static> OpenMlsEngine<P> {
         group
             .merge_pending_commit(self.provider.as_ref())
             .map_err(|e| MlsError::Internal(format!("Failed to merge commit: {:?}", e)))?;
+        self.after_merge(&group);
 
         // Serialize commit message
         let commit_bytes = commit
@@ -445,18 +757,178 @@ impl<P: OpenMlsProvider + 'static> OpenMlsEngine<P> {
         Ok((commit_bytes, welcome_bytes))
     }
 
+    /// Propose removing ourselves from the group
+    ///
+    /// MLS does not allow a member to commit its own removal, so this
+    /// returns a self-remove proposal that a remaining member commits.
+    ///
+    /// # Returns
+    /// Serialized proposal message for broadcast
+    pub async fn leave_group(&self) -> MlsResult<Vec<u8>> {
+        let mut group = self.group.write().await;
+
+        let proposal = group
+            .leave_group(self.provider.as_ref(), &self.signature_keys)
+            .map_err(|e| MlsError::InvalidMessage(format!("Failed to leave group: {:?}", e)))?;
+
+        proposal
+            .tls_serialize_detached()
+            .map_err(|e| MlsError::Internal(format!("Failed to serialize proposal: {:?}", e)))
+    }
+
+    /// Store an external pre-shared key in the provider
+    ///
+    /// OpenMLS looks PSKs up by ID when it stages a commit or Welcome that
+    /// references them, so every member (and every joiner) has to store
+    /// the PSK before handling one.
+    ///
+    /// # Arguments
+    /// * `provider` - Provider whose storage receives the PSK
+    /// * `psk_id` - Application-chosen PSK ID
+    /// * `secret` - The shared secret
+    pub fn store_external_psk(provider: &P, psk_id: &[u8], secret: &[u8]) -> MlsResult<()> {
+        Self::external_psk_id(provider, psk_id)?
+            .store(provider, secret)
+            .map_err(|e| MlsError::Storage(format!("Failed to store PSK: {:?}", e)))
+    }
+
+    /// PSK ID with a fresh nonce for an external PSK
+    pub(crate) fn external_psk_id(provider: &P, psk_id: &[u8]) -> MlsResult<PreSharedKeyId> {
+        let ciphersuite = Ciphersuite::MLS_128_DHKEMX25519_AES128GCM_SHA256_Ed25519;
+        PreSharedKeyId::new(
+            ciphersuite,
+            provider.rand(),
+            Psk::External(ExternalPsk::new(psk_id.to_vec())),
+        )
+        .map_err(|e| MlsError::CryptoError(format!("Failed to create PSK ID: {:?}", e)))
+    }
+
+    /// Propose mixing an external PSK into the next epoch
+    ///
+    /// The proposal is kept in our proposal store, so the next commit we
+    /// create (see [`commit_pending`](Self::commit_pending)) includes it.
+    /// The PSK must already be stored with
+    /// [`store_external_psk`](Self::store_external_psk).
+    ///
+    /// # Returns
+    /// Serialized proposal message for broadcast
+    pub async fn propose_external_psk(&self, psk_id: &[u8]) -> MlsResult<Vec<u8>> {
+        let psk_id = Self::external_psk_id(self.provider.as_ref(), psk_id)?;
+        let mut group = self.group.write().await;
+
+        let (proposal, _) = group
+            .propose_external_psk(self.provider.as_ref(), &self.signature_keys, psk_id)
+            .map_err(|e| MlsError::InvalidMessage(format!("Failed to propose PSK: {:?}", e)))?;
+
+        proposal
+            .tls_serialize_detached()
+            .map_err(|e| MlsError::Internal(format!("Failed to serialize proposal: {:?}", e)))
+    }
+
+    /// Propose adding a member, without committing
+    ///
+    /// The proposal is kept in our proposal store like the ones we receive,
+    /// so whoever commits next can include it.
+    ///
+    /// # Returns
+    /// Serialized proposal message for broadcast
+    pub async fn propose_add(&self, key_package: &[u8]) -> MlsResult<Vec<u8>> {
+        let key_package = KeyPackageIn::tls_deserialize(&mut &key_package[..])
+            .map_err(|e| MlsError::InvalidMessage(format!("Invalid key package: {:?}", e)))?
+            .validate(self.provider.crypto(), ProtocolVersion::default())
+            .map_err(|e| {
+                MlsError::InvalidMessage(format!("Key package validation failed: {:?}", e))
+            })?;
+        let mut group = self.group.write().await;
+
+        let (proposal, _) = group
+            .propose_add_member(self.provider.as_ref(), &self.signature_keys, &key_package)
+            .map_err(|e| MlsError::InvalidMessage(format!("Failed to propose add: {:?}", e)))?;
+
+        proposal
+            .tls_serialize_detached()
+            .map_err(|e| MlsError::Internal(format!("Failed to serialize proposal: {:?}", e)))
+    }
+
+    /// Propose removing the member at a leaf, without committing
+    ///
+    /// # Returns
+    /// Serialized proposal message for broadcast
+    pub async fn propose_remove(&self, leaf_index: u32) -> MlsResult<Vec<u8>> {
+        let mut group = self.group.write().await;
+
+        let (proposal, _) = group
+            .propose_remove_member(
+                self.provider.as_ref(),
+                &self.signature_keys,
+                LeafNodeIndex::new(leaf_index),
+            )
+            .map_err(|e| MlsError::InvalidMessage(format!("Failed to propose removal: {:?}", e)))?;
+
+        proposal
+            .tls_serialize_detached()
+            .map_err(|e| MlsError::Internal(format!("Failed to serialize proposal: {:?}", e)))
+    }
+
+    /// Reload the group from the provider storage
+    ///
+    /// For when the storage was rolled back underneath the engine. The
+    /// sealed-sender keys restart at the reloaded epoch.
+    pub async fn reload(&self) -> MlsResult<()> {
+        let mut group = self.group.write().await;
+        let group_id = group.group_id().clone();
+
+        let restored = MlsGroup::load(self.provider.storage(), &group_id)
+            .map_err(|e| MlsError::Storage(format!("Failed to load group: {:?}", e)))?
+            .ok_or_else(|| MlsError::GroupNotFound(hex::encode(group_id.as_slice())))?;
+        *self.lock_sender_keys() = Self::sender_key_ring(&*self.provider, &restored);
+        *group = restored;
+        Ok(())
+    }
+
+    /// Delete this group's state from the provider storage
+    pub async fn delete_state(&self) -> MlsResult<()> {
+        let mut group = self.group.write().await;
+        group
+            .delete(self.provider.storage())
+            .map_err(|e| MlsError::Storage(format!("Failed to delete group state: {:?}", e)))
+    }
+
     /// Process an incoming MLS message
     ///
-    /// Handles application messages, proposals, and commits from other members
+    /// Handles application messages, proposals, and commits from other members.
+    /// External commits are rejected; see
+    /// [`process_message_with_external_commits`](Self::process_message_with_external_commits).
     ///
     /// # Returns
     /// ProcessedMessage enum indicating what was processed
     pub async fn process_message(&self, message_bytes: &[u8]) -> MlsResult<ProcessedMessage> {
+        self.process_message_inner(message_bytes, false).await
+    }
+
+    /// Process an incoming MLS message, accepting external commits
+    ///
+    /// For groups that publish their GroupInfo: a newcomer's external
+    /// commit is validated by OpenMLS like any other commit and merged.
+    pub async fn process_message_with_external_commits(
+        &self,
+        message_bytes: &[u8],
+    ) -> MlsResult<ProcessedMessage> {
+        self.process_message_inner(message_bytes, true).await
+    }
+
+    async fn process_message_inner(
+        &self,
+        message_bytes: &[u8],
+        allow_external_commits: bool,
+    ) -> MlsResult<ProcessedMessage> {
         let mut group = self.group.write().await;
 
         // Parse the wire message
-        let mls_message_in = MlsMessageIn::tls_deserialize_exact(message_bytes)
-            .map_err(|e| MlsError::InvalidMessage(format!("Failed to parse message: {:?}", e)))?;
+        let mls_message_in = reject_on_panic(|| {
+            MlsMessageIn::tls_deserialize_exact(message_bytes)
+                .map_err(|e| MlsError::InvalidMessage(format!("Failed to parse message: {:?}", e)))
+        })?;
 
         // Extract the protocol message from the MLS message
         let protocol_message: ProtocolMessage = match mls_message_in.extract() {
@@ -466,26 +938,81 @@ impl<P: OpenMlsProvider + 'static> OpenMlsEngine<P> {
         };
 
         // Process the message through OpenMLS - this handles decryption and validation
-        let processed = group
-            .process_message(self.provider.as_ref(), protocol_message)
-            .map_err(|e| MlsError::InvalidMessage(format!("Failed to process message: {:?}", e)))?;
+        let group_id = group.group_id().clone();
+        let epoch = protocol_message.epoch().as_u64();
+        let outcome = reject_on_panic(|| {
+            group.process_message(self.provider.as_ref(), protocol_message).map_err(|e| match e {
+                ProcessMessageError::ValidationError(ValidationError::CannotDecryptOwnMessage) => {
+                    MlsError::OwnLeafMessage { epoch }
+                }
+                e => MlsError::InvalidMessage(format!("Failed to process message: {:?}", e)),
+            })
+        });
+
+        let processed = match outcome {
+            Ok(processed) => processed,
+            Err(e) => {
+                // Decryption advances the in-memory secret tree before the AEAD
+                // check, but OpenMLS only persists it on success. Reload the
+                // persisted state so a forged copy can't burn the key of the
+                // genuine message (which would wedge us on a commit).
+                match MlsGroup::load(self.provider.storage(), &group_id) {
+                    Ok(Some(restored)) => *group = restored,
+                    _ => tracing::warn!("Failed to restore group state after rejected message"),
+                }
+                return Err(e);
+            }
+        };
+
+        // Only groups that publish their GroupInfo take newcomers' commits
+        if matches!(processed.sender(), Sender::NewMemberCommit) && !allow_external_commits {
+            return Err(MlsError::PermissionDenied(
+                "External commits are not accepted in this group".to_string(),
+            ));
+        }
+
+        let sender = processed.sender().clone();
+        let credential = processed.credential().clone();
 
         // Handle based on message type
         match processed.into_content() {
             ProcessedMessageContent::ApplicationMessage(app_msg) => {
+                // Past-epoch secrets are kept for messages that cross a
+                // commit in flight, not for members the commit removed
+                if epoch < group.epoch().as_u64()
+                    && !is_current_member(&group, &sender, &credential)
+                {
+                    return Err(MlsError::PermissionDenied(
+                        "Sender of past-epoch message is no longer a member".to_string(),
+                    ));
+                }
+
                 // Extract plaintext from application message
                 let plaintext = app_msg.into_bytes();
                 Ok(ProcessedMessage::Application(plaintext))
             }
-            ProcessedMessageContent::ProposalMessage(_proposal) => {
-                // Proposal has been added to group's pending state
+            ProcessedMessageContent::ProposalMessage(proposal) => {
+                // Queue the proposal so a later commit can include it
+                group.store_pending_proposal(self.provider.storage(), *proposal).map_err(|e| {
+                    MlsError::Internal(format!("Failed to store proposal: {:?}", e))
+                })?;
                 Ok(ProcessedMessage::Proposal)
             }
             ProcessedMessageContent::StagedCommitMessage(staged_commit) => {
+                // Membership, metadata and role changes need the right role
+                if let Err(e) = commit_policy::check_commit(&group, &sender, &staged_commit) {
+                    match MlsGroup::load(self.provider.storage(), &group_id) {
+                        Ok(Some(restored)) => *group = restored,
+                        _ => tracing::warn!("Failed to restore group state after rejected commit"),
+                    }
+                    return Err(e);
+                }
+
                 // Merge the staged commit to advance the epoch
                 group
                     .merge_staged_commit(self.provider.as_ref(), *staged_commit)
                     .map_err(|e| MlsError::Internal(format!("Failed to merge commit: {:?}", e)))?;
+                self.after_merge(&group);
 
                 let new_epoch = group.epoch().as_u64();
 
@@ -499,6 +1026,54 @@ impl<P: OpenMlsProvider + 'static> OpenMlsEngine<P> {
     }
 }
 
+/// OpenMLS's resumption PSK store, which it doesn't export
+///
+/// A group OpenMLS creates keeps the resumption secrets of its last 32
+/// epochs whatever `max_past_epochs` says, so they are trimmed through
+/// storage. The fields mirror OpenMLS's serialized layout.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ResumptionPsks {
+    max_number_of_secrets: usize,
+    resumption_psk: Vec<(u64, ResumptionPskSecret)>,
+    cursor: usize,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ResumptionPskSecret {
+    secret: StoredSecret,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct StoredSecret {
+    value: SecretBytes,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, zeroize::Zeroize, zeroize::ZeroizeOnDrop)]
+struct SecretBytes {
+    vec: Vec<u8>,
+}
+
+impl Entity<CURRENT_VERSION> for ResumptionPsks {}
+impl storage_traits::ResumptionPskStore<CURRENT_VERSION> for ResumptionPsks {}
+
+/// Whether `sender` still holds its leaf with the same credential
+fn is_current_member(group: &MlsGroup, sender: &Sender, credential: &Credential) -> bool {
+    match sender {
+        Sender::Member(leaf) => group.member(*leaf) == Some(credential),
+        _ => false,
+    }
+}
+
+/// Run an OpenMLS call on untrusted input, turning panics into errors
+///
+/// OpenMLS and tls_codec `debug_assert!` on some malformed input (bad length
+/// prefixes, AEAD failures) that they reject with an error in release builds.
+/// Treat both the same so garbage from the network can't panic a debug build.
+fn reject_on_panic<T>(f: impl FnOnce() -> MlsResult<T>) -> MlsResult<T> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f))
+        .unwrap_or_else(|_| Err(MlsError::InvalidMessage("Malformed message rejected".to_string())))
+}
+
 /// Result of processing an incoming message
 #[derive(Debug)]
 pub enum ProcessedMessage {
@@ -571,6 +1146,7 @@ impl<P: OpenMlsProvider + 'static> OpenMlsEngine<P> {
                     leaf_index,
                     joined_at,
                     role: super::super::types::MemberRole::Member,
+                    signature_key: member.signature_key.clone(),
                 }
             })
             .collect();
@@ -591,6 +1167,28 @@ impl<P: OpenMlsProvider + 'static> OpenMlsEngine<P> {
         Ok(snapshot)
     }
 
+    /// Export a signed GroupInfo for joining by external commit
+    ///
+    /// Carries the ratchet tree and the external_pub extension, which is
+    /// everything a newcomer needs for
+    /// [`join_by_external_commit`](Self::join_by_external_commit). It is
+    /// only valid for the current epoch.
+    pub async fn export_group_info(&self) -> MlsResult<Vec<u8>> {
+        let group = self.group.read().await;
+        let group_info = group
+            .export_group_info(self.provider.crypto(), &self.signature_keys, true)
+            .map_err(|e| MlsError::Internal(format!("Failed to export group info: {:?}", e)))?;
+        group_info
+            .tls_serialize_detached()
+            .map_err(|e| MlsError::Internal(format!("Failed to serialize group info: {:?}", e)))
+    }
+
+    /// Sign bytes with this member's leaf signature key
+    pub fn sign(&self, payload: &[u8]) -> MlsResult<Vec<u8>> {
+        openmls_traits::signatures::Signer::sign(&self.signature_keys, payload)
+            .map_err(|e| MlsError::CryptoError(format!("Failed to sign: {:?}", e)))
+    }
+
     /// Export only the ratchet tree for sharing with new members
     ///
     /// This is needed when joining from a Welcome message that doesn't include
@@ -656,6 +1254,7 @@ impl<P: OpenMlsProvider + 'static> OpenMlsEngine<P> {
                 leaf_index,
                 joined_at,
                 role: super::super::types::MemberRole::Member,
+                signature_key: member.signature_key,
             });
         }
 
@@ -731,6 +1330,133 @@ mod tests {
         assert_eq!(restored.members().len(), snapshot.members().len());
     }
 
+    #[tokio::test]
+    async fn test_join_by_external_commit() {
+        let config = MlsConfig::default();
+        let alice = OpenMlsEngine::create_group(
+            GroupId::random(),
+            b"alice".to_vec(),
+            config.clone(),
+            Arc::new(OpenMlsRustCrypto::default()),
+        )
+        .await
+        .expect("Failed to create group");
+
+        let group_info = alice.export_group_info().await.expect("Failed to export group info");
+        let (bob, commit) = OpenMlsEngine::join_by_external_commit(
+            &group_info,
+            b"bob".to_vec(),
+            config,
+            Arc::new(OpenMlsRustCrypto::default()),
+        )
+        .await
+        .expect("Failed to join by external commit");
+        assert_eq!(bob.group_id().await, alice.group_id().await);
+        assert_eq!(bob.epoch().await, 1);
+
+        // Only taken when the caller opts in
+        assert!(matches!(
+            alice.process_message(&commit).await,
+            Err(MlsError::PermissionDenied(_))
+        ));
+        assert_eq!(alice.epoch().await, 0);
+        let processed = alice.process_message_with_external_commits(&commit).await.unwrap();
+        assert!(matches!(processed, ProcessedMessage::Commit { new_epoch: 1 }));
+
+        let ciphertext = bob.send_message(b"hi alice").await.unwrap();
+        let processed = alice.process_message(&ciphertext).await.unwrap();
+        assert!(matches!(processed, ProcessedMessage::Application(ref m) if m == b"hi alice"));
+    }
+
+    #[tokio::test]
+    async fn test_own_leaf_message_reported() {
+        let config = MlsConfig::default();
+        let alice = OpenMlsEngine::create_group(
+            GroupId::random(),
+            b"alice".to_vec(),
+            config.clone(),
+            Arc::new(OpenMlsRustCrypto::default()),
+        )
+        .await
+        .expect("Failed to create group");
+        let group_info = alice.export_group_info().await.unwrap();
+        let (bob, commit) = OpenMlsEngine::join_by_external_commit(
+            &group_info,
+            b"bob".to_vec(),
+            config,
+            Arc::new(OpenMlsRustCrypto::default()),
+        )
+        .await
+        .unwrap();
+        alice.process_message_with_external_commits(&commit).await.unwrap();
+
+        let ciphertext = bob.send_message(b"from bob's leaf").await.unwrap();
+        assert!(matches!(
+            bob.process_message(&ciphertext).await,
+            Err(MlsError::OwnLeafMessage { epoch: 1 })
+        ));
+
+        // Rejecting it leaves the group usable
+        let processed = alice.process_message(&ciphertext).await.unwrap();
+        assert!(matches!(processed, ProcessedMessage::Application(ref m) if m == b"from bob's leaf"));
+        let ciphertext = bob.send_message(b"still here").await.unwrap();
+        assert!(alice.process_message(&ciphertext).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_only_admin_changes_channel_metadata() {
+        let alice = OpenMlsEngine::create_group(
+            GroupId::random(),
+            b"alice".to_vec(),
+            MlsConfig::default(),
+            Arc::new(OpenMlsRustCrypto::default()),
+        )
+        .await
+        .expect("Failed to create group");
+
+        let mut members: Vec<OpenMlsEngine<OpenMlsRustCrypto>> = Vec::new();
+        for identity in [b"bob".to_vec(), b"carol".to_vec()] {
+            let group_info = alice.export_group_info().await.unwrap();
+            let (member, commit) = OpenMlsEngine::join_by_external_commit(
+                &group_info,
+                identity,
+                MlsConfig::default(),
+                Arc::new(OpenMlsRustCrypto::default()),
+            )
+            .await
+            .expect("Failed to join by external commit");
+            alice.process_message_with_external_commits(&commit).await.unwrap();
+            for earlier in &members {
+                earlier.process_message_with_external_commits(&commit).await.unwrap();
+            }
+            members.push(member);
+        }
+        let (bob, carol) = (&members[0], &members[1]);
+
+        // Bob isn't the admin; his commit only moves himself along
+        let commit = bob
+            .update_channel_metadata(&ChannelMetadata::new("bobs-channel"))
+            .await
+            .unwrap();
+        for member in [&alice, carol] {
+            assert!(matches!(
+                member.process_message(&commit).await,
+                Err(MlsError::PermissionDenied(_))
+            ));
+            assert_eq!(member.epoch().await, 2);
+            assert_eq!(member.channel_metadata().await.unwrap(), None);
+        }
+
+        let metadata = ChannelMetadata {
+            topic: Some("release planning".to_string()),
+            ..ChannelMetadata::new("general")
+        };
+        let commit = alice.update_channel_metadata(&metadata).await.unwrap();
+        carol.process_message(&commit).await.unwrap();
+        assert_eq!(carol.channel_metadata().await.unwrap(), Some(metadata));
+        assert_eq!(carol.metadata().await.unwrap().name.as_deref(), Some("general"));
+    }
+
     #[tokio::test]
     async fn test_event_emission() {
         let group_id = GroupId::random();
@@ -28,6 +28,33 @@ pub enum MlsError {
     #[error("Epoch mismatch: expected {expected}, got {actual}")]
     EpochMismatch { expected: u64, actual: u64 },
 
+    /// Envelope is for an epoch too far ahead of the group to be held back
+    #[error("Epoch {epoch} is more than {max_ahead} ahead of current epoch {current}")]
+    EpochTooFarAhead { current: u64, epoch: u64, max_ahead: u64 },
+
+    /// Envelope is for an epoch older than the replay window
+    #[error("Epoch {epoch} is older than the replay window of {window} before epoch {current}")]
+    EpochTooOld { current: u64, epoch: u64, window: u64 },
+
+    /// A different commit was already merged for this epoch
+    #[error("Conflicting commit for epoch {epoch}: another commit was already merged")]
+    EpochConflict { epoch: u64 },
+
+    /// Message was sent from our own leaf
+    ///
+    /// Either an echo of something we sent, or another device using the
+    /// same identity and group state.
+    #[error("Message in epoch {epoch} was sent from our own leaf")]
+    OwnLeafMessage { epoch: u64 },
+
+    /// Ratchet tree doesn't match the hash it was pinned to
+    #[error("Ratchet tree hash mismatch for epoch {epoch}")]
+    TreeHashMismatch { epoch: u64 },
+
+    /// No room left to hold back envelopes for future epochs
+    #[error("Pending buffer full: {0} envelopes waiting for commits")]
+    PendingBufferFull(usize),
+
     /// Persistence/storage error
     #[error("Persistence error: {0}")]
     PersistenceError(String),
@@ -92,6 +119,10 @@ pub enum MlsError {
     #[error("Storage error: {0}")]
     Storage(String),
 
+    /// Database was written by a newer version than this build supports
+    #[error("Database schema version {found} is newer than supported version {supported}")]
+    SchemaTooNew { found: i32, supported: i32 },
+
     /// Item not found
     #[error("Not found: {0}")]
     NotFound(String),
@@ -44,6 +44,19 @@ pub enum MlsEvent {
     /// Commit was created
     CommitCreated { group_id: Vec<u8>, epoch: u64, proposal_count: usize },
 
+    /// Two commits were made for the same epoch and the lowest hash won
+    ///
+    /// With `rolled_back`, our branch lost: the group went back to `epoch`
+    /// and merged the winner. Changes we had committed on the losing
+    /// branch were proposed again; `requeued_proposals` are those
+    /// proposals, to be sent to the group.
+    ForkResolved {
+        group_id: Vec<u8>,
+        epoch: u64,
+        rolled_back: bool,
+        requeued_proposals: Vec<Vec<u8>>,
+    },
+
     /// Error occurred
     Error { group_id: Vec<u8>, error: String },
 }
@@ -74,6 +87,7 @@ impl MlsEvent {
             MlsEvent::GroupLeft { group_id, .. } => group_id,
             MlsEvent::ProposalCreated { group_id, .. } => group_id,
             MlsEvent::CommitCreated { group_id, .. } => group_id,
+            MlsEvent::ForkResolved { group_id, .. } => group_id,
             MlsEvent::Error { group_id, .. } => group_id,
         }
     }
@@ -90,6 +104,7 @@ impl MlsEvent {
             MlsEvent::GroupLeft { final_epoch, .. } => Some(*final_epoch),
             MlsEvent::ProposalCreated { epoch, .. } => Some(*epoch),
             MlsEvent::CommitCreated { epoch, .. } => Some(*epoch),
+            MlsEvent::ForkResolved { epoch, .. } => Some(*epoch),
             _ => None,
         }
     }
@@ -0,0 +1,183 @@
+//! Resolution of concurrent commits
+//!
+//! Two members can commit on top of the same epoch without seeing each
+//! other's commit first, splitting the group into two branches. Every
+//! member resolves the fork the same way: the commit with the lowest hash
+//! wins. A member on the losing branch returns to the state before the
+//! commit it merged and applies the winner instead; changes it had
+//! committed itself are proposed again so they aren't lost.
+//!
+//! Returning to the previous state needs a copy of the group's entries in
+//! the provider storage from before the merge ([`ForkPoint`]). Only those
+//! change when a commit is merged: the leaf key pair a commit of ours
+//! writes under its public key is deleted again by the merge, and the
+//! keys it leaves behind are stored under the group. Only the last commit
+//! of each group is kept that way, so a fork is resolved only while the
+//! losing branch is a single commit long; anything older is an
+//! `MlsError::EpochConflict`.
+
+use crate::core_mls::commit_log::CommitHash;
+use crate::core_mls::errors::{MlsError, MlsResult};
+use crate::core_mls::types::GroupId;
+use openmls_basic_credential::SignatureKeyPair;
+use openmls_memory_storage::MemoryStorage;
+use std::collections::HashMap;
+
+/// Copy of a group's entries in the provider storage
+pub type StorageSnapshot = HashMap<Vec<u8>, Vec<u8>>;
+
+/// A change we committed that a lost fork dropped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DroppedChange {
+    /// Add of the member with this key package
+    Add(Vec<u8>),
+
+    /// Removal of the leaf with this signature key
+    Remove(Vec<u8>),
+}
+
+/// State to return to if the last merged commit loses a fork
+pub struct ForkPoint {
+    /// Epoch the merged commit applied to
+    pub epoch: u64,
+
+    /// Hash of the merged commit
+    pub commit: CommitHash,
+
+    /// The group's entries in the provider storage before the merge
+    pub storage: StorageSnapshot,
+
+    /// Our own changes in the merged commit
+    pub dropped: Vec<DroppedChange>,
+}
+
+/// Whether `incoming` beats the commit merged for the same epoch
+pub fn wins(incoming: &CommitHash, merged: &CommitHash) -> bool {
+    incoming < merged
+}
+
+/// Labels OpenMLS keeps a group's state under
+///
+/// Each is followed by the group ID, or by a tuple starting with it.
+const GROUP_LABELS: [&[u8]; 15] = [
+    b"Tree",
+    b"GroupContext",
+    b"ApplicationExportTree",
+    b"InterimTranscriptHash",
+    b"ConfirmationTag",
+    b"MlsGroupJoinConfig",
+    b"OwnLeafNodes",
+    b"GroupState",
+    b"QueuedProposal",
+    b"ProposalQueueRefs",
+    b"OwnLeafNodeIndex",
+    b"EpochSecrets",
+    b"ResumptionPsk",
+    b"MessageSecrets",
+    b"EpochKeyPairs",
+];
+
+/// Copy a group's entries in the provider storage
+pub fn snapshot(storage: &MemoryStorage, group_id: &GroupId) -> StorageSnapshot {
+    let group_key = group_storage_key(group_id);
+    let values = storage.values.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+    values
+        .iter()
+        .filter(|(key, _)| belongs(key, &group_key))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+/// Provider storage entries holding a signature key pair
+///
+/// The pair is kept under its public key rather than under the group, so
+/// a copy meant to rebuild the group in an empty storage has to add it.
+pub fn signature_key_entries(keys: &SignatureKeyPair) -> MlsResult<StorageSnapshot> {
+    let storage = MemoryStorage::default();
+    keys.store(&storage)
+        .map_err(|e| MlsError::Storage(format!("Failed to copy signature keys: {:?}", e)))?;
+    Ok(storage.values.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner()))
+}
+
+/// Put a group's entries back the way they were in `snapshot`
+///
+/// Entries of the group that aren't in the snapshot are deleted. Entries
+/// of other groups, and those not kept under any group, are left alone,
+/// apart from the ones the snapshot carries (see `signature_key_entries`).
+pub fn restore(storage: &MemoryStorage, group_id: &GroupId, snapshot: &StorageSnapshot) {
+    let group_key = group_storage_key(group_id);
+    let mut values = storage.values.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    values.retain(|key, _| !belongs(key, &group_key));
+    for (key, value) in snapshot {
+        values.insert(key.clone(), value.clone());
+    }
+}
+
+/// Whether a storage key holds part of the state of the group encoded as
+/// `group_key`
+fn belongs(key: &[u8], group_key: &[u8]) -> bool {
+    GROUP_LABELS.iter().any(|label| {
+        key.strip_prefix(*label).is_some_and(|rest| {
+            rest.starts_with(group_key)
+                || rest.strip_prefix(b"[").is_some_and(|rest| rest.starts_with(group_key))
+        })
+    })
+}
+
+/// How OpenMLS encodes the group ID in its storage keys
+fn group_storage_key(group_id: &GroupId) -> Vec<u8> {
+    let group_id = openmls::prelude::GroupId::from_slice(group_id.as_bytes());
+    serde_json::to_vec(&group_id).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core_mls::commit_log::commit_hash;
+
+    fn group_entry(group_id: &GroupId, label: &[u8]) -> Vec<u8> {
+        [label, &group_storage_key(group_id)].concat()
+    }
+
+    #[test]
+    fn test_lowest_hash_wins() {
+        let (a, b) = (commit_hash(b"commit a"), commit_hash(b"commit b"));
+        assert_ne!(wins(&a, &b), wins(&b, &a));
+        assert!(!wins(&a, &a));
+    }
+
+    #[test]
+    fn test_restore_only_touches_the_group() {
+        let storage = MemoryStorage::default();
+        let group = GroupId::new(b"general".to_vec());
+        let other = GroupId::new(b"random".to_vec());
+        let proposal =
+            [b"QueuedProposal[".as_slice(), &group_storage_key(&group), b",\"ref\"]"].concat();
+        {
+            let mut values = storage.values.write().unwrap();
+            values.insert(group_entry(&group, b"GroupContext"), b"epoch 1".to_vec());
+            values.insert(group_entry(&other, b"GroupContext"), b"epoch 7".to_vec());
+            values.insert(b"EncryptionKeyPair 1".to_vec(), b"secret 1".to_vec());
+        }
+        let before = snapshot(&storage, &group);
+        assert_eq!(before.len(), 1);
+
+        {
+            let mut values = storage.values.write().unwrap();
+            values.insert(group_entry(&group, b"GroupContext"), b"epoch 2".to_vec());
+            values.insert(proposal.clone(), b"queued".to_vec());
+            values.insert(group_entry(&other, b"GroupContext"), b"epoch 8".to_vec());
+            values.remove(b"EncryptionKeyPair 1".as_slice());
+            values.insert(b"EncryptionKeyPair 2".to_vec(), b"secret 2".to_vec());
+        }
+        assert_eq!(snapshot(&storage, &group).len(), 2);
+        restore(&storage, &group, &before);
+
+        let values = storage.values.read().unwrap();
+        assert_eq!(values[&group_entry(&group, b"GroupContext")], b"epoch 1");
+        assert!(!values.contains_key(&proposal));
+        assert_eq!(values[&group_entry(&other, b"GroupContext")], b"epoch 8");
+        assert!(!values.contains_key(b"EncryptionKeyPair 1".as_slice()));
+        assert_eq!(values[b"EncryptionKeyPair 2".as_slice()], b"secret 2");
+    }
+}
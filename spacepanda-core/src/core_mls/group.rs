@@ -43,6 +43,8 @@ pub struct MlsGroup {
     sender_sequences: HashMap<u32, u64>,
     /// This member's leaf index
     pub self_index: LeafIndex,
+    /// External pre-shared keys (PSK ID -> secret)
+    psks: HashMap<Vec<u8>, Vec<u8>>,
 }
 
 impl MlsGroup {
@@ -66,6 +68,7 @@ impl MlsGroup {
                 leaf_index: self_index,
                 joined_at: current_timestamp(),
                 role: super::types::MemberRole::Admin, // Creator is admin
+                signature_key: creator_public_key,
             }],
             created_at: current_timestamp(),
             updated_at: current_timestamp(),
@@ -84,6 +87,7 @@ impl MlsGroup {
             replay_cache: HashSet::new(),
             sender_sequences: HashMap::new(),
             self_index,
+            psks: HashMap::new(),
         })
     }
 
@@ -97,10 +101,35 @@ impl MlsGroup {
         welcome: &Welcome,
         member_index: LeafIndex,
         member_secret_key: &[u8],
+    ) -> MlsResult<Self> {
+        Self::from_welcome_with_psks(welcome, member_index, member_secret_key, HashMap::new())
+    }
+
+    /// Join a group via a Welcome that may require external PSKs
+    ///
+    /// # Arguments
+    /// * `welcome` - The Welcome message
+    /// * `member_index` - This member's leaf index
+    /// * `member_secret_key` - This member's X25519 secret key for HPKE decryption
+    /// * `psks` - External PSKs known to this member (PSK ID -> secret)
+    ///
+    /// # Errors
+    /// `CryptoError` if the Welcome lists a PSK that isn't in `psks`
+    pub fn from_welcome_with_psks(
+        welcome: &Welcome,
+        member_index: LeafIndex,
+        member_secret_key: &[u8],
+        psks: HashMap<Vec<u8>, Vec<u8>>,
     ) -> MlsResult<Self> {
         let (secrets, tree) = welcome.process(member_index, member_secret_key)?;
 
-        let key_schedule = KeySchedule::new(secrets.epoch, secrets.application_secret);
+        let application_secret = if secrets.psk_ids.is_empty() {
+            secrets.application_secret
+        } else {
+            let psk_secret = derive_psk_secret(&psks, &secrets.psk_ids)?;
+            mix_psk_secret(&secrets.application_secret, &psk_secret)
+        };
+        let key_schedule = KeySchedule::new(secrets.epoch, application_secret);
 
         Ok(Self {
             group_id: welcome.group_id.clone(),
@@ -113,9 +142,18 @@ impl MlsGroup {
             replay_cache: HashSet::new(),
             sender_sequences: HashMap::new(),
             self_index: member_index,
+            psks,
         })
     }
 
+    /// Register an external pre-shared key
+    ///
+    /// Every member processing a commit with a PreSharedKey proposal for
+    /// `psk_id` must have registered the same secret, or the commit fails.
+    pub fn inject_psk(&mut self, psk_id: Vec<u8>, secret: Vec<u8>) {
+        self.psks.insert(psk_id, secret);
+    }
+
     /// Add a proposal to the queue
     pub fn add_proposal(&mut self, proposal: Proposal) -> MlsResult<u32> {
         // Verify epoch matches
@@ -183,8 +221,21 @@ impl MlsGroup {
                     )));
                 }
             }
-            ProposalContent::PreSharedKey { .. } => {
-                // PSK validation would go here
+            ProposalContent::PreSharedKey { psk_id } => {
+                // Verify sender exists
+                if proposal.sender >= self.tree.leaf_count() {
+                    return Err(MlsError::InvalidProposal(format!(
+                        "Invalid sender index: {}",
+                        proposal.sender
+                    )));
+                }
+                // The commit can only be processed with the PSK at hand
+                if !self.psks.contains_key(psk_id) {
+                    return Err(MlsError::InvalidProposal(format!(
+                        "Unknown PSK: {}",
+                        hex::encode(psk_id)
+                    )));
+                }
             }
         }
 
@@ -214,17 +265,23 @@ impl MlsGroup {
         commit.set_confirmation_tag(confirmation_tag);
 
         // Apply proposals to get new members
+        let psk_ids = self.proposals.psk_ids();
         let result = self.apply_proposals_internal()?;
 
         // Advance epoch FIRST
-        self.advance_epoch()?;
+        let joiner_secret = self.advance_epoch(&psk_ids)?;
 
         // Create Welcome messages for new members (after epoch advancement)
         let mut welcomes = Vec::new();
         for added_idx in &result.added_members {
             if let Some(node) = self.tree.get_node(MlsTree::leaf_to_node_index(*added_idx)) {
                 if let Some(ref public_key) = node.public_key {
-                    let welcome = self.create_welcome_for_member(*added_idx, public_key.clone())?;
+                    let welcome = self.create_welcome_for_member(
+                        *added_idx,
+                        public_key.clone(),
+                        &joiner_secret,
+                        &psk_ids,
+                    )?;
                     welcomes.push(welcome);
                 }
             }
@@ -254,10 +311,11 @@ impl MlsGroup {
         }
 
         // Apply proposals
+        let psk_ids = self.proposals.psk_ids();
         let result = self.apply_proposals_internal()?;
 
         // Advance epoch
-        self.advance_epoch()?;
+        self.advance_epoch(&psk_ids)?;
 
         Ok(result)
     }
@@ -327,6 +385,7 @@ impl MlsGroup {
                         leaf_index: leaf_idx,
                         joined_at: current_timestamp(),
                         role: super::types::MemberRole::Member, // New members are regular members
+                        signature_key: public_key.clone(),
                     });
 
                     result.added_members.push(leaf_idx);
@@ -342,8 +401,7 @@ impl MlsGroup {
                     result.removed_members.push(*removed);
                 }
                 ProposalContent::PreSharedKey { .. } => {
-                    // PSK proposals would update key schedule
-                    // Not implemented in this prototype
+                    // Mixed into the key schedule by advance_epoch
                 }
             }
         }
@@ -354,20 +412,30 @@ impl MlsGroup {
         Ok(result)
     }
 
-    fn advance_epoch(&mut self) -> MlsResult<()> {
+    /// Move to the next epoch, mixing in the committed PSKs
+    ///
+    /// Returns the new epoch's secret before the PSKs were mixed in, which
+    /// is what Welcomes for this epoch carry.
+    fn advance_epoch(&mut self, psk_ids: &[Vec<u8>]) -> MlsResult<Vec<u8>> {
+        // Derive new application secret (simplified)
+        let joiner_secret = derive_next_epoch_secret(&self.key_schedule.application_secret);
+        let new_app_secret = if psk_ids.is_empty() {
+            joiner_secret.clone()
+        } else {
+            let psk_secret = derive_psk_secret(&self.psks, psk_ids)?;
+            mix_psk_secret(&joiner_secret, &psk_secret)
+        };
+
         self.epoch += 1;
         self.metadata.epoch = self.epoch;
         self.metadata.updated_at = current_timestamp();
-
-        // Derive new application secret (simplified)
-        let new_app_secret = derive_next_epoch_secret(&self.key_schedule.application_secret);
         self.key_schedule = KeySchedule::new(self.epoch, new_app_secret);
 
         // Clear replay cache on epoch change
         self.replay_cache.clear();
         self.sender_sequences.clear();
 
-        Ok(())
+        Ok(joiner_secret)
     }
 
     fn compute_confirmation_tag(&self) -> Vec<u8> {
@@ -387,11 +455,20 @@ impl MlsGroup {
         &self,
         member_index: LeafIndex,
         public_key: Vec<u8>,
+        joiner_secret: &[u8],
+        psk_ids: &[Vec<u8>],
     ) -> MlsResult<Welcome> {
+        // With PSKs the joiner gets the pre-PSK secret and has to mix them in
+        let application_secret = if psk_ids.is_empty() {
+            self.key_schedule.application_secret.clone()
+        } else {
+            joiner_secret.to_vec()
+        };
         let secrets = WelcomeGroupSecrets {
             epoch: self.epoch,
-            application_secret: self.key_schedule.application_secret.clone(),
+            application_secret,
             epoch_authenticator: self.compute_confirmation_tag(),
+            psk_ids: psk_ids.to_vec(),
         };
 
         Welcome::create(
@@ -440,6 +517,48 @@ fn derive_next_epoch_secret(current: &[u8]) -> Vec<u8> {
     hasher.finalize().to_vec()
 }
 
+/// Chain the PSKs of a commit into one secret (RFC 9420 §8.4)
+///
+/// psk_extracted[i] = Extract(0, psk[i])
+/// psk_input[i] = Expand(psk_extracted[i], "derived psk" || id || index || count)
+/// psk_secret[i] = Extract(psk_input[i], psk_secret[i-1]), psk_secret[0] = 0
+fn derive_psk_secret(psks: &HashMap<Vec<u8>, Vec<u8>>, psk_ids: &[Vec<u8>]) -> MlsResult<Vec<u8>> {
+    use hkdf::Hkdf;
+    use sha2::Sha256;
+
+    let count = psk_ids.len() as u16;
+    let mut psk_secret = vec![0u8; 32];
+    for (index, psk_id) in psk_ids.iter().enumerate() {
+        let psk = psks.get(psk_id).ok_or_else(|| {
+            MlsError::CryptoError(format!("Missing PSK: {}", hex::encode(psk_id)))
+        })?;
+
+        let (_, psk_extracted) = Hkdf::<Sha256>::extract(None, psk);
+        let mut label = b"derived psk".to_vec();
+        label.extend_from_slice(psk_id);
+        label.extend_from_slice(&(index as u16).to_be_bytes());
+        label.extend_from_slice(&count.to_be_bytes());
+        let mut psk_input = [0u8; 32];
+        psk_extracted
+            .expand(&label, &mut psk_input)
+            .map_err(|e| MlsError::CryptoError(format!("HKDF expand failed: {}", e)))?;
+
+        let (prk, _) = Hkdf::<Sha256>::extract(Some(&psk_input), &psk_secret);
+        psk_secret = prk.to_vec();
+    }
+
+    Ok(psk_secret)
+}
+
+/// Epoch secret from the joiner secret and the PSK secret
+///
+/// Mirrors member_secret = Extract(joiner_secret, psk_secret) followed by
+/// the "epoch" expansion in the RFC 9420 key schedule.
+fn mix_psk_secret(joiner_secret: &[u8], psk_secret: &[u8]) -> Vec<u8> {
+    let (member_secret, _) = hkdf::Hkdf::<sha2::Sha256>::extract(Some(joiner_secret), psk_secret);
+    derive_next_epoch_secret(&member_secret)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -735,4 +854,46 @@ mod tests {
         assert_eq!(bob_group.group_id, creator.group_id);
         assert_eq!(bob_group.self_index, 1);
     }
+
+    #[test]
+    fn test_psk_required_to_use_welcome() {
+        let (alice_pk, _) = test_keypair("alice");
+        let mut creator = MlsGroup::new(
+            test_group_id(),
+            alice_pk,
+            b"alice".to_vec(),
+            vec![1, 2, 3, 4],
+            MlsConfig::default(),
+        )
+        .unwrap();
+
+        // Unknown PSKs can't be proposed
+        let unknown = Proposal::new_psk(0, 0, b"ops-psk".to_vec());
+        assert!(matches!(creator.add_proposal(unknown), Err(MlsError::InvalidProposal(_))));
+
+        let psk = (b"ops-psk".to_vec(), b"correct horse".to_vec());
+        creator.inject_psk(psk.0.clone(), psk.1.clone());
+        let (bob_pk, bob_sk) = test_keypair("bob");
+        creator.add_proposal(Proposal::new_add(0, 0, bob_pk, b"bob".to_vec())).unwrap();
+        creator.add_proposal(Proposal::new_psk(0, 0, psk.0.clone())).unwrap();
+        let (_, welcomes) = creator.commit(None).unwrap();
+        let ciphertext = creator.seal_message(b"for PSK holders").unwrap();
+
+        // The Welcome alone is not enough
+        assert!(matches!(
+            MlsGroup::from_welcome(&welcomes[0], 1, &bob_sk),
+            Err(MlsError::CryptoError(_))
+        ));
+
+        // A wrong secret yields different epoch secrets
+        let wrong = HashMap::from([(psk.0.clone(), b"battery staple".to_vec())]);
+        let mut guesser =
+            MlsGroup::from_welcome_with_psks(&welcomes[0], 1, &bob_sk, wrong).unwrap();
+        assert!(guesser.open_message(&ciphertext).is_err());
+
+        let psks = HashMap::from([psk]);
+        let mut bob = MlsGroup::from_welcome_with_psks(&welcomes[0], 1, &bob_sk, psks).unwrap();
+        assert_eq!(bob.key_schedule.application_secret, creator.key_schedule.application_secret);
+        assert_eq!(bob.open_message(&ciphertext).unwrap(), b"for PSK holders");
+    }
 }
@@ -0,0 +1,304 @@
+//! Key package verification cache
+//!
+//! Verifying a key package means decoding it and checking its signature
+//! and lifetime. Large invite batches used to do this serially, and again
+//! for the same package when it was queued and when it was committed.
+//! [`KeyPackageVerifier`] keeps each verdict, keyed by a hash of the
+//! package, until the package expires, and verifies the misses of a batch
+//! in parallel.
+//!
+//! Leaf signature keys can be revoked (for example when a device is
+//! revoked). Revoking a key drops every cached verdict for packages signed
+//! with it, and packages with a revoked key are rejected even when their
+//! signature is fine.
+
+use crate::core_mls::errors::{MlsError, MlsResult};
+use crate::metrics::record_counter;
+use openmls::prelude::tls_codec::Deserialize as TlsDeserialize;
+use openmls::prelude::*;
+use openmls_traits::crypto::OpenMlsCrypto;
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Verdicts kept before the oldest are dropped
+const DEFAULT_CAPACITY: usize = 4096;
+
+/// How long a rejection is remembered, in seconds
+///
+/// Short, because a package that is not yet valid becomes valid later.
+const REJECTION_TTL_SECS: u64 = 60;
+
+/// What a valid key package commits its holder to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedKeyPackage {
+    /// Credential identity (the invitee's user ID bytes)
+    pub identity: Vec<u8>,
+
+    /// Leaf signature key the package was signed with
+    pub signature_key: Vec<u8>,
+
+    /// End of the package's lifetime (Unix seconds)
+    pub not_after: u64,
+}
+
+struct CacheEntry {
+    verdict: Result<VerifiedKeyPackage, String>,
+    expires_at: u64,
+}
+
+/// Verifies key packages once and remembers the verdict
+pub struct KeyPackageVerifier {
+    entries: RwLock<HashMap<[u8; 32], CacheEntry>>,
+    revoked: RwLock<HashSet<Vec<u8>>>,
+    capacity: usize,
+}
+
+impl Default for KeyPackageVerifier {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+}
+
+impl KeyPackageVerifier {
+    /// Create a verifier that keeps at most `capacity` verdicts
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: RwLock::new(HashMap::new()),
+            revoked: RwLock::new(HashSet::new()),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Verify one key package
+    ///
+    /// # Errors
+    ///
+    /// `InvalidMessage` if the package is malformed, badly signed, expired,
+    /// not yet valid, or signed with a revoked key.
+    pub fn verify(
+        &self,
+        crypto: &impl OpenMlsCrypto,
+        key_package: &[u8],
+    ) -> MlsResult<VerifiedKeyPackage> {
+        let hash = package_hash(key_package);
+        let verdict = match self.cached(&hash) {
+            Some(verdict) => verdict,
+            None => {
+                let verdict = check(crypto, key_package);
+                self.remember(hash, verdict.clone());
+                verdict
+            }
+        };
+        self.judge(verdict)
+    }
+
+    /// Verify a batch of key packages, in order
+    ///
+    /// Packages without a cached verdict are verified in parallel.
+    pub fn verify_batch(
+        &self,
+        crypto: &impl OpenMlsCrypto,
+        key_packages: &[Vec<u8>],
+    ) -> Vec<MlsResult<VerifiedKeyPackage>> {
+        let hashes: Vec<[u8; 32]> = key_packages.iter().map(|kp| package_hash(kp)).collect();
+        let mut verdicts: Vec<Option<Result<VerifiedKeyPackage, String>>> =
+            hashes.iter().map(|hash| self.cached(hash)).collect();
+
+        let misses: Vec<usize> =
+            (0..key_packages.len()).filter(|&i| verdicts[i].is_none()).collect();
+        let checked: Vec<(usize, Result<VerifiedKeyPackage, String>)> =
+            misses.into_par_iter().map(|i| (i, check(crypto, &key_packages[i]))).collect();
+        for (i, verdict) in checked {
+            self.remember(hashes[i], verdict.clone());
+            verdicts[i] = Some(verdict);
+        }
+
+        verdicts.into_iter().flatten().map(|verdict| self.judge(verdict)).collect()
+    }
+
+    /// Revoke leaf signature keys
+    ///
+    /// Cached verdicts for packages signed with them are dropped, and such
+    /// packages are rejected from now on.
+    pub fn revoke(&self, signature_keys: impl IntoIterator<Item = Vec<u8>>) {
+        let mut revoked = self.revoked.write().unwrap_or_else(|e| e.into_inner());
+        let before = revoked.len();
+        revoked.extend(signature_keys);
+        if revoked.len() == before {
+            return;
+        }
+
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|_, entry| match &entry.verdict {
+            Ok(verified) => !revoked.contains(&verified.signature_key),
+            Err(_) => true,
+        });
+    }
+
+    /// Whether a leaf signature key has been revoked
+    pub fn is_revoked(&self, signature_key: &[u8]) -> bool {
+        self.revoked.read().unwrap_or_else(|e| e.into_inner()).contains(signature_key)
+    }
+
+    /// Number of cached verdicts
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Whether no verdicts are cached
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn cached(&self, hash: &[u8; 32]) -> Option<Result<VerifiedKeyPackage, String>> {
+        let entries = self.entries.read().unwrap_or_else(|e| e.into_inner());
+        match entries.get(hash) {
+            Some(entry) if entry.expires_at > now_secs() => {
+                record_counter("mls.key_packages.verify_cache_hits", 1);
+                Some(entry.verdict.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn remember(&self, hash: [u8; 32], verdict: Result<VerifiedKeyPackage, String>) {
+        record_counter("mls.key_packages.verified", 1);
+        let now = now_secs();
+        let expires_at = match &verdict {
+            Ok(verified) => verified.not_after,
+            Err(_) => now + REJECTION_TTL_SECS,
+        };
+
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        if entries.len() >= self.capacity {
+            entries.retain(|_, entry| entry.expires_at > now);
+        }
+        if entries.len() >= self.capacity {
+            // Still full of live verdicts: drop the ones closest to expiry
+            let mut by_expiry: Vec<([u8; 32], u64)> =
+                entries.iter().map(|(hash, entry)| (*hash, entry.expires_at)).collect();
+            by_expiry.sort_by_key(|&(_, expires_at)| expires_at);
+            for (hash, _) in by_expiry.into_iter().take(entries.len() + 1 - self.capacity) {
+                entries.remove(&hash);
+            }
+        }
+        entries.insert(hash, CacheEntry { verdict, expires_at });
+    }
+
+    /// Apply the revocation list to a (possibly cached) verdict
+    fn judge(&self, verdict: Result<VerifiedKeyPackage, String>) -> MlsResult<VerifiedKeyPackage> {
+        match verdict {
+            Ok(verified) if self.is_revoked(&verified.signature_key) => Err(
+                MlsError::InvalidMessage("Key package is signed with a revoked key".to_string()),
+            ),
+            Ok(verified) => Ok(verified),
+            Err(reason) => Err(MlsError::InvalidMessage(reason)),
+        }
+    }
+}
+
+/// Decode a key package and check its signature and lifetime
+fn check(crypto: &impl OpenMlsCrypto, key_package: &[u8]) -> Result<VerifiedKeyPackage, String> {
+    let kp_in = KeyPackageIn::tls_deserialize_exact(key_package)
+        .map_err(|e| format!("Malformed key package: {:?}", e))?;
+
+    match kp_in.validate(crypto, ProtocolVersion::default()) {
+        Ok(key_package) => Ok(VerifiedKeyPackage {
+            identity: key_package.leaf_node().credential().serialized_content().to_vec(),
+            signature_key: key_package.leaf_node().signature_key().as_slice().to_vec(),
+            not_after: key_package.life_time().not_after(),
+        }),
+        Err(KeyPackageVerifyError::InvalidLifetime) => {
+            Err("Key package has expired or is not yet valid".to_string())
+        }
+        Err(e) => Err(format!("Key package validation failed: {:?}", e)),
+    }
+}
+
+fn package_hash(key_package: &[u8]) -> [u8; 32] {
+    Sha256::digest(key_package).into()
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::core_mls::service::MlsService;
+    use crate::shutdown::ShutdownCoordinator;
+    use openmls_rust_crypto::OpenMlsRustCrypto;
+    use openmls_traits::OpenMlsProvider;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    async fn key_packages(n: usize) -> Vec<Vec<u8>> {
+        let shutdown = Arc::new(ShutdownCoordinator::new(Duration::from_secs(30)));
+        let service = MlsService::new(&Config::default(), shutdown);
+        let mut packages = Vec::new();
+        for i in 0..n {
+            packages.push(
+                service.generate_key_package(format!("user-{}", i).into_bytes()).await.unwrap(),
+            );
+        }
+        packages
+    }
+
+    #[tokio::test]
+    async fn test_batch_matches_single_verification() {
+        let provider = OpenMlsRustCrypto::default();
+        let mut packages = key_packages(8).await;
+        packages.insert(3, b"not a key package".to_vec());
+
+        let verifier = KeyPackageVerifier::default();
+        let results = verifier.verify_batch(provider.crypto(), &packages);
+        assert_eq!(results.len(), packages.len());
+        assert!(results[3].is_err());
+        let identities: Vec<Vec<u8>> = results
+            .iter()
+            .filter_map(|r| r.as_ref().ok())
+            .map(|v| v.identity.clone())
+            .collect();
+        let expected: Vec<Vec<u8>> = (0..8).map(|i| format!("user-{}", i).into_bytes()).collect();
+        assert_eq!(identities, expected);
+        assert_eq!(verifier.len(), packages.len());
+
+        // A second pass is answered from the cache
+        let again = verifier.verify_batch(provider.crypto(), &packages);
+        assert_eq!(again.iter().filter(|r| r.is_ok()).count(), 8);
+        assert_eq!(verifier.len(), packages.len());
+    }
+
+    #[tokio::test]
+    async fn test_revoked_key_is_rejected_despite_cached_verdict() {
+        let provider = OpenMlsRustCrypto::default();
+        let packages = key_packages(2).await;
+        let verifier = KeyPackageVerifier::default();
+
+        let verified = verifier.verify(provider.crypto(), &packages[0]).unwrap();
+        verifier.verify(provider.crypto(), &packages[1]).unwrap();
+        assert_eq!(verifier.len(), 2);
+
+        verifier.revoke([verified.signature_key.clone()]);
+        assert_eq!(verifier.len(), 1);
+        assert!(verifier.verify(provider.crypto(), &packages[0]).is_err());
+        let batch = verifier.verify_batch(provider.crypto(), &packages);
+        assert!(batch[0].is_err());
+        assert!(batch[1].is_ok());
+    }
+
+    #[test]
+    fn test_capacity_is_bounded() {
+        let provider = OpenMlsRustCrypto::default();
+        let verifier = KeyPackageVerifier::with_capacity(4);
+        for i in 0..10u8 {
+            assert!(verifier.verify(provider.crypto(), &[i]).is_err());
+        }
+        assert_eq!(verifier.len(), 4);
+    }
+}
@@ -1,17 +1,29 @@
 //! Inbound Message Processing
 //!
 //! Handles incoming MLS messages: parsing, verification, and application to group state.
+//!
+//! Messages can overtake the commit that starts their epoch: a member sends
+//! right after committing, and the application message reaches a peer first.
+//! [`InboundHandler::receive`] parks such envelopes in a bounded pending
+//! buffer and retries them after each commit it processes. Envelopes too far
+//! ahead of the group, or older than the replay window, are rejected.
 
 use super::EncryptedEnvelope;
 use crate::core_mls::{
     engine::openmls_engine::{OpenMlsEngine, ProcessedMessage},
     errors::{MlsError, MlsResult},
     events::MlsEvent,
-    sealed_sender,
+    padding,
+    types::{GroupId, MlsConfig},
 };
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tracing::{debug, warn};
 
 #[cfg(test)]
 use super::MessageType;
+#[cfg(test)]
+use crate::core_mls::sealed_sender;
 
 /// Inbound message processor
 ///
@@ -19,12 +31,126 @@ use super::MessageType;
 pub struct InboundHandler {
     // Future: event emitter for notifying CRDT layer
     // event_tx: mpsc::Sender<MlsEvent>,
+    /// Envelopes waiting for the commit of their epoch
+    pending: Mutex<PendingBuffer>,
+    /// Furthest ahead of the group an envelope may be parked
+    max_epochs_ahead: u64,
+    /// Past epochs still accepted
+    replay_window_epochs: u64,
 }
 
 impl InboundHandler {
     /// Create a new inbound message handler
     pub fn new() -> Self {
-        Self {}
+        Self::with_config(&MlsConfig::default())
+    }
+
+    /// Create a handler with the pending buffer limits from `config`
+    pub fn with_config(config: &MlsConfig) -> Self {
+        Self {
+            pending: Mutex::new(PendingBuffer::new(config.pending_buffer_size)),
+            max_epochs_ahead: config.max_epochs_ahead,
+            replay_window_epochs: config.replay_window_epochs,
+        }
+    }
+
+    /// Receive an envelope, holding it back if its epoch hasn't started yet
+    ///
+    /// Envelopes for a future epoch are parked until a commit moves the
+    /// group there. Once an envelope is processed as a commit, the parked
+    /// envelopes it unblocked are retried; ones that still fail are logged
+    /// and dropped.
+    ///
+    /// # Returns
+    /// Results for this envelope and every parked envelope it unblocked, in
+    /// processing order; empty if the envelope was parked.
+    pub async fn receive<P: openmls_traits::OpenMlsProvider + 'static>(
+        &self,
+        engine: &OpenMlsEngine<P>,
+        envelope: EncryptedEnvelope,
+    ) -> MlsResult<Vec<ProcessedMessageResult>> {
+        let group_id = engine.group_id().await;
+        if envelope.group_id() != &group_id {
+            return Err(MlsError::InvalidMessage(format!(
+                "Group ID mismatch: expected {:?}, got {:?}",
+                group_id,
+                envelope.group_id()
+            )));
+        }
+
+        let current_epoch = engine.epoch().await;
+        self.check_epoch(envelope.epoch(), current_epoch)?;
+        if envelope.epoch() > current_epoch {
+            debug!(
+                epoch = envelope.epoch(),
+                current_epoch, "Parking envelope until its epoch starts"
+            );
+            self.lock_pending().park(envelope)?;
+            return Ok(Vec::new());
+        }
+
+        let result = self.process_envelope(engine, &envelope).await?;
+        let advanced = matches!(result.content, MessageContent::Commit { .. });
+        let mut results = vec![result];
+        if advanced {
+            results.extend(self.drain_pending(engine, &group_id).await);
+        }
+        Ok(results)
+    }
+
+    /// Number of envelopes parked for future epochs
+    pub fn pending_len(&self) -> usize {
+        self.lock_pending().len
+    }
+
+    /// Reject epochs outside `[current - replay window, current + max ahead]`
+    fn check_epoch(&self, epoch: u64, current: u64) -> MlsResult<()> {
+        if epoch < current.saturating_sub(self.replay_window_epochs) {
+            return Err(MlsError::EpochTooOld {
+                current,
+                epoch,
+                window: self.replay_window_epochs,
+            });
+        }
+        if epoch > current.saturating_add(self.max_epochs_ahead) {
+            return Err(MlsError::EpochTooFarAhead {
+                current,
+                epoch,
+                max_ahead: self.max_epochs_ahead,
+            });
+        }
+        Ok(())
+    }
+
+    /// Process parked envelopes whose epoch has started, until none is left
+    async fn drain_pending<P: openmls_traits::OpenMlsProvider + 'static>(
+        &self,
+        engine: &OpenMlsEngine<P>,
+        group_id: &GroupId,
+    ) -> Vec<ProcessedMessageResult> {
+        let mut results = Vec::new();
+        loop {
+            let current_epoch = engine.epoch().await;
+            let ready = self.lock_pending().take_ready(group_id, current_epoch);
+            if ready.is_empty() {
+                return results;
+            }
+
+            for envelope in ready {
+                match self.process_envelope(engine, &envelope).await {
+                    Ok(result) => results.push(result),
+                    Err(e) => warn!(
+                        epoch = envelope.epoch(),
+                        error = %e,
+                        "Dropping parked envelope that failed after its commit"
+                    ),
+                }
+            }
+        }
+    }
+
+    fn lock_pending(&self) -> std::sync::MutexGuard<'_, PendingBuffer> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
     }
 
     /// Process an incoming encrypted envelope
@@ -49,19 +175,18 @@ impl InboundHandler {
             });
         }
 
-        // Unseal sender identity (decrypt using group secret)
-        let group_secret = engine.export_secret("sender_key", b"", 32).await?;
-        let sender_key = sealed_sender::derive_sender_key(&group_secret);
-        let sender_id =
-            sealed_sender::unseal_sender(envelope.sealed_sender(), &sender_key, envelope.epoch())?;
+        // Unseal sender identity with the key of the envelope's epoch; one
+        // epoch back is accepted for envelopes that raced a commit
+        let sender_id = engine.unseal_sender(envelope.sealed_sender(), envelope.epoch()).await?;
 
         // Process the MLS message payload
         let processed = engine.process_message(envelope.payload()).await?;
 
         // Convert to result with events
         let result = match processed {
-            ProcessedMessage::Application(plaintext) => {
-                // Application message received
+            ProcessedMessage::Application(padded) => {
+                // Application message received; strip the sender's padding
+                let plaintext = padding::unpad_message(&padded)?;
                 let event = MlsEvent::MessageReceived {
                     group_id: envelope.group_id().as_bytes().to_vec(),
                     sender_id: sender_id.clone(),
@@ -139,6 +264,47 @@ impl Default for InboundHandler {
     }
 }
 
+/// Envelopes parked until the commit of their epoch arrives
+struct PendingBuffer {
+    capacity: usize,
+    len: usize,
+    /// Parked envelopes per group, in arrival order
+    groups: HashMap<GroupId, Vec<EncryptedEnvelope>>,
+}
+
+impl PendingBuffer {
+    fn new(capacity: usize) -> Self {
+        Self { capacity, len: 0, groups: HashMap::new() }
+    }
+
+    fn park(&mut self, envelope: EncryptedEnvelope) -> MlsResult<()> {
+        if self.len >= self.capacity {
+            return Err(MlsError::PendingBufferFull(self.capacity));
+        }
+        self.groups.entry(envelope.group_id().clone()).or_default().push(envelope);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Remove the group's envelopes for `epoch` or earlier, in arrival order
+    ///
+    /// Earlier ones were overtaken by a commit from another member; they
+    /// are handed back so processing reports why they can't be used.
+    fn take_ready(&mut self, group_id: &GroupId, epoch: u64) -> Vec<EncryptedEnvelope> {
+        let Some(parked) = self.groups.get_mut(group_id) else {
+            return Vec::new();
+        };
+        let (ready, waiting): (Vec<_>, Vec<_>) =
+            std::mem::take(parked).into_iter().partition(|e| e.epoch() <= epoch);
+        *parked = waiting;
+        if parked.is_empty() {
+            self.groups.remove(group_id);
+        }
+        self.len -= ready.len();
+        ready
+    }
+}
+
 /// Result of processing an inbound message
 #[derive(Debug)]
 pub struct ProcessedMessageResult {
@@ -165,7 +331,185 @@ pub enum MessageContent {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core_mls::types::{GroupId, MlsConfig};
+    use crate::core_mls::engine::group_ops::GroupOperations;
+    use crate::core_mls::messages::outbound::OutboundBuilder;
+    use crate::core_mls::types::GroupId;
+    use openmls::prelude::*;
+    use openmls_basic_credential::SignatureKeyPair;
+    use openmls_rust_crypto::OpenMlsRustCrypto;
+    use std::sync::Arc;
+    use tls_codec::Serialize as TlsSerializeTrait;
+
+    /// Alice's and Bob's engines in a two-member group
+    async fn two_party() -> (OpenMlsEngine<OpenMlsRustCrypto>, OpenMlsEngine<OpenMlsRustCrypto>) {
+        let alice = OpenMlsEngine::create_group(
+            GroupId::random(),
+            b"alice".to_vec(),
+            MlsConfig::default(),
+            Arc::new(OpenMlsRustCrypto::default()),
+        )
+        .await
+        .unwrap();
+
+        let provider = Arc::new(OpenMlsRustCrypto::default());
+        let ciphersuite = Ciphersuite::MLS_128_DHKEMX25519_AES128GCM_SHA256_Ed25519;
+        let signature_keys = SignatureKeyPair::new(ciphersuite.signature_algorithm()).unwrap();
+        signature_keys.store(provider.storage()).unwrap();
+        let credential = CredentialWithKey {
+            credential: BasicCredential::new(b"bob".to_vec()).into(),
+            signature_key: signature_keys.public().into(),
+        };
+        let bundle = KeyPackage::builder()
+            .build(ciphersuite, provider.as_ref(), &signature_keys, credential)
+            .unwrap();
+
+        let key_package = bundle.key_package().tls_serialize_detached().unwrap();
+        let (_commit, welcome) = alice.add_members(vec![key_package]).await.unwrap();
+        let tree = alice.export_ratchet_tree_bytes().await.unwrap();
+        let bob = OpenMlsEngine::join_from_welcome(
+            &welcome.unwrap(),
+            Some(tree),
+            MlsConfig::default(),
+            Some(bundle),
+            provider,
+        )
+        .await
+        .unwrap();
+
+        (alice, bob)
+    }
+
+    /// An envelope that is rejected before its payload is looked at
+    async fn envelope_at(
+        engine: &OpenMlsEngine<OpenMlsRustCrypto>,
+        epoch: u64,
+    ) -> EncryptedEnvelope {
+        let key = sealed_sender::derive_sender_key(b"test_secret");
+        let sealed = sealed_sender::seal_sender(b"mallory", &key, epoch).unwrap();
+        EncryptedEnvelope::new(
+            engine.group_id().await,
+            epoch,
+            sealed,
+            vec![1, 2, 3],
+            MessageType::Application,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_message_delivered_before_its_commit_still_decrypts() {
+        let (alice, bob) = two_party().await;
+        let outbound = OutboundBuilder::new(b"alice".to_vec());
+        let handler = InboundHandler::new();
+
+        let (commit, _) = outbound.build_commit_message(&alice).await.unwrap();
+        let message = outbound
+            .build_application_message(&alice, b"sent after the commit")
+            .await
+            .unwrap();
+        assert_eq!(message.epoch(), commit.epoch() + 1);
+
+        // The message overtakes the commit and is held back
+        assert!(handler.receive(&bob, message).await.unwrap().is_empty());
+        assert_eq!(handler.pending_len(), 1);
+
+        let results = handler.receive(&bob, commit).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(matches!(results[0].content, MessageContent::Commit { .. }));
+        match &results[1].content {
+            MessageContent::Application(plaintext) => {
+                assert_eq!(plaintext, b"sent after the commit")
+            }
+            other => panic!("expected an application message, got {:?}", other),
+        }
+        assert_eq!(handler.pending_len(), 0);
+        assert_eq!(bob.epoch().await, alice.epoch().await);
+    }
+
+    #[tokio::test]
+    async fn test_message_that_raced_a_commit_unseals_with_previous_key() {
+        let (alice, bob) = two_party().await;
+        let outbound = OutboundBuilder::new(b"alice".to_vec());
+        let handler = InboundHandler::new();
+
+        // Sent before the commit, delivered after it
+        let late = outbound.build_application_message(&alice, b"sent first").await.unwrap();
+        let stale = outbound.build_application_message(&alice, b"sent long ago").await.unwrap();
+        let (commit, _) = outbound.build_commit_message(&alice).await.unwrap();
+        handler.receive(&bob, commit).await.unwrap();
+
+        let results = handler.receive(&bob, late).await.unwrap();
+        assert!(
+            matches!(&results[0].content, MessageContent::Application(p) if p == b"sent first")
+        );
+
+        // Two epochs back there is no key left to try
+        let (commit, _) = outbound.build_commit_message(&alice).await.unwrap();
+        handler.receive(&bob, commit).await.unwrap();
+        let result = handler.receive(&bob, stale).await;
+        assert!(matches!(result, Err(MlsError::EpochTooOld { window: 1, .. })));
+    }
+
+    #[tokio::test]
+    async fn test_removed_member_cannot_unseal_later_senders() {
+        let (alice, bob) = two_party().await;
+        let outbound = OutboundBuilder::new(b"alice".to_vec());
+        let (removed_at, bob_key) = bob.sender_key().await.unwrap();
+
+        // Alice removes Bob (leaf 1) and keeps talking
+        outbound.build_remove_proposal(&alice, vec![1]).await.unwrap();
+        let envelope = outbound.build_application_message(&alice, b"after bob left").await.unwrap();
+        assert!(envelope.epoch() > removed_at);
+
+        // Bob's engine has no key for the new epoch
+        let result = bob.unseal_sender(envelope.sealed_sender(), envelope.epoch()).await;
+        assert!(matches!(result, Err(MlsError::EpochMismatch { .. })));
+
+        // and the last key he had doesn't open it either
+        let result =
+            sealed_sender::unseal_sender(envelope.sealed_sender(), &bob_key, envelope.epoch());
+        assert!(matches!(result, Err(MlsError::CryptoError(_))));
+        let result = sealed_sender::unseal_sender(envelope.sealed_sender(), &bob_key, removed_at);
+        assert!(matches!(result, Err(MlsError::CryptoError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_epochs_outside_the_window_are_rejected() {
+        let (alice, _bob) = two_party().await;
+        let outbound = OutboundBuilder::new(b"alice".to_vec());
+        for _ in 0..3 {
+            outbound.build_commit_message(&alice).await.unwrap();
+        }
+        let current = alice.epoch().await;
+
+        let config =
+            MlsConfig { max_epochs_ahead: 2, replay_window_epochs: 1, ..Default::default() };
+        let handler = InboundHandler::with_config(&config);
+
+        let result = handler.receive(&alice, envelope_at(&alice, current - 2).await).await;
+        assert!(matches!(result, Err(MlsError::EpochTooOld { window: 1, .. })));
+
+        let result = handler.receive(&alice, envelope_at(&alice, current + 3).await).await;
+        assert!(matches!(result, Err(MlsError::EpochTooFarAhead { max_ahead: 2, .. })));
+
+        let result = handler.receive(&alice, envelope_at(&alice, current + 2).await).await;
+        assert!(result.unwrap().is_empty());
+        assert_eq!(handler.pending_len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pending_buffer_is_bounded() {
+        let (alice, _bob) = two_party().await;
+        let current = alice.epoch().await;
+        let config = MlsConfig { pending_buffer_size: 2, ..Default::default() };
+        let handler = InboundHandler::with_config(&config);
+
+        for _ in 0..2 {
+            handler.receive(&alice, envelope_at(&alice, current + 1).await).await.unwrap();
+        }
+        let result = handler.receive(&alice, envelope_at(&alice, current + 1).await).await;
+        assert!(matches!(result, Err(MlsError::PendingBufferFull(2))));
+        assert_eq!(handler.pending_len(), 2);
+    }
 
     #[tokio::test]
     async fn test_inbound_handler_creation() {
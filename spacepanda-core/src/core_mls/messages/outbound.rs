@@ -32,6 +32,10 @@ impl OutboundBuilder {
 
     /// Build an application message envelope
     ///
+    /// The plaintext is padded with the group's
+    /// [`PaddingStrategy`](crate::core_mls::padding::PaddingStrategy) before
+    /// it is encrypted.
+    ///
     /// # Arguments
     /// * `engine` - The MLS engine managing the group
     /// * `plaintext` - The plaintext message to encrypt
@@ -44,17 +48,14 @@ impl OutboundBuilder {
         plaintext: &[u8],
     ) -> MlsResult<EncryptedEnvelope> {
         let group_id = engine.group_id().await;
-        let epoch = engine.epoch().await;
-
-        // Derive sender key from group secret
-        let group_secret = engine.export_secret("sender_key", b"", 32).await?;
-        let sender_key = sealed_sender::derive_sender_key(&group_secret);
 
-        // Seal sender identity
+        // Seal sender identity with this epoch's sender key
+        let (epoch, sender_key) = engine.sender_key().await?;
         let sealed_sender = sealed_sender::seal_sender(&self.identity, &sender_key, epoch)?;
 
-        // Encrypt the message
-        let encrypted_payload = engine.send_message(plaintext).await?;
+        // Pad, then encrypt the message
+        let padded = engine.config().padding.pad(plaintext)?;
+        let encrypted_payload = engine.send_message(&padded).await?;
 
         // Wrap in envelope with sealed sender
         Ok(EncryptedEnvelope::new(
@@ -78,13 +79,9 @@ impl OutboundBuilder {
         engine: &OpenMlsEngine<P>,
     ) -> MlsResult<(EncryptedEnvelope, Option<Vec<Vec<u8>>>)> {
         let group_id = engine.group_id().await;
-        let epoch = engine.epoch().await;
-
-        // Derive sender key from group secret
-        let group_secret = engine.export_secret("sender_key", b"", 32).await?;
-        let sender_key = sealed_sender::derive_sender_key(&group_secret);
 
-        // Seal sender identity
+        // Seal sender identity with this epoch's sender key
+        let (epoch, sender_key) = engine.sender_key().await?;
         let sealed_sender = sealed_sender::seal_sender(&self.identity, &sender_key, epoch)?;
 
         // Create commit for pending proposals
@@ -112,13 +109,9 @@ impl OutboundBuilder {
         key_packages: Vec<Vec<u8>>,
     ) -> MlsResult<EncryptedEnvelope> {
         let group_id = engine.group_id().await;
-        let epoch = engine.epoch().await;
 
-        // Derive sender key from group secret
-        let group_secret = engine.export_secret("sender_key", b"", 32).await?;
-        let sender_key = sealed_sender::derive_sender_key(&group_secret);
-
-        // Seal sender identity
+        // Seal sender identity with this epoch's sender key
+        let (epoch, sender_key) = engine.sender_key().await?;
         let sealed_sender = sealed_sender::seal_sender(&self.identity, &sender_key, epoch)?;
 
         // For now, we skip the proposal step and go straight to commit
@@ -146,13 +139,9 @@ impl OutboundBuilder {
         leaf_indices: Vec<u32>,
     ) -> MlsResult<EncryptedEnvelope> {
         let group_id = engine.group_id().await;
-        let epoch = engine.epoch().await;
-
-        // Derive sender key from group secret
-        let group_secret = engine.export_secret("sender_key", b"", 32).await?;
-        let sender_key = sealed_sender::derive_sender_key(&group_secret);
 
-        // Seal sender identity
+        // Seal sender identity with this epoch's sender key
+        let (epoch, sender_key) = engine.sender_key().await?;
         let sealed_sender = sealed_sender::seal_sender(&self.identity, &sender_key, epoch)?;
 
         // For now, we skip the proposal step and go straight to commit
@@ -178,7 +167,59 @@ impl OutboundBuilder {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core_mls::padding::PaddingStrategy;
     use crate::core_mls::types::MlsConfig;
+    use proptest::prelude::*;
+
+    /// Lengths of the ciphertexts of plaintexts of each of `lengths` bytes
+    fn ciphertext_lengths(padding: PaddingStrategy, lengths: &[usize]) -> Vec<usize> {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        runtime.block_on(async {
+            let config = MlsConfig { padding, ..Default::default() };
+            let engine = OpenMlsEngine::create_group(
+                GroupId::random(),
+                b"alice".to_vec(),
+                config,
+                Arc::new(OpenMlsRustCrypto::default()),
+            )
+            .await
+            .unwrap();
+            let builder = OutboundBuilder::new(b"alice".to_vec());
+
+            let mut sizes = Vec::new();
+            for &len in lengths {
+                let envelope =
+                    builder.build_application_message(&engine, &vec![b'x'; len]).await.unwrap();
+                sizes.push(envelope.payload().len());
+            }
+            sizes
+        })
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn prop_bucketed_ciphertexts_within_a_bucket_have_equal_length(
+            a in 1usize..=251,
+            b in 1usize..=251,
+            c in 252usize..=1019,
+            d in 252usize..=1019,
+        ) {
+            // 5 bytes of framing: up to 251 bytes fit the 256 bucket
+            let padding = PaddingStrategy::Buckets { sizes: vec![256, 1024] };
+            let sizes = ciphertext_lengths(padding, &[a, b, c, d]);
+            prop_assert_eq!(sizes[0], sizes[1]);
+            prop_assert_eq!(sizes[2], sizes[3]);
+            prop_assert!(sizes[0] < sizes[2]);
+        }
+    }
+
+    #[test]
+    fn test_unpadded_ciphertexts_track_length() {
+        let sizes = ciphertext_lengths(PaddingStrategy::None, &[10, 200]);
+        assert!(sizes[1] - sizes[0] >= 190);
+    }
 
     #[tokio::test]
     async fn test_outbound_builder_creation() {
@@ -28,9 +28,13 @@
 //! - Proof-of-possession: devices prove key ownership
 
 // Core types and errors
+pub mod channel_metadata;
+pub mod commit_log;
+pub mod commit_policy;
 pub mod crypto;
 pub mod errors;
 pub mod events;
+pub mod fork;
 pub mod sealed_sender;
 pub mod timing_obfuscation;
 pub mod types;
@@ -47,6 +51,9 @@ pub mod storage;
 // OpenMLS engine wrapper (Phase 3)
 pub mod engine;
 
+// Key package verification cache
+pub mod key_package_cache;
+
 // High-level service with production integration
 pub mod service;
 
@@ -77,6 +84,7 @@ pub mod encryption;
 pub mod group;
 pub mod persistence;
 pub mod proposals;
+pub mod recovery;
 pub mod transport;
 pub mod tree;
 pub mod welcome;
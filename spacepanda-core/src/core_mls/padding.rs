@@ -31,8 +31,17 @@
 //! ✅ No side channels: Constant-time operations
 //! ✅ Backwards compatible: Receivers strip padding automatically
 //! ⚠️ Does NOT hide: Message count, timing, or sender/receiver
+//!
+//! ## Strategies
+//!
+//! [`PaddingStrategy`] selects how far messages are padded: not at all, to
+//! a configurable list of buckets (the sizes above by default), or with
+//! Padmé, which bounds the overhead at about 12% and leaks only
+//! O(log log L) bits of the length. All strategies share the same framing,
+//! so [`unpad_message`] strips any of them.
 
 use crate::core_mls::errors::{MlsError, MlsResult};
+use serde::{Deserialize, Serialize};
 
 /// Standard padding bucket sizes (in bytes)
 ///
@@ -40,7 +49,7 @@ use crate::core_mls::errors::{MlsError, MlsResult};
 /// - Cover common message lengths (50-500 chars)
 /// - Minimize overhead for typical use
 /// - Support large media attachments
-const PADDING_BUCKETS: &[usize] = &[
+pub const PADDING_BUCKETS: &[usize] = &[
     256,   // Short messages (tweets, quick replies)
     1024,  // Medium messages (paragraphs)
     4096,  // Long messages (essays, formatted text)
@@ -57,6 +66,215 @@ pub const MAX_PADDED_SIZE: usize = 65536;
 /// This allows receivers to strip padding deterministically
 const PADDING_VERSION: u8 = 0x01;
 
+/// Size of the framing header: 1 byte version + 4 bytes length
+const HEADER_SIZE: usize = 5;
+
+/// How messages are padded before encryption
+///
+/// Every member of a channel should use the same strategy, otherwise the
+/// members with the weakest one give the lengths away.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum PaddingStrategy {
+    /// Frame messages without padding them
+    None,
+    /// Pad to the next of a list of sizes, smallest first
+    Buckets {
+        /// Padded sizes, strictly increasing
+        sizes: Vec<usize>,
+    },
+    /// Padmé: round the length so that only its top O(log log L) bits vary
+    Padme,
+}
+
+impl Default for PaddingStrategy {
+    fn default() -> Self {
+        Self::Buckets { sizes: PADDING_BUCKETS.to_vec() }
+    }
+}
+
+impl PaddingStrategy {
+    /// Check that the bucket sizes are usable
+    ///
+    /// # Errors
+    ///
+    /// `InvalidInput` if the list is empty, not strictly increasing, or has
+    /// a size that can't hold the framing header or exceeds
+    /// [`MAX_PADDED_SIZE`]
+    pub fn validate(&self) -> MlsResult<()> {
+        let Self::Buckets { sizes } = self else {
+            return Ok(());
+        };
+
+        if sizes.is_empty() {
+            return Err(MlsError::InvalidInput("No padding buckets configured".to_string()));
+        }
+        if sizes.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(MlsError::InvalidInput(format!(
+                "Padding buckets must be strictly increasing: {:?}",
+                sizes
+            )));
+        }
+        if let Some(size) =
+            sizes.iter().find(|&&size| size <= HEADER_SIZE || size > MAX_PADDED_SIZE)
+        {
+            return Err(MlsError::InvalidInput(format!(
+                "Padding bucket {} is outside {}..={}",
+                size,
+                HEADER_SIZE + 1,
+                MAX_PADDED_SIZE
+            )));
+        }
+        Ok(())
+    }
+
+    /// Drop the buckets smaller than `min_bucket`
+    ///
+    /// Keeps the largest bucket if all of them are smaller. Other
+    /// strategies are returned unchanged.
+    pub fn with_min_bucket(self, min_bucket: usize) -> Self {
+        match self {
+            Self::Buckets { sizes } => {
+                let largest = sizes.last().copied();
+                let mut sizes: Vec<usize> =
+                    sizes.into_iter().filter(|&size| size >= min_bucket).collect();
+                if sizes.is_empty() {
+                    sizes.extend(largest);
+                }
+                Self::Buckets { sizes }
+            }
+            other => other,
+        }
+    }
+
+    /// Size a message of `plaintext_len` bytes is padded to
+    ///
+    /// # Errors
+    ///
+    /// `InvalidInput` if the message doesn't fit in [`MAX_PADDED_SIZE`] or,
+    /// for buckets, in the largest bucket
+    pub fn padded_size(&self, plaintext_len: usize) -> MlsResult<usize> {
+        let content_size = HEADER_SIZE + plaintext_len;
+        if content_size > MAX_PADDED_SIZE {
+            return Err(MlsError::InvalidInput(format!(
+                "Message too large: {} bytes (max {})",
+                plaintext_len,
+                MAX_PADDED_SIZE - HEADER_SIZE
+            )));
+        }
+
+        match self {
+            Self::None => Ok(content_size),
+            Self::Buckets { sizes } => {
+                sizes.iter().find(|&&size| size >= content_size).copied().ok_or_else(|| {
+                    MlsError::InvalidInput(format!(
+                        "Message too large: {} bytes (largest bucket {})",
+                        plaintext_len,
+                        sizes.last().copied().unwrap_or(0)
+                    ))
+                })
+            }
+            Self::Padme => Ok(padme(content_size).min(MAX_PADDED_SIZE)),
+        }
+    }
+
+    /// Frame and pad a message
+    ///
+    /// # Errors
+    ///
+    /// `InvalidInput` if the message is empty or too large
+    pub fn pad(&self, plaintext: &[u8]) -> MlsResult<Vec<u8>> {
+        if plaintext.is_empty() {
+            return Err(MlsError::InvalidInput("Cannot pad empty message".to_string()));
+        }
+        Ok(frame(plaintext, self.padded_size(plaintext.len())?))
+    }
+}
+
+/// Compact encoding of a [`PaddingStrategy`] for bincode
+///
+/// The strategy's own encoding is internally tagged for config files, which
+/// bincode can't decode.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) enum StoredPadding {
+    None,
+    Buckets(Vec<usize>),
+    Padme,
+}
+
+impl From<&PaddingStrategy> for StoredPadding {
+    fn from(padding: &PaddingStrategy) -> Self {
+        match padding {
+            PaddingStrategy::None => Self::None,
+            PaddingStrategy::Buckets { sizes } => Self::Buckets(sizes.clone()),
+            PaddingStrategy::Padme => Self::Padme,
+        }
+    }
+}
+
+impl From<&StoredPadding> for PaddingStrategy {
+    fn from(padding: &StoredPadding) -> Self {
+        match padding {
+            StoredPadding::None => Self::None,
+            StoredPadding::Buckets(sizes) => Self::Buckets { sizes: sizes.clone() },
+            StoredPadding::Padme => Self::Padme,
+        }
+    }
+}
+
+/// Serde adapter storing an optional strategy as `StoredPadding`
+pub(crate) mod optional_stored {
+    use super::{PaddingStrategy, StoredPadding};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        padding: &Option<PaddingStrategy>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        padding.as_ref().map(StoredPadding::from).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<PaddingStrategy>, D::Error> {
+        Ok(Option::<StoredPadding>::deserialize(deserializer)?.as_ref().map(PaddingStrategy::from))
+    }
+}
+
+/// Padmé length for `len` bytes
+///
+/// With E = ⌊log₂ len⌋ and S = ⌊log₂ E⌋ + 1, the low E − S bits of the
+/// length are rounded up to zero.
+fn padme(len: usize) -> usize {
+    if len < 2 {
+        return len;
+    }
+    let e = usize::BITS - 1 - len.leading_zeros();
+    let s = u32::BITS - e.leading_zeros();
+    let mask = (1usize << (e - s)) - 1;
+    (len + mask) & !mask
+}
+
+/// Build [VERSION][LEN][PAYLOAD][PADDING] of `target_size` bytes
+fn frame(plaintext: &[u8], target_size: usize) -> Vec<u8> {
+    let mut padded = Vec::with_capacity(target_size);
+
+    // Write version
+    padded.push(PADDING_VERSION);
+
+    // Write original length (big-endian u32)
+    let len_bytes = (plaintext.len() as u32).to_be_bytes();
+    padded.extend_from_slice(&len_bytes);
+
+    // Write payload
+    padded.extend_from_slice(plaintext);
+
+    // Pad with zeros to reach target size
+    padded.resize(target_size, 0);
+
+    padded
+}
+
 /// Pad a message to the next bucket size
 ///
 /// # Arguments
@@ -75,18 +293,29 @@ const PADDING_VERSION: u8 = 0x01;
 /// assert_eq!(padded.len(), 256);
 /// ```
 pub fn pad_message(plaintext: &[u8]) -> MlsResult<Vec<u8>> {
+    pad_message_to(plaintext, PADDING_BUCKETS[0])
+}
+
+/// Pad a message to the next bucket size of at least `min_bucket`
+///
+/// Raising the smallest bucket hides more about short messages, at the
+/// cost of bandwidth. Receivers strip padding the same way regardless.
+///
+/// # Arguments
+///
+/// * `plaintext` - Original message bytes
+/// * `min_bucket` - Smallest padded size to use
+pub fn pad_message_to(plaintext: &[u8], min_bucket: usize) -> MlsResult<Vec<u8>> {
     if plaintext.is_empty() {
         return Err(MlsError::InvalidInput("Cannot pad empty message".to_string()));
     }
 
-    // Header: 1 byte version + 4 bytes length
-    const HEADER_SIZE: usize = 5;
     let content_size = HEADER_SIZE + plaintext.len();
 
     // Find the appropriate bucket
     let target_size = PADDING_BUCKETS
         .iter()
-        .find(|&&size| size >= content_size)
+        .find(|&&size| size >= content_size.max(min_bucket))
         .copied()
         .unwrap_or(MAX_PADDED_SIZE);
 
@@ -98,23 +327,7 @@ pub fn pad_message(plaintext: &[u8]) -> MlsResult<Vec<u8>> {
         )));
     }
 
-    // Build padded message: [VERSION][LEN][PAYLOAD][PADDING]
-    let mut padded = Vec::with_capacity(target_size);
-
-    // Write version
-    padded.push(PADDING_VERSION);
-
-    // Write original length (big-endian u32)
-    let len_bytes = (plaintext.len() as u32).to_be_bytes();
-    padded.extend_from_slice(&len_bytes);
-
-    // Write payload
-    padded.extend_from_slice(plaintext);
-
-    // Pad with zeros to reach target size
-    padded.resize(target_size, 0);
-
-    Ok(padded)
+    Ok(frame(plaintext, target_size))
 }
 
 /// Remove padding from a message
@@ -134,8 +347,6 @@ pub fn pad_message(plaintext: &[u8]) -> MlsResult<Vec<u8>> {
 /// - Invalid padding version
 /// - Claimed length exceeds message size
 pub fn unpad_message(padded: &[u8]) -> MlsResult<Vec<u8>> {
-    const HEADER_SIZE: usize = 5;
-
     if padded.len() < HEADER_SIZE {
         return Err(MlsError::InvalidInput(format!(
             "Message too short for padding header: {} bytes",
@@ -175,7 +386,6 @@ pub fn unpad_message(padded: &[u8]) -> MlsResult<Vec<u8>> {
 ///
 /// Useful for preallocation and bandwidth estimation
 pub fn get_padded_size(plaintext_len: usize) -> usize {
-    const HEADER_SIZE: usize = 5;
     let content_size = HEADER_SIZE + plaintext_len;
 
     PADDING_BUCKETS
@@ -189,6 +399,96 @@ pub fn get_padded_size(plaintext_len: usize) -> usize {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_pad_to_minimum_bucket() {
+        let padded = pad_message_to(b"Hello", 1024).unwrap();
+        assert_eq!(padded.len(), 1024);
+        assert_eq!(unpad_message(&padded).unwrap(), b"Hello");
+
+        // Larger messages still use the next bucket up
+        let msg = vec![b'a'; 2000];
+        assert_eq!(pad_message_to(&msg, 1024).unwrap().len(), 4096);
+    }
+
+    #[test]
+    fn test_stored_padding_bincode_roundtrip() {
+        #[derive(Serialize, Deserialize)]
+        struct Holder {
+            #[serde(with = "optional_stored")]
+            padding: Option<PaddingStrategy>,
+        }
+
+        for padding in [None, Some(PaddingStrategy::default()), Some(PaddingStrategy::Padme)] {
+            let bytes = bincode::serialize(&Holder { padding: padding.clone() }).unwrap();
+            let decoded: Holder = bincode::deserialize(&bytes).unwrap();
+            assert_eq!(decoded.padding, padding);
+        }
+    }
+
+    #[test]
+    fn test_strategies_roundtrip() {
+        let strategies = [
+            PaddingStrategy::None,
+            PaddingStrategy::default(),
+            PaddingStrategy::Buckets { sizes: vec![64, 512] },
+            PaddingStrategy::Padme,
+        ];
+        for strategy in &strategies {
+            for size in [1, 58, 59, 300, 507] {
+                let msg = vec![b'P'; size];
+                let padded = strategy.pad(&msg).unwrap();
+                assert_eq!(padded.len(), strategy.padded_size(size).unwrap());
+                assert_eq!(unpad_message(&padded).unwrap(), msg, "{:?} at {}", strategy, size);
+            }
+        }
+
+        assert_eq!(PaddingStrategy::None.pad(b"Hello").unwrap().len(), HEADER_SIZE + 5);
+        let custom = PaddingStrategy::Buckets { sizes: vec![64, 512] };
+        assert_eq!(custom.padded_size(59).unwrap(), 64);
+        assert_eq!(custom.padded_size(60).unwrap(), 512);
+        assert!(custom.pad(&[0; 600]).is_err());
+    }
+
+    #[test]
+    fn test_padme_sizes() {
+        // Lengths with E = ⌊log₂ L⌋ keep their top ⌊log₂ E⌋ + 1 bits
+        assert_eq!(padme(1), 1);
+        assert_eq!(padme(7), 7);
+        assert_eq!(padme(9), 10);
+        assert_eq!(padme(256), 256);
+        assert_eq!(padme(257), 272);
+        assert_eq!(padme(1000), 1024);
+        assert_eq!(padme(65000), 65536);
+
+        // Overhead stays around 12% at most
+        for len in [100usize, 1234, 5000, 40_000] {
+            let padded = PaddingStrategy::Padme.padded_size(len).unwrap();
+            assert!(padded >= len + HEADER_SIZE);
+            assert!((padded as f64) < (len + HEADER_SIZE) as f64 * 1.13);
+        }
+    }
+
+    #[test]
+    fn test_validate_buckets() {
+        assert!(PaddingStrategy::default().validate().is_ok());
+        assert!(PaddingStrategy::Padme.validate().is_ok());
+        assert!(PaddingStrategy::Buckets { sizes: vec![128, 4096] }.validate().is_ok());
+
+        for sizes in [vec![], vec![1024, 256], vec![256, 256], vec![4], vec![256, 70_000]] {
+            assert!(PaddingStrategy::Buckets { sizes }.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_with_min_bucket() {
+        let strategy = PaddingStrategy::default().with_min_bucket(4096);
+        assert_eq!(strategy, PaddingStrategy::Buckets { sizes: vec![4096, 16384, 65536] });
+
+        let strategy = PaddingStrategy::Buckets { sizes: vec![64, 128] }.with_min_bucket(256);
+        assert_eq!(strategy, PaddingStrategy::Buckets { sizes: vec![128] });
+        assert_eq!(PaddingStrategy::Padme.with_min_bucket(1024), PaddingStrategy::Padme);
+    }
+
     #[test]
     fn test_pad_small_message() {
         let msg = b"Hello";
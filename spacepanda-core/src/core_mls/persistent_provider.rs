@@ -7,7 +7,8 @@ use crate::core_mls::errors::{MlsError, MlsResult};
 use openmls_memory_storage::MemoryStorage;
 use openmls_rust_crypto::RustCrypto;
 use openmls_traits::OpenMlsProvider;
-use std::fs::File;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 
 /// Persistent OpenMLS provider that can save/load state to/from disk
@@ -60,15 +61,30 @@ impl PersistentProvider {
     }
 
     /// Save the current storage state to disk
+    ///
+    /// The file is overwritten in place: its old contents are zeroed before
+    /// the new state is written, so key material deleted from memory (e.g.
+    /// purged epochs) doesn't linger in blocks the shorter file no longer
+    /// uses. Copies kept by a journaling filesystem or SSD wear levelling
+    /// are out of reach.
     pub fn save(&self) -> MlsResult<()> {
         tracing::debug!("Saving OpenMLS storage to {:?}", self.storage_path);
 
-        let file = File::create(&self.storage_path)
-            .map_err(|e| MlsError::Storage(format!("Failed to create storage file: {}", e)))?;
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.storage_path)
+            .map_err(|e| MlsError::Storage(format!("Failed to open storage file: {}", e)))?;
+
+        scrub(&mut file)
+            .map_err(|e| MlsError::Storage(format!("Failed to scrub storage file: {}", e)))?;
 
         self.storage
             .save_to_file(&file)
             .map_err(|e| MlsError::Storage(format!("Failed to save storage: {}", e)))?;
+        file.sync_data()
+            .map_err(|e| MlsError::Storage(format!("Failed to sync storage file: {}", e)))?;
 
         tracing::debug!("Successfully saved OpenMLS storage");
         Ok(())
@@ -80,6 +96,22 @@ impl PersistentProvider {
     }
 }
 
+/// Overwrite a file's contents with zeros, then empty it
+fn scrub(file: &mut File) -> io::Result<()> {
+    let len = file.metadata()?.len();
+    let zeros = [0u8; 4096];
+    let mut remaining = len;
+    while remaining > 0 {
+        let n = remaining.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..n])?;
+        remaining -= n as u64;
+    }
+    file.sync_data()?;
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(())
+}
+
 impl OpenMlsProvider for PersistentProvider {
     type CryptoProvider = RustCrypto;
     type RandProvider = RustCrypto;
@@ -112,6 +144,9 @@ impl Clone for PersistentProvider {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core_mls::engine::{GroupOperations, OpenMlsEngine};
+    use crate::core_mls::types::{GroupId, MlsConfig};
+    use std::sync::Arc;
     use tempfile::TempDir;
 
     #[test]
@@ -141,4 +176,40 @@ mod tests {
         let provider2 = PersistentProvider::new(&storage_path).unwrap();
         assert_eq!(provider2.storage_path(), storage_path);
     }
+
+    #[tokio::test]
+    async fn test_storage_size_bounded_across_epochs() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage_path = temp_dir.path().join("test_storage.json");
+        let provider = Arc::new(PersistentProvider::new(&storage_path).unwrap());
+
+        let config = MlsConfig::default();
+        let retention = config.past_epoch_retention;
+        let engine = OpenMlsEngine::create_group(
+            GroupId::random(),
+            b"alice".to_vec(),
+            config,
+            provider.clone(),
+        )
+        .await
+        .unwrap();
+
+        let mut sizes = Vec::new();
+        for _ in 0..30 {
+            engine.self_update().await.unwrap();
+            provider.save().unwrap();
+            sizes.push(std::fs::metadata(&storage_path).unwrap().len());
+        }
+
+        // Once the retention window is full, each epoch's material replaces
+        // the oldest one's (allow for epoch numbers growing a digit)
+        let settled = sizes[retention + 2];
+        assert!(sizes.iter().skip(retention + 2).all(|&size| size <= settled + 256));
+        assert_eq!(engine.purge_old_epochs().await.unwrap(), 0);
+
+        // What's left still loads
+        let reloaded = PersistentProvider::new(&storage_path).unwrap();
+        let group_id = openmls::group::GroupId::from_slice(engine.group_id().await.as_bytes());
+        assert!(openmls::group::MlsGroup::load(reloaded.storage(), &group_id).unwrap().is_some());
+    }
 }
@@ -235,6 +235,17 @@ impl ProposalQueue {
     pub fn by_sender(&self, sender: u32) -> Vec<&Proposal> {
         self.proposals.iter().filter(|p| p.sender == sender).collect()
     }
+
+    /// IDs of the PSKs proposed for injection, in proposal order
+    pub fn psk_ids(&self) -> Vec<Vec<u8>> {
+        self.proposals
+            .iter()
+            .filter_map(|p| match &p.content {
+                ProposalContent::PreSharedKey { psk_id } => Some(psk_id.clone()),
+                _ => None,
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
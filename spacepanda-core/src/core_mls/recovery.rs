@@ -0,0 +1,492 @@
+//! Crash recovery of group state
+//!
+//! The provider storage only ever holds a group's latest state; if it is
+//! lost or damaged, so is the group. Services with storage therefore also
+//! keep, for every group, under `<storage>/recovery/<group>/`:
+//!
+//! - snapshots: the group's [`GroupSnapshot`] with the provider storage
+//!   attached under [`PROVIDER_STATE_KEY`], signed with our leaf key. One
+//!   is written every `MlsConfig::snapshot_interval` commits and after each
+//!   commit of our own, which OpenMLS can't replay. The last
+//!   [`SNAPSHOTS_KEPT`] are kept, and only while their epoch is within
+//!   the `MlsConfig::past_epoch_retention` window: a snapshot holds the
+//!   key material of its epoch, which the provider storage purges once
+//!   the epoch leaves the window.
+//! - `commits.log`: an append-only log of the commits merged since the
+//!   oldest kept snapshot.
+//!
+//! Every snapshot and log record is sealed with AES-256-GCM under a key
+//! kept in the identity keystore, with the group ID and epoch as
+//! associated data. A truncated or overwritten file fails authentication
+//! (`MlsError::VerifyFailed`) before anything is decoded; recovery then
+//! falls back to an older snapshot, and replay stops at the first damaged
+//! log record.
+
+use crate::core_identity::keystore::{Keystore, KeystoreError};
+use crate::core_mls::errors::{MlsError, MlsResult};
+use crate::core_mls::state::GroupSnapshot;
+use crate::core_mls::types::GroupId;
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+/// Snapshot metadata entry holding the provider storage
+pub const PROVIDER_STATE_KEY: &str = "provider_state";
+
+/// Snapshots kept per group
+pub const SNAPSHOTS_KEPT: usize = 2;
+
+/// Keystore secret holding the sealing key
+pub const KEY_SECRET: &str = "mls_recovery_key";
+
+/// Where the sealing key used to be kept, next to what it seals
+const LEGACY_KEY_FILE: &str = "recovery.key";
+const COMMIT_LOG: &str = "commits.log";
+const SNAPSHOT_PREFIX: &str = "snapshot-";
+
+/// Size of the sealing key (AES-256)
+const KEY_SIZE: usize = 32;
+
+/// Size of the AES-GCM nonce
+const NONCE_SIZE: usize = 12;
+
+/// A group snapshot signed by the member that wrote it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedSnapshot {
+    snapshot: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+impl SignedSnapshot {
+    /// Sign a snapshot with our leaf key
+    pub fn new(
+        snapshot: &GroupSnapshot,
+        sign: impl FnOnce(&[u8]) -> MlsResult<Vec<u8>>,
+    ) -> MlsResult<Self> {
+        let snapshot = snapshot.to_bytes()?;
+        let signature = sign(&snapshot)?;
+        Ok(Self { snapshot, signature })
+    }
+
+    /// Decode the snapshot if it was signed by its own leaf
+    ///
+    /// `verify_fn` gets the signature key, the signed bytes and the
+    /// signature.
+    pub fn verify(
+        &self,
+        verify_fn: impl FnOnce(&[u8], &[u8], &[u8]) -> bool,
+    ) -> MlsResult<GroupSnapshot> {
+        let snapshot = GroupSnapshot::from_bytes(&self.snapshot)?;
+        let own_leaf = snapshot
+            .members
+            .iter()
+            .find(|member| member.leaf_index == snapshot.own_leaf_index)
+            .ok_or_else(|| {
+                MlsError::VerifyFailed("Recovery snapshot has no own leaf".to_string())
+            })?;
+        if !verify_fn(&own_leaf.signature_key, &self.snapshot, &self.signature) {
+            return Err(MlsError::VerifyFailed("Recovery snapshot signature invalid".to_string()));
+        }
+        Ok(snapshot)
+    }
+}
+
+/// Recovery snapshots and commit logs of all groups
+pub struct RecoveryLog {
+    dir: PathBuf,
+    key: Zeroizing<Vec<u8>>,
+}
+
+impl RecoveryLog {
+    /// Open a recovery directory, creating it on first use
+    ///
+    /// The sealing key is read from `keystore`, or created there on first
+    /// use. A key left in the directory by an older version is moved into
+    /// the keystore.
+    pub fn open(dir: impl Into<PathBuf>, keystore: &dyn Keystore) -> MlsResult<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        let legacy_path = dir.join(LEGACY_KEY_FILE);
+        let key = match keystore.get_secret(KEY_SECRET) {
+            Ok(key) => Zeroizing::new(key),
+            Err(KeystoreError::NotFound(_)) => {
+                let key = match fs::read(&legacy_path) {
+                    Ok(key) => Zeroizing::new(key),
+                    Err(e) if e.kind() == ErrorKind::NotFound => {
+                        let mut key = Zeroizing::new(vec![0u8; KEY_SIZE]);
+                        rand::rng().fill_bytes(&mut key);
+                        key
+                    }
+                    Err(e) => return Err(e.into()),
+                };
+                keystore.put_secret(KEY_SECRET, &key).map_err(keystore_error)?;
+                key
+            }
+            Err(e) => return Err(keystore_error(e)),
+        };
+        if key.len() != KEY_SIZE {
+            return Err(MlsError::PersistenceError("Recovery key is damaged".to_string()));
+        }
+        match fs::remove_file(&legacy_path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+
+        Ok(Self { dir, key })
+    }
+
+    /// Store a snapshot of `group_id` at `epoch`
+    ///
+    /// Then prunes as [`prune`](Self::prune) does.
+    pub fn write_snapshot(
+        &self,
+        group_id: &GroupId,
+        epoch: u64,
+        snapshot: &SignedSnapshot,
+        keep_from: u64,
+    ) -> MlsResult<()> {
+        let dir = self.group_dir(group_id);
+        fs::create_dir_all(&dir)?;
+
+        let plaintext = Zeroizing::new(bincode::serialize(snapshot)?);
+        let sealed = self.seal(&snapshot_aad(group_id, epoch), &plaintext)?;
+        write_atomic(&dir.join(snapshot_file(epoch)), &sealed)?;
+
+        self.prune(group_id, keep_from)
+    }
+
+    /// Delete the snapshots of `group_id` before epoch `keep_from`, or
+    /// beyond the newest [`SNAPSHOTS_KEPT`]
+    ///
+    /// Log records only the deleted snapshots needed go with them.
+    pub fn prune(&self, group_id: &GroupId, keep_from: u64) -> MlsResult<()> {
+        let dir = self.group_dir(group_id);
+        let epochs = self.snapshot_epochs(group_id);
+        let kept = epochs
+            .iter()
+            .take(SNAPSHOTS_KEPT)
+            .take_while(|epoch| **epoch >= keep_from)
+            .count();
+        for old in &epochs[kept..] {
+            fs::remove_file(dir.join(snapshot_file(*old)))?;
+        }
+        match epochs[..kept].last() {
+            Some(oldest) => self.prune_commits(group_id, *oldest),
+            None => self.prune_commits(group_id, keep_from),
+        }
+    }
+
+    /// Epochs of the stored snapshots of `group_id`, newest first
+    pub fn snapshot_epochs(&self, group_id: &GroupId) -> Vec<u64> {
+        let Ok(entries) = fs::read_dir(self.group_dir(group_id)) else {
+            return Vec::new();
+        };
+        let mut epochs: Vec<u64> = entries
+            .filter_map(|entry| {
+                let name = entry.ok()?.file_name();
+                name.to_str()?.strip_prefix(SNAPSHOT_PREFIX)?.parse().ok()
+            })
+            .collect();
+        epochs.sort_unstable_by(|a, b| b.cmp(a));
+        epochs
+    }
+
+    /// Read the snapshot of `group_id` at `epoch`
+    ///
+    /// # Errors
+    ///
+    /// `VerifyFailed` if the file was damaged.
+    pub fn open_snapshot(&self, group_id: &GroupId, epoch: u64) -> MlsResult<SignedSnapshot> {
+        let sealed = fs::read(self.group_dir(group_id).join(snapshot_file(epoch)))?;
+        let plaintext = self.unseal(&snapshot_aad(group_id, epoch), &sealed)?;
+        Ok(bincode::deserialize(&plaintext)?)
+    }
+
+    /// Append a merged commit for `epoch` to the log of `group_id`
+    ///
+    /// A damaged tail left by a crash is cut off first.
+    pub fn append_commit(&self, group_id: &GroupId, epoch: u64, commit: &[u8]) -> MlsResult<()> {
+        let dir = self.group_dir(group_id);
+        fs::create_dir_all(&dir)?;
+
+        let path = dir.join(COMMIT_LOG);
+        let (_, valid_len) = self.read_commits(group_id)?;
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        if file.metadata()?.len() > valid_len as u64 {
+            file.set_len(valid_len as u64)?;
+        }
+
+        let plaintext = [epoch.to_be_bytes().as_slice(), commit].concat();
+        let sealed = self.seal(&commit_aad(group_id), &plaintext)?;
+        file.write_all(&(sealed.len() as u32).to_be_bytes())?;
+        file.write_all(&sealed)?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    /// Logged commits of `group_id` from `epoch` on, in epoch order
+    ///
+    /// Reading stops at the first damaged record. If a resolved fork logged
+    /// two commits for an epoch, the later one is returned.
+    pub fn commits_since(&self, group_id: &GroupId, epoch: u64) -> Vec<Vec<u8>> {
+        let records = match self.read_commits(group_id) {
+            Ok((records, _)) => records,
+            Err(_) => return Vec::new(),
+        };
+        let mut commits = BTreeMap::new();
+        for record in records.into_iter().filter(|record| record.epoch >= epoch) {
+            commits.insert(record.epoch, record.commit);
+        }
+        commits.into_values().collect()
+    }
+
+    /// Delete everything stored for `group_id`
+    pub fn remove(&self, group_id: &GroupId) -> MlsResult<()> {
+        match fs::remove_dir_all(self.group_dir(group_id)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn group_dir(&self, group_id: &GroupId) -> PathBuf {
+        self.dir.join(group_id.to_hex())
+    }
+
+    /// Drop log records older than `epoch`
+    fn prune_commits(&self, group_id: &GroupId, epoch: u64) -> MlsResult<()> {
+        let (records, _) = self.read_commits(group_id)?;
+        if records.iter().all(|record| record.epoch >= epoch) {
+            return Ok(());
+        }
+        let kept: Vec<u8> = records
+            .iter()
+            .filter(|record| record.epoch >= epoch)
+            .flat_map(|record| record.raw.iter().copied())
+            .collect();
+        write_atomic(&self.group_dir(group_id).join(COMMIT_LOG), &kept)
+    }
+
+    /// Intact records of the log, and the length they take up
+    fn read_commits(&self, group_id: &GroupId) -> MlsResult<(Vec<LogRecord>, usize)> {
+        let bytes = match fs::read(self.group_dir(group_id).join(COMMIT_LOG)) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok((Vec::new(), 0)),
+            Err(e) => return Err(e.into()),
+        };
+
+        let aad = commit_aad(group_id);
+        let mut records = Vec::new();
+        let mut offset = 0;
+        while let Some(len_bytes) = bytes.get(offset..offset + 4) {
+            let len = u32::from_be_bytes(len_bytes.try_into().expect("4 bytes")) as usize;
+            let Some(sealed) = bytes.get(offset + 4..offset + 4 + len) else {
+                break;
+            };
+            let Ok(plaintext) = self.unseal(&aad, sealed) else {
+                break;
+            };
+            let Some((epoch, commit)) = plaintext.split_first_chunk::<8>() else {
+                break;
+            };
+            records.push(LogRecord {
+                epoch: u64::from_be_bytes(*epoch),
+                commit: commit.to_vec(),
+                raw: bytes[offset..offset + 4 + len].to_vec(),
+            });
+            offset += 4 + len;
+        }
+        Ok((records, offset))
+    }
+
+    fn seal(&self, aad: &[u8], plaintext: &[u8]) -> MlsResult<Vec<u8>> {
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.key));
+        let mut nonce = [0u8; NONCE_SIZE];
+        rand::rng().fill_bytes(&mut nonce);
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad })
+            .map_err(|e| MlsError::CryptoError(format!("Failed to seal recovery data: {}", e)))?;
+        Ok([nonce.as_slice(), &ciphertext].concat())
+    }
+
+    fn unseal(&self, aad: &[u8], sealed: &[u8]) -> MlsResult<Zeroizing<Vec<u8>>> {
+        if sealed.len() < NONCE_SIZE {
+            return Err(MlsError::VerifyFailed("Recovery data is truncated".to_string()));
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_SIZE);
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.key));
+        cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+            .map(Zeroizing::new)
+            .map_err(|_| MlsError::VerifyFailed("Recovery data failed authentication".to_string()))
+    }
+}
+
+/// A commit log record
+struct LogRecord {
+    epoch: u64,
+    commit: Vec<u8>,
+    /// The record as stored, for rewriting the log
+    raw: Vec<u8>,
+}
+
+fn snapshot_file(epoch: u64) -> String {
+    format!("{}{:020}", SNAPSHOT_PREFIX, epoch)
+}
+
+fn snapshot_aad(group_id: &GroupId, epoch: u64) -> Vec<u8> {
+    [b"snapshot".as_slice(), group_id.as_bytes(), &epoch.to_be_bytes()].concat()
+}
+
+fn commit_aad(group_id: &GroupId) -> Vec<u8> {
+    [b"commit".as_slice(), group_id.as_bytes()].concat()
+}
+
+fn keystore_error(e: KeystoreError) -> MlsError {
+    MlsError::PersistenceError(format!("Recovery key: {}", e))
+}
+
+/// Write a file so that a crash leaves either the old or the new content
+fn write_atomic(path: &Path, bytes: &[u8]) -> MlsResult<()> {
+    let temp_path = path.with_extension("tmp");
+    let mut file = fs::File::create(&temp_path)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core_identity::keystore::memory_keystore::MemoryKeystore;
+    use crate::core_mls::types::{MemberInfo, MemberRole};
+    use tempfile::TempDir;
+
+    fn group() -> GroupId {
+        GroupId::new(b"general".to_vec())
+    }
+
+    fn signed_snapshot(epoch: u64) -> SignedSnapshot {
+        let member = MemberInfo {
+            identity: b"alice".to_vec(),
+            leaf_index: 0,
+            joined_at: 0,
+            role: MemberRole::Admin,
+            signature_key: b"alice key".to_vec(),
+        };
+        let snapshot = GroupSnapshot::new(group(), epoch, vec![], vec![], vec![member], 0);
+        SignedSnapshot::new(&snapshot, |bytes| Ok([b"alice key".as_slice(), bytes].concat()))
+            .unwrap()
+    }
+
+    fn signed_by(key: &[u8], bytes: &[u8], signature: &[u8]) -> bool {
+        signature == [key, bytes].concat()
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let keystore = MemoryKeystore::new();
+        let log = RecoveryLog::open(dir.path(), &keystore).unwrap();
+        log.write_snapshot(&group(), 3, &signed_snapshot(3), 0).unwrap();
+
+        // The key survives reopening, and isn't kept with the snapshots
+        let log = RecoveryLog::open(dir.path(), &keystore).unwrap();
+        assert!(!dir.path().join(LEGACY_KEY_FILE).exists());
+        assert_eq!(log.snapshot_epochs(&group()), vec![3]);
+        let snapshot = log.open_snapshot(&group(), 3).unwrap().verify(signed_by).unwrap();
+        assert_eq!(snapshot.epoch, 3);
+
+        let forged = log.open_snapshot(&group(), 3).unwrap().verify(|_, _, _| false);
+        assert!(matches!(forged, Err(MlsError::VerifyFailed(_))));
+    }
+
+    #[test]
+    fn test_damaged_snapshot_fails_authentication() {
+        let dir = TempDir::new().unwrap();
+        let log = RecoveryLog::open(dir.path(), &MemoryKeystore::new()).unwrap();
+        log.write_snapshot(&group(), 3, &signed_snapshot(3), 0).unwrap();
+        let path = log.group_dir(&group()).join(snapshot_file(3));
+        let bytes = fs::read(&path).unwrap();
+
+        for len in [0, NONCE_SIZE / 2, NONCE_SIZE + 1, bytes.len() - 1] {
+            fs::write(&path, &bytes[..len]).unwrap();
+            assert!(matches!(log.open_snapshot(&group(), 3), Err(MlsError::VerifyFailed(_))));
+        }
+
+        // Renamed to another epoch
+        fs::write(log.group_dir(&group()).join(snapshot_file(4)), &bytes).unwrap();
+        assert!(matches!(log.open_snapshot(&group(), 4), Err(MlsError::VerifyFailed(_))));
+    }
+
+    #[test]
+    fn test_commit_log_stops_at_torn_record() {
+        let dir = TempDir::new().unwrap();
+        let log = RecoveryLog::open(dir.path(), &MemoryKeystore::new()).unwrap();
+        log.append_commit(&group(), 1, b"commit 1").unwrap();
+        log.append_commit(&group(), 2, b"commit 2").unwrap();
+
+        // A crash while appending the third record
+        let path = log.group_dir(&group()).join(COMMIT_LOG);
+        let intact = fs::read(&path).unwrap().len();
+        log.append_commit(&group(), 3, b"commit 3").unwrap();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(intact as u64 + 10).unwrap();
+        assert_eq!(
+            log.commits_since(&group(), 0),
+            vec![b"commit 1".to_vec(), b"commit 2".to_vec()]
+        );
+
+        // The torn tail is cut off before appending again
+        log.append_commit(&group(), 3, b"commit 3'").unwrap();
+        assert_eq!(
+            log.commits_since(&group(), 2),
+            vec![b"commit 2".to_vec(), b"commit 3'".to_vec()]
+        );
+    }
+
+    #[test]
+    fn test_old_snapshots_and_commits_are_pruned() {
+        let dir = TempDir::new().unwrap();
+        let log = RecoveryLog::open(dir.path(), &MemoryKeystore::new()).unwrap();
+        for epoch in 1..=6 {
+            log.append_commit(&group(), epoch, &epoch.to_be_bytes()).unwrap();
+            if epoch % 2 == 0 {
+                log.write_snapshot(&group(), epoch, &signed_snapshot(epoch), 0).unwrap();
+            }
+        }
+
+        assert_eq!(log.snapshot_epochs(&group()), vec![6, 4]);
+        let commits = log.commits_since(&group(), 0);
+        let expected: Vec<Vec<u8>> = (4u64..=6).map(|epoch| epoch.to_be_bytes().to_vec()).collect();
+        assert_eq!(commits, expected);
+
+        // Epoch 4 leaves the retention window
+        log.append_commit(&group(), 7, &7u64.to_be_bytes()).unwrap();
+        log.prune(&group(), 5).unwrap();
+        assert_eq!(log.snapshot_epochs(&group()), vec![6]);
+        let expected: Vec<Vec<u8>> = (6u64..=7).map(|epoch| epoch.to_be_bytes().to_vec()).collect();
+        assert_eq!(log.commits_since(&group(), 0), expected);
+    }
+
+    #[test]
+    fn test_key_moves_into_the_keystore() {
+        let dir = TempDir::new().unwrap();
+        let key = vec![7u8; KEY_SIZE];
+        fs::write(dir.path().join(LEGACY_KEY_FILE), &key).unwrap();
+
+        let keystore = MemoryKeystore::new();
+        RecoveryLog::open(dir.path(), &keystore).unwrap();
+        assert_eq!(keystore.get_secret(KEY_SECRET).unwrap(), key);
+        assert!(!dir.path().join(LEGACY_KEY_FILE).exists());
+    }
+}
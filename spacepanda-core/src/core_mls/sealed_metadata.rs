@@ -239,12 +239,14 @@ mod tests {
                     leaf_index: 0,
                     joined_at: 1000,
                     role: MemberRole::Admin,
+                    signature_key: vec![0xa1; 32],
                 },
                 MemberInfo {
                     identity: b"bob".to_vec(),
                     leaf_index: 1,
                     joined_at: 1001,
                     role: MemberRole::Member,
+                    signature_key: vec![0xb0; 32],
                 },
             ],
             created_at: 1000,
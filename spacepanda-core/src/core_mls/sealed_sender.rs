@@ -42,6 +42,8 @@
 //! - **Domain Separation**: "Sealed Sender v1" label
 //! - **Nonce**: Random 12 bytes (never reused)
 //! - **AAD**: Epoch number (binds sender to specific group state)
+//! - **Rotation**: A new key per epoch, re-derived by the engine on every
+//!   merged commit; see [`SenderKeyRing`]
 //!
 //! ## Format
 //!
@@ -93,6 +95,9 @@ const NONCE_SIZE: usize = 12;
 /// AES-256 key size (256 bits)
 const KEY_SIZE: usize = 32;
 
+/// Exporter label the sender key's group secret is exported with
+pub const SENDER_KEY_LABEL: &str = "sender_key";
+
 /// Sealed sender structure - encrypts sender identity
 ///
 /// This structure contains an encrypted sender identity that only group
@@ -164,6 +169,57 @@ pub fn derive_sender_key(group_secret: &[u8]) -> [u8; KEY_SIZE] {
     key
 }
 
+/// Sender keys of a group's current epoch and the epoch before it
+///
+/// Each key is derived from its epoch's exporter secret, so a member
+/// removed by a commit can't derive the keys that follow it. The previous
+/// epoch's key is kept only for envelopes sealed just before a commit;
+/// anything older is refused rather than tried.
+#[derive(Debug, Default)]
+pub struct SenderKeyRing {
+    current: Option<(u64, [u8; KEY_SIZE])>,
+    previous: Option<(u64, [u8; KEY_SIZE])>,
+}
+
+impl SenderKeyRing {
+    /// Move to the key of `epoch`, keeping the current one if it directly precedes it
+    ///
+    /// Ignored if `epoch` isn't past the current epoch.
+    pub fn advance(&mut self, epoch: u64, key: [u8; KEY_SIZE]) {
+        if self.current.is_some_and(|(current, _)| current >= epoch) {
+            return;
+        }
+        self.previous = self.current.take().filter(|&(current, _)| current + 1 == epoch);
+        self.current = Some((epoch, key));
+    }
+
+    /// Epoch and key in use, if one was derived
+    pub fn current(&self) -> Option<(u64, [u8; KEY_SIZE])> {
+        self.current
+    }
+
+    /// Key for envelopes sealed in `epoch`
+    ///
+    /// # Errors
+    ///
+    /// - `EpochTooOld` if `epoch` is more than one epoch back
+    /// - `EpochMismatch` if `epoch` is ahead of the current epoch
+    /// - `InvalidState` if no key was derived yet
+    pub fn get(&self, epoch: u64) -> MlsResult<[u8; KEY_SIZE]> {
+        let Some((current, key)) = self.current else {
+            return Err(MlsError::InvalidState("No sender key derived yet".to_string()));
+        };
+        if epoch == current {
+            return Ok(key);
+        }
+        match self.previous {
+            Some((previous, key)) if previous == epoch => Ok(key),
+            _ if epoch < current => Err(MlsError::EpochTooOld { current, epoch, window: 1 }),
+            _ => Err(MlsError::EpochMismatch { expected: current, actual: epoch }),
+        }
+    }
+}
+
 /// Seal a sender identity for network transmission
 ///
 /// Encrypts the sender identity using AES-256-GCM so only group members
@@ -470,4 +526,30 @@ mod tests {
         let unsealed = unseal_sender(&sealed, &key, epoch).expect("Unsealing should succeed");
         assert_eq!(unsealed, long);
     }
+
+    #[test]
+    fn test_key_ring_keeps_one_epoch_back() {
+        let mut ring = SenderKeyRing::default();
+        assert!(matches!(ring.get(0), Err(MlsError::InvalidState(_))));
+
+        ring.advance(3, [3; KEY_SIZE]);
+        ring.advance(4, [4; KEY_SIZE]);
+        assert_eq!(ring.current(), Some((4, [4; KEY_SIZE])));
+        assert_eq!(ring.get(4).unwrap(), [4; KEY_SIZE]);
+        assert_eq!(ring.get(3).unwrap(), [3; KEY_SIZE]);
+        assert!(matches!(ring.get(5), Err(MlsError::EpochMismatch { .. })));
+
+        // Stale or repeated epochs don't move the ring back
+        ring.advance(4, [9; KEY_SIZE]);
+        ring.advance(2, [9; KEY_SIZE]);
+        assert_eq!(ring.get(4).unwrap(), [4; KEY_SIZE]);
+
+        ring.advance(5, [5; KEY_SIZE]);
+        assert!(matches!(ring.get(3), Err(MlsError::EpochTooOld { window: 1, .. })));
+
+        // Skipping an epoch leaves nothing to fall back to
+        ring.advance(7, [7; KEY_SIZE]);
+        assert!(matches!(ring.get(5), Err(MlsError::EpochTooOld { .. })));
+        assert!(matches!(ring.get(6), Err(MlsError::EpochTooOld { .. })));
+    }
 }
@@ -0,0 +1,536 @@
+//! Chaos Testing for the MLS Inbound Pipeline
+//!
+//! Injects malformed input at every layer of the inbound path
+//! (envelope decoding -> metadata checks -> sealed sender -> OpenMLS) while a
+//! four-party group exchanges application messages and commits.
+//!
+//! Fault classes:
+//! - Truncated envelopes and payloads
+//! - Wrong group IDs
+//! - Stale epochs (replayed traffic from earlier epochs)
+//! - Future epochs
+//! - Bit-flipped ciphertexts
+//! - Tampered sealed sender
+//! - Duplicated messages and commits
+//! - Shuffled delivery order
+//! - Oversized payloads
+//! - Random garbage
+//!
+//! Invariants checked after every round:
+//! - No panics
+//! - Every fault is rejected with a typed `MlsError` (never `Internal`)
+//! - Honest members stay on the same epoch and exporter secret
+//! - Every honest message is still delivered exactly once
+//! - Provider storage does not grow with the amount of garbage received
+//!
+//! Run with `cargo test chaos_`.
+
+#[cfg(test)]
+mod tests {
+    use crate::core_mls::{
+        engine::{group_ops::GroupOperations, openmls_engine::OpenMlsEngine},
+        errors::{MlsError, MlsResult},
+        messages::{
+            inbound::{InboundHandler, MessageContent},
+            outbound::OutboundBuilder,
+            EncryptedEnvelope,
+        },
+        types::{GroupId, MlsConfig},
+    };
+    use openmls::prelude::*;
+    use openmls_basic_credential::SignatureKeyPair;
+    use openmls_rust_crypto::OpenMlsRustCrypto;
+    use rand::rngs::StdRng;
+    use rand::seq::{IndexedRandom, SliceRandom};
+    use rand::{Rng, RngCore, SeedableRng};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tls_codec::Serialize as TlsSerializeTrait;
+
+    /// Maximum epoch drift accepted by the pipeline
+    const MAX_EPOCH_DRIFT: u64 = 1;
+
+    /// Size of the garbage appended for oversized payloads
+    const OVERSIZED_BYTES: usize = 1024 * 1024;
+
+    /// Class of fault injected into inbound traffic
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    enum Fault {
+        TruncatedEnvelope,
+        TruncatedPayload,
+        WrongGroupId,
+        StaleEpoch,
+        FutureEpoch,
+        BitFlippedCiphertext,
+        TamperedSealedSender,
+        Duplicate,
+        Shuffle,
+        Oversized,
+        Garbage,
+    }
+
+    const ALL_FAULTS: [Fault; 11] = [
+        Fault::TruncatedEnvelope,
+        Fault::TruncatedPayload,
+        Fault::WrongGroupId,
+        Fault::StaleEpoch,
+        Fault::FutureEpoch,
+        Fault::BitFlippedCiphertext,
+        Fault::TamperedSealedSender,
+        Fault::Duplicate,
+        Fault::Shuffle,
+        Fault::Oversized,
+        Fault::Garbage,
+    ];
+
+    /// Which faults to inject and how often
+    #[derive(Debug, Clone)]
+    struct ChaosConfig {
+        /// Fault classes with the probability of injecting them per delivery
+        faults: Vec<(Fault, f64)>,
+        /// Number of rounds (each round: messages from everyone, then one commit)
+        rounds: usize,
+        /// Application messages each member sends per round
+        messages_per_member: usize,
+        /// RNG seed, so failures are reproducible
+        seed: u64,
+    }
+
+    impl ChaosConfig {
+        /// Inject a single fault class on every delivery
+        fn single(fault: Fault) -> Self {
+            Self { faults: vec![(fault, 1.0)], rounds: 3, messages_per_member: 3, seed: 0x5eed }
+        }
+
+        /// Inject every fault class at the given rate
+        fn mixed(rate: f64, rounds: usize, seed: u64) -> Self {
+            Self {
+                faults: ALL_FAULTS.iter().map(|f| (*f, rate)).collect(),
+                rounds,
+                messages_per_member: 3,
+                seed,
+            }
+        }
+
+        fn rate(&self, fault: Fault) -> f64 {
+            self.faults.iter().find(|(f, _)| *f == fault).map_or(0.0, |(_, rate)| *rate)
+        }
+    }
+
+    /// Counters reported by a chaos run
+    #[derive(Debug, Default)]
+    struct ChaosStats {
+        injected: HashMap<Fault, usize>,
+        honest_delivered: usize,
+    }
+
+    struct Member {
+        name: Vec<u8>,
+        provider: Arc<OpenMlsRustCrypto>,
+        engine: OpenMlsEngine<OpenMlsRustCrypto>,
+        outbound: OutboundBuilder,
+        /// Plaintexts decrypted from honest traffic, in arrival order
+        received: Vec<Vec<u8>>,
+    }
+
+    struct ChaosHarness {
+        group_id: GroupId,
+        members: Vec<Member>,
+        handler: InboundHandler,
+        config: ChaosConfig,
+        rng: StdRng,
+        /// Honest envelopes from earlier epochs, replayed as stale traffic
+        history: Vec<EncryptedEnvelope>,
+        stats: ChaosStats,
+    }
+
+    /// Build a key package for a joining member on its own provider
+    fn key_package(identity: &[u8], provider: &OpenMlsRustCrypto) -> KeyPackageBundle {
+        let ciphersuite = Ciphersuite::MLS_128_DHKEMX25519_AES128GCM_SHA256_Ed25519;
+        let signature_keys = SignatureKeyPair::new(ciphersuite.signature_algorithm()).unwrap();
+        signature_keys.store(provider.storage()).unwrap();
+
+        let credential = CredentialWithKey {
+            credential: BasicCredential::new(identity.to_vec()).into(),
+            signature_key: signature_keys.public().into(),
+        };
+
+        KeyPackage::builder()
+            .build(ciphersuite, provider, &signature_keys, credential)
+            .unwrap()
+    }
+
+    impl ChaosHarness {
+        /// Set up alice, bob, carol and dave in one group
+        async fn four_party(config: ChaosConfig) -> Self {
+            let group_id = GroupId::random();
+            let alice_provider = Arc::new(OpenMlsRustCrypto::default());
+            let alice = OpenMlsEngine::create_group(
+                group_id.clone(),
+                b"alice".to_vec(),
+                MlsConfig::default(),
+                alice_provider.clone(),
+            )
+            .await
+            .unwrap();
+
+            let joiners: Vec<(&[u8], Arc<OpenMlsRustCrypto>)> = [&b"bob"[..], b"carol", b"dave"]
+                .into_iter()
+                .map(|name| (name, Arc::new(OpenMlsRustCrypto::default())))
+                .collect();
+            let bundles: Vec<KeyPackageBundle> =
+                joiners.iter().map(|(name, provider)| key_package(name, provider)).collect();
+            let key_packages = bundles
+                .iter()
+                .map(|b| b.key_package().tls_serialize_detached().unwrap())
+                .collect();
+
+            let (_commit, welcome) = alice.add_members(key_packages).await.unwrap();
+            let welcome = welcome.unwrap();
+            let tree = alice.export_ratchet_tree_bytes().await.unwrap();
+
+            let mut members = vec![Member {
+                name: b"alice".to_vec(),
+                provider: alice_provider,
+                engine: alice,
+                outbound: OutboundBuilder::new(b"alice".to_vec()),
+                received: Vec::new(),
+            }];
+
+            for ((name, provider), bundle) in joiners.into_iter().zip(bundles) {
+                let engine = OpenMlsEngine::join_from_welcome(
+                    &welcome,
+                    Some(tree.clone()),
+                    MlsConfig::default(),
+                    Some(bundle),
+                    provider.clone(),
+                )
+                .await
+                .unwrap();
+
+                members.push(Member {
+                    name: name.to_vec(),
+                    provider,
+                    engine,
+                    outbound: OutboundBuilder::new(name.to_vec()),
+                    received: Vec::new(),
+                });
+            }
+
+            let rng = StdRng::seed_from_u64(config.seed);
+            Self {
+                group_id,
+                members,
+                handler: InboundHandler::new(),
+                config,
+                rng,
+                history: Vec::new(),
+                stats: ChaosStats::default(),
+            }
+        }
+
+        /// The inbound pipeline under test: decode, check metadata, process
+        async fn receive(&self, member: usize, bytes: &[u8]) -> MlsResult<MessageContent> {
+            let engine = &self.members[member].engine;
+            let envelope = EncryptedEnvelope::from_bytes(bytes)?;
+            let current_epoch = engine.epoch().await;
+            self.handler.verify_envelope_metadata(
+                &envelope,
+                &self.group_id,
+                MAX_EPOCH_DRIFT,
+                current_epoch,
+            )?;
+            Ok(self.handler.process_envelope(engine, &envelope).await?.content)
+        }
+
+        fn roll(&mut self, fault: Fault) -> bool {
+            let rate = self.config.rate(fault);
+            rate > 0.0 && self.rng.random_bool(rate)
+        }
+
+        /// Produce a corrupted variant of an honest envelope
+        fn corrupt(&mut self, fault: Fault, envelope: &EncryptedEnvelope) -> Vec<u8> {
+            let bytes = envelope.to_bytes().unwrap();
+            let mut env = envelope.clone();
+
+            match fault {
+                Fault::TruncatedEnvelope => {
+                    let len = self.rng.random_range(0..bytes.len());
+                    return bytes[..len].to_vec();
+                }
+                Fault::TruncatedPayload => {
+                    let len = self.rng.random_range(0..env.payload.len());
+                    env.payload.truncate(len);
+                }
+                Fault::WrongGroupId => env.group_id = GroupId::random(),
+                Fault::StaleEpoch => {
+                    let stale: Vec<_> =
+                        self.history.iter().filter(|e| e.epoch < envelope.epoch).collect();
+                    match stale.choose(&mut self.rng) {
+                        Some(old) => env = (*old).clone(),
+                        None => env.epoch = envelope.epoch.wrapping_sub(MAX_EPOCH_DRIFT + 1),
+                    }
+                }
+                Fault::FutureEpoch => {
+                    env.epoch = envelope.epoch + self.rng.random_range(MAX_EPOCH_DRIFT + 1..100);
+                }
+                Fault::BitFlippedCiphertext => {
+                    let bit = self.rng.random_range(0..env.payload.len() * 8);
+                    env.payload[bit / 8] ^= 1 << (bit % 8);
+                }
+                Fault::TamperedSealedSender => {
+                    let mut sealed = bincode::serialize(&env.sealed_sender).unwrap();
+                    let last = sealed.len() - 1;
+                    sealed[last] ^= 0x01;
+                    env.sealed_sender = bincode::deserialize(&sealed).unwrap();
+                }
+                Fault::Oversized => env.payload.extend(vec![0xAB; OVERSIZED_BYTES]),
+                Fault::Garbage => {
+                    let mut garbage = vec![0u8; self.rng.random_range(0..4096)];
+                    self.rng.fill_bytes(&mut garbage);
+                    return garbage;
+                }
+                Fault::Duplicate | Fault::Shuffle => {
+                    unreachable!("{:?} is a delivery fault, not a corruption", fault)
+                }
+            }
+
+            env.to_bytes().unwrap()
+        }
+
+        /// Deliver garbage derived from `envelope` and require a typed rejection
+        async fn inject(&mut self, recipient: usize, envelope: &EncryptedEnvelope) {
+            let corruptions: Vec<Fault> = ALL_FAULTS
+                .iter()
+                .copied()
+                .filter(|f| !matches!(f, Fault::Duplicate | Fault::Shuffle))
+                .collect();
+
+            for fault in corruptions {
+                if !self.roll(fault) {
+                    continue;
+                }
+
+                let bytes = self.corrupt(fault, envelope);
+                let epoch_before = self.members[recipient].engine.epoch().await;
+                let result = self.receive(recipient, &bytes).await;
+                assert_rejected(fault, &result);
+                assert_eq!(
+                    self.members[recipient].engine.epoch().await,
+                    epoch_before,
+                    "{:?} changed the recipient's epoch",
+                    fault
+                );
+                *self.stats.injected.entry(fault).or_default() += 1;
+            }
+        }
+
+        /// Deliver an honest envelope (after any injected faults)
+        async fn deliver(&mut self, recipient: usize, envelope: &EncryptedEnvelope) {
+            self.inject(recipient, envelope).await;
+
+            let bytes = envelope.to_bytes().unwrap();
+            let content = self
+                .receive(recipient, &bytes)
+                .await
+                .unwrap_or_else(|e| panic!("honest {:?} rejected: {}", envelope.message_type, e));
+            if let MessageContent::Application(plaintext) = content {
+                self.members[recipient].received.push(plaintext);
+            }
+            self.stats.honest_delivered += 1;
+
+            if self.roll(Fault::Duplicate) {
+                let result = self.receive(recipient, &bytes).await;
+                assert_rejected(Fault::Duplicate, &result);
+                *self.stats.injected.entry(Fault::Duplicate).or_default() += 1;
+            }
+        }
+
+        /// One round: everyone sends, deliveries are faulted, then one member commits
+        async fn round(&mut self, round: usize) {
+            let mut outbox: Vec<(usize, EncryptedEnvelope, Vec<u8>)> = Vec::new();
+            for sender in 0..self.members.len() {
+                for n in 0..self.config.messages_per_member {
+                    let plaintext = format!("round {} message {} from {}", round, n, sender);
+                    let member = &self.members[sender];
+                    let envelope = member
+                        .outbound
+                        .build_application_message(&member.engine, plaintext.as_bytes())
+                        .await
+                        .unwrap();
+                    outbox.push((sender, envelope, plaintext.into_bytes()));
+                }
+            }
+
+            for recipient in 0..self.members.len() {
+                let mut inbound: Vec<_> =
+                    outbox.iter().filter(|(s, _, _)| *s != recipient).collect();
+                if self.roll(Fault::Shuffle) {
+                    inbound.shuffle(&mut self.rng);
+                    *self.stats.injected.entry(Fault::Shuffle).or_default() += 1;
+                }
+
+                let before = self.members[recipient].received.len();
+                for (_, envelope, _) in inbound.iter() {
+                    self.deliver(recipient, envelope).await;
+                }
+
+                // Every honest message arrives exactly once, whatever the order
+                let mut got = self.members[recipient].received[before..].to_vec();
+                let mut expected: Vec<Vec<u8>> =
+                    inbound.iter().map(|(_, _, p)| p.clone()).collect();
+                got.sort();
+                expected.sort();
+                assert_eq!(got, expected, "member {} lost or duplicated messages", recipient);
+            }
+
+            self.history.extend(outbox.into_iter().map(|(_, envelope, _)| envelope));
+
+            let committer = round % self.members.len();
+            let member = &self.members[committer];
+            let (commit, _welcome) =
+                member.outbound.build_commit_message(&member.engine).await.unwrap();
+            for recipient in (0..self.members.len()).filter(|r| *r != committer) {
+                self.deliver(recipient, &commit).await;
+            }
+            self.history.push(commit);
+
+            self.assert_converged().await;
+        }
+
+        async fn run(&mut self) {
+            for round in 0..self.config.rounds {
+                self.round(round).await;
+            }
+        }
+
+        /// Honest members agree on epoch and key schedule
+        async fn assert_converged(&self) {
+            let epoch = self.members[0].engine.epoch().await;
+            let secret = self.members[0].engine.export_secret("chaos", b"", 32).await.unwrap();
+
+            for member in &self.members[1..] {
+                assert_eq!(
+                    member.engine.epoch().await,
+                    epoch,
+                    "{} diverged from the group epoch",
+                    String::from_utf8_lossy(&member.name)
+                );
+                assert_eq!(
+                    member.engine.export_secret("chaos", b"", 32).await.unwrap(),
+                    secret,
+                    "{} diverged from the group key schedule",
+                    String::from_utf8_lossy(&member.name)
+                );
+            }
+        }
+
+        /// Total bytes held in every member's provider storage
+        fn storage_bytes(&self) -> usize {
+            self.members
+                .iter()
+                .map(|m| {
+                    let values = m.provider.storage().values.read().unwrap();
+                    values.iter().map(|(k, v)| k.len() + v.len()).sum::<usize>()
+                })
+                .sum()
+        }
+    }
+
+    /// A fault must surface as a typed error, never success or an internal bug
+    fn assert_rejected(fault: Fault, result: &MlsResult<MessageContent>) {
+        match result {
+            Ok(content) => panic!("{:?} was accepted as {:?}", fault, content),
+            Err(MlsError::Internal(msg)) => panic!("{:?} hit an internal error: {}", fault, msg),
+            Err(_) => {}
+        }
+    }
+
+    async fn run_single(fault: Fault) -> ChaosStats {
+        let mut harness = ChaosHarness::four_party(ChaosConfig::single(fault)).await;
+        harness.run().await;
+        assert!(
+            harness.stats.injected.get(&fault).copied().unwrap_or(0) > 0,
+            "{:?} was never injected",
+            fault
+        );
+        harness.stats
+    }
+
+    #[tokio::test]
+    async fn chaos_truncated_envelope() {
+        run_single(Fault::TruncatedEnvelope).await;
+    }
+
+    #[tokio::test]
+    async fn chaos_truncated_payload() {
+        run_single(Fault::TruncatedPayload).await;
+    }
+
+    #[tokio::test]
+    async fn chaos_wrong_group_id() {
+        run_single(Fault::WrongGroupId).await;
+    }
+
+    #[tokio::test]
+    async fn chaos_stale_epoch() {
+        run_single(Fault::StaleEpoch).await;
+    }
+
+    #[tokio::test]
+    async fn chaos_future_epoch() {
+        run_single(Fault::FutureEpoch).await;
+    }
+
+    #[tokio::test]
+    async fn chaos_bit_flipped_ciphertext() {
+        run_single(Fault::BitFlippedCiphertext).await;
+    }
+
+    #[tokio::test]
+    async fn chaos_tampered_sealed_sender() {
+        run_single(Fault::TamperedSealedSender).await;
+    }
+
+    #[tokio::test]
+    async fn chaos_duplicated_messages_and_commits() {
+        run_single(Fault::Duplicate).await;
+    }
+
+    #[tokio::test]
+    async fn chaos_shuffled_delivery() {
+        run_single(Fault::Shuffle).await;
+    }
+
+    #[tokio::test]
+    async fn chaos_oversized_payload() {
+        run_single(Fault::Oversized).await;
+    }
+
+    #[tokio::test]
+    async fn chaos_garbage() {
+        run_single(Fault::Garbage).await;
+    }
+
+    #[tokio::test]
+    async fn chaos_mixed_soak() {
+        let mut baseline = ChaosHarness::four_party(ChaosConfig::mixed(0.0, 8, 1)).await;
+        baseline.run().await;
+
+        let mut harness = ChaosHarness::four_party(ChaosConfig::mixed(0.3, 8, 7)).await;
+        harness.run().await;
+
+        let injected: usize = harness.stats.injected.values().sum();
+        assert!(injected > 100, "soak injected only {} faults", injected);
+        assert_eq!(harness.stats.honest_delivered, baseline.stats.honest_delivered);
+
+        // Rejected garbage must not accumulate in member state
+        let (clean, soaked) = (baseline.storage_bytes(), harness.storage_bytes());
+        assert!(
+            soaked <= clean + clean / 10,
+            "storage grew under chaos: {} bytes vs {} clean",
+            soaked,
+            clean
+        );
+    }
+}
@@ -2,6 +2,7 @@
 //!
 //! Comprehensive security testing and hardening for SpacePanda
 
+pub mod chaos_tests;
 pub mod crypto_tests;
 pub mod input_validation;
 pub mod privacy_tests;
@@ -154,8 +154,10 @@ mod tests {
             .await
             .unwrap();
 
-        // Read database file
-        let db_contents = std::fs::read(&db_path).unwrap();
+        // Read database file, and the write-ahead log fresh rows sit in
+        // until a checkpoint
+        let mut db_contents = std::fs::read(&db_path).unwrap();
+        db_contents.extend(std::fs::read(format!("{}-wal", db_path)).unwrap_or_default());
         let contents_str = String::from_utf8_lossy(&db_contents);
 
         // Verify plaintext sender is NOT in database
@@ -8,13 +8,22 @@
 //! - Configuration management
 
 use crate::{
-    config::Config,
+    config::{Config, StorageBackend},
+    core_identity::keystore::Keystore,
     core_mls::{
+        channel_metadata::{self, ChannelMetadata},
+        commit_log::{self, CommitHash, ProcessedCommits},
+        commit_policy::ChannelRoles,
+        discovery::GroupPublicInfo,
+        engine::openmls_engine::OpenMlsEngine,
         engine::{adapter::OpenMlsHandleAdapter, GroupOperations},
         errors::{MlsError, MlsResult},
         events::{EventBroadcaster, MlsEvent},
+        fork::{self, DroppedChange, ForkPoint, StorageSnapshot},
+        key_package_cache::{KeyPackageVerifier, VerifiedKeyPackage},
         providers::PersistentProvider,
-        storage::SqlStorageProvider,
+        recovery::{self, RecoveryLog, SignedSnapshot},
+        storage::{FileStorageProvider, MemoryStorageProvider, SqlStorageProvider},
         traits::storage::StorageProvider,
         types::{GroupId, GroupMetadata, MlsConfig},
     },
@@ -32,11 +41,14 @@ use tracing::{debug, error, info, warn};
 
 // OpenMLS imports for key package generation
 use openmls::key_packages::KeyPackage as MlsKeyPackage;
-use openmls::prelude::tls_codec::Serialize;
+use openmls::prelude::tls_codec::{Deserialize as TlsDeserialize, Serialize};
 use openmls::prelude::*;
 use openmls_basic_credential::SignatureKeyPair;
 use openmls_traits::OpenMlsProvider;
 
+/// Longest secret the exporter hands out (HKDF-SHA256 expands to 255 blocks)
+pub const MAX_EXPORTED_SECRET_LEN: usize = 255 * 32;
+
 /// MLS Service for managing multiple groups
 pub struct MlsService {
     /// Active MLS groups indexed by GroupId
@@ -60,8 +72,26 @@ pub struct MlsService {
     /// This allows us to retrieve the correct signature keys when joining from Welcome
     key_package_bundles: Arc<RwLock<HashMap<Vec<u8>, KeyPackageBundle>>>,
 
-    /// SQL storage provider for persisting messages and channel metadata
-    storage: Option<Arc<SqlStorageProvider>>,
+    /// Storage provider for persisting group snapshots and blobs
+    storage: Option<Arc<dyn StorageProvider>>,
+
+    /// SQL storage for messages and channel metadata (SQLite backend only)
+    sql_storage: Option<Arc<SqlStorageProvider>>,
+
+    /// Cached key package verdicts and revoked signature keys
+    key_package_verifier: Arc<KeyPackageVerifier>,
+
+    /// Recently processed commits per group, loaded from storage on first use
+    processed_commits: Arc<RwLock<HashMap<GroupId, ProcessedCommits>>>,
+
+    /// State before the last merged commit of each group, for fork resolution
+    fork_points: Arc<RwLock<HashMap<GroupId, ForkPoint>>>,
+
+    /// Snapshots and commit logs for [`recover_group`](Self::recover_group)
+    recovery: Option<Arc<RecoveryLog>>,
+
+    /// Where recovery snapshots go once a keystore holds their key
+    recovery_dir: Option<PathBuf>,
 }
 
 impl MlsService {
@@ -69,7 +99,8 @@ impl MlsService {
     pub fn new(config: &Config, shutdown: Arc<ShutdownCoordinator>) -> Self {
         info!("Initializing MLS service");
 
-        let mls_config = MlsConfig::default();
+        let mls_config =
+            MlsConfig { padding: config.privacy.padding_strategy(), ..Default::default() };
         let provider = Arc::new(PersistentProvider::default());
 
         Self {
@@ -80,47 +111,110 @@ impl MlsService {
             provider,
             key_package_bundles: Arc::new(RwLock::new(HashMap::new())),
             storage: None,
+            sql_storage: None,
+            key_package_verifier: Arc::new(KeyPackageVerifier::default()),
+            processed_commits: Arc::new(RwLock::new(HashMap::new())),
+            fork_points: Arc::new(RwLock::new(HashMap::new())),
+            recovery: None,
+            recovery_dir: None,
         }
     }
 
-    /// Create MLS service with file-based storage for persistence
+    /// Create MLS service with storage for persistence
+    ///
+    /// Uses the backend set in `config.store.mls_backend`, or else a SQLite
+    /// database in `storage_dir`.
     pub fn with_storage(
         config: &Config,
         shutdown: Arc<ShutdownCoordinator>,
         storage_dir: PathBuf,
     ) -> MlsResult<Self> {
-        info!("Initializing MLS service with storage at: {:?}", storage_dir);
-
-        let mls_config = MlsConfig::default();
+        let backend = config
+            .store
+            .mls_backend
+            .clone()
+            .unwrap_or(StorageBackend::Sqlite { path: storage_dir.join("mls_state.db") });
+        Self::with_backend(config, shutdown, backend)
+    }
 
-        // Create persistent provider with SQLite database
-        std::fs::create_dir_all(&storage_dir)
-            .map_err(|e| MlsError::Storage(format!("Failed to create storage directory: {}", e)))?;
+    /// Create MLS service storing its state in the given backend
+    ///
+    /// Recovery snapshots can be kept next to a file or SQLite backend; see
+    /// [`with_recovery_keystore`](Self::with_recovery_keystore).
+    pub fn with_backend(
+        config: &Config,
+        shutdown: Arc<ShutdownCoordinator>,
+        backend: StorageBackend,
+    ) -> MlsResult<Self> {
+        info!("Initializing MLS service with storage backend: {:?}", backend);
 
-        let db_path = storage_dir.join("mls_state.db");
-        let persistent_provider = PersistentProvider::new(
-            db_path
-                .to_str()
-                .ok_or_else(|| MlsError::Storage("Invalid database path".to_string()))?,
-        )?;
+        let mls_config =
+            MlsConfig { padding: config.privacy.padding_strategy(), ..Default::default() };
 
-        info!("Using SQL storage at: {:?}", db_path);
+        if let Some(dir) = backend.dir() {
+            std::fs::create_dir_all(dir).map_err(|e| {
+                MlsError::Storage(format!("Failed to create storage directory: {}", e))
+            })?;
+        }
 
-        // Get SQL storage reference before moving provider into Arc
-        let sql_storage = persistent_provider.sql_storage_arc();
-        let provider = Arc::new(persistent_provider);
+        // Create persistent provider, with the SQLite database if that's the backend
+        let provider = match &backend {
+            StorageBackend::Sqlite { path } => PersistentProvider::new(
+                path.to_str()
+                    .ok_or_else(|| MlsError::Storage("Invalid database path".to_string()))?,
+            )?,
+            _ => PersistentProvider::default(),
+        };
+        let sql_storage =
+            matches!(backend, StorageBackend::Sqlite { .. }).then(|| provider.sql_storage_arc());
+        let storage: Arc<dyn StorageProvider> = match &backend {
+            StorageBackend::Sqlite { .. } => provider.sql_storage_arc(),
+            StorageBackend::File { dir } => Arc::new(FileStorageProvider::new(dir.clone(), None)?),
+            StorageBackend::Memory => Arc::new(MemoryStorageProvider::new()),
+        };
+        let recovery_dir = backend.dir().map(|dir| dir.join("recovery"));
 
         Ok(Self {
             groups: Arc::new(RwLock::new(HashMap::new())),
             config: mls_config,
             events: EventBroadcaster::default(),
             shutdown,
-            provider,
+            provider: Arc::new(provider),
             key_package_bundles: Arc::new(RwLock::new(HashMap::new())),
-            storage: Some(sql_storage),
+            storage: Some(storage),
+            sql_storage,
+            key_package_verifier: Arc::new(KeyPackageVerifier::default()),
+            processed_commits: Arc::new(RwLock::new(HashMap::new())),
+            fork_points: Arc::new(RwLock::new(HashMap::new())),
+            recovery: None,
+            recovery_dir,
         })
     }
 
+    /// Keep recovery snapshots, sealed under a key held in `keystore`
+    ///
+    /// Only a file or SQLite backend has room for them; with the memory
+    /// backend this does nothing.
+    pub fn with_recovery_keystore(mut self, keystore: &dyn Keystore) -> MlsResult<Self> {
+        if let Some(dir) = &self.recovery_dir {
+            self.recovery = Some(Arc::new(RecoveryLog::open(dir, keystore)?));
+        }
+        Ok(self)
+    }
+
+    /// Use a non-default group configuration
+    ///
+    /// Applies to groups created or joined afterwards.
+    pub fn with_config(mut self, config: MlsConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Group configuration
+    pub fn config(&self) -> &MlsConfig {
+        &self.config
+    }
+
     /// Load all persisted groups from storage
     ///
     /// Should be called on service initialization to restore previous session state.
@@ -179,40 +273,7 @@ impl MlsService {
                         }
                     };
 
-                    // Extract credential from the loaded group
-                    let own_leaf = mls_group.own_leaf().expect("Group must have own leaf");
-                    let credential = own_leaf.credential().clone();
-                    let signature_key = own_leaf.signature_key().clone();
-
-                    // Retrieve signature keys from provider storage
-                    let signature_keys = openmls_basic_credential::SignatureKeyPair::read(
-                        self.provider.storage(),
-                        signature_key.as_slice(),
-                        mls_group.ciphersuite().signature_algorithm(),
-                    )
-                    .ok_or_else(|| {
-                        MlsError::CryptoError(format!(
-                            "Failed to retrieve signature keys for group {}",
-                            group_id
-                        ))
-                    })?;
-
-                    // Create credential bundle
-                    let credential_bundle =
-                        openmls::prelude::CredentialWithKey { credential, signature_key };
-
-                    // Create engine from loaded group
-                    use crate::core_mls::engine::openmls_engine::OpenMlsEngine;
-                    let engine = OpenMlsEngine::from_group_with_provider(
-                        mls_group,
-                        self.provider.clone(),
-                        self.config.clone(),
-                        signature_keys,
-                        credential_bundle,
-                    );
-
-                    // Wrap in adapter
-                    use crate::core_mls::engine::adapter::OpenMlsHandleAdapter;
+                    let engine = self.engine_from_group(&group_id, mls_group)?;
                     let adapter = OpenMlsHandleAdapter::from_engine(engine, self.config.clone());
 
                     // Add to active groups
@@ -237,6 +298,43 @@ impl MlsService {
         Ok(loaded)
     }
 
+    /// Engine for a group loaded from the provider storage
+    fn engine_from_group(
+        &self,
+        group_id: &GroupId,
+        mls_group: MlsGroup,
+    ) -> MlsResult<OpenMlsEngine<PersistentProvider>> {
+        // Extract credential from the loaded group
+        let own_leaf = mls_group.own_leaf().ok_or_else(|| {
+            MlsError::InvalidState(format!("Group {} has no leaf of ours", group_id))
+        })?;
+        let credential = own_leaf.credential().clone();
+        let signature_key = own_leaf.signature_key().clone();
+
+        // Retrieve signature keys from provider storage
+        let signature_keys = SignatureKeyPair::read(
+            self.provider.storage(),
+            signature_key.as_slice(),
+            mls_group.ciphersuite().signature_algorithm(),
+        )
+        .ok_or_else(|| {
+            MlsError::CryptoError(format!(
+                "Failed to retrieve signature keys for group {}",
+                group_id
+            ))
+        })?;
+
+        // Create engine from loaded group
+        let credential_bundle = CredentialWithKey { credential, signature_key };
+        Ok(OpenMlsEngine::from_group_with_provider(
+            mls_group,
+            self.provider.clone(),
+            self.config.clone(),
+            signature_keys,
+            credential_bundle,
+        ))
+    }
+
     /// Save a group's state to storage
     pub async fn save_group(&self, group_id: &GroupId) -> MlsResult<()> {
         let Some(storage) = &self.storage else {
@@ -297,6 +395,94 @@ impl MlsService {
         ))
     }
 
+    /// Rebuild a group from its recovery snapshots and commit log
+    ///
+    /// For when the provider storage lost or damaged the group. Loads the
+    /// newest snapshot that authenticates and carries a valid signature of
+    /// our leaf, skipping damaged ones, then replays the commits logged
+    /// since. Replay stops at the first commit that doesn't apply. The
+    /// recovered group replaces any loaded copy.
+    ///
+    /// # Returns
+    /// The epoch the group was recovered to
+    ///
+    /// # Errors
+    ///
+    /// - `Storage` if the service keeps no recovery snapshots
+    /// - `PersistenceError` if no snapshot is usable
+    pub async fn recover_group(&self, group_id: &GroupId) -> MlsResult<u64> {
+        let Some(recovery) = &self.recovery else {
+            return Err(MlsError::Storage("No recovery snapshots kept".to_string()));
+        };
+
+        info!("Recovering group {}", group_id);
+
+        let crypto = self.provider.crypto();
+        let verify_fn = |signature_key: &[u8], bytes: &[u8], signature: &[u8]| {
+            crypto
+                .verify_signature(SignatureScheme::ED25519, bytes, signature_key, signature)
+                .is_ok()
+        };
+        let mut found = None;
+        for epoch in recovery.snapshot_epochs(group_id) {
+            match recovery.open_snapshot(group_id, epoch).and_then(|s| s.verify(verify_fn)) {
+                Ok(snapshot) => {
+                    found = Some(snapshot);
+                    break;
+                }
+                Err(e) => {
+                    warn!("Skipping recovery snapshot of {} at epoch {}: {}", group_id, epoch, e);
+                    record_counter("mls.recovery.snapshots_skipped", 1);
+                }
+            }
+        }
+        let snapshot = found.ok_or_else(|| {
+            MlsError::PersistenceError(format!("No usable recovery snapshot of {}", group_id))
+        })?;
+
+        let provider_state: StorageSnapshot = snapshot
+            .metadata
+            .get(recovery::PROVIDER_STATE_KEY)
+            .map(|bytes| bincode::deserialize(bytes))
+            .transpose()?
+            .ok_or_else(|| {
+                MlsError::PersistenceError("Recovery snapshot has no provider state".to_string())
+            })?;
+        fork::restore(self.provider.storage(), group_id, &provider_state);
+
+        let openmls_group_id = openmls::prelude::GroupId::from_slice(group_id.as_bytes());
+        let mls_group = MlsGroup::load(self.provider.storage(), &openmls_group_id)
+            .map_err(|e| MlsError::PersistenceError(format!("Failed to load group: {:?}", e)))?
+            .ok_or_else(|| MlsError::GroupNotFound(group_id.to_string()))?;
+        let engine = self.engine_from_group(group_id, mls_group)?;
+
+        let mut replayed = 0;
+        for commit in recovery.commits_since(group_id, snapshot.epoch) {
+            if let Err(e) = engine.process_message_with_external_commits(&commit).await {
+                warn!("Stopped replaying commits of {}: {}", group_id, e);
+                break;
+            }
+            replayed += 1;
+        }
+        let epoch = engine.epoch().await;
+        self.write_recovery_snapshot(&engine, group_id).await;
+
+        let adapter = OpenMlsHandleAdapter::from_engine(engine, self.config.clone());
+        self.groups.write().await.insert(group_id.clone(), Arc::new(adapter));
+        self.fork_points.write().await.remove(group_id);
+
+        if let Err(e) = self.provider.save() {
+            warn!("Failed to save provider state after recovering group: {}", e);
+        }
+
+        record_counter("mls.groups.recovered", 1);
+        info!(
+            "Recovered group {} at epoch {} ({} commit(s) replayed)",
+            group_id, epoch, replayed
+        );
+        Ok(epoch)
+    }
+
     /// Generate a key package for joining groups
     ///
     /// Creates a KeyPackageBundle with cryptographic material and stores it
@@ -322,6 +508,26 @@ impl MlsService {
     /// 5. Builds KeyPackageBundle (auto-stored in provider)
     /// 6. Returns serialized public key package
     pub async fn generate_key_package(&self, identity: Vec<u8>) -> MlsResult<Vec<u8>> {
+        self.build_key_package(identity, None).await
+    }
+
+    /// Generate a key package that expires after `lifetime_secs`
+    ///
+    /// Same as [`generate_key_package`](Self::generate_key_package), but
+    /// with an explicit validity window instead of the OpenMLS default.
+    pub async fn generate_key_package_with_lifetime(
+        &self,
+        identity: Vec<u8>,
+        lifetime_secs: u64,
+    ) -> MlsResult<Vec<u8>> {
+        self.build_key_package(identity, Some(Lifetime::new(lifetime_secs))).await
+    }
+
+    async fn build_key_package(
+        &self,
+        identity: Vec<u8>,
+        lifetime: Option<Lifetime>,
+    ) -> MlsResult<Vec<u8>> {
         let timer = Timer::new("mls.generate_key_package.duration_ms");
 
         info!("Generating key package for identity: {:?}", hex::encode(&identity));
@@ -359,7 +565,12 @@ impl MlsService {
         // Build the key package bundle
         // NOTE: The KeyPackageBundle is automatically stored in the provider's storage
         // when built. This allows join_from_welcome to find it later.
-        let key_package_bundle = KeyPackage::builder()
+        let mut builder =
+            KeyPackage::builder().leaf_node_capabilities(channel_metadata::capabilities());
+        if let Some(lifetime) = lifetime {
+            builder = builder.key_package_lifetime(lifetime);
+        }
+        let key_package_bundle = builder
             .build(ciphersuite, provider.as_ref(), &signature_keys, credential_with_key)
             .map_err(|e| {
                 MlsError::InvalidMessage(format!("Failed to build key package: {:?}", e))
@@ -417,6 +628,7 @@ impl MlsService {
         .await?;
 
         let gid = adapter.group_id().await;
+        self.write_recovery_snapshot(&*adapter.engine().read().await, &gid).await;
 
         // Store the group
         {
@@ -481,6 +693,7 @@ impl MlsService {
         .await?;
 
         let gid = adapter.group_id().await;
+        self.write_recovery_snapshot(&*adapter.engine().read().await, &gid).await;
 
         // Store the group
         {
@@ -510,25 +723,205 @@ impl MlsService {
         Ok(gid)
     }
 
+    /// Join a public group by external commit
+    ///
+    /// Builds a commit adding `identity` from the group's published info
+    /// and applies it locally. The info's signature must come from a
+    /// member of the joined group, or the join is undone.
+    ///
+    /// # Returns
+    /// The group ID and the serialized commit to broadcast to its members
+    ///
+    /// # Errors
+    ///
+    /// - `PermissionDenied` if the info carries no GroupInfo
+    /// - `InvalidMessage` if OpenMLS rejects the GroupInfo
+    /// - `VerifyFailed` if the info wasn't signed by a member
+    pub async fn join_by_external_commit(
+        &self,
+        info: &GroupPublicInfo,
+        identity: Vec<u8>,
+    ) -> MlsResult<(GroupId, Vec<u8>)> {
+        let timer = Timer::new("mls.join_group.duration_ms");
+
+        info!("Joining MLS group {} by external commit", info.group_id);
+
+        if self.shutdown.is_shutting_down().await {
+            warn!("Cannot join group: service is shutting down");
+            return Err(MlsError::ServiceUnavailable("MLS service is shutting down".to_string()));
+        }
+
+        if !info.accepts_external_commits() {
+            return Err(MlsError::PermissionDenied(format!(
+                "Group {} does not accept external commits",
+                info.group_id
+            )));
+        }
+
+        if self.groups.read().await.contains_key(&info.group_id) {
+            return Err(MlsError::InvalidState(format!(
+                "Already a member of group {}",
+                info.group_id
+            )));
+        }
+
+        use crate::core_mls::engine::openmls_engine::OpenMlsEngine;
+        let (engine, commit) = OpenMlsEngine::join_by_external_commit(
+            &info.group_info,
+            identity,
+            self.config.clone(),
+            self.provider.clone(),
+        )
+        .await?;
+
+        // The GroupInfo is checked by OpenMLS; the listing around it must
+        // be signed by one of the members it let us in with
+        let metadata = engine.metadata().await?;
+        let crypto = self.provider.crypto();
+        let signed_by_member = info
+            .verify(|bytes, signature| {
+                metadata.members.iter().any(|member| {
+                    crypto
+                        .verify_signature(
+                            SignatureScheme::ED25519,
+                            bytes,
+                            &member.signature_key,
+                            signature,
+                        )
+                        .is_ok()
+                })
+            })
+            .is_ok();
+        if metadata.group_id != info.group_id || !signed_by_member {
+            engine.delete_state().await?;
+            return Err(MlsError::VerifyFailed(format!(
+                "Public info for group {} is not signed by a member",
+                info.group_id
+            )));
+        }
+
+        let gid = metadata.group_id;
+        self.write_recovery_snapshot(&engine, &gid).await;
+        let adapter = OpenMlsHandleAdapter::from_engine(engine, self.config.clone());
+        self.groups.write().await.insert(gid.clone(), Arc::new(adapter));
+
+        if self.storage.is_some() {
+            if let Err(e) = self.save_group(&gid).await {
+                warn!("Failed to save joined group {}: {}", gid, e);
+            }
+        }
+
+        if let Err(e) = self.provider.save() {
+            warn!("Failed to save provider state after joining group: {}", e);
+        }
+
+        record_counter("mls.groups.joined", 1);
+        record_counter("mls.external_commits.created", 1);
+        timer.stop();
+
+        info!("Successfully joined MLS group {} by external commit", gid);
+        Ok((gid, commit))
+    }
+
+    /// Check that a serialized key package is well-formed and still valid
+    ///
+    /// Verifies the encoding, the signature and the lifetime, so an invite
+    /// is never built on a key package the invitee cannot join with.
+    ///
+    /// # Errors
+    ///
+    /// `InvalidMessage` if the key package is malformed, badly signed,
+    /// expired or not yet valid.
+    pub fn validate_key_package(&self, key_package: &[u8]) -> MlsResult<()> {
+        self.key_package_identity(key_package).map(|_| ())
+    }
+
+    /// Validate a serialized key package and return its credential identity
+    ///
+    /// # Errors
+    ///
+    /// As [`validate_key_package`](Self::validate_key_package).
+    pub fn key_package_identity(&self, key_package: &[u8]) -> MlsResult<Vec<u8>> {
+        Ok(self.verify_key_package(key_package)?.identity)
+    }
+
+    /// Validate a serialized key package and return its leaf signature key
+    ///
+    /// This is the `signature_key` its leaf will have in the group's
+    /// [`MemberInfo`](crate::core_mls::types::MemberInfo).
+    ///
+    /// # Errors
+    ///
+    /// As [`validate_key_package`](Self::validate_key_package).
+    pub fn key_package_signature_key(&self, key_package: &[u8]) -> MlsResult<Vec<u8>> {
+        Ok(self.verify_key_package(key_package)?.signature_key)
+    }
+
+    /// Validate a serialized key package
+    ///
+    /// Verdicts are cached until the package expires, so checking the same
+    /// package again (when it is queued and again when it is committed)
+    /// costs a hash lookup.
+    ///
+    /// # Errors
+    ///
+    /// As [`validate_key_package`](Self::validate_key_package), or if the
+    /// package is signed with a revoked key.
+    pub fn verify_key_package(&self, key_package: &[u8]) -> MlsResult<VerifiedKeyPackage> {
+        self.key_package_verifier.verify(self.provider.crypto(), key_package)
+    }
+
+    /// Validate a batch of serialized key packages, in parallel
+    ///
+    /// Results are in the same order as `key_packages`.
+    pub fn verify_key_packages(
+        &self,
+        key_packages: &[Vec<u8>],
+    ) -> Vec<MlsResult<VerifiedKeyPackage>> {
+        let timer = Timer::new("mls.key_packages.verify_batch.duration_ms");
+        let results = self.key_package_verifier.verify_batch(self.provider.crypto(), key_packages);
+        timer.stop();
+        results
+    }
+
+    /// Reject key packages signed with any of these leaf signature keys
+    ///
+    /// Cached verdicts for them are dropped.
+    pub fn revoke_key_package_signers(&self, signature_keys: impl IntoIterator<Item = Vec<u8>>) {
+        self.key_package_verifier.revoke(signature_keys);
+    }
+
     /// Find the KeyPackageBundle that matches the Welcome message
     ///
-    /// This is a simplified implementation that tries all stored bundles.
-    /// In production, we would parse the Welcome to get the specific KeyPackage hash.
+    /// The Welcome names the key package it was encrypted to, so only the
+    /// device that generated that key package can join from it.
     async fn find_key_package_bundle_for_welcome(
         &self,
-        _welcome_bytes: &[u8],
+        welcome_bytes: &[u8],
     ) -> MlsResult<KeyPackageBundle> {
-        let bundles = self.key_package_bundles.read().await;
+        let mls_message = MlsMessageIn::tls_deserialize_exact(welcome_bytes)
+            .map_err(|e| MlsError::InvalidMessage(format!("Failed to parse welcome: {:?}", e)))?;
+        let welcome = match mls_message.extract() {
+            MlsMessageBodyIn::Welcome(welcome) => welcome,
+            _ => return Err(MlsError::InvalidMessage("Expected Welcome message".to_string())),
+        };
 
-        // For MVP: just return the first (and likely only) bundle
-        // TODO: Parse Welcome message to find the correct KeyPackage hash
-        if let Some((_, bundle)) = bundles.iter().next() {
-            Ok(bundle.clone())
-        } else {
-            Err(MlsError::InvalidMessage(
-                "No KeyPackageBundle found for this Welcome message".to_string(),
-            ))
+        let recipients: Vec<KeyPackageRef> =
+            welcome.secrets().iter().map(|secrets| secrets.new_member()).collect();
+
+        let bundles = self.key_package_bundles.read().await;
+        for bundle in bundles.values() {
+            let hash_ref = bundle.key_package().hash_ref(self.provider.crypto()).map_err(|e| {
+                MlsError::CryptoError(format!("Failed to hash key package: {:?}", e))
+            })?;
+            if recipients.contains(&hash_ref) {
+                return Ok(bundle.clone());
+            }
         }
+
+        Err(MlsError::InvalidMessage(
+            "Welcome was not created for a key package generated by this device".to_string(),
+        ))
     }
 
     /// Send an encrypted message to a group
@@ -571,10 +964,34 @@ impl MlsService {
     }
 
     /// Process an incoming MLS message
+    ///
+    /// External commits are rejected; see
+    /// [`process_message_with_external_commits`](Self::process_message_with_external_commits).
     pub async fn process_message(
         &self,
         group_id: &GroupId,
         message_bytes: &[u8],
+    ) -> MlsResult<Option<Vec<u8>>> {
+        self.process_message_inner(group_id, message_bytes, false).await
+    }
+
+    /// Process an incoming MLS message, accepting external commits
+    ///
+    /// For groups whose GroupInfo is published (see
+    /// [`export_public_info`](Self::export_public_info)).
+    pub async fn process_message_with_external_commits(
+        &self,
+        group_id: &GroupId,
+        message_bytes: &[u8],
+    ) -> MlsResult<Option<Vec<u8>>> {
+        self.process_message_inner(group_id, message_bytes, true).await
+    }
+
+    async fn process_message_inner(
+        &self,
+        group_id: &GroupId,
+        message_bytes: &[u8],
+        allow_external_commits: bool,
     ) -> MlsResult<Option<Vec<u8>>> {
         let trace = trace_decrypt(message_bytes.len());
 
@@ -586,10 +1003,85 @@ impl MlsService {
             .get(group_id)
             .ok_or_else(|| MlsError::GroupNotFound(group_id.to_string()))?;
 
-        // Process the message
+        // A redelivered commit is answered from the log instead of OpenMLS,
+        // which would reject it as being for a past epoch
+        let commit = commit_log::commit_epoch(group_id, message_bytes)
+            .map(|epoch| (epoch, commit_log::commit_hash(message_bytes)));
         let engine_ref = adapter.engine();
         let engine = engine_ref.read().await;
-        let processed = engine.process_message(message_bytes).await?;
+        let mut lost_fork = None;
+        if let Some((epoch, hash)) = &commit {
+            let log = self.processed_commits(group_id).await;
+            if log.contains(hash) {
+                debug!("Commit for epoch {} of group {} already processed", epoch, group_id);
+                record_counter("mls.commits.duplicate", 1);
+                trace.record_event("duplicate commit skipped");
+                trace.complete();
+                return Ok(None);
+            }
+            if let Some(merged) = log.commit_for(*epoch) {
+                // Another commit was merged for this epoch: the group forked
+                let fork = self
+                    .fork_points
+                    .write()
+                    .await
+                    .remove(group_id)
+                    .filter(|fork| fork.epoch == *epoch && fork.commit == *merged);
+                match fork {
+                    Some(fork) if fork::wins(hash, &fork.commit) => {
+                        info!("Commit for epoch {} of group {} wins a fork", epoch, group_id);
+                        let current = fork::snapshot(self.provider.storage(), group_id);
+                        fork::restore(self.provider.storage(), group_id, &fork.storage);
+                        if let Err(e) = engine.reload().await {
+                            fork::restore(self.provider.storage(), group_id, &current);
+                            self.fork_points.write().await.insert(group_id.clone(), fork);
+                            return Err(e);
+                        }
+                        lost_fork = Some((fork, current));
+                    }
+                    fork => {
+                        record_counter("mls.commits.conflicting", 1);
+                        if let Some(fork) = fork {
+                            self.fork_points.write().await.insert(group_id.clone(), fork);
+                            self.events.emit(MlsEvent::ForkResolved {
+                                group_id: group_id.as_bytes().to_vec(),
+                                epoch: *epoch,
+                                rolled_back: false,
+                                requeued_proposals: Vec::new(),
+                            });
+                        }
+                        return Err(MlsError::EpochConflict { epoch: *epoch });
+                    }
+                }
+            }
+        }
+        let before_merge =
+            commit.is_some().then(|| fork::snapshot(self.provider.storage(), group_id));
+
+        // Process the message
+        let outcome = if allow_external_commits {
+            engine.process_message_with_external_commits(message_bytes).await
+        } else {
+            engine.process_message(message_bytes).await
+        };
+        let processed = match outcome {
+            Ok(processed) => processed,
+            Err(e) => {
+                // The winner doesn't apply after all; stay on our branch
+                if let Some((fork, current)) = lost_fork {
+                    fork::restore(self.provider.storage(), group_id, &current);
+                    if let Err(e) = engine.reload().await {
+                        error!("Failed to return group {} to its branch: {}", group_id, e);
+                    }
+                    self.fork_points.write().await.insert(group_id.clone(), fork);
+                }
+                return Err(e);
+            }
+        };
+        let requeued_proposals = match &lost_fork {
+            Some((fork, _)) => self.requeue(&engine, &fork.dropped).await,
+            None => Vec::new(),
+        };
         drop(engine); // Release lock before saving
 
         // Handle different message types
@@ -607,10 +1099,39 @@ impl MlsService {
             crate::core_mls::engine::openmls_engine::ProcessedMessage::Commit { new_epoch } => {
                 record_counter("mls.commits.received", 1);
                 trace.record_event(&format!("commit processed, new epoch: {}", new_epoch));
+                if let Some((epoch, hash)) = commit {
+                    self.record_processed_commit(group_id, epoch, hash).await;
+                    if let Some(storage) = before_merge {
+                        let fork = ForkPoint { epoch, commit: hash, storage, dropped: Vec::new() };
+                        self.fork_points.write().await.insert(group_id.clone(), fork);
+                    }
+                    let engine = engine_ref.read().await;
+                    self.log_recovery_commit(&engine, group_id, epoch, message_bytes).await;
+                    if lost_fork.is_some() {
+                        // Snapshots taken on the losing branch are superseded
+                        self.write_recovery_snapshot(&engine, group_id).await;
+                    }
+                }
                 None
             }
         };
 
+        if let Some((fork, _)) = lost_fork {
+            warn!(
+                "Group {} forked at epoch {}; rolled back and requeued {} proposal(s)",
+                group_id,
+                fork.epoch,
+                requeued_proposals.len()
+            );
+            record_counter("mls.forks.rolled_back", 1);
+            self.events.emit(MlsEvent::ForkResolved {
+                group_id: group_id.as_bytes().to_vec(),
+                epoch: fork.epoch,
+                rolled_back: true,
+                requeued_proposals,
+            });
+        }
+
         // Save provider state (commits update epoch state)
         if let Err(e) = self.provider.save() {
             warn!("Failed to save provider state after processing message: {}", e);
@@ -621,26 +1142,267 @@ impl MlsService {
         Ok(plaintext)
     }
 
+    /// Processed-commit log of a group, read from storage on first use
+    async fn processed_commits(&self, group_id: &GroupId) -> ProcessedCommits {
+        if let Some(log) = self.processed_commits.read().await.get(group_id) {
+            return log.clone();
+        }
+
+        let mut log = ProcessedCommits::default();
+        if let Some(storage) = &self.storage {
+            match storage.get_blob(&ProcessedCommits::storage_key(group_id)).await {
+                Ok(bytes) => match ProcessedCommits::from_bytes(&bytes) {
+                    Ok(persisted) => log = persisted,
+                    Err(e) => warn!("Discarding unreadable commit log of {}: {}", group_id, e),
+                },
+                Err(MlsError::NotFound(_)) => {}
+                Err(e) => warn!("Failed to load commit log of {}: {}", group_id, e),
+            }
+        }
+
+        self.processed_commits
+            .write()
+            .await
+            .entry(group_id.clone())
+            .or_insert(log)
+            .clone()
+    }
+
+    /// Add a merged commit to a group's log and persist the log
+    async fn record_processed_commit(&self, group_id: &GroupId, epoch: u64, hash: CommitHash) {
+        let mut log = self.processed_commits(group_id).await;
+        log.record(epoch, hash);
+        self.store_processed_commits(group_id, log).await;
+    }
+
+    /// The group's provider storage before one of our own commits, with its
+    /// epoch
+    async fn fork_base(
+        &self,
+        engine: &OpenMlsEngine<PersistentProvider>,
+    ) -> (u64, StorageSnapshot) {
+        let group_id = engine.group_id().await;
+        (engine.epoch().await, fork::snapshot(self.provider.storage(), &group_id))
+    }
+
+    /// Remember a commit we merged ourselves
+    ///
+    /// Its echo from the network is then skipped as a duplicate, and a
+    /// concurrent commit that beats it can take its place. OpenMLS can't
+    /// replay our own commits, so the new state gets a recovery snapshot.
+    async fn record_own_commit(
+        &self,
+        engine: &OpenMlsEngine<PersistentProvider>,
+        group_id: &GroupId,
+        (epoch, storage): (u64, StorageSnapshot),
+        commit: &[u8],
+        dropped: Vec<DroppedChange>,
+    ) {
+        let hash = commit_log::commit_hash(commit);
+        self.record_processed_commit(group_id, epoch, hash).await;
+        let fork = ForkPoint { epoch, commit: hash, storage, dropped };
+        self.fork_points.write().await.insert(group_id.clone(), fork);
+        self.write_recovery_snapshot(engine, group_id).await;
+    }
+
+    /// Propose again the changes of ours that a lost fork dropped
+    ///
+    /// Changes the winning commit made anyway are skipped.
+    async fn requeue(
+        &self,
+        engine: &OpenMlsEngine<PersistentProvider>,
+        dropped: &[DroppedChange],
+    ) -> Vec<Vec<u8>> {
+        let members = match engine.metadata().await {
+            Ok(metadata) => metadata.members,
+            Err(e) => {
+                warn!("Failed to read members, not requeuing proposals: {}", e);
+                return Vec::new();
+            }
+        };
+        let leaf_of = |signature_key: &[u8]| {
+            members.iter().find(|m| m.signature_key == signature_key).map(|m| m.leaf_index)
+        };
+
+        let mut proposals = Vec::new();
+        for change in dropped {
+            let proposal = match change {
+                DroppedChange::Add(key_package) => {
+                    let added = self
+                        .key_package_signature_key(key_package)
+                        .is_ok_and(|signature_key| leaf_of(&signature_key).is_some());
+                    if added {
+                        continue;
+                    }
+                    engine.propose_add(key_package).await
+                }
+                DroppedChange::Remove(signature_key) => match leaf_of(signature_key) {
+                    Some(leaf) => engine.propose_remove(leaf).await,
+                    None => continue,
+                },
+            };
+            match proposal {
+                Ok(proposal) => proposals.push(proposal),
+                Err(e) => warn!("Failed to requeue a dropped change: {}", e),
+            }
+        }
+        proposals
+    }
+
+    /// Write a recovery snapshot of a group's current state
+    async fn write_recovery_snapshot(
+        &self,
+        engine: &OpenMlsEngine<PersistentProvider>,
+        group_id: &GroupId,
+    ) {
+        let Some(recovery) = &self.recovery else {
+            return;
+        };
+        let written: MlsResult<()> = async {
+            // Recovery may start from an empty provider storage
+            let mut provider_state = fork::snapshot(self.provider.storage(), group_id);
+            provider_state.extend(fork::signature_key_entries(engine.signature_keys())?);
+            let provider_state = bincode::serialize(&provider_state)?;
+            let snapshot = engine
+                .export_snapshot()
+                .await?
+                .with_metadata(recovery::PROVIDER_STATE_KEY.to_string(), provider_state);
+            let signed = SignedSnapshot::new(&snapshot, |bytes| engine.sign(bytes))?;
+            let keep_from = self.retained_from(snapshot.epoch);
+            recovery.write_snapshot(group_id, snapshot.epoch, &signed, keep_from)
+        }
+        .await;
+        if let Err(e) = written {
+            warn!("Failed to write recovery snapshot of {}: {}", group_id, e);
+        }
+    }
+
+    /// First epoch whose key material is kept when at `epoch`
+    fn retained_from(&self, epoch: u64) -> u64 {
+        epoch.saturating_sub(self.config.past_epoch_retention as u64)
+    }
+
+    /// Drop recovery snapshots whose epoch left the retention window
+    ///
+    /// Takes a new snapshot first if none would be left.
+    async fn prune_recovery(&self, engine: &OpenMlsEngine<PersistentProvider>, group_id: &GroupId) {
+        let Some(recovery) = &self.recovery else {
+            return;
+        };
+        let keep_from = self.retained_from(engine.epoch().await);
+        match recovery.snapshot_epochs(group_id).first() {
+            Some(latest) if *latest < keep_from => {
+                self.write_recovery_snapshot(engine, group_id).await;
+            }
+            _ => {
+                if let Err(e) = recovery.prune(group_id, keep_from) {
+                    warn!("Failed to prune recovery snapshots of {}: {}", group_id, e);
+                }
+            }
+        }
+    }
+
+    /// Log a merged commit for recovery
+    ///
+    /// Takes a snapshot once `snapshot_interval` epochs passed since the
+    /// last one, or sooner if the last one left the retention window.
+    async fn log_recovery_commit(
+        &self,
+        engine: &OpenMlsEngine<PersistentProvider>,
+        group_id: &GroupId,
+        epoch: u64,
+        commit: &[u8],
+    ) {
+        let Some(recovery) = &self.recovery else {
+            return;
+        };
+        if let Err(e) = recovery.append_commit(group_id, epoch, commit) {
+            warn!("Failed to log commit of {} for recovery: {}", group_id, e);
+        }
+        let due = match recovery.snapshot_epochs(group_id).first() {
+            Some(latest) => {
+                engine.epoch().await.saturating_sub(*latest) >= self.config.snapshot_interval
+            }
+            None => true,
+        };
+        if due {
+            self.write_recovery_snapshot(engine, group_id).await;
+        } else {
+            self.prune_recovery(engine, group_id).await;
+        }
+    }
+
+    async fn store_processed_commits(&self, group_id: &GroupId, log: ProcessedCommits) {
+        if let Some(storage) = &self.storage {
+            let key = ProcessedCommits::storage_key(group_id);
+            let stored = match log.to_bytes() {
+                Ok(bytes) => storage.put_blob(&key, &bytes).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = stored {
+                warn!("Failed to save commit log of {}: {}", group_id, e);
+            }
+        }
+        self.processed_commits.write().await.insert(group_id.clone(), log);
+    }
+
     /// Add members to a group
     pub async fn add_members(
         &self,
         group_id: &GroupId,
         key_packages: Vec<Vec<u8>>,
+    ) -> MlsResult<(Vec<u8>, Vec<u8>, Vec<u8>)> {
+        self.add_members_with_psk(group_id, key_packages, None).await
+    }
+
+    /// Store an external pre-shared key for use in groups
+    ///
+    /// Must be called before creating or processing a commit, or joining
+    /// from a Welcome, that requires the PSK.
+    pub fn store_psk(&self, psk_id: &[u8], secret: &[u8]) -> MlsResult<()> {
+        use crate::core_mls::engine::openmls_engine::OpenMlsEngine;
+        OpenMlsEngine::store_external_psk(self.provider.as_ref(), psk_id, secret)?;
+
+        if let Err(e) = self.provider.save() {
+            warn!("Failed to save provider state after storing PSK: {}", e);
+        }
+        Ok(())
+    }
+
+    /// Add members to a group, mixing an external PSK into the new epoch
+    ///
+    /// With `psk_id`, the Welcome can only be used by a joiner that has
+    /// stored the same PSK (see [`store_psk`](Self::store_psk)).
+    pub async fn add_members_with_psk(
+        &self,
+        group_id: &GroupId,
+        key_packages: Vec<Vec<u8>>,
+        psk_id: Option<&[u8]>,
     ) -> MlsResult<(Vec<u8>, Vec<u8>, Vec<u8>)> {
         let timer = Timer::new("mls.add_members.duration_ms");
 
         info!("Adding {} members to group {}", key_packages.len(), group_id);
 
+        // Usually answered from the cache: callers validate before queueing
+        if let Some(Err(e)) =
+            self.verify_key_packages(&key_packages).into_iter().find(|result| result.is_err())
+        {
+            return Err(e);
+        }
+
         // Get the group
         let groups = self.groups.read().await;
         let adapter = groups
             .get(group_id)
             .ok_or_else(|| MlsError::GroupNotFound(group_id.to_string()))?;
 
-        // Add members (using the group_ops trait method)
+        // Add members, with the PSK proposal if one is required
         let engine_ref = adapter.engine();
         let engine = engine_ref.read().await;
-        let (commit, welcome_opt) = engine.add_members(key_packages).await?;
+        let base = self.fork_base(&engine).await;
+        let dropped = key_packages.iter().cloned().map(DroppedChange::Add).collect();
+        let (commit, welcome_opt) = engine.add_members_with_psk(key_packages, psk_id).await?;
+        self.record_own_commit(&engine, group_id, base, &commit, dropped).await;
 
         // Convert Option<Vec<u8>> to Vec<u8> (empty vec if None)
         let welcome = welcome_opt.unwrap_or_default();
@@ -692,8 +1454,26 @@ impl MlsService {
         // Remove members (using the group_ops trait method)
         let engine_ref = adapter.engine();
         let engine = engine_ref.read().await;
+        let base = self.fork_base(&engine).await;
+        let dropped = engine
+            .metadata()
+            .await?
+            .members
+            .into_iter()
+            .filter(|member| leaf_indices.contains(&member.leaf_index))
+            .map(|member| DroppedChange::Remove(member.signature_key))
+            .collect();
         let commit = engine.remove_members(leaf_indices).await?;
+        self.record_own_commit(&engine, group_id, base, &commit, dropped).await;
         drop(engine); // Release lock before saving
+        drop(groups);
+
+        // Persist the new epoch so the removal survives a restart
+        if self.storage.is_some() {
+            if let Err(e) = self.save_group(group_id).await {
+                warn!("Failed to save group {} after removing members: {}", group_id, e);
+            }
+        }
 
         // Save provider state (membership changes)
         if let Err(e) = self.provider.save() {
@@ -709,6 +1489,285 @@ impl MlsService {
         Ok(commit)
     }
 
+    /// Leave a group and delete its local state
+    ///
+    /// Produces a self-remove proposal, then drops the group from memory,
+    /// from the OpenMLS provider and from snapshot storage. A remaining
+    /// member must commit the proposal with
+    /// [`commit_pending`](Self::commit_pending) to complete the removal.
+    ///
+    /// # Returns
+    /// Serialized self-remove proposal to broadcast to the group
+    pub async fn leave_group(&self, group_id: &GroupId) -> MlsResult<Vec<u8>> {
+        info!("Leaving group {}", group_id);
+
+        let adapter = {
+            let groups = self.groups.read().await;
+            groups
+                .get(group_id)
+                .cloned()
+                .ok_or_else(|| MlsError::GroupNotFound(group_id.to_string()))?
+        };
+
+        let engine_ref = adapter.engine();
+        let engine = engine_ref.read().await;
+        let proposal = engine.leave_group().await?;
+        engine.delete_state().await?;
+        drop(engine);
+
+        self.groups.write().await.remove(group_id);
+        self.store_processed_commits(group_id, ProcessedCommits::default()).await;
+        self.processed_commits.write().await.remove(group_id);
+        self.fork_points.write().await.remove(group_id);
+        if let Some(recovery) = &self.recovery {
+            recovery.remove(group_id)?;
+        }
+
+        if let Some(storage) = &self.storage {
+            storage.delete_group_snapshot(&group_id.as_bytes().to_vec()).await?;
+        }
+
+        if let Err(e) = self.provider.save() {
+            warn!("Failed to save provider state after leaving group: {}", e);
+        }
+
+        record_counter("mls.groups.left", 1);
+
+        info!("Left group {}", group_id);
+        Ok(proposal)
+    }
+
+    /// Commit all pending proposals in a group
+    ///
+    /// Used to apply proposals received from other members, such as a
+    /// self-remove sent by a member leaving the group.
+    ///
+    /// # Returns
+    /// Serialized commit message to broadcast to the group
+    pub async fn commit_pending(&self, group_id: &GroupId) -> MlsResult<Vec<u8>> {
+        info!("Committing pending proposals in group {}", group_id);
+
+        let groups = self.groups.read().await;
+        let adapter = groups
+            .get(group_id)
+            .ok_or_else(|| MlsError::GroupNotFound(group_id.to_string()))?;
+
+        let engine_ref = adapter.engine();
+        let engine = engine_ref.read().await;
+        let base = self.fork_base(&engine).await;
+        let (commit, _welcomes) = engine.commit_pending().await?;
+        self.record_own_commit(&engine, group_id, base, &commit, Vec::new()).await;
+        drop(engine);
+        drop(groups);
+
+        // Persist the new epoch so the commit survives a restart
+        if self.storage.is_some() {
+            if let Err(e) = self.save_group(group_id).await {
+                warn!("Failed to save group {} after committing proposals: {}", group_id, e);
+            }
+        }
+
+        if let Err(e) = self.provider.save() {
+            warn!("Failed to save provider state after committing proposals: {}", e);
+        }
+
+        record_counter("mls.commits.created", 1);
+
+        Ok(commit)
+    }
+
+    /// Rotate our own leaf key material in a group
+    ///
+    /// Issues an update commit (see [`GroupOperations::self_update`]) and
+    /// merges it, so the group moves to a new epoch. Epochs that fall out of
+    /// the retention window are purged as part of the merge.
+    ///
+    /// # Returns
+    /// Serialized commit message to broadcast to the group
+    pub async fn self_update(&self, group_id: &GroupId) -> MlsResult<Vec<u8>> {
+        info!("Updating own leaf in group {}", group_id);
+
+        let groups = self.groups.read().await;
+        let adapter = groups
+            .get(group_id)
+            .ok_or_else(|| MlsError::GroupNotFound(group_id.to_string()))?;
+
+        let engine_ref = adapter.engine();
+        let engine = engine_ref.read().await;
+        let base = self.fork_base(&engine).await;
+        let result = engine.self_update().await?;
+        self.record_own_commit(&engine, group_id, base, &result.commit_message, Vec::new())
+            .await;
+        drop(engine);
+        drop(groups);
+
+        if self.storage.is_some() {
+            if let Err(e) = self.save_group(group_id).await {
+                warn!("Failed to save group {} after self-update: {}", group_id, e);
+            }
+        }
+
+        if let Err(e) = self.provider.save() {
+            warn!("Failed to save provider state after self-update: {}", e);
+        }
+
+        record_counter("mls.updates.created", 1);
+        debug!("Group {} moved to epoch {} by self-update", group_id, result.new_epoch);
+
+        Ok(result.commit_message)
+    }
+
+    /// Replace a group's channel metadata
+    ///
+    /// Commits a GroupContextExtensions proposal and merges it. Only
+    /// commits from the group's admin are accepted by the other members.
+    ///
+    /// # Returns
+    /// Serialized commit message to broadcast to the group
+    pub async fn update_channel_metadata(
+        &self,
+        group_id: &GroupId,
+        metadata: &ChannelMetadata,
+    ) -> MlsResult<Vec<u8>> {
+        info!("Updating channel metadata of group {}", group_id);
+
+        let groups = self.groups.read().await;
+        let adapter = groups
+            .get(group_id)
+            .ok_or_else(|| MlsError::GroupNotFound(group_id.to_string()))?;
+
+        let engine_ref = adapter.engine();
+        let engine = engine_ref.read().await;
+        let base = self.fork_base(&engine).await;
+        let commit = engine.update_channel_metadata(metadata).await?;
+        self.record_own_commit(&engine, group_id, base, &commit, Vec::new()).await;
+        drop(engine);
+        drop(groups);
+
+        if self.storage.is_some() {
+            if let Err(e) = self.save_group(group_id).await {
+                warn!("Failed to save group {} after metadata update: {}", group_id, e);
+            }
+        }
+
+        if let Err(e) = self.provider.save() {
+            warn!("Failed to save provider state after metadata update: {}", e);
+        }
+
+        Ok(commit)
+    }
+
+    /// Channel metadata in a group's context, if any has been set
+    pub async fn channel_metadata(
+        &self,
+        group_id: &GroupId,
+    ) -> MlsResult<Option<ChannelMetadata>> {
+        let groups = self.groups.read().await;
+        let adapter = groups
+            .get(group_id)
+            .ok_or_else(|| MlsError::GroupNotFound(group_id.to_string()))?;
+
+        let engine_ref = adapter.engine();
+        let engine = engine_ref.read().await;
+        engine.channel_metadata().await
+    }
+
+    /// Public signature key of our own leaf in a group
+    pub async fn own_signature_key(&self, group_id: &GroupId) -> MlsResult<Vec<u8>> {
+        let groups = self.groups.read().await;
+        let adapter = groups
+            .get(group_id)
+            .ok_or_else(|| MlsError::GroupNotFound(group_id.to_string()))?;
+
+        let engine_ref = adapter.engine();
+        let engine = engine_ref.read().await;
+        Ok(engine.signature_keys().public().to_vec())
+    }
+
+    /// Replace a group's owner and admins
+    ///
+    /// Commits the new roles into the group context and merges the commit.
+    /// Only commits from the current owner are accepted by the other
+    /// members.
+    ///
+    /// # Returns
+    /// Serialized commit message to broadcast to the group
+    pub async fn update_channel_roles(
+        &self,
+        group_id: &GroupId,
+        roles: &ChannelRoles,
+    ) -> MlsResult<Vec<u8>> {
+        info!("Updating roles of group {}", group_id);
+
+        let groups = self.groups.read().await;
+        let adapter = groups
+            .get(group_id)
+            .ok_or_else(|| MlsError::GroupNotFound(group_id.to_string()))?;
+
+        let engine_ref = adapter.engine();
+        let engine = engine_ref.read().await;
+        let base = self.fork_base(&engine).await;
+        let commit = engine.update_channel_roles(roles).await?;
+        self.record_own_commit(&engine, group_id, base, &commit, Vec::new()).await;
+        drop(engine);
+        drop(groups);
+
+        if self.storage.is_some() {
+            if let Err(e) = self.save_group(group_id).await {
+                warn!("Failed to save group {} after role update: {}", group_id, e);
+            }
+        }
+
+        if let Err(e) = self.provider.save() {
+            warn!("Failed to save provider state after role update: {}", e);
+        }
+
+        Ok(commit)
+    }
+
+    /// Owner and admins of a group
+    pub async fn channel_roles(&self, group_id: &GroupId) -> MlsResult<ChannelRoles> {
+        let groups = self.groups.read().await;
+        let adapter = groups
+            .get(group_id)
+            .ok_or_else(|| MlsError::GroupNotFound(group_id.to_string()))?;
+
+        let engine_ref = adapter.engine();
+        let engine = engine_ref.read().await;
+        engine.channel_roles().await
+    }
+
+    /// Delete key material of epochs outside the retention window
+    ///
+    /// Runs automatically whenever a commit is merged; exposed for callers
+    /// that change the retention or want to purge before a backup. Keeps the
+    /// current epoch and `past_epoch_retention` epochs before it, so
+    /// messages from a purged epoch can no longer be decrypted. Recovery
+    /// snapshots of purged epochs are deleted too.
+    ///
+    /// # Returns
+    /// Number of epochs purged
+    pub async fn purge_old_epochs(&self, group_id: &GroupId) -> MlsResult<u64> {
+        let groups = self.groups.read().await;
+        let adapter = groups
+            .get(group_id)
+            .ok_or_else(|| MlsError::GroupNotFound(group_id.to_string()))?;
+
+        let engine_ref = adapter.engine();
+        let engine = engine_ref.read().await;
+        let purged = engine.purge_old_epochs().await?;
+        self.prune_recovery(&engine, group_id).await;
+        drop(engine);
+        drop(groups);
+
+        if purged > 0 {
+            self.provider.save()?;
+            debug!("Purged {} old epochs of group {}", purged, group_id);
+        }
+
+        Ok(purged)
+    }
+
     /// Export ratchet tree for a group
     ///
     /// This exports the current ratchet tree state, which is needed
@@ -726,6 +1785,45 @@ impl MlsService {
         engine.export_ratchet_tree_bytes().await
     }
 
+    /// Export a group's public info for joining by external commit
+    ///
+    /// Carries a GroupInfo for the current epoch and is signed with our
+    /// leaf key, so it has to be exported again after every commit.
+    ///
+    /// # Arguments
+    /// * `group_id` - The group to export
+    /// * `name` - Group name to advertise
+    pub async fn export_public_info(
+        &self,
+        group_id: &GroupId,
+        name: Option<String>,
+    ) -> MlsResult<GroupPublicInfo> {
+        let groups = self.groups.read().await;
+        let adapter = groups
+            .get(group_id)
+            .ok_or_else(|| MlsError::GroupNotFound(group_id.to_string()))?;
+
+        let engine_ref = adapter.engine();
+        let engine = engine_ref.read().await;
+        let mut metadata = engine.metadata().await?;
+        let group_info = engine.export_group_info().await?;
+        metadata.name = name;
+        metadata.updated_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        // Signing can fail, which the signing callback can't report
+        let mut signed = Ok(Vec::new());
+        let mut info =
+            GroupPublicInfo::from_group_info(group_id.clone(), &metadata, group_info, |bytes| {
+                signed = engine.sign(bytes);
+                Vec::new()
+            });
+        info.signature = signed?;
+        Ok(info)
+    }
+
     /// Export a secret from the MLS group (for sealed sender, etc.)
     ///
     /// This uses the MLS exporter interface to derive application-specific secrets
@@ -736,6 +1834,13 @@ impl MlsService {
     /// * `label` - Domain separation label (e.g., "sender_key")
     /// * `context` - Additional context bytes (can be empty)
     /// * `length` - Desired secret length in bytes
+    ///
+    /// Every member gets the same secret for the same label, context and
+    /// length within an epoch; each commit changes it.
+    ///
+    /// # Errors
+    ///
+    /// `InvalidInput` if `length` is zero or above [`MAX_EXPORTED_SECRET_LEN`]
     pub async fn export_secret(
         &self,
         group_id: &GroupId,
@@ -743,6 +1848,13 @@ impl MlsService {
         context: &[u8],
         length: usize,
     ) -> MlsResult<Vec<u8>> {
+        if length == 0 || length > MAX_EXPORTED_SECRET_LEN {
+            return Err(MlsError::InvalidInput(format!(
+                "Exported secret length must be between 1 and {}, got {}",
+                MAX_EXPORTED_SECRET_LEN, length
+            )));
+        }
+
         let groups = self.groups.read().await;
         let adapter = groups
             .get(group_id)
@@ -753,6 +1865,65 @@ impl MlsService {
         engine.export_secret(label, context, length).await
     }
 
+    /// Sealed-sender key of a group for envelopes sealed in `epoch`
+    ///
+    /// Keys rotate with every merged commit; only the current epoch and the
+    /// one before it have one.
+    pub async fn sender_key(&self, group_id: &GroupId, epoch: u64) -> MlsResult<[u8; 32]> {
+        let groups = self.groups.read().await;
+        let adapter = groups
+            .get(group_id)
+            .ok_or_else(|| MlsError::GroupNotFound(group_id.to_string()))?;
+
+        let engine_ref = adapter.engine();
+        let engine = engine_ref.read().await;
+        engine.sender_key_for(epoch).await
+    }
+
+    /// Sign bytes with our leaf signature key in a group
+    ///
+    /// Other members check the signature with
+    /// [`verify_member_signature`](Self::verify_member_signature).
+    pub async fn sign_as_member(&self, group_id: &GroupId, payload: &[u8]) -> MlsResult<Vec<u8>> {
+        let groups = self.groups.read().await;
+        let adapter = groups
+            .get(group_id)
+            .ok_or_else(|| MlsError::GroupNotFound(group_id.to_string()))?;
+
+        let engine_ref = adapter.engine();
+        let engine = engine_ref.read().await;
+        engine.sign(payload)
+    }
+
+    /// Whether a signature was made by one of a member's current leaves
+    ///
+    /// Any leaf whose credential carries `identity` is accepted, so each of
+    /// the member's devices can sign.
+    pub async fn verify_member_signature(
+        &self,
+        group_id: &GroupId,
+        identity: &[u8],
+        payload: &[u8],
+        signature: &[u8],
+    ) -> MlsResult<bool> {
+        let metadata = self.get_metadata(group_id).await?;
+        let crypto = self.provider.crypto();
+        Ok(metadata
+            .members
+            .iter()
+            .filter(|member| member.identity == identity)
+            .any(|member| {
+                crypto
+                    .verify_signature(
+                        SignatureScheme::ED25519,
+                        payload,
+                        &member.signature_key,
+                        signature,
+                    )
+                    .is_ok()
+            }))
+    }
+
     /// Get the current epoch for a group
     ///
     /// Used for binding sealed senders to specific group states.
@@ -856,7 +2027,7 @@ impl MlsService {
         sequence: i64,
         plaintext_content: Option<&[u8]>,
     ) -> MlsResult<()> {
-        if let Some(ref storage) = self.storage {
+        if let Some(ref storage) = self.sql_storage {
             storage
                 .save_message_with_plaintext(message_id, group_id.as_bytes(), encrypted_content, sealed_sender_bytes, sequence, plaintext_content)
                 .await
@@ -873,7 +2044,7 @@ impl MlsService {
         limit: i64,
         offset: i64,
     ) -> MlsResult<Vec<(Vec<u8>, Vec<u8>, Vec<u8>, i64, bool, Option<Vec<u8>>)>> {
-        if let Some(ref storage) = self.storage {
+        if let Some(ref storage) = self.sql_storage {
             storage.load_messages(group_id.as_bytes(), limit, offset).await
         } else {
             warn!("No SQL storage available for loading messages");
@@ -890,7 +2061,7 @@ impl MlsService {
         encrypted_members: &[&[u8]],
         channel_type: i32,
     ) -> MlsResult<()> {
-        if let Some(ref storage) = self.storage {
+        if let Some(ref storage) = self.sql_storage {
             // Concatenate all member IDs
             let members_bytes: Vec<u8> = encrypted_members.iter().flat_map(|&m| m.iter().copied()).collect();
             storage
@@ -906,6 +2077,7 @@ impl MlsService {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core_identity::keystore::memory_keystore::MemoryKeystore;
     use std::time::Duration;
 
     #[tokio::test]
@@ -945,4 +2117,62 @@ mod tests {
         let health = service.health_check().await;
         assert_eq!(health.status, HealthStatus::Healthy);
     }
+
+    /// Create a group and commit, then start over on the same backend
+    ///
+    /// Returns the service started over, after recovering the group.
+    async fn create_commit_reload(backend: StorageBackend) -> (MlsService, GroupId) {
+        let config = Config::default();
+        let keystore = MemoryKeystore::new();
+        let start = |backend| {
+            let shutdown = Arc::new(ShutdownCoordinator::new(Duration::from_secs(30)));
+            MlsService::with_backend(&config, shutdown, backend)
+                .and_then(|service| service.with_recovery_keystore(&keystore))
+                .unwrap()
+        };
+
+        let service = start(backend.clone());
+        let group_id = service.create_group(b"alice".to_vec(), None).await.unwrap();
+        service.self_update(&group_id).await.unwrap();
+        assert_eq!(service.get_epoch(&group_id).await.unwrap(), 1);
+        let storage = service.storage.as_ref().unwrap();
+        let persisted = storage.load_group_snapshot(&group_id.as_bytes().to_vec()).await.unwrap();
+        assert_eq!(persisted.epoch, 1);
+        drop(service);
+
+        let reloaded = start(backend);
+        let _ = reloaded.recover_group(&group_id).await;
+        (reloaded, group_id)
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = StorageBackend::Sqlite { path: dir.path().join("mls").join("mls_state.db") };
+        let (service, group_id) = create_commit_reload(backend).await;
+
+        assert_eq!(service.get_epoch(&group_id).await.unwrap(), 1);
+        assert!(service.sql_storage.is_some());
+        assert!(dir.path().join("mls").join("recovery").is_dir());
+    }
+
+    #[tokio::test]
+    async fn test_file_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = StorageBackend::File { dir: dir.path().join("mls") };
+        let (service, group_id) = create_commit_reload(backend).await;
+
+        assert_eq!(service.get_epoch(&group_id).await.unwrap(), 1);
+        let storage = service.storage.as_ref().unwrap();
+        assert_eq!(storage.list_groups().await.unwrap(), vec![group_id.as_bytes().to_vec()]);
+    }
+
+    #[tokio::test]
+    async fn test_memory_backend() {
+        let (service, group_id) = create_commit_reload(StorageBackend::Memory).await;
+
+        // Nothing survives starting over
+        assert!(service.get_epoch(&group_id).await.is_err());
+        assert!(service.storage.as_ref().unwrap().list_groups().await.unwrap().is_empty());
+    }
 }
@@ -289,6 +289,29 @@ impl StorageProvider for FileStorageProvider {
 
         Ok(groups)
     }
+
+    async fn list_blobs(&self) -> MlsResult<Vec<String>> {
+        let mut keys = Vec::new();
+
+        let mut entries = tokio::fs::read_dir(&self.base_path)
+            .await
+            .map_err(|e| MlsError::Storage(format!("Failed to read directory: {}", e)))?;
+
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .map_err(|e| MlsError::Storage(format!("Failed to read entry: {}", e)))?
+        {
+            let path = entry.path();
+            if let Some(key) = path.file_name().and_then(|n| n.to_str()) {
+                if let Some(key) = key.strip_suffix(".blob") {
+                    keys.push(key.to_string());
+                }
+            }
+        }
+
+        Ok(keys)
+    }
 }
 
 #[cfg(test)]
@@ -72,6 +72,11 @@ impl StorageProvider for MemoryStorageProvider {
         let snapshots = self.snapshots.read().await;
         Ok(snapshots.keys().cloned().collect())
     }
+
+    async fn list_blobs(&self) -> MlsResult<Vec<String>> {
+        let blobs = self.blobs.read().await;
+        Ok(blobs.keys().cloned().collect())
+    }
 }
 
 #[cfg(test)]
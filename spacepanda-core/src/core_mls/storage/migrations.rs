@@ -1,32 +1,57 @@
 //! Database Schema Migration System
 //!
 //! Provides versioned migrations for the SQL storage schema.
-//! Each migration is applied atomically and tracked in the schema_version table.
+//! Migrations form an ordered registry: each has a version, an `up` step
+//! (SQL or a function) and an integrity check run before it commits. Each
+//! migration is applied in its own transaction; the applied version is kept
+//! in the `schema_meta` table and every step is logged to `schema_version`.
 
 use crate::core_mls::errors::{MlsError, MlsResult};
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::params;
+use rusqlite::{params, Connection, OptionalExtension, Transaction, TransactionBehavior};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-/// Current schema version
-pub const CURRENT_SCHEMA_VERSION: i32 = 3;
+/// Current schema version, the version of the last migration
+pub const CURRENT_SCHEMA_VERSION: i32 = 5;
+
+/// How a migration brings the schema up
+pub enum MigrationStep {
+    /// A batch of SQL statements
+    Sql(&'static str),
+    /// Code, for changes SQL alone can't express
+    Fn(fn(&Transaction<'_>) -> rusqlite::Result<()>),
+}
 
 /// Migration descriptor
 pub struct Migration {
     pub version: i32,
     pub description: &'static str,
-    pub up_sql: &'static str,
+    pub up: MigrationStep,
+    /// Verifies the schema after `up`; the migration is rolled back if false
+    pub check: fn(&Connection) -> rusqlite::Result<bool>,
     pub down_sql: Option<&'static str>,
 }
 
+/// Outcome of `migrate_with`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationReport {
+    /// Schema version the database was at
+    pub from_version: i32,
+    /// Version and description of each migration applied, or that would be
+    /// applied on a dry run
+    pub pending: Vec<(i32, &'static str)>,
+    pub dry_run: bool,
+}
+
 /// All available migrations in order
 pub fn get_migrations() -> Vec<Migration> {
     vec![
         Migration {
             version: 1,
             description: "Initial schema with MLS groups, key packages, and metadata",
-            up_sql: r#"
+            up: MigrationStep::Sql(
+                r#"
                 -- Schema version tracking
                 CREATE TABLE IF NOT EXISTS schema_version (
                     version INTEGER PRIMARY KEY,
@@ -79,6 +104,20 @@ pub fn get_migrations() -> Vec<Migration> {
                     updated_at INTEGER NOT NULL
                 );
             "#,
+            ),
+            check: |conn| {
+                tables_exist(
+                    conn,
+                    &[
+                        "schema_version",
+                        "group_snapshots",
+                        "key_packages",
+                        "signature_keys",
+                        "psks",
+                        "kv_blobs",
+                    ],
+                )
+            },
             down_sql: Some(
                 r#"
                 DROP TABLE IF EXISTS kv_blobs;
@@ -94,7 +133,8 @@ pub fn get_migrations() -> Vec<Migration> {
         Migration {
             version: 2,
             description: "Add privacy-focused channel and message metadata tables",
-            up_sql: r#"
+            up: MigrationStep::Sql(
+                r#"
                 -- Privacy-focused channel metadata
                 CREATE TABLE IF NOT EXISTS channels (
                     group_id BLOB PRIMARY KEY,
@@ -125,6 +165,11 @@ pub fn get_migrations() -> Vec<Migration> {
                 CREATE INDEX IF NOT EXISTS idx_messages_unprocessed
                     ON messages(group_id, processed) WHERE processed = 0;
             "#,
+            ),
+            check: |conn| {
+                Ok(tables_exist(conn, &["channels"])?
+                    && has_columns(conn, "messages", &["sender_hash", "sequence"])?)
+            },
             down_sql: Some(
                 r#"
                 DROP INDEX IF EXISTS idx_messages_unprocessed;
@@ -137,7 +182,8 @@ pub fn get_migrations() -> Vec<Migration> {
         Migration {
             version: 3,
             description: "Remove updated_at from group_snapshots for privacy (per privacy audit)",
-            up_sql: r#"
+            up: MigrationStep::Sql(
+                r#"
                 -- Create new table without updated_at
                 CREATE TABLE IF NOT EXISTS group_snapshots_new (
                     group_id BLOB PRIMARY KEY,
@@ -155,6 +201,11 @@ pub fn get_migrations() -> Vec<Migration> {
                 DROP TABLE group_snapshots;
                 ALTER TABLE group_snapshots_new RENAME TO group_snapshots;
             "#,
+            ),
+            check: |conn| {
+                Ok(has_columns(conn, "group_snapshots", &["snapshot_data", "epoch"])?
+                    && !has_columns(conn, "group_snapshots", &["updated_at"])?)
+            },
             down_sql: Some(
                 r#"
                 -- Create old table with updated_at
@@ -180,10 +231,13 @@ pub fn get_migrations() -> Vec<Migration> {
         Migration {
             version: 4,
             description: "Add plaintext_content field for sent messages (MLS senders cannot decrypt own messages)",
-            up_sql: r#"
+            up: MigrationStep::Sql(
+                r#"
                 -- Add plaintext_content column to messages table
                 ALTER TABLE messages ADD COLUMN plaintext_content BLOB;
             "#,
+            ),
+            check: |conn| has_columns(conn, "messages", &["plaintext_content"]),
             down_sql: Some(
                 r#"
                 -- Remove plaintext_content column
@@ -214,7 +268,8 @@ pub fn get_migrations() -> Vec<Migration> {
         Migration {
             version: 5,
             description: "Replace sender_hash with sealed_sender for privacy (metadata protection)",
-            up_sql: r#"
+            up: MigrationStep::Sql(
+                r#"
                 -- PRIVACY MIGRATION: Replace plaintext sender with encrypted sealed sender
                 -- This prevents network observers from linking messages to specific senders
                 
@@ -247,6 +302,11 @@ pub fn get_migrations() -> Vec<Migration> {
                 CREATE INDEX IF NOT EXISTS idx_messages_unprocessed
                     ON messages(group_id, processed) WHERE processed = 0;
             "#,
+            ),
+            check: |conn| {
+                Ok(has_columns(conn, "messages", &["sealed_sender_bytes", "plaintext_content"])?
+                    && !has_columns(conn, "messages", &["sender_hash"])?)
+            },
             down_sql: Some(
                 r#"
                 -- Rollback sealed_sender to sender_hash (privacy degradation)
@@ -279,35 +339,107 @@ pub fn get_migrations() -> Vec<Migration> {
     ]
 }
 
+/// Tables that all exist
+fn tables_exist(conn: &Connection, tables: &[&str]) -> rusqlite::Result<bool> {
+    for table in tables {
+        let found = conn
+            .query_row(
+                "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?",
+                params![table],
+                |_| Ok(()),
+            )
+            .optional()?;
+        if found.is_none() {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Whether `table` exists and has all of `columns`
+fn has_columns(conn: &Connection, table: &str, columns: &[&str]) -> rusqlite::Result<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let existing: Vec<String> =
+        stmt.query_map([], |row| row.get(1))?.collect::<rusqlite::Result<_>>()?;
+    Ok(!existing.is_empty() && columns.iter().all(|c| existing.iter().any(|e| e == c)))
+}
+
+fn read_version(conn: &Connection) -> rusqlite::Result<i32> {
+    if tables_exist(conn, &["schema_meta"])? {
+        let version = conn
+            .query_row("SELECT value FROM schema_meta WHERE key = 'version'", [], |row| row.get(0))
+            .optional()?;
+        if let Some(version) = version {
+            return Ok(version);
+        }
+    }
+
+    // Databases created before schema_meta only have the migration log
+    if tables_exist(conn, &["schema_version"])? {
+        return conn.query_row("SELECT COALESCE(MAX(version), 0) FROM schema_version", [], |row| {
+            row.get(0)
+        });
+    }
+    Ok(0)
+}
+
+fn write_version(conn: &Connection, version: i32) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_meta (key TEXT PRIMARY KEY, value INTEGER NOT NULL)",
+    )?;
+    conn.execute(
+        "INSERT INTO schema_meta (key, value) VALUES ('version', ?)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![version],
+    )?;
+    Ok(())
+}
+
 /// Get current schema version from database
 pub fn get_current_version(pool: &Pool<SqliteConnectionManager>) -> MlsResult<i32> {
     let conn = pool
         .get()
         .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
 
-    let version = conn
-        .query_row("SELECT MAX(version) FROM schema_version", [], |row| {
-            row.get::<_, Option<i32>>(0)
-        })
-        .unwrap_or(Some(0))
-        .unwrap_or(0);
-
-    Ok(version)
+    read_version(&conn)
+        .map_err(|e| MlsError::Storage(format!("Failed to read schema version: {}", e)))
 }
 
 /// Apply a single migration
+///
+/// Does nothing if the database already reached `migration.version`, which
+/// happens when another connection migrated it first.
 fn apply_migration(pool: &Pool<SqliteConnectionManager>, migration: &Migration) -> MlsResult<()> {
     let mut conn = pool
         .get()
         .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
 
     let tx = conn
-        .transaction()
+        .transaction_with_behavior(TransactionBehavior::Immediate)
         .map_err(|e| MlsError::Storage(format!("Failed to begin transaction: {}", e)))?;
 
-    // Execute migration SQL
-    tx.execute_batch(migration.up_sql)
-        .map_err(|e| MlsError::Storage(format!("Migration {} failed: {}", migration.version, e)))?;
+    let version = read_version(&tx)
+        .map_err(|e| MlsError::Storage(format!("Failed to read schema version: {}", e)))?;
+    if version >= migration.version {
+        return Ok(());
+    }
+
+    // Execute migration
+    match &migration.up {
+        MigrationStep::Sql(sql) => tx.execute_batch(sql),
+        MigrationStep::Fn(up) => up(&tx),
+    }
+    .map_err(|e| MlsError::Storage(format!("Migration {} failed: {}", migration.version, e)))?;
+
+    let intact = (migration.check)(&tx).map_err(|e| {
+        MlsError::Storage(format!("Migration {} check failed: {}", migration.version, e))
+    })?;
+    if !intact {
+        return Err(MlsError::Storage(format!(
+            "Migration {} left the schema inconsistent: {}",
+            migration.version, migration.description
+        )));
+    }
 
     // Record migration
     let now = SystemTime::now()
@@ -316,9 +448,10 @@ fn apply_migration(pool: &Pool<SqliteConnectionManager>, migration: &Migration)
         .as_secs() as i64;
 
     tx.execute(
-        "INSERT INTO schema_version (version, applied_at) VALUES (?, ?)",
+        "INSERT OR REPLACE INTO schema_version (version, applied_at) VALUES (?, ?)",
         params![migration.version, now],
     )
+    .and_then(|_| write_version(&tx, migration.version))
     .map_err(|e| MlsError::Storage(format!("Failed to record migration: {}", e)))?;
 
     tx.commit()
@@ -329,25 +462,45 @@ fn apply_migration(pool: &Pool<SqliteConnectionManager>, migration: &Migration)
 
 /// Run all pending migrations
 pub fn migrate(pool: &Pool<SqliteConnectionManager>) -> MlsResult<()> {
-    let current_version = get_current_version(pool)?;
-    let migrations = get_migrations();
+    migrate_with(pool, false).map(|_| ())
+}
 
-    let pending_migrations: Vec<_> =
-        migrations.into_iter().filter(|m| m.version > current_version).collect();
+/// Run all pending migrations, or with `dry_run` only report them
+///
+/// Fails with `MlsError::SchemaTooNew` if the database was written by a
+/// newer version, without touching it.
+pub fn migrate_with(
+    pool: &Pool<SqliteConnectionManager>,
+    dry_run: bool,
+) -> MlsResult<MigrationReport> {
+    let from_version = get_current_version(pool)?;
+    if from_version > CURRENT_SCHEMA_VERSION {
+        return Err(MlsError::SchemaTooNew {
+            found: from_version,
+            supported: CURRENT_SCHEMA_VERSION,
+        });
+    }
 
-    if pending_migrations.is_empty() {
-        return Ok(());
+    let pending_migrations: Vec<_> =
+        get_migrations().into_iter().filter(|m| m.version > from_version).collect();
+    let report = MigrationReport {
+        from_version,
+        pending: pending_migrations.iter().map(|m| (m.version, m.description)).collect(),
+        dry_run,
+    };
+    if dry_run {
+        return Ok(report);
     }
 
     for migration in pending_migrations {
-        println!("Applying migration {}: {}", migration.version, migration.description);
+        tracing::info!("Applying migration {}: {}", migration.version, migration.description);
         apply_migration(pool, &migration)?;
     }
 
-    Ok(())
+    Ok(report)
 }
 
-/// Rollback a migration (if down_sql is available)
+/// Rollback the latest applied migration (if down_sql is available)
 pub fn rollback_migration(pool: &Pool<SqliteConnectionManager>, version: i32) -> MlsResult<()> {
     let migrations = get_migrations();
     let migration = migrations
@@ -364,16 +517,29 @@ pub fn rollback_migration(pool: &Pool<SqliteConnectionManager>, version: i32) ->
         .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
 
     let tx = conn
-        .transaction()
+        .transaction_with_behavior(TransactionBehavior::Immediate)
         .map_err(|e| MlsError::Storage(format!("Failed to begin transaction: {}", e)))?;
 
+    let current = read_version(&tx)
+        .map_err(|e| MlsError::Storage(format!("Failed to read schema version: {}", e)))?;
+    if current != version {
+        return Err(MlsError::Storage(format!(
+            "Cannot roll back version {}: the database is at version {}",
+            version, current
+        )));
+    }
+
     // Execute rollback SQL
     tx.execute_batch(down_sql)
         .map_err(|e| MlsError::Storage(format!("Rollback {} failed: {}", version, e)))?;
 
-    // Remove migration record
-    tx.execute("DELETE FROM schema_version WHERE version = ?", params![version])
-        .map_err(|e| MlsError::Storage(format!("Failed to remove migration record: {}", e)))?;
+    // Remove migration record (version 1 drops the log itself)
+    if tables_exist(&tx, &["schema_version"]).unwrap_or(false) {
+        tx.execute("DELETE FROM schema_version WHERE version = ?", params![version])
+            .map_err(|e| MlsError::Storage(format!("Failed to remove migration record: {}", e)))?;
+    }
+    write_version(&tx, version - 1)
+        .map_err(|e| MlsError::Storage(format!("Failed to record rollback: {}", e)))?;
 
     tx.commit()
         .map_err(|e| MlsError::Storage(format!("Failed to commit rollback: {}", e)))?;
@@ -449,8 +615,13 @@ mod tests {
         migrate(&pool).unwrap();
         assert_eq!(get_current_version(&pool).unwrap(), CURRENT_SCHEMA_VERSION);
 
-        // Rollback version 3 (privacy fix)
-        rollback_migration(&pool, 3).unwrap();
+        // Only the latest migration can be rolled back
+        assert!(rollback_migration(&pool, 3).is_err());
+
+        // Rollback down to before version 3 (privacy fix)
+        for version in (3..=CURRENT_SCHEMA_VERSION).rev() {
+            rollback_migration(&pool, version).unwrap();
+        }
         assert_eq!(get_current_version(&pool).unwrap(), 2);
 
         // Verify group_snapshots still exists after rollback
@@ -486,4 +657,132 @@ mod tests {
 
         assert_eq!(get_current_version(&pool).unwrap(), CURRENT_SCHEMA_VERSION);
     }
+
+    #[test]
+    fn test_registry_is_ordered() {
+        let migrations = get_migrations();
+        assert!(migrations.windows(2).all(|pair| pair[1].version == pair[0].version + 1));
+        assert_eq!(migrations.last().unwrap().version, CURRENT_SCHEMA_VERSION);
+    }
+
+    /// A database as written by the first release: v1 schema, version
+    /// logged in `schema_version` only, and some data
+    fn v1_fixture(db_path: &std::path::Path) {
+        let conn = Connection::open(db_path).unwrap();
+        let MigrationStep::Sql(v1) = get_migrations()[0].up else {
+            unreachable!()
+        };
+        conn.execute_batch(v1).unwrap();
+        conn.execute_batch(
+            r#"
+            INSERT INTO schema_version (version, applied_at) VALUES (1, 1700000000);
+            INSERT INTO group_snapshots (group_id, snapshot_data, epoch, created_at, updated_at)
+                VALUES (x'0102', x'deadbeef', 7, 1700000000, 1700000500);
+            INSERT INTO key_packages (key_package_id, key_package_data, credential_id, created_at)
+                VALUES (x'aa', x'bbbb', x'cc', 1700000000);
+            INSERT INTO kv_blobs (key, value, created_at, updated_at)
+                VALUES ('identity', x'0badcafe', 1700000000, 1700000000);
+            "#,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_migrate_v1_fixture() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("v1.db");
+        v1_fixture(&db_path);
+
+        let pool = Pool::builder().build(SqliteConnectionManager::file(&db_path)).unwrap();
+        assert_eq!(get_current_version(&pool).unwrap(), 1);
+
+        let report = migrate_with(&pool, false).unwrap();
+        assert_eq!(report.from_version, 1);
+        assert_eq!(
+            report.pending.iter().map(|(version, _)| *version).collect::<Vec<_>>(),
+            (2..=CURRENT_SCHEMA_VERSION).collect::<Vec<_>>()
+        );
+        assert_eq!(get_current_version(&pool).unwrap(), CURRENT_SCHEMA_VERSION);
+
+        // Checks of the migrations after v2 hold on the result
+        let conn = pool.get().unwrap();
+        for migration in get_migrations().iter().skip(2) {
+            assert!((migration.check)(&conn).unwrap(), "check {} failed", migration.version);
+        }
+
+        // Data written by v1 survived
+        let (snapshot, epoch): (Vec<u8>, i64) = conn
+            .query_row(
+                "SELECT snapshot_data, epoch FROM group_snapshots WHERE group_id = x'0102'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((snapshot, epoch), (vec![0xde, 0xad, 0xbe, 0xef], 7));
+        let key_package: Vec<u8> = conn
+            .query_row(
+                "SELECT key_package_data FROM key_packages WHERE key_package_id = x'aa'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(key_package, vec![0xbb, 0xbb]);
+        let blob: Vec<u8> = conn
+            .query_row("SELECT value FROM kv_blobs WHERE key = 'identity'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(blob, vec![0x0b, 0xad, 0xca, 0xfe]);
+    }
+
+    #[test]
+    fn test_dry_run_reports_without_migrating() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("dry_run.db");
+        v1_fixture(&db_path);
+        let pool = Pool::builder().build(SqliteConnectionManager::file(&db_path)).unwrap();
+
+        let report = migrate_with(&pool, true).unwrap();
+        assert!(report.dry_run);
+        assert_eq!(report.pending.len(), (CURRENT_SCHEMA_VERSION - 1) as usize);
+        assert_eq!(get_current_version(&pool).unwrap(), 1);
+
+        let conn = pool.get().unwrap();
+        assert!(!tables_exist(&conn, &["channels"]).unwrap());
+    }
+
+    #[test]
+    fn test_newer_database_refused() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("newer.db");
+        let pool = Pool::builder().build(SqliteConnectionManager::file(&db_path)).unwrap();
+        migrate(&pool).unwrap();
+        write_version(&pool.get().unwrap(), CURRENT_SCHEMA_VERSION + 1).unwrap();
+
+        for dry_run in [true, false] {
+            assert!(matches!(
+                migrate_with(&pool, dry_run),
+                Err(MlsError::SchemaTooNew { found, supported })
+                    if found == CURRENT_SCHEMA_VERSION + 1 && supported == CURRENT_SCHEMA_VERSION
+            ));
+        }
+    }
+
+    #[test]
+    fn test_failed_check_rolls_back() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("check.db");
+        let pool = Pool::builder().build(SqliteConnectionManager::file(&db_path)).unwrap();
+        migrate(&pool).unwrap();
+
+        let broken = Migration {
+            version: CURRENT_SCHEMA_VERSION + 1,
+            description: "Creates the wrong table",
+            up: MigrationStep::Fn(|tx| tx.execute_batch("CREATE TABLE wrong (id INTEGER)")),
+            check: |conn| tables_exist(conn, &["right"]),
+            down_sql: None,
+        };
+        assert!(apply_migration(&pool, &broken).is_err());
+
+        assert_eq!(get_current_version(&pool).unwrap(), CURRENT_SCHEMA_VERSION);
+        assert!(!tables_exist(&pool.get().unwrap(), &["wrong"]).unwrap());
+    }
 }
@@ -7,6 +7,7 @@ pub mod file_store;
 pub mod memory_store;
 pub mod metadata_encryption;
 pub mod migrations;
+pub mod provider_migration;
 pub mod sql_store;
 
 #[cfg(test)]
@@ -17,5 +18,8 @@ mod stress_tests;
 pub use channel_metadata::{ChannelMetadata, MessageMetadata};
 pub use file_store::FileStorageProvider;
 pub use memory_store::MemoryStorageProvider;
-pub use migrations::{migrate, CURRENT_SCHEMA_VERSION};
-pub use sql_store::SqlStorageProvider;
+pub use migrations::{migrate, migrate_with, MigrationReport, CURRENT_SCHEMA_VERSION};
+pub use provider_migration::{
+    is_migrated, migrate_provider, ProviderMigrationReport, StorageConflict,
+};
+pub use sql_store::{SqlStorageConfig, SqlStorageProvider};
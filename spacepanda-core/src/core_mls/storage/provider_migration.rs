@@ -0,0 +1,242 @@
+//! Migration Between Storage Providers
+//!
+//! Copies everything a `StorageProvider` holds into another one, e.g. from
+//! the memory or file store of early releases into `SqlStorageProvider`:
+//! group snapshots, and the blobs that hold key packages, signature keys,
+//! epoch secrets and PSKs.
+//!
+//! Nothing in the destination is ever overwritten. If it already holds
+//! different data under a group id or blob key from the source, the
+//! migration aborts before writing and reports the conflicts. Each copied
+//! group is loaded back and must produce the same tree hash; only then is
+//! the source marked migrated.
+
+use crate::core_mls::errors::{MlsError, MlsResult};
+use crate::core_mls::state::GroupSnapshot;
+use crate::core_mls::traits::storage::{GroupId, PersistedGroupSnapshot, StorageProvider};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Blob marking a source as migrated; holds the migration time (Unix
+/// seconds, big endian)
+pub const MIGRATED_KEY: &str = "storage_migrated";
+
+/// Entry the destination holds different data for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageConflict {
+    Group(GroupId),
+    Blob(String),
+}
+
+impl fmt::Display for StorageConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageConflict::Group(group_id) => write!(f, "group {}", hex::encode(group_id)),
+            StorageConflict::Blob(key) => write!(f, "blob '{}'", key),
+        }
+    }
+}
+
+/// Outcome of `migrate_provider`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProviderMigrationReport {
+    /// Groups now in the destination
+    pub groups: usize,
+    /// Blobs now in the destination
+    pub blobs: usize,
+    /// Why the migration was aborted; nothing was written if not empty
+    pub conflicts: Vec<StorageConflict>,
+}
+
+impl ProviderMigrationReport {
+    /// Whether everything was copied and the source marked migrated
+    pub fn is_migrated(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// Whether `migrate_provider` completed with this source
+pub async fn is_migrated(src: &dyn StorageProvider) -> MlsResult<bool> {
+    match src.get_blob(MIGRATED_KEY).await {
+        Ok(_) => Ok(true),
+        Err(MlsError::NotFound(_)) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Copy all groups and blobs from `src` to `dst`
+///
+/// Entries the destination already holds with the same data are left as
+/// they are, so an interrupted migration can be run again.
+pub async fn migrate_provider(
+    src: &dyn StorageProvider,
+    dst: &dyn StorageProvider,
+) -> MlsResult<ProviderMigrationReport> {
+    let mut groups = Vec::new();
+    for group_id in src.list_groups().await? {
+        let snapshot = src.load_group_snapshot(&group_id).await?;
+        let hash = tree_hash(&snapshot)?;
+        groups.push((snapshot, hash));
+    }
+    let mut blobs = Vec::new();
+    for key in src.list_blobs().await? {
+        if key != MIGRATED_KEY {
+            let data = src.get_blob(&key).await?;
+            blobs.push((key, data));
+        }
+    }
+
+    let mut report = ProviderMigrationReport::default();
+    for (snapshot, _) in &groups {
+        match dst.load_group_snapshot(&snapshot.group_id).await {
+            Ok(existing)
+                if existing.epoch != snapshot.epoch
+                    || existing.serialized_group != snapshot.serialized_group =>
+            {
+                report.conflicts.push(StorageConflict::Group(snapshot.group_id.clone()));
+            }
+            Ok(_) | Err(MlsError::NotFound(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    for (key, data) in &blobs {
+        match dst.get_blob(key).await {
+            Ok(existing) if existing != *data => {
+                report.conflicts.push(StorageConflict::Blob(key.clone()));
+            }
+            Ok(_) | Err(MlsError::NotFound(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    if !report.conflicts.is_empty() {
+        return Ok(report);
+    }
+
+    for (key, data) in &blobs {
+        dst.put_blob(key, data).await?;
+        if dst.get_blob(key).await? != *data {
+            return Err(MlsError::Storage(format!("Blob '{}' changed while copying", key)));
+        }
+    }
+    report.groups = groups.len();
+    report.blobs = blobs.len();
+    for (snapshot, hash) in groups {
+        let group_id = snapshot.group_id.clone();
+        let epoch = snapshot.epoch;
+        dst.save_group_snapshot(snapshot).await?;
+
+        let copied = dst.load_group_snapshot(&group_id).await?;
+        if copied.epoch != epoch || tree_hash(&copied)? != hash {
+            return Err(MlsError::TreeHashMismatch { epoch });
+        }
+    }
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    src.put_blob(MIGRATED_KEY, &now.to_be_bytes()).await?;
+
+    Ok(report)
+}
+
+/// Hash of the ratchet tree in a persisted group
+fn tree_hash(snapshot: &PersistedGroupSnapshot) -> MlsResult<Vec<u8>> {
+    let group = GroupSnapshot::from_bytes(&snapshot.serialized_group)?;
+    Ok(Sha256::digest(&group.ratchet_tree_bytes).to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core_mls::storage::{
+        FileStorageProvider, MemoryStorageProvider, SqlStorageProvider,
+    };
+    use crate::core_mls::types::GroupId as MlsGroupId;
+    use tempfile::TempDir;
+
+    fn persisted(group_id: &[u8], epoch: u64, tree: &[u8]) -> PersistedGroupSnapshot {
+        let snapshot = GroupSnapshot::new(
+            MlsGroupId::new(group_id.to_vec()),
+            epoch,
+            tree.to_vec(),
+            vec![0; 8],
+            Vec::new(),
+            0,
+        );
+        PersistedGroupSnapshot {
+            group_id: group_id.to_vec(),
+            epoch,
+            serialized_group: snapshot.to_bytes().unwrap(),
+        }
+    }
+
+    async fn populate(src: &dyn StorageProvider) {
+        src.save_group_snapshot(persisted(b"group-a", 3, b"tree-a")).await.unwrap();
+        src.save_group_snapshot(persisted(b"group-b", 9, b"tree-b")).await.unwrap();
+        src.put_blob("key_package_1", b"kp").await.unwrap();
+        src.put_blob("signature_key_1", b"sk").await.unwrap();
+        src.put_blob("psk_1", b"psk").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_migrate_memory_to_sql() {
+        let dir = TempDir::new().unwrap();
+        let src = MemoryStorageProvider::new();
+        let dst = SqlStorageProvider::new(dir.path().join("mls.db")).unwrap();
+        populate(&src).await;
+
+        let report = migrate_provider(&src, &dst).await.unwrap();
+        assert!(report.is_migrated());
+        assert_eq!((report.groups, report.blobs), (2, 3));
+        assert!(is_migrated(&src).await.unwrap());
+
+        let mut groups = dst.list_groups().await.unwrap();
+        groups.sort();
+        assert_eq!(groups, vec![b"group-a".to_vec(), b"group-b".to_vec()]);
+        let copied = dst.load_group_snapshot(&b"group-b".to_vec()).await.unwrap();
+        assert_eq!(copied.epoch, 9);
+        assert_eq!(tree_hash(&copied).unwrap(), Sha256::digest(b"tree-b").to_vec());
+        assert_eq!(dst.get_blob("psk_1").await.unwrap(), b"psk");
+
+        // The marker stays with the source
+        assert!(!is_migrated(&dst).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_migrate_file_store_again() {
+        let dir = TempDir::new().unwrap();
+        let src = FileStorageProvider::new(dir.path().join("files"), Some("secret")).unwrap();
+        let dst = SqlStorageProvider::new(dir.path().join("mls.db")).unwrap();
+        populate(&src).await;
+
+        assert!(migrate_provider(&src, &dst).await.unwrap().is_migrated());
+
+        // Running it again finds the same data and succeeds
+        let report = migrate_provider(&src, &dst).await.unwrap();
+        assert_eq!(report.conflicts, Vec::new());
+        assert_eq!(dst.list_blobs().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_conflicting_destination_is_not_overwritten() {
+        let dir = TempDir::new().unwrap();
+        let src = MemoryStorageProvider::new();
+        let dst = SqlStorageProvider::new(dir.path().join("mls.db")).unwrap();
+        populate(&src).await;
+        let theirs = persisted(b"group-a", 4, b"other tree");
+        dst.save_group_snapshot(theirs.clone()).await.unwrap();
+        dst.put_blob("psk_1", b"other psk").await.unwrap();
+
+        let report = migrate_provider(&src, &dst).await.unwrap();
+        assert!(!report.is_migrated());
+        assert_eq!(report.conflicts.len(), 2);
+        assert!(report.conflicts.contains(&StorageConflict::Group(b"group-a".to_vec())));
+        assert!(report.conflicts.contains(&StorageConflict::Blob("psk_1".to_string())));
+
+        // Nothing was written and the source isn't marked
+        let kept = dst.load_group_snapshot(&b"group-a".to_vec()).await.unwrap();
+        assert_eq!(kept.serialized_group, theirs.serialized_group);
+        assert!(dst.load_group_snapshot(&b"group-b".to_vec()).await.is_err());
+        assert!(dst.get_blob("key_package_1").await.is_err());
+        assert!(!is_migrated(&src).await.unwrap());
+    }
+}
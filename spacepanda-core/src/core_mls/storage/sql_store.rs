@@ -8,36 +8,89 @@
 //! - Arbitrary key-value blobs
 //!
 //! Uses connection pooling for concurrent access and transactions for atomicity.
+//!
+//! The database runs in WAL mode, so readers never wait for a writer and
+//! operations on different groups only contend for the short write lock.
+//! A connection waits up to [`SqlStorageConfig::busy_timeout`] for that
+//! lock; an operation that still finds the database busy or locked is
+//! retried with exponential backoff before it fails with
+//! `MlsError::Storage`.
 
 use crate::core_mls::errors::{MlsError, MlsResult};
 use crate::core_mls::storage::metadata_encryption::{decrypt_metadata, encrypt_metadata};
 use crate::core_mls::traits::storage::{GroupId, PersistedGroupSnapshot, StorageProvider};
+use crate::metrics::record_counter;
 use async_trait::async_trait;
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::{params, Connection, OptionalExtension};
+use rusqlite::{params, Connection, ErrorCode, OptionalExtension, TransactionBehavior};
 use std::path::Path;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Connection pool settings of a [`SqlStorageProvider`]
+#[derive(Debug, Clone)]
+pub struct SqlStorageConfig {
+    /// Connections in the pool
+    pub max_connections: u32,
+
+    /// How long a connection waits for a lock held by another one
+    pub busy_timeout: Duration,
+
+    /// Retries of an operation that still found the database busy
+    pub max_retries: u32,
+
+    /// Pause before the first retry, doubled for every further one
+    pub retry_backoff: Duration,
+}
+
+impl Default for SqlStorageConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 16,
+            busy_timeout: Duration::from_secs(5),
+            max_retries: 5,
+            retry_backoff: Duration::from_millis(10),
+        }
+    }
+}
 
 /// SQLite-backed storage provider
 pub struct SqlStorageProvider {
     pool: Arc<Pool<SqliteConnectionManager>>,
+    config: SqlStorageConfig,
 }
 
 impl SqlStorageProvider {
     /// Create a new SQL storage provider
     ///
+    /// Connections run with `secure_delete`, so SQLite zeroes the content of
+    /// deleted or overwritten rows (e.g. a group snapshot replaced after a
+    /// commit) instead of leaving it in free pages.
+    ///
     /// # Arguments
     /// * `db_path` - Path to SQLite database file
     pub fn new<P: AsRef<Path>>(db_path: P) -> MlsResult<Self> {
-        let manager = SqliteConnectionManager::file(db_path);
+        Self::with_config(db_path, SqlStorageConfig::default())
+    }
+
+    /// Create a new SQL storage provider with non-default pool settings
+    pub fn with_config<P: AsRef<Path>>(db_path: P, config: SqlStorageConfig) -> MlsResult<Self> {
+        let busy_timeout = config.busy_timeout;
+        let manager = SqliteConnectionManager::file(db_path).with_init(move |conn| {
+            conn.busy_timeout(busy_timeout)?;
+            conn.execute_batch(
+                "PRAGMA journal_mode = WAL;
+                 PRAGMA synchronous = NORMAL;
+                 PRAGMA secure_delete = ON;",
+            )
+        });
         let pool = Pool::builder()
-            .max_size(16) // Support concurrent access
+            .max_size(config.max_connections)
             .build(manager)
             .map_err(|e| MlsError::Storage(format!("Failed to create connection pool: {}", e)))?;
 
-        let provider = Self { pool: Arc::new(pool) };
+        let provider = Self { pool: Arc::new(pool), config };
 
         // Run migrations to ensure schema is up to date
         super::migrations::migrate(&provider.pool)?;
@@ -80,30 +133,44 @@ impl SqlStorageProvider {
         .map_err(|e| MlsError::Storage(format!("Task join error: {}", e)))?
     }
 
+    /// Run a database operation on a pooled connection
+    ///
+    /// The operation runs on the blocking thread pool and is retried as a
+    /// whole while the database is busy, so it must be safe to repeat:
+    /// a single statement, or several in one transaction.
+    async fn run<T, F>(&self, context: &'static str, op: F) -> MlsResult<T>
+    where
+        T: Send + 'static,
+        F: FnMut(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let pool = self.pool.clone();
+        let config = self.config.clone();
+
+        tokio::task::spawn_blocking(move || run_with_retry(&pool, &config, context, op))
+            .await
+            .map_err(|e| MlsError::Storage(format!("Task join error: {}", e)))?
+    }
+
     /// Save multiple group snapshots atomically
     pub async fn save_group_snapshots_atomic(
         &self,
         snapshots: &[PersistedGroupSnapshot],
     ) -> MlsResult<()> {
-        let pool = self.pool.clone();
-        let snapshots = snapshots.to_vec();
-
-        tokio::task::spawn_blocking(move || {
-            let mut conn = pool
-                .get()
-                .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
-
-            let tx = conn
-                .transaction()
-                .map_err(|e| MlsError::Storage(format!("Failed to begin transaction: {}", e)))?;
-
-            let now = current_timestamp();
-
-            for snapshot in snapshots {
-                let snapshot_bytes = serde_json::to_vec(&snapshot).map_err(|e| {
+        let rows = snapshots
+            .iter()
+            .map(|snapshot| {
+                let snapshot_bytes = serde_json::to_vec(snapshot).map_err(|e| {
                     MlsError::Serialization(format!("Failed to serialize snapshot: {}", e))
                 })?;
+                Ok((snapshot.group_id.clone(), snapshot.epoch, snapshot_bytes))
+            })
+            .collect::<MlsResult<Vec<_>>>()?;
+
+        self.run("Failed to save group snapshots", move |conn| {
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let now = current_timestamp();
 
+            for (group_id, epoch, snapshot_bytes) in &rows {
                 tx.execute(
                     r#"
                     INSERT INTO group_snapshots (group_id, epoch, snapshot_data, created_at)
@@ -112,87 +179,44 @@ impl SqlStorageProvider {
                         epoch = excluded.epoch,
                         snapshot_data = excluded.snapshot_data
                     "#,
-                    params![&snapshot.group_id, &snapshot.epoch, &snapshot_bytes, now],
-                )
-                .map_err(|e| MlsError::Storage(format!("Failed to save group snapshot: {}", e)))?;
+                    params![group_id, epoch, snapshot_bytes, now],
+                )?;
             }
 
             tx.commit()
-                .map_err(|e| MlsError::Storage(format!("Failed to commit transaction: {}", e)))?;
-
-            Ok(())
         })
         .await
-        .map_err(|e| MlsError::Storage(format!("Task join error: {}", e)))?
     }
 
     /// Delete multiple groups atomically
     pub async fn delete_groups_atomic(&self, group_ids: &[GroupId]) -> MlsResult<()> {
-        let pool = self.pool.clone();
         let group_ids: Vec<Vec<u8>> = group_ids.to_vec();
 
-        tokio::task::spawn_blocking(move || {
-            let mut conn = pool
-                .get()
-                .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
+        self.run("Failed to delete groups", move |conn| {
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
 
-            let tx = conn
-                .transaction()
-                .map_err(|e| MlsError::Storage(format!("Failed to begin transaction: {}", e)))?;
-
-            for group_id in group_ids {
-                tx.execute("DELETE FROM group_snapshots WHERE group_id = ?", params![&group_id])
-                    .map_err(|e| MlsError::Storage(format!("Failed to delete group: {}", e)))?;
+            for group_id in &group_ids {
+                tx.execute("DELETE FROM group_snapshots WHERE group_id = ?", params![group_id])?;
             }
 
             tx.commit()
-                .map_err(|e| MlsError::Storage(format!("Failed to commit transaction: {}", e)))?;
-
-            Ok(())
         })
         .await
-        .map_err(|e| MlsError::Storage(format!("Task join error: {}", e)))?
     }
 
-    /// Initialize database schema
     /// Clean up expired key packages
     pub fn cleanup_expired_key_packages(&self) -> MlsResult<usize> {
-        let conn = self
-            .pool
-            .get()
-            .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
-
-        let now = current_timestamp();
-        let deleted = conn
-            .execute(
+        run_with_retry(&self.pool, &self.config, "Failed to cleanup expired key packages", |conn| {
+            conn.execute(
                 "DELETE FROM key_packages WHERE expires_at IS NOT NULL AND expires_at < ?",
-                params![now],
+                params![current_timestamp()],
             )
-            .map_err(|e| {
-                MlsError::Storage(format!("Failed to cleanup expired key packages: {}", e))
-            })?;
-
-        Ok(deleted)
+        })
     }
 
     /// List all stored group IDs
     pub fn list_all_groups(&self) -> MlsResult<Vec<GroupId>> {
-        let conn = self
-            .pool
-            .get()
-            .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
-
-        let mut stmt = conn
-            .prepare("SELECT group_id FROM group_snapshots")
-            .map_err(|e| MlsError::Storage(format!("Failed to prepare statement: {}", e)))?;
-
-        let groups = stmt
-            .query_map([], |row| row.get::<_, Vec<u8>>(0))
-            .map_err(|e| MlsError::Storage(format!("Failed to query groups: {}", e)))?
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| MlsError::Storage(format!("Failed to collect groups: {}", e)))?;
-
-        Ok(groups)
+        run_with_retry(&self.pool, &self.config, "Failed to list groups", select_group_ids)
     }
 
     /// Store a key package for future use
@@ -209,18 +233,11 @@ impl SqlStorageProvider {
         credential_id: &[u8],
         expires_at: Option<i64>,
     ) -> MlsResult<()> {
-        let pool = self.pool.clone();
         let key_package_id = key_package_id.to_vec();
         let key_package_data = key_package_data.to_vec();
         let credential_id = credential_id.to_vec();
 
-        tokio::task::spawn_blocking(move || {
-            let conn = pool
-                .get()
-                .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
-
-            let now = current_timestamp();
-
+        self.run("Failed to store key package", move |conn| {
             conn.execute(
                 r#"
                 INSERT INTO key_packages (key_package_id, key_package_data, credential_id, created_at, expires_at, used)
@@ -230,103 +247,87 @@ impl SqlStorageProvider {
                     credential_id = excluded.credential_id,
                     expires_at = excluded.expires_at
                 "#,
-                params![&key_package_id, &key_package_data, &credential_id, now, expires_at],
-            )
-            .map_err(|e| MlsError::Storage(format!("Failed to store key package: {}", e)))?;
-
+                params![
+                    &key_package_id,
+                    &key_package_data,
+                    &credential_id,
+                    current_timestamp(),
+                    expires_at
+                ],
+            )?;
             Ok(())
         })
         .await
-        .map_err(|e| MlsError::Storage(format!("Task join error: {}", e)))?
     }
 
     /// Load an unused key package
     ///
     /// Returns the first unused, non-expired key package and marks it as used.
+    /// Both happen in one transaction, so concurrent loads can't both get it.
     pub async fn load_key_package(&self, key_package_id: &[u8]) -> MlsResult<Vec<u8>> {
-        let pool = self.pool.clone();
         let key_package_id = key_package_id.to_vec();
 
-        tokio::task::spawn_blocking(move || {
-            let conn = pool
-                .get()
-                .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
-
-            let now = current_timestamp();
-
-            // Get unused, non-expired key package
-            let data: Vec<u8> = conn
-                .query_row(
-                    r#"
-                    SELECT key_package_data FROM key_packages
-                    WHERE key_package_id = ?
-                      AND used = 0
-                      AND (expires_at IS NULL OR expires_at > ?)
-                    "#,
-                    params![&key_package_id, now],
-                    |row| row.get(0),
-                )
-                .optional()
-                .map_err(|e| MlsError::Storage(format!("Failed to load key package: {}", e)))?
-                .ok_or_else(|| {
-                    MlsError::NotFound(format!(
-                        "Key package {:?} not found or expired",
-                        key_package_id
-                    ))
-                })?;
-
-            // Mark as used
-            conn.execute(
-                "UPDATE key_packages SET used = 1 WHERE key_package_id = ?",
-                params![&key_package_id],
-            )
-            .map_err(|e| MlsError::Storage(format!("Failed to mark key package as used: {}", e)))?;
+        let data = self
+            .run("Failed to load key package", {
+                let key_package_id = key_package_id.clone();
+                move |conn| {
+                    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+                    // Get unused, non-expired key package
+                    let data: Option<Vec<u8>> = tx
+                        .query_row(
+                            r#"
+                            SELECT key_package_data FROM key_packages
+                            WHERE key_package_id = ?
+                              AND used = 0
+                              AND (expires_at IS NULL OR expires_at > ?)
+                            "#,
+                            params![&key_package_id, current_timestamp()],
+                            |row| row.get(0),
+                        )
+                        .optional()?;
+
+                    // Mark as used
+                    if data.is_some() {
+                        tx.execute(
+                            "UPDATE key_packages SET used = 1 WHERE key_package_id = ?",
+                            params![&key_package_id],
+                        )?;
+                    }
+
+                    tx.commit()?;
+                    Ok(data)
+                }
+            })
+            .await?;
 
-            Ok(data)
+        data.ok_or_else(|| {
+            MlsError::NotFound(format!("Key package {:?} not found or expired", key_package_id))
         })
-        .await
-        .map_err(|e| MlsError::Storage(format!("Task join error: {}", e)))?
     }
 
     /// Delete a key package
     pub async fn delete_key_package(&self, key_package_id: &[u8]) -> MlsResult<()> {
-        let pool = self.pool.clone();
         let key_package_id = key_package_id.to_vec();
 
-        tokio::task::spawn_blocking(move || {
-            let conn = pool
-                .get()
-                .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
-
+        self.run("Failed to delete key package", move |conn| {
             conn.execute(
                 "DELETE FROM key_packages WHERE key_package_id = ?",
                 params![&key_package_id],
-            )
-            .map_err(|e| MlsError::Storage(format!("Failed to delete key package: {}", e)))?;
-
+            )?;
             Ok(())
         })
         .await
-        .map_err(|e| MlsError::Storage(format!("Task join error: {}", e)))?
     }
 }
 
 #[async_trait]
 impl StorageProvider for SqlStorageProvider {
     async fn save_group_snapshot(&self, snapshot: PersistedGroupSnapshot) -> MlsResult<()> {
-        let pool = self.pool.clone();
-
-        tokio::task::spawn_blocking(move || {
-            let conn = pool
-                .get()
-                .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
-
-            let snapshot_bytes = serde_json::to_vec(&snapshot).map_err(|e| {
-                MlsError::Serialization(format!("Failed to serialize snapshot: {}", e))
-            })?;
-
-            let now = current_timestamp();
+        let snapshot_bytes = serde_json::to_vec(&snapshot)
+            .map_err(|e| MlsError::Serialization(format!("Failed to serialize snapshot: {}", e)))?;
 
+        self.run("Failed to save snapshot", move |conn| {
             conn.execute(
                 r#"
                 INSERT INTO group_snapshots (group_id, snapshot_data, epoch, created_at)
@@ -335,76 +336,51 @@ impl StorageProvider for SqlStorageProvider {
                     snapshot_data = excluded.snapshot_data,
                     epoch = excluded.epoch
                 "#,
-                params![&snapshot.group_id, &snapshot_bytes, snapshot.epoch, now,],
-            )
-            .map_err(|e| MlsError::Storage(format!("Failed to save snapshot: {}", e)))?;
-
+                params![&snapshot.group_id, &snapshot_bytes, snapshot.epoch, current_timestamp()],
+            )?;
             Ok(())
         })
         .await
-        .map_err(|e| MlsError::Storage(format!("Task join error: {}", e)))?
     }
 
     async fn load_group_snapshot(&self, group_id: &GroupId) -> MlsResult<PersistedGroupSnapshot> {
-        let pool = self.pool.clone();
         let group_id = group_id.clone();
 
-        tokio::task::spawn_blocking(move || {
-            let conn = pool
-                .get()
-                .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
-
-            let snapshot_bytes: Vec<u8> = conn
-                .query_row(
-                    "SELECT snapshot_data FROM group_snapshots WHERE group_id = ?",
-                    params![&group_id],
-                    |row| row.get(0),
-                )
-                .optional()
-                .map_err(|e| MlsError::Storage(format!("Failed to load snapshot: {}", e)))?
-                .ok_or_else(|| MlsError::NotFound(format!("Group {:?} not found", group_id)))?;
-
-            let snapshot: PersistedGroupSnapshot = serde_json::from_slice(&snapshot_bytes)
-                .map_err(|e| {
-                    MlsError::Serialization(format!("Failed to deserialize snapshot: {}", e))
-                })?;
+        let snapshot_bytes = self
+            .run("Failed to load snapshot", {
+                let group_id = group_id.clone();
+                move |conn| {
+                    conn.query_row(
+                        "SELECT snapshot_data FROM group_snapshots WHERE group_id = ?",
+                        params![&group_id],
+                        |row| row.get::<_, Vec<u8>>(0),
+                    )
+                    .optional()
+                }
+            })
+            .await?
+            .ok_or_else(|| MlsError::NotFound(format!("Group {:?} not found", group_id)))?;
 
-            Ok(snapshot)
-        })
-        .await
-        .map_err(|e| MlsError::Storage(format!("Task join error: {}", e)))?
+        serde_json::from_slice(&snapshot_bytes)
+            .map_err(|e| MlsError::Serialization(format!("Failed to deserialize snapshot: {}", e)))
     }
 
     async fn delete_group_snapshot(&self, group_id: &GroupId) -> MlsResult<()> {
-        let pool = self.pool.clone();
         let group_id = group_id.clone();
 
-        tokio::task::spawn_blocking(move || {
-            let conn = pool
-                .get()
-                .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
-
-            conn.execute("DELETE FROM group_snapshots WHERE group_id = ?", params![&group_id])
-                .map_err(|e| MlsError::Storage(format!("Failed to delete snapshot: {}", e)))?;
-
+        self.run("Failed to delete snapshot", move |conn| {
+            conn.execute("DELETE FROM group_snapshots WHERE group_id = ?", params![&group_id])?;
             Ok(())
         })
         .await
-        .map_err(|e| MlsError::Storage(format!("Task join error: {}", e)))?
     }
 
     async fn put_blob(&self, key: &str, data: &[u8]) -> MlsResult<()> {
-        let pool = self.pool.clone();
         let key = key.to_string();
         let data = data.to_vec();
 
-        tokio::task::spawn_blocking(move || {
-            let conn = pool
-                .get()
-                .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
-
+        self.run("Failed to put blob", move |conn| {
             let now = current_timestamp();
-
             conn.execute(
                 r#"
                 INSERT INTO kv_blobs (key, value, created_at, updated_at)
@@ -414,60 +390,39 @@ impl StorageProvider for SqlStorageProvider {
                     updated_at = excluded.updated_at
                 "#,
                 params![&key, &data, now, now],
-            )
-            .map_err(|e| MlsError::Storage(format!("Failed to put blob: {}", e)))?;
-
+            )?;
             Ok(())
         })
         .await
-        .map_err(|e| MlsError::Storage(format!("Task join error: {}", e)))?
     }
 
     async fn get_blob(&self, key: &str) -> MlsResult<Vec<u8>> {
-        let pool = self.pool.clone();
         let key = key.to_string();
 
-        tokio::task::spawn_blocking(move || {
-            let conn = pool
-                .get()
-                .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
-
-            let data: Vec<u8> = conn
-                .query_row("SELECT value FROM kv_blobs WHERE key = ?", params![&key], |row| {
+        self.run("Failed to get blob", {
+            let key = key.clone();
+            move |conn| {
+                conn.query_row("SELECT value FROM kv_blobs WHERE key = ?", params![&key], |row| {
                     row.get(0)
                 })
                 .optional()
-                .map_err(|e| MlsError::Storage(format!("Failed to get blob: {}", e)))?
-                .ok_or_else(|| MlsError::NotFound(format!("Blob '{}' not found", key)))?;
-
-            Ok(data)
+            }
         })
-        .await
-        .map_err(|e| MlsError::Storage(format!("Task join error: {}", e)))?
+        .await?
+        .ok_or_else(|| MlsError::NotFound(format!("Blob '{}' not found", key)))
     }
 
     async fn list_groups(&self) -> MlsResult<Vec<GroupId>> {
-        let pool = self.pool.clone();
-
-        tokio::task::spawn_blocking(move || {
-            let conn = pool
-                .get()
-                .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
-
-            let mut stmt = conn
-                .prepare("SELECT group_id FROM group_snapshots")
-                .map_err(|e| MlsError::Storage(format!("Failed to prepare statement: {}", e)))?;
-
-            let groups = stmt
-                .query_map([], |row| row.get::<_, Vec<u8>>(0))
-                .map_err(|e| MlsError::Storage(format!("Failed to query groups: {}", e)))?
-                .collect::<Result<Vec<_>, _>>()
-                .map_err(|e| MlsError::Storage(format!("Failed to collect groups: {}", e)))?;
+        self.run("Failed to list groups", select_group_ids).await
+    }
 
-            Ok(groups)
+    async fn list_blobs(&self) -> MlsResult<Vec<String>> {
+        self.run("Failed to list blobs", |conn| {
+            let mut stmt = conn.prepare("SELECT key FROM kv_blobs")?;
+            let keys = stmt.query_map([], |row| row.get::<_, String>(0))?.collect();
+            keys
         })
         .await
-        .map_err(|e| MlsError::Storage(format!("Task join error: {}", e)))?
     }
 }
 
@@ -490,23 +445,26 @@ impl SqlStorageProvider {
         let enc_name = encrypt_metadata(group_id, encrypted_name)?;
         let enc_topic = encrypted_topic.map(|t| encrypt_metadata(group_id, t)).transpose()?;
         let enc_members = encrypt_metadata(group_id, encrypted_members)?;
+        let group_id = group_id.to_vec();
 
-        let conn = self
-            .pool
-            .get()
-            .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
-
-        let now = current_timestamp();
-
-        conn.execute(
-            "INSERT OR REPLACE INTO channels 
-             (group_id, encrypted_name, encrypted_topic, created_at, encrypted_members, channel_type, archived) 
-             VALUES (?, ?, ?, COALESCE((SELECT created_at FROM channels WHERE group_id = ?), ?), ?, ?, 0)",
-            params![group_id, enc_name, enc_topic, group_id, now, enc_members, channel_type],
-        )
-        .map_err(|e| MlsError::Storage(format!("Failed to save channel metadata: {}", e)))?;
-
-        Ok(())
+        self.run("Failed to save channel metadata", move |conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO channels
+                 (group_id, encrypted_name, encrypted_topic, created_at, encrypted_members, channel_type, archived)
+                 VALUES (?, ?, ?, COALESCE((SELECT created_at FROM channels WHERE group_id = ?), ?), ?, ?, 0)",
+                params![
+                    group_id,
+                    enc_name,
+                    enc_topic,
+                    group_id,
+                    current_timestamp(),
+                    enc_members,
+                    channel_type
+                ],
+            )?;
+            Ok(())
+        })
+        .await
     }
 
     /// Load channel metadata
@@ -514,91 +472,79 @@ impl SqlStorageProvider {
         &self,
         group_id: &[u8],
     ) -> MlsResult<(Vec<u8>, Option<Vec<u8>>, i64, Vec<u8>, i32, bool)> {
-        let conn = self
-            .pool
-            .get()
-            .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
-
-        let result = conn.query_row(
-            "SELECT encrypted_name, encrypted_topic, created_at, encrypted_members, channel_type, archived 
-             FROM channels WHERE group_id = ?",
-            params![group_id],
-            |row| {
-                Ok((
-                    row.get::<_, Vec<u8>>(0)?,
-                    row.get::<_, Option<Vec<u8>>>(1)?,
-                    row.get::<_, i64>(2)?,
-                    row.get::<_, Vec<u8>>(3)?,
-                    row.get::<_, i32>(4)?,
-                    row.get::<_, i32>(5)? != 0,
-                ))
-            },
-        );
-
-        match result {
-            Ok((enc_name, enc_topic, created_at, enc_members, channel_type, archived)) => {
-                // Decrypt metadata after loading
-                let decrypted_name = decrypt_metadata(group_id, &enc_name)?;
-                let decrypted_topic =
-                    enc_topic.map(|t| decrypt_metadata(group_id, &t)).transpose()?;
-                let decrypted_members = decrypt_metadata(group_id, &enc_members)?;
-
-                Ok((
-                    decrypted_name,
-                    decrypted_topic,
-                    created_at,
-                    decrypted_members,
-                    channel_type,
-                    archived,
-                ))
-            }
-            Err(rusqlite::Error::QueryReturnedNoRows) => {
-                Err(MlsError::NotFound(format!("Channel not found: {:?}", group_id)))
-            }
-            Err(e) => Err(MlsError::Storage(format!("Failed to load channel metadata: {}", e))),
-        }
+        let row = self
+            .run("Failed to load channel metadata", {
+                let group_id = group_id.to_vec();
+                move |conn| {
+                    conn.query_row(
+                        "SELECT encrypted_name, encrypted_topic, created_at, encrypted_members, channel_type, archived
+                         FROM channels WHERE group_id = ?",
+                        params![group_id],
+                        |row| {
+                            Ok((
+                                row.get::<_, Vec<u8>>(0)?,
+                                row.get::<_, Option<Vec<u8>>>(1)?,
+                                row.get::<_, i64>(2)?,
+                                row.get::<_, Vec<u8>>(3)?,
+                                row.get::<_, i32>(4)?,
+                                row.get::<_, i32>(5)? != 0,
+                            ))
+                        },
+                    )
+                    .optional()
+                }
+            })
+            .await?;
+
+        let Some((enc_name, enc_topic, created_at, enc_members, channel_type, archived)) = row
+        else {
+            return Err(MlsError::NotFound(format!("Channel not found: {:?}", group_id)));
+        };
+
+        // Decrypt metadata after loading
+        let decrypted_name = decrypt_metadata(group_id, &enc_name)?;
+        let decrypted_topic = enc_topic.map(|t| decrypt_metadata(group_id, &t)).transpose()?;
+        let decrypted_members = decrypt_metadata(group_id, &enc_members)?;
+
+        Ok((
+            decrypted_name,
+            decrypted_topic,
+            created_at,
+            decrypted_members,
+            channel_type,
+            archived,
+        ))
     }
 
     /// List all channels (non-archived by default)
     pub async fn list_channels(&self, include_archived: bool) -> MlsResult<Vec<Vec<u8>>> {
-        let conn = self
-            .pool
-            .get()
-            .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
-
         let query = if include_archived {
             "SELECT group_id FROM channels ORDER BY created_at DESC"
         } else {
             "SELECT group_id FROM channels WHERE archived = 0 ORDER BY created_at DESC"
         };
 
-        let mut stmt = conn
-            .prepare(query)
-            .map_err(|e| MlsError::Storage(format!("Failed to prepare statement: {}", e)))?;
-
-        let rows = stmt
-            .query_map([], |row| row.get::<_, Vec<u8>>(0))
-            .map_err(|e| MlsError::Storage(format!("Failed to query channels: {}", e)))?;
-
-        let mut group_ids = Vec::new();
-        for row in rows {
+        self.run("Failed to list channels", move |conn| {
+            let mut stmt = conn.prepare(query)?;
+            let group_ids = stmt.query_map([], |row| row.get::<_, Vec<u8>>(0))?.collect();
             group_ids
-                .push(row.map_err(|e| MlsError::Storage(format!("Failed to read row: {}", e)))?);
-        }
-
-        Ok(group_ids)
+        })
+        .await
     }
 
     /// Archive a channel (soft delete)
     pub async fn archive_channel(&self, group_id: &[u8]) -> MlsResult<()> {
-        let conn = self
-            .pool
-            .get()
-            .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
-
-        let updated = conn
-            .execute("UPDATE channels SET archived = 1 WHERE group_id = ?", params![group_id])
-            .map_err(|e| MlsError::Storage(format!("Failed to archive channel: {}", e)))?;
+        let updated = self
+            .run("Failed to archive channel", {
+                let group_id = group_id.to_vec();
+                move |conn| {
+                    conn.execute(
+                        "UPDATE channels SET archived = 1 WHERE group_id = ?",
+                        params![group_id],
+                    )
+                }
+            })
+            .await?;
 
         if updated == 0 {
             return Err(MlsError::NotFound(format!("Channel not found: {:?}", group_id)));
@@ -609,15 +555,15 @@ impl SqlStorageProvider {
 
     /// Delete channel and all associated messages (hard delete)
     pub async fn delete_channel(&self, group_id: &[u8]) -> MlsResult<()> {
-        let conn = self
-            .pool
-            .get()
-            .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
-
         // Messages are deleted via CASCADE foreign key
-        let deleted = conn
-            .execute("DELETE FROM channels WHERE group_id = ?", params![group_id])
-            .map_err(|e| MlsError::Storage(format!("Failed to delete channel: {}", e)))?;
+        let deleted = self
+            .run("Failed to delete channel", {
+                let group_id = group_id.to_vec();
+                move |conn| {
+                    conn.execute("DELETE FROM channels WHERE group_id = ?", params![group_id])
+                }
+            })
+            .await?;
 
         if deleted == 0 {
             return Err(MlsError::NotFound(format!("Channel not found: {:?}", group_id)));
@@ -652,20 +598,29 @@ impl SqlStorageProvider {
         sequence: i64,
         plaintext_content: Option<&[u8]>,
     ) -> MlsResult<()> {
-        let conn = self
-            .pool
-            .get()
-            .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
-
-        conn.execute(
-            "INSERT OR REPLACE INTO messages 
-             (message_id, group_id, encrypted_content, sealed_sender_bytes, sequence, processed, plaintext_content) 
-             VALUES (?, ?, ?, ?, ?, 0, ?)",
-            params![message_id, group_id, encrypted_content, sealed_sender_bytes, sequence, plaintext_content],
-        )
-        .map_err(|e| MlsError::Storage(format!("Failed to save message: {}", e)))?;
+        let message_id = message_id.to_vec();
+        let group_id = group_id.to_vec();
+        let encrypted_content = encrypted_content.to_vec();
+        let sealed_sender_bytes = sealed_sender_bytes.to_vec();
+        let plaintext_content = plaintext_content.map(<[u8]>::to_vec);
 
-        Ok(())
+        self.run("Failed to save message", move |conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO messages
+                 (message_id, group_id, encrypted_content, sealed_sender_bytes, sequence, processed, plaintext_content)
+                 VALUES (?, ?, ?, ?, ?, 0, ?)",
+                params![
+                    message_id,
+                    group_id,
+                    encrypted_content,
+                    sealed_sender_bytes,
+                    sequence,
+                    plaintext_content
+                ],
+            )?;
+            Ok(())
+        })
+        .await
     }
 
     /// Load messages for a channel with pagination
@@ -675,53 +630,47 @@ impl SqlStorageProvider {
         limit: i64,
         offset: i64,
     ) -> MlsResult<Vec<(Vec<u8>, Vec<u8>, Vec<u8>, i64, bool, Option<Vec<u8>>)>> {
-        let conn = self
-            .pool
-            .get()
-            .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
-
-        let mut stmt = conn
-            .prepare(
-                "SELECT message_id, encrypted_content, sealed_sender_bytes, sequence, processed, plaintext_content 
-                 FROM messages 
-                 WHERE group_id = ? 
-                 ORDER BY sequence ASC 
+        let group_id = group_id.to_vec();
+
+        self.run("Failed to load messages", move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT message_id, encrypted_content, sealed_sender_bytes, sequence, processed, plaintext_content
+                 FROM messages
+                 WHERE group_id = ?
+                 ORDER BY sequence ASC
                  LIMIT ? OFFSET ?",
-            )
-            .map_err(|e| MlsError::Storage(format!("Failed to prepare statement: {}", e)))?;
-
-        let rows = stmt
-            .query_map(params![group_id, limit, offset], |row| {
-                Ok((
-                    row.get::<_, Vec<u8>>(0)?,
-                    row.get::<_, Vec<u8>>(1)?,
-                    row.get::<_, Vec<u8>>(2)?,
-                    row.get::<_, i64>(3)?,
-                    row.get::<_, i32>(4)? != 0,
-                    row.get::<_, Option<Vec<u8>>>(5)?,
-                ))
-            })
-            .map_err(|e| MlsError::Storage(format!("Failed to query messages: {}", e)))?;
-
-        let mut messages = Vec::new();
-        for row in rows {
+            )?;
+
+            let messages = stmt
+                .query_map(params![group_id, limit, offset], |row| {
+                    Ok((
+                        row.get::<_, Vec<u8>>(0)?,
+                        row.get::<_, Vec<u8>>(1)?,
+                        row.get::<_, Vec<u8>>(2)?,
+                        row.get::<_, i64>(3)?,
+                        row.get::<_, i32>(4)? != 0,
+                        row.get::<_, Option<Vec<u8>>>(5)?,
+                    ))
+                })?
+                .collect();
             messages
-                .push(row.map_err(|e| MlsError::Storage(format!("Failed to read row: {}", e)))?);
-        }
-
-        Ok(messages)
+        })
+        .await
     }
 
     /// Mark a message as processed
     pub async fn mark_message_processed(&self, message_id: &[u8]) -> MlsResult<()> {
-        let conn = self
-            .pool
-            .get()
-            .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
-
-        let updated = conn
-            .execute("UPDATE messages SET processed = 1 WHERE message_id = ?", params![message_id])
-            .map_err(|e| MlsError::Storage(format!("Failed to mark message processed: {}", e)))?;
+        let updated = self
+            .run("Failed to mark message processed", {
+                let message_id = message_id.to_vec();
+                move |conn| {
+                    conn.execute(
+                        "UPDATE messages SET processed = 1 WHERE message_id = ?",
+                        params![message_id],
+                    )
+                }
+            })
+            .await?;
 
         if updated == 0 {
             return Err(MlsError::NotFound(format!("Message not found: {:?}", message_id)));
@@ -732,49 +681,86 @@ impl SqlStorageProvider {
 
     /// Get count of unprocessed messages for a channel
     pub async fn get_unprocessed_count(&self, group_id: &[u8]) -> MlsResult<i64> {
-        let conn = self
-            .pool
-            .get()
-            .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
+        let group_id = group_id.to_vec();
 
-        let count = conn
-            .query_row(
+        self.run("Failed to count unprocessed messages", move |conn| {
+            conn.query_row(
                 "SELECT COUNT(*) FROM messages WHERE group_id = ? AND processed = 0",
                 params![group_id],
                 |row| row.get::<_, i64>(0),
             )
-            .map_err(|e| {
-                MlsError::Storage(format!("Failed to count unprocessed messages: {}", e))
-            })?;
-
-        Ok(count)
+        })
+        .await
     }
 
     /// Delete old messages (keep only last N messages per channel)
     pub async fn prune_old_messages(&self, group_id: &[u8], keep_count: i64) -> MlsResult<usize> {
-        let conn = self
-            .pool
-            .get()
-            .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
-
-        let deleted = conn
-            .execute(
-                "DELETE FROM messages 
-                 WHERE group_id = ? 
+        let group_id = group_id.to_vec();
+
+        self.run("Failed to prune messages", move |conn| {
+            conn.execute(
+                "DELETE FROM messages
+                 WHERE group_id = ?
                  AND message_id NOT IN (
-                     SELECT message_id FROM messages 
-                     WHERE group_id = ? 
-                     ORDER BY sequence DESC 
+                     SELECT message_id FROM messages
+                     WHERE group_id = ?
+                     ORDER BY sequence DESC
                      LIMIT ?
                  )",
                 params![group_id, group_id, keep_count],
             )
-            .map_err(|e| MlsError::Storage(format!("Failed to prune messages: {}", e)))?;
+        })
+        .await
+    }
+}
 
-        Ok(deleted)
+/// Run `op` on a connection from `pool`, retrying while the database is busy
+fn run_with_retry<T>(
+    pool: &Pool<SqliteConnectionManager>,
+    config: &SqlStorageConfig,
+    context: &str,
+    mut op: impl FnMut(&mut Connection) -> rusqlite::Result<T>,
+) -> MlsResult<T> {
+    let mut conn = pool
+        .get()
+        .map_err(|e| MlsError::Storage(format!("Failed to get connection: {}", e)))?;
+
+    let mut backoff = config.retry_backoff;
+    let mut retries = 0;
+    loop {
+        match op(&mut conn) {
+            Ok(value) => return Ok(value),
+            Err(e) if is_busy(&e) && retries < config.max_retries => {
+                record_counter("mls.storage.busy_retries", 1);
+                std::thread::sleep(backoff);
+                backoff = backoff.saturating_mul(2);
+                retries += 1;
+            }
+            Err(e) if is_busy(&e) => {
+                return Err(MlsError::Storage(format!(
+                    "{}: database still busy after {} retries",
+                    context, retries
+                )));
+            }
+            Err(e) => return Err(MlsError::Storage(format!("{}: {}", context, e))),
+        }
     }
 }
 
+/// Whether an error means another connection holds the lock
+fn is_busy(error: &rusqlite::Error) -> bool {
+    matches!(
+        error.sqlite_error_code(),
+        Some(ErrorCode::DatabaseBusy) | Some(ErrorCode::DatabaseLocked)
+    )
+}
+
+fn select_group_ids(conn: &mut Connection) -> rusqlite::Result<Vec<GroupId>> {
+    let mut stmt = conn.prepare("SELECT group_id FROM group_snapshots")?;
+    let groups = stmt.query_map([], |row| row.get::<_, Vec<u8>>(0))?.collect();
+    groups
+}
+
 /// Get current Unix timestamp in seconds
 fn current_timestamp() -> i64 {
     SystemTime::now()
@@ -821,6 +807,74 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_connections_use_secure_delete() {
+        let dir = tempdir().unwrap();
+        let storage = SqlStorageProvider::new(dir.path().join("test.db")).unwrap();
+
+        let conn = storage.pool.get().unwrap();
+        let secure_delete: i64 =
+            conn.query_row("PRAGMA secure_delete", [], |row| row.get(0)).unwrap();
+        assert_eq!(secure_delete, 1);
+    }
+
+    #[test]
+    fn test_connections_use_wal_and_busy_timeout() {
+        let dir = tempdir().unwrap();
+        let config =
+            SqlStorageConfig { busy_timeout: Duration::from_millis(1500), ..Default::default() };
+        let storage = SqlStorageProvider::with_config(dir.path().join("test.db"), config).unwrap();
+
+        let conn = storage.pool.get().unwrap();
+        let journal_mode: String =
+            conn.query_row("PRAGMA journal_mode", [], |row| row.get(0)).unwrap();
+        assert_eq!(journal_mode, "wal");
+        let busy_timeout: i64 =
+            conn.query_row("PRAGMA busy_timeout", [], |row| row.get(0)).unwrap();
+        assert_eq!(busy_timeout, 1500);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_busy_database_is_retried() {
+        let dir = tempdir().unwrap();
+        let config = SqlStorageConfig {
+            busy_timeout: Duration::from_millis(10),
+            max_retries: 8,
+            ..Default::default()
+        };
+        let storage =
+            Arc::new(SqlStorageProvider::with_config(dir.path().join("test.db"), config).unwrap());
+
+        // Another connection holds the write lock for a while
+        let writer = storage.begin_transaction().await.unwrap();
+        let put = tokio::spawn({
+            let storage = storage.clone();
+            async move { storage.put_blob("key", b"value").await }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        writer.execute("COMMIT", []).unwrap();
+
+        put.await.unwrap().unwrap();
+        assert_eq!(storage.get_blob("key").await.unwrap(), b"value");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_busy_database_without_retries() {
+        let dir = tempdir().unwrap();
+        let config = SqlStorageConfig {
+            busy_timeout: Duration::from_millis(10),
+            max_retries: 0,
+            ..Default::default()
+        };
+        let storage = SqlStorageProvider::with_config(dir.path().join("test.db"), config).unwrap();
+
+        let writer = storage.begin_transaction().await.unwrap();
+        let result = storage.put_blob("key", b"value").await;
+        writer.execute("ROLLBACK", []).unwrap();
+
+        assert!(matches!(result, Err(MlsError::Storage(message)) if message.contains("busy")));
+    }
+
     #[tokio::test]
     async fn test_blob_storage() {
         let dir = tempdir().unwrap();
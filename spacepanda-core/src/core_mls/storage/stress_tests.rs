@@ -5,8 +5,10 @@
 
 #[cfg(test)]
 mod tests {
+    use crate::core_mls::errors::MlsError;
     use crate::core_mls::storage::sql_store::SqlStorageProvider;
     use crate::core_mls::traits::storage::{PersistedGroupSnapshot, StorageProvider};
+    use std::sync::Arc;
     use std::time::Instant;
     use tempfile::tempdir;
     use tokio::task::JoinSet;
@@ -387,4 +389,64 @@ mod tests {
         let messages = storage.load_messages(group_id, 1000, 0).await.unwrap();
         assert_eq!(messages.len(), 1000);
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_interleaved_commits_on_many_groups() {
+        const TASKS: usize = 8;
+        const GROUPS: usize = 8;
+        const COMMITS: u64 = 25;
+
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("interleaved_commits.db");
+        let storage = Arc::new(SqlStorageProvider::new(&db_path).unwrap());
+        let group_id = |group: usize| format!("group_{}", group).into_bytes();
+
+        let mut tasks = JoinSet::new();
+
+        // Every task commits to every group, each walking them in its own
+        // order, and shares the snapshot of its group with the next task
+        for task_id in 0..TASKS {
+            let storage = storage.clone();
+            tasks.spawn(async move {
+                for epoch in 1..=COMMITS {
+                    for offset in 0..GROUPS {
+                        let group = (task_id + offset) % GROUPS;
+                        let key = format!("group_{}:task_{}:epoch_{}", group, task_id, epoch);
+                        storage.put_blob(&key, &epoch.to_be_bytes()).await?;
+                    }
+
+                    let snapshots: Vec<_> = [task_id, (task_id + 1) % GROUPS]
+                        .into_iter()
+                        .map(|group| PersistedGroupSnapshot {
+                            group_id: group_id(group),
+                            epoch,
+                            serialized_group: epoch.to_be_bytes().to_vec(),
+                        })
+                        .collect();
+                    storage.save_group_snapshots_atomic(&snapshots).await?;
+                }
+                Ok::<_, MlsError>(())
+            });
+        }
+
+        let mut errors = Vec::new();
+        while let Some(result) = tasks.join_next().await {
+            if let Err(e) = result.unwrap() {
+                errors.push(e.to_string());
+            }
+        }
+        assert!(errors.is_empty(), "storage errors under contention: {:?}", errors);
+
+        // No update was lost
+        for group in 0..GROUPS {
+            let snapshot = storage.load_group_snapshot(&group_id(group)).await.unwrap();
+            assert_eq!(snapshot.epoch, COMMITS);
+            for task_id in 0..TASKS {
+                for epoch in 1..=COMMITS {
+                    let key = format!("group_{}:task_{}:epoch_{}", group, task_id, epoch);
+                    assert_eq!(storage.get_blob(&key).await.unwrap(), epoch.to_be_bytes());
+                }
+            }
+        }
+    }
 }
@@ -55,4 +55,11 @@ pub trait StorageProvider: Send + Sync {
         // Default implementation returns empty (can be overridden)
         Ok(Vec::new())
     }
+
+    /// List the keys of all stored blobs
+    ///
+    /// Optional: Used to migrate between providers.
+    async fn list_blobs(&self) -> MlsResult<Vec<String>> {
+        Ok(Vec::new())
+    }
 }
@@ -1,5 +1,7 @@
 //! Type definitions for MLS operations
 
+use crate::core_mls::padding::PaddingStrategy;
+use crate::core_store::model::{Permission, PermissionSet};
 use serde::{Deserialize, Serialize};
 
 /// Group identifier (32 bytes)
@@ -65,6 +67,21 @@ pub struct MlsConfig {
     pub key_rotation_interval_secs: u64,
     /// Replay cache size (number of (epoch, sender, seq) tuples to remember)
     pub replay_cache_size: usize,
+    /// Envelopes held back until the commit of their epoch arrives
+    pub pending_buffer_size: usize,
+    /// Furthest an envelope's epoch may be ahead of the group's and still be held back
+    pub max_epochs_ahead: u64,
+    /// Epochs before the current one whose envelopes are still accepted
+    pub replay_window_epochs: u64,
+    /// Past epochs whose secrets are kept for late messages; older ones are purged
+    pub past_epoch_retention: usize,
+    /// Publish the ratchet tree to the DHT instead of embedding it in invites
+    pub external_tree: bool,
+    /// How application messages are padded before encryption
+    pub padding: PaddingStrategy,
+    /// Commits between two recovery snapshots of a group, at most
+    /// `past_epoch_retention + 1`
+    pub snapshot_interval: u64,
 }
 
 impl Default for MlsConfig {
@@ -74,6 +91,13 @@ impl Default for MlsConfig {
             auto_key_rotation: true,
             key_rotation_interval_secs: 86400, // 24 hours
             replay_cache_size: 10_000,
+            pending_buffer_size: 256,
+            max_epochs_ahead: 8,
+            replay_window_epochs: 2,
+            past_epoch_retention: 2,
+            external_tree: false,
+            padding: PaddingStrategy::default(),
+            snapshot_interval: 16,
         }
     }
 }
@@ -90,19 +114,28 @@ pub enum MemberRole {
 }
 
 impl MemberRole {
+    /// Capabilities granted by this role
+    pub fn permissions(&self) -> PermissionSet {
+        match self {
+            MemberRole::Admin => PermissionSet::all(),
+            MemberRole::Member => Permission::Post.into(),
+            MemberRole::ReadOnly => PermissionSet::empty(),
+        }
+    }
+
     /// Check if this role can remove members
     pub fn can_remove_members(&self) -> bool {
-        matches!(self, MemberRole::Admin)
+        self.permissions().contains(Permission::Kick)
     }
 
     /// Check if this role can promote/demote members
     pub fn can_manage_roles(&self) -> bool {
-        matches!(self, MemberRole::Admin)
+        self.permissions().contains(Permission::ManageRoles)
     }
 
     /// Check if this role can send messages
     pub fn can_send_messages(&self) -> bool {
-        matches!(self, MemberRole::Admin | MemberRole::Member)
+        self.permissions().contains(Permission::Post)
     }
 }
 
@@ -123,6 +156,9 @@ pub struct MemberInfo {
     pub joined_at: u64,
     /// Member's role in the channel
     pub role: MemberRole,
+    /// Leaf signature public key (identifies the member's device)
+    #[serde(default)]
+    pub signature_key: Vec<u8>,
 }
 
 /// Public group metadata (safe to publish to CRDT/DHT)
@@ -184,6 +220,7 @@ mod tests {
         assert_eq!(config.auto_key_rotation, true);
         assert_eq!(config.key_rotation_interval_secs, 86400);
         assert_eq!(config.replay_cache_size, 10_000);
+        assert_eq!(config.pending_buffer_size, 256);
     }
 
     #[test]
@@ -194,6 +231,19 @@ mod tests {
         assert_eq!(group_id, deserialized);
     }
 
+    #[test]
+    fn test_member_role_permissions() {
+        assert!(MemberRole::Admin.can_remove_members());
+        assert!(MemberRole::Admin.can_manage_roles());
+        assert!(MemberRole::Admin.can_send_messages());
+
+        assert!(!MemberRole::Member.can_remove_members());
+        assert!(!MemberRole::Member.can_manage_roles());
+        assert!(MemberRole::Member.can_send_messages());
+
+        assert!(MemberRole::ReadOnly.permissions().is_empty());
+    }
+
     #[test]
     fn test_member_info() {
         let member = MemberInfo {
@@ -201,6 +251,7 @@ mod tests {
             leaf_index: 0,
             joined_at: 1234567890,
             role: MemberRole::Member,
+            signature_key: vec![4, 5, 6],
         };
 
         let json = serde_json::to_string(&member).unwrap();
@@ -14,6 +14,7 @@ use super::errors::{MlsError, MlsResult};
 use super::tree::{MlsTree, NodeIndex};
 use super::types::{GroupId, GroupMetadata};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 
 #[cfg(test)]
@@ -49,9 +50,14 @@ pub struct WelcomeGroupSecrets {
     /// Current epoch
     pub epoch: u64,
     /// Application secret for deriving message keys
+    ///
+    /// If `psk_ids` is non-empty this is the secret before the PSKs are
+    /// mixed in, so the joiner needs every listed PSK to use it.
     pub application_secret: Vec<u8>,
     /// Epoch authenticator (MAC of epoch data)
     pub epoch_authenticator: Vec<u8>,
+    /// IDs of the external PSKs mixed into this epoch
+    pub psk_ids: Vec<Vec<u8>>,
 }
 
 impl WelcomeGroupSecrets {
@@ -78,6 +84,9 @@ pub struct TreeSnapshot {
     pub public_nodes: HashMap<NodeIndex, Vec<u8>>,
     /// Node hashes for verification
     pub node_hashes: HashMap<NodeIndex, Vec<u8>>,
+    /// Exported OpenMLS ratchet tree (empty for trees built from an `MlsTree`)
+    #[serde(default)]
+    pub ratchet_tree: Vec<u8>,
 }
 
 impl TreeSnapshot {
@@ -96,7 +105,44 @@ impl TreeSnapshot {
             }
         }
 
-        Self { leaf_count: tree.leaf_count(), public_nodes, node_hashes }
+        Self {
+            leaf_count: tree.leaf_count(),
+            public_nodes,
+            node_hashes,
+            ratchet_tree: Vec::new(),
+        }
+    }
+
+    /// Create snapshot from an OpenMLS group's exported ratchet tree
+    ///
+    /// Used when the tree is shipped separately from the Welcome; the
+    /// joiner checks it against the [`hash`](Self::hash) pinned in the
+    /// invite before using it.
+    pub fn from_ratchet_tree(ratchet_tree: Vec<u8>) -> Self {
+        Self {
+            leaf_count: 0,
+            public_nodes: HashMap::new(),
+            node_hashes: HashMap::new(),
+            ratchet_tree,
+        }
+    }
+
+    /// SHA-256 of the exported ratchet tree
+    pub fn hash(&self) -> Vec<u8> {
+        Sha256::digest(&self.ratchet_tree).to_vec()
+    }
+
+    /// Check the exported ratchet tree against a pinned hash
+    ///
+    /// # Errors
+    ///
+    /// `TreeHashMismatch` if the tree isn't the one the hash was taken of
+    pub fn verify(&self, epoch: u64, expected_hash: &[u8]) -> MlsResult<()> {
+        if self.hash() == expected_hash {
+            Ok(())
+        } else {
+            Err(MlsError::TreeHashMismatch { epoch })
+        }
     }
 
     /// Reconstruct tree from snapshot
@@ -302,7 +348,6 @@ impl WelcomeBuilder {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use sha2::{Digest, Sha256};
     use x25519_dalek::{PublicKey, StaticSecret};
 
     fn test_keypair(name: &str) -> (Vec<u8>, Vec<u8>) {
@@ -326,6 +371,7 @@ mod tests {
             epoch,
             application_secret: vec![1, 2, 3, 4],
             epoch_authenticator: vec![5, 6, 7, 8],
+            psk_ids: Vec::new(),
         }
     }
 
@@ -349,12 +395,14 @@ mod tests {
                     leaf_index: 0,
                     joined_at: 1234567890,
                     role: crate::core_mls::types::MemberRole::Admin,
+                    signature_key: Vec::new(),
                 },
                 MemberInfo {
                     identity: b"bob".to_vec(),
                     leaf_index: 1,
                     joined_at: 1234567891,
                     role: crate::core_mls::types::MemberRole::Member,
+                    signature_key: Vec::new(),
                 },
             ],
         }
@@ -390,6 +438,7 @@ mod tests {
             leaf_count: 2,
             public_nodes: HashMap::new(),
             node_hashes: HashMap::new(),
+            ratchet_tree: Vec::new(),
         };
 
         // Only add one leaf, missing the second
@@ -598,4 +647,18 @@ mod tests {
 
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_ratchet_tree_snapshot_verify() {
+        let snapshot = TreeSnapshot::from_ratchet_tree(vec![7; 128]);
+        let hash = snapshot.hash();
+        assert!(snapshot.verify(3, &hash).is_ok());
+
+        let mut tampered = snapshot.clone();
+        tampered.ratchet_tree[0] ^= 0x01;
+        assert!(matches!(
+            tampered.verify(3, &hash),
+            Err(MlsError::TreeHashMismatch { epoch: 3 })
+        ));
+    }
 }
@@ -0,0 +1,828 @@
+//! Encrypted account export for moving to a new machine
+//!
+//! An export is a single file holding everything needed to pick up an
+//! account elsewhere: the identity and keystore, the MLS group states, the
+//! local store (messages, indexes, queues) and the descriptors of every
+//! joined channel. The attachment cache and other profiles are left out.
+//!
+//! Layout:
+//!
+//! ```text
+//! header   "SPBACKUP" | version u32 | salt [16] | nonce prefix [7] | key check [32]
+//! frame*   length u32 (high bit: last frame) | ChaCha20-Poly1305 ciphertext
+//! ```
+//!
+//! The first frame is the [`ExportManifest`] as JSON (channel descriptors
+//! carry config-style enums that bincode can't decode), followed by the
+//! contents of each file it lists, in order, in frames of at most
+//! [`FRAME_SIZE`] bytes, and an empty final frame. Frames are sealed with the STREAM construction:
+//! the nonce is the prefix, a frame counter and the last-frame flag, and
+//! the header is authenticated with every frame. Reordered, dropped or
+//! flipped frames fail to decrypt, and a file cut short is missing its
+//! final frame, so [`AccountImporter`] authenticates the whole export
+//! before writing anything and then restores it in a second pass. Neither
+//! side holds more than a frame in memory.
+//!
+//! The key comes from the passphrase (Argon2id); the header carries a check
+//! value so a wrong passphrase is reported as such rather than as damage.
+//!
+//! An import is a copy of the account, not a hand-over: if the old machine
+//! keeps running, both use the same leaves in every group. The other copy's
+//! messages then arrive as if sent by ourselves, which
+//! [`ChannelManager`](crate::core_mvp::ChannelManager) reports as
+//! [`SecurityEvent::IdentityInUseElsewhere`](crate::core_mvp::SecurityEvent::IdentityInUseElsewhere).
+
+use crate::core_mvp::channel_manager::Identity;
+use crate::core_mvp::types::ChannelDescriptor;
+use crate::core_store::backup::{BackupError, BackupResult};
+use crate::core_store::model::types::{Timestamp, UserId};
+use crate::core_store::store::preflight::StoragePreflight;
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use tracing::{debug, info, warn};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Leading bytes of an export
+const MAGIC: &[u8; 8] = b"SPBACKUP";
+
+/// Current export format
+pub const EXPORT_VERSION: u32 = 1;
+
+/// Largest plaintext in a file data frame (64 KiB)
+pub const FRAME_SIZE: usize = 64 * 1024;
+
+/// Largest accepted manifest frame (16 MiB)
+const MAX_MANIFEST_LEN: usize = 16 * 1024 * 1024;
+
+const SALT_LEN: usize = 16;
+const NONCE_PREFIX_LEN: usize = 7;
+const KEY_CHECK_LEN: usize = 32;
+const HEADER_LEN: usize = MAGIC.len() + 4 + SALT_LEN + NONCE_PREFIX_LEN + KEY_CHECK_LEN;
+const TAG_LEN: usize = 16;
+const LAST_FRAME: u32 = 1 << 31;
+
+/// Top-level entries of the data directory that are not exported
+///
+/// The attachment cache is refetched on demand, and other profiles are
+/// separate accounts.
+const EXCLUDED_DIRS: &[&str] = &["attachments", "profiles"];
+const EXCLUDED_EXTENSIONS: &[&str] = &["tmp"];
+
+/// What part of the account a file belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ExportSection {
+    /// `identity.json` and the keystore
+    Identity,
+
+    /// MLS group states
+    MlsState,
+
+    /// `config.toml`
+    Config,
+
+    /// Messages, indexes and queues of the local store
+    Store,
+}
+
+impl ExportSection {
+    fn of(path: &str) -> Self {
+        match path.split('/').next().unwrap_or_default() {
+            "identity.json" | "keystore" => ExportSection::Identity,
+            "mls_groups" => ExportSection::MlsState,
+            "config.toml" => ExportSection::Config,
+            _ => ExportSection::Store,
+        }
+    }
+}
+
+/// A file in an export
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportEntry {
+    /// Path relative to the data directory, `/`-separated
+    pub path: String,
+
+    /// Size in bytes
+    pub size: u64,
+
+    /// Part of the account the file belongs to
+    pub section: ExportSection,
+}
+
+/// Contents of an export, stored in its first frame
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportManifest {
+    /// Format version
+    pub version: u32,
+
+    /// When the export was written
+    pub created_at: Timestamp,
+
+    /// Exported account
+    pub user_id: UserId,
+
+    /// Display name of the exported account
+    pub display_name: String,
+
+    /// Joined channels
+    pub channels: Vec<ChannelDescriptor>,
+
+    /// Files, in the order their contents follow
+    pub entries: Vec<ExportEntry>,
+}
+
+impl ExportManifest {
+    /// Bytes of file content in the export
+    pub fn total_size(&self) -> u64 {
+        self.entries.iter().map(|e| e.size).sum()
+    }
+
+    /// Number of files and bytes in one section
+    pub fn section_size(&self, section: ExportSection) -> (usize, u64) {
+        self.entries
+            .iter()
+            .filter(|e| e.section == section)
+            .fold((0, 0), |(files, bytes), e| (files + 1, bytes + e.size))
+    }
+}
+
+/// Write an encrypted export of the account in `data_dir` to `out`
+///
+/// The account must not be in use by another process: a file that changes
+/// size while it is being exported fails the export.
+pub fn export_account<W: Write>(
+    data_dir: &Path,
+    identity: &Identity,
+    channels: Vec<ChannelDescriptor>,
+    passphrase: &str,
+    out: &mut W,
+) -> BackupResult<ExportManifest> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce_prefix = [0u8; NONCE_PREFIX_LEN];
+    rand::rng().fill_bytes(&mut salt);
+    rand::rng().fill_bytes(&mut nonce_prefix);
+    let key = ExportKey::derive(passphrase, &salt)?;
+
+    let header = Header { version: EXPORT_VERSION, salt, nonce_prefix, key_check: key.check };
+    out.write_all(&header.to_bytes())?;
+    let mut frames = FrameWriter::new(out, &key, &header);
+
+    let files = collect_files(data_dir)?;
+    let mut entries = Vec::with_capacity(files.len());
+    for (relative, path) in &files {
+        entries.push(ExportEntry {
+            path: relative.clone(),
+            size: fs::metadata(path)?.len(),
+            section: ExportSection::of(relative),
+        });
+    }
+    let manifest = ExportManifest {
+        version: EXPORT_VERSION,
+        created_at: Timestamp::now(),
+        user_id: identity.user_id.clone(),
+        display_name: identity.display_name.clone(),
+        channels,
+        entries,
+    };
+    let manifest_bytes =
+        serde_json::to_vec(&manifest).map_err(|e| BackupError::Serialization(e.to_string()))?;
+    if manifest_bytes.len() > MAX_MANIFEST_LEN {
+        return Err(BackupError::Serialization(format!(
+            "Export manifest is {} bytes, above the {} byte limit",
+            manifest_bytes.len(),
+            MAX_MANIFEST_LEN
+        )));
+    }
+    frames.write(&manifest_bytes, false)?;
+
+    let mut buf = vec![0u8; FRAME_SIZE];
+    for (entry, (_, path)) in manifest.entries.iter().zip(&files) {
+        let mut file = fs::File::open(path)?;
+        let mut remaining = entry.size;
+        while remaining > 0 {
+            let want = remaining.min(FRAME_SIZE as u64) as usize;
+            let n = read_full(&mut file, &mut buf[..want])?;
+            if n < want {
+                return Err(changed_during_export(&entry.path));
+            }
+            frames.write(&buf[..n], false)?;
+            remaining -= n as u64;
+        }
+        if file.read(&mut buf[..1])? != 0 {
+            return Err(changed_during_export(&entry.path));
+        }
+    }
+    frames.write(&[], true)?;
+    out.flush()?;
+
+    info!(
+        files = manifest.entries.len(),
+        bytes = manifest.total_size(),
+        channels = manifest.channels.len(),
+        "Exported account"
+    );
+    Ok(manifest)
+}
+
+/// Authenticate a whole export without restoring it
+///
+/// Reads the export once, holding a single frame in memory at a time.
+pub fn verify_account_export<R: Read>(
+    reader: &mut R,
+    passphrase: &str,
+) -> BackupResult<ExportManifest> {
+    let header = Header::read(reader)?;
+    let key = header.unlock(passphrase)?;
+    verify_with_key(reader, &key, &header)
+}
+
+/// Restores an export into a fresh data directory
+pub struct AccountImporter {
+    passphrase: String,
+    preflight: Option<StoragePreflight>,
+}
+
+impl AccountImporter {
+    /// Importer for exports sealed with `passphrase`
+    pub fn new(passphrase: impl Into<String>) -> Self {
+        Self { passphrase: passphrase.into(), preflight: None }
+    }
+
+    /// Check free disk space before restoring
+    pub fn with_storage_preflight(mut self, preflight: StoragePreflight) -> Self {
+        self.preflight = Some(preflight);
+        self
+    }
+
+    /// Restore the export in `reader` into `dest_dir`
+    ///
+    /// # Errors
+    ///
+    /// - `IdentityExists` if `dest_dir` already holds an identity
+    /// - `Decryption` for a wrong passphrase or a damaged export, detected
+    ///   before anything is written
+    /// - `WouldOverwrite` if a file from the export already exists
+    /// - `InsufficientStorage` if the preflight fails
+    pub fn import<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        dest_dir: &Path,
+    ) -> BackupResult<ExportManifest> {
+        if let Some(existing) = existing_identity(dest_dir)? {
+            return Err(BackupError::IdentityExists(existing.display().to_string()));
+        }
+
+        let header = Header::read(reader)?;
+        let key = header.unlock(&self.passphrase)?;
+        let manifest = verify_with_key(reader, &key, &header)?;
+
+        if let Some(preflight) = &self.preflight {
+            preflight.check(dest_dir, manifest.total_size())?;
+        }
+        let mut targets = Vec::with_capacity(manifest.entries.len());
+        for entry in &manifest.entries {
+            let path = dest_dir.join(safe_relative_path(&entry.path)?);
+            if path.exists() {
+                return Err(BackupError::WouldOverwrite(path.display().to_string()));
+            }
+            targets.push(path);
+        }
+
+        reader.seek(SeekFrom::Start(HEADER_LEN as u64))?;
+        let mut written = Vec::with_capacity(targets.len());
+        let result = restore(reader, &key, &header, &manifest, &targets, &mut written);
+        if result.is_err() {
+            // The export changed between the passes; drop what was staged
+            for tmp in &written {
+                let _ = fs::remove_file(tmp);
+            }
+        }
+        result?;
+
+        for (tmp, path) in written.iter().zip(&targets) {
+            fs::rename(tmp, path)?;
+        }
+        info!(
+            user = %manifest.user_id.0,
+            files = manifest.entries.len(),
+            bytes = manifest.total_size(),
+            "Imported account"
+        );
+        Ok(manifest)
+    }
+}
+
+/// Stage every file of the export next to its target
+fn restore<R: Read>(
+    reader: &mut R,
+    key: &ExportKey,
+    header: &Header,
+    manifest: &ExportManifest,
+    targets: &[PathBuf],
+    written: &mut Vec<PathBuf>,
+) -> BackupResult<()> {
+    let mut frames = FrameReader::new(reader, key, header);
+    if frames.read(MAX_MANIFEST_LEN)?.1 {
+        return Err(truncated());
+    }
+
+    for (entry, path) in manifest.entries.iter().zip(targets) {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+        tmp_name.push(".import-tmp");
+        let tmp = path.with_file_name(tmp_name);
+        let mut file = fs::File::create(&tmp)?;
+        written.push(tmp);
+        read_entry(&mut frames, entry, |data| file.write_all(data))?;
+        file.sync_all()?;
+        debug!(path = %entry.path, size = entry.size, "Restored file");
+    }
+    frames.finish()
+}
+
+/// Authenticate the frames following the header, returning the manifest
+fn verify_with_key<R: Read>(
+    reader: &mut R,
+    key: &ExportKey,
+    header: &Header,
+) -> BackupResult<ExportManifest> {
+    let mut frames = FrameReader::new(reader, key, header);
+    let (manifest_bytes, last) = frames.read(MAX_MANIFEST_LEN)?;
+    if last {
+        return Err(truncated());
+    }
+    let manifest: ExportManifest = serde_json::from_slice(&manifest_bytes)
+        .map_err(|e| BackupError::Serialization(e.to_string()))?;
+    if manifest.version != header.version {
+        return Err(BackupError::Decryption("Manifest version does not match header".to_string()));
+    }
+
+    for entry in &manifest.entries {
+        read_entry(&mut frames, entry, |_| Ok(()))?;
+    }
+    frames.finish()?;
+    Ok(manifest)
+}
+
+/// Read the frames holding one file's contents
+fn read_entry<R: Read>(
+    frames: &mut FrameReader<'_, R>,
+    entry: &ExportEntry,
+    mut sink: impl FnMut(&[u8]) -> io::Result<()>,
+) -> BackupResult<()> {
+    let mut remaining = entry.size;
+    while remaining > 0 {
+        let (data, last) = frames.read(FRAME_SIZE)?;
+        if last || data.is_empty() || data.len() as u64 > remaining {
+            return Err(BackupError::Decryption(format!(
+                "Contents of {} do not match the manifest",
+                entry.path
+            )));
+        }
+        sink(&data)?;
+        remaining -= data.len() as u64;
+    }
+    Ok(())
+}
+
+/// Unencrypted export header
+struct Header {
+    version: u32,
+    salt: [u8; SALT_LEN],
+    nonce_prefix: [u8; NONCE_PREFIX_LEN],
+    key_check: [u8; KEY_CHECK_LEN],
+}
+
+impl Header {
+    fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0u8; HEADER_LEN];
+        let (magic, rest) = bytes.split_at_mut(MAGIC.len());
+        magic.copy_from_slice(MAGIC);
+        let (version, rest) = rest.split_at_mut(4);
+        version.copy_from_slice(&self.version.to_le_bytes());
+        let (salt, rest) = rest.split_at_mut(SALT_LEN);
+        salt.copy_from_slice(&self.salt);
+        let (prefix, check) = rest.split_at_mut(NONCE_PREFIX_LEN);
+        prefix.copy_from_slice(&self.nonce_prefix);
+        check.copy_from_slice(&self.key_check);
+        bytes
+    }
+
+    fn read<R: Read>(reader: &mut R) -> BackupResult<Self> {
+        let mut bytes = [0u8; HEADER_LEN];
+        read_exact_or_truncated(reader, &mut bytes)?;
+        if &bytes[..MAGIC.len()] != MAGIC {
+            return Err(BackupError::InvalidConfig("Not a SpacePanda account export".to_string()));
+        }
+
+        let mut offset = MAGIC.len();
+        let mut take = |len: usize| {
+            let field = &bytes[offset..offset + len];
+            offset += len;
+            field
+        };
+        let version = u32::from_le_bytes(take(4).try_into().expect("4 bytes"));
+        if version != EXPORT_VERSION {
+            return Err(BackupError::InvalidConfig(format!(
+                "Unsupported account export version {}",
+                version
+            )));
+        }
+        Ok(Self {
+            version,
+            salt: take(SALT_LEN).try_into().expect("salt length"),
+            nonce_prefix: take(NONCE_PREFIX_LEN).try_into().expect("nonce prefix length"),
+            key_check: take(KEY_CHECK_LEN).try_into().expect("key check length"),
+        })
+    }
+
+    /// Derive the key, checking the passphrase
+    fn unlock(&self, passphrase: &str) -> BackupResult<ExportKey> {
+        let key = ExportKey::derive(passphrase, &self.salt)?;
+        if key.check != self.key_check {
+            return Err(BackupError::Decryption("Wrong export passphrase".to_string()));
+        }
+        Ok(key)
+    }
+}
+
+/// Keys derived from the export passphrase
+#[derive(Zeroize, ZeroizeOnDrop)]
+struct ExportKey {
+    cipher_key: [u8; 32],
+    check: [u8; KEY_CHECK_LEN],
+}
+
+impl ExportKey {
+    fn derive(passphrase: &str, salt: &[u8]) -> BackupResult<Self> {
+        let mut master = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut master)
+            .map_err(|e| BackupError::InvalidConfig(format!("Key derivation failed: {}", e)))?;
+
+        let key = Self {
+            cipher_key: blake3::derive_key("spacepanda account export v1 cipher", &master),
+            check: blake3::derive_key("spacepanda account export v1 key check", &master),
+        };
+        master.zeroize();
+        Ok(key)
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(Key::from_slice(&self.cipher_key))
+    }
+}
+
+/// Nonce of a frame: prefix, big-endian counter, last-frame flag
+fn frame_nonce(prefix: &[u8; NONCE_PREFIX_LEN], counter: u32, last: bool) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[..NONCE_PREFIX_LEN].copy_from_slice(prefix);
+    nonce[NONCE_PREFIX_LEN..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = u8::from(last);
+    nonce
+}
+
+struct FrameWriter<'a, W: Write> {
+    out: &'a mut W,
+    cipher: ChaCha20Poly1305,
+    nonce_prefix: [u8; NONCE_PREFIX_LEN],
+    aad: [u8; HEADER_LEN],
+    counter: u32,
+}
+
+impl<'a, W: Write> FrameWriter<'a, W> {
+    fn new(out: &'a mut W, key: &ExportKey, header: &Header) -> Self {
+        Self {
+            out,
+            cipher: key.cipher(),
+            nonce_prefix: header.nonce_prefix,
+            aad: header.to_bytes(),
+            counter: 0,
+        }
+    }
+
+    fn write(&mut self, plaintext: &[u8], last: bool) -> BackupResult<()> {
+        let nonce = frame_nonce(&self.nonce_prefix, self.counter, last);
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad: &self.aad })
+            .map_err(|e| BackupError::Serialization(format!("Encryption failed: {}", e)))?;
+
+        let mut length = ciphertext.len() as u32;
+        if last {
+            length |= LAST_FRAME;
+        }
+        self.out.write_all(&length.to_le_bytes())?;
+        self.out.write_all(&ciphertext)?;
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .ok_or_else(|| BackupError::Serialization("Export too large".to_string()))?;
+        Ok(())
+    }
+}
+
+struct FrameReader<'a, R: Read> {
+    input: &'a mut R,
+    cipher: ChaCha20Poly1305,
+    nonce_prefix: [u8; NONCE_PREFIX_LEN],
+    aad: [u8; HEADER_LEN],
+    counter: u32,
+}
+
+impl<'a, R: Read> FrameReader<'a, R> {
+    fn new(input: &'a mut R, key: &ExportKey, header: &Header) -> Self {
+        Self {
+            input,
+            cipher: key.cipher(),
+            nonce_prefix: header.nonce_prefix,
+            aad: header.to_bytes(),
+            counter: 0,
+        }
+    }
+
+    /// Next frame's plaintext, and whether it is the last frame
+    fn read(&mut self, max_len: usize) -> BackupResult<(Vec<u8>, bool)> {
+        let mut length = [0u8; 4];
+        read_exact_or_truncated(self.input, &mut length)?;
+        let length = u32::from_le_bytes(length);
+        let last = length & LAST_FRAME != 0;
+        let length = (length & !LAST_FRAME) as usize;
+        if length < TAG_LEN || length > max_len + TAG_LEN {
+            return Err(BackupError::Decryption(format!("Invalid frame length {}", length)));
+        }
+
+        let mut ciphertext = vec![0u8; length];
+        read_exact_or_truncated(self.input, &mut ciphertext)?;
+        let nonce = frame_nonce(&self.nonce_prefix, self.counter, last);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: &self.aad })
+            .map_err(|_| {
+                BackupError::Decryption(format!("Frame {} failed authentication", self.counter))
+            })?;
+        self.counter = self.counter.wrapping_add(1);
+        Ok((plaintext, last))
+    }
+
+    /// Read the final frame and check nothing follows it
+    fn finish(&mut self) -> BackupResult<()> {
+        let (data, last) = self.read(FRAME_SIZE)?;
+        if !last || !data.is_empty() {
+            return Err(BackupError::Decryption(
+                "Export has more data than its manifest lists".to_string(),
+            ));
+        }
+        let mut probe = [0u8; 1];
+        if self.input.read(&mut probe)? != 0 {
+            return Err(BackupError::Decryption("Trailing data after the final frame".to_string()));
+        }
+        Ok(())
+    }
+}
+
+fn read_exact_or_truncated<R: Read>(reader: &mut R, buf: &mut [u8]) -> BackupResult<()> {
+    reader.read_exact(buf).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => truncated(),
+        _ => BackupError::Io(e),
+    })
+}
+
+fn truncated() -> BackupError {
+    BackupError::Decryption("Export is truncated".to_string())
+}
+
+fn changed_during_export(path: &str) -> BackupError {
+    BackupError::Io(io::Error::other(format!(
+        "{} changed during export; stop other SpacePanda processes and retry",
+        path
+    )))
+}
+
+/// Fill `buf` as far as possible, returning the number of bytes read
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+/// The identity file or non-empty keystore in `dir`, if any
+fn existing_identity(dir: &Path) -> io::Result<Option<PathBuf>> {
+    let identity = dir.join("identity.json");
+    if identity.exists() {
+        return Ok(Some(identity));
+    }
+    let keystore = dir.join("keystore");
+    if keystore.is_dir() && fs::read_dir(&keystore)?.next().is_some() {
+        return Ok(Some(keystore));
+    }
+    Ok(None)
+}
+
+/// All exported files under `root` as `(relative path, absolute path)`, sorted
+fn collect_files(root: &Path) -> BackupResult<Vec<(String, PathBuf)>> {
+    fn walk(root: &Path, dir: &Path, out: &mut Vec<(String, PathBuf)>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+
+            if file_type.is_dir() {
+                if dir == root && EXCLUDED_DIRS.contains(&name.as_str()) {
+                    continue;
+                }
+                walk(root, &path, out)?;
+            } else if file_type.is_file() {
+                let excluded = path
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .is_some_and(|e| EXCLUDED_EXTENSIONS.contains(&e));
+                if excluded {
+                    continue;
+                }
+                let relative = path
+                    .strip_prefix(root)
+                    .unwrap_or(&path)
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                out.push((relative, path));
+            } else {
+                warn!(path = %path.display(), "Skipping special file in account export");
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    walk(root, root, &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+/// Reject manifest paths that would escape the destination directory
+fn safe_relative_path(path: &str) -> BackupResult<PathBuf> {
+    let relative = PathBuf::from(path);
+    let safe = !path.is_empty() && relative.components().all(|c| matches!(c, Component::Normal(_)));
+    if !safe {
+        return Err(BackupError::Decryption(format!("Unsafe path in manifest: {}", path)));
+    }
+    Ok(relative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core_mls::types::GroupId;
+    use crate::core_store::model::types::ChannelId;
+    use std::io::Cursor;
+    use tempfile::TempDir;
+
+    const PASSPHRASE: &str = "correct horse battery staple";
+
+    fn write(dir: &Path, path: &str, contents: &[u8]) {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    fn data_dir() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        write(dir.path(), "identity.json", b"{\"user_id\":\"alice\"}");
+        write(dir.path(), "keystore/identity.key", b"identity key");
+        write(dir.path(), "mls_groups/mls_state.db", &vec![3u8; FRAME_SIZE * 2 + 17]);
+        write(dir.path(), "config.toml", b"[store]\n");
+        write(dir.path(), "commit_log", b"entry 1\n");
+        write(dir.path(), "inbox.bin", b"");
+        write(dir.path(), "attachments/blobs/abc", b"cached blob");
+        write(dir.path(), "profiles/work/identity.json", b"other account");
+        write(dir.path(), "outbox.tmp", b"half written");
+        dir
+    }
+
+    fn export(dir: &Path) -> (Vec<u8>, ExportManifest) {
+        let identity =
+            Identity::new(UserId("alice".to_string()), "Alice".to_string(), "node-a".to_string());
+        let channel = ChannelDescriptor::new(
+            ChannelId("general".to_string()),
+            identity.user_id.clone(),
+            "general".to_string(),
+            false,
+            GroupId::random(),
+        );
+        let mut out = Vec::new();
+        let manifest = export_account(dir, &identity, vec![channel], PASSPHRASE, &mut out).unwrap();
+        (out, manifest)
+    }
+
+    fn is_empty(dir: &Path) -> bool {
+        !dir.exists() || fs::read_dir(dir).unwrap().next().is_none()
+    }
+
+    #[test]
+    fn test_export_import_roundtrip() {
+        let source = data_dir();
+        let (bytes, manifest) = export(source.path());
+
+        let paths: Vec<&str> = manifest.entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "commit_log",
+                "config.toml",
+                "identity.json",
+                "inbox.bin",
+                "keystore/identity.key",
+                "mls_groups/mls_state.db"
+            ]
+        );
+        assert_eq!(manifest.section_size(ExportSection::Identity).0, 2);
+        assert_eq!(manifest.section_size(ExportSection::MlsState).1, (FRAME_SIZE * 2 + 17) as u64);
+
+        let dest = TempDir::new().unwrap();
+        let restored =
+            AccountImporter::new(PASSPHRASE).import(&mut Cursor::new(&bytes), dest.path()).unwrap();
+        assert_eq!(restored, manifest);
+        assert_eq!(restored.channels[0].name, "general");
+        for entry in &manifest.entries {
+            assert_eq!(
+                fs::read(dest.path().join(&entry.path)).unwrap(),
+                fs::read(source.path().join(&entry.path)).unwrap(),
+                "{}",
+                entry.path
+            );
+        }
+        assert!(!dest.path().join("attachments").exists());
+        assert!(!dest.path().join("profiles").exists());
+    }
+
+    #[test]
+    fn test_wrong_passphrase() {
+        let source = data_dir();
+        let (bytes, _) = export(source.path());
+
+        let err = verify_account_export(&mut Cursor::new(&bytes), "wrong").unwrap_err();
+        assert!(matches!(err, BackupError::Decryption(ref msg) if msg.contains("passphrase")));
+    }
+
+    #[test]
+    fn test_truncation_detected_before_restore() {
+        let source = data_dir();
+        let (bytes, _) = export(source.path());
+
+        // Cut at a frame boundary (dropping only the final frame) and mid-frame
+        for cut in [bytes.len() - (4 + TAG_LEN), bytes.len() - 100] {
+            let dest = TempDir::new().unwrap();
+            let err = AccountImporter::new(PASSPHRASE)
+                .import(&mut Cursor::new(&bytes[..cut]), dest.path())
+                .unwrap_err();
+            assert!(matches!(err, BackupError::Decryption(_)), "{:?}", err);
+            assert!(is_empty(dest.path()));
+        }
+    }
+
+    #[test]
+    fn test_bit_flip_detected_before_restore() {
+        let source = data_dir();
+        let (mut bytes, _) = export(source.path());
+        let middle = bytes.len() / 2;
+        bytes[middle] ^= 0x01;
+
+        let dest = TempDir::new().unwrap();
+        let err = AccountImporter::new(PASSPHRASE)
+            .import(&mut Cursor::new(&bytes), dest.path())
+            .unwrap_err();
+        assert!(matches!(err, BackupError::Decryption(_)), "{:?}", err);
+        assert!(is_empty(dest.path()));
+    }
+
+    #[test]
+    fn test_refuses_existing_identity() {
+        let source = data_dir();
+        let (bytes, _) = export(source.path());
+
+        let dest = TempDir::new().unwrap();
+        write(dest.path(), "keystore/identity.key", b"someone else");
+        let err = AccountImporter::new(PASSPHRASE)
+            .import(&mut Cursor::new(&bytes), dest.path())
+            .unwrap_err();
+        assert!(matches!(err, BackupError::IdentityExists(_)));
+        assert_eq!(fs::read(dest.path().join("keystore/identity.key")).unwrap(), b"someone else");
+    }
+}
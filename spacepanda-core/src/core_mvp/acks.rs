@@ -0,0 +1,145 @@
+//! Delivery acknowledgements
+//!
+//! Members acknowledge the chat messages they decrypt with a
+//! [`DeliveryAck`], an application message encrypted like any other, so the
+//! sender can tell who a message reached. Acknowledgements are collected
+//! per channel in [`PendingAcks`] and go out as one bundle at most every
+//! [`ACK_INTERVAL`]. They are control messages: never stored, never shown,
+//! and never acknowledged themselves.
+
+use crate::core_mvp::errors::{MvpError, MvpResult};
+use crate::core_store::model::types::{ChannelId, MessageId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Marker at the start of an acknowledgement's plaintext
+const ACK_MAGIC: &[u8; 4] = b"SPAK";
+
+/// Least time between two acknowledgement bundles sent to a channel
+pub const ACK_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Most message IDs in one bundle; the rest wait for the next
+pub const MAX_ACKS_PER_BUNDLE: usize = 256;
+
+/// Acknowledgement of chat messages received in a channel
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeliveryAck {
+    /// Messages received, oldest first
+    pub message_ids: Vec<MessageId>,
+}
+
+impl DeliveryAck {
+    /// Plaintext to send to the channel
+    pub fn encode(&self) -> MvpResult<Vec<u8>> {
+        let encoded = bincode::serialize(self).map_err(|e| {
+            MvpError::Serialization(format!("Failed to serialize acknowledgement: {}", e))
+        })?;
+        let mut plaintext = ACK_MAGIC.to_vec();
+        plaintext.extend_from_slice(&encoded);
+        Ok(plaintext)
+    }
+
+    /// The acknowledgement in a decrypted plaintext, if it is one
+    pub fn decode(plaintext: &[u8]) -> Option<Self> {
+        bincode::deserialize(plaintext.strip_prefix(ACK_MAGIC.as_slice())?).ok()
+    }
+}
+
+#[derive(Debug, Default)]
+struct ChannelAcks {
+    message_ids: Vec<MessageId>,
+    last_sent: Option<Instant>,
+}
+
+/// Acknowledgements waiting to be sent, per channel
+#[derive(Debug, Default)]
+pub struct PendingAcks {
+    channels: HashMap<ChannelId, ChannelAcks>,
+}
+
+impl PendingAcks {
+    /// Queue an acknowledgement of a received message
+    pub fn push(&mut self, channel_id: ChannelId, message_id: MessageId) {
+        let acks = self.channels.entry(channel_id).or_default();
+        if !acks.message_ids.contains(&message_id) {
+            acks.message_ids.push(message_id);
+        }
+    }
+
+    /// Put back acknowledgements that couldn't be sent, ahead of newer ones
+    pub fn restore(&mut self, channel_id: ChannelId, mut message_ids: Vec<MessageId>) {
+        let acks = self.channels.entry(channel_id).or_default();
+        message_ids.retain(|id| !acks.message_ids.contains(id));
+        message_ids.append(&mut acks.message_ids);
+        acks.message_ids = message_ids;
+    }
+
+    /// Take the bundles due at `now`
+    ///
+    /// A channel's bundle is due if it has acknowledgements queued and none
+    /// was taken in the last `interval`.
+    pub fn take_due(&mut self, now: Instant, interval: Duration) -> Vec<(ChannelId, DeliveryAck)> {
+        let mut due = Vec::new();
+        for (channel_id, acks) in self.channels.iter_mut() {
+            if acks.message_ids.is_empty()
+                || acks.last_sent.is_some_and(|sent| now.duration_since(sent) < interval)
+            {
+                continue;
+            }
+            let count = acks.message_ids.len().min(MAX_ACKS_PER_BUNDLE);
+            let message_ids = acks.message_ids.drain(..count).collect();
+            acks.last_sent = Some(now);
+            due.push((channel_id.clone(), DeliveryAck { message_ids }));
+        }
+        due
+    }
+
+    /// Number of acknowledgements queued across all channels
+    pub fn len(&self) -> usize {
+        self.channels.values().map(|acks| acks.message_ids.len()).sum()
+    }
+
+    /// Whether no acknowledgements are queued
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(n: u32) -> MessageId {
+        MessageId(format!("m{}", n))
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let ack = DeliveryAck { message_ids: vec![id(1), id(2)] };
+        assert_eq!(DeliveryAck::decode(&ack.encode().unwrap()), Some(ack));
+        assert_eq!(DeliveryAck::decode(b"hello"), None);
+    }
+
+    #[test]
+    fn test_one_bundle_per_interval() {
+        let general = ChannelId("general".to_string());
+        let mut pending = PendingAcks::default();
+        let start = Instant::now();
+
+        pending.push(general.clone(), id(1));
+        pending.push(general.clone(), id(2));
+        pending.push(general.clone(), id(1));
+        let due = pending.take_due(start, ACK_INTERVAL);
+        assert_eq!(due, vec![(general.clone(), DeliveryAck { message_ids: vec![id(1), id(2)] })]);
+
+        pending.push(general.clone(), id(3));
+        assert!(pending.take_due(start + ACK_INTERVAL / 2, ACK_INTERVAL).is_empty());
+        pending.restore(general.clone(), vec![id(2)]);
+        assert_eq!(pending.len(), 2);
+
+        let due = pending.take_due(start + ACK_INTERVAL, ACK_INTERVAL);
+        assert_eq!(due, vec![(general, DeliveryAck { message_ids: vec![id(2), id(3)] })]);
+        assert!(pending.is_empty());
+    }
+}
@@ -0,0 +1,475 @@
+//! Encrypted file attachments stored in the DHT
+//!
+//! A file is split into [`ATTACHMENT_CHUNK_SIZE`] chunks, each encrypted
+//! with ChaCha20-Poly1305 under a per-file key exported from the channel's
+//! MLS group. The attachment ID and chunk index are bound in as associated
+//! data, so chunks can't be swapped between files or reordered. Chunks are
+//! stored in the DHT through an [`AttachmentStore`], and an
+//! [`AttachmentManifest`] listing their keys and hashes is sent to the
+//! channel as an ordinary application message.
+//!
+//! The manifest carries the file key: a member downloading in a later epoch
+//! can no longer export the sending epoch's secret, and the manifest itself
+//! only ever travels MLS-encrypted.
+
+use crate::core_dht::{DhtClient, DhtKey, DhtStorage, DhtValue, FindValueResult, RoutingTable};
+use crate::core_mvp::errors::{MvpError, MvpResult};
+use crate::core_store::model::Attachment;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Nonce,
+};
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+/// Marker at the start of an attachment manifest's plaintext
+const ATTACHMENT_MAGIC: &[u8; 4] = b"SPAT";
+
+/// MLS exporter label for per-file keys
+pub const ATTACHMENT_KEY_LABEL: &str = "spacepanda attachment";
+
+/// Plaintext bytes per chunk (the last chunk may be shorter)
+pub const ATTACHMENT_CHUNK_SIZE: usize = 256 * 1024;
+
+/// How long chunks stay in the DHT
+pub const ATTACHMENT_CHUNK_TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Times a chunk is fetched before the download fails
+pub const CHUNK_FETCH_ATTEMPTS: u32 = 3;
+
+/// Wait between attempts to fetch a chunk
+pub const CHUNK_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Peers each chunk is stored on, besides ourselves
+pub const DEFAULT_CHUNK_REPLICAS: usize = 3;
+
+/// An encrypted chunk of an attachment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkRef {
+    /// Where the chunk is stored in the DHT
+    pub key: DhtKey,
+
+    /// BLAKE3 hash of the encrypted chunk
+    pub hash: [u8; 32],
+}
+
+/// Everything a member needs to download and decrypt an attachment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttachmentManifest {
+    /// Random attachment ID (hex)
+    pub id: String,
+
+    /// Name of the file as sent
+    pub filename: String,
+
+    /// MIME type guessed from the file name
+    pub mime_type: String,
+
+    /// Size of the file in bytes
+    pub size: u64,
+
+    /// Plaintext bytes per chunk
+    pub chunk_size: u32,
+
+    /// Per-file key the chunks are encrypted under
+    pub key: [u8; 32],
+
+    /// The chunks, in file order
+    pub chunks: Vec<ChunkRef>,
+}
+
+impl AttachmentManifest {
+    /// Manifest for a file about to be uploaded, without chunks yet
+    pub fn new(id: String, filename: String, mime_type: String, key: [u8; 32]) -> Self {
+        Self {
+            id,
+            filename,
+            mime_type,
+            size: 0,
+            chunk_size: ATTACHMENT_CHUNK_SIZE as u32,
+            key,
+            chunks: Vec::new(),
+        }
+    }
+
+    /// DHT key of one of an attachment's chunks
+    pub fn chunk_key(id: &str, index: usize) -> DhtKey {
+        DhtKey::hash(format!("attachment:{}:{}", id, index).as_bytes())
+    }
+
+    /// Encrypt a chunk of the file
+    pub fn encrypt_chunk(&self, index: usize, plaintext: &[u8]) -> MvpResult<Vec<u8>> {
+        let aad = self.associated_data(index);
+        ChaCha20Poly1305::new((&self.key).into())
+            .encrypt(&chunk_nonce(index), Payload { msg: plaintext, aad: &aad })
+            .map_err(|_| MvpError::Internal(format!("Failed to encrypt chunk {}", index)))
+    }
+
+    /// Decrypt a chunk of the file
+    pub fn decrypt_chunk(&self, index: usize, ciphertext: &[u8]) -> MvpResult<Vec<u8>> {
+        let aad = self.associated_data(index);
+        ChaCha20Poly1305::new((&self.key).into())
+            .decrypt(&chunk_nonce(index), Payload { msg: ciphertext, aad: &aad })
+            .map_err(|_| MvpError::InvalidMessage(format!("Failed to decrypt chunk {}", index)))
+    }
+
+    fn associated_data(&self, index: usize) -> Vec<u8> {
+        let mut aad = self.id.as_bytes().to_vec();
+        aad.extend_from_slice(&(index as u64).to_be_bytes());
+        aad
+    }
+
+    /// Metadata kept on the stored message
+    pub fn metadata(&self) -> Attachment {
+        let mut hasher = blake3::Hasher::new();
+        for chunk in &self.chunks {
+            hasher.update(&chunk.hash);
+        }
+        Attachment {
+            id: self.id.clone(),
+            filename: self.filename.clone(),
+            mime_type: self.mime_type.clone(),
+            size_bytes: self.size,
+            content_hash: hasher.finalize().to_hex().to_string(),
+        }
+    }
+
+    /// Plaintext to send to the channel
+    pub fn encode(&self) -> MvpResult<Vec<u8>> {
+        let encoded = bincode::serialize(self)
+            .map_err(|e| MvpError::Serialization(format!("Failed to serialize manifest: {}", e)))?;
+        let mut plaintext = ATTACHMENT_MAGIC.to_vec();
+        plaintext.extend_from_slice(&encoded);
+        Ok(plaintext)
+    }
+
+    /// The manifest in a decrypted plaintext, if it is one
+    pub fn decode(plaintext: &[u8]) -> Option<Self> {
+        bincode::deserialize(plaintext.strip_prefix(ATTACHMENT_MAGIC.as_slice())?).ok()
+    }
+}
+
+/// Chunks are only ever encrypted once under their file's key, so the
+/// index alone makes a unique nonce
+fn chunk_nonce(index: usize) -> Nonce {
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&(index as u64).to_be_bytes());
+    Nonce::from(nonce)
+}
+
+fn chunk_hash(ciphertext: &[u8]) -> [u8; 32] {
+    *blake3::hash(ciphertext).as_bytes()
+}
+
+/// MIME type of a file, from its extension
+pub fn mime_type_for(path: &Path) -> &'static str {
+    let extension = path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("txt") | Some("md") | Some("log") => "text/plain",
+        Some("html") | Some("htm") => "text/html",
+        Some("csv") => "text/csv",
+        Some("json") => "application/json",
+        Some("pdf") => "application/pdf",
+        Some("zip") => "application/zip",
+        Some("gz") => "application/gzip",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("svg") => "image/svg+xml",
+        Some("mp3") => "audio/mpeg",
+        Some("ogg") => "audio/ogg",
+        Some("mp4") => "video/mp4",
+        Some("webm") => "video/webm",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Where a download in progress is written
+pub fn partial_path(dest: &Path) -> PathBuf {
+    let mut partial = dest.as_os_str().to_owned();
+    partial.push(".part");
+    PathBuf::from(partial)
+}
+
+fn io_error(action: &str, path: &Path, e: std::io::Error) -> MvpError {
+    MvpError::Store(format!("Failed to {} {}: {}", action, path.display(), e))
+}
+
+/// Attachment chunks stored in the DHT
+///
+/// Chunks always go to the local DHT storage. With peers attached, each is
+/// also stored on the closest peers, and a chunk missing locally is fetched
+/// from any peer holding a replica.
+pub struct AttachmentStore {
+    storage: DhtStorage,
+    peers: Option<(Arc<DhtClient>, Arc<Mutex<RoutingTable>>)>,
+    replicas: usize,
+    fetch_attempts: u32,
+    retry_delay: Duration,
+}
+
+impl AttachmentStore {
+    /// Create a store backed by the given DHT storage
+    pub fn new(storage: DhtStorage) -> Self {
+        Self {
+            storage,
+            peers: None,
+            replicas: DEFAULT_CHUNK_REPLICAS,
+            fetch_attempts: CHUNK_FETCH_ATTEMPTS,
+            retry_delay: CHUNK_RETRY_DELAY,
+        }
+    }
+
+    /// Replicate chunks to, and fetch them from, the closest DHT peers
+    ///
+    /// # Arguments
+    /// * `client` - Client for STORE and FIND_VALUE requests
+    /// * `routing_table` - Table the closest peers are picked from
+    /// * `replicas` - Peers each chunk is stored on
+    pub fn with_peers(
+        mut self,
+        client: Arc<DhtClient>,
+        routing_table: Arc<Mutex<RoutingTable>>,
+        replicas: usize,
+    ) -> Self {
+        self.peers = Some((client, routing_table));
+        self.replicas = replicas;
+        self
+    }
+
+    /// Set how often, and how far apart, a chunk fetch is attempted
+    pub fn with_retries(mut self, attempts: u32, delay: Duration) -> Self {
+        self.fetch_attempts = attempts.max(1);
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Encrypt and store a file's chunks, adding them to its manifest
+    ///
+    /// Stops with `PayloadTooLarge` as soon as the file grows past
+    /// `max_size`. Returns the fewest peers any chunk was stored on, which
+    /// is the configured replica count for a file without chunks.
+    pub async fn upload(
+        &self,
+        manifest: &mut AttachmentManifest,
+        mut file: impl Read,
+        max_size: u64,
+    ) -> MvpResult<usize> {
+        let mut replicated: Option<usize> = None;
+        loop {
+            let mut plaintext = Vec::with_capacity(manifest.chunk_size as usize);
+            (&mut file)
+                .take(manifest.chunk_size as u64)
+                .read_to_end(&mut plaintext)
+                .map_err(|e| MvpError::Store(format!("Failed to read attachment: {}", e)))?;
+            if plaintext.is_empty() {
+                return Ok(replicated.unwrap_or(self.replicas));
+            }
+
+            manifest.size += plaintext.len() as u64;
+            if manifest.size > max_size {
+                return Err(MvpError::PayloadTooLarge {
+                    size: usize::try_from(manifest.size).unwrap_or(usize::MAX),
+                    limit: usize::try_from(max_size).unwrap_or(usize::MAX),
+                });
+            }
+
+            let index = manifest.chunks.len();
+            let ciphertext = manifest.encrypt_chunk(index, &plaintext)?;
+            let chunk = ChunkRef {
+                key: AttachmentManifest::chunk_key(&manifest.id, index),
+                hash: chunk_hash(&ciphertext),
+            };
+            let peers = self.put_chunk(chunk.key, ciphertext).await?;
+            replicated = Some(replicated.map_or(peers, |fewest| fewest.min(peers)));
+            manifest.chunks.push(chunk);
+        }
+    }
+
+    /// Fetch, verify and decrypt an attachment into `dest`
+    ///
+    /// Chunks are appended to `dest` with `.part` added to its name, which
+    /// is renamed to `dest` once complete. A download that failed part way
+    /// resumes after the last whole chunk in that file.
+    pub async fn download(&self, manifest: &AttachmentManifest, dest: &Path) -> MvpResult<()> {
+        let partial = partial_path(dest);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&partial)
+            .map_err(|e| io_error("open", &partial, e))?;
+
+        let chunk_size = u64::from(manifest.chunk_size.max(1));
+        let written = file.metadata().map_err(|e| io_error("read", &partial, e))?.len();
+        let done = (written / chunk_size).min(manifest.chunks.len() as u64);
+        file.set_len(done * chunk_size).map_err(|e| io_error("truncate", &partial, e))?;
+        file.seek(SeekFrom::End(0)).map_err(|e| io_error("seek", &partial, e))?;
+        if done > 0 {
+            debug!(attachment = %manifest.id, chunks = done, "Resuming attachment download");
+        }
+
+        for (index, chunk) in manifest.chunks.iter().enumerate().skip(done as usize) {
+            let ciphertext = self.fetch_chunk(index, chunk).await?;
+            let plaintext = manifest.decrypt_chunk(index, &ciphertext)?;
+            file.write_all(&plaintext).map_err(|e| io_error("write", &partial, e))?;
+        }
+        file.sync_all().map_err(|e| io_error("sync", &partial, e))?;
+
+        let size = file.metadata().map_err(|e| io_error("read", &partial, e))?.len();
+        if size != manifest.size {
+            let _ = std::fs::remove_file(&partial);
+            return Err(MvpError::InvalidMessage(format!(
+                "Attachment {} is {} bytes, manifest says {}",
+                manifest.id, size, manifest.size
+            )));
+        }
+        std::fs::rename(&partial, dest).map_err(|e| io_error("rename", &partial, e))
+    }
+
+    /// Store an encrypted chunk locally and on the closest peers
+    ///
+    /// Failing to reach a peer is logged, not fatal: the chunk is still
+    /// served from our own storage. Returns how many peers stored it.
+    pub async fn put_chunk(&self, key: DhtKey, ciphertext: Vec<u8>) -> MvpResult<usize> {
+        let value = DhtValue::new(ciphertext).with_ttl_duration(ATTACHMENT_CHUNK_TTL);
+        self.storage.put(key, value.clone()).map_err(MvpError::Dht)?;
+
+        let Some((client, routing_table)) = &self.peers else {
+            return Ok(0);
+        };
+        let closest = routing_table.lock().await.find_closest(&key, self.replicas);
+        let mut stored = 0;
+        for peer in closest {
+            match client.store(peer.id, key, value.clone()).await {
+                Ok(()) => {
+                    let _ = self.storage.add_replica(&key, peer.id);
+                    stored += 1;
+                }
+                Err(e) => {
+                    warn!(chunk = %key, peer = %peer.id, error = %e, "Failed to replicate chunk")
+                }
+            }
+        }
+        Ok(stored)
+    }
+
+    /// Fetch an encrypted chunk, retrying until its hash checks out
+    ///
+    /// # Errors
+    ///
+    /// `ChunkUnavailable` with the last failure once every attempt has
+    /// failed
+    pub async fn fetch_chunk(&self, index: usize, chunk: &ChunkRef) -> MvpResult<Vec<u8>> {
+        let mut reason = String::new();
+        for attempt in 1..=self.fetch_attempts {
+            match self.try_fetch(chunk).await {
+                Ok(ciphertext) => return Ok(ciphertext),
+                Err(e) => {
+                    debug!(chunk = %chunk.key, index, attempt, error = %e, "Chunk fetch failed");
+                    reason = e;
+                }
+            }
+            if attempt < self.fetch_attempts {
+                tokio::time::sleep(self.retry_delay).await;
+            }
+        }
+        Err(MvpError::ChunkUnavailable { index, attempts: self.fetch_attempts, reason })
+    }
+
+    /// One attempt at a chunk: our own storage, then each replica in turn
+    async fn try_fetch(&self, chunk: &ChunkRef) -> Result<Vec<u8>, String> {
+        let mut reason = match self.storage.get(&chunk.key) {
+            Ok(value) if chunk_hash(&value.data) == chunk.hash => return Ok(value.data),
+            Ok(_) => "stored chunk doesn't match its hash".to_string(),
+            Err(e) => e,
+        };
+
+        let Some((client, routing_table)) = &self.peers else {
+            return Err(reason);
+        };
+        let mut peers = self.storage.get_replicas(&chunk.key).unwrap_or_default();
+        for peer in routing_table.lock().await.find_closest(&chunk.key, self.replicas) {
+            if !peers.contains(&peer.id) {
+                peers.push(peer.id);
+            }
+        }
+
+        for peer in peers {
+            match client.find_value(peer, chunk.key).await {
+                Ok(FindValueResult::Found(value)) if chunk_hash(&value.data) == chunk.hash => {
+                    // Keep a copy so a resumed download needn't ask again
+                    let _ = self.storage.delete(&chunk.key);
+                    if self.storage.put(chunk.key, value.clone()).is_ok() {
+                        let _ = self.storage.add_replica(&chunk.key, peer);
+                    }
+                    return Ok(value.data);
+                }
+                Ok(FindValueResult::Found(_)) => {
+                    reason = format!("peer {} returned a chunk that doesn't match its hash", peer)
+                }
+                Ok(FindValueResult::NotFound { .. }) => {
+                    reason = format!("peer {} doesn't have the chunk", peer)
+                }
+                Err(e) => reason = format!("peer {}: {}", peer, e),
+            }
+        }
+        Err(reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest() -> AttachmentManifest {
+        AttachmentManifest::new(
+            "0123abcd".to_string(),
+            "notes.txt".to_string(),
+            "text/plain".to_string(),
+            [7u8; 32],
+        )
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let mut manifest = manifest();
+        manifest
+            .chunks
+            .push(ChunkRef { key: AttachmentManifest::chunk_key("0123abcd", 0), hash: [1; 32] });
+
+        let plaintext = manifest.encode().unwrap();
+        assert_eq!(AttachmentManifest::decode(&plaintext), Some(manifest));
+
+        // Ordinary messages aren't manifests
+        assert_eq!(AttachmentManifest::decode(b"hello"), None);
+        assert_eq!(AttachmentManifest::decode(b"SPAT not really"), None);
+    }
+
+    #[test]
+    fn test_chunks_are_bound_to_their_position() {
+        let manifest = manifest();
+        let ciphertext = manifest.encrypt_chunk(1, b"second chunk").unwrap();
+        assert_eq!(manifest.decrypt_chunk(1, &ciphertext).unwrap(), b"second chunk");
+
+        // Moved to another position or another file, it no longer decrypts
+        assert!(manifest.decrypt_chunk(0, &ciphertext).is_err());
+        let mut other = manifest.clone();
+        other.id = "ffff0000".to_string();
+        assert!(other.decrypt_chunk(1, &ciphertext).is_err());
+    }
+
+    #[test]
+    fn test_mime_type_for() {
+        assert_eq!(mime_type_for(Path::new("photo.JPG")), "image/jpeg");
+        assert_eq!(mime_type_for(Path::new("report.pdf")), "application/pdf");
+        assert_eq!(mime_type_for(Path::new("Makefile")), "application/octet-stream");
+    }
+}
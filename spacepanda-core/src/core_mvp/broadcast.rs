@@ -0,0 +1,270 @@
+//! Broadcast channel mode
+//!
+//! Channels with thousands of read-mostly members make every MLS commit
+//! expensive: each one is O(members) to build and must reach everyone.
+//! Broadcast mode stays within MLS but changes how the channel is run:
+//!
+//! - Membership changes for readers (non-publishers) are queued and
+//!   committed together on a slow cadence instead of one commit each
+//! - Publishers' messages are written once to a DHT mailbox that readers
+//!   pull from, instead of being sent over a session to every peer
+//!
+//! See [`BroadcastPolicy`] for the security tradeoff.
+
+use crate::core_dht::{DhtKey, DhtStorage, DhtValue};
+use crate::core_mvp::errors::{MvpError, MvpResult};
+use crate::core_mvp::types::InviteToken;
+use crate::core_store::model::types::{ChannelId, UserId};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// Default cadence for committing queued membership changes
+pub const DEFAULT_MEMBERSHIP_BATCH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Policy for a channel run in broadcast mode
+///
+/// # Security tradeoff
+///
+/// Batching reader membership changes also means the channel's epoch
+/// advances at most once per `membership_batch_interval` for them:
+///
+/// - A removed reader can keep decrypting new messages until the next
+///   batch commit
+/// - A reader's leaked keys stay useful for up to one interval, because
+///   post-compromise security only kicks in at the next epoch
+/// - New readers wait up to one interval for their Welcome
+///
+/// Publisher adds and removals are not queued and still take effect
+/// immediately. Shorten the interval for channels whose readership is
+/// itself sensitive.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BroadcastPolicy {
+    /// Members allowed to post; everyone else is a reader
+    pub publishers: HashSet<UserId>,
+
+    /// How often queued reader membership changes are committed
+    pub membership_batch_interval: Duration,
+}
+
+impl BroadcastPolicy {
+    /// Create a policy with the default batch interval
+    pub fn new(publishers: impl IntoIterator<Item = UserId>) -> Self {
+        Self {
+            publishers: publishers.into_iter().collect(),
+            membership_batch_interval: DEFAULT_MEMBERSHIP_BATCH_INTERVAL,
+        }
+    }
+
+    /// Set the batch interval
+    pub fn with_batch_interval(mut self, interval: Duration) -> Self {
+        self.membership_batch_interval = interval;
+        self
+    }
+
+    /// Whether a user may post to the channel
+    pub fn is_publisher(&self, user_id: &UserId) -> bool {
+        self.publishers.contains(user_id)
+    }
+}
+
+/// A reader membership change waiting for the next batch commit
+#[derive(Debug, Clone)]
+pub enum QueuedMembershipChange {
+    /// Add the owner of a key package
+    Add(Vec<u8>),
+    /// Remove a member by identity
+    Remove(Vec<u8>),
+}
+
+/// Result of committing a channel's membership queue
+#[derive(Debug, Clone, Default)]
+pub struct MembershipBatch {
+    /// One invite for all added members (they share the Welcome)
+    pub invite: Option<InviteToken>,
+
+    /// Commits for existing members, in the order they must be processed
+    pub commits: Vec<Vec<u8>>,
+
+    /// Number of members added
+    pub added: usize,
+
+    /// Number of members removed
+    pub removed: usize,
+}
+
+/// Per-channel broadcast state held by the ChannelManager
+#[derive(Debug)]
+pub(crate) struct BroadcastState {
+    pub(crate) policy: BroadcastPolicy,
+    pub(crate) queue: Vec<QueuedMembershipChange>,
+    pub(crate) last_batch: Instant,
+    /// Next mailbox sequence number to fetch
+    pub(crate) mailbox_cursor: u64,
+}
+
+impl BroadcastState {
+    pub(crate) fn new(policy: BroadcastPolicy) -> Self {
+        Self { policy, queue: Vec::new(), last_batch: Instant::now(), mailbox_cursor: 0 }
+    }
+
+    /// Whether the queue should be committed now
+    pub(crate) fn batch_due(&self, now: Instant) -> bool {
+        !self.queue.is_empty()
+            && now.duration_since(self.last_batch) >= self.policy.membership_batch_interval
+    }
+}
+
+/// A published broadcast message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MailboxEntry {
+    /// Position in the channel's mailbox
+    pub seq: u64,
+
+    /// Publisher's user ID
+    pub sender: UserId,
+
+    /// MLS ciphertext
+    pub ciphertext: Vec<u8>,
+}
+
+/// Store-and-forward fan-out for broadcast channels
+///
+/// Publishers write each message once; readers pull what they haven't
+/// seen. Entries are MLS ciphertext, so the mailbox learns nothing
+/// beyond sizes and timing.
+#[async_trait]
+pub trait BroadcastMailbox: Send + Sync {
+    /// Append a message, returning its sequence number
+    async fn publish(
+        &self,
+        channel_id: &ChannelId,
+        sender: &UserId,
+        ciphertext: Vec<u8>,
+    ) -> MvpResult<u64>;
+
+    /// Messages with `seq >= from_seq`, oldest first
+    async fn fetch(&self, channel_id: &ChannelId, from_seq: u64) -> MvpResult<Vec<MailboxEntry>>;
+}
+
+/// Broadcast mailbox stored in the DHT
+///
+/// Entries live under `broadcast:<channel>:<seq>` and a head record under
+/// `broadcast-head:<channel>` holds the next sequence number.
+pub struct DhtMailbox {
+    storage: DhtStorage,
+    /// Serializes publishes so sequence numbers are unique
+    publish_lock: Mutex<()>,
+}
+
+impl DhtMailbox {
+    /// Create a mailbox backed by the given DHT storage
+    pub fn new(storage: DhtStorage) -> Self {
+        Self { storage, publish_lock: Mutex::new(()) }
+    }
+
+    fn head_key(channel_id: &ChannelId) -> DhtKey {
+        DhtKey::hash(format!("broadcast-head:{}", channel_id.0).as_bytes())
+    }
+
+    fn entry_key(channel_id: &ChannelId, seq: u64) -> DhtKey {
+        DhtKey::hash(format!("broadcast:{}:{}", channel_id.0, seq).as_bytes())
+    }
+
+    /// Next sequence number to be published
+    fn head(&self, channel_id: &ChannelId) -> u64 {
+        self.storage
+            .get(&Self::head_key(channel_id))
+            .map(|value| value.sequence)
+            .unwrap_or(0)
+    }
+}
+
+#[async_trait]
+impl BroadcastMailbox for DhtMailbox {
+    async fn publish(
+        &self,
+        channel_id: &ChannelId,
+        sender: &UserId,
+        ciphertext: Vec<u8>,
+    ) -> MvpResult<u64> {
+        let _guard = self.publish_lock.lock().await;
+
+        let seq = self.head(channel_id);
+        let entry = MailboxEntry { seq, sender: sender.clone(), ciphertext };
+        let data = bincode::serialize(&entry)
+            .map_err(|e| MvpError::SerializationError(format!("Failed to serialize: {}", e)))?;
+
+        self.storage
+            .put(Self::entry_key(channel_id, seq), DhtValue::new(data))
+            .map_err(MvpError::Dht)?;
+
+        // The head's DHT sequence number doubles as the mailbox length
+        let head = DhtValue::new(Vec::new()).with_sequence(seq + 1);
+        self.storage.put(Self::head_key(channel_id), head).map_err(MvpError::Dht)?;
+
+        Ok(seq)
+    }
+
+    async fn fetch(&self, channel_id: &ChannelId, from_seq: u64) -> MvpResult<Vec<MailboxEntry>> {
+        let head = self.head(channel_id);
+
+        let mut entries = Vec::new();
+        for seq in from_seq..head {
+            // Expired entries are skipped rather than failing the fetch
+            let Ok(value) = self.storage.get(&Self::entry_key(channel_id, seq)) else {
+                continue;
+            };
+            let entry = bincode::deserialize(&value.data).map_err(|e| {
+                MvpError::SerializationError(format!("Failed to deserialize: {}", e))
+            })?;
+            entries.push(entry);
+        }
+
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_dht_mailbox_fetch_from_cursor() {
+        let mailbox = DhtMailbox::new(DhtStorage::new());
+        let channel_id = ChannelId::generate();
+        let other = ChannelId::generate();
+        let sender = UserId("publisher".to_string());
+
+        for i in 0..3u8 {
+            assert_eq!(mailbox.publish(&channel_id, &sender, vec![i]).await.unwrap(), i as u64);
+        }
+        mailbox.publish(&other, &sender, vec![9]).await.unwrap();
+
+        let all = mailbox.fetch(&channel_id, 0).await.unwrap();
+        assert_eq!(all.iter().map(|e| e.ciphertext[0]).collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert_eq!(all[0].sender, sender);
+
+        let rest = mailbox.fetch(&channel_id, 2).await.unwrap();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].seq, 2);
+        assert!(mailbox.fetch(&channel_id, 3).await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_batch_due() {
+        let policy = BroadcastPolicy::new([UserId("publisher".to_string())])
+            .with_batch_interval(Duration::from_secs(60));
+        let mut state = BroadcastState::new(policy);
+        let later = state.last_batch + Duration::from_secs(61);
+
+        // Nothing queued, nothing to commit
+        assert!(!state.batch_due(later));
+
+        state.queue.push(QueuedMembershipChange::Remove(b"reader".to_vec()));
+        assert!(!state.batch_due(state.last_batch));
+        assert!(state.batch_due(later));
+    }
+}
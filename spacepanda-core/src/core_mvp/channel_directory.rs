@@ -0,0 +1,106 @@
+//! Channel listings in the DHT
+//!
+//! Channels created with a namespace key (see
+//! [`ChannelManager::create_channel_with_key`](crate::core_mvp::ChannelManager::create_channel_with_key))
+//! are listed here under their derived ID. The listing lets a re-run of the
+//! same provisioning find the channel even from a node without local state
+//! for it, and a tombstone left on deletion keeps the ID from being reused
+//! until it expires.
+
+use crate::core_dht::private_lookup::CHANNEL_LISTING_NAMESPACE;
+use crate::core_dht::{DhtKey, DhtStorage, DhtValue};
+use crate::core_mvp::errors::{MvpError, MvpResult};
+use crate::core_store::model::types::{ChannelId, Timestamp, UserId};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How long a live listing stays in the DHT
+pub const LISTING_TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Default time a deleted channel's ID stays reserved
+pub const DEFAULT_TOMBSTONE_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// A channel's entry in the directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelListing {
+    pub channel_id: ChannelId,
+    pub name: String,
+    pub creator: UserId,
+    pub created_at: Timestamp,
+
+    /// Set once the creator deletes the channel
+    pub deleted_at: Option<Timestamp>,
+}
+
+impl ChannelListing {
+    /// Whether this listing is a deletion tombstone
+    pub fn is_tombstone(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+}
+
+/// Directory of keyed channels stored in the DHT
+pub struct ChannelDirectory {
+    storage: DhtStorage,
+    tombstone_ttl: Duration,
+}
+
+impl ChannelDirectory {
+    /// Create a directory backed by the given DHT storage
+    pub fn new(storage: DhtStorage) -> Self {
+        Self { storage, tombstone_ttl: DEFAULT_TOMBSTONE_TTL }
+    }
+
+    /// Set how long a deleted channel's ID stays reserved
+    pub fn with_tombstone_ttl(mut self, ttl: Duration) -> Self {
+        self.tombstone_ttl = ttl;
+        self
+    }
+
+    fn key(channel_id: &ChannelId) -> DhtKey {
+        DhtKey::hash(format!("{}:{}", CHANNEL_LISTING_NAMESPACE, channel_id.0).as_bytes())
+    }
+
+    /// Current listing for a channel
+    ///
+    /// Expired listings, including expired tombstones, count as absent.
+    pub fn lookup(&self, channel_id: &ChannelId) -> MvpResult<Option<ChannelListing>> {
+        let Ok(value) = self.storage.get(&Self::key(channel_id)) else {
+            return Ok(None);
+        };
+        let listing = bincode::deserialize(&value.data)
+            .map_err(|e| MvpError::SerializationError(format!("Failed to deserialize: {}", e)))?;
+        Ok(Some(listing))
+    }
+
+    /// Publish a live listing
+    pub fn publish(&self, listing: &ChannelListing) -> MvpResult<()> {
+        self.put(listing, LISTING_TTL)
+    }
+
+    /// Replace a listing with a tombstone that expires after the tombstone TTL
+    pub fn tombstone(&self, listing: &ChannelListing) -> MvpResult<()> {
+        let tombstone = ChannelListing { deleted_at: Some(Timestamp::now()), ..listing.clone() };
+        self.put(&tombstone, self.tombstone_ttl)
+    }
+
+    fn put(&self, listing: &ChannelListing, ttl: Duration) -> MvpResult<()> {
+        let key = Self::key(&listing.channel_id);
+        let data = bincode::serialize(listing)
+            .map_err(|e| MvpError::SerializationError(format!("Failed to serialize: {}", e)))?;
+
+        // Sequence by write time so the latest listing wins on every replica
+        let previous = self.storage.get(&key).ok().map(|value| value.sequence);
+        let sequence = Timestamp::now().as_millis().max(previous.map_or(0, |seq| seq + 1));
+        let value = DhtValue::new(data).with_ttl_duration(ttl).with_sequence(sequence);
+
+        match self.storage.put(key, value.clone()) {
+            // An expired entry written in the same millisecond; replace it
+            Err(_) if previous.is_none() => {
+                let _ = self.storage.delete(&key);
+                self.storage.put(key, value).map_err(MvpError::Dht)
+            }
+            result => result.map_err(MvpError::Dht),
+        }
+    }
+}
@@ -23,34 +23,109 @@
 
 use crate::{
     config::Config,
+    core_dht::ChannelFeed,
+    core_identity::{validate_device_bundle, DeviceBundle, DeviceId},
     core_mls::{
+        channel_metadata::{ChannelMetadata, RetentionPolicy},
+        commit_policy::ChannelRoles,
         engine::GroupOperations,
+        errors::MlsError,
+        events::MlsEvent,
+        messages::{EncryptedEnvelope, MessageType as EnvelopeType},
+        padding::PaddingStrategy,
+        sealed_sender,
         service::MlsService,
-        types::{GroupId, GroupMetadata, MemberRole},
+        types::{GroupId, GroupMetadata, MemberInfo, MemberRole},
+        welcome::TreeSnapshot,
     },
     core_mvp::{
+        acks::{DeliveryAck, PendingAcks, ACK_INTERVAL},
+        attachments::{mime_type_for, AttachmentManifest, AttachmentStore, ATTACHMENT_KEY_LABEL},
+        broadcast::{
+            BroadcastMailbox, BroadcastPolicy, BroadcastState, MembershipBatch,
+            QueuedMembershipChange,
+        },
+        channel_directory::{ChannelDirectory, ChannelListing},
+        circuit_feed::FeedPublisher,
+        device_link::{DeviceLinkRequest, LinkedDevice, RevokedDevice},
+        ephemeral::{
+            EphemeralKind, EphemeralMessage, EPHEMERAL_FEED_TTL, PRESENCE_TIMEOUT, TYPING_INTERVAL,
+        },
         errors::{MvpError, MvpResult},
+        events::{ChannelEvent, ChannelEventKind, EventQueue},
         identity_scoping::IdentityScoper,
-        network::NetworkLayer,
+        key_package_directory::{FetchedKeyPackage, KeyPackageDirectory},
+        mailbox_delivery::{Delivery, DeliveryMailbox},
+        message_edits::{MessageChange, PendingChanges},
+        network::{IncomingCommit, IncomingMessage, InviteAcceptance, NetworkLayer},
+        offline_invite::{OfflineInvite, ResumeReport},
         peer_discovery::PeerDiscoveryService,
+        privacy::{ChannelPrivacy, PrivacyReport},
+        public_group_directory::PublicGroupDirectory,
+        reactions::{ParkedReactions, ReactionOp, ReactionUpdate},
+        supervisor::{
+            ChannelSupervisor, InboundHandler, InboundWork, SecurityEvent, SupervisorPolicy,
+        },
+        transcript::{TranscriptOptions, TranscriptWriter},
+        transform::{detect_language, ContentTransformer, RenderedMessage, TransformerChain},
+        tree_directory::TreeDirectory,
         types::{
-            ChannelDescriptor, ChatMessage, InviteToken, MessageWithThread, Reaction,
-            ReactionSummary, ThreadInfo,
+            ChannelDescriptor, ChannelMember, ChatMessage, DeliverySummary, ExternalTree,
+            InviteBatch, InviteOptions, InviteToken, Invitee, KeyRotationReport, MessageEdit,
+            MessageType, MessageWithThread, ReactionSummary, RetentionReport, ThreadInfo,
+            TranscriptReport,
+        },
+        webhooks::{
+            check_content_size, generate_token, generate_webhook_id, hash_token, open_webhook_mls,
+            token_matches, webhook_user_id, HostedWebhook, WebhookCredentials, WebhookHost,
+            WebhookOptions,
         },
     },
     core_store::{
+        crdt::AddId,
         model::{
-            channel::Channel,
+            channel::{
+                Channel, InviteRecord, LinkedDeviceRecord, MemberDevice, ReadMarker, WebhookRecord,
+            },
+            message::Attachment,
+            permission::{Permission, PermissionSet},
             types::{ChannelId, ChannelType, MessageId, Timestamp, UserId},
         },
-        store::local_store::LocalStore,
+        store::{
+            attachment_cache::AttachmentCache,
+            inbox::PendingEnvelope,
+            local_store::LocalStore,
+            outbox::{DeferredJoin, OutgoingEnvelope},
+            preflight::{estimate_join, StoragePreflight},
+            receipts::{DeliveryState, SentReceipts},
+        },
     },
+    metrics::record_counter,
+    shutdown::{ShutdownCoordinator, ShutdownHandler},
 };
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, Mutex, RwLock};
 use tracing::{debug, info, warn};
 
+/// Failed attempts after which a pending envelope is dropped
+const MAX_PENDING_ATTEMPTS: u32 = 8;
+
+/// Capacity of the decrypted message broadcast channel
+const INCOMING_BROADCAST_CAPACITY: usize = 256;
+
+/// Default cap on the outbox's total ciphertext size
+const DEFAULT_OUTBOX_MAX_BYTES: usize = 16 * 1024 * 1024;
+
+/// Prefix keeping application exporter labels apart from internal ones
+const APP_SECRET_LABEL_PREFIX: &str = "spacepanda app ";
+
+/// Messages read from the store per page when exporting a transcript
+const EXPORT_PAGE_SIZE: usize = 256;
+
 /// Channel Manager - orchestrates all channel operations
 pub struct ChannelManager {
     /// MLS service for group encryption
@@ -74,13 +149,106 @@ pub struct ChannelManager {
     /// Optional peer discovery service
     peer_discovery: Option<PeerDiscoveryService>,
 
-    /// In-memory reaction storage (MessageId -> Vec<Reaction>)
-    /// TODO: Persist to CRDT in production
-    reactions: Arc<RwLock<HashMap<MessageId, Vec<Reaction>>>>,
-
     /// In-memory message storage (ChannelId -> Vec<ChatMessage>)
     /// TODO: Persist to CRDT in production
     messages: Arc<RwLock<HashMap<ChannelId, Vec<ChatMessage>>>>,
+
+    /// Display-time transformers applied when rendering history
+    transformers: TransformerChain,
+
+    /// Optional attachment blob cache
+    attachments: Option<Arc<AttachmentCache>>,
+
+    /// Optional DHT store for attachment chunks
+    attachment_store: Option<Arc<AttachmentStore>>,
+
+    /// Free disk space check before joins
+    preflight: StoragePreflight,
+
+    /// Broadcast of decrypted incoming messages
+    incoming: broadcast::Sender<ChatMessage>,
+
+    /// Broadcast of ordered channel events
+    events: broadcast::Sender<ChannelEvent>,
+
+    /// Per-channel queues ordering emitted events
+    event_queues: Mutex<HashMap<ChannelId, Arc<Mutex<EventQueue>>>>,
+
+    /// Per-channel locks serializing processing of the store's inbox
+    inbox_locks: Mutex<HashMap<ChannelId, Arc<Mutex<()>>>>,
+
+    /// Channels in broadcast mode, with their queued membership changes
+    broadcast_channels: Mutex<HashMap<ChannelId, BroadcastState>>,
+
+    /// Optional mailbox for broadcast channel fan-out
+    mailbox: Option<Arc<dyn BroadcastMailbox>>,
+
+    /// Commits created per channel by this manager
+    commit_counts: RwLock<HashMap<ChannelId, u64>>,
+
+    /// Webhook leaves hosted by this node, and their rate limits
+    webhooks: Mutex<WebhookHost>,
+
+    /// Optional DHT directory of keyed channels
+    directory: Option<Arc<ChannelDirectory>>,
+
+    /// Optional DHT directory of one-time key packages
+    key_packages: Option<Arc<KeyPackageDirectory>>,
+
+    /// Optional DHT directory of public channels' GroupInfo
+    public_groups: Option<Arc<PublicGroupDirectory>>,
+
+    /// Optional DHT directory of ratchet trees left out of invites
+    trees: Option<Arc<TreeDirectory>>,
+
+    /// Optional DHT mailbox for Welcomes and commits
+    deliveries: Option<Arc<DeliveryMailbox>>,
+
+    /// Identity keys of users resolved through the key package directory
+    mailbox_contacts: RwLock<HashMap<UserId, Vec<u8>>>,
+
+    /// Optional DHT feed of channel messages
+    feed: Option<Arc<ChannelFeed>>,
+
+    /// Optional publisher writing feed entries for us in anonymity mode
+    feed_publisher: Option<Arc<dyn FeedPublisher>>,
+
+    /// Next feed sequence to read, per channel
+    feed_cursors: Mutex<HashMap<ChannelId, u64>>,
+
+    /// Limits for the per-channel inbound workers
+    supervisor_policy: SupervisorPolicy,
+
+    /// Per-channel inbound workers, started with the first processor
+    supervisor: OnceLock<Arc<ChannelSupervisor>>,
+
+    /// When this node last rotated its own leaf key in each channel
+    key_rotations: Mutex<HashMap<ChannelId, Instant>>,
+
+    /// Edits and deletions received before the message they refer to
+    pending_changes: Mutex<PendingChanges>,
+
+    /// Reactions received before the message they refer to
+    parked_reactions: Mutex<ParkedReactions>,
+
+    /// When we last sent a typing indicator to each channel
+    typing_sent: Mutex<HashMap<ChannelId, Instant>>,
+
+    /// When each member was last heard from, per channel
+    presence: RwLock<HashMap<ChannelId, HashMap<UserId, Instant>>>,
+
+    /// Cap on the outbox's total ciphertext size; oldest messages go first
+    outbox_max_bytes: usize,
+
+    /// Held while deciding between sending and queueing, and while flushing
+    /// the outbox, so queued messages can't be overtaken
+    outbox_flush: Mutex<()>,
+
+    /// Acknowledgements of received messages waiting to be sent
+    pending_acks: Mutex<PendingAcks>,
+
+    /// Least time between two acknowledgement bundles sent to a channel
+    ack_interval: Duration,
 }
 
 /// Simple identity holder (will integrate with core_identity later)
@@ -128,6 +296,7 @@ impl ChannelManager {
 
         // Initialize identity scoper with global identity
         let identity_scoper = Arc::new(IdentityScoper::new(identity.clone()));
+        let preflight = StoragePreflight::new(config.store.free_space_margin);
 
         Self {
             mls_service,
@@ -137,8 +306,39 @@ impl ChannelManager {
             config,
             network: None,
             peer_discovery: None,
-            reactions: Arc::new(RwLock::new(HashMap::new())),
             messages: Arc::new(RwLock::new(HashMap::new())),
+            transformers: TransformerChain::new(),
+            attachments: None,
+            attachment_store: None,
+            preflight,
+            incoming: broadcast::channel(INCOMING_BROADCAST_CAPACITY).0,
+            events: broadcast::channel(INCOMING_BROADCAST_CAPACITY).0,
+            event_queues: Mutex::new(HashMap::new()),
+            inbox_locks: Mutex::new(HashMap::new()),
+            broadcast_channels: Mutex::new(HashMap::new()),
+            mailbox: None,
+            commit_counts: RwLock::new(HashMap::new()),
+            webhooks: Mutex::new(WebhookHost::default()),
+            directory: None,
+            key_packages: None,
+            public_groups: None,
+            trees: None,
+            deliveries: None,
+            mailbox_contacts: RwLock::new(HashMap::new()),
+            feed: None,
+            feed_publisher: None,
+            feed_cursors: Mutex::new(HashMap::new()),
+            supervisor_policy: SupervisorPolicy::default(),
+            supervisor: OnceLock::new(),
+            key_rotations: Mutex::new(HashMap::new()),
+            pending_changes: Mutex::new(PendingChanges::default()),
+            parked_reactions: Mutex::new(ParkedReactions::default()),
+            typing_sent: Mutex::new(HashMap::new()),
+            presence: RwLock::new(HashMap::new()),
+            outbox_max_bytes: DEFAULT_OUTBOX_MAX_BYTES,
+            outbox_flush: Mutex::new(()),
+            pending_acks: Mutex::new(PendingAcks::default()),
+            ack_interval: ACK_INTERVAL,
         }
     }
 
@@ -154,6 +354,23 @@ impl ChannelManager {
         self
     }
 
+    /// Cap the outbox's total ciphertext size (16 MiB by default)
+    ///
+    /// When a queued message pushes the outbox past the cap, the oldest
+    /// queued messages are dropped and reported with
+    /// [`ChannelEventKind::MessageEvicted`].
+    pub fn with_outbox_limit(mut self, max_bytes: usize) -> Self {
+        self.outbox_max_bytes = max_bytes;
+        self
+    }
+
+    /// Set the least time between two acknowledgement bundles sent to a
+    /// channel ([`ACK_INTERVAL`] by default)
+    pub fn with_ack_interval(mut self, interval: Duration) -> Self {
+        self.ack_interval = interval;
+        self
+    }
+
     /// Attach a peer discovery service
     ///
     /// This enables automatic discovery of peer IDs for users
@@ -166,15 +383,262 @@ impl ChannelManager {
         self
     }
 
+    /// Register a display-time content transformer
+    ///
+    /// Transformers run in registration order when messages are rendered
+    /// and never modify stored messages.
+    ///
+    /// # Arguments
+    /// * `transformer` - Transformer to append to the chain
+    pub fn with_content_transformer(mut self, transformer: Arc<dyn ContentTransformer>) -> Self {
+        info!(transformer = transformer.name(), "Registering content transformer");
+        self.transformers.push(transformer);
+        self
+    }
+
+    /// Attach an attachment blob cache
+    ///
+    /// # Arguments
+    /// * `cache` - Quota-aware cache for attachment blobs
+    pub fn with_attachment_cache(mut self, cache: Arc<AttachmentCache>) -> Self {
+        info!(
+            quota_bytes = cache.quota_bytes(),
+            "Attaching attachment cache to ChannelManager"
+        );
+        self.attachments = Some(cache);
+        self
+    }
+
+    /// Attach a DHT store for attachment chunks
+    ///
+    /// Enables [`send_attachment`](Self::send_attachment) and
+    /// [`download_attachment`](Self::download_attachment).
+    ///
+    /// # Arguments
+    /// * `store` - Attachment store (shared with other nodes via the DHT)
+    pub fn with_attachment_store(mut self, store: Arc<AttachmentStore>) -> Self {
+        info!("Attaching attachment store to ChannelManager");
+        self.attachment_store = Some(store);
+        self
+    }
+
+    /// Replace the free disk space check run before joins
+    ///
+    /// # Arguments
+    /// * `preflight` - Check to run (e.g. with a different safety margin)
+    pub fn with_storage_preflight(mut self, preflight: StoragePreflight) -> Self {
+        info!(
+            safety_margin = preflight.safety_margin(),
+            "Attaching storage preflight to ChannelManager"
+        );
+        self.preflight = preflight;
+        self
+    }
+
+    /// Attach a mailbox for broadcast channel fan-out
+    ///
+    /// Without one, broadcast channels still batch membership changes but
+    /// publishers' messages go over the network layer as usual.
+    ///
+    /// # Arguments
+    /// * `mailbox` - Store-and-forward mailbox (e.g. a `DhtMailbox`)
+    pub fn with_broadcast_mailbox(mut self, mailbox: Arc<dyn BroadcastMailbox>) -> Self {
+        info!("Attaching broadcast mailbox to ChannelManager");
+        self.mailbox = Some(mailbox);
+        self
+    }
+
+    /// Attach a DHT directory for keyed channels
+    ///
+    /// Without one, [`create_channel_with_key`](Self::create_channel_with_key)
+    /// only finds channels held in the local store, and a deleted keyed
+    /// channel can be recreated straight away.
+    ///
+    /// # Arguments
+    /// * `directory` - Channel directory (shared with other nodes via the DHT)
+    pub fn with_channel_directory(mut self, directory: Arc<ChannelDirectory>) -> Self {
+        info!("Attaching channel directory to ChannelManager");
+        self.directory = Some(directory);
+        self
+    }
+
+    /// Attach a DHT directory of one-time key packages
+    ///
+    /// Enables [`publish_key_packages`](Self::publish_key_packages) (if the
+    /// directory has a signer) and inviting users by ID instead of by key
+    /// package.
+    ///
+    /// # Arguments
+    /// * `directory` - Key package directory (shared with other nodes via the DHT)
+    pub fn with_key_package_directory(mut self, directory: Arc<KeyPackageDirectory>) -> Self {
+        info!("Attaching key package directory to ChannelManager");
+        self.key_packages = Some(directory);
+        self
+    }
+
+    /// Attach a DHT directory of public channels' GroupInfo
+    ///
+    /// Public channels then publish their GroupInfo after every commit, and
+    /// [`join_public_channel`](Self::join_public_channel) can join them
+    /// without an invite.
+    ///
+    /// # Arguments
+    /// * `directory` - Public group directory (shared with other nodes via the DHT)
+    pub fn with_public_group_directory(mut self, directory: Arc<PublicGroupDirectory>) -> Self {
+        info!("Attaching public group directory to ChannelManager");
+        self.public_groups = Some(directory);
+        self
+    }
+
+    /// Attach a DHT directory for ratchet trees
+    ///
+    /// Needed to invite with `external_tree` set in the MLS configuration,
+    /// and to join from invites created that way.
+    ///
+    /// # Arguments
+    /// * `directory` - Tree directory (shared with other nodes via the DHT)
+    pub fn with_tree_directory(mut self, directory: Arc<TreeDirectory>) -> Self {
+        info!("Attaching ratchet tree directory to ChannelManager");
+        self.trees = Some(directory);
+        self
+    }
+
+    /// Attach a DHT mailbox for Welcomes and commits
+    ///
+    /// Invitees resolved through the key package directory then get their
+    /// Welcome, and later commits for the channel, in their mailbox; our
+    /// own is read by [`poll_mailbox`](Self::poll_mailbox).
+    ///
+    /// # Arguments
+    /// * `mailbox` - Delivery mailbox holding our identity key
+    pub fn with_delivery_mailbox(mut self, mailbox: Arc<DeliveryMailbox>) -> Self {
+        info!("Attaching delivery mailbox to ChannelManager");
+        self.deliveries = Some(mailbox);
+        self
+    }
+
+    /// Attach a DHT feed for channel messages
+    ///
+    /// Messages sent to non-broadcast channels are then also appended to the
+    /// channel's feed, and members catch up with
+    /// [`sync_channel`](Self::sync_channel).
+    ///
+    /// # Arguments
+    /// * `feed` - Channel feed (shared with other nodes via the DHT)
+    pub fn with_channel_feed(mut self, feed: Arc<ChannelFeed>) -> Self {
+        info!("Attaching channel feed to ChannelManager");
+        self.feed = Some(feed);
+        self
+    }
+
+    /// Attach a publisher for feed appends made in anonymity mode
+    ///
+    /// While [`FeatureFlags::anonymity_mode`](crate::config::FeatureFlags) is
+    /// on, sent messages are appended through the publisher (e.g. a
+    /// [`CircuitFeedPublisher`](super::circuit_feed::CircuitFeedPublisher))
+    /// instead of directly to the channel feed.
+    ///
+    /// # Arguments
+    /// * `publisher` - Writes feed entries without revealing this node
+    pub fn with_feed_publisher(mut self, publisher: Arc<dyn FeedPublisher>) -> Self {
+        info!("Attaching feed publisher to ChannelManager");
+        self.feed_publisher = Some(publisher);
+        self
+    }
+
+    /// Set limits for the per-channel inbound workers
+    ///
+    /// # Arguments
+    /// * `policy` - Mailbox capacity and quarantine threshold
+    pub fn with_supervisor_policy(mut self, policy: SupervisorPolicy) -> Self {
+        info!("Attaching supervisor policy to ChannelManager");
+        self.supervisor_policy = policy;
+        self
+    }
+
+    /// Attachment blob cache, if configured
+    pub fn attachment_cache(&self) -> Option<&Arc<AttachmentCache>> {
+        self.attachments.as_ref()
+    }
+
     /// Check if network layer is enabled
     pub fn is_network_enabled(&self) -> bool {
         self.network.is_some()
     }
 
+    /// Inbound supervisor, started on first use
+    fn supervisor(self: &Arc<Self>) -> &Arc<ChannelSupervisor> {
+        self.supervisor.get_or_init(|| {
+            let handler = Arc::new(SupervisedInbound(Arc::downgrade(self)));
+            Arc::new(ChannelSupervisor::new(self.supervisor_policy.clone(), handler))
+        })
+    }
+
+    /// Subscribe to security events such as channel quarantines
+    pub fn subscribe_security_events(self: &Arc<Self>) -> broadcast::Receiver<SecurityEvent> {
+        self.supervisor().subscribe()
+    }
+
+    /// Warn that another device sent from our own leaf in a channel
+    ///
+    /// The network never hands us our own messages, so this means a copy of
+    /// the identity is active elsewhere; both copies now reuse the same
+    /// keys.
+    fn report_identity_in_use_elsewhere(&self, channel_id: &ChannelId, epoch: u64) {
+        warn!(
+            channel_id = %channel_id,
+            epoch,
+            "Received a message from our own leaf; this identity is active on another device"
+        );
+        record_counter("mvp.identity.in_use_elsewhere", 1);
+        if let Some(supervisor) = self.supervisor.get() {
+            let channel_id = channel_id.clone();
+            supervisor.report(SecurityEvent::IdentityInUseElsewhere { channel_id, epoch });
+        }
+    }
+
+    /// Whether a channel is quarantined after repeated worker panics
+    pub async fn is_quarantined(&self, channel_id: &ChannelId) -> bool {
+        match self.supervisor.get() {
+            Some(supervisor) => supervisor.is_quarantined(channel_id).await,
+            None => false,
+        }
+    }
+
+    /// Fail if a channel is quarantined and so read-only
+    async fn ensure_writable(&self, channel_id: &ChannelId) -> MvpResult<()> {
+        if self.is_quarantined(channel_id).await {
+            return Err(MvpError::ChannelQuarantined(channel_id.0.clone()));
+        }
+        Ok(())
+    }
+
+    /// Lift a channel's quarantine and catch up on messages parked meanwhile
+    ///
+    /// # Returns
+    ///
+    /// Messages decrypted from the catch-up
+    pub async fn lift_quarantine(&self, channel_id: &ChannelId) -> MvpResult<Vec<ChatMessage>> {
+        let lifted = match self.supervisor.get() {
+            Some(supervisor) => supervisor.lift_quarantine(channel_id).await,
+            None => false,
+        };
+        if !lifted {
+            return Err(MvpError::InvalidOperation(format!(
+                "Channel {} is not quarantined",
+                channel_id
+            )));
+        }
+
+        info!(channel_id = %channel_id, "Lifted channel quarantine");
+        self.process_pending(channel_id).await
+    }
+
     /// Start processing incoming commits from the network
     ///
     /// This spawns a background task that listens for commits from the network
-    /// and processes them to keep group state synchronized.
+    /// and hands each to its channel's supervised worker, so a stuck or
+    /// crashing channel doesn't hold up commits for the others.
     ///
     /// # Arguments
     /// * `mut commits_rx` - Receiver for incoming commits from NetworkLayer
@@ -183,37 +647,93 @@ impl ChannelManager {
     /// JoinHandle for the background task
     pub fn spawn_commit_processor(
         self: Arc<Self>,
-        mut commits_rx: tokio::sync::mpsc::Receiver<crate::core_mvp::network::IncomingCommit>,
+        mut commits_rx: tokio::sync::mpsc::Receiver<IncomingCommit>,
     ) -> tokio::task::JoinHandle<()> {
+        let supervisor = self.supervisor().clone();
         tokio::spawn(async move {
             info!("Started commit processor task");
 
             while let Some(incoming_commit) = commits_rx.recv().await {
-                debug!(
+                supervisor.dispatch(InboundWork::Commit(incoming_commit)).await;
+            }
+
+            warn!("Commit processor task ended (channel closed)");
+        })
+    }
+
+    /// Process one incoming commit or invite acceptance
+    async fn handle_incoming_commit(&self, incoming_commit: IncomingCommit) {
+        if let Some(acceptance) = incoming_commit.accepted_invite {
+            if let Err(e) =
+                self.process_invite_acceptance(&incoming_commit.channel_id, &acceptance).await
+            {
+                warn!(
                     channel_id = %incoming_commit.channel_id,
-                    size = incoming_commit.commit_data.len(),
-                    peer_id = ?incoming_commit.sender_peer_id,
-                    "Processing incoming commit"
+                    invite_id = %acceptance.invite_id,
+                    error = %e,
+                    "Failed to process invite acceptance"
                 );
+            }
+            return;
+        }
 
-                match self.process_commit(&incoming_commit.commit_data).await {
-                    Ok(()) => {
-                        info!(
-                            channel_id = %incoming_commit.channel_id,
-                            "Successfully processed incoming commit"
-                        );
-                    }
-                    Err(e) => {
-                        warn!(
-                            channel_id = %incoming_commit.channel_id,
-                            error = %e,
-                            "Failed to process incoming commit"
-                        );
-                    }
+        debug!(
+            channel_id = %incoming_commit.channel_id,
+            size = incoming_commit.commit_data.len(),
+            peer_id = ?incoming_commit.sender_peer_id,
+            "Processing incoming commit"
+        );
+
+        match self.process_commit(&incoming_commit.commit_data).await {
+            Ok(()) => {
+                info!(
+                    channel_id = %incoming_commit.channel_id,
+                    "Successfully processed incoming commit"
+                );
+
+                // Messages from the new epoch may have arrived first
+                if let Err(e) = self.process_pending(&incoming_commit.channel_id).await {
+                    warn!(
+                        channel_id = %incoming_commit.channel_id,
+                        error = %e,
+                        "Failed to process pending messages after commit"
+                    );
                 }
             }
+            Err(e) => {
+                warn!(
+                    channel_id = %incoming_commit.channel_id,
+                    error = %e,
+                    "Failed to process incoming commit"
+                );
+            }
+        }
+    }
+
+    /// Start processing incoming messages from the network
+    ///
+    /// This spawns a background task that hands each message to its
+    /// channel's supervised worker, which decrypts it and publishes it to
+    /// [`subscribe_messages`](Self::subscribe_messages) subscribers.
+    ///
+    /// # Arguments
+    /// * `mut messages_rx` - Receiver for incoming messages from NetworkLayer
+    ///
+    /// # Returns
+    /// JoinHandle for the background task
+    pub fn spawn_message_processor(
+        self: Arc<Self>,
+        mut messages_rx: tokio::sync::mpsc::Receiver<IncomingMessage>,
+    ) -> tokio::task::JoinHandle<()> {
+        let supervisor = self.supervisor().clone();
+        tokio::spawn(async move {
+            info!("Started message processor task");
+
+            while let Some(incoming) = messages_rx.recv().await {
+                supervisor.dispatch(InboundWork::Message(incoming)).await;
+            }
 
-            warn!("Commit processor task ended (channel closed)");
+            warn!("Message processor task ended (channel closed)");
         })
     }
 
@@ -238,6 +758,11 @@ impl ChannelManager {
         &self.identity
     }
 
+    /// Get the local store channel state is kept in
+    pub fn store(&self) -> &Arc<LocalStore> {
+        &self.store
+    }
+
     /// Generate a key package for joining channels
     ///
     /// Creates a KeyPackageBundle with cryptographic material and stores it
@@ -279,57 +804,430 @@ impl ChannelManager {
         Ok(key_package)
     }
 
-    /// Create a new channel
+    /// Generate one-time key packages and publish them to the directory
     ///
-    /// This performs three operations:
-    /// 1. Creates an MLS group for encryption
-    /// 2. Creates a CRDT Channel model for metadata
-    /// 3. (If public) Publishes to DHT for discovery
+    /// Other users can then invite us by user ID (see
+    /// [`fetch_key_package`](Self::fetch_key_package)).
     ///
     /// # Arguments
     ///
-    /// * `name` - Human-readable channel name
-    /// * `is_public` - Whether channel is publicly discoverable
+    /// * `count` - Number of key packages to publish
     ///
     /// # Returns
     ///
-    /// The created channel's ID
+    /// How many of our key packages the directory now holds
     ///
-    /// # Example
+    /// # Errors
     ///
-    /// ```ignore
-    /// let channel_id = manager.create_channel("general", false).await?;
-    /// ```
-    pub async fn create_channel(&self, name: String, is_public: bool) -> MvpResult<ChannelId> {
-        info!(
-            name = %name,
-            is_public = is_public,
-            user_id = %self.identity.user_id,
-            "Creating channel"
-        );
+    /// Returns `Config` if no key package directory with a signer is attached.
+    pub async fn publish_key_packages(&self, count: usize) -> MvpResult<usize> {
+        let directory = self.key_package_directory()?;
 
-        // Generate unique IDs
-        let channel_id = ChannelId::generate();
-        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let mut key_packages = Vec::with_capacity(count);
+        for _ in 0..count {
+            key_packages.push(self.generate_key_package().await?);
+        }
+        let available = directory.publish(&self.identity.user_id, key_packages)?;
 
-        // Get per-channel identity for privacy (OPTIONAL - currently disabled to maintain compatibility)
-        // TODO: Enable per-channel identities once full integration is complete
-        // use crate::core_mvp::identity_scoping::ChannelIdentityMode;
-        // let channel_identity = self.identity_scoper
-        //     .get_or_create_channel_identity(
-        //         &channel_id.0,
-        //         ChannelIdentityMode::PerChannel
-        //     )
-        //     .await;
+        record_counter("mvp.key_packages.published", count as u64);
+        info!(published = count, available, "Published key packages");
+        Ok(available)
+    }
 
-        // For now, use global identity for both MLS and CRDT
-        let channel_identity = self.identity.clone();
+    /// Publish key packages if the directory is running low on ours
+    ///
+    /// # Returns
+    ///
+    /// How many key packages were published (zero if none were needed)
+    pub async fn replenish_key_packages(&self) -> MvpResult<usize> {
+        let shortfall = self.key_package_directory()?.shortfall(&self.identity.user_id)?;
+        if shortfall > 0 {
+            debug!(shortfall, "Replenishing key packages");
+            self.publish_key_packages(shortfall).await?;
+        }
+        Ok(shortfall)
+    }
 
-        debug!(
-            channel_id = %channel_id,
-            channel_user_id = %channel_identity.user_id,
-            "Using identity for channel"
-        );
+    /// Take a user's key package from the directory
+    ///
+    /// The key package is consumed, so no two invites use the same one.
+    /// Its identity signature is checked, and it must be a valid MLS key
+    /// package issued for `user_id`; unusable ones are skipped.
+    ///
+    /// # Errors
+    ///
+    /// - `NoKeyPackages` if the user has no usable key packages left
+    /// - `Config` if no key package directory is attached
+    pub async fn fetch_key_package(&self, user_id: &UserId) -> MvpResult<Vec<u8>> {
+        Ok(self.take_key_package(user_id).await?.key_package)
+    }
+
+    async fn take_key_package(&self, user_id: &UserId) -> MvpResult<FetchedKeyPackage> {
+        let directory = self.key_package_directory()?;
+        loop {
+            let fetched = directory.take(user_id)?;
+            match self.mls_service.key_package_identity(&fetched.key_package) {
+                Ok(identity) if identity == user_id.0.as_bytes() => {
+                    record_counter("mvp.key_packages.fetched", 1);
+                    return Ok(fetched);
+                }
+                Ok(_) => warn!(user_id = %user_id, "Skipping key package issued for another user"),
+                Err(e) => warn!(user_id = %user_id, error = %e, "Skipping unusable key package"),
+            }
+        }
+    }
+
+    fn key_package_directory(&self) -> MvpResult<&KeyPackageDirectory> {
+        self.key_packages
+            .as_deref()
+            .ok_or_else(|| MvpError::Config("No key package directory configured".to_string()))
+    }
+
+    /// Resolve an invitee to the key package to add
+    ///
+    /// Users resolved through the directory also yield their identity key.
+    async fn invitee_key_package(
+        &self,
+        invitee: Invitee,
+    ) -> MvpResult<(Vec<u8>, Option<(UserId, Vec<u8>)>)> {
+        match invitee {
+            Invitee::KeyPackage(key_package) => Ok((key_package, None)),
+            Invitee::User(user_id) => {
+                let fetched = self.take_key_package(&user_id).await?;
+                Ok((fetched.key_package, Some((user_id, fetched.identity_public_key))))
+            }
+        }
+    }
+
+    /// Fetch and process Welcomes and commits left in our DHT mailbox
+    ///
+    /// Processed deliveries are removed, so polling again is a no-op.
+    /// Ones that fail to process stay until they expire; ones that can't
+    /// be opened are dropped. Without a delivery mailbox there is nothing
+    /// to poll.
+    ///
+    /// # Returns
+    ///
+    /// The channels joined from Welcomes
+    pub async fn poll_mailbox(&self) -> MvpResult<Vec<ChannelId>> {
+        let Some(ref deliveries) = self.deliveries else {
+            return Ok(Vec::new());
+        };
+
+        let mut joined = Vec::new();
+        for (seq, delivery) in deliveries.pending() {
+            let processed = match delivery {
+                Ok(Delivery::Welcome(invite)) => {
+                    self.join_channel(&invite).await.map(|channel_id| joined.push(channel_id))
+                }
+                Ok(Delivery::Commit { channel_id, commit }) => {
+                    debug!(channel_id = %channel_id, "Processing commit from mailbox");
+                    self.process_commit(&commit).await
+                }
+                Err(e) => {
+                    warn!(seq, error = %e, "Dropping unreadable mailbox delivery");
+                    deliveries.remove(seq);
+                    continue;
+                }
+            };
+            match processed {
+                Ok(()) => {
+                    deliveries.remove(seq);
+                    record_counter("mvp.mailbox.processed", 1);
+                }
+                Err(e) => warn!(seq, error = %e, "Failed to process mailbox delivery"),
+            }
+        }
+
+        if !joined.is_empty() {
+            info!(channels = joined.len(), "Joined channels from mailbox");
+        }
+        Ok(joined)
+    }
+
+    /// Leave a delivery in a user's mailbox, if we have one
+    fn deliver_to_mailbox(&self, identity_public_key: &[u8], delivery: &Delivery) {
+        if let Some(ref deliveries) = self.deliveries {
+            if let Err(e) = deliveries.deliver(identity_public_key, delivery) {
+                warn!(error = %e, "Failed to leave delivery in mailbox");
+            }
+        }
+    }
+
+    /// Leave a commit in the mailboxes of the channel's members we know
+    async fn deliver_commit(&self, channel_id: &ChannelId, commit: &[u8]) {
+        if self.deliveries.is_none() {
+            return;
+        }
+        let contacts = self.mailbox_contacts.read().await.clone();
+        if contacts.is_empty() {
+            return;
+        }
+        let Ok(members) = self.get_channel_members(channel_id).await else {
+            return;
+        };
+
+        let delivery = Delivery::Commit { channel_id: channel_id.clone(), commit: commit.to_vec() };
+        for (user_id, identity_public_key) in contacts {
+            let is_member = members.iter().any(|member| member == user_id.0.as_bytes());
+            if is_member && user_id != self.identity.user_id {
+                self.deliver_to_mailbox(&identity_public_key, &delivery);
+            }
+        }
+    }
+
+    /// Create a new channel
+    ///
+    /// This performs three operations:
+    /// 1. Creates an MLS group for encryption
+    /// 2. Creates a CRDT Channel model for metadata
+    /// 3. (If public) Publishes its GroupInfo to the DHT so anyone can join
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Human-readable channel name
+    /// * `is_public` - Whether anyone can join without an invite (see
+    ///   [`join_public_channel`](Self::join_public_channel))
+    ///
+    /// # Returns
+    ///
+    /// The created channel's ID
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let channel_id = manager.create_channel("general", false).await?;
+    /// ```
+    pub async fn create_channel(&self, name: String, is_public: bool) -> MvpResult<ChannelId> {
+        info!(
+            name = %name,
+            is_public = is_public,
+            user_id = %self.identity.user_id,
+            "Creating channel"
+        );
+
+        self.create_channel_with_id(ChannelId::generate(), name, is_public).await
+    }
+
+    /// Create a channel under an ID chosen by the caller
+    ///
+    /// For channels whose ID is allocated elsewhere, such as a Space's
+    /// channel directory, so both refer to the channel the same way.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ChannelExists` if this user already has a channel with the ID
+    pub async fn create_channel_as(
+        &self,
+        channel_id: ChannelId,
+        name: String,
+        is_public: bool,
+    ) -> MvpResult<ChannelId> {
+        let existing = self
+            .store
+            .get_channel(&channel_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?;
+        if existing.is_some() {
+            return Err(MvpError::ChannelExists(channel_id.to_string()));
+        }
+
+        info!(
+            channel_id = %channel_id,
+            name = %name,
+            is_public = is_public,
+            user_id = %self.identity.user_id,
+            "Creating channel"
+        );
+
+        self.create_channel_with_id(channel_id, name, is_public).await
+    }
+
+    /// Create a channel whose ID is derived from a namespace key
+    ///
+    /// The ID (and MLS group ID) is derived from this user's identity and
+    /// `namespace_key`, so provisioning can be re-run safely: if the channel
+    /// already exists, locally or as a listing in the channel directory, its
+    /// ID is returned and nothing is created. Another user with the same
+    /// key gets a different channel.
+    ///
+    /// After the creator leaves a keyed channel, the directory keeps its ID
+    /// reserved until the tombstone expires; until then this fails with
+    /// [`MvpError::ChannelExists`].
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Human-readable channel name (ignored if the channel exists)
+    /// * `namespace_key` - Caller-chosen key, e.g. `"project:1234"`
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let first = manager.create_channel_with_key("ops".into(), "project:42").await?;
+    /// let again = manager.create_channel_with_key("ops".into(), "project:42").await?;
+    /// assert_eq!(first, again);
+    /// ```
+    pub async fn create_channel_with_key(
+        &self,
+        name: String,
+        namespace_key: &str,
+    ) -> MvpResult<ChannelId> {
+        let channel_id = ChannelId::derive(&self.identity.user_id, namespace_key);
+        info!(
+            name = %name,
+            channel_id = %channel_id,
+            user_id = %self.identity.user_id,
+            "Creating keyed channel"
+        );
+
+        if self
+            .store
+            .get_channel(&channel_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .is_some()
+        {
+            debug!(channel_id = %channel_id, "Keyed channel already exists locally");
+            return Ok(channel_id);
+        }
+
+        if let Some(ref directory) = self.directory {
+            match directory.lookup(&channel_id)? {
+                Some(listing) if listing.is_tombstone() => {
+                    return Err(MvpError::ChannelExists(format!(
+                        "{} was deleted and can't be recreated until its tombstone expires",
+                        channel_id
+                    )));
+                }
+                Some(_) => {
+                    // Another of this user's nodes created it; rejoining needs an invite
+                    warn!(
+                        channel_id = %channel_id,
+                        "Keyed channel is listed in the directory but not held locally"
+                    );
+                    return Ok(channel_id);
+                }
+                None => {}
+            }
+        }
+
+        self.create_channel_with_id(channel_id.clone(), name.clone(), false).await?;
+
+        if let Some(ref directory) = self.directory {
+            let listing = ChannelListing {
+                channel_id: channel_id.clone(),
+                name,
+                creator: self.identity.user_id.clone(),
+                created_at: Timestamp::now(),
+                deleted_at: None,
+            };
+            if let Err(e) = directory.publish(&listing) {
+                warn!(error = %e, "Failed to list keyed channel in directory");
+            }
+        }
+
+        Ok(channel_id)
+    }
+
+    /// Create a channel whose invitees must also know a pre-shared key
+    ///
+    /// Every invite to the channel commits the PSK (RFC 9420 external PSK)
+    /// alongside the new member, so the Welcome is useless without the
+    /// secret: the joiner can't derive the epoch secrets from it alone. The
+    /// secret has to reach invitees separately; they join with
+    /// [`join_with_psk`](Self::join_with_psk).
+    ///
+    /// Linked devices and webhooks are added without the PSK: they are
+    /// provisioned by a node that already holds the channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Human-readable channel name
+    /// * `psk_id` - ID the PSK is known by, shared with invitees
+    /// * `psk` - The shared secret
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let channel_id = manager.create_channel_with_psk("ops".into(), b"ops-2026", &secret).await?;
+    /// ```
+    pub async fn create_channel_with_psk(
+        &self,
+        name: String,
+        psk_id: &[u8],
+        psk: &[u8],
+    ) -> MvpResult<ChannelId> {
+        if psk_id.is_empty() {
+            return Err(MvpError::Config("PSK ID must not be empty".to_string()));
+        }
+        info!(
+            name = %name,
+            user_id = %self.identity.user_id,
+            "Creating channel with pre-shared key"
+        );
+
+        self.mls_service.store_psk(psk_id, psk)?;
+        let channel_id = self.create_channel_with_id(ChannelId::generate(), name, false).await?;
+        self.require_psk(&channel_id, psk_id)?;
+
+        Ok(channel_id)
+    }
+
+    /// Record that invites to a channel must commit an external PSK
+    fn require_psk(&self, channel_id: &ChannelId, psk_id: &[u8]) -> MvpResult<()> {
+        let mut channel = self
+            .store
+            .get_channel(channel_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .ok_or_else(|| MvpError::ChannelNotFound(channel_id.to_string()))?;
+        channel.set_required_psk(psk_id.to_vec(), &self.identity.node_id);
+        self.store.store_channel(&channel).map_err(|e| MvpError::Store(e.to_string()))
+    }
+
+    /// Add invitees to a channel's MLS group
+    ///
+    /// Channels that require a PSK get it committed with the adds.
+    async fn add_invitees(
+        &self,
+        channel: &Channel,
+        key_packages: Vec<Vec<u8>>,
+    ) -> MvpResult<(Vec<u8>, Vec<u8>, Vec<u8>)> {
+        // Members reject adds from anyone else; fail before forking ourselves
+        self.require_commit_permission(&channel.id, Permission::Invite, "invite")
+            .await?;
+
+        let group_id = GroupId::new(channel.id.0.as_bytes().to_vec());
+        Ok(self
+            .mls_service
+            .add_members_with_psk(&group_id, key_packages, channel.required_psk())
+            .await?)
+    }
+
+    /// Create the MLS group and CRDT channel for a chosen channel ID
+    async fn create_channel_with_id(
+        &self,
+        channel_id: ChannelId,
+        name: String,
+        is_public: bool,
+    ) -> MvpResult<ChannelId> {
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+
+        // Get per-channel identity for privacy (OPTIONAL - currently disabled to maintain compatibility)
+        // TODO: Enable per-channel identities once full integration is complete
+        // use crate::core_mvp::identity_scoping::ChannelIdentityMode;
+        // let channel_identity = self.identity_scoper
+        //     .get_or_create_channel_identity(
+        //         &channel_id.0,
+        //         ChannelIdentityMode::PerChannel
+        //     )
+        //     .await;
+
+        // For now, use global identity for both MLS and CRDT
+        let channel_identity = self.identity.clone();
+
+        debug!(
+            channel_id = %channel_id,
+            channel_user_id = %channel_identity.user_id,
+            "Using identity for channel"
+        );
 
         // Step 1: Create MLS group
         debug!(group_id = ?group_id, "Creating MLS group");
@@ -349,7 +1247,7 @@ impl ChannelManager {
 
         // Step 2: Create CRDT channel model
         debug!(channel_id = %channel_id, "Creating CRDT channel");
-        let channel = Channel::new(
+        let mut channel = Channel::new(
             channel_id.clone(),
             name.clone(),
             ChannelType::Text,
@@ -357,17 +1255,21 @@ impl ChannelManager {
             Timestamp::now(),
             self.identity.node_id.clone(),
         );
+        if is_public {
+            channel.set_public(true, &self.identity.node_id);
+        }
+        channel.set_padding(self.mls_service.config().padding.clone(), &self.identity.node_id);
 
         self.store.store_channel(&channel).map_err(|e| {
             warn!(error = ?e, "Failed to store channel");
             MvpError::Store(e.to_string())
         })?;
+        self.sync_member_devices(&channel_id).await;
 
-        // Step 3: Publish to DHT (if public)
+        // Step 3: Publish GroupInfo to DHT (if public)
         if is_public {
             debug!(channel_id = %channel_id, "Publishing channel to DHT");
-            // TODO: Implement DHT publication
-            // For MVP, we'll skip this and use local discovery
+            self.publish_group_info(&channel_id).await;
         }
 
         info!(
@@ -390,16 +1292,25 @@ impl ChannelManager {
     /// Create an invite for a new member
     ///
     /// This generates an MLS Welcome message and exports the ratchet tree.
+    /// The key package must come from the invitee (see
+    /// [`generate_key_package`](Self::generate_key_package)); the Welcome is
+    /// encrypted to it, so only the device holding its private keys can join.
     ///
     /// # Arguments
     ///
     /// * `channel_id` - Target channel
-    /// * `key_package` - Invitee's serialized key package
+    /// * `invitee` - Invitee's serialized key package, or their user ID to
+    ///   take a key package from the directory
     ///
     /// # Returns
     ///
     /// An InviteToken containing the Welcome message and ratchet tree
     ///
+    /// # Errors
+    ///
+    /// Returns `InvalidKeyPackage` if the key package is malformed or expired,
+    /// or `NoKeyPackages` if the directory has none left for the invitee.
+    ///
     /// # Example
     ///
     /// ```ignore
@@ -408,7 +1319,38 @@ impl ChannelManager {
     pub async fn create_invite(
         &self,
         channel_id: &ChannelId,
-        key_package: Vec<u8>,
+        invitee: impl Into<Invitee>,
+    ) -> MvpResult<(InviteToken, Option<Vec<u8>>)> {
+        self.create_invite_with_options(channel_id, invitee, InviteOptions::default())
+            .await
+    }
+
+    /// Create an invite with an expiry time and use limit
+    ///
+    /// The invite is recorded in the channel so it can be revoked with
+    /// [`revoke_invite`](Self::revoke_invite) and checked when the invitee's
+    /// acceptance arrives (see
+    /// [`process_invite_acceptance`](Self::process_invite_acceptance)).
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Target channel
+    /// * `invitee` - Invitee's key package or user ID (see
+    ///   [`create_invite`](Self::create_invite))
+    /// * `options` - TTL and maximum number of uses
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let options = InviteOptions::default().with_ttl(Duration::from_secs(24 * 3600));
+    /// let (invite, commit) =
+    ///     manager.create_invite_with_options(&channel_id, bob_kp, options).await?;
+    /// ```
+    pub async fn create_invite_with_options(
+        &self,
+        channel_id: &ChannelId,
+        invitee: impl Into<Invitee>,
+        options: InviteOptions,
     ) -> MvpResult<(InviteToken, Option<Vec<u8>>)> {
         info!(
             channel_id = %channel_id,
@@ -422,49 +1364,55 @@ impl ChannelManager {
             .get_channel(channel_id)
             .map_err(|e| MvpError::Store(e.to_string()))?
             .ok_or_else(|| MvpError::ChannelNotFound(channel_id.to_string()))?;
+        let (key_package, contact) = self.invitee_key_package(invitee.into()).await?;
+
+        // Reject unusable key packages before touching the group
+        self.mls_service.validate_key_package(&key_package).map_err(|e| {
+            warn!(error = %e, "Rejecting invitee key package");
+            match e {
+                MlsError::InvalidMessage(reason) => MvpError::InvalidKeyPackage(reason),
+                other => MvpError::Mls(other),
+            }
+        })?;
 
-        // Get group ID from channel
-        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let members_before: HashSet<Vec<u8>> =
+            self.get_channel_members(channel_id).await?.into_iter().collect();
 
         // Add member via MLS service and get Welcome
         debug!("Adding member to MLS group");
         let (commit, welcome_bytes, ratchet_tree) =
-            self.mls_service.add_members(&group_id, vec![key_package]).await?;
+            self.add_invitees(&channel, vec![key_package]).await?;
 
         if welcome_bytes.is_empty() {
             warn!("No Welcome message generated");
             return Err(MvpError::Internal("Failed to generate Welcome message".to_string()));
         }
+        self.sync_member_devices(channel_id).await;
 
-        // Convert ratchet tree to Option (None if empty)
-        let ratchet_tree_opt = if ratchet_tree.is_empty() {
-            None
-        } else {
-            Some(ratchet_tree)
-        };
-
-        // Get channel name and is_public flag
-        let channel_name =
-            channel.get_name().cloned().unwrap_or_else(|| "Unnamed Channel".to_string());
-        let is_public = false; // TODO: Get from channel metadata when implemented
-
-        // Create invite token with channel metadata
-        let mut invite = InviteToken::new(
-            channel_id.clone(),
-            welcome_bytes,
-            ratchet_tree_opt,
-            channel_name,
-            is_public,
-            self.identity.user_id.clone(),
-        );
+        let mut invite = self.build_invite(channel_id, &channel, welcome_bytes, ratchet_tree).await;
+        invite.expires_at = options.ttl.map(|ttl| {
+            Timestamp::from_millis(invite.created_at.as_millis() + ttl.as_millis() as u64)
+        });
 
-        // Add our peer ID to invite if network is enabled (for invite-based peer discovery)
-        if let Some(ref network) = self.network {
-            if let Some(local_peer_id) = network.get_local_peer_id().await {
-                debug!("Adding local peer ID to invite for secure peer exchange");
-                invite = invite.with_peer_id(local_peer_id.0.clone());
-            }
-        }
+        // Track the invite so it can be revoked and its uses counted
+        let invitee = self
+            .get_channel_members(channel_id)
+            .await?
+            .into_iter()
+            .find(|identity| !members_before.contains(identity))
+            .unwrap_or_default();
+        self.put_invite_record(
+            channel_id,
+            InviteRecord {
+                invite_id: invite.invite_id.clone(),
+                invitee,
+                issued_by: self.identity.user_id.clone(),
+                created_at: invite.created_at,
+                expires_at: invite.expires_at,
+                max_uses: options.max_uses,
+                uses: 0,
+            },
+        )?;
 
         info!(
             channel_id = %channel_id,
@@ -472,6 +1420,10 @@ impl ChannelManager {
             "Invite created successfully"
         );
 
+        if let Some((_, ref identity_public_key)) = contact {
+            self.deliver_to_mailbox(identity_public_key, &Delivery::Welcome(invite.clone()));
+        }
+
         // Trigger peer discovery after adding member
         if let Some(ref network) = self.network {
             debug!("Network enabled, updating peer discovery after adding member");
@@ -502,837 +1454,5438 @@ impl ChannelManager {
         let commit_opt = if commit.is_empty() {
             None
         } else {
+            self.record_commit(channel_id, &commit).await;
             Some(commit)
         };
+
+        // Only after the commit went out: the invitee joins from the Welcome
+        if let Some((user_id, identity_public_key)) = contact {
+            self.mailbox_contacts.write().await.insert(user_id, identity_public_key);
+        }
         Ok((invite, commit_opt))
     }
 
-    /// Join a channel from an invite
-    ///
-    /// This processes the Welcome message and syncs channel state.
-    ///
-    /// # Arguments
-    ///
-    /// * `invite` - The invite token to process
-    ///
-    /// # Returns
-    ///
-    /// The joined channel's ID
+    /// Create an invite that can be joined without any network
     ///
-    /// # Example
-    ///
-    /// ```ignore
-    /// let channel_id = manager.join_channel(&invite).await?;
-    /// ```
-    pub async fn join_channel(&self, invite: &InviteToken) -> MvpResult<ChannelId> {
-        info!(
-            channel_id = %invite.channel_id,
-            user_id = %self.identity.user_id,
-            "Joining channel"
-        );
-
-        // Check if invite is expired
-        if invite.is_expired() {
-            warn!("Invite has expired");
-            return Err(MvpError::InvalidInvite("Invite has expired".to_string()));
+    /// Issues a regular invite (see
+    /// [`create_invite_with_options`](Self::create_invite_with_options)) and
+    /// bundles it with everything the invitee would otherwise fetch: the
+    /// full ratchet tree, the channel's broadcast policy, our identity and
+    /// our bootstrap peers. Seal the bundle with
+    /// [`OfflineInvite::seal`] before handing it over.
+    pub async fn create_offline_invite(
+        &self,
+        channel_id: &ChannelId,
+        invitee: impl Into<Invitee>,
+        options: InviteOptions,
+    ) -> MvpResult<(OfflineInvite, Option<Vec<u8>>)> {
+        let (mut invite, commit) =
+            self.create_invite_with_options(channel_id, invitee, options).await?;
+
+        // The invitee can't fetch the tree, so it always travels with the Welcome
+        if invite.ratchet_tree.is_none() {
+            let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+            invite.ratchet_tree = Some(self.mls_service.export_ratchet_tree(&group_id).await?);
         }
 
-        // Join MLS group from Welcome
-        debug!("Joining MLS group from Welcome");
+        let bundle = OfflineInvite {
+            invite,
+            inviter: (*self.identity).clone(),
+            broadcast_policy: self.broadcast_policy(channel_id).await,
+            member_count: self.get_channel_members(channel_id).await?.len(),
+            bootstrap_peers: self
+                .config
+                .dht
+                .bootstrap_peers
+                .iter()
+                .map(|addr| addr.to_string())
+                .collect(),
+        };
 
-        // Convert ratchet tree to Vec<u8> if present
-        let ratchet_tree_vec = invite.ratchet_tree.clone();
+        info!(
+            channel_id = %channel_id,
+            members = bundle.member_count,
+            tree_size = bundle.invite.ratchet_tree.as_ref().map_or(0, Vec::len),
+            "Offline invite created"
+        );
+        Ok((bundle, commit))
+    }
 
-        let group_id = self.mls_service.join_group(&invite.welcome_blob, ratchet_tree_vec).await?;
+    /// Invite several key packages with a single commit
+    ///
+    /// The key packages are verified as a batch, in parallel, and the valid
+    /// ones are added to the group in one commit. Each invitee gets its
+    /// own invite record (sharing the Welcome), so invites can be revoked
+    /// and counted individually. Invalid or duplicate key packages are
+    /// reported in [`InviteBatch::rejected`] rather than failing the batch.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Target channel
+    /// * `key_packages` - Invitees' serialized key packages
+    /// * `options` - TTL and maximum number of uses for every invite
+    pub async fn create_invites_batch(
+        &self,
+        channel_id: &ChannelId,
+        key_packages: Vec<Vec<u8>>,
+        options: InviteOptions,
+    ) -> MvpResult<InviteBatch> {
+        info!(
+            channel_id = %channel_id,
+            inviter = %self.identity.user_id,
+            count = key_packages.len(),
+            "Creating invite batch"
+        );
 
-        // Verify group ID matches channel ID
-        let expected_group_id = GroupId::new(invite.channel_id.0.as_bytes().to_vec());
-        if group_id != expected_group_id {
-            warn!(
-                expected = ?expected_group_id,
-                got = ?group_id,
-                "Group ID mismatch"
-            );
-            return Err(MvpError::Internal("Group ID mismatch after join".to_string()));
-        }
+        let channel = self
+            .store
+            .get_channel(channel_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .ok_or_else(|| MvpError::ChannelNotFound(channel_id.to_string()))?;
 
-        // Fetch or create channel metadata
-        debug!(channel_id = %invite.channel_id, "Fetching channel metadata");
-        match self.store.get_channel(&invite.channel_id) {
-            Ok(Some(_channel)) => {
-                // Channel exists, we're good
-                debug!("Channel metadata found in local store");
-            }
-            Ok(None) => {
-                // Create placeholder channel using metadata from invite
-                debug!("Channel metadata not found, creating from invite");
-                let channel = Channel::new(
-                    invite.channel_id.clone(),
-                    invite.channel_name.clone(),
-                    ChannelType::Text,
-                    invite.inviter.clone(),
-                    Timestamp::now(),
-                    self.identity.node_id.clone(),
-                );
-                self.store.store_channel(&channel).map_err(|e| MvpError::Store(e.to_string()))?;
-            }
-            Err(e) => {
-                return Err(MvpError::Store(e.to_string()));
+        let mut batch = InviteBatch { invites: Vec::new(), commit: None, rejected: Vec::new() };
+        let mut seen = HashSet::new();
+        let mut accepted = Vec::new();
+        let mut invitees = Vec::new();
+        let results = self.mls_service.verify_key_packages(&key_packages);
+        for (index, (key_package, result)) in key_packages.into_iter().zip(results).enumerate() {
+            match result {
+                Ok(verified) if seen.insert(verified.signature_key.clone()) => {
+                    accepted.push(key_package);
+                    invitees.push(verified.identity);
+                }
+                Ok(_) => batch.rejected.push((index, "duplicate key package".to_string())),
+                Err(e) => {
+                    warn!(index, error = %e, "Rejecting invitee key package");
+                    batch.rejected.push((index, e.to_string()));
+                }
             }
         }
+        if accepted.is_empty() {
+            return Ok(batch);
+        }
 
-        info!(
-            channel_id = %invite.channel_id,
-            "Successfully joined channel"
-        );
+        let (commit, welcome_bytes, ratchet_tree) = self.add_invitees(&channel, accepted).await?;
+        if welcome_bytes.is_empty() {
+            return Err(MvpError::Internal("Failed to generate Welcome message".to_string()));
+        }
+        self.sync_member_devices(channel_id).await;
 
-        // Register inviter's peer ID if provided in invite (invite-based peer discovery)
-        if let (Some(ref network), Some(ref inviter_peer_id)) =
-            (&self.network, &invite.inviter_peer_id)
-        {
-            debug!("Registering inviter's peer ID from invite");
-            let peer_id = crate::core_router::session_manager::PeerId(inviter_peer_id.clone());
-            network
-                .register_channel_member(&invite.channel_id, invite.inviter.clone(), peer_id)
+        for invitee in invitees {
+            let mut invite = self
+                .build_invite(channel_id, &channel, welcome_bytes.clone(), ratchet_tree.clone())
                 .await;
+            invite.expires_at = options.ttl.map(|ttl| {
+                Timestamp::from_millis(invite.created_at.as_millis() + ttl.as_millis() as u64)
+            });
+            self.put_invite_record(
+                channel_id,
+                InviteRecord {
+                    invite_id: invite.invite_id.clone(),
+                    invitee,
+                    issued_by: self.identity.user_id.clone(),
+                    created_at: invite.created_at,
+                    expires_at: invite.expires_at,
+                    max_uses: options.max_uses,
+                    uses: 0,
+                },
+            )?;
+            batch.invites.push(invite);
         }
 
-        // Trigger peer discovery if network is enabled
         if let Some(ref network) = self.network {
-            debug!("Network enabled, triggering peer discovery for channel members");
-            if let Err(e) = self.discover_and_register_peers(&invite.channel_id, network).await {
-                warn!(error = %e, "Failed to discover peers after joining channel");
+            if let Err(e) = self.discover_and_register_peers(channel_id, network).await {
+                warn!(error = %e, "Failed to discover peers after adding members");
+            }
+        }
+        if !commit.is_empty() {
+            self.record_commit(channel_id, &commit).await;
+            if let Some(ref network) = self.network {
+                if let Err(e) = network.broadcast_commit(channel_id, commit.clone()).await {
+                    warn!(error = %e, "Failed to broadcast invite batch commit");
+                }
             }
+            batch.commit = Some(commit);
         }
 
-        Ok(invite.channel_id.clone())
+        info!(
+            channel_id = %channel_id,
+            invited = batch.invites.len(),
+            rejected = batch.rejected.len(),
+            "Invite batch created"
+        );
+        Ok(batch)
     }
 
-    /// Send a message to a channel
+    /// Link another device of this user to all of its channels
     ///
-    /// This encrypts the message via MLS and returns the ciphertext.
-    ///
-    /// # Arguments
+    /// The request's device bundle must carry a binding from the identity
+    /// key (see [`IdentityBundle::authorize_device`]), and its key packages
+    /// must be signed by that device and issued for this user. Each channel
+    /// gets an MLS Add for one key package, and the invites are returned
+    /// for the new device. A channel the device can't be added to (for
+    /// example because the request ran out of key packages) is skipped.
     ///
-    /// * `channel_id` - Target channel
-    /// * `plaintext` - Message body
+    /// [`IdentityBundle::authorize_device`]: crate::core_identity::IdentityBundle::authorize_device
     ///
-    /// # Returns
+    /// # Arguments
     ///
-    /// The encrypted message ciphertext
+    /// * `request` - The new device's request, with its binding attached
+    /// * `identity_public_key` - Identity master public key
     ///
-    /// # Example
+    /// # Errors
     ///
-    /// ```ignore
-    /// let ciphertext = manager.send_message(&channel_id, b"Hello!").await?;
-    /// ```
-    pub async fn send_message(
+    /// - `Unauthorized` if the device isn't authorized by the identity key,
+    ///   or has been revoked
+    /// - `InvalidKeyPackage` if a key package is invalid or for another user
+    pub async fn link_device(
         &self,
-        channel_id: &ChannelId,
-        plaintext: &[u8],
-    ) -> MvpResult<Vec<u8>> {
-        debug!(
-            channel_id = %channel_id,
-            size = plaintext.len(),
-            "Sending message"
-        );
+        request: &DeviceLinkRequest,
+        identity_public_key: &[u8],
+    ) -> MvpResult<LinkedDevice> {
+        let bundle = &request.device_bundle;
+        if bundle.binding.is_none() {
+            return Err(MvpError::Unauthorized(
+                "device bundle is not authorized by the identity key".to_string(),
+            ));
+        }
+        let expected_user = crate::core_identity::UserId::from_public_key(identity_public_key);
+        validate_device_bundle(bundle, &expected_user, identity_public_key).map_err(|e| {
+            warn!(error = %e, "Rejecting device link request");
+            MvpError::Unauthorized(format!("device not authorized: {}", e))
+        })?;
+        // A revoked device stays revoked, whatever else is wrong with the request
+        let device_id = bundle.device_metadata.device_id.clone();
+        for descriptor in self.list_channels().await? {
+            let channel = self.store.get_channel(&descriptor.channel_id).ok().flatten();
+            if channel.is_some_and(|channel| channel.is_device_revoked(&device_id.as_hex())) {
+                return Err(MvpError::Unauthorized(format!(
+                    "device {} has been revoked",
+                    device_id.as_hex()
+                )));
+            }
+        }
+        if !request.verify() {
+            return Err(MvpError::Unauthorized(
+                "key packages are not signed by the device".to_string(),
+            ));
+        }
 
-        // Apply message padding for traffic analysis resistance
-        let padded_plaintext = crate::core_mls::padding::pad_message(plaintext)
-            .map_err(|e| MvpError::InvalidOperation(format!("Failed to pad message: {}", e)))?;
+        for key_package in &request.key_packages {
+            let identity =
+                self.mls_service.key_package_identity(key_package).map_err(|e| match e {
+                    MlsError::InvalidMessage(reason) => MvpError::InvalidKeyPackage(reason),
+                    other => MvpError::Mls(other),
+                })?;
+            if identity != self.identity.as_bytes() {
+                return Err(MvpError::InvalidKeyPackage(
+                    "key package is for another user".to_string(),
+                ));
+            }
+        }
 
-        debug!(
-            original_size = plaintext.len(),
-            padded_size = padded_plaintext.len(),
-            "Message padded for privacy"
-        );
+        info!(device_id = %device_id.as_hex(), "Linking device");
 
-        // Get group ID
-        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let mut linked = LinkedDevice {
+            device_id,
+            invites: Vec::new(),
+            commits: Vec::new(),
+            skipped: Vec::new(),
+        };
+        let mut key_packages = request.key_packages.iter().cloned();
+        for descriptor in self.list_channels().await? {
+            let channel_id = descriptor.channel_id;
+            let Some(key_package) = key_packages.next() else {
+                warn!(channel_id = %channel_id, "No key package left to link device");
+                linked.skipped.push(channel_id);
+                continue;
+            };
+            match self.add_own_device(&channel_id, key_package, bundle).await {
+                Ok((invite, commit)) => {
+                    linked.invites.push(invite);
+                    if !commit.is_empty() {
+                        linked.commits.push((channel_id, commit));
+                    }
+                }
+                Err(e) => {
+                    warn!(channel_id = %channel_id, error = %e, "Failed to link device");
+                    linked.skipped.push(channel_id);
+                }
+            }
+        }
 
-        // Encrypt padded message via MLS service
-        let ciphertext = self.mls_service.send_message(&group_id, &padded_plaintext).await?;
+        info!(channels = linked.invites.len(), skipped = linked.skipped.len(), "Device linked");
+        Ok(linked)
+    }
 
-        // If network layer is enabled, broadcast to channel members
-        if let Some(network) = &self.network {
-            debug!(
-                channel_id = %channel_id,
-                "Broadcasting message over network"
-            );
+    /// Add one of this user's own devices to a channel
+    ///
+    /// Like an invite, but no invite record is kept: the device joins as
+    /// the same member. Its leaf is recorded against the device instead.
+    async fn add_own_device(
+        &self,
+        channel_id: &ChannelId,
+        key_package: Vec<u8>,
+        bundle: &DeviceBundle,
+    ) -> MvpResult<(InviteToken, Vec<u8>)> {
+        let channel = self
+            .store
+            .get_channel(channel_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .ok_or_else(|| MvpError::ChannelNotFound(channel_id.to_string()))?;
 
-            network
-                .broadcast_message(channel_id, ciphertext.clone(), &self.identity.user_id)
-                .await?;
-        } else {
-            debug!(
-                channel_id = %channel_id,
-                "Network layer not enabled, message not broadcast"
-            );
+        let signature_key = self.mls_service.key_package_signature_key(&key_package)?;
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let (commit, welcome_bytes, ratchet_tree) =
+            self.mls_service.add_members(&group_id, vec![key_package]).await?;
+        if welcome_bytes.is_empty() {
+            return Err(MvpError::Internal("Failed to generate Welcome message".to_string()));
         }
+        self.sync_member_devices(channel_id).await;
+
+        let record = LinkedDeviceRecord {
+            leaf: leaf_device_id(&signature_key),
+            user_id: self.identity.user_id.clone(),
+            device_id: bundle.device_metadata.device_id.as_hex(),
+            device_public_key: bundle.device_public_key().to_vec(),
+            linked_at: Timestamp::now(),
+        };
+        let mut updated = self
+            .store
+            .get_channel(channel_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .unwrap_or_else(|| channel.clone());
+        updated.put_linked_device(record, &self.identity.node_id);
+        self.store.store_channel(&updated).map_err(|e| MvpError::Store(e.to_string()))?;
 
-        info!(
-            channel_id = %channel_id,
-            plaintext_size = plaintext.len(),
-            ciphertext_size = ciphertext.len(),
-            network_broadcast = self.network.is_some(),
-            "Message encrypted successfully"
-        );
+        if !commit.is_empty() {
+            self.record_commit(channel_id, &commit).await;
+            if let Some(ref network) = self.network {
+                if let Err(e) = network.broadcast_commit(channel_id, commit.clone()).await {
+                    warn!(error = %e, "Failed to broadcast device link commit");
+                }
+            }
+        }
 
-        Ok(ciphertext)
+        let invite = self.build_invite(channel_id, &channel, welcome_bytes, ratchet_tree).await;
+        Ok((invite, commit))
     }
 
-    /// Receive and decrypt a message
+    /// Revoke one of this user's linked devices
     ///
-    /// This decrypts an MLS message and returns the plaintext.
+    /// Marks the device revoked in every channel, so it can't be linked
+    /// again, and removes its leaves from each group. The removal commits
+    /// move the groups to a new epoch with fresh secrets, which the device
+    /// never learns: it can't decrypt anything sent afterwards, and the
+    /// other members reject anything it sends once they have processed
+    /// the commit.
     ///
     /// # Arguments
     ///
-    /// * `ciphertext` - Encrypted message bytes
+    /// * `device_id` - Device to revoke, as in its key binding
     ///
     /// # Returns
     ///
-    /// The decrypted message plaintext
+    /// The channels the device was revoked in and the removal commits to
+    /// broadcast
     ///
-    /// # Example
+    /// # Errors
     ///
-    /// ```ignore
-    /// let plaintext = manager.receive_message(&ciphertext).await?;
-    /// ```
-    pub async fn receive_message(&self, ciphertext: &[u8]) -> MvpResult<Vec<u8>> {
-        debug!(size = ciphertext.len(), "Receiving message");
-
-        // For MVP, we need to try all groups until we find the right one
-        let groups = self.mls_service.list_groups().await;
+    /// Returns `DeviceNotFound` if no channel has a leaf linked for the
+    /// device.
+    pub async fn revoke_device(&self, device_id: &DeviceId) -> MvpResult<RevokedDevice> {
+        let device_hex = device_id.as_hex();
+        info!(device_id = %device_hex, "Revoking device");
+
+        let mut revoked = RevokedDevice {
+            device_id: device_id.clone(),
+            channels: Vec::new(),
+            commits: Vec::new(),
+            skipped: Vec::new(),
+        };
+        for descriptor in self.list_channels().await? {
+            let channel_id = descriptor.channel_id;
+            let Some(mut channel) = self
+                .store
+                .get_channel(&channel_id)
+                .map_err(|e| MvpError::Store(e.to_string()))?
+            else {
+                continue;
+            };
+            let leaves: HashSet<String> = channel
+                .get_linked_devices()
+                .into_iter()
+                .filter(|record| {
+                    record.device_id == device_hex && record.user_id == self.identity.user_id
+                })
+                .map(|record| record.leaf)
+                .collect();
+            if leaves.is_empty() {
+                continue;
+            }
 
-        for group_id in groups.iter() {
-            // Try to process message with this group
-            match self.mls_service.process_message(group_id, ciphertext).await {
-                Ok(Some(padded_plaintext)) => {
-                    // Remove padding to get original message
-                    let plaintext = crate::core_mls::padding::unpad_message(&padded_plaintext)
-                        .map_err(|e| {
-                            MvpError::InvalidMessage(format!("Failed to unpad message: {}", e))
-                        })?;
+            channel.revoke_device(&device_hex, &self.identity.node_id);
+            self.store.store_channel(&channel).map_err(|e| MvpError::Store(e.to_string()))?;
+            revoked.channels.push(channel_id.clone());
 
-                    info!(
-                        group_id = ?group_id,
-                        padded_size = padded_plaintext.len(),
-                        plaintext_size = plaintext.len(),
-                        "Message decrypted and unpadded successfully"
-                    );
-                    return Ok(plaintext);
-                }
-                Ok(None) => {
-                    // Commit or proposal, continue trying
-                }
-                Err(_e) => {
-                    // Failed with this group, try next
+            match self.remove_device_leaves(&channel_id, &leaves).await {
+                Ok(Some(commit)) => revoked.commits.push((channel_id, commit)),
+                Ok(None) => {}
+                Err(e) => {
+                    warn!(channel_id = %channel_id, error = %e, "Failed to remove revoked device");
+                    revoked.skipped.push(channel_id);
                 }
             }
         }
 
-        warn!("Failed to decrypt message with any of {} groups", groups.len());
-        Err(MvpError::InvalidMessage("Could not decrypt message".to_string()))
+        if revoked.channels.is_empty() {
+            return Err(MvpError::DeviceNotFound(device_hex));
+        }
+        record_counter("mvp.devices.revoked", 1);
+        info!(
+            channels = revoked.channels.len(),
+            skipped = revoked.skipped.len(),
+            "Device revoked"
+        );
+        Ok(revoked)
     }
 
-    /// Process a commit message from the group
+    /// Remove the given device leaves from a channel's group
     ///
-    /// This updates the member's group state when other members add/remove participants
-    /// or perform other group operations. Essential for keeping epochs in sync.
-    ///
-    /// # Arguments
-    ///
-    /// * `commit` - Serialized commit message
-    ///
-    /// # Example
-    ///
-    /// ```ignore
-    /// // Alice adds Charlie, gets commit for Bob
-    /// let (invite, Some(commit)) = alice.create_invite(&channel_id, charlie_kp).await?;
-    /// // Bob processes commit to advance epoch
-    /// bob.process_commit(&commit).await?;
-    /// ```
-    pub async fn process_commit(&self, commit: &[u8]) -> MvpResult<()> {
-        debug!(size = commit.len(), "Processing commit");
+    /// Returns `None` if none of them is in the group any more.
+    async fn remove_device_leaves(
+        &self,
+        channel_id: &ChannelId,
+        leaves: &HashSet<String>,
+    ) -> MvpResult<Option<Vec<u8>>> {
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let metadata = self.mls_service.get_metadata(&group_id).await?;
+        let removed: Vec<&MemberInfo> = metadata
+            .members
+            .iter()
+            .filter(|member| leaves.contains(&leaf_device_id(&member.signature_key)))
+            .collect();
+        if removed.is_empty() {
+            return Ok(None);
+        }
 
-        // Try all groups until we find the right one
-        let groups = self.mls_service.list_groups().await;
+        // Key packages signed by the revoked leaves must not be accepted
+        // again, even if a positive verdict is cached
+        self.mls_service
+            .revoke_key_package_signers(removed.iter().map(|member| member.signature_key.clone()));
+        let leaf_indices = removed.iter().map(|member| member.leaf_index).collect();
 
-        for group_id in groups.iter() {
-            // Try to process commit with this group
-            match self.mls_service.process_message(group_id, commit).await {
-                Ok(Some(_)) => {
-                    // This shouldn't happen for commits, but if it does, it worked
-                    info!(group_id = ?group_id, "Commit processed (unexpected app message)");
-                    return Ok(());
-                }
-                Ok(None) => {
-                    // Commit or proposal processed successfully
-                    info!(group_id = ?group_id, "Commit processed successfully");
-                    return Ok(());
-                }
-                Err(_e) => {
-                    // Failed with this group, try next
+        let commit = self.mls_service.remove_members(&group_id, leaf_indices).await?;
+        self.record_commit(channel_id, &commit).await;
+        self.sync_member_devices(channel_id).await;
+        if let Some(ref network) = self.network {
+            if let Err(e) = network.broadcast_commit(channel_id, commit.clone()).await {
+                warn!(error = %e, "Failed to broadcast device revocation commit");
+            }
+        }
+        Ok(Some(commit))
+    }
+
+    /// Build an invite token for a Welcome
+    async fn build_invite(
+        &self,
+        channel_id: &ChannelId,
+        channel: &Channel,
+        welcome_bytes: Vec<u8>,
+        ratchet_tree: Vec<u8>,
+    ) -> InviteToken {
+        // Convert ratchet tree to Option (None if empty)
+        let ratchet_tree_opt = if ratchet_tree.is_empty() {
+            None
+        } else {
+            Some(ratchet_tree)
+        };
+
+        // Leave the tree out if it can go to the DHT instead
+        let (ratchet_tree_opt, external_tree) = match ratchet_tree_opt {
+            Some(tree) if self.mls_service.config().external_tree => {
+                match self.publish_ratchet_tree(channel_id, &tree).await {
+                    Ok(external) => (None, Some(external)),
+                    Err(e) => {
+                        warn!(error = %e, "Failed to publish ratchet tree, embedding it instead");
+                        (Some(tree), None)
+                    }
                 }
             }
+            other => (other, None),
+        };
+
+        // Get channel name and is_public flag
+        let channel_name =
+            channel.get_name().cloned().unwrap_or_else(|| "Unnamed Channel".to_string());
+        let is_public = channel.is_public();
+
+        // Create invite token with channel metadata
+        let mut invite = InviteToken::new(
+            channel_id.clone(),
+            welcome_bytes,
+            ratchet_tree_opt,
+            channel_name,
+            is_public,
+            self.identity.user_id.clone(),
+        );
+        invite.external_tree = external_tree;
+        invite.padding = channel.padding().cloned();
+
+        // Add our peer ID to invite if network is enabled (for invite-based peer discovery)
+        if let Some(ref network) = self.network {
+            if let Some(local_peer_id) = network.get_local_peer_id().await {
+                debug!("Adding local peer ID to invite for secure peer exchange");
+                invite = invite.with_peer_id(local_peer_id.0.clone());
+            }
         }
 
-        warn!("Failed to process commit with any of {} groups", groups.len());
-        Err(MvpError::InvalidMessage("Could not process commit".to_string()))
+        invite
     }
 
-    /// Get the role of a specific member in a channel
+    /// Publish a channel's current ratchet tree to the tree directory
+    async fn publish_ratchet_tree(
+        &self,
+        channel_id: &ChannelId,
+        ratchet_tree: &[u8],
+    ) -> MvpResult<ExternalTree> {
+        let directory = self.trees.as_ref().ok_or_else(|| {
+            MvpError::InvalidOperation("No ratchet tree directory configured".to_string())
+        })?;
+
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let epoch = self.mls_service.get_epoch(&group_id).await?;
+        let snapshot = TreeSnapshot::from_ratchet_tree(ratchet_tree.to_vec());
+        directory.publish(&group_id, epoch, &snapshot)?;
+
+        debug!(
+            channel_id = %channel_id,
+            epoch,
+            tree_size = ratchet_tree.len(),
+            "Published ratchet tree"
+        );
+        Ok(ExternalTree { epoch, hash: snapshot.hash() })
+    }
+
+    /// Fetch the ratchet tree an invite points to and check its hash
+    fn fetch_ratchet_tree(
+        &self,
+        invite: &InviteToken,
+        external: &ExternalTree,
+    ) -> MvpResult<Vec<u8>> {
+        let directory = self.trees.as_ref().ok_or_else(|| {
+            MvpError::InvalidOperation(
+                "Invite needs a ratchet tree directory to fetch the tree".to_string(),
+            )
+        })?;
+
+        let group_id = GroupId::new(invite.channel_id.0.as_bytes().to_vec());
+        let snapshot = directory.lookup(&group_id, external.epoch)?.ok_or_else(|| {
+            MvpError::InvalidInvite(format!(
+                "Ratchet tree for epoch {} is not published",
+                external.epoch
+            ))
+        })?;
+        snapshot.verify(external.epoch, &external.hash)?;
+
+        Ok(snapshot.ratchet_tree)
+    }
+
+    /// Store an invite record in the channel
+    fn put_invite_record(&self, channel_id: &ChannelId, record: InviteRecord) -> MvpResult<()> {
+        let mut channel = self
+            .store
+            .get_channel(channel_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .ok_or_else(|| MvpError::ChannelNotFound(channel_id.to_string()))?;
+        channel.put_invite(record, &self.identity.node_id);
+        self.store.store_channel(&channel).map_err(|e| MvpError::Store(e.to_string()))
+    }
+
+    /// List the invites issued for a channel, oldest first
+    pub async fn list_invites(&self, channel_id: &ChannelId) -> MvpResult<Vec<InviteRecord>> {
+        let channel = self
+            .store
+            .get_channel(channel_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .ok_or_else(|| MvpError::ChannelNotFound(channel_id.to_string()))?;
+
+        let mut invites = channel.get_invites();
+        invites.sort_by(|a, b| (a.created_at, &a.invite_id).cmp(&(b.created_at, &b.invite_id)));
+        Ok(invites)
+    }
+
+    /// Revoke an invite
+    ///
+    /// The Welcome in an invite is already bound to a leaf added when the
+    /// invite was created, so revoking an unused invite also removes that
+    /// leaf; the Welcome is useless once members process the returned
+    /// commit. A used invite is only marked revoked.
     ///
     /// # Arguments
     ///
-    /// * `channel_id` - Target channel
-    /// * `member_identity` - Identity bytes of the member
+    /// * `channel_id` - Channel the invite is for
+    /// * `invite_id` - ID from the invite token
     ///
     /// # Returns
     ///
-    /// The member's role if found
-    pub async fn get_member_role(
+    /// The removal commit to broadcast, if a leaf was removed
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidInvite` if no such invite was issued for the channel.
+    pub async fn revoke_invite(
         &self,
         channel_id: &ChannelId,
-        member_identity: &[u8],
-    ) -> MvpResult<MemberRole> {
-        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
-        let metadata =
-            self.mls_service.get_metadata(&group_id).await.map_err(|e| MvpError::Mls(e))?;
+        invite_id: &str,
+    ) -> MvpResult<Option<Vec<u8>>> {
+        let mut channel = self
+            .store
+            .get_channel(channel_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .ok_or_else(|| MvpError::ChannelNotFound(channel_id.to_string()))?;
+        let record = channel
+            .get_invite(invite_id)
+            .cloned()
+            .ok_or_else(|| MvpError::InvalidInvite(format!("Unknown invite: {}", invite_id)))?;
 
-        metadata
-            .members
-            .iter()
-            .find(|m| m.identity == member_identity)
-            .map(|m| m.role)
-            .ok_or_else(|| {
-                MvpError::InvalidOperation(format!(
-                    "Member {} not found in channel",
-                    std::str::from_utf8(member_identity).unwrap_or("<non-utf8>")
-                ))
-            })
+        // The issuer may always revoke; anyone else needs to be able to kick
+        let actor_identity = self.identity.user_id.0.as_bytes();
+        if record.issued_by != self.identity.user_id
+            && !self
+                .can_remove_member(channel_id, actor_identity, Some(&record.invitee))
+                .await?
+        {
+            return Err(MvpError::PermissionDenied {
+                user: self.identity.user_id.to_string(),
+                action: "revoke_invite".to_string(),
+                channel: channel_id.to_string(),
+            });
+        }
+
+        info!(channel_id = %channel_id, invite_id, "Revoking invite");
+        channel.revoke_invite(invite_id, &self.identity.node_id);
+        self.store.store_channel(&channel).map_err(|e| MvpError::Store(e.to_string()))?;
+
+        if record.uses > 0 || !self.is_current_member(channel_id, &record.invitee).await? {
+            return Ok(None);
+        }
+        self.remove_member(channel_id, &record.invitee).await.map(Some)
     }
 
-    /// Check if a member has admin role in a channel
-    ///
-    /// # Arguments
+    /// Check an invitee's acceptance against the invite record
     ///
-    /// * `channel_id` - Target channel
-    /// * `member_identity` - Identity bytes of the member
+    /// Called by the inviter when an acceptance arrives on the commit path.
+    /// Acceptances of revoked, expired or used-up invites get the joiner
+    /// removed again; otherwise the use is counted. Acceptances of invites
+    /// this replica has no record of are ignored.
     ///
     /// # Returns
     ///
-    /// `true` if the member is an admin, `false` otherwise
-    pub async fn is_admin(
+    /// The removal commit to broadcast, if the joiner was removed
+    pub async fn process_invite_acceptance(
         &self,
         channel_id: &ChannelId,
-        member_identity: &[u8],
-    ) -> MvpResult<bool> {
-        let role = self.get_member_role(channel_id, member_identity).await?;
-        Ok(matches!(role, MemberRole::Admin))
+        acceptance: &InviteAcceptance,
+    ) -> MvpResult<Option<Vec<u8>>> {
+        let channel = self
+            .store
+            .get_channel(channel_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .ok_or_else(|| MvpError::ChannelNotFound(channel_id.to_string()))?;
+        let Some(mut record) = channel.get_invite(&acceptance.invite_id).cloned() else {
+            debug!(invite_id = %acceptance.invite_id, "Ignoring acceptance of unknown invite");
+            return Ok(None);
+        };
+        if record.invitee != acceptance.user_id.0.as_bytes() {
+            warn!(
+                invite_id = %acceptance.invite_id,
+                user_id = %acceptance.user_id,
+                "Ignoring invite acceptance from someone other than the invitee"
+            );
+            return Ok(None);
+        }
+
+        let rejection = if channel.is_invite_revoked(&record.invite_id) {
+            Some("revoked")
+        } else if record.is_expired_at(Timestamp::now()) {
+            Some("expired")
+        } else if record.is_used_up() {
+            Some("already used")
+        } else {
+            None
+        };
+
+        if let Some(reason) = rejection {
+            warn!(
+                channel_id = %channel_id,
+                invite_id = %record.invite_id,
+                reason,
+                "Invite no longer valid, removing joiner"
+            );
+            if !self.is_current_member(channel_id, &record.invitee).await? {
+                return Ok(None);
+            }
+            return self.remove_member(channel_id, &record.invitee).await.map(Some);
+        }
+
+        record.uses += 1;
+        self.put_invite_record(channel_id, record)?;
+
+        let kind = ChannelEventKind::InviteAccepted { invite_id: acceptance.invite_id.clone() };
+        self.emit_event(channel_id, Some(acceptance.user_id.clone()), kind).await;
+        Ok(None)
     }
 
-    /// Check if a member can remove other members
+    /// Whether an identity is in the channel's MLS group
+    async fn is_current_member(&self, channel_id: &ChannelId, identity: &[u8]) -> MvpResult<bool> {
+        let members = self.get_channel_members(channel_id).await?;
+        Ok(members.iter().any(|member| member.as_slice() == identity))
+    }
+
+    /// Join a channel from an invite
+    ///
+    /// This processes the Welcome message and syncs channel state.
     ///
     /// # Arguments
     ///
-    /// * `channel_id` - Target channel
-    /// * `actor_identity` - Identity bytes of the member attempting removal
-    /// * `target_identity` - Identity bytes of the member to be removed (optional, for self-removal check)
+    /// * `invite` - The invite token to process
     ///
     /// # Returns
     ///
-    /// `true` if the actor has permission to remove members
-    pub async fn can_remove_member(
-        &self,
-        channel_id: &ChannelId,
-        actor_identity: &[u8],
-        _target_identity: Option<&[u8]>,
-    ) -> MvpResult<bool> {
-        // For now, only admins can remove members
-        // Future: Allow members to remove themselves
-        self.is_admin(channel_id, actor_identity).await
+    /// The joined channel's ID
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let channel_id = manager.join_channel(&invite).await?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// `InsufficientStorage` if the data directory's filesystem can't hold
+    /// the estimated group state plus the configured safety margin; see
+    /// [`join_channel_forced`](Self::join_channel_forced).
+    pub async fn join_channel(&self, invite: &InviteToken) -> MvpResult<ChannelId> {
+        self.join_channel_inner(invite, false).await
     }
 
-    /// Remove a member from the channel
+    /// Join a channel from an invite without checking free disk space
     ///
-    /// This generates an MLS commit that removes the specified member.
-    /// All remaining members must process this commit to stay in sync.
+    /// For when the estimate is known to be pessimistic; a join that does
+    /// run out of space leaves partial state for recovery to clean up.
+    pub async fn join_channel_forced(&self, invite: &InviteToken) -> MvpResult<ChannelId> {
+        self.join_channel_inner(invite, true).await
+    }
+
+    /// Join a channel whose invites require a pre-shared key
+    ///
+    /// Stores the PSK before processing the Welcome (see
+    /// [`create_channel_with_psk`](Self::create_channel_with_psk)); the
+    /// channel keeps requiring it for invites we issue.
     ///
     /// # Arguments
     ///
-    /// * `channel_id` - Target channel
-    /// * `member_identity` - Identity bytes of the member to remove
+    /// * `invite` - Invite from a member of the channel
+    /// * `psk_id` - ID the PSK is known by
+    /// * `psk` - The shared secret, received separately from the invite
+    ///
+    /// # Errors
+    ///
+    /// `Mls` if the Welcome can't be used with this PSK, e.g. because the
+    /// secret is wrong; the invite's key package is used up either way
+    pub async fn join_with_psk(
+        &self,
+        invite: &InviteToken,
+        psk_id: &[u8],
+        psk: &[u8],
+    ) -> MvpResult<ChannelId> {
+        if psk_id.is_empty() {
+            return Err(MvpError::Config("PSK ID must not be empty".to_string()));
+        }
+
+        self.mls_service.store_psk(psk_id, psk)?;
+        self.join_from_welcome(invite, false).await?;
+        self.require_psk(&invite.channel_id, psk_id)?;
+        self.announce_join(
+            &invite.channel_id,
+            &invite.invite_id,
+            &invite.inviter,
+            invite.inviter_peer_id.as_deref(),
+        )
+        .await;
+
+        Ok(invite.channel_id.clone())
+    }
+
+    /// Join a public channel without an invite
+    ///
+    /// Fetches the channel's GroupInfo from the public group directory,
+    /// joins by external commit (RFC 9420) and broadcasts the commit so the
+    /// members add us. Members only accept external commits for channels
+    /// they hold as public.
     ///
     /// # Returns
     ///
-    /// Serialized commit message to broadcast to remaining members
+    /// The external commit, for members the network doesn't reach
+    ///
+    /// # Errors
+    ///
+    /// - `Config` without a public group directory
+    /// - `ChannelNotFound` if the channel publishes no GroupInfo (it is
+    ///   private, or doesn't exist)
+    /// - `ChannelExists` if we already hold the channel
+    /// - `InsufficientStorage` if the group state wouldn't fit on disk
     ///
     /// # Example
     ///
     /// ```ignore
-    /// // Alice removes Bob from the channel
-    /// let bob_identity = bob_user_id.0.as_bytes();
-    /// let commit = alice.remove_member(&channel_id, bob_identity).await?;
-    ///
-    /// // Charlie and Dave process the removal commit
-    /// charlie.process_commit(&commit).await?;
-    /// dave.process_commit(&commit).await?;
+    /// let commit = bob.join_public_channel(&channel_id).await?;
     /// ```
-    pub async fn remove_member(
-        &self,
-        channel_id: &ChannelId,
-        member_identity: &[u8],
-    ) -> MvpResult<Vec<u8>> {
+    pub async fn join_public_channel(&self, channel_id: &ChannelId) -> MvpResult<Vec<u8>> {
         info!(
             channel_id = %channel_id,
-            member = ?std::str::from_utf8(member_identity).unwrap_or("<non-utf8>"),
-            "Removing member from channel"
+            user_id = %self.identity.user_id,
+            "Joining public channel"
         );
 
-        // Check permission: Only admins can remove members
-        let actor_identity = self.identity.user_id.0.as_bytes();
-        let can_remove = self
-            .can_remove_member(channel_id, actor_identity, Some(member_identity))
+        let directory = self
+            .public_groups
+            .as_ref()
+            .ok_or_else(|| MvpError::Config("No public group directory configured".to_string()))?;
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        if self.mls_service.list_groups().await.contains(&group_id) {
+            return Err(MvpError::ChannelExists(channel_id.to_string()));
+        }
+
+        let info = directory
+            .lookup(channel_id)?
+            .filter(|info| info.group_id == group_id && info.accepts_external_commits())
+            .ok_or_else(|| MvpError::ChannelNotFound(channel_id.to_string()))?;
+        self.preflight
+            .check(self.store.data_dir(), estimate_join(info.group_info.len(), 0))?;
+
+        let (_, commit) = self
+            .mls_service
+            .join_by_external_commit(&info, self.identity.as_bytes())
             .await?;
 
-        if !can_remove {
-            warn!(
-                actor = ?std::str::from_utf8(actor_identity).unwrap_or("<non-utf8>"),
-                "Permission denied: Actor is not an admin"
-            );
-            return Err(MvpError::InvalidOperation("Only admins can remove members".to_string()));
+        // The creator holds leaf 0
+        let metadata = self.mls_service.get_metadata(&group_id).await?;
+        let creator = metadata
+            .members
+            .iter()
+            .find(|member| member.leaf_index == 0)
+            .and_then(|member| String::from_utf8(member.identity.clone()).ok())
+            .map(UserId)
+            .unwrap_or_else(|| self.identity.user_id.clone());
+
+        if self
+            .store
+            .get_channel(channel_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .is_none()
+        {
+            let mut channel = Channel::new(
+                channel_id.clone(),
+                info.name.clone().unwrap_or_else(|| "Unnamed Channel".to_string()),
+                ChannelType::Text,
+                creator,
+                Timestamp::now(),
+                self.identity.node_id.clone(),
+            );
+            channel.set_public(true, &self.identity.node_id);
+            self.store.store_channel(&channel).map_err(|e| MvpError::Store(e.to_string()))?;
+        }
+        self.sync_member_devices(channel_id).await;
+        self.sync_channel_metadata(channel_id).await;
+
+        self.record_commit(channel_id, &commit).await;
+        if let Some(ref network) = self.network {
+            if let Err(e) = network.broadcast_commit(channel_id, commit.clone()).await {
+                warn!(error = %e, "Failed to broadcast external commit");
+            }
+            if let Err(e) = self.discover_and_register_peers(channel_id, network).await {
+                warn!(error = %e, "Failed to discover peers after joining channel");
+            }
+        }
+
+        info!(channel_id = %channel_id, epoch = info.epoch + 1, "Joined public channel");
+        Ok(commit)
+    }
+
+    /// Join a channel from an offline invite, without any network
+    ///
+    /// Joins the group from the bundled Welcome and ratchet tree and applies
+    /// the channel's broadcast policy. The steps that need the network
+    /// (directory listing refresh, mailbox drain, key package top-up,
+    /// acceptance to the inviter, catching up on messages) are recorded in
+    /// the outbox and run by [`resume_online`](Self::resume_online). Until
+    /// then, messages sent to the channel are queued in the outbox.
+    ///
+    /// # Errors
+    ///
+    /// - `InvalidInvite` if the bundle's invite wasn't issued by its inviter
+    /// - Everything [`join_channel`](Self::join_channel) returns
+    pub async fn join_offline(&self, bundle: &OfflineInvite) -> MvpResult<ChannelId> {
+        let invite = &bundle.invite;
+        if invite.inviter != bundle.inviter.user_id {
+            return Err(MvpError::InvalidInvite(format!(
+                "Invite issued by {} but bundled with identity {}",
+                invite.inviter, bundle.inviter.user_id
+            )));
+        }
+
+        self.join_from_welcome(invite, false).await?;
+        if bundle.broadcast_policy.is_some() {
+            self.set_broadcast_policy(&invite.channel_id, bundle.broadcast_policy.clone())
+                .await?;
+        }
+
+        self.store
+            .outbox()
+            .defer_join(DeferredJoin {
+                channel_id: invite.channel_id.clone(),
+                invite_id: invite.invite_id.clone(),
+                inviter: invite.inviter.clone(),
+                inviter_peer_id: invite.inviter_peer_id.clone(),
+                bootstrap_peers: bundle.bootstrap_peers.clone(),
+                joined_at: Timestamp::now(),
+            })
+            .map_err(|e| MvpError::Store(e.to_string()))?;
+
+        info!(
+            channel_id = %invite.channel_id,
+            inviter = %bundle.inviter.user_id,
+            "Joined channel offline, network steps deferred"
+        );
+        Ok(invite.channel_id.clone())
+    }
+
+    /// Run the work queued while we had no network
+    ///
+    /// For each channel joined offline, refreshes its directory listing,
+    /// drains the delivery mailbox, runs the usual post-join network steps
+    /// and catches up on the channel's messages. A join whose listing,
+    /// mailbox or catch-up fails stays deferred for the next call. Then
+    /// queued messages are handed to the network again; those that still
+    /// can't be delivered stay queued.
+    ///
+    /// Safe to call repeatedly, e.g. whenever connectivity might be back.
+    pub async fn resume_online(&self) -> MvpResult<ResumeReport> {
+        let outbox = self.store.outbox();
+        let mut report = ResumeReport::default();
+
+        let deferred = outbox.deferred_joins();
+        if !deferred.is_empty() && self.drain_mailbox_for_resume().await {
+            for join in deferred {
+                match self.complete_deferred_join(&join).await {
+                    Ok(received) => {
+                        outbox
+                            .complete_join(&join.channel_id)
+                            .map_err(|e| MvpError::Store(e.to_string()))?;
+                        report.received.extend(received);
+                        report.joins_completed.push(join.channel_id);
+                    }
+                    Err(e) => {
+                        warn!(
+                            channel_id = %join.channel_id,
+                            error = %e,
+                            "Deferred join still can't complete"
+                        );
+                    }
+                }
+            }
+        }
+
+        report.delivered = self.flush_outbox().await?;
+        report.still_queued = outbox.len();
+
+        info!(
+            joins_completed = report.joins_completed.len(),
+            delivered = report.delivered,
+            still_queued = report.still_queued,
+            received = report.received.len(),
+            "Resumed online"
+        );
+        Ok(report)
+    }
+
+    /// Whether a channel was joined offline and its network steps haven't run
+    pub fn is_join_deferred(&self, channel_id: &ChannelId) -> bool {
+        self.store.outbox().is_deferred(channel_id)
+    }
+
+    /// Number of sent messages waiting in the outbox
+    pub fn outbox_len(&self) -> usize {
+        self.store.outbox().len()
+    }
+
+    /// Sent messages waiting in the outbox, oldest first
+    pub fn outbox_messages(&self) -> Vec<OutgoingEnvelope> {
+        self.store.outbox().all()
+    }
+
+    /// Hand queued messages to the network again, returning how many went out
+    ///
+    /// Messages go out in the order they were queued. Once one of a
+    /// channel's messages can't be delivered, the channel's later messages
+    /// wait too, so they can't overtake it. Channels whose join is deferred
+    /// are skipped. Each delivered message is removed from the outbox and
+    /// reported with [`ChannelEventKind::MessageDelivered`].
+    pub async fn flush_outbox(&self) -> MvpResult<usize> {
+        let _flush = self.outbox_flush.lock().await;
+        let outbox = self.store.outbox();
+        let mut blocked = HashSet::new();
+        let mut delivered = 0;
+
+        for envelope in outbox.all() {
+            // Not announced yet; delivering now would skip the join steps
+            if blocked.contains(&envelope.channel_id) || outbox.is_deferred(&envelope.channel_id) {
+                continue;
+            }
+            match self
+                .deliver(
+                    &envelope.channel_id,
+                    envelope.broadcast,
+                    &envelope.ciphertext,
+                    &envelope.sender,
+                )
+                .await
+            {
+                Ok(()) => {
+                    outbox.remove(envelope.id).map_err(|e| MvpError::Store(e.to_string()))?;
+                    delivered += 1;
+                    record_counter("mvp.outbox.delivered", 1);
+                    let message_id = MessageId::from_ciphertext(&envelope.ciphertext);
+                    let kind = ChannelEventKind::MessageDelivered { message_id };
+                    self.emit_event(&envelope.channel_id, Some(envelope.sender), kind).await;
+                }
+                Err(e) => {
+                    let attempts = outbox
+                        .record_attempt(envelope.id)
+                        .map_err(|e| MvpError::Store(e.to_string()))?;
+                    debug!(
+                        channel_id = %envelope.channel_id,
+                        attempts,
+                        error = %e,
+                        "Queued message still undeliverable"
+                    );
+                    blocked.insert(envelope.channel_id);
+                }
+            }
+        }
+
+        Ok(delivered)
+    }
+
+    /// Retry the outbox in the background until it's empty
+    ///
+    /// Every peer connection reported on `connectivity` triggers a flush
+    /// straight away. Otherwise flushes are retried after `retry`, doubling
+    /// after each flush that leaves messages queued up to `max_retry`, and
+    /// back to `retry` once the outbox empties or a peer connects.
+    ///
+    /// # Arguments
+    /// * `connectivity` - Peer connections, e.g. from
+    ///   [`NetworkLayer::peer_connections`](super::network::NetworkLayer::peer_connections)
+    /// * `retry` - Delay before the first retry
+    /// * `max_retry` - Longest delay between retries
+    ///
+    /// # Returns
+    /// JoinHandle for the background task
+    pub fn spawn_outbox_flusher(
+        self: Arc<Self>,
+        mut connectivity: broadcast::Receiver<crate::core_router::session_manager::PeerId>,
+        retry: Duration,
+        max_retry: Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut delay = retry;
+            let mut connected = true;
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    event = connectivity.recv(), if connected => match event {
+                        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => delay = retry,
+                        Err(broadcast::error::RecvError::Closed) => {
+                            connected = false;
+                            continue;
+                        }
+                    },
+                }
+
+                if self.outbox_len() == 0 {
+                    delay = retry;
+                    continue;
+                }
+                match self.flush_outbox().await {
+                    Ok(_) if self.outbox_len() == 0 => delay = retry,
+                    Ok(_) => delay = (delay * 2).min(max_retry),
+                    Err(e) => {
+                        warn!(error = %e, "Outbox flush failed");
+                        delay = (delay * 2).min(max_retry);
+                    }
+                }
+            }
+        })
+    }
+
+    /// Drain the delivery mailbox before completing deferred joins
+    ///
+    /// Welcomes and commits queued there may be what brings an offline
+    /// joined channel up to date. Returns false if the mailbox failed.
+    async fn drain_mailbox_for_resume(&self) -> bool {
+        if self.deliveries.is_none() {
+            return true;
+        }
+        match self.poll_mailbox().await {
+            Ok(_) => true,
+            Err(e) => {
+                warn!(error = %e, "Failed to drain delivery mailbox");
+                false
+            }
+        }
+    }
+
+    /// Network steps of a join made from an offline invite
+    async fn complete_deferred_join(&self, join: &DeferredJoin) -> MvpResult<Vec<ChatMessage>> {
+        if let Some(ref directory) = self.directory {
+            if directory
+                .lookup(&join.channel_id)?
+                .is_some_and(|listing| listing.is_tombstone())
+            {
+                warn!(
+                    channel_id = %join.channel_id,
+                    "Channel joined offline has since been deleted"
+                );
+            }
+        }
+
+        let mut received = self.sync_channel(&join.channel_id).await?;
+        if self.mailbox.is_some() && self.broadcast_policy(&join.channel_id).await.is_some() {
+            received.extend(self.fetch_broadcast(&join.channel_id).await?);
+        }
+
+        self.announce_join(
+            &join.channel_id,
+            &join.invite_id,
+            &join.inviter,
+            join.inviter_peer_id.as_deref(),
+        )
+        .await;
+        Ok(received)
+    }
+
+    async fn join_channel_inner(&self, invite: &InviteToken, force: bool) -> MvpResult<ChannelId> {
+        self.join_from_welcome(invite, force).await?;
+        self.announce_join(
+            &invite.channel_id,
+            &invite.invite_id,
+            &invite.inviter,
+            invite.inviter_peer_id.as_deref(),
+        )
+        .await;
+
+        Ok(invite.channel_id.clone())
+    }
+
+    /// Join the MLS group and store the channel, without any network steps
+    async fn join_from_welcome(&self, invite: &InviteToken, force: bool) -> MvpResult<()> {
+        info!(
+            channel_id = %invite.channel_id,
+            user_id = %self.identity.user_id,
+            "Joining channel"
+        );
+
+        // Check if invite is expired
+        if invite.is_expired() {
+            warn!(invite_id = %invite.invite_id, "Invite has expired");
+            return Err(MvpError::InviteExpired(invite.invite_id.clone()));
+        }
+
+        // Use the embedded ratchet tree, or fetch the one the invite points to
+        let ratchet_tree_vec = match (&invite.ratchet_tree, &invite.external_tree) {
+            (None, Some(external)) => Some(self.fetch_ratchet_tree(invite, external)?),
+            (tree, _) => tree.clone(),
+        };
+
+        let estimate =
+            estimate_join(invite.welcome_blob.len(), ratchet_tree_vec.as_ref().map_or(0, Vec::len));
+        if force {
+            warn!(estimate, "Skipping free disk space check for join");
+        } else {
+            self.preflight.check(self.store.data_dir(), estimate)?;
+        }
+
+        // Join MLS group from Welcome
+        debug!("Joining MLS group from Welcome");
+
+        let group_id = self.mls_service.join_group(&invite.welcome_blob, ratchet_tree_vec).await?;
+
+        // Verify group ID matches channel ID
+        let expected_group_id = GroupId::new(invite.channel_id.0.as_bytes().to_vec());
+        if group_id != expected_group_id {
+            warn!(
+                expected = ?expected_group_id,
+                got = ?group_id,
+                "Group ID mismatch"
+            );
+            return Err(MvpError::Internal("Group ID mismatch after join".to_string()));
+        }
+
+        // Fetch or create channel metadata
+        debug!(channel_id = %invite.channel_id, "Fetching channel metadata");
+        match self.store.get_channel(&invite.channel_id) {
+            Ok(Some(_channel)) => {
+                // Channel exists, we're good
+                debug!("Channel metadata found in local store");
+            }
+            Ok(None) => {
+                // Create placeholder channel using metadata from invite
+                debug!("Channel metadata not found, creating from invite");
+                let mut channel = Channel::new(
+                    invite.channel_id.clone(),
+                    invite.channel_name.clone(),
+                    ChannelType::Text,
+                    invite.inviter.clone(),
+                    Timestamp::now(),
+                    self.identity.node_id.clone(),
+                );
+                if invite.is_public {
+                    channel.set_public(true, &self.identity.node_id);
+                }
+                if let Some(ref padding) = invite.padding {
+                    channel.set_padding(padding.clone(), &self.identity.node_id);
+                }
+                self.store.store_channel(&channel).map_err(|e| MvpError::Store(e.to_string()))?;
+            }
+            Err(e) => {
+                return Err(MvpError::Store(e.to_string()));
+            }
+        }
+        self.sync_member_devices(&invite.channel_id).await;
+        self.sync_channel_metadata(&invite.channel_id).await;
+
+        info!(
+            channel_id = %invite.channel_id,
+            "Successfully joined channel"
+        );
+
+        Ok(())
+    }
+
+    /// Network steps after joining a channel
+    ///
+    /// Tops up key packages, tells the inviter the invite was used and
+    /// looks up the other members' peers. Failures are logged: the join
+    /// itself has already succeeded.
+    async fn announce_join(
+        &self,
+        channel_id: &ChannelId,
+        invite_id: &str,
+        inviter: &UserId,
+        inviter_peer_id: Option<&[u8]>,
+    ) {
+        // Joining may have used up one of our published key packages
+        if self.key_packages.is_some() {
+            if let Err(e) = self.replenish_key_packages().await {
+                warn!(error = %e, "Failed to replenish key packages");
+            }
+        }
+
+        // Register inviter's peer ID if provided in invite (invite-based peer discovery)
+        if let (Some(ref network), Some(inviter_peer_id)) = (&self.network, inviter_peer_id) {
+            debug!("Registering inviter's peer ID from invite");
+            let peer_id = crate::core_router::session_manager::PeerId(inviter_peer_id.to_vec());
+            network
+                .register_channel_member(channel_id, inviter.clone(), peer_id.clone())
+                .await;
+
+            // Let the inviter check the invite is still good
+            if !invite_id.is_empty() {
+                if let Err(e) = network
+                    .send_invite_acceptance(&peer_id, channel_id, invite_id, &self.identity.user_id)
+                    .await
+                {
+                    warn!(error = %e, "Failed to notify inviter of invite acceptance");
+                }
+            }
+        }
+
+        // Trigger peer discovery if network is enabled
+        if let Some(ref network) = self.network {
+            debug!("Network enabled, triggering peer discovery for channel members");
+            if let Err(e) = self.discover_and_register_peers(channel_id, network).await {
+                warn!(error = %e, "Failed to discover peers after joining channel");
+            }
+        }
+    }
+
+    /// Send a message to a channel
+    ///
+    /// This encrypts the message via MLS and returns the ciphertext.
+    /// If the network can't be reached, the message is kept in the outbox
+    /// and sent later; see [`flush_outbox`](Self::flush_outbox).
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Target channel
+    /// * `plaintext` - Message body
+    ///
+    /// # Returns
+    ///
+    /// The encrypted message ciphertext
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let ciphertext = manager.send_message(&channel_id, b"Hello!").await?;
+    /// ```
+    pub async fn send_message(
+        &self,
+        channel_id: &ChannelId,
+        plaintext: &[u8],
+    ) -> MvpResult<Vec<u8>> {
+        debug!(
+            channel_id = %channel_id,
+            size = plaintext.len(),
+            "Sending message"
+        );
+
+        self.ensure_writable(channel_id).await?;
+
+        // Only publishers post in broadcast channels
+        let policy = self.broadcast_policy(channel_id).await;
+        if let Some(ref policy) = policy {
+            if !policy.is_publisher(&self.identity.user_id) {
+                return Err(MvpError::InvalidOperation(
+                    "Only publishers can post in a broadcast channel".to_string(),
+                ));
+            }
+        }
+
+        // Apply the channel's padding for traffic analysis resistance
+        let padded_plaintext = self
+            .channel_padding(channel_id)?
+            .pad(plaintext)
+            .map_err(|e| MvpError::InvalidOperation(format!("Failed to pad message: {}", e)))?;
+
+        debug!(
+            original_size = plaintext.len(),
+            padded_size = padded_plaintext.len(),
+            "Message padded for privacy"
+        );
+
+        // Get group ID
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+
+        // Encrypt padded message via MLS service
+        let ciphertext =
+            self.mls_service.send_message(&group_id, &padded_plaintext).await.map_err(
+                |e| match e {
+                    MlsError::GroupNotFound(_) => MvpError::ChannelNotFound(channel_id.to_string()),
+                    other => MvpError::Mls(other),
+                },
+            )?;
+
+        // Our own messages are part of the channel history too
+        let mut message =
+            ChatMessage::new(channel_id.clone(), self.identity.user_id.clone(), plaintext.to_vec());
+        message.message_id = MessageId::from_ciphertext(&ciphertext);
+        let message_id = message.message_id.clone();
+        let sent_at = message.timestamp;
+        self.store_message(message).await?;
+
+        // Broadcast readers don't acknowledge, so only track other channels
+        if policy.is_none() {
+            self.track_delivery(channel_id, &group_id, message_id.clone(), sent_at).await;
+            self.append_to_feed(&group_id, &message_id, &ciphertext, None).await?;
+        }
+
+        self.deliver_or_defer(channel_id, &ciphertext).await?;
+
+        info!(
+            channel_id = %channel_id,
+            plaintext_size = plaintext.len(),
+            ciphertext_size = ciphertext.len(),
+            network_broadcast = self.network.is_some(),
+            "Message encrypted successfully"
+        );
+
+        Ok(ciphertext)
+    }
+
+    /// Fan a sent message's ciphertext out to the channel
+    ///
+    /// Failures that [`is_unreachable`] recognises leave the message worth
+    /// queueing for another try.
+    async fn deliver(
+        &self,
+        channel_id: &ChannelId,
+        broadcast: bool,
+        ciphertext: &[u8],
+        sender: &UserId,
+    ) -> MvpResult<()> {
+        // Broadcast channels fan out through the mailbox: one write,
+        // readers pull, instead of a send per member
+        if let (true, Some(mailbox)) = (broadcast, &self.mailbox) {
+            let seq = mailbox.publish(channel_id, sender, ciphertext.to_vec()).await?;
+            record_counter("mvp.broadcast.mailbox_published", 1);
+            debug!(channel_id = %channel_id, seq, "Published message to broadcast mailbox");
+        } else if let Some(network) = &self.network {
+            // If network layer is enabled, broadcast to channel members
+            debug!(
+                channel_id = %channel_id,
+                "Broadcasting message over network"
+            );
+
+            network.broadcast_message(channel_id, ciphertext.to_vec(), sender).await?;
+        } else {
+            debug!(
+                channel_id = %channel_id,
+                "Network layer not enabled, message not broadcast"
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Append a sent message to the channel's feed, if we have one
+    ///
+    /// The entry expires after `ttl`, or the feed's own TTL if `None`. In
+    /// anonymity mode it goes through the feed publisher instead.
+    async fn append_to_feed(
+        &self,
+        group_id: &GroupId,
+        message_id: &MessageId,
+        ciphertext: &[u8],
+        ttl: Option<Duration>,
+    ) -> MvpResult<()> {
+        let publisher =
+            self.feed_publisher.as_ref().filter(|_| self.config.features.anonymity_mode);
+        if self.feed.is_none() && publisher.is_none() {
+            return Ok(());
+        }
+
+        let epoch = self.mls_service.get_epoch(group_id).await?;
+        let sender_key = self.sender_key(group_id, epoch).await?;
+        let sealed = sealed_sender::seal_sender(&self.identity.as_bytes(), &sender_key, epoch)?;
+        let envelope = EncryptedEnvelope::new(
+            group_id.clone(),
+            epoch,
+            sealed,
+            ciphertext.to_vec(),
+            EnvelopeType::Application,
+        );
+
+        let payload = envelope.to_bytes()?;
+        if let Some(publisher) = publisher {
+            publisher.append(group_id, &message_id.0, payload, ttl).await?;
+            record_counter("mvp.feed.appended_anonymously", 1);
+            debug!(group_id = %group_id, "Appended message to channel feed through publisher");
+            return Ok(());
+        }
+
+        let Some(ref feed) = self.feed else {
+            return Ok(());
+        };
+        let seq = match ttl {
+            Some(ttl) => feed.append_with_ttl(group_id, &message_id.0, payload, ttl),
+            None => feed.append(group_id, &message_id.0, payload),
+        }
+        .map_err(MvpError::Dht)?;
+        record_counter("mvp.feed.appended", 1);
+        debug!(group_id = %group_id, seq, "Appended message to channel feed");
+        Ok(())
+    }
+
+    /// Key sealing senders in the given epoch of a group
+    async fn sender_key(&self, group_id: &GroupId, epoch: u64) -> MvpResult<[u8; 32]> {
+        Ok(self.mls_service.sender_key(group_id, epoch).await?)
+    }
+
+    /// Fetch, decrypt and store messages appended to a channel's feed
+    ///
+    /// Reads the feed from where the last sync stopped. Envelopes whose
+    /// message is already in the channel history (our own, or a copy
+    /// stored twice) are skipped, and so are ones from past epochs, which
+    /// can no longer be decrypted. An envelope from a later epoch ends the
+    /// pass: the commit that gets us there hasn't been processed yet.
+    ///
+    /// The next sync starts again at the first missing entry or the first
+    /// envelope left unread, so entries that show up late are still picked
+    /// up; those read in between are skipped as duplicates. Without a
+    /// channel feed there is nothing to sync.
+    ///
+    /// # Returns
+    ///
+    /// Messages decrypted by this pass
+    pub async fn sync_channel(&self, channel_id: &ChannelId) -> MvpResult<Vec<ChatMessage>> {
+        let Some(ref feed) = self.feed else {
+            return Ok(Vec::new());
+        };
+
+        // Decrypting consumes MLS keys, so don't race the inbox
+        let lock = self.inbox_lock(channel_id).await;
+        let _guard = lock.lock().await;
+
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let epoch = self.mls_service.get_epoch(&group_id).await.map_err(|e| match e {
+            MlsError::GroupNotFound(_) => MvpError::ChannelNotFound(channel_id.to_string()),
+            other => MvpError::Mls(other),
+        })?;
+        let sender_key = self.sender_key(&group_id, epoch).await?;
+
+        let from = self.feed_cursors.lock().await.get(channel_id).copied().unwrap_or(0);
+        let head = feed.head(&group_id);
+        let mut next = from;
+        let mut resume_at = None;
+        let mut decrypted = Vec::new();
+
+        for entry in feed.fetch(&group_id, from) {
+            if entry.seq > next && resume_at.is_none() {
+                debug!(channel_id = %channel_id, seq = next, "Gap in channel feed");
+                resume_at = Some(next);
+            }
+            next = entry.seq + 1;
+
+            let message_id = MessageId(entry.message_id);
+            if self.has_message(channel_id, &message_id).await {
+                continue;
+            }
+
+            let envelope = match EncryptedEnvelope::from_bytes(&entry.payload) {
+                Ok(envelope) if envelope.group_id == group_id => envelope,
+                _ => {
+                    warn!(
+                        channel_id = %channel_id,
+                        seq = entry.seq,
+                        "Skipping unreadable feed entry"
+                    );
+                    continue;
+                }
+            };
+            if envelope.epoch > epoch {
+                resume_at.get_or_insert(entry.seq);
+                break;
+            }
+            if envelope.epoch < epoch {
+                debug!(
+                    channel_id = %channel_id,
+                    seq = entry.seq,
+                    "Skipping feed entry from a past epoch"
+                );
+                continue;
+            }
+
+            match self.open_feed_envelope(channel_id, &envelope, &sender_key).await {
+                Ok(Some((sender, plaintext))) => {
+                    if self.apply_control_message(channel_id, &sender, &plaintext).await {
+                        continue;
+                    }
+                    let mut message = ChatMessage::new(channel_id.clone(), sender, plaintext);
+                    message.message_id = message_id;
+                    mark_attachment(&mut message);
+                    self.store_message(message.clone()).await?;
+                    let message = self.apply_held_changes(message).await;
+                    self.apply_parked_reactions(&message.message_id).await;
+
+                    self.queue_ack(&message).await;
+                    self.emit_message(message.clone()).await;
+                    decrypted.push(message);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    // Typically a message we already got over the network
+                    debug!(
+                        channel_id = %channel_id,
+                        seq = entry.seq,
+                        error = %e,
+                        "Skipping undecryptable feed entry"
+                    );
+                }
+            }
+        }
+        if next < head && resume_at.is_none() {
+            resume_at = Some(next);
+        }
+
+        self.feed_cursors
+            .lock()
+            .await
+            .insert(channel_id.clone(), resume_at.unwrap_or(head.max(from)));
+
+        if !decrypted.is_empty() {
+            record_counter("mvp.feed.synced", decrypted.len() as u64);
+            debug!(
+                channel_id = %channel_id,
+                count = decrypted.len(),
+                "Synced messages from channel feed"
+            );
+        }
+        Ok(decrypted)
+    }
+
+    /// Unseal the sender and decrypt the message of a feed envelope
+    async fn open_feed_envelope(
+        &self,
+        channel_id: &ChannelId,
+        envelope: &EncryptedEnvelope,
+        sender_key: &[u8; 32],
+    ) -> MvpResult<Option<(UserId, Vec<u8>)>> {
+        let sender =
+            sealed_sender::unseal_sender(&envelope.sealed_sender, sender_key, envelope.epoch)?;
+        let sender = String::from_utf8(sender)
+            .map_err(|_| MvpError::InvalidMessage("Sender is not valid UTF-8".to_string()))?;
+
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let Some(padded_plaintext) =
+            self.mls_service.process_message(&group_id, &envelope.payload).await?
+        else {
+            return Ok(None);
+        };
+        let plaintext = crate::core_mls::padding::unpad_message(&padded_plaintext)
+            .map_err(|e| MvpError::InvalidMessage(format!("Failed to unpad message: {}", e)))?;
+
+        Ok(Some((UserId(sender), plaintext)))
+    }
+
+    /// Whether a message is already in a channel's history
+    async fn has_message(&self, channel_id: &ChannelId, message_id: &MessageId) -> bool {
+        self.messages
+            .read()
+            .await
+            .get(channel_id)
+            .is_some_and(|messages| messages.iter().any(|m| &m.message_id == message_id))
+    }
+
+    /// Receive and decrypt a message
+    ///
+    /// This decrypts an MLS message and returns the plaintext.
+    ///
+    /// # Arguments
+    ///
+    /// * `ciphertext` - Encrypted message bytes
+    ///
+    /// # Returns
+    ///
+    /// The decrypted message plaintext
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let plaintext = manager.receive_message(&ciphertext).await?;
+    /// ```
+    pub async fn receive_message(&self, ciphertext: &[u8]) -> MvpResult<Vec<u8>> {
+        debug!(size = ciphertext.len(), "Receiving message");
+
+        // For MVP, we need to try all groups until we find the right one
+        let groups = self.mls_service.list_groups().await;
+
+        for group_id in groups.iter() {
+            // Try to process message with this group
+            match self.mls_service.process_message(group_id, ciphertext).await {
+                Ok(Some(padded_plaintext)) => {
+                    // Remove padding to get original message
+                    let plaintext = crate::core_mls::padding::unpad_message(&padded_plaintext)
+                        .map_err(|e| {
+                            MvpError::InvalidMessage(format!("Failed to unpad message: {}", e))
+                        })?;
+
+                    info!(
+                        group_id = ?group_id,
+                        padded_size = padded_plaintext.len(),
+                        plaintext_size = plaintext.len(),
+                        "Message decrypted and unpadded successfully"
+                    );
+                    return Ok(plaintext);
+                }
+                Ok(None) => {
+                    // Commit or proposal, continue trying
+                }
+                Err(_e) => {
+                    // Failed with this group, try next
+                }
+            }
+        }
+
+        warn!("Failed to decrypt message with any of {} groups", groups.len());
+        Err(MvpError::InvalidMessage("Could not decrypt message".to_string()))
+    }
+
+    /// Subscribe to decrypted incoming messages
+    ///
+    /// Receives every message decrypted by
+    /// [`handle_incoming_message`](Self::handle_incoming_message) or
+    /// [`process_pending`](Self::process_pending) after subscribing.
+    pub fn subscribe_messages(&self) -> broadcast::Receiver<ChatMessage> {
+        self.incoming.subscribe()
+    }
+
+    /// Subscribe to everything happening in this replica's channels, in order
+    ///
+    /// Covers epoch changes, membership changes, decrypted messages,
+    /// metadata changes, accepted invites, messages dropped as
+    /// undecryptable, typing indicators and presence heartbeats.
+    ///
+    /// For each channel, events arrive in the order implied by its MLS
+    /// epochs: the joins and leaves of a commit come before any message
+    /// from the epoch it starts, and `(epoch, seq)` strictly increases.
+    /// A subscriber that falls too far behind gets `RecvError::Lagged`;
+    /// processing never waits for it. See [`events`](crate::core_mvp::events).
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ChannelEvent> {
+        self.events.subscribe()
+    }
+
+    /// Tell the other members we're typing
+    ///
+    /// Sent at most once every [`TYPING_INTERVAL`] per channel; calls in
+    /// between are dropped, so this can be called on every keystroke.
+    ///
+    /// # Returns
+    ///
+    /// The encrypted indicator, already sent to the channel, or `None` if
+    /// one was sent too recently
+    pub async fn send_typing(&self, channel_id: &ChannelId) -> MvpResult<Option<Vec<u8>>> {
+        {
+            let mut typing_sent = self.typing_sent.lock().await;
+            if typing_sent.get(channel_id).is_some_and(|sent| sent.elapsed() < TYPING_INTERVAL) {
+                return Ok(None);
+            }
+            typing_sent.insert(channel_id.clone(), Instant::now());
+        }
+        self.send_ephemeral(channel_id, EphemeralKind::Typing).await.map(Some)
+    }
+
+    /// Tell the other members we're online
+    ///
+    /// Meant to be sent every
+    /// [`PRESENCE_INTERVAL`](crate::core_mvp::ephemeral::PRESENCE_INTERVAL)
+    /// while a client is running.
+    ///
+    /// # Returns
+    ///
+    /// The encrypted heartbeat, already sent to the channel
+    pub async fn send_presence(&self, channel_id: &ChannelId) -> MvpResult<Vec<u8>> {
+        self.send_ephemeral(channel_id, EphemeralKind::Presence).await
+    }
+
+    /// Members heard from in the last [`PRESENCE_TIMEOUT`], by user ID
+    pub async fn present_members(&self, channel_id: &ChannelId) -> Vec<UserId> {
+        let presence = self.presence.read().await;
+        let mut members: Vec<UserId> = presence
+            .get(channel_id)
+            .map(|seen| {
+                seen.iter()
+                    .filter(|(_, at)| at.elapsed() < PRESENCE_TIMEOUT)
+                    .map(|(user, _)| user.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+        members.sort();
+        members
+    }
+
+    /// Encrypt and send a typing indicator or presence heartbeat
+    ///
+    /// Not stored anywhere on our side, nor queued for channels joined
+    /// offline: by the time they're announced it would be stale.
+    async fn send_ephemeral(
+        &self,
+        channel_id: &ChannelId,
+        kind: EphemeralKind,
+    ) -> MvpResult<Vec<u8>> {
+        self.ensure_writable(channel_id).await?;
+        if self.broadcast_policy(channel_id).await.is_some() {
+            return Err(MvpError::InvalidOperation(
+                "Broadcast channels don't carry typing or presence".to_string(),
+            ));
+        }
+
+        let ephemeral =
+            EphemeralMessage::new(channel_id.clone(), self.identity.user_id.clone(), kind);
+        let padded_plaintext = self
+            .channel_padding(channel_id)?
+            .pad(&ephemeral.encode()?)
+            .map_err(|e| MvpError::InvalidOperation(format!("Failed to pad message: {}", e)))?;
+
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let ciphertext =
+            self.mls_service.send_message(&group_id, &padded_plaintext).await.map_err(
+                |e| match e {
+                    MlsError::GroupNotFound(_) => MvpError::ChannelNotFound(channel_id.to_string()),
+                    other => MvpError::Mls(other),
+                },
+            )?;
+
+        if !self.store.outbox().is_deferred(channel_id) {
+            let message_id = MessageId::from_ciphertext(&ciphertext);
+            self.append_to_feed(&group_id, &message_id, &ciphertext, Some(EPHEMERAL_FEED_TTL))
+                .await?;
+            self.deliver(channel_id, false, &ciphertext, &self.identity.user_id).await?;
+        }
+
+        debug!(channel_id = %channel_id, kind = ?kind, "Sent ephemeral message");
+        Ok(ciphertext)
+    }
+
+    /// Turn a received typing indicator or heartbeat into an event
+    ///
+    /// Members only signal for themselves; stale signals (e.g. from a feed
+    /// caught up on late) are dropped.
+    async fn receive_ephemeral(
+        &self,
+        channel_id: &ChannelId,
+        sender: &UserId,
+        ephemeral: EphemeralMessage,
+    ) {
+        if &ephemeral.channel_id != channel_id || &ephemeral.user != sender {
+            warn!(
+                channel_id = %channel_id,
+                sender = %sender,
+                "Dropping ephemeral message sent for someone else"
+            );
+            return;
+        }
+        if ephemeral.user == self.identity.user_id || ephemeral.is_stale() {
+            return;
+        }
+
+        self.presence
+            .write()
+            .await
+            .entry(channel_id.clone())
+            .or_default()
+            .insert(ephemeral.user.clone(), Instant::now());
+
+        let kind = match ephemeral.kind {
+            EphemeralKind::Typing => ChannelEventKind::Typing(ephemeral.user.clone()),
+            EphemeralKind::Presence => ChannelEventKind::Presence(ephemeral.user.clone()),
+        };
+        self.emit_event(channel_id, Some(ephemeral.user), kind).await;
+    }
+
+    /// Queue ordering one channel's events
+    async fn event_queue(&self, channel_id: &ChannelId) -> Arc<Mutex<EventQueue>> {
+        self.event_queues.lock().await.entry(channel_id.clone()).or_default().clone()
+    }
+
+    /// Emit the membership changes since the channel's last emitted roster
+    async fn emit_membership_events(&self, channel_id: &ChannelId) {
+        let queue = self.event_queue(channel_id).await;
+        let mut queue = queue.lock().await;
+        self.emit_roster(channel_id, &mut queue).await;
+    }
+
+    /// Emit a decrypted message, after any membership changes before it
+    async fn emit_message(&self, message: ChatMessage) {
+        let queue = self.event_queue(&message.channel_id).await;
+        let mut queue = queue.lock().await;
+        self.emit_roster(&message.channel_id, &mut queue).await;
+
+        // No subscribers is fine; the message is already stored
+        let _ = self.incoming.send(message.clone());
+        let _ = self.events.send(queue.message(message));
+    }
+
+    /// Emit an event other than a message, after any membership changes before it
+    async fn emit_event(
+        &self,
+        channel_id: &ChannelId,
+        actor: Option<UserId>,
+        kind: ChannelEventKind,
+    ) {
+        let queue = self.event_queue(channel_id).await;
+        let mut queue = queue.lock().await;
+        self.emit_roster(channel_id, &mut queue).await;
+        let _ = self.events.send(queue.event(channel_id, actor, kind));
+    }
+
+    /// Emit joins and leaves from the current MLS roster (caller holds the queue)
+    async fn emit_roster(&self, channel_id: &ChannelId, queue: &mut EventQueue) {
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let metadata = match self.mls_service.get_metadata(&group_id).await {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                debug!(channel_id = %channel_id, error = ?e, "No roster to emit events from");
+                return;
+            }
+        };
+
+        let roster = metadata.members.iter().map(|m| member_device(m).user_id).collect();
+        for event in queue.observe_roster(channel_id, metadata.epoch, roster) {
+            let _ = self.events.send(event);
+        }
+    }
+
+    /// Handle an encrypted message received from the network
+    ///
+    /// The ciphertext is queued in the store's inbox before decryption is
+    /// attempted, so it survives a restart. If it cannot be decrypted yet
+    /// (e.g. it belongs to an epoch whose commit has not arrived), it stays
+    /// queued and is retried by [`process_pending`](Self::process_pending).
+    ///
+    /// # Returns
+    ///
+    /// The decrypted message, or `None` if it was queued for later or
+    /// carried no chat message (a commit, read receipt, edit, deletion or
+    /// reaction)
+    pub async fn handle_incoming_message(
+        &self,
+        incoming: IncomingMessage,
+    ) -> MvpResult<Option<ChatMessage>> {
+        let lock = self.inbox_lock(&incoming.channel_id).await;
+        let _guard = lock.lock().await;
+
+        let inbox = self.store.inbox();
+        let id = inbox
+            .enqueue(incoming.channel_id, incoming.sender_id, incoming.ciphertext)
+            .map_err(|e| MvpError::Store(e.to_string()))?;
+        let Some(envelope) = inbox.get(id) else {
+            return Ok(None);
+        };
+
+        self.try_process_pending(&envelope).await
+    }
+
+    /// Retry queued messages for a channel, oldest first
+    ///
+    /// Call this on startup to catch up on messages received but not
+    /// processed before the client exited, and after a commit advances
+    /// the channel's epoch.
+    ///
+    /// # Returns
+    ///
+    /// Messages decrypted by this pass
+    pub async fn process_pending(&self, channel_id: &ChannelId) -> MvpResult<Vec<ChatMessage>> {
+        let lock = self.inbox_lock(channel_id).await;
+        let _guard = lock.lock().await;
+
+        let mut decrypted = Vec::new();
+        for envelope in self.store.inbox().pending(channel_id) {
+            if let Some(message) = self.try_process_pending(&envelope).await? {
+                decrypted.push(message);
+            }
+        }
+
+        if !decrypted.is_empty() {
+            debug!(
+                channel_id = %channel_id,
+                count = decrypted.len(),
+                "Processed pending messages"
+            );
+        }
+
+        Ok(decrypted)
+    }
+
+    /// Lock serializing inbox processing for one channel
+    ///
+    /// Channels don't share a lock, so one slow channel never holds up
+    /// another's inbox.
+    async fn inbox_lock(&self, channel_id: &ChannelId) -> Arc<Mutex<()>> {
+        self.inbox_locks.lock().await.entry(channel_id.clone()).or_default().clone()
+    }
+
+    /// Park a message for a later [`process_pending`](Self::process_pending)
+    async fn park_incoming_message(&self, incoming: IncomingMessage) -> MvpResult<()> {
+        let lock = self.inbox_lock(&incoming.channel_id).await;
+        let _guard = lock.lock().await;
+        self.store
+            .inbox()
+            .enqueue(incoming.channel_id, incoming.sender_id, incoming.ciphertext)
+            .map_err(|e| MvpError::Store(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Try to process one queued envelope (caller holds the channel's inbox lock)
+    async fn try_process_pending(
+        &self,
+        envelope: &PendingEnvelope,
+    ) -> MvpResult<Option<ChatMessage>> {
+        let inbox = self.store.inbox();
+        let group_id = GroupId::new(envelope.channel_id.0.as_bytes().to_vec());
+
+        let padded_plaintext =
+            match self.mls_service.process_message(&group_id, &envelope.ciphertext).await {
+                Ok(Some(padded_plaintext)) => padded_plaintext,
+                Ok(None) => {
+                    // Commit or proposal delivered on the message path
+                    inbox.remove(envelope.id).map_err(|e| MvpError::Store(e.to_string()))?;
+                    return Ok(None);
+                }
+                Err(MlsError::OwnLeafMessage { epoch }) => {
+                    inbox.remove(envelope.id).map_err(|e| MvpError::Store(e.to_string()))?;
+                    // A redelivered copy of something we sent is already in history
+                    let message_id = MessageId::from_ciphertext(&envelope.ciphertext);
+                    if !self.has_message(&envelope.channel_id, &message_id).await {
+                        self.report_identity_in_use_elsewhere(&envelope.channel_id, epoch);
+                    }
+                    return Ok(None);
+                }
+                Err(e) => {
+                    let attempts = inbox
+                        .record_attempt(envelope.id)
+                        .map_err(|e| MvpError::Store(e.to_string()))?;
+                    if attempts >= MAX_PENDING_ATTEMPTS {
+                        warn!(
+                            channel_id = %envelope.channel_id,
+                            attempts,
+                            error = %e,
+                            "Dropping undecryptable message"
+                        );
+                        inbox.remove(envelope.id).map_err(|e| MvpError::Store(e.to_string()))?;
+                        let kind = ChannelEventKind::DecryptionFailed { error: e.to_string() };
+                        self.emit_event(&envelope.channel_id, Some(envelope.sender.clone()), kind)
+                            .await;
+                    } else {
+                        debug!(
+                            channel_id = %envelope.channel_id,
+                            attempts,
+                            error = %e,
+                            "Message not decryptable yet, keeping it queued"
+                        );
+                    }
+                    return Ok(None);
+                }
+            };
+
+        // Processing consumed the MLS key, so the envelope is done either way
+        inbox.remove(envelope.id).map_err(|e| MvpError::Store(e.to_string()))?;
+
+        let plaintext = crate::core_mls::padding::unpad_message(&padded_plaintext)
+            .map_err(|e| MvpError::InvalidMessage(format!("Failed to unpad message: {}", e)))?;
+
+        if self
+            .apply_control_message(&envelope.channel_id, &envelope.sender, &plaintext)
+            .await
+        {
+            return Ok(None);
+        }
+
+        let mut message =
+            ChatMessage::new(envelope.channel_id.clone(), envelope.sender.clone(), plaintext);
+        message.message_id = MessageId::from_ciphertext(&envelope.ciphertext);
+        message.timestamp = envelope.received_at;
+        message.language = message.body_as_string().as_deref().and_then(detect_language);
+        mark_attachment(&mut message);
+        self.store_message(message.clone()).await?;
+        let message = self.apply_held_changes(message).await;
+        self.apply_parked_reactions(&message.message_id).await;
+
+        self.queue_ack(&message).await;
+        self.emit_message(message.clone()).await;
+
+        Ok(Some(message))
+    }
+
+    /// Apply a read receipt, acknowledgement, message change, reaction or
+    /// ephemeral message
+    ///
+    /// These travel as application messages but aren't part of the
+    /// history themselves.
+    ///
+    /// # Returns
+    ///
+    /// Whether the plaintext was one of them rather than a chat message
+    async fn apply_control_message(
+        &self,
+        channel_id: &ChannelId,
+        sender: &UserId,
+        plaintext: &[u8],
+    ) -> bool {
+        if let Some(marker) = decode_read_receipt(plaintext) {
+            self.apply_read_receipt(channel_id, sender, marker).await;
+        } else if let Some(ack) = DeliveryAck::decode(plaintext) {
+            self.receive_ack(channel_id, sender, ack).await;
+        } else if let Some(change) = MessageChange::decode(plaintext) {
+            self.receive_change(channel_id, change).await;
+        } else if let Some(update) = ReactionUpdate::decode(plaintext) {
+            self.receive_reaction(channel_id, sender, update).await;
+        } else if let Some(ephemeral) = EphemeralMessage::decode(plaintext) {
+            self.receive_ephemeral(channel_id, sender, ephemeral).await;
+        } else {
+            return false;
+        }
+        true
+    }
+
+    /// Process a commit message from the group
+    ///
+    /// This updates the member's group state when other members add/remove participants
+    /// or perform other group operations. Essential for keeping epochs in sync.
+    ///
+    /// # Arguments
+    ///
+    /// * `commit` - Serialized commit message
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// // Alice adds Charlie, gets commit for Bob
+    /// let (invite, Some(commit)) = alice.create_invite(&channel_id, charlie_kp).await?;
+    /// // Bob processes commit to advance epoch
+    /// bob.process_commit(&commit).await?;
+    /// ```
+    pub async fn process_commit(&self, commit: &[u8]) -> MvpResult<()> {
+        debug!(size = commit.len(), "Processing commit");
+
+        // Try all groups until we find the right one
+        let groups = self.mls_service.list_groups().await;
+        let mut mls_events = self.mls_service.subscribe();
+
+        for group_id in groups.iter() {
+            // Newcomers join public channels by external commit
+            let channel_id = String::from_utf8(group_id.as_bytes().to_vec()).ok().map(ChannelId);
+            let result = match channel_id {
+                Some(ref channel_id) if self.is_public_channel(channel_id) => {
+                    self.mls_service.process_message_with_external_commits(group_id, commit).await
+                }
+                _ => self.mls_service.process_message(group_id, commit).await,
+            };
+
+            // Try to process commit with this group
+            match result {
+                Ok(Some(_)) => {
+                    // This shouldn't happen for commits, but if it does, it worked
+                    info!(group_id = ?group_id, "Commit processed (unexpected app message)");
+                    return Ok(());
+                }
+                Ok(None) => {
+                    // Commit or proposal processed successfully
+                    info!(group_id = ?group_id, "Commit processed successfully");
+                    if let Some(channel_id) = channel_id {
+                        self.resend_requeued_proposals(&channel_id, &mut mls_events).await;
+                        self.sync_member_devices(&channel_id).await;
+                        self.sync_channel_metadata(&channel_id).await;
+                        self.advance_webhooks(&channel_id, commit).await;
+                    }
+                    return Ok(());
+                }
+                Err(MlsError::OwnLeafMessage { epoch }) => {
+                    if let Some(channel_id) = channel_id {
+                        self.report_identity_in_use_elsewhere(&channel_id, epoch);
+                    }
+                    return Err(MvpError::Mls(MlsError::OwnLeafMessage { epoch }));
+                }
+                Err(_e) => {
+                    // Failed with this group, try next
+                }
+            }
+        }
+
+        warn!("Failed to process commit with any of {} groups", groups.len());
+        Err(MvpError::InvalidMessage("Could not process commit".to_string()))
+    }
+
+    /// Send the proposals that a lost fork requeued to the rest of the channel
+    ///
+    /// When a commit beats one of ours, the MLS service rolls back and
+    /// proposes our dropped changes again (see `MlsEvent::ForkResolved`);
+    /// whoever commits next includes them.
+    async fn resend_requeued_proposals(
+        &self,
+        channel_id: &ChannelId,
+        events: &mut broadcast::Receiver<MlsEvent>,
+    ) {
+        loop {
+            let proposals = match events.try_recv() {
+                Ok(MlsEvent::ForkResolved { group_id, requeued_proposals, .. })
+                    if group_id == channel_id.0.as_bytes() =>
+                {
+                    requeued_proposals
+                }
+                Ok(_) | Err(broadcast::error::TryRecvError::Lagged(_)) => continue,
+                Err(_) => break,
+            };
+
+            warn!(
+                channel_id = %channel_id,
+                proposals = proposals.len(),
+                "Commit lost a fork, re-sending its proposals"
+            );
+            let Some(ref network) = self.network else {
+                continue;
+            };
+            for proposal in proposals {
+                if let Err(e) = network.broadcast_commit(channel_id, proposal).await {
+                    warn!(error = %e, "Failed to re-send requeued proposal");
+                }
+            }
+        }
+    }
+
+    /// Get the role of a specific member in a channel
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Target channel
+    /// * `member_identity` - Identity bytes of the member
+    ///
+    /// # Returns
+    ///
+    /// The member's role if found
+    pub async fn get_member_role(
+        &self,
+        channel_id: &ChannelId,
+        member_identity: &[u8],
+    ) -> MvpResult<MemberRole> {
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let metadata =
+            self.mls_service.get_metadata(&group_id).await.map_err(|e| MvpError::Mls(e))?;
+
+        metadata
+            .members
+            .iter()
+            .find(|m| m.identity == member_identity)
+            .map(|m| m.role)
+            .ok_or_else(|| {
+                MvpError::InvalidOperation(format!(
+                    "Member {} not found in channel",
+                    std::str::from_utf8(member_identity).unwrap_or("<non-utf8>")
+                ))
+            })
+    }
+
+    /// Check if a member has admin role in a channel
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Target channel
+    /// * `member_identity` - Identity bytes of the member
+    ///
+    /// # Returns
+    ///
+    /// `true` if the member is an admin, `false` otherwise
+    pub async fn is_admin(
+        &self,
+        channel_id: &ChannelId,
+        member_identity: &[u8],
+    ) -> MvpResult<bool> {
+        let role = self.get_member_role(channel_id, member_identity).await?;
+        Ok(matches!(role, MemberRole::Admin))
+    }
+
+    /// Get the capabilities a member holds in a channel
+    ///
+    /// In a channel that belongs to a space, a member who has a role in the
+    /// space holds that role's capabilities (the space owner holds all of
+    /// them). Anyone else holds what their channel role grants.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Target channel
+    /// * `member_identity` - Identity bytes of the member
+    ///
+    /// # Returns
+    ///
+    /// The capability set granted to the member
+    pub async fn member_permissions(
+        &self,
+        channel_id: &ChannelId,
+        member_identity: &[u8],
+    ) -> MvpResult<PermissionSet> {
+        let role = self.get_member_role(channel_id, member_identity).await?;
+        Ok(self
+            .space_permissions(channel_id, member_identity)?
+            .unwrap_or(role.permissions()))
+    }
+
+    /// Capabilities a member may use through an MLS commit
+    ///
+    /// Every member checks commits against the channel roles in the group
+    /// context, so a space role narrows what the channel role allows here
+    /// but cannot widen it.
+    async fn commit_permissions(
+        &self,
+        channel_id: &ChannelId,
+        member_identity: &[u8],
+    ) -> MvpResult<PermissionSet> {
+        let role = self.get_member_role(channel_id, member_identity).await?;
+        let granted = self.member_permissions(channel_id, member_identity).await?;
+        Ok(granted.intersection(role.permissions()))
+    }
+
+    /// Capabilities from the member's role in the space holding the channel
+    ///
+    /// `None` if no stored space holds the channel or the member has no
+    /// role in it.
+    fn space_permissions(
+        &self,
+        channel_id: &ChannelId,
+        member_identity: &[u8],
+    ) -> MvpResult<Option<PermissionSet>> {
+        let Ok(user) = std::str::from_utf8(member_identity) else {
+            return Ok(None);
+        };
+        let user_id = UserId(user.to_string());
+        let space_ids = self.store.list_spaces().map_err(|e| MvpError::Store(e.to_string()))?;
+        for space_id in space_ids {
+            let Some(space) =
+                self.store.get_space(&space_id).map_err(|e| MvpError::Store(e.to_string()))?
+            else {
+                continue;
+            };
+            if space.has_channel(channel_id)
+                && (space.owner == user_id || space.get_user_role_id(&user_id).is_some())
+            {
+                return Ok(Some(space.get_user_permissions(&user_id)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Check if a member can remove other members
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Target channel
+    /// * `actor_identity` - Identity bytes of the member attempting removal
+    /// * `target_identity` - Identity bytes of the member to be removed (optional, for self-removal check)
+    ///
+    /// # Returns
+    ///
+    /// `true` if the actor has permission to remove members
+    pub async fn can_remove_member(
+        &self,
+        channel_id: &ChannelId,
+        actor_identity: &[u8],
+        _target_identity: Option<&[u8]>,
+    ) -> MvpResult<bool> {
+        // Removal requires the Kick capability
+        // Future: Allow members to remove themselves
+        let permissions = self.member_permissions(channel_id, actor_identity).await?;
+        Ok(permissions.contains(Permission::Kick))
+    }
+
+    /// Remove a member from the channel
+    ///
+    /// This generates an MLS commit that removes the specified member.
+    /// All remaining members must process this commit to stay in sync.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Target channel
+    /// * `member_identity` - Identity bytes of the member to remove
+    ///
+    /// # Returns
+    ///
+    /// Serialized commit message to broadcast to remaining members
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// // Alice removes Bob from the channel
+    /// let bob_identity = bob_user_id.0.as_bytes();
+    /// let commit = alice.remove_member(&channel_id, bob_identity).await?;
+    ///
+    /// // Charlie and Dave process the removal commit
+    /// charlie.process_commit(&commit).await?;
+    /// dave.process_commit(&commit).await?;
+    /// ```
+    pub async fn remove_member(
+        &self,
+        channel_id: &ChannelId,
+        member_identity: &[u8],
+    ) -> MvpResult<Vec<u8>> {
+        info!(
+            channel_id = %channel_id,
+            member = ?std::str::from_utf8(member_identity).unwrap_or("<non-utf8>"),
+            "Removing member from channel"
+        );
+
+        // Check permission: removal requires Kick
+        let actor_identity = self.identity.user_id.0.as_bytes();
+        let can_remove = self
+            .can_remove_member(channel_id, actor_identity, Some(member_identity))
+            .await?;
+
+        if !can_remove {
+            warn!(
+                actor = ?std::str::from_utf8(actor_identity).unwrap_or("<non-utf8>"),
+                "Permission denied: Actor lacks the kick permission"
+            );
+            return Err(MvpError::InvalidOperation(
+                "Only members with the kick permission can remove members".to_string(),
+            ));
+        }
+
+        debug!("Permission check passed");
+
+        // Map channel ID to group ID
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+
+        // Get group metadata to find the member's leaf index
+        let metadata = self.mls_service.get_metadata(&group_id).await.map_err(|e| {
+            warn!(error = ?e, "Failed to get group metadata");
+            MvpError::Mls(e)
+        })?;
+
+        // Find the member's leaf index
+        let leaf_index = metadata
+            .members
+            .iter()
+            .find(|m| m.identity == member_identity)
+            .map(|m| m.leaf_index)
+            .ok_or_else(|| {
+                warn!(
+                    member = ?std::str::from_utf8(member_identity).unwrap_or("<non-utf8>"),
+                    "Member not found in group"
+                );
+                MvpError::MemberNotFound {
+                    channel: channel_id.to_string(),
+                    member: String::from_utf8_lossy(member_identity).into_owned(),
+                }
+            })?;
+
+        debug!(leaf_index, "Found member's leaf index");
+
+        // Remove the member via MLS service
+        let commit =
+            self.mls_service
+                .remove_members(&group_id, vec![leaf_index])
+                .await
+                .map_err(|e| {
+                    warn!(error = ?e, "Failed to remove member");
+                    MvpError::Mls(e)
+                })?;
+        self.record_commit(channel_id, &commit).await;
+        self.sync_member_devices(channel_id).await;
+
+        // Broadcast removal commit to remaining channel members
+        if let Some(ref network) = self.network {
+            debug!(
+                channel_id = %channel_id,
+                commit_size = commit.len(),
+                "Broadcasting removal commit to channel"
+            );
+
+            if let Err(e) = network.broadcast_commit(channel_id, commit.clone()).await {
+                warn!(
+                    error = %e,
+                    "Failed to broadcast removal commit, members may be out of sync"
+                );
+            }
+        }
+
+        info!(
+            channel_id = %channel_id,
+            leaf_index,
+            commit_size = commit.len(),
+            network_broadcast = self.network.is_some(),
+            "Member removed successfully"
+        );
+
+        Ok(commit)
+    }
+
+    /// Leave a channel
+    ///
+    /// MLS does not let a member commit its own removal, so this issues a
+    /// self-remove proposal which a remaining member commits with
+    /// [`commit_pending_proposals`](Self::commit_pending_proposals). The
+    /// local MLS group state and the channel's entry in the store are
+    /// deleted immediately.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Channel to leave
+    ///
+    /// # Returns
+    ///
+    /// Serialized leave proposal to broadcast to the remaining members
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// // Bob leaves; Alice commits his removal and Charlie catches up
+    /// let proposal = bob.leave_channel(&channel_id).await?;
+    /// alice.process_commit(&proposal).await?;
+    /// let commit = alice.commit_pending_proposals(&channel_id).await?;
+    /// charlie.process_commit(&proposal).await?;
+    /// charlie.process_commit(&commit).await?;
+    /// ```
+    pub async fn leave_channel(&self, channel_id: &ChannelId) -> MvpResult<Vec<u8>> {
+        info!(
+            channel_id = %channel_id,
+            user_id = %self.identity.user_id,
+            "Leaving channel"
+        );
+
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let proposal = self.mls_service.leave_group(&group_id).await.map_err(|e| match e {
+            MlsError::GroupNotFound(_) => MvpError::ChannelNotFound(channel_id.to_string()),
+            other => {
+                warn!(error = ?other, "Failed to leave MLS group");
+                MvpError::Mls(other)
+            }
+        })?;
+
+        self.store
+            .remove_channel(channel_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?;
+        self.messages.write().await.remove(channel_id);
+
+        // The creator leaving a keyed channel deletes it; reserve its ID for a while
+        if let Some(directory) = self.directory.as_ref().filter(|_| channel_id.is_derived()) {
+            match directory.lookup(channel_id) {
+                Ok(Some(listing))
+                    if !listing.is_tombstone() && listing.creator == self.identity.user_id =>
+                {
+                    if let Err(e) = directory.tombstone(&listing) {
+                        warn!(error = %e, "Failed to tombstone keyed channel listing");
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => warn!(error = %e, "Failed to look up keyed channel listing"),
+            }
+        }
+
+        if let Some(ref network) = self.network {
+            debug!(
+                channel_id = %channel_id,
+                proposal_size = proposal.len(),
+                "Broadcasting leave proposal to channel"
+            );
+
+            if let Err(e) = network.broadcast_commit(channel_id, proposal.clone()).await {
+                warn!(
+                    error = %e,
+                    "Failed to broadcast leave proposal, members will still list us until it arrives"
+                );
+            }
+        }
+
+        info!(channel_id = %channel_id, "Left channel");
+
+        Ok(proposal)
+    }
+
+    /// Commit proposals received from other members
+    ///
+    /// Completes a member's departure after their leave proposal has been
+    /// passed to [`process_commit`](Self::process_commit).
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Target channel
+    ///
+    /// # Returns
+    ///
+    /// Serialized commit message to broadcast to remaining members
+    pub async fn commit_pending_proposals(&self, channel_id: &ChannelId) -> MvpResult<Vec<u8>> {
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let commit = self.mls_service.commit_pending(&group_id).await.map_err(|e| match e {
+            MlsError::GroupNotFound(_) => MvpError::ChannelNotFound(channel_id.to_string()),
+            other => MvpError::Mls(other),
+        })?;
+        self.record_commit(channel_id, &commit).await;
+        self.sync_member_devices(channel_id).await;
+
+        if let Some(ref network) = self.network {
+            if let Err(e) = network.broadcast_commit(channel_id, commit.clone()).await {
+                warn!(error = %e, "Failed to broadcast commit, members may be out of sync");
+            }
+        }
+
+        info!(
+            channel_id = %channel_id,
+            commit_size = commit.len(),
+            "Committed pending proposals"
+        );
+
+        Ok(commit)
+    }
+
+    /// Rotate this node's leaf key in a channel
+    ///
+    /// Commits an Update for the local leaf, which moves the group to a new
+    /// epoch with fresh key material.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Channel to rotate keys in
+    ///
+    /// # Returns
+    ///
+    /// The commit, already broadcast to the other members
+    pub async fn rotate_keys(&self, channel_id: &ChannelId) -> MvpResult<Vec<u8>> {
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let commit = self.mls_service.self_update(&group_id).await.map_err(|e| match e {
+            MlsError::GroupNotFound(_) => MvpError::ChannelNotFound(channel_id.to_string()),
+            other => MvpError::Mls(other),
+        })?;
+        self.record_commit(channel_id, &commit).await;
+        self.key_rotations.lock().await.insert(channel_id.clone(), Instant::now());
+
+        if let Some(ref network) = self.network {
+            if let Err(e) = network.broadcast_commit(channel_id, commit.clone()).await {
+                warn!(error = %e, "Failed to broadcast commit, members may be out of sync");
+            }
+        }
+
+        info!(channel_id = %channel_id, "Rotated leaf key");
+
+        Ok(commit)
+    }
+
+    /// Change a channel's name, topic or avatar for every member
+    ///
+    /// Commits the metadata into the group context, where it is
+    /// authenticated like any other group state. Members copy it to their
+    /// store when they process the commit; commits from anyone but an
+    /// owner or admin are rejected there.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Target channel
+    /// * `metadata` - New name, topic and avatar hash
+    ///
+    /// # Returns
+    ///
+    /// The commit, already broadcast to the other members
+    ///
+    /// # Errors
+    ///
+    /// `PermissionDenied` without the `edit_channel` permission
+    pub async fn update_channel_metadata(
+        &self,
+        channel_id: &ChannelId,
+        metadata: ChannelMetadata,
+    ) -> MvpResult<Vec<u8>> {
+        self.require_commit_permission(
+            channel_id,
+            Permission::EditChannel,
+            "update channel metadata",
+        )
+        .await?;
+
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let commit =
+            self.mls_service
+                .update_channel_metadata(&group_id, &metadata)
+                .await
+                .map_err(|e| match e {
+                    MlsError::GroupNotFound(_) => MvpError::ChannelNotFound(channel_id.to_string()),
+                    other => MvpError::Mls(other),
+                })?;
+        self.sync_channel_metadata(channel_id).await;
+        self.record_commit(channel_id, &commit).await;
+
+        if let Some(ref network) = self.network {
+            if let Err(e) = network.broadcast_commit(channel_id, commit.clone()).await {
+                warn!(error = %e, "Failed to broadcast commit, members may be out of sync");
+            }
+        }
+
+        info!(channel_id = %channel_id, name = %metadata.name, "Updated channel metadata");
+
+        Ok(commit)
+    }
+
+    /// Change a channel's topic, keeping its name and avatar
+    ///
+    /// See [`update_channel_metadata`](Self::update_channel_metadata).
+    pub async fn set_channel_topic(
+        &self,
+        channel_id: &ChannelId,
+        topic: Option<String>,
+    ) -> MvpResult<Vec<u8>> {
+        let channel = self
+            .store
+            .get_channel(channel_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .ok_or_else(|| MvpError::ChannelNotFound(channel_id.to_string()))?;
+
+        let metadata = ChannelMetadata { topic, ..Self::metadata_of(&channel) };
+        self.update_channel_metadata(channel_id, metadata).await
+    }
+
+    /// Change how long members keep a channel's messages
+    ///
+    /// The policy travels with the rest of the channel metadata, so every
+    /// member applies it to its own replica. See
+    /// [`update_channel_metadata`](Self::update_channel_metadata).
+    ///
+    /// # Errors
+    ///
+    /// - `PermissionDenied` without the `edit_channel` permission
+    /// - `Mls(InvalidInput)` for a policy keeping no days or no messages
+    pub async fn set_retention(
+        &self,
+        channel_id: &ChannelId,
+        policy: RetentionPolicy,
+    ) -> MvpResult<Vec<u8>> {
+        let channel = self
+            .store
+            .get_channel(channel_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .ok_or_else(|| MvpError::ChannelNotFound(channel_id.to_string()))?;
+
+        let metadata = ChannelMetadata { retention: policy, ..Self::metadata_of(&channel) };
+        self.update_channel_metadata(channel_id, metadata).await
+    }
+
+    /// Delete a channel's messages that fall outside its retention policy
+    ///
+    /// Removes them from the store, its commit log and indexes, the
+    /// in-memory history and the attachment cache, and emits
+    /// [`MessagesPruned`](ChannelEventKind::MessagesPruned). Pruned
+    /// messages are not restored if a peer sends them again.
+    ///
+    /// # Returns
+    ///
+    /// Number of messages pruned
+    pub async fn prune_channel(&self, channel_id: &ChannelId) -> MvpResult<usize> {
+        let expired = self
+            .store
+            .expired_messages(channel_id, Timestamp::now())
+            .map_err(|e| MvpError::Store(e.to_string()))?;
+        if expired.is_empty() {
+            return Ok(0);
+        }
+
+        let message_ids: Vec<MessageId> = expired.into_iter().map(|message| message.id).collect();
+        let count = self
+            .store
+            .prune_messages(channel_id, &message_ids)
+            .map_err(|e| MvpError::Store(e.to_string()))?;
+
+        if let Some(history) = self.messages.write().await.get_mut(channel_id) {
+            history.retain(|message| !message_ids.contains(&message.message_id));
+        }
+        if let Some(ref cache) = self.attachments {
+            for message_id in &message_ids {
+                if let Err(e) = cache.remove_message(message_id) {
+                    warn!(message_id = %message_id, error = %e, "Failed to drop attachments");
+                }
+            }
+        }
+
+        info!(channel_id = %channel_id, count, "Pruned expired messages");
+        self.emit_event(channel_id, None, ChannelEventKind::MessagesPruned { message_ids }).await;
+        Ok(count)
+    }
+
+    /// Prune expired messages in every channel
+    ///
+    /// A channel that fails to prune is logged and left for the next sweep;
+    /// the remaining channels are still pruned.
+    pub async fn prune_expired(&self) -> MvpResult<RetentionReport> {
+        let mut report = RetentionReport::default();
+
+        for channel in self.list_channels().await? {
+            let channel_id = channel.channel_id;
+            match self.prune_channel(&channel_id).await {
+                Ok(0) => {}
+                Ok(count) => report.pruned.push((channel_id, count)),
+                Err(e) => {
+                    warn!(channel_id = %channel_id, error = %e, "Failed to prune messages");
+                    report.failed.push((channel_id, e.to_string()));
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Start pruning expired messages in the background until shutdown
+    ///
+    /// Sweeps with [`prune_expired`](Self::prune_expired) once per `tick`,
+    /// starting right away so history that expired while offline goes
+    /// first.
+    ///
+    /// # Arguments
+    /// * `shutdown` - Coordinator whose shutdown signal stops the task
+    /// * `tick` - How often to check for expired messages
+    ///
+    /// # Returns
+    /// JoinHandle for the background task
+    pub fn spawn_retention_pruning(
+        self: Arc<Self>,
+        shutdown: Arc<ShutdownCoordinator>,
+        tick: Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            ShutdownHandler::new(shutdown, "retention")
+                .run(|| async {
+                    let mut interval = tokio::time::interval(tick);
+                    loop {
+                        interval.tick().await;
+                        if let Err(e) = self.prune_expired().await {
+                            warn!(error = %e, "Retention sweep failed");
+                        }
+                    }
+                })
+                .await;
+        })
+    }
+
+    /// Rotate keys in every channel not rotated within the configured interval
+    ///
+    /// Uses `key_rotation_interval_secs` from the MLS configuration. A
+    /// channel that fails to rotate is logged and left for the next sweep;
+    /// the remaining channels are still rotated.
+    pub async fn rotate_due_keys(&self) -> MvpResult<KeyRotationReport> {
+        let interval = Duration::from_secs(self.mls_service.config().key_rotation_interval_secs);
+        let mut report = KeyRotationReport::default();
+
+        for channel in self.list_channels().await? {
+            let channel_id = channel.channel_id;
+            let last = self.key_rotations.lock().await.get(&channel_id).copied();
+            if last.is_some_and(|at| at.elapsed() < interval) {
+                report.skipped.push(channel_id);
+                continue;
+            }
+
+            match self.rotate_keys(&channel_id).await {
+                Ok(_) => report.rotated.push(channel_id),
+                Err(e) => {
+                    warn!(channel_id = %channel_id, error = %e, "Failed to rotate leaf key");
+                    report.failed.push((channel_id, e.to_string()));
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Start rotating keys in the background until shutdown
+    ///
+    /// Does nothing if `auto_key_rotation` is disabled in the MLS
+    /// configuration. Otherwise sweeps with
+    /// [`rotate_due_keys`](Self::rotate_due_keys) once per `tick`, starting
+    /// one tick after the call.
+    ///
+    /// # Arguments
+    /// * `shutdown` - Coordinator whose shutdown signal stops the task
+    /// * `tick` - How often to check for due rotations
+    ///
+    /// # Returns
+    /// JoinHandle for the background task
+    pub fn spawn_key_rotation(
+        self: Arc<Self>,
+        shutdown: Arc<ShutdownCoordinator>,
+        tick: Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            if !self.mls_service.config().auto_key_rotation {
+                debug!("Automatic key rotation disabled");
+                return;
+            }
+
+            ShutdownHandler::new(shutdown, "key-rotation")
+                .run(|| async {
+                    let mut interval = tokio::time::interval(tick);
+                    interval.tick().await;
+                    loop {
+                        interval.tick().await;
+                        if let Err(e) = self.rotate_due_keys().await {
+                            warn!(error = %e, "Key rotation sweep failed");
+                        }
+                    }
+                })
+                .await;
+        })
+    }
+
+    /// List a channel's members
+    ///
+    /// Reads the replicated member set, which tracks every device (MLS leaf)
+    /// of every member along with when it joined. Sorted by join time.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Target channel
+    ///
+    /// # Returns
+    ///
+    /// One entry per member device
+    pub async fn list_members(&self, channel_id: &ChannelId) -> MvpResult<Vec<ChannelMember>> {
+        let channel = self
+            .store
+            .get_channel(channel_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .ok_or_else(|| MvpError::ChannelNotFound(channel_id.to_string()))?;
+
+        let mut members: Vec<ChannelMember> = channel
+            .get_member_devices()
+            .into_iter()
+            .map(|(device, joined_at)| ChannelMember {
+                user_id: device.user_id,
+                device_id: device.device_id,
+                joined_at,
+            })
+            .collect();
+        members.sort_by(|a, b| {
+            (a.joined_at, &a.user_id.0, &a.device_id).cmp(&(
+                b.joined_at,
+                &b.user_id.0,
+                &b.device_id,
+            ))
+        });
+
+        Ok(members)
+    }
+
+    /// Reconcile the channel's replicated member set with the MLS roster
+    ///
+    /// Called after every membership change this replica processes. Our own
+    /// device's node is published alongside, so peers can tell which roster
+    /// member they are syncing with. The member set is derived state, so
+    /// failures are logged, not returned.
+    async fn sync_member_devices(&self, channel_id: &ChannelId) {
+        self.emit_membership_events(channel_id).await;
+
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let metadata = match self.mls_service.get_metadata(&group_id).await {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                warn!(channel_id = %channel_id, error = ?e, "Failed to read MLS roster");
+                return;
+            }
+        };
+
+        let mut channel = match self.store.get_channel(channel_id) {
+            Ok(Some(channel)) => channel,
+            Ok(None) => return,
+            Err(e) => {
+                warn!(channel_id = %channel_id, error = %e, "Failed to load channel");
+                return;
+            }
+        };
+
+        let node_id = &self.identity.node_id;
+        let own_device = match self.mls_service.own_signature_key(&group_id).await {
+            Ok(key) => Some(leaf_device_id(&key)),
+            Err(e) => {
+                warn!(channel_id = %channel_id, error = ?e, "Failed to read own signature key");
+                None
+            }
+        };
+        let publish_node =
+            own_device.filter(|device| channel.replica_node(device) != Some(node_id));
+
+        let roster: HashSet<MemberDevice> = metadata.members.iter().map(member_device).collect();
+        let current: HashSet<MemberDevice> =
+            channel.member_devices.elements().into_iter().collect();
+        if roster == current && publish_node.is_none() {
+            return;
+        }
+
+        if let Some(device) = publish_node {
+            channel.set_replica_node(&device, node_id);
+        }
+        for device in roster.difference(&current) {
+            channel.add_member_device(device.clone(), Timestamp::now(), node_id);
+        }
+        for device in current.difference(&roster) {
+            channel.remove_member_device(device, node_id);
+        }
+
+        if let Err(e) = self.store.store_channel(&channel) {
+            warn!(channel_id = %channel_id, error = %e, "Failed to store channel members");
+        }
+    }
+
+    /// Copy the channel metadata agreed in the group context to the store
+    ///
+    /// Called after every commit this replica processes and after joining.
+    /// Groups whose metadata was never set keep the name from creation or
+    /// the invite. A change is emitted as
+    /// [`ChannelMetadataChanged`](ChannelEventKind::ChannelMetadataChanged).
+    /// Failures are logged, not returned.
+    async fn sync_channel_metadata(&self, channel_id: &ChannelId) {
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let metadata = match self.mls_service.channel_metadata(&group_id).await {
+            Ok(Some(metadata)) => metadata,
+            Ok(None) => return,
+            Err(e) => {
+                warn!(channel_id = %channel_id, error = ?e, "Failed to read channel metadata");
+                return;
+            }
+        };
+
+        let mut channel = match self.store.get_channel(channel_id) {
+            Ok(Some(channel)) => channel,
+            Ok(None) => return,
+            Err(e) => {
+                warn!(channel_id = %channel_id, error = %e, "Failed to load channel");
+                return;
+            }
+        };
+        if metadata == Self::metadata_of(&channel) {
+            return;
+        }
+
+        let node_id = &self.identity.node_id;
+        channel.set_name(metadata.name.clone(), node_id);
+        channel.set_topic(metadata.topic.clone().unwrap_or_default(), node_id);
+        channel.set_avatar_hash(metadata.avatar_hash.map(Vec::from).unwrap_or_default(), node_id);
+        channel.set_retention(metadata.retention, node_id);
+
+        if let Err(e) = self.store.store_channel(&channel) {
+            warn!(channel_id = %channel_id, error = %e, "Failed to store channel metadata");
+        }
+        self.emit_event(channel_id, None, ChannelEventKind::ChannelMetadataChanged(metadata))
+            .await;
+    }
+
+    /// Channel metadata as recorded in the store
+    fn metadata_of(channel: &Channel) -> ChannelMetadata {
+        ChannelMetadata {
+            name: channel.get_name().cloned().unwrap_or_default(),
+            topic: channel.get_topic().filter(|topic| !topic.is_empty()).cloned(),
+            avatar_hash: channel.avatar_hash().and_then(|hash| hash.try_into().ok()),
+            retention: channel.retention(),
+        }
+    }
+
+    /// Put a channel in broadcast mode, or take it out with `None`
+    ///
+    /// Every member sets this locally: publishers to post through the
+    /// mailbox, readers to fetch from it. Taking a channel out of broadcast
+    /// mode drops any queued membership changes.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Target channel
+    /// * `policy` - Broadcast policy, or `None` for a regular channel
+    pub async fn set_broadcast_policy(
+        &self,
+        channel_id: &ChannelId,
+        policy: Option<BroadcastPolicy>,
+    ) -> MvpResult<()> {
+        self.store
+            .get_channel(channel_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .ok_or_else(|| MvpError::ChannelNotFound(channel_id.to_string()))?;
+
+        let mut channels = self.broadcast_channels.lock().await;
+        match policy {
+            Some(policy) => {
+                info!(
+                    channel_id = %channel_id,
+                    publishers = policy.publishers.len(),
+                    batch_interval_secs = policy.membership_batch_interval.as_secs(),
+                    "Enabling broadcast mode"
+                );
+                match channels.get_mut(channel_id) {
+                    Some(state) => state.policy = policy,
+                    None => {
+                        channels.insert(channel_id.clone(), BroadcastState::new(policy));
+                    }
+                }
+            }
+            None => {
+                if let Some(state) = channels.remove(channel_id) {
+                    if !state.queue.is_empty() {
+                        warn!(
+                            channel_id = %channel_id,
+                            dropped = state.queue.len(),
+                            "Disabling broadcast mode with queued membership changes"
+                        );
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Broadcast policy for a channel, if it is in broadcast mode
+    pub async fn broadcast_policy(&self, channel_id: &ChannelId) -> Option<BroadcastPolicy> {
+        self.broadcast_channels
+            .lock()
+            .await
+            .get(channel_id)
+            .map(|state| state.policy.clone())
+    }
+
+    /// Derive a secret bound to a channel's current epoch
+    ///
+    /// For application-level keys such as voice or file encryption. All
+    /// members get the same bytes for the same `label`, `context` and
+    /// `length` within an epoch and different ones after every commit, so
+    /// keys should be re-derived when the epoch changes. Labels are
+    /// namespaced apart from the ones used internally, like the
+    /// sealed-sender key's.
+    ///
+    /// # Errors
+    ///
+    /// `ChannelNotFound` if the channel isn't held locally, and
+    /// `Mls(InvalidInput)` if `length` is zero or above
+    /// [`MAX_EXPORTED_SECRET_LEN`](crate::core_mls::service::MAX_EXPORTED_SECRET_LEN)
+    pub async fn derive_channel_secret(
+        &self,
+        channel_id: &ChannelId,
+        label: &str,
+        context: &[u8],
+        length: usize,
+    ) -> MvpResult<Vec<u8>> {
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let label = format!("{}{}", APP_SECRET_LABEL_PREFIX, label);
+        self.mls_service
+            .export_secret(&group_id, &label, context, length)
+            .await
+            .map_err(|e| match e {
+                MlsError::GroupNotFound(_) => MvpError::ChannelNotFound(channel_id.to_string()),
+                other => MvpError::Mls(other),
+            })
+    }
+
+    /// Report what metadata a channel exposes
+    ///
+    /// Aggregates the channel's directory listing, the local padding
+    /// configuration and its broadcast policy into a [`PrivacyReport`] with
+    /// a coarse score and recommendations.
+    ///
+    /// # Errors
+    ///
+    /// `ChannelNotFound` if the channel isn't held locally
+    pub async fn privacy_report(&self, channel_id: &ChannelId) -> MvpResult<PrivacyReport> {
+        self.store
+            .get_channel(channel_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .ok_or_else(|| MvpError::ChannelNotFound(channel_id.to_string()))?;
+
+        let listed = match self.directory {
+            Some(ref directory) => {
+                directory.lookup(channel_id)?.is_some_and(|listing| !listing.is_tombstone())
+            }
+            None => false,
+        };
+        let settings = ChannelPrivacy {
+            config: &self.config,
+            listed,
+            broadcast: self.broadcast_policy(channel_id).await,
+        };
+
+        Ok(PrivacyReport::evaluate(channel_id.0.clone(), &settings))
+    }
+
+    /// Queue a reader's add for the next batch commit
+    ///
+    /// Publishers should be added with [`create_invite`](Self::create_invite)
+    /// so they can post right away.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Broadcast channel
+    /// * `key_package` - Reader's serialized key package
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidKeyPackage` if the key package is unusable, and
+    /// `InvalidOperation` if the channel is not in broadcast mode.
+    pub async fn queue_member_add(
+        &self,
+        channel_id: &ChannelId,
+        key_package: Vec<u8>,
+    ) -> MvpResult<()> {
+        // Reject now rather than failing the whole batch later
+        self.mls_service.validate_key_package(&key_package).map_err(|e| match e {
+            MlsError::InvalidMessage(reason) => MvpError::InvalidKeyPackage(reason),
+            other => MvpError::Mls(other),
+        })?;
+
+        self.queue_membership_change(channel_id, QueuedMembershipChange::Add(key_package))
+            .await
+    }
+
+    /// Queue a reader's removal for the next batch commit
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Broadcast channel
+    /// * `member_identity` - Identity bytes of the member to remove
+    pub async fn queue_member_removal(
+        &self,
+        channel_id: &ChannelId,
+        member_identity: &[u8],
+    ) -> MvpResult<()> {
+        let actor_identity = self.identity.user_id.0.as_bytes();
+        if !self
+            .can_remove_member(channel_id, actor_identity, Some(member_identity))
+            .await?
+        {
+            return Err(MvpError::InvalidOperation(
+                "Only members with the kick permission can remove members".to_string(),
+            ));
+        }
+
+        self.queue_membership_change(
+            channel_id,
+            QueuedMembershipChange::Remove(member_identity.to_vec()),
+        )
+        .await
+    }
+
+    async fn queue_membership_change(
+        &self,
+        channel_id: &ChannelId,
+        change: QueuedMembershipChange,
+    ) -> MvpResult<()> {
+        let mut channels = self.broadcast_channels.lock().await;
+        let state = channels.get_mut(channel_id).ok_or_else(|| {
+            MvpError::InvalidOperation(format!(
+                "Channel {} is not in broadcast mode; add or remove members directly",
+                channel_id
+            ))
+        })?;
+
+        state.queue.push(change);
+        record_counter("mvp.broadcast.changes_queued", 1);
+        debug!(channel_id = %channel_id, queued = state.queue.len(), "Queued membership change");
+
+        Ok(())
+    }
+
+    /// Commit all queued membership changes for a broadcast channel
+    ///
+    /// Adds go into one commit and removals into another, however many
+    /// members are involved. Called by the batcher when the policy's
+    /// interval elapses, or directly to flush early.
+    ///
+    /// # Returns
+    ///
+    /// The shared invite for added members and the commits to distribute
+    pub async fn commit_membership_queue(
+        &self,
+        channel_id: &ChannelId,
+    ) -> MvpResult<MembershipBatch> {
+        let queue = {
+            let mut channels = self.broadcast_channels.lock().await;
+            let state = channels.get_mut(channel_id).ok_or_else(|| {
+                MvpError::InvalidOperation(format!(
+                    "Channel {} is not in broadcast mode",
+                    channel_id
+                ))
+            })?;
+            state.last_batch = Instant::now();
+            std::mem::take(&mut state.queue)
+        };
+
+        let mut batch = MembershipBatch::default();
+        if queue.is_empty() {
+            return Ok(batch);
+        }
+
+        let (adds, removals): (Vec<_>, Vec<_>) = queue
+            .into_iter()
+            .partition(|change| matches!(change, QueuedMembershipChange::Add(_)));
+        let key_packages: Vec<Vec<u8>> = adds
+            .into_iter()
+            .filter_map(|change| match change {
+                QueuedMembershipChange::Add(key_package) => Some(key_package),
+                QueuedMembershipChange::Remove(_) => None,
+            })
+            .collect();
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+
+        if !key_packages.is_empty() {
+            let channel = self
+                .store
+                .get_channel(channel_id)
+                .map_err(|e| MvpError::Store(e.to_string()))?
+                .ok_or_else(|| MvpError::ChannelNotFound(channel_id.to_string()))?;
+
+            batch.added = key_packages.len();
+            let (commit, welcome_bytes, ratchet_tree) =
+                self.add_invitees(&channel, key_packages).await?;
+            batch.invite =
+                Some(self.build_invite(channel_id, &channel, welcome_bytes, ratchet_tree).await);
+            if !commit.is_empty() {
+                batch.commits.push(commit);
+            }
+        }
+
+        if !removals.is_empty() {
+            let metadata = self.mls_service.get_metadata(&group_id).await?;
+            let leaf_indices: Vec<u32> = removals
+                .iter()
+                .filter_map(|change| match change {
+                    QueuedMembershipChange::Remove(identity) => {
+                        let leaf = metadata.members.iter().find(|m| &m.identity == identity);
+                        if leaf.is_none() {
+                            warn!(
+                                member = %String::from_utf8_lossy(identity),
+                                "Queued removal for a member no longer in the group"
+                            );
+                        }
+                        leaf.map(|m| m.leaf_index)
+                    }
+                    QueuedMembershipChange::Add(_) => None,
+                })
+                .collect();
+
+            if !leaf_indices.is_empty() {
+                batch.removed = leaf_indices.len();
+                batch
+                    .commits
+                    .push(self.mls_service.remove_members(&group_id, leaf_indices).await?);
+            }
+        }
+
+        for commit in &batch.commits {
+            self.record_commit(channel_id, commit).await;
+            if let Some(ref network) = self.network {
+                if let Err(e) = network.broadcast_commit(channel_id, commit.clone()).await {
+                    warn!(
+                        error = %e,
+                        "Failed to broadcast batch commit,
+                        members may be out of sync",
+                    );
+                }
+            }
+        }
+        self.sync_member_devices(channel_id).await;
+
+        info!(
+            channel_id = %channel_id,
+            added = batch.added,
+            removed = batch.removed,
+            commits = batch.commits.len(),
+            "Committed queued membership changes"
+        );
+
+        Ok(batch)
+    }
+
+    /// Start committing queued membership changes on each channel's cadence
+    ///
+    /// Every `tick`, channels whose batch interval has elapsed and that have
+    /// queued changes are committed.
+    ///
+    /// # Arguments
+    /// * `tick` - How often to check for due batches
+    ///
+    /// # Returns
+    /// JoinHandle for the background task
+    pub fn spawn_membership_batcher(
+        self: Arc<Self>,
+        tick: Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(tick);
+            loop {
+                interval.tick().await;
+
+                let now = Instant::now();
+                let due: Vec<ChannelId> = self
+                    .broadcast_channels
+                    .lock()
+                    .await
+                    .iter()
+                    .filter(|(_, state)| state.batch_due(now))
+                    .map(|(channel_id, _)| channel_id.clone())
+                    .collect();
+
+                for channel_id in due {
+                    if let Err(e) = self.commit_membership_queue(&channel_id).await {
+                        warn!(
+                            channel_id = %channel_id,
+                            error = %e,
+                            "Failed to commit membership batch",
+                        );
+                    }
+                }
+            }
+        })
+    }
+
+    /// Fetch and decrypt new messages from a broadcast channel's mailbox
+    ///
+    /// Messages that can't be decrypted yet (e.g. their epoch's commit
+    /// hasn't been processed) stay in the inbox for
+    /// [`process_pending`](Self::process_pending).
+    ///
+    /// # Returns
+    ///
+    /// Messages decrypted by this fetch
+    pub async fn fetch_broadcast(&self, channel_id: &ChannelId) -> MvpResult<Vec<ChatMessage>> {
+        let mailbox = self.mailbox.as_ref().ok_or_else(|| {
+            MvpError::InvalidOperation("No broadcast mailbox configured".to_string())
+        })?;
+        let cursor = self
+            .broadcast_channels
+            .lock()
+            .await
+            .get(channel_id)
+            .map(|state| state.mailbox_cursor)
+            .ok_or_else(|| {
+                MvpError::InvalidOperation(format!(
+                    "Channel {} is not in broadcast mode",
+                    channel_id
+                ))
+            })?;
+
+        let entries = mailbox.fetch(channel_id, cursor).await?;
+
+        let lock = self.inbox_lock(channel_id).await;
+        let _guard = lock.lock().await;
+        let inbox = self.store.inbox();
+        let mut next = cursor;
+        let mut decrypted = Vec::new();
+        for entry in entries {
+            next = next.max(entry.seq + 1);
+
+            // Our own posts are already in the history
+            if entry.sender == self.identity.user_id {
+                continue;
+            }
+
+            let id = inbox
+                .enqueue(channel_id.clone(), entry.sender, entry.ciphertext)
+                .map_err(|e| MvpError::Store(e.to_string()))?;
+            if let Some(envelope) = inbox.get(id) {
+                if let Some(message) = self.try_process_pending(&envelope).await? {
+                    decrypted.push(message);
+                }
+            }
+        }
+
+        if let Some(state) = self.broadcast_channels.lock().await.get_mut(channel_id) {
+            state.mailbox_cursor = state.mailbox_cursor.max(next);
+        }
+
+        Ok(decrypted)
+    }
+
+    /// Number of commits this manager has created for a channel
+    ///
+    /// Also exported as the `mvp.commits.created` metric.
+    pub async fn commit_count(&self, channel_id: &ChannelId) -> u64 {
+        self.commit_counts.read().await.get(channel_id).copied().unwrap_or(0)
+    }
+
+    /// Count a commit this manager created and apply it to hosted webhooks
+    async fn record_commit(&self, channel_id: &ChannelId, commit: &[u8]) {
+        *self.commit_counts.write().await.entry(channel_id.clone()).or_insert(0) += 1;
+        record_counter("mvp.commits.created", 1);
+        self.advance_webhooks(channel_id, commit).await;
+        self.deliver_commit(channel_id, commit).await;
+        self.publish_group_info(channel_id).await;
+    }
+
+    /// Publish a public channel's GroupInfo for the current epoch
+    ///
+    /// Does nothing for private channels or without a public group
+    /// directory. Failures are logged: the GroupInfo is republished with
+    /// the next commit.
+    async fn publish_group_info(&self, channel_id: &ChannelId) {
+        let Some(ref directory) = self.public_groups else {
+            return;
+        };
+        let channel = match self.store.get_channel(channel_id) {
+            Ok(Some(channel)) if channel.is_public() => channel,
+            _ => return,
+        };
+
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let result = match self
+            .mls_service
+            .export_public_info(&group_id, channel.get_name().cloned())
+            .await
+        {
+            Ok(info) => directory.publish(channel_id, &info).map(|_| ()),
+            Err(e) => Err(MvpError::Mls(e)),
+        };
+        if let Err(e) = result {
+            warn!(channel_id = %channel_id, error = %e, "Failed to publish group info");
+        }
+    }
+
+    /// Whether a channel held locally is public
+    fn is_public_channel(&self, channel_id: &ChannelId) -> bool {
+        matches!(self.store.get_channel(channel_id), Ok(Some(channel)) if channel.is_public())
+    }
+
+    /// Create an inbound webhook for a channel
+    ///
+    /// See [`create_webhook_with_options`](Self::create_webhook_with_options);
+    /// this uses the default rate limit and content-size cap.
+    pub async fn create_webhook(
+        &self,
+        channel_id: &ChannelId,
+        name: impl Into<String>,
+    ) -> MvpResult<(WebhookCredentials, Option<Vec<u8>>)> {
+        self.create_webhook_with_options(channel_id, name, WebhookOptions::default())
+            .await
+    }
+
+    /// Create an inbound webhook with custom limits
+    ///
+    /// Adds a pseudo-member, `webhook:<id>`, to the group. Its MLS leaf is
+    /// held by this node only, in a client of its own, so webhook posts are
+    /// never attributed to the admin who created it. The webhook is recorded
+    /// in the channel so every admin can list, rotate and revoke it.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Target channel
+    /// * `name` - Human-readable name (e.g. "CI")
+    /// * `options` - Rate limit and content-size cap
+    ///
+    /// # Returns
+    ///
+    /// The webhook's ID and secret token, and the add commit for existing
+    /// members. The token is not stored and cannot be recovered; rotate it
+    /// if it is lost.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PermissionDenied` without the `edit_channel` permission.
+    pub async fn create_webhook_with_options(
+        &self,
+        channel_id: &ChannelId,
+        name: impl Into<String>,
+        options: WebhookOptions,
+    ) -> MvpResult<(WebhookCredentials, Option<Vec<u8>>)> {
+        let name = name.into();
+        self.require_commit_permission(channel_id, Permission::EditChannel, "create_webhook")
+            .await?;
+
+        let webhook_id = generate_webhook_id();
+        let user_id = webhook_user_id(&webhook_id);
+        info!(channel_id = %channel_id, webhook_id = %webhook_id, name = %name, "Creating webhook");
+
+        let mls = open_webhook_mls(&self.config, &self.webhook_dir(&webhook_id)).await?;
+        let key_package = mls.generate_key_package(user_id.0.as_bytes().to_vec()).await?;
+
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let (commit, welcome_bytes, ratchet_tree) =
+            self.mls_service.add_members(&group_id, vec![key_package]).await?;
+        let ratchet_tree = if ratchet_tree.is_empty() {
+            None
+        } else {
+            Some(ratchet_tree)
+        };
+        mls.join_group(&welcome_bytes, ratchet_tree).await?;
+        self.sync_member_devices(channel_id).await;
+
+        let token = generate_token();
+        self.put_webhook_record(
+            channel_id,
+            WebhookRecord {
+                webhook_id: webhook_id.clone(),
+                name,
+                user_id,
+                token_hash: hash_token(&token),
+                created_by: self.identity.user_id.clone(),
+                created_at: Timestamp::now(),
+                rotated_at: None,
+                max_content_bytes: options.max_content_bytes,
+                max_posts_per_minute: options.max_posts_per_minute,
+            },
+        )?;
+
+        let commit_opt = if commit.is_empty() {
+            None
+        } else {
+            // Counted before the new leaf is hosted: it joined past this commit
+            self.record_commit(channel_id, &commit).await;
+            if let Some(ref network) = self.network {
+                if let Err(e) = network.broadcast_commit(channel_id, commit.clone()).await {
+                    warn!(
+                        error = %e,
+                        "Failed to broadcast webhook commit,
+                        members may be out of sync",
+                    );
+                }
+            }
+            Some(commit)
+        };
+
+        self.webhooks
+            .lock()
+            .await
+            .hosted
+            .insert(webhook_id.clone(), HostedWebhook { channel_id: channel_id.clone(), mls });
+        record_counter("mvp.webhooks.created", 1);
+
+        Ok((WebhookCredentials { webhook_id, token }, commit_opt))
+    }
+
+    /// List a channel's active webhooks, oldest first
+    pub async fn list_webhooks(&self, channel_id: &ChannelId) -> MvpResult<Vec<WebhookRecord>> {
+        let channel = self
+            .store
+            .get_channel(channel_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .ok_or_else(|| MvpError::ChannelNotFound(channel_id.to_string()))?;
+
+        let mut webhooks: Vec<WebhookRecord> = channel
+            .get_webhooks()
+            .into_iter()
+            .filter(|webhook| !channel.is_webhook_revoked(&webhook.webhook_id))
+            .collect();
+        webhooks.sort_by(|a, b| (a.created_at, &a.webhook_id).cmp(&(b.created_at, &b.webhook_id)));
+        Ok(webhooks)
+    }
+
+    /// Replace a webhook's secret token
+    ///
+    /// The old token stops working as soon as the hosting node sees the
+    /// updated record.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PermissionDenied` without the `edit_channel` permission, and
+    /// `WebhookNotFound` for unknown or revoked webhooks.
+    pub async fn rotate_webhook_token(
+        &self,
+        channel_id: &ChannelId,
+        webhook_id: &str,
+    ) -> MvpResult<WebhookCredentials> {
+        self.require_commit_permission(channel_id, Permission::EditChannel, "rotate_webhook_token")
+            .await?;
+
+        let mut record = self
+            .list_webhooks(channel_id)
+            .await?
+            .into_iter()
+            .find(|webhook| webhook.webhook_id == webhook_id)
+            .ok_or_else(|| MvpError::WebhookNotFound(webhook_id.to_string()))?;
+
+        info!(channel_id = %channel_id, webhook_id, "Rotating webhook token");
+        let token = generate_token();
+        record.token_hash = hash_token(&token);
+        record.rotated_at = Some(Timestamp::now());
+        self.put_webhook_record(channel_id, record)?;
+
+        Ok(WebhookCredentials { webhook_id: webhook_id.to_string(), token })
+    }
+
+    /// Revoke a webhook
+    ///
+    /// Marks it revoked in the channel and removes its leaf from the group,
+    /// so it can't post again even from the node that hosted it.
+    ///
+    /// # Returns
+    ///
+    /// The removal commit to broadcast, if the leaf was still in the group
+    ///
+    /// # Errors
+    ///
+    /// Returns `PermissionDenied` without the `edit_channel` permission, and
+    /// `WebhookNotFound` if no such webhook was created for the channel.
+    pub async fn revoke_webhook(
+        &self,
+        channel_id: &ChannelId,
+        webhook_id: &str,
+    ) -> MvpResult<Option<Vec<u8>>> {
+        self.require_commit_permission(channel_id, Permission::EditChannel, "revoke_webhook")
+            .await?;
+
+        let mut channel = self
+            .store
+            .get_channel(channel_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .ok_or_else(|| MvpError::ChannelNotFound(channel_id.to_string()))?;
+        let record = channel
+            .get_webhook(webhook_id)
+            .cloned()
+            .ok_or_else(|| MvpError::WebhookNotFound(webhook_id.to_string()))?;
+
+        info!(channel_id = %channel_id, webhook_id, "Revoking webhook");
+        channel.revoke_webhook(webhook_id, &self.identity.node_id);
+        self.store.store_channel(&channel).map_err(|e| MvpError::Store(e.to_string()))?;
+
+        // Drop the leaf's keys if we host it
+        {
+            let mut host = self.webhooks.lock().await;
+            host.rate_limiter.forget(webhook_id);
+            if host.hosted.remove(webhook_id).is_some() {
+                if let Err(e) = std::fs::remove_dir_all(self.webhook_dir(webhook_id)) {
+                    warn!(webhook_id, error = %e, "Failed to delete webhook MLS state");
+                }
+            }
+        }
+
+        let identity = record.user_id.0.as_bytes();
+        if !self.is_current_member(channel_id, identity).await? {
+            return Ok(None);
+        }
+        self.remove_member(channel_id, identity).await.map(Some)
+    }
+
+    /// Post a message through an inbound webhook
+    ///
+    /// Called by the HTTP listener for `POST /webhooks/<id>`. The message is
+    /// sent from the webhook's own leaf and attributed to its pseudo-member;
+    /// nothing in the request can change the sender.
+    ///
+    /// # Arguments
+    ///
+    /// * `webhook_id` - Webhook the request was addressed to
+    /// * `token` - Bearer token presented with the request
+    /// * `body` - Message body
+    ///
+    /// # Returns
+    ///
+    /// The encrypted message bytes, as [`send_message`](Self::send_message)
+    ///
+    /// # Errors
+    ///
+    /// - `WebhookNotFound` if the webhook is unknown, revoked or not hosted here
+    /// - `Unauthorized` if the token doesn't match
+    /// - `PayloadTooLarge` if the body is over the webhook's cap
+    /// - `RateLimited` if the webhook has used up its posts for this minute
+    pub async fn post_webhook_message(
+        &self,
+        webhook_id: &str,
+        token: &str,
+        body: &[u8],
+    ) -> MvpResult<Vec<u8>> {
+        let (channel_id, record) = self
+            .find_webhook(webhook_id)?
+            .ok_or_else(|| MvpError::WebhookNotFound(webhook_id.to_string()))?;
+
+        if !token_matches(token, &record.token_hash) {
+            record_counter("mvp.webhooks.rejected", 1);
+            warn!(webhook_id, "Rejecting webhook post with an invalid token");
+            return Err(MvpError::Unauthorized("invalid webhook token".to_string()));
+        }
+        check_content_size(body, record.max_content_bytes)?;
+        self.ensure_writable(&channel_id).await?;
+
+        let mls = {
+            let mut host = self.webhooks.lock().await;
+            let Some(hosted) = host.hosted.get(webhook_id) else {
+                return Err(MvpError::WebhookNotFound(format!(
+                    "{} is not hosted on this node",
+                    webhook_id
+                )));
+            };
+            let mls = hosted.mls.clone();
+            if !host.rate_limiter.try_acquire(
+                webhook_id,
+                record.max_posts_per_minute,
+                Instant::now(),
+            ) {
+                record_counter("mvp.webhooks.rate_limited", 1);
+                return Err(MvpError::RateLimited(format!(
+                    "webhook {} allows {} posts per minute",
+                    webhook_id, record.max_posts_per_minute
+                )));
+            }
+            mls
+        };
+
+        let policy = self.broadcast_policy(&channel_id).await;
+        if policy.as_ref().is_some_and(|policy| !policy.is_publisher(&record.user_id)) {
+            return Err(MvpError::InvalidOperation(
+                "Only publishers can post in a broadcast channel".to_string(),
+            ));
+        }
+
+        let padded_plaintext = self
+            .channel_padding(&channel_id)?
+            .pad(body)
+            .map_err(|e| MvpError::InvalidOperation(format!("Failed to pad message: {}", e)))?;
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let ciphertext =
+            mls.send_message(&group_id, &padded_plaintext).await.map_err(|e| match e {
+                // The leaf was removed by another admin's revocation
+                MlsError::GroupNotFound(_) => MvpError::WebhookNotFound(webhook_id.to_string()),
+                other => MvpError::Mls(other),
+            })?;
+
+        self.store_message(ChatMessage::new(
+            channel_id.clone(),
+            record.user_id.clone(),
+            body.to_vec(),
+        ))
+        .await?;
+        self.deliver(&channel_id, policy.is_some(), &ciphertext, &record.user_id)
+            .await?;
+        record_counter("mvp.webhooks.posted", 1);
+
+        info!(
+            channel_id = %channel_id,
+            webhook_id,
+            size = body.len(),
+            "Webhook message posted"
+        );
+
+        Ok(ciphertext)
+    }
+
+    /// Reopen the webhook leaves this node hosts
+    ///
+    /// Call on startup, after the store is loaded, so webhooks keep posting
+    /// and following commits across restarts.
+    ///
+    /// # Returns
+    ///
+    /// Number of webhooks restored
+    pub async fn restore_webhooks(&self) -> MvpResult<usize> {
+        let channel_ids = self.store.list_channels().map_err(|e| MvpError::Store(e.to_string()))?;
+
+        let mut restored = 0;
+        for channel_id in channel_ids {
+            for record in self.list_webhooks(&channel_id).await? {
+                let dir = self.webhook_dir(&record.webhook_id);
+                if !dir.exists() {
+                    // Hosted by another admin's node
+                    continue;
+                }
+                let mls = open_webhook_mls(&self.config, &dir).await?;
+                self.webhooks.lock().await.hosted.insert(
+                    record.webhook_id,
+                    HostedWebhook { channel_id: channel_id.clone(), mls },
+                );
+                restored += 1;
+            }
+        }
+
+        Ok(restored)
+    }
+
+    /// Find an active webhook and its channel
+    fn find_webhook(&self, webhook_id: &str) -> MvpResult<Option<(ChannelId, WebhookRecord)>> {
+        let channel_ids = self.store.list_channels().map_err(|e| MvpError::Store(e.to_string()))?;
+
+        for channel_id in channel_ids {
+            let Some(channel) = self
+                .store
+                .get_channel(&channel_id)
+                .map_err(|e| MvpError::Store(e.to_string()))?
+            else {
+                continue;
+            };
+            if channel.is_webhook_revoked(webhook_id) {
+                return Ok(None);
+            }
+            if let Some(record) = channel.get_webhook(webhook_id) {
+                return Ok(Some((channel_id, record.clone())));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Store a webhook record in the channel
+    fn put_webhook_record(&self, channel_id: &ChannelId, record: WebhookRecord) -> MvpResult<()> {
+        let mut channel = self
+            .store
+            .get_channel(channel_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .ok_or_else(|| MvpError::ChannelNotFound(channel_id.to_string()))?;
+        channel.put_webhook(record, &self.identity.node_id);
+        self.store.store_channel(&channel).map_err(|e| MvpError::Store(e.to_string()))
+    }
+
+    /// Apply a commit to the webhook leaves this node hosts for the channel
+    ///
+    /// Webhook leaves are members like any other and must follow the
+    /// group's epochs to keep posting.
+    async fn advance_webhooks(&self, channel_id: &ChannelId, commit: &[u8]) {
+        let leaves: Vec<(String, Arc<MlsService>)> = self
+            .webhooks
+            .lock()
+            .await
+            .hosted
+            .iter()
+            .filter(|(_, hosted)| &hosted.channel_id == channel_id)
+            .map(|(webhook_id, hosted)| (webhook_id.clone(), hosted.mls.clone()))
+            .collect();
+
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        for (webhook_id, mls) in leaves {
+            if let Err(e) = mls.process_message(&group_id, commit).await {
+                warn!(webhook_id = %webhook_id, error = %e, "Webhook failed to process commit");
+            }
+        }
+    }
+
+    /// Directory holding a hosted webhook's MLS state
+    fn webhook_dir(&self, webhook_id: &str) -> PathBuf {
+        self.store.data_dir().join("webhooks").join(webhook_id)
+    }
+
+    /// Fail with `PermissionDenied` unless this user owns the channel
+    ///
+    /// Returns the channel's current roles.
+    async fn require_owner(&self, channel_id: &ChannelId, action: &str) -> MvpResult<ChannelRoles> {
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let roles = self.mls_service.channel_roles(&group_id).await.map_err(|e| match e {
+            MlsError::GroupNotFound(_) => MvpError::ChannelNotFound(channel_id.to_string()),
+            other => MvpError::Mls(other),
+        })?;
+        if roles.is_owner(self.identity.user_id.0.as_bytes()) {
+            return Ok(roles);
+        }
+        Err(MvpError::PermissionDenied {
+            user: self.identity.user_id.to_string(),
+            action: action.to_string(),
+            channel: channel_id.to_string(),
+        })
+    }
+
+    /// Fail with `PermissionDenied` unless this user holds `permission`
+    async fn require_permission(
+        &self,
+        channel_id: &ChannelId,
+        permission: Permission,
+        action: &str,
+    ) -> MvpResult<()> {
+        let permissions =
+            self.member_permissions(channel_id, self.identity.user_id.0.as_bytes()).await?;
+        self.check_permission(channel_id, permissions, permission, action)
+    }
+
+    /// Fail with `PermissionDenied` unless this user may use `permission`
+    /// in a commit
+    async fn require_commit_permission(
+        &self,
+        channel_id: &ChannelId,
+        permission: Permission,
+        action: &str,
+    ) -> MvpResult<()> {
+        let permissions =
+            self.commit_permissions(channel_id, self.identity.user_id.0.as_bytes()).await?;
+        self.check_permission(channel_id, permissions, permission, action)
+    }
+
+    fn check_permission(
+        &self,
+        channel_id: &ChannelId,
+        permissions: PermissionSet,
+        permission: Permission,
+        action: &str,
+    ) -> MvpResult<()> {
+        if permissions.contains(permission) {
+            return Ok(());
+        }
+        Err(MvpError::PermissionDenied {
+            user: self.identity.user_id.to_string(),
+            action: action.to_string(),
+            channel: channel_id.to_string(),
+        })
+    }
+
+    /// Make a member an admin of the channel
+    ///
+    /// Admins may add and remove members and change the channel metadata.
+    /// The new roles are committed into the group context, so every member
+    /// enforces them once it processes the commit.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Target channel
+    /// * `member_identity` - Identity bytes of the member to promote
+    ///
+    /// # Returns
+    ///
+    /// The commit, already broadcast to the other members
+    ///
+    /// # Errors
+    ///
+    /// `PermissionDenied` unless this user owns the channel;
+    /// `InvalidOperation` if the member isn't in the channel or already an
+    /// admin
+    pub async fn promote_admin(
+        &self,
+        channel_id: &ChannelId,
+        member_identity: &[u8],
+    ) -> MvpResult<Vec<u8>> {
+        let mut roles = self.require_owner(channel_id, "promote_admin").await?;
+        self.get_member_role(channel_id, member_identity).await?;
+        if !roles.promote(member_identity) {
+            return Err(MvpError::InvalidOperation(format!(
+                "{} is already an admin",
+                String::from_utf8_lossy(member_identity)
+            )));
+        }
+        self.commit_roles(channel_id, &roles).await
+    }
+
+    /// Take admin rights from a member of the channel
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Target channel
+    /// * `member_identity` - Identity bytes of the admin to demote
+    ///
+    /// # Returns
+    ///
+    /// The commit, already broadcast to the other members
+    ///
+    /// # Errors
+    ///
+    /// `PermissionDenied` unless this user owns the channel;
+    /// `InvalidOperation` if the member isn't an admin (the owner can't be
+    /// demoted)
+    pub async fn demote_admin(
+        &self,
+        channel_id: &ChannelId,
+        member_identity: &[u8],
+    ) -> MvpResult<Vec<u8>> {
+        let mut roles = self.require_owner(channel_id, "demote_admin").await?;
+        if !roles.demote(member_identity) {
+            return Err(MvpError::InvalidOperation(format!(
+                "{} is not an admin",
+                String::from_utf8_lossy(member_identity)
+            )));
+        }
+        self.commit_roles(channel_id, &roles).await
+    }
+
+    /// Commit new roles to the group and broadcast the commit
+    async fn commit_roles(
+        &self,
+        channel_id: &ChannelId,
+        roles: &ChannelRoles,
+    ) -> MvpResult<Vec<u8>> {
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let commit = self.mls_service.update_channel_roles(&group_id, roles).await?;
+        self.record_commit(channel_id, &commit).await;
+
+        if let Some(ref network) = self.network {
+            if let Err(e) = network.broadcast_commit(channel_id, commit.clone()).await {
+                warn!(error = %e, "Failed to broadcast commit, members may be out of sync");
+            }
+        }
+
+        info!(channel_id = %channel_id, admins = roles.admins.len(), "Updated channel roles");
+
+        Ok(commit)
+    }
+
+    /// Promote a member to Admin role
+    ///
+    /// **NOTE**: Role persistence is not yet fully implemented.
+    /// Currently, roles are only stored in MLS GroupMetadata, which cannot be
+    /// directly modified without add/remove operations. In a production system,
+    /// roles would be stored in the CRDT layer alongside MLS state. Admin
+    /// rights that members enforce on commits are granted with
+    /// [`promote_admin`](Self::promote_admin).
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Target channel
+    /// * `member_identity` - Identity bytes of the member to promote
+    ///
+    /// # Returns
+    ///
+    /// Success if the member was found and actor has permission
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidOperation` if:
+    /// - Actor lacks the `ManageRoles` permission
+    /// - Member not found in channel
+    pub async fn promote_member(
+        &self,
+        channel_id: &ChannelId,
+        member_identity: &[u8],
+    ) -> MvpResult<()> {
+        // Check permission: role changes require ManageRoles
+        let actor_identity = self.identity.user_id.0.as_bytes();
+        let permissions = self.member_permissions(channel_id, actor_identity).await?;
+        if !permissions.contains(Permission::ManageRoles) {
+            return Err(MvpError::InvalidOperation(
+                "Only members with the manage_roles permission can promote members".to_string(),
+            ));
+        }
+
+        // Verify member exists
+        let _current_role = self.get_member_role(channel_id, member_identity).await?;
+
+        info!(
+            channel_id = %channel_id,
+            member = ?std::str::from_utf8(member_identity).unwrap_or("<non-utf8>"),
+            "Member promoted to Admin (role change pending CRDT integration)"
+        );
+
+        // TODO: Store role change in CRDT
+        // This would involve:
+        // 1. Get channel from store
+        // 2. Update member role in channel.permissions or similar
+        // 3. Broadcast CRDT update to other members
+        // 4. Persist change
+
+        Ok(())
+    }
+
+    /// Demote a member to regular Member role
+    ///
+    /// **NOTE**: Role persistence is not yet fully implemented.
+    /// See `promote_member` documentation for details.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Target channel
+    /// * `member_identity` - Identity bytes of the member to demote
+    ///
+    /// # Returns
+    ///
+    /// Success if the member was found and actor has permission
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidOperation` if:
+    /// - Actor lacks the `ManageRoles` permission
+    /// - Member not found in channel
+    /// - Trying to demote the last admin (not yet enforced)
+    pub async fn demote_member(
+        &self,
+        channel_id: &ChannelId,
+        member_identity: &[u8],
+    ) -> MvpResult<()> {
+        // Check permission: role changes require ManageRoles
+        let actor_identity = self.identity.user_id.0.as_bytes();
+        let permissions = self.member_permissions(channel_id, actor_identity).await?;
+        if !permissions.contains(Permission::ManageRoles) {
+            return Err(MvpError::InvalidOperation(
+                "Only members with the manage_roles permission can demote members".to_string(),
+            ));
+        }
+
+        // Verify member exists
+        let _current_role = self.get_member_role(channel_id, member_identity).await?;
+
+        info!(
+            channel_id = %channel_id,
+            member = ?std::str::from_utf8(member_identity).unwrap_or("<non-utf8>"),
+            "Member demoted to Member (role change pending CRDT integration)"
+        );
+
+        // TODO: Store role change in CRDT (same as promote_member)
+        // TODO: Prevent demoting last admin
+
+        Ok(())
+    }
+
+    /// Get channel metadata
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Channel to query
+    ///
+    /// # Returns
+    ///
+    /// Channel descriptor if found
+    pub async fn get_channel(&self, channel_id: &ChannelId) -> MvpResult<ChannelDescriptor> {
+        let channel = self
+            .store
+            .get_channel(channel_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .ok_or_else(|| MvpError::ChannelNotFound(channel_id.to_string()))?;
+
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+
+        let mut descriptor = ChannelDescriptor::new(
+            channel_id.clone(),
+            channel.created_by.clone(),
+            channel.get_name().cloned().unwrap_or_default(),
+            channel.is_public(),
+            group_id,
+        );
+        descriptor.padding = self.padding_of(&channel);
+        let metadata = Self::metadata_of(&channel);
+        descriptor.description = metadata.topic;
+        descriptor.avatar_hash = metadata.avatar_hash;
+        Ok(descriptor)
+    }
+
+    /// Padding strategy messages in a channel are sent with
+    fn channel_padding(&self, channel_id: &ChannelId) -> MvpResult<PaddingStrategy> {
+        let channel = self
+            .store
+            .get_channel(channel_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .ok_or_else(|| MvpError::ChannelNotFound(channel_id.to_string()))?;
+        Ok(self.padding_of(&channel))
+    }
+
+    /// The channel's agreed padding, or ours for channels that predate it
+    fn padding_of(&self, channel: &Channel) -> PaddingStrategy {
+        channel
+            .padding()
+            .cloned()
+            .unwrap_or_else(|| self.mls_service.config().padding.clone())
+    }
+
+    /// List all channels for current user
+    pub async fn list_channels(&self) -> MvpResult<Vec<ChannelDescriptor>> {
+        // For MVP, list all groups from MLS service
+        let group_ids = self.mls_service.list_groups().await;
+
+        let mut channels = Vec::new();
+        for group_id in group_ids {
+            // Convert group ID to channel ID
+            // GroupId is created from ChannelId UUID string bytes, so reverse the conversion
+            match String::from_utf8(group_id.as_bytes().to_vec()) {
+                Ok(channel_id_str) => {
+                    let channel_id = ChannelId(channel_id_str);
+                    if let Ok(descriptor) = self.get_channel(&channel_id).await {
+                        channels.push(descriptor);
+                    } else {
+                        debug!(channel_id = %channel_id, "Channel not found in store");
+                    }
+                }
+                Err(e) => {
+                    warn!(
+                        group_id = %group_id,
+                        error = %e,
+                        "Failed to convert GroupId to ChannelId UTF-8 string"
+                    );
+                }
+            }
+        }
+
+        Ok(channels)
+    }
+
+    /// React to a message with an emoji
+    ///
+    /// The reaction goes to the other members as a small application
+    /// message and is kept in the message's reaction set, which converges
+    /// however reactions and unreactions from different devices interleave.
+    ///
+    /// # Returns
+    ///
+    /// The encrypted reaction, already sent to the channel
+    ///
+    /// # Errors
+    ///
+    /// - `MessageNotFound` if the message is not in this channel
+    /// - `InvalidOperation` if we already reacted with this emoji, or the
+    ///   message was deleted
+    pub async fn react(
+        &self,
+        channel_id: &ChannelId,
+        message_id: MessageId,
+        emoji: &str,
+    ) -> MvpResult<Vec<u8>> {
+        self.ensure_writable(channel_id).await?;
+
+        let message = self.stored_message(channel_id, &message_id)?;
+        if message.deleted {
+            return Err(MvpError::InvalidOperation("Message was deleted".to_string()));
+        }
+        let user = self.identity.user_id.clone();
+        if !message.reaction_add_ids(&user, emoji).is_empty() {
+            return Err(MvpError::InvalidOperation(
+                "User has already reacted with this emoji".to_string(),
+            ));
+        }
+
+        let add_id = AddId::new(self.identity.node_id.clone(), Timestamp::now().as_millis());
+        let update = ReactionUpdate {
+            channel_id: channel_id.clone(),
+            target: message_id,
+            user,
+            emoji: emoji.to_string(),
+            op: ReactionOp::React(add_id),
+        };
+        self.send_reaction(update, message).await
+    }
+
+    /// Remove our reaction with an emoji from a message
+    ///
+    /// Only the reactions this device has seen are removed; one made
+    /// concurrently on another of our devices stays.
+    ///
+    /// # Returns
+    ///
+    /// The encrypted unreaction, already sent to the channel
+    ///
+    /// # Errors
+    ///
+    /// - `MessageNotFound` if the message is not in this channel
+    /// - `InvalidOperation` if we haven't reacted with this emoji
+    pub async fn unreact(
+        &self,
+        channel_id: &ChannelId,
+        message_id: MessageId,
+        emoji: &str,
+    ) -> MvpResult<Vec<u8>> {
+        self.ensure_writable(channel_id).await?;
+
+        let message = self.stored_message(channel_id, &message_id)?;
+        let user = self.identity.user_id.clone();
+        let add_ids = message.reaction_add_ids(&user, emoji);
+        if add_ids.is_empty() {
+            return Err(MvpError::InvalidOperation("Reaction not found".to_string()));
+        }
+
+        let update = ReactionUpdate {
+            channel_id: channel_id.clone(),
+            target: message_id,
+            user,
+            emoji: emoji.to_string(),
+            op: ReactionOp::Unreact(add_ids.into_iter().collect()),
+        };
+        self.send_reaction(update, message).await
+    }
+
+    /// Add a reaction to a message in whichever channel it is
+    ///
+    /// See [`react`](Self::react).
+    pub async fn add_reaction(&self, message_id: &MessageId, emoji: String) -> MvpResult<()> {
+        let channel_id = self.message_channel(message_id)?;
+        self.react(&channel_id, message_id.clone(), &emoji).await?;
+        Ok(())
+    }
+
+    /// Remove a reaction from a message in whichever channel it is
+    ///
+    /// See [`unreact`](Self::unreact).
+    pub async fn remove_reaction(&self, message_id: &MessageId, emoji: String) -> MvpResult<()> {
+        let channel_id = self.message_channel(message_id)?;
+        self.unreact(&channel_id, message_id.clone(), &emoji).await?;
+        Ok(())
+    }
+
+    /// Get aggregated reactions for a message
+    ///
+    /// # Arguments
+    ///
+    /// * `message_id` - ID of the message
+    ///
+    /// # Returns
+    ///
+    /// Reaction summaries grouped by emoji, most popular first; empty for
+    /// an unknown message
+    pub async fn get_reactions(&self, message_id: &MessageId) -> MvpResult<Vec<ReactionSummary>> {
+        let message =
+            self.store.get_message(message_id).map_err(|e| MvpError::Store(e.to_string()))?;
+        Ok(message
+            .map(|message| reaction_summaries(message.reactions(), &self.identity.user_id))
+            .unwrap_or_default())
+    }
+
+    /// Channel of a stored message
+    fn message_channel(&self, message_id: &MessageId) -> MvpResult<ChannelId> {
+        self.store
+            .get_message(message_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .map(|message| message.channel_id)
+            .ok_or_else(|| MvpError::MessageNotFound(message_id.0.clone()))
+    }
+
+    /// Apply a reaction to our copy of a message and send it to the channel
+    async fn send_reaction(
+        &self,
+        update: ReactionUpdate,
+        mut message: crate::core_store::model::Message,
+    ) -> MvpResult<Vec<u8>> {
+        let channel_id = update.channel_id.clone();
+        let padded_plaintext = self
+            .channel_padding(&channel_id)?
+            .pad(&update.encode()?)
+            .map_err(|e| MvpError::InvalidOperation(format!("Failed to pad message: {}", e)))?;
+
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let ciphertext = self.mls_service.send_message(&group_id, &padded_plaintext).await?;
+        update.apply_to(&mut message)?;
+        self.store.store_message(&message).map_err(|e| MvpError::Store(e.to_string()))?;
+        self.deliver_or_defer(&channel_id, &ciphertext).await?;
+
+        debug!(
+            channel_id = %channel_id,
+            message_id = %update.target,
+            emoji = %update.emoji,
+            "Sent reaction"
+        );
+        Ok(ciphertext)
+    }
+
+    /// Apply a received reaction, or park it until its message arrives
+    ///
+    /// Members only react for themselves.
+    async fn receive_reaction(
+        &self,
+        channel_id: &ChannelId,
+        sender: &UserId,
+        update: ReactionUpdate,
+    ) {
+        if &update.channel_id != channel_id || &update.user != sender {
+            warn!(
+                channel_id = %channel_id,
+                sender = %sender,
+                "Dropping reaction sent for someone else"
+            );
+            return;
+        }
+
+        match self.store.get_message(&update.target) {
+            Ok(Some(message)) => self.apply_reaction(&update, message),
+            Ok(None) => {
+                debug!(
+                    channel_id = %channel_id,
+                    message_id = %update.target,
+                    "Parking reaction until its message arrives"
+                );
+                self.parked_reactions.lock().await.park(update);
+            }
+            Err(e) => warn!(channel_id = %channel_id, error = %e, "Failed to load message"),
+        }
+    }
+
+    /// Apply the reactions parked for a message that just arrived
+    async fn apply_parked_reactions(&self, message_id: &MessageId) {
+        let parked = self.parked_reactions.lock().await.take(message_id);
+        for update in parked {
+            match self.store.get_message(message_id) {
+                Ok(Some(message)) => self.apply_reaction(&update, message),
+                Ok(None) => {}
+                Err(e) => warn!(error = %e, "Failed to apply parked reaction"),
+            }
+        }
+    }
+
+    /// Add or remove a reaction in a stored message
+    fn apply_reaction(
+        &self,
+        update: &ReactionUpdate,
+        mut message: crate::core_store::model::Message,
+    ) {
+        if message.channel_id != update.channel_id {
+            warn!(
+                channel_id = %update.channel_id,
+                message_id = %update.target,
+                "Dropping reaction to a message in another channel"
+            );
+            return;
+        }
+
+        let result = update.apply_to(&mut message).and_then(|()| {
+            self.store.store_message(&message).map_err(|e| MvpError::Store(e.to_string()))
+        });
+        if let Err(e) = result {
+            warn!(channel_id = %update.channel_id, error = %e, "Failed to apply reaction");
+        }
+    }
+
+    /// Pin a message's attachments so they are never evicted from the cache
+    ///
+    /// Pins apply to attachments downloaded later as well.
+    ///
+    /// # Arguments
+    ///
+    /// * `message_id` - Message whose attachments to pin
+    pub fn pin_attachment(&self, message_id: &MessageId) -> MvpResult<()> {
+        let cache = self.attachments.as_ref().ok_or_else(|| {
+            MvpError::InvalidOperation("Attachment cache not configured".to_string())
+        })?;
+        cache.pin(message_id).map_err(|e| MvpError::Store(e.to_string()))?;
+
+        debug!(message_id = %message_id, "Pinned message attachments");
+        Ok(())
+    }
+
+    /// Unpin a message's attachments, making them evictable again
+    ///
+    /// # Returns
+    ///
+    /// `false` if the message was not pinned
+    pub fn unpin_attachment(&self, message_id: &MessageId) -> MvpResult<bool> {
+        let cache = self.attachments.as_ref().ok_or_else(|| {
+            MvpError::InvalidOperation("Attachment cache not configured".to_string())
+        })?;
+        cache.unpin(message_id).map_err(|e| MvpError::Store(e.to_string()))
+    }
+
+    /// Send a file to a channel
+    ///
+    /// The file is encrypted in chunks under a key exported from the
+    /// channel's group and stored in the DHT; the channel gets a message
+    /// with the manifest members download it from. With an attachment cache
+    /// attached the file is kept there as authored, until its chunks are
+    /// known to be stored on enough peers.
+    ///
+    /// # Returns
+    ///
+    /// The encrypted manifest message, already sent to the channel
+    ///
+    /// # Errors
+    ///
+    /// - `InvalidOperation` if no attachment store is attached
+    /// - `PayloadTooLarge` if the file is over the configured
+    ///   `max_attachment_size`
+    pub async fn send_attachment(&self, channel_id: &ChannelId, path: &Path) -> MvpResult<Vec<u8>> {
+        let attachments = self.attachment_store()?;
+        self.ensure_writable(channel_id).await?;
+
+        let mut file = std::fs::File::open(path).map_err(|e| {
+            MvpError::InvalidOperation(format!("Failed to open {}: {}", path.display(), e))
+        })?;
+        let max_size = self.config.store.max_attachment_size;
+        let size = file.metadata().map_err(|e| MvpError::Store(e.to_string()))?.len();
+        if size > max_size {
+            return Err(MvpError::PayloadTooLarge {
+                size: usize::try_from(size).unwrap_or(usize::MAX),
+                limit: usize::try_from(max_size).unwrap_or(usize::MAX),
+            });
+        }
+        let mut data = Vec::new();
+        std::io::Read::read_to_end(&mut file, &mut data).map_err(|e| {
+            MvpError::InvalidOperation(format!("Failed to read {}: {}", path.display(), e))
+        })?;
+
+        let id = hex::encode(rand::random::<[u8; 16]>());
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let secret = self
+            .mls_service
+            .export_secret(&group_id, ATTACHMENT_KEY_LABEL, id.as_bytes(), 32)
+            .await
+            .map_err(|e| match e {
+                MlsError::GroupNotFound(_) => MvpError::ChannelNotFound(channel_id.to_string()),
+                other => MvpError::Mls(other),
+            })?;
+        let key: [u8; 32] = secret
+            .try_into()
+            .map_err(|_| MvpError::Internal("Exported attachment key has wrong length".into()))?;
+
+        let filename = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| id.clone());
+        let mut manifest =
+            AttachmentManifest::new(id, filename, mime_type_for(path).to_string(), key);
+        let replicas = attachments.upload(&mut manifest, data.as_slice(), max_size).await?;
+
+        info!(
+            channel_id = %channel_id,
+            attachment = %manifest.id,
+            size = manifest.size,
+            chunks = manifest.chunks.len(),
+            replicas,
+            "Uploaded attachment"
+        );
+        let ciphertext = self.send_message(channel_id, &manifest.encode()?).await?;
+
+        // The message is out; a cache failure only costs a later download
+        if let Some(cache) = &self.attachments {
+            let message_id = MessageId::from_ciphertext(&ciphertext);
+            let metadata = manifest.metadata();
+            let cached = cache
+                .insert_authored(&message_id, &metadata, &data)
+                .and_then(|_| cache.confirm_delivery(&manifest.id, replicas));
+            if let Err(e) = cached {
+                warn!(attachment = %manifest.id, error = %e, "Failed to cache attachment");
+            }
+        }
+        Ok(ciphertext)
+    }
+
+    /// Download a file sent to a channel
+    ///
+    /// Chunks are fetched from any replica, checked against the manifest's
+    /// hashes and decrypted into `dest`. Calling this again after a failure
+    /// resumes where the download stopped. With an attachment cache
+    /// attached, a cached file is copied from there instead, and a
+    /// downloaded one is added to it.
+    ///
+    /// # Returns
+    ///
+    /// The attachment's metadata
+    ///
+    /// # Errors
+    ///
+    /// - `MessageNotFound` if the message is not in this channel
+    /// - `InvalidOperation` if it has no attachment, or no attachment store
+    ///   is attached
+    /// - `PayloadTooLarge` if the file is over the configured
+    ///   `max_attachment_size`
+    /// - `ChunkUnavailable` if a chunk couldn't be fetched after retries
+    pub async fn download_attachment(
+        &self,
+        channel_id: &ChannelId,
+        message_id: MessageId,
+        dest: &Path,
+    ) -> MvpResult<Attachment> {
+        let message = self
+            .store
+            .get_message(&message_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .filter(|message| &message.channel_id == channel_id)
+            .ok_or_else(|| MvpError::MessageNotFound(message_id.0.clone()))?;
+        let manifest = AttachmentManifest::decode(message.current_content())
+            .filter(|_| !message.deleted)
+            .ok_or_else(|| {
+                MvpError::InvalidOperation(format!("Message {} has no attachment", message_id))
+            })?;
+
+        let max_size = self.config.store.max_attachment_size;
+        if manifest.size > max_size {
+            return Err(MvpError::PayloadTooLarge {
+                size: usize::try_from(manifest.size).unwrap_or(usize::MAX),
+                limit: usize::try_from(max_size).unwrap_or(usize::MAX),
+            });
+        }
+        let dir = dest
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        self.preflight.check(dir, manifest.size)?;
+
+        let metadata = manifest.metadata();
+        let cached = match &self.attachments {
+            Some(cache) => cache.read(&manifest.id).map_err(|e| MvpError::Store(e.to_string()))?,
+            None => None,
+        };
+        if let Some(data) = cached {
+            std::fs::write(dest, data).map_err(|e| {
+                MvpError::Store(format!("Failed to write {}: {}", dest.display(), e))
+            })?;
+            debug!(attachment = %manifest.id, "Attachment served from cache");
+            return Ok(metadata);
+        }
+
+        self.attachment_store()?.download(&manifest, dest).await?;
+        info!(
+            channel_id = %channel_id,
+            attachment = %manifest.id,
+            size = manifest.size,
+            "Downloaded attachment"
+        );
+
+        if let Some(cache) = &self.attachments {
+            let cached = std::fs::read(dest).map_err(|e| e.to_string()).and_then(|data| {
+                cache
+                    .insert_downloaded(&message_id, &metadata, &data)
+                    .map_err(|e| e.to_string())
+            });
+            if let Err(e) = cached {
+                warn!(attachment = %manifest.id, error = %e, "Failed to cache attachment");
+            }
+        }
+        Ok(metadata)
+    }
+
+    fn attachment_store(&self) -> MvpResult<&Arc<AttachmentStore>> {
+        self.attachment_store.as_ref().ok_or_else(|| {
+            MvpError::InvalidOperation("Attachment store not configured".to_string())
+        })
+    }
+
+    /// Edit a message we sent
+    ///
+    /// The new body goes to the other members as a change signed with our
+    /// leaf key; each of them checks that we sent the original before
+    /// applying it. The body it replaces is kept in the message's edit
+    /// history.
+    ///
+    /// # Returns
+    ///
+    /// The encrypted change, already sent to the channel
+    ///
+    /// # Errors
+    ///
+    /// - `MessageNotFound` if the message is not in this channel
+    /// - `PermissionDenied` if someone else sent it
+    /// - `InvalidOperation` if it was deleted
+    pub async fn edit_message(
+        &self,
+        channel_id: &ChannelId,
+        message_id: MessageId,
+        new_body: &[u8],
+    ) -> MvpResult<Vec<u8>> {
+        self.ensure_writable(channel_id).await?;
+
+        let original = self.stored_message(channel_id, &message_id)?;
+        if original.sender != self.identity.user_id {
+            return Err(MvpError::PermissionDenied {
+                user: self.identity.user_id.to_string(),
+                action: "edit another member's message".to_string(),
+                channel: channel_id.to_string(),
+            });
+        }
+        if original.deleted {
+            return Err(MvpError::InvalidOperation("Message was deleted".to_string()));
+        }
+
+        let change = MessageChange::edit(
+            channel_id.clone(),
+            message_id,
+            new_body.to_vec(),
+            self.identity.user_id.clone(),
+        );
+        self.send_change(change, original).await
+    }
+
+    /// Delete a message, leaving a tombstone in its place
+    ///
+    /// Members may delete their own messages; admins may delete anyone's.
+    /// The body and its edit history are dropped from the store.
+    ///
+    /// # Returns
+    ///
+    /// The encrypted change, already sent to the channel
+    ///
+    /// # Errors
+    ///
+    /// - `MessageNotFound` if the message is not in this channel
+    /// - `PermissionDenied` if someone else sent it and we lack the
+    ///   `delete_messages` permission
+    pub async fn delete_message(
+        &self,
+        channel_id: &ChannelId,
+        message_id: MessageId,
+    ) -> MvpResult<Vec<u8>> {
+        self.ensure_writable(channel_id).await?;
+
+        let original = self.stored_message(channel_id, &message_id)?;
+        if original.sender != self.identity.user_id {
+            self.require_permission(
+                channel_id,
+                Permission::DeleteMessages,
+                "delete another member's message",
+            )
+            .await?;
+        }
+
+        let change =
+            MessageChange::delete(channel_id.clone(), message_id, self.identity.user_id.clone());
+        self.send_change(change, original).await
+    }
+
+    /// A stored message, if it is in the given channel
+    fn stored_message(
+        &self,
+        channel_id: &ChannelId,
+        message_id: &MessageId,
+    ) -> MvpResult<crate::core_store::model::Message> {
+        self.store
+            .get_message(message_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .filter(|message| &message.channel_id == channel_id)
+            .ok_or_else(|| MvpError::MessageNotFound(message_id.0.clone()))
+    }
+
+    /// Sign a change, apply it to our copy and send it to the channel
+    async fn send_change(
+        &self,
+        mut change: MessageChange,
+        original: crate::core_store::model::Message,
+    ) -> MvpResult<Vec<u8>> {
+        let channel_id = change.channel_id.clone();
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        change.signature = self
+            .mls_service
+            .sign_as_member(&group_id, &change.signed_bytes())
+            .await
+            .map_err(|e| match e {
+                MlsError::GroupNotFound(_) => MvpError::ChannelNotFound(channel_id.to_string()),
+                other => MvpError::Mls(other),
+            })?;
+
+        let padded_plaintext = self
+            .channel_padding(&channel_id)?
+            .pad(&change.encode()?)
+            .map_err(|e| MvpError::InvalidOperation(format!("Failed to pad message: {}", e)))?;
+        let ciphertext = self.mls_service.send_message(&group_id, &padded_plaintext).await?;
+        self.apply_change(&change, original).await?;
+        self.deliver_or_defer(&channel_id, &ciphertext).await?;
+
+        info!(
+            channel_id = %channel_id,
+            message_id = %change.target,
+            change = ?change.message_type,
+            "Sent message change"
+        );
+        Ok(ciphertext)
+    }
+
+    /// Deliver one of our messages, or queue it in the outbox
+    ///
+    /// Messages are queued while the channel's join is deferred (it isn't
+    /// announced yet), while earlier messages to the channel are still
+    /// queued (to keep them in order), and when the network can't be
+    /// reached. Other delivery errors are returned.
+    async fn deliver_or_defer(&self, channel_id: &ChannelId, ciphertext: &[u8]) -> MvpResult<()> {
+        let broadcast = self.broadcast_policy(channel_id).await.is_some();
+        let _flush = self.outbox_flush.lock().await;
+        let outbox = self.store.outbox();
+        if !outbox.is_deferred(channel_id) && !outbox.has_pending(channel_id) {
+            match self.deliver(channel_id, broadcast, ciphertext, &self.identity.user_id).await {
+                Err(e) if is_unreachable(&e) => {
+                    warn!(
+                        channel_id = %channel_id,
+                        error = %e,
+                        "Network unreachable, queueing message"
+                    );
+                }
+                result => return result,
+            }
+        }
+
+        let id = outbox
+            .enqueue(
+                channel_id.clone(),
+                self.identity.user_id.clone(),
+                ciphertext.to_vec(),
+                broadcast,
+            )
+            .map_err(|e| MvpError::Store(e.to_string()))?;
+        debug!(channel_id = %channel_id, id, "Queued message in outbox");
+
+        let evicted = outbox
+            .evict_oldest(self.outbox_max_bytes, id)
+            .map_err(|e| MvpError::Store(e.to_string()))?;
+        for envelope in evicted {
+            let message_id = MessageId::from_ciphertext(&envelope.ciphertext);
+            warn!(
+                channel_id = %envelope.channel_id,
+                message_id = %message_id,
+                "Outbox full, dropped oldest undelivered message"
+            );
+            record_counter("mvp.outbox.evicted", 1);
+            let kind = ChannelEventKind::MessageEvicted { message_id };
+            self.emit_event(&envelope.channel_id, Some(envelope.sender), kind).await;
+        }
+        Ok(())
+    }
+
+    /// Check a received change and apply it, or hold it for its message
+    ///
+    /// The signature must come from a current leaf of the editor; whether
+    /// the editor may change the message is checked once it is known.
+    async fn receive_change(&self, channel_id: &ChannelId, change: MessageChange) {
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let signed = &change.channel_id == channel_id
+            && self
+                .mls_service
+                .verify_member_signature(
+                    &group_id,
+                    change.editor.0.as_bytes(),
+                    &change.signed_bytes(),
+                    &change.signature,
+                )
+                .await
+                .unwrap_or(false);
+        if !signed {
+            warn!(
+                channel_id = %channel_id,
+                editor = %change.editor,
+                "Dropping message change not signed by the editor"
+            );
+            return;
+        }
+
+        match self.store.get_message(&change.target) {
+            Ok(Some(original)) => self.authorize_and_apply(&change, original).await,
+            Ok(None) => {
+                debug!(
+                    channel_id = %channel_id,
+                    message_id = %change.target,
+                    "Holding change until its message arrives"
+                );
+                self.pending_changes.lock().await.hold(change);
+            }
+            Err(e) => warn!(channel_id = %channel_id, error = %e, "Failed to load message"),
+        }
+    }
+
+    /// Apply the changes held for a message that just arrived
+    ///
+    /// # Returns
+    ///
+    /// The message with the changes applied
+    async fn apply_held_changes(&self, message: ChatMessage) -> ChatMessage {
+        let held = self.pending_changes.lock().await.take(&message.message_id);
+        if held.is_empty() {
+            return message;
+        }
+
+        for change in held {
+            match self.stored_message(&message.channel_id, &change.target) {
+                Ok(original) => self.authorize_and_apply(&change, original).await,
+                Err(e) => warn!(error = %e, "Failed to apply held message change"),
+            }
+        }
+        match self.stored_message(&message.channel_id, &message.message_id) {
+            Ok(stored) => chat_message_from_store(stored),
+            Err(_) => message,
+        }
+    }
+
+    /// Apply a verified change if its editor may make it
+    async fn authorize_and_apply(
+        &self,
+        change: &MessageChange,
+        original: crate::core_store::model::Message,
+    ) {
+        let authorized = original.channel_id == change.channel_id
+            && (change.editor == original.sender
+                || (change.message_type == MessageType::Delete
+                    && self
+                        .member_permissions(&change.channel_id, change.editor.0.as_bytes())
+                        .await
+                        .is_ok_and(|permissions| {
+                            permissions.contains(Permission::DeleteMessages)
+                        })));
+        if !authorized {
+            warn!(
+                channel_id = %change.channel_id,
+                editor = %change.editor,
+                message_id = %change.target,
+                "Dropping message change by a member who may not make it"
+            );
+            return;
+        }
+
+        if let Err(e) = self.apply_change(change, original).await {
+            warn!(channel_id = %change.channel_id, error = %e, "Failed to apply message change");
+        }
+    }
+
+    /// Update the stored message with an edit or deletion
+    ///
+    /// Edits are kept in timestamp order, so the latest one is the body
+    /// whatever order they arrive in. A deleted message keeps neither its
+    /// body nor its edits, and ignores later edits.
+    async fn apply_change(
+        &self,
+        change: &MessageChange,
+        mut message: crate::core_store::model::Message,
+    ) -> MvpResult<()> {
+        if message.deleted {
+            return Ok(());
+        }
+        if change.message_type == MessageType::Delete {
+            message.content = Vec::new();
+            message.edits.clear();
+            message.delete();
+        } else {
+            let known = message.edits.iter().any(|(timestamp, editor, body)| {
+                *timestamp == change.timestamp && *editor == change.editor && *body == change.body
+            });
+            if known {
+                return Ok(());
+            }
+            message.add_edit(change.timestamp, change.editor.clone(), change.body.clone());
+            message.edits.sort_by_key(|(timestamp, _, _)| *timestamp);
+        }
+
+        self.store.store_message(&message).map_err(|e| MvpError::Store(e.to_string()))?;
+
+        let mut messages = self.messages.write().await;
+        if let Some(cached) = messages
+            .get_mut(&message.channel_id)
+            .and_then(|cached| cached.iter_mut().find(|m| m.message_id == message.id))
+        {
+            *cached = chat_message_from_store(message);
+        }
+        Ok(())
+    }
+
+    /// Store a message (for testing/MVP)
+    ///
+    /// Persists the message to both in-memory cache and CRDT store.
+    /// Messages are encrypted via MLS before being stored.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - ChatMessage to store
+    pub async fn store_message(&self, mut message: ChatMessage) -> MvpResult<()> {
+        if message.language.is_none() {
+            message.language = message.body_as_string().as_deref().and_then(detect_language);
         }
+        let manifest = mark_attachment(&mut message);
 
-        debug!("Permission check passed");
+        // Store in memory cache for fast thread queries
+        let mut messages = self.messages.write().await;
+        messages
+            .entry(message.channel_id.clone())
+            .or_insert_with(Vec::new)
+            .push(message.clone());
 
-        // Map channel ID to group ID
-        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        // Convert ChatMessage to store Message format
+        use crate::core_store::model::Message as StoreMessage;
 
-        // Get group metadata to find the member's leaf index
-        let metadata = self.mls_service.get_metadata(&group_id).await.map_err(|e| {
-            warn!(error = ?e, "Failed to get group metadata");
-            MvpError::Mls(e)
-        })?;
+        let mut store_msg = StoreMessage::new(
+            message.message_id.clone(),
+            message.channel_id.clone(),
+            message.sender.clone(),
+            message.body.clone(), // In production, this would be encrypted
+            message.timestamp,
+        );
+        store_msg.attachments.extend(manifest.map(|manifest| manifest.metadata()));
 
-        // Find the member's leaf index
-        let leaf_index = metadata
-            .members
-            .iter()
-            .find(|m| m.identity == member_identity)
-            .map(|m| m.leaf_index)
-            .ok_or_else(|| {
-                warn!(
-                    member = ?std::str::from_utf8(member_identity).unwrap_or("<non-utf8>"),
-                    "Member not found in group"
-                );
-                MvpError::InvalidOperation(format!(
-                    "Member {} not found in channel",
-                    std::str::from_utf8(member_identity).unwrap_or("<non-utf8>")
-                ))
-            })?;
+        // Persist to CRDT store
+        self.store
+            .store_message(&store_msg)
+            .map_err(|e| MvpError::Store(e.to_string()))?;
 
-        debug!(leaf_index, "Found member's leaf index");
+        Ok(())
+    }
 
-        // Remove the member via MLS service
-        let commit =
-            self.mls_service
-                .remove_members(&group_id, vec![leaf_index])
-                .await
-                .map_err(|e| {
-                    warn!(error = ?e, "Failed to remove member");
-                    MvpError::Mls(e)
-                })?;
+    /// Load messages for a channel from persistent storage
+    ///
+    /// Populates the in-memory cache with messages from the CRDT store.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Channel to load messages for
+    pub async fn load_channel_messages(&self, channel_id: &ChannelId) -> MvpResult<()> {
+        use crate::core_store::model::Message as StoreMessage;
 
-        // Broadcast removal commit to remaining channel members
-        if let Some(ref network) = self.network {
-            debug!(
-                channel_id = %channel_id,
-                commit_size = commit.len(),
-                "Broadcasting removal commit to channel"
-            );
+        // Get messages from store
+        let store_messages = self
+            .store
+            .get_channel_messages(channel_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?;
 
-            if let Err(e) = network.broadcast_commit(channel_id, commit.clone()).await {
-                warn!(
-                    error = %e,
-                    "Failed to broadcast removal commit, members may be out of sync"
-                );
-            }
+        // Convert to ChatMessage and load into memory
+        let mut messages = self.messages.write().await;
+        let channel_messages = messages.entry(channel_id.clone()).or_insert_with(Vec::new);
+
+        for store_msg in store_messages {
+            channel_messages.push(chat_message_from_store(store_msg));
         }
 
-        info!(
+        debug!(
             channel_id = %channel_id,
-            leaf_index,
-            commit_size = commit.len(),
-            network_broadcast = self.network.is_some(),
-            "Member removed successfully"
+            count = channel_messages.len(),
+            "Loaded messages from store"
         );
 
-        Ok(commit)
+        Ok(())
     }
 
-    /// Promote a member to Admin role
+    /// Get channel history, newest first
     ///
-    /// **NOTE**: Role persistence is not yet fully implemented.
-    /// Currently, roles are only stored in MLS GroupMetadata, which cannot be
-    /// directly modified without add/remove operations. In a production system,
-    /// roles would be stored in the CRDT layer alongside MLS state.
+    /// Reads persisted messages (sent and received) from the store. Pass the
+    /// ID of the oldest message of the previous page as `before` to get the
+    /// next page.
     ///
     /// # Arguments
     ///
-    /// * `channel_id` - Target channel
-    /// * `member_identity` - Identity bytes of the member to promote
+    /// * `channel_id` - Channel to read
+    /// * `limit` - Maximum number of messages to return
+    /// * `before` - Only return messages older than this one
     ///
     /// # Returns
     ///
-    /// Success if the member was found and actor has permission
+    /// Up to `limit` messages in reverse chronological order; empty once
+    /// the beginning of the history is reached
     ///
     /// # Errors
     ///
-    /// Returns `InvalidOperation` if:
-    /// - Actor is not an admin
-    /// - Member not found in channel
-    pub async fn promote_member(
+    /// Returns `MessageNotFound` if `before` is not a message in this channel
+    pub async fn get_messages(
         &self,
         channel_id: &ChannelId,
-        member_identity: &[u8],
-    ) -> MvpResult<()> {
-        // Check permission: Only admins can promote
-        let actor_identity = self.identity.user_id.0.as_bytes();
-        if !self.is_admin(channel_id, actor_identity).await? {
-            return Err(MvpError::InvalidOperation("Only admins can promote members".to_string()));
-        }
-
-        // Verify member exists
-        let _current_role = self.get_member_role(channel_id, member_identity).await?;
-
-        info!(
-            channel_id = %channel_id,
-            member = ?std::str::from_utf8(member_identity).unwrap_or("<non-utf8>"),
-            "Member promoted to Admin (role change pending CRDT integration)"
-        );
-
-        // TODO: Store role change in CRDT
-        // This would involve:
-        // 1. Get channel from store
-        // 2. Update member role in channel.permissions or similar
-        // 3. Broadcast CRDT update to other members
-        // 4. Persist change
+        limit: usize,
+        before: Option<MessageId>,
+    ) -> MvpResult<Vec<ChatMessage>> {
+        let page = self
+            .store
+            .get_channel_history(channel_id, limit, before.as_ref())
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .ok_or_else(|| MvpError::MessageNotFound(before.map(|id| id.0).unwrap_or_default()))?;
 
-        Ok(())
+        let read_up_to = self.read_position(channel_id)?;
+        Ok(page
+            .into_iter()
+            .map(|stored| {
+                let reactions = reaction_summaries(stored.reactions(), &self.identity.user_id);
+                let mut message = chat_message_from_store(stored);
+                let ours = message.sender == self.identity.user_id;
+                message.read =
+                    ours || read_up_to.is_some_and(|position| message.timestamp <= position);
+                message.reactions = reactions;
+                if ours {
+                    let receipts = self.store.receipts().get(&message.message_id);
+                    message.delivery = receipts.as_ref().map(DeliverySummary::from);
+                }
+                message
+            })
+            .collect())
     }
 
-    /// Demote a member to regular Member role
+    /// Export a channel's history as a transcript
     ///
-    /// **NOTE**: Role persistence is not yet fully implemented.
-    /// See `promote_member` documentation for details.
+    /// Writes every stored message oldest first, in the format and with the
+    /// redactions of `options` (see [`transcript`](crate::core_mvp::transcript)).
+    /// The history is read from the store a page at a time, so `out` can be
+    /// a file for a channel of any length.
     ///
     /// # Arguments
     ///
-    /// * `channel_id` - Target channel
-    /// * `member_identity` - Identity bytes of the member to demote
+    /// * `channel_id` - Channel to export
+    /// * `options` - Format and redactions, or just a `TranscriptFormat`
+    /// * `out` - Where to write the transcript
     ///
     /// # Returns
     ///
-    /// Success if the member was found and actor has permission
+    /// How many messages were written and how many were redacted
     ///
     /// # Errors
     ///
-    /// Returns `InvalidOperation` if:
-    /// - Actor is not an admin
-    /// - Member not found in channel
-    /// - Trying to demote the last admin (not yet enforced)
-    pub async fn demote_member(
+    /// Returns `ChannelNotFound` for an unknown channel, or `Store` if the
+    /// history cannot be read or `out` cannot be written
+    pub async fn export_channel<W: Write>(
         &self,
         channel_id: &ChannelId,
-        member_identity: &[u8],
-    ) -> MvpResult<()> {
-        // Check permission: Only admins can demote
-        let actor_identity = self.identity.user_id.0.as_bytes();
-        if !self.is_admin(channel_id, actor_identity).await? {
-            return Err(MvpError::InvalidOperation("Only admins can demote members".to_string()));
-        }
+        options: impl Into<TranscriptOptions>,
+        out: W,
+    ) -> MvpResult<TranscriptReport> {
+        let descriptor = self.get_channel(channel_id).await?;
+        let mut writer = TranscriptWriter::new(out, options.into(), channel_id, &descriptor.name)?;
+
+        let mut after: Option<MessageId> = None;
+        loop {
+            let page = self
+                .store
+                .get_channel_history_after(channel_id, EXPORT_PAGE_SIZE, after.as_ref())
+                .map_err(|e| MvpError::Store(e.to_string()))?
+                .ok_or_else(|| {
+                    // The last message of the previous page was pruned meanwhile
+                    MvpError::Store(format!("Channel {} changed during export", channel_id))
+                })?;
+            let Some(last) = page.last() else { break };
+            after = Some(last.id.clone());
 
-        // Verify member exists
-        let _current_role = self.get_member_role(channel_id, member_identity).await?;
+            for message in &page {
+                writer.write(message, &message.reactions())?;
+            }
+            if page.len() < EXPORT_PAGE_SIZE {
+                break;
+            }
+        }
 
-        info!(
-            channel_id = %channel_id,
-            member = ?std::str::from_utf8(member_identity).unwrap_or("<non-utf8>"),
-            "Member demoted to Member (role change pending CRDT integration)"
+        let report = writer.finish()?;
+        debug!(
+            "Exported channel {}: {} messages, {} redacted",
+            channel_id, report.messages, report.redacted
         );
-
-        // TODO: Store role change in CRDT (same as promote_member)
-        // TODO: Prevent demoting last admin
-
-        Ok(())
+        Ok(report)
     }
 
-    /// Get channel metadata
-    ///
-    /// # Arguments
+    /// Mark a channel read up to and including a message
     ///
-    /// * `channel_id` - Channel to query
+    /// Moves this user's read marker, which reaches their other devices
+    /// when the channel is synced. A marker behind the current one is
+    /// ignored, so devices marking concurrently keep the furthest. If the
+    /// channel shares read receipts, a marker that moved is also sent to
+    /// the other members.
     ///
     /// # Returns
     ///
-    /// Channel descriptor if found
-    pub async fn get_channel(&self, channel_id: &ChannelId) -> MvpResult<ChannelDescriptor> {
-        let channel = self
+    /// The read marker now in effect
+    ///
+    /// # Errors
+    ///
+    /// Returns `MessageNotFound` if the message is not in this channel
+    pub async fn mark_read(
+        &self,
+        channel_id: &ChannelId,
+        message_id: MessageId,
+    ) -> MvpResult<ReadMarker> {
+        let message = self
+            .store
+            .get_message(&message_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .filter(|message| &message.channel_id == channel_id)
+            .ok_or_else(|| MvpError::MessageNotFound(message_id.0.clone()))?;
+        let mut channel = self
             .store
             .get_channel(channel_id)
             .map_err(|e| MvpError::Store(e.to_string()))?
             .ok_or_else(|| MvpError::ChannelNotFound(channel_id.to_string()))?;
 
-        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
-
-        Ok(ChannelDescriptor::new(
-            channel_id.clone(),
-            channel.created_by.clone(),
-            channel.get_name().cloned().unwrap_or_default(),
-            false, // TODO: Track public/private in Channel model
-            group_id,
-        ))
-    }
-
-    /// List all channels for current user
-    pub async fn list_channels(&self) -> MvpResult<Vec<ChannelDescriptor>> {
-        // For MVP, list all groups from MLS service
-        let group_ids = self.mls_service.list_groups().await;
+        let user_id = self.identity.user_id.clone();
+        let marker = ReadMarker { message_id, position: message.timestamp };
+        if !channel.set_read_marker(user_id.clone(), marker.clone(), &self.identity.node_id) {
+            return Ok(channel.get_read_marker(&user_id).cloned().unwrap_or(marker));
+        }
+        self.store.store_channel(&channel).map_err(|e| MvpError::Store(e.to_string()))?;
 
-        let mut channels = Vec::new();
-        for group_id in group_ids {
-            // Convert group ID to channel ID
-            // GroupId is created from ChannelId UUID string bytes, so reverse the conversion
-            match String::from_utf8(group_id.as_bytes().to_vec()) {
-                Ok(channel_id_str) => {
-                    let channel_id = ChannelId(channel_id_str);
-                    if let Ok(descriptor) = self.get_channel(&channel_id).await {
-                        channels.push(descriptor);
-                    } else {
-                        debug!(channel_id = %channel_id, "Channel not found in store");
-                    }
-                }
-                Err(e) => {
-                    warn!(
-                        group_id = %group_id,
-                        error = %e,
-                        "Failed to convert GroupId to ChannelId UTF-8 string"
-                    );
-                }
+        if channel.shares_read_receipts() {
+            if let Err(e) = self.send_read_receipt(channel_id, &marker).await {
+                warn!(channel_id = %channel_id, error = %e, "Failed to send read receipt");
             }
         }
 
-        Ok(channels)
+        debug!(channel_id = %channel_id, message_id = %marker.message_id, "Marked channel read");
+        Ok(marker)
     }
 
-    /// Add a reaction to a message
-    ///
-    /// # Arguments
+    /// Get how far each member has read
     ///
-    /// * `message_id` - ID of the message to react to
-    /// * `emoji` - Emoji string (e.g., "👍", "❤️", "🎉")
-    ///
-    /// # Returns
-    ///
-    /// Success if reaction was added
-    ///
-    /// # Errors
-    ///
-    /// Returns error if user has already reacted with this emoji
-    pub async fn add_reaction(&self, message_id: &MessageId, emoji: String) -> MvpResult<()> {
-        let user_id = self.identity.user_id.clone();
-
-        info!(
-            message_id = %message_id,
-            emoji = %emoji,
-            user_id = %user_id,
-            "Adding reaction"
-        );
-
-        let mut reactions = self.reactions.write().await;
-        let message_reactions = reactions.entry(message_id.clone()).or_insert_with(Vec::new);
-
-        // Check if user already reacted with this emoji
-        if message_reactions.iter().any(|r| r.user_id == user_id && r.emoji == emoji) {
-            return Err(MvpError::InvalidOperation(
-                "User has already reacted with this emoji".to_string(),
-            ));
-        }
+    /// Other members' markers are only known if the channel shares read
+    /// receipts.
+    pub async fn read_markers(
+        &self,
+        channel_id: &ChannelId,
+    ) -> MvpResult<Vec<(UserId, ReadMarker)>> {
+        let channel = self
+            .store
+            .get_channel(channel_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .ok_or_else(|| MvpError::ChannelNotFound(channel_id.to_string()))?;
+        Ok(channel.get_read_markers())
+    }
 
-        // Add the reaction
-        message_reactions.push(Reaction::new(emoji, user_id));
+    /// Start or stop sharing read markers with the other members
+    ///
+    /// # Errors
+    ///
+    /// `PermissionDenied` without the `edit_channel` permission
+    pub async fn set_read_receipts(&self, channel_id: &ChannelId, enabled: bool) -> MvpResult<()> {
+        self.require_permission(channel_id, Permission::EditChannel, "change read receipts")
+            .await?;
 
-        debug!(
-            message_id = %message_id,
-            total_reactions = message_reactions.len(),
-            "Reaction added"
-        );
+        let mut channel = self
+            .store
+            .get_channel(channel_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .ok_or_else(|| MvpError::ChannelNotFound(channel_id.to_string()))?;
+        channel.set_read_receipts(enabled, &self.identity.node_id);
+        self.store.store_channel(&channel).map_err(|e| MvpError::Store(e.to_string()))?;
 
+        info!(channel_id = %channel_id, enabled, "Changed read receipts");
         Ok(())
     }
 
-    /// Remove a reaction from a message
-    ///
-    /// # Arguments
-    ///
-    /// * `message_id` - ID of the message
-    /// * `emoji` - Emoji to remove
-    ///
-    /// # Returns
-    ///
-    /// Success if reaction was removed
-    ///
-    /// # Errors
+    /// Timestamp up to which this user has read a channel
     ///
-    /// Returns error if reaction doesn't exist
-    pub async fn remove_reaction(&self, message_id: &MessageId, emoji: String) -> MvpResult<()> {
-        let user_id = self.identity.user_id.clone();
+    /// Devices store a received message with their own receive time, so
+    /// the marked message's local timestamp is used when we have it.
+    fn read_position(&self, channel_id: &ChannelId) -> MvpResult<Option<Timestamp>> {
+        let Some(channel) =
+            self.store.get_channel(channel_id).map_err(|e| MvpError::Store(e.to_string()))?
+        else {
+            return Ok(None);
+        };
+        let Some(marker) = channel.get_read_marker(&self.identity.user_id) else {
+            return Ok(None);
+        };
 
-        info!(
-            message_id = %message_id,
-            emoji = %emoji,
-            user_id = %user_id,
-            "Removing reaction"
-        );
+        let local = self
+            .store
+            .get_message(&marker.message_id)
+            .map_err(|e| MvpError::Store(e.to_string()))?
+            .map(|message| message.timestamp);
+        Ok(Some(local.unwrap_or(marker.position)))
+    }
+
+    /// Send a read marker to the other members as an application message
+    ///
+    /// Nothing is sent in broadcast channels, where only publishers post,
+    /// or in channels joined offline and not yet announced.
+    async fn send_read_receipt(
+        &self,
+        channel_id: &ChannelId,
+        marker: &ReadMarker,
+    ) -> MvpResult<()> {
+        if self.broadcast_policy(channel_id).await.is_some()
+            || self.store.outbox().is_deferred(channel_id)
+        {
+            return Ok(());
+        }
 
-        let mut reactions = self.reactions.write().await;
+        let padded_plaintext = self
+            .channel_padding(channel_id)?
+            .pad(&encode_read_receipt(marker)?)
+            .map_err(|e| MvpError::InvalidOperation(format!("Failed to pad message: {}", e)))?;
 
-        if let Some(message_reactions) = reactions.get_mut(message_id) {
-            let initial_len = message_reactions.len();
-            message_reactions.retain(|r| !(r.user_id == user_id && r.emoji == emoji));
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let ciphertext = self.mls_service.send_message(&group_id, &padded_plaintext).await?;
+        self.deliver(channel_id, false, &ciphertext, &self.identity.user_id).await
+    }
 
-            if message_reactions.len() == initial_len {
-                return Err(MvpError::InvalidOperation("Reaction not found".to_string()));
+    /// Record a read marker another member sent
+    ///
+    /// Our messages up to the marked one count as read by that member.
+    async fn apply_read_receipt(
+        &self,
+        channel_id: &ChannelId,
+        sender: &UserId,
+        marker: ReadMarker,
+    ) {
+        let mut channel = match self.store.get_channel(channel_id) {
+            Ok(Some(channel)) => channel,
+            Ok(None) => return,
+            Err(e) => {
+                warn!(channel_id = %channel_id, error = %e, "Failed to load channel");
+                return;
             }
+        };
 
-            debug!(
-                message_id = %message_id,
-                remaining_reactions = message_reactions.len(),
-                "Reaction removed"
-            );
+        // As in read_position, prefer our own timestamp of the marked message
+        let local = self.store.get_message(&marker.message_id).ok().flatten();
+        let position = local.map(|message| message.timestamp).unwrap_or(marker.position);
+        if !channel.set_read_marker(sender.clone(), marker, &sender.0) {
+            return;
+        }
+        if let Err(e) = self.store.store_channel(&channel) {
+            warn!(channel_id = %channel_id, error = %e, "Failed to store read marker");
+        }
 
-            Ok(())
-        } else {
-            Err(MvpError::InvalidOperation("No reactions found for this message".to_string()))
+        let receipts = self.store.receipts();
+        match receipts.advance_up_to(channel_id, sender, position, DeliveryState::Read) {
+            Ok(moved) => {
+                for (message_id, receipts) in moved {
+                    self.emit_delivery(channel_id, sender, message_id, &receipts).await;
+                }
+            }
+            Err(e) => warn!(channel_id = %channel_id, error = %e, "Failed to record read receipt"),
         }
     }
 
-    /// Get aggregated reactions for a message
+    /// Per-member delivery state of one of our messages
     ///
-    /// # Arguments
-    ///
-    /// * `message_id` - ID of the message
+    /// Only members the message was sent to are listed; those who joined
+    /// later aren't.
     ///
-    /// # Returns
+    /// # Errors
     ///
-    /// List of reaction summaries grouped by emoji
-    pub async fn get_reactions(&self, message_id: &MessageId) -> MvpResult<Vec<ReactionSummary>> {
-        let reactions = self.reactions.read().await;
-        let user_id = self.identity.user_id.clone();
-
-        let message_reactions = reactions.get(message_id).map(|v| v.as_slice()).unwrap_or(&[]);
-
-        // Group reactions by emoji
-        let mut emoji_map: HashMap<String, Vec<&Reaction>> = HashMap::new();
-        for reaction in message_reactions {
-            emoji_map.entry(reaction.emoji.clone()).or_insert_with(Vec::new).push(reaction);
-        }
-
-        // Convert to ReactionSummary
-        let mut summaries: Vec<ReactionSummary> = emoji_map
-            .into_iter()
-            .map(|(emoji, reactions)| {
-                let users: Vec<UserId> = reactions.iter().map(|r| r.user_id.clone()).collect();
-                let user_reacted = users.contains(&user_id);
-
-                ReactionSummary { emoji, count: reactions.len(), users, user_reacted }
-            })
-            .collect();
-
-        // Sort by count (descending), then by emoji
-        summaries.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.emoji.cmp(&b.emoji)));
-
-        Ok(summaries)
+    /// `MessageNotFound` unless we sent the message to this channel
+    pub fn delivery_receipts(
+        &self,
+        channel_id: &ChannelId,
+        message_id: &MessageId,
+    ) -> MvpResult<Vec<(UserId, DeliveryState)>> {
+        self.store
+            .receipts()
+            .get(message_id)
+            .filter(|receipts| &receipts.channel_id == channel_id)
+            .map(|receipts| receipts.recipients.into_iter().collect())
+            .ok_or_else(|| MvpError::MessageNotFound(message_id.0.clone()))
     }
 
-    /// Store a message (for testing/MVP)
+    /// Send the acknowledgements that are due
     ///
-    /// Persists the message to both in-memory cache and CRDT store.
-    /// Messages are encrypted via MLS before being stored.
+    /// Each channel gets at most one bundle every [`ACK_INTERVAL`] (see
+    /// [`with_ack_interval`](Self::with_ack_interval)); messages received
+    /// in between are acknowledged in the next one. A bundle that can't
+    /// reach the network is put back for the next call.
     ///
-    /// # Arguments
+    /// # Returns
     ///
-    /// * `message` - ChatMessage to store
-    pub async fn store_message(&self, message: ChatMessage) -> MvpResult<()> {
-        // Store in memory cache for fast thread queries
-        let mut messages = self.messages.write().await;
-        messages
-            .entry(message.channel_id.clone())
-            .or_insert_with(Vec::new)
-            .push(message.clone());
-
-        // Convert ChatMessage to store Message format
-        use crate::core_store::model::Message as StoreMessage;
-
-        let store_msg = StoreMessage::new(
-            message.message_id.clone(),
-            message.channel_id.clone(),
-            message.sender.clone(),
-            message.body.clone(), // In production, this would be encrypted
-            message.timestamp,
-        );
-
-        // Persist to CRDT store
-        self.store
-            .store_message(&store_msg)
-            .map_err(|e| MvpError::Store(e.to_string()))?;
-
-        Ok(())
+    /// The encrypted bundles, already sent, with their channels
+    pub async fn flush_acks(&self) -> Vec<(ChannelId, Vec<u8>)> {
+        let due = self.pending_acks.lock().await.take_due(Instant::now(), self.ack_interval);
+        let mut sent = Vec::new();
+        for (channel_id, ack) in due {
+            match self.send_ack(&channel_id, &ack).await {
+                Ok(ciphertext) => sent.push((channel_id, ciphertext)),
+                Err(e) => {
+                    warn!(channel_id = %channel_id, error = %e, "Failed to send acknowledgements");
+                    if is_unreachable(&e) {
+                        self.pending_acks.lock().await.restore(channel_id, ack.message_ids);
+                    }
+                }
+            }
+        }
+        sent
     }
 
-    /// Load messages for a channel from persistent storage
-    ///
-    /// Populates the in-memory cache with messages from the CRDT store.
+    /// Start sending due acknowledgements in the background
     ///
     /// # Arguments
+    /// * `tick` - How often to check for due bundles
     ///
-    /// * `channel_id` - Channel to load messages for
-    pub async fn load_channel_messages(&self, channel_id: &ChannelId) -> MvpResult<()> {
-        use crate::core_store::model::Message as StoreMessage;
+    /// # Returns
+    /// JoinHandle for the background task
+    pub fn spawn_ack_sender(self: Arc<Self>, tick: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(tick);
+            loop {
+                interval.tick().await;
+                self.flush_acks().await;
+            }
+        })
+    }
 
-        // Get messages from store
-        let store_messages = self
-            .store
-            .get_channel_messages(channel_id)
-            .map_err(|e| MvpError::Store(e.to_string()))?;
+    /// Queue an acknowledgement of a chat message another member sent
+    ///
+    /// Nothing is acknowledged in broadcast channels, where readers don't
+    /// post, or in channels joined offline and not yet announced.
+    async fn queue_ack(&self, message: &ChatMessage) {
+        if message.sender == self.identity.user_id
+            || self.broadcast_policy(&message.channel_id).await.is_some()
+            || self.store.outbox().is_deferred(&message.channel_id)
+        {
+            return;
+        }
+        let (channel_id, message_id) = (message.channel_id.clone(), message.message_id.clone());
+        self.pending_acks.lock().await.push(channel_id, message_id);
+    }
 
-        // Convert to ChatMessage and load into memory
-        let mut messages = self.messages.write().await;
-        let channel_messages = messages.entry(channel_id.clone()).or_insert_with(Vec::new);
+    /// Encrypt and send a bundle of acknowledgements
+    async fn send_ack(&self, channel_id: &ChannelId, ack: &DeliveryAck) -> MvpResult<Vec<u8>> {
+        let padded_plaintext = self
+            .channel_padding(channel_id)?
+            .pad(&ack.encode()?)
+            .map_err(|e| MvpError::InvalidOperation(format!("Failed to pad message: {}", e)))?;
 
-        for store_msg in store_messages {
-            // Convert store Message to ChatMessage
-            let chat_msg = ChatMessage {
-                message_id: store_msg.id.clone(),
-                channel_id: store_msg.channel_id.clone(),
-                sender: store_msg.sender.clone(),
-                timestamp: store_msg.timestamp,
-                body: store_msg.content.clone(),
-                reply_to: store_msg.reply_to.clone(),
-                message_type: crate::core_mvp::types::MessageType::Text,
-            };
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let ciphertext = self.mls_service.send_message(&group_id, &padded_plaintext).await?;
+        self.deliver(channel_id, false, &ciphertext, &self.identity.user_id).await?;
+        Ok(ciphertext)
+    }
 
-            channel_messages.push(chat_msg);
+    /// Record a member's acknowledgement of our messages
+    ///
+    /// IDs of messages we didn't send to that member are ignored.
+    async fn receive_ack(&self, channel_id: &ChannelId, sender: &UserId, ack: DeliveryAck) {
+        let receipts = self.store.receipts();
+        for message_id in ack.message_ids {
+            match receipts.advance(channel_id, &message_id, sender, DeliveryState::Delivered) {
+                Ok(Some(moved)) => self.emit_delivery(channel_id, sender, message_id, &moved).await,
+                Ok(None) => {}
+                Err(e) => {
+                    warn!(channel_id = %channel_id, error = %e, "Failed to record acknowledgement");
+                }
+            }
         }
+    }
 
-        debug!(
-            channel_id = %channel_id,
-            count = channel_messages.len(),
-            "Loaded messages from store"
+    /// Start tracking delivery of a message we sent to everyone else in the
+    /// roster it was encrypted for
+    async fn track_delivery(
+        &self,
+        channel_id: &ChannelId,
+        group_id: &GroupId,
+        message_id: MessageId,
+        sent_at: Timestamp,
+    ) {
+        let metadata = match self.mls_service.get_metadata(group_id).await {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                debug!(channel_id = %channel_id, error = ?e, "No roster to track delivery with");
+                return;
+            }
+        };
+        let recipients: HashSet<UserId> = metadata
+            .members
+            .iter()
+            .map(|member| member_device(member).user_id)
+            .filter(|user_id| user_id != &self.identity.user_id)
+            .collect();
+        let result = self.store.receipts().track(
+            message_id,
+            channel_id.clone(),
+            metadata.epoch,
+            sent_at,
+            recipients,
         );
+        if let Err(e) = result {
+            warn!(channel_id = %channel_id, error = %e, "Failed to track delivery");
+        }
+    }
 
-        Ok(())
+    /// Tell subscribers how far one of our messages has got
+    async fn emit_delivery(
+        &self,
+        channel_id: &ChannelId,
+        member: &UserId,
+        message_id: MessageId,
+        receipts: &SentReceipts,
+    ) {
+        let kind = ChannelEventKind::DeliveryUpdated { message_id, summary: receipts.into() };
+        self.emit_event(channel_id, Some(member.clone()), kind).await;
     }
 
     /// Get messages from store for a channel
@@ -1480,6 +7033,33 @@ impl ChannelManager {
         Ok(replies)
     }
 
+    /// Get a channel's message history with display-time renderings
+    ///
+    /// Each entry carries the original message unchanged, plus the output
+    /// of the registered content transformers if any applied.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Channel to query
+    ///
+    /// # Returns
+    ///
+    /// Rendered messages sorted by timestamp
+    pub async fn get_rendered_messages(
+        &self,
+        channel_id: &ChannelId,
+    ) -> MvpResult<Vec<RenderedMessage>> {
+        let messages_lock = self.messages.read().await;
+
+        let mut rendered: Vec<RenderedMessage> = messages_lock
+            .get(channel_id)
+            .map(|messages| messages.iter().map(|msg| self.transformers.render(msg)).collect())
+            .unwrap_or_default();
+        rendered.sort_by_key(|r| r.message.timestamp);
+
+        Ok(rendered)
+    }
+
     /// Get message with thread context
     ///
     /// # Arguments
@@ -1635,6 +7215,159 @@ impl ChannelManager {
     }
 }
 
+/// Member set key for an MLS group member
+///
+/// Devices are told apart by a short prefix of their leaf signature key.
+fn member_device(member: &MemberInfo) -> MemberDevice {
+    let device_id = if member.signature_key.is_empty() {
+        format!("leaf-{}", member.leaf_index)
+    } else {
+        leaf_device_id(&member.signature_key)
+    };
+
+    MemberDevice {
+        user_id: UserId(String::from_utf8_lossy(&member.identity).into_owned()),
+        device_id,
+    }
+}
+
+/// Device ID of the leaf with this signature key
+fn leaf_device_id(signature_key: &[u8]) -> String {
+    hex::encode(&signature_key[..signature_key.len().min(8)])
+}
+
+/// Convert a persisted store message into a ChatMessage
+fn chat_message_from_store(store_msg: crate::core_store::model::Message) -> ChatMessage {
+    let body = store_msg.current_content().to_vec();
+    let language = std::str::from_utf8(&body).ok().and_then(detect_language);
+
+    // Each edit replaced the original or the edit before it
+    let replaced = std::iter::once(&store_msg.content).chain(store_msg.edits.iter().map(|e| &e.2));
+    let edits = store_msg
+        .edits
+        .iter()
+        .zip(replaced)
+        .map(|((edited_at, _, _), body)| MessageEdit { body: body.clone(), edited_at: *edited_at })
+        .collect();
+
+    ChatMessage {
+        message_id: store_msg.id,
+        channel_id: store_msg.channel_id,
+        sender: store_msg.sender,
+        timestamp: store_msg.timestamp,
+        body,
+        reply_to: store_msg.reply_to,
+        message_type: if store_msg.attachments.is_empty() {
+            MessageType::Text
+        } else {
+            MessageType::Attachment
+        },
+        language,
+        read: false,
+        edits,
+        deleted: store_msg.deleted,
+        reactions: Vec::new(),
+        delivery: None,
+    }
+}
+
+/// Mark a message carrying an attachment manifest as an attachment
+fn mark_attachment(message: &mut ChatMessage) -> Option<AttachmentManifest> {
+    let manifest = AttachmentManifest::decode(&message.body)?;
+    message.message_type = MessageType::Attachment;
+    Some(manifest)
+}
+
+/// Group (user, emoji) reactions by emoji, most popular first
+fn reaction_summaries(reactions: Vec<(UserId, String)>, me: &UserId) -> Vec<ReactionSummary> {
+    let mut by_emoji: HashMap<String, Vec<UserId>> = HashMap::new();
+    for (user, emoji) in reactions {
+        by_emoji.entry(emoji).or_default().push(user);
+    }
+
+    let mut summaries: Vec<ReactionSummary> = by_emoji
+        .into_iter()
+        .map(|(emoji, mut users)| {
+            users.sort();
+            let user_reacted = users.contains(me);
+            ReactionSummary { emoji, count: users.len(), users, user_reacted }
+        })
+        .collect();
+
+    // Sort by count (descending), then by emoji
+    summaries.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.emoji.cmp(&b.emoji)));
+    summaries
+}
+
+/// Marker at the start of a read receipt's plaintext
+const READ_RECEIPT_MAGIC: &[u8; 4] = b"SPRR";
+
+/// Plaintext of a read receipt sent to the other members
+pub(crate) fn encode_read_receipt(marker: &ReadMarker) -> MvpResult<Vec<u8>> {
+    let mut plaintext = READ_RECEIPT_MAGIC.to_vec();
+    let encoded = bincode::serialize(marker)
+        .map_err(|e| MvpError::Serialization(format!("Failed to serialize read receipt: {}", e)))?;
+    plaintext.extend_from_slice(&encoded);
+    Ok(plaintext)
+}
+
+/// Whether a delivery failed because the network couldn't be reached
+///
+/// Such messages are queued in the outbox rather than failing the send.
+fn is_unreachable(error: &MvpError) -> bool {
+    matches!(error, MvpError::NetworkError(_) | MvpError::Dht(_))
+}
+
+/// The read marker in a decrypted plaintext, if it is a read receipt
+fn decode_read_receipt(plaintext: &[u8]) -> Option<ReadMarker> {
+    bincode::deserialize(plaintext.strip_prefix(READ_RECEIPT_MAGIC.as_slice())?).ok()
+}
+
+/// Inbound handler for the manager's per-channel workers
+///
+/// Holds the manager weakly; the manager owns the supervisor.
+struct SupervisedInbound(Weak<ChannelManager>);
+
+#[async_trait::async_trait]
+impl InboundHandler for SupervisedInbound {
+    async fn handle(&self, work: InboundWork) {
+        let Some(manager) = self.0.upgrade() else {
+            return;
+        };
+        match work {
+            InboundWork::Commit(commit) => manager.handle_incoming_commit(commit).await,
+            InboundWork::Message(incoming) => {
+                let channel_id = incoming.channel_id.clone();
+                if let Err(e) = manager.handle_incoming_message(incoming).await {
+                    warn!(
+                        channel_id = %channel_id,
+                        error = %e,
+                        "Failed to handle incoming message"
+                    );
+                }
+            }
+        }
+    }
+
+    async fn park(&self, work: InboundWork) {
+        let Some(manager) = self.0.upgrade() else {
+            return;
+        };
+        match work {
+            // Stays in the inbox until the quarantine is lifted
+            InboundWork::Message(incoming) => {
+                let channel_id = incoming.channel_id.clone();
+                if let Err(e) = manager.park_incoming_message(incoming).await {
+                    warn!(channel_id = %channel_id, error = %e, "Failed to park incoming message");
+                }
+            }
+            InboundWork::Commit(commit) => {
+                warn!(channel_id = %commit.channel_id, "Dropping commit for unavailable channel");
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1696,4 +7429,119 @@ mod tests {
         let channels = manager.list_channels().await.unwrap();
         assert_eq!(channels.len(), 2);
     }
+
+    #[tokio::test]
+    async fn test_pin_attachment() {
+        let (manager, temp_dir) = create_test_manager().await;
+        let message_id = MessageId::generate();
+        assert!(manager.pin_attachment(&message_id).is_err());
+
+        let cache =
+            Arc::new(AttachmentCache::open(&temp_dir.path().join("attachments"), 1024, 3).unwrap());
+        let manager = Arc::try_unwrap(manager).ok().unwrap().with_attachment_cache(cache.clone());
+
+        manager.pin_attachment(&message_id).unwrap();
+        assert!(cache.is_pinned(&message_id));
+        assert!(manager.unpin_attachment(&message_id).unwrap());
+        assert!(!manager.unpin_attachment(&message_id).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_rendered_history_keeps_stored_plaintext() {
+        use crate::core_mvp::transform::DictionaryTransformer;
+
+        let (manager, _temp_dir) = create_test_manager().await;
+        let manager = Arc::try_unwrap(manager).ok().unwrap().with_content_transformer(Arc::new(
+            DictionaryTransformer::new("es", "en")
+                .with_entry("hola", "hello")
+                .with_entry("amigos", "friends"),
+        ));
+
+        let channel_id = ChannelId::generate();
+        let body = "Hola amigos, la reunión es por la tarde".to_string();
+        let message = ChatMessage::new(channel_id.clone(), UserId::generate(), body.clone().into());
+        manager.store_message(message).await.unwrap();
+
+        let rendered = manager.get_rendered_messages(&channel_id).await.unwrap();
+        assert_eq!(rendered.len(), 1);
+        assert_eq!(rendered[0].message.language.as_deref(), Some("es"));
+        assert_eq!(rendered[0].message.body, body.as_bytes());
+
+        let rendering = rendered[0].rendering.as_ref().unwrap();
+        assert_eq!(rendering.content, "Hello friends, la reunión es por la tarde");
+        assert_eq!(rendering.language.as_deref(), Some("en"));
+
+        // Neither the cache nor the store ever see the rendering
+        let stored = manager.get_stored_messages(&channel_id).await.unwrap();
+        assert_eq!(stored[0].content, body.as_bytes());
+        manager.load_channel_messages(&channel_id).await.unwrap();
+        let rendered = manager.get_rendered_messages(&channel_id).await.unwrap();
+        assert!(rendered.iter().all(|r| r.message.body == body.as_bytes()));
+    }
+
+    #[tokio::test]
+    async fn test_retention_prunes_oldest_messages() {
+        let (manager, _temp_dir) = create_test_manager().await;
+        let channel_id = manager.create_channel("general".to_string(), false).await.unwrap();
+        let mut events = manager.subscribe_events();
+
+        let sender = UserId::generate();
+        let mut ids = Vec::new();
+        for (i, body) in ["one", "two", "three"].into_iter().enumerate() {
+            let mut message =
+                ChatMessage::new(channel_id.clone(), sender.clone(), body.as_bytes().to_vec());
+            message.timestamp = Timestamp::from_millis(1_000 * (i as u64 + 1));
+            ids.push(message.message_id.clone());
+            manager.store_message(message).await.unwrap();
+        }
+
+        // Keeping forever prunes nothing
+        assert_eq!(manager.prune_expired().await.unwrap().total_pruned(), 0);
+
+        manager.set_retention(&channel_id, RetentionPolicy::KeepMessages(1)).await.unwrap();
+        assert!(manager
+            .set_retention(&channel_id, RetentionPolicy::KeepDays(0))
+            .await
+            .is_err());
+
+        let report = manager.prune_expired().await.unwrap();
+        assert_eq!(report.pruned, vec![(channel_id.clone(), 2)]);
+        assert!(report.failed.is_empty());
+
+        let stored = manager.get_stored_messages(&channel_id).await.unwrap();
+        assert_eq!(stored.iter().map(|m| &m.id).collect::<Vec<_>>(), vec![&ids[2]]);
+        let pruned = std::iter::from_fn(|| events.try_recv().ok()).find_map(|event| match event.kind {
+            ChannelEventKind::MessagesPruned { message_ids } => Some(message_ids),
+            _ => None,
+        });
+        assert_eq!(pruned, Some(vec![ids[0].clone(), ids[1].clone()]));
+    }
+
+    #[tokio::test]
+    async fn test_own_leaf_message_reports_identity_in_use_elsewhere() {
+        let (manager, _temp_dir) = create_test_manager().await;
+        let channel_id = manager.create_channel("general".to_string(), false).await.unwrap();
+        let mut security_events = manager.subscribe_security_events();
+        let incoming = |ciphertext: Vec<u8>| IncomingMessage {
+            channel_id: channel_id.clone(),
+            ciphertext,
+            sender_id: manager.identity.user_id.clone(),
+            sender_peer_id: crate::core_router::session_manager::PeerId(vec![1]),
+        };
+
+        // A redelivered copy of our own message is expected
+        let sent = manager.send_message(&channel_id, b"hello").await.unwrap();
+        assert!(manager.handle_incoming_message(incoming(sent)).await.unwrap().is_none());
+        assert!(security_events.try_recv().is_err());
+
+        // Our leaf, but not a message we sent: another copy of the identity
+        let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+        let cloned = manager.mls_service.send_message(&group_id, b"from elsewhere").await.unwrap();
+        assert!(manager.handle_incoming_message(incoming(cloned)).await.unwrap().is_none());
+        assert_eq!(
+            security_events.try_recv().unwrap(),
+            SecurityEvent::IdentityInUseElsewhere { channel_id: channel_id.clone(), epoch: 0 }
+        );
+        assert!(manager.store.inbox().pending(&channel_id).is_empty());
+    }
 }
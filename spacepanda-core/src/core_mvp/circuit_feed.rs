@@ -0,0 +1,138 @@
+//! Channel feed appends over onion circuits
+//!
+//! With [`FeatureFlags::anonymity_mode`](crate::config::FeatureFlags) on, a
+//! [`ChannelManager`](super::ChannelManager) with a [`FeedPublisher`] attached
+//! hands its feed appends to the publisher instead of writing the feed itself.
+//!
+//! [`CircuitFeedPublisher`] sends each append as a [`FeedPut`] through an
+//! onion circuit. The circuit's exit stores it with [`FeedPut::apply`], so
+//! the DHT sees the exit as the writer, while the exit only knows the relay
+//! before it. No single relay learns both who wrote an entry and which feed
+//! it went to.
+
+use crate::core_dht::ChannelFeed;
+use crate::core_mls::types::GroupId;
+use crate::core_mvp::errors::{MvpError, MvpResult};
+use crate::core_router::{CircuitId, InnerEnvelope, OnionRouter};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+/// Writes channel feed entries on this node's behalf
+#[async_trait]
+pub trait FeedPublisher: Send + Sync {
+    /// Append a payload to a group's feed
+    ///
+    /// The entry expires after `ttl`, or the feed's own TTL if `None`.
+    async fn append(
+        &self,
+        group_id: &GroupId,
+        message_id: &str,
+        payload: Vec<u8>,
+        ttl: Option<Duration>,
+    ) -> MvpResult<()>;
+}
+
+/// A feed append carried to a circuit's exit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeedPut {
+    pub group_id: GroupId,
+    pub message_id: String,
+    pub payload: Vec<u8>,
+    pub ttl: Option<Duration>,
+}
+
+impl FeedPut {
+    /// Read an append from an envelope delivered by a circuit
+    ///
+    /// The envelope's destination must be the key of the feed it names.
+    pub fn from_envelope(envelope: &InnerEnvelope) -> MvpResult<Self> {
+        let put: FeedPut = bincode::deserialize(&envelope.payload)
+            .map_err(|e| MvpError::Serialization(format!("Invalid feed put: {}", e)))?;
+        if envelope.destination != ChannelFeed::head_key(&put.group_id).as_bytes() {
+            return Err(MvpError::InvalidMessage(
+                "Feed put addressed to a different feed".to_string(),
+            ));
+        }
+        Ok(put)
+    }
+
+    /// Store the append in the exit's feed, returning its sequence number
+    pub fn apply(&self, feed: &ChannelFeed) -> MvpResult<u64> {
+        let payload = self.payload.clone();
+        match self.ttl {
+            Some(ttl) => feed.append_with_ttl(&self.group_id, &self.message_id, payload, ttl),
+            None => feed.append(&self.group_id, &self.message_id, payload),
+        }
+        .map_err(MvpError::Dht)
+    }
+}
+
+/// Publishes feed appends through an onion circuit, built on first use
+///
+/// The router rebuilds the circuit around relays that die; a circuit torn
+/// down after idling is replaced on the next append.
+pub struct CircuitFeedPublisher {
+    onion: Arc<OnionRouter>,
+    hops: usize,
+    circuit: Mutex<Option<CircuitId>>,
+}
+
+impl CircuitFeedPublisher {
+    /// Create a publisher using circuits of `hops` relays
+    pub fn new(onion: Arc<OnionRouter>, hops: usize) -> Self {
+        Self { onion, hops, circuit: Mutex::new(None) }
+    }
+
+    /// The current circuit, building one if there is none
+    async fn circuit(&self) -> MvpResult<CircuitId> {
+        let mut circuit = self.circuit.lock().await;
+        if let Some(circuit_id) = *circuit {
+            return Ok(circuit_id);
+        }
+        let circuit_id =
+            self.onion.build_circuit(self.hops).await.map_err(MvpError::NetworkError)?;
+        *circuit = Some(circuit_id);
+        Ok(circuit_id)
+    }
+}
+
+#[async_trait]
+impl FeedPublisher for CircuitFeedPublisher {
+    async fn append(
+        &self,
+        group_id: &GroupId,
+        message_id: &str,
+        payload: Vec<u8>,
+        ttl: Option<Duration>,
+    ) -> MvpResult<()> {
+        let put = FeedPut {
+            group_id: group_id.clone(),
+            message_id: message_id.to_string(),
+            payload,
+            ttl,
+        };
+        let bytes = bincode::serialize(&put)
+            .map_err(|e| MvpError::Serialization(format!("Failed to encode feed put: {}", e)))?;
+        let destination = ChannelFeed::head_key(group_id).as_bytes().to_vec();
+
+        let circuit_id = self.circuit().await?;
+        let sent = self
+            .onion
+            .send_through_circuit(circuit_id, destination.clone(), bytes.clone())
+            .await;
+        if sent.is_ok() {
+            return Ok(());
+        }
+
+        // Torn down after idling, or its rebuild failed: try once on a fresh circuit
+        self.circuit.lock().await.take();
+        let circuit_id = self.circuit().await?;
+        self.onion
+            .send_through_circuit(circuit_id, destination, bytes)
+            .await
+            .map_err(MvpError::NetworkError)
+    }
+}
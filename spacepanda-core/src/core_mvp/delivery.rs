@@ -0,0 +1,701 @@
+//! Delivery latency tracking against an SLO
+//!
+//! When a tracker is attached to the [`NetworkLayer`](crate::core_mvp::network::NetworkLayer),
+//! outgoing messages carry a hybrid logical clock (HLC) send timestamp and
+//! each recipient answers with a delivery receipt stamped by its own HLC.
+//! The latency of every receipt goes into a rolling per-channel window, and
+//! the window's compliance with the [`DeliverySloConfig`] drives a
+//! Healthy/Degraded state per channel.
+//!
+//! - Only recipients the message actually reached are expected to answer;
+//!   offline members, and receipts that never arrive, are left out rather
+//!   than counted as slow
+//! - Clock skew is handled on both sides: the recipient's HLC never stamps a
+//!   receipt before the send, and a latency is never taken as longer than
+//!   the round trip measured on the sender's own clock
+//! - A state change emits a [`DeliverySloEvent`] and updates the `delivery`
+//!   component of an attached [`HealthChecker`]
+
+use crate::config::DeliverySloConfig;
+use crate::core_store::model::types::{ChannelId, Timestamp, UserId};
+use crate::health::{HealthChecker, HealthStatus};
+use metrics::{gauge, histogram};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+/// File in the data directory `spacepanda stats delivery` reads
+pub const DELIVERY_REPORT_FILE: &str = "delivery_stats.json";
+
+/// Health component updated by the tracker
+pub const HEALTH_COMPONENT: &str = "delivery";
+
+/// Identifies a message to both sender and recipients (BLAKE3 of its ciphertext)
+pub type MessageRef = [u8; 32];
+
+/// Reference for a message ciphertext
+pub fn message_ref(ciphertext: &[u8]) -> MessageRef {
+    *blake3::hash(ciphertext).as_bytes()
+}
+
+/// Hybrid logical clock timestamp
+///
+/// Ordered by physical time, then by the logical counter.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct HybridTimestamp {
+    /// Wall-clock milliseconds since the Unix epoch
+    pub physical_ms: u64,
+
+    /// Counter ordering events within the same millisecond
+    pub logical: u32,
+}
+
+/// Hybrid logical clock
+///
+/// Follows wall time but never goes backwards, and never stamps an event
+/// before a remote timestamp it has observed.
+#[derive(Debug, Default)]
+pub struct HybridClock {
+    last: Mutex<HybridTimestamp>,
+}
+
+impl HybridClock {
+    /// Create a clock
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Timestamp a local event
+    pub fn now(&self) -> HybridTimestamp {
+        self.now_at(Timestamp::now().as_millis())
+    }
+
+    /// Timestamp the receipt of a remote event
+    pub fn observe(&self, remote: HybridTimestamp) -> HybridTimestamp {
+        self.observe_at(remote, Timestamp::now().as_millis())
+    }
+
+    fn now_at(&self, wall_ms: u64) -> HybridTimestamp {
+        let mut last = self.last.lock().unwrap();
+        *last = if wall_ms > last.physical_ms {
+            HybridTimestamp { physical_ms: wall_ms, logical: 0 }
+        } else {
+            HybridTimestamp { physical_ms: last.physical_ms, logical: last.logical + 1 }
+        };
+        *last
+    }
+
+    fn observe_at(&self, remote: HybridTimestamp, wall_ms: u64) -> HybridTimestamp {
+        let mut last = self.last.lock().unwrap();
+        let physical_ms = wall_ms.max(last.physical_ms).max(remote.physical_ms);
+        let logical = match (physical_ms == last.physical_ms, physical_ms == remote.physical_ms) {
+            (true, true) => last.logical.max(remote.logical) + 1,
+            (true, false) => last.logical + 1,
+            (false, true) => remote.logical + 1,
+            (false, false) => 0,
+        };
+        *last = HybridTimestamp { physical_ms, logical };
+        *last
+    }
+}
+
+/// Whether a channel is meeting its delivery objective
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SloState {
+    #[default]
+    Healthy,
+    Degraded,
+}
+
+/// Raised when a channel's SLO state changes
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeliverySloEvent {
+    /// Compliance stayed below the objective for the sustain period
+    Degraded { channel_id: ChannelId, compliance: f64, p95_ms: u64 },
+
+    /// Compliance stayed at the recovery objective for the sustain period
+    Recovered { channel_id: ChannelId, compliance: f64, p95_ms: u64 },
+}
+
+impl fmt::Display for DeliverySloEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Degraded { channel_id, compliance, p95_ms } => write!(
+                f,
+                "Delivery SLO breached in {}: {:.1}% on target, p95 {} ms",
+                channel_id,
+                compliance * 100.0,
+                p95_ms
+            ),
+            Self::Recovered { channel_id, compliance, p95_ms } => write!(
+                f,
+                "Delivery SLO recovered in {}: {:.1}% on target, p95 {} ms",
+                channel_id,
+                compliance * 100.0,
+                p95_ms
+            ),
+        }
+    }
+}
+
+/// Latency summary for one channel
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeliveryStats {
+    pub channel_id: ChannelId,
+
+    /// Receipts in the rolling window
+    pub samples: usize,
+
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub p99_ms: u64,
+
+    /// Share of samples within the target latency
+    pub compliance: f64,
+
+    /// Expected receipts that never arrived (not counted in compliance)
+    pub unacknowledged: u64,
+
+    pub state: SloState,
+}
+
+/// Snapshot of every tracked channel, persisted for the CLI
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeliveryReport {
+    pub generated_at_ms: u64,
+    pub target_latency_ms: u64,
+    pub objective: f64,
+    pub channels: Vec<DeliveryStats>,
+}
+
+impl DeliveryReport {
+    /// Stats for one channel
+    pub fn channel(&self, channel_id: &ChannelId) -> Option<&DeliveryStats> {
+        self.channels.iter().find(|stats| &stats.channel_id == channel_id)
+    }
+
+    /// Write the report, replacing any previous one atomically
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(tmp, path)
+    }
+
+    /// Read a saved report
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let json = std::fs::read(path)?;
+        Ok(serde_json::from_slice(&json)?)
+    }
+}
+
+/// Nearest-rank percentile of sorted values (`p` in 0..=1)
+fn percentile(sorted: &[u64], p: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// A message waiting on receipts
+struct PendingDelivery {
+    channel_id: ChannelId,
+    sent_at: HybridTimestamp,
+    awaiting: HashSet<UserId>,
+}
+
+/// Rolling latency window and SLO state of one channel
+#[derive(Default)]
+struct ChannelWindow {
+    /// (recorded at, latency) in milliseconds, oldest first
+    samples: VecDeque<(u64, u64)>,
+    unacknowledged: u64,
+    state: SloState,
+
+    /// When compliance last crossed the threshold that would change `state`
+    crossed_at: Option<u64>,
+}
+
+impl ChannelWindow {
+    fn stats(&self, channel_id: &ChannelId, target_ms: u64) -> DeliveryStats {
+        let mut sorted: Vec<u64> = self.samples.iter().map(|&(_, latency)| latency).collect();
+        sorted.sort_unstable();
+        let on_target = sorted.iter().filter(|&&latency| latency <= target_ms).count();
+        DeliveryStats {
+            channel_id: channel_id.clone(),
+            samples: sorted.len(),
+            p50_ms: percentile(&sorted, 0.50),
+            p95_ms: percentile(&sorted, 0.95),
+            p99_ms: percentile(&sorted, 0.99),
+            compliance: if sorted.is_empty() {
+                1.0
+            } else {
+                on_target as f64 / sorted.len() as f64
+            },
+            unacknowledged: self.unacknowledged,
+            state: self.state,
+        }
+    }
+}
+
+#[derive(Default)]
+struct TrackerState {
+    pending: HashMap<MessageRef, PendingDelivery>,
+    channels: HashMap<ChannelId, ChannelWindow>,
+}
+
+/// Tracks delivery latency per channel and flags SLO breaches
+pub struct DeliveryTracker {
+    slo: DeliverySloConfig,
+    state: Mutex<TrackerState>,
+    events: broadcast::Sender<DeliverySloEvent>,
+    health: Option<Arc<HealthChecker>>,
+    health_registered: AtomicBool,
+}
+
+impl DeliveryTracker {
+    /// Create a tracker for the given objective
+    pub fn new(slo: DeliverySloConfig) -> Self {
+        let (events, _) = broadcast::channel(64);
+        Self {
+            slo,
+            state: Mutex::new(TrackerState::default()),
+            events,
+            health: None,
+            health_registered: AtomicBool::new(false),
+        }
+    }
+
+    /// Report SLO state as the `delivery` health component
+    pub fn with_health_checker(mut self, health: Arc<HealthChecker>) -> Self {
+        info!("Attaching health checker to delivery tracker");
+        self.health = Some(health);
+        self
+    }
+
+    /// The objective being tracked
+    pub fn slo(&self) -> &DeliverySloConfig {
+        &self.slo
+    }
+
+    /// Subscribe to SLO state changes
+    pub fn subscribe(&self) -> broadcast::Receiver<DeliverySloEvent> {
+        self.events.subscribe()
+    }
+
+    /// Expect receipts for a sent message
+    ///
+    /// `recipients` should be only the members the message reached; nobody
+    /// else is expected to answer.
+    pub fn record_sent(
+        &self,
+        channel_id: &ChannelId,
+        message_ref: MessageRef,
+        sent_at: HybridTimestamp,
+        recipients: impl IntoIterator<Item = UserId>,
+    ) {
+        let awaiting: HashSet<UserId> = recipients.into_iter().collect();
+        if awaiting.is_empty() {
+            return;
+        }
+        let pending = PendingDelivery { channel_id: channel_id.clone(), sent_at, awaiting };
+        self.state.lock().unwrap().pending.insert(message_ref, pending);
+    }
+
+    /// Record a recipient's delivery receipt
+    ///
+    /// `delivered_at` is the recipient's HLC stamp and `received_at_ms` the
+    /// local wall time the receipt arrived. Returns the latency, or `None`
+    /// if no receipt was expected from this recipient.
+    pub fn record_receipt(
+        &self,
+        message_ref: &MessageRef,
+        recipient: &UserId,
+        delivered_at: HybridTimestamp,
+        received_at_ms: u64,
+    ) -> Option<u64> {
+        let mut state = self.state.lock().unwrap();
+        let pending = state.pending.get_mut(message_ref)?;
+        if !pending.awaiting.remove(recipient) {
+            return None;
+        }
+
+        // A recipient clock running ahead can't stretch the latency past
+        // the round trip we measured ourselves
+        let sent_ms = pending.sent_at.physical_ms;
+        let round_trip = received_at_ms.saturating_sub(sent_ms);
+        let latency = delivered_at.physical_ms.saturating_sub(sent_ms).min(round_trip);
+
+        let channel_id = pending.channel_id.clone();
+        if pending.awaiting.is_empty() {
+            state.pending.remove(message_ref);
+        }
+        let window = state.channels.entry(channel_id.clone()).or_default();
+        window.samples.push_back((received_at_ms, latency));
+
+        histogram!("mvp.delivery.latency_ms", "channel" => channel_id.0).record(latency as f64);
+        Some(latency)
+    }
+
+    /// Latency summary for a channel
+    pub fn stats(&self, channel_id: &ChannelId) -> Option<DeliveryStats> {
+        let state = self.state.lock().unwrap();
+        let window = state.channels.get(channel_id)?;
+        Some(window.stats(channel_id, self.slo.target_latency.as_millis() as u64))
+    }
+
+    /// Summary of every tracked channel
+    pub fn report(&self) -> DeliveryReport {
+        let target_ms = self.slo.target_latency.as_millis() as u64;
+        let state = self.state.lock().unwrap();
+        let mut channels: Vec<DeliveryStats> = state
+            .channels
+            .iter()
+            .map(|(channel_id, window)| window.stats(channel_id, target_ms))
+            .collect();
+        channels.sort_by(|a, b| a.channel_id.0.cmp(&b.channel_id.0));
+
+        DeliveryReport {
+            generated_at_ms: Timestamp::now().as_millis(),
+            target_latency_ms: target_ms,
+            objective: self.slo.objective,
+            channels,
+        }
+    }
+
+    /// Expire old samples and receipts, then update each channel's state
+    ///
+    /// Returns the state changes, which are also broadcast to subscribers.
+    pub async fn evaluate(&self, now_ms: u64) -> Vec<DeliverySloEvent> {
+        let target_ms = self.slo.target_latency.as_millis() as u64;
+        let sustain_ms = self.slo.sustain.as_millis() as u64;
+        let window_ms = self.slo.window.as_millis() as u64;
+        let timeout_ms = self.slo.receipt_timeout.as_millis() as u64;
+
+        let mut events = Vec::new();
+        let mut degraded = Vec::new();
+        {
+            let mut guard = self.state.lock().unwrap();
+            let state = &mut *guard;
+
+            // Receipts that never came are excluded, not counted as slow:
+            // the recipient may have gone offline or not support receipts
+            let mut expired = Vec::new();
+            state.pending.retain(|_, pending| {
+                let live = pending.sent_at.physical_ms + timeout_ms > now_ms;
+                if !live {
+                    expired.push((pending.channel_id.clone(), pending.awaiting.len() as u64));
+                }
+                live
+            });
+            for (channel_id, missing) in expired {
+                state.channels.entry(channel_id).or_default().unacknowledged += missing;
+            }
+
+            for (channel_id, window) in state.channels.iter_mut() {
+                while window.samples.front().is_some_and(|&(at, _)| at + window_ms <= now_ms) {
+                    window.samples.pop_front();
+                }
+
+                let stats = window.stats(channel_id, target_ms);
+                let channel = channel_id.0.clone();
+                gauge!("mvp.delivery.p50_ms", "channel" => channel.clone())
+                    .set(stats.p50_ms as f64);
+                gauge!("mvp.delivery.p95_ms", "channel" => channel.clone())
+                    .set(stats.p95_ms as f64);
+                gauge!("mvp.delivery.p99_ms", "channel" => channel.clone())
+                    .set(stats.p99_ms as f64);
+                gauge!("mvp.delivery.compliance", "channel" => channel).set(stats.compliance);
+
+                if let Some(event) = self.step(channel_id, window, &stats, now_ms, sustain_ms) {
+                    events.push(event);
+                }
+                if window.state == SloState::Degraded {
+                    degraded.push(channel_id.0.clone());
+                }
+            }
+        }
+
+        for event in &events {
+            match event {
+                DeliverySloEvent::Degraded { .. } => warn!("{}", event),
+                DeliverySloEvent::Recovered { .. } => info!("{}", event),
+            }
+            let _ = self.events.send(event.clone());
+        }
+        self.update_health(degraded).await;
+
+        events
+    }
+
+    /// Advance one channel's state machine
+    ///
+    /// The state only changes once compliance has stayed past the relevant
+    /// threshold for the whole sustain period; the gap between the
+    /// objective and the recovery objective keeps it from flapping.
+    fn step(
+        &self,
+        channel_id: &ChannelId,
+        window: &mut ChannelWindow,
+        stats: &DeliveryStats,
+        now_ms: u64,
+        sustain_ms: u64,
+    ) -> Option<DeliverySloEvent> {
+        if stats.samples < self.slo.min_samples {
+            window.crossed_at = None;
+            return None;
+        }
+
+        let crossed = match window.state {
+            SloState::Healthy => stats.compliance < self.slo.objective,
+            SloState::Degraded => stats.compliance >= self.slo.recovery_objective,
+        };
+        if !crossed {
+            window.crossed_at = None;
+            return None;
+        }
+        let since = *window.crossed_at.get_or_insert(now_ms);
+        if now_ms - since < sustain_ms {
+            return None;
+        }
+
+        window.crossed_at = None;
+        let (channel_id, compliance, p95_ms) = (channel_id.clone(), stats.compliance, stats.p95_ms);
+        Some(match window.state {
+            SloState::Healthy => {
+                window.state = SloState::Degraded;
+                DeliverySloEvent::Degraded { channel_id, compliance, p95_ms }
+            }
+            SloState::Degraded => {
+                window.state = SloState::Healthy;
+                DeliverySloEvent::Recovered { channel_id, compliance, p95_ms }
+            }
+        })
+    }
+
+    async fn update_health(&self, degraded: Vec<String>) {
+        let Some(health) = &self.health else {
+            return;
+        };
+        if !self.health_registered.swap(true, Ordering::SeqCst) {
+            health.register_component(HEALTH_COMPONENT).await;
+        }
+
+        if degraded.is_empty() {
+            health.update_component(HEALTH_COMPONENT, HealthStatus::Healthy, None).await;
+        } else {
+            let message = format!(
+                "Delivery SLO breached in {} channel(s): {}",
+                degraded.len(),
+                degraded.join(", ")
+            );
+            health
+                .update_component(HEALTH_COMPONENT, HealthStatus::Degraded, Some(message))
+                .await;
+        }
+    }
+
+    /// Evaluate periodically, saving a report after each pass if a path is given
+    pub fn spawn_evaluator(
+        self: Arc<Self>,
+        interval: Duration,
+        report_path: Option<PathBuf>,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                self.evaluate(Timestamp::now().as_millis()).await;
+                if let Some(path) = &report_path {
+                    if let Err(e) = self.report().save(path) {
+                        warn!("Failed to save delivery report to {:?}: {}", path, e);
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: u64 = 1000;
+
+    fn channel() -> ChannelId {
+        ChannelId("ops".to_string())
+    }
+
+    fn user(name: &str) -> UserId {
+        UserId(format!("{}@spacepanda.local", name))
+    }
+
+    fn at(physical_ms: u64) -> HybridTimestamp {
+        HybridTimestamp { physical_ms, logical: 0 }
+    }
+
+    /// Record one delivery to bob with the given latency, sent at `sent_ms`
+    fn deliver(tracker: &DeliveryTracker, n: u64, sent_ms: u64, latency_ms: u64) {
+        let message = message_ref(&n.to_be_bytes());
+        tracker.record_sent(&channel(), message, at(sent_ms), [user("bob")]);
+        let done = sent_ms + latency_ms;
+        assert_eq!(
+            tracker.record_receipt(&message, &user("bob"), at(done), done),
+            Some(latency_ms)
+        );
+    }
+
+    fn slo() -> DeliverySloConfig {
+        DeliverySloConfig::default()
+            .with_target(Duration::from_secs(5), 0.9)
+            .with_recovery_objective(0.95)
+            .with_sustain(Duration::from_secs(60))
+            .with_min_samples(10)
+    }
+
+    #[test]
+    fn test_hybrid_clock_orders_past_remote_and_skew() {
+        let clock = HybridClock::new();
+        let a = clock.now_at(1_000);
+        let b = clock.now_at(1_000);
+        assert!(b > a);
+
+        // Wall time going backwards doesn't move the clock back
+        assert!(clock.now_at(500) > b);
+
+        // A remote clock ahead of ours pulls us forward
+        let remote = HybridTimestamp { physical_ms: 9_000, logical: 4 };
+        let observed = clock.observe_at(remote, 1_100);
+        assert_eq!(observed, HybridTimestamp { physical_ms: 9_000, logical: 5 });
+        assert!(clock.now_at(1_200) > observed);
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let tracker = DeliveryTracker::new(slo());
+        for latency in 1..=100 {
+            deliver(&tracker, latency, 0, latency * 100);
+        }
+
+        let stats = tracker.stats(&channel()).unwrap();
+        assert_eq!(stats.samples, 100);
+        assert_eq!((stats.p50_ms, stats.p95_ms, stats.p99_ms), (5_000, 9_500, 9_900));
+        assert_eq!(stats.compliance, 0.5);
+
+        assert_eq!(percentile(&[], 0.95), 0);
+        assert_eq!(percentile(&[7], 0.01), 7);
+        assert_eq!(percentile(&[1, 2, 3], 1.0), 3);
+    }
+
+    #[test]
+    fn test_latency_is_skew_adjusted() {
+        let tracker = DeliveryTracker::new(slo());
+        let message = message_ref(b"ciphertext");
+        tracker.record_sent(&channel(), message, at(10 * SECOND), [user("bob"), user("carol")]);
+
+        // Bob's clock runs a minute fast; the round trip caps it
+        let latency = tracker.record_receipt(&message, &user("bob"), at(70 * SECOND), 11 * SECOND);
+        assert_eq!(latency, Some(SECOND));
+
+        // Carol's HLC stamp can't be before the send
+        let latency =
+            tracker.record_receipt(&message, &user("carol"), at(10 * SECOND), 12 * SECOND);
+        assert_eq!(latency, Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_offline_and_silent_recipients_are_excluded() {
+        let tracker = DeliveryTracker::new(slo().with_receipt_timeout(Duration::from_secs(30)));
+        let message = message_ref(b"hello");
+
+        // Dave was offline, so the message only reached Bob and Carol
+        tracker.record_sent(&channel(), message, at(0), [user("bob"), user("carol")]);
+        assert_eq!(tracker.record_receipt(&message, &user("dave"), at(SECOND), SECOND), None);
+        assert_eq!(
+            tracker.record_receipt(&message, &user("bob"), at(SECOND), SECOND),
+            Some(SECOND)
+        );
+        assert_eq!(tracker.record_receipt(&message, &user("bob"), at(SECOND), SECOND), None);
+
+        // Carol never answers; that is tallied but not counted as slow
+        tracker.evaluate(30 * SECOND).await;
+        let stats = tracker.stats(&channel()).unwrap();
+        assert_eq!((stats.samples, stats.unacknowledged, stats.compliance), (1, 1, 1.0));
+        assert_eq!(
+            tracker.record_receipt(&message, &user("carol"), at(31 * SECOND), 31 * SECOND),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_alert_needs_sustained_breach_and_recovers_with_hysteresis() {
+        let health = Arc::new(HealthChecker::new("test"));
+        let tracker = DeliveryTracker::new(slo()).with_health_checker(health.clone());
+        let mut events = tracker.subscribe();
+        let mut n = 0;
+        let mut feed = |sent_ms: u64, fast: u64, slow: u64| {
+            for latency in std::iter::repeat(SECOND)
+                .take(fast as usize)
+                .chain(std::iter::repeat(8 * SECOND).take(slow as usize))
+            {
+                n += 1;
+                deliver(&tracker, n, sent_ms, latency);
+            }
+        };
+
+        // Too few samples to judge
+        feed(0, 0, 5);
+        assert!(tracker.evaluate(10 * SECOND).await.is_empty());
+
+        // 50% on target: a breach, but not yet sustained
+        feed(10 * SECOND, 10, 0);
+        assert!(tracker.evaluate(20 * SECOND).await.is_empty());
+        assert!(tracker.evaluate(79 * SECOND).await.is_empty());
+
+        let fired = tracker.evaluate(80 * SECOND).await;
+        assert!(matches!(fired.as_slice(), [DeliverySloEvent::Degraded { .. }]));
+        assert!(matches!(events.try_recv(), Ok(DeliverySloEvent::Degraded { .. })));
+        let component = health.check_health().await.components[0].clone();
+        assert_eq!(component.status, HealthStatus::Degraded);
+
+        // Above the objective but below the recovery objective: stays degraded
+        feed(80 * SECOND, 60, 0);
+        let stats = tracker.stats(&channel()).unwrap();
+        assert!(stats.compliance >= 0.9 && stats.compliance < 0.95, "{}", stats.compliance);
+        assert!(tracker.evaluate(200 * SECOND).await.is_empty());
+        assert!(tracker.evaluate(300 * SECOND).await.is_empty());
+
+        // The slow samples (recorded at 8s) age out of the 15 minute window;
+        // compliance must then hold for the sustain period
+        let window_end = (15 * 60 + 8) * SECOND;
+        assert!(tracker.evaluate(window_end).await.is_empty());
+        assert_eq!(tracker.stats(&channel()).unwrap().state, SloState::Degraded);
+        let fired = tracker.evaluate(window_end + 60 * SECOND).await;
+        assert!(matches!(fired.as_slice(), [DeliverySloEvent::Recovered { .. }]));
+
+        let component = health.check_health().await.components[0].clone();
+        assert_eq!(component.status, HealthStatus::Healthy);
+    }
+
+    #[test]
+    fn test_report_round_trip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let tracker = DeliveryTracker::new(slo());
+        deliver(&tracker, 1, 0, 250);
+
+        let path = dir.path().join(DELIVERY_REPORT_FILE);
+        tracker.report().save(&path).unwrap();
+        let report = DeliveryReport::load(&path).unwrap();
+        assert_eq!(report.channel(&channel()).unwrap().p95_ms, 250);
+        assert!(report.channel(&ChannelId("other".to_string())).is_none());
+    }
+}
@@ -0,0 +1,129 @@
+//! Linking another device to an identity
+//!
+//! A new device asks to be linked with a [`DeviceLinkRequest`]: a device
+//! bundle signed with its own key, plus a batch of MLS key packages for the
+//! user's ID, signed by the same key. A device holding the identity master
+//! key authorizes the bundle with
+//! [`IdentityBundle::authorize_device`](crate::core_identity::IdentityBundle::authorize_device)
+//! and hands it to
+//! [`ChannelManager::link_device`](crate::core_mvp::ChannelManager::link_device),
+//! which adds the new device to each of the user's channels. The
+//! [`DeviceLinkApproval`] sent back carries the binding and one invite per
+//! channel. Both are exchanged out of band.
+//!
+//! Each leaf added for the device is recorded in its channel, so a lost or
+//! stolen device can later be cut off with
+//! [`ChannelManager::revoke_device`](crate::core_mvp::ChannelManager::revoke_device).
+
+use crate::core_identity::{DeviceBundle, DeviceId, DeviceKeyBinding, Keypair};
+use crate::core_mvp::types::InviteToken;
+use crate::core_store::model::types::{ChannelId, UserId};
+use serde::{Deserialize, Serialize};
+
+/// Key packages a link request carries by default
+///
+/// Joining from a Welcome uses up its key package, so the new device can
+/// join at most this many channels from one request.
+pub const DEFAULT_LINK_KEY_PACKAGES: usize = 32;
+
+/// A new device's request to be linked to an identity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceLinkRequest {
+    /// Device bundle, signed with the device key
+    pub device_bundle: DeviceBundle,
+
+    /// MLS key packages for the user's ID, one per channel to join
+    pub key_packages: Vec<Vec<u8>>,
+
+    /// Device key's signature over the key packages
+    pub signature: Vec<u8>,
+}
+
+impl DeviceLinkRequest {
+    /// Create a request, signing the key packages with the device key
+    pub fn new(
+        device_kp: &Keypair,
+        device_bundle: DeviceBundle,
+        key_packages: Vec<Vec<u8>>,
+    ) -> Self {
+        let signature = device_kp.sign(&Self::body(&key_packages));
+        Self { device_bundle, key_packages, signature }
+    }
+
+    fn body(key_packages: &[Vec<u8>]) -> Vec<u8> {
+        let mut body = b"DEVICE_LINK_REQUEST_V1:".to_vec();
+        for key_package in key_packages {
+            body.extend_from_slice(&(key_package.len() as u64).to_le_bytes());
+            body.extend_from_slice(key_package);
+        }
+        body
+    }
+
+    /// Whether the key packages were signed by the requesting device
+    pub fn verify(&self) -> bool {
+        Keypair::verify(
+            self.device_bundle.device_public_key(),
+            &Self::body(&self.key_packages),
+            &self.signature,
+        )
+    }
+}
+
+/// Approval sent back to a new device once it is linked
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceLinkApproval {
+    /// User the device now belongs to
+    pub user_id: UserId,
+
+    /// User's display name
+    pub display_name: String,
+
+    /// Identity master public key that signed the binding
+    pub identity_public_key: Vec<u8>,
+
+    /// The identity key's authorization of the device
+    pub binding: DeviceKeyBinding,
+
+    /// Invites to the user's channels, one per channel
+    pub invites: Vec<InviteToken>,
+}
+
+impl DeviceLinkApproval {
+    /// Whether this approval authorizes the device with the given key
+    pub fn authorizes(&self, device_public_key: &[u8]) -> bool {
+        self.binding.device_public_key == device_public_key
+            && self.binding.verify(&self.identity_public_key)
+    }
+}
+
+/// Result of linking a device on the approving side
+#[derive(Debug, Clone)]
+pub struct LinkedDevice {
+    /// The linked device
+    pub device_id: DeviceId,
+
+    /// Invites for the device, one per channel it was added to
+    pub invites: Vec<InviteToken>,
+
+    /// Add commits for the other members, by channel
+    pub commits: Vec<(ChannelId, Vec<u8>)>,
+
+    /// Channels the device could not be added to
+    pub skipped: Vec<ChannelId>,
+}
+
+/// Result of revoking a device
+#[derive(Debug, Clone)]
+pub struct RevokedDevice {
+    /// The revoked device
+    pub device_id: DeviceId,
+
+    /// Channels the device is now marked revoked in
+    pub channels: Vec<ChannelId>,
+
+    /// Removal commits for the other members, by channel
+    pub commits: Vec<(ChannelId, Vec<u8>)>,
+
+    /// Channels the device's leaves could not be removed from
+    pub skipped: Vec<ChannelId>,
+}
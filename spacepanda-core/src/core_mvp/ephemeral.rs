@@ -0,0 +1,121 @@
+//! Ephemeral messages: typing indicators and presence
+//!
+//! An [`EphemeralMessage`] is encrypted and delivered like any application
+//! message, but members only turn it into a [`ChannelEvent`]: it is never
+//! written to the store, so it stays out of history, snapshots and the
+//! commit log. Channels with a feed keep it there for
+//! [`EPHEMERAL_FEED_TTL`] only, and one older than [`EPHEMERAL_MAX_AGE`]
+//! by the time it arrives is dropped.
+//!
+//! [`ChannelEvent`]: crate::core_mvp::events::ChannelEvent
+
+use crate::core_mvp::errors::{MvpError, MvpResult};
+use crate::core_store::model::types::{ChannelId, Timestamp, UserId};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Marker at the start of an ephemeral message's plaintext
+const EPHEMERAL_MAGIC: &[u8; 4] = b"SPEP";
+
+/// How long an ephemeral message stays in a channel's feed
+pub const EPHEMERAL_FEED_TTL: Duration = Duration::from_secs(30);
+
+/// Ephemeral messages older than this on arrival are dropped
+pub const EPHEMERAL_MAX_AGE: Duration = Duration::from_secs(30);
+
+/// Least time between two typing indicators sent to a channel
+pub const TYPING_INTERVAL: Duration = Duration::from_secs(3);
+
+/// How often to send a presence heartbeat while online
+pub const PRESENCE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A member counts as present this long after their last heartbeat
+pub const PRESENCE_TIMEOUT: Duration = Duration::from_secs(150);
+
+/// What an ephemeral message signals
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EphemeralKind {
+    /// The sender is typing
+    Typing,
+    /// The sender is online
+    Presence,
+}
+
+/// A signal to the other members that isn't kept
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EphemeralMessage {
+    /// Channel the signal is for
+    pub channel_id: ChannelId,
+
+    /// Member sending it
+    pub user: UserId,
+
+    /// Typing or presence
+    pub kind: EphemeralKind,
+
+    /// When it was sent
+    pub sent_at: Timestamp,
+}
+
+impl EphemeralMessage {
+    /// A signal sent now
+    pub fn new(channel_id: ChannelId, user: UserId, kind: EphemeralKind) -> Self {
+        Self { channel_id, user, kind, sent_at: Timestamp::now() }
+    }
+
+    /// Whether it is too old to be worth showing
+    pub fn is_stale(&self) -> bool {
+        let age = Timestamp::now().as_millis().saturating_sub(self.sent_at.as_millis());
+        age > EPHEMERAL_MAX_AGE.as_millis() as u64
+    }
+
+    /// Plaintext to send to the channel
+    pub fn encode(&self) -> MvpResult<Vec<u8>> {
+        let encoded = bincode::serialize(self).map_err(|e| {
+            MvpError::Serialization(format!("Failed to serialize ephemeral message: {}", e))
+        })?;
+        let mut plaintext = EPHEMERAL_MAGIC.to_vec();
+        plaintext.extend_from_slice(&encoded);
+        Ok(plaintext)
+    }
+
+    /// The ephemeral message in a decrypted plaintext, if it is one
+    pub fn decode(plaintext: &[u8]) -> Option<Self> {
+        bincode::deserialize(plaintext.strip_prefix(EPHEMERAL_MAGIC.as_slice())?).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typing() -> EphemeralMessage {
+        EphemeralMessage::new(
+            ChannelId("general".to_string()),
+            UserId("alice".to_string()),
+            EphemeralKind::Typing,
+        )
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let message = typing();
+
+        let plaintext = message.encode().unwrap();
+        assert_eq!(EphemeralMessage::decode(&plaintext), Some(message));
+
+        // Ordinary messages aren't ephemeral
+        assert_eq!(EphemeralMessage::decode(b"hello"), None);
+        assert_eq!(EphemeralMessage::decode(b"SPEP not really"), None);
+    }
+
+    #[test]
+    fn test_old_messages_are_stale() {
+        let mut message = typing();
+        assert!(!message.is_stale());
+
+        let sent_at = message.sent_at.as_millis() - EPHEMERAL_MAX_AGE.as_millis() as u64 - 1;
+        message.sent_at = Timestamp::from_millis(sent_at);
+        assert!(message.is_stale());
+    }
+}
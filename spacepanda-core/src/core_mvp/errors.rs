@@ -40,6 +40,50 @@ pub enum MvpError {
     #[error("Invalid invite token: {0}")]
     InvalidInvite(String),
 
+    /// Invite token is past its expiry time
+    #[error("Invite expired: {0}")]
+    InviteExpired(String),
+
+    /// No active webhook with this ID
+    #[error("Webhook not found: {0}")]
+    WebhookNotFound(String),
+
+    /// No linked device with this ID
+    #[error("Device not found: {0}")]
+    DeviceNotFound(String),
+
+    /// Missing or wrong credentials
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    /// Too many requests in the current window
+    #[error("Rate limited: {0}")]
+    RateLimited(String),
+
+    /// Channel is read-only after repeated worker panics
+    #[error("Channel quarantined: {0}")]
+    ChannelQuarantined(String),
+
+    /// Request body over the allowed size
+    #[error("Payload too large: {size} bytes (limit {limit})")]
+    PayloadTooLarge { size: usize, limit: usize },
+
+    /// An attachment chunk couldn't be fetched from any replica
+    #[error("Attachment chunk {index} unavailable after {attempts} attempts: {reason}")]
+    ChunkUnavailable { index: usize, attempts: u32, reason: String },
+
+    /// Not enough free disk space to start an operation
+    #[error("Insufficient storage: {required} bytes required, {available} available")]
+    InsufficientStorage { required: u64, available: u64 },
+
+    /// The key package directory has no key packages left for a user
+    #[error("No key packages published for {0}")]
+    NoKeyPackages(String),
+
+    /// Key package is malformed, expired or otherwise unusable
+    #[error("Invalid key package: {0}")]
+    InvalidKeyPackage(String),
+
     /// Invalid message format
     #[error("Invalid message: {0}")]
     InvalidMessage(String),
@@ -79,6 +123,12 @@ impl From<serde_json::Error> for MvpError {
     }
 }
 
+impl From<crate::core_store::store::preflight::InsufficientStorage> for MvpError {
+    fn from(e: crate::core_store::store::preflight::InsufficientStorage) -> Self {
+        MvpError::InsufficientStorage { required: e.required, available: e.available }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,244 @@
+//! Ordered per-channel event stream
+//!
+//! Membership changes and decrypted messages reach a channel through
+//! different code paths (commits over the network or from a mailbox, local
+//! invites and removals, the inbox, feeds), so emitting them where they are
+//! processed lets "Bob: hello" overtake "Bob joined". Instead every event for
+//! a channel goes through the channel's [`EventQueue`], which compares the
+//! MLS roster it last reported with the current one before emitting anything:
+//! a message decrypted in a new epoch is always preceded by the membership
+//! changes of the commit that started that epoch.
+//!
+//! Events carry the epoch the channel had reached when they were emitted and
+//! their position within that epoch, so `(epoch, seq)` strictly increases
+//! along a channel's stream. Each epoch's events start with
+//! [`ChannelEventKind::EpochAdvanced`].
+//!
+//! Subscribers read from a bounded broadcast channel: one that falls behind
+//! gets `RecvError::Lagged` and misses events rather than holding up
+//! message processing.
+
+use crate::core_mls::channel_metadata::ChannelMetadata;
+use crate::core_mvp::types::{ChatMessage, DeliverySummary};
+use crate::core_store::model::types::{ChannelId, MessageId, Timestamp, UserId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// What happened in a channel
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ChannelEventKind {
+    /// A user became a member
+    MemberJoined(UserId),
+
+    /// A user stopped being a member
+    MemberLeft(UserId),
+
+    /// A message from another member was decrypted
+    MessageReceived(ChatMessage),
+
+    /// A member is typing
+    Typing(UserId),
+
+    /// A member sent a presence heartbeat
+    Presence(UserId),
+
+    /// A commit moved the channel to this epoch
+    EpochAdvanced(u64),
+
+    /// The channel's name, topic, avatar or retention policy changed
+    ChannelMetadataChanged(ChannelMetadata),
+
+    /// The invitee joined with one of our invites
+    InviteAccepted { invite_id: String },
+
+    /// A message could not be decrypted and was dropped
+    DecryptionFailed { error: String },
+
+    /// One of our messages waiting in the outbox reached the network
+    MessageDelivered { message_id: MessageId },
+
+    /// The outbox was full and dropped one of our undelivered messages
+    MessageEvicted { message_id: MessageId },
+
+    /// A member acknowledged or read one of our messages; the actor is the
+    /// member and `summary` covers every recipient
+    DeliveryUpdated { message_id: MessageId, summary: DeliverySummary },
+
+    /// Messages past the channel's retention policy were deleted locally
+    MessagesPruned { message_ids: Vec<MessageId> },
+}
+
+/// One event in a channel's ordered stream
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChannelEvent {
+    pub channel_id: ChannelId,
+
+    /// Epoch the channel had reached when the event was emitted
+    pub epoch: u64,
+
+    /// Position within the epoch, starting at 0
+    pub seq: u64,
+
+    /// Who caused it, when known: the sender of a message, the member
+    /// typing, the invitee accepting. Commit-derived events have none.
+    pub actor: Option<UserId>,
+
+    /// When the event was emitted
+    pub timestamp: Timestamp,
+
+    pub kind: ChannelEventKind,
+}
+
+impl ChannelEvent {
+    /// Whether this is a membership change
+    pub fn is_membership(&self) -> bool {
+        matches!(self.kind, ChannelEventKind::MemberJoined(_) | ChannelEventKind::MemberLeft(_))
+    }
+
+    /// Whether this reports a commit: the epoch advancing or a membership change
+    pub fn is_commit(&self) -> bool {
+        self.is_membership() || matches!(self.kind, ChannelEventKind::EpochAdvanced(_))
+    }
+}
+
+/// Emission state of one channel's stream
+///
+/// The first roster seen is taken as the starting point and reported as
+/// nothing; callers observe it when the channel is created or joined.
+#[derive(Debug, Default)]
+pub(crate) struct EventQueue {
+    epoch: u64,
+    next_seq: u64,
+    members: Option<HashSet<UserId>>,
+}
+
+impl EventQueue {
+    /// Events for the roster of the channel at `epoch`
+    ///
+    /// Returns [`EpochAdvanced`](ChannelEventKind::EpochAdvanced) followed
+    /// by the leaves and joins since the last roster observed, stamped with
+    /// `epoch`; nothing if the epoch hasn't advanced.
+    pub fn observe_roster(
+        &mut self,
+        channel_id: &ChannelId,
+        epoch: u64,
+        roster: HashSet<UserId>,
+    ) -> Vec<ChannelEvent> {
+        let Some(ref members) = self.members else {
+            self.epoch = epoch;
+            self.members = Some(roster);
+            return Vec::new();
+        };
+        if epoch <= self.epoch {
+            return Vec::new();
+        }
+
+        let mut joined: Vec<_> = roster.difference(members).cloned().collect();
+        let mut left: Vec<_> = members.difference(&roster).cloned().collect();
+        joined.sort_by(|a, b| a.0.cmp(&b.0));
+        left.sort_by(|a, b| a.0.cmp(&b.0));
+
+        self.epoch = epoch;
+        self.next_seq = 0;
+        self.members = Some(roster);
+
+        let kinds = std::iter::once(ChannelEventKind::EpochAdvanced(epoch))
+            .chain(left.into_iter().map(ChannelEventKind::MemberLeft))
+            .chain(joined.into_iter().map(ChannelEventKind::MemberJoined));
+        kinds.map(|kind| self.stamp(channel_id, None, kind)).collect()
+    }
+
+    /// Event for a decrypted message
+    ///
+    /// Call [`observe_roster`](Self::observe_roster) with the current
+    /// roster first, so membership changes of its epoch come before it.
+    pub fn message(&mut self, message: ChatMessage) -> ChannelEvent {
+        let channel_id = message.channel_id.clone();
+        let actor = Some(message.sender.clone());
+        self.stamp(&channel_id, actor, ChannelEventKind::MessageReceived(message))
+    }
+
+    /// Event for anything else: typing, presence, metadata, invites, failures
+    ///
+    /// Like messages, call [`observe_roster`](Self::observe_roster) first.
+    pub fn event(
+        &mut self,
+        channel_id: &ChannelId,
+        actor: Option<UserId>,
+        kind: ChannelEventKind,
+    ) -> ChannelEvent {
+        self.stamp(channel_id, actor, kind)
+    }
+
+    fn stamp(
+        &mut self,
+        channel_id: &ChannelId,
+        actor: Option<UserId>,
+        kind: ChannelEventKind,
+    ) -> ChannelEvent {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        ChannelEvent {
+            channel_id: channel_id.clone(),
+            epoch: self.epoch,
+            seq,
+            actor,
+            timestamp: Timestamp::now(),
+            kind,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roster(names: &[&str]) -> HashSet<UserId> {
+        names.iter().map(|name| UserId(name.to_string())).collect()
+    }
+
+    #[test]
+    fn test_first_roster_is_the_baseline() {
+        let channel_id = ChannelId("general".to_string());
+        let mut queue = EventQueue::default();
+        assert!(queue.observe_roster(&channel_id, 1, roster(&["alice", "bob"])).is_empty());
+
+        let event = queue.message(ChatMessage::new(
+            channel_id.clone(),
+            UserId("bob".to_string()),
+            b"hi".to_vec(),
+        ));
+        assert_eq!((event.epoch, event.seq), (1, 0));
+        assert_eq!(event.actor, Some(UserId("bob".to_string())));
+    }
+
+    #[test]
+    fn test_membership_changes_are_stamped_with_the_new_epoch() {
+        let channel_id = ChannelId("general".to_string());
+        let mut queue = EventQueue::default();
+        queue.observe_roster(&channel_id, 1, roster(&["alice", "bob"]));
+        queue.message(ChatMessage::new(channel_id.clone(), UserId("bob".into()), b"a".to_vec()));
+
+        let events = queue.observe_roster(&channel_id, 3, roster(&["alice", "carol", "dave"]));
+        let kinds: Vec<_> = events.iter().map(|e| (e.epoch, e.seq, e.kind.clone())).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (3, 0, ChannelEventKind::EpochAdvanced(3)),
+                (3, 1, ChannelEventKind::MemberLeft(UserId("bob".into()))),
+                (3, 2, ChannelEventKind::MemberJoined(UserId("carol".into()))),
+                (3, 3, ChannelEventKind::MemberJoined(UserId("dave".into()))),
+            ]
+        );
+        assert!(events.iter().all(|event| event.is_commit() && event.actor.is_none()));
+
+        // Seeing the same epoch again reports nothing
+        assert!(queue.observe_roster(&channel_id, 3, roster(&["alice"])).is_empty());
+        let event = queue.message(ChatMessage::new(
+            channel_id.clone(),
+            UserId("carol".into()),
+            b"b".to_vec(),
+        ));
+        assert_eq!((event.epoch, event.seq), (3, 4));
+    }
+}
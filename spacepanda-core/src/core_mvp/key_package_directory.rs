@@ -0,0 +1,204 @@
+//! MLS key packages in the DHT
+//!
+//! Each user publishes a set of one-time key packages under a key derived
+//! from their user ID, each wrapped in a [`KeyPackage`] signed by their
+//! identity key. An inviter takes one out of the set, so no key package is
+//! used for two invites, and the owner tops the set up again when it runs
+//! low (see
+//! [`ChannelManager::replenish_key_packages`](crate::core_mvp::ChannelManager::replenish_key_packages)).
+
+use crate::core_dht::private_lookup::KEY_PACKAGE_NAMESPACE;
+use crate::core_dht::{DhtKey, DhtStorage, DhtValue};
+use crate::core_identity::{validate_keypackage, KeyPackage, Keypair};
+use crate::core_mvp::errors::{MvpError, MvpResult};
+use crate::core_store::model::types::{Timestamp, UserId};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::warn;
+
+/// How long a published set stays in the DHT
+pub const KEY_PACKAGE_TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Replenish once fewer key packages than this are left
+pub const DEFAULT_REPLENISH_THRESHOLD: usize = 5;
+
+/// Number of key packages replenishment tops the set up to
+pub const DEFAULT_REPLENISH_TARGET: usize = 20;
+
+/// A user's published key packages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KeyPackageSet {
+    user_id: UserId,
+
+    /// Identity key every package in the set is signed with
+    identity_public_key: Vec<u8>,
+
+    /// Serialized identity-signed [`KeyPackage`]s, oldest first
+    packages: Vec<Vec<u8>>,
+}
+
+/// A key package taken from the directory
+#[derive(Debug, Clone)]
+pub struct FetchedKeyPackage {
+    /// Serialized MLS key package
+    pub key_package: Vec<u8>,
+
+    /// Identity key that signed it
+    pub identity_public_key: Vec<u8>,
+}
+
+/// Directory of one-time key packages stored in the DHT
+pub struct KeyPackageDirectory {
+    storage: DhtStorage,
+    signer: Option<Keypair>,
+    threshold: usize,
+    target: usize,
+}
+
+impl KeyPackageDirectory {
+    /// Create a directory backed by the given DHT storage
+    ///
+    /// Without a signer the directory can only be fetched from.
+    pub fn new(storage: DhtStorage) -> Self {
+        Self {
+            storage,
+            signer: None,
+            threshold: DEFAULT_REPLENISH_THRESHOLD,
+            target: DEFAULT_REPLENISH_TARGET,
+        }
+    }
+
+    /// Set the identity key our own key packages are signed with
+    pub fn with_signer(mut self, identity_kp: Keypair) -> Self {
+        self.signer = Some(identity_kp);
+        self
+    }
+
+    /// Set when to replenish our key packages and how many to keep
+    pub fn with_replenishment(mut self, threshold: usize, target: usize) -> Self {
+        self.threshold = threshold;
+        self.target = target.max(threshold);
+        self
+    }
+
+    fn key(user_id: &UserId) -> DhtKey {
+        DhtKey::hash(format!("{}:{}", KEY_PACKAGE_NAMESPACE, user_id.0).as_bytes())
+    }
+
+    /// Number of key packages `user_id` has left
+    pub fn available(&self, user_id: &UserId) -> MvpResult<usize> {
+        Ok(self.load(user_id)?.map_or(0, |set| set.packages.len()))
+    }
+
+    /// How many key packages to publish to top `user_id`'s set up
+    ///
+    /// Zero while the set is at or above the replenish threshold.
+    pub fn shortfall(&self, user_id: &UserId) -> MvpResult<usize> {
+        let available = self.available(user_id)?;
+        if available >= self.threshold {
+            return Ok(0);
+        }
+        Ok(self.target - available)
+    }
+
+    /// Sign and publish MLS key packages for `user_id`
+    ///
+    /// # Returns
+    ///
+    /// How many key packages `user_id` now has in the directory
+    ///
+    /// # Errors
+    ///
+    /// - `Config` if the directory has no signer
+    /// - `Unauthorized` if the set is held by another identity key
+    pub fn publish(&self, user_id: &UserId, key_packages: Vec<Vec<u8>>) -> MvpResult<usize> {
+        let signer = self.signer.as_ref().ok_or_else(|| {
+            MvpError::Config("Key package directory has no signing key".to_string())
+        })?;
+
+        let mut set = match self.load(user_id)? {
+            Some(set) if set.identity_public_key != signer.public_key() => {
+                return Err(MvpError::Unauthorized(format!(
+                    "key packages for {} are published by another identity key",
+                    user_id
+                )));
+            }
+            Some(set) => set,
+            None => KeyPackageSet {
+                user_id: user_id.clone(),
+                identity_public_key: signer.public_key().to_vec(),
+                packages: Vec::new(),
+            },
+        };
+        set.packages
+            .extend(key_packages.into_iter().map(|kp| KeyPackage::for_mls(signer, kp).to_bytes()));
+        self.store(&set)?;
+        Ok(set.packages.len())
+    }
+
+    /// Take one of `user_id`'s key packages out of the directory
+    ///
+    /// The package is removed before it is returned, so it is never handed
+    /// out twice. Entries that aren't validly signed by the set's identity
+    /// key are dropped on the way.
+    ///
+    /// # Errors
+    ///
+    /// `NoKeyPackages` if `user_id` has none left
+    pub fn take(&self, user_id: &UserId) -> MvpResult<FetchedKeyPackage> {
+        let mut set =
+            self.load(user_id)?.ok_or_else(|| MvpError::NoKeyPackages(user_id.0.clone()))?;
+
+        let mut fetched = None;
+        while fetched.is_none() && !set.packages.is_empty() {
+            let bytes = set.packages.remove(0);
+            let package = match validate_keypackage(&bytes) {
+                Ok(package) if package.verify(&set.identity_public_key) => package,
+                Ok(_) => {
+                    warn!(user_id = %user_id, "Dropping key package with a bad signature");
+                    continue;
+                }
+                Err(e) => {
+                    warn!(user_id = %user_id, error = %e, "Dropping malformed key package");
+                    continue;
+                }
+            };
+            fetched = package.mls_key_package().map(|key_package| FetchedKeyPackage {
+                key_package: key_package.to_vec(),
+                identity_public_key: set.identity_public_key.clone(),
+            });
+        }
+
+        self.store(&set)?;
+        fetched.ok_or_else(|| MvpError::NoKeyPackages(user_id.0.clone()))
+    }
+
+    fn load(&self, user_id: &UserId) -> MvpResult<Option<KeyPackageSet>> {
+        let Ok(value) = self.storage.get(&Self::key(user_id)) else {
+            return Ok(None);
+        };
+        let set = bincode::deserialize(&value.data)
+            .map_err(|e| MvpError::SerializationError(format!("Failed to deserialize: {}", e)))?;
+        Ok(Some(set))
+    }
+
+    fn store(&self, set: &KeyPackageSet) -> MvpResult<()> {
+        let key = Self::key(&set.user_id);
+        let data = bincode::serialize(set)
+            .map_err(|e| MvpError::SerializationError(format!("Failed to serialize: {}", e)))?;
+
+        // Sequence by write time so the latest set wins on every replica
+        let previous = self.storage.get(&key).ok().map(|value| value.sequence);
+        let sequence = Timestamp::now().as_millis().max(previous.map_or(0, |seq| seq + 1));
+        let value = DhtValue::new(data).with_ttl_duration(KEY_PACKAGE_TTL).with_sequence(sequence);
+
+        match self.storage.put(key, value.clone()) {
+            // An expired entry written in the same millisecond; replace it
+            Err(_) if previous.is_none() => {
+                let _ = self.storage.delete(&key);
+                self.storage.put(key, value).map_err(MvpError::Dht)
+            }
+            result => result.map_err(MvpError::Dht),
+        }
+    }
+}
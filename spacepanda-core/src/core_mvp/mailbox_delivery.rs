@@ -0,0 +1,89 @@
+//! Welcomes and commits delivered through DHT mailboxes
+//!
+//! When an invitee is resolved through the key package directory, the
+//! inviter also learns their identity key. The invite's Welcome (and later
+//! commits for channels they are in) can then be left in their
+//! [`RecipientMailbox`], sealed to that key, and picked up with
+//! [`ChannelManager::poll_mailbox`](crate::core_mvp::ChannelManager::poll_mailbox)
+//! whenever they next come online.
+
+use crate::core_dht::{DhtKey, RecipientMailbox};
+use crate::core_identity::Keypair;
+use crate::core_mls::encryption::HpkeContext;
+use crate::core_mvp::errors::{MvpError, MvpResult};
+use crate::core_mvp::types::InviteToken;
+use crate::core_store::model::types::ChannelId;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Associated data binding a sealed delivery to its purpose
+const DELIVERY_AAD: &[u8] = b"spacepanda mailbox delivery v1";
+
+/// What can be left in a recipient's mailbox
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Delivery {
+    /// An invite, with its Welcome and ratchet tree
+    Welcome(InviteToken),
+
+    /// A commit for a channel the recipient is a member of
+    Commit { channel_id: ChannelId, commit: Vec<u8> },
+}
+
+/// Our mailbox, and the means to deliver to others'
+pub struct DeliveryMailbox {
+    mailbox: Arc<RecipientMailbox>,
+    identity: Keypair,
+    address: DhtKey,
+}
+
+impl DeliveryMailbox {
+    /// Use `mailbox` to send, and to receive as the holder of `identity_kp`
+    ///
+    /// `identity_kp` must be the identity key our key packages are
+    /// published with, since that is the key inviters seal to.
+    pub fn new(mailbox: Arc<RecipientMailbox>, identity_kp: Keypair) -> Self {
+        let address = RecipientMailbox::recipient(identity_kp.public_key());
+        Self { mailbox, identity: identity_kp, address }
+    }
+
+    /// Seal a delivery to an identity key and leave it in their mailbox
+    pub fn deliver(&self, identity_public_key: &[u8], delivery: &Delivery) -> MvpResult<()> {
+        let plaintext = bincode::serialize(delivery)
+            .map_err(|e| MvpError::SerializationError(format!("Failed to serialize: {}", e)))?;
+        let recipient_key = Keypair::x25519_public_from_ed25519(identity_public_key)
+            .map_err(|e| MvpError::InvalidOperation(format!("Cannot seal delivery: {}", e)))?;
+        let sealed = HpkeContext::new(recipient_key).seal(&plaintext, DELIVERY_AAD)?;
+
+        self.mailbox
+            .deposit(&RecipientMailbox::recipient(identity_public_key), sealed)
+            .map(|_| ())
+            .map_err(MvpError::Dht)
+    }
+
+    /// Deliveries waiting for us, in order, with their mailbox positions
+    ///
+    /// An entry that can't be opened (not sealed to us, or corrupted) is
+    /// returned as an error so the caller can drop it.
+    pub fn pending(&self) -> Vec<(u64, MvpResult<Delivery>)> {
+        let secret = Keypair::derive_x25519_from_ed25519(&self.identity);
+        self.mailbox
+            .fetch(&self.address)
+            .into_iter()
+            .map(|entry| {
+                let delivery = HpkeContext::open(secret.secret_key(), &entry.payload, DELIVERY_AAD)
+                    .map_err(MvpError::from)
+                    .and_then(|plaintext| {
+                        bincode::deserialize(&plaintext).map_err(|e| {
+                            MvpError::SerializationError(format!("Failed to deserialize: {}", e))
+                        })
+                    });
+                (entry.seq, delivery)
+            })
+            .collect()
+    }
+
+    /// Remove a processed delivery from our mailbox
+    pub fn remove(&self, seq: u64) {
+        self.mailbox.remove(&self.address, seq);
+    }
+}
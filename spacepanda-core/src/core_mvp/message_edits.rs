@@ -0,0 +1,224 @@
+//! Editing and deleting sent messages
+//!
+//! An edit or delete travels as an application message of its own: a
+//! [`MessageChange`] naming the original message by ID, signed with the
+//! leaf key of the member making it. Receivers check the signature against
+//! the group's current leaves, then that the editor sent the original (an
+//! admin may also delete), before touching the stored message.
+//!
+//! A change can arrive before the message it refers to. It is then held
+//! in [`PendingChanges`] and applied once the original is stored.
+
+use crate::core_mvp::errors::{MvpError, MvpResult};
+use crate::core_mvp::types::MessageType;
+use crate::core_store::model::types::{ChannelId, MessageId, Timestamp, UserId};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// Marker at the start of a message change's plaintext
+const MESSAGE_CHANGE_MAGIC: &[u8; 4] = b"SPMC";
+
+/// Changes held for messages that haven't arrived, across all channels
+pub const MAX_PENDING_CHANGES: usize = 1024;
+
+/// An edit or deletion of an earlier message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageChange {
+    /// `Edit` or `Delete`
+    pub message_type: MessageType,
+
+    /// Channel of the original message
+    pub channel_id: ChannelId,
+
+    /// The original message
+    pub target: MessageId,
+
+    /// New body of an edit; empty for a deletion
+    pub body: Vec<u8>,
+
+    /// Member making the change
+    pub editor: UserId,
+
+    /// When the change was made
+    pub timestamp: Timestamp,
+
+    /// Editor's leaf signature over everything above
+    pub signature: Vec<u8>,
+}
+
+impl MessageChange {
+    /// An unsigned edit
+    pub fn edit(channel_id: ChannelId, target: MessageId, body: Vec<u8>, editor: UserId) -> Self {
+        Self::new(MessageType::Edit, channel_id, target, body, editor)
+    }
+
+    /// An unsigned deletion
+    pub fn delete(channel_id: ChannelId, target: MessageId, editor: UserId) -> Self {
+        Self::new(MessageType::Delete, channel_id, target, Vec::new(), editor)
+    }
+
+    fn new(
+        message_type: MessageType,
+        channel_id: ChannelId,
+        target: MessageId,
+        body: Vec<u8>,
+        editor: UserId,
+    ) -> Self {
+        Self {
+            message_type,
+            channel_id,
+            target,
+            body,
+            editor,
+            timestamp: Timestamp::now(),
+            signature: Vec::new(),
+        }
+    }
+
+    /// Bytes the editor signs
+    pub fn signed_bytes(&self) -> Vec<u8> {
+        let mut bytes = b"MESSAGE_CHANGE_V1:".to_vec();
+        bytes.push(match self.message_type {
+            MessageType::Delete => 1,
+            _ => 0,
+        });
+        for field in [
+            self.channel_id.0.as_bytes(),
+            self.target.0.as_bytes(),
+            self.body.as_slice(),
+            self.editor.0.as_bytes(),
+        ] {
+            bytes.extend_from_slice(&(field.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(field);
+        }
+        bytes.extend_from_slice(&self.timestamp.as_millis().to_le_bytes());
+        bytes
+    }
+
+    /// Plaintext to send to the channel
+    pub fn encode(&self) -> MvpResult<Vec<u8>> {
+        let encoded = bincode::serialize(self).map_err(|e| {
+            MvpError::Serialization(format!("Failed to serialize message change: {}", e))
+        })?;
+        let mut plaintext = MESSAGE_CHANGE_MAGIC.to_vec();
+        plaintext.extend_from_slice(&encoded);
+        Ok(plaintext)
+    }
+
+    /// The change in a decrypted plaintext, if it is one
+    pub fn decode(plaintext: &[u8]) -> Option<Self> {
+        let change: Self =
+            bincode::deserialize(plaintext.strip_prefix(MESSAGE_CHANGE_MAGIC.as_slice())?).ok()?;
+        matches!(change.message_type, MessageType::Edit | MessageType::Delete).then_some(change)
+    }
+}
+
+/// Verified changes waiting for the message they refer to
+///
+/// Holds at most [`MAX_PENDING_CHANGES`]; the oldest change is dropped to
+/// make room.
+#[derive(Debug, Default)]
+pub struct PendingChanges {
+    changes: HashMap<MessageId, Vec<MessageChange>>,
+    order: VecDeque<MessageId>,
+}
+
+impl PendingChanges {
+    /// Hold a change until its message arrives
+    pub fn hold(&mut self, change: MessageChange) {
+        if self.order.len() >= MAX_PENDING_CHANGES {
+            if let Some(oldest) = self.order.pop_front() {
+                if let Some(changes) = self.changes.get_mut(&oldest) {
+                    changes.remove(0);
+                    if changes.is_empty() {
+                        self.changes.remove(&oldest);
+                    }
+                }
+            }
+        }
+
+        self.order.push_back(change.target.clone());
+        self.changes.entry(change.target.clone()).or_default().push(change);
+    }
+
+    /// Take the changes held for a message, oldest first
+    pub fn take(&mut self, message_id: &MessageId) -> Vec<MessageChange> {
+        let Some(changes) = self.changes.remove(message_id) else {
+            return Vec::new();
+        };
+        self.order.retain(|id| id != message_id);
+        changes
+    }
+
+    /// Number of changes held
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    /// Whether no changes are held
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(target: &MessageId, body: &str) -> MessageChange {
+        MessageChange::edit(
+            ChannelId("general".to_string()),
+            target.clone(),
+            body.as_bytes().to_vec(),
+            UserId("alice".to_string()),
+        )
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let mut change = edit(&MessageId::generate(), "fixed");
+        change.signature = vec![7; 64];
+
+        let plaintext = change.encode().unwrap();
+        assert_eq!(MessageChange::decode(&plaintext), Some(change));
+
+        // Ordinary messages aren't changes
+        assert_eq!(MessageChange::decode(b"hello"), None);
+        assert_eq!(MessageChange::decode(b"SPMC not really"), None);
+    }
+
+    #[test]
+    fn test_signed_bytes_cover_type_and_body() {
+        let target = MessageId::generate();
+        let change = edit(&target, "fixed");
+
+        let mut other_body = change.clone();
+        other_body.body = b"fixes".to_vec();
+        assert_ne!(change.signed_bytes(), other_body.signed_bytes());
+
+        let mut deletion = change.clone();
+        deletion.message_type = MessageType::Delete;
+        assert_ne!(change.signed_bytes(), deletion.signed_bytes());
+    }
+
+    #[test]
+    fn test_pending_changes_are_bounded() {
+        let mut pending = PendingChanges::default();
+        let first = MessageId::generate();
+        pending.hold(edit(&first, "one"));
+        pending.hold(edit(&first, "two"));
+
+        let taken = pending.take(&first);
+        let bodies: Vec<Vec<u8>> = taken.into_iter().map(|c| c.body).collect();
+        assert_eq!(bodies, vec![b"one".to_vec(), b"two".to_vec()]);
+        assert!(pending.is_empty());
+
+        let oldest = MessageId::generate();
+        pending.hold(edit(&oldest, "dropped"));
+        for _ in 0..MAX_PENDING_CHANGES {
+            pending.hold(edit(&MessageId::generate(), "kept"));
+        }
+        assert_eq!(pending.len(), MAX_PENDING_CHANGES);
+        assert!(pending.take(&oldest).is_empty());
+    }
+}
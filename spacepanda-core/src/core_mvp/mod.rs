@@ -3,24 +3,71 @@
 //! This module provides the high-level API for SpacePanda's MVP by coordinating
 //! `core_identity`, `core_mls`, `core_store`, and `core_dht` subsystems.
 
+pub mod account_export;
+pub mod acks;
 pub mod adapters;
+pub mod attachments;
+pub mod broadcast;
+pub mod channel_directory;
 pub mod channel_manager;
+pub mod circuit_feed;
+pub mod delivery;
+pub mod device_link;
+pub mod ephemeral;
 pub mod errors;
+pub mod events;
 pub mod group_provider;
 pub mod identity_scoping;
+pub mod key_package_directory;
+pub mod mailbox_delivery;
+pub mod message_edits;
 pub mod message_mixer;
 pub mod network;
+pub mod notifications;
+pub mod offline_invite;
 pub mod peer_discovery;
+pub mod privacy;
+pub mod public_group_directory;
+pub mod reactions;
+pub mod supervisor;
 pub mod test_harness;
+pub mod transcript;
+pub mod transform;
+pub mod tree_directory;
 pub mod types;
+pub mod webhooks;
 
 #[cfg(test)]
 mod tests;
 
 // Re-exports
+pub use account_export::{AccountImporter, ExportManifest, ExportSection};
 pub use adapters::{CoreMlsAdapter, MockGroupProvider};
+pub use attachments::{AttachmentManifest, AttachmentStore};
+pub use broadcast::{BroadcastMailbox, BroadcastPolicy, DhtMailbox, MembershipBatch};
+pub use channel_directory::{ChannelDirectory, ChannelListing};
 pub use channel_manager::{ChannelManager, Identity};
+pub use circuit_feed::{CircuitFeedPublisher, FeedPublisher, FeedPut};
+pub use delivery::{DeliveryReport, DeliverySloEvent, DeliveryStats, DeliveryTracker};
+pub use device_link::{DeviceLinkApproval, DeviceLinkRequest, LinkedDevice, RevokedDevice};
 pub use errors::{MvpError, MvpResult};
+pub use events::{ChannelEvent, ChannelEventKind};
 pub use group_provider::{GroupConfig, GroupHandle, GroupProvider, Welcome};
+pub use key_package_directory::{FetchedKeyPackage, KeyPackageDirectory};
+pub use mailbox_delivery::{Delivery, DeliveryMailbox};
+pub use notifications::{
+    DigestEvent, Notification, NotificationEvent, NotificationRules, WebhookSink,
+};
+pub use offline_invite::{OfflineInvite, ResumeReport};
 pub use peer_discovery::{NoPeerDiscovery, PeerDiscovery, PeerDiscoveryService};
-pub use types::{ChannelDescriptor, ChatMessage, InviteToken};
+pub use privacy::{MemberListVisibility, PrivacyRating, PrivacyReport, PrivacySettings};
+pub use public_group_directory::PublicGroupDirectory;
+pub use supervisor::{SecurityEvent, SupervisorPolicy};
+pub use transcript::{TranscriptFormat, TranscriptOptions, TranscriptWriter};
+pub use transform::{ContentTransformer, RenderedMessage, Rendering, TransformerChain};
+pub use tree_directory::TreeDirectory;
+pub use types::{
+    ChannelDescriptor, ChannelMember, ChatMessage, DeliverySummary, ExternalTree, InviteBatch,
+    InviteOptions, InviteToken, Invitee, KeyRotationReport, TranscriptReport,
+};
+pub use webhooks::{WebhookCredentials, WebhookOptions};
@@ -27,29 +27,96 @@
 //! │  • peer discovery                   │
 //! └─────────────────────────────────────┘
 //! ```
+//!
+//! # Delivery paths
+//!
+//! Each peer is reached over the best of its direct, relay (onion circuit)
+//! and mailbox paths, as ranked by the [`PathScorer`]. The recipient answers
+//! every frame but a receipt with a [`ChannelNetworkMessage::DeliveryReceipt`]
+//! to the peer it came from, so a direct or relay send only counts as
+//! delivered once its receipt is back, and the round trip is what the scorer
+//! learns from. The same receipts feed the [`DeliveryTracker`]. A mailbox
+//! send is delivered once the mailbox has stored it. Idle paths are
+//! re-measured with keepalive probes by
+//! [`spawn_path_prober`](NetworkLayer::spawn_path_prober).
+//!
+//! Relay and mailbox frames reach the recipient from a relay or a mailbox
+//! rather than their sender, so they are wrapped in a
+//! [`ChannelNetworkMessage::Signed`] frame naming the sender. It is only
+//! accepted under the key that sender announced over a live session
+//! ([`ChannelNetworkMessage::PeerKey`]). The announced keys and our position
+//! in our mailbox inbox are kept in the [`NETWORK_STATE_FILE`].
 
+use crate::core_identity::Keypair;
+use crate::core_mvp::broadcast::BroadcastMailbox;
+use crate::core_mvp::delivery::{
+    message_ref, DeliveryTracker, HybridClock, HybridTimestamp, MessageRef,
+};
 use crate::core_mvp::errors::{MvpError, MvpResult};
-use crate::core_router::{PeerId, RouterEvent, RouterHandle};
-use crate::core_store::model::types::{ChannelId, UserId};
+use crate::core_router::{DeliveryDetail, PathKind, PathScorer, PeerId, RouterEvent, RouterHandle};
+use crate::core_store::model::types::{ChannelId, Timestamp, UserId};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
-use tracing::{debug, error, info, warn};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, oneshot, RwLock};
+use tracing::{debug, error, info, info_span, warn, Instrument};
 
 /// Network message types for MLS channels
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ChannelNetworkMessage {
     /// Encrypted application message (MLS ciphertext)
-    EncryptedMessage { channel_id: String, ciphertext: Vec<u8>, sender_id: String },
+    EncryptedMessage {
+        channel_id: String,
+        ciphertext: Vec<u8>,
+        sender_id: String,
+        /// Send time, which the delivery receipt's time is ordered after
+        #[serde(default)]
+        sent_at: Option<HybridTimestamp>,
+    },
     /// Commit message (group state change)
     Commit { channel_id: String, commit_data: Vec<u8> },
     /// Proposal message
     Proposal { channel_id: String, proposal_data: Vec<u8> },
     /// Peer wants to join a channel
     JoinRequest { channel_id: String, key_package: Vec<u8> },
+    /// Invitee joined from an invite (sent to the inviter)
+    InviteAccepted { channel_id: String, invite_id: String, user_id: String },
+    /// Recipient received the frame with `message_ref` (sent back to its sender)
+    DeliveryReceipt { channel_id: String, message_ref: Vec<u8>, delivered_at: HybridTimestamp },
+    /// Another message, signed by `sender` over the sender and payload
+    Signed { sender: Vec<u8>, payload: Vec<u8>, signature: Vec<u8> },
+    /// Key the sender signs its relay and mailbox frames with
+    PeerKey { public_key: Vec<u8> },
+    /// Keepalive probe, to measure a path; the nonce keeps probes apart
+    Keepalive { nonce: u64 },
 }
 
+impl ChannelNetworkMessage {
+    /// Channel the message is about, if any
+    fn channel_id(&self) -> Option<&str> {
+        match self {
+            Self::EncryptedMessage { channel_id, .. }
+            | Self::Commit { channel_id, .. }
+            | Self::Proposal { channel_id, .. }
+            | Self::JoinRequest { channel_id, .. }
+            | Self::InviteAccepted { channel_id, .. }
+            | Self::DeliveryReceipt { channel_id, .. } => Some(channel_id.as_str()),
+            Self::Signed { .. } | Self::PeerKey { .. } | Self::Keepalive { .. } => None,
+        }
+    }
+}
+
+/// How long a frame may go without a receipt before its path counts as failed
+pub const DEFAULT_ACK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// File in the data directory announced peer keys and the inbox position are kept in
+pub const NETWORK_STATE_FILE: &str = "network_state.json";
+
+/// Upper bound on the length of a serialized receipt
+const MAX_RECEIPT_LEN: usize = 512;
+
 /// Maps channel members to their network peer IDs
 type ChannelMemberMap = HashMap<ChannelId, HashMap<UserId, PeerId>>;
 
@@ -58,6 +125,8 @@ type ChannelMemberMap = HashMap<ChannelId, HashMap<UserId, PeerId>>;
 pub struct IncomingMessage {
     pub channel_id: ChannelId,
     pub ciphertext: Vec<u8>,
+    /// Sender as announced by the delivering peer
+    pub sender_id: UserId,
     pub sender_peer_id: PeerId,
 }
 
@@ -67,6 +136,35 @@ pub struct IncomingCommit {
     pub channel_id: ChannelId,
     pub commit_data: Vec<u8>,
     pub sender_peer_id: PeerId,
+    /// Set when this is an invitee's join notice rather than a commit
+    pub accepted_invite: Option<InviteAcceptance>,
+}
+
+/// An invitee's notice that they joined from an invite
+#[derive(Debug, Clone)]
+pub struct InviteAcceptance {
+    pub invite_id: String,
+    pub user_id: UserId,
+}
+
+/// Saved peer keys and inbox position
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct NetworkState {
+    /// Sequence number of the next entry to read from our inbox
+    inbox_cursor: u64,
+
+    /// Key each peer (by hex peer ID) announced over a live session
+    peer_keys: BTreeMap<String, Vec<u8>>,
+}
+
+impl NetworkState {
+    /// Write the state, replacing the previous one atomically
+    fn save(&self, path: &Path) -> std::io::Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(tmp, path)
+    }
 }
 
 /// Network layer for P2P messaging
@@ -85,6 +183,36 @@ pub struct NetworkLayer {
 
     /// Our peer ID
     local_peer_id: PeerId,
+
+    /// Ranks the available paths to each peer
+    path_scorer: Arc<PathScorer>,
+
+    /// Frames waiting for their receipt, by peer and frame reference
+    pending_receipts: Mutex<HashMap<(PeerId, MessageRef), oneshot::Sender<()>>>,
+
+    /// How long to wait for a frame's receipt
+    ack_timeout: Duration,
+
+    /// Store-and-forward mailbox, used as the last resort path to a peer
+    mailbox: Option<Arc<dyn BroadcastMailbox>>,
+
+    /// Signs our relay and mailbox frames
+    signing_key: Option<Keypair>,
+
+    /// Announced peer keys and our inbox position
+    state: Mutex<NetworkState>,
+
+    /// Where the state is saved, if anywhere
+    state_path: Option<PathBuf>,
+
+    /// Stamps send times and delivery receipts
+    clock: Arc<HybridClock>,
+
+    /// Delivery latency tracking (receipts are only requested when set)
+    delivery: Option<Arc<DeliveryTracker>>,
+
+    /// Announces each peer connection, e.g. to retry queued messages
+    peer_connected_tx: broadcast::Sender<PeerId>,
 }
 
 impl NetworkLayer {
@@ -109,6 +237,16 @@ impl NetworkLayer {
             incoming_tx,
             incoming_commits_tx,
             local_peer_id,
+            path_scorer: Arc::new(PathScorer::default()),
+            pending_receipts: Mutex::new(HashMap::new()),
+            ack_timeout: DEFAULT_ACK_TIMEOUT,
+            mailbox: None,
+            signing_key: None,
+            state: Mutex::new(NetworkState::default()),
+            state_path: None,
+            clock: Arc::new(HybridClock::new()),
+            delivery: None,
+            peer_connected_tx: broadcast::channel(16).0,
         };
 
         (network, incoming_rx, incoming_commits_rx)
@@ -140,11 +278,75 @@ impl NetworkLayer {
             incoming_tx,
             incoming_commits_tx,
             local_peer_id,
+            path_scorer: Arc::new(PathScorer::default()),
+            pending_receipts: Mutex::new(HashMap::new()),
+            ack_timeout: DEFAULT_ACK_TIMEOUT,
+            mailbox: None,
+            signing_key: None,
+            state: Mutex::new(NetworkState::default()),
+            state_path: None,
+            clock: Arc::new(HybridClock::new()),
+            delivery: None,
+            peer_connected_tx: broadcast::channel(16).0,
         };
 
         (network, incoming_rx, incoming_commits_rx)
     }
 
+    /// Request delivery receipts for sent messages and track their latency
+    pub fn with_delivery_tracker(mut self, tracker: Arc<DeliveryTracker>) -> Self {
+        info!("Attaching delivery tracker to network layer");
+        self.delivery = Some(tracker);
+        self
+    }
+
+    /// Reach peers through a store-and-forward mailbox when no live path works
+    ///
+    /// Each peer reads its own inbox in the mailbox; see
+    /// [`spawn_mailbox_poller`](Self::spawn_mailbox_poller). Entries are
+    /// signed, so the mailbox path needs a signing key.
+    pub fn with_mailbox(mut self, mailbox: Arc<dyn BroadcastMailbox>) -> Self {
+        info!("Attaching mailbox to network layer");
+        self.mailbox = Some(mailbox);
+        self
+    }
+
+    /// Sign relay and mailbox frames with `signing_key` (an Ed25519 keypair)
+    ///
+    /// The public key is announced to each peer as it connects.
+    pub fn with_signing_key(mut self, signing_key: Keypair) -> Self {
+        self.signing_key = Some(signing_key);
+        self
+    }
+
+    /// Keep announced peer keys and the inbox position at `path`, picking up saved ones
+    pub fn with_state_file(mut self, path: &Path) -> MvpResult<Self> {
+        if path.exists() {
+            let json = std::fs::read(path).map_err(|e| MvpError::Store(e.to_string()))?;
+            let state = serde_json::from_slice(&json)
+                .map_err(|e| MvpError::SerializationError(e.to_string()))?;
+            self.state = Mutex::new(state);
+        }
+        self.state_path = Some(path.to_path_buf());
+        Ok(self)
+    }
+
+    /// Set how long a frame may go without a receipt
+    pub fn with_ack_timeout(mut self, timeout: Duration) -> Self {
+        self.ack_timeout = timeout;
+        self
+    }
+
+    /// Delivery tracker, if attached
+    pub fn delivery_tracker(&self) -> Option<&Arc<DeliveryTracker>> {
+        self.delivery.as_ref()
+    }
+
+    /// Subscribe to peer connections seen by the event processor
+    pub fn peer_connections(&self) -> broadcast::Receiver<PeerId> {
+        self.peer_connected_tx.subscribe()
+    }
+
     /// Start listening on an address
     pub async fn listen(&self, addr: &str) -> MvpResult<()> {
         self.router
@@ -179,7 +381,8 @@ impl NetworkLayer {
         members
             .entry(channel_id.clone())
             .or_insert_with(HashMap::new)
-            .insert(user_id, peer_id);
+            .insert(user_id, peer_id.clone());
+        self.add_known_paths(&peer_id);
 
         eprintln!("[P2P] Channel {} now has {} registered members", 
             channel_id.0, members.get(channel_id).map(|m| m.len()).unwrap_or(0));
@@ -192,6 +395,279 @@ impl NetworkLayer {
         );
     }
 
+    /// Register an additional path to a peer (e.g. a relay circuit)
+    pub fn add_peer_path(&self, peer_id: &PeerId, kind: PathKind) {
+        self.path_scorer.add_path(peer_id, kind);
+    }
+
+    /// Feed a keepalive RTT measurement into path selection
+    pub fn record_peer_rtt(&self, peer_id: &PeerId, kind: PathKind, rtt: Duration) {
+        self.path_scorer.record_rtt(peer_id, kind, rtt);
+    }
+
+    /// Register every path we can use to reach a peer
+    ///
+    /// Direct and relay paths always exist (a relay send fails fast when
+    /// there are no relays); the mailbox path needs a mailbox.
+    fn add_known_paths(&self, peer_id: &PeerId) {
+        self.path_scorer.add_path(peer_id, PathKind::Direct);
+        self.path_scorer.add_path(peer_id, PathKind::Relay);
+        if self.mailbox.is_some() {
+            self.path_scorer.add_path(peer_id, PathKind::Mailbox);
+        }
+    }
+
+    /// Path scorer used for delivery
+    pub fn path_scorer(&self) -> &Arc<PathScorer> {
+        &self.path_scorer
+    }
+
+    /// Deliver bytes to a peer over its best path, failing over on error
+    ///
+    /// A path only succeeds once the peer has the bytes: receipted for
+    /// direct and relay paths, stored for the mailbox. The chosen path is
+    /// recorded on the `deliver` span.
+    pub async fn deliver_to_peer(
+        &self,
+        peer_id: &PeerId,
+        data: Vec<u8>,
+    ) -> Result<DeliveryDetail, String> {
+        // Peers we have never seen a route for are still tried
+        self.add_known_paths(peer_id);
+
+        let span = info_span!("deliver", peer_id = ?peer_id, path = tracing::field::Empty);
+        let result = self
+            .path_scorer
+            .deliver_with_failover(peer_id, |kind| self.send_tracked(peer_id, kind, &data))
+            .instrument(span.clone())
+            .await;
+
+        match result {
+            Ok(detail) => {
+                span.record("path", tracing::field::display(detail.path));
+                Ok(detail)
+            }
+            Err(failures) => Err(failures
+                .into_iter()
+                .map(|(kind, e)| format!("{}: {}", kind, e))
+                .collect::<Vec<_>>()
+                .join("; ")),
+        }
+    }
+
+    /// Send bytes over one path and wait until the peer has them
+    async fn send_tracked(
+        &self,
+        peer_id: &PeerId,
+        kind: PathKind,
+        data: &[u8],
+    ) -> Result<(), String> {
+        // The mailbox stores what it accepts, so accepting is delivery
+        if kind == PathKind::Mailbox {
+            return self.send_over(peer_id, kind, data.to_vec()).await;
+        }
+
+        let pending = (peer_id.clone(), message_ref(data));
+        let (receipt_tx, receipt_rx) = oneshot::channel();
+        self.pending_receipts.lock().unwrap().insert(pending.clone(), receipt_tx);
+
+        let result = match self.send_over(peer_id, kind, data.to_vec()).await {
+            Ok(()) => match tokio::time::timeout(self.ack_timeout, receipt_rx).await {
+                Ok(Ok(())) => Ok(()),
+                _ => Err(format!("No delivery receipt within {:?}", self.ack_timeout)),
+            },
+            Err(e) => Err(e),
+        };
+        self.pending_receipts.lock().unwrap().remove(&pending);
+        result
+    }
+
+    /// Hand bytes to one path, without waiting for the peer
+    async fn send_over(
+        &self,
+        peer_id: &PeerId,
+        kind: PathKind,
+        data: Vec<u8>,
+    ) -> Result<(), String> {
+        match kind {
+            PathKind::Direct => self.router.send_direct(peer_id.clone(), data).await,
+            PathKind::Relay => {
+                let frame = self.sign(data)?;
+                self.router.send_anonymous(peer_id.clone(), frame).await
+            }
+            PathKind::Mailbox => {
+                let Some(mailbox) = &self.mailbox else {
+                    return Err("No mailbox attached".to_string());
+                };
+                let frame = self.sign(data)?;
+                let sender = UserId(hex::encode(self.local_peer_id.as_bytes()));
+                mailbox
+                    .publish(&inbox(peer_id), &sender, frame)
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    /// Wrap a frame in a [`ChannelNetworkMessage::Signed`] frame from us
+    fn sign(&self, payload: Vec<u8>) -> Result<Vec<u8>, String> {
+        let Some(signing_key) = &self.signing_key else {
+            return Err("No signing key attached".to_string());
+        };
+        let sender = self.local_peer_id.0.clone();
+        let signature = signing_key.sign(&signed_bytes(&sender, &payload));
+        serde_json::to_vec(&ChannelNetworkMessage::Signed { sender, payload, signature })
+            .map_err(|e| format!("Failed to serialize: {}", e))
+    }
+
+    /// The sender of a signed frame, if it is signed under the key that sender announced
+    fn verify_signed(&self, sender: &[u8], payload: &[u8], signature: &[u8]) -> MvpResult<PeerId> {
+        let state = self.state.lock().unwrap();
+        let Some(public_key) = state.peer_keys.get(&hex::encode(sender)) else {
+            return Err(MvpError::Unauthorized(format!(
+                "No key announced by peer {}",
+                hex::encode(sender)
+            )));
+        };
+        if !Keypair::verify(public_key, &signed_bytes(sender, payload), signature) {
+            return Err(MvpError::Unauthorized(format!(
+                "Bad signature on a frame from peer {}",
+                hex::encode(sender)
+            )));
+        }
+        Ok(PeerId(sender.to_vec()))
+    }
+
+    /// Remember the key a peer announced, replacing any earlier one
+    fn set_peer_key(&self, peer_id: &PeerId, public_key: Vec<u8>) {
+        let peer = hex::encode(peer_id.as_bytes());
+        let mut state = self.state.lock().unwrap();
+        if state.peer_keys.get(&peer) != Some(&public_key) {
+            state.peer_keys.insert(peer, public_key);
+            self.save_state(&state);
+        }
+    }
+
+    fn save_state(&self, state: &NetworkState) {
+        let Some(path) = &self.state_path else {
+            return;
+        };
+        if let Err(e) = state.save(path) {
+            warn!("Failed to save network state to {:?}: {}", path, e);
+        }
+    }
+
+    /// Tell a peer the key our relay and mailbox frames are signed with
+    ///
+    /// Only a live session authenticates us to the peer, so the key goes
+    /// over the direct path and nowhere else.
+    pub async fn announce_peer_key(&self, peer_id: &PeerId) -> MvpResult<()> {
+        let Some(signing_key) = &self.signing_key else {
+            return Ok(());
+        };
+        let message = ChannelNetworkMessage::PeerKey { public_key: signing_key.public.clone() };
+        let message_bytes = serde_json::to_vec(&message)
+            .map_err(|e| MvpError::SerializationError(format!("Failed to serialize: {}", e)))?;
+        self.send_over(peer_id, PathKind::Direct, message_bytes)
+            .await
+            .map_err(|e| MvpError::NetworkError(format!("Failed to announce key: {}", e)))
+    }
+
+    /// Re-measure one path to a peer with a keepalive probe
+    async fn probe_path(&self, peer_id: &PeerId, kind: PathKind) {
+        let keepalive = ChannelNetworkMessage::Keepalive { nonce: rand::random() };
+        let probe = match serde_json::to_vec(&keepalive) {
+            Ok(probe) => probe,
+            Err(e) => {
+                error!(error = %e, "Failed to serialize keepalive");
+                return;
+            }
+        };
+
+        let started = tokio::time::Instant::now();
+        match self.send_tracked(peer_id, kind, &probe).await {
+            Ok(()) => self.path_scorer.record_rtt(peer_id, kind, started.elapsed()),
+            Err(e) => {
+                debug!(peer_id = ?peer_id, path = %kind, error = %e, "Keepalive probe failed");
+                self.path_scorer.record_failure(peer_id, kind);
+            }
+        }
+    }
+
+    /// Periodically probe the paths the scorer has not measured lately
+    ///
+    /// Mailbox paths are not probed: a mailbox has no round trip to time,
+    /// and it is measured whenever a delivery falls back to it.
+    pub fn spawn_path_prober(self: Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                for peer_id in self.path_scorer.peers() {
+                    for kind in self.path_scorer.paths_due_for_probe(&peer_id) {
+                        if kind != PathKind::Mailbox {
+                            self.probe_path(&peer_id, kind).await;
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// Periodically read our mailbox inbox and handle what peers left there
+    ///
+    /// Only signed entries are handled, as coming from their signer; the
+    /// sender the mailbox records is not trusted. The read position is
+    /// saved after each batch, so a restart picks up where it left off.
+    pub fn spawn_mailbox_poller(
+        self: Arc<Self>,
+        interval: Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let Some(mailbox) = self.mailbox.clone() else {
+                return;
+            };
+            let own_inbox = inbox(&self.local_peer_id);
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let cursor = self.state.lock().unwrap().inbox_cursor;
+                let entries = match mailbox.fetch(&own_inbox, cursor).await {
+                    Ok(entries) => entries,
+                    Err(e) => {
+                        warn!(error = %e, "Failed to read mailbox inbox");
+                        continue;
+                    }
+                };
+                let Some(last) = entries.last().map(|entry| entry.seq) else {
+                    continue;
+                };
+                for entry in entries {
+                    if let Err(e) = self.handle_inbox_entry(entry.ciphertext).await {
+                        warn!(seq = entry.seq, error = %e, "Dropping inbox entry");
+                    }
+                }
+                {
+                    let mut state = self.state.lock().unwrap();
+                    state.inbox_cursor = last + 1;
+                    self.save_state(&state);
+                }
+            }
+        })
+    }
+
+    /// Handle a frame left in our inbox, which must be signed
+    async fn handle_inbox_entry(&self, data: Vec<u8>) -> MvpResult<()> {
+        let message: ChannelNetworkMessage = serde_json::from_slice(&data)
+            .map_err(|e| MvpError::InvalidMessage(format!("Failed to deserialize: {}", e)))?;
+        let ChannelNetworkMessage::Signed { sender, payload, signature } = message else {
+            return Err(MvpError::Unauthorized("Unsigned inbox entry".to_string()));
+        };
+        let peer_id = self.verify_signed(&sender, &payload, &signature)?;
+        self.handle_incoming_data(peer_id, payload).await
+    }
+
     /// Get our local peer ID
     pub async fn get_local_peer_id(&self) -> Option<PeerId> {
         Some(self.local_peer_id.clone())
@@ -226,10 +702,12 @@ impl NetworkLayer {
             eprintln!("[P2P]   - User: {}, PeerID: {:?}", user_id.0, peer_id);
         }
 
+        let sent_at = self.delivery.as_ref().map(|_| self.clock.now());
         let message = ChannelNetworkMessage::EncryptedMessage {
             channel_id: channel_id.0.clone(),
             ciphertext: ciphertext.clone(),
             sender_id: sender_id.0.clone(),
+            sent_at,
         };
 
         let message_bytes = serde_json::to_vec(&message)
@@ -237,6 +715,7 @@ impl NetworkLayer {
 
         let mut sent_count = 0;
         let mut error_count = 0;
+        let mut reached = Vec::new();
 
         // Send to all members except ourselves
         for (user_id, peer_id) in channel_members.iter() {
@@ -246,13 +725,16 @@ impl NetworkLayer {
             }
 
             eprintln!("[P2P] Sending message to user {} (peer {:?})", user_id.0, peer_id);
-            match self.router.send_direct(peer_id.clone(), message_bytes.clone()).await {
-                Ok(_) => {
+            match self.deliver_to_peer(peer_id, message_bytes.clone()).await {
+                Ok(detail) => {
                     sent_count += 1;
+                    reached.push(user_id.clone());
                     eprintln!("[P2P] ✓ Successfully sent to peer {:?}", peer_id);
                     debug!(
                         channel_id = %channel_id,
                         peer_id = ?peer_id,
+                        path = %detail.path,
+                        failovers = detail.failed_paths.len(),
                         "Sent message to peer"
                     );
                 }
@@ -280,6 +762,11 @@ impl NetworkLayer {
             "Broadcast complete"
         );
 
+        // Only members the message reached are expected to send a receipt
+        if let (Some(tracker), Some(sent_at)) = (&self.delivery, sent_at) {
+            tracker.record_sent(channel_id, message_ref(&message_bytes), sent_at, reached);
+        }
+
         if error_count > 0 && sent_count == 0 {
             eprintln!("[P2P] ERROR: Failed to send to any channel members!");
             return Err(MvpError::NetworkError(format!(
@@ -311,7 +798,7 @@ impl NetworkLayer {
 
         // Send to all members
         for (_user_id, peer_id) in channel_members.iter() {
-            if let Err(e) = self.router.send_direct(peer_id.clone(), message_bytes.clone()).await {
+            if let Err(e) = self.deliver_to_peer(peer_id, message_bytes.clone()).await {
                 warn!(peer_id = ?peer_id, error = %e, "Failed to send commit");
             }
         }
@@ -319,37 +806,194 @@ impl NetworkLayer {
         Ok(())
     }
 
+    /// Tell an inviter that we joined from their invite
+    pub async fn send_invite_acceptance(
+        &self,
+        inviter_peer_id: &PeerId,
+        channel_id: &ChannelId,
+        invite_id: &str,
+        user_id: &UserId,
+    ) -> MvpResult<()> {
+        let message = ChannelNetworkMessage::InviteAccepted {
+            channel_id: channel_id.0.clone(),
+            invite_id: invite_id.to_string(),
+            user_id: user_id.0.clone(),
+        };
+
+        let message_bytes = serde_json::to_vec(&message)
+            .map_err(|e| MvpError::SerializationError(format!("Failed to serialize: {}", e)))?;
+
+        self.deliver_to_peer(inviter_peer_id, message_bytes)
+            .await
+            .map(|_| ())
+            .map_err(|e| MvpError::NetworkError(format!("Failed to send invite acceptance: {}", e)))
+    }
+
+    /// Acknowledge a frame to the peer it came from, over the first path that takes it
+    ///
+    /// Receipts are not waited on themselves, so sending one never waits
+    /// on another receipt.
+    async fn send_delivery_receipt(
+        &self,
+        peer_id: &PeerId,
+        message: &ChannelNetworkMessage,
+        frame: &[u8],
+    ) -> MvpResult<()> {
+        let delivered_at = match message {
+            ChannelNetworkMessage::EncryptedMessage { sent_at: Some(sent_at), .. } => {
+                self.clock.observe(*sent_at)
+            }
+            _ => self.clock.now(),
+        };
+        let receipt = ChannelNetworkMessage::DeliveryReceipt {
+            channel_id: message.channel_id().unwrap_or_default().to_string(),
+            message_ref: message_ref(frame).to_vec(),
+            delivered_at,
+        };
+        let receipt = serde_json::to_vec(&receipt)
+            .map_err(|e| MvpError::SerializationError(format!("Failed to serialize: {}", e)))?;
+
+        // Whoever sent the frame may not be a peer we track paths to
+        let mut paths = self.path_scorer.rank(peer_id);
+        if paths.is_empty() {
+            paths = vec![PathKind::Direct, PathKind::Relay];
+        }
+
+        let mut errors = Vec::new();
+        for kind in paths {
+            match self.send_over(peer_id, kind, receipt.clone()).await {
+                Ok(()) => return Ok(()),
+                Err(e) => errors.push(format!("{}: {}", kind, e)),
+            }
+        }
+
+        Err(MvpError::NetworkError(format!(
+            "Failed to send delivery receipt: {}",
+            errors.join("; ")
+        )))
+    }
+
+    /// Settle the delivery a receipt is for, and credit it to the channel member behind the peer
+    async fn handle_delivery_receipt(
+        &self,
+        peer_id: &PeerId,
+        channel_id: ChannelId,
+        message_ref: Vec<u8>,
+        delivered_at: HybridTimestamp,
+    ) -> MvpResult<()> {
+        self.clock.observe(delivered_at);
+        let message_ref: MessageRef = message_ref
+            .try_into()
+            .map_err(|_| MvpError::InvalidMessage("Malformed delivery receipt".to_string()))?;
+        self.settle_delivery(peer_id, &message_ref);
+        let Some(tracker) = &self.delivery else {
+            return Ok(());
+        };
+
+        // The recipient is whoever we know at that peer, not a claimed ID
+        let members = self.channel_members.read().await;
+        let recipient = members
+            .get(&channel_id)
+            .and_then(|m| m.iter().find(|(_, peer)| *peer == peer_id).map(|(user, _)| user));
+        if let Some(recipient) = recipient {
+            let received_ms = Timestamp::now().as_millis();
+            tracker.record_receipt(&message_ref, recipient, delivered_at, received_ms);
+        }
+
+        Ok(())
+    }
+
     /// Start processing incoming network events
     ///
     /// This spawns a background task that listens for router events
-    /// and forwards channel messages to the incoming message channel
+    /// and forwards channel messages to the incoming message channel.
+    /// Delivery receipts settle their delivery as soon as they arrive, so the
+    /// measured round trip does not include time spent queued behind other
+    /// messages. Each connecting peer is told our signing key.
     pub fn spawn_event_processor(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        let mut events = self.router.subscribe();
+        let (data_tx, mut data_rx) = mpsc::unbounded_channel::<(PeerId, Vec<u8>)>();
+
+        // Everything is handled in arrival order, one at a time
+        let worker = self.clone();
+        let data_worker = tokio::spawn(async move {
+            while let Some((peer_id, data)) = data_rx.recv().await {
+                if let Err(e) = worker.handle_incoming_data(peer_id, data).await {
+                    error!(error = %e, "Failed to handle incoming data");
+                }
+            }
+        });
+
         tokio::spawn(async move {
             loop {
-                match self.router.next_event().await {
-                    Some(RouterEvent::DataReceived(peer_id, data)) => {
-                        if let Err(e) = self.handle_incoming_data(peer_id, data).await {
-                            error!(error = %e, "Failed to handle incoming data");
+                match events.recv().await {
+                    Ok(RouterEvent::DataReceived(peer_id, data)) => {
+                        self.settle_receipt(&peer_id, &data);
+                        if data_tx.send((peer_id, data)).is_err() {
+                            break;
                         }
                     }
-                    Some(RouterEvent::PeerConnected(peer_id)) => {
+                    Ok(RouterEvent::PeerConnected(peer_id)) => {
                         info!(peer_id = ?peer_id, "Peer connected");
+                        let network = self.clone();
+                        let peer = peer_id.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = network.announce_peer_key(&peer).await {
+                                warn!(peer_id = ?peer, error = %e, "Failed to announce signing key");
+                            }
+                        });
+                        // Nobody listening is fine
+                        let _ = self.peer_connected_tx.send(peer_id);
                     }
-                    Some(RouterEvent::PeerDisconnected(peer_id)) => {
+                    Ok(RouterEvent::PeerDisconnected(peer_id)) => {
                         info!(peer_id = ?peer_id, "Peer disconnected");
                     }
-                    Some(RouterEvent::Listening(addr)) => {
+                    Ok(RouterEvent::Listening(addr)) => {
                         info!(addr = %addr, "Router listening");
                     }
-                    None => {
+                    Ok(RouterEvent::PeerReputationChanged(peer_id, score)) => {
+                        debug!(peer_id = ?peer_id, score, "Peer reputation changed");
+                    }
+                    Ok(RouterEvent::PeerQuarantined(peer_id, cooldown)) => {
+                        warn!(peer_id = ?peer_id, ?cooldown, "Peer quarantined");
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!(skipped, "Network event processor fell behind");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
                         warn!("Router event channel closed");
                         break;
                     }
                 }
             }
+            data_worker.abort();
         })
     }
 
+    /// Settle the pending delivery a receipt is for, if `data` is a direct receipt
+    fn settle_receipt(&self, peer_id: &PeerId, data: &[u8]) {
+        // Receipts are small, so anything longer is not worth parsing twice
+        if data.len() > MAX_RECEIPT_LEN {
+            return;
+        }
+        if let Ok(ChannelNetworkMessage::DeliveryReceipt { message_ref, .. }) =
+            serde_json::from_slice(data)
+        {
+            if let Ok(message_ref) = MessageRef::try_from(message_ref) {
+                self.settle_delivery(peer_id, &message_ref);
+            }
+        }
+    }
+
+    /// Settle a delivery to `peer_id` waiting for its receipt
+    fn settle_delivery(&self, peer_id: &PeerId, message_ref: &MessageRef) {
+        // Receipts for frames we did not wait on (or gave up on) are ignored
+        let pending = (peer_id.clone(), *message_ref);
+        if let Some(receipt_tx) = self.pending_receipts.lock().unwrap().remove(&pending) {
+            let _ = receipt_tx.send(());
+        }
+    }
+
     /// Handle incoming data from a peer
     /// Handle incoming data from a peer
     ///
@@ -366,14 +1010,30 @@ impl NetworkLayer {
 
         eprintln!("[P2P] Deserialized message successfully");
 
+        // Everything but a receipt, or a wrapper whose payload gets its own, is receipted
+        if !matches!(
+            message,
+            ChannelNetworkMessage::DeliveryReceipt { .. } | ChannelNetworkMessage::Signed { .. }
+        ) {
+            if let Err(e) = self.send_delivery_receipt(&peer_id, &message, &data).await {
+                warn!(peer_id = ?peer_id, error = %e, "Failed to send delivery receipt");
+            }
+        }
+
         match message {
-            ChannelNetworkMessage::EncryptedMessage { channel_id, ciphertext, sender_id: _ } => {
+            ChannelNetworkMessage::EncryptedMessage {
+                channel_id,
+                ciphertext,
+                sender_id,
+                ..
+            } => {
                 eprintln!("[P2P] Forwarding encrypted message to incoming_tx for channel {}", hex::encode(&channel_id));
                 
                 // Forward to channel manager for decryption
                 let incoming = IncomingMessage {
                     channel_id: ChannelId(channel_id),
                     ciphertext,
+                    sender_id: UserId(sender_id),
                     sender_peer_id: peer_id,
                 };
 
@@ -396,6 +1056,7 @@ impl NetworkLayer {
                     channel_id: ChannelId(channel_id),
                     commit_data,
                     sender_peer_id: peer_id.clone(),
+                    accepted_invite: None,
                 };
 
                 if let Err(e) = self.incoming_commits_tx.send(incoming_commit).await {
@@ -418,6 +1079,40 @@ impl NetworkLayer {
                 );
                 // TODO: Forward to channel manager for processing
             }
+            ChannelNetworkMessage::InviteAccepted { channel_id, invite_id, user_id } => {
+                debug!(
+                    channel_id = %channel_id,
+                    invite_id = %invite_id,
+                    "Received invite acceptance"
+                );
+
+                // Acceptances share the commit path so they are handled in
+                // order with the commits around them
+                let incoming_commit = IncomingCommit {
+                    channel_id: ChannelId(channel_id),
+                    commit_data: Vec::new(),
+                    sender_peer_id: peer_id,
+                    accepted_invite: Some(InviteAcceptance { invite_id, user_id: UserId(user_id) }),
+                };
+
+                if let Err(e) = self.incoming_commits_tx.send(incoming_commit).await {
+                    error!(error = %e, "Failed to forward invite acceptance");
+                }
+            }
+            ChannelNetworkMessage::DeliveryReceipt { channel_id, message_ref, delivered_at } => {
+                let channel_id = ChannelId(channel_id);
+                self.handle_delivery_receipt(&peer_id, channel_id, message_ref, delivered_at)
+                    .await?;
+            }
+            ChannelNetworkMessage::Signed { sender, payload, signature } => {
+                // Relayed frames arrive from the exit relay; the signer sent them
+                let sender = self.verify_signed(&sender, &payload, &signature)?;
+                Box::pin(self.handle_incoming_data(sender, payload)).await?;
+            }
+            ChannelNetworkMessage::PeerKey { public_key } => {
+                self.set_peer_key(&peer_id, public_key);
+            }
+            ChannelNetworkMessage::Keepalive { .. } => {}
         }
 
         Ok(())
@@ -438,9 +1133,48 @@ impl NetworkLayer {
     }
 }
 
+/// A peer's inbox in the mailbox
+fn inbox(peer_id: &PeerId) -> ChannelId {
+    ChannelId(format!("inbox:{}", hex::encode(peer_id.as_bytes())))
+}
+
+/// What a [`ChannelNetworkMessage::Signed`] frame's signature covers
+fn signed_bytes(sender: &[u8], payload: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(8 + sender.len() + payload.len());
+    bytes.extend_from_slice(&(sender.len() as u64).to_be_bytes());
+    bytes.extend_from_slice(sender);
+    bytes.extend_from_slice(payload);
+    bytes
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core_dht::DhtStorage;
+    use crate::core_identity::KeyType;
+    use crate::core_mvp::broadcast::DhtMailbox;
+    use tempfile::TempDir;
+
+    fn keepalive() -> Vec<u8> {
+        serde_json::to_vec(&ChannelNetworkMessage::Keepalive { nonce: 7 }).unwrap()
+    }
+
+    fn encrypted_message() -> Vec<u8> {
+        let message = ChannelNetworkMessage::EncryptedMessage {
+            channel_id: "team".to_string(),
+            ciphertext: b"ciphertext".to_vec(),
+            sender_id: "alice@spacepanda.local".to_string(),
+            sent_at: None,
+        };
+        serde_json::to_vec(&message).unwrap()
+    }
+
+    /// Have `network` learn the signing key `peer_id` announced over a live session
+    async fn announce(network: &NetworkLayer, peer_id: &PeerId, signing_key: &Keypair) {
+        let message = ChannelNetworkMessage::PeerKey { public_key: signing_key.public.clone() };
+        let message = serde_json::to_vec(&message).unwrap();
+        network.handle_incoming_data(peer_id.clone(), message).await.unwrap();
+    }
 
     #[tokio::test]
     async fn test_network_layer_creation() {
@@ -469,5 +1203,201 @@ mod tests {
         let peers = network.get_channel_peers(&channel_id).await;
         assert_eq!(peers.len(), 1);
         assert_eq!(peers[0], member_peer_id);
+        assert_eq!(
+            network.path_scorer().rank(&member_peer_id),
+            vec![PathKind::Direct, PathKind::Relay]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deliver_to_peer_prefers_measured_direct_path() {
+        let (router, _handle) = RouterHandle::new();
+        let (network, _rx, _commits_rx) = NetworkLayer::new(router, PeerId(vec![1]));
+        let network = Arc::new(network);
+        let _events = network.clone().spawn_event_processor();
+        let remote = PeerId(vec![2]);
+
+        network.add_peer_path(&remote, PathKind::Relay);
+        network.add_peer_path(&remote, PathKind::Direct);
+        network.record_peer_rtt(&remote, PathKind::Relay, Duration::from_millis(2000));
+        network.record_peer_rtt(&remote, PathKind::Direct, Duration::from_millis(30));
+
+        let detail = network.deliver_to_peer(&remote, keepalive()).await.unwrap();
+        assert_eq!(detail.path, PathKind::Direct);
+        assert!(detail.failed_paths.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unacknowledged_delivery_fails_over_to_mailbox() {
+        let (router, _handle) = RouterHandle::new();
+        let mailbox = Arc::new(DhtMailbox::new(DhtStorage::new()));
+        let (network, _rx, _commits_rx) = NetworkLayer::new(router, PeerId(vec![1]));
+        // No event processor: direct sends are handed over but never acknowledged
+        let network = network
+            .with_mailbox(mailbox.clone())
+            .with_signing_key(Keypair::generate(KeyType::Ed25519))
+            .with_ack_timeout(Duration::from_millis(50));
+        let remote = PeerId(vec![2]);
+
+        let detail = network.deliver_to_peer(&remote, keepalive()).await.unwrap();
+        assert_eq!(detail.path, PathKind::Mailbox);
+        let failed: Vec<PathKind> = detail.failed_paths.iter().map(|(kind, _)| *kind).collect();
+        assert_eq!(failed, vec![PathKind::Direct, PathKind::Relay]);
+
+        // Enqueueing on the direct path is not counted as a delivery
+        let direct = network.path_scorer().stats(&remote);
+        let direct = direct.iter().find(|p| p.kind == PathKind::Direct).unwrap();
+        assert_eq!((direct.successes, direct.failures), (0, 1));
+
+        let entries = mailbox.fetch(&inbox(&remote), 0).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        let entry = serde_json::from_slice(&entries[0].ciphertext).unwrap();
+        assert!(matches!(
+            entry,
+            ChannelNetworkMessage::Signed { sender, payload, .. }
+                if sender == vec![1] && payload == keepalive()
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_mailbox_poller_handles_inbox_entries() {
+        let dir = TempDir::new().unwrap();
+        let state_path = dir.path().join(NETWORK_STATE_FILE);
+        let mailbox = Arc::new(DhtMailbox::new(DhtStorage::new()));
+        let alice_key = Keypair::generate(KeyType::Ed25519);
+        let (alice_router, _alice_task) = RouterHandle::new();
+        let (alice, _rx, _commits_rx) = NetworkLayer::new(alice_router, PeerId(vec![1]));
+        let alice = alice
+            .with_mailbox(mailbox.clone())
+            .with_signing_key(alice_key.clone())
+            .with_ack_timeout(Duration::from_millis(50));
+        let (bob_router, _bob_task) = RouterHandle::new();
+        let (bob, mut bob_rx, _commits_rx) = NetworkLayer::new(bob_router, PeerId(vec![2]));
+        let bob = Arc::new(bob.with_mailbox(mailbox.clone()).with_state_file(&state_path).unwrap());
+        announce(&bob, alice.local_peer_id(), &alice_key).await;
+        let poller = bob.clone().spawn_mailbox_poller(Duration::from_millis(10));
+
+        let detail = alice.deliver_to_peer(bob.local_peer_id(), encrypted_message()).await.unwrap();
+        assert_eq!(detail.path, PathKind::Mailbox);
+
+        let incoming = tokio::time::timeout(Duration::from_secs(5), bob_rx.recv())
+            .await
+            .expect("inbox entry was never handled")
+            .unwrap();
+        assert_eq!(incoming.ciphertext, b"ciphertext".to_vec());
+        assert_eq!(incoming.sender_peer_id, PeerId(vec![1]));
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !std::fs::read_to_string(&state_path).unwrap().contains("\"inbox_cursor\": 1") {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("inbox position was never saved");
+        poller.abort();
+
+        // After a restart the saved key and position carry over
+        let (bob_router, _bob_task) = RouterHandle::new();
+        let (bob, mut bob_rx, _commits_rx) = NetworkLayer::new(bob_router, PeerId(vec![2]));
+        let bob = Arc::new(bob.with_mailbox(mailbox).with_state_file(&state_path).unwrap());
+        let _poller = bob.clone().spawn_mailbox_poller(Duration::from_millis(10));
+        alice.deliver_to_peer(bob.local_peer_id(), keepalive()).await.unwrap();
+        alice.deliver_to_peer(bob.local_peer_id(), encrypted_message()).await.unwrap();
+
+        let incoming = tokio::time::timeout(Duration::from_secs(5), bob_rx.recv())
+            .await
+            .expect("inbox entry was never handled")
+            .unwrap();
+        assert_eq!(incoming.sender_peer_id, PeerId(vec![1]));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(bob_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_signed_frames_need_the_announced_key() {
+        let (router, _handle) = RouterHandle::new();
+        let (network, mut rx, _commits_rx) = NetworkLayer::new(router, PeerId(vec![2]));
+        let alice = PeerId(vec![1]);
+        let alice_key = Keypair::generate(KeyType::Ed25519);
+        let relay = PeerId(vec![3]);
+        let signed = |key: &Keypair| {
+            let payload = encrypted_message();
+            let signature = key.sign(&signed_bytes(alice.as_bytes(), &payload));
+            let message =
+                ChannelNetworkMessage::Signed { sender: alice.0.clone(), payload, signature };
+            serde_json::to_vec(&message).unwrap()
+        };
+
+        // Nothing is accepted from a signer that never announced a key
+        let unknown = network.handle_incoming_data(relay.clone(), signed(&alice_key)).await;
+        assert!(matches!(unknown, Err(MvpError::Unauthorized(_))));
+
+        // A relayed frame is credited to its signer, not the relay
+        announce(&network, &alice, &alice_key).await;
+        network.handle_incoming_data(relay.clone(), signed(&alice_key)).await.unwrap();
+        assert_eq!(rx.try_recv().unwrap().sender_peer_id, alice);
+
+        // Another key claiming to be the signer is refused
+        let forger = Keypair::generate(KeyType::Ed25519);
+        let forged = network.handle_incoming_data(relay, signed(&forger)).await;
+        assert!(matches!(forged, Err(MvpError::Unauthorized(_))));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_path_prober_measures_stale_paths() {
+        let (router, _handle) = RouterHandle::new();
+        let (network, _rx, _commits_rx) = NetworkLayer::new(router, PeerId(vec![1]));
+        let network = Arc::new(network);
+        let _events = network.clone().spawn_event_processor();
+        let remote = PeerId(vec![2]);
+        network.add_peer_path(&remote, PathKind::Direct);
+        assert_eq!(network.path_scorer().paths_due_for_probe(&remote), vec![PathKind::Direct]);
+
+        let prober = network.clone().spawn_path_prober(Duration::from_millis(10));
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while network.path_scorer().stats(&remote)[0].rtt.is_none() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("keepalive RTT was never recorded");
+        prober.abort();
+
+        assert!(network.path_scorer().paths_due_for_probe(&remote).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delivery_receipt_is_credited_to_peer_member() {
+        let (router, _handle) = RouterHandle::new();
+        let tracker = Arc::new(DeliveryTracker::new(Default::default()));
+        let (network, _rx, _commits_rx) = NetworkLayer::new(router, PeerId(vec![1]));
+        let network = network.with_delivery_tracker(tracker.clone());
+
+        let channel_id = ChannelId("team".to_string());
+        let bob = UserId("bob@spacepanda.local".to_string());
+        let bob_peer = PeerId(vec![2]);
+        network
+            .register_channel_member(&channel_id, bob.clone(), bob_peer.clone())
+            .await;
+
+        let ciphertext = b"ciphertext".to_vec();
+        let sent_at = network.clock.now();
+        tracker.record_sent(&channel_id, message_ref(&ciphertext), sent_at, [bob]);
+
+        let receipt = |peer_id: PeerId| {
+            let message = ChannelNetworkMessage::DeliveryReceipt {
+                channel_id: channel_id.0.clone(),
+                message_ref: message_ref(&ciphertext).to_vec(),
+                delivered_at: sent_at,
+            };
+            network.handle_incoming_data(peer_id, serde_json::to_vec(&message).unwrap())
+        };
+
+        // A receipt from a peer that isn't the recipient is ignored
+        receipt(PeerId(vec![3])).await.unwrap();
+        assert!(tracker.stats(&channel_id).is_none());
+
+        receipt(bob_peer).await.unwrap();
+        assert_eq!(tracker.stats(&channel_id).unwrap().samples, 1);
     }
 }
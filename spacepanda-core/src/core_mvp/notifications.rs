@@ -0,0 +1,514 @@
+//! Notification rules and the daily digest
+//!
+//! [`NotificationRules`] turns the messages in the channel event stream into
+//! notifications according to each channel's [`NotificationMode`]:
+//!
+//! - `All` notifies every message, `MentionsOnly` only messages mentioning
+//!   the local user (`@display name` or `@user id`), `Mute` nothing
+//! - `Digest` notifies nothing per message. Counts, mentions and senders are
+//!   tallied per channel instead and go out as one [`DigestEvent`] at the
+//!   configured local time of day
+//!
+//! The tally and the time of the last digest are saved to
+//! [`DIGEST_STATE_FILE`] on every change, so a restart loses nothing. A digest
+//! whose time passed while the process was down goes out on the first poll
+//! after startup, covering everything since the previous one; days missed in
+//! between do not produce a digest each.
+//!
+//! A [`WebhookSink`] forwards notifications and digests, each as one JSON
+//! payload.
+
+use crate::config::{ConfigError, NotificationConfig, NotificationMode};
+use crate::core_mvp::channel_manager::Identity;
+use crate::core_mvp::errors::{MvpError, MvpResult};
+use crate::core_mvp::events::{ChannelEvent, ChannelEventKind};
+use crate::core_store::model::types::{ChannelId, MessageId, Timestamp, UserId};
+use chrono::{DateTime, Local, NaiveTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// File in the data directory the digest tally is kept in
+pub const DIGEST_STATE_FILE: &str = "notification_digest.json";
+
+/// Senders listed per channel in a digest
+const TOP_SENDERS: usize = 3;
+
+/// Characters of the message body shown in a notification
+const PREVIEW_CHARS: usize = 80;
+
+/// A message worth notifying
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NotificationEvent {
+    pub channel_id: ChannelId,
+    pub message_id: MessageId,
+    pub sender: UserId,
+
+    /// Start of the message body
+    pub preview: String,
+
+    /// Whether the message mentions the local user
+    pub mention: bool,
+
+    pub timestamp: Timestamp,
+}
+
+impl fmt::Display for NotificationEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mention = if self.mention { " (mentions you)" } else { "" };
+        write!(f, "{} in {}{}: {}", self.sender, self.channel_id, mention, self.preview)
+    }
+}
+
+/// Activity of one channel since the last digest
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChannelDigest {
+    pub channel_id: ChannelId,
+    pub messages: u64,
+    pub mentions: u64,
+
+    /// Most active senders with their message counts, busiest first
+    pub top_senders: Vec<(UserId, u64)>,
+}
+
+/// Summary of the digest channels, sent on the digest schedule
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DigestEvent {
+    /// Time of the previous digest
+    pub since: Timestamp,
+
+    /// Time this digest was made
+    pub until: Timestamp,
+
+    /// Channels with activity, by channel ID
+    pub channels: Vec<ChannelDigest>,
+}
+
+impl fmt::Display for DigestEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let since = local_time(self.since);
+        write!(f, "Digest of {} channel(s) since {}", self.channels.len(), since)?;
+        for channel in &self.channels {
+            let senders: Vec<String> = channel
+                .top_senders
+                .iter()
+                .map(|(sender, count)| format!("{} ({})", sender, count))
+                .collect();
+            write!(
+                f,
+                "\n  {}: {} message(s), {} mention(s); most from {}",
+                channel.channel_id,
+                channel.messages,
+                channel.mentions,
+                senders.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// What goes to the webhook sink
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Notification {
+    Message(NotificationEvent),
+    Digest(DigestEvent),
+}
+
+/// Tally of one digest channel
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct ChannelTally {
+    messages: u64,
+    mentions: u64,
+    senders: BTreeMap<UserId, u64>,
+}
+
+/// Saved accumulation state
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct DigestState {
+    /// When the last digest went out (or the tally started)
+    last_digest: Timestamp,
+
+    /// Tally per channel ID
+    channels: BTreeMap<String, ChannelTally>,
+}
+
+impl DigestState {
+    /// Write the state, replacing the previous one atomically
+    fn save(&self, path: &Path) -> std::io::Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(tmp, path)
+    }
+}
+
+/// Applies the notification policy to channel events
+pub struct NotificationRules {
+    config: NotificationConfig,
+    digest_at: NaiveTime,
+
+    /// Lowercase `@name`s that mention the local user
+    mentions: Vec<String>,
+
+    state: DigestState,
+
+    /// Where the state is saved, if anywhere
+    path: Option<PathBuf>,
+}
+
+impl NotificationRules {
+    /// Rules for `identity`, with an in-memory tally starting at `now`
+    pub fn new(
+        config: NotificationConfig,
+        identity: &Identity,
+        now: Timestamp,
+    ) -> Result<Self, ConfigError> {
+        let mut mentions = vec![format!("@{}", identity.user_id.0.to_lowercase())];
+        if !identity.display_name.is_empty() {
+            mentions.push(format!("@{}", identity.display_name.to_lowercase()));
+        }
+
+        Ok(Self {
+            digest_at: config.digest_time()?,
+            config,
+            mentions,
+            state: DigestState { last_digest: now, channels: BTreeMap::new() },
+            path: None,
+        })
+    }
+
+    /// Rules whose tally is saved at `path`, picking up a saved tally if there is one
+    pub fn open(
+        config: NotificationConfig,
+        identity: &Identity,
+        path: &Path,
+        now: Timestamp,
+    ) -> MvpResult<Self> {
+        let mut rules =
+            Self::new(config, identity, now).map_err(|e| MvpError::Config(e.to_string()))?;
+        if path.exists() {
+            let json = std::fs::read(path).map_err(|e| MvpError::Store(e.to_string()))?;
+            rules.state = serde_json::from_slice(&json)
+                .map_err(|e| MvpError::SerializationError(e.to_string()))?;
+        }
+        rules.path = Some(path.to_path_buf());
+        rules.save();
+        Ok(rules)
+    }
+
+    /// Notification for an event, if its channel's mode calls for one
+    ///
+    /// Messages in digest channels are tallied instead.
+    pub fn handle(&mut self, event: &ChannelEvent) -> Option<NotificationEvent> {
+        let ChannelEventKind::MessageReceived(message) = &event.kind else {
+            return None;
+        };
+        let body = String::from_utf8_lossy(&message.body);
+        let mention = self.mentions(&body);
+
+        match self.config.mode_for(&event.channel_id.0) {
+            NotificationMode::Mute => return None,
+            NotificationMode::MentionsOnly if !mention => return None,
+            NotificationMode::All | NotificationMode::MentionsOnly => {}
+            NotificationMode::Digest => {
+                let tally = self.state.channels.entry(event.channel_id.0.clone()).or_default();
+                tally.messages += 1;
+                tally.mentions += u64::from(mention);
+                *tally.senders.entry(message.sender.clone()).or_default() += 1;
+                self.save();
+                return None;
+            }
+        }
+
+        Some(NotificationEvent {
+            channel_id: event.channel_id.clone(),
+            message_id: message.message_id.clone(),
+            sender: message.sender.clone(),
+            preview: body.chars().take(PREVIEW_CHARS).collect(),
+            mention,
+            timestamp: message.timestamp,
+        })
+    }
+
+    /// When the next digest is due
+    pub fn next_digest(&self) -> Timestamp {
+        next_digest_after(self.state.last_digest, self.digest_at)
+    }
+
+    /// The digest, if it is due at `now`
+    ///
+    /// A due digest resets the tally even when there was nothing to report,
+    /// in which case no event is returned.
+    pub fn poll_digest(&mut self, now: Timestamp) -> Option<DigestEvent> {
+        if now < self.next_digest() {
+            return None;
+        }
+
+        let since = std::mem::replace(&mut self.state.last_digest, now);
+        let tallies = std::mem::take(&mut self.state.channels);
+        self.save();
+        if tallies.is_empty() {
+            return None;
+        }
+
+        let channels = tallies
+            .into_iter()
+            .map(|(channel_id, tally)| {
+                let mut top_senders: Vec<(UserId, u64)> = tally.senders.into_iter().collect();
+                top_senders.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+                top_senders.truncate(TOP_SENDERS);
+                ChannelDigest {
+                    channel_id: ChannelId(channel_id),
+                    messages: tally.messages,
+                    mentions: tally.mentions,
+                    top_senders,
+                }
+            })
+            .collect();
+        Some(DigestEvent { since, until: now, channels })
+    }
+
+    fn mentions(&self, body: &str) -> bool {
+        let body = body.to_lowercase();
+        self.mentions.iter().any(|mention| body.contains(mention.as_str()))
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        if let Err(e) = self.state.save(path) {
+            warn!("Failed to save notification digest state to {:?}: {}", path, e);
+        }
+    }
+}
+
+/// First time `at` on the local clock strictly after `after`
+fn next_digest_after(after: Timestamp, at: NaiveTime) -> Timestamp {
+    let after_local = local_time(after);
+    let mut date = after_local.date_naive();
+    loop {
+        // A time skipped by a DST change falls on the next day
+        if let Some(candidate) = Local.from_local_datetime(&date.and_time(at)).earliest() {
+            if candidate > after_local {
+                return Timestamp::from_millis(candidate.timestamp_millis() as u64);
+            }
+        }
+        date = date.succ_opt().expect("date out of range");
+    }
+}
+
+fn local_time(timestamp: Timestamp) -> DateTime<Local> {
+    DateTime::from_timestamp_millis(timestamp.as_millis() as i64)
+        .unwrap_or_default()
+        .with_timezone(&Local)
+}
+
+/// Forwards notifications to a webhook as JSON
+#[derive(Clone)]
+pub struct WebhookSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookSink {
+    /// Sink POSTing to `url`
+    pub fn new(url: impl Into<String>) -> Self {
+        Self { client: reqwest::Client::new(), url: url.into() }
+    }
+
+    /// POST one notification or digest
+    pub async fn forward(&self, notification: &Notification) -> MvpResult<()> {
+        let body = serde_json::to_vec(notification)
+            .map_err(|e| MvpError::SerializationError(e.to_string()))?;
+        let response = self
+            .client
+            .post(&self.url)
+            .header("Content-Type", "application/json")
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| MvpError::NetworkError(format!("Webhook request failed: {}", e)))?;
+
+        if !response.status().is_success() {
+            return Err(MvpError::NetworkError(format!("Webhook answered {}", response.status())));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core_mvp::types::ChatMessage;
+    use chrono::NaiveDate;
+    use std::sync::{Arc, Mutex};
+
+    const DIGEST: &str = "digest-channel";
+    const LOUD: &str = "loud-channel";
+    const QUIET: &str = "quiet-channel";
+    const MUTED: &str = "muted-channel";
+
+    fn config() -> NotificationConfig {
+        let mut config = NotificationConfig::default();
+        config.channels.insert(DIGEST.to_string(), NotificationMode::Digest);
+        config.channels.insert(QUIET.to_string(), NotificationMode::MentionsOnly);
+        config.channels.insert(MUTED.to_string(), NotificationMode::Mute);
+        config
+    }
+
+    fn identity() -> Identity {
+        Identity::new(UserId("carol-id".to_string()), "Carol".to_string(), "node".to_string())
+    }
+
+    /// Local time on a day of June 2026
+    fn at(day: u32, hour: u32, minute: u32) -> Timestamp {
+        let naive = NaiveDate::from_ymd_opt(2026, 6, day).unwrap().and_hms_opt(hour, minute, 0);
+        let local = Local.from_local_datetime(&naive.unwrap()).earliest().unwrap();
+        Timestamp::from_millis(local.timestamp_millis() as u64)
+    }
+
+    fn message(channel: &str, sender: &str, body: &str) -> ChannelEvent {
+        let message = ChatMessage::new(
+            ChannelId(channel.to_string()),
+            UserId(sender.to_string()),
+            body.as_bytes().to_vec(),
+        );
+        ChannelEvent {
+            channel_id: ChannelId(channel.to_string()),
+            epoch: 1,
+            seq: 0,
+            actor: Some(UserId(sender.to_string())),
+            timestamp: Timestamp::now(),
+            kind: ChannelEventKind::MessageReceived(message),
+        }
+    }
+
+    #[test]
+    fn test_digest_accumulates_counts_and_top_senders() {
+        let mut rules = NotificationRules::new(config(), &identity(), at(1, 12, 0)).unwrap();
+
+        for (sender, body) in [
+            ("alice", "one"),
+            ("bob", "two"),
+            ("alice", "three, @carol"),
+            ("dave", "four"),
+            ("alice", "five"),
+            ("bob", "six"),
+            ("erin", "seven"),
+        ] {
+            assert!(rules.handle(&message(DIGEST, sender, body)).is_none());
+        }
+
+        // Not due before the digest time, due once it passes
+        assert_eq!(rules.next_digest(), at(2, 9, 0));
+        assert!(rules.poll_digest(at(2, 8, 59)).is_none());
+        let digest = rules.poll_digest(at(2, 9, 0)).expect("digest due");
+
+        assert_eq!(digest.since, at(1, 12, 0));
+        assert_eq!(digest.until, at(2, 9, 0));
+        assert_eq!(digest.channels.len(), 1);
+        let channel = &digest.channels[0];
+        assert_eq!(channel.channel_id.0, DIGEST);
+        assert_eq!(channel.messages, 7);
+        assert_eq!(channel.mentions, 1);
+        assert_eq!(
+            channel.top_senders,
+            vec![
+                (UserId("alice".to_string()), 3),
+                (UserId("bob".to_string()), 2),
+                (UserId("dave".to_string()), 1),
+            ]
+        );
+
+        // The tally starts over
+        assert!(rules.poll_digest(at(3, 9, 0)).is_none());
+        assert_eq!(rules.next_digest(), at(4, 9, 0));
+    }
+
+    #[test]
+    fn test_modes_decide_what_is_notified() {
+        let mut rules = NotificationRules::new(config(), &identity(), at(1, 12, 0)).unwrap();
+
+        let loud = rules.handle(&message(LOUD, "alice", "hello")).expect("all mode notifies");
+        assert!(!loud.mention);
+        assert_eq!(loud.preview, "hello");
+
+        // Mentions-only channels notify mentions and tally nothing
+        assert!(rules.handle(&message(QUIET, "alice", "hello")).is_none());
+        let mention = rules.handle(&message(QUIET, "alice", "hi @Carol")).expect("mention");
+        assert!(mention.mention);
+        assert!(rules.handle(&message(QUIET, "alice", "ping @carol-id")).is_some());
+
+        // Muted channels notify nothing, mentions included, and tally nothing
+        assert!(rules.handle(&message(MUTED, "alice", "hi @carol")).is_none());
+
+        // Events other than messages are ignored
+        let mut joined = message(LOUD, "alice", "");
+        joined.kind = ChannelEventKind::MemberJoined(UserId("alice".to_string()));
+        assert!(rules.handle(&joined).is_none());
+
+        assert!(rules.poll_digest(at(2, 9, 0)).is_none());
+    }
+
+    #[test]
+    fn test_overdue_digest_goes_out_on_startup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(DIGEST_STATE_FILE);
+
+        {
+            let mut rules =
+                NotificationRules::open(config(), &identity(), &path, at(1, 12, 0)).unwrap();
+            rules.handle(&message(DIGEST, "alice", "before the restart"));
+            rules.handle(&message(DIGEST, "bob", "also before"));
+        }
+
+        // Down over two digest times; the tally survived and one digest covers both days
+        let mut rules =
+            NotificationRules::open(config(), &identity(), &path, at(3, 18, 0)).unwrap();
+        rules.handle(&message(DIGEST, "alice", "after the restart"));
+        let digest = rules.poll_digest(at(3, 18, 0)).expect("overdue digest");
+        assert_eq!(digest.since, at(1, 12, 0));
+        assert_eq!(digest.channels[0].messages, 3);
+        assert!(rules.poll_digest(at(3, 18, 1)).is_none());
+
+        // Sending it was saved too
+        let rules = NotificationRules::open(config(), &identity(), &path, at(3, 19, 0)).unwrap();
+        assert_eq!(rules.next_digest(), at(4, 9, 0));
+    }
+
+    #[tokio::test]
+    async fn test_webhook_sink_posts_one_payload() {
+        use axum::{routing::post, Json, Router};
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink_received = received.clone();
+        let app = Router::new().route(
+            "/hook",
+            post(move |Json(body): Json<serde_json::Value>| {
+                let received = sink_received.clone();
+                async move { received.lock().unwrap().push(body) }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let mut rules = NotificationRules::new(config(), &identity(), at(1, 12, 0)).unwrap();
+        rules.handle(&message(DIGEST, "alice", "one"));
+        rules.handle(&message(DIGEST, "bob", "two"));
+        let digest = rules.poll_digest(at(2, 9, 0)).unwrap();
+
+        let sink = WebhookSink::new(format!("http://{}/hook", addr));
+        sink.forward(&Notification::Digest(digest.clone())).await.unwrap();
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0]["type"], "digest");
+        let forwarded: Notification = serde_json::from_value(received[0].clone()).unwrap();
+        assert_eq!(forwarded, Notification::Digest(digest));
+    }
+}
@@ -0,0 +1,194 @@
+//! Self-contained invites for joining without a network
+//!
+//! A regular invite only carries the Welcome; everything else a new member
+//! needs (key packages, the channel listing, queued Welcomes and commits)
+//! is fetched from the DHT. An [`OfflineInvite`] bundles all of it into one
+//! file that can be carried over on removable media: the Welcome, a full
+//! snapshot of the ratchet tree, the channel's broadcast policy, the
+//! inviter's identity and the addresses to try once a network is
+//! available.
+//!
+//! The file is sealed with a passphrase (Argon2id, then AES-256-GCM), which
+//! has to reach the invitee separately. Its size grows with the group: the
+//! ratchet tree has a node per member, so bundles for large channels can
+//! run to megabytes; see [`OfflineInvite::size_warning`].
+//!
+//! Joining from a bundle
+//! ([`ChannelManager::join_offline`](crate::core_mvp::ChannelManager::join_offline))
+//! never touches the network. The steps that need it are recorded in the
+//! store's outbox and run by
+//! [`ChannelManager::resume_online`](crate::core_mvp::ChannelManager::resume_online).
+
+use crate::core_mvp::broadcast::BroadcastPolicy;
+use crate::core_mvp::channel_manager::Identity;
+use crate::core_mvp::errors::{MvpError, MvpResult};
+use crate::core_mvp::types::{ChatMessage, InviteToken};
+use crate::core_store::model::types::ChannelId;
+use crate::core_store::store::encryption::EncryptionManager;
+use serde::{Deserialize, Serialize};
+
+/// Leading bytes of a sealed bundle
+const MAGIC: &[u8; 4] = b"SPOI";
+
+/// Current bundle format
+const FORMAT_VERSION: u8 = 1;
+
+/// Argon2id salt length
+const SALT_LEN: usize = 16;
+
+/// Sealed size above which a bundle is reported as large (1 MiB)
+pub const LARGE_BUNDLE_BYTES: usize = 1024 * 1024;
+
+/// Group size above which a bundle is reported as large
+pub const LARGE_GROUP_MEMBERS: usize = 256;
+
+/// Everything needed to join a channel without a network
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OfflineInvite {
+    /// Invite with the Welcome and the full ratchet tree
+    pub invite: InviteToken,
+
+    /// Identity of the member who issued the invite
+    pub inviter: Identity,
+
+    /// Channel's broadcast policy, if it is in broadcast mode
+    pub broadcast_policy: Option<BroadcastPolicy>,
+
+    /// Members in the group when the invite was issued
+    pub member_count: usize,
+
+    /// Addresses to try first once a network is available
+    pub bootstrap_peers: Vec<String>,
+}
+
+impl OfflineInvite {
+    /// Encrypt the bundle under a passphrase
+    ///
+    /// Layout: magic, version, salt, then the AES-GCM sealed bincode body.
+    pub fn seal(&self, passphrase: &str) -> MvpResult<Vec<u8>> {
+        let body = bincode::serialize(self)
+            .map_err(|e| MvpError::SerializationError(format!("Failed to serialize: {}", e)))?;
+
+        let salt = rand::random::<[u8; SALT_LEN]>();
+        let sealed = EncryptionManager::from_passphrase_with_salt(passphrase, &salt)
+            .and_then(|cipher| cipher.encrypt(&body))
+            .map_err(|e| MvpError::Internal(format!("Failed to seal offline invite: {}", e)))?;
+
+        let mut bytes = Vec::with_capacity(MAGIC.len() + 1 + SALT_LEN + sealed.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(FORMAT_VERSION);
+        bytes.extend_from_slice(&salt);
+        bytes.extend_from_slice(&sealed);
+        Ok(bytes)
+    }
+
+    /// Decrypt a sealed bundle
+    ///
+    /// # Errors
+    ///
+    /// `InvalidInvite` if the data isn't an offline invite, uses an unknown
+    /// format version, or the passphrase is wrong
+    pub fn open(bytes: &[u8], passphrase: &str) -> MvpResult<Self> {
+        let header_len = MAGIC.len() + 1 + SALT_LEN;
+        if bytes.len() < header_len || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(MvpError::InvalidInvite("Not an offline invite".to_string()));
+        }
+
+        let version = bytes[MAGIC.len()];
+        if version != FORMAT_VERSION {
+            return Err(MvpError::InvalidInvite(format!(
+                "Unsupported offline invite version {}",
+                version
+            )));
+        }
+
+        let salt = &bytes[MAGIC.len() + 1..header_len];
+        let body = EncryptionManager::from_passphrase_with_salt(passphrase, salt)
+            .and_then(|cipher| cipher.decrypt(&bytes[header_len..]))
+            .map_err(|_| {
+                MvpError::InvalidInvite("Wrong passphrase or corrupted offline invite".to_string())
+            })?;
+
+        bincode::deserialize(&body)
+            .map_err(|e| MvpError::SerializationError(format!("Failed to deserialize: {}", e)))
+    }
+
+    /// Warning to show for a bundle of `sealed_len` bytes, if it is large
+    pub fn size_warning(&self, sealed_len: usize) -> Option<String> {
+        if sealed_len < LARGE_BUNDLE_BYTES && self.member_count < LARGE_GROUP_MEMBERS {
+            return None;
+        }
+
+        Some(format!(
+            "Offline invite is {} KiB for a group of {} members; large bundles are slow to \
+             copy and the group may move several epochs ahead before it is used",
+            sealed_len.div_ceil(1024),
+            self.member_count
+        ))
+    }
+}
+
+/// What [`ChannelManager::resume_online`](crate::core_mvp::ChannelManager::resume_online)
+/// got done
+#[derive(Debug, Clone, Default)]
+pub struct ResumeReport {
+    /// Channels whose deferred join steps have now run
+    pub joins_completed: Vec<ChannelId>,
+
+    /// Queued messages handed to the network
+    pub delivered: usize,
+
+    /// Messages still waiting in the outbox
+    pub still_queued: usize,
+
+    /// Messages received while catching up
+    pub received: Vec<ChatMessage>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core_store::model::types::UserId;
+
+    fn bundle(member_count: usize) -> OfflineInvite {
+        let inviter = UserId("alice@spacepanda.local".to_string());
+        OfflineInvite {
+            invite: InviteToken::new(
+                ChannelId("general".to_string()),
+                vec![1; 64],
+                Some(vec![2; 64]),
+                "general".to_string(),
+                false,
+                inviter.clone(),
+            ),
+            inviter: Identity::new(inviter, "alice".to_string(), "node-alice".to_string()),
+            broadcast_policy: None,
+            member_count,
+            bootstrap_peers: vec!["192.0.2.1:7000".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        let bundle = bundle(2);
+        let sealed = bundle.seal("correct horse").unwrap();
+
+        let opened = OfflineInvite::open(&sealed, "correct horse").unwrap();
+        assert_eq!(opened.invite.invite_id, bundle.invite.invite_id);
+        assert_eq!(opened.invite.ratchet_tree, bundle.invite.ratchet_tree);
+        assert_eq!(opened.bootstrap_peers, bundle.bootstrap_peers);
+
+        assert!(matches!(
+            OfflineInvite::open(&sealed, "battery staple"),
+            Err(MvpError::InvalidInvite(_))
+        ));
+        assert!(matches!(OfflineInvite::open(b"SPOI", "x"), Err(MvpError::InvalidInvite(_))));
+    }
+
+    #[test]
+    fn test_size_warning() {
+        assert!(bundle(2).size_warning(4096).is_none());
+        assert!(bundle(2).size_warning(LARGE_BUNDLE_BYTES).is_some());
+        assert!(bundle(LARGE_GROUP_MEMBERS).size_warning(4096).is_some());
+    }
+}
@@ -0,0 +1,235 @@
+//! Per-channel privacy reports
+//!
+//! What metadata a channel exposes is decided in several places: the channel
+//! directory (is it listed), the padding configuration (how much message
+//! lengths leak), the transport (does it name senders), and the channel's
+//! broadcast policy (how long removed readers keep reading). The
+//! [`PrivacySettings`] trait is a read-only view over those subsystems, and
+//! [`PrivacyReport::evaluate`] turns any such view into the same report, so
+//! every front end scores a channel the same way.
+
+use crate::config::Config;
+use crate::core_mvp::broadcast::BroadcastPolicy;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+/// Smallest padding bucket that hides the length of typical short messages
+pub const RECOMMENDED_PADDING_BUCKET: usize = 1024;
+
+/// Who can see a channel's member list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MemberListVisibility {
+    /// Only the channel's members, who share its MLS ratchet tree
+    Members,
+
+    /// Everyone in the space the channel belongs to
+    SpaceMembers,
+}
+
+impl fmt::Display for MemberListVisibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Members => write!(f, "channel members"),
+            Self::SpaceMembers => write!(f, "space members"),
+        }
+    }
+}
+
+/// Coarse reading of a [`PrivacyReport`] score
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PrivacyRating {
+    /// Little metadata exposed beyond what membership implies
+    Strong,
+
+    /// Some metadata exposed; see the recommendations
+    Moderate,
+
+    /// Significant metadata exposed
+    Weak,
+}
+
+impl PrivacyRating {
+    fn from_score(score: u8) -> Self {
+        match score {
+            80.. => Self::Strong,
+            50.. => Self::Moderate,
+            _ => Self::Weak,
+        }
+    }
+}
+
+impl fmt::Display for PrivacyRating {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Strong => write!(f, "strong"),
+            Self::Moderate => write!(f, "moderate"),
+            Self::Weak => write!(f, "weak"),
+        }
+    }
+}
+
+/// Read-only view of the settings that decide a channel's metadata exposure
+pub trait PrivacySettings {
+    /// Whether the channel can be found without an invite
+    fn discoverable(&self) -> bool;
+
+    /// Whether senders are hidden from the peers relaying their messages
+    fn sealed_sender(&self) -> bool;
+
+    /// Smallest size messages are padded to, or `None` if they aren't padded
+    fn padding_bucket(&self) -> Option<usize>;
+
+    /// Whether online presence is announced to other members
+    fn presence(&self) -> bool;
+
+    /// How long messages are kept, or `None` if until the channel is left
+    fn retention(&self) -> Option<Duration>;
+
+    /// Who can see the member list
+    fn member_list(&self) -> MemberListVisibility;
+
+    /// How often reader removals are committed, for broadcast channels
+    fn membership_batch_interval(&self) -> Option<Duration>;
+}
+
+/// What metadata a channel exposes, with a score and recommendations
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrivacyReport {
+    pub channel_id: String,
+    pub discoverable: bool,
+    pub sealed_sender: bool,
+    pub padding_bucket: Option<usize>,
+    pub presence: bool,
+    pub retention: Option<Duration>,
+    pub member_list: MemberListVisibility,
+    pub membership_batch_interval: Option<Duration>,
+
+    /// 0 (exposes the most) to 100
+    pub score: u8,
+    pub rating: PrivacyRating,
+
+    /// What to change, or be aware of, most significant first
+    pub recommendations: Vec<String>,
+}
+
+impl PrivacyReport {
+    /// Score a channel's settings and collect recommendations
+    pub fn evaluate(channel_id: impl Into<String>, settings: &dyn PrivacySettings) -> Self {
+        let mut penalty = 0u8;
+        let mut recommendations = Vec::new();
+        let mut flag = |points: u8, recommendation: String| {
+            penalty += points;
+            recommendations.push(recommendation);
+        };
+
+        let discoverable = settings.discoverable();
+        if discoverable {
+            flag(25, "This channel is publicly discoverable".to_string());
+        }
+
+        let padding_bucket = settings.padding_bucket();
+        match padding_bucket {
+            None => flag(20, "Messages are not padded; their lengths are visible".to_string()),
+            Some(bucket) if bucket < RECOMMENDED_PADDING_BUCKET => flag(
+                10,
+                format!(
+                    "Enable padding bucket ≥{} (currently {})",
+                    RECOMMENDED_PADDING_BUCKET, bucket
+                ),
+            ),
+            Some(_) => {}
+        }
+
+        let sealed_sender = settings.sealed_sender();
+        if !sealed_sender {
+            flag(15, "Peers relaying messages can see who sent them".to_string());
+        }
+
+        let member_list = settings.member_list();
+        if member_list == MemberListVisibility::SpaceMembers {
+            flag(10, "Everyone in the space can see who is in this channel".to_string());
+        }
+
+        let presence = settings.presence();
+        if presence {
+            flag(10, "Turn off presence to stop announcing when you are online".to_string());
+        }
+
+        let membership_batch_interval = settings.membership_batch_interval();
+        if let Some(interval) = membership_batch_interval {
+            flag(
+                5,
+                format!(
+                    "Removed readers can keep reading for up to {}s (broadcast batching)",
+                    interval.as_secs()
+                ),
+            );
+        }
+
+        let retention = settings.retention();
+        if retention.is_none() {
+            flag(5, "Messages are kept until you leave the channel".to_string());
+        }
+
+        let score = 100u8.saturating_sub(penalty);
+        Self {
+            channel_id: channel_id.into(),
+            discoverable,
+            sealed_sender,
+            padding_bucket,
+            presence,
+            retention,
+            member_list,
+            membership_batch_interval,
+            score,
+            rating: PrivacyRating::from_score(score),
+            recommendations,
+        }
+    }
+}
+
+/// Privacy settings of a channel held by a [`ChannelManager`](crate::core_mvp::ChannelManager)
+pub(crate) struct ChannelPrivacy<'a> {
+    /// Local configuration (padding)
+    pub config: &'a Config,
+
+    /// Whether the channel directory holds a live listing for the channel
+    pub listed: bool,
+
+    /// The channel's broadcast policy, if it is in broadcast mode
+    pub broadcast: Option<BroadcastPolicy>,
+}
+
+impl PrivacySettings for ChannelPrivacy<'_> {
+    fn discoverable(&self) -> bool {
+        self.listed
+    }
+
+    fn sealed_sender(&self) -> bool {
+        // The network envelope names the sender so receivers can attribute
+        // messages before decrypting them
+        false
+    }
+
+    fn padding_bucket(&self) -> Option<usize> {
+        // Every message sent through the manager is padded
+        Some(self.config.privacy.padding_bucket)
+    }
+
+    fn presence(&self) -> bool {
+        false
+    }
+
+    fn retention(&self) -> Option<Duration> {
+        None
+    }
+
+    fn member_list(&self) -> MemberListVisibility {
+        MemberListVisibility::Members
+    }
+
+    fn membership_batch_interval(&self) -> Option<Duration> {
+        self.broadcast.as_ref().map(|policy| policy.membership_batch_interval)
+    }
+}
@@ -0,0 +1,80 @@
+//! Public channels' GroupInfo in the DHT
+//!
+//! A public channel publishes its [`GroupPublicInfo`] here, including the
+//! signed MLS GroupInfo for the current epoch, so anyone can join it by
+//! external commit without an invite (see
+//! [`ChannelManager::join_public_channel`](crate::core_mvp::ChannelManager::join_public_channel)).
+//! Private channels are never published. Whoever commits republishes the
+//! record, as the GroupInfo of an old epoch can't be joined from.
+
+use crate::core_dht::private_lookup::PUBLIC_GROUP_NAMESPACE;
+use crate::core_dht::{DhtKey, DhtStorage, DhtValue};
+use crate::core_mls::discovery::GroupPublicInfo;
+use crate::core_mvp::errors::{MvpError, MvpResult};
+use crate::core_store::model::types::{ChannelId, Timestamp};
+use std::time::Duration;
+
+/// How long a published record stays in the DHT
+pub const PUBLIC_GROUP_TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Directory of public channels' GroupInfo stored in the DHT
+pub struct PublicGroupDirectory {
+    storage: DhtStorage,
+}
+
+impl PublicGroupDirectory {
+    /// Create a directory backed by the given DHT storage
+    pub fn new(storage: DhtStorage) -> Self {
+        Self { storage }
+    }
+
+    fn key(channel_id: &ChannelId) -> DhtKey {
+        DhtKey::hash(format!("{}:{}", PUBLIC_GROUP_NAMESPACE, channel_id.0).as_bytes())
+    }
+
+    /// Published info for a channel, if it is public
+    pub fn lookup(&self, channel_id: &ChannelId) -> MvpResult<Option<GroupPublicInfo>> {
+        let Ok(value) = self.storage.get(&Self::key(channel_id)) else {
+            return Ok(None);
+        };
+        let json = std::str::from_utf8(&value.data)
+            .map_err(|e| MvpError::SerializationError(format!("Failed to deserialize: {}", e)))?;
+        let info = GroupPublicInfo::from_json(json)
+            .map_err(|e| MvpError::SerializationError(format!("Failed to deserialize: {}", e)))?;
+        Ok(Some(info))
+    }
+
+    /// Publish a channel's info
+    ///
+    /// Returns false, leaving the record alone, if it already holds a
+    /// later epoch.
+    pub fn publish(&self, channel_id: &ChannelId, info: &GroupPublicInfo) -> MvpResult<bool> {
+        if let Some(current) = self.lookup(channel_id)? {
+            if current.epoch > info.epoch {
+                return Ok(false);
+            }
+        }
+
+        let key = Self::key(channel_id);
+        let json = info
+            .to_json()
+            .map_err(|e| MvpError::SerializationError(format!("Failed to serialize: {}", e)))?;
+
+        // Sequence by write time so the latest record wins on every replica
+        let previous = self.storage.get(&key).ok().map(|value| value.sequence);
+        let sequence = Timestamp::now().as_millis().max(previous.map_or(0, |seq| seq + 1));
+        let value = DhtValue::new(json.into_bytes())
+            .with_ttl_duration(PUBLIC_GROUP_TTL)
+            .with_sequence(sequence);
+
+        match self.storage.put(key, value.clone()) {
+            // An expired entry written in the same millisecond; replace it
+            Err(_) if previous.is_none() => {
+                let _ = self.storage.delete(&key);
+                self.storage.put(key, value).map_err(MvpError::Dht)?;
+            }
+            result => result.map_err(MvpError::Dht)?,
+        }
+        Ok(true)
+    }
+}
@@ -0,0 +1,207 @@
+//! Emoji reactions to messages
+//!
+//! Reacting or unreacting sends a small [`ReactionUpdate`] to the channel.
+//! Each member applies it to the message's reaction set in the store, an
+//! OR-Set of (user, emoji) pairs: an unreact removes only the adds its
+//! sender had seen, so a user reacting and unreacting on two devices at
+//! once converges the same way everywhere.
+//!
+//! An update can arrive before the message it refers to. It is then parked
+//! in [`ParkedReactions`] for up to [`REACTION_PARK_TTL`], and dropped if
+//! the message hasn't turned up by then.
+
+use crate::core_mvp::errors::{MvpError, MvpResult};
+use crate::core_store::crdt::AddId;
+use crate::core_store::model::types::{ChannelId, MessageId, UserId};
+use crate::core_store::model::Message;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Marker at the start of a reaction update's plaintext
+const REACTION_MAGIC: &[u8; 4] = b"SPRX";
+
+/// How long an update waits for a message that hasn't arrived
+pub const REACTION_PARK_TTL: Duration = Duration::from_secs(300);
+
+/// Updates parked for messages that haven't arrived, across all channels
+pub const MAX_PARKED_REACTIONS: usize = 1024;
+
+/// What a reaction update does
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReactionOp {
+    /// Add the reaction; the ID tells apart adds from different devices
+    React(AddId),
+    /// Remove the adds of the reaction the sender had seen
+    Unreact(Vec<AddId>),
+}
+
+/// A reaction added to or removed from a message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReactionUpdate {
+    /// Channel of the message
+    pub channel_id: ChannelId,
+
+    /// The message reacted to
+    pub target: MessageId,
+
+    /// Member reacting
+    pub user: UserId,
+
+    /// The emoji (e.g., "👍", "❤️", "🎉")
+    pub emoji: String,
+
+    /// Add or remove
+    pub op: ReactionOp,
+}
+
+impl ReactionUpdate {
+    /// Apply the update to a stored message
+    pub fn apply_to(&self, message: &mut Message) -> MvpResult<()> {
+        match &self.op {
+            ReactionOp::React(add_id) => message
+                .add_reaction(self.user.clone(), self.emoji.clone(), add_id.clone())
+                .map_err(|e| MvpError::Store(e.to_string())),
+            ReactionOp::Unreact(add_ids) => {
+                let add_ids: HashSet<AddId> = add_ids.iter().cloned().collect();
+                message.remove_reaction(self.user.clone(), self.emoji.clone(), &add_ids);
+                Ok(())
+            }
+        }
+    }
+
+    /// Plaintext to send to the channel
+    pub fn encode(&self) -> MvpResult<Vec<u8>> {
+        let encoded = bincode::serialize(self)
+            .map_err(|e| MvpError::Serialization(format!("Failed to serialize reaction: {}", e)))?;
+        let mut plaintext = REACTION_MAGIC.to_vec();
+        plaintext.extend_from_slice(&encoded);
+        Ok(plaintext)
+    }
+
+    /// The update in a decrypted plaintext, if it is one
+    pub fn decode(plaintext: &[u8]) -> Option<Self> {
+        bincode::deserialize(plaintext.strip_prefix(REACTION_MAGIC.as_slice())?).ok()
+    }
+}
+
+/// Updates waiting a bounded time for the message they refer to
+///
+/// Holds at most [`MAX_PARKED_REACTIONS`]; the oldest update is dropped to
+/// make room.
+#[derive(Debug)]
+pub struct ParkedReactions {
+    ttl: Duration,
+    updates: VecDeque<(Instant, ReactionUpdate)>,
+}
+
+impl Default for ParkedReactions {
+    fn default() -> Self {
+        Self::new(REACTION_PARK_TTL)
+    }
+}
+
+impl ParkedReactions {
+    /// Park updates for up to `ttl`
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl, updates: VecDeque::new() }
+    }
+
+    /// Park an update until its message arrives
+    pub fn park(&mut self, update: ReactionUpdate) {
+        self.expire();
+        if self.updates.len() >= MAX_PARKED_REACTIONS {
+            self.updates.pop_front();
+        }
+        self.updates.push_back((Instant::now(), update));
+    }
+
+    /// Take the updates parked for a message, oldest first
+    pub fn take(&mut self, message_id: &MessageId) -> Vec<ReactionUpdate> {
+        self.expire();
+        let (taken, kept): (VecDeque<_>, VecDeque<_>) =
+            self.updates.drain(..).partition(|(_, update)| &update.target == message_id);
+        self.updates = kept;
+        taken.into_iter().map(|(_, update)| update).collect()
+    }
+
+    /// Number of updates parked
+    pub fn len(&self) -> usize {
+        self.updates.len()
+    }
+
+    /// Whether no updates are parked
+    pub fn is_empty(&self) -> bool {
+        self.updates.is_empty()
+    }
+
+    /// Drop updates that waited too long
+    fn expire(&mut self) {
+        while self
+            .updates
+            .front()
+            .is_some_and(|(parked_at, _)| parked_at.elapsed() > self.ttl)
+        {
+            self.updates.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn react(target: &MessageId, emoji: &str) -> ReactionUpdate {
+        ReactionUpdate {
+            channel_id: ChannelId("general".to_string()),
+            target: target.clone(),
+            user: UserId("alice".to_string()),
+            emoji: emoji.to_string(),
+            op: ReactionOp::React(AddId::new("node-alice".to_string(), 1)),
+        }
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let update = react(&MessageId::generate(), "👍");
+
+        let plaintext = update.encode().unwrap();
+        assert_eq!(ReactionUpdate::decode(&plaintext), Some(update));
+
+        // Ordinary messages aren't reactions
+        assert_eq!(ReactionUpdate::decode(b"hello"), None);
+        assert_eq!(ReactionUpdate::decode(b"SPRX not really"), None);
+    }
+
+    #[test]
+    fn test_parked_reactions_are_bounded() {
+        let mut parked = ParkedReactions::default();
+        let first = MessageId::generate();
+        parked.park(react(&first, "👍"));
+        parked.park(react(&MessageId::generate(), "🎉"));
+        parked.park(react(&first, "❤️"));
+
+        let emojis: Vec<String> = parked.take(&first).into_iter().map(|u| u.emoji).collect();
+        assert_eq!(emojis, vec!["👍".to_string(), "❤️".to_string()]);
+        assert_eq!(parked.len(), 1);
+
+        let oldest = MessageId::generate();
+        parked.park(react(&oldest, "dropped"));
+        for _ in 0..MAX_PARKED_REACTIONS {
+            parked.park(react(&MessageId::generate(), "kept"));
+        }
+        assert_eq!(parked.len(), MAX_PARKED_REACTIONS);
+        assert!(parked.take(&oldest).is_empty());
+    }
+
+    #[test]
+    fn test_parked_reactions_expire() {
+        let mut parked = ParkedReactions::new(Duration::from_millis(10));
+        let target = MessageId::generate();
+        parked.park(react(&target, "👍"));
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(parked.take(&target).is_empty());
+        assert!(parked.is_empty());
+    }
+}
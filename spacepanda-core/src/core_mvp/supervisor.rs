@@ -0,0 +1,431 @@
+//! Per-channel inbound workers
+//!
+//! Inbound commits and messages are dispatched to one worker per channel,
+//! each draining its own bounded mailbox. Every item runs in a task of its
+//! own, so a panic while handling it unwinds only that task: the panic is
+//! logged with the channel ID and counted, and the worker carries on with
+//! the channel's next item. Durable state (the MLS provider and the store's
+//! inbox) is untouched by the unwind, and other channels never wait on the
+//! crashing one.
+//!
+//! A channel whose worker panics [`SupervisorPolicy::max_panics`] times
+//! within [`SupervisorPolicy::window`] is quarantined: it becomes read-only,
+//! further inbound work for it is parked instead of handled, and a
+//! [`SecurityEvent`] is published.
+
+use crate::core_mvp::network::{IncomingCommit, IncomingMessage};
+use crate::core_store::model::types::ChannelId;
+use async_trait::async_trait;
+use std::any::Any;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{broadcast, mpsc, Mutex, RwLock};
+use tracing::{debug, error, warn};
+
+/// Capacity of the security event broadcast channel
+const EVENT_BROADCAST_CAPACITY: usize = 64;
+
+/// Limits for per-channel workers
+#[derive(Debug, Clone)]
+pub struct SupervisorPolicy {
+    /// Items a channel's mailbox holds before new work is parked
+    pub mailbox_capacity: usize,
+
+    /// Panics within `window` that quarantine a channel
+    pub max_panics: usize,
+
+    /// Period over which panics are counted
+    pub window: Duration,
+}
+
+impl Default for SupervisorPolicy {
+    fn default() -> Self {
+        Self { mailbox_capacity: 256, max_panics: 3, window: Duration::from_secs(60) }
+    }
+}
+
+impl SupervisorPolicy {
+    /// Set the per-channel mailbox capacity
+    pub fn with_mailbox_capacity(mut self, capacity: usize) -> Self {
+        self.mailbox_capacity = capacity.max(1);
+        self
+    }
+
+    /// Quarantine a channel after `max_panics` panics within `window`
+    pub fn with_quarantine_after(mut self, max_panics: usize, window: Duration) -> Self {
+        self.max_panics = max_panics.max(1);
+        self.window = window;
+        self
+    }
+}
+
+/// Security-relevant events raised by the supervisor or at startup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecurityEvent {
+    /// A channel's worker kept panicking and the channel is now read-only
+    ChannelQuarantined { channel_id: ChannelId, panics: usize, window: Duration },
+
+    /// The data directory failed its startup attestation and the operator
+    /// accepted it anyway
+    IntegrityRollbackAccepted { anomalies: Vec<String> },
+
+    /// Another device sent from our own leaf: the identity was copied (e.g.
+    /// by an account export) and the copy is still active
+    IdentityInUseElsewhere { channel_id: ChannelId, epoch: u64 },
+}
+
+impl fmt::Display for SecurityEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecurityEvent::ChannelQuarantined { channel_id, panics, window } => write!(
+                f,
+                "channel {} quarantined after {} worker panics within {:?}",
+                channel_id, panics, window
+            ),
+            SecurityEvent::IntegrityRollbackAccepted { anomalies } => write!(
+                f,
+                "data directory rollback accepted despite integrity anomalies: {}",
+                anomalies.join("; ")
+            ),
+            SecurityEvent::IdentityInUseElsewhere { channel_id, epoch } => write!(
+                f,
+                "another device is using this identity: channel {} received a message from our own leaf in epoch {}",
+                channel_id, epoch
+            ),
+        }
+    }
+}
+
+/// One item of inbound work for a channel
+#[derive(Debug)]
+pub enum InboundWork {
+    Commit(IncomingCommit),
+    Message(IncomingMessage),
+}
+
+impl InboundWork {
+    /// Channel this work belongs to
+    pub fn channel_id(&self) -> &ChannelId {
+        match self {
+            InboundWork::Commit(commit) => &commit.channel_id,
+            InboundWork::Message(message) => &message.channel_id,
+        }
+    }
+}
+
+/// Handles inbound work on behalf of the supervisor
+#[async_trait]
+pub trait InboundHandler: Send + Sync + 'static {
+    /// Process one item; may panic
+    async fn handle(&self, work: InboundWork);
+
+    /// Keep work that can't be handled now (full mailbox or quarantine)
+    ///
+    /// The default drops it.
+    async fn park(&self, work: InboundWork) {
+        warn!(channel_id = %work.channel_id(), "Dropping inbound work for channel");
+    }
+}
+
+/// State shared between the supervisor and its workers
+struct Shared {
+    policy: SupervisorPolicy,
+    handler: Arc<dyn InboundHandler>,
+    panics: Mutex<HashMap<ChannelId, VecDeque<Instant>>>,
+    quarantined: RwLock<HashSet<ChannelId>>,
+    events: broadcast::Sender<SecurityEvent>,
+}
+
+impl Shared {
+    /// Record a worker panic, quarantining the channel if it keeps crashing
+    async fn record_panic(&self, channel_id: &ChannelId, payload: Box<dyn Any + Send>) {
+        error!(
+            channel_id = %channel_id,
+            panic = %panic_message(payload.as_ref()),
+            "Channel worker panicked; continuing with the next item"
+        );
+        metrics::counter!("mvp.channel_worker.panics", "channel" => channel_id.0.clone())
+            .increment(1);
+
+        let now = Instant::now();
+        let recent = {
+            let mut panics = self.panics.lock().await;
+            let history = panics.entry(channel_id.clone()).or_default();
+            history.push_back(now);
+            while history.front().is_some_and(|at| now.duration_since(*at) > self.policy.window) {
+                history.pop_front();
+            }
+            history.len()
+        };
+        if recent < self.policy.max_panics {
+            return;
+        }
+
+        if !self.quarantined.write().await.insert(channel_id.clone()) {
+            return;
+        }
+        warn!(channel_id = %channel_id, panics = recent, "Quarantining channel");
+        metrics::counter!("mvp.channel_worker.quarantined", "channel" => channel_id.0.clone())
+            .increment(1);
+        let _ = self.events.send(SecurityEvent::ChannelQuarantined {
+            channel_id: channel_id.clone(),
+            panics: recent,
+            window: self.policy.window,
+        });
+    }
+}
+
+/// Dispatches inbound work to supervised per-channel workers
+pub struct ChannelSupervisor {
+    shared: Arc<Shared>,
+    mailboxes: Mutex<HashMap<ChannelId, mpsc::Sender<InboundWork>>>,
+}
+
+impl ChannelSupervisor {
+    /// Create a supervisor that hands work to `handler`
+    pub fn new(policy: SupervisorPolicy, handler: Arc<dyn InboundHandler>) -> Self {
+        let shared = Shared {
+            policy,
+            handler,
+            panics: Mutex::new(HashMap::new()),
+            quarantined: RwLock::new(HashSet::new()),
+            events: broadcast::channel(EVENT_BROADCAST_CAPACITY).0,
+        };
+        Self { shared: Arc::new(shared), mailboxes: Mutex::new(HashMap::new()) }
+    }
+
+    /// Subscribe to security events
+    pub fn subscribe(&self) -> broadcast::Receiver<SecurityEvent> {
+        self.shared.events.subscribe()
+    }
+
+    /// Publish a security event detected outside the workers
+    pub fn report(&self, event: SecurityEvent) {
+        let _ = self.shared.events.send(event);
+    }
+
+    /// Whether a channel is quarantined
+    pub async fn is_quarantined(&self, channel_id: &ChannelId) -> bool {
+        self.shared.quarantined.read().await.contains(channel_id)
+    }
+
+    /// Channels currently quarantined
+    pub async fn quarantined(&self) -> Vec<ChannelId> {
+        self.shared.quarantined.read().await.iter().cloned().collect()
+    }
+
+    /// Lift a channel's quarantine and forget its panic history
+    ///
+    /// Returns false if the channel wasn't quarantined.
+    pub async fn lift_quarantine(&self, channel_id: &ChannelId) -> bool {
+        self.shared.panics.lock().await.remove(channel_id);
+        self.shared.quarantined.write().await.remove(channel_id)
+    }
+
+    /// Queue work on its channel's worker, starting the worker if needed
+    ///
+    /// Never waits on a busy channel: work for a quarantined channel, or
+    /// beyond a full mailbox, is parked with the handler instead.
+    pub async fn dispatch(&self, work: InboundWork) {
+        let channel_id = work.channel_id().clone();
+        if self.is_quarantined(&channel_id).await {
+            debug!(channel_id = %channel_id, "Parking inbound work for quarantined channel");
+            self.shared.handler.park(work).await;
+            return;
+        }
+
+        let mailbox = self.mailbox(&channel_id).await;
+        if let Err(TrySendError::Full(work) | TrySendError::Closed(work)) = mailbox.try_send(work) {
+            warn!(channel_id = %channel_id, "Channel mailbox full; parking inbound work");
+            metrics::counter!("mvp.channel_worker.overflow", "channel" => channel_id.0.clone())
+                .increment(1);
+            self.shared.handler.park(work).await;
+        }
+    }
+
+    /// Sender for a channel's mailbox
+    async fn mailbox(&self, channel_id: &ChannelId) -> mpsc::Sender<InboundWork> {
+        let mut mailboxes = self.mailboxes.lock().await;
+        if let Some(mailbox) = mailboxes.get(channel_id) {
+            return mailbox.clone();
+        }
+
+        let (tx, rx) = mpsc::channel(self.shared.policy.mailbox_capacity);
+        tokio::spawn(run_worker(self.shared.clone(), channel_id.clone(), rx));
+        mailboxes.insert(channel_id.clone(), tx.clone());
+        tx
+    }
+}
+
+/// Drain one channel's mailbox until the supervisor is dropped
+async fn run_worker(
+    shared: Arc<Shared>,
+    channel_id: ChannelId,
+    mut mailbox: mpsc::Receiver<InboundWork>,
+) {
+    debug!(channel_id = %channel_id, "Started channel worker");
+
+    while let Some(work) = mailbox.recv().await {
+        // Quarantine may have started while this work was queued
+        if shared.quarantined.read().await.contains(&channel_id) {
+            shared.handler.park(work).await;
+            continue;
+        }
+
+        // A task per item, so a panic unwinds only that item
+        let handler = shared.handler.clone();
+        if let Err(e) = tokio::spawn(async move { handler.handle(work).await }).await {
+            if e.is_panic() {
+                shared.record_panic(&channel_id, e.into_panic()).await;
+            }
+        }
+    }
+
+    debug!(channel_id = %channel_id, "Channel worker ended");
+}
+
+/// Text of a panic payload, if it has any
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("<non-string panic>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core_router::PeerId;
+    use crate::core_store::model::types::UserId;
+
+    /// Panics on messages for the "bad" channel, records all others
+    struct FlakyHandler {
+        handled: mpsc::UnboundedSender<(ChannelId, Vec<u8>)>,
+        parked: mpsc::UnboundedSender<ChannelId>,
+    }
+
+    #[async_trait]
+    impl InboundHandler for FlakyHandler {
+        async fn handle(&self, work: InboundWork) {
+            let InboundWork::Message(message) = work else {
+                return;
+            };
+            if message.channel_id.0 == "bad" {
+                panic!("malformed message");
+            }
+            let _ = self.handled.send((message.channel_id, message.ciphertext));
+        }
+
+        async fn park(&self, work: InboundWork) {
+            let _ = self.parked.send(work.channel_id().clone());
+        }
+    }
+
+    type Received<T> = mpsc::UnboundedReceiver<T>;
+
+    fn supervisor(
+        policy: SupervisorPolicy,
+    ) -> (ChannelSupervisor, Received<(ChannelId, Vec<u8>)>, Received<ChannelId>) {
+        let (handled, handled_rx) = mpsc::unbounded_channel();
+        let (parked, parked_rx) = mpsc::unbounded_channel();
+        let handler = Arc::new(FlakyHandler { handled, parked });
+        (ChannelSupervisor::new(policy, handler), handled_rx, parked_rx)
+    }
+
+    fn message(channel: &str, body: &[u8]) -> InboundWork {
+        InboundWork::Message(IncomingMessage {
+            channel_id: ChannelId(channel.to_string()),
+            ciphertext: body.to_vec(),
+            sender_id: UserId("alice@spacepanda.local".to_string()),
+            sender_peer_id: PeerId(vec![1]),
+        })
+    }
+
+    async fn next<T>(rx: &mut Received<T>) -> T {
+        tokio::time::timeout(Duration::from_secs(5), rx.recv()).await.unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_panicking_channel_does_not_stall_others() {
+        let policy =
+            SupervisorPolicy::default().with_quarantine_after(100, Duration::from_secs(60));
+        let (supervisor, mut handled, _parked) = supervisor(policy);
+        let good = ChannelId("good".to_string());
+
+        for i in 0..5u8 {
+            supervisor.dispatch(message("bad", b"boom")).await;
+            supervisor.dispatch(message("good", &[i])).await;
+        }
+
+        // Every good message arrives, in order, despite the panics
+        for i in 0..5u8 {
+            assert_eq!(next(&mut handled).await, (good.clone(), vec![i]));
+        }
+        assert!(!supervisor.is_quarantined(&ChannelId("bad".to_string())).await);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_panics_quarantine_channel() {
+        let policy = SupervisorPolicy::default().with_quarantine_after(3, Duration::from_secs(60));
+        let (supervisor, mut handled, mut parked) = supervisor(policy);
+        let mut events = supervisor.subscribe();
+        let bad = ChannelId("bad".to_string());
+
+        for _ in 0..3 {
+            supervisor.dispatch(message("bad", b"boom")).await;
+        }
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            event,
+            SecurityEvent::ChannelQuarantined {
+                channel_id: bad.clone(),
+                panics: 3,
+                window: Duration::from_secs(60)
+            }
+        );
+        assert_eq!(supervisor.quarantined().await, vec![bad.clone()]);
+
+        // Quarantined work is parked, and other channels still flow
+        supervisor.dispatch(message("bad", b"later")).await;
+        assert_eq!(next(&mut parked).await, bad);
+        supervisor.dispatch(message("good", b"hi")).await;
+        assert_eq!(next(&mut handled).await, (ChannelId("good".to_string()), b"hi".to_vec()));
+
+        assert!(supervisor.lift_quarantine(&bad).await);
+        assert!(!supervisor.lift_quarantine(&bad).await);
+        assert!(!supervisor.is_quarantined(&bad).await);
+    }
+
+    #[tokio::test]
+    async fn test_full_mailbox_parks_instead_of_blocking() {
+        struct Stuck(mpsc::UnboundedSender<ChannelId>);
+
+        #[async_trait]
+        impl InboundHandler for Stuck {
+            async fn handle(&self, _work: InboundWork) {
+                std::future::pending::<()>().await;
+            }
+
+            async fn park(&self, work: InboundWork) {
+                let _ = self.0.send(work.channel_id().clone());
+            }
+        }
+
+        let (parked, mut parked_rx) = mpsc::unbounded_channel();
+        let policy = SupervisorPolicy::default().with_mailbox_capacity(1);
+        let supervisor = ChannelSupervisor::new(policy, Arc::new(Stuck(parked)));
+
+        // One item in flight and one queued; the rest overflow
+        for _ in 0..4 {
+            supervisor.dispatch(message("slow", b"x")).await;
+        }
+        assert_eq!(next(&mut parked_rx).await, ChannelId("slow".to_string()));
+    }
+}
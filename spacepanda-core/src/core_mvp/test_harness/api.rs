@@ -37,6 +37,20 @@ pub fn build_router(state: Arc<AppState>) -> Router {
         .route("/messages/:id/replies", get(handlers::get_thread_replies))
         .route("/messages/:id/context", get(handlers::get_message_with_thread))
         .route("/channels/:id/threads", get(handlers::get_channel_threads))
+        // Webhook routes
+        .route("/channels/:id/webhooks", post(handlers::create_webhook))
+        .route("/channels/:id/webhooks", get(handlers::list_webhooks))
+        .route("/channels/:id/webhooks/:webhook_id", delete(handlers::revoke_webhook))
+        .route("/channels/:id/webhooks/:webhook_id/rotate", post(handlers::rotate_webhook))
+        .route("/webhooks/:id", post(handlers::post_webhook))
+        // Simulated user routes
+        .route("/users/:user/init", post(handlers::init_user))
+        .route("/users/:user/join", post(handlers::user_join_channel))
+        .route("/users/:user/channels/create", post(handlers::user_create_channel))
+        .route("/users/:user/channels/:id/invite", post(handlers::user_create_invite))
+        .route("/users/:user/channels/:id/remove-member", post(handlers::user_remove_member))
+        .route("/users/:user/channels/:id/send", post(handlers::user_send_message))
+        .route("/users/:user/channels/:id/messages", get(handlers::user_get_messages))
         // State
         .with_state(state)
 }
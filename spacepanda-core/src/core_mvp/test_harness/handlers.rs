@@ -2,11 +2,14 @@
 
 use super::state::AppState;
 use super::types::*;
+use crate::core_mvp::errors::MvpError;
 use crate::core_mvp::types::InviteToken;
+use crate::core_mvp::webhooks::WebhookOptions;
 use crate::core_store::model::types::ChannelId;
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -16,6 +19,7 @@ use std::sync::Arc;
 pub enum ApiError {
     Internal(anyhow::Error),
     NotFound(String),
+    Status(StatusCode, String),
 }
 
 impl IntoResponse for ApiError {
@@ -29,16 +33,54 @@ impl IntoResponse for ApiError {
                 let error_response = ErrorResponse { error: msg, details: None };
                 (StatusCode::NOT_FOUND, Json(error_response)).into_response()
             }
+            ApiError::Status(status, msg) => {
+                let error_response = ErrorResponse { error: msg, details: None };
+                (status, Json(error_response)).into_response()
+            }
+        }
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        ApiError::Internal(err)
+    }
+}
+
+impl From<MvpError> for ApiError {
+    fn from(err: MvpError) -> Self {
+        match mvp_status(&err) {
+            StatusCode::INTERNAL_SERVER_ERROR => ApiError::Internal(err.into()),
+            status => ApiError::Status(status, err.to_string()),
         }
     }
 }
 
-impl<E> From<E> for ApiError
-where
-    E: Into<anyhow::Error>,
-{
-    fn from(err: E) -> Self {
-        ApiError::Internal(err.into())
+/// The HTTP status a caller should see for a channel manager error
+fn mvp_status(err: &MvpError) -> StatusCode {
+    match err {
+        MvpError::ChannelNotFound(_)
+        | MvpError::MemberNotFound { .. }
+        | MvpError::MessageNotFound(_)
+        | MvpError::WebhookNotFound(_)
+        | MvpError::DeviceNotFound(_) => StatusCode::NOT_FOUND,
+        MvpError::ChannelExists(_)
+        | MvpError::NoKeyPackages(_)
+        | MvpError::ChannelQuarantined(_)
+        | MvpError::InvalidOperation(_) => StatusCode::CONFLICT,
+        MvpError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+        MvpError::PermissionDenied { .. } => StatusCode::FORBIDDEN,
+        MvpError::InvalidInvite(_)
+        | MvpError::InvalidKeyPackage(_)
+        | MvpError::InvalidMessage(_) => StatusCode::BAD_REQUEST,
+        MvpError::InviteExpired(_) => StatusCode::GONE,
+        MvpError::PayloadTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+        MvpError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+        MvpError::InsufficientStorage { .. } => StatusCode::INSUFFICIENT_STORAGE,
+        MvpError::Dht(_) | MvpError::NetworkError(_) | MvpError::ChunkUnavailable { .. } => {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
     }
 }
 
@@ -487,3 +529,259 @@ pub async fn get_channel_threads(
 
     Ok(Json(GetChannelThreadsResponse { channel_id, threads: threads_http }))
 }
+
+// ============================================================================
+// Webhook Handlers
+// ============================================================================
+
+/// POST /channels/:id/webhooks - Create an inbound webhook
+pub async fn create_webhook(
+    State(state): State<Arc<AppState>>,
+    Path(channel_id): Path<String>,
+    Json(req): Json<WebhookCreateRequest>,
+) -> ApiResult<Json<WebhookCreateResponse>> {
+    let channel_id = ChannelId(channel_id);
+
+    let mut options = WebhookOptions::default();
+    if let Some(max_content_bytes) = req.max_content_bytes {
+        options = options.with_max_content_bytes(max_content_bytes);
+    }
+    if let Some(max_posts_per_minute) = req.max_posts_per_minute {
+        options = options.with_max_posts_per_minute(max_posts_per_minute);
+    }
+
+    let (credentials, commit) = state
+        .channel_manager
+        .create_webhook_with_options(&channel_id, req.name, options)
+        .await?;
+
+    Ok(Json(WebhookCreateResponse {
+        webhook_id: credentials.webhook_id,
+        token: credentials.token,
+        commit,
+    }))
+}
+
+/// GET /channels/:id/webhooks - List a channel's active webhooks
+pub async fn list_webhooks(
+    State(state): State<Arc<AppState>>,
+    Path(channel_id): Path<String>,
+) -> ApiResult<Json<WebhookListResponse>> {
+    let channel_id = ChannelId(channel_id);
+    let webhooks = state.channel_manager.list_webhooks(&channel_id).await?;
+
+    let webhooks = webhooks
+        .into_iter()
+        .map(|webhook| WebhookInfoHttp {
+            webhook_id: webhook.webhook_id,
+            name: webhook.name,
+            user_id: webhook.user_id.0,
+            created_by: webhook.created_by.0,
+            created_at: webhook.created_at.0,
+            rotated_at: webhook.rotated_at.map(|t| t.0),
+            max_content_bytes: webhook.max_content_bytes,
+            max_posts_per_minute: webhook.max_posts_per_minute,
+        })
+        .collect();
+
+    Ok(Json(WebhookListResponse { webhooks }))
+}
+
+/// POST /channels/:id/webhooks/:webhook_id/rotate - Replace a webhook's token
+pub async fn rotate_webhook(
+    State(state): State<Arc<AppState>>,
+    Path((channel_id, webhook_id)): Path<(String, String)>,
+) -> ApiResult<Json<WebhookRotateResponse>> {
+    let channel_id = ChannelId(channel_id);
+    let credentials = state.channel_manager.rotate_webhook_token(&channel_id, &webhook_id).await?;
+
+    Ok(Json(WebhookRotateResponse {
+        webhook_id: credentials.webhook_id,
+        token: credentials.token,
+    }))
+}
+
+/// DELETE /channels/:id/webhooks/:webhook_id - Revoke a webhook
+pub async fn revoke_webhook(
+    State(state): State<Arc<AppState>>,
+    Path((channel_id, webhook_id)): Path<(String, String)>,
+) -> ApiResult<Json<WebhookRevokeResponse>> {
+    let channel_id = ChannelId(channel_id);
+    let commit = state.channel_manager.revoke_webhook(&channel_id, &webhook_id).await?;
+
+    Ok(Json(WebhookRevokeResponse { webhook_id, commit }))
+}
+
+/// POST /webhooks/:id - Post a message through an inbound webhook
+///
+/// Authenticated with `Authorization: Bearer <token>`. The request body is
+/// the message text.
+pub async fn post_webhook(
+    State(state): State<Arc<AppState>>,
+    Path(webhook_id): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> ApiResult<Json<WebhookPostResponse>> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| {
+            ApiError::Status(StatusCode::UNAUTHORIZED, "Missing bearer token".to_string())
+        })?;
+
+    let encrypted = state.channel_manager.post_webhook_message(&webhook_id, token, &body).await?;
+
+    Ok(Json(WebhookPostResponse { webhook_id, encrypted_bytes: encrypted.len() }))
+}
+
+// ============================================================================
+// Simulated User Handlers
+// ============================================================================
+
+/// POST /users/:user/init - Add a simulated user with their own channel manager
+pub async fn init_user(
+    State(state): State<Arc<AppState>>,
+    Path(user): Path<String>,
+) -> ApiResult<(StatusCode, Json<UserInitResponse>)> {
+    let valid =
+        !user.is_empty() && user.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(ApiError::Status(
+            StatusCode::BAD_REQUEST,
+            format!("User names are letters, digits, '-' and '_': {:?}", user),
+        ));
+    }
+
+    let manager = state.add_user(&user).await?;
+    let user_id = manager.identity().user_id.0.clone();
+
+    Ok((StatusCode::CREATED, Json(UserInitResponse { user, user_id })))
+}
+
+/// POST /users/:user/channels/create - Create a channel as a simulated user
+pub async fn user_create_channel(
+    State(state): State<Arc<AppState>>,
+    Path(user): Path<String>,
+    Json(req): Json<ChannelCreateRequest>,
+) -> ApiResult<(StatusCode, Json<ChannelCreateResponse>)> {
+    let manager = state.user(&user).await?;
+    let channel_id = manager.create_channel(req.name.clone(), req.is_public).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(ChannelCreateResponse {
+            channel_id: channel_id.0,
+            name: req.name,
+            is_public: req.is_public,
+        }),
+    ))
+}
+
+/// POST /users/:user/channels/:id/invite - Invite another simulated user
+///
+/// The invitee's key package is generated on their behalf, and the commit
+/// adding them is applied by the channel's other members.
+pub async fn user_create_invite(
+    State(state): State<Arc<AppState>>,
+    Path((user, channel_id)): Path<(String, String)>,
+    Json(req): Json<UserInviteRequest>,
+) -> ApiResult<Json<UserInviteResponse>> {
+    let manager = state.user(&user).await?;
+    let invitee = state.user(&req.invitee).await?;
+    let channel_id = ChannelId(channel_id);
+
+    let key_package = invitee.generate_key_package().await?;
+    let (invite, commit) = manager.create_invite(&channel_id, key_package).await?;
+    let commit_applied_by = match commit {
+        Some(commit) => state.deliver_commit(&user, &channel_id, &commit, &[&req.invitee]).await,
+        None => Vec::new(),
+    };
+
+    Ok(Json(UserInviteResponse { invite, commit_applied_by }))
+}
+
+/// POST /users/:user/join - Join a channel from an invite
+pub async fn user_join_channel(
+    State(state): State<Arc<AppState>>,
+    Path(user): Path<String>,
+    Json(req): Json<UserJoinRequest>,
+) -> ApiResult<Json<JoinResponse>> {
+    let manager = state.user(&user).await?;
+    let channel_id = manager.join_channel(&req.invite).await?;
+
+    Ok(Json(JoinResponse {
+        channel_id: channel_id.0,
+        channel_name: req.invite.channel_name,
+        is_public: req.invite.is_public,
+        success: true,
+    }))
+}
+
+/// POST /users/:user/channels/:id/remove-member - Remove another simulated user
+///
+/// The removal commit is applied by the remaining members; the removed
+/// member isn't sent it.
+pub async fn user_remove_member(
+    State(state): State<Arc<AppState>>,
+    Path((user, channel_id)): Path<(String, String)>,
+    Json(req): Json<UserRemoveMemberRequest>,
+) -> ApiResult<Json<UserRemoveMemberResponse>> {
+    let manager = state.user(&user).await?;
+    let member = state.user(&req.member).await?;
+    let channel_id = ChannelId(channel_id);
+
+    let member_identity = member.identity().user_id.0.as_bytes();
+    let commit = manager.remove_member(&channel_id, member_identity).await?;
+    let commit_applied_by = state.deliver_commit(&user, &channel_id, &commit, &[&req.member]).await;
+
+    Ok(Json(UserRemoveMemberResponse { removed: req.member, commit_applied_by }))
+}
+
+/// POST /users/:user/channels/:id/send - Send a message to the channel's members
+pub async fn user_send_message(
+    State(state): State<Arc<AppState>>,
+    Path((user, channel_id)): Path<(String, String)>,
+    Json(req): Json<SendMessageRequest>,
+) -> ApiResult<Json<UserSendMessageResponse>> {
+    use crate::core_store::model::types::MessageId;
+
+    let manager = state.user(&user).await?;
+    let channel_id = ChannelId(channel_id);
+
+    let ciphertext = manager.send_message(&channel_id, req.plaintext.as_bytes()).await?;
+    let delivered_to = state.deliver_message(&user, &channel_id, &ciphertext).await?;
+
+    Ok(Json(UserSendMessageResponse {
+        message_id: MessageId::from_ciphertext(&ciphertext).0,
+        delivered_to,
+    }))
+}
+
+/// GET /users/:user/channels/:id/messages - A simulated user's message history
+///
+/// The most recent `limit` messages, oldest first.
+pub async fn user_get_messages(
+    State(state): State<Arc<AppState>>,
+    Path((user, channel_id)): Path<(String, String)>,
+    Query(query): Query<UserMessagesQuery>,
+) -> ApiResult<Json<MessageHistoryResponse>> {
+    let manager = state.user(&user).await?;
+    let channel_id = ChannelId(channel_id);
+    let limit = query.limit.unwrap_or(100).min(1000);
+
+    let mut messages = manager.get_messages(&channel_id, limit, None).await?;
+    messages.reverse();
+
+    let messages = messages
+        .into_iter()
+        .map(|msg| MessageInfo {
+            plaintext: msg.body_as_string().unwrap_or_else(|| "<binary>".to_string()),
+            message_id: msg.message_id.0,
+            sender_id: msg.sender.0,
+            timestamp: msg.timestamp.0,
+        })
+        .collect();
+
+    Ok(Json(MessageHistoryResponse { messages }))
+}
@@ -2,6 +2,22 @@
 //!
 //! This module provides an HTTP API for testing the MLS implementation.
 //! It's useful for manual testing, integration tests, and demonstrations.
+//!
+//! The `/users/:user/...` routes drive several simulated users at once, so
+//! multi-party flows can be run with curl:
+//!
+//! ```text
+//! curl -X POST localhost:3000/users/alice/init
+//! curl -X POST localhost:3000/users/bob/init
+//! curl -X POST localhost:3000/users/alice/channels/create \
+//!   -H 'Content-Type: application/json' -d '{"name": "general", "is_public": false}'
+//! curl -X POST localhost:3000/users/alice/channels/$CHANNEL/invite \
+//!   -H 'Content-Type: application/json' -d '{"invitee": "bob"}' | jq '{invite}' \
+//!   | curl -X POST localhost:3000/users/bob/join -H 'Content-Type: application/json' -d @-
+//! curl -X POST localhost:3000/users/alice/channels/$CHANNEL/send \
+//!   -H 'Content-Type: application/json' -d '{"plaintext": "hi"}'
+//! curl localhost:3000/users/bob/channels/$CHANNEL/messages
+//! ```
 
 pub mod api;
 pub mod handlers;
@@ -10,4 +26,4 @@ pub mod state;
 pub mod types;
 
 // Re-export the start_server function for convenience
-pub use server::start_server;
+pub use server::{start_server, temp_state, TestHarnessServer};
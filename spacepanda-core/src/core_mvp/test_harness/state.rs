@@ -2,13 +2,29 @@
 //!
 //! Maintains in-memory state for identities, channels, and messages
 //! across HTTP requests.
+//!
+//! Besides the server's own channel manager, the harness can host any
+//! number of simulated users, each with a channel manager of their own.
+//! The harness stands in for the network between them: commits and messages
+//! one user produces are handed to every other user in the channel.
 
+use crate::config::Config;
+use crate::core_mls::service::MlsService;
 use crate::core_mvp::channel_manager::ChannelManager;
+use crate::core_mvp::errors::{MvpError, MvpResult};
+use crate::core_mvp::network::IncomingMessage;
 use crate::core_mvp::types::InviteToken;
+use crate::core_router::session_manager::PeerId;
+use crate::core_store::model::types::{ChannelId, UserId};
+use crate::core_store::store::{LocalStore, LocalStoreConfig};
+use crate::shutdown::ShutdownCoordinator;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
+use tracing::debug;
 
 /// Represents a user identity in the test harness
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,16 +56,31 @@ pub struct AppState {
 
     /// Invite tokens awaiting acceptance (for debugging/inspection)
     pub pending_invites: Arc<RwLock<HashMap<String, InviteToken>>>,
+
+    /// Simulated users by name
+    pub users: Arc<RwLock<BTreeMap<String, Arc<ChannelManager>>>>,
+
+    /// Where simulated users keep their stores, one directory each
+    pub data_dir: PathBuf,
 }
 
 impl AppState {
     /// Create a new server state
     pub fn new(channel_manager: Arc<ChannelManager>) -> Self {
+        let data_dir =
+            std::env::temp_dir().join(format!("spacepanda_test_{}", uuid::Uuid::new_v4()));
+        Self::with_data_dir(channel_manager, data_dir)
+    }
+
+    /// Create a new server state keeping simulated users under `data_dir`
+    pub fn with_data_dir(channel_manager: Arc<ChannelManager>, data_dir: PathBuf) -> Self {
         Self {
             identity: Arc::new(RwLock::new(None)),
             channel_manager,
             message_history: Arc::new(RwLock::new(HashMap::new())),
             pending_invites: Arc::new(RwLock::new(HashMap::new())),
+            users: Arc::new(RwLock::new(BTreeMap::new())),
+            data_dir,
         }
     }
 
@@ -75,3 +106,145 @@ impl AppState {
         history.get(channel_id).cloned().unwrap_or_default()
     }
 }
+
+impl AppState {
+    /// Add a simulated user with a fresh channel manager
+    ///
+    /// The name is used as a directory name, so callers check it first.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidOperation` if the name is taken
+    pub async fn add_user(&self, name: &str) -> MvpResult<Arc<ChannelManager>> {
+        let mut users = self.users.write().await;
+        if users.contains_key(name) {
+            return Err(MvpError::InvalidOperation(format!("User {} already exists", name)));
+        }
+        let manager = new_channel_manager(&self.data_dir.join(name), name)?;
+        users.insert(name.to_string(), manager.clone());
+        Ok(manager)
+    }
+
+    /// A simulated user's channel manager
+    ///
+    /// # Errors
+    ///
+    /// Returns `MemberNotFound` if there's no user by that name
+    pub async fn user(&self, name: &str) -> MvpResult<Arc<ChannelManager>> {
+        self.users
+            .read()
+            .await
+            .get(name)
+            .cloned()
+            .ok_or_else(|| MvpError::MemberNotFound {
+                channel: String::new(),
+                member: name.to_string(),
+            })
+    }
+
+    /// Users other than `from` who have the channel, except those in `skip`
+    async fn channel_peers(
+        &self,
+        from: &str,
+        channel_id: &ChannelId,
+        skip: &[&str],
+    ) -> Vec<(String, Arc<ChannelManager>)> {
+        let users: Vec<_> = self
+            .users
+            .read()
+            .await
+            .iter()
+            .filter(|(name, _)| name.as_str() != from && !skip.contains(&name.as_str()))
+            .map(|(name, manager)| (name.clone(), manager.clone()))
+            .collect();
+
+        let mut peers = Vec::new();
+        for (name, manager) in users {
+            if manager.get_channel(channel_id).await.is_ok() {
+                peers.push((name, manager));
+            }
+        }
+        peers
+    }
+
+    /// Hand a commit from `from` to the channel's other members
+    ///
+    /// `skip` lists members the commit removes, who wouldn't be sent it.
+    /// Returns the names of the members who applied it.
+    pub async fn deliver_commit(
+        &self,
+        from: &str,
+        channel_id: &ChannelId,
+        commit: &[u8],
+        skip: &[&str],
+    ) -> Vec<String> {
+        let mut applied = Vec::new();
+        for (name, manager) in self.channel_peers(from, channel_id, skip).await {
+            match manager.process_commit(commit).await {
+                Ok(()) => applied.push(name),
+                Err(e) => debug!("{} could not apply commit in {}: {}", name, channel_id, e),
+            }
+        }
+        applied
+    }
+
+    /// Hand a message from `from` to the channel's other members
+    ///
+    /// Returns the names of the members who could decrypt it; the others
+    /// keep it queued in their inbox.
+    pub async fn deliver_message(
+        &self,
+        from: &str,
+        channel_id: &ChannelId,
+        ciphertext: &[u8],
+    ) -> MvpResult<Vec<String>> {
+        let sender_id = self.user(from).await?.identity().user_id.clone();
+        let mut delivered = Vec::new();
+        for (name, manager) in self.channel_peers(from, channel_id, &[]).await {
+            let incoming = IncomingMessage {
+                channel_id: channel_id.clone(),
+                ciphertext: ciphertext.to_vec(),
+                sender_id: sender_id.clone(),
+                sender_peer_id: PeerId::from_bytes(sender_id.0.as_bytes().to_vec()),
+            };
+            match manager.handle_incoming_message(incoming).await {
+                Ok(Some(_)) => delivered.push(name),
+                Ok(None) => debug!("{} queued a message in {}", name, channel_id),
+                Err(e) => debug!("{} could not take a message in {}: {}", name, channel_id, e),
+            }
+        }
+        Ok(delivered)
+    }
+}
+
+/// A channel manager for `name` with its store under `data_dir`
+pub fn new_channel_manager(data_dir: &Path, name: &str) -> MvpResult<Arc<ChannelManager>> {
+    let mut config = Config::default();
+    config.store.data_dir = data_dir.to_path_buf();
+    let config = Arc::new(config);
+    let shutdown = Arc::new(ShutdownCoordinator::new(Duration::from_secs(30)));
+    let mls_service = Arc::new(MlsService::new(&config, shutdown));
+
+    std::fs::create_dir_all(data_dir).map_err(|e| MvpError::Store(e.to_string()))?;
+    let store_config = LocalStoreConfig {
+        data_dir: data_dir.to_path_buf(),
+        enable_encryption: false, // Disable for testing
+        require_signatures: false,
+        authorized_keys: Vec::new(),
+        ..Default::default()
+    };
+    let store = LocalStore::new(store_config).map_err(|e| MvpError::Store(e.to_string()))?;
+
+    let identity = crate::core_mvp::channel_manager::Identity::new(
+        UserId(format!("{}@spacepanda.local", name)),
+        name.to_string(),
+        format!("{}-node", name),
+    );
+
+    Ok(Arc::new(ChannelManager::new(
+        mls_service,
+        Arc::new(store),
+        Arc::new(identity),
+        config,
+    )))
+}
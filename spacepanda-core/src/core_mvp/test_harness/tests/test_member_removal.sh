@@ -1,83 +1,47 @@
 #!/usr/bin/env bash
-# Test script for member removal HTTP endpoint
-# Prerequisites: Start the HTTP test harness server first
+# Four-party member removal over the HTTP test harness
+# Prerequisites: start the harness first (cargo run -p test-harness)
 
 set -e
 
-BASE_URL="http://localhost:3000"
+BASE_URL="${BASE_URL:-http://localhost:3000}"
+JSON=(-H "Content-Type: application/json")
 
 echo "=== Testing Member Removal Flow ==="
 
-# Create 3 identities
-echo -n "Creating Alice... "
-ALICE=$(curl -s -X POST "$BASE_URL/identity/create" | jq -r '.user_id')
-echo "✓ $ALICE"
+for user in alice bob charlie dave; do
+  echo -n "Creating $user... "
+  curl -sf -X POST "$BASE_URL/users/$user/init" | jq -r '.user_id'
+done
 
-echo -n "Creating Bob... "
-BOB=$(curl -s -X POST "$BASE_URL/identity/create" | jq -r '.user_id')
-echo "✓ $BOB"
-
-echo -n "Creating Charlie... "
-CHARLIE=$(curl -s -X POST "$BASE_URL/identity/create" | jq -r '.user_id')
-echo "✓ $CHARLIE"
-
-# Alice creates a channel
 echo -n "Alice creates channel... "
-CHANNEL=$(curl -s -X POST "$BASE_URL/channels/create" \
-  -H "Content-Type: application/json" \
-  -d '{"name": "test-removal", "is_public": true}' | jq -r '.channel_id')
+CHANNEL=$(curl -sf -X POST "$BASE_URL/users/alice/channels/create" "${JSON[@]}" \
+  -d '{"name": "test-removal", "is_public": false}' | jq -r '.channel_id')
 echo "✓ $CHANNEL"
 
-# Alice invites Bob
-echo -n "Alice invites Bob... "
-BOB_INVITE=$(curl -s -X POST "$BASE_URL/channels/$CHANNEL/invite" \
-  -H "Content-Type: application/json" \
-  -d "{\"invitee_user_id\": \"$BOB\"}" | jq -r '.commit')
-echo "✓"
-
-# Bob processes invite and joins
-echo -n "Bob joins... "
-curl -s -X POST "$BASE_URL/channels/$CHANNEL/process-commit" \
-  -H "Content-Type: application/json" \
-  -d "{\"commit\": $BOB_INVITE}" > /dev/null
-echo "✓"
-
-# Alice invites Charlie
-echo -n "Alice invites Charlie... "
-CHARLIE_INVITE=$(curl -s -X POST "$BASE_URL/channels/$CHANNEL/invite" \
-  -H "Content-Type: application/json" \
-  -d "{\"invitee_user_id\": \"$CHARLIE\"}" | jq -r '.commit')
-echo "✓"
-
-# Bob and Charlie process commits
-echo -n "Processing commits... "
-curl -s -X POST "$BASE_URL/channels/$CHANNEL/process-commit" \
-  -H "Content-Type: application/json" \
-  -d "{\"commit\": $CHARLIE_INVITE}" > /dev/null
-echo "✓"
+for user in bob charlie dave; do
+  echo -n "Alice invites $user... "
+  curl -sf -X POST "$BASE_URL/users/alice/channels/$CHANNEL/invite" "${JSON[@]}" \
+    -d "{\"invitee\": \"$user\"}" | jq '{invite}' \
+    | curl -sf -X POST "$BASE_URL/users/$user/join" "${JSON[@]}" -d @- > /dev/null
+  echo "✓"
+done
 
-# List members
-echo "Members before removal:"
-curl -s "$BASE_URL/channels/$CHANNEL/members" | jq '.members'
+echo "Alice says hello:"
+curl -sf -X POST "$BASE_URL/users/alice/channels/$CHANNEL/send" "${JSON[@]}" \
+  -d '{"plaintext": "Before removal"}' | jq '.delivered_to'
 
-# Alice removes Bob
-echo -n "Alice removes Bob... "
-REMOVAL_COMMIT=$(curl -s -X POST "$BASE_URL/channels/$CHANNEL/remove-member" \
-  -H "Content-Type: application/json" \
-  -d "{\"member_id\": \"$BOB\"}" | jq -r '.commit')
-echo "✓"
+echo "Alice removes Bob:"
+curl -sf -X POST "$BASE_URL/users/alice/channels/$CHANNEL/remove-member" "${JSON[@]}" \
+  -d '{"member": "bob"}' | jq '.commit_applied_by'
 
-# Charlie processes the removal commit
-echo -n "Charlie processes removal... "
-curl -s -X POST "$BASE_URL/channels/$CHANNEL/process-commit" \
-  -H "Content-Type: application/json" \
-  -d "{\"commit\": $REMOVAL_COMMIT}" > /dev/null
-echo "✓"
+echo "Alice sends after the removal:"
+curl -sf -X POST "$BASE_URL/users/alice/channels/$CHANNEL/send" "${JSON[@]}" \
+  -d '{"plaintext": "After Bob was removed"}' | jq '.delivered_to'
 
-# List members after removal
-echo "Members after removal:"
-curl -s "$BASE_URL/channels/$CHANNEL/members" | jq '.members'
+for user in bob charlie; do
+  echo "$user's messages:"
+  curl -sf "$BASE_URL/users/$user/channels/$CHANNEL/messages" | jq -r '.messages[].plaintext'
+done
 
-echo ""
-echo "=== Member Removal Test Complete ==="
-echo "Expected: Bob should no longer be in the member list"
+echo "=== Done ==="
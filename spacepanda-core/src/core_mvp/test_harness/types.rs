@@ -1,5 +1,6 @@
 //! Request/Response types for the HTTP test harness
 
+use crate::core_mvp::types::InviteToken;
 use serde::{Deserialize, Serialize};
 
 // Import core_mvp types
@@ -269,6 +270,119 @@ pub struct ThreadSummaryHttp {
     pub thread_info: Option<GetThreadInfoResponse>,
 }
 
+// ============================================================================
+// Webhook Types
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookCreateRequest {
+    pub name: String,
+    /// Content-size cap in bytes (server default if omitted)
+    pub max_content_bytes: Option<usize>,
+    /// Posts per minute (server default if omitted)
+    pub max_posts_per_minute: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookCreateResponse {
+    pub webhook_id: String,
+    /// Secret token; only returned here and on rotation
+    pub token: String,
+    /// Optional commit for existing members to process
+    pub commit: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookInfoHttp {
+    pub webhook_id: String,
+    pub name: String,
+    pub user_id: String,
+    pub created_by: String,
+    pub created_at: u64,
+    pub rotated_at: Option<u64>,
+    pub max_content_bytes: usize,
+    pub max_posts_per_minute: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookListResponse {
+    pub webhooks: Vec<WebhookInfoHttp>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookRotateResponse {
+    pub webhook_id: String,
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookRevokeResponse {
+    pub webhook_id: String,
+    /// Commit removing the webhook's leaf, for existing members to process
+    pub commit: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookPostResponse {
+    pub webhook_id: String,
+    pub encrypted_bytes: usize,
+}
+
+// ============================================================================
+// Simulated User Types
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserInitResponse {
+    pub user: String,
+    pub user_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserInviteRequest {
+    /// Name of the simulated user to invite
+    pub invitee: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserInviteResponse {
+    /// Invite for the invitee to pass to `/users/:user/join`
+    pub invite: InviteToken,
+    /// Existing members who applied the commit adding the invitee
+    pub commit_applied_by: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserJoinRequest {
+    pub invite: InviteToken,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserRemoveMemberRequest {
+    /// Name of the simulated user to remove
+    pub member: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserRemoveMemberResponse {
+    pub removed: String,
+    /// Remaining members who applied the removal commit
+    pub commit_applied_by: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserSendMessageResponse {
+    pub message_id: String,
+    /// Members who decrypted the message
+    pub delivered_to: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserMessagesQuery {
+    /// Most recent messages to return (default 100)
+    pub limit: Option<usize>,
+}
+
 // ============================================================================
 // Error Types
 // ============================================================================
@@ -0,0 +1,259 @@
+//! Tests for file attachments
+//!
+//! Files are encrypted in chunks stored in the DHT, and the channel only
+//! gets the manifest. Members download from the DHT, checking each chunk's
+//! hash, and a download that failed part way resumes where it stopped.
+//! Sent and downloaded files are kept in the attachment cache, which
+//! serves later downloads without touching the DHT.
+
+use super::common::{create_manager_with, ManagerOptions};
+use crate::config::Config;
+use crate::core_dht::DhtStorage;
+use crate::core_mvp::attachments::{partial_path, AttachmentManifest, AttachmentStore};
+use crate::core_mvp::channel_manager::ChannelManager;
+use crate::core_mvp::errors::MvpError;
+use crate::core_mvp::network::IncomingMessage;
+use crate::core_mvp::types::{ChatMessage, MessageType};
+use crate::core_router::PeerId;
+use crate::core_store::model::types::ChannelId;
+use crate::core_store::store::attachment_cache::AttachmentCache;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+/// Helper to create a test ChannelManager storing chunks in `dht`, with an
+/// attachment cache in `temp_dir`
+fn create_manager(
+    name: &str,
+    temp_dir: &TempDir,
+    dht: &DhtStorage,
+    max_attachment_size: u64,
+) -> Arc<ChannelManager> {
+    let mut config = Config::default();
+    config.store.max_attachment_size = max_attachment_size;
+    let options = ManagerOptions { config, ..Default::default() };
+    let (manager, _) = create_manager_with(name, temp_dir, options);
+
+    let attachments = AttachmentStore::new(dht.clone()).with_retries(2, Duration::from_millis(1));
+    let cache =
+        AttachmentCache::open(&temp_dir.path().join("attachment-cache"), 16 * 1024 * 1024, 1)
+            .unwrap();
+    Arc::new(
+        manager
+            .with_attachment_store(Arc::new(attachments))
+            .with_attachment_cache(Arc::new(cache)),
+    )
+}
+
+/// Alice and Bob in a channel, sharing a DHT
+async fn setup(
+    dirs: &[TempDir],
+    dht: &DhtStorage,
+) -> (Arc<ChannelManager>, Arc<ChannelManager>, ChannelId) {
+    let alice = create_manager("alice", &dirs[0], dht, 1024 * 1024);
+    let bob = create_manager("bob", &dirs[1], dht, 1024 * 1024);
+
+    let channel_id = alice.create_channel("files".to_string(), false).await.unwrap();
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.join_channel(&invite).await.unwrap();
+    (alice, bob, channel_id)
+}
+
+/// Write a file of `len` bytes that differ from chunk to chunk
+fn write_file(dir: &TempDir, name: &str, len: usize) -> (PathBuf, Vec<u8>) {
+    let contents: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+    let path = dir.path().join(name);
+    std::fs::write(&path, &contents).unwrap();
+    (path, contents)
+}
+
+/// Send Alice's file and hand the manifest message to Bob
+async fn send_to_bob(
+    alice: &ChannelManager,
+    bob: &ChannelManager,
+    channel_id: &ChannelId,
+    path: &Path,
+) -> ChatMessage {
+    let ciphertext = alice.send_attachment(channel_id, path).await.unwrap();
+    let incoming = IncomingMessage {
+        channel_id: channel_id.clone(),
+        ciphertext,
+        sender_id: alice.identity().user_id.clone(),
+        sender_peer_id: PeerId(vec![1]),
+    };
+    bob.handle_incoming_message(incoming).await.unwrap().unwrap()
+}
+
+#[tokio::test]
+async fn test_attachment_round_trip() {
+    let dirs: Vec<TempDir> = (0..3).map(|_| TempDir::new().unwrap()).collect();
+    let dht = DhtStorage::new();
+    let (alice, bob, channel_id) = setup(&dirs, &dht).await;
+
+    // Two full chunks and a partial one
+    let (path, contents) = write_file(&dirs[2], "holiday.png", 600 * 1024);
+    let received = send_to_bob(&alice, &bob, &channel_id, &path).await;
+    assert_eq!(received.message_type, MessageType::Attachment);
+
+    let manifest = AttachmentManifest::decode(&received.body).unwrap();
+    assert_eq!(manifest.chunks.len(), 3);
+    assert_eq!(manifest.mime_type, "image/png");
+
+    // Only ciphertext reaches the DHT
+    let stored = dht.get(&manifest.chunks[0].key).unwrap();
+    assert_ne!(&stored.data[..64], &contents[..64]);
+
+    let dest = dirs[1].path().join("download.png");
+    let metadata = bob
+        .download_attachment(&channel_id, received.message_id.clone(), &dest)
+        .await
+        .unwrap();
+    assert_eq!(std::fs::read(&dest).unwrap(), contents);
+    assert_eq!(metadata.filename, "holiday.png");
+    assert_eq!(metadata.size_bytes, contents.len() as u64);
+    assert!(!partial_path(&dest).exists());
+
+    // The sender's copy of the history shows the attachment too
+    let history = alice.get_messages(&channel_id, 10, None).await.unwrap();
+    assert_eq!(history[0].message_type, MessageType::Attachment);
+}
+
+#[tokio::test]
+async fn test_download_resumes_after_missing_chunk() {
+    let dirs: Vec<TempDir> = (0..3).map(|_| TempDir::new().unwrap()).collect();
+    let dht = DhtStorage::new();
+    let (alice, bob, channel_id) = setup(&dirs, &dht).await;
+
+    let (path, contents) = write_file(&dirs[2], "archive.zip", 700 * 1024);
+    let received = send_to_bob(&alice, &bob, &channel_id, &path).await;
+    let manifest = AttachmentManifest::decode(&received.body).unwrap();
+
+    // The second chunk has gone missing from the DHT
+    let missing = manifest.chunks[1].key;
+    let value = dht.get(&missing).unwrap();
+    dht.delete(&missing).unwrap();
+
+    let dest = dirs[1].path().join("archive.zip");
+    let err = bob
+        .download_attachment(&channel_id, received.message_id.clone(), &dest)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, MvpError::ChunkUnavailable { index: 1, attempts: 2, .. }));
+    assert!(!dest.exists());
+    let partial = partial_path(&dest);
+    assert_eq!(std::fs::metadata(&partial).unwrap().len(), u64::from(manifest.chunk_size));
+
+    // Once it's back, the download picks up from the second chunk
+    dht.put(missing, value).unwrap();
+    bob.download_attachment(&channel_id, received.message_id, &dest).await.unwrap();
+    assert_eq!(std::fs::read(&dest).unwrap(), contents);
+    assert!(!partial.exists());
+}
+
+#[tokio::test]
+async fn test_downloads_go_through_the_attachment_cache() {
+    let dirs: Vec<TempDir> = (0..3).map(|_| TempDir::new().unwrap()).collect();
+    let dht = DhtStorage::new();
+    let (alice, bob, channel_id) = setup(&dirs, &dht).await;
+
+    let (path, contents) = write_file(&dirs[2], "photo.jpg", 100 * 1024);
+    let received = send_to_bob(&alice, &bob, &channel_id, &path).await;
+    let manifest = AttachmentManifest::decode(&received.body).unwrap();
+
+    // Alice keeps her copy until the chunks reach enough peers
+    let alice_cache = alice.attachment_cache().unwrap();
+    assert!(alice_cache.contains(&manifest.id));
+    assert_eq!(alice_cache.usage().pending_delivery, 1);
+
+    let dest = dirs[1].path().join("photo.jpg");
+    bob.download_attachment(&channel_id, received.message_id.clone(), &dest)
+        .await
+        .unwrap();
+    let bob_cache = bob.attachment_cache().unwrap();
+    assert!(bob_cache.contains(&manifest.id));
+
+    // The chunks are gone from the DHT, but Bob still has the file
+    let chunks: Vec<_> = manifest
+        .chunks
+        .iter()
+        .map(|chunk| (chunk.key, dht.get(&chunk.key).unwrap()))
+        .collect();
+    for (key, _) in &chunks {
+        dht.delete(key).unwrap();
+    }
+    let again = dirs[1].path().join("photo-again.jpg");
+    bob.download_attachment(&channel_id, received.message_id.clone(), &again)
+        .await
+        .unwrap();
+    assert_eq!(std::fs::read(&again).unwrap(), contents);
+
+    // Once evicted it comes from the DHT again
+    bob_cache.evict_all().unwrap();
+    let evicted = dirs[1].path().join("photo-evicted.jpg");
+    let err = bob
+        .download_attachment(&channel_id, received.message_id.clone(), &evicted)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, MvpError::ChunkUnavailable { index: 0, .. }));
+
+    for (key, value) in chunks {
+        dht.put(key, value).unwrap();
+    }
+    bob.download_attachment(&channel_id, received.message_id, &evicted)
+        .await
+        .unwrap();
+    assert_eq!(std::fs::read(&evicted).unwrap(), contents);
+    assert!(bob_cache.contains(&manifest.id));
+}
+
+#[tokio::test]
+async fn test_corrupted_chunk_is_rejected() {
+    let dirs: Vec<TempDir> = (0..3).map(|_| TempDir::new().unwrap()).collect();
+    let dht = DhtStorage::new();
+    let (alice, bob, channel_id) = setup(&dirs, &dht).await;
+
+    let (path, _) = write_file(&dirs[2], "notes.txt", 1000);
+    let received = send_to_bob(&alice, &bob, &channel_id, &path).await;
+    let manifest = AttachmentManifest::decode(&received.body).unwrap();
+
+    // Someone overwrote the chunk in the DHT
+    let key = manifest.chunks[0].key;
+    let mut value = dht.get(&key).unwrap();
+    value.data[0] ^= 0xff;
+    value.sequence += 1;
+    dht.put(key, value).unwrap();
+
+    let dest = dirs[1].path().join("notes.txt");
+    let err = bob
+        .download_attachment(&channel_id, received.message_id, &dest)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, MvpError::ChunkUnavailable { index: 0, .. }));
+    assert!(!dest.exists());
+}
+
+#[tokio::test]
+async fn test_attachment_size_limit() {
+    let dirs: Vec<TempDir> = (0..2).map(|_| TempDir::new().unwrap()).collect();
+    let dht = DhtStorage::new();
+    let alice = create_manager("alice", &dirs[0], &dht, 1024);
+    let channel_id = alice.create_channel("files".to_string(), false).await.unwrap();
+
+    let (path, _) = write_file(&dirs[1], "big.bin", 2048);
+    let result = alice.send_attachment(&channel_id, &path).await;
+    assert!(matches!(result, Err(MvpError::PayloadTooLarge { size: 2048, limit: 1024 })));
+    assert_eq!(dht.size().unwrap(), 0);
+
+    // Plain messages aren't attachments
+    alice.send_message(&channel_id, b"hello").await.unwrap();
+    let history = alice.get_messages(&channel_id, 10, None).await.unwrap();
+    let dest = dirs[1].path().join("hello");
+    let result = alice
+        .download_attachment(&channel_id, history[0].message_id.clone(), &dest)
+        .await;
+    assert!(matches!(result, Err(MvpError::InvalidOperation(_))));
+}
@@ -0,0 +1,223 @@
+//! Tests for broadcast channel mode
+//!
+//! A 500-member channel is simulated with one real publisher, two real
+//! readers and synthetic readers whose key packages come from a shared
+//! MLS service. Commit counts are compared with and without batching, and
+//! delivery latency is compared between per-peer sends and the mailbox
+//! under a simulated link delay (tokio's paused clock).
+
+use super::common::{create_manager_with, ManagerOptions};
+use crate::config::Config;
+use crate::core_dht::DhtStorage;
+use crate::core_mls::service::MlsService;
+use crate::core_mvp::broadcast::{BroadcastMailbox, BroadcastPolicy, DhtMailbox, MailboxEntry};
+use crate::core_mvp::channel_manager::ChannelManager;
+use crate::core_mvp::errors::{MvpError, MvpResult};
+use crate::core_mvp::network::IncomingMessage;
+use crate::core_router::PeerId;
+use crate::core_store::model::types::{ChannelId, UserId};
+use crate::shutdown::ShutdownCoordinator;
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::time::Instant;
+
+/// Simulated channel size
+const MEMBERS: usize = 500;
+
+/// Simulated one-way delay of a network hop
+const LINK_DELAY: Duration = Duration::from_millis(20);
+
+/// Mailbox whose operations each take one simulated network hop
+struct DelayedMailbox {
+    inner: DhtMailbox,
+}
+
+#[async_trait]
+impl BroadcastMailbox for DelayedMailbox {
+    async fn publish(
+        &self,
+        channel_id: &ChannelId,
+        sender: &UserId,
+        ciphertext: Vec<u8>,
+    ) -> MvpResult<u64> {
+        tokio::time::sleep(LINK_DELAY).await;
+        self.inner.publish(channel_id, sender, ciphertext).await
+    }
+
+    async fn fetch(&self, channel_id: &ChannelId, from_seq: u64) -> MvpResult<Vec<MailboxEntry>> {
+        tokio::time::sleep(LINK_DELAY).await;
+        self.inner.fetch(channel_id, from_seq).await
+    }
+}
+
+fn mls_service(temp_dir: &TempDir, config: &Config) -> Arc<MlsService> {
+    let shutdown = Arc::new(ShutdownCoordinator::new(Duration::from_secs(30)));
+    Arc::new(
+        MlsService::with_storage(config, shutdown, temp_dir.path().join("mls_groups")).unwrap(),
+    )
+}
+
+/// Helper to create a test ChannelManager
+fn create_manager(
+    name: &str,
+    temp_dir: &TempDir,
+    mailbox: Option<Arc<dyn BroadcastMailbox>>,
+) -> Arc<ChannelManager> {
+    let (mut manager, _) = create_manager_with(name, temp_dir, ManagerOptions::default());
+    if let Some(mailbox) = mailbox {
+        manager = manager.with_broadcast_mailbox(mailbox);
+    }
+    Arc::new(manager)
+}
+
+/// Key packages for `count` synthetic readers
+async fn synthetic_key_packages(count: usize, temp_dir: &TempDir) -> Vec<Vec<u8>> {
+    let service = mls_service(temp_dir, &Config::default());
+    let mut key_packages = Vec::with_capacity(count);
+    for i in 0..count {
+        let identity = format!("reader-{}@spacepanda.local", i).into_bytes();
+        key_packages.push(service.generate_key_package(identity).await.unwrap());
+    }
+    key_packages
+}
+
+fn publisher_policy(publisher: &ChannelManager) -> BroadcastPolicy {
+    BroadcastPolicy::new([publisher.identity().user_id.clone()])
+}
+
+#[tokio::test]
+async fn test_broadcast_mode_batches_membership_commits() {
+    let dirs: Vec<TempDir> = (0..3).map(|_| TempDir::new().unwrap()).collect();
+    let regular = create_manager("regular", &dirs[0], None);
+    let broadcaster = create_manager("broadcaster", &dirs[1], None);
+
+    // Without broadcast mode: one commit per reader
+    let regular_channel = regular.create_channel("news".to_string(), false).await.unwrap();
+    for key_package in synthetic_key_packages(MEMBERS - 1, &dirs[2]).await {
+        regular.create_invite(&regular_channel, key_package).await.unwrap();
+    }
+
+    // With broadcast mode: readers are queued and committed together
+    let broadcast_channel = broadcaster.create_channel("news".to_string(), false).await.unwrap();
+    broadcaster
+        .set_broadcast_policy(&broadcast_channel, Some(publisher_policy(&broadcaster)))
+        .await
+        .unwrap();
+    for key_package in synthetic_key_packages(MEMBERS - 1, &dirs[2]).await {
+        broadcaster.queue_member_add(&broadcast_channel, key_package).await.unwrap();
+    }
+
+    // Nothing is committed until the batch runs
+    assert_eq!(broadcaster.commit_count(&broadcast_channel).await, 0);
+    assert_eq!(broadcaster.get_channel_members(&broadcast_channel).await.unwrap().len(), 1);
+
+    let batch = broadcaster.commit_membership_queue(&broadcast_channel).await.unwrap();
+    assert_eq!(batch.added, MEMBERS - 1);
+    assert!(batch.invite.is_some());
+
+    assert_eq!(regular.get_channel_members(&regular_channel).await.unwrap().len(), MEMBERS);
+    assert_eq!(
+        broadcaster.get_channel_members(&broadcast_channel).await.unwrap().len(),
+        MEMBERS
+    );
+    assert_eq!(regular.commit_count(&regular_channel).await, (MEMBERS - 1) as u64);
+    assert_eq!(broadcaster.commit_count(&broadcast_channel).await, 1);
+
+    // Removing ten readers is one more commit, not ten
+    for i in 0..10 {
+        let identity = format!("reader-{}@spacepanda.local", i);
+        broadcaster
+            .queue_member_removal(&broadcast_channel, identity.as_bytes())
+            .await
+            .unwrap();
+    }
+    let batch = broadcaster.commit_membership_queue(&broadcast_channel).await.unwrap();
+    assert_eq!(batch.removed, 10);
+    assert_eq!(batch.commits.len(), 1);
+    assert_eq!(broadcaster.commit_count(&broadcast_channel).await, 2);
+    assert_eq!(
+        broadcaster.get_channel_members(&broadcast_channel).await.unwrap().len(),
+        MEMBERS - 10
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_broadcast_mailbox_delivery_latency() {
+    let dirs: Vec<TempDir> = (0..4).map(|_| TempDir::new().unwrap()).collect();
+    let mailbox: Arc<dyn BroadcastMailbox> =
+        Arc::new(DelayedMailbox { inner: DhtMailbox::new(DhtStorage::new()) });
+    let alice = create_manager("alice", &dirs[0], Some(mailbox.clone()));
+    let bob = create_manager("bob", &dirs[1], Some(mailbox.clone()));
+    let carol = create_manager("carol", &dirs[2], Some(mailbox));
+
+    // Everyone but the two real readers is synthetic
+    let channel_id = alice.create_channel("announcements".to_string(), false).await.unwrap();
+    let policy = publisher_policy(&alice);
+    alice.set_broadcast_policy(&channel_id, Some(policy.clone())).await.unwrap();
+    alice
+        .queue_member_add(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    alice
+        .queue_member_add(&channel_id, carol.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    for key_package in synthetic_key_packages(MEMBERS - 3, &dirs[3]).await {
+        alice.queue_member_add(&channel_id, key_package).await.unwrap();
+    }
+    let invite = alice.commit_membership_queue(&channel_id).await.unwrap().invite.unwrap();
+    for reader in [&bob, &carol] {
+        reader.join_channel(&invite).await.unwrap();
+        reader.set_broadcast_policy(&channel_id, Some(policy.clone())).await.unwrap();
+    }
+    assert_eq!(alice.get_channel_members(&channel_id).await.unwrap().len(), MEMBERS);
+
+    // Readers can't post
+    assert!(matches!(
+        bob.send_message(&channel_id, b"me too").await,
+        Err(MvpError::InvalidOperation(_))
+    ));
+
+    // Without the mailbox: one send per member, one after another as
+    // NetworkLayer::broadcast_message does. The real readers are last.
+    alice.set_broadcast_policy(&channel_id, None).await.unwrap();
+    let start = Instant::now();
+    let ciphertext = alice.send_message(&channel_id, b"per-peer").await.unwrap();
+    for _ in 0..MEMBERS - 3 {
+        tokio::time::sleep(LINK_DELAY).await;
+    }
+    for reader in [&bob, &carol] {
+        tokio::time::sleep(LINK_DELAY).await;
+        let incoming = IncomingMessage {
+            channel_id: channel_id.clone(),
+            ciphertext: ciphertext.clone(),
+            sender_id: alice.identity().user_id.clone(),
+            sender_peer_id: PeerId(vec![1]),
+        };
+        let message = reader.handle_incoming_message(incoming).await.unwrap().unwrap();
+        assert_eq!(message.body, b"per-peer");
+    }
+    let per_peer_latency = start.elapsed();
+
+    // With the mailbox: one publish, then every reader pulls concurrently
+    alice.set_broadcast_policy(&channel_id, Some(policy)).await.unwrap();
+    let start = Instant::now();
+    alice.send_message(&channel_id, b"mailbox").await.unwrap();
+    let (bob_fetched, carol_fetched) =
+        tokio::join!(bob.fetch_broadcast(&channel_id), carol.fetch_broadcast(&channel_id));
+    let mailbox_latency = start.elapsed();
+
+    for fetched in [bob_fetched.unwrap(), carol_fetched.unwrap()] {
+        assert_eq!(fetched.len(), 1);
+        assert_eq!(fetched[0].body, b"mailbox");
+        assert_eq!(fetched[0].sender, alice.identity().user_id);
+    }
+
+    assert_eq!(per_peer_latency, LINK_DELAY * (MEMBERS - 1) as u32);
+    assert_eq!(mailbox_latency, LINK_DELAY * 2);
+
+    // Nothing new on a second fetch
+    assert!(bob.fetch_broadcast(&channel_id).await.unwrap().is_empty());
+}
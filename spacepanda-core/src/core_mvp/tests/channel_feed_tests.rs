@@ -0,0 +1,280 @@
+//! Tests for syncing channel messages through the DHT channel feed
+//!
+//! Members share one `DhtStorage`, standing in for the DHT. Messages sent by
+//! one member must reach the others through `sync_channel` alone, exactly
+//! once, whatever order the feed's entries show up in.
+
+use super::common::{create_manager_with, ManagerOptions};
+use crate::config::Config;
+use crate::core_dht::channel_feed::CHANNEL_FEED_NAMESPACE;
+use crate::core_dht::{ChannelFeed, DhtKey, DhtStorage};
+use crate::core_mls::types::GroupId;
+use crate::core_mvp::channel_manager::ChannelManager;
+use crate::core_mvp::circuit_feed::{CircuitFeedPublisher, FeedPut};
+use crate::core_router::{
+    Capability, OnionCommand, OnionConfig, OnionEvent, OnionRouter, PeerId, PeerInfo, RouteTable,
+    RouteTableCommand,
+};
+use crate::core_store::model::types::ChannelId;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::sync::mpsc;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+/// Helper to create a test ChannelManager sharing the given feed
+fn create_manager(name: &str, temp_dir: &TempDir, feed: Arc<ChannelFeed>) -> ChannelManager {
+    create_manager_with_config(name, temp_dir, feed, Config::default())
+}
+
+fn create_manager_with_config(
+    name: &str,
+    temp_dir: &TempDir,
+    feed: Arc<ChannelFeed>,
+    config: Config,
+) -> ChannelManager {
+    let options = ManagerOptions { config, ..Default::default() };
+    let (manager, _) = create_manager_with(name, temp_dir, options);
+    manager.with_channel_feed(feed)
+}
+
+/// Key of a feed entry, as the feed lays it out
+fn entry_key(channel_id: &ChannelId, seq: u64) -> DhtKey {
+    let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+    let name = format!("{}:{}:{}", CHANNEL_FEED_NAMESPACE, group_id.to_hex(), seq);
+    DhtKey::hash(name.as_bytes())
+}
+
+/// Alice creates a channel and invites Bob
+async fn setup(alice: &ChannelManager, bob: &ChannelManager) -> ChannelId {
+    let channel_id = alice.create_channel("general".to_string(), false).await.unwrap();
+    let key_package = bob.generate_key_package().await.unwrap();
+    let (invite, _) = alice.create_invite(&channel_id, key_package).await.unwrap();
+    bob.join_channel(&invite).await.unwrap();
+    channel_id
+}
+
+#[tokio::test]
+async fn test_sync_delivers_sent_messages() {
+    let feed = Arc::new(ChannelFeed::new(DhtStorage::new()));
+    let (alice_dir, bob_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let alice = create_manager("alice", &alice_dir, feed.clone());
+    let bob = create_manager("bob", &bob_dir, feed);
+    let channel_id = setup(&alice, &bob).await;
+
+    alice.send_message(&channel_id, b"first").await.unwrap();
+    alice.send_message(&channel_id, b"second").await.unwrap();
+
+    let mut updates = bob.subscribe_messages();
+    let synced = bob.sync_channel(&channel_id).await.unwrap();
+    let bodies: Vec<_> = synced.iter().map(|m| m.body.clone()).collect();
+    assert_eq!(bodies, vec![b"first".to_vec(), b"second".to_vec()]);
+    assert!(synced.iter().all(|m| m.sender.0 == "alice@spacepanda.local"));
+    assert_eq!(updates.recv().await.unwrap().body, b"first");
+
+    // Both sides hold the message under the sender's ID
+    let sent = alice.get_messages(&channel_id, 10, None).await.unwrap();
+    assert!(sent.iter().any(|m| m.message_id == synced[0].message_id));
+
+    // Nothing new, and our own messages are never synced back to us
+    assert!(bob.sync_channel(&channel_id).await.unwrap().is_empty());
+    assert!(alice.sync_channel(&channel_id).await.unwrap().is_empty());
+
+    bob.send_message(&channel_id, b"reply").await.unwrap();
+    let synced = alice.sync_channel(&channel_id).await.unwrap();
+    assert_eq!(synced.len(), 1);
+    assert_eq!(synced[0].body, b"reply");
+}
+
+#[tokio::test]
+async fn test_sync_skips_duplicate_entries() {
+    let feed = Arc::new(ChannelFeed::new(DhtStorage::new()));
+    let (alice_dir, bob_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let alice = create_manager("alice", &alice_dir, feed.clone());
+    let bob = create_manager("bob", &bob_dir, feed.clone());
+    let channel_id = setup(&alice, &bob).await;
+
+    alice.send_message(&channel_id, b"once").await.unwrap();
+
+    // The same envelope stored again, e.g. by a retried append
+    let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+    let original = feed.fetch(&group_id, 0).remove(0);
+    feed.append(&group_id, &original.message_id, original.payload).unwrap();
+
+    let synced = bob.sync_channel(&channel_id).await.unwrap();
+    assert_eq!(synced.len(), 1);
+    assert_eq!(synced[0].message_id.0, original.message_id);
+    assert_eq!(bob.get_messages(&channel_id, 10, None).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_sync_picks_up_entries_missing_earlier() {
+    let storage = DhtStorage::new();
+    let feed = Arc::new(ChannelFeed::new(storage.clone()));
+    let (alice_dir, bob_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let alice = create_manager("alice", &alice_dir, feed.clone());
+    let bob = create_manager("bob", &bob_dir, feed);
+    let channel_id = setup(&alice, &bob).await;
+
+    for body in [b"one", b"two", b"six"] {
+        alice.send_message(&channel_id, body).await.unwrap();
+    }
+
+    // The middle entry hasn't reached this replica yet
+    let key = entry_key(&channel_id, 1);
+    let late = storage.get(&key).unwrap();
+    storage.delete(&key).unwrap();
+
+    let synced = bob.sync_channel(&channel_id).await.unwrap();
+    let bodies: Vec<_> = synced.iter().map(|m| m.body.clone()).collect();
+    assert_eq!(bodies, vec![b"one".to_vec(), b"six".to_vec()]);
+
+    storage.put(key, late).unwrap();
+    let synced = bob.sync_channel(&channel_id).await.unwrap();
+    assert_eq!(synced.len(), 1);
+    assert_eq!(synced[0].body, b"two");
+    assert!(bob.sync_channel(&channel_id).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_sync_waits_for_commit_of_later_epoch() {
+    let feed = Arc::new(ChannelFeed::new(DhtStorage::new()));
+    let (alice_dir, bob_dir, carol_dir) =
+        (TempDir::new().unwrap(), TempDir::new().unwrap(), TempDir::new().unwrap());
+    let alice = create_manager("alice", &alice_dir, feed.clone());
+    let bob = create_manager("bob", &bob_dir, feed.clone());
+    let carol = create_manager("carol", &carol_dir, feed);
+    let channel_id = setup(&alice, &bob).await;
+
+    // Adding Carol moves the channel to a new epoch Bob hasn't seen
+    let key_package = carol.generate_key_package().await.unwrap();
+    let (invite, commit) = alice.create_invite(&channel_id, key_package).await.unwrap();
+    carol.join_channel(&invite).await.unwrap();
+    alice.send_message(&channel_id, b"welcome carol").await.unwrap();
+
+    assert!(bob.sync_channel(&channel_id).await.unwrap().is_empty());
+    assert_eq!(carol.sync_channel(&channel_id).await.unwrap().len(), 1);
+
+    bob.process_commit(&commit.unwrap()).await.unwrap();
+    let synced = bob.sync_channel(&channel_id).await.unwrap();
+    assert_eq!(synced.len(), 1);
+    assert_eq!(synced[0].body, b"welcome carol");
+}
+
+/// An onion router for `origin_id` that knows three relays, running in-process
+///
+/// Cells are carried between the routers; whatever a relay delivers as a
+/// circuit exit is stored into `exit_feed`, which appends record under the
+/// exit instead of the origin.
+async fn circuit_network(origin_id: PeerId, exit_feed: Arc<ChannelFeed>) -> Arc<OnionRouter> {
+    let route_table = Arc::new(RouteTable::new());
+    let mut nodes = vec![(origin_id.clone(), route_table.clone(), None)];
+    for i in 1..=3u8 {
+        let onion_key = StaticSecret::from([i; 32]);
+        let mut relay =
+            PeerInfo::new(PeerId::from_bytes(vec![i; 32]), vec![format!("10.{}.0.1:9000", i)]);
+        relay.capabilities.push(Capability::Relay);
+        relay.overlay_pubkey = *PublicKey::from(&onion_key).as_bytes();
+        route_table
+            .handle_command(RouteTableCommand::InsertPeer(relay.clone()))
+            .await
+            .unwrap();
+        nodes.push((relay.peer_id, Arc::new(RouteTable::new()), Some(onion_key)));
+    }
+
+    let mut commands = HashMap::new();
+    let mut event_streams = Vec::new();
+    let mut origin = None;
+    for (id, table, onion_key) in nodes {
+        let (event_tx, event_rx) = mpsc::channel(100);
+        let mut router =
+            OnionRouter::new(OnionConfig::default(), table, event_tx).with_local_id(id.clone());
+        if let Some(onion_key) = onion_key {
+            router = router.with_onion_key(onion_key);
+        }
+        let router = Arc::new(router);
+        let (cmd_tx, cmd_rx) = mpsc::channel(100);
+        tokio::spawn(router.clone().run(cmd_rx));
+        if id == origin_id {
+            origin = Some(router);
+        }
+        commands.insert(id.clone(), cmd_tx);
+        event_streams.push((id, event_rx));
+    }
+
+    let commands = Arc::new(commands);
+    for (id, mut event_rx) in event_streams {
+        let (commands, exit_feed) = (commands.clone(), exit_feed.clone());
+        tokio::spawn(async move {
+            while let Some(event) = event_rx.recv().await {
+                match event {
+                    OnionEvent::CellForward { next_peer, cell } => {
+                        let _ = commands[&next_peer]
+                            .send(OnionCommand::HandleCell { from: id.clone(), cell })
+                            .await;
+                    }
+                    OnionEvent::DeliverLocal { envelope } => {
+                        FeedPut::from_envelope(&envelope).unwrap().apply(&exit_feed).unwrap();
+                    }
+                    _ => {}
+                }
+            }
+        });
+    }
+    origin.unwrap()
+}
+
+#[tokio::test]
+async fn test_anonymity_mode_appends_through_circuit() {
+    let feed = Arc::new(ChannelFeed::new(DhtStorage::new()));
+    let onion = circuit_network(PeerId::from_bytes(vec![200; 32]), feed.clone()).await;
+    let publisher = Arc::new(CircuitFeedPublisher::new(onion.clone(), 3));
+
+    let mut config = Config::default();
+    config.features.anonymity_mode = true;
+    let (alice_dir, bob_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    // Alice's own copy of the feed stays empty: her appends go out through the circuit
+    let alice_feed = Arc::new(ChannelFeed::new(DhtStorage::new()));
+    let alice = create_manager_with_config("alice", &alice_dir, alice_feed.clone(), config)
+        .with_feed_publisher(publisher);
+    let bob = create_manager("bob", &bob_dir, feed.clone());
+    let channel_id = setup(&alice, &bob).await;
+
+    alice.send_message(&channel_id, b"from nowhere").await.unwrap();
+    let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while feed.head(&group_id) == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("The exit should store the entry");
+    assert_eq!(alice_feed.head(&group_id), 0);
+    assert_eq!(onion.circuit_count().await, 1);
+
+    let synced = bob.sync_channel(&channel_id).await.unwrap();
+    assert_eq!(synced.len(), 1);
+    assert_eq!(synced[0].body, b"from nowhere");
+    assert_eq!(synced[0].sender.0, "alice@spacepanda.local");
+
+    // Later appends reuse the circuit
+    alice.send_message(&channel_id, b"again").await.unwrap();
+    assert_eq!(onion.circuit_count().await, 1);
+}
+
+#[tokio::test]
+async fn test_feed_publisher_unused_without_anonymity_mode() {
+    let feed = Arc::new(ChannelFeed::new(DhtStorage::new()));
+    let onion = circuit_network(PeerId::from_bytes(vec![200; 32]), feed.clone()).await;
+    let publisher = Arc::new(CircuitFeedPublisher::new(onion.clone(), 3));
+
+    let (alice_dir, bob_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let alice = create_manager("alice", &alice_dir, feed.clone()).with_feed_publisher(publisher);
+    let bob = create_manager("bob", &bob_dir, feed);
+    let channel_id = setup(&alice, &bob).await;
+
+    alice.send_message(&channel_id, b"direct").await.unwrap();
+    assert_eq!(onion.circuit_count().await, 0);
+    assert_eq!(bob.sync_channel(&channel_id).await.unwrap()[0].body, b"direct");
+}
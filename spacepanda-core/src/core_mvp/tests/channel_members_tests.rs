@@ -0,0 +1,84 @@
+//! Tests for the replicated channel member list
+//!
+//! Every replica updates its member set from the MLS roster whenever it
+//! processes a membership change, so `list_members` agrees across members.
+
+use super::common::create_manager;
+use crate::core_mvp::channel_manager::ChannelManager;
+use crate::core_mvp::errors::MvpError;
+use crate::core_store::model::types::ChannelId;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+/// User IDs of a channel's members, sorted
+async fn member_ids(manager: &ChannelManager, channel_id: &ChannelId) -> Vec<String> {
+    let mut ids: Vec<String> = manager
+        .list_members(channel_id)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|m| m.user_id.0)
+        .collect();
+    ids.sort();
+    ids
+}
+
+#[tokio::test]
+async fn test_creator_is_listed() {
+    let dir = TempDir::new().unwrap();
+    let alice = Arc::new(create_manager("alice", &dir));
+    let channel_id = alice.create_channel("general".to_string(), false).await.unwrap();
+
+    let members = alice.list_members(&channel_id).await.unwrap();
+    assert_eq!(members.len(), 1);
+    assert_eq!(members[0].user_id, alice.identity().user_id);
+    assert!(!members[0].device_id.is_empty());
+
+    let result = alice.list_members(&ChannelId::generate()).await;
+    assert!(matches!(result, Err(MvpError::ChannelNotFound(_))));
+}
+
+#[tokio::test]
+async fn test_members_follow_adds_and_removes() {
+    let (a, b, c) = (TempDir::new().unwrap(), TempDir::new().unwrap(), TempDir::new().unwrap());
+    let alice = Arc::new(create_manager("alice", &a));
+    let bob = Arc::new(create_manager("bob", &b));
+    let charlie = Arc::new(create_manager("charlie", &c));
+
+    let channel_id = alice.create_channel("team".to_string(), false).await.unwrap();
+
+    let bob_kp = bob.generate_key_package().await.unwrap();
+    let (invite, _) = alice.create_invite(&channel_id, bob_kp).await.unwrap();
+    bob.join_channel(&invite).await.unwrap();
+
+    let charlie_kp = charlie.generate_key_package().await.unwrap();
+    let (invite, commit) = alice.create_invite(&channel_id, charlie_kp).await.unwrap();
+    charlie.join_channel(&invite).await.unwrap();
+    bob.process_commit(&commit.unwrap()).await.unwrap();
+
+    let everyone = vec![
+        "alice@spacepanda.local".to_string(),
+        "bob@spacepanda.local".to_string(),
+        "charlie@spacepanda.local".to_string(),
+    ];
+    for member in [&alice, &bob, &charlie] {
+        assert_eq!(member_ids(member, &channel_id).await, everyone);
+    }
+
+    // Members are listed in join order
+    let listed = alice.list_members(&channel_id).await.unwrap();
+    assert_eq!(listed[0].user_id, alice.identity().user_id);
+    assert!(listed.windows(2).all(|w| w[0].joined_at <= w[1].joined_at));
+
+    // Removal is reflected by the remover and by whoever processes the commit
+    let commit = alice
+        .remove_member(&channel_id, bob.identity().user_id.0.as_bytes())
+        .await
+        .unwrap();
+    charlie.process_commit(&commit).await.unwrap();
+
+    let remaining =
+        vec!["alice@spacepanda.local".to_string(), "charlie@spacepanda.local".to_string()];
+    assert_eq!(member_ids(&alice, &channel_id).await, remaining);
+    assert_eq!(member_ids(&charlie, &channel_id).await, remaining);
+}
@@ -0,0 +1,121 @@
+//! Tests for channel metadata carried in the group context
+//!
+//! The name, topic and avatar hash are committed into the MLS group
+//! context by the admin. Members copy them to their store when they merge
+//! the commit, and reject such commits from anyone else.
+
+use super::common::{create_manager, create_manager_with, ManagerOptions};
+use crate::core_mls::channel_metadata::{ChannelMetadata, RetentionPolicy};
+use crate::core_mls::types::GroupId;
+use crate::core_mvp::channel_manager::ChannelManager;
+use crate::core_mvp::errors::MvpError;
+use crate::core_store::model::types::ChannelId;
+use tempfile::TempDir;
+
+/// Alice's channel with Bob and Carol in it
+async fn three_member_channel(
+    alice: &ChannelManager,
+    bob: &ChannelManager,
+    carol: &ChannelManager,
+) -> ChannelId {
+    let channel_id = alice.create_channel("general".to_string(), false).await.unwrap();
+
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.join_channel(&invite).await.unwrap();
+
+    let (invite, commit) = alice
+        .create_invite(&channel_id, carol.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.process_commit(&commit.unwrap()).await.unwrap();
+    carol.join_channel(&invite).await.unwrap();
+
+    channel_id
+}
+
+#[tokio::test]
+async fn test_admin_metadata_update_reaches_members() {
+    let dirs: Vec<TempDir> = (0..3).map(|_| TempDir::new().unwrap()).collect();
+    let alice = create_manager("alice", &dirs[0]);
+    let bob = create_manager("bob", &dirs[1]);
+    let carol = create_manager("carol", &dirs[2]);
+    let channel_id = three_member_channel(&alice, &bob, &carol).await;
+
+    let metadata = ChannelMetadata {
+        name: "release".to_string(),
+        topic: Some("Release planning for 0.4".to_string()),
+        avatar_hash: Some([7; 32]),
+        ..Default::default()
+    };
+    let commit = alice.update_channel_metadata(&channel_id, metadata).await.unwrap();
+    for member in [&bob, &carol] {
+        member.process_commit(&commit).await.unwrap();
+    }
+
+    for member in [&alice, &bob, &carol] {
+        let descriptor = member.get_channel(&channel_id).await.unwrap();
+        assert_eq!(descriptor.name, "release");
+        assert_eq!(descriptor.description.as_deref(), Some("Release planning for 0.4"));
+        assert_eq!(descriptor.avatar_hash, Some([7; 32]));
+    }
+
+    // Changing the topic keeps the name and avatar
+    let commit = alice.set_channel_topic(&channel_id, None).await.unwrap();
+    bob.process_commit(&commit).await.unwrap();
+    let descriptor = bob.get_channel(&channel_id).await.unwrap();
+    assert_eq!(descriptor.name, "release");
+    assert_eq!(descriptor.description, None);
+    assert_eq!(descriptor.avatar_hash, Some([7; 32]));
+}
+
+#[tokio::test]
+async fn test_non_admin_metadata_update_is_rejected() {
+    let dirs: Vec<TempDir> = (0..3).map(|_| TempDir::new().unwrap()).collect();
+    let alice = create_manager("alice", &dirs[0]);
+    let (bob, bob_mls) = create_manager_with("bob", &dirs[1], ManagerOptions::default());
+    let carol = create_manager("carol", &dirs[2]);
+    let channel_id = three_member_channel(&alice, &bob, &carol).await;
+
+    let result = bob.set_channel_topic(&channel_id, Some("bob was here".to_string())).await;
+    assert!(matches!(result, Err(MvpError::PermissionDenied { .. })));
+
+    // Going around the manager produces a commit nobody else accepts
+    let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+    let commit = bob_mls
+        .update_channel_metadata(&group_id, &ChannelMetadata::new("bobs-channel"))
+        .await
+        .unwrap();
+    for member in [&alice, &carol] {
+        assert!(member.process_commit(&commit).await.is_err());
+        assert_eq!(member.get_channel(&channel_id).await.unwrap().name, "general");
+    }
+
+    // The rejected commit left Carol in step with Alice
+    let ciphertext = alice.send_message(&channel_id, b"still here").await.unwrap();
+    assert_eq!(carol.receive_message(&ciphertext).await.unwrap(), b"still here");
+}
+
+#[tokio::test]
+async fn test_retention_policy_reaches_members() {
+    let dirs: Vec<TempDir> = (0..3).map(|_| TempDir::new().unwrap()).collect();
+    let alice = create_manager("alice", &dirs[0]);
+    let bob = create_manager("bob", &dirs[1]);
+    let carol = create_manager("carol", &dirs[2]);
+    let channel_id = three_member_channel(&alice, &bob, &carol).await;
+
+    let result = bob.set_retention(&channel_id, RetentionPolicy::KeepForever).await;
+    assert!(matches!(result, Err(MvpError::PermissionDenied { .. })));
+
+    let commit = alice.set_retention(&channel_id, RetentionPolicy::KeepDays(7)).await.unwrap();
+    for member in [&bob, &carol] {
+        member.process_commit(&commit).await.unwrap();
+    }
+    for member in [&alice, &bob, &carol] {
+        let channel = member.store().get_channel(&channel_id).unwrap().unwrap();
+        assert_eq!(channel.retention(), RetentionPolicy::KeepDays(7));
+        assert_eq!(channel.get_name().map(String::as_str), Some("general"));
+    }
+}
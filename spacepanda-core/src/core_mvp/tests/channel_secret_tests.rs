@@ -0,0 +1,65 @@
+//! Tests for deriving application secrets from a channel's epoch
+
+use super::common::create_manager;
+use crate::core_mls::errors::MlsError;
+use crate::core_mls::service::MAX_EXPORTED_SECRET_LEN;
+use crate::core_mvp::errors::MvpError;
+use crate::core_store::model::types::ChannelId;
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_members_derive_the_same_secret_per_epoch() {
+    let (alice_dir, bob_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let alice = create_manager("alice", &alice_dir);
+    let bob = create_manager("bob", &bob_dir);
+
+    let channel_id = alice.create_channel("voice".to_string(), false).await.unwrap();
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.join_channel(&invite).await.unwrap();
+
+    let secret = alice.derive_channel_secret(&channel_id, "voice", b"call-1", 32).await.unwrap();
+    assert_eq!(secret.len(), 32);
+    assert_eq!(
+        bob.derive_channel_secret(&channel_id, "voice", b"call-1", 32).await.unwrap(),
+        secret
+    );
+
+    // Label and context both separate secrets
+    let other_label = alice.derive_channel_secret(&channel_id, "files", b"call-1", 32).await;
+    let other_context = alice.derive_channel_secret(&channel_id, "voice", b"call-2", 32).await;
+    assert_ne!(other_label.unwrap(), secret);
+    assert_ne!(other_context.unwrap(), secret);
+
+    // A commit moves both members to a new secret
+    let commit = alice.rotate_keys(&channel_id).await.unwrap();
+    bob.process_commit(&commit).await.unwrap();
+    let rotated = alice.derive_channel_secret(&channel_id, "voice", b"call-1", 32).await.unwrap();
+    assert_ne!(rotated, secret);
+    assert_eq!(
+        bob.derive_channel_secret(&channel_id, "voice", b"call-1", 32).await.unwrap(),
+        rotated
+    );
+}
+
+#[tokio::test]
+async fn test_secret_length_is_bounded() {
+    let temp_dir = TempDir::new().unwrap();
+    let alice = create_manager("alice", &temp_dir);
+    let channel_id = alice.create_channel("voice".to_string(), false).await.unwrap();
+
+    for length in [0, MAX_EXPORTED_SECRET_LEN + 1] {
+        let result = alice.derive_channel_secret(&channel_id, "voice", b"", length).await;
+        assert!(matches!(result, Err(MvpError::Mls(MlsError::InvalidInput(_)))));
+    }
+    let secret = alice
+        .derive_channel_secret(&channel_id, "voice", b"", MAX_EXPORTED_SECRET_LEN)
+        .await;
+    assert_eq!(secret.unwrap().len(), MAX_EXPORTED_SECRET_LEN);
+
+    let unknown = ChannelId("not-a-channel".to_string());
+    let result = alice.derive_channel_secret(&unknown, "voice", b"", 32).await;
+    assert!(matches!(result, Err(MvpError::ChannelNotFound(_))));
+}
@@ -0,0 +1,243 @@
+//! Tests for the commit authorization policy
+//!
+//! Only the owner and admins may add or remove members, and only the owner
+//! may change who the admins are. A member who builds such a commit anyway
+//! (going around the `ChannelManager` checks) only forks itself off: every
+//! honest member rejects the commit and stays in its epoch.
+//!
+//! In a channel that belongs to a space, members' space roles decide what
+//! the `ChannelManager` lets them do, but never beyond what the commit
+//! policy accepts.
+
+use super::common::{create_manager, create_manager_with, ManagerOptions};
+use crate::core_mls::commit_policy::ChannelRoles;
+use crate::core_mls::types::GroupId;
+use crate::core_mvp::channel_manager::ChannelManager;
+use crate::core_mvp::errors::MvpError;
+use crate::core_mvp::network::IncomingMessage;
+use crate::core_router::PeerId;
+use crate::core_store::crdt::{AddId, LWWRegister, VectorClock};
+use crate::core_store::model::types::{ChannelId, SpaceId, Timestamp, UserId};
+use crate::core_store::model::{Permission, PermissionSet, Role, Space};
+use tempfile::TempDir;
+
+/// Alice's channel with Bob (leaf 1) and Carol (leaf 2) in it
+async fn three_member_channel(
+    alice: &ChannelManager,
+    bob: &ChannelManager,
+    carol: &ChannelManager,
+) -> ChannelId {
+    let channel_id = alice.create_channel("general".to_string(), false).await.unwrap();
+
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.join_channel(&invite).await.unwrap();
+
+    let (invite, commit) = alice
+        .create_invite(&channel_id, carol.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.process_commit(&commit.unwrap()).await.unwrap();
+    carol.join_channel(&invite).await.unwrap();
+
+    channel_id
+}
+
+fn group_of(channel_id: &ChannelId) -> GroupId {
+    GroupId::new(channel_id.0.as_bytes().to_vec())
+}
+
+/// A space holding the channel, owned by someone outside it, with a custom
+/// role for each of `roles`
+fn space_with_roles(
+    channel_id: &ChannelId,
+    roles: &[(&ChannelManager, &str, PermissionSet)],
+) -> Space {
+    let now = Timestamp::now();
+    let owner = UserId::generate();
+    let mut space =
+        Space::new(SpaceId::generate(), "Team".to_string(), owner, now, "node1".to_string());
+    let mut vc = VectorClock::new();
+    vc.increment("node1");
+    space
+        .channels
+        .add(channel_id.clone(), AddId::new("node1".to_string(), 1), vc.clone());
+
+    for (member, name, permissions) in roles {
+        let role = Role::with_permissions(name.to_string(), *permissions, "node1".to_string());
+        space
+            .roles
+            .put(name.to_string(), role, AddId::new(name.to_string(), 1), vc.clone());
+
+        let user_id = member.identity().user_id.clone();
+        let mut role_reg = LWWRegister::new();
+        role_reg.set(name.to_string(), now.as_millis(), "node1".to_string(), vc.clone());
+        space
+            .member_roles
+            .put(user_id.clone(), role_reg, AddId::new(user_id.0, 1), vc.clone());
+    }
+    space
+}
+
+#[tokio::test]
+async fn test_member_remove_commit_is_rejected() {
+    let dirs: Vec<TempDir> = (0..4).map(|_| TempDir::new().unwrap()).collect();
+    let (alice, alice_mls) = create_manager_with("alice", &dirs[0], ManagerOptions::default());
+    let (bob, bob_mls) = create_manager_with("bob", &dirs[1], ManagerOptions::default());
+    let (carol, carol_mls) = create_manager_with("carol", &dirs[2], ManagerOptions::default());
+    let dave = create_manager("dave", &dirs[3]);
+    let channel_id = three_member_channel(&alice, &bob, &carol).await;
+    let group_id = group_of(&channel_id);
+    let epoch = alice_mls.get_epoch(&group_id).await.unwrap();
+
+    // The manager refuses outright
+    let result = bob.remove_member(&channel_id, carol.identity().user_id.0.as_bytes()).await;
+    assert!(result.is_err());
+    let result = bob.create_invite(&channel_id, dave.generate_key_package().await.unwrap()).await;
+    assert!(matches!(result, Err(MvpError::PermissionDenied { .. })));
+
+    // Bob builds the commit himself; nobody else follows
+    let commit = bob_mls.remove_members(&group_id, vec![2]).await.unwrap();
+    assert!(alice.process_commit(&commit).await.is_err());
+    assert!(carol.process_commit(&commit).await.is_err());
+    assert_eq!(alice_mls.get_epoch(&group_id).await.unwrap(), epoch);
+    assert_eq!(carol_mls.get_epoch(&group_id).await.unwrap(), epoch);
+    assert_eq!(alice.get_channel_members(&channel_id).await.unwrap().len(), 3);
+
+    // Alice and Carol carry on in the epoch Bob tried to leave behind
+    let ciphertext = alice.send_message(&channel_id, b"carol, still there?").await.unwrap();
+    assert_eq!(carol.receive_message(&ciphertext).await.unwrap(), b"carol, still there?");
+}
+
+#[tokio::test]
+async fn test_promoted_admin_manages_members() {
+    let dirs: Vec<TempDir> = (0..4).map(|_| TempDir::new().unwrap()).collect();
+    let alice = create_manager("alice", &dirs[0]);
+    let bob = create_manager("bob", &dirs[1]);
+    let carol = create_manager("carol", &dirs[2]);
+    let dave = create_manager("dave", &dirs[3]);
+    let channel_id = three_member_channel(&alice, &bob, &carol).await;
+    let bob_identity = bob.identity().as_bytes();
+
+    let commit = alice.promote_admin(&channel_id, &bob_identity).await.unwrap();
+    bob.process_commit(&commit).await.unwrap();
+    carol.process_commit(&commit).await.unwrap();
+    assert!(carol.is_admin(&channel_id, &bob_identity).await.unwrap());
+
+    // Bob can now invite and remove
+    let (invite, commit) = bob
+        .create_invite(&channel_id, dave.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    let commit = commit.unwrap();
+    alice.process_commit(&commit).await.unwrap();
+    carol.process_commit(&commit).await.unwrap();
+    dave.join_channel(&invite).await.unwrap();
+
+    let commit = bob
+        .remove_member(&channel_id, dave.identity().user_id.0.as_bytes())
+        .await
+        .unwrap();
+    alice.process_commit(&commit).await.unwrap();
+    carol.process_commit(&commit).await.unwrap();
+    assert_eq!(alice.get_channel_members(&channel_id).await.unwrap().len(), 3);
+
+    // Only the owner changes roles
+    let carol_identity = carol.identity().as_bytes();
+    let result = bob.promote_admin(&channel_id, &carol_identity).await;
+    assert!(matches!(result, Err(MvpError::PermissionDenied { .. })));
+    assert!(matches!(
+        alice.promote_admin(&channel_id, &bob_identity).await,
+        Err(MvpError::InvalidOperation(_))
+    ));
+
+    let commit = alice.demote_admin(&channel_id, &bob_identity).await.unwrap();
+    bob.process_commit(&commit).await.unwrap();
+    carol.process_commit(&commit).await.unwrap();
+    assert!(!carol.is_admin(&channel_id, &bob_identity).await.unwrap());
+    let result = bob.remove_member(&channel_id, carol.identity().user_id.0.as_bytes()).await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_admin_cannot_change_roles() {
+    let dirs: Vec<TempDir> = (0..3).map(|_| TempDir::new().unwrap()).collect();
+    let (alice, alice_mls) = create_manager_with("alice", &dirs[0], ManagerOptions::default());
+    let (bob, bob_mls) = create_manager_with("bob", &dirs[1], ManagerOptions::default());
+    let carol = create_manager("carol", &dirs[2]);
+    let channel_id = three_member_channel(&alice, &bob, &carol).await;
+    let group_id = group_of(&channel_id);
+
+    let commit = alice.promote_admin(&channel_id, &bob.identity().as_bytes()).await.unwrap();
+    bob.process_commit(&commit).await.unwrap();
+    carol.process_commit(&commit).await.unwrap();
+
+    // Bob, an admin but not the owner, tries to take the channel over
+    let epoch = alice_mls.get_epoch(&group_id).await.unwrap();
+    let takeover = ChannelRoles::new(bob.identity().as_bytes());
+    let commit = bob_mls.update_channel_roles(&group_id, &takeover).await.unwrap();
+    assert!(alice.process_commit(&commit).await.is_err());
+    assert!(carol.process_commit(&commit).await.is_err());
+    assert_eq!(alice_mls.get_epoch(&group_id).await.unwrap(), epoch);
+    assert!(carol.is_admin(&channel_id, &alice.identity().as_bytes()).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_space_roles_decide_permissions() {
+    let dirs: Vec<TempDir> = (0..4).map(|_| TempDir::new().unwrap()).collect();
+    let alice = create_manager("alice", &dirs[0]);
+    let bob = create_manager("bob", &dirs[1]);
+    let carol = create_manager("carol", &dirs[2]);
+    let dave = create_manager("dave", &dirs[3]);
+    let channel_id = three_member_channel(&alice, &bob, &carol).await;
+
+    // Bob moderates the space; Alice, the channel's owner, is only a guest
+    let moderator = [Permission::Invite, Permission::DeleteMessages, Permission::EditChannel]
+        .into_iter()
+        .collect();
+    let space = space_with_roles(
+        &channel_id,
+        &[(&bob, "moderator", moderator), (&alice, "guest", Permission::Post.into())],
+    );
+    for manager in [&alice, &bob, &carol] {
+        manager.store().store_space(&space).unwrap();
+    }
+
+    let permissions = carol
+        .member_permissions(&channel_id, bob.identity().user_id.0.as_bytes())
+        .await
+        .unwrap();
+    assert_eq!(permissions, moderator);
+    let result = alice
+        .create_invite(&channel_id, dave.generate_key_package().await.unwrap())
+        .await;
+    assert!(matches!(result, Err(MvpError::PermissionDenied { .. })));
+    let result = alice.set_read_receipts(&channel_id, true).await;
+    assert!(matches!(result, Err(MvpError::PermissionDenied { .. })));
+
+    // Bob deletes Alice's message, and Carol accepts the deletion
+    bob.set_read_receipts(&channel_id, true).await.unwrap();
+    let ciphertext = alice.send_message(&channel_id, b"spam").await.unwrap();
+    let incoming = |ciphertext: Vec<u8>, sender: &ChannelManager| IncomingMessage {
+        channel_id: channel_id.clone(),
+        ciphertext,
+        sender_id: sender.identity().user_id.clone(),
+        sender_peer_id: PeerId(vec![1]),
+    };
+    let message = bob
+        .handle_incoming_message(incoming(ciphertext.clone(), &alice))
+        .await
+        .unwrap()
+        .unwrap();
+    carol.handle_incoming_message(incoming(ciphertext, &alice)).await.unwrap();
+    let delete = bob.delete_message(&channel_id, message.message_id.clone()).await.unwrap();
+    carol.handle_incoming_message(incoming(delete, &bob)).await.unwrap();
+    let history = carol.get_messages(&channel_id, 10, None).await.unwrap();
+    assert!(history.iter().any(|m| m.message_id == message.message_id && m.deleted));
+
+    // Members would reject Bob's adds, so his space role doesn't let him invite
+    let result = bob.create_invite(&channel_id, dave.generate_key_package().await.unwrap()).await;
+    assert!(matches!(result, Err(MvpError::PermissionDenied { .. })));
+}
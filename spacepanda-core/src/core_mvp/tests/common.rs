@@ -0,0 +1,77 @@
+//! Fixtures shared by the core_mvp tests
+
+use crate::config::Config;
+use crate::core_identity::keystore::file_keystore::FileKeystore;
+use crate::core_mls::service::MlsService;
+use crate::core_mls::types::MlsConfig;
+use crate::core_mvp::channel_manager::{ChannelManager, Identity};
+use crate::core_store::model::types::UserId;
+use crate::core_store::store::{LocalStore, LocalStoreConfig};
+use crate::shutdown::ShutdownCoordinator;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+/// Settings for [`create_manager_with`]
+#[derive(Default)]
+pub(super) struct ManagerOptions {
+    /// Node configuration
+    pub config: Config,
+
+    /// MLS settings, if not the defaults
+    pub mls_config: Option<MlsConfig>,
+
+    /// Device name, for users with several managers
+    pub device: Option<&'static str>,
+}
+
+/// User ID of a test user
+pub(super) fn user(name: &str) -> UserId {
+    UserId(format!("{}@spacepanda.local", name))
+}
+
+/// Create a test ChannelManager with default settings
+///
+/// MLS groups and the local store live under `temp_dir`.
+pub(super) fn create_manager(name: &str, temp_dir: &TempDir) -> ChannelManager {
+    create_manager_with(name, temp_dir, ManagerOptions::default()).0
+}
+
+/// Create a test ChannelManager, along with its MLS service
+///
+/// The MLS service keeps recovery snapshots, with their key in an
+/// unprotected keystore under `temp_dir`.
+pub(super) fn create_manager_with(
+    name: &str,
+    temp_dir: &TempDir,
+    options: ManagerOptions,
+) -> (ChannelManager, Arc<MlsService>) {
+    let config = Arc::new(options.config);
+    let shutdown = Arc::new(ShutdownCoordinator::new(Duration::from_secs(30)));
+    let keystore = FileKeystore::new(temp_dir.path().join("keystore"), None).unwrap();
+    let mls_service =
+        MlsService::with_storage(&config, shutdown, temp_dir.path().join("mls_groups"))
+            .and_then(|service| service.with_recovery_keystore(&keystore))
+            .unwrap();
+    let mls_service = Arc::new(match options.mls_config {
+        Some(mls_config) => mls_service.with_config(mls_config),
+        None => mls_service,
+    });
+
+    let store_config = LocalStoreConfig {
+        data_dir: temp_dir.path().to_path_buf(),
+        enable_encryption: false,
+        require_signatures: false,
+        authorized_keys: Vec::new(),
+        ..Default::default()
+    };
+    let store = Arc::new(LocalStore::new(store_config).unwrap());
+
+    let node_id = match options.device {
+        Some(device) => format!("node-{}-{}", name, device),
+        None => format!("node-{}", name),
+    };
+    let identity = Arc::new(Identity::new(user(name), name.to_string(), node_id));
+
+    (ChannelManager::new(mls_service.clone(), store, identity, config), mls_service)
+}
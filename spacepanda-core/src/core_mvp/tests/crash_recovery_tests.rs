@@ -0,0 +1,189 @@
+//! Tests for recovering groups from snapshots and the commit log
+//!
+//! A member restarts with an empty provider storage, as after a crash that
+//! lost it, and rebuilds the group with `MlsService::recover_group`. Files
+//! cut short at a random offset must be detected and worked around, never
+//! decoded.
+
+use super::common::{create_manager_with, ManagerOptions};
+use crate::core_mls::errors::MlsError;
+use crate::core_mls::service::MlsService;
+use crate::core_mls::types::{GroupId, MlsConfig};
+use crate::core_mvp::channel_manager::ChannelManager;
+use rand::Rng;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+/// Helper to create a test ChannelManager, along with its MLS service
+///
+/// The retention window is wide enough to keep two snapshots.
+fn create_manager(name: &str, temp_dir: &TempDir) -> (ChannelManager, Arc<MlsService>) {
+    let mls_config =
+        MlsConfig { snapshot_interval: 2, past_epoch_retention: 4, ..Default::default() };
+    create_manager_with_config(name, temp_dir, mls_config)
+}
+
+fn create_manager_with_config(
+    name: &str,
+    temp_dir: &TempDir,
+    mls_config: MlsConfig,
+) -> (ChannelManager, Arc<MlsService>) {
+    let options = ManagerOptions { mls_config: Some(mls_config), ..Default::default() };
+    create_manager_with(name, temp_dir, options)
+}
+
+/// Epoch, ratchet tree and an exported secret: equal only in the same state
+async fn group_state(mls: &MlsService, group_id: &GroupId) -> (u64, Vec<u8>, Vec<u8>) {
+    (
+        mls.get_epoch(group_id).await.unwrap(),
+        mls.export_ratchet_tree(group_id).await.unwrap(),
+        mls.export_secret(group_id, "recovery test", b"", 32).await.unwrap(),
+    )
+}
+
+fn recovery_dir(temp_dir: &TempDir, group_id: &GroupId) -> PathBuf {
+    temp_dir.path().join("mls_groups").join("recovery").join(group_id.to_hex())
+}
+
+/// Snapshot files of a group, newest first
+fn snapshot_files(temp_dir: &TempDir, group_id: &GroupId) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(recovery_dir(temp_dir, group_id))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.file_name().unwrap().to_str().unwrap().starts_with("snapshot-"))
+        .collect();
+    files.sort();
+    files.reverse();
+    files
+}
+
+/// Cut a file short at a random offset
+fn truncate_randomly(path: &Path) {
+    let bytes = std::fs::read(path).unwrap();
+    let len = rand::rng().random_range(0..bytes.len());
+    std::fs::write(path, &bytes[..len]).unwrap();
+}
+
+/// Alice and Bob in a channel, after three commits by Alice
+///
+/// Bob took a snapshot when joining and another two commits later, and has
+/// logged all three commits. Returns Bob's epoch.
+async fn setup(dirs: &[TempDir]) -> (Arc<MlsService>, GroupId, u64) {
+    let (alice, alice_mls) = create_manager("alice", &dirs[0]);
+    let (bob, bob_mls) = create_manager("bob", &dirs[1]);
+
+    let channel_id = alice.create_channel("general".to_string(), false).await.unwrap();
+    let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.join_channel(&invite).await.unwrap();
+    let joined = bob_mls.get_epoch(&group_id).await.unwrap();
+
+    for _ in 0..3 {
+        let commit = alice_mls.self_update(&group_id).await.unwrap();
+        bob_mls.process_message(&group_id, &commit).await.unwrap();
+    }
+    assert_eq!(snapshot_files(&dirs[1], &group_id).len(), 2);
+
+    (alice_mls, group_id, joined + 3)
+}
+
+#[tokio::test]
+async fn test_recover_after_restart() {
+    let dirs: Vec<TempDir> = (0..2).map(|_| TempDir::new().unwrap()).collect();
+    let (alice_mls, group_id, epoch) = setup(&dirs).await;
+
+    // Bob restarts with nothing in the provider storage
+    let (_bob, bob_mls) = create_manager("bob", &dirs[1]);
+    assert!(bob_mls.get_epoch(&group_id).await.is_err());
+
+    assert_eq!(bob_mls.recover_group(&group_id).await.unwrap(), epoch);
+    assert_eq!(group_state(&bob_mls, &group_id).await, group_state(&alice_mls, &group_id).await);
+
+    // The recovered group keeps following the channel
+    let commit = alice_mls.self_update(&group_id).await.unwrap();
+    bob_mls.process_message(&group_id, &commit).await.unwrap();
+    assert_eq!(group_state(&bob_mls, &group_id).await, group_state(&alice_mls, &group_id).await);
+}
+
+#[tokio::test]
+async fn test_recover_skips_truncated_snapshot() {
+    let dirs: Vec<TempDir> = (0..2).map(|_| TempDir::new().unwrap()).collect();
+    let (alice_mls, group_id, epoch) = setup(&dirs).await;
+    truncate_randomly(&snapshot_files(&dirs[1], &group_id)[0]);
+
+    // The older snapshot and the whole log get Bob back to the same state
+    let (_bob, bob_mls) = create_manager("bob", &dirs[1]);
+    assert_eq!(bob_mls.recover_group(&group_id).await.unwrap(), epoch);
+    assert_eq!(group_state(&bob_mls, &group_id).await, group_state(&alice_mls, &group_id).await);
+}
+
+#[tokio::test]
+async fn test_recover_from_truncated_log() {
+    let dirs: Vec<TempDir> = (0..2).map(|_| TempDir::new().unwrap()).collect();
+    let (_, group_id, epoch) = setup(&dirs).await;
+    truncate_randomly(&snapshot_files(&dirs[1], &group_id)[0]);
+    truncate_randomly(&recovery_dir(&dirs[1], &group_id).join("commits.log"));
+
+    // Replay stops where the log was cut
+    let (_bob, bob_mls) = create_manager("bob", &dirs[1]);
+    let recovered = bob_mls.recover_group(&group_id).await.unwrap();
+    assert!((epoch - 3..=epoch).contains(&recovered));
+    assert_eq!(bob_mls.get_epoch(&group_id).await.unwrap(), recovered);
+}
+
+#[tokio::test]
+async fn test_no_usable_snapshot() {
+    let dirs: Vec<TempDir> = (0..2).map(|_| TempDir::new().unwrap()).collect();
+    let (_, group_id, _) = setup(&dirs).await;
+    for snapshot in snapshot_files(&dirs[1], &group_id) {
+        truncate_randomly(&snapshot);
+    }
+
+    let (_bob, bob_mls) = create_manager("bob", &dirs[1]);
+    assert!(matches!(
+        bob_mls.recover_group(&group_id).await,
+        Err(MlsError::PersistenceError(_))
+    ));
+    assert!(bob_mls.get_epoch(&group_id).await.is_err());
+}
+
+#[tokio::test]
+async fn test_snapshots_stay_within_retention_window() {
+    let dirs: Vec<TempDir> = (0..2).map(|_| TempDir::new().unwrap()).collect();
+    let mls_config =
+        MlsConfig { snapshot_interval: 16, past_epoch_retention: 1, ..Default::default() };
+    let (alice, alice_mls) = create_manager_with_config("alice", &dirs[0], mls_config.clone());
+    let (bob, bob_mls) = create_manager_with_config("bob", &dirs[1], mls_config.clone());
+
+    let channel_id = alice.create_channel("general".to_string(), false).await.unwrap();
+    let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.join_channel(&invite).await.unwrap();
+
+    // Snapshots are due long after their epoch left the window
+    for _ in 0..5 {
+        let commit = alice_mls.self_update(&group_id).await.unwrap();
+        bob_mls.process_message(&group_id, &commit).await.unwrap();
+        let epoch = bob_mls.get_epoch(&group_id).await.unwrap();
+        let files = snapshot_files(&dirs[1], &group_id);
+        assert!(!files.is_empty());
+        for file in files {
+            let name = file.file_name().unwrap().to_str().unwrap();
+            let snapshot_epoch: u64 = name.strip_prefix("snapshot-").unwrap().parse().unwrap();
+            assert!(snapshot_epoch + 1 >= epoch);
+        }
+    }
+
+    // And still get Bob back
+    let (_bob, bob_mls) = create_manager_with_config("bob", &dirs[1], mls_config);
+    let epoch = bob_mls.recover_group(&group_id).await.unwrap();
+    assert_eq!(group_state(&bob_mls, &group_id).await, group_state(&alice_mls, &group_id).await);
+    assert_eq!(epoch, alice_mls.get_epoch(&group_id).await.unwrap());
+}
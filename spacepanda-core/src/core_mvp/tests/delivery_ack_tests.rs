@@ -0,0 +1,179 @@
+//! Tests for delivery acknowledgements
+//!
+//! Alice writes to Bob and Carol while Carol is offline. Bob's client
+//! acknowledges what it decrypts in batches and later sends a read
+//! receipt; Carol's acknowledges once they are back. Alice's copy of each
+//! message tracks every recipient from pending to delivered to read, and
+//! Dave, who joins afterwards, is never counted for the earlier messages.
+
+use super::common::{create_manager_with, user, ManagerOptions};
+use crate::core_mls::padding::PaddingStrategy;
+use crate::core_mls::service::MlsService;
+use crate::core_mls::types::GroupId;
+use crate::core_mvp::channel_manager::{encode_read_receipt, ChannelManager};
+use crate::core_mvp::events::{ChannelEvent, ChannelEventKind};
+use crate::core_mvp::network::IncomingMessage;
+use crate::core_mvp::types::DeliverySummary;
+use crate::core_router::PeerId;
+use crate::core_store::model::types::{ChannelId, MessageId, UserId};
+use crate::core_store::store::DeliveryState;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::sync::broadcast;
+
+const ACK_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Helper to create a test ChannelManager, along with its MLS service
+fn create_manager(name: &str, temp_dir: &TempDir) -> (ChannelManager, Arc<MlsService>) {
+    let (manager, mls_service) = create_manager_with(name, temp_dir, ManagerOptions::default());
+    (manager.with_ack_interval(ACK_INTERVAL), mls_service)
+}
+
+fn incoming(channel_id: &ChannelId, sender: &str, ciphertext: Vec<u8>) -> IncomingMessage {
+    IncomingMessage {
+        channel_id: channel_id.clone(),
+        ciphertext,
+        sender_id: user(sender),
+        sender_peer_id: PeerId(vec![1]),
+    }
+}
+
+/// Delivery updates emitted so far, as (member, message, summary)
+fn delivery_updates(
+    events: &mut broadcast::Receiver<ChannelEvent>,
+) -> Vec<(UserId, MessageId, DeliverySummary)> {
+    std::iter::from_fn(|| events.try_recv().ok())
+        .filter_map(|event| match event.kind {
+            ChannelEventKind::DeliveryUpdated { message_id, summary } => {
+                Some((event.actor?, message_id, summary))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Summary of one of Alice's messages as `get_messages` reports it
+async fn summary(
+    alice: &ChannelManager,
+    channel_id: &ChannelId,
+    id: &MessageId,
+) -> DeliverySummary {
+    let history = alice.get_messages(channel_id, 10, None).await.unwrap();
+    let message = history.iter().find(|m| &m.message_id == id).unwrap();
+    message.delivery.unwrap()
+}
+
+fn pending(recipients: usize, delivered: usize, read: usize) -> DeliverySummary {
+    DeliverySummary { state: DeliveryState::Pending, recipients, delivered, read }
+}
+
+#[tokio::test]
+async fn test_three_members_with_one_offline() {
+    let dirs: Vec<_> = (0..4).map(|_| TempDir::new().unwrap()).collect();
+    let (alice, _) = create_manager("alice", &dirs[0]);
+    let (bob, bob_mls) = create_manager("bob", &dirs[1]);
+    let (carol, _) = create_manager("carol", &dirs[2]);
+    let (dave, _) = create_manager("dave", &dirs[3]);
+
+    let channel_id = alice.create_channel("team".to_string(), false).await.unwrap();
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.join_channel(&invite).await.unwrap();
+    let (invite, commit) = alice
+        .create_invite(&channel_id, carol.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    carol.join_channel(&invite).await.unwrap();
+    bob.process_commit(&commit.unwrap()).await.unwrap();
+    let mut alice_events = alice.subscribe_events();
+
+    // Carol is offline; only Bob gets the first message
+    let first = alice.send_message(&channel_id, b"standup in five").await.unwrap();
+    let first_id = MessageId::from_ciphertext(&first);
+    assert_eq!(summary(&alice, &channel_id, &first_id).await, pending(2, 0, 0));
+    bob.handle_incoming_message(incoming(&channel_id, "alice", first.clone()))
+        .await
+        .unwrap();
+    let acks = bob.flush_acks().await;
+    assert_eq!(acks.len(), 1);
+
+    // A second message inside the interval waits for the next bundle
+    let second = alice.send_message(&channel_id, b"room 4").await.unwrap();
+    let second_id = MessageId::from_ciphertext(&second);
+    bob.handle_incoming_message(incoming(&channel_id, "alice", second.clone()))
+        .await
+        .unwrap();
+    assert!(bob.flush_acks().await.is_empty());
+    tokio::time::sleep(ACK_INTERVAL).await;
+    let later = bob.flush_acks().await;
+    assert_eq!(later.len(), 1);
+
+    for (_, ack) in acks.into_iter().chain(later) {
+        let handled = alice.handle_incoming_message(incoming(&channel_id, "bob", ack)).await;
+        assert!(handled.unwrap().is_none(), "acknowledgements aren't chat messages");
+    }
+    // Acknowledgements aren't acknowledged
+    tokio::time::sleep(ACK_INTERVAL).await;
+    assert!(alice.flush_acks().await.is_empty());
+
+    let updates = delivery_updates(&mut alice_events);
+    assert_eq!(
+        updates,
+        vec![
+            (user("bob"), first_id.clone(), pending(2, 1, 0)),
+            (user("bob"), second_id.clone(), pending(2, 1, 0)),
+        ]
+    );
+    assert_eq!(summary(&alice, &channel_id, &first_id).await, pending(2, 1, 0));
+    assert_eq!(
+        alice.delivery_receipts(&channel_id, &first_id).unwrap(),
+        vec![(user("bob"), DeliveryState::Delivered), (user("carol"), DeliveryState::Pending)]
+    );
+
+    // Bob reads up to the second message
+    let marker = bob.mark_read(&channel_id, second_id.clone()).await.unwrap();
+    let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+    let padded = PaddingStrategy::default().pad(&encode_read_receipt(&marker).unwrap()).unwrap();
+    let receipt = bob_mls.send_message(&group_id, &padded).await.unwrap();
+    alice
+        .handle_incoming_message(incoming(&channel_id, "bob", receipt))
+        .await
+        .unwrap();
+    assert_eq!(summary(&alice, &channel_id, &first_id).await, pending(2, 1, 1));
+    assert_eq!(summary(&alice, &channel_id, &second_id).await, pending(2, 1, 1));
+
+    // Carol comes back and catches up
+    for ciphertext in [first, second] {
+        carol
+            .handle_incoming_message(incoming(&channel_id, "alice", ciphertext))
+            .await
+            .unwrap();
+    }
+    let acks = carol.flush_acks().await;
+    assert_eq!(acks.len(), 1, "one bundle for both messages");
+    alice
+        .handle_incoming_message(incoming(&channel_id, "carol", acks[0].1.clone()))
+        .await
+        .unwrap();
+    let delivered =
+        DeliverySummary { state: DeliveryState::Delivered, recipients: 2, delivered: 2, read: 1 };
+    assert_eq!(summary(&alice, &channel_id, &first_id).await, delivered);
+    let updates = delivery_updates(&mut alice_events);
+    assert_eq!(updates.last(), Some(&(user("carol"), second_id.clone(), delivered)));
+
+    // Dave joins later and only counts for messages sent after that
+    let (invite, _) = alice
+        .create_invite(&channel_id, dave.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    dave.join_channel(&invite).await.unwrap();
+    let third = alice.send_message(&channel_id, b"welcome dave").await.unwrap();
+    let third_id = MessageId::from_ciphertext(&third);
+    assert_eq!(summary(&alice, &channel_id, &first_id).await, delivered);
+    assert_eq!(summary(&alice, &channel_id, &third_id).await, pending(3, 0, 0));
+    let receipts = alice.delivery_receipts(&channel_id, &first_id).unwrap();
+    assert!(receipts.iter().all(|(member, _)| member != &user("dave")));
+}
@@ -0,0 +1,123 @@
+//! Tests for keyed (deterministic) channel creation
+//!
+//! Re-running provisioning with the same namespace key must return the
+//! existing channel, and a deleted keyed channel's ID must stay reserved
+//! until its directory tombstone expires.
+
+use super::common;
+use crate::core_dht::DhtStorage;
+use crate::core_mvp::channel_directory::ChannelDirectory;
+use crate::core_mvp::channel_manager::ChannelManager;
+use crate::core_mvp::errors::MvpError;
+use crate::core_store::model::types::ChannelId;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+/// Helper to create a test ChannelManager, optionally with a channel directory
+fn create_manager(
+    name: &str,
+    temp_dir: &TempDir,
+    directory: Option<Arc<ChannelDirectory>>,
+) -> ChannelManager {
+    let manager = common::create_manager(name, temp_dir);
+    match directory {
+        Some(directory) => manager.with_channel_directory(directory),
+        None => manager,
+    }
+}
+
+#[tokio::test]
+async fn test_keyed_channel_creation_is_idempotent() {
+    let dir = TempDir::new().unwrap();
+    let bot = create_manager("bot", &dir, None);
+
+    let first = bot
+        .create_channel_with_key("project-42".to_string(), "project:42")
+        .await
+        .unwrap();
+    let again = bot.create_channel_with_key("renamed".to_string(), "project:42").await.unwrap();
+    let other = bot
+        .create_channel_with_key("project-43".to_string(), "project:43")
+        .await
+        .unwrap();
+
+    assert_eq!(first, again);
+    assert_ne!(first, other);
+    assert_eq!(first, ChannelId::derive(&bot.identity().user_id, "project:42"));
+    assert_eq!(bot.list_channels().await.unwrap().len(), 2);
+    assert_eq!(bot.get_channel(&first).await.unwrap().name, "project-42");
+
+    // The returned channel is fully usable
+    bot.send_message(&first, b"provisioned").await.unwrap();
+}
+
+#[tokio::test]
+async fn test_keyed_channels_are_scoped_to_creator() {
+    let (a, b) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let directory = Arc::new(ChannelDirectory::new(DhtStorage::new()));
+    let alice = create_manager("alice", &a, Some(directory.clone()));
+    let bob = create_manager("bob", &b, Some(directory));
+
+    let alice_channel = alice.create_channel_with_key("ops".to_string(), "ops").await.unwrap();
+    let bob_channel = bob.create_channel_with_key("ops".to_string(), "ops").await.unwrap();
+
+    // Bob didn't pick up Alice's listing; he got a channel of his own
+    assert_ne!(alice_channel, bob_channel);
+    assert_eq!(bob.list_channels().await.unwrap().len(), 1);
+
+    // Derived IDs never look like generated ones
+    assert!(alice_channel.is_derived());
+    assert!(!alice.create_channel("ops".to_string(), false).await.unwrap().is_derived());
+}
+
+#[tokio::test]
+async fn test_keyed_channel_found_via_directory() {
+    let (a, b) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let directory = Arc::new(ChannelDirectory::new(DhtStorage::new()));
+    let first_node = create_manager("bot", &a, Some(directory.clone()));
+
+    let channel_id = first_node.create_channel_with_key("ci".to_string(), "ci").await.unwrap();
+    let listing = directory.lookup(&channel_id).unwrap().unwrap();
+    assert_eq!(listing.creator, first_node.identity().user_id);
+    assert!(!listing.is_tombstone());
+
+    // Same identity on a node with no local state: the listing is found
+    // and no second group is created
+    let second_node = create_manager("bot", &b, Some(directory));
+    let found = second_node.create_channel_with_key("ci".to_string(), "ci").await.unwrap();
+    assert_eq!(found, channel_id);
+    assert!(second_node.list_channels().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_keyed_channel_recreation_waits_for_tombstone() {
+    let dir = TempDir::new().unwrap();
+    let directory = Arc::new(ChannelDirectory::new(DhtStorage::new()));
+    let bot = create_manager("bot", &dir, Some(directory.clone()));
+
+    let channel_id = bot.create_channel_with_key("build".to_string(), "build").await.unwrap();
+    bot.leave_channel(&channel_id).await.unwrap();
+    assert!(directory.lookup(&channel_id).unwrap().unwrap().is_tombstone());
+
+    let result = bot.create_channel_with_key("build".to_string(), "build").await;
+    assert!(matches!(result, Err(MvpError::ChannelExists(_))));
+}
+
+#[tokio::test]
+async fn test_keyed_channel_recreated_after_tombstone_expiry() {
+    let dir = TempDir::new().unwrap();
+    let directory =
+        Arc::new(ChannelDirectory::new(DhtStorage::new()).with_tombstone_ttl(Duration::ZERO));
+    let bot = create_manager("bot", &dir, Some(directory.clone()));
+
+    let channel_id = bot.create_channel_with_key("build".to_string(), "build").await.unwrap();
+    bot.leave_channel(&channel_id).await.unwrap();
+
+    // The tombstone has expired, so the same ID is handed out again
+    assert!(directory.lookup(&channel_id).unwrap().is_none());
+    let recreated = bot.create_channel_with_key("build".to_string(), "build").await.unwrap();
+    assert_eq!(recreated, channel_id);
+    assert!(!directory.lookup(&channel_id).unwrap().unwrap().is_tombstone());
+    bot.send_message(&recreated, b"fresh start").await.unwrap();
+}
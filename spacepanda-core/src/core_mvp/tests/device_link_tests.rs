@@ -0,0 +1,280 @@
+//! Tests for linking a second device to an identity
+//!
+//! A linked device joins each of its user's channels as another leaf of
+//! the same member, so it must decrypt what is sent after the link commit,
+//! and only devices the identity key authorized may be linked. A revoked
+//! device must lose access to everything sent after its removal.
+
+use super::common::{create_manager_with, ManagerOptions};
+use crate::core_identity::{
+    DeviceBundle, DeviceId, DeviceKeyBinding, DeviceMetadata, IdentityBundle, KeyType, Keypair,
+    MasterKey,
+};
+use crate::core_mvp::channel_manager::{ChannelManager, Identity};
+use crate::core_mvp::device_link::DeviceLinkRequest;
+use crate::core_mvp::errors::MvpError;
+use crate::core_mvp::network::IncomingMessage;
+use crate::core_router::PeerId;
+use crate::core_store::model::types::ChannelId;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+/// Helper to create a test ChannelManager for one of a user's devices
+fn create_manager(name: &str, device: &'static str, temp_dir: &TempDir) -> Arc<ChannelManager> {
+    let options = ManagerOptions { device: Some(device), ..Default::default() };
+    Arc::new(create_manager_with(name, temp_dir, options).0)
+}
+
+/// A new device's link request, signed with its own key
+async fn link_request(device: &ChannelManager, key_packages: usize) -> DeviceLinkRequest {
+    let device_kp = Keypair::generate(KeyType::Ed25519);
+    let metadata = DeviceMetadata::new(
+        DeviceId::from_pubkey(device_kp.public_key()),
+        "Laptop".to_string(),
+        &device.identity().node_id,
+    );
+    let bundle = DeviceBundle::for_new_device(&device_kp, metadata);
+
+    let mut packages = Vec::new();
+    for _ in 0..key_packages {
+        packages.push(device.generate_key_package().await.unwrap());
+    }
+    DeviceLinkRequest::new(&device_kp, bundle, packages)
+}
+
+/// The identity bundle of a manager's user
+fn identity_bundle(manager: &ChannelManager, master_key: &MasterKey) -> IdentityBundle {
+    IdentityBundle::new(
+        manager.identity().as_bytes(),
+        master_key.public_key().to_vec(),
+        Vec::new(),
+        master_key.keypair(),
+    )
+}
+
+/// Number of leaves a user has in a channel
+async fn leaves_of(manager: &ChannelManager, channel_id: &ChannelId, user: &Identity) -> usize {
+    let members = manager.get_channel_members(channel_id).await.unwrap();
+    members.iter().filter(|identity| identity.as_slice() == user.as_bytes()).count()
+}
+
+#[tokio::test]
+async fn test_linked_device_decrypts_after_link() {
+    let (a, b, c) = (TempDir::new().unwrap(), TempDir::new().unwrap(), TempDir::new().unwrap());
+    let phone = create_manager("alice", "phone", &a);
+    let laptop = create_manager("alice", "laptop", &b);
+    let bob = create_manager("bob", "phone", &c);
+
+    let general = phone.create_channel("general".to_string(), false).await.unwrap();
+    let notes = phone.create_channel("notes".to_string(), false).await.unwrap();
+    let (invite, _) = phone
+        .create_invite(&general, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.join_channel(&invite).await.unwrap();
+
+    // The phone holds the master key and authorizes the laptop
+    let master_key = MasterKey::generate();
+    let mut identity = identity_bundle(&phone, &master_key);
+    let mut request = link_request(&laptop, 4).await;
+    let binding = identity.authorize_device(&master_key, &request.device_bundle).unwrap();
+    request.device_bundle = request.device_bundle.with_binding(binding);
+    assert_eq!(identity.devices.len(), 1);
+
+    let linked = phone.link_device(&request, master_key.public_key()).await.unwrap();
+    assert_eq!(linked.invites.len(), 2);
+    assert!(linked.skipped.is_empty());
+
+    for invite in &linked.invites {
+        laptop.join_channel(invite).await.unwrap();
+    }
+    for (channel_id, commit) in &linked.commits {
+        if *channel_id == general {
+            bob.process_commit(commit).await.unwrap();
+        }
+    }
+    assert_eq!(leaves_of(&phone, &general, phone.identity()).await, 2);
+    assert_eq!(leaves_of(&bob, &general, phone.identity()).await, 2);
+
+    // Messages sent after the link commit decrypt on the new device
+    let sends = [(&phone, &general, b"from phone"), (&bob, &general, b"from bob!!")];
+    for (sender, channel_id, text) in sends.into_iter().chain([(&phone, &notes, b"note to me")]) {
+        let ciphertext = sender.send_message(channel_id, text).await.unwrap();
+        let message = laptop
+            .handle_incoming_message(IncomingMessage {
+                channel_id: channel_id.clone(),
+                ciphertext,
+                sender_id: sender.identity().user_id.clone(),
+                sender_peer_id: PeerId(vec![1]),
+            })
+            .await
+            .unwrap()
+            .expect("message should decrypt on the linked device");
+        assert_eq!(message.body, text);
+    }
+}
+
+#[tokio::test]
+async fn test_link_request_runs_out_of_key_packages() {
+    let (a, b) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let phone = create_manager("alice", "phone", &a);
+    let laptop = create_manager("alice", "laptop", &b);
+    for name in ["one", "two", "three"] {
+        phone.create_channel(name.to_string(), false).await.unwrap();
+    }
+
+    let master_key = MasterKey::generate();
+    let mut request = link_request(&laptop, 2).await;
+    let binding = identity_bundle(&phone, &master_key)
+        .authorize_device(&master_key, &request.device_bundle)
+        .unwrap();
+    request.device_bundle = request.device_bundle.with_binding(binding);
+
+    let linked = phone.link_device(&request, master_key.public_key()).await.unwrap();
+    assert_eq!(linked.invites.len(), 2);
+    assert_eq!(linked.skipped.len(), 1);
+}
+
+#[tokio::test]
+async fn test_unauthorized_devices_are_rejected() {
+    let (a, b, c) = (TempDir::new().unwrap(), TempDir::new().unwrap(), TempDir::new().unwrap());
+    let phone = create_manager("alice", "phone", &a);
+    let laptop = create_manager("alice", "laptop", &b);
+    let mallory = create_manager("mallory", "laptop", &c);
+    let channel_id = phone.create_channel("general".to_string(), false).await.unwrap();
+
+    let master_key = MasterKey::generate();
+    let request = link_request(&laptop, 1).await;
+    let link = |request: DeviceLinkRequest| {
+        let phone = phone.clone();
+        let public_key = master_key.public_key().to_vec();
+        async move { phone.link_device(&request, &public_key).await }
+    };
+
+    // No binding at all
+    assert!(matches!(link(request.clone()).await, Err(MvpError::Unauthorized(_))));
+
+    // A binding signed by some other master key
+    let intruder = MasterKey::generate();
+    let forged = DeviceKeyBinding::new(
+        &intruder,
+        request.device_bundle.device_metadata.device_id.clone(),
+        1,
+        request.device_bundle.device_public_key().to_vec(),
+    );
+    let mut unauthorized = request.clone();
+    unauthorized.device_bundle = unauthorized.device_bundle.with_binding(forged);
+    assert!(matches!(link(unauthorized).await, Err(MvpError::Unauthorized(_))));
+
+    let binding = identity_bundle(&phone, &master_key)
+        .authorize_device(&master_key, &request.device_bundle)
+        .unwrap();
+    let mut authorized = request.clone();
+    authorized.device_bundle = authorized.device_bundle.with_binding(binding);
+
+    // Key packages swapped after the device signed them
+    let mut swapped = authorized.clone();
+    swapped.key_packages = vec![mallory.generate_key_package().await.unwrap()];
+    assert!(matches!(link(swapped).await, Err(MvpError::Unauthorized(_))));
+
+    // Key packages signed by the device, but for another user
+    let device_kp = Keypair::generate(KeyType::Ed25519);
+    let metadata = DeviceMetadata::new(
+        DeviceId::from_pubkey(device_kp.public_key()),
+        "Laptop".to_string(),
+        &mallory.identity().node_id,
+    );
+    let bundle = DeviceBundle::for_new_device(&device_kp, metadata);
+    let binding = identity_bundle(&phone, &master_key)
+        .authorize_device(&master_key, &bundle)
+        .unwrap();
+    let foreign = DeviceLinkRequest::new(
+        &device_kp,
+        bundle.with_binding(binding),
+        vec![mallory.generate_key_package().await.unwrap()],
+    );
+    assert!(matches!(link(foreign).await, Err(MvpError::InvalidKeyPackage(_))));
+
+    // None of the rejected requests touched the channel
+    assert_eq!(leaves_of(&phone, &channel_id, phone.identity()).await, 1);
+    assert!(link(authorized).await.is_ok());
+    assert_eq!(leaves_of(&phone, &channel_id, phone.identity()).await, 2);
+}
+
+#[tokio::test]
+async fn test_revoked_device_loses_access() {
+    let (a, b, c) = (TempDir::new().unwrap(), TempDir::new().unwrap(), TempDir::new().unwrap());
+    let phone = create_manager("alice", "phone", &a);
+    let laptop = create_manager("alice", "laptop", &b);
+    let bob = create_manager("bob", "phone", &c);
+
+    let general = phone.create_channel("general".to_string(), false).await.unwrap();
+    let notes = phone.create_channel("notes".to_string(), false).await.unwrap();
+    let (invite, _) = phone
+        .create_invite(&general, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.join_channel(&invite).await.unwrap();
+
+    let master_key = MasterKey::generate();
+    let mut request = link_request(&laptop, 4).await;
+    let binding = identity_bundle(&phone, &master_key)
+        .authorize_device(&master_key, &request.device_bundle)
+        .unwrap();
+    request.device_bundle = request.device_bundle.with_binding(binding);
+    let linked = phone.link_device(&request, master_key.public_key()).await.unwrap();
+    for invite in &linked.invites {
+        laptop.join_channel(invite).await.unwrap();
+    }
+    for (channel_id, commit) in &linked.commits {
+        if *channel_id == general {
+            bob.process_commit(commit).await.unwrap();
+        }
+    }
+
+    // The laptop is stolen
+    let revoked = phone.revoke_device(&linked.device_id).await.unwrap();
+    let mut channels = revoked.channels.clone();
+    channels.sort_by(|x, y| x.0.cmp(&y.0));
+    let mut expected = vec![general.clone(), notes.clone()];
+    expected.sort_by(|x, y| x.0.cmp(&y.0));
+    assert_eq!(channels, expected);
+    assert_eq!(revoked.commits.len(), 2);
+    assert!(revoked.skipped.is_empty());
+    for (channel_id, commit) in &revoked.commits {
+        if *channel_id == general {
+            bob.process_commit(commit).await.unwrap();
+        }
+    }
+    assert_eq!(leaves_of(&phone, &general, phone.identity()).await, 1);
+    assert_eq!(leaves_of(&bob, &general, phone.identity()).await, 1);
+
+    // Traffic after the revocation is out of the laptop's reach
+    let deliver = |manager: Arc<ChannelManager>, from: &Arc<ChannelManager>, ciphertext| {
+        let incoming = IncomingMessage {
+            channel_id: general.clone(),
+            ciphertext,
+            sender_id: from.identity().user_id.clone(),
+            sender_peer_id: PeerId(vec![1]),
+        };
+        async move { manager.handle_incoming_message(incoming).await }
+    };
+    let ciphertext = phone.send_message(&general, b"after revoke").await.unwrap();
+    let result = deliver(laptop.clone(), &phone, ciphertext.clone()).await;
+    assert!(!matches!(result, Ok(Some(_))), "revoked device decrypted {:?}", result);
+
+    // The remaining members carry on
+    let message = deliver(bob.clone(), &phone, ciphertext).await.unwrap().unwrap();
+    assert_eq!(message.body, b"after revoke");
+    let reply = bob.send_message(&general, b"hi alice").await.unwrap();
+    let message = deliver(phone.clone(), &bob, reply).await.unwrap().unwrap();
+    assert_eq!(message.body, b"hi alice");
+
+    // The revocation is recorded, so the device can't simply be linked again
+    let relink = phone.link_device(&request, master_key.public_key()).await;
+    assert!(matches!(relink, Err(MvpError::Unauthorized(_))));
+
+    // Revoking a device that was never linked is an error
+    let unknown = DeviceId::from_pubkey(b"unknown device key");
+    assert!(matches!(phone.revoke_device(&unknown).await, Err(MvpError::DeviceNotFound(_))));
+}
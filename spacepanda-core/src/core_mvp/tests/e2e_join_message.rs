@@ -101,12 +101,10 @@ async fn test_e2e_list_channels() -> MvpResult<()> {
 
     // Verify channel properties
     let general = channels.iter().find(|c| c.name == "general").unwrap();
-    // TODO: is_public not yet stored in Channel model, always returns false
-    // assert!(!general.is_public);
+    assert!(!general.is_public);
 
     let random = channels.iter().find(|c| c.name == "random").unwrap();
-    // TODO: is_public not yet stored in Channel model
-    // assert!(random.is_public);
+    assert!(random.is_public);
 
     println!("  List channels test PASSED!");
     Ok(())
@@ -0,0 +1,84 @@
+//! End-to-end test for the two-step invite flow
+//!
+//! The invitee exports a key package on their own device, the inviter
+//! builds the invite from it, and only the exporting device can join:
+//! 1. Bob exports a key package
+//! 2. Alice creates an invite from it
+//! 3. Charlie (a different device) cannot join with Bob's invite
+//! 4. Bob joins and both can exchange messages
+//! 5. Malformed and expired key packages are rejected
+
+use super::common::{create_manager, create_manager_with, user, ManagerOptions};
+use crate::core_mvp::errors::MvpError;
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_invite_joinable_only_by_exporting_device() {
+    let (alice_dir, bob_dir, charlie_dir) =
+        (TempDir::new().unwrap(), TempDir::new().unwrap(), TempDir::new().unwrap());
+    let alice = create_manager("alice", &alice_dir);
+    let bob = create_manager("bob", &bob_dir);
+    let charlie = create_manager("charlie", &charlie_dir);
+
+    let channel_id = alice.create_channel("two-step".to_string(), false).await.unwrap();
+
+    // Bob exports his key package on his own device and hands it to Alice
+    let key_package = bob.generate_key_package().await.unwrap();
+    let _charlie_key_package = charlie.generate_key_package().await.unwrap();
+
+    let (invite, _commit) = alice.create_invite(&channel_id, key_package).await.unwrap();
+
+    // Charlie holds a key package too, but not the one the Welcome targets
+    assert!(charlie.join_channel(&invite).await.is_err());
+    assert!(charlie.list_channels().await.unwrap().is_empty());
+
+    let joined = bob.join_channel(&invite).await.unwrap();
+    assert_eq!(joined, channel_id);
+
+    let ciphertext = alice.send_message(&channel_id, b"hello bob").await.unwrap();
+    assert_eq!(bob.receive_message(&ciphertext).await.unwrap(), b"hello bob");
+
+    let reply = bob.send_message(&channel_id, b"hi alice").await.unwrap();
+    assert_eq!(alice.receive_message(&reply).await.unwrap(), b"hi alice");
+}
+
+#[tokio::test]
+async fn test_create_invite_rejects_malformed_key_package() {
+    let alice_dir = TempDir::new().unwrap();
+    let alice = create_manager("alice", &alice_dir);
+    let channel_id = alice.create_channel("strict".to_string(), false).await.unwrap();
+
+    let result = alice.create_invite(&channel_id, b"not a key package".to_vec()).await;
+    assert!(matches!(result, Err(MvpError::InvalidKeyPackage(_))));
+
+    // Trailing garbage after a valid key package is malformed as well
+    let bob_dir = TempDir::new().unwrap();
+    let bob = create_manager("bob", &bob_dir);
+    let mut key_package = bob.generate_key_package().await.unwrap();
+    key_package.extend_from_slice(b"junk");
+
+    let result = alice.create_invite(&channel_id, key_package).await;
+    assert!(matches!(result, Err(MvpError::InvalidKeyPackage(_))));
+
+    // The group is unchanged by rejected invites
+    assert_eq!(alice.get_channel_members(&channel_id).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_create_invite_rejects_expired_key_package() {
+    let (alice_dir, bob_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let alice = create_manager("alice", &alice_dir);
+    let (_bob, bob_mls) = create_manager_with("bob", &bob_dir, ManagerOptions::default());
+    let channel_id = alice.create_channel("strict".to_string(), false).await.unwrap();
+
+    let key_package = bob_mls
+        .generate_key_package_with_lifetime(user("bob").0.into_bytes(), 0)
+        .await
+        .unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+
+    match alice.create_invite(&channel_id, key_package).await {
+        Err(MvpError::InvalidKeyPackage(reason)) => assert!(reason.contains("expired")),
+        other => panic!("expected InvalidKeyPackage, got {:?}", other.map(|_| ())),
+    }
+}
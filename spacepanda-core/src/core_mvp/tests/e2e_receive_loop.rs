@@ -0,0 +1,173 @@
+//! E2E test: incoming message pipeline
+//!
+//! Exercises the receive path used by `spacepanda listen`:
+//! - Incoming messages are decrypted and published to subscribers
+//! - Messages from an epoch whose commit hasn't arrived yet stay queued
+//!   in the store's inbox and decrypt once the commit is processed
+//! - Queued messages survive a restart and are processed on startup
+
+use crate::config::Config;
+use crate::core_mls::service::MlsService;
+use crate::core_mvp::channel_manager::{ChannelManager, Identity};
+use crate::core_mvp::network::IncomingMessage;
+use crate::core_router::PeerId;
+use crate::core_store::model::types::{ChannelId, UserId};
+use crate::core_store::store::inbox::PendingInbox;
+use crate::core_store::store::local_store::{LocalStore, LocalStoreConfig};
+use crate::shutdown::ShutdownCoordinator;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::{tempdir, TempDir};
+
+fn store_at(dir: &Path) -> Arc<LocalStore> {
+    let store_config = LocalStoreConfig {
+        data_dir: dir.to_path_buf(),
+        enable_encryption: false,
+        require_signatures: false,
+        authorized_keys: Vec::new(),
+        ..Default::default()
+    };
+    Arc::new(LocalStore::new(store_config).unwrap())
+}
+
+fn manager_with(
+    name: &str,
+    mls_service: Arc<MlsService>,
+    store: Arc<LocalStore>,
+) -> Arc<ChannelManager> {
+    let user_id = UserId(format!("{}@spacepanda.local", name));
+    let identity = Arc::new(Identity::new(user_id, name.to_string(), format!("{}-node", name)));
+    Arc::new(ChannelManager::new(mls_service, store, identity, Arc::new(Config::default())))
+}
+
+fn create_test_manager(name: &str) -> (Arc<ChannelManager>, Arc<MlsService>, TempDir) {
+    let temp_dir = tempdir().unwrap();
+    let shutdown = Arc::new(ShutdownCoordinator::new(Duration::from_secs(5)));
+    let mls_service = Arc::new(MlsService::new(&Config::default(), shutdown));
+    let manager = manager_with(name, mls_service.clone(), store_at(temp_dir.path()));
+    (manager, mls_service, temp_dir)
+}
+
+fn incoming(channel_id: &ChannelId, sender: &str, ciphertext: Vec<u8>) -> IncomingMessage {
+    IncomingMessage {
+        channel_id: channel_id.clone(),
+        ciphertext,
+        sender_id: UserId(format!("{}@spacepanda.local", sender)),
+        sender_peer_id: PeerId(vec![1]),
+    }
+}
+
+/// Alice creates a channel and Bob joins it
+async fn alice_and_bob() -> (
+    (Arc<ChannelManager>, TempDir),
+    (Arc<ChannelManager>, Arc<MlsService>, TempDir),
+    ChannelId,
+) {
+    let (alice, _, alice_dir) = create_test_manager("alice");
+    let (bob, bob_mls, bob_dir) = create_test_manager("bob");
+
+    let channel_id = alice.create_channel("general".to_string(), false).await.unwrap();
+    let bob_kp = bob.generate_key_package().await.unwrap();
+    let (invite, _) = alice.create_invite(&channel_id, bob_kp).await.unwrap();
+    bob.join_channel(&invite).await.unwrap();
+
+    ((alice, alice_dir), (bob, bob_mls, bob_dir), channel_id)
+}
+
+#[tokio::test]
+async fn test_incoming_messages_are_published() {
+    let ((alice, _alice_dir), (bob, _, _bob_dir), channel_id) = alice_and_bob().await;
+    let mut subscriber = bob.subscribe_messages();
+
+    let ciphertext = alice.send_message(&channel_id, b"hello bob").await.unwrap();
+    let message = bob
+        .handle_incoming_message(incoming(&channel_id, "alice", ciphertext))
+        .await
+        .unwrap()
+        .expect("message should decrypt");
+
+    assert_eq!(message.body, b"hello bob");
+    assert_eq!(message.sender.0, "alice@spacepanda.local");
+    assert_eq!(subscriber.recv().await.unwrap(), message);
+    assert_eq!(bob.get_rendered_messages(&channel_id).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_message_from_future_epoch_waits_for_commit() {
+    let ((alice, _alice_dir), (bob, _, _bob_dir), channel_id) = alice_and_bob().await;
+    let (carol, _, _carol_dir) = create_test_manager("carol");
+
+    // Alice adds Carol; Bob receives the next message before the commit
+    let carol_kp = carol.generate_key_package().await.unwrap();
+    let (_, commit) = alice.create_invite(&channel_id, carol_kp).await.unwrap();
+    let ciphertext = alice.send_message(&channel_id, b"welcome carol").await.unwrap();
+
+    let queued = bob.handle_incoming_message(incoming(&channel_id, "alice", ciphertext)).await;
+    assert!(queued.unwrap().is_none());
+
+    bob.process_commit(&commit.expect("add should produce a commit")).await.unwrap();
+    let decrypted = bob.process_pending(&channel_id).await.unwrap();
+    assert_eq!(decrypted.len(), 1);
+    assert_eq!(decrypted[0].body, b"welcome carol");
+
+    // Nothing left to retry
+    assert!(bob.process_pending(&channel_id).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_pending_messages_survive_restart() {
+    let ((alice, _alice_dir), (bob, bob_mls, bob_dir), channel_id) = alice_and_bob().await;
+
+    // The envelope is recorded in Bob's inbox while his client is not running
+    drop(bob);
+    let ciphertext = alice.send_message(&channel_id, b"while you were away").await.unwrap();
+    PendingInbox::open(bob_dir.path().join("inbox.bin"))
+        .unwrap()
+        .enqueue(channel_id.clone(), UserId("alice@spacepanda.local".to_string()), ciphertext)
+        .unwrap();
+
+    let bob = manager_with("bob", bob_mls, store_at(bob_dir.path()));
+    let mut subscriber = bob.subscribe_messages();
+
+    let caught_up = bob.process_pending(&channel_id).await.unwrap();
+    assert_eq!(caught_up.len(), 1);
+    assert_eq!(caught_up[0].body, b"while you were away");
+    assert_eq!(subscriber.recv().await.unwrap().body, b"while you were away");
+}
+
+#[tokio::test]
+async fn test_processor_keeps_channels_independent() {
+    let ((alice, _alice_dir), (bob, _, _bob_dir), general) = alice_and_bob().await;
+    let random = alice.create_channel("random".to_string(), false).await.unwrap();
+    let bob_kp = bob.generate_key_package().await.unwrap();
+    let (invite, _) = alice.create_invite(&random, bob_kp).await.unwrap();
+    bob.join_channel(&invite).await.unwrap();
+
+    let mut subscriber = bob.subscribe_messages();
+    let (messages_tx, messages_rx) = tokio::sync::mpsc::channel(16);
+    let processor = bob.clone().spawn_message_processor(messages_rx);
+
+    // Undecryptable input on one channel doesn't hold up the other
+    for (channel_id, body) in [(&general, b"in general"), (&random, b"in random!")] {
+        messages_tx.send(incoming(channel_id, "alice", vec![0xff; 64])).await.unwrap();
+        let ciphertext = alice.send_message(channel_id, body).await.unwrap();
+        messages_tx.send(incoming(channel_id, "alice", ciphertext)).await.unwrap();
+    }
+
+    let mut bodies = Vec::new();
+    for _ in 0..2 {
+        let message = tokio::time::timeout(Duration::from_secs(5), subscriber.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        bodies.push(message.body);
+    }
+    bodies.sort();
+    assert_eq!(bodies, vec![b"in general".to_vec(), b"in random!".to_vec()]);
+
+    // Errors are not panics; neither channel is quarantined
+    assert!(!bob.is_quarantined(&general).await);
+    assert!(!bob.is_quarantined(&random).await);
+    processor.abort();
+}
@@ -0,0 +1,175 @@
+//! Tests for typing indicators and presence
+//!
+//! Ephemeral messages reach the other members as events only: they never
+//! show up in history, and they expire from the channel feed quickly.
+
+use super::common::{create_manager_with, ManagerOptions};
+use crate::core_dht::{ChannelFeed, DhtStorage};
+use crate::core_mls::padding::PaddingStrategy;
+use crate::core_mls::service::MlsService;
+use crate::core_mls::types::GroupId;
+use crate::core_mvp::channel_manager::ChannelManager;
+use crate::core_mvp::ephemeral::{
+    EphemeralKind, EphemeralMessage, EPHEMERAL_FEED_TTL, EPHEMERAL_MAX_AGE,
+};
+use crate::core_mvp::events::{ChannelEvent, ChannelEventKind};
+use crate::core_mvp::network::IncomingMessage;
+use crate::core_router::PeerId;
+use crate::core_store::model::types::{ChannelId, Timestamp};
+use std::sync::Arc;
+use tempfile::TempDir;
+use tokio::sync::broadcast;
+
+/// Helper to create a test ChannelManager sharing the given feed, along
+/// with its MLS service
+fn create_manager(
+    name: &str,
+    temp_dir: &TempDir,
+    feed: Arc<ChannelFeed>,
+) -> (Arc<ChannelManager>, Arc<MlsService>) {
+    let (manager, mls_service) = create_manager_with(name, temp_dir, ManagerOptions::default());
+    (Arc::new(manager.with_channel_feed(feed)), mls_service)
+}
+
+/// Alice and Bob in a channel with a feed
+async fn setup(
+    dirs: &[TempDir],
+    dht: &DhtStorage,
+) -> (Arc<ChannelManager>, Arc<ChannelManager>, Arc<MlsService>, ChannelId) {
+    let feed = Arc::new(ChannelFeed::new(dht.clone()));
+    let (alice, _) = create_manager("alice", &dirs[0], feed.clone());
+    let (bob, bob_mls) = create_manager("bob", &dirs[1], feed);
+
+    let channel_id = alice.create_channel("chat".to_string(), false).await.unwrap();
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.join_channel(&invite).await.unwrap();
+    (alice, bob, bob_mls, channel_id)
+}
+
+/// Hand a ciphertext from one manager to another
+async fn deliver(
+    to: &ChannelManager,
+    from: &ChannelManager,
+    channel_id: &ChannelId,
+    ciphertext: Vec<u8>,
+) {
+    let incoming = IncomingMessage {
+        channel_id: channel_id.clone(),
+        ciphertext,
+        sender_id: from.identity().user_id.clone(),
+        sender_peer_id: PeerId(vec![1]),
+    };
+    assert!(to.handle_incoming_message(incoming).await.unwrap().is_none());
+}
+
+/// Typing and presence events received so far
+fn ephemeral_events(events: &mut broadcast::Receiver<ChannelEvent>) -> Vec<ChannelEventKind> {
+    std::iter::from_fn(|| events.try_recv().ok())
+        .map(|event| event.kind)
+        .filter(|kind| matches!(kind, ChannelEventKind::Typing(_) | ChannelEventKind::Presence(_)))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_typing_reaches_members_as_events_only() {
+    let dirs: Vec<TempDir> = (0..2).map(|_| TempDir::new().unwrap()).collect();
+    let (alice, bob, _, channel_id) = setup(&dirs, &DhtStorage::new()).await;
+    let alice_id = alice.identity().user_id.clone();
+    let mut events = bob.subscribe_events();
+
+    let typing = alice.send_typing(&channel_id).await.unwrap().unwrap();
+    deliver(&bob, &alice, &channel_id, typing).await;
+    let heartbeat = alice.send_presence(&channel_id).await.unwrap();
+    deliver(&bob, &alice, &channel_id, heartbeat).await;
+
+    assert_eq!(
+        ephemeral_events(&mut events),
+        vec![
+            ChannelEventKind::Typing(alice_id.clone()),
+            ChannelEventKind::Presence(alice_id.clone())
+        ]
+    );
+    assert_eq!(bob.present_members(&channel_id).await, vec![alice_id]);
+
+    // Neither side keeps them
+    for manager in [&alice, &bob] {
+        assert!(manager.get_messages(&channel_id, 10, None).await.unwrap().is_empty());
+    }
+    assert!(bob.sync_channel(&channel_id).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_typing_is_rate_limited() {
+    let dirs: Vec<TempDir> = (0..2).map(|_| TempDir::new().unwrap()).collect();
+    let (alice, _, _, channel_id) = setup(&dirs, &DhtStorage::new()).await;
+
+    assert!(alice.send_typing(&channel_id).await.unwrap().is_some());
+    assert!(alice.send_typing(&channel_id).await.unwrap().is_none());
+
+    // Heartbeats aren't throttled
+    alice.send_presence(&channel_id).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_ephemeral_feed_entries_expire_quickly() {
+    let dirs: Vec<TempDir> = (0..2).map(|_| TempDir::new().unwrap()).collect();
+    let dht = DhtStorage::new();
+    let (alice, bob, _, channel_id) = setup(&dirs, &dht).await;
+    let mut events = bob.subscribe_events();
+
+    alice.send_message(&channel_id, b"hello").await.unwrap();
+    alice.send_typing(&channel_id).await.unwrap().unwrap();
+
+    let mut ttls: Vec<u64> = dht
+        .entries()
+        .unwrap()
+        .into_iter()
+        .filter(|(_, value)| !value.data.is_empty())
+        .map(|(_, value)| value.ttl)
+        .collect();
+    ttls.sort();
+    assert_eq!(ttls[0], EPHEMERAL_FEED_TTL.as_secs());
+    assert!(ttls[1] > EPHEMERAL_FEED_TTL.as_secs());
+
+    // Synced from the feed, the indicator is an event and not history
+    let synced = bob.sync_channel(&channel_id).await.unwrap();
+    assert_eq!(synced.len(), 1);
+    assert_eq!(
+        ephemeral_events(&mut events),
+        vec![ChannelEventKind::Typing(alice.identity().user_id.clone())]
+    );
+}
+
+#[tokio::test]
+async fn test_stale_or_forged_signals_are_dropped() {
+    let dirs: Vec<TempDir> = (0..2).map(|_| TempDir::new().unwrap()).collect();
+    let (alice, bob, bob_mls, channel_id) = setup(&dirs, &DhtStorage::new()).await;
+    let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+    let mut events = alice.subscribe_events();
+
+    // Bob types as Alice, then sends a typing indicator from long ago
+    let forged = EphemeralMessage::new(
+        channel_id.clone(),
+        alice.identity().user_id.clone(),
+        EphemeralKind::Typing,
+    );
+    let mut stale = EphemeralMessage::new(
+        channel_id.clone(),
+        bob.identity().user_id.clone(),
+        EphemeralKind::Typing,
+    );
+    let sent_at = Timestamp::now().as_millis() - 2 * EPHEMERAL_MAX_AGE.as_millis() as u64;
+    stale.sent_at = Timestamp::from_millis(sent_at);
+
+    for ephemeral in [forged, stale] {
+        let padded = PaddingStrategy::default().pad(&ephemeral.encode().unwrap()).unwrap();
+        let ciphertext = bob_mls.send_message(&group_id, &padded).await.unwrap();
+        deliver(&alice, &bob, &channel_id, ciphertext).await;
+    }
+
+    assert!(ephemeral_events(&mut events).is_empty());
+    assert!(alice.present_members(&channel_id).await.is_empty());
+}
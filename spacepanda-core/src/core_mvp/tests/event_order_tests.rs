@@ -0,0 +1,274 @@
+//! Tests for the ordering of channel events
+//!
+//! Four members go through joins, messages and a removal while one of them
+//! receives everything in the worst order: messages before the commits of
+//! their epochs, queued in the inbox until the commits arrive. Whatever
+//! the delivery order, a member's event stream must show each commit's
+//! joins and leaves before any message from the epoch it starts. Events
+//! from the rest of the pipeline are stamped with who caused them and when.
+
+use super::common::{create_manager, user};
+use crate::core_mvp::events::{ChannelEvent, ChannelEventKind};
+use crate::core_mvp::network::{IncomingMessage, InviteAcceptance};
+use crate::core_router::PeerId;
+use crate::core_store::model::types::{ChannelId, Timestamp};
+use tempfile::TempDir;
+use tokio::sync::broadcast;
+
+fn incoming(channel_id: &ChannelId, sender: &str, ciphertext: Vec<u8>) -> IncomingMessage {
+    IncomingMessage {
+        channel_id: channel_id.clone(),
+        ciphertext,
+        sender_id: user(sender),
+        sender_peer_id: PeerId(vec![1]),
+    }
+}
+
+/// Everything emitted so far
+fn drain(events: &mut broadcast::Receiver<ChannelEvent>) -> Vec<ChannelEvent> {
+    std::iter::from_fn(|| events.try_recv().ok()).collect()
+}
+
+/// Events as (epoch, seq, short description)
+fn describe(events: &[ChannelEvent]) -> Vec<(u64, u64, String)> {
+    events
+        .iter()
+        .map(|event| {
+            let what = match &event.kind {
+                ChannelEventKind::MemberJoined(user_id) => format!("+{}", user_id.0),
+                ChannelEventKind::MemberLeft(user_id) => format!("-{}", user_id.0),
+                ChannelEventKind::MessageReceived(message) => {
+                    format!("{}: {}", message.sender.0, String::from_utf8_lossy(&message.body))
+                }
+                ChannelEventKind::Typing(user_id) => format!("{} typing", user_id.0),
+                ChannelEventKind::Presence(user_id) => format!("{} online", user_id.0),
+                ChannelEventKind::EpochAdvanced(epoch) => format!("epoch {}", epoch),
+                ChannelEventKind::ChannelMetadataChanged(metadata) => {
+                    format!("metadata {}", metadata.name)
+                }
+                ChannelEventKind::InviteAccepted { invite_id } => format!("accepted {}", invite_id),
+                ChannelEventKind::DecryptionFailed { error } => format!("undecryptable: {}", error),
+                ChannelEventKind::MessageDelivered { message_id } => {
+                    format!("delivered {}", message_id.0)
+                }
+                ChannelEventKind::MessageEvicted { message_id } => {
+                    format!("evicted {}", message_id.0)
+                }
+                ChannelEventKind::DeliveryUpdated { message_id, summary } => {
+                    format!("{:?} {}", summary.state, message_id.0)
+                }
+                ChannelEventKind::MessagesPruned { message_ids } => {
+                    format!("pruned {}", message_ids.len())
+                }
+            };
+            (event.epoch, event.seq, what)
+        })
+        .collect()
+}
+
+/// `(epoch, seq)` strictly increases and no message precedes a commit
+/// event of its epoch
+fn assert_ordered(events: &[ChannelEvent]) {
+    for pair in events.windows(2) {
+        assert!(
+            (pair[0].epoch, pair[0].seq) < (pair[1].epoch, pair[1].seq),
+            "out of order: {:?}",
+            describe(pair)
+        );
+        if pair[1].is_commit() && !pair[0].is_commit() {
+            assert!(
+                pair[1].epoch > pair[0].epoch,
+                "join or leave after a message: {:?}",
+                describe(pair)
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_four_party_events_follow_epochs_despite_delivery_order() {
+    let dirs: Vec<_> = (0..4).map(|_| TempDir::new().unwrap()).collect();
+    let alice = create_manager("alice", &dirs[0]);
+    let bob = create_manager("bob", &dirs[1]);
+    let carol = create_manager("carol", &dirs[2]);
+    let dave = create_manager("dave", &dirs[3]);
+
+    let channel_id = alice.create_channel("team".to_string(), false).await.unwrap();
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.join_channel(&invite).await.unwrap();
+    let mut bob_events = bob.subscribe_events();
+
+    // Epoch 2: Carol joins and Alice greets her
+    let (invite, carol_commit) = alice
+        .create_invite(&channel_id, carol.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    carol.join_channel(&invite).await.unwrap();
+    let mut carol_events = carol.subscribe_events();
+    let welcome = alice.send_message(&channel_id, b"welcome carol").await.unwrap();
+    carol
+        .handle_incoming_message(incoming(&channel_id, "alice", welcome.clone()))
+        .await
+        .unwrap();
+
+    // Epoch 3: Dave joins and Carol says hello
+    let (invite, dave_commit) = alice
+        .create_invite(&channel_id, dave.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    let dave_commit = dave_commit.unwrap();
+    dave.join_channel(&invite).await.unwrap();
+    let mut dave_events = dave.subscribe_events();
+    carol.process_commit(&dave_commit).await.unwrap();
+    let hello = carol.send_message(&channel_id, b"hello").await.unwrap();
+    dave.handle_incoming_message(incoming(&channel_id, "carol", hello.clone()))
+        .await
+        .unwrap();
+
+    // Epoch 4: Carol is removed and Alice tells the others
+    let carol_removal = alice.remove_member(&channel_id, user("carol").0.as_bytes()).await.unwrap();
+    dave.process_commit(&carol_removal).await.unwrap();
+    let bye = alice.send_message(&channel_id, b"carol left").await.unwrap();
+    dave.handle_incoming_message(incoming(&channel_id, "alice", bye.clone()))
+        .await
+        .unwrap();
+
+    // Bob gets every message before the commit it needs, newest first
+    for (sender, ciphertext) in [("alice", bye), ("carol", hello), ("alice", welcome)] {
+        let decrypted = bob
+            .handle_incoming_message(incoming(&channel_id, sender, ciphertext))
+            .await
+            .unwrap();
+        assert!(decrypted.is_none());
+    }
+    assert!(drain(&mut bob_events).is_empty());
+
+    for commit in [carol_commit.unwrap(), dave_commit, carol_removal] {
+        bob.process_commit(&commit).await.unwrap();
+        bob.process_pending(&channel_id).await.unwrap();
+    }
+
+    let events = drain(&mut bob_events);
+    assert_ordered(&events);
+    let alice_id = user("alice").0;
+    let carol_id = user("carol").0;
+    let dave_id = user("dave").0;
+    assert_eq!(
+        describe(&events),
+        vec![
+            (2, 0, "epoch 2".to_string()),
+            (2, 1, format!("+{}", carol_id)),
+            (2, 2, format!("{}: welcome carol", alice_id)),
+            (3, 0, "epoch 3".to_string()),
+            (3, 1, format!("+{}", dave_id)),
+            (3, 2, format!("{}: hello", carol_id)),
+            (4, 0, "epoch 4".to_string()),
+            (4, 1, format!("-{}", carol_id)),
+            (4, 2, format!("{}: carol left", alice_id)),
+        ]
+    );
+
+    // The others saw the part of the history they were there for, in order
+    let carol_events = drain(&mut carol_events);
+    assert_ordered(&carol_events);
+    assert_eq!(
+        describe(&carol_events),
+        vec![
+            (2, 0, format!("{}: welcome carol", alice_id)),
+            (3, 0, "epoch 3".to_string()),
+            (3, 1, format!("+{}", dave_id)),
+        ]
+    );
+
+    let dave_events = drain(&mut dave_events);
+    assert_ordered(&dave_events);
+    assert_eq!(
+        describe(&dave_events),
+        vec![
+            (3, 0, format!("{}: hello", carol_id)),
+            (4, 0, "epoch 4".to_string()),
+            (4, 1, format!("-{}", carol_id)),
+            (4, 2, format!("{}: carol left", alice_id)),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_local_membership_changes_are_emitted() {
+    let (alice_dir, bob_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let alice = create_manager("alice", &alice_dir);
+    let bob = create_manager("bob", &bob_dir);
+    let channel_id = alice.create_channel("team".to_string(), false).await.unwrap();
+    let mut alice_events = alice.subscribe_events();
+
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.join_channel(&invite).await.unwrap();
+    alice.remove_member(&channel_id, user("bob").0.as_bytes()).await.unwrap();
+
+    let events = drain(&mut alice_events);
+    assert_ordered(&events);
+    let bob_id = user("bob").0;
+    assert_eq!(
+        describe(&events),
+        vec![
+            (1, 0, "epoch 1".to_string()),
+            (1, 1, format!("+{}", bob_id)),
+            (2, 0, "epoch 2".to_string()),
+            (2, 1, format!("-{}", bob_id)),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_pipeline_events_carry_actor_and_time() {
+    let (alice_dir, bob_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let alice = create_manager("alice", &alice_dir);
+    let bob = create_manager("bob", &bob_dir);
+    let channel_id = alice.create_channel("team".to_string(), false).await.unwrap();
+
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.join_channel(&invite).await.unwrap();
+    let mut alice_events = alice.subscribe_events();
+    let mut bob_events = bob.subscribe_events();
+
+    let before = Timestamp::now();
+    let acceptance = InviteAcceptance { invite_id: invite.invite_id.clone(), user_id: user("bob") };
+    alice.process_invite_acceptance(&channel_id, &acceptance).await.unwrap();
+    let commit = alice.set_channel_topic(&channel_id, Some("plans".to_string())).await.unwrap();
+    bob.process_commit(&commit).await.unwrap();
+    let hello = alice.send_message(&channel_id, b"hello").await.unwrap();
+    bob.handle_incoming_message(incoming(&channel_id, "alice", hello))
+        .await
+        .unwrap();
+
+    let events = drain(&mut alice_events);
+    assert_eq!(
+        describe(&events),
+        vec![
+            (1, 2, format!("accepted {}", invite.invite_id)),
+            (2, 0, "epoch 2".to_string()),
+            (2, 1, "metadata team".to_string()),
+        ]
+    );
+    assert_eq!(events[0].actor, Some(user("bob")));
+    assert_eq!(events[2].actor, None);
+
+    let events = drain(&mut bob_events);
+    assert_ordered(&events);
+    assert_eq!(events.len(), 3);
+    let ChannelEventKind::ChannelMetadataChanged(ref metadata) = events[1].kind else {
+        panic!("expected a metadata change, got {:?}", events[1].kind);
+    };
+    assert_eq!(metadata.topic.as_deref(), Some("plans"));
+    assert_eq!(events[2].actor, Some(user("alice")));
+    assert!(events.iter().all(|event| event.timestamp >= before));
+}
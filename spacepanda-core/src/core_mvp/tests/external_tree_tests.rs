@@ -0,0 +1,97 @@
+//! Tests for invites whose ratchet tree is published to the DHT
+//!
+//! With `external_tree` set, the invite carries only the Welcome and a hash
+//! of the tree; the tree itself is fetched from the tree directory and has
+//! to match that hash.
+
+use super::common::{create_manager_with, ManagerOptions};
+use crate::core_dht::DhtStorage;
+use crate::core_mls::errors::MlsError;
+use crate::core_mls::types::{GroupId, MlsConfig};
+use crate::core_mls::welcome::TreeSnapshot;
+use crate::core_mvp::channel_manager::ChannelManager;
+use crate::core_mvp::errors::MvpError;
+use crate::core_mvp::tree_directory::TreeDirectory;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+/// Helper to create a test ChannelManager sharing a tree directory
+fn create_manager(
+    name: &str,
+    temp_dir: &TempDir,
+    trees: &Arc<TreeDirectory>,
+    mls_config: MlsConfig,
+) -> ChannelManager {
+    let options = ManagerOptions { mls_config: Some(mls_config), ..Default::default() };
+    let (manager, _) = create_manager_with(name, temp_dir, options);
+    manager.with_tree_directory(trees.clone())
+}
+
+fn external_tree_config() -> MlsConfig {
+    MlsConfig { external_tree: true, ..Default::default() }
+}
+
+#[tokio::test]
+async fn test_join_with_external_tree() {
+    let dirs: Vec<TempDir> = (0..3).map(|_| TempDir::new().unwrap()).collect();
+    let trees = Arc::new(TreeDirectory::new(DhtStorage::new()));
+    let alice = create_manager("alice", &dirs[0], &trees, external_tree_config());
+    let bob = create_manager("bob", &dirs[1], &trees, MlsConfig::default());
+    let carol = create_manager("carol", &dirs[2], &trees, MlsConfig::default());
+
+    let channel_id = alice.create_channel("general".to_string(), false).await.unwrap();
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    assert!(invite.ratchet_tree.is_none());
+    assert!(invite.external_tree.is_some());
+    bob.join_channel(&invite).await.unwrap();
+
+    let ciphertext = bob.send_message(&channel_id, b"found the tree").await.unwrap();
+    assert_eq!(alice.receive_message(&ciphertext).await.unwrap(), b"found the tree");
+
+    let commit = alice.promote_admin(&channel_id, &bob.identity().as_bytes()).await.unwrap();
+    bob.process_commit(&commit).await.unwrap();
+
+    // Default configuration still embeds the tree
+    let (invite, commit) = bob
+        .create_invite(&channel_id, carol.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    alice.process_commit(&commit.unwrap()).await.unwrap();
+    assert!(invite.ratchet_tree.is_some());
+    assert!(invite.external_tree.is_none());
+    carol.join_channel(&invite).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_tampered_external_tree_is_rejected() {
+    let dirs: Vec<TempDir> = (0..2).map(|_| TempDir::new().unwrap()).collect();
+    let trees = Arc::new(TreeDirectory::new(DhtStorage::new()));
+    let alice = create_manager("alice", &dirs[0], &trees, external_tree_config());
+    let bob = create_manager("bob", &dirs[1], &trees, MlsConfig::default());
+
+    let channel_id = alice.create_channel("general".to_string(), false).await.unwrap();
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    let external = invite.external_tree.clone().unwrap();
+
+    // Someone replaces the published tree
+    let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+    let mut tree = trees.lookup(&group_id, external.epoch).unwrap().unwrap().ratchet_tree;
+    let last = tree.len() - 1;
+    tree[last] ^= 0x01;
+    trees
+        .publish(&group_id, external.epoch, &TreeSnapshot::from_ratchet_tree(tree))
+        .unwrap();
+
+    let result = bob.join_channel(&invite).await;
+    assert!(matches!(
+        result,
+        Err(MvpError::Mls(MlsError::TreeHashMismatch { epoch })) if epoch == external.epoch
+    ));
+    assert!(bob.get_channel(&channel_id).await.is_err());
+}
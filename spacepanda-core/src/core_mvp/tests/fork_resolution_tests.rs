@@ -0,0 +1,110 @@
+//! Tests for concurrent commits
+//!
+//! Two admins who can't see each other both invite someone on top of the
+//! same epoch. When the partition heals, every member settles on the
+//! commit with the lowest hash: the losing admin rolls back, merges the
+//! winner and proposes its invite again.
+
+use super::common::{create_manager, create_manager_with, ManagerOptions};
+use crate::core_mls::commit_log::commit_hash;
+use crate::core_mls::events::MlsEvent;
+use crate::core_mls::service::MlsService;
+use crate::core_mls::types::GroupId;
+use tempfile::TempDir;
+
+/// Epoch, ratchet tree and an exported secret: equal only in the same state
+async fn group_state(mls: &MlsService, group_id: &GroupId) -> (u64, Vec<u8>, Vec<u8>) {
+    (
+        mls.get_epoch(group_id).await.unwrap(),
+        mls.export_ratchet_tree(group_id).await.unwrap(),
+        mls.export_secret(group_id, "fork test", b"", 32).await.unwrap(),
+    )
+}
+
+#[tokio::test]
+async fn test_partitioned_admins_converge() {
+    let dirs: Vec<TempDir> = (0..5).map(|_| TempDir::new().unwrap()).collect();
+    let (alice, alice_mls) = create_manager_with("alice", &dirs[0], ManagerOptions::default());
+    let (bob, bob_mls) = create_manager_with("bob", &dirs[1], ManagerOptions::default());
+    let (carol, carol_mls) = create_manager_with("carol", &dirs[2], ManagerOptions::default());
+    let dave = create_manager("dave", &dirs[3]);
+    let erin = create_manager("erin", &dirs[4]);
+
+    let channel_id = alice.create_channel("general".to_string(), false).await.unwrap();
+    let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.join_channel(&invite).await.unwrap();
+    let (invite, commit) = alice
+        .create_invite(&channel_id, carol.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.process_commit(&commit.unwrap()).await.unwrap();
+    carol.join_channel(&invite).await.unwrap();
+    let commit = alice.promote_admin(&channel_id, &bob.identity().as_bytes()).await.unwrap();
+    bob.process_commit(&commit).await.unwrap();
+    carol.process_commit(&commit).await.unwrap();
+    let epoch = alice_mls.get_epoch(&group_id).await.unwrap();
+
+    // Partitioned: Alice invites Dave while Bob invites Erin
+    let (_, alice_commit) = alice
+        .create_invite(&channel_id, dave.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    let (_, bob_commit) = bob
+        .create_invite(&channel_id, erin.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    let (alice_commit, bob_commit) = (alice_commit.unwrap(), bob_commit.unwrap());
+
+    let alice_wins = commit_hash(&alice_commit) < commit_hash(&bob_commit);
+    let (winner, winner_commit, loser, loser_mls, loser_commit) = if alice_wins {
+        (&alice, alice_commit, &bob, &bob_mls, bob_commit)
+    } else {
+        (&bob, bob_commit, &alice, &alice_mls, alice_commit)
+    };
+
+    // Carol saw the losing commit first
+    carol.process_commit(&loser_commit).await.unwrap();
+
+    // The partition heals
+    let mut loser_events = loser_mls.subscribe();
+    assert!(winner.process_commit(&loser_commit).await.is_err());
+    loser.process_commit(&winner_commit).await.unwrap();
+    carol.process_commit(&winner_commit).await.unwrap();
+
+    let requeued = loop {
+        match loser_events.try_recv().unwrap() {
+            MlsEvent::ForkResolved { epoch: forked, rolled_back, requeued_proposals, .. } => {
+                assert_eq!(forked, epoch);
+                assert!(rolled_back);
+                break requeued_proposals;
+            }
+            _ => continue,
+        }
+    };
+    assert_eq!(requeued.len(), 1);
+
+    let state = group_state(&alice_mls, &group_id).await;
+    assert_eq!(state.0, epoch + 1);
+    assert_eq!(group_state(&bob_mls, &group_id).await, state);
+    assert_eq!(group_state(&carol_mls, &group_id).await, state);
+
+    // The losing commit stays rejected once everyone moved on
+    assert!(carol.process_commit(&loser_commit).await.is_err());
+
+    // The dropped invite goes out as a proposal and is committed again
+    winner.process_commit(&requeued[0]).await.unwrap();
+    carol.process_commit(&requeued[0]).await.unwrap();
+    let commit = loser.commit_pending_proposals(&channel_id).await.unwrap();
+    winner.process_commit(&commit).await.unwrap();
+    carol.process_commit(&commit).await.unwrap();
+
+    let state = group_state(&alice_mls, &group_id).await;
+    assert_eq!(state.0, epoch + 2);
+    assert_eq!(group_state(&bob_mls, &group_id).await, state);
+    assert_eq!(group_state(&carol_mls, &group_id).await, state);
+    assert_eq!(alice_mls.get_metadata(&group_id).await.unwrap().members.len(), 5);
+}
@@ -0,0 +1,156 @@
+//! Tests for invite expiry, use limits and revocation
+//!
+//! Joiners check expiry themselves; revocation and use limits are enforced
+//! by the inviter when the invitee's acceptance arrives.
+
+use super::common::create_manager;
+use crate::core_mvp::channel_manager::ChannelManager;
+use crate::core_mvp::errors::MvpError;
+use crate::core_mvp::network::InviteAcceptance;
+use crate::core_mvp::types::InviteOptions;
+use crate::core_store::model::types::Timestamp;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+fn acceptance(invite_id: &str, user: &ChannelManager) -> InviteAcceptance {
+    InviteAcceptance { invite_id: invite_id.to_string(), user_id: user.identity().user_id.clone() }
+}
+
+#[tokio::test]
+async fn test_expired_invite_is_rejected() {
+    let (a, b) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let alice = Arc::new(create_manager("alice", &a));
+    let bob = Arc::new(create_manager("bob", &b));
+    let channel_id = alice.create_channel("team".to_string(), false).await.unwrap();
+
+    let options = InviteOptions::default().with_ttl(Duration::from_secs(24 * 3600));
+    let (mut invite, _) = alice
+        .create_invite_with_options(&channel_id, bob.generate_key_package().await.unwrap(), options)
+        .await
+        .unwrap();
+    assert_eq!(
+        invite.expires_at,
+        Some(Timestamp::from_millis(invite.created_at.as_millis() + 24 * 3600 * 1000))
+    );
+
+    // The same token a day later
+    invite.expires_at = Some(Timestamp::from_millis(Timestamp::now().as_millis() - 1));
+    let result = bob.join_channel(&invite).await;
+    assert!(matches!(result, Err(MvpError::InviteExpired(id)) if id == invite.invite_id));
+}
+
+#[tokio::test]
+async fn test_revoke_unused_invite_removes_pending_leaf() {
+    let (a, b) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let alice = Arc::new(create_manager("alice", &a));
+    let bob = Arc::new(create_manager("bob", &b));
+    let channel_id = alice.create_channel("team".to_string(), false).await.unwrap();
+
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    assert_eq!(alice.get_channel_members(&channel_id).await.unwrap().len(), 2);
+
+    let invites = alice.list_invites(&channel_id).await.unwrap();
+    assert_eq!(invites.len(), 1);
+    assert_eq!(invites[0].invite_id, invite.invite_id);
+    assert_eq!(invites[0].invitee, bob.identity().user_id.0.as_bytes());
+
+    let commit = alice.revoke_invite(&channel_id, &invite.invite_id).await.unwrap();
+    assert!(commit.is_some());
+    assert_eq!(alice.get_channel_members(&channel_id).await.unwrap().len(), 1);
+
+    // A late acceptance changes nothing
+    let late = acceptance(&invite.invite_id, &bob);
+    let result = alice.process_invite_acceptance(&channel_id, &late).await;
+    assert!(result.unwrap().is_none());
+
+    let result = alice.revoke_invite(&channel_id, "no-such-invite").await;
+    assert!(matches!(result, Err(MvpError::InvalidInvite(_))));
+}
+
+#[tokio::test]
+async fn test_acceptance_enforces_use_limit_and_revocation() {
+    let (a, b, c) = (TempDir::new().unwrap(), TempDir::new().unwrap(), TempDir::new().unwrap());
+    let alice = Arc::new(create_manager("alice", &a));
+    let bob = Arc::new(create_manager("bob", &b));
+    let charlie = Arc::new(create_manager("charlie", &c));
+    let channel_id = alice.create_channel("team".to_string(), false).await.unwrap();
+
+    // Single use by default: the first acceptance counts, a replay is kicked
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.join_channel(&invite).await.unwrap();
+
+    let bob_accepts = acceptance(&invite.invite_id, &bob);
+    assert!(alice
+        .process_invite_acceptance(&channel_id, &bob_accepts)
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(alice.list_invites(&channel_id).await.unwrap()[0].uses, 1);
+
+    // Someone other than the invitee can't spend the invite
+    let forged = acceptance(&invite.invite_id, &charlie);
+    assert!(alice.process_invite_acceptance(&channel_id, &forged).await.unwrap().is_none());
+    assert_eq!(alice.list_invites(&channel_id).await.unwrap()[0].uses, 1);
+
+    let commit = alice.process_invite_acceptance(&channel_id, &bob_accepts).await.unwrap();
+    assert!(commit.is_some());
+    assert_eq!(alice.get_channel_members(&channel_id).await.unwrap().len(), 1);
+
+    // Revoked between join and acceptance: the joiner is removed
+    let options = InviteOptions::default().with_max_uses(None);
+    let (invite, _) = alice
+        .create_invite_with_options(
+            &channel_id,
+            charlie.generate_key_package().await.unwrap(),
+            options,
+        )
+        .await
+        .unwrap();
+    charlie.join_channel(&invite).await.unwrap();
+    alice.revoke_invite(&channel_id, &invite.invite_id).await.unwrap();
+    assert_eq!(alice.get_channel_members(&channel_id).await.unwrap().len(), 1);
+
+    let charlie_accepts = acceptance(&invite.invite_id, &charlie);
+    assert!(alice
+        .process_invite_acceptance(&channel_id, &charlie_accepts)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_invite_batch_adds_valid_key_packages_in_one_commit() {
+    let dirs: Vec<TempDir> = (0..3).map(|_| TempDir::new().unwrap()).collect();
+    let alice = Arc::new(create_manager("alice", &dirs[0]));
+    let bob = Arc::new(create_manager("bob", &dirs[1]));
+    let carol = Arc::new(create_manager("carol", &dirs[2]));
+    let channel_id = alice.create_channel("team".to_string(), false).await.unwrap();
+
+    let bob_kp = bob.generate_key_package().await.unwrap();
+    let key_packages = vec![
+        bob_kp.clone(),
+        b"not a key package".to_vec(),
+        carol.generate_key_package().await.unwrap(),
+        bob_kp,
+    ];
+    let batch = alice
+        .create_invites_batch(&channel_id, key_packages, InviteOptions::default())
+        .await
+        .unwrap();
+
+    assert_eq!(batch.invites.len(), 2);
+    assert!(batch.commit.is_some());
+    let rejected: Vec<usize> = batch.rejected.iter().map(|(index, _)| *index).collect();
+    assert_eq!(rejected, vec![1, 3]);
+    assert_eq!(alice.get_channel_members(&channel_id).await.unwrap().len(), 3);
+
+    bob.join_channel(&batch.invites[0]).await.unwrap();
+    carol.join_channel(&batch.invites[1]).await.unwrap();
+}
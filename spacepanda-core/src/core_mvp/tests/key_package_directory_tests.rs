@@ -0,0 +1,73 @@
+//! Tests for the DHT key package directory
+//!
+//! Users publish one-time key packages and are then invited by user ID;
+//! each fetch consumes a package, and joining tops the set up again.
+
+use super::common;
+use crate::core_dht::DhtStorage;
+use crate::core_identity::{KeyType, Keypair};
+use crate::core_mvp::channel_manager::ChannelManager;
+use crate::core_mvp::errors::MvpError;
+use crate::core_mvp::key_package_directory::KeyPackageDirectory;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+/// Helper to create a test ChannelManager publishing to a shared directory
+fn create_manager(name: &str, temp_dir: &TempDir, dht: &DhtStorage) -> ChannelManager {
+    let manager = common::create_manager(name, temp_dir);
+    let directory = KeyPackageDirectory::new(dht.clone())
+        .with_signer(Keypair::generate(KeyType::Ed25519))
+        .with_replenishment(1, 3);
+    manager.with_key_package_directory(Arc::new(directory))
+}
+
+#[tokio::test]
+async fn test_invite_by_user_id_consumes_published_key_packages() {
+    let dht = DhtStorage::new();
+    let (a, b) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let alice = create_manager("alice", &a, &dht);
+    let bob = create_manager("bob", &b, &dht);
+    let bob_id = bob.identity().user_id.clone();
+    let channel_id = alice.create_channel("team".to_string(), false).await.unwrap();
+    let other_id = alice.create_channel("other".to_string(), false).await.unwrap();
+
+    // Nothing published yet
+    let result = alice.create_invite(&channel_id, bob_id.clone()).await;
+    assert!(matches!(result, Err(MvpError::NoKeyPackages(user)) if user == bob_id.0));
+
+    assert_eq!(bob.publish_key_packages(2).await.unwrap(), 2);
+    let (invite, _) = alice.create_invite(&channel_id, bob_id.clone()).await.unwrap();
+    alice.create_invite(&other_id, bob_id.clone()).await.unwrap();
+
+    // Both key packages were used up
+    assert!(matches!(
+        alice.fetch_key_package(&bob_id).await,
+        Err(MvpError::NoKeyPackages(_))
+    ));
+
+    // Joining replenishes Bob's key packages
+    bob.join_channel(&invite).await.unwrap();
+    let fetched = alice.fetch_key_package(&bob_id).await.unwrap();
+    assert!(!fetched.is_empty());
+    assert_eq!(bob.replenish_key_packages().await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_directory_entry_is_pinned_to_identity_key() {
+    let dht = DhtStorage::new();
+    let dir = TempDir::new().unwrap();
+    let bob = create_manager("bob", &dir, &dht);
+    bob.publish_key_packages(1).await.unwrap();
+
+    // Someone else can't add key packages to Bob's set
+    let impostor =
+        KeyPackageDirectory::new(dht.clone()).with_signer(Keypair::generate(KeyType::Ed25519));
+    let result = impostor.publish(&bob.identity().user_id, vec![b"forged".to_vec()]);
+    assert!(matches!(result, Err(MvpError::Unauthorized(_))));
+
+    // Readers don't need a signer
+    let reader = KeyPackageDirectory::new(dht);
+    let fetched = reader.take(&bob.identity().user_id).unwrap();
+    assert!(!fetched.key_package.is_empty());
+    assert_eq!(reader.available(&bob.identity().user_id).unwrap(), 0);
+}
@@ -0,0 +1,107 @@
+//! Tests for rotating a member's own leaf key
+//!
+//! A rotation is a self-update commit that moves the epoch forward. Secrets
+//! of the last few epochs are kept for late messages; once an epoch falls
+//! out of that window its traffic can't be opened anymore.
+
+use super::common::{create_manager, create_manager_with, ManagerOptions};
+use crate::core_mls::types::GroupId;
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_rotate_keys_advances_epoch() {
+    let (alice_dir, bob_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let (alice, alice_mls) = create_manager_with("alice", &alice_dir, ManagerOptions::default());
+    let bob = create_manager("bob", &bob_dir);
+
+    let channel_id = alice.create_channel("ops".to_string(), false).await.unwrap();
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.join_channel(&invite).await.unwrap();
+
+    let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+    let before = alice_mls.get_epoch(&group_id).await.unwrap();
+
+    let commit = alice.rotate_keys(&channel_id).await.unwrap();
+    assert_eq!(alice_mls.get_epoch(&group_id).await.unwrap(), before + 1);
+
+    bob.process_commit(&commit).await.unwrap();
+    let ciphertext = bob.send_message(&channel_id, b"after rotation").await.unwrap();
+    assert_eq!(alice.receive_message(&ciphertext).await.unwrap(), b"after rotation");
+}
+
+#[tokio::test]
+async fn test_purged_epochs_no_longer_decrypt() {
+    let (alice_dir, bob_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let (alice, alice_mls) = create_manager_with("alice", &alice_dir, ManagerOptions::default());
+    let bob = create_manager("bob", &bob_dir);
+    let retention = alice_mls.config().past_epoch_retention;
+
+    let channel_id = alice.create_channel("ops".to_string(), false).await.unwrap();
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.join_channel(&invite).await.unwrap();
+
+    // Both recorded under the same epoch, delivered late
+    let late = bob.send_message(&channel_id, b"still in the window").await.unwrap();
+    let stale = bob.send_message(&channel_id, b"too late").await.unwrap();
+
+    // Within the retention window the old epoch's secrets are still there
+    alice.rotate_keys(&channel_id).await.unwrap();
+    assert_eq!(alice.receive_message(&late).await.unwrap(), b"still in the window");
+
+    // One rotation past the window and they're gone
+    for _ in 0..retention {
+        alice.rotate_keys(&channel_id).await.unwrap();
+    }
+    assert!(alice.receive_message(&stale).await.is_err());
+
+    // Purged as part of each merge, so nothing is left for a manual purge
+    let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+    assert_eq!(alice_mls.purge_old_epochs(&group_id).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_removed_member_past_epoch_messages_rejected() {
+    let (alice_dir, bob_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let alice = create_manager("alice", &alice_dir);
+    let bob = create_manager("bob", &bob_dir);
+
+    let channel_id = alice.create_channel("ops".to_string(), false).await.unwrap();
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.join_channel(&invite).await.unwrap();
+
+    // Sent before the removal, which is still within the retention window
+    let late = bob.send_message(&channel_id, b"sent before removal").await.unwrap();
+    alice
+        .remove_member(&channel_id, bob.identity().user_id.0.as_bytes())
+        .await
+        .unwrap();
+
+    assert!(alice.receive_message(&late).await.is_err());
+}
+
+#[tokio::test]
+async fn test_rotate_due_keys_skips_recent_rotations() {
+    let dir = TempDir::new().unwrap();
+    let (alice, alice_mls) = create_manager_with("alice", &dir, ManagerOptions::default());
+
+    let rotated = alice.create_channel("ops".to_string(), false).await.unwrap();
+    let fresh = alice.create_channel("dev".to_string(), false).await.unwrap();
+    alice.rotate_keys(&rotated).await.unwrap();
+    let group_id = GroupId::new(rotated.0.as_bytes().to_vec());
+    let epoch = alice_mls.get_epoch(&group_id).await.unwrap();
+
+    let report = alice.rotate_due_keys().await.unwrap();
+    assert_eq!(report.rotated, vec![fresh.clone()]);
+    assert_eq!(report.skipped, vec![rotated.clone()]);
+    assert!(report.failed.is_empty());
+    assert_eq!(alice_mls.get_epoch(&group_id).await.unwrap(), epoch);
+}
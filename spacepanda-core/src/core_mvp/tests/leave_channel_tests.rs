@@ -0,0 +1,92 @@
+//! Tests for members leaving a channel
+//!
+//! A leaving member sends a self-remove proposal and drops the channel
+//! locally; a remaining member commits the proposal for everyone else.
+
+use super::common::create_manager;
+use crate::core_mvp::channel_manager::ChannelManager;
+use crate::core_mvp::errors::MvpError;
+use crate::core_store::model::types::ChannelId;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+/// Alice creates a channel and invites Bob, then Charlie
+async fn three_member_channel(
+    alice: &ChannelManager,
+    bob: &ChannelManager,
+    charlie: &ChannelManager,
+) -> ChannelId {
+    let channel_id = alice.create_channel("leavers".to_string(), false).await.unwrap();
+
+    let bob_kp = bob.generate_key_package().await.unwrap();
+    let (invite, _) = alice.create_invite(&channel_id, bob_kp).await.unwrap();
+    bob.join_channel(&invite).await.unwrap();
+
+    let charlie_kp = charlie.generate_key_package().await.unwrap();
+    let (invite, commit) = alice.create_invite(&channel_id, charlie_kp).await.unwrap();
+    charlie.join_channel(&invite).await.unwrap();
+    bob.process_commit(&commit.unwrap()).await.unwrap();
+
+    channel_id
+}
+
+#[tokio::test]
+async fn test_leave_channel_removes_local_state() {
+    let (a, b, c) = (TempDir::new().unwrap(), TempDir::new().unwrap(), TempDir::new().unwrap());
+    let alice = Arc::new(create_manager("alice", &a));
+    let bob = Arc::new(create_manager("bob", &b));
+    let charlie = Arc::new(create_manager("charlie", &c));
+    let channel_id = three_member_channel(&alice, &bob, &charlie).await;
+
+    let proposal = bob.leave_channel(&channel_id).await.unwrap();
+    assert!(!proposal.is_empty());
+
+    assert!(bob.list_channels().await.unwrap().is_empty());
+    assert!(matches!(
+        bob.send_message(&channel_id, b"still here?").await,
+        Err(MvpError::ChannelNotFound(_))
+    ));
+    assert!(matches!(bob.get_channel(&channel_id).await, Err(MvpError::ChannelNotFound(_))));
+
+    // Leaving twice is a ChannelNotFound, not an MLS error
+    assert!(matches!(
+        bob.leave_channel(&channel_id).await,
+        Err(MvpError::ChannelNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_remaining_member_commits_leave() {
+    let (a, b, c) = (TempDir::new().unwrap(), TempDir::new().unwrap(), TempDir::new().unwrap());
+    let alice = Arc::new(create_manager("alice", &a));
+    let bob = Arc::new(create_manager("bob", &b));
+    let charlie = Arc::new(create_manager("charlie", &c));
+    let channel_id = three_member_channel(&alice, &bob, &charlie).await;
+
+    let proposal = bob.leave_channel(&channel_id).await.unwrap();
+
+    // Every remaining member queues the proposal; one of them commits it
+    alice.process_commit(&proposal).await.unwrap();
+    charlie.process_commit(&proposal).await.unwrap();
+    let commit = alice.commit_pending_proposals(&channel_id).await.unwrap();
+    charlie.process_commit(&commit).await.unwrap();
+
+    let bob_identity = bob.identity().user_id.0.as_bytes().to_vec();
+    for member in [&alice, &charlie] {
+        let members = member.get_channel_members(&channel_id).await.unwrap();
+        assert_eq!(members.len(), 2);
+        assert!(!members.contains(&bob_identity));
+    }
+
+    let ciphertext = alice.send_message(&channel_id, b"bye bob").await.unwrap();
+    assert_eq!(charlie.receive_message(&ciphertext).await.unwrap(), b"bye bob");
+}
+
+#[tokio::test]
+async fn test_leave_unknown_channel() {
+    let dir = TempDir::new().unwrap();
+    let alice = Arc::new(create_manager("alice", &dir));
+
+    let result = alice.leave_channel(&ChannelId::generate()).await;
+    assert!(matches!(result, Err(MvpError::ChannelNotFound(_))));
+}
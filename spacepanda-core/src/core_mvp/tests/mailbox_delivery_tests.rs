@@ -0,0 +1,112 @@
+//! Tests for invites delivered over the DHT
+//!
+//! Two nodes share a DHT: the invitee publishes key packages, the inviter
+//! invites them by user ID, and the invitee picks up the Welcome (and later
+//! commits) by polling its mailbox, with no blob passed by hand.
+
+use crate::config::Config;
+use crate::core_dht::{DhtStorage, RecipientMailbox};
+use crate::core_identity::{KeyType, Keypair};
+use crate::core_mls::service::MlsService;
+use crate::core_mvp::channel_manager::{ChannelManager, Identity};
+use crate::core_mvp::key_package_directory::KeyPackageDirectory;
+use crate::core_mvp::mailbox_delivery::{Delivery, DeliveryMailbox};
+use crate::core_store::model::types::{ChannelId, UserId};
+use crate::core_store::store::{LocalStore, LocalStoreConfig};
+use crate::shutdown::ShutdownCoordinator;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+/// Helper to create a node on the shared DHT, with its own identity key
+fn create_node(name: &str, temp_dir: &TempDir, dht: &DhtStorage) -> ChannelManager {
+    let config = Arc::new(Config::default());
+    let shutdown = Arc::new(ShutdownCoordinator::new(Duration::from_secs(30)));
+    let mls_service = Arc::new(
+        MlsService::with_storage(&config, shutdown, temp_dir.path().join("mls_groups")).unwrap(),
+    );
+
+    let store_config = LocalStoreConfig {
+        data_dir: temp_dir.path().to_path_buf(),
+        enable_encryption: false,
+        require_signatures: false,
+        authorized_keys: Vec::new(),
+        ..Default::default()
+    };
+    let store = Arc::new(LocalStore::new(store_config).unwrap());
+
+    let identity = Arc::new(Identity::new(
+        UserId(format!("{}@spacepanda.local", name)),
+        name.to_string(),
+        format!("node-{}", name),
+    ));
+
+    let identity_kp = Keypair::generate(KeyType::Ed25519);
+    let directory = KeyPackageDirectory::new(dht.clone()).with_signer(identity_kp.clone());
+    let mailbox = DeliveryMailbox::new(Arc::new(RecipientMailbox::new(dht.clone())), identity_kp);
+    ChannelManager::new(mls_service, store, identity, config)
+        .with_key_package_directory(Arc::new(directory))
+        .with_delivery_mailbox(Arc::new(mailbox))
+}
+
+#[tokio::test]
+async fn test_invite_over_dht() {
+    let dht = DhtStorage::new();
+    let dirs: Vec<TempDir> = (0..3).map(|_| TempDir::new().unwrap()).collect();
+    let alice = create_node("alice", &dirs[0], &dht);
+    let bob = create_node("bob", &dirs[1], &dht);
+    let carol = create_node("carol", &dirs[2], &dht);
+    bob.publish_key_packages(2).await.unwrap();
+    carol.publish_key_packages(2).await.unwrap();
+
+    let channel_id = alice.create_channel("team".to_string(), false).await.unwrap();
+    alice.create_invite(&channel_id, bob.identity().user_id.clone()).await.unwrap();
+
+    // Bob finds the Welcome in his mailbox; polling again does nothing
+    assert_eq!(bob.poll_mailbox().await.unwrap(), vec![channel_id.clone()]);
+    assert!(bob.poll_mailbox().await.unwrap().is_empty());
+    assert!(alice.poll_mailbox().await.unwrap().is_empty());
+
+    // Adding Carol leaves the commit for Bob and the Welcome for Carol
+    alice
+        .create_invite(&channel_id, carol.identity().user_id.clone())
+        .await
+        .unwrap();
+    assert!(bob.poll_mailbox().await.unwrap().is_empty());
+    assert_eq!(bob.get_channel_members(&channel_id).await.unwrap().len(), 3);
+    assert_eq!(carol.poll_mailbox().await.unwrap(), vec![channel_id.clone()]);
+
+    // All three are in the same epoch
+    let ciphertext = alice.send_message(&channel_id, b"hello over the DHT").await.unwrap();
+    for member in [&bob, &carol] {
+        let plaintext = member.receive_message(&ciphertext).await.unwrap();
+        assert_eq!(plaintext, b"hello over the DHT");
+    }
+}
+
+#[test]
+fn test_deliveries_are_sealed_to_the_recipient() {
+    let mailbox = Arc::new(RecipientMailbox::new(DhtStorage::new()));
+    let (bob_kp, eve_kp) =
+        (Keypair::generate(KeyType::Ed25519), Keypair::generate(KeyType::Ed25519));
+    let sender = DeliveryMailbox::new(mailbox.clone(), Keypair::generate(KeyType::Ed25519));
+
+    let delivery =
+        Delivery::Commit { channel_id: ChannelId("team".to_string()), commit: b"secret".to_vec() };
+    sender.deliver(bob_kp.public_key(), &delivery).unwrap();
+
+    // The DHT only ever sees ciphertext
+    let raw = mailbox.fetch(&RecipientMailbox::recipient(bob_kp.public_key()));
+    assert_eq!(raw.len(), 1);
+    assert!(!raw[0].payload.windows(6).any(|window| window == b"secret"));
+
+    // Another identity can't open it, even when it lands in their mailbox
+    let eve_address = RecipientMailbox::recipient(eve_kp.public_key());
+    mailbox.deposit(&eve_address, raw[0].payload.clone()).unwrap();
+    let eve = DeliveryMailbox::new(mailbox.clone(), eve_kp);
+    assert!(eve.pending()[0].1.is_err());
+
+    let bob = DeliveryMailbox::new(mailbox, bob_kp);
+    let pending = bob.pending();
+    assert!(matches!(&pending[0].1, Ok(Delivery::Commit { commit, .. }) if commit == b"secret"));
+}
@@ -8,6 +8,7 @@
 use crate::config::Config;
 use crate::core_mls::service::MlsService;
 use crate::core_mvp::channel_manager::{ChannelManager, Identity};
+use crate::core_mvp::errors::MvpError;
 use crate::core_store::model::types::UserId;
 use crate::core_store::store::{LocalStore, LocalStoreConfig};
 use crate::shutdown::ShutdownCoordinator;
@@ -27,6 +28,7 @@ async fn create_manager(name: &str) -> (Arc<ChannelManager>, TempDir) {
         snapshot_interval: 1000,
         max_log_size: 10_000_000,
         enable_compaction: false,
+        enable_wal: true,
         require_signatures: false,
         authorized_keys: Vec::new(),
     };
@@ -144,11 +146,46 @@ async fn test_remove_nonexistent_member() {
     let fake_identity = b"nonexistent_user_id";
     let result = alice.remove_member(&channel_id, fake_identity).await;
 
-    assert!(result.is_err(), "Should fail to remove non-existent member");
+    assert!(
+        matches!(result, Err(MvpError::MemberNotFound { .. })),
+        "Should fail to remove non-existent member"
+    );
     println!("✓ Correctly rejects removing non-existent member");
     println!("✅ Error handling test passed\n");
 }
 
+#[tokio::test]
+async fn test_removed_member_decrypt_fails_deterministically() {
+    println!("\n=== TEST: Removed Member Decrypt Failures ===");
+
+    let (alice, _a) = create_manager("Alice").await;
+    let (bob, _b) = create_manager("Bob").await;
+
+    let channel_id = alice.create_channel("test".to_string(), false).await.unwrap();
+
+    let bob_kp = bob.generate_key_package().await.unwrap();
+    let (welcome, _) = alice.create_invite(&channel_id, bob_kp).await.unwrap();
+    bob.join_channel(&welcome).await.unwrap();
+
+    let bob_identity = bob.identity().user_id.0.as_bytes();
+    alice.remove_member(&channel_id, bob_identity).await.unwrap();
+    println!("✓ Alice removed Bob");
+
+    // Every message after the removal fails, and retrying gives the same error
+    for i in 0..3 {
+        let msg = alice
+            .send_message(&channel_id, format!("secret {}", i).as_bytes())
+            .await
+            .unwrap();
+        let first = bob.receive_message(&msg).await.unwrap_err().to_string();
+        let retry = bob.receive_message(&msg).await.unwrap_err().to_string();
+        assert_eq!(first, retry, "Decrypt failure should not depend on the attempt");
+    }
+    println!("✓ Bob's decrypt attempts fail consistently");
+
+    println!("✅ Removed member decrypt test passed\n");
+}
+
 #[tokio::test]
 async fn test_remove_self() {
     println!("\n=== TEST: Remove Self ===");
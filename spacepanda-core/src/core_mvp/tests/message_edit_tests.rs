@@ -0,0 +1,170 @@
+//! Tests for editing and deleting messages
+//!
+//! Changes are signed with the editor's leaf key and checked by every
+//! receiver against the original sender, so members can only change their
+//! own messages (admins may also delete). A change that arrives before its
+//! message waits for it.
+
+use super::common::{create_manager, create_manager_with, ManagerOptions};
+use crate::core_mls::padding::PaddingStrategy;
+use crate::core_mls::service::MlsService;
+use crate::core_mls::types::GroupId;
+use crate::core_mvp::channel_manager::ChannelManager;
+use crate::core_mvp::errors::MvpError;
+use crate::core_mvp::message_edits::MessageChange;
+use crate::core_mvp::network::IncomingMessage;
+use crate::core_mvp::types::{ChatMessage, MessageEdit};
+use crate::core_router::PeerId;
+use crate::core_store::model::types::{ChannelId, MessageId};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+/// Alice (the channel's creator and admin) and Bob in a channel
+async fn setup(
+    dirs: &[TempDir],
+) -> (Arc<ChannelManager>, Arc<ChannelManager>, Arc<MlsService>, ChannelId) {
+    let alice = Arc::new(create_manager("alice", &dirs[0]));
+    let (bob, bob_mls) = create_manager_with("bob", &dirs[1], ManagerOptions::default());
+    let bob = Arc::new(bob);
+
+    let channel_id = alice.create_channel("chat".to_string(), false).await.unwrap();
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.join_channel(&invite).await.unwrap();
+    (alice, bob, bob_mls, channel_id)
+}
+
+/// Hand a ciphertext from one manager to another
+async fn deliver(
+    to: &ChannelManager,
+    from: &ChannelManager,
+    channel_id: &ChannelId,
+    ciphertext: Vec<u8>,
+) -> Option<ChatMessage> {
+    let incoming = IncomingMessage {
+        channel_id: channel_id.clone(),
+        ciphertext,
+        sender_id: from.identity().user_id.clone(),
+        sender_peer_id: PeerId(vec![1]),
+    };
+    to.handle_incoming_message(incoming).await.unwrap()
+}
+
+async fn only_message(manager: &ChannelManager, channel_id: &ChannelId) -> ChatMessage {
+    let mut history = manager.get_messages(channel_id, 10, None).await.unwrap();
+    assert_eq!(history.len(), 1);
+    history.remove(0)
+}
+
+#[tokio::test]
+async fn test_edit_reaches_members() {
+    let dirs: Vec<TempDir> = (0..2).map(|_| TempDir::new().unwrap()).collect();
+    let (alice, bob, _, channel_id) = setup(&dirs).await;
+
+    let ciphertext = alice.send_message(&channel_id, b"see you at 5").await.unwrap();
+    let received = deliver(&bob, &alice, &channel_id, ciphertext).await.unwrap();
+
+    // Both sides know the message by the same ID
+    let sent = only_message(&alice, &channel_id).await;
+    assert_eq!(received.message_id, sent.message_id);
+
+    let edit = alice.edit_message(&channel_id, sent.message_id, b"see you at 6").await.unwrap();
+    assert!(deliver(&bob, &alice, &channel_id, edit).await.is_none());
+
+    for manager in [&alice, &bob] {
+        let message = only_message(manager, &channel_id).await;
+        assert_eq!(message.body_as_string().unwrap(), "see you at 6");
+        assert_eq!(message.edits.len(), 1);
+        assert_eq!(message.edits[0].body, b"see you at 5");
+        assert!(!message.deleted);
+    }
+}
+
+#[tokio::test]
+async fn test_only_sender_or_admin_may_change() {
+    let dirs: Vec<TempDir> = (0..2).map(|_| TempDir::new().unwrap()).collect();
+    let (alice, bob, _, channel_id) = setup(&dirs).await;
+
+    let ciphertext = alice.send_message(&channel_id, b"from alice").await.unwrap();
+    let from_alice = deliver(&bob, &alice, &channel_id, ciphertext).await.unwrap();
+
+    let result = bob.edit_message(&channel_id, from_alice.message_id.clone(), b"hijacked").await;
+    assert!(matches!(result, Err(MvpError::PermissionDenied { .. })));
+    let result = bob.delete_message(&channel_id, from_alice.message_id.clone()).await;
+    assert!(matches!(result, Err(MvpError::PermissionDenied { .. })));
+
+    let result = bob.edit_message(&channel_id, MessageId::generate(), b"?").await;
+    assert!(matches!(result, Err(MvpError::MessageNotFound(_))));
+
+    // Alice, an admin, deletes Bob's message
+    let ciphertext = bob.send_message(&channel_id, b"off topic").await.unwrap();
+    let from_bob = deliver(&alice, &bob, &channel_id, ciphertext).await.unwrap();
+    let delete = alice.delete_message(&channel_id, from_bob.message_id.clone()).await.unwrap();
+    deliver(&bob, &alice, &channel_id, delete).await;
+
+    let history = bob.get_messages(&channel_id, 10, None).await.unwrap();
+    let tombstone = history.iter().find(|m| m.message_id == from_bob.message_id).unwrap();
+    assert!(tombstone.deleted);
+    assert!(tombstone.body.is_empty());
+
+    // A deleted message can't be edited
+    let result = bob.edit_message(&channel_id, from_bob.message_id.clone(), b"back").await;
+    assert!(matches!(result, Err(MvpError::InvalidOperation(_))));
+}
+
+#[tokio::test]
+async fn test_forged_changes_are_dropped() {
+    let dirs: Vec<TempDir> = (0..2).map(|_| TempDir::new().unwrap()).collect();
+    let (alice, bob, bob_mls, channel_id) = setup(&dirs).await;
+    let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+
+    let ciphertext = alice.send_message(&channel_id, b"original").await.unwrap();
+    let message = deliver(&bob, &alice, &channel_id, ciphertext).await.unwrap();
+
+    // Bob claims to be Alice, but can only sign with his own leaf
+    let mut as_alice = MessageChange::edit(
+        channel_id.clone(),
+        message.message_id.clone(),
+        b"forged".to_vec(),
+        alice.identity().user_id.clone(),
+    );
+    // Bob signs as himself, but didn't send the message
+    let mut as_bob = as_alice.clone();
+    as_bob.editor = bob.identity().user_id.clone();
+
+    for change in [&mut as_alice, &mut as_bob] {
+        change.signature = bob_mls.sign_as_member(&group_id, &change.signed_bytes()).await.unwrap();
+        let padded = PaddingStrategy::default().pad(&change.encode().unwrap()).unwrap();
+        let ciphertext = bob_mls.send_message(&group_id, &padded).await.unwrap();
+        assert!(deliver(&alice, &bob, &channel_id, ciphertext).await.is_none());
+    }
+
+    let message = only_message(&alice, &channel_id).await;
+    assert_eq!(message.body_as_string().unwrap(), "original");
+    assert!(message.edits.is_empty());
+}
+
+#[tokio::test]
+async fn test_change_before_message_is_held() {
+    let dirs: Vec<TempDir> = (0..2).map(|_| TempDir::new().unwrap()).collect();
+    let (alice, bob, _, channel_id) = setup(&dirs).await;
+
+    let original = alice.send_message(&channel_id, b"draft").await.unwrap();
+    let message_id = only_message(&alice, &channel_id).await.message_id;
+    let edit = alice.edit_message(&channel_id, message_id, b"final").await.unwrap();
+
+    // The edit overtakes the message on the way to Bob
+    assert!(deliver(&bob, &alice, &channel_id, edit).await.is_none());
+    assert!(bob.get_messages(&channel_id, 10, None).await.unwrap().is_empty());
+
+    let received = deliver(&bob, &alice, &channel_id, original).await.unwrap();
+    assert_eq!(received.body_as_string().unwrap(), "final");
+    let stored = only_message(&bob, &channel_id).await;
+    assert_eq!(stored.body_as_string().unwrap(), "final");
+    assert_eq!(
+        stored.edits.iter().map(|e: &MessageEdit| e.body.clone()).collect::<Vec<_>>(),
+        vec![b"draft".to_vec()]
+    );
+}
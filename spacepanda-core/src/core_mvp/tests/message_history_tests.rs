@@ -0,0 +1,93 @@
+//! Tests for reading back channel history
+//!
+//! Sent messages are persisted at send time and received messages at
+//! decrypt time, so `get_messages` sees both directions, newest first.
+
+use super::common::create_manager;
+use crate::core_mvp::errors::MvpError;
+use crate::core_mvp::network::IncomingMessage;
+use crate::core_router::PeerId;
+use crate::core_store::model::types::{ChannelId, MessageId};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn bodies(messages: &[crate::core_mvp::types::ChatMessage]) -> Vec<String> {
+    messages.iter().map(|m| m.body_as_string().unwrap()).collect()
+}
+
+#[tokio::test]
+async fn test_empty_channel_has_no_history() {
+    let dir = TempDir::new().unwrap();
+    let alice = Arc::new(create_manager("alice", &dir));
+    let channel_id = alice.create_channel("quiet".to_string(), false).await.unwrap();
+
+    assert!(alice.get_messages(&channel_id, 10, None).await.unwrap().is_empty());
+    assert!(alice.get_messages(&ChannelId::generate(), 10, None).await.unwrap().is_empty());
+
+    // A cursor that isn't in the channel is an error, not an empty page
+    let result = alice.get_messages(&channel_id, 10, Some(MessageId::generate())).await;
+    assert!(matches!(result, Err(MvpError::MessageNotFound(_))));
+}
+
+#[tokio::test]
+async fn test_pagination_boundaries() {
+    let dir = TempDir::new().unwrap();
+    let alice = Arc::new(create_manager("alice", &dir));
+    let channel_id = alice.create_channel("busy".to_string(), false).await.unwrap();
+
+    for i in 1..=6 {
+        alice
+            .send_message(&channel_id, format!("message {}", i).as_bytes())
+            .await
+            .unwrap();
+    }
+
+    // Pages that divide the history evenly
+    let page1 = alice.get_messages(&channel_id, 3, None).await.unwrap();
+    assert_eq!(bodies(&page1), vec!["message 6", "message 5", "message 4"]);
+
+    let cursor = page1.last().unwrap().message_id.clone();
+    let page2 = alice.get_messages(&channel_id, 3, Some(cursor)).await.unwrap();
+    assert_eq!(bodies(&page2), vec!["message 3", "message 2", "message 1"]);
+
+    // Past the oldest message there is nothing left
+    let cursor = page2.last().unwrap().message_id.clone();
+    assert!(alice.get_messages(&channel_id, 3, Some(cursor)).await.unwrap().is_empty());
+
+    // A limit larger than the history returns everything
+    assert_eq!(alice.get_messages(&channel_id, 100, None).await.unwrap().len(), 6);
+    assert!(alice.get_messages(&channel_id, 0, None).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_history_includes_sent_and_received() {
+    let (a, b) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let alice = Arc::new(create_manager("alice", &a));
+    let bob = Arc::new(create_manager("bob", &b));
+
+    let channel_id = alice.create_channel("chat".to_string(), false).await.unwrap();
+    let bob_kp = bob.generate_key_package().await.unwrap();
+    let (invite, _) = alice.create_invite(&channel_id, bob_kp).await.unwrap();
+    bob.join_channel(&invite).await.unwrap();
+
+    let ciphertext = alice.send_message(&channel_id, b"hi bob").await.unwrap();
+    let incoming = IncomingMessage {
+        channel_id: channel_id.clone(),
+        ciphertext,
+        sender_id: alice.identity().user_id.clone(),
+        sender_peer_id: PeerId(vec![1]),
+    };
+    bob.handle_incoming_message(incoming).await.unwrap().unwrap();
+    bob.send_message(&channel_id, b"hi alice").await.unwrap();
+
+    let history = bob.get_messages(&channel_id, 10, None).await.unwrap();
+    assert_eq!(bodies(&history), vec!["hi alice", "hi bob"]);
+    assert_eq!(history[0].sender, bob.identity().user_id);
+    assert_eq!(history[1].sender, alice.identity().user_id);
+
+    // Alice only has what she sent so far
+    assert_eq!(
+        bodies(&alice.get_messages(&channel_id, 10, None).await.unwrap()),
+        vec!["hi bob"]
+    );
+}
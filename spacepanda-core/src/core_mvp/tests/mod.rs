@@ -1,7 +1,45 @@
 // Integration tests for core_mvp module
 
 pub mod e2e_join_message;
+pub mod e2e_key_package_invite;
 pub mod e2e_member_removal;
 pub mod e2e_offline_sync;
+pub mod e2e_receive_loop;
 pub mod full_join_flow;
+mod attachment_tests;
+mod broadcast_channel_tests;
+mod channel_feed_tests;
+mod channel_members_tests;
+mod channel_metadata_tests;
+mod channel_secret_tests;
+mod common;
+mod commit_policy_tests;
+mod commit_replay_tests;
+mod crash_recovery_tests;
+mod delivery_ack_tests;
+mod deterministic_channel_tests;
+mod device_link_tests;
+mod ephemeral_tests;
+mod event_order_tests;
+mod external_tree_tests;
+mod fork_resolution_tests;
+mod invite_lifecycle_tests;
+mod key_package_directory_tests;
+mod key_rotation_tests;
+mod leave_channel_tests;
+mod mailbox_delivery_tests;
 mod member_removal_tests;
+mod message_edit_tests;
+mod message_history_tests;
+mod offline_invite_tests;
+mod outbox_tests;
+mod padding_policy_tests;
+mod privacy_report_tests;
+mod psk_channel_tests;
+mod public_channel_tests;
+mod reaction_tests;
+mod read_receipt_tests;
+mod storage_preflight_tests;
+mod test_harness_http_tests;
+mod transcript_tests;
+mod webhook_tests;
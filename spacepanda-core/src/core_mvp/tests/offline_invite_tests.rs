@@ -0,0 +1,154 @@
+//! Tests for joining from an offline invite
+//!
+//! Bob joins from a sealed bundle on a node whose network components all
+//! fail: the router task is gone and the broadcast mailbox refuses every
+//! operation. The channel has to be usable anyway, with Bob's message
+//! held in the outbox, and everything deferred has to go through once the
+//! mailbox comes back.
+
+use super::common::{create_manager, user};
+use crate::core_dht::DhtStorage;
+use crate::core_mvp::broadcast::{BroadcastMailbox, BroadcastPolicy, DhtMailbox, MailboxEntry};
+use crate::core_mvp::errors::{MvpError, MvpResult};
+use crate::core_mvp::network::NetworkLayer;
+use crate::core_mvp::offline_invite::OfflineInvite;
+use crate::core_mvp::types::InviteOptions;
+use crate::core_router::{PeerId, RouterHandle};
+use crate::core_store::model::types::{ChannelId, UserId};
+use async_trait::async_trait;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+const PASSPHRASE: &str = "carried by hand";
+
+/// Mailbox that fails every operation until switched online
+struct SwitchableMailbox {
+    inner: DhtMailbox,
+    online: AtomicBool,
+}
+
+impl SwitchableMailbox {
+    fn check(&self) -> MvpResult<()> {
+        if self.online.load(Ordering::SeqCst) {
+            Ok(())
+        } else {
+            Err(MvpError::NetworkError("mailbox unreachable".to_string()))
+        }
+    }
+}
+
+#[async_trait]
+impl BroadcastMailbox for SwitchableMailbox {
+    async fn publish(
+        &self,
+        channel_id: &ChannelId,
+        sender: &UserId,
+        ciphertext: Vec<u8>,
+    ) -> MvpResult<u64> {
+        self.check()?;
+        self.inner.publish(channel_id, sender, ciphertext).await
+    }
+
+    async fn fetch(&self, channel_id: &ChannelId, from_seq: u64) -> MvpResult<Vec<MailboxEntry>> {
+        self.check()?;
+        self.inner.fetch(channel_id, from_seq).await
+    }
+}
+
+/// Network layer whose router task has already stopped
+fn dead_network() -> Arc<NetworkLayer> {
+    let (router, router_task) = RouterHandle::new();
+    router_task.abort();
+    let (network, _messages, _commits) = NetworkLayer::new(router, PeerId(vec![2]));
+    Arc::new(network)
+}
+
+#[tokio::test]
+async fn test_offline_join_queues_until_network_returns() {
+    let (alice_dir, bob_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let dht = DhtStorage::new();
+
+    let alice = Arc::new(
+        create_manager("alice", &alice_dir)
+            .with_broadcast_mailbox(Arc::new(DhtMailbox::new(dht.clone()))),
+    );
+    let bob_mailbox = Arc::new(SwitchableMailbox {
+        inner: DhtMailbox::new(dht.clone()),
+        online: AtomicBool::new(false),
+    });
+    let bob = Arc::new(
+        create_manager("bob", &bob_dir)
+            .with_network(dead_network())
+            .with_broadcast_mailbox(bob_mailbox.clone()),
+    );
+
+    let channel_id = alice.create_channel("field-notes".to_string(), false).await.unwrap();
+    let policy = BroadcastPolicy::new([user("alice"), user("bob")]);
+    alice.set_broadcast_policy(&channel_id, Some(policy.clone())).await.unwrap();
+
+    let key_package = bob.generate_key_package().await.unwrap();
+    let (bundle, _) = alice
+        .create_offline_invite(&channel_id, key_package, InviteOptions::default())
+        .await
+        .unwrap();
+    assert!(bundle.invite.ratchet_tree.is_some());
+    let sealed = bundle.seal(PASSPHRASE).unwrap();
+    assert!(bundle.size_warning(sealed.len()).is_none());
+
+    // Bob joins from the file alone
+    let bundle = OfflineInvite::open(&sealed, PASSPHRASE).unwrap();
+    let joined = bob.join_offline(&bundle).await.unwrap();
+    assert_eq!(joined, channel_id);
+    assert!(bob.is_join_deferred(&channel_id));
+    assert_eq!(bob.broadcast_policy(&channel_id).await, Some(policy));
+
+    // Alice posts while Bob is cut off; Bob's own post goes to the outbox
+    alice.send_message(&channel_id, b"check in when you can").await.unwrap();
+    bob.send_message(&channel_id, b"made it to base camp").await.unwrap();
+    assert_eq!(bob.outbox_len(), 1);
+    let history = bob.get_messages(&channel_id, 10, None).await.unwrap();
+    assert!(history.iter().any(|m| m.body == b"made it to base camp"));
+
+    // Still offline: nothing completes, nothing is lost
+    let report = bob.resume_online().await.unwrap();
+    assert!(report.joins_completed.is_empty());
+    assert_eq!((report.delivered, report.still_queued), (0, 1));
+    assert!(bob.is_join_deferred(&channel_id));
+
+    // The network comes back
+    bob_mailbox.online.store(true, Ordering::SeqCst);
+    let report = bob.resume_online().await.unwrap();
+    assert_eq!(report.joins_completed, vec![channel_id.clone()]);
+    assert_eq!((report.delivered, report.still_queued), (1, 0));
+    assert_eq!(report.received.len(), 1);
+    assert_eq!(report.received[0].body, b"check in when you can");
+    assert!(!bob.is_join_deferred(&channel_id));
+
+    let received = alice.fetch_broadcast(&channel_id).await.unwrap();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].body, b"made it to base camp");
+    assert_eq!(received[0].sender, user("bob"));
+}
+
+#[tokio::test]
+async fn test_offline_invite_rejects_mismatched_inviter() {
+    let (alice_dir, bob_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let alice = create_manager("alice", &alice_dir);
+    let bob = create_manager("bob", &bob_dir).with_network(dead_network());
+
+    let channel_id = alice.create_channel("field-notes".to_string(), false).await.unwrap();
+    let (mut bundle, _) = alice
+        .create_offline_invite(
+            &channel_id,
+            bob.generate_key_package().await.unwrap(),
+            InviteOptions::default(),
+        )
+        .await
+        .unwrap();
+    bundle.inviter.user_id = user("mallory");
+
+    let result = bob.join_offline(&bundle).await;
+    assert!(matches!(result, Err(MvpError::InvalidInvite(_))));
+    assert!(!bob.is_join_deferred(&channel_id));
+}
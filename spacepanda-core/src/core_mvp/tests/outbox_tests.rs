@@ -0,0 +1,161 @@
+//! Tests for the outbox of undeliverable sends
+//!
+//! Bob posts to a broadcast channel of their own while its mailbox
+//! refuses every operation. The sends have to succeed locally and wait in
+//! the outbox, survive Bob restarting, and reach the mailbox in the order
+//! they were sent once a peer connects.
+
+use super::common::{create_manager, user};
+use crate::core_dht::DhtStorage;
+use crate::core_mvp::broadcast::{BroadcastMailbox, BroadcastPolicy, DhtMailbox, MailboxEntry};
+use crate::core_mvp::channel_manager::ChannelManager;
+use crate::core_mvp::errors::{MvpError, MvpResult};
+use crate::core_mvp::events::ChannelEventKind;
+use crate::core_router::PeerId;
+use crate::core_store::model::types::{ChannelId, MessageId, UserId};
+use async_trait::async_trait;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::sync::broadcast;
+use tokio::time::timeout;
+
+/// Mailbox that fails every operation until switched online
+struct SwitchableMailbox {
+    inner: DhtMailbox,
+    online: AtomicBool,
+}
+
+impl SwitchableMailbox {
+    fn new(dht: &DhtStorage, online: bool) -> Arc<Self> {
+        Arc::new(Self { inner: DhtMailbox::new(dht.clone()), online: AtomicBool::new(online) })
+    }
+
+    fn check(&self) -> MvpResult<()> {
+        if self.online.load(Ordering::SeqCst) {
+            Ok(())
+        } else {
+            Err(MvpError::NetworkError("mailbox unreachable".to_string()))
+        }
+    }
+}
+
+#[async_trait]
+impl BroadcastMailbox for SwitchableMailbox {
+    async fn publish(
+        &self,
+        channel_id: &ChannelId,
+        sender: &UserId,
+        ciphertext: Vec<u8>,
+    ) -> MvpResult<u64> {
+        self.check()?;
+        self.inner.publish(channel_id, sender, ciphertext).await
+    }
+
+    async fn fetch(&self, channel_id: &ChannelId, from_seq: u64) -> MvpResult<Vec<MailboxEntry>> {
+        self.check()?;
+        self.inner.fetch(channel_id, from_seq).await
+    }
+}
+
+/// A broadcast channel of Bob's, created while the mailbox is reachable
+async fn setup(bob: &ChannelManager, bob_mailbox: &SwitchableMailbox) -> ChannelId {
+    let channel_id = bob.create_channel("field-notes".to_string(), false).await.unwrap();
+    let policy = BroadcastPolicy::new([user("bob")]);
+    bob.set_broadcast_policy(&channel_id, Some(policy)).await.unwrap();
+    bob_mailbox.online.store(false, Ordering::SeqCst);
+    channel_id
+}
+
+#[tokio::test]
+async fn test_queued_sends_survive_restart_and_deliver_in_order() {
+    let bob_dir = TempDir::new().unwrap();
+    let dht = DhtStorage::new();
+    let published = DhtMailbox::new(dht.clone());
+
+    let bob_mailbox = SwitchableMailbox::new(&dht, true);
+    let bob = create_manager("bob", &bob_dir).with_broadcast_mailbox(bob_mailbox.clone());
+    let channel_id = setup(&bob, &bob_mailbox).await;
+
+    // The network is down: sends succeed locally and wait in the outbox
+    let bodies: [&[u8]; 3] = [b"one", b"two", b"three"];
+    let mut ciphertexts = Vec::new();
+    for body in bodies {
+        ciphertexts.push(bob.send_message(&channel_id, body).await.unwrap());
+    }
+    let sent: Vec<MessageId> = ciphertexts.iter().map(|c| MessageId::from_ciphertext(c)).collect();
+    assert_eq!(bob.outbox_len(), 3);
+    assert!(published.fetch(&channel_id, 0).await.unwrap().is_empty());
+    let history = bob.get_messages(&channel_id, 10, None).await.unwrap();
+    assert_eq!(history.len(), 3);
+
+    // Bob restarts, still offline
+    drop(bob);
+    let bob_mailbox = SwitchableMailbox::new(&dht, false);
+    let bob = Arc::new(create_manager("bob", &bob_dir).with_broadcast_mailbox(bob_mailbox.clone()));
+    let queued: Vec<MessageId> = bob
+        .outbox_messages()
+        .iter()
+        .map(|e| MessageId::from_ciphertext(&e.ciphertext))
+        .collect();
+    assert_eq!(queued, sent);
+    assert_eq!(bob.flush_outbox().await.unwrap(), 0);
+    assert_eq!(bob.outbox_messages()[0].attempts, 1);
+
+    // The network comes back and a peer connects
+    let mut events = bob.subscribe_events();
+    let (connected_tx, connected_rx) = broadcast::channel(4);
+    let flusher = bob.clone().spawn_outbox_flusher(
+        connected_rx,
+        Duration::from_secs(3600),
+        Duration::from_secs(3600),
+    );
+    bob_mailbox.online.store(true, Ordering::SeqCst);
+    connected_tx.send(PeerId(vec![1])).unwrap();
+
+    let mut delivered = Vec::new();
+    while delivered.len() < sent.len() {
+        let event = timeout(Duration::from_secs(5), events.recv()).await.unwrap().unwrap();
+        if let ChannelEventKind::MessageDelivered { message_id } = event.kind {
+            assert_eq!(event.channel_id, channel_id);
+            delivered.push(message_id);
+        }
+    }
+    flusher.abort();
+    assert_eq!(delivered, sent);
+    assert_eq!(bob.outbox_len(), 0);
+
+    let entries = published.fetch(&channel_id, 0).await.unwrap();
+    let published: Vec<Vec<u8>> = entries.into_iter().map(|e| e.ciphertext).collect();
+    assert_eq!(published, ciphertexts);
+}
+
+#[tokio::test]
+async fn test_full_outbox_evicts_oldest() {
+    let bob_dir = TempDir::new().unwrap();
+    let dht = DhtStorage::new();
+
+    let bob_mailbox = SwitchableMailbox::new(&dht, true);
+    let bob = create_manager("bob", &bob_dir)
+        .with_broadcast_mailbox(bob_mailbox.clone())
+        .with_outbox_limit(1);
+    let channel_id = setup(&bob, &bob_mailbox).await;
+    let mut events = bob.subscribe_events();
+
+    let first = bob.send_message(&channel_id, b"first").await.unwrap();
+    let second = bob.send_message(&channel_id, b"second").await.unwrap();
+
+    // Only the newest message is kept, however far over the cap it is
+    let queued = bob.outbox_messages();
+    assert_eq!(queued.len(), 1);
+    assert_eq!(queued[0].ciphertext, second);
+
+    let evicted = loop {
+        let event = timeout(Duration::from_secs(5), events.recv()).await.unwrap().unwrap();
+        if let ChannelEventKind::MessageEvicted { message_id } = event.kind {
+            break message_id;
+        }
+    };
+    assert_eq!(evicted, MessageId::from_ciphertext(&first));
+}
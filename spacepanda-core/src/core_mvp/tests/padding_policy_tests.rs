@@ -0,0 +1,68 @@
+//! Tests for the channel-wide padding policy
+//!
+//! The channel creator's padding strategy is recorded with the channel and
+//! carried in invites, so members who join with a different local
+//! configuration still pad the way the rest of the channel does.
+
+use super::common::{create_manager_with, ManagerOptions};
+use crate::config::Config;
+use crate::core_mls::padding::PaddingStrategy;
+use crate::core_mvp::channel_manager::ChannelManager;
+use tempfile::TempDir;
+
+/// Helper to create a test ChannelManager
+fn create_manager(name: &str, temp_dir: &TempDir, config: Config) -> ChannelManager {
+    create_manager_with(name, temp_dir, ManagerOptions { config, ..Default::default() }).0
+}
+
+#[tokio::test]
+async fn test_joiner_adopts_channel_padding() {
+    let (alice_dir, bob_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let mut config = Config::default();
+    config.privacy.padding = PaddingStrategy::Buckets { sizes: vec![1024, 65536] };
+    config.privacy.padding_bucket = 1024;
+    let alice = create_manager("alice", &alice_dir, config);
+    let bob = create_manager("bob", &bob_dir, Config::default());
+
+    let channel_id = alice.create_channel("quiet".to_string(), false).await.unwrap();
+    let policy = alice.get_channel(&channel_id).await.unwrap().padding;
+    assert_eq!(policy, PaddingStrategy::Buckets { sizes: vec![1024, 65536] });
+
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    assert_eq!(invite.padding.as_ref(), Some(&policy));
+    bob.join_channel(&invite).await.unwrap();
+    assert_eq!(bob.get_channel(&channel_id).await.unwrap().padding, policy);
+
+    // Bob's own default would put these in different buckets
+    let short = bob.send_message(&channel_id, b"ok").await.unwrap();
+    let long = bob.send_message(&channel_id, &[b'a'; 600]).await.unwrap();
+    assert_eq!(short.len(), long.len());
+
+    assert_eq!(alice.receive_message(&short).await.unwrap(), b"ok");
+    assert_eq!(alice.receive_message(&long).await.unwrap(), vec![b'a'; 600]);
+}
+
+#[tokio::test]
+async fn test_padme_channel_round_trip() {
+    let (alice_dir, bob_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let mut config = Config::default();
+    config.privacy.padding = PaddingStrategy::Padme;
+    let alice = create_manager("alice", &alice_dir, config);
+    let bob = create_manager("bob", &bob_dir, Config::default());
+
+    let channel_id = alice.create_channel("terse".to_string(), false).await.unwrap();
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.join_channel(&invite).await.unwrap();
+    assert_eq!(bob.get_channel(&channel_id).await.unwrap().padding, PaddingStrategy::Padme);
+
+    // Padmé keeps short messages short
+    let ciphertext = alice.send_message(&channel_id, b"hi").await.unwrap();
+    assert!(ciphertext.len() < 256);
+    assert_eq!(bob.receive_message(&ciphertext).await.unwrap(), b"hi");
+}
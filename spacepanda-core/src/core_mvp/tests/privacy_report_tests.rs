@@ -0,0 +1,146 @@
+//! Tests for per-channel privacy reports
+//!
+//! Each report field must follow the setting it is read from: the channel
+//! directory listing, the configured padding bucket and the broadcast policy.
+
+use super::common::{create_manager_with, ManagerOptions};
+use crate::config::Config;
+use crate::core_dht::DhtStorage;
+use crate::core_mvp::broadcast::BroadcastPolicy;
+use crate::core_mvp::channel_directory::ChannelDirectory;
+use crate::core_mvp::channel_manager::ChannelManager;
+use crate::core_mvp::errors::MvpError;
+use crate::core_mvp::privacy::{MemberListVisibility, PrivacyRating};
+use crate::core_store::model::types::{ChannelId, UserId};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+/// Helper to create a test ChannelManager with the given config
+fn create_manager(
+    name: &str,
+    temp_dir: &TempDir,
+    config: Config,
+    directory: Option<Arc<ChannelDirectory>>,
+) -> ChannelManager {
+    let options = ManagerOptions { config, ..Default::default() };
+    let (manager, _) = create_manager_with(name, temp_dir, options);
+    match directory {
+        Some(directory) => manager.with_channel_directory(directory),
+        None => manager,
+    }
+}
+
+#[tokio::test]
+async fn test_report_for_private_channel() {
+    let dir = TempDir::new().unwrap();
+    let alice = create_manager("alice", &dir, Config::default(), None);
+    let channel_id = alice.create_channel("general".to_string(), false).await.unwrap();
+
+    let report = alice.privacy_report(&channel_id).await.unwrap();
+    assert_eq!(report.channel_id, channel_id.0);
+    assert!(!report.discoverable);
+    assert!(!report.sealed_sender);
+    assert!(!report.presence);
+    assert_eq!(report.member_list, MemberListVisibility::Members);
+    assert_eq!(report.membership_batch_interval, None);
+    assert!(!report.recommendations.is_empty());
+    assert!(report.score <= 100);
+}
+
+#[tokio::test]
+async fn test_report_reflects_directory_listing() {
+    let storage = DhtStorage::new();
+    let listed_dir = TempDir::new().unwrap();
+    let unlisted_dir = TempDir::new().unwrap();
+    let directory = Arc::new(ChannelDirectory::new(storage));
+    let listed = create_manager("bot", &listed_dir, Config::default(), Some(directory));
+    let unlisted = create_manager("bot", &unlisted_dir, Config::default(), None);
+
+    let channel_id = listed.create_channel_with_key("ops".to_string(), "ops").await.unwrap();
+    assert_eq!(
+        unlisted.create_channel_with_key("ops".to_string(), "ops").await.unwrap(),
+        channel_id
+    );
+
+    let listed_report = listed.privacy_report(&channel_id).await.unwrap();
+    let unlisted_report = unlisted.privacy_report(&channel_id).await.unwrap();
+
+    assert!(listed_report.discoverable);
+    assert!(listed_report
+        .recommendations
+        .iter()
+        .any(|r| r.contains("publicly discoverable")));
+    assert!(!unlisted_report.discoverable);
+    assert!(unlisted_report.score > listed_report.score);
+}
+
+#[tokio::test]
+async fn test_report_reflects_padding_bucket() {
+    let small_dir = TempDir::new().unwrap();
+    let large_dir = TempDir::new().unwrap();
+    let mut config = Config::default();
+    config.privacy.padding_bucket = 1024;
+    let small = create_manager("alice", &small_dir, Config::default(), None);
+    let large = create_manager("alice", &large_dir, config, None);
+
+    let small_channel = small.create_channel("general".to_string(), false).await.unwrap();
+    let large_channel = large.create_channel("general".to_string(), false).await.unwrap();
+
+    let small_report = small.privacy_report(&small_channel).await.unwrap();
+    let large_report = large.privacy_report(&large_channel).await.unwrap();
+
+    assert_eq!(small_report.padding_bucket, Some(256));
+    assert!(small_report.recommendations.iter().any(|r| r.contains("padding bucket ≥1024")));
+    assert_eq!(large_report.padding_bucket, Some(1024));
+    assert!(!large_report.recommendations.iter().any(|r| r.contains("padding")));
+    assert!(large_report.score > small_report.score);
+
+    // The configured bucket is what messages are actually padded to
+    let small_ciphertext = small.send_message(&small_channel, b"hi").await.unwrap();
+    let large_ciphertext = large.send_message(&large_channel, b"hi").await.unwrap();
+    assert!(large_ciphertext.len() > small_ciphertext.len() + 512);
+}
+
+#[tokio::test]
+async fn test_report_reflects_broadcast_policy() {
+    let dir = TempDir::new().unwrap();
+    let alice = create_manager("alice", &dir, Config::default(), None);
+    let channel_id = alice.create_channel("announcements".to_string(), false).await.unwrap();
+    let baseline = alice.privacy_report(&channel_id).await.unwrap();
+
+    let policy = BroadcastPolicy::new([UserId("alice@spacepanda.local".to_string())])
+        .with_batch_interval(Duration::from_secs(600));
+    alice.set_broadcast_policy(&channel_id, Some(policy)).await.unwrap();
+    let broadcast = alice.privacy_report(&channel_id).await.unwrap();
+    assert_eq!(broadcast.membership_batch_interval, Some(Duration::from_secs(600)));
+    assert!(broadcast.recommendations.iter().any(|r| r.contains("600s")));
+    assert!(broadcast.score < baseline.score);
+
+    alice.set_broadcast_policy(&channel_id, None).await.unwrap();
+    assert_eq!(alice.privacy_report(&channel_id).await.unwrap(), baseline);
+}
+
+#[tokio::test]
+async fn test_report_rating_follows_score() {
+    let dir = TempDir::new().unwrap();
+    let mut config = Config::default();
+    config.privacy.padding_bucket = 4096;
+    let alice = create_manager("alice", &dir, config, None);
+    let channel_id = alice.create_channel("general".to_string(), false).await.unwrap();
+
+    // Only the fixed findings apply: sender visible to relays, no retention
+    let report = alice.privacy_report(&channel_id).await.unwrap();
+    assert_eq!(report.score, 80);
+    assert_eq!(report.rating, PrivacyRating::Strong);
+    assert_eq!(report.recommendations.len(), 2);
+}
+
+#[tokio::test]
+async fn test_report_for_unknown_channel() {
+    let dir = TempDir::new().unwrap();
+    let alice = create_manager("alice", &dir, Config::default(), None);
+
+    let result = alice.privacy_report(&ChannelId("missing".to_string())).await;
+    assert!(matches!(result, Err(MvpError::ChannelNotFound(_))));
+}
@@ -0,0 +1,82 @@
+//! Tests for channels that require a pre-shared key
+//!
+//! Invites to a PSK channel commit the PSK together with the new member,
+//! so a valid Welcome is not enough to join: without the secret (or with
+//! the wrong one) the joiner can't derive the epoch secrets.
+
+use super::common::create_manager;
+use crate::core_mvp::errors::MvpError;
+use tempfile::TempDir;
+
+const PSK_ID: &[u8] = b"ops-psk";
+const PSK: &[u8] = b"shared out of band";
+
+#[tokio::test]
+async fn test_welcome_alone_does_not_join_psk_channel() {
+    let dirs: Vec<TempDir> = (0..3).map(|_| TempDir::new().unwrap()).collect();
+    let alice = create_manager("alice", &dirs[0]);
+    let mallory = create_manager("mallory", &dirs[1]);
+    let eve = create_manager("eve", &dirs[2]);
+
+    let channel_id = alice.create_channel_with_psk("ops".to_string(), PSK_ID, PSK).await.unwrap();
+
+    // A valid Welcome, but no PSK
+    let (invite, _) = alice
+        .create_invite(&channel_id, mallory.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    assert!(matches!(mallory.join_channel(&invite).await, Err(MvpError::Mls(_))));
+    assert!(mallory.get_channel(&channel_id).await.is_err());
+
+    // A valid Welcome and the wrong secret
+    let (invite, _) = alice
+        .create_invite(&channel_id, eve.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    assert!(matches!(
+        eve.join_with_psk(&invite, PSK_ID, b"a guess").await,
+        Err(MvpError::Mls(_))
+    ));
+    assert!(eve.get_channel(&channel_id).await.is_err());
+}
+
+#[tokio::test]
+async fn test_join_with_psk() {
+    let dirs: Vec<TempDir> = (0..4).map(|_| TempDir::new().unwrap()).collect();
+    let alice = create_manager("alice", &dirs[0]);
+    let bob = create_manager("bob", &dirs[1]);
+    let carol = create_manager("carol", &dirs[2]);
+    let dave = create_manager("dave", &dirs[3]);
+
+    let channel_id = alice.create_channel_with_psk("ops".to_string(), PSK_ID, PSK).await.unwrap();
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    assert_eq!(bob.join_with_psk(&invite, PSK_ID, PSK).await.unwrap(), channel_id);
+
+    let ciphertext = bob.send_message(&channel_id, b"on call tonight").await.unwrap();
+    assert_eq!(alice.receive_message(&ciphertext).await.unwrap(), b"on call tonight");
+
+    let commit = alice.promote_admin(&channel_id, &bob.identity().as_bytes()).await.unwrap();
+    bob.process_commit(&commit).await.unwrap();
+
+    // Bob's invites commit the PSK too; Alice has it, so she follows along
+    let (invite, commit) = bob
+        .create_invite(&channel_id, carol.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    alice.process_commit(&commit.unwrap()).await.unwrap();
+    carol.join_with_psk(&invite, PSK_ID, PSK).await.unwrap();
+
+    let ciphertext = carol.send_message(&channel_id, b"taking over").await.unwrap();
+    assert_eq!(alice.receive_message(&ciphertext).await.unwrap(), b"taking over");
+    assert_eq!(bob.receive_message(&ciphertext).await.unwrap(), b"taking over");
+
+    let (invite, commit) = bob
+        .create_invite(&channel_id, dave.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    alice.process_commit(&commit.unwrap()).await.unwrap();
+    assert!(matches!(dave.join_channel(&invite).await, Err(MvpError::Mls(_))));
+}
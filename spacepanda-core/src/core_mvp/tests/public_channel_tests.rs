@@ -0,0 +1,103 @@
+//! Tests for joining public channels by external commit
+//!
+//! A public channel publishes its GroupInfo to the public group directory;
+//! a newcomer joins from it alone and the members merge the newcomer's
+//! external commit. Private channels publish nothing.
+
+use super::common;
+use crate::core_dht::DhtStorage;
+use crate::core_mvp::channel_manager::ChannelManager;
+use crate::core_mvp::errors::MvpError;
+use crate::core_mvp::public_group_directory::PublicGroupDirectory;
+use crate::core_store::model::types::{ChannelId, UserId};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+/// Helper to create a test ChannelManager sharing a public group directory
+fn create_manager(
+    name: &str,
+    temp_dir: &TempDir,
+    directory: &Arc<PublicGroupDirectory>,
+) -> ChannelManager {
+    let manager = common::create_manager(name, temp_dir);
+    manager.with_public_group_directory(directory.clone())
+}
+
+/// User IDs of a channel's members, sorted
+async fn member_ids(manager: &ChannelManager, channel_id: &ChannelId) -> Vec<String> {
+    let mut ids: Vec<String> = manager
+        .list_members(channel_id)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|m| m.user_id.0)
+        .collect();
+    ids.sort();
+    ids
+}
+
+#[tokio::test]
+async fn test_join_public_channel_by_external_commit() {
+    let dirs: Vec<TempDir> = (0..3).map(|_| TempDir::new().unwrap()).collect();
+    let directory = Arc::new(PublicGroupDirectory::new(DhtStorage::new()));
+    let alice = create_manager("alice", &dirs[0], &directory);
+    let bob = create_manager("bob", &dirs[1], &directory);
+    let carol = create_manager("carol", &dirs[2], &directory);
+
+    let channel_id = alice.create_channel("lobby".to_string(), true).await.unwrap();
+    assert!(alice.get_channel(&channel_id).await.unwrap().is_public);
+    let info = directory.lookup(&channel_id).unwrap().unwrap();
+    assert!(info.accepts_external_commits());
+    assert_eq!((info.epoch, info.name.as_deref()), (0, Some("lobby")));
+
+    // Bob joins from the directory alone
+    let commit = bob.join_public_channel(&channel_id).await.unwrap();
+    alice.process_commit(&commit).await.unwrap();
+
+    let bob_channel = bob.get_channel(&channel_id).await.unwrap();
+    assert_eq!(bob_channel.name, "lobby");
+    assert!(bob_channel.is_public);
+    assert_eq!(bob_channel.owner, UserId("alice@spacepanda.local".to_string()));
+
+    let ciphertext = bob.send_message(&channel_id, b"hello lobby").await.unwrap();
+    assert_eq!(alice.receive_message(&ciphertext).await.unwrap(), b"hello lobby");
+
+    // Bob republished the GroupInfo for the new epoch, so Carol can join too
+    assert_eq!(directory.lookup(&channel_id).unwrap().unwrap().epoch, 1);
+    let commit = carol.join_public_channel(&channel_id).await.unwrap();
+    alice.process_commit(&commit).await.unwrap();
+    bob.process_commit(&commit).await.unwrap();
+
+    let expected = vec!["alice@spacepanda.local", "bob@spacepanda.local", "carol@spacepanda.local"];
+    assert_eq!(member_ids(&alice, &channel_id).await, expected);
+    assert_eq!(member_ids(&bob, &channel_id).await, expected);
+    assert_eq!(member_ids(&carol, &channel_id).await, expected);
+
+    assert!(matches!(
+        carol.join_public_channel(&channel_id).await,
+        Err(MvpError::ChannelExists(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_private_channel_is_not_published() {
+    let (alice_dir, bob_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let directory = Arc::new(PublicGroupDirectory::new(DhtStorage::new()));
+    let alice = create_manager("alice", &alice_dir, &directory);
+    let bob = create_manager("bob", &bob_dir, &directory);
+
+    let channel_id = alice.create_channel("backroom".to_string(), false).await.unwrap();
+    assert!(!alice.get_channel(&channel_id).await.unwrap().is_public);
+
+    // Commits don't publish it either
+    alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    assert!(directory.lookup(&channel_id).unwrap().is_none());
+
+    assert!(matches!(
+        bob.join_public_channel(&channel_id).await,
+        Err(MvpError::ChannelNotFound(_))
+    ));
+}
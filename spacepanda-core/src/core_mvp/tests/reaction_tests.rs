@@ -0,0 +1,170 @@
+//! Tests for emoji reactions
+//!
+//! Reactions travel as small application messages and are kept in each
+//! message's OR-Set of (user, emoji) pairs, so members converge whatever
+//! order reactions and unreactions reach them in. A reaction that arrives
+//! before its message is parked until the message lands.
+
+use super::common::{create_manager, create_manager_with, ManagerOptions};
+use crate::core_mls::padding::PaddingStrategy;
+use crate::core_mls::service::MlsService;
+use crate::core_mls::types::GroupId;
+use crate::core_mvp::channel_manager::ChannelManager;
+use crate::core_mvp::errors::MvpError;
+use crate::core_mvp::network::IncomingMessage;
+use crate::core_mvp::reactions::{ReactionOp, ReactionUpdate};
+use crate::core_mvp::types::{ChatMessage, ReactionSummary};
+use crate::core_router::PeerId;
+use crate::core_store::crdt::AddId;
+use crate::core_store::model::types::{ChannelId, MessageId};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+/// Alice and Bob in a channel
+async fn setup(
+    dirs: &[TempDir],
+) -> (Arc<ChannelManager>, Arc<ChannelManager>, Arc<MlsService>, ChannelId) {
+    let alice = Arc::new(create_manager("alice", &dirs[0]));
+    let (bob, bob_mls) = create_manager_with("bob", &dirs[1], ManagerOptions::default());
+    let bob = Arc::new(bob);
+
+    let channel_id = alice.create_channel("chat".to_string(), false).await.unwrap();
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.join_channel(&invite).await.unwrap();
+    (alice, bob, bob_mls, channel_id)
+}
+
+/// Hand a ciphertext from one manager to another
+async fn deliver(
+    to: &ChannelManager,
+    from: &ChannelManager,
+    channel_id: &ChannelId,
+    ciphertext: Vec<u8>,
+) -> Option<ChatMessage> {
+    let incoming = IncomingMessage {
+        channel_id: channel_id.clone(),
+        ciphertext,
+        sender_id: from.identity().user_id.clone(),
+        sender_peer_id: PeerId(vec![1]),
+    };
+    to.handle_incoming_message(incoming).await.unwrap()
+}
+
+/// (emoji, count) of the only message in the channel
+async fn counts(manager: &ChannelManager, channel_id: &ChannelId) -> Vec<(String, usize)> {
+    let history = manager.get_messages(channel_id, 10, None).await.unwrap();
+    assert_eq!(history.len(), 1);
+    history[0].reactions.iter().map(|r| (r.emoji.clone(), r.count)).collect()
+}
+
+#[tokio::test]
+async fn test_reactions_reach_members() {
+    let dirs: Vec<TempDir> = (0..2).map(|_| TempDir::new().unwrap()).collect();
+    let (alice, bob, _, channel_id) = setup(&dirs).await;
+
+    let ciphertext = alice.send_message(&channel_id, b"lunch?").await.unwrap();
+    let message_id = deliver(&bob, &alice, &channel_id, ciphertext).await.unwrap().message_id;
+
+    let reaction = alice.react(&channel_id, message_id.clone(), "👍").await.unwrap();
+    assert!(deliver(&bob, &alice, &channel_id, reaction).await.is_none());
+    for emoji in ["👍", "❤️"] {
+        let reaction = bob.react(&channel_id, message_id.clone(), emoji).await.unwrap();
+        deliver(&alice, &bob, &channel_id, reaction).await;
+    }
+
+    let expected = vec![("👍".to_string(), 2), ("❤️".to_string(), 1)];
+    assert_eq!(counts(&alice, &channel_id).await, expected);
+    assert_eq!(counts(&bob, &channel_id).await, expected);
+
+    let history = alice.get_messages(&channel_id, 10, None).await.unwrap();
+    let heart: &ReactionSummary = &history[0].reactions[1];
+    assert_eq!(heart.users, vec![bob.identity().user_id.clone()]);
+    assert!(!heart.user_reacted);
+    assert!(history[0].reactions[0].user_reacted);
+
+    let result = alice.react(&channel_id, message_id.clone(), "👍").await;
+    assert!(matches!(result, Err(MvpError::InvalidOperation(_))));
+    let result = alice.unreact(&channel_id, message_id.clone(), "❤️").await;
+    assert!(matches!(result, Err(MvpError::InvalidOperation(_))));
+    let result = alice.react(&channel_id, MessageId::generate(), "👍").await;
+    assert!(matches!(result, Err(MvpError::MessageNotFound(_))));
+
+    let unreaction = bob.unreact(&channel_id, message_id.clone(), "👍").await.unwrap();
+    deliver(&alice, &bob, &channel_id, unreaction).await;
+    let expected = vec![("❤️".to_string(), 1), ("👍".to_string(), 1)];
+    assert_eq!(counts(&alice, &channel_id).await, expected);
+    assert_eq!(alice.get_reactions(&message_id).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_concurrent_reactions_converge() {
+    let dirs: Vec<TempDir> = (0..2).map(|_| TempDir::new().unwrap()).collect();
+    let (alice, bob, _, channel_id) = setup(&dirs).await;
+
+    let ciphertext = alice.send_message(&channel_id, b"ship it?").await.unwrap();
+    let message_id = deliver(&bob, &alice, &channel_id, ciphertext).await.unwrap().message_id;
+
+    // Concurrently: both react, and Bob changes his mind before hearing
+    // from Alice
+    let from_alice = alice.react(&channel_id, message_id.clone(), "👍").await.unwrap();
+    let bob_react = bob.react(&channel_id, message_id.clone(), "👍").await.unwrap();
+    let bob_unreact = bob.unreact(&channel_id, message_id.clone(), "👍").await.unwrap();
+    let bob_party = bob.react(&channel_id, message_id.clone(), "🎉").await.unwrap();
+
+    // Bob's unreaction overtakes his reaction on the way to Alice
+    deliver(&alice, &bob, &channel_id, bob_unreact).await;
+    deliver(&alice, &bob, &channel_id, bob_party).await;
+    deliver(&alice, &bob, &channel_id, bob_react).await;
+    deliver(&bob, &alice, &channel_id, from_alice).await;
+
+    let expected = vec![("🎉".to_string(), 1), ("👍".to_string(), 1)];
+    assert_eq!(counts(&alice, &channel_id).await, expected);
+    assert_eq!(counts(&bob, &channel_id).await, expected);
+
+    let history = bob.get_messages(&channel_id, 10, None).await.unwrap();
+    assert_eq!(history[0].reactions[1].users, vec![alice.identity().user_id.clone()]);
+}
+
+#[tokio::test]
+async fn test_reaction_before_message_is_parked() {
+    let dirs: Vec<TempDir> = (0..2).map(|_| TempDir::new().unwrap()).collect();
+    let (alice, bob, _, channel_id) = setup(&dirs).await;
+
+    let original = alice.send_message(&channel_id, b"first!").await.unwrap();
+    let history = alice.get_messages(&channel_id, 10, None).await.unwrap();
+    let reaction = alice.react(&channel_id, history[0].message_id.clone(), "🎉").await.unwrap();
+
+    // The reaction overtakes the message on the way to Bob
+    assert!(deliver(&bob, &alice, &channel_id, reaction).await.is_none());
+    assert!(bob.get_messages(&channel_id, 10, None).await.unwrap().is_empty());
+
+    deliver(&bob, &alice, &channel_id, original).await.unwrap();
+    assert_eq!(counts(&bob, &channel_id).await, vec![("🎉".to_string(), 1)]);
+}
+
+#[tokio::test]
+async fn test_reaction_for_someone_else_is_dropped() {
+    let dirs: Vec<TempDir> = (0..2).map(|_| TempDir::new().unwrap()).collect();
+    let (alice, bob, bob_mls, channel_id) = setup(&dirs).await;
+
+    let ciphertext = alice.send_message(&channel_id, b"vote here").await.unwrap();
+    let message_id = deliver(&bob, &alice, &channel_id, ciphertext).await.unwrap().message_id;
+
+    // Bob sends a reaction claiming to be Alice
+    let update = ReactionUpdate {
+        channel_id: channel_id.clone(),
+        target: message_id,
+        user: alice.identity().user_id.clone(),
+        emoji: "👎".to_string(),
+        op: ReactionOp::React(AddId::new("node-bob".to_string(), 1)),
+    };
+    let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+    let padded = PaddingStrategy::default().pad(&update.encode().unwrap()).unwrap();
+    let ciphertext = bob_mls.send_message(&group_id, &padded).await.unwrap();
+    assert!(deliver(&alice, &bob, &channel_id, ciphertext).await.is_none());
+
+    assert!(counts(&alice, &channel_id).await.is_empty());
+}
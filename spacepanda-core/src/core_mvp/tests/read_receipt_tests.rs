@@ -0,0 +1,182 @@
+//! Tests for read markers and read receipts
+//!
+//! Each user's read marker lives in the channel's CRDT state, so the
+//! user's devices converge on the furthest one when the channel is synced.
+//! Channels that share read receipts also send the marker to the other
+//! members as an encrypted application message.
+
+use super::common::{create_manager_with, ManagerOptions};
+use crate::core_mls::padding::PaddingStrategy;
+use crate::core_mls::service::MlsService;
+use crate::core_mls::types::GroupId;
+use crate::core_mvp::channel_manager::{encode_read_receipt, ChannelManager};
+use crate::core_mvp::errors::MvpError;
+use crate::core_mvp::network::IncomingMessage;
+use crate::core_mvp::types::ChatMessage;
+use crate::core_router::PeerId;
+use crate::core_store::model::types::{ChannelId, MessageId};
+use crate::core_store::model::ReadMarker;
+use crate::core_store::store::{LocalStore, LocalStoreConfig};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+/// Helper to create a test ChannelManager, along with its store and MLS service
+fn create_manager(
+    name: &str,
+    temp_dir: &TempDir,
+) -> (Arc<ChannelManager>, Arc<LocalStore>, Arc<MlsService>) {
+    let (manager, mls_service) = create_manager_with(name, temp_dir, ManagerOptions::default());
+    let store = manager.store().clone();
+    (Arc::new(manager), store, mls_service)
+}
+
+fn incoming(
+    channel_id: &ChannelId,
+    ciphertext: Vec<u8>,
+    sender: &ChannelManager,
+) -> IncomingMessage {
+    IncomingMessage {
+        channel_id: channel_id.clone(),
+        ciphertext,
+        sender_id: sender.identity().user_id.clone(),
+        sender_peer_id: PeerId(vec![1]),
+    }
+}
+
+fn read_flags(messages: &[ChatMessage]) -> Vec<bool> {
+    messages.iter().map(|m| m.read).collect()
+}
+
+/// Alice and Bob in a channel
+async fn setup(dirs: &[TempDir]) -> (Arc<ChannelManager>, Arc<ChannelManager>, ChannelId) {
+    let (alice, _, _) = create_manager("alice", &dirs[0]);
+    let (bob, _, _) = create_manager("bob", &dirs[1]);
+
+    let channel_id = alice.create_channel("chat".to_string(), false).await.unwrap();
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.join_channel(&invite).await.unwrap();
+    (alice, bob, channel_id)
+}
+
+#[tokio::test]
+async fn test_get_messages_annotates_read() {
+    let dirs: Vec<TempDir> = (0..2).map(|_| TempDir::new().unwrap()).collect();
+    let (alice, bob, channel_id) = setup(&dirs).await;
+
+    let mut received = Vec::new();
+    for body in ["one", "two", "three"] {
+        let ciphertext = alice.send_message(&channel_id, body.as_bytes()).await.unwrap();
+        let message = bob
+            .handle_incoming_message(incoming(&channel_id, ciphertext, &alice))
+            .await
+            .unwrap()
+            .unwrap();
+        received.push(message.message_id);
+        tokio::time::sleep(Duration::from_millis(2)).await;
+    }
+    let history = bob.get_messages(&channel_id, 10, None).await.unwrap();
+    assert_eq!(read_flags(&history), vec![false, false, false]);
+
+    // Newest first: "three" is still unread
+    let marker = bob.mark_read(&channel_id, received[1].clone()).await.unwrap();
+    assert_eq!(marker.message_id, received[1]);
+    let history = bob.get_messages(&channel_id, 10, None).await.unwrap();
+    assert_eq!(read_flags(&history), vec![false, true, true]);
+
+    // Marking an older message keeps the marker where it is
+    assert_eq!(bob.mark_read(&channel_id, received[0].clone()).await.unwrap(), marker);
+
+    let result = bob.mark_read(&channel_id, MessageId::generate()).await;
+    assert!(matches!(result, Err(MvpError::MessageNotFound(_))));
+
+    // Our own messages count as read
+    let history = alice.get_messages(&channel_id, 10, None).await.unwrap();
+    assert_eq!(read_flags(&history), vec![true, true, true]);
+}
+
+#[tokio::test]
+async fn test_devices_converge_on_furthest_marker() {
+    let dirs: Vec<TempDir> = (0..2).map(|_| TempDir::new().unwrap()).collect();
+    let (phone, phone_store, _) = create_manager("alice", &dirs[0]);
+    let channel_id = phone.create_channel("notes".to_string(), false).await.unwrap();
+    phone.send_message(&channel_id, b"first").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(2)).await;
+    phone.send_message(&channel_id, b"second").await.unwrap();
+    let history = phone.get_messages(&channel_id, 10, None).await.unwrap();
+    let (second, first) = (&history[0], &history[1]);
+
+    // Alice's laptop has a copy of the channel and reads further
+    let laptop_store = LocalStore::new(LocalStoreConfig {
+        data_dir: dirs[1].path().to_path_buf(),
+        enable_encryption: false,
+        require_signatures: false,
+        authorized_keys: Vec::new(),
+        ..Default::default()
+    })
+    .unwrap();
+    let mut laptop_channel = phone_store.get_channel(&channel_id).unwrap().unwrap();
+    let furthest = ReadMarker { message_id: second.message_id.clone(), position: second.timestamp };
+    let user_id = phone.identity().user_id.clone();
+    assert!(laptop_channel.set_read_marker(user_id.clone(), furthest.clone(), "node-laptop"));
+    laptop_store.store_channel(&laptop_channel).unwrap();
+
+    // Concurrently, the phone marks the older message read
+    phone.mark_read(&channel_id, first.message_id.clone()).await.unwrap();
+
+    // Syncing both ways leaves both devices on the later position
+    let phone_channel = phone_store.get_channel(&channel_id).unwrap().unwrap();
+    phone_store.apply_remote_channel(&laptop_channel).unwrap();
+    laptop_store.apply_remote_channel(&phone_channel).unwrap();
+
+    let markers = phone.read_markers(&channel_id).await.unwrap();
+    assert_eq!(markers, vec![(user_id.clone(), furthest.clone())]);
+    let laptop_channel = laptop_store.get_channel(&channel_id).unwrap().unwrap();
+    assert_eq!(laptop_channel.get_read_marker(&user_id), Some(&furthest));
+}
+
+#[tokio::test]
+async fn test_read_receipt_reaches_other_members() {
+    let dirs: Vec<TempDir> = (0..2).map(|_| TempDir::new().unwrap()).collect();
+    let (alice, alice_store, _) = create_manager("alice", &dirs[0]);
+    let (bob, bob_store, bob_mls) = create_manager("bob", &dirs[1]);
+
+    let channel_id = alice.create_channel("chat".to_string(), false).await.unwrap();
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.join_channel(&invite).await.unwrap();
+
+    // Only admins turn read receipts on; the flag reaches Bob with the channel
+    let result = bob.set_read_receipts(&channel_id, true).await;
+    assert!(matches!(result, Err(MvpError::PermissionDenied { .. })));
+    alice.set_read_receipts(&channel_id, true).await.unwrap();
+    bob_store
+        .apply_remote_channel(&alice_store.get_channel(&channel_id).unwrap().unwrap())
+        .unwrap();
+    assert!(bob_store.get_channel(&channel_id).unwrap().unwrap().shares_read_receipts());
+
+    let ciphertext = alice.send_message(&channel_id, b"did you see this?").await.unwrap();
+    let message = bob
+        .handle_incoming_message(incoming(&channel_id, ciphertext, &alice))
+        .await
+        .unwrap()
+        .unwrap();
+    let marker = bob.mark_read(&channel_id, message.message_id).await.unwrap();
+
+    // The receipt as it goes out to the channel
+    let group_id = GroupId::new(channel_id.0.as_bytes().to_vec());
+    let padded = PaddingStrategy::default().pad(&encode_read_receipt(&marker).unwrap()).unwrap();
+    let receipt = bob_mls.send_message(&group_id, &padded).await.unwrap();
+
+    // Alice records Bob's marker; the receipt isn't part of the history
+    let handled = alice.handle_incoming_message(incoming(&channel_id, receipt, &bob)).await;
+    assert!(handled.unwrap().is_none());
+    let markers = alice.read_markers(&channel_id).await.unwrap();
+    assert!(markers.contains(&(bob.identity().user_id.clone(), marker)));
+    assert_eq!(alice.get_messages(&channel_id, 10, None).await.unwrap().len(), 1);
+}
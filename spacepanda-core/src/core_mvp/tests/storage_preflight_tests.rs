@@ -0,0 +1,84 @@
+//! Tests for the free disk space check before joining a channel
+//!
+//! A mock filesystem reports how much space is left, so the tests control
+//! whether the estimated group state plus safety margin fits.
+
+use super::common::create_manager;
+use crate::core_mvp::channel_manager::ChannelManager;
+use crate::core_mvp::errors::MvpError;
+use crate::core_mvp::types::InviteToken;
+use crate::core_store::store::preflight::{estimate_join, FsStats, StoragePreflight};
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+const MARGIN: u64 = 1024;
+
+/// Reports a fixed amount of free space
+struct FreeSpace(u64);
+
+impl FsStats for FreeSpace {
+    fn available_space(&self, _path: &Path) -> io::Result<u64> {
+        Ok(self.0)
+    }
+}
+
+/// Bob's manager, seeing `available` bytes free
+fn create_joiner(temp_dir: &TempDir, available: u64) -> ChannelManager {
+    let preflight = StoragePreflight::new(MARGIN).with_fs_stats(Arc::new(FreeSpace(available)));
+    create_manager("bob", temp_dir).with_storage_preflight(preflight)
+}
+
+async fn invite(alice: &ChannelManager, bob: &ChannelManager) -> InviteToken {
+    let channel_id = alice.create_channel("general".to_string(), false).await.unwrap();
+    let key_package = bob.generate_key_package().await.unwrap();
+    alice.create_invite(&channel_id, key_package).await.unwrap().0
+}
+
+fn required(invite: &InviteToken) -> u64 {
+    let tree_len = invite.ratchet_tree.as_ref().map_or(0, Vec::len);
+    estimate_join(invite.welcome_blob.len(), tree_len) + MARGIN
+}
+
+#[tokio::test]
+async fn test_join_refused_below_estimate_plus_margin() {
+    let (alice_dir, bob_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let alice = create_manager("alice", &alice_dir);
+    let bob = create_joiner(&bob_dir, 0);
+    let invite = invite(&alice, &bob).await;
+    let required = required(&invite);
+
+    let err = bob.join_channel(&invite).await.unwrap_err();
+    assert!(matches!(err, MvpError::InsufficientStorage { available: 0, .. }));
+
+    // One byte short still fails, before anything is written
+    let bob = bob.with_storage_preflight(
+        StoragePreflight::new(MARGIN).with_fs_stats(Arc::new(FreeSpace(required - 1))),
+    );
+    match bob.join_channel(&invite).await.unwrap_err() {
+        MvpError::InsufficientStorage { required: r, available } => {
+            assert_eq!((r, available), (required, required - 1));
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+    assert!(bob.list_channels().await.unwrap().is_empty());
+
+    // Exactly enough is enough
+    let bob = bob.with_storage_preflight(
+        StoragePreflight::new(MARGIN).with_fs_stats(Arc::new(FreeSpace(required))),
+    );
+    assert_eq!(bob.join_channel(&invite).await.unwrap(), invite.channel_id);
+}
+
+#[tokio::test]
+async fn test_forced_join_skips_the_check() {
+    let (alice_dir, bob_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let alice = create_manager("alice", &alice_dir);
+    let bob = create_joiner(&bob_dir, 0);
+    let invite = invite(&alice, &bob).await;
+
+    assert!(bob.join_channel(&invite).await.is_err());
+    assert_eq!(bob.join_channel_forced(&invite).await.unwrap(), invite.channel_id);
+    assert_eq!(bob.list_channels().await.unwrap().len(), 1);
+}
@@ -0,0 +1,152 @@
+//! The HTTP test harness, driven over HTTP the way curl would
+
+use crate::core_mvp::test_harness::{temp_state, TestHarnessServer};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+
+/// A harness served on an ephemeral port
+struct Harness {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+impl Harness {
+    async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = TestHarnessServer::with_state(temp_state().unwrap(), addr.to_string());
+        tokio::spawn(server.serve(listener));
+
+        Self { client: reqwest::Client::new(), base_url: format!("http://{}", addr) }
+    }
+
+    async fn post(&self, path: &str, body: Value) -> (u16, Value) {
+        let response = self
+            .client
+            .post(format!("{}{}", self.base_url, path))
+            .header("Content-Type", "application/json")
+            .body(body.to_string())
+            .send()
+            .await
+            .unwrap();
+        Self::decode(response).await
+    }
+
+    async fn get(&self, path: &str) -> (u16, Value) {
+        let response = self.client.get(format!("{}{}", self.base_url, path)).send().await.unwrap();
+        Self::decode(response).await
+    }
+
+    async fn decode(response: reqwest::Response) -> (u16, Value) {
+        let status = response.status().as_u16();
+        let body = response.bytes().await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    /// Message texts in a user's history, oldest first
+    async fn plaintexts(&self, user: &str, channel: &str) -> Vec<String> {
+        let (status, body) =
+            self.get(&format!("/users/{}/channels/{}/messages", user, channel)).await;
+        assert_eq!(status, 200, "{}", body);
+        body["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|message| message["plaintext"].as_str().unwrap().to_string())
+            .collect()
+    }
+}
+
+#[tokio::test]
+async fn test_four_party_removal_over_http() {
+    let harness = Harness::start().await;
+
+    for user in ["alice", "bob", "charlie", "dave"] {
+        let (status, body) = harness.post(&format!("/users/{}/init", user), json!({})).await;
+        assert_eq!(status, 201, "{}", body);
+        assert_eq!(body["user"], user);
+    }
+
+    let (status, body) = harness
+        .post(
+            "/users/alice/channels/create",
+            json!({"name": "removal-test", "is_public": false}),
+        )
+        .await;
+    assert_eq!(status, 201, "{}", body);
+    let channel = body["channel_id"].as_str().unwrap().to_string();
+
+    // Each invite's commit is applied by everyone already in the channel
+    let mut members = vec!["alice"];
+    for user in ["bob", "charlie", "dave"] {
+        let path = format!("/users/alice/channels/{}/invite", channel);
+        let (status, body) = harness.post(&path, json!({"invitee": user})).await;
+        assert_eq!(status, 200, "{}", body);
+        let mut applied: Vec<_> = body["commit_applied_by"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|name| name.as_str().unwrap())
+            .collect();
+        applied.sort();
+        assert_eq!(applied, members[1..]);
+
+        let path = format!("/users/{}/join", user);
+        let (status, body) = harness.post(&path, json!({"invite": body["invite"]})).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["channel_id"], channel.as_str());
+        members.push(user);
+    }
+
+    let send = format!("/users/alice/channels/{}/send", channel);
+    let (status, body) = harness.post(&send, json!({"plaintext": "Before removal"})).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["delivered_to"], json!(["bob", "charlie", "dave"]));
+
+    let path = format!("/users/alice/channels/{}/remove-member", channel);
+    let (status, body) = harness.post(&path, json!({"member": "bob"})).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["commit_applied_by"], json!(["charlie", "dave"]));
+
+    // Bob still has the channel but can't read what's sent after his removal
+    let (status, body) = harness.post(&send, json!({"plaintext": "After Bob was removed"})).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["delivered_to"], json!(["charlie", "dave"]));
+
+    let send = format!("/users/charlie/channels/{}/send", channel);
+    let (status, body) = harness.post(&send, json!({"plaintext": "Charlie confirms"})).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["delivered_to"], json!(["alice", "dave"]));
+
+    assert_eq!(harness.plaintexts("bob", &channel).await, ["Before removal"]);
+    for user in ["alice", "dave"] {
+        assert_eq!(
+            harness.plaintexts(user, &channel).await,
+            ["Before removal", "After Bob was removed", "Charlie confirms"]
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_errors_map_to_status_codes() {
+    let harness = Harness::start().await;
+
+    let (status, _) = harness.post("/users/alice/init", json!({})).await;
+    assert_eq!(status, 201);
+    let (status, body) = harness.post("/users/alice/init", json!({})).await;
+    assert_eq!(status, 409);
+    assert!(body["error"].as_str().unwrap().contains("already exists"));
+    let (status, _) = harness.post("/users/al.ice/init", json!({})).await;
+    assert_eq!(status, 400);
+
+    let (status, _) = harness.get("/users/nobody/channels/missing/messages").await;
+    assert_eq!(status, 404);
+    let (status, _) = harness
+        .post("/users/alice/channels/missing/send", json!({"plaintext": "hi"}))
+        .await;
+    assert_eq!(status, 404);
+    let (status, _) = harness
+        .post("/users/alice/channels/missing/invite", json!({"invitee": "bob"}))
+        .await;
+    assert_eq!(status, 404);
+}
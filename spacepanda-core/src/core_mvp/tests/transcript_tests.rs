@@ -0,0 +1,202 @@
+//! Tests for channel transcript export
+//!
+//! A fixture channel is exported in both formats and compared byte for
+//! byte against the files in `golden/`. Set `UPDATE_GOLDEN=1` to rewrite
+//! them after an intended change to the layout.
+
+use super::common::{self, user};
+use crate::core_mvp::channel_manager::ChannelManager;
+use crate::core_mvp::errors::MvpError;
+use crate::core_mvp::transcript::{TranscriptFormat, TranscriptOptions};
+use crate::core_mvp::types::TranscriptReport;
+use crate::core_store::crdt::AddId;
+use crate::core_store::model::message::{Attachment, Message};
+use crate::core_store::model::types::{ChannelId, MessageId, Timestamp};
+use crate::core_store::store::LocalStore;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+/// 2026-03-14 09:26:53.589 UTC
+const BASE_MILLIS: u64 = 1_773_480_413_589;
+
+/// Helper to create a test ChannelManager, along with its store
+fn create_manager(temp_dir: &TempDir) -> (ChannelManager, Arc<LocalStore>) {
+    let manager = common::create_manager("alice", temp_dir);
+    let store = manager.store().clone();
+    (manager, store)
+}
+
+fn message(channel_id: &ChannelId, id: &str, sender: &str, body: &str, offset: u64) -> Message {
+    Message::new(
+        MessageId(id.to_string()),
+        channel_id.clone(),
+        user(sender),
+        body.as_bytes().to_vec(),
+        Timestamp::from_millis(BASE_MILLIS + offset),
+    )
+}
+
+fn react(message: &mut Message, name: &str, emoji: &str, offset: u64) {
+    message
+        .add_reaction(user(name), emoji.to_string(), AddId::new(format!("node-{}", name), offset))
+        .unwrap();
+}
+
+/// A channel with edits, reactions, a reply, a deletion and an attachment
+///
+/// Messages are stored out of order, and two share a timestamp, to check
+/// that the export orders them itself.
+async fn fixture_channel(manager: &ChannelManager, store: &LocalStore) -> ChannelId {
+    let channel_id = manager
+        .create_channel_as(ChannelId("fixture-channel".to_string()), "design".to_string(), false)
+        .await
+        .unwrap();
+
+    let mut greeting = message(&channel_id, "msg-01", "alice", "Morning! Agenda is in the doc", 0);
+    react(&mut greeting, "carol", "👋", 1);
+    react(&mut greeting, "bob", "👋", 2);
+    react(&mut greeting, "bob", "👍", 3);
+
+    let mut question = message(&channel_id, "msg-02", "bob", "Are we shiping on Friday?", 61_000);
+    question.add_edit(
+        Timestamp::from_millis(BASE_MILLIS + 75_000),
+        user("bob"),
+        b"Are we shipping on Friday?".to_vec(),
+    );
+
+    let answer = Message::new_reply(
+        MessageId("msg-03".to_string()),
+        channel_id.clone(),
+        user("carol"),
+        b"Yes, if review is done by Thursday".to_vec(),
+        Timestamp::from_millis(BASE_MILLIS + 122_000),
+        MessageId("msg-02".to_string()),
+    );
+
+    let mut deleted = message(&channel_id, "msg-04", "bob", "wrong channel, sorry", 122_000);
+    deleted.delete();
+
+    let mut mockup = message(&channel_id, "msg-05", "carol", "", 183_000);
+    mockup.add_attachment(Attachment {
+        id: "att-01".to_string(),
+        filename: "mockup.png".to_string(),
+        mime_type: "image/png".to_string(),
+        size_bytes: 48_213,
+        content_hash: "c0ffee".to_string(),
+    });
+    react(&mut mockup, "alice", "🎉", 4);
+
+    for message in [&mockup, &answer, &greeting, &deleted, &question] {
+        store.store_message(message).unwrap();
+    }
+    channel_id
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/core_mvp/tests/golden")
+        .join(name)
+}
+
+fn assert_golden(name: &str, actual: &str) {
+    let path = golden_path(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(actual, expected, "transcript differs from {}", path.display());
+}
+
+async fn export(
+    manager: &ChannelManager,
+    channel_id: &ChannelId,
+    options: TranscriptOptions,
+) -> (String, TranscriptReport) {
+    let mut out = Vec::new();
+    let report = manager.export_channel(channel_id, options, &mut out).await.unwrap();
+    (String::from_utf8(out).unwrap(), report)
+}
+
+#[tokio::test]
+async fn test_json_transcript_matches_golden() {
+    let temp_dir = TempDir::new().unwrap();
+    let (manager, store) = create_manager(&temp_dir);
+    let channel_id = fixture_channel(&manager, &store).await;
+
+    let (json, report) = export(&manager, &channel_id, TranscriptFormat::Json.into()).await;
+    assert_eq!(report, TranscriptReport { messages: 5, redacted: 0 });
+    assert_golden("transcript.json", &json);
+
+    let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed["messages"].as_array().unwrap().len(), 5);
+}
+
+#[tokio::test]
+async fn test_markdown_transcript_matches_golden() {
+    let temp_dir = TempDir::new().unwrap();
+    let (manager, store) = create_manager(&temp_dir);
+    let channel_id = fixture_channel(&manager, &store).await;
+
+    let (markdown, _) = export(&manager, &channel_id, TranscriptFormat::Markdown.into()).await;
+    assert_golden("transcript.md", &markdown);
+}
+
+#[tokio::test]
+async fn test_transcript_redacts_sender_and_attachments() {
+    let temp_dir = TempDir::new().unwrap();
+    let (manager, store) = create_manager(&temp_dir);
+    let channel_id = fixture_channel(&manager, &store).await;
+
+    let options = TranscriptOptions::new(TranscriptFormat::Json)
+        .with_excluded_sender(user("bob"))
+        .with_attachments_excluded();
+    let (json, report) = export(&manager, &channel_id, options).await;
+    assert_eq!(report, TranscriptReport { messages: 2, redacted: 3 });
+
+    let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let ids: Vec<&str> = parsed["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(ids, ["msg-01", "msg-03"]);
+    // Bob's reactions go with his messages
+    assert!(!json.contains("bob@"));
+    assert!(!json.contains("mockup.png"));
+}
+
+#[tokio::test]
+async fn test_transcript_pages_through_long_history() {
+    let temp_dir = TempDir::new().unwrap();
+    let (manager, store) = create_manager(&temp_dir);
+    let channel_id = manager
+        .create_channel_as(ChannelId("long-channel".to_string()), "long".to_string(), false)
+        .await
+        .unwrap();
+
+    // Spans several store pages, with every pair of messages sharing a timestamp
+    for i in 0..600u64 {
+        let id = format!("msg-{:04}", i);
+        store.store_message(&message(&channel_id, &id, "alice", &id, i / 2)).unwrap();
+    }
+
+    let (json, report) = export(&manager, &channel_id, TranscriptFormat::Json.into()).await;
+    assert_eq!(report.messages, 600);
+    let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let messages = parsed["messages"].as_array().unwrap();
+    assert!(messages.iter().enumerate().all(|(i, m)| m["id"] == format!("msg-{:04}", i)));
+}
+
+#[tokio::test]
+async fn test_export_unknown_channel() {
+    let temp_dir = TempDir::new().unwrap();
+    let (manager, _) = create_manager(&temp_dir);
+
+    let result = manager
+        .export_channel(&ChannelId("missing".to_string()), TranscriptFormat::Markdown, Vec::new())
+        .await;
+    assert!(matches!(result, Err(MvpError::ChannelNotFound(_))));
+}
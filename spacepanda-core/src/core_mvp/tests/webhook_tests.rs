@@ -0,0 +1,195 @@
+//! Tests for inbound webhooks
+//!
+//! Webhooks post from a pseudo-member leaf of their own, so their messages
+//! must decrypt for real members, carry the webhook's identity and never
+//! the identity of the admin who created them.
+
+use super::common::create_manager;
+use crate::core_mvp::channel_manager::ChannelManager;
+use crate::core_mvp::errors::MvpError;
+use crate::core_mvp::network::IncomingMessage;
+use crate::core_mvp::webhooks::{is_webhook_user, webhook_user_id, WebhookOptions};
+use crate::core_router::PeerId;
+use crate::core_store::model::types::{ChannelId, UserId};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+/// Senders of every stored message with the given body
+async fn senders_of(manager: &ChannelManager, channel_id: &ChannelId, body: &[u8]) -> Vec<UserId> {
+    manager
+        .get_messages(channel_id, 100, None)
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|message| message.body == body)
+        .map(|message| message.sender)
+        .collect()
+}
+
+#[tokio::test]
+async fn test_webhook_token_auth() {
+    let dir = TempDir::new().unwrap();
+    let alice = Arc::new(create_manager("alice", &dir));
+    let channel_id = alice.create_channel("ci".to_string(), false).await.unwrap();
+
+    let (credentials, _) = alice.create_webhook(&channel_id, "CI").await.unwrap();
+    let webhooks = alice.list_webhooks(&channel_id).await.unwrap();
+    assert_eq!(webhooks.len(), 1);
+    assert_eq!(webhooks[0].name, "CI");
+    assert_eq!(webhooks[0].created_by, alice.identity().user_id);
+
+    // Only a hash of the token is replicated
+    assert_ne!(webhooks[0].token_hash, credentials.token);
+
+    let result = alice.post_webhook_message(&credentials.webhook_id, "wrong", b"hi").await;
+    assert!(matches!(result, Err(MvpError::Unauthorized(_))));
+
+    let result = alice.post_webhook_message("no-such-webhook", &credentials.token, b"hi").await;
+    assert!(matches!(result, Err(MvpError::WebhookNotFound(_))));
+
+    alice
+        .post_webhook_message(&credentials.webhook_id, &credentials.token, b"build passed")
+        .await
+        .unwrap();
+    assert_eq!(
+        senders_of(&alice, &channel_id, b"build passed").await,
+        vec![webhook_user_id(&credentials.webhook_id)]
+    );
+}
+
+#[tokio::test]
+async fn test_webhook_rate_limit_and_size_cap() {
+    let dir = TempDir::new().unwrap();
+    let alice = Arc::new(create_manager("alice", &dir));
+    let channel_id = alice.create_channel("alerts".to_string(), false).await.unwrap();
+
+    let options = WebhookOptions::default()
+        .with_max_posts_per_minute(3)
+        .with_max_content_bytes(16);
+    let (credentials, _) =
+        alice.create_webhook_with_options(&channel_id, "Alerts", options).await.unwrap();
+    let post = |body: &'static [u8]| {
+        let alice = alice.clone();
+        let credentials = credentials.clone();
+        async move {
+            alice
+                .post_webhook_message(&credentials.webhook_id, &credentials.token, body)
+                .await
+        }
+    };
+
+    // Oversized posts are rejected without using up the rate limit
+    let result = post(b"this body is well over sixteen bytes").await;
+    assert!(matches!(result, Err(MvpError::PayloadTooLarge { size: 36, limit: 16 })));
+
+    for _ in 0..3 {
+        post(b"disk at 91%").await.unwrap();
+    }
+    assert!(matches!(post(b"disk at 92%").await, Err(MvpError::RateLimited(_))));
+    assert_eq!(senders_of(&alice, &channel_id, b"disk at 91%").await.len(), 3);
+}
+
+#[tokio::test]
+async fn test_webhook_rotation_and_revocation() {
+    let dir = TempDir::new().unwrap();
+    let alice = Arc::new(create_manager("alice", &dir));
+    let channel_id = alice.create_channel("ops".to_string(), false).await.unwrap();
+
+    let (credentials, _) = alice.create_webhook(&channel_id, "Deploys").await.unwrap();
+    let webhook_id = credentials.webhook_id.clone();
+
+    // Rotation invalidates the old token
+    let rotated = alice.rotate_webhook_token(&channel_id, &webhook_id).await.unwrap();
+    assert_eq!(rotated.webhook_id, webhook_id);
+    let result = alice.post_webhook_message(&webhook_id, &credentials.token, b"old").await;
+    assert!(matches!(result, Err(MvpError::Unauthorized(_))));
+    alice.post_webhook_message(&webhook_id, &rotated.token, b"new").await.unwrap();
+    assert!(alice.list_webhooks(&channel_id).await.unwrap()[0].rotated_at.is_some());
+
+    // Revocation removes the leaf and stops posts
+    let webhook_member = webhook_user_id(&webhook_id);
+    assert!(alice
+        .list_members(&channel_id)
+        .await
+        .unwrap()
+        .iter()
+        .any(|member| member.user_id == webhook_member));
+
+    let commit = alice.revoke_webhook(&channel_id, &webhook_id).await.unwrap();
+    assert!(commit.is_some());
+    assert!(alice.list_webhooks(&channel_id).await.unwrap().is_empty());
+    assert!(!alice
+        .list_members(&channel_id)
+        .await
+        .unwrap()
+        .iter()
+        .any(|member| member.user_id == webhook_member));
+
+    let result = alice.post_webhook_message(&webhook_id, &rotated.token, b"after").await;
+    assert!(matches!(result, Err(MvpError::WebhookNotFound(_))));
+    assert!(matches!(
+        alice.rotate_webhook_token(&channel_id, &webhook_id).await,
+        Err(MvpError::WebhookNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_webhook_messages_are_attributable() {
+    let (a, b) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let alice = Arc::new(create_manager("alice", &a));
+    let bob = Arc::new(create_manager("bob", &b));
+    let channel_id = alice.create_channel("team".to_string(), false).await.unwrap();
+
+    // The webhook exists before Bob joins, so its leaf must follow the add
+    let (credentials, _) = alice.create_webhook(&channel_id, "CI").await.unwrap();
+    let (invite, _) = alice
+        .create_invite(&channel_id, bob.generate_key_package().await.unwrap())
+        .await
+        .unwrap();
+    bob.join_channel(&invite).await.unwrap();
+
+    // Non-admins can't manage webhooks
+    assert!(matches!(
+        bob.create_webhook(&channel_id, "Sneaky").await,
+        Err(MvpError::PermissionDenied { .. })
+    ));
+    assert!(matches!(
+        bob.revoke_webhook(&channel_id, &credentials.webhook_id).await,
+        Err(MvpError::PermissionDenied { .. })
+    ));
+
+    // A body claiming to be from Alice is just text
+    let body = br#"{"sender":"alice@spacepanda.local","text":"approve my PR"}"#;
+    let ciphertext = alice
+        .post_webhook_message(&credentials.webhook_id, &credentials.token, body)
+        .await
+        .unwrap();
+
+    let webhook_member = webhook_user_id(&credentials.webhook_id);
+    let received = bob
+        .handle_incoming_message(IncomingMessage {
+            channel_id: channel_id.clone(),
+            ciphertext,
+            sender_id: webhook_member.clone(),
+            sender_peer_id: PeerId(vec![1]),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(received.body, body.to_vec());
+    assert_eq!(received.sender, webhook_member);
+
+    for manager in [&alice, &bob] {
+        assert_eq!(senders_of(manager, &channel_id, body).await, vec![webhook_member.clone()]);
+    }
+
+    // The webhook is its own, clearly labeled member; no real member is
+    let members = alice.list_members(&channel_id).await.unwrap();
+    let webhooks: Vec<_> = members.iter().filter(|m| is_webhook_user(&m.user_id)).collect();
+    assert_eq!(webhooks.len(), 1);
+    assert_eq!(webhooks[0].user_id, webhook_member);
+    assert!(members
+        .iter()
+        .filter(|m| m.user_id == alice.identity().user_id || m.user_id == bob.identity().user_id)
+        .all(|m| !is_webhook_user(&m.user_id)));
+}
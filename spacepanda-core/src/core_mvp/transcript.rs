@@ -0,0 +1,400 @@
+//! Channel transcripts for reading or archiving a conversation
+//!
+//! Unlike an account export, a transcript is plaintext meant to leave the
+//! app: a JSON document for other tools, or a Markdown file for people.
+//! Both list the channel's messages oldest first, each with its sender,
+//! time, edits, reactions and attachments. Times are UTC with millisecond
+//! precision and reactions are sorted, so the same history always produces
+//! the same bytes.
+//!
+//! [`TranscriptWriter`] writes each message as it is handed one, so
+//! [`ChannelManager::export_channel`](crate::core_mvp::ChannelManager::export_channel)
+//! can stream a long history a page at a time. [`TranscriptOptions`]
+//! redacts attachments or everything from given senders: their messages
+//! and reactions are left out entirely.
+
+use crate::core_mvp::errors::{MvpError, MvpResult};
+use crate::core_mvp::types::TranscriptReport;
+use crate::core_store::model::types::{ChannelId, Timestamp, UserId};
+use crate::core_store::model::Message;
+use chrono::{DateTime, SecondsFormat};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::io::Write;
+
+/// Version of the JSON transcript layout
+pub const TRANSCRIPT_VERSION: u32 = 1;
+
+/// Output format of a transcript
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscriptFormat {
+    /// One JSON document, one message per line
+    Json,
+
+    /// Markdown for reading
+    Markdown,
+}
+
+/// Format and redactions of a transcript
+#[derive(Debug, Clone)]
+pub struct TranscriptOptions {
+    /// Output format
+    pub format: TranscriptFormat,
+
+    /// Whether messages carrying attachments are included
+    pub include_attachments: bool,
+
+    /// Senders whose messages and reactions are left out
+    pub excluded_senders: HashSet<UserId>,
+}
+
+impl TranscriptOptions {
+    /// Everything, in the given format
+    pub fn new(format: TranscriptFormat) -> Self {
+        Self { format, include_attachments: true, excluded_senders: HashSet::new() }
+    }
+
+    /// Leave out messages carrying attachments
+    pub fn with_attachments_excluded(mut self) -> Self {
+        self.include_attachments = false;
+        self
+    }
+
+    /// Leave out a sender's messages and reactions
+    pub fn with_excluded_sender(mut self, sender: UserId) -> Self {
+        self.excluded_senders.insert(sender);
+        self
+    }
+}
+
+impl From<TranscriptFormat> for TranscriptOptions {
+    fn from(format: TranscriptFormat) -> Self {
+        Self::new(format)
+    }
+}
+
+/// Fields of a JSON transcript ahead of its messages
+#[derive(Debug, Serialize)]
+struct TranscriptHeader<'a> {
+    version: u32,
+    channel_id: &'a str,
+    name: &'a str,
+}
+
+/// A message as written to a JSON transcript
+#[derive(Debug, Serialize)]
+struct TranscriptMessage<'a> {
+    id: &'a str,
+    sender: &'a str,
+    timestamp: String,
+    reply_to: Option<&'a str>,
+    /// Current body; `None` once deleted or for an attachment
+    body: Option<String>,
+    deleted: bool,
+    edits: Vec<TranscriptEdit>,
+    reactions: Vec<TranscriptReaction<'a>>,
+    attachments: Vec<TranscriptAttachment<'a>>,
+}
+
+/// An edit, with the body it replaced
+#[derive(Debug, Serialize)]
+struct TranscriptEdit {
+    edited_at: String,
+    previous_body: String,
+}
+
+#[derive(Debug, Serialize)]
+struct TranscriptReaction<'a> {
+    emoji: &'a str,
+    users: Vec<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+struct TranscriptAttachment<'a> {
+    filename: &'a str,
+    mime_type: &'a str,
+    size: u64,
+}
+
+impl<'a> TranscriptMessage<'a> {
+    fn new(message: &'a Message, reactions: &'a [(UserId, String)]) -> Self {
+        let deleted = message.deleted;
+        let attachments: Vec<TranscriptAttachment<'a>> = if deleted {
+            Vec::new()
+        } else {
+            message
+                .attachments
+                .iter()
+                .map(|a| TranscriptAttachment {
+                    filename: &a.filename,
+                    mime_type: &a.mime_type,
+                    size: a.size_bytes,
+                })
+                .collect()
+        };
+        let body = (!deleted && attachments.is_empty())
+            .then(|| String::from_utf8_lossy(message.current_content()).into_owned());
+
+        // Each edit replaced the original or the edit before it
+        let edits = if deleted {
+            Vec::new()
+        } else {
+            let replaced =
+                std::iter::once(&message.content).chain(message.edits.iter().map(|e| &e.2));
+            message
+                .edits
+                .iter()
+                .zip(replaced)
+                .map(|((edited_at, _, _), previous)| TranscriptEdit {
+                    edited_at: format_timestamp(*edited_at),
+                    previous_body: String::from_utf8_lossy(previous).into_owned(),
+                })
+                .collect()
+        };
+
+        let mut by_emoji: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        if !deleted {
+            for (user, emoji) in reactions {
+                by_emoji.entry(emoji).or_default().push(&user.0);
+            }
+        }
+        let reactions = by_emoji
+            .into_iter()
+            .map(|(emoji, mut users)| {
+                users.sort_unstable();
+                TranscriptReaction { emoji, users }
+            })
+            .collect();
+
+        Self {
+            id: &message.id.0,
+            sender: &message.sender.0,
+            timestamp: format_timestamp(message.timestamp),
+            reply_to: message.reply_to.as_ref().map(|id| id.0.as_str()),
+            body,
+            deleted,
+            edits,
+            reactions,
+            attachments,
+        }
+    }
+}
+
+/// Writes a transcript one message at a time
+pub struct TranscriptWriter<W: Write> {
+    out: W,
+    options: TranscriptOptions,
+    report: TranscriptReport,
+}
+
+impl<W: Write> TranscriptWriter<W> {
+    /// Start a transcript of a channel, writing its preamble
+    pub fn new(
+        mut out: W,
+        options: TranscriptOptions,
+        channel_id: &ChannelId,
+        name: &str,
+    ) -> MvpResult<Self> {
+        match options.format {
+            TranscriptFormat::Json => {
+                let header = TranscriptHeader {
+                    version: TRANSCRIPT_VERSION,
+                    channel_id: &channel_id.0,
+                    name,
+                };
+                // Reopen the header object to append the message array
+                let header = serde_json::to_string(&header)?;
+                let header = header.strip_suffix('}').unwrap_or(&header);
+                write!(out, "{},\"messages\":[", header).map_err(write_error)?;
+            }
+            TranscriptFormat::Markdown => {
+                write!(out, "# {}\n\nTranscript of channel `{}`\n", name, channel_id)
+                    .map_err(write_error)?;
+            }
+        }
+        Ok(Self { out, options, report: TranscriptReport::default() })
+    }
+
+    /// Write a message, unless the options redact it
+    ///
+    /// `reactions` are the message's (user, emoji) pairs.
+    pub fn write(&mut self, message: &Message, reactions: &[(UserId, String)]) -> MvpResult<()> {
+        let redacted = self.options.excluded_senders.contains(&message.sender)
+            || (!self.options.include_attachments && !message.attachments.is_empty());
+        if redacted {
+            self.report.redacted += 1;
+            return Ok(());
+        }
+
+        let reactions: Vec<(UserId, String)> = reactions
+            .iter()
+            .filter(|(user, _)| !self.options.excluded_senders.contains(user))
+            .cloned()
+            .collect();
+        let entry = TranscriptMessage::new(message, &reactions);
+        match self.options.format {
+            TranscriptFormat::Json => {
+                let separator = if self.report.messages == 0 { "" } else { "," };
+                write!(self.out, "{}\n{}", separator, serde_json::to_string(&entry)?)
+                    .map_err(write_error)?;
+            }
+            TranscriptFormat::Markdown => write_markdown(&mut self.out, &entry)?,
+        }
+        self.report.messages += 1;
+        Ok(())
+    }
+
+    /// Close the transcript and flush the writer
+    pub fn finish(mut self) -> MvpResult<TranscriptReport> {
+        if self.options.format == TranscriptFormat::Json {
+            writeln!(self.out, "\n]}}").map_err(write_error)?;
+        }
+        self.out.flush().map_err(write_error)?;
+        Ok(self.report)
+    }
+}
+
+fn write_markdown<W: Write>(out: &mut W, entry: &TranscriptMessage<'_>) -> MvpResult<()> {
+    let mut text = format!("\n---\n\n**{}** · {} · `{}`", entry.sender, entry.timestamp, entry.id);
+    if let Some(reply_to) = entry.reply_to {
+        text.push_str(&format!(" · reply to `{}`", reply_to));
+    }
+    text.push_str("\n\n");
+
+    if entry.deleted {
+        text.push_str("_(deleted)_\n");
+    }
+    if let Some(body) = &entry.body {
+        text.push_str(body);
+        text.push('\n');
+    }
+    for attachment in &entry.attachments {
+        text.push_str(&format!(
+            "📎 {} ({}, {} bytes)\n",
+            attachment.filename, attachment.mime_type, attachment.size
+        ));
+    }
+    if let Some(last) = entry.edits.last() {
+        text.push_str(&format!("\n_(edited {})_\n", last.edited_at));
+    }
+    if !entry.reactions.is_empty() {
+        let reactions: Vec<String> = entry
+            .reactions
+            .iter()
+            .map(|r| format!("{} {}", r.emoji, r.users.join(", ")))
+            .collect();
+        text.push_str(&format!("\n{}\n", reactions.join(" · ")));
+    }
+    out.write_all(text.as_bytes()).map_err(write_error)
+}
+
+/// A timestamp as UTC RFC 3339 with milliseconds
+fn format_timestamp(timestamp: Timestamp) -> String {
+    match DateTime::from_timestamp_millis(timestamp.as_millis() as i64) {
+        Some(time) => time.to_rfc3339_opts(SecondsFormat::Millis, true),
+        None => timestamp.to_string(),
+    }
+}
+
+fn write_error(e: std::io::Error) -> MvpError {
+    MvpError::Store(format!("Failed to write transcript: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core_store::model::message::Attachment;
+    use crate::core_store::model::types::MessageId;
+
+    fn message(id: &str, sender: &str, body: &str, millis: u64) -> Message {
+        Message::new(
+            MessageId(id.to_string()),
+            ChannelId("c".to_string()),
+            UserId(sender.to_string()),
+            body.as_bytes().to_vec(),
+            Timestamp::from_millis(millis),
+        )
+    }
+
+    fn transcript(
+        options: TranscriptOptions,
+        messages: &[(Message, Vec<(UserId, String)>)],
+    ) -> String {
+        let mut out = Vec::new();
+        let mut writer =
+            TranscriptWriter::new(&mut out, options, &ChannelId("c".to_string()), "general")
+                .unwrap();
+        for (message, reactions) in messages {
+            writer.write(message, reactions).unwrap();
+        }
+        writer.finish().unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_json_transcript_parses() {
+        let mut edited = message("m2", "bob", "helo", 2_000);
+        edited.add_edit(
+            Timestamp::from_millis(3_000),
+            UserId("bob".to_string()),
+            b"hello".to_vec(),
+        );
+        let reactions = vec![
+            (UserId("carol".to_string()), "👍".to_string()),
+            (UserId("alice".to_string()), "👍".to_string()),
+        ];
+        let messages = [(message("m1", "alice", "hi", 1_000), reactions), (edited, Vec::new())];
+
+        let json: serde_json::Value =
+            serde_json::from_str(&transcript(TranscriptFormat::Json.into(), &messages)).unwrap();
+        assert_eq!(json["name"], "general");
+        assert_eq!(json["messages"][0]["timestamp"], "1970-01-01T00:00:01.000Z");
+        assert_eq!(
+            json["messages"][0]["reactions"][0]["users"],
+            serde_json::json!(["alice", "carol"])
+        );
+        assert_eq!(json["messages"][1]["body"], "hello");
+        assert_eq!(json["messages"][1]["edits"][0]["previous_body"], "helo");
+
+        let empty: serde_json::Value =
+            serde_json::from_str(&transcript(TranscriptFormat::Json.into(), &[])).unwrap();
+        assert_eq!(empty["messages"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_redaction() {
+        let mut attachment = message("m2", "alice", "", 2_000);
+        attachment.add_attachment(Attachment {
+            id: "a1".to_string(),
+            filename: "plan.pdf".to_string(),
+            mime_type: "application/pdf".to_string(),
+            size_bytes: 2048,
+            content_hash: String::new(),
+        });
+        let reactions = vec![(UserId("mallory".to_string()), "🎉".to_string())];
+        let messages = [
+            (message("m1", "alice", "hi", 1_000), reactions),
+            (attachment, Vec::new()),
+            (message("m3", "mallory", "secret", 3_000), Vec::new()),
+        ];
+
+        let options = TranscriptOptions::new(TranscriptFormat::Markdown)
+            .with_attachments_excluded()
+            .with_excluded_sender(UserId("mallory".to_string()));
+        let mut out = Vec::new();
+        let mut writer =
+            TranscriptWriter::new(&mut out, options, &ChannelId("c".to_string()), "general")
+                .unwrap();
+        for (message, reactions) in &messages {
+            writer.write(message, reactions).unwrap();
+        }
+        let report = writer.finish().unwrap();
+        assert_eq!(report, TranscriptReport { messages: 1, redacted: 2 });
+
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("hi"));
+        assert!(!text.contains("plan.pdf"));
+        assert!(!text.contains("mallory"));
+        assert!(!text.contains("🎉"));
+    }
+}
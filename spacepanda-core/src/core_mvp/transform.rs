@@ -0,0 +1,429 @@
+//! Display-time Content Transformation
+//!
+//! Hooks that produce an alternate rendering of a decrypted message (e.g. a
+//! translation) when it is read. Transformers run at render time only: the
+//! stored plaintext is never modified, and history queries return the
+//! rendering alongside the original message.
+//!
+//! # Language Detection
+//!
+//! [`detect_language`] is a lightweight heuristic run when a message is
+//! received. Non-Latin scripts are identified by Unicode block; Latin-script
+//! text is scored against small stopword lists. It returns an ISO 639-1 tag,
+//! or `None` when the text is too short or ambiguous to call.
+//!
+//! # Chaining
+//!
+//! Transformers registered on a [`TransformerChain`] run in registration
+//! order. Each one sees the output (content and language) of the previous
+//! transformer, so e.g. a normalizer can run before a translator.
+
+use crate::core_mvp::types::ChatMessage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Minimum number of stopword hits before a Latin-script language is reported
+const MIN_STOPWORD_HITS: usize = 2;
+
+/// Stopword lists for Latin-script languages
+const STOPWORDS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "the", "and", "is", "are", "was", "you", "that", "this", "with", "for", "have", "not",
+            "what", "it", "to", "of", "in", "i", "we", "be",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "la", "los", "las", "es", "y", "que", "de", "en", "un", "una", "por", "con",
+            "para", "está", "como", "pero", "muy", "yo", "hola",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "la", "les", "est", "et", "que", "de", "des", "un", "une", "je", "vous", "nous",
+            "pas", "pour", "avec", "dans", "bonjour", "merci", "ce",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "das", "und", "ist", "nicht", "ich", "du", "wir", "ein", "eine", "mit",
+            "für", "auf", "zu", "sie", "es", "hallo", "danke", "auch",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "il", "lo", "gli", "che", "di", "e", "è", "non", "un", "una", "sono", "per", "con",
+            "ciao", "grazie", "come", "anche", "io", "noi", "questo",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "o", "os", "as", "que", "de", "não", "um", "uma", "é", "com", "para", "eu", "você",
+            "obrigado", "olá", "mas", "muito", "isso", "também", "em",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "de", "het", "een", "en", "is", "niet", "ik", "je", "wij", "van", "met", "voor", "op",
+            "dat", "ook", "maar", "hallo", "dank", "zijn", "wel",
+        ],
+    ),
+];
+
+/// Detect the language of a message body
+///
+/// Returns an ISO 639-1 language tag (e.g. `"en"`, `"ja"`), or `None` if the
+/// text is too short or ambiguous.
+pub fn detect_language(text: &str) -> Option<String> {
+    detect_script(text).or_else(|| detect_latin(text)).map(str::to_string)
+}
+
+/// Identify languages written in a distinctive script
+fn detect_script(text: &str) -> Option<&'static str> {
+    let mut counts: HashMap<&'static str, usize> = HashMap::new();
+    let mut letters = 0usize;
+
+    for c in text.chars().filter(|c| c.is_alphabetic()) {
+        letters += 1;
+        let script = match c as u32 {
+            0x3040..=0x30FF => "ja",
+            0x4E00..=0x9FFF | 0x3400..=0x4DBF => "zh",
+            0xAC00..=0xD7AF | 0x1100..=0x11FF => "ko",
+            0x0400..=0x04FF => "ru",
+            0x0600..=0x06FF => "ar",
+            0x0370..=0x03FF => "el",
+            0x0590..=0x05FF => "he",
+            0x0900..=0x097F => "hi",
+            0x0E00..=0x0E7F => "th",
+            _ => continue,
+        };
+        *counts.entry(script).or_insert(0) += 1;
+    }
+
+    // Japanese mixes kana with Han characters
+    if counts.contains_key("ja") {
+        let han = counts.remove("zh").unwrap_or(0);
+        *counts.entry("ja").or_insert(0) += han;
+    }
+
+    let (script, count) = counts.into_iter().max_by_key(|(_, count)| *count)?;
+    (count * 2 > letters).then_some(script)
+}
+
+/// Score Latin-script text against the stopword lists
+fn detect_latin(text: &str) -> Option<&'static str> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+
+    let mut scores: Vec<(&'static str, usize)> = STOPWORDS
+        .iter()
+        .map(|(lang, stopwords)| {
+            (*lang, words.iter().filter(|w| stopwords.contains(&w.as_str())).count())
+        })
+        .collect();
+    scores.sort_by_key(|(_, hits)| std::cmp::Reverse(*hits));
+
+    match scores.as_slice() {
+        [(lang, best), (_, runner_up), ..] if *best >= MIN_STOPWORD_HITS && best > runner_up => {
+            Some(lang)
+        }
+        _ => None,
+    }
+}
+
+/// Output of a single transformer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transformed {
+    /// Transformed content
+    pub content: String,
+
+    /// Language of the transformed content (e.g. the translation target)
+    pub language: Option<String>,
+}
+
+/// Display-time hook producing an alternate rendering of message content
+///
+/// Implementations must be pure with respect to the message: they receive
+/// a borrowed copy of the decrypted content and never affect what is stored.
+pub trait ContentTransformer: Send + Sync {
+    /// Name reported in [`Rendering::applied`]
+    fn name(&self) -> &str;
+
+    /// Transform content, or return `None` to leave it unchanged
+    fn transform(&self, content: &str, language: Option<&str>) -> Option<Transformed>;
+}
+
+/// Transformer that never changes content
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopTransformer;
+
+impl ContentTransformer for NoopTransformer {
+    fn name(&self) -> &str {
+        "noop"
+    }
+
+    fn transform(&self, _content: &str, _language: Option<&str>) -> Option<Transformed> {
+        None
+    }
+}
+
+/// Word-for-word dictionary translation between two languages
+///
+/// Only applies to content tagged with the source language. Intended as a
+/// reference implementation for real translation backends.
+#[derive(Debug, Clone)]
+pub struct DictionaryTransformer {
+    source: String,
+    target: String,
+    entries: HashMap<String, String>,
+}
+
+impl DictionaryTransformer {
+    /// Create an empty dictionary from `source` to `target`
+    pub fn new(source: impl Into<String>, target: impl Into<String>) -> Self {
+        Self { source: source.into(), target: target.into(), entries: HashMap::new() }
+    }
+
+    /// Add a translation (matched case-insensitively)
+    pub fn with_entry(mut self, from: &str, to: &str) -> Self {
+        self.entries.insert(from.to_lowercase(), to.to_string());
+        self
+    }
+
+    fn translate_word(&self, word: &str) -> Option<String> {
+        let translated = self.entries.get(&word.to_lowercase())?;
+
+        // Preserve a leading capital
+        let mut chars = translated.chars();
+        match (word.chars().next(), chars.next()) {
+            (Some(w), Some(first)) if w.is_uppercase() => {
+                Some(first.to_uppercase().chain(chars).collect())
+            }
+            _ => Some(translated.clone()),
+        }
+    }
+}
+
+impl ContentTransformer for DictionaryTransformer {
+    fn name(&self) -> &str {
+        "dictionary"
+    }
+
+    fn transform(&self, content: &str, language: Option<&str>) -> Option<Transformed> {
+        if language != Some(self.source.as_str()) {
+            return None;
+        }
+
+        let mut output = String::with_capacity(content.len());
+        let mut word = String::new();
+        let mut replaced = false;
+
+        let mut flush = |word: &mut String, output: &mut String| {
+            if let Some(translated) = self.translate_word(word) {
+                output.push_str(&translated);
+                replaced = true;
+            } else {
+                output.push_str(word);
+            }
+            word.clear();
+        };
+
+        for c in content.chars() {
+            if c.is_alphabetic() {
+                word.push(c);
+            } else {
+                flush(&mut word, &mut output);
+                output.push(c);
+            }
+        }
+        flush(&mut word, &mut output);
+
+        replaced.then(|| Transformed { content: output, language: Some(self.target.clone()) })
+    }
+}
+
+/// Alternate rendering of a message produced by a transformer chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rendering {
+    /// Rendered content
+    pub content: String,
+
+    /// Language of the rendered content
+    pub language: Option<String>,
+
+    /// Names of the transformers that changed the content, in order
+    pub applied: Vec<String>,
+}
+
+/// A message together with its display-time rendering
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RenderedMessage {
+    /// Original message, exactly as stored
+    pub message: ChatMessage,
+
+    /// Alternate rendering, if any transformer applied
+    pub rendering: Option<Rendering>,
+}
+
+impl RenderedMessage {
+    /// Content to display: the rendering if present, else the original body
+    pub fn display_content(&self) -> Option<String> {
+        match &self.rendering {
+            Some(rendering) => Some(rendering.content.clone()),
+            None => self.message.body_as_string(),
+        }
+    }
+}
+
+/// Ordered set of content transformers
+#[derive(Clone, Default)]
+pub struct TransformerChain {
+    transformers: Vec<Arc<dyn ContentTransformer>>,
+}
+
+impl TransformerChain {
+    /// Create an empty chain
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a transformer to the chain
+    pub fn push(&mut self, transformer: Arc<dyn ContentTransformer>) {
+        self.transformers.push(transformer);
+    }
+
+    /// Number of registered transformers
+    pub fn len(&self) -> usize {
+        self.transformers.len()
+    }
+
+    /// Whether the chain has no transformers
+    pub fn is_empty(&self) -> bool {
+        self.transformers.is_empty()
+    }
+
+    /// Run the chain over some content
+    ///
+    /// Returns `None` if no transformer changed the content.
+    pub fn apply(&self, content: &str, language: Option<&str>) -> Option<Rendering> {
+        let mut rendering: Option<Rendering> = None;
+
+        for transformer in &self.transformers {
+            let (current, current_language) = match &rendering {
+                Some(r) => (r.content.as_str(), r.language.as_deref()),
+                None => (content, language),
+            };
+
+            if let Some(out) = transformer.transform(current, current_language) {
+                let applied = rendering.take().map(|r| r.applied).unwrap_or_default();
+                let mut next = Rendering { content: out.content, language: out.language, applied };
+                next.applied.push(transformer.name().to_string());
+                rendering = Some(next);
+            }
+        }
+
+        rendering
+    }
+
+    /// Render a message without modifying it
+    ///
+    /// Messages whose body is not valid UTF-8 are never transformed.
+    pub fn render(&self, message: &ChatMessage) -> RenderedMessage {
+        let rendering = std::str::from_utf8(&message.body)
+            .ok()
+            .and_then(|content| self.apply(content, message.language.as_deref()));
+
+        RenderedMessage { message: message.clone(), rendering }
+    }
+}
+
+impl std::fmt::Debug for TransformerChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.transformers.iter().map(|t| t.name())).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_language_fixtures() {
+        let fixtures = [
+            ("Hello, how are you? I think that the meeting is at noon.", Some("en")),
+            (
+                "Hola, ¿cómo estás? Yo estoy muy bien, pero la reunión es por la tarde.",
+                Some("es"),
+            ),
+            ("Bonjour, je suis content de vous voir. Merci pour le café.", Some("fr")),
+            ("Hallo, ich bin heute nicht im Büro. Danke und bis morgen.", Some("de")),
+            ("Ciao, come stai? Io sono a casa, grazie per il messaggio.", Some("it")),
+            ("Olá, eu não sei se você vai também para a festa.", Some("pt")),
+            ("Привет, как дела? Увидимся завтра.", Some("ru")),
+            ("こんにちは、元気ですか？", Some("ja")),
+            ("你好，今天天气很好。", Some("zh")),
+            ("안녕하세요, 반갑습니다.", Some("ko")),
+            ("مرحبا كيف حالك", Some("ar")),
+            ("ok", None),
+            ("👍🎉", None),
+            ("", None),
+        ];
+
+        for (text, expected) in fixtures {
+            assert_eq!(detect_language(text).as_deref(), expected, "text: {text:?}");
+        }
+    }
+
+    struct Upper;
+
+    impl ContentTransformer for Upper {
+        fn name(&self) -> &str {
+            "upper"
+        }
+
+        fn transform(&self, content: &str, language: Option<&str>) -> Option<Transformed> {
+            Some(Transformed {
+                content: content.to_uppercase(),
+                language: language.map(str::to_string),
+            })
+        }
+    }
+
+    #[test]
+    fn test_transformer_chaining() {
+        let mut chain = TransformerChain::new();
+        chain.push(Arc::new(NoopTransformer));
+        chain.push(Arc::new(
+            DictionaryTransformer::new("es", "en")
+                .with_entry("hola", "hello")
+                .with_entry("amigo", "friend"),
+        ));
+        chain.push(Arc::new(Upper));
+
+        let rendering = chain.apply("Hola, amigo!", Some("es")).unwrap();
+        assert_eq!(rendering.content, "HELLO, FRIEND!");
+        assert_eq!(rendering.language.as_deref(), Some("en"));
+        assert_eq!(rendering.applied, vec!["dictionary", "upper"]);
+
+        // Dictionary only applies to its source language
+        let rendering = chain.apply("hola", Some("fr")).unwrap();
+        assert_eq!(rendering.content, "HOLA");
+        assert_eq!(rendering.applied, vec!["upper"]);
+
+        let mut noop = TransformerChain::new();
+        noop.push(Arc::new(NoopTransformer));
+        assert!(noop.apply("hola", Some("es")).is_none());
+    }
+}
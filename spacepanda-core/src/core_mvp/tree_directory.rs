@@ -0,0 +1,78 @@
+//! Ratchet trees published to the DHT
+//!
+//! An invite normally carries the group's full ratchet tree next to the
+//! Welcome, and the tree grows with every member. With
+//! [`MlsConfig::external_tree`](crate::core_mls::types::MlsConfig) set, the
+//! inviter publishes the tree here instead, keyed by group and epoch, and
+//! pins its hash in the invite. The joiner fetches the tree and checks it
+//! against the pin before joining, so a tree swapped out in the DHT is
+//! rejected with `MlsError::TreeHashMismatch`.
+
+use crate::core_dht::private_lookup::RATCHET_TREE_NAMESPACE;
+use crate::core_dht::{DhtKey, DhtStorage, DhtValue};
+use crate::core_mls::types::GroupId;
+use crate::core_mls::welcome::TreeSnapshot;
+use crate::core_mvp::errors::{MvpError, MvpResult};
+use crate::core_store::model::types::Timestamp;
+use std::time::Duration;
+
+/// How long a published tree stays in the DHT
+pub const RATCHET_TREE_TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Directory of ratchet trees stored in the DHT
+pub struct TreeDirectory {
+    storage: DhtStorage,
+}
+
+impl TreeDirectory {
+    /// Create a directory backed by the given DHT storage
+    pub fn new(storage: DhtStorage) -> Self {
+        Self { storage }
+    }
+
+    fn key(group_id: &GroupId, epoch: u64) -> DhtKey {
+        DhtKey::hash(
+            format!("{}:{}:{}", RATCHET_TREE_NAMESPACE, group_id.to_hex(), epoch).as_bytes(),
+        )
+    }
+
+    /// Tree published for a group's epoch, if any
+    pub fn lookup(&self, group_id: &GroupId, epoch: u64) -> MvpResult<Option<TreeSnapshot>> {
+        let Ok(value) = self.storage.get(&Self::key(group_id, epoch)) else {
+            return Ok(None);
+        };
+        let snapshot = bincode::deserialize(&value.data)
+            .map_err(|e| MvpError::SerializationError(format!("Failed to deserialize: {}", e)))?;
+        Ok(Some(snapshot))
+    }
+
+    /// Publish the tree of a group's epoch
+    ///
+    /// Replaces whatever is stored for that epoch; joiners rely on the
+    /// hash in their invite, not on the record being the first one.
+    pub fn publish(
+        &self,
+        group_id: &GroupId,
+        epoch: u64,
+        snapshot: &TreeSnapshot,
+    ) -> MvpResult<()> {
+        let key = Self::key(group_id, epoch);
+        let bytes = bincode::serialize(snapshot)
+            .map_err(|e| MvpError::SerializationError(format!("Failed to serialize: {}", e)))?;
+
+        let previous = self.storage.get(&key).ok().map(|value| value.sequence);
+        let sequence = Timestamp::now().as_millis().max(previous.map_or(0, |seq| seq + 1));
+        let value =
+            DhtValue::new(bytes).with_ttl_duration(RATCHET_TREE_TTL).with_sequence(sequence);
+
+        match self.storage.put(key, value.clone()) {
+            // An expired entry written in the same millisecond; replace it
+            Err(_) if previous.is_none() => {
+                let _ = self.storage.delete(&key);
+                self.storage.put(key, value).map_err(MvpError::Dht)?;
+            }
+            result => result.map_err(MvpError::Dht)?,
+        }
+        Ok(())
+    }
+}
@@ -1,8 +1,11 @@
 //! Core data types for MVP layer
 
+use crate::core_mls::padding::PaddingStrategy;
 use crate::core_mls::types::GroupId;
 use crate::core_store::model::types::{ChannelId, MessageId, Timestamp, UserId};
+use crate::core_store::store::{DeliveryState, SentReceipts};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 /// Channel descriptor for discovery and metadata
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -30,6 +33,14 @@ pub struct ChannelDescriptor {
 
     /// Optional description
     pub description: Option<String>,
+
+    /// How members pad messages in this channel
+    #[serde(default)]
+    pub padding: PaddingStrategy,
+
+    /// SHA-256 of the channel avatar
+    #[serde(default)]
+    pub avatar_hash: Option<[u8; 32]>,
 }
 
 impl ChannelDescriptor {
@@ -50,6 +61,8 @@ impl ChannelDescriptor {
             created_at: Timestamp::now(),
             bootstrap_peers: Vec::new(),
             description: None,
+            padding: PaddingStrategy::default(),
+            avatar_hash: None,
         }
     }
 
@@ -61,9 +74,26 @@ impl ChannelDescriptor {
     }
 }
 
+/// A member device of a channel, as listed by `ChannelManager::list_members`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChannelMember {
+    /// Member's user ID
+    pub user_id: UserId,
+
+    /// Device identifier (one per MLS leaf)
+    pub device_id: String,
+
+    /// When this device joined the channel
+    pub joined_at: Timestamp,
+}
+
 /// Invite token containing Welcome message and metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InviteToken {
+    /// Random identifier, used to revoke the invite
+    #[serde(default)]
+    pub invite_id: String,
+
     /// Target channel ID
     pub channel_id: ChannelId,
 
@@ -91,6 +121,24 @@ pub struct InviteToken {
     /// Inviter's peer ID (for P2P connection)
     /// This enables secure peer discovery without DHT metadata leakage
     pub inviter_peer_id: Option<Vec<u8>>,
+
+    /// Where to fetch the ratchet tree when it isn't embedded
+    #[serde(default)]
+    pub external_tree: Option<ExternalTree>,
+
+    /// Channel's padding strategy, for the joiner to adopt
+    #[serde(default, with = "crate::core_mls::padding::optional_stored")]
+    pub padding: Option<PaddingStrategy>,
+}
+
+/// Ratchet tree published to the DHT instead of embedded in an invite
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExternalTree {
+    /// Epoch the tree belongs to (part of its DHT key)
+    pub epoch: u64,
+
+    /// SHA-256 of the exported tree
+    pub hash: Vec<u8>,
 }
 
 impl InviteToken {
@@ -104,6 +152,7 @@ impl InviteToken {
         inviter: UserId,
     ) -> Self {
         Self {
+            invite_id: hex::encode(rand::random::<[u8; 16]>()),
             channel_id,
             welcome_blob,
             ratchet_tree,
@@ -113,6 +162,8 @@ impl InviteToken {
             expires_at: None,
             inviter,
             inviter_peer_id: None,
+            external_tree: None,
+            padding: None,
         }
     }
 
@@ -133,12 +184,117 @@ impl InviteToken {
 
     /// Set expiration time (seconds from now)
     pub fn with_expiry(mut self, seconds: u64) -> Self {
-        let now = Timestamp::now().0;
-        self.expires_at = Some(Timestamp(now + seconds));
+        let now = Timestamp::now().as_millis();
+        self.expires_at = Some(Timestamp::from_millis(now + seconds * 1000));
         self
     }
 }
 
+/// Options for `ChannelManager::create_invite_with_options`
+#[derive(Debug, Clone, PartialEq)]
+pub struct InviteOptions {
+    /// How long the invite stays valid (`None` = no expiry)
+    pub ttl: Option<Duration>,
+
+    /// How many joins the invite allows (`None` = unlimited)
+    pub max_uses: Option<u32>,
+}
+
+impl Default for InviteOptions {
+    fn default() -> Self {
+        Self { ttl: None, max_uses: Some(1) }
+    }
+}
+
+impl InviteOptions {
+    /// Set how long the invite stays valid
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Set how many joins the invite allows
+    pub fn with_max_uses(mut self, max_uses: Option<u32>) -> Self {
+        self.max_uses = max_uses;
+        self
+    }
+}
+
+/// Who an invite is for
+#[derive(Debug, Clone, PartialEq)]
+pub enum Invitee {
+    /// The invitee's serialized key package, exchanged out of band
+    KeyPackage(Vec<u8>),
+
+    /// A user whose key package is taken from the key package directory
+    User(UserId),
+}
+
+impl From<Vec<u8>> for Invitee {
+    fn from(key_package: Vec<u8>) -> Self {
+        Invitee::KeyPackage(key_package)
+    }
+}
+
+impl From<UserId> for Invitee {
+    fn from(user_id: UserId) -> Self {
+        Invitee::User(user_id)
+    }
+}
+
+/// Result of inviting several key packages in one commit
+#[derive(Debug, Clone)]
+pub struct InviteBatch {
+    /// One invite per accepted key package, in input order
+    pub invites: Vec<InviteToken>,
+
+    /// Commit adding all accepted invitees (`None` if nothing was added)
+    pub commit: Option<Vec<u8>>,
+
+    /// Rejected key packages: input index and reason
+    pub rejected: Vec<(usize, String)>,
+}
+
+/// Outcome of a key rotation sweep
+#[derive(Debug, Clone, Default)]
+pub struct KeyRotationReport {
+    /// Channels whose leaf key was rotated
+    pub rotated: Vec<ChannelId>,
+
+    /// Channels rotated recently enough to be left alone
+    pub skipped: Vec<ChannelId>,
+
+    /// Channels that failed to rotate, with the reason
+    pub failed: Vec<(ChannelId, String)>,
+}
+
+/// Outcome of a retention sweep
+#[derive(Debug, Clone, Default)]
+pub struct RetentionReport {
+    /// Channels that had expired messages, with how many were pruned
+    pub pruned: Vec<(ChannelId, usize)>,
+
+    /// Channels that failed to prune, with the reason
+    pub failed: Vec<(ChannelId, String)>,
+}
+
+impl RetentionReport {
+    /// Messages pruned across all channels
+    pub fn total_pruned(&self) -> usize {
+        self.pruned.iter().map(|(_, count)| count).sum()
+    }
+}
+
+/// Outcome of a channel transcript export
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TranscriptReport {
+    /// Messages written to the transcript
+    pub messages: usize,
+
+    /// Messages left out by the redaction options
+    pub redacted: usize,
+}
+
 /// Chat message structure (decrypted)
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ChatMessage {
@@ -162,6 +318,31 @@ pub struct ChatMessage {
 
     /// Message type (for future extensions)
     pub message_type: MessageType,
+
+    /// Detected language of the body (ISO 639-1), set when received
+    #[serde(default)]
+    pub language: Option<String>,
+
+    /// Whether the local user has read the message, set by `get_messages`
+    #[serde(default)]
+    pub read: bool,
+
+    /// Earlier versions of the body, oldest first, if it was edited
+    #[serde(default)]
+    pub edits: Vec<MessageEdit>,
+
+    /// Whether the message was deleted; its body is then empty
+    #[serde(default)]
+    pub deleted: bool,
+
+    /// Reactions grouped by emoji, most popular first, set by `get_messages`
+    #[serde(default)]
+    pub reactions: Vec<ReactionSummary>,
+
+    /// How far one of our own messages got with the other members, set by
+    /// `get_messages`
+    #[serde(default)]
+    pub delivery: Option<DeliverySummary>,
 }
 
 impl ChatMessage {
@@ -175,6 +356,12 @@ impl ChatMessage {
             body,
             reply_to: None,
             message_type: MessageType::Text,
+            language: None,
+            read: false,
+            edits: Vec::new(),
+            deleted: false,
+            reactions: Vec::new(),
+            delivery: None,
         }
     }
 
@@ -199,6 +386,20 @@ pub enum MessageType {
     System,
     /// File/media attachment
     Attachment,
+    /// New body for an earlier message
+    Edit,
+    /// Deletion of an earlier message
+    Delete,
+}
+
+/// A version of an edited message's body
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MessageEdit {
+    /// Body before the edit
+    pub body: Vec<u8>,
+
+    /// When the edit was made
+    pub edited_at: Timestamp,
 }
 
 /// Emoji reaction to a message
@@ -232,6 +433,30 @@ pub struct ReactionSummary {
     pub user_reacted: bool,
 }
 
+/// How far a sent message got with the members it was sent to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DeliverySummary {
+    /// State of the member least far along
+    pub state: DeliveryState,
+    /// Members the message was sent to
+    pub recipients: usize,
+    /// Members who received it (including those who read it)
+    pub delivered: usize,
+    /// Members who read it
+    pub read: usize,
+}
+
+impl From<&SentReceipts> for DeliverySummary {
+    fn from(receipts: &SentReceipts) -> Self {
+        Self {
+            state: receipts.state(),
+            recipients: receipts.recipients.len(),
+            delivered: receipts.count(DeliveryState::Delivered),
+            read: receipts.count(DeliveryState::Read),
+        }
+    }
+}
+
 /// Thread metadata for a message
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ThreadInfo {
@@ -373,11 +598,19 @@ mod tests {
             inviter,
         );
 
+        assert!(!invite.is_expired());
+        assert_eq!(invite.invite_id.len(), 32);
+
+        // Expiry is in seconds, timestamps in milliseconds
+        let created = Timestamp::now().as_millis();
+        let invite = invite.with_expiry(60);
+        let expires_at = invite.expires_at.unwrap().as_millis();
+        assert!(expires_at >= created + 60_000);
         assert!(!invite.is_expired());
 
-        let expired_invite = invite.with_expiry(0); // Expires immediately
-                                                    // Note: This might pass or fail depending on timing
-                                                    // In production, use proper time mocking
+        let mut expired = invite.clone();
+        expired.expires_at = Some(Timestamp::from_millis(created - 1));
+        assert!(expired.is_expired());
     }
 
     #[test]
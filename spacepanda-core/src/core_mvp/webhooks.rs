@@ -0,0 +1,217 @@
+//! Inbound webhooks
+//!
+//! A webhook lets an external system (CI, alerting) post into a channel
+//! over HTTP without being a real member. Each webhook is a pseudo-member
+//! with its own MLS leaf, so its messages are encrypted like any other and
+//! attributed to `webhook:<id>` rather than to the admin who created it.
+//!
+//! - The webhook record (name, limits, token hash) lives in the channel's
+//!   CRDT, so every admin can list, rotate and revoke it
+//! - The leaf's keys live only on the node that created the webhook; that
+//!   node's HTTP listener serves `POST /webhooks/<id>`
+//! - Revoking removes the leaf from the group, so a leaked token is useless
+//!   even to the hosting node once the removal commit is processed
+
+use crate::config::Config;
+use crate::core_mls::service::MlsService;
+use crate::core_mvp::errors::{MvpError, MvpResult};
+use crate::core_store::model::types::{ChannelId, UserId};
+use crate::shutdown::ShutdownCoordinator;
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Prefix of every webhook pseudo-member's user ID
+pub const WEBHOOK_USER_PREFIX: &str = "webhook:";
+
+/// Default cap on a webhook message body
+pub const DEFAULT_MAX_CONTENT_BYTES: usize = 4 * 1024;
+
+/// Default number of posts a webhook may make per minute
+pub const DEFAULT_MAX_POSTS_PER_MINUTE: u32 = 30;
+
+/// Window the per-minute rate limit is counted over
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Limits applied to a webhook's posts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookOptions {
+    /// Largest message body accepted, in bytes
+    pub max_content_bytes: usize,
+
+    /// Posts accepted per minute
+    pub max_posts_per_minute: u32,
+}
+
+impl Default for WebhookOptions {
+    fn default() -> Self {
+        Self {
+            max_content_bytes: DEFAULT_MAX_CONTENT_BYTES,
+            max_posts_per_minute: DEFAULT_MAX_POSTS_PER_MINUTE,
+        }
+    }
+}
+
+impl WebhookOptions {
+    /// Set the content-size cap
+    pub fn with_max_content_bytes(mut self, max_content_bytes: usize) -> Self {
+        self.max_content_bytes = max_content_bytes;
+        self
+    }
+
+    /// Set the per-minute rate limit
+    pub fn with_max_posts_per_minute(mut self, max_posts_per_minute: u32) -> Self {
+        self.max_posts_per_minute = max_posts_per_minute;
+        self
+    }
+}
+
+/// A webhook's ID and secret token
+///
+/// The token is only ever returned here, on creation and rotation; the
+/// channel stores its hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookCredentials {
+    /// Webhook identifier (the `<id>` in `POST /webhooks/<id>`)
+    pub webhook_id: String,
+
+    /// Secret bearer token
+    pub token: String,
+}
+
+/// User ID a webhook posts as
+pub fn webhook_user_id(webhook_id: &str) -> UserId {
+    UserId(format!("{}{}", WEBHOOK_USER_PREFIX, webhook_id))
+}
+
+/// Whether a user ID belongs to a webhook pseudo-member
+pub fn is_webhook_user(user_id: &UserId) -> bool {
+    user_id.0.starts_with(WEBHOOK_USER_PREFIX)
+}
+
+/// Generate a fresh webhook ID
+pub(crate) fn generate_webhook_id() -> String {
+    hex::encode(rand::random::<[u8; 16]>())
+}
+
+/// Generate a fresh secret token
+pub(crate) fn generate_token() -> String {
+    hex::encode(rand::random::<[u8; 32]>())
+}
+
+/// Hex-encoded BLAKE3 hash of a token, as stored in the channel
+pub(crate) fn hash_token(token: &str) -> String {
+    blake3::hash(token.as_bytes()).to_hex().to_string()
+}
+
+/// Check a presented token against a stored hash
+pub(crate) fn token_matches(token: &str, token_hash: &str) -> bool {
+    // blake3::Hash equality is constant-time
+    blake3::Hash::from_hex(token_hash)
+        .map(|expected| blake3::hash(token.as_bytes()) == expected)
+        .unwrap_or(false)
+}
+
+/// Sliding-window rate limiter keyed by webhook ID
+#[derive(Debug, Default)]
+pub(crate) struct RateLimiter {
+    posts: HashMap<String, VecDeque<Instant>>,
+}
+
+impl RateLimiter {
+    /// Count a post if it is within `limit` posts per minute
+    ///
+    /// Returns false, without counting the post, when the limit is reached.
+    pub(crate) fn try_acquire(&mut self, webhook_id: &str, limit: u32, now: Instant) -> bool {
+        let posts = self.posts.entry(webhook_id.to_string()).or_default();
+        while posts.front().is_some_and(|&at| now.duration_since(at) >= RATE_WINDOW) {
+            posts.pop_front();
+        }
+        if posts.len() >= limit as usize {
+            return false;
+        }
+        posts.push_back(now);
+        true
+    }
+
+    /// Forget a webhook's history
+    pub(crate) fn forget(&mut self, webhook_id: &str) {
+        self.posts.remove(webhook_id);
+    }
+}
+
+/// A webhook leaf hosted by this node
+pub(crate) struct HostedWebhook {
+    pub(crate) channel_id: ChannelId,
+
+    /// MLS client holding only this webhook's leaf
+    pub(crate) mls: Arc<MlsService>,
+}
+
+/// Webhook state held by the ChannelManager
+#[derive(Default)]
+pub(crate) struct WebhookHost {
+    pub(crate) hosted: HashMap<String, HostedWebhook>,
+    pub(crate) rate_limiter: RateLimiter,
+}
+
+/// Open the MLS client for a webhook leaf, restoring any persisted state
+pub(crate) async fn open_webhook_mls(config: &Config, dir: &Path) -> MvpResult<Arc<MlsService>> {
+    let shutdown = Arc::new(ShutdownCoordinator::new(Duration::from_secs(30)));
+    let mls = MlsService::with_storage(config, shutdown, dir.to_path_buf())?;
+    mls.load_persisted_groups().await?;
+    Ok(Arc::new(mls))
+}
+
+/// Reject bodies over a webhook's content-size cap
+pub(crate) fn check_content_size(body: &[u8], limit: usize) -> MvpResult<()> {
+    if body.len() > limit {
+        return Err(MvpError::PayloadTooLarge { size: body.len(), limit });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_hash_round_trip() {
+        let token = generate_token();
+        let hash = hash_token(&token);
+
+        assert!(token_matches(&token, &hash));
+        assert!(!token_matches(&generate_token(), &hash));
+        assert!(!token_matches(&token, "not-hex"));
+        assert_ne!(hash, token);
+    }
+
+    #[test]
+    fn test_rate_limiter_window() {
+        let mut limiter = RateLimiter::default();
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter.try_acquire("ci", 3, start));
+        }
+        assert!(!limiter.try_acquire("ci", 3, start + Duration::from_secs(30)));
+
+        // Other webhooks have their own budget
+        assert!(limiter.try_acquire("alerts", 3, start));
+
+        // The window slides
+        assert!(limiter.try_acquire("ci", 3, start + RATE_WINDOW));
+
+        limiter.forget("ci");
+        assert!(limiter.posts.get("ci").is_none());
+    }
+
+    #[test]
+    fn test_webhook_user_ids_are_labeled() {
+        let user_id = webhook_user_id("abc");
+        assert_eq!(user_id.0, "webhook:abc");
+        assert!(is_webhook_user(&user_id));
+        assert!(!is_webhook_user(&UserId("alice@spacepanda.local".to_string())));
+    }
+}
@@ -0,0 +1,354 @@
+/*
+    Bandwidth - per-peer byte accounting and limits for the transport
+
+    Every message a connection sends or receives is charged to the peer bound to that
+    connection, separately per direction, in fixed windows (an hour by default). Past the
+    soft limit, traffic with the peer is paced to the throttle rate by a byte-mode
+    RateLimiter; past the hard limit, the connection is closed. Usage is kept per PeerId
+    rather than per conn_id, so reconnecting doesn't reset the meter, and it is saved to a
+    state file so restarting doesn't either.
+
+    Workflow:
+    The router binds a conn_id to the PeerId and PeerClass of its session once the session is
+    established. TransportManager calls pass() before every send and after every receive;
+    connections that were never bound are counted in the metrics but not limited.
+
+    Inputs:
+      - bind(conn_id, peer_id, class) / unbind(conn_id)
+      - admit(conn_id, direction, bytes) or pass(...), which waits out a delay itself
+
+    Outputs:
+      - Admission::Pass, Admission::Delay(duration) or Admission::Close
+      - network.bytes.sent / network.bytes.received counters labeled by peer class
+*/
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+use super::metrics;
+use super::rate_limiter::{RateLimiter, RateLimiterConfig};
+use super::route_table::Capability;
+use super::session_manager::PeerId;
+
+/// How often usage is written to the state file while traffic flows
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// What a peer is to us, for labeling its traffic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PeerClass {
+    /// Relays traffic for us or through us
+    Relay,
+    /// A peer we talk to directly
+    Direct,
+    /// A DHT node
+    Dht,
+}
+
+impl PeerClass {
+    /// Class of a peer advertising `capabilities`; relaying wins over DHT service
+    pub fn from_capabilities(capabilities: &[Capability]) -> Self {
+        if capabilities.contains(&Capability::Relay) {
+            PeerClass::Relay
+        } else if capabilities.contains(&Capability::DhtNode) {
+            PeerClass::Dht
+        } else {
+            PeerClass::Direct
+        }
+    }
+
+    /// Metric label
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PeerClass::Relay => "relay",
+            PeerClass::Direct => "direct",
+            PeerClass::Dht => "dht",
+        }
+    }
+}
+
+/// Direction of a transfer, from our side
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+/// Bandwidth limits applied to every bound peer, per direction
+#[derive(Debug, Clone)]
+pub struct BandwidthConfig {
+    /// Bytes per window after which traffic with the peer is delayed
+    pub soft_limit: Option<u64>,
+    /// Bytes per window after which the peer's connection is closed
+    pub hard_limit: Option<u64>,
+    /// Accounting window the limits apply to
+    pub window: Duration,
+    /// Bytes per second a peer over its soft limit is paced to
+    pub throttle_rate: u64,
+    /// File usage is saved to and restored from
+    pub state_path: Option<PathBuf>,
+}
+
+impl Default for BandwidthConfig {
+    fn default() -> Self {
+        BandwidthConfig {
+            soft_limit: None,
+            hard_limit: None,
+            window: Duration::from_secs(60 * 60),
+            throttle_rate: 64 * 1024,
+            state_path: None,
+        }
+    }
+}
+
+/// Bytes exchanged with a peer in the current window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BandwidthUsage {
+    pub sent: u64,
+    pub received: u64,
+}
+
+/// What to do with a transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    /// Go ahead
+    Pass,
+    /// Over the soft limit: wait this long first
+    Delay(Duration),
+    /// Over the hard limit: close the connection instead
+    Close,
+}
+
+/// Usage of one peer, as kept in memory and in the state file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PeerUsage {
+    /// Seconds since the epoch when the current window began
+    window_start: u64,
+    sent: u64,
+    received: u64,
+}
+
+impl PeerUsage {
+    /// Start a new window if the current one is over
+    fn roll(&mut self, now: u64, window: Duration) {
+        if now.saturating_sub(self.window_start) >= window.as_secs().max(1) {
+            *self = PeerUsage { window_start: now, sent: 0, received: 0 };
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedUsage {
+    /// Hex-encoded PeerId
+    peer_id: String,
+    #[serde(flatten)]
+    usage: PeerUsage,
+}
+
+/// Per-peer byte meter shared by the transports
+pub struct BandwidthMeter {
+    config: BandwidthConfig,
+    bound: Mutex<HashMap<u64, (PeerId, PeerClass)>>,
+    usage: Mutex<HashMap<PeerId, PeerUsage>>,
+    last_save: Mutex<Instant>,
+    throttle_sent: RateLimiter,
+    throttle_received: RateLimiter,
+}
+
+impl BandwidthMeter {
+    /// Create a meter, restoring usage from config.state_path if the file exists
+    pub fn new(config: BandwidthConfig) -> Result<Self, String> {
+        let mut usage = HashMap::new();
+        if let Some(path) = &config.state_path {
+            match std::fs::read(path) {
+                Ok(bytes) => {
+                    let saved: Vec<SavedUsage> = serde_json::from_slice(&bytes).map_err(|e| {
+                        format!("Invalid bandwidth state in {}: {}", path.display(), e)
+                    })?;
+                    for entry in saved {
+                        let peer_id = hex::decode(&entry.peer_id).map_err(|e| {
+                            format!("Invalid peer in bandwidth state {}: {}", path.display(), e)
+                        })?;
+                        usage.insert(PeerId::from_bytes(peer_id), entry.usage);
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    return Err(format!("Failed to read {}: {}", path.display(), e));
+                }
+            }
+        }
+
+        let throttle = RateLimiterConfig {
+            max_bytes_per_sec: config.throttle_rate,
+            // A tenth of a second's worth, so pacing starts close to the soft limit
+            byte_burst_size: (config.throttle_rate / 10).max(1),
+            ..RateLimiterConfig::default()
+        };
+        Ok(BandwidthMeter {
+            throttle_sent: RateLimiter::new_with_config(throttle.clone()),
+            throttle_received: RateLimiter::new_with_config(throttle),
+            config,
+            bound: Mutex::new(HashMap::new()),
+            usage: Mutex::new(usage),
+            last_save: Mutex::new(Instant::now()),
+        })
+    }
+
+    /// Charge conn_id's traffic to peer_id from now on
+    pub async fn bind(&self, conn_id: u64, peer_id: PeerId, class: PeerClass) {
+        self.bound.lock().await.insert(conn_id, (peer_id, class));
+    }
+
+    /// Forget a closed connection, saving its peer's usage
+    pub async fn unbind(&self, conn_id: u64) {
+        if self.bound.lock().await.remove(&conn_id).is_some() {
+            self.save_logged().await;
+        }
+    }
+
+    /// Bytes exchanged with a peer in its current window
+    pub async fn usage(&self, peer_id: &PeerId) -> BandwidthUsage {
+        let now = unix_now();
+        let mut usage = self.usage.lock().await;
+        match usage.get_mut(peer_id) {
+            Some(peer) => {
+                peer.roll(now, self.config.window);
+                BandwidthUsage { sent: peer.sent, received: peer.received }
+            }
+            None => BandwidthUsage::default(),
+        }
+    }
+
+    /// Charge `bytes` in `direction` to conn_id's peer and decide what happens to them
+    pub async fn admit(&self, conn_id: u64, direction: Direction, bytes: usize) -> Admission {
+        let bound = self.bound.lock().await.get(&conn_id).cloned();
+        let class = bound.as_ref().map_or(PeerClass::Direct, |(_, class)| *class);
+        match direction {
+            Direction::Sent => metrics::network_bytes_sent(class.as_str(), bytes as u64),
+            Direction::Received => metrics::network_bytes_received(class.as_str(), bytes as u64),
+        }
+        let Some((peer_id, _)) = bound else {
+            return Admission::Pass;
+        };
+
+        let total = {
+            let mut usage = self.usage.lock().await;
+            let now = unix_now();
+            let peer = usage.entry(peer_id.clone()).or_insert(PeerUsage {
+                window_start: now,
+                sent: 0,
+                received: 0,
+            });
+            peer.roll(now, self.config.window);
+            let total = match direction {
+                Direction::Sent => &mut peer.sent,
+                Direction::Received => &mut peer.received,
+            };
+            *total += bytes as u64;
+            *total
+        };
+
+        if self.config.hard_limit.is_some_and(|hard| total > hard) {
+            warn!(
+                peer_id = ?peer_id,
+                conn_id,
+                ?direction,
+                total,
+                "Peer over its hard bandwidth limit, closing connection"
+            );
+            metrics::bandwidth_limit_reached("hard");
+            self.save_logged().await;
+            return Admission::Close;
+        }
+
+        let due = self.last_save.lock().await.elapsed() >= SAVE_INTERVAL;
+        if due {
+            self.save_logged().await;
+        }
+
+        let Some(soft) = self.config.soft_limit.filter(|soft| total > *soft) else {
+            return Admission::Pass;
+        };
+        // Only the part of this transfer past the limit is paced
+        let over = (total - soft).min(bytes as u64);
+        let throttle = match direction {
+            Direction::Sent => &self.throttle_sent,
+            Direction::Received => &self.throttle_received,
+        };
+        let delay = throttle.reserve_bytes(&peer_id, over).await;
+        if delay.is_zero() {
+            return Admission::Pass;
+        }
+        debug!(peer_id = ?peer_id, conn_id, ?direction, ?delay, "Peer over soft bandwidth limit");
+        metrics::bandwidth_limit_reached("soft");
+        Admission::Delay(delay)
+    }
+
+    /// Admit a transfer, waiting out any delay; errors if the connection must be closed
+    pub async fn pass(
+        &self,
+        conn_id: u64,
+        direction: Direction,
+        bytes: usize,
+    ) -> Result<(), String> {
+        match self.admit(conn_id, direction, bytes).await {
+            Admission::Pass => Ok(()),
+            Admission::Delay(delay) => {
+                tokio::time::sleep(delay).await;
+                Ok(())
+            }
+            Admission::Close => {
+                Err(format!("Connection {} closed: peer over its hard bandwidth limit", conn_id))
+            }
+        }
+    }
+
+    /// Write usage to the state file, if there is one
+    pub async fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.config.state_path else {
+            return Ok(());
+        };
+        *self.last_save.lock().await = Instant::now();
+
+        let saved: Vec<SavedUsage> = self
+            .usage
+            .lock()
+            .await
+            .iter()
+            .map(|(peer_id, usage)| SavedUsage {
+                peer_id: hex::encode(peer_id.as_bytes()),
+                usage: usage.clone(),
+            })
+            .collect();
+        let bytes = serde_json::to_vec(&saved)
+            .map_err(|e| format!("Failed to encode bandwidth state: {}", e))?;
+
+        // Write then rename, so a crash mid-write leaves the previous state intact
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, bytes)
+            .map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+        std::fs::rename(&tmp, path)
+            .map_err(|e| format!("Failed to replace {}: {}", path.display(), e))
+    }
+
+    async fn save_logged(&self) {
+        if let Err(e) = self.save().await {
+            warn!("{}", e);
+        }
+    }
+}
+
+impl Default for BandwidthMeter {
+    /// A meter with no limits and no state file
+    fn default() -> Self {
+        Self::new(BandwidthConfig::default()).expect("No state file to read")
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
@@ -44,6 +44,21 @@ pub fn init_metrics() {
         "Total number of handshakes that timed out"
     );
 
+    describe_counter!(
+        "spacepanda_sessions_established_total",
+        "Total number of sessions established, labeled by kind (full, resumed)"
+    );
+
+    describe_counter!(
+        "spacepanda_peers_quarantined_total",
+        "Total number of peers quarantined for a reputation below the threshold"
+    );
+
+    describe_counter!(
+        "spacepanda_session_resumptions_rejected_total",
+        "Total number of session resumptions refused, sending the dialer back to a full handshake"
+    );
+
     // Rate Limiting
     describe_counter!(
         "spacepanda_rate_limit_exceeded_total",
@@ -81,6 +96,45 @@ pub fn init_metrics() {
         "Total number of RPC handler errors (method not found, handler crashed)"
     );
 
+    // NAT Traversal
+    describe_counter!(
+        "spacepanda_hole_punches_total",
+        "Total number of hole punch attempts, labeled by result (punched, relayed)"
+    );
+
+    // Discovery
+    describe_counter!(
+        "spacepanda_peer_exchange_descriptors_total",
+        "Total number of peer descriptors received over peer exchange, labeled by result"
+    );
+
+    // Onion Routing
+    describe_counter!(
+        "spacepanda_onion_packets_sent_total",
+        "Total number of onion packets sent, labeled by kind (real, cover)"
+    );
+
+    describe_counter!(
+        "spacepanda_cover_packets_dropped_total",
+        "Total number of cover traffic packets received and dropped"
+    );
+
+    describe_counter!(
+        "spacepanda_onion_circuits_built_total",
+        "Total number of onion circuits built, labeled by kind (new, rebuilt)"
+    );
+
+    describe_counter!(
+        "spacepanda_onion_circuits_closed_total",
+        "Total number of onion circuits closed, labeled by reason (idle, destroyed)"
+    );
+
+    // Bandwidth
+    describe_counter!(
+        "spacepanda_bandwidth_limits_reached_total",
+        "Total number of transfers past a peer's bandwidth limit, labeled by limit (soft, hard)"
+    );
+
     // System Health
     describe_gauge!("spacepanda_active_peers", "Current number of active peer connections");
 
@@ -143,6 +197,21 @@ pub fn handshake_timeout() {
     counter!("spacepanda_handshake_timeouts_total").increment(1);
 }
 
+/// Record a session established by a full handshake or resumed from a ticket
+pub fn session_established(kind: &str) {
+    counter!("spacepanda_sessions_established_total", "kind" => kind.to_string()).increment(1);
+}
+
+/// Record a session resumption refused
+pub fn session_resumption_rejected() {
+    counter!("spacepanda_session_resumptions_rejected_total").increment(1);
+}
+
+/// Record a peer quarantined for misbehaving
+pub fn peer_quarantined() {
+    counter!("spacepanda_peers_quarantined_total").increment(1);
+}
+
 /// Record circuit breaker state transition
 pub fn circuit_breaker_transition(transition: &str) {
     counter!("spacepanda_circuit_breaker_state_transitions_total", "transition" => transition.to_string()).increment(1);
@@ -169,6 +238,53 @@ pub fn rpc_handler_error(error_type: &str) {
         .increment(1);
 }
 
+/// Record the outcome of a hole punch
+pub fn hole_punch_result(result: &str) {
+    counter!("spacepanda_hole_punches_total", "result" => result.to_string()).increment(1);
+}
+
+/// Record a descriptor received over peer exchange (merged, known, rejected)
+pub fn peer_exchange_descriptor(result: &str) {
+    counter!("spacepanda_peer_exchange_descriptors_total", "result" => result.to_string())
+        .increment(1);
+}
+
+/// Record an onion packet sent, real or cover
+pub fn onion_packet_sent(kind: &str) {
+    counter!("spacepanda_onion_packets_sent_total", "kind" => kind.to_string()).increment(1);
+}
+
+/// Record a cover traffic packet dropped on receipt
+pub fn cover_packet_dropped() {
+    counter!("spacepanda_cover_packets_dropped_total").increment(1);
+}
+
+/// Record an onion circuit built, new or rebuilt after a relay was lost
+pub fn onion_circuit_built(kind: &str) {
+    counter!("spacepanda_onion_circuits_built_total", "kind" => kind.to_string()).increment(1);
+}
+
+/// Record an onion circuit torn down
+pub fn onion_circuit_closed(reason: &str) {
+    counter!("spacepanda_onion_circuits_closed_total", "reason" => reason.to_string()).increment(1);
+}
+
+/// Record bytes sent to a peer, labeled by peer class (relay, direct, dht)
+pub fn network_bytes_sent(class: &str, bytes: u64) {
+    counter!("network.bytes.sent", "class" => class.to_string()).increment(bytes);
+}
+
+/// Record bytes received from a peer, labeled by peer class (relay, direct, dht)
+pub fn network_bytes_received(class: &str, bytes: u64) {
+    counter!("network.bytes.received", "class" => class.to_string()).increment(bytes);
+}
+
+/// Record a transfer past a peer's soft (delayed) or hard (disconnected) bandwidth limit
+pub fn bandwidth_limit_reached(limit: &str) {
+    counter!("spacepanda_bandwidth_limits_reached_total", "limit" => limit.to_string())
+        .increment(1);
+}
+
 /// Update active peers gauge
 pub fn set_active_peers(count: usize) {
     gauge!("spacepanda_active_peers").set(count as f64);
@@ -205,11 +321,23 @@ mod tests {
         handshake_replay_detected();
         expired_handshake_rejected();
         handshake_timeout();
+        session_established("resumed");
+        session_resumption_rejected();
+        peer_quarantined();
+        peer_exchange_descriptor("merged");
         circuit_breaker_transition("closed_to_open");
         rpc_call_duration(0.5);
         rpc_call_result("success");
         rpc_method_invoked("test_method");
         rpc_handler_error("method_not_found");
+        hole_punch_result("punched");
+        onion_packet_sent("cover");
+        cover_packet_dropped();
+        onion_circuit_built("rebuilt");
+        onion_circuit_closed("idle");
+        network_bytes_sent("relay", 1024);
+        network_bytes_received("dht", 512);
+        bandwidth_limit_reached("soft");
         set_active_peers(10);
         set_pending_rpc_requests(5);
         set_seen_requests_cache_size(100);
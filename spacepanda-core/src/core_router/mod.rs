@@ -1,28 +1,51 @@
+pub mod bandwidth;
 pub mod metrics;
+pub mod nat_traversal;
+pub mod onion_circuit;
 pub mod onion_router;
 pub mod overlay_discovery;
+pub mod path_scorer;
+pub mod quic_transport;
 pub mod rate_limiter;
+pub mod reputation;
 pub mod route_table;
 pub mod router_handle;
 pub mod rpc_protocol;
 pub mod session_manager;
 pub mod transport_manager;
+pub mod ws_transport;
 
 #[cfg(test)]
 mod tests;
 
+pub use bandwidth::{
+    Admission, BandwidthConfig, BandwidthMeter, BandwidthUsage, Direction, PeerClass,
+};
+pub use nat_traversal::{
+    connect_via_relay, dial_peer, serve_connect_requests, ConnectAccept, ConnectRequest, Connection,
+    CONNECT_REQUEST_METHOD,
+};
+pub use onion_circuit::{CircuitId, CircuitPolicy};
 pub use onion_router::{
-    InnerEnvelope, OnionCommand, OnionConfig, OnionEvent, OnionHeader, OnionRouter,
+    CoverStats, CoverTrafficConfig, InnerEnvelope, OnionCommand, OnionConfig, OnionEvent,
+    OnionHeader, OnionRouter,
 };
 pub use overlay_discovery::{
-    DiscoveryCommand, DiscoveryConfig, DiscoveryEvent, OverlayDiscovery, PeerDescriptor,
-    PeerExchangeRequest, PeerExchangeResponse,
+    DiscoveryCommand, DiscoveryConfig, DiscoveryEvent, ExchangeTransport, OverlayDiscovery,
+    PeerDescriptor, PeerExchangeRequest, PeerExchangeResponse, PEER_EXCHANGE_METHOD,
 };
+pub use path_scorer::{DeliveryDetail, PathKind, PathScorer, PathScorerConfig, PathStats};
+pub use quic_transport::{negotiate_transport, QuicIdentity, QuicTransport, TransportKind};
 pub use rate_limiter::{RateLimitResult, RateLimiter, RateLimiterConfig};
+pub use reputation::{Behaviour, Reputation, ReputationConfig};
 pub use route_table::{
-    Capability, GeoLocation, PeerInfo, PeerStats, RouteTable, RouteTableCommand,
+    Capability, GeoLocation, PeerInfo, PeerStats, Reachability, RouteTable, RouteTableCommand,
 };
 pub use router_handle::{RouterCommand, RouterEvent, RouterHandle};
-pub use rpc_protocol::{RpcCommand, RpcError, RpcMessage, RpcProtocol, RpcRequest};
+pub use rpc_protocol::{
+    fetch_blob, serve_blobs, BlobSlice, BlobSource, FetchBlob, RpcCommand, RpcError, RpcMessage,
+    RpcProtocol, RpcRequest, BLOB_SLICE_SIZE, FETCH_BLOB_METHOD,
+};
 pub use session_manager::{PeerId, SessionCommand, SessionEvent, SessionManager};
-pub use transport_manager::{TransportCommand, TransportEvent, TransportManager};
+pub use transport_manager::{DatagramSocket, TransportCommand, TransportEvent, TransportManager};
+pub use ws_transport::{ws_tls_config, WsTransport};
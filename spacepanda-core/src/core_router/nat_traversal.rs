@@ -0,0 +1,244 @@
+/*
+    NatTraversal - relay-coordinated hole punching
+
+    Nodes behind NAT can't accept inbound connections. When A wants to reach NATed B it asks
+    a peer both of them are connected to (the relay) to pass B a ConnectRequest. B answers
+    with its external address and starts punching towards A's; A starts punching towards
+    B's as soon as the answer comes back, so both NATs open a mapping for the other side.
+
+    Workflow:
+    1. Each node learns its external address with TransportManager::observe(public_peer)
+    2. A calls connect_via_relay(B, relay) -> `connect_request` RPC to the relay
+    3. The relay forwards the request to B (only on behalf of the peer that sent it)
+    4. B replies with its external address and punches towards A
+    5. A punches towards B; on success both sides get TransportEvent::Connected
+    6. If punching times out, A falls back to reaching B through the relay
+
+    The router does this from its dial path (RouterHandle::dial_peer, see dial_peer): a peer
+    is dialed on its advertised addresses first, unless it is known to be behind a NAT, and
+    through each connected peer as a relay after that. Every router answers
+    `connect_request` with serve_connect_requests.
+
+    Inputs:
+      - `connect_request` RPCs (relay and target side, see serve_connect_requests)
+      - connect_via_relay(target, relay) (initiator side)
+      - dial_peer(peer, relays) (the router's dial path)
+
+    Outputs:
+      - Connection::Direct(conn_id) or Connection::Relayed(relay)
+      - Reachability of the remote peer in RouteTable (NatPunchable or RelayOnly)
+*/
+
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use super::metrics;
+use super::route_table::{PeerInfo, Reachability, RouteTable, RouteTableCommand};
+use super::rpc_protocol::{RpcError, RpcRequest};
+use super::session_manager::PeerId;
+use super::transport_manager::TransportManager;
+
+/// Method name of the hole punch coordination RPC
+pub const CONNECT_REQUEST_METHOD: &str = "connect_request";
+
+/// How long dial_peer waits on each advertised address before trying relays
+const DIRECT_DIAL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Params of `connect_request`: `from` wants a direct path to `target`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectRequest {
+    pub from: Vec<u8>,
+    pub target: Vec<u8>,
+    /// External address of `from` to punch towards
+    pub addr: String,
+}
+
+/// Result of `connect_request`: the target's external address, which it is punching from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectAccept {
+    pub addr: String,
+}
+
+/// How a peer ended up reachable after connect_via_relay
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Connection {
+    /// A punched path, usable like any transport connection
+    Direct(u64),
+    /// Punching failed; traffic has to go through this relay
+    Relayed(PeerId),
+}
+
+/// Answer `connect_request` for `local`, both as relay and as target
+///
+/// Requests addressed to another peer are passed on through `forward(target, params)`,
+/// but only when the sender is the peer asking to connect. Requests addressed to `local`
+/// start a punch towards the initiator. Register the returned sender as the handler for
+/// CONNECT_REQUEST_METHOD.
+pub fn serve_connect_requests<F, Fut>(
+    local: PeerId,
+    transport: Arc<TransportManager>,
+    route_table: Arc<RouteTable>,
+    forward: F,
+) -> (mpsc::Sender<RpcRequest>, JoinHandle<()>)
+where
+    F: Fn(PeerId, serde_json::Value) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<serde_json::Value, RpcError>> + Send + 'static,
+{
+    let (handler_tx, mut handler_rx) = mpsc::channel::<RpcRequest>(100);
+    let task = tokio::spawn(async move {
+        while let Some(request) = handler_rx.recv().await {
+            let params = match serde_json::from_value::<ConnectRequest>(request.params) {
+                Ok(params) => params,
+                Err(e) => {
+                    let _ = request.response_tx.send(Err(RpcError::invalid_params(&e.to_string())));
+                    continue;
+                }
+            };
+
+            if params.target != local.0 {
+                if request.peer_id.0 != params.from {
+                    let _ = request
+                        .response_tx
+                        .send(Err(RpcError::invalid_params("connect request for another peer")));
+                    continue;
+                }
+                let forwarded = match serde_json::to_value(&params) {
+                    Ok(value) => forward(PeerId::from_bytes(params.target), value),
+                    Err(e) => {
+                        let _ =
+                            request.response_tx.send(Err(RpcError::internal_error(&e.to_string())));
+                        continue;
+                    }
+                };
+                tokio::spawn(async move {
+                    let _ = request.response_tx.send(forwarded.await);
+                });
+                continue;
+            }
+
+            let Some(addr) = transport.observed_addr().await else {
+                let _ = request
+                    .response_tx
+                    .send(Err(RpcError::internal_error("no observed address to punch from")));
+                continue;
+            };
+            let result = serde_json::to_value(ConnectAccept { addr })
+                .map_err(|e| RpcError::internal_error(&e.to_string()));
+            let accepted = result.is_ok();
+            let _ = request.response_tx.send(result);
+            if !accepted {
+                continue;
+            }
+
+            let transport = transport.clone();
+            let route_table = route_table.clone();
+            tokio::spawn(async move {
+                let punched = transport.punch(&params.addr, false).await.is_ok();
+                record_punch(&route_table, PeerId::from_bytes(params.from), punched).await;
+            });
+        }
+    });
+    (handler_tx, task)
+}
+
+/// Open a connection to NATed `target` by punching, coordinated through `relay`
+///
+/// `call` sends `connect_request` params to the relay and returns its result. Needs our
+/// external address, so observe a public peer first.
+pub async fn connect_via_relay<F, Fut>(
+    transport: &TransportManager,
+    route_table: &RouteTable,
+    local: &PeerId,
+    target: &PeerId,
+    relay: &PeerId,
+    call: F,
+) -> Result<Connection, RpcError>
+where
+    F: FnOnce(serde_json::Value) -> Fut,
+    Fut: Future<Output = Result<serde_json::Value, RpcError>>,
+{
+    let addr = transport
+        .observed_addr()
+        .await
+        .ok_or_else(|| RpcError::internal_error("no observed address to punch from"))?;
+    let params = serde_json::to_value(ConnectRequest {
+        from: local.0.clone(),
+        target: target.0.clone(),
+        addr,
+    })
+    .map_err(|e| RpcError::internal_error(&e.to_string()))?;
+    let accept: ConnectAccept = serde_json::from_value(call(params).await?)
+        .map_err(|e| RpcError::internal_error(&format!("bad connect accept: {}", e)))?;
+
+    let connection = match transport.punch(&accept.addr, true).await {
+        Ok(conn_id) => Connection::Direct(conn_id),
+        Err(_) => Connection::Relayed(relay.clone()),
+    };
+    record_punch(route_table, target.clone(), matches!(connection, Connection::Direct(_))).await;
+    Ok(connection)
+}
+
+/// Open a connection to `peer` the way the router dials
+///
+/// Peers not known to be behind a NAT are dialed on their advertised addresses first. If
+/// none answers, or the peer is NATed, each of `relays` in turn is asked to coordinate a
+/// punch through connect_via_relay; `call(relay, params)` sends it `connect_request`.
+pub async fn dial_peer<F, Fut>(
+    transport: &TransportManager,
+    route_table: &RouteTable,
+    local: &PeerId,
+    peer: &PeerInfo,
+    relays: &[PeerId],
+    call: F,
+) -> Result<Connection, RpcError>
+where
+    F: Fn(PeerId, serde_json::Value) -> Fut,
+    Fut: Future<Output = Result<serde_json::Value, RpcError>>,
+{
+    let natted = matches!(peer.reachability, Reachability::NatPunchable | Reachability::RelayOnly);
+    let mut last_error = RpcError::internal_error("no address or relay reaches the peer");
+
+    if !natted {
+        for addr in &peer.addresses {
+            match tokio::time::timeout(DIRECT_DIAL_TIMEOUT, transport.dial(addr)).await {
+                Ok(Ok(conn_id)) => return Ok(Connection::Direct(conn_id)),
+                Ok(Err(e)) => last_error = RpcError::internal_error(&e),
+                Err(_) => {
+                    last_error = RpcError::internal_error(&format!("dialing {} timed out", addr))
+                }
+            }
+        }
+    }
+
+    for relay in relays.iter().filter(|relay| **relay != peer.peer_id) {
+        let result =
+            connect_via_relay(transport, route_table, local, &peer.peer_id, relay, |params| {
+                call(relay.clone(), params)
+            })
+            .await;
+        match result {
+            Ok(connection) => return Ok(connection),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+/// Record what a punch says about the peer's reachability
+async fn record_punch(route_table: &RouteTable, peer_id: PeerId, punched: bool) {
+    let reachability = if punched {
+        metrics::hole_punch_result("punched");
+        Reachability::NatPunchable
+    } else {
+        metrics::hole_punch_result("relayed");
+        Reachability::RelayOnly
+    };
+    // Peers we haven't inserted yet have nothing to update
+    let _ = route_table
+        .handle_command(RouteTableCommand::SetReachability { peer_id, reachability })
+        .await;
+}
@@ -0,0 +1,441 @@
+/*
+    OnionCircuit - Telescoping circuits for the onion router.
+
+    A circuit is a path of relays the origin shares a key with, hop by hop, so that each relay
+    only ever sees its two neighbours. OnionRouter owns the circuits and runs the protocol; this
+    module holds the wire cells, the per-hop key exchange and the relay selection policy.
+
+    Workflow for building (origin, build_circuit(3))
+    1. Select R1, R2, R3 from the route table under CircuitPolicy
+    2. CREATE to R1 with an ephemeral X25519 key X. R1 answers CREATED with its own ephemeral
+       key Y and an auth tag only the holder of its onion key B (PeerInfo::overlay_pubkey) can
+       compute
+    3. EXTEND(R2, X2) in a RELAY cell layered for R1; R1 sends CREATE to R2 and returns R2's
+       CREATED to us as EXTENDED. EXTEND(R3, X3) goes through R1 and R2 the same way
+    4. Data is layered with every hop key (innermost for the exit); each relay peels one
+       layer and passes the rest on. Replies gain a layer per hop on the way back
+
+    Workflow for relaying (at R)
+    - CREATE from P: derive hop keys, remember (P, circ)
+    - RELAY from P: peel our layer; forward the rest, or run EXTEND / DATA if it was for us
+    - RELAY / CREATED from the next hop: add our layer and send it back towards P
+    - DESTROY from P: drop the hop and pass DESTROY on
+    - Next hop lost: answer TRUNCATED back towards the origin, which rebuilds
+
+    Key derivation (ntor-style, one-way authenticated):
+      HKDF-SHA256(DH(x, Y) || DH(x, B), info = X || Y || B) -> forward key, backward key,
+      auth key; CREATED carries BLAKE3-keyed(auth key, Y).
+
+    Relay selection (CircuitPolicy):
+      - distinct IPv4 /16 (IPv6 /32) subnets across the path
+      - relays above a relay_score ceiling (failures, latency, staleness) are skipped
+      - optionally no two relays in the same country (GeoLocation::country_code)
+*/
+
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Nonce,
+};
+use rand::RngCore;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use super::route_table::{Capability, PeerInfo};
+use super::session_manager::PeerId;
+
+const HOP_KEYS_INFO: &[u8] = b"spacepanda-circuit-v1";
+const NONCE_SIZE: usize = 12;
+
+/// Handle for a circuit built by this node
+///
+/// Stays valid across automatic rebuilds until the circuit is destroyed or times out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CircuitId(pub u64);
+
+/// Rules for choosing the relays of a circuit
+#[derive(Debug, Clone)]
+pub struct CircuitPolicy {
+    /// No two relays share an IPv4 /16 (IPv6 /32)
+    pub distinct_subnets: bool,
+    /// Minimum reliability: relays whose relay_score (lower is better) exceeds this are skipped
+    pub max_relay_score: u64,
+    /// No two relays report the same country
+    pub geo_diverse: bool,
+}
+
+impl Default for CircuitPolicy {
+    fn default() -> Self {
+        CircuitPolicy { distinct_subnets: true, max_relay_score: 2_000, geo_diverse: false }
+    }
+}
+
+/// Cell exchanged between neighbouring hops; `circ` is local to that link
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) enum Cell {
+    Create { circ: u64, client_pub: [u8; 32] },
+    Created { circ: u64, relay_pub: [u8; 32], auth: [u8; 32] },
+    Relay { circ: u64, body: Vec<u8> },
+    Destroy { circ: u64 },
+}
+
+impl Cell {
+    /// Circuit number on the link the cell travelled
+    pub fn circ(&self) -> u64 {
+        match self {
+            Cell::Create { circ, .. }
+            | Cell::Created { circ, .. }
+            | Cell::Relay { circ, .. }
+            | Cell::Destroy { circ } => *circ,
+        }
+    }
+}
+
+/// A forward RELAY body once a hop has removed its layer
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) enum Forward {
+    /// Still layered for later hops
+    Next(Vec<u8>),
+    /// Extend the circuit to another relay
+    Extend { next_hop: Vec<u8>, client_pub: [u8; 32] },
+    /// Exit: hand the payload to the local application
+    Data { destination: Vec<u8>, payload: Vec<u8> },
+}
+
+/// A backward RELAY body once the origin has removed a layer
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) enum Backward {
+    /// Layered by hops further along
+    Inner(Vec<u8>),
+    /// The relay asked to extend to answered CREATED
+    Extended { relay_pub: [u8; 32], auth: [u8; 32] },
+    /// The hop after the sender is gone
+    Truncated,
+}
+
+/// Keys a hop shares with the origin
+#[derive(Clone)]
+pub(super) struct HopKeys {
+    pub forward: [u8; 32],
+    pub backward: [u8; 32],
+}
+
+/// Origin half of a hop handshake
+pub(super) struct Handshake {
+    secret: StaticSecret,
+    pub client_pub: [u8; 32],
+    onion_pub: [u8; 32],
+}
+
+impl Handshake {
+    /// Start a handshake with the relay whose onion key is `onion_pub`
+    pub fn new(onion_pub: [u8; 32]) -> Self {
+        let mut secret_bytes = [0u8; 32];
+        rand::rng().fill_bytes(&mut secret_bytes);
+        let secret = StaticSecret::from(secret_bytes);
+        let client_pub = *PublicKey::from(&secret).as_bytes();
+        Handshake { secret, client_pub, onion_pub }
+    }
+
+    /// Check the relay's CREATED and derive the hop keys
+    pub fn finish(self, relay_pub: [u8; 32], auth: [u8; 32]) -> Result<HopKeys, String> {
+        let ephemeral = self.secret.diffie_hellman(&PublicKey::from(relay_pub));
+        let static_dh = self.secret.diffie_hellman(&PublicKey::from(self.onion_pub));
+        if !ephemeral.was_contributory() || !static_dh.was_contributory() {
+            return Err("Relay sent a weak key".to_string());
+        }
+
+        let (keys, auth_key) = derive_hop_keys(
+            ephemeral.as_bytes(),
+            static_dh.as_bytes(),
+            &self.client_pub,
+            &relay_pub,
+            &self.onion_pub,
+        )?;
+        if blake3::keyed_hash(&auth_key, &relay_pub) != blake3::Hash::from(auth) {
+            return Err("Relay failed to prove its onion key".to_string());
+        }
+        Ok(keys)
+    }
+}
+
+/// Relay half of a hop handshake: keys, our ephemeral public key and the auth tag
+pub(super) fn respond(
+    onion_key: &StaticSecret,
+    client_pub: [u8; 32],
+) -> Result<(HopKeys, [u8; 32], [u8; 32]), String> {
+    let mut secret_bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut secret_bytes);
+    let ephemeral_secret = StaticSecret::from(secret_bytes);
+    let relay_pub = *PublicKey::from(&ephemeral_secret).as_bytes();
+
+    let client = PublicKey::from(client_pub);
+    let ephemeral = ephemeral_secret.diffie_hellman(&client);
+    let static_dh = onion_key.diffie_hellman(&client);
+    if !ephemeral.was_contributory() || !static_dh.was_contributory() {
+        return Err("Client sent a weak key".to_string());
+    }
+
+    let onion_pub = *PublicKey::from(onion_key).as_bytes();
+    let (keys, auth_key) = derive_hop_keys(
+        ephemeral.as_bytes(),
+        static_dh.as_bytes(),
+        &client_pub,
+        &relay_pub,
+        &onion_pub,
+    )?;
+    let auth = *blake3::keyed_hash(&auth_key, &relay_pub).as_bytes();
+    Ok((keys, relay_pub, auth))
+}
+
+/// Split both DH results into the hop keys and the CREATED auth key
+fn derive_hop_keys(
+    ephemeral: &[u8; 32],
+    static_dh: &[u8; 32],
+    client_pub: &[u8; 32],
+    relay_pub: &[u8; 32],
+    onion_pub: &[u8; 32],
+) -> Result<(HopKeys, [u8; 32]), String> {
+    let mut secret = ephemeral.to_vec();
+    secret.extend_from_slice(static_dh);
+    let mut info = HOP_KEYS_INFO.to_vec();
+    info.extend_from_slice(client_pub);
+    info.extend_from_slice(relay_pub);
+    info.extend_from_slice(onion_pub);
+
+    let mut okm = [0u8; 96];
+    hkdf::Hkdf::<sha2::Sha256>::new(None, &secret)
+        .expand(&info, &mut okm)
+        .map_err(|e| format!("HKDF expand failed: {}", e))?;
+
+    let mut keys = HopKeys { forward: [0u8; 32], backward: [0u8; 32] };
+    let mut auth_key = [0u8; 32];
+    keys.forward.copy_from_slice(&okm[..32]);
+    keys.backward.copy_from_slice(&okm[32..64]);
+    auth_key.copy_from_slice(&okm[64..]);
+    Ok((keys, auth_key))
+}
+
+/// Encode and encrypt a layer: nonce (12) || ciphertext
+pub(super) fn seal<T: Serialize>(key: &[u8; 32], value: &T) -> Result<Vec<u8>, String> {
+    let plaintext =
+        bincode::serialize(value).map_err(|e| format!("Failed to encode layer: {}", e))?;
+    let mut nonce = [0u8; NONCE_SIZE];
+    rand::rng().fill_bytes(&mut nonce);
+
+    let ciphertext = ChaCha20Poly1305::new(key.into())
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+        .map_err(|e| format!("Encryption failed: {}", e))?;
+    let mut sealed = nonce.to_vec();
+    sealed.extend(ciphertext);
+    Ok(sealed)
+}
+
+/// Decrypt and decode a layer made by seal
+pub(super) fn open<T: DeserializeOwned>(key: &[u8; 32], sealed: &[u8]) -> Result<T, String> {
+    if sealed.len() < NONCE_SIZE {
+        return Err("Layer too short".to_string());
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_SIZE);
+    let plaintext = ChaCha20Poly1305::new(key.into())
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|e| format!("Decryption failed: {}", e))?;
+    bincode::deserialize(&plaintext).map_err(|e| format!("Failed to decode layer: {}", e))
+}
+
+/// Layer a message for the last hop of `keys`, then for every hop before it
+pub(super) fn wrap_forward(keys: &[HopKeys], message: &Forward) -> Result<Vec<u8>, String> {
+    let (last, earlier) = keys.split_last().ok_or("Circuit has no hops")?;
+    let mut body = seal(&last.forward, message)?;
+    for hop in earlier.iter().rev() {
+        body = seal(&hop.forward, &Forward::Next(body))?;
+    }
+    Ok(body)
+}
+
+/// Peel backward layers until a hop's reply; returns that hop's index with the reply
+pub(super) fn unwrap_backward(
+    keys: &[HopKeys],
+    mut body: Vec<u8>,
+) -> Result<(usize, Backward), String> {
+    for (index, hop) in keys.iter().enumerate() {
+        match open(&hop.backward, &body)? {
+            Backward::Inner(inner) => body = inner,
+            reply => return Ok((index, reply)),
+        }
+    }
+    Err("Reply has more layers than the circuit has hops".to_string())
+}
+
+/// Network prefix an address belongs to: /16 for IPv4, /32 for IPv6
+fn subnet(address: &str) -> Option<Vec<u8>> {
+    let ip = address
+        .parse::<SocketAddr>()
+        .map(|addr| addr.ip())
+        .or_else(|_| address.parse::<IpAddr>())
+        .ok()?;
+    Some(match ip {
+        IpAddr::V4(v4) => v4.octets()[..2].to_vec(),
+        IpAddr::V6(v6) => v6.octets()[..4].to_vec(),
+    })
+}
+
+/// Choose `hops` relays from `candidates` under `policy`
+///
+/// Candidates are taken in the given order, so callers shuffle them first. Relays in
+/// `exclude` (ourselves, relays seen dying) and relays without an onion key are skipped.
+pub(super) fn select_relays(
+    candidates: &[PeerInfo],
+    hops: usize,
+    policy: &CircuitPolicy,
+    exclude: &[PeerId],
+) -> Result<Vec<PeerInfo>, String> {
+    let mut selected: Vec<PeerInfo> = Vec::new();
+    let mut used_subnets: Vec<Vec<u8>> = Vec::new();
+    let mut used_countries: Vec<String> = Vec::new();
+
+    for peer in candidates {
+        if selected.len() >= hops {
+            break;
+        }
+        if !peer.capabilities.contains(&Capability::Relay)
+            || peer.overlay_pubkey == [0u8; 32]
+            || peer.relay_score() > policy.max_relay_score
+            || exclude.contains(&peer.peer_id)
+            || selected.iter().any(|p| p.peer_id == peer.peer_id)
+        {
+            continue;
+        }
+
+        let subnets: Vec<Vec<u8>> = peer.addresses.iter().filter_map(|a| subnet(a)).collect();
+        if policy.distinct_subnets && subnets.iter().any(|s| used_subnets.contains(s)) {
+            continue;
+        }
+        let country = peer.geo_location.as_ref().and_then(|geo| geo.country_code.clone());
+        if policy.geo_diverse && country.as_ref().is_some_and(|c| used_countries.contains(c)) {
+            continue;
+        }
+
+        used_subnets.extend(subnets);
+        used_countries.extend(country);
+        selected.push(peer.clone());
+    }
+
+    if selected.len() < hops {
+        return Err(format!(
+            "Only {} of {} circuit relays satisfy the relay policy",
+            selected.len(),
+            hops
+        ));
+    }
+    Ok(selected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::route_table::GeoLocation;
+    use super::*;
+
+    fn relay(id: u8, address: &str) -> PeerInfo {
+        let mut peer = PeerInfo::new(PeerId::from_bytes(vec![id]), vec![address.to_string()]);
+        peer.capabilities.push(Capability::Relay);
+        peer.overlay_pubkey = *PublicKey::from(&StaticSecret::from([id; 32])).as_bytes();
+        peer
+    }
+
+    #[test]
+    fn test_handshake_agrees_on_keys() {
+        let onion_key = StaticSecret::from([5u8; 32]);
+        let handshake = Handshake::new(*PublicKey::from(&onion_key).as_bytes());
+        let (relay_keys, relay_pub, auth) = respond(&onion_key, handshake.client_pub).unwrap();
+        let client_keys = handshake.finish(relay_pub, auth).unwrap();
+
+        assert_eq!(client_keys.forward, relay_keys.forward);
+        assert_eq!(client_keys.backward, relay_keys.backward);
+        assert_ne!(client_keys.forward, client_keys.backward);
+
+        let sealed = seal(&client_keys.forward, &Forward::Next(b"inner".to_vec())).unwrap();
+        let Forward::Next(inner) = open(&relay_keys.forward, &sealed).unwrap() else {
+            panic!("Expected Next");
+        };
+        assert_eq!(inner, b"inner");
+        assert!(open::<Forward>(&relay_keys.backward, &sealed).is_err());
+    }
+
+    #[test]
+    fn test_layers_peel_in_path_order() {
+        let keys: Vec<HopKeys> = (1..=3u8)
+            .map(|i| HopKeys { forward: [i; 32], backward: [i + 10; 32] })
+            .collect();
+        let mut body = wrap_forward(
+            &keys,
+            &Forward::Data { destination: b"key".to_vec(), payload: b"value".to_vec() },
+        )
+        .unwrap();
+        for hop in &keys[..2] {
+            let Forward::Next(inner) = open(&hop.forward, &body).unwrap() else {
+                panic!("Only the exit may see the message");
+            };
+            body = inner;
+        }
+        assert!(matches!(open(&keys[2].forward, &body).unwrap(), Forward::Data { .. }));
+
+        // The second hop reports its successor gone; the first adds its layer on the way back
+        let reply = seal(&keys[1].backward, &Backward::Truncated).unwrap();
+        let reply = seal(&keys[0].backward, &Backward::Inner(reply)).unwrap();
+        let (hop, reply) = unwrap_backward(&keys, reply).unwrap();
+        assert_eq!(hop, 1);
+        assert!(matches!(reply, Backward::Truncated));
+    }
+
+    #[test]
+    fn test_handshake_rejects_wrong_onion_key() {
+        let advertised = StaticSecret::from([5u8; 32]);
+        let impostor = StaticSecret::from([6u8; 32]);
+        let handshake = Handshake::new(*PublicKey::from(&advertised).as_bytes());
+        let (_, relay_pub, auth) = respond(&impostor, handshake.client_pub).unwrap();
+        assert!(handshake.finish(relay_pub, auth).is_err());
+    }
+
+    #[test]
+    fn test_select_relays_policy() {
+        let policy = CircuitPolicy::default();
+        let same_subnet = vec![
+            relay(1, "10.1.0.1:9000"),
+            relay(2, "10.1.200.7:9000"),
+            relay(3, "10.2.0.1:9000"),
+            relay(4, "10.3.0.1:9000"),
+        ];
+        let path = select_relays(&same_subnet, 3, &policy, &[]).unwrap();
+        let ids: Vec<u8> = path.iter().map(|p| p.peer_id.0[0]).collect();
+        assert_eq!(ids, vec![1, 3, 4]);
+        assert!(select_relays(&same_subnet[..3], 3, &policy, &[]).is_err());
+
+        // Unreliable and excluded relays are passed over
+        let mut flaky = relay(5, "10.5.0.1:9000");
+        flaky.failure_count = 3;
+        let candidates = vec![flaky, relay(6, "10.6.0.1:9000"), relay(7, "10.7.0.1:9000")];
+        let excluded = [PeerId::from_bytes(vec![6])];
+        let path = select_relays(&candidates, 1, &policy, &excluded).unwrap();
+        assert_eq!(path[0].peer_id.0, vec![7]);
+
+        // Geo diversity
+        let mut candidates = vec![
+            relay(8, "10.8.0.1:9000"),
+            relay(9, "10.9.0.1:9000"),
+            relay(10, "10.10.0.1:9000"),
+        ];
+        for (peer, country) in candidates.iter_mut().zip(["DE", "DE", "NL"]) {
+            peer.geo_location = Some(GeoLocation {
+                latitude: 0.0,
+                longitude: 0.0,
+                country_code: Some(country.to_string()),
+            });
+        }
+        let geo = CircuitPolicy { geo_diverse: true, ..CircuitPolicy::default() };
+        let path = select_relays(&candidates, 2, &geo, &[]).unwrap();
+        let ids: Vec<u8> = path.iter().map(|p| p.peer_id.0[0]).collect();
+        assert_eq!(ids, vec![8, 10]);
+        assert!(select_relays(&candidates, 3, &geo, &[]).is_err());
+    }
+}
@@ -21,9 +21,13 @@
     Inputs:
       - OnionCommand::Send(dest_node, payload)
       - OnionCommand::RelayPacket(encrypted_blob)
+      - OnionCommand::SetCoverTraffic(enabled) (kill switch for low-power devices)
+      - OnionCommand::BuildCircuit(hops) / SendThroughCircuit(circuit_id, dest, payload)
+      - OnionCommand::HandleCell(from, cell), OnionCommand::PeerDisconnected(peer)
 
     Outputs:
       - OnionEvent::PacketForward(next_peer, blob)
+      - OnionEvent::CellForward(next_peer, cell)
       - OnionEvent::DeliverLocal(inner_envelope)
 
     Notes:
@@ -31,6 +35,20 @@
     Mixing: optionally batch multiple decrypted inner blobs into a short window (50-200ms), shuffle,
     and forward to reduce timing correlation. This is heavier but increases anonymity.
 
+    Cover traffic: with OnionConfig::cover_traffic enabled, the node sends one packet per slot of
+    a Poisson schedule. A slot carries the oldest queued real packet, or else a dummy
+    InnerEnvelope (cover = true) onioned to a random known peer, which drops it on receipt. Real
+    packets never wait more than max_delay for a slot; past that they go out at once. Every packet
+    on the wire is padded to a size bucket (PACKET_BUCKETS), so dummies and real packets of the
+    same bucket look alike.
+
+    Circuits: build_circuit(hops) picks relays under OnionConfig::circuit_policy and
+    establishes a key with each hop through the hops before it (see onion_circuit.rs). The
+    returned CircuitId carries any number of send_through_circuit calls. When a relay of the
+    path is lost (its neighbour reports TRUNCATED, or our link to the first hop drops) the
+    circuit is rebuilt around it under the same CircuitId. Circuits unused for
+    circuit_idle_timeout are torn down, at the origin and at every relay.
+
     Architecture:
 
     ┌────────────────────────────────────────────────────┐
@@ -77,11 +95,19 @@ use chacha20poly1305::{
     ChaCha20Poly1305, Nonce,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::{mpsc, Mutex};
-use tokio::time::interval;
-
+use tokio::sync::{mpsc, oneshot, Mutex, Notify};
+use tokio::time::{interval, Instant};
+use x25519_dalek::StaticSecret;
+
+use super::metrics;
+use super::onion_circuit::{
+    open, respond, seal, select_relays, unwrap_backward, wrap_forward, Backward, Cell, CircuitId,
+    CircuitPolicy, Forward, Handshake, HopKeys,
+};
 use super::route_table::{PeerInfo, RouteTable, RouteTableCommand};
 use super::session_manager::PeerId;
 
@@ -103,8 +129,14 @@ pub struct InnerEnvelope {
     pub destination: Vec<u8>,
     /// Actual payload
     pub payload: Vec<u8>,
+    /// Cover traffic; dropped by the hop that unwraps it
+    #[serde(default)]
+    pub cover: bool,
 }
 
+/// Wire sizes packets are padded to; larger packets round up to a multiple of the last one
+pub const PACKET_BUCKETS: [usize; 4] = [1024, 4096, 16 * 1024, 64 * 1024];
+
 /// Commands for OnionRouter
 #[derive(Debug)]
 pub enum OnionCommand {
@@ -118,6 +150,23 @@ pub enum OnionCommand {
     RelayPacket { encrypted_blob: Vec<u8> },
     /// Enable/disable mixing
     SetMixing { enabled: bool, window_ms: u64 },
+    /// Enable/disable cover traffic; disabling sends queued real packets right away
+    SetCoverTraffic { enabled: bool },
+    /// Build a circuit of `hops` relays
+    BuildCircuit { hops: usize, response_tx: oneshot::Sender<Result<CircuitId, String>> },
+    /// Send a payload out of a circuit's exit
+    SendThroughCircuit {
+        circuit_id: CircuitId,
+        destination: Vec<u8>,
+        payload: Vec<u8>,
+        response_tx: Option<oneshot::Sender<Result<(), String>>>,
+    },
+    /// Tear a circuit down
+    DestroyCircuit { circuit_id: CircuitId },
+    /// Handle a circuit cell received from a neighbour
+    HandleCell { from: PeerId, cell: Vec<u8> },
+    /// A neighbour's session closed; circuits through it are broken
+    PeerDisconnected { peer_id: PeerId },
     /// Shutdown
     Shutdown,
 }
@@ -127,6 +176,8 @@ pub enum OnionCommand {
 pub enum OnionEvent {
     /// Forward packet to next peer
     PacketForward { next_peer: PeerId, blob: Vec<u8> },
+    /// Send a circuit cell to a neighbour, to be handed to its HandleCell
+    CellForward { next_peer: PeerId, cell: Vec<u8> },
     /// Deliver to local application
     DeliverLocal { envelope: InnerEnvelope },
     /// Circuit built successfully
@@ -144,6 +195,14 @@ pub struct OnionConfig {
     pub mixing_enabled: bool,
     /// Mixing window duration
     pub mixing_window: Duration,
+    /// Dummy packets and scheduled sending
+    pub cover_traffic: CoverTrafficConfig,
+    /// How circuit relays are chosen
+    pub circuit_policy: CircuitPolicy,
+    /// Circuits unused this long are torn down
+    pub circuit_idle_timeout: Duration,
+    /// Longest a circuit hop may take to answer CREATE or EXTEND
+    pub circuit_build_timeout: Duration,
 }
 
 impl Default for OnionConfig {
@@ -152,10 +211,84 @@ impl Default for OnionConfig {
             circuit_hops: 3,
             mixing_enabled: false,
             mixing_window: Duration::from_millis(100),
+            cover_traffic: CoverTrafficConfig::default(),
+            circuit_policy: CircuitPolicy::default(),
+            circuit_idle_timeout: Duration::from_secs(10 * 60),
+            circuit_build_timeout: Duration::from_secs(5),
         }
     }
 }
 
+/// Configuration for cover traffic
+#[derive(Debug, Clone)]
+pub struct CoverTrafficConfig {
+    /// Send on a Poisson schedule, filling empty slots with dummies
+    pub enabled: bool,
+    /// Mean packets per second of the schedule
+    pub rate: f64,
+    /// Longest a real packet waits for a slot (added latency per hop)
+    pub max_delay: Duration,
+}
+
+impl Default for CoverTrafficConfig {
+    fn default() -> Self {
+        CoverTrafficConfig { enabled: false, rate: 2.0, max_delay: Duration::from_millis(500) }
+    }
+}
+
+/// Packets sent and dropped by the cover traffic scheduler
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CoverStats {
+    pub real_sent: u64,
+    pub cover_sent: u64,
+    pub cover_dropped: u64,
+}
+
+/// Real packets waiting for a slot of the cover schedule
+struct CoverQueue {
+    packets: VecDeque<(Instant, PeerId, Vec<u8>)>,
+    enabled: bool,
+}
+
+/// A circuit this node built
+struct Circuit {
+    /// Path length to keep across rebuilds
+    hops: usize,
+    path: Vec<PeerInfo>,
+    keys: Vec<HopKeys>,
+    /// Circuit number on our link to the first hop
+    circ: u64,
+    last_used: Instant,
+    /// A relay of the path was lost; rebuilt before the next send
+    broken: bool,
+    /// Relays lost on this circuit, left out of rebuilds
+    dead: Vec<PeerId>,
+}
+
+/// A CREATE or EXTEND waiting for its answer
+struct PendingHop {
+    first_hop: PeerId,
+    /// Keys of the hops established so far, to peel EXTENDED
+    keys: Vec<HopKeys>,
+    reply_tx: oneshot::Sender<Result<([u8; 32], [u8; 32]), String>>,
+}
+
+/// A hop of another node's circuit that we relay
+struct RelayHop {
+    keys: HopKeys,
+    /// Next hop and the circuit number on that link, once extended
+    next: Option<(PeerId, u64)>,
+    last_used: Instant,
+}
+
+/// Circuits we relay, keyed by (previous hop, circuit number)
+#[derive(Default)]
+struct RelayTable {
+    hops: HashMap<(PeerId, u64), RelayHop>,
+    /// (next hop, circuit number) back to the key in `hops`
+    back: HashMap<(PeerId, u64), (PeerId, u64)>,
+}
+
 /// Mix queue for batching and shuffling packets
 struct MixQueue {
     packets: Vec<(PeerId, Vec<u8>)>,
@@ -186,12 +319,55 @@ impl MixQueue {
     }
 }
 
+/// Pad a packet to its size bucket: 4-byte length, packet, random filler
+pub fn pad_to_bucket(packet: &[u8]) -> Vec<u8> {
+    let needed = packet.len() + 4;
+    let largest = PACKET_BUCKETS[PACKET_BUCKETS.len() - 1];
+    let size = PACKET_BUCKETS
+        .iter()
+        .copied()
+        .find(|bucket| *bucket >= needed)
+        .unwrap_or_else(|| needed.div_ceil(largest) * largest);
+
+    let mut padded = Vec::with_capacity(size);
+    padded.extend_from_slice(&(packet.len() as u32).to_be_bytes());
+    padded.extend_from_slice(packet);
+    let mut filler = vec![0u8; size - needed];
+    rand::fill(&mut filler[..]);
+    padded.extend_from_slice(&filler);
+    padded
+}
+
+/// Strip the padding added by pad_to_bucket
+pub fn unpad(padded: &[u8]) -> Result<&[u8], String> {
+    let (len, rest) = padded.split_first_chunk::<4>().ok_or("Packet too short")?;
+    let len = u32::from_be_bytes(*len) as usize;
+    rest.get(..len).ok_or_else(|| "Packet length exceeds padding".to_string())
+}
+
 /// OnionRouter manages onion routing
 pub struct OnionRouter {
     config: OnionConfig,
     route_table: Arc<RouteTable>,
     event_tx: mpsc::Sender<OnionEvent>,
     mix_queue: Arc<Mutex<MixQueue>>,
+    /// Our identity; layers addressed to us are keyed from it
+    local_id: PeerId,
+    cover_queue: Mutex<CoverQueue>,
+    /// Wakes the cover scheduler when a packet is queued or the mode changes
+    cover_notify: Notify,
+    real_sent: AtomicU64,
+    cover_sent: AtomicU64,
+    cover_dropped: AtomicU64,
+    /// Onion key answering CREATE; nodes without one don't relay circuits
+    onion_key: Option<StaticSecret>,
+    circuits: Mutex<HashMap<CircuitId, Circuit>>,
+    next_circuit_id: AtomicU64,
+    /// Answers awaited by circuit builds, by circuit number
+    pending_hops: Mutex<HashMap<u64, PendingHop>>,
+    relay_table: Mutex<RelayTable>,
+    /// One rebuild at a time, so a broken circuit isn't rebuilt twice
+    rebuild_lock: Mutex<()>,
 }
 
 impl OnionRouter {
@@ -204,7 +380,43 @@ impl OnionRouter {
         let mix_queue =
             Arc::new(Mutex::new(MixQueue::new(config.mixing_enabled, config.mixing_window)));
 
-        OnionRouter { config, route_table, event_tx, mix_queue }
+        let cover_queue = Mutex::new(CoverQueue {
+            packets: VecDeque::new(),
+            enabled: config.cover_traffic.enabled,
+        });
+
+        OnionRouter {
+            config,
+            route_table,
+            event_tx,
+            mix_queue,
+            local_id: PeerId::from_bytes(Vec::new()),
+            cover_queue,
+            cover_notify: Notify::new(),
+            real_sent: AtomicU64::new(0),
+            cover_sent: AtomicU64::new(0),
+            cover_dropped: AtomicU64::new(0),
+            onion_key: None,
+            circuits: Mutex::new(HashMap::new()),
+            next_circuit_id: AtomicU64::new(1),
+            pending_hops: Mutex::new(HashMap::new()),
+            relay_table: Mutex::new(RelayTable::default()),
+            rebuild_lock: Mutex::new(()),
+        }
+    }
+
+    /// Set our identity, used to unwrap layers addressed to us
+    pub fn with_local_id(mut self, local_id: PeerId) -> Self {
+        self.local_id = local_id;
+        self
+    }
+
+    /// Set our onion key, letting us relay circuits
+    ///
+    /// Its public half is what others see as our PeerInfo::overlay_pubkey.
+    pub fn with_onion_key(mut self, onion_key: StaticSecret) -> Self {
+        self.onion_key = Some(onion_key);
+        self
     }
 
     /// Start the onion router loop
@@ -229,9 +441,23 @@ impl OnionRouter {
             });
         }
 
+        let scheduler = self.clone();
+        let cover_task = tokio::spawn(async move {
+            scheduler.run_cover_schedule().await;
+        });
+
+        let mut idle_check = interval(
+            (self.config.circuit_idle_timeout / 4)
+                .clamp(Duration::from_millis(10), Duration::from_secs(30)),
+        );
+
         loop {
             tokio::select! {
-                Some(cmd) = command_rx.recv() => {
+                cmd = command_rx.recv() => {
+                    // All command senders dropped (router stopped)
+                    let Some(cmd) = cmd else {
+                        break;
+                    };
                     match cmd {
                         OnionCommand::Send { destination, payload, response_tx } => {
                             let result = self.handle_send(destination, payload).await;
@@ -251,15 +477,196 @@ impl OnionRouter {
                             queue.enabled = enabled;
                             queue.window = Duration::from_millis(window_ms);
                         }
+                        OnionCommand::SetCoverTraffic { enabled } => {
+                            self.set_cover_traffic(enabled).await;
+                        }
+                        // Builds wait for cells this loop delivers, so they run on their own
+                        OnionCommand::BuildCircuit { hops, response_tx } => {
+                            let router = self.clone();
+                            tokio::spawn(async move {
+                                let _ = response_tx.send(router.build_circuit(hops).await);
+                            });
+                        }
+                        OnionCommand::SendThroughCircuit {
+                            circuit_id,
+                            destination,
+                            payload,
+                            response_tx,
+                        } => {
+                            let router = self.clone();
+                            tokio::spawn(async move {
+                                let result = router
+                                    .send_through_circuit(circuit_id, destination, payload)
+                                    .await;
+                                if let Some(tx) = response_tx {
+                                    let _ = tx.send(result);
+                                }
+                            });
+                        }
+                        OnionCommand::DestroyCircuit { circuit_id } => {
+                            self.destroy_circuit(circuit_id).await;
+                        }
+                        OnionCommand::HandleCell { from, cell } => {
+                            match self.handle_cell(from, cell).await {
+                                Ok(broken) => self.spawn_rebuilds(broken),
+                                Err(e) => {
+                                    let _ = self.event_tx
+                                        .send(OnionEvent::RelayError { error: e })
+                                        .await;
+                                }
+                            }
+                        }
+                        OnionCommand::PeerDisconnected { peer_id } => {
+                            let broken = self.handle_peer_lost(&peer_id).await;
+                            self.spawn_rebuilds(broken);
+                        }
                         OnionCommand::Shutdown => {
                             break;
                         }
                     }
                 }
+                _ = idle_check.tick() => {
+                    self.expire_idle_circuits().await;
+                }
+            }
+        }
+        cover_task.abort();
+    }
+
+    /// Send packets on the cover schedule while cover traffic is enabled
+    async fn run_cover_schedule(&self) {
+        loop {
+            let (enabled, oldest_deadline) = {
+                let queue = self.cover_queue.lock().await;
+                let deadline = queue
+                    .packets
+                    .front()
+                    .map(|(queued, _, _)| *queued + self.config.cover_traffic.max_delay);
+                (queue.enabled, deadline)
+            };
+            if !enabled {
+                self.cover_notify.notified().await;
+                continue;
+            }
+
+            // The schedule is memoryless, so drawing a fresh gap after a wakeup is fine
+            let slot = Instant::now() + self.next_cover_gap();
+            let wake = oldest_deadline.map_or(slot, |deadline| deadline.min(slot));
+            tokio::select! {
+                _ = tokio::time::sleep_until(wake) => {}
+                _ = self.cover_notify.notified() => continue,
+            }
+
+            let now = Instant::now();
+            let mut due = Vec::new();
+            {
+                let mut queue = self.cover_queue.lock().await;
+                let max_delay = self.config.cover_traffic.max_delay;
+                while queue.packets.front().is_some_and(|(queued, _, _)| *queued + max_delay <= now)
+                {
+                    due.extend(queue.packets.pop_front());
+                }
+                if due.is_empty() && now >= slot {
+                    due.extend(queue.packets.pop_front());
+                }
+            }
+
+            if due.is_empty() && now >= slot {
+                self.send_dummy().await;
+            }
+            for (_, next_peer, packet) in due {
+                let _ =
+                    self.event_tx.send(OnionEvent::PacketForward { next_peer, blob: packet }).await;
             }
         }
     }
 
+    /// Time until the next slot of the Poisson schedule
+    fn next_cover_gap(&self) -> Duration {
+        let rate = self.config.cover_traffic.rate.max(f64::MIN_POSITIVE);
+        // 1 - U is in (0, 1], so the log is finite
+        let uniform = 1.0 - rand::random::<f64>();
+        Duration::from_secs_f64((-uniform.ln() / rate).min(3600.0))
+    }
+
+    /// Onion a dummy envelope to a random known peer
+    async fn send_dummy(&self) {
+        use rand::seq::IndexedRandom;
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        if self
+            .route_table
+            .handle_command(RouteTableCommand::GetAllPeers { response_tx: tx })
+            .await
+            .is_err()
+        {
+            return;
+        }
+        let peers = rx.await.unwrap_or_default();
+        let Some(peer) = peers.choose(&mut rand::rng()).cloned() else {
+            return;
+        };
+
+        let envelope = InnerEnvelope { destination: Vec::new(), payload: Vec::new(), cover: true };
+        let Ok(envelope_bytes) = serde_json::to_vec(&envelope) else {
+            return;
+        };
+        let Ok(onion) = self.build_onion_layers(std::slice::from_ref(&peer), envelope_bytes) else {
+            return;
+        };
+
+        self.cover_sent.fetch_add(1, Ordering::Relaxed);
+        metrics::onion_packet_sent("cover");
+        let _ = self
+            .event_tx
+            .send(OnionEvent::PacketForward {
+                next_peer: peer.peer_id,
+                blob: pad_to_bucket(&onion),
+            })
+            .await;
+    }
+
+    /// Turn cover traffic on or off; off sends everything still queued
+    async fn set_cover_traffic(&self, enabled: bool) {
+        let flushed: Vec<_> = {
+            let mut queue = self.cover_queue.lock().await;
+            queue.enabled = enabled;
+            if enabled {
+                Vec::new()
+            } else {
+                queue.packets.drain(..).collect()
+            }
+        };
+        self.cover_notify.notify_one();
+        for (_, next_peer, packet) in flushed {
+            let _ = self.event_tx.send(OnionEvent::PacketForward { next_peer, blob: packet }).await;
+        }
+    }
+
+    /// Pad a packet and send it towards next_peer: on the cover schedule, through the mix
+    /// queue, or straight away
+    async fn dispatch(&self, next_peer: PeerId, blob: Vec<u8>) {
+        let packet = pad_to_bucket(&blob);
+        self.real_sent.fetch_add(1, Ordering::Relaxed);
+        metrics::onion_packet_sent("real");
+
+        {
+            let mut queue = self.cover_queue.lock().await;
+            if queue.enabled {
+                queue.packets.push_back((Instant::now(), next_peer, packet));
+                drop(queue);
+                self.cover_notify.notify_one();
+                return;
+            }
+        }
+
+        if self.config.mixing_enabled {
+            self.mix_queue.lock().await.add(next_peer, packet);
+        } else {
+            let _ = self.event_tx.send(OnionEvent::PacketForward { next_peer, blob: packet }).await;
+        }
+    }
+
     /// Handle sending data via onion routing
     async fn handle_send(&self, destination: PeerId, payload: Vec<u8>) -> Result<(), String> {
         // 1. Pick path from route table
@@ -270,7 +677,7 @@ impl OnionRouter {
         }
 
         // 2. Build inner envelope
-        let envelope = InnerEnvelope { destination: destination.0.clone(), payload };
+        let envelope = InnerEnvelope { destination: destination.0.clone(), payload, cover: false };
 
         let envelope_bytes = serde_json::to_vec(&envelope)
             .map_err(|e| format!("Failed to serialize envelope: {}", e))?;
@@ -280,17 +687,7 @@ impl OnionRouter {
 
         // 4. Send to first hop
         if let Some(first_hop) = path.first() {
-            if self.config.mixing_enabled {
-                self.mix_queue.lock().await.add(first_hop.peer_id.clone(), onion_packet);
-            } else {
-                let _ = self
-                    .event_tx
-                    .send(OnionEvent::PacketForward {
-                        next_peer: first_hop.peer_id.clone(),
-                        blob: onion_packet,
-                    })
-                    .await;
-            }
+            self.dispatch(first_hop.peer_id.clone(), onion_packet).await;
 
             let _ = self.event_tx.send(OnionEvent::CircuitBuilt { path_length: path.len() }).await;
 
@@ -311,32 +708,33 @@ impl OnionRouter {
         // 5. Either forward or deliver locally
 
         // For now, simplified version:
-        let decrypted = self.decrypt_layer(&encrypted_blob)?;
-
-        // Try to parse as header + remaining layers
-        if let Ok(header) =
-            serde_json::from_slice::<OnionHeader>(&decrypted[..64.min(decrypted.len())])
-        {
-            if header.deliver_local {
-                // Final hop - deliver to application
-                let envelope: InnerEnvelope = serde_json::from_slice(&decrypted[64..])
-                    .map_err(|e| format!("Failed to parse inner envelope: {}", e))?;
-
-                let _ = self.event_tx.send(OnionEvent::DeliverLocal { envelope }).await;
-            } else {
-                // Forward to next hop
-                let next_peer = PeerId::from_bytes(header.next_hop);
-                let remaining_blob = decrypted[64..].to_vec();
-
-                if self.config.mixing_enabled {
-                    self.mix_queue.lock().await.add(next_peer, remaining_blob);
-                } else {
-                    let _ = self
-                        .event_tx
-                        .send(OnionEvent::PacketForward { next_peer, blob: remaining_blob })
-                        .await;
-                }
+        let decrypted = self.decrypt_layer(unpad(&encrypted_blob)?)?;
+
+        // Layer = 4-byte header length, header, remaining layers
+        let (header_len, rest) =
+            decrypted.split_first_chunk::<4>().ok_or("Onion layer too short")?;
+        let header_len = u32::from_be_bytes(*header_len) as usize;
+        if header_len > rest.len() {
+            return Err("Onion header length exceeds layer".to_string());
+        }
+        let (header, inner) = rest.split_at(header_len);
+        let header: OnionHeader = serde_json::from_slice(header)
+            .map_err(|e| format!("Failed to parse onion header: {}", e))?;
+
+        if header.deliver_local {
+            // Final hop - deliver to application
+            let envelope: InnerEnvelope = serde_json::from_slice(inner)
+                .map_err(|e| format!("Failed to parse inner envelope: {}", e))?;
+
+            if envelope.cover {
+                self.cover_dropped.fetch_add(1, Ordering::Relaxed);
+                metrics::cover_packet_dropped();
+                return Ok(());
             }
+            let _ = self.event_tx.send(OnionEvent::DeliverLocal { envelope }).await;
+        } else {
+            // Forward to next hop
+            self.dispatch(PeerId::from_bytes(header.next_hop), inner.to_vec()).await;
         }
 
         Ok(())
@@ -377,8 +775,9 @@ impl OnionRouter {
             let header_bytes = serde_json::to_vec(&header)
                 .map_err(|e| format!("Failed to serialize header: {}", e))?;
 
-            // Combine header and current blob
-            let mut combined = header_bytes;
+            // Combine length-prefixed header and current blob
+            let mut combined = (header_bytes.len() as u32).to_be_bytes().to_vec();
+            combined.extend_from_slice(&header_bytes);
             combined.extend_from_slice(&current_blob);
 
             // Encrypt this layer
@@ -390,13 +789,7 @@ impl OnionRouter {
 
     /// Encrypt a layer (simplified - uses peer_id as key material)
     fn encrypt_layer(&self, peer_id: &PeerId, data: &[u8]) -> Result<Vec<u8>, String> {
-        // Derive a key from peer_id (in production, use proper X25519 ECDH)
-        let mut key = [0u8; 32];
-        for (i, byte) in peer_id.0.iter().take(32).enumerate() {
-            key[i] = *byte;
-        }
-
-        let cipher = ChaCha20Poly1305::new(&key.into());
+        let cipher = ChaCha20Poly1305::new(&Self::layer_key(peer_id).into());
         let nonce = Nonce::from_slice(&[0u8; 12]); // Simplified: use proper nonce
 
         cipher.encrypt(nonce, data).map_err(|e| format!("Encryption failed: {}", e))
@@ -405,19 +798,524 @@ impl OnionRouter {
     /// Decrypt a layer (simplified)
     fn decrypt_layer(&self, data: &[u8]) -> Result<Vec<u8>, String> {
         // In production, derive key from our private key + ephemeral public key
-        // For now, use a dummy key
-        let key = [0u8; 32];
-
-        let cipher = ChaCha20Poly1305::new(&key.into());
+        let cipher = ChaCha20Poly1305::new(&Self::layer_key(&self.local_id).into());
         let nonce = Nonce::from_slice(&[0u8; 12]);
 
         cipher.decrypt(nonce, data).map_err(|e| format!("Decryption failed: {}", e))
     }
 
+    /// Layer key of a hop (simplified - derived from peer_id; in production, use X25519 ECDH)
+    fn layer_key(peer_id: &PeerId) -> [u8; 32] {
+        let mut key = [0u8; 32];
+        for (i, byte) in peer_id.0.iter().take(32).enumerate() {
+            key[i] = *byte;
+        }
+        key
+    }
+
+    /// Packets sent and dropped so far, for the cover/real ratio
+    pub fn cover_stats(&self) -> CoverStats {
+        CoverStats {
+            real_sent: self.real_sent.load(Ordering::Relaxed),
+            cover_sent: self.cover_sent.load(Ordering::Relaxed),
+            cover_dropped: self.cover_dropped.load(Ordering::Relaxed),
+        }
+    }
+
     /// Get mixing queue size
     pub async fn mix_queue_size(&self) -> usize {
         self.mix_queue.lock().await.packets.len()
     }
+
+    /// Build a circuit of `hops` relays chosen under the circuit policy
+    ///
+    /// The hops answer through cells handled by `run`, so this must not be awaited from the
+    /// task running it (OnionCommand::BuildCircuit spawns it instead).
+    pub async fn build_circuit(&self, hops: usize) -> Result<CircuitId, String> {
+        if hops == 0 {
+            return Err("A circuit needs at least one hop".to_string());
+        }
+        let path = self.select_circuit_path(hops, &[]).await?;
+        let (circ, keys) = self.establish_circuit(&path).await?;
+
+        let circuit_id = CircuitId(self.next_circuit_id.fetch_add(1, Ordering::Relaxed));
+        self.circuits.lock().await.insert(
+            circuit_id,
+            Circuit {
+                hops,
+                path,
+                keys,
+                circ,
+                last_used: Instant::now(),
+                broken: false,
+                dead: Vec::new(),
+            },
+        );
+        metrics::onion_circuit_built("new");
+        let _ = self.event_tx.send(OnionEvent::CircuitBuilt { path_length: hops }).await;
+        Ok(circuit_id)
+    }
+
+    /// Send a payload through a circuit; its exit delivers it as an InnerEnvelope
+    ///
+    /// `destination` (a peer ID, a DHT key, ...) is only readable by the exit. A circuit
+    /// that lost a relay is rebuilt before sending.
+    pub async fn send_through_circuit(
+        &self,
+        circuit_id: CircuitId,
+        destination: Vec<u8>,
+        payload: Vec<u8>,
+    ) -> Result<(), String> {
+        self.rebuild_circuit(circuit_id).await?;
+
+        let (first_hop, circ, body) = {
+            let mut circuits = self.circuits.lock().await;
+            let circuit = circuits.get_mut(&circuit_id).ok_or("Unknown circuit")?;
+            circuit.last_used = Instant::now();
+            let body = wrap_forward(&circuit.keys, &Forward::Data { destination, payload })?;
+            (circuit.path[0].peer_id.clone(), circuit.circ, body)
+        };
+        metrics::onion_packet_sent("real");
+        self.send_cell(first_hop, &Cell::Relay { circ, body }).await
+    }
+
+    /// Tear a circuit down at every hop
+    pub async fn destroy_circuit(&self, circuit_id: CircuitId) {
+        let Some(circuit) = self.circuits.lock().await.remove(&circuit_id) else {
+            return;
+        };
+        metrics::onion_circuit_closed("destroyed");
+        let first_hop = circuit.path[0].peer_id.clone();
+        let _ = self.send_cell(first_hop, &Cell::Destroy { circ: circuit.circ }).await;
+    }
+
+    /// Relays a circuit currently runs through, first hop first
+    pub async fn circuit_path(&self, circuit_id: CircuitId) -> Option<Vec<PeerId>> {
+        let circuits = self.circuits.lock().await;
+        circuits
+            .get(&circuit_id)
+            .map(|circuit| circuit.path.iter().map(|hop| hop.peer_id.clone()).collect())
+    }
+
+    /// Number of open circuits this node built
+    pub async fn circuit_count(&self) -> usize {
+        self.circuits.lock().await.len()
+    }
+
+    /// Number of other nodes' circuits this node relays
+    pub async fn relayed_circuit_count(&self) -> usize {
+        self.relay_table.lock().await.hops.len()
+    }
+
+    /// Relays for a new path: known healthy peers in random order, filtered by the policy
+    async fn select_circuit_path(
+        &self,
+        hops: usize,
+        dead: &[PeerId],
+    ) -> Result<Vec<PeerInfo>, String> {
+        use rand::seq::SliceRandom;
+
+        let (tx, rx) = oneshot::channel();
+        self.route_table
+            .handle_command(RouteTableCommand::GetAllPeers { response_tx: tx })
+            .await?;
+        let mut peers = rx.await.map_err(|e| format!("Failed to list relays: {}", e))?;
+        peers.retain(|peer| peer.is_healthy(3, Duration::from_secs(3600)));
+        peers.shuffle(&mut rand::rng());
+
+        let mut exclude = dead.to_vec();
+        exclude.push(self.local_id.clone());
+        select_relays(&peers, hops, &self.config.circuit_policy, &exclude)
+    }
+
+    /// Establish keys along `path`: CREATE to the first hop, then EXTEND through the
+    /// circuit so far to each further hop
+    ///
+    /// Returns the circuit number on our link to the first hop and the hop keys.
+    async fn establish_circuit(&self, path: &[PeerInfo]) -> Result<(u64, Vec<HopKeys>), String> {
+        let first_hop = path.first().ok_or("Empty path")?.peer_id.clone();
+        let circ = rand::random::<u64>();
+        let mut keys: Vec<HopKeys> = Vec::with_capacity(path.len());
+
+        for hop in path {
+            let handshake = Handshake::new(hop.overlay_pubkey);
+            let cell = if keys.is_empty() {
+                Cell::Create { circ, client_pub: handshake.client_pub }
+            } else {
+                let extend = Forward::Extend {
+                    next_hop: hop.peer_id.0.clone(),
+                    client_pub: handshake.client_pub,
+                };
+                Cell::Relay { circ, body: wrap_forward(&keys, &extend)? }
+            };
+
+            let (reply_tx, reply_rx) = oneshot::channel();
+            self.pending_hops.lock().await.insert(
+                circ,
+                PendingHop { first_hop: first_hop.clone(), keys: keys.clone(), reply_tx },
+            );
+            let reply = match self.send_cell(first_hop.clone(), &cell).await {
+                Ok(()) => tokio::time::timeout(self.config.circuit_build_timeout, reply_rx)
+                    .await
+                    .map_err(|_| format!("Circuit hop {} did not answer", keys.len() + 1))
+                    .and_then(|reply| reply.unwrap_or(Err("Circuit build abandoned".to_string()))),
+                Err(e) => Err(e),
+            };
+            self.pending_hops.lock().await.remove(&circ);
+
+            match reply.and_then(|(relay_pub, auth)| handshake.finish(relay_pub, auth)) {
+                Ok(hop_keys) => keys.push(hop_keys),
+                Err(e) => {
+                    // Release whatever the hops so far hold for us
+                    let _ = self.send_cell(first_hop, &Cell::Destroy { circ }).await;
+                    return Err(e);
+                }
+            }
+        }
+        Ok((circ, keys))
+    }
+
+    /// Rebuild a broken circuit around its lost relays, keeping its CircuitId
+    async fn rebuild_circuit(&self, circuit_id: CircuitId) -> Result<(), String> {
+        let is_broken = |circuits: &HashMap<CircuitId, Circuit>| {
+            circuits.get(&circuit_id).map(|circuit| circuit.broken).ok_or("Unknown circuit")
+        };
+        if !is_broken(&*self.circuits.lock().await)? {
+            return Ok(());
+        }
+
+        let _guard = self.rebuild_lock.lock().await;
+        let (hops, dead, old_first, old_circ) = {
+            let circuits = self.circuits.lock().await;
+            // Another task may have rebuilt it while we waited
+            if !is_broken(&circuits)? {
+                return Ok(());
+            }
+            let circuit = &circuits[&circuit_id];
+            (
+                circuit.hops,
+                circuit.dead.clone(),
+                circuit.path[0].peer_id.clone(),
+                circuit.circ,
+            )
+        };
+        // Release what is left of the old path
+        if !dead.contains(&old_first) {
+            let _ = self.send_cell(old_first, &Cell::Destroy { circ: old_circ }).await;
+        }
+
+        let path = self.select_circuit_path(hops, &dead).await?;
+        let (circ, keys) = self.establish_circuit(&path).await?;
+
+        let mut circuits = self.circuits.lock().await;
+        let Some(circuit) = circuits.get_mut(&circuit_id) else {
+            // Destroyed while we were rebuilding
+            drop(circuits);
+            let _ = self.send_cell(path[0].peer_id.clone(), &Cell::Destroy { circ }).await;
+            return Err("Unknown circuit".to_string());
+        };
+        circuit.path = path;
+        circuit.keys = keys;
+        circuit.circ = circ;
+        circuit.broken = false;
+        circuit.last_used = Instant::now();
+        drop(circuits);
+
+        metrics::onion_circuit_built("rebuilt");
+        let _ = self.event_tx.send(OnionEvent::CircuitBuilt { path_length: hops }).await;
+        Ok(())
+    }
+
+    /// Rebuild circuits in the background, as soon as they are known to be broken
+    fn spawn_rebuilds(self: &Arc<Self>, broken: Vec<CircuitId>) {
+        for circuit_id in broken {
+            let router = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = router.rebuild_circuit(circuit_id).await {
+                    let error = format!("Circuit rebuild failed: {}", e);
+                    let _ = router.event_tx.send(OnionEvent::RelayError { error }).await;
+                }
+            });
+        }
+    }
+
+    /// Send a cell to a neighbour, padded like onion packets
+    async fn send_cell(&self, next_peer: PeerId, cell: &Cell) -> Result<(), String> {
+        let bytes =
+            bincode::serialize(cell).map_err(|e| format!("Failed to encode cell: {}", e))?;
+        self.event_tx
+            .send(OnionEvent::CellForward { next_peer, cell: pad_to_bucket(&bytes) })
+            .await
+            .map_err(|e| format!("Failed to forward cell: {}", e))
+    }
+
+    /// Handle a cell from a neighbour, as the origin of its circuit or as a relay
+    ///
+    /// Returns the circuits of ours it reported broken.
+    async fn handle_cell(&self, from: PeerId, cell: Vec<u8>) -> Result<Vec<CircuitId>, String> {
+        let cell: Cell = bincode::deserialize(unpad(&cell)?)
+            .map_err(|e| format!("Failed to decode cell: {}", e))?;
+
+        // Answers for a circuit we are building
+        let pending = {
+            let mut pending_hops = self.pending_hops.lock().await;
+            match pending_hops.get(&cell.circ()) {
+                Some(pending) if pending.first_hop == from => pending_hops.remove(&cell.circ()),
+                _ => None,
+            }
+        };
+        if let Some(pending) = pending {
+            let reply = match cell {
+                Cell::Created { relay_pub, auth, .. } => Ok((relay_pub, auth)),
+                Cell::Relay { body, .. } => match unwrap_backward(&pending.keys, body)? {
+                    (_, Backward::Extended { relay_pub, auth }) => Ok((relay_pub, auth)),
+                    (hop, _) => Err(format!("Circuit cut past hop {}", hop + 1)),
+                },
+                _ => Err("Circuit refused by its first hop".to_string()),
+            };
+            let _ = pending.reply_tx.send(reply);
+            return Ok(Vec::new());
+        }
+
+        // Cells on circuits we built
+        let origin = {
+            let circuits = self.circuits.lock().await;
+            circuits
+                .iter()
+                .find(|(_, circuit)| circuit.circ == cell.circ() && circuit.path[0].peer_id == from)
+                .map(|(circuit_id, _)| *circuit_id)
+        };
+        match origin {
+            Some(circuit_id) => self.handle_origin_cell(circuit_id, cell).await,
+            None => self.handle_relay_cell(from, cell).await.map(|_| Vec::new()),
+        }
+    }
+
+    /// Handle a cell that came back along a circuit we built
+    async fn handle_origin_cell(
+        &self,
+        circuit_id: CircuitId,
+        cell: Cell,
+    ) -> Result<Vec<CircuitId>, String> {
+        let mut circuits = self.circuits.lock().await;
+        let Some(circuit) = circuits.get_mut(&circuit_id) else {
+            return Ok(Vec::new());
+        };
+        match cell {
+            Cell::Relay { body, .. } => match unwrap_backward(&circuit.keys, body)? {
+                (hop, Backward::Truncated) => {
+                    // The relay after `hop` is gone
+                    circuit.dead.extend(circuit.path.get(hop + 1).map(|lost| lost.peer_id.clone()));
+                    circuit.broken = true;
+                    Ok(vec![circuit_id])
+                }
+                (hop, _) => Err(format!("Unexpected reply from circuit hop {}", hop + 1)),
+            },
+            Cell::Destroy { .. } => {
+                circuit.broken = true;
+                Ok(vec![circuit_id])
+            }
+            _ => Err("Unexpected cell on an established circuit".to_string()),
+        }
+    }
+
+    /// Handle a cell on a circuit we relay for another node
+    async fn handle_relay_cell(&self, from: PeerId, cell: Cell) -> Result<(), String> {
+        match cell {
+            Cell::Create { circ, client_pub } => {
+                let onion_key = self.onion_key.as_ref().ok_or("Not relaying circuits")?;
+                let (keys, relay_pub, auth) = respond(onion_key, client_pub)?;
+                self.relay_table.lock().await.hops.insert(
+                    (from.clone(), circ),
+                    RelayHop { keys, next: None, last_used: Instant::now() },
+                );
+                self.send_cell(from, &Cell::Created { circ, relay_pub, auth }).await
+            }
+            Cell::Relay { circ, body } => {
+                let mut guard = self.relay_table.lock().await;
+                let table = &mut *guard;
+                let key = (from, circ);
+                if let Some(prev) = table.back.get(&key).cloned() {
+                    // From the next hop: add our layer on the way back
+                    drop(guard);
+                    return self.send_backward(prev, Backward::Inner(body)).await;
+                }
+
+                let hop = table.hops.get_mut(&key).ok_or("Cell for an unknown circuit")?;
+                hop.last_used = Instant::now();
+                match open(&hop.keys.forward, &body)? {
+                    Forward::Next(inner) => {
+                        let (next_peer, next_circ) =
+                            hop.next.clone().ok_or("Circuit does not extend past this hop")?;
+                        drop(guard);
+                        self.send_cell(next_peer, &Cell::Relay { circ: next_circ, body: inner })
+                            .await
+                    }
+                    Forward::Extend { next_hop, client_pub } => {
+                        if hop.next.is_some() {
+                            return Err("Circuit already extended past this hop".to_string());
+                        }
+                        let next_peer = PeerId::from_bytes(next_hop);
+                        let next_circ = rand::random::<u64>();
+                        hop.next = Some((next_peer.clone(), next_circ));
+                        table.back.insert((next_peer.clone(), next_circ), key);
+                        drop(guard);
+                        self.send_cell(next_peer, &Cell::Create { circ: next_circ, client_pub })
+                            .await
+                    }
+                    Forward::Data { destination, payload } => {
+                        drop(guard);
+                        let envelope = InnerEnvelope { destination, payload, cover: false };
+                        let _ = self.event_tx.send(OnionEvent::DeliverLocal { envelope }).await;
+                        Ok(())
+                    }
+                }
+            }
+            Cell::Created { circ, relay_pub, auth } => {
+                let prev = self.relay_table.lock().await.back.get(&(from, circ)).cloned();
+                let prev = prev.ok_or("CREATED for an unknown circuit")?;
+                self.send_backward(prev, Backward::Extended { relay_pub, auth }).await
+            }
+            Cell::Destroy { circ } => {
+                let mut guard = self.relay_table.lock().await;
+                let table = &mut *guard;
+                if let Some(hop) = table.hops.remove(&(from.clone(), circ)) {
+                    // From the previous hop: pass it on
+                    if let Some((next_peer, next_circ)) = hop.next {
+                        table.back.remove(&(next_peer.clone(), next_circ));
+                        drop(guard);
+                        let _ = self.send_cell(next_peer, &Cell::Destroy { circ: next_circ }).await;
+                    }
+                } else if let Some(prev) = table.back.remove(&(from, circ)) {
+                    // From the next hop: the origin's path is cut past us
+                    if let Some(hop) = table.hops.get_mut(&prev) {
+                        hop.next = None;
+                    }
+                    drop(guard);
+                    let _ = self.send_backward(prev, Backward::Truncated).await;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Add our layer to a reply and send it back towards the circuit's origin
+    async fn send_backward(&self, prev: (PeerId, u64), reply: Backward) -> Result<(), String> {
+        let body = {
+            let mut table = self.relay_table.lock().await;
+            let hop = table.hops.get_mut(&prev).ok_or("Reply for an unknown circuit")?;
+            hop.last_used = Instant::now();
+            seal(&hop.keys.backward, &reply)?
+        };
+        let (prev_peer, circ) = prev;
+        self.send_cell(prev_peer, &Cell::Relay { circ, body }).await
+    }
+
+    /// Drop circuits through a neighbour whose session closed
+    ///
+    /// Relayed circuits are destroyed downstream and reported truncated upstream. Returns the
+    /// circuits of ours that ran through it.
+    async fn handle_peer_lost(&self, peer_id: &PeerId) -> Vec<CircuitId> {
+        let mut destroyed = Vec::new();
+        let mut truncated = Vec::new();
+        {
+            let mut guard = self.relay_table.lock().await;
+            let table = &mut *guard;
+            let orphaned: Vec<_> =
+                table.hops.keys().filter(|(prev, _)| prev == peer_id).cloned().collect();
+            for key in orphaned {
+                if let Some(next) = table.hops.remove(&key).and_then(|hop| hop.next) {
+                    table.back.remove(&next);
+                    destroyed.push(next);
+                }
+            }
+            let cut: Vec<_> =
+                table.back.keys().filter(|(next, _)| next == peer_id).cloned().collect();
+            for key in cut {
+                if let Some(prev) = table.back.remove(&key) {
+                    if let Some(hop) = table.hops.get_mut(&prev) {
+                        hop.next = None;
+                    }
+                    truncated.push(prev);
+                }
+            }
+        }
+        for (next_peer, circ) in destroyed {
+            let _ = self.send_cell(next_peer, &Cell::Destroy { circ }).await;
+        }
+        for prev in truncated {
+            let _ = self.send_backward(prev, Backward::Truncated).await;
+        }
+
+        {
+            let mut pending_hops = self.pending_hops.lock().await;
+            let waiting: Vec<u64> = pending_hops
+                .iter()
+                .filter(|(_, pending)| pending.first_hop == *peer_id)
+                .map(|(circ, _)| *circ)
+                .collect();
+            for circ in waiting {
+                if let Some(pending) = pending_hops.remove(&circ) {
+                    let _ = pending.reply_tx.send(Err("First hop disconnected".to_string()));
+                }
+            }
+        }
+
+        let mut circuits = self.circuits.lock().await;
+        let mut broken = Vec::new();
+        for (circuit_id, circuit) in circuits.iter_mut() {
+            if circuit.path[0].peer_id == *peer_id && !circuit.broken {
+                circuit.broken = true;
+                circuit.dead.push(peer_id.clone());
+                broken.push(*circuit_id);
+            }
+        }
+        broken
+    }
+
+    /// Tear down circuits, ours and relayed, unused for the idle timeout
+    async fn expire_idle_circuits(&self) {
+        let idle = self.config.circuit_idle_timeout;
+        let now = Instant::now();
+
+        let expired: Vec<Circuit> = {
+            let mut circuits = self.circuits.lock().await;
+            let ids: Vec<CircuitId> = circuits
+                .iter()
+                .filter(|(_, circuit)| circuit.last_used + idle <= now)
+                .map(|(circuit_id, _)| *circuit_id)
+                .collect();
+            ids.iter().filter_map(|circuit_id| circuits.remove(circuit_id)).collect()
+        };
+        for circuit in expired {
+            metrics::onion_circuit_closed("idle");
+            let first_hop = circuit.path[0].peer_id.clone();
+            let _ = self.send_cell(first_hop, &Cell::Destroy { circ: circuit.circ }).await;
+        }
+
+        // Relayed hops whose origin went quiet without a DESTROY reaching us
+        let abandoned: Vec<(PeerId, u64)> = {
+            let mut guard = self.relay_table.lock().await;
+            let table = &mut *guard;
+            let keys: Vec<_> = table
+                .hops
+                .iter()
+                .filter(|(_, hop)| hop.last_used + idle <= now)
+                .map(|(key, _)| key.clone())
+                .collect();
+            let mut abandoned = Vec::new();
+            for key in keys {
+                if let Some(next) = table.hops.remove(&key).and_then(|hop| hop.next) {
+                    table.back.remove(&next);
+                    abandoned.push(next);
+                }
+            }
+            abandoned
+        };
+        for (next_peer, circ) in abandoned {
+            let _ = self.send_cell(next_peer, &Cell::Destroy { circ }).await;
+        }
+    }
 }
 
 #[cfg(test)]
@@ -647,7 +1545,11 @@ mod tests {
 
     #[tokio::test]
     async fn test_inner_envelope_serialization() {
-        let envelope = InnerEnvelope { destination: vec![1, 2, 3, 4], payload: vec![5, 6, 7, 8] };
+        let envelope = InnerEnvelope {
+            destination: vec![1, 2, 3, 4],
+            payload: vec![5, 6, 7, 8],
+            cover: false,
+        };
 
         let serialized = serde_json::to_vec(&envelope).unwrap();
         let deserialized: InnerEnvelope = serde_json::from_slice(&serialized).unwrap();
@@ -670,4 +1572,188 @@ mod tests {
         assert_eq!(deserialized.next_hop, header.next_hop);
         assert_eq!(deserialized.deliver_local, header.deliver_local);
     }
+
+    fn cover_config(rate: f64, max_delay: Duration) -> OnionConfig {
+        OnionConfig {
+            cover_traffic: CoverTrafficConfig { enabled: true, rate, max_delay },
+            ..OnionConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_padding_buckets() {
+        let small = pad_to_bucket(&[7u8; 10]);
+        assert_eq!(small.len(), PACKET_BUCKETS[0]);
+        assert_eq!(unpad(&small).unwrap(), &[7u8; 10]);
+
+        let medium = pad_to_bucket(&[7u8; 2000]);
+        assert_eq!(medium.len(), PACKET_BUCKETS[1]);
+
+        let huge = pad_to_bucket(&vec![7u8; 100_000]);
+        assert_eq!(huge.len() % PACKET_BUCKETS[3], 0);
+        assert_eq!(unpad(&huge).unwrap().len(), 100_000);
+
+        assert!(unpad(&[0, 0, 1, 0, 1]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cover_dummies_dropped_at_first_hop() {
+        let hop_id = PeerId::from_bytes(vec![7; 32]);
+        let route_table = Arc::new(RouteTable::new());
+        route_table
+            .handle_command(RouteTableCommand::InsertPeer(PeerInfo::new(hop_id.clone(), vec![])))
+            .await
+            .unwrap();
+        let (event_tx, mut event_rx) = mpsc::channel(100);
+        let sender = Arc::new(OnionRouter::new(
+            cover_config(50.0, Duration::from_millis(100)),
+            route_table,
+            event_tx,
+        ));
+        let (_cmd_tx, cmd_rx) = mpsc::channel(10);
+        let task = tokio::spawn(sender.clone().run(cmd_rx));
+
+        let mut dummies = Vec::new();
+        while dummies.len() < 5 {
+            let event = tokio::time::timeout(Duration::from_secs(2), event_rx.recv())
+                .await
+                .expect("Cover traffic should flow with nothing to send")
+                .unwrap();
+            let OnionEvent::PacketForward { next_peer, blob } = event else {
+                panic!("Unexpected event: {:?}", event);
+            };
+            assert_eq!(next_peer, hop_id);
+            // Same size as any small real packet
+            assert_eq!(blob.len(), PACKET_BUCKETS[0]);
+            dummies.push(blob);
+        }
+        task.abort();
+        assert!(sender.cover_stats().cover_sent >= 5);
+        assert_eq!(sender.cover_stats().real_sent, 0);
+
+        let (hop_tx, mut hop_rx) = mpsc::channel(100);
+        let hop = OnionRouter::new(OnionConfig::default(), Arc::new(RouteTable::new()), hop_tx)
+            .with_local_id(hop_id);
+        for dummy in dummies {
+            hop.handle_relay(dummy).await.unwrap();
+        }
+        assert_eq!(hop.cover_stats().cover_dropped, 5);
+        assert!(hop_rx.try_recv().is_err(), "Dummies must go no further than the first hop");
+    }
+
+    #[tokio::test]
+    async fn test_cover_traffic_bounded_latency() {
+        let max_delay = Duration::from_millis(100);
+        let relay_ids: Vec<PeerId> = (1..=3).map(|i| PeerId::from_bytes(vec![i; 32])).collect();
+        let route_table = Arc::new(RouteTable::new());
+        for (i, id) in relay_ids.iter().enumerate() {
+            let mut relay = PeerInfo::new(id.clone(), vec![]);
+            relay.capabilities.push(Capability::Relay);
+            relay.asn = Some(1000 + i as u32);
+            route_table.handle_command(RouteTableCommand::InsertPeer(relay)).await.unwrap();
+        }
+
+        // Sender and relays all run the cover schedule; a slow rate makes max_delay decide
+        let (forward_tx, mut forward_rx) = mpsc::channel::<OnionEvent>(100);
+        let mut commands = std::collections::HashMap::new();
+        let mut tasks = Vec::new();
+        let nodes = std::iter::once((PeerId::from_bytes(vec![9; 32]), route_table))
+            .chain(relay_ids.iter().map(|id| (id.clone(), Arc::new(RouteTable::new()))));
+        for (id, table) in nodes {
+            let (event_tx, mut event_rx) = mpsc::channel(100);
+            let router = Arc::new(
+                OnionRouter::new(cover_config(0.5, max_delay), table, event_tx)
+                    .with_local_id(id.clone()),
+            );
+            let (cmd_tx, cmd_rx) = mpsc::channel(100);
+            tasks.push(tokio::spawn(router.run(cmd_rx)));
+            commands.insert(id, cmd_tx);
+            let forward_tx = forward_tx.clone();
+            tasks.push(tokio::spawn(async move {
+                while let Some(event) = event_rx.recv().await {
+                    let _ = forward_tx.send(event).await;
+                }
+            }));
+        }
+        let sender = commands[&PeerId::from_bytes(vec![9; 32])].clone();
+
+        let started = Instant::now();
+        sender
+            .send(OnionCommand::Send {
+                destination: PeerId::from_bytes(vec![42; 32]),
+                payload: b"on schedule".to_vec(),
+                response_tx: None,
+            })
+            .await
+            .unwrap();
+
+        // Carry packets between the in-process nodes until the exit delivers
+        let envelope = loop {
+            let event = tokio::time::timeout(Duration::from_secs(5), forward_rx.recv())
+                .await
+                .expect("Message should be delivered")
+                .unwrap();
+            match event {
+                OnionEvent::PacketForward { next_peer, blob } => {
+                    commands[&next_peer]
+                        .send(OnionCommand::RelayPacket { encrypted_blob: blob })
+                        .await
+                        .unwrap();
+                }
+                OnionEvent::DeliverLocal { envelope } => break envelope,
+                OnionEvent::RelayError { error } => panic!("Relay error: {}", error),
+                OnionEvent::CircuitBuilt { .. } | OnionEvent::CellForward { .. } => {}
+            }
+        };
+        let elapsed = started.elapsed();
+
+        assert_eq!(envelope.payload, b"on schedule");
+        // The sender and two forwarding relays each hold the packet for at most max_delay
+        assert!(elapsed <= max_delay * 3 + Duration::from_millis(150), "took {:?}", elapsed);
+        for task in tasks {
+            task.abort();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cover_traffic_kill_switch() {
+        let route_table = Arc::new(RouteTable::new());
+        for i in 1..=3 {
+            route_table
+                .handle_command(RouteTableCommand::InsertPeer(create_test_relay(
+                    i,
+                    1000 + i as u32,
+                )))
+                .await
+                .unwrap();
+        }
+        let (event_tx, mut event_rx) = mpsc::channel(100);
+        // Rare slots and a long bound: a real packet would sit in the queue
+        let router = Arc::new(OnionRouter::new(
+            cover_config(0.01, Duration::from_secs(60)),
+            route_table,
+            event_tx,
+        ));
+        let (cmd_tx, cmd_rx) = mpsc::channel(10);
+        let task = tokio::spawn(router.clone().run(cmd_rx));
+
+        cmd_tx
+            .send(OnionCommand::Send {
+                destination: PeerId::from_bytes(vec![99]),
+                payload: b"urgent".to_vec(),
+                response_tx: None,
+            })
+            .await
+            .unwrap();
+        let event = tokio::time::timeout(Duration::from_millis(200), event_rx.recv()).await;
+        assert!(matches!(event, Ok(Some(OnionEvent::CircuitBuilt { .. }))));
+        assert!(tokio::time::timeout(Duration::from_millis(200), event_rx.recv()).await.is_err());
+
+        // Low-power mode: cover traffic off, the queued packet leaves at once
+        cmd_tx.send(OnionCommand::SetCoverTraffic { enabled: false }).await.unwrap();
+        let event = tokio::time::timeout(Duration::from_millis(200), event_rx.recv()).await;
+        assert!(matches!(event, Ok(Some(OnionEvent::PacketForward { .. }))));
+        assert_eq!(router.cover_stats().real_sent, 1);
+        task.abort();
+    }
 }
@@ -5,14 +5,26 @@
 
     Workflow:
     1. Periodic tick:
-      - call some peers with "peer_exchange" RPC to get new candidates
-      - validate returned peer descriptors
+      - call a few random healthy peers with "peer_exchange" RPC to get new candidates,
+        asking for up to peer_exchange_count descriptors with the wanted capabilities
+      - validate returned peer descriptors: each is signed by the peer it describes
       - ping the ones we don't have currently
       - if under capacity, try bootstrap list
     2. Feed new peers into route_table.rs and notify onion_router.rs for path selection updates
 
+    Peer exchange:
+
+    A descriptor is signed with the described peer's Ed25519 identity key, whose X25519
+    form is its PeerId (as in quic_transport.rs), so a peer passing descriptors on can't
+    forge or alter them. Nodes answer with their own descriptor and the verified ones they
+    have learned, for peers still healthy in their route table. Answers never hold more
+    than was asked for, and each peer may ask at most once per min_exchange_interval, so
+    exchange can't be used to amplify traffic. Responders sending more than asked, or
+    descriptors that fail verification or don't match the request, are recorded as
+    misbehaving; quarantined peers aren't merged, and merging stops at max_peers.
+
     Inputs:
-      - Config: desired relay pool size N,
+      - Config: desired relay pool size N, wanted capabilities, exchange limits
       - Events:
         -  PeerDiscovered(peer_info)
         -  PeerLivenessResult(peer_id, is_alive)
@@ -41,16 +53,38 @@
                       RouteTable
 */
 
+use async_trait::async_trait;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use rand::seq::SliceRandom;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
 use tokio::time::interval;
 
-use super::route_table::{Capability, PeerInfo, RouteTable, RouteTableCommand};
+use super::metrics;
+use super::quic_transport::peer_id_for;
+use super::reputation::Behaviour;
+use super::route_table::{Capability, PeerInfo, Reachability, RouteTable, RouteTableCommand};
+use super::router_handle::RouterHandle;
+use super::rpc_protocol::{RpcError, RpcRequest};
 use super::session_manager::PeerId;
 
+/// RPC method answering peer exchange requests
+pub const PEER_EXCHANGE_METHOD: &str = "peer_exchange";
+
+/// Prefix of the bytes a descriptor signature covers
+const DESCRIPTOR_DOMAIN: &[u8] = b"spacepanda-peer-descriptor-v1";
+
+/// How far in the future a descriptor may be signed, for clock skew
+const MAX_CLOCK_SKEW: Duration = Duration::from_secs(5 * 60);
+
+/// Failures and age past which a peer's descriptor is no longer passed on
+const SHARE_MAX_FAILURES: u32 = 3;
+const SHARE_MAX_AGE: Duration = Duration::from_secs(3600);
+
 /// Configuration for overlay discovery
 #[derive(Debug, Clone)]
 pub struct DiscoveryConfig {
@@ -60,8 +94,18 @@ pub struct DiscoveryConfig {
     pub discovery_interval: Duration,
     /// Bootstrap peer addresses
     pub bootstrap_peers: Vec<String>,
-    /// Maximum number of peers to request in peer exchange
+    /// Maximum number of peers to request in peer exchange, and to answer with
     pub peer_exchange_count: usize,
+    /// Peers asked per peer exchange round
+    pub peer_exchange_fanout: usize,
+    /// Capabilities every exchanged peer must have; empty for any peer
+    pub wanted_capabilities: Vec<Capability>,
+    /// Shortest time between two exchange requests we answer from the same peer
+    pub min_exchange_interval: Duration,
+    /// Age past which a descriptor is rejected
+    pub descriptor_max_age: Duration,
+    /// Route table size past which exchanged peers are no longer merged
+    pub max_peers: usize,
 }
 
 impl Default for DiscoveryConfig {
@@ -71,6 +115,11 @@ impl Default for DiscoveryConfig {
             discovery_interval: Duration::from_secs(60),
             bootstrap_peers: Vec::new(),
             peer_exchange_count: 10,
+            peer_exchange_fanout: 3,
+            wanted_capabilities: Vec::new(),
+            min_exchange_interval: Duration::from_secs(10),
+            descriptor_max_age: Duration::from_secs(24 * 3600),
+            max_peers: 1000,
         }
     }
 }
@@ -79,6 +128,9 @@ impl Default for DiscoveryConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PeerExchangeRequest {
     pub count: usize,
+    /// Capabilities every returned peer must have, as in PeerDescriptor; empty for any
+    #[serde(default)]
+    pub capabilities: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -93,6 +145,32 @@ pub struct PeerDescriptor {
     pub addresses: Vec<String>,
     pub capabilities: Vec<String>,
     pub asn: Option<u32>,
+    /// External address the peer's own peers see it connecting from
+    #[serde(default)]
+    pub observed_addr: Option<String>,
+    /// WebSocket listener address, advertised alongside the `ws` capability
+    #[serde(default)]
+    pub ws_addr: Option<String>,
+    /// Ed25519 identity key of the peer, which signed the descriptor
+    #[serde(default)]
+    pub public_key: Option<Vec<u8>>,
+    /// Unix time in seconds the descriptor was signed
+    #[serde(default)]
+    pub signed_at: u64,
+    #[serde(default)]
+    pub signature: Option<Vec<u8>>,
+}
+
+/// Name of a capability in descriptors and exchange requests
+fn capability_name(capability: &Capability) -> &'static str {
+    match capability {
+        Capability::Relay => "relay",
+        Capability::DhtNode => "dht",
+        Capability::Storage => "storage",
+        Capability::LongLived => "longlived",
+        Capability::Quic => "quic",
+        Capability::WebSocket => "ws",
+    }
 }
 
 impl PeerDescriptor {
@@ -108,35 +186,129 @@ impl PeerDescriptor {
                 "dht" => peer_info.capabilities.push(Capability::DhtNode),
                 "storage" => peer_info.capabilities.push(Capability::Storage),
                 "longlived" => peer_info.capabilities.push(Capability::LongLived),
+                "quic" => peer_info.capabilities.push(Capability::Quic),
+                "ws" => peer_info.capabilities.push(Capability::WebSocket),
                 _ => {}
             }
         }
 
         peer_info.asn = self.asn;
+        peer_info.ws_addr = self.ws_addr.clone();
+
+        // A peer seen from an address it advertises isn't behind a NAT
+        if let Some(observed) = &self.observed_addr {
+            if self.addresses.contains(observed) {
+                peer_info.reachability = Reachability::Public;
+            }
+            peer_info.observed_addr = Some(observed.clone());
+        }
         peer_info
     }
 
     /// Create from PeerInfo
     pub fn from_peer_info(info: &PeerInfo) -> Self {
-        let capabilities = info
-            .capabilities
-            .iter()
-            .map(|c| match c {
-                Capability::Relay => "relay",
-                Capability::DhtNode => "dht",
-                Capability::Storage => "storage",
-                Capability::LongLived => "longlived",
-            })
-            .map(String::from)
-            .collect();
+        let capabilities =
+            info.capabilities.iter().map(capability_name).map(String::from).collect();
 
         PeerDescriptor {
             peer_id_bytes: info.peer_id.0.clone(),
             addresses: info.addresses.clone(),
             capabilities,
             asn: info.asn,
+            observed_addr: info.observed_addr.clone(),
+            ws_addr: info.ws_addr.clone(),
+            public_key: None,
+            signed_at: 0,
+            signature: None,
         }
     }
+
+    /// Sign as the peer holding `key`, which must be the one the descriptor is for
+    pub fn sign(mut self, key: &SigningKey) -> Self {
+        self.public_key = Some(key.verifying_key().to_bytes().to_vec());
+        self.signed_at = unix_secs();
+        self.signature = Some(key.sign(&self.signed_bytes()).to_bytes().to_vec());
+        self
+    }
+
+    /// Check the descriptor was signed by the peer it describes, at most `max_age` ago
+    pub fn verify(&self, max_age: Duration) -> Result<(), String> {
+        let key: [u8; 32] = self
+            .public_key
+            .as_deref()
+            .and_then(|key| key.try_into().ok())
+            .ok_or("descriptor has no public key")?;
+        let key = VerifyingKey::from_bytes(&key).map_err(|e| format!("bad public key: {}", e))?;
+        if peer_id_for(&key).0 != self.peer_id_bytes {
+            return Err("public key is not the peer's".to_string());
+        }
+
+        let now = unix_secs();
+        if self.signed_at > now + MAX_CLOCK_SKEW.as_secs() {
+            return Err("descriptor signed in the future".to_string());
+        }
+        if now.saturating_sub(self.signed_at) > max_age.as_secs() {
+            return Err("descriptor expired".to_string());
+        }
+
+        let signature = self.signature.as_deref().ok_or("descriptor is unsigned")?;
+        let signature =
+            Signature::from_slice(signature).map_err(|e| format!("bad signature: {}", e))?;
+        key.verify_strict(&self.signed_bytes(), &signature)
+            .map_err(|_| "signature doesn't match".to_string())
+    }
+
+    /// Whether the peer has every capability in `wanted`
+    fn has_capabilities(&self, wanted: &[String]) -> bool {
+        wanted.iter().all(|capability| self.capabilities.contains(capability))
+    }
+
+    /// Everything the signature covers: the whole descriptor but the signature
+    fn signed_bytes(&self) -> Vec<u8> {
+        let fields = (
+            &self.peer_id_bytes,
+            &self.addresses,
+            &self.capabilities,
+            self.asn,
+            &self.observed_addr,
+            &self.ws_addr,
+            &self.public_key,
+            self.signed_at,
+        );
+        let mut bytes = DESCRIPTOR_DOMAIN.to_vec();
+        bytes.extend(serde_json::to_vec(&fields).unwrap_or_default());
+        bytes
+    }
+}
+
+fn unix_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+/// Carries peer exchange requests to other peers
+#[async_trait]
+pub trait ExchangeTransport: Send + Sync {
+    /// Ask `peer` for descriptors and wait for its answer
+    async fn exchange(
+        &self,
+        peer: PeerId,
+        request: PeerExchangeRequest,
+    ) -> Result<PeerExchangeResponse, RpcError>;
+}
+
+#[async_trait]
+impl ExchangeTransport for RouterHandle {
+    async fn exchange(
+        &self,
+        peer: PeerId,
+        request: PeerExchangeRequest,
+    ) -> Result<PeerExchangeResponse, RpcError> {
+        let params =
+            serde_json::to_value(&request).map_err(|e| RpcError::internal_error(&e.to_string()))?;
+        let result = self.rpc_call(peer, PEER_EXCHANGE_METHOD.to_string(), params).await?;
+        serde_json::from_value(result)
+            .map_err(|e| RpcError::internal_error(&format!("bad peer exchange response: {}", e)))
+    }
 }
 
 /// Commands for OverlayDiscovery
@@ -169,6 +341,14 @@ pub struct OverlayDiscovery {
     route_table: Arc<RouteTable>,
     discovered_peers: Arc<Mutex<HashSet<PeerId>>>,
     event_tx: mpsc::Sender<DiscoveryEvent>,
+    /// Our identity key and what we advertise, to answer with our own descriptor
+    identity: Option<(SigningKey, PeerInfo)>,
+    /// Carries our exchange requests; without one, discovery only uses the bootstrap list
+    transport: Option<Arc<dyn ExchangeTransport>>,
+    /// Verified descriptors learned over exchange, passed on to whoever asks
+    descriptors: Mutex<HashMap<PeerId, PeerDescriptor>>,
+    /// When we last answered each peer's exchange request
+    last_served: Mutex<HashMap<PeerId, Instant>>,
 }
 
 impl OverlayDiscovery {
@@ -183,7 +363,114 @@ impl OverlayDiscovery {
             route_table,
             discovered_peers: Arc::new(Mutex::new(HashSet::new())),
             event_tx,
+            identity: None,
+            transport: None,
+            descriptors: Mutex::new(HashMap::new()),
+            last_served: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Advertise `local` in exchange answers, signed with the identity key of this seed
+    ///
+    /// The PeerId is taken from the key, whatever `local` says.
+    pub fn with_identity(mut self, seed: &[u8; 32], mut local: PeerInfo) -> Self {
+        let key = SigningKey::from_bytes(seed);
+        local.peer_id = peer_id_for(&key.verifying_key());
+        self.identity = Some((key, local));
+        self
+    }
+
+    /// Exchange peers over `transport`
+    pub fn with_transport(mut self, transport: Arc<dyn ExchangeTransport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Answer `peer_exchange` requests
+    ///
+    /// Register the returned sender as the handler for PEER_EXCHANGE_METHOD. The task ends
+    /// when the handler is unregistered and every sender dropped.
+    pub fn serve_peer_exchange(self: Arc<Self>) -> (mpsc::Sender<RpcRequest>, JoinHandle<()>) {
+        let (handler_tx, mut handler_rx) = mpsc::channel::<RpcRequest>(100);
+        let task = tokio::spawn(async move {
+            while let Some(request) = handler_rx.recv().await {
+                let result = match serde_json::from_value(request.params) {
+                    Ok(params) => {
+                        self.answer_exchange(&request.peer_id, params).await.and_then(|response| {
+                            serde_json::to_value(response)
+                                .map_err(|e| RpcError::internal_error(&e.to_string()))
+                        })
+                    }
+                    Err(e) => Err(RpcError::invalid_params(&e.to_string())),
+                };
+                let _ = request.response_tx.send(result);
+            }
+        });
+        (handler_tx, task)
+    }
+
+    /// Descriptors for `from`'s exchange request: ours first, then healthy peers we learned
+    ///
+    /// At most what was asked for and at most peer_exchange_count, whatever was asked.
+    pub async fn answer_exchange(
+        &self,
+        from: &PeerId,
+        request: PeerExchangeRequest,
+    ) -> Result<PeerExchangeResponse, RpcError> {
+        {
+            let mut last_served = self.last_served.lock().await;
+            let now = Instant::now();
+            let interval = self.config.min_exchange_interval;
+            last_served.retain(|_, served| now.duration_since(*served) < interval);
+            if last_served.contains_key(from) {
+                drop(last_served);
+                self.route_table.record_behaviour(from.clone(), Behaviour::RateLimited).await;
+                return Err(RpcError::rate_limited());
+            }
+            last_served.insert(from.clone(), now);
         }
+
+        let limit = request.count.min(self.config.peer_exchange_count);
+        let mut peers = Vec::new();
+        if let Some((key, local)) = &self.identity {
+            let own = PeerDescriptor::from_peer_info(local).sign(key);
+            if own.has_capabilities(&request.capabilities) {
+                peers.push(own);
+            }
+        }
+
+        let mut learned: Vec<PeerDescriptor> = self
+            .descriptors
+            .lock()
+            .await
+            .iter()
+            .filter(|(peer_id, descriptor)| {
+                *peer_id != from && descriptor.has_capabilities(&request.capabilities)
+            })
+            .map(|(_, descriptor)| descriptor.clone())
+            .collect();
+        learned.shuffle(&mut rand::rng());
+        for descriptor in learned {
+            if peers.len() >= limit {
+                break;
+            }
+            let peer_id = PeerId::from_bytes(descriptor.peer_id_bytes.clone());
+            if self.is_shareable(&peer_id).await {
+                peers.push(descriptor);
+            }
+        }
+        peers.truncate(limit);
+        Ok(PeerExchangeResponse { peers })
+    }
+
+    /// Whether a learned peer is still healthy enough to pass on
+    async fn is_shareable(&self, peer_id: &PeerId) -> bool {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let command = RouteTableCommand::GetPeer { peer_id: peer_id.clone(), response_tx: tx };
+        if self.route_table.handle_command(command).await.is_err() {
+            return false;
+        }
+        matches!(rx.await, Ok(Some(peer)) if peer.is_healthy(SHARE_MAX_FAILURES, SHARE_MAX_AGE))
     }
 
     /// Start the discovery loop
@@ -243,40 +530,109 @@ impl OverlayDiscovery {
         Ok(())
     }
 
-    /// Perform peer exchange with known peers
+    /// Perform peer exchange with a few random healthy peers
     async fn peer_exchange(&self) -> Result<(), String> {
+        let Some(transport) = &self.transport else {
+            return Ok(());
+        };
+
         // Get current peers from route table
         let (tx, rx) = tokio::sync::oneshot::channel();
         self.route_table
             .handle_command(RouteTableCommand::GetAllPeers { response_tx: tx })
             .await?;
 
-        let all_peers = rx.await.map_err(|e| format!("Failed to get peers: {}", e))?;
+        let mut candidates = rx.await.map_err(|e| format!("Failed to get peers: {}", e))?;
+        candidates.retain(|p| p.is_healthy(SHARE_MAX_FAILURES, SHARE_MAX_AGE));
+        candidates.shuffle(&mut rand::rng());
+        candidates.truncate(self.config.peer_exchange_fanout);
+
+        let request = PeerExchangeRequest {
+            count: self.config.peer_exchange_count,
+            capabilities: self
+                .config
+                .wanted_capabilities
+                .iter()
+                .map(|c| capability_name(c).to_string())
+                .collect(),
+        };
+        for peer in candidates {
+            match transport.exchange(peer.peer_id.clone(), request.clone()).await {
+                Ok(response) => self.merge_exchange(&peer.peer_id, &request, response).await,
+                Err(e) => eprintln!("Peer exchange with {:?} failed: {}", peer.peer_id, e.message),
+            }
+        }
 
-        // Pick a few random peers to ask for more peers
-        let candidates: Vec<&PeerInfo> = all_peers
-            .iter()
-            .filter(|p| p.capabilities.contains(&Capability::Relay))
-            .take(3)
-            .collect();
+        Ok(())
+    }
 
-        for peer in candidates {
-            // In a real implementation, we would make RPC calls here
-            // For now, we simulate by just logging
-            // rpc_call(peer.peer_id, "peer_exchange", PeerExchangeRequest { count: self.config.peer_exchange_count })
+    /// Merge the verified descriptors of `from`'s answer into the route table
+    ///
+    /// An answer with more than was asked for is dropped whole. Anything wrong in it counts
+    /// against `from`, once per answer.
+    async fn merge_exchange(
+        &self,
+        from: &PeerId,
+        request: &PeerExchangeRequest,
+        response: PeerExchangeResponse,
+    ) {
+        if response.peers.len() > request.count {
+            metrics::peer_exchange_descriptor("rejected");
+            self.route_table.record_behaviour(from.clone(), Behaviour::InvalidMessage).await;
+            return;
+        }
 
-            // Simulate discovering the peer itself if not already known
-            let mut discovered = self.discovered_peers.lock().await;
-            if !discovered.contains(&peer.peer_id) {
-                discovered.insert(peer.peer_id.clone());
-                let _ = self
-                    .event_tx
-                    .send(DiscoveryEvent::NewPeerFound { peer_id: peer.peer_id.clone() })
-                    .await;
+        let local_id = self.identity.as_ref().map(|(_, local)| local.peer_id.clone());
+        let mut misbehaved = false;
+        for descriptor in response.peers {
+            let peer_id = PeerId::from_bytes(descriptor.peer_id_bytes.clone());
+            if local_id.as_ref() == Some(&peer_id) {
+                continue;
+            }
+            if let Err(e) = descriptor.verify(self.config.descriptor_max_age) {
+                eprintln!("Rejected descriptor of {:?} from {:?}: {}", peer_id, from, e);
+                metrics::peer_exchange_descriptor("rejected");
+                misbehaved = true;
+                continue;
+            }
+            if !descriptor.has_capabilities(&request.capabilities) {
+                metrics::peer_exchange_descriptor("rejected");
+                misbehaved = true;
+                continue;
+            }
+            if self.route_table.is_quarantined(&peer_id).await {
+                continue;
             }
+
+            let (tx, rx) = tokio::sync::oneshot::channel();
+            let command = RouteTableCommand::GetPeer { peer_id: peer_id.clone(), response_tx: tx };
+            let known = self.route_table.handle_command(command).await.is_ok()
+                && matches!(rx.await, Ok(Some(_)));
+            if !known && self.route_table.peer_count().await >= self.config.max_peers {
+                continue;
+            }
+
+            {
+                let mut descriptors = self.descriptors.lock().await;
+                let newer = descriptors
+                    .get(&peer_id)
+                    .is_none_or(|stored| stored.signed_at < descriptor.signed_at);
+                if newer {
+                    descriptors.insert(peer_id.clone(), descriptor.clone());
+                }
+            }
+            if known {
+                metrics::peer_exchange_descriptor("known");
+                continue;
+            }
+
+            metrics::peer_exchange_descriptor("merged");
+            self.handle_peer_discovered(descriptor.to_peer_info()).await;
         }
 
-        Ok(())
+        if misbehaved {
+            self.route_table.record_behaviour(from.clone(), Behaviour::InvalidMessage).await;
+        }
     }
 
     /// Try connecting to bootstrap peers
@@ -430,6 +786,51 @@ mod tests {
         assert!(converted.capabilities.contains(&Capability::DhtNode));
     }
 
+    #[tokio::test]
+    async fn test_peer_descriptor_observed_addr() {
+        let mut peer_info =
+            PeerInfo::new(PeerId::from_bytes(vec![5]), vec!["198.51.100.7:7000".to_string()]);
+        peer_info.observed_addr = Some("198.51.100.7:7000".to_string());
+        let public = PeerDescriptor::from_peer_info(&peer_info).to_peer_info();
+        assert_eq!(public.reachability, Reachability::Public);
+
+        // Seen from an address it doesn't advertise: behind a NAT
+        peer_info.addresses = vec!["10.0.0.2:7000".to_string()];
+        let descriptor = PeerDescriptor::from_peer_info(&peer_info);
+        let natted: PeerDescriptor =
+            serde_json::from_value(serde_json::to_value(&descriptor).unwrap()).unwrap();
+        let natted = natted.to_peer_info();
+        assert_eq!(natted.observed_addr.as_deref(), Some("198.51.100.7:7000"));
+        assert_eq!(natted.reachability, Reachability::Unknown);
+    }
+
+    #[test]
+    fn test_peer_descriptor_signature() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let peer_id = peer_id_for(&key.verifying_key());
+        let mut peer_info = PeerInfo::new(peer_id, vec!["198.51.100.7:7000".to_string()]);
+        peer_info.capabilities.push(Capability::Relay);
+        let max_age = Duration::from_secs(60);
+
+        let signed = PeerDescriptor::from_peer_info(&peer_info).sign(&key);
+        assert!(signed.verify(max_age).is_ok());
+        assert!(PeerDescriptor::from_peer_info(&peer_info).verify(max_age).is_err());
+
+        // Any change to what was signed breaks the signature
+        let mut tampered = signed.clone();
+        tampered.capabilities.push("storage".to_string());
+        assert!(tampered.verify(max_age).is_err());
+
+        // A descriptor signed by someone other than the peer it describes
+        let forged =
+            PeerDescriptor::from_peer_info(&peer_info).sign(&SigningKey::from_bytes(&[8; 32]));
+        assert_eq!(forged.verify(max_age), Err("public key is not the peer's".to_string()));
+
+        let mut stale = signed;
+        stale.signed_at -= 120;
+        assert!(stale.verify(max_age).is_err());
+    }
+
     #[tokio::test]
     async fn test_overlay_discovery_creation() {
         let config = DiscoveryConfig::default();
@@ -0,0 +1,493 @@
+/*
+    PathScorer - latency-aware selection between delivery paths
+
+    A peer can often be reached over more than one path: a direct session,
+    an onion relay circuit, or an offline mailbox. PathScorer ranks those
+    candidates per peer so the send path tries the best one first and fails
+    over to the next one on error.
+
+    Ranking inputs:
+    - Measured RTT (keepalive probes and acknowledged deliveries, smoothed with an EWMA)
+    - Historical success rate of deliveries over the path
+    - Path type preference (direct < relay < mailbox)
+
+    Paths that have not been used for `probe_interval` are reported by
+    `paths_due_for_probe()` so the keepalive driver can re-measure them, and
+    consecutive-failure penalties expire after the same interval so a path
+    that recovered gets another chance.
+
+    Workflow:
+    1. add_path(peer, kind) when a route to the peer becomes known
+    2. record_rtt(peer, kind, rtt) from keepalives
+    3. deliver_with_failover(peer, send) in the send path
+
+    The `send` given to deliver_with_failover must only resolve once the peer has the payload
+    (e.g. its ack is back): its duration is recorded as the path's RTT, and an error is what
+    moves delivery on to the next path.
+*/
+
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use super::session_manager::PeerId;
+
+/// Kind of path used to reach a peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PathKind {
+    /// Direct session with the peer
+    Direct,
+    /// Onion-routed circuit through relays
+    Relay,
+    /// Store-and-forward mailbox
+    Mailbox,
+}
+
+impl PathKind {
+    /// Static penalty (in milliseconds) expressing path type preference
+    fn preference_penalty_ms(&self) -> f64 {
+        match self {
+            PathKind::Direct => 0.0,
+            PathKind::Relay => 100.0,
+            PathKind::Mailbox => 1000.0,
+        }
+    }
+}
+
+impl fmt::Display for PathKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathKind::Direct => write!(f, "direct"),
+            PathKind::Relay => write!(f, "relay"),
+            PathKind::Mailbox => write!(f, "mailbox"),
+        }
+    }
+}
+
+/// Configuration for path scoring
+#[derive(Debug, Clone)]
+pub struct PathScorerConfig {
+    /// Weight of a new RTT sample in the moving average (0.0 - 1.0)
+    pub rtt_smoothing: f64,
+    /// RTT assumed for paths that have never been measured
+    pub unknown_rtt: Duration,
+    /// Penalty (in milliseconds) per consecutive failure
+    pub failure_penalty_ms: f64,
+    /// Unused paths are re-probed (and forgiven) after this long
+    pub probe_interval: Duration,
+}
+
+impl Default for PathScorerConfig {
+    fn default() -> Self {
+        Self {
+            rtt_smoothing: 0.3,
+            unknown_rtt: Duration::from_millis(500),
+            failure_penalty_ms: 10_000.0,
+            probe_interval: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Measurements for a single path to a peer
+#[derive(Debug, Clone)]
+pub struct PathStats {
+    pub kind: PathKind,
+    pub rtt: Option<Duration>,
+    pub successes: u32,
+    pub failures: u32,
+    pub consecutive_failures: u32,
+    pub last_attempt: Option<Instant>,
+}
+
+impl PathStats {
+    fn new(kind: PathKind) -> Self {
+        Self {
+            kind,
+            rtt: None,
+            successes: 0,
+            failures: 0,
+            consecutive_failures: 0,
+            last_attempt: None,
+        }
+    }
+
+    /// Fraction of deliveries that succeeded
+    ///
+    /// Smoothed with one virtual success so unused paths start at 1.0 and a
+    /// single failure does not rule a path out forever.
+    pub fn success_rate(&self) -> f64 {
+        let total = self.successes as f64 + self.failures as f64;
+        (self.successes as f64 + 1.0) / (total + 1.0)
+    }
+
+    fn is_stale(&self, now: Instant, probe_interval: Duration) -> bool {
+        match self.last_attempt {
+            Some(at) => now.saturating_duration_since(at) >= probe_interval,
+            None => true,
+        }
+    }
+
+    /// Score for ranking (lower is better)
+    fn score(&self, config: &PathScorerConfig, now: Instant) -> f64 {
+        let rtt_ms = self.rtt.unwrap_or(config.unknown_rtt).as_secs_f64() * 1000.0;
+
+        // Unreliable paths look proportionally slower
+        let mut score = rtt_ms / self.success_rate();
+        score += self.kind.preference_penalty_ms();
+
+        // Recent failures push the path down until it is due for a re-probe
+        if !self.is_stale(now, config.probe_interval) {
+            score += self.consecutive_failures as f64 * config.failure_penalty_ms;
+        }
+
+        score
+    }
+}
+
+/// Outcome of a delivery through `deliver_with_failover`
+#[derive(Debug, Clone)]
+pub struct DeliveryDetail {
+    /// Path the payload was delivered over
+    pub path: PathKind,
+    /// Time spent on the successful attempt
+    pub elapsed: Duration,
+    /// Paths tried and failed before the successful one
+    pub failed_paths: Vec<(PathKind, String)>,
+}
+
+/// Ranks delivery paths per peer
+pub struct PathScorer {
+    config: PathScorerConfig,
+    paths: Mutex<HashMap<PeerId, Vec<PathStats>>>,
+}
+
+impl PathScorer {
+    /// Create a new PathScorer
+    pub fn new(config: PathScorerConfig) -> Self {
+        Self { config, paths: Mutex::new(HashMap::new()) }
+    }
+
+    /// Register a path to a peer (no-op if already known)
+    pub fn add_path(&self, peer_id: &PeerId, kind: PathKind) {
+        let mut paths = self.paths.lock().unwrap();
+        let entry = paths.entry(peer_id.clone()).or_default();
+        if !entry.iter().any(|p| p.kind == kind) {
+            entry.push(PathStats::new(kind));
+        }
+    }
+
+    /// Forget a path to a peer
+    pub fn remove_path(&self, peer_id: &PeerId, kind: PathKind) {
+        let mut paths = self.paths.lock().unwrap();
+        if let Some(entry) = paths.get_mut(peer_id) {
+            entry.retain(|p| p.kind != kind);
+        }
+    }
+
+    /// Record an RTT sample (e.g. from a keepalive) for a path
+    pub fn record_rtt(&self, peer_id: &PeerId, kind: PathKind, rtt: Duration) {
+        self.with_path(peer_id, kind, |stats, alpha| {
+            stats.rtt = Some(match stats.rtt {
+                Some(prev) => prev.mul_f64(1.0 - alpha) + rtt.mul_f64(alpha),
+                None => rtt,
+            });
+            stats.consecutive_failures = 0;
+            stats.last_attempt = Some(Instant::now());
+        });
+    }
+
+    /// Record a successful delivery over a path
+    pub fn record_success(&self, peer_id: &PeerId, kind: PathKind, elapsed: Duration) {
+        self.record_rtt(peer_id, kind, elapsed);
+        self.with_path(peer_id, kind, |stats, _| {
+            stats.successes = stats.successes.saturating_add(1);
+        });
+    }
+
+    /// Record a failed delivery over a path
+    pub fn record_failure(&self, peer_id: &PeerId, kind: PathKind) {
+        self.with_path(peer_id, kind, |stats, _| {
+            stats.failures = stats.failures.saturating_add(1);
+            stats.consecutive_failures = stats.consecutive_failures.saturating_add(1);
+            stats.last_attempt = Some(Instant::now());
+        });
+    }
+
+    /// Paths to a peer, best first
+    pub fn rank(&self, peer_id: &PeerId) -> Vec<PathKind> {
+        let now = Instant::now();
+        let paths = self.paths.lock().unwrap();
+        let mut scored: Vec<(f64, PathKind)> = paths
+            .get(peer_id)
+            .map(|entry| entry.iter().map(|p| (p.score(&self.config, now), p.kind)).collect())
+            .unwrap_or_default();
+        scored.sort_by(|a, b| a.0.total_cmp(&b.0));
+        scored.into_iter().map(|(_, kind)| kind).collect()
+    }
+
+    /// Paths to a peer that have not been measured within the probe interval
+    pub fn paths_due_for_probe(&self, peer_id: &PeerId) -> Vec<PathKind> {
+        let now = Instant::now();
+        let paths = self.paths.lock().unwrap();
+        paths
+            .get(peer_id)
+            .map(|entry| {
+                entry
+                    .iter()
+                    .filter(|p| p.is_stale(now, self.config.probe_interval))
+                    .map(|p| p.kind)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Peers with at least one known path
+    pub fn peers(&self) -> Vec<PeerId> {
+        let paths = self.paths.lock().unwrap();
+        paths
+            .iter()
+            .filter(|(_, entry)| !entry.is_empty())
+            .map(|(peer, _)| peer.clone())
+            .collect()
+    }
+
+    /// Current measurements for a peer's paths
+    pub fn stats(&self, peer_id: &PeerId) -> Vec<PathStats> {
+        let paths = self.paths.lock().unwrap();
+        paths.get(peer_id).cloned().unwrap_or_default()
+    }
+
+    /// Try each path in rank order until one succeeds
+    ///
+    /// `send` must resolve once the payload is delivered, not when it is
+    /// queued, since the time it takes is recorded as the path's RTT.
+    ///
+    /// Every attempt updates the path's statistics, so a failed path drops
+    /// in the ranking for subsequent deliveries.
+    pub async fn deliver_with_failover<F, Fut>(
+        &self,
+        peer_id: &PeerId,
+        mut send: F,
+    ) -> Result<DeliveryDetail, Vec<(PathKind, String)>>
+    where
+        F: FnMut(PathKind) -> Fut,
+        Fut: Future<Output = Result<(), String>>,
+    {
+        let mut failed_paths = Vec::new();
+
+        for kind in self.rank(peer_id) {
+            let started = Instant::now();
+            match send(kind).await {
+                Ok(()) => {
+                    let elapsed = started.elapsed();
+                    self.record_success(peer_id, kind, elapsed);
+                    return Ok(DeliveryDetail { path: kind, elapsed, failed_paths });
+                }
+                Err(e) => {
+                    self.record_failure(peer_id, kind);
+                    failed_paths.push((kind, e));
+                }
+            }
+        }
+
+        Err(failed_paths)
+    }
+
+    fn with_path(&self, peer_id: &PeerId, kind: PathKind, f: impl FnOnce(&mut PathStats, f64)) {
+        let mut paths = self.paths.lock().unwrap();
+        if let Some(stats) = paths
+            .get_mut(peer_id)
+            .and_then(|entry| entry.iter_mut().find(|p| p.kind == kind))
+        {
+            f(stats, self.config.rtt_smoothing);
+        }
+    }
+}
+
+impl Default for PathScorer {
+    fn default() -> Self {
+        Self::new(PathScorerConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::sync::Arc;
+
+    fn peer(id: u8) -> PeerId {
+        PeerId::from_bytes(vec![id])
+    }
+
+    /// Simulated links with fixed latency that can be cut
+    struct SimulatedNetwork {
+        latency: HashMap<PathKind, Duration>,
+        down: Mutex<HashSet<PathKind>>,
+        used: Mutex<Vec<PathKind>>,
+    }
+
+    impl SimulatedNetwork {
+        fn new(links: &[(PathKind, u64)]) -> Arc<Self> {
+            Arc::new(Self {
+                latency: links.iter().map(|(k, ms)| (*k, Duration::from_millis(*ms))).collect(),
+                down: Mutex::new(HashSet::new()),
+                used: Mutex::new(Vec::new()),
+            })
+        }
+
+        fn kill(&self, kind: PathKind) {
+            self.down.lock().unwrap().insert(kind);
+        }
+
+        async fn send(&self, kind: PathKind) -> Result<(), String> {
+            self.used.lock().unwrap().push(kind);
+            if self.down.lock().unwrap().contains(&kind) {
+                return Err(format!("{} path unreachable", kind));
+            }
+            tokio::time::sleep(self.latency[&kind]).await;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_rank_prefers_low_rtt() {
+        let scorer = PathScorer::default();
+        let p = peer(1);
+        scorer.add_path(&p, PathKind::Relay);
+        scorer.add_path(&p, PathKind::Direct);
+
+        scorer.record_rtt(&p, PathKind::Relay, Duration::from_millis(2000));
+        scorer.record_rtt(&p, PathKind::Direct, Duration::from_millis(30));
+
+        assert_eq!(scorer.rank(&p), vec![PathKind::Direct, PathKind::Relay]);
+    }
+
+    #[test]
+    fn test_rank_prefers_direct_when_unmeasured() {
+        let scorer = PathScorer::default();
+        let p = peer(1);
+        scorer.add_path(&p, PathKind::Mailbox);
+        scorer.add_path(&p, PathKind::Relay);
+        scorer.add_path(&p, PathKind::Direct);
+
+        assert_eq!(scorer.rank(&p), vec![PathKind::Direct, PathKind::Relay, PathKind::Mailbox]);
+    }
+
+    #[test]
+    fn test_failures_demote_path() {
+        let scorer = PathScorer::default();
+        let p = peer(1);
+        scorer.add_path(&p, PathKind::Direct);
+        scorer.add_path(&p, PathKind::Relay);
+        scorer.record_rtt(&p, PathKind::Direct, Duration::from_millis(30));
+        scorer.record_rtt(&p, PathKind::Relay, Duration::from_millis(300));
+
+        scorer.record_failure(&p, PathKind::Direct);
+
+        assert_eq!(scorer.rank(&p)[0], PathKind::Relay);
+        let direct = scorer.stats(&p).into_iter().find(|s| s.kind == PathKind::Direct).unwrap();
+        assert_eq!(direct.consecutive_failures, 1);
+        assert_eq!(direct.success_rate(), 0.5);
+    }
+
+    #[test]
+    fn test_failed_path_is_reprobed_after_interval() {
+        let scorer = PathScorer::new(PathScorerConfig {
+            probe_interval: Duration::from_millis(20),
+            ..Default::default()
+        });
+        let p = peer(1);
+        scorer.add_path(&p, PathKind::Direct);
+        scorer.add_path(&p, PathKind::Relay);
+        scorer.record_rtt(&p, PathKind::Relay, Duration::from_millis(300));
+        scorer.record_failure(&p, PathKind::Direct);
+
+        assert!(scorer.paths_due_for_probe(&p).is_empty());
+        assert_eq!(scorer.rank(&p)[0], PathKind::Relay);
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(scorer.paths_due_for_probe(&p).len(), 2);
+
+        // A successful keepalive restores the direct path
+        scorer.record_rtt(&p, PathKind::Direct, Duration::from_millis(30));
+        assert_eq!(scorer.rank(&p)[0], PathKind::Direct);
+    }
+
+    #[test]
+    fn test_rtt_is_smoothed() {
+        let scorer = PathScorer::default();
+        let p = peer(1);
+        scorer.add_path(&p, PathKind::Direct);
+
+        scorer.record_rtt(&p, PathKind::Direct, Duration::from_millis(100));
+        scorer.record_rtt(&p, PathKind::Direct, Duration::from_millis(200));
+
+        let rtt = scorer.stats(&p)[0].rtt.unwrap();
+        assert!(rtt > Duration::from_millis(100) && rtt < Duration::from_millis(200));
+    }
+
+    #[test]
+    fn test_unknown_peer_has_no_paths() {
+        let scorer = PathScorer::default();
+        assert!(scorer.rank(&peer(9)).is_empty());
+
+        scorer.add_path(&peer(9), PathKind::Direct);
+        scorer.remove_path(&peer(9), PathKind::Direct);
+        assert!(scorer.rank(&peer(9)).is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_simulated_failover_from_direct_to_relay() {
+        let scorer = PathScorer::default();
+        let p = peer(1);
+        scorer.add_path(&p, PathKind::Relay);
+        scorer.add_path(&p, PathKind::Direct);
+
+        let net = SimulatedNetwork::new(&[(PathKind::Direct, 30), (PathKind::Relay, 2000)]);
+
+        // Keepalives measured both paths
+        scorer.record_rtt(&p, PathKind::Direct, Duration::from_millis(30));
+        scorer.record_rtt(&p, PathKind::Relay, Duration::from_millis(2000));
+
+        for _ in 0..3 {
+            let detail = scorer.deliver_with_failover(&p, |k| net.send(k)).await.unwrap();
+            assert_eq!(detail.path, PathKind::Direct);
+            assert!(detail.failed_paths.is_empty());
+        }
+
+        // Kill the direct path: the next delivery fails over after one retry
+        net.kill(PathKind::Direct);
+        net.used.lock().unwrap().clear();
+
+        let detail = scorer.deliver_with_failover(&p, |k| net.send(k)).await.unwrap();
+        assert_eq!(detail.path, PathKind::Relay);
+        assert_eq!(detail.failed_paths.len(), 1);
+        assert_eq!(detail.failed_paths[0].0, PathKind::Direct);
+        assert_eq!(*net.used.lock().unwrap(), vec![PathKind::Direct, PathKind::Relay]);
+
+        // Subsequent deliveries go straight to the relay
+        net.used.lock().unwrap().clear();
+        let detail = scorer.deliver_with_failover(&p, |k| net.send(k)).await.unwrap();
+        assert_eq!(detail.path, PathKind::Relay);
+        assert_eq!(*net.used.lock().unwrap(), vec![PathKind::Relay]);
+    }
+
+    #[tokio::test]
+    async fn test_all_paths_failing() {
+        let scorer = PathScorer::default();
+        let p = peer(1);
+        scorer.add_path(&p, PathKind::Direct);
+        scorer.add_path(&p, PathKind::Relay);
+
+        let result = scorer
+            .deliver_with_failover(&p, |k| async move { Err(format!("{} down", k)) })
+            .await;
+
+        let failures = result.unwrap_err();
+        assert_eq!(failures.len(), 2);
+    }
+}
@@ -0,0 +1,461 @@
+/*
+    QuicTransport - QUIC backend for TransportManager
+
+    Multiplexed, encrypted alternative to the TCP transport. Each node presents a self-signed
+    certificate for its Ed25519 identity key; the peer's verifier ignores CAs and names and
+    instead maps the certificate key to a PeerId and checks it against the peer it meant to
+    dial. The PeerId is the key's X25519 form, i.e. the same Noise static key the session
+    layer identifies the node by, so a peer has one PeerId whichever transport reaches it.
+
+    Workflow:
+    1. bind(addr) -> endpoint that both accepts and dials
+    2. dial(addr, expected_peer) -> TLS 1.3 handshake, fails unless the certificate key
+       belongs to expected_peer
+    3. Every message travels on its own unidirectional stream (stream-per-RPC), so one
+       slow RPC never holds up the others
+    4. rebind(addr) moves the endpoint to a new socket; open connections migrate with it
+
+    Inputs:
+      - Dial/Send/Close for connections it owns, Rebind for address changes
+
+    Outputs:
+      - TransportEvent::Authenticated(conn_id, remote_addr, peer_id) once a handshake completes
+      - TransportEvent::Data(conn_id, bytes) per received stream
+      - TransportEvent::Disconnected(conn_id) when the connection goes away
+
+    Transport selection: peers advertising Capability::Quic are dialed over QUIC when we
+    speak it too (negotiate_transport), everyone else over TCP.
+*/
+
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use quinn::crypto::rustls::{QuicClientConfig, QuicServerConfig};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls13_signature, CryptoProvider, WebPkiSupportedAlgorithms};
+use rustls::pki_types::{CertificateDer, PrivatePkcs8KeyDer, ServerName, UnixTime};
+use rustls::server::danger::{ClientCertVerified, ClientCertVerifier};
+use rustls::{CertificateError, DigitallySignedStruct, DistinguishedName, SignatureScheme};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+
+use super::bandwidth::{BandwidthMeter, Direction};
+use super::route_table::{Capability, PeerInfo};
+use super::session_manager::PeerId;
+use super::transport_manager::TransportEvent;
+
+/// ALPN protocol id of the router over QUIC
+const ALPN: &[u8] = b"spacepanda/1";
+
+/// Name put in certificates and dialed; the verifier doesn't look at it
+const SERVER_NAME: &str = "spacepanda";
+
+/// Largest message accepted on one stream (same cap as TCP frames)
+const MAX_STREAM_MESSAGE: usize = 10_000_000;
+
+/// PKCS#8 v1 header of an Ed25519 private key; the 32-byte seed follows
+const ED25519_PKCS8_PREFIX: [u8; 16] = [
+    0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20,
+];
+
+/// SubjectPublicKeyInfo header of an Ed25519 public key; the 32-byte key follows
+const ED25519_SPKI_PREFIX: [u8; 12] =
+    [0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
+
+/// Transport used to reach a peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportKind {
+    Tcp,
+    Quic,
+}
+
+/// QUIC when both sides advertise Capability::Quic, TCP otherwise
+pub fn negotiate_transport(local: &[Capability], peer: &PeerInfo) -> TransportKind {
+    if local.contains(&Capability::Quic) && peer.capabilities.contains(&Capability::Quic) {
+        TransportKind::Quic
+    } else {
+        TransportKind::Tcp
+    }
+}
+
+/// A node's QUIC credentials: a self-signed certificate for its Ed25519 identity key
+pub struct QuicIdentity {
+    cert: CertificateDer<'static>,
+    key: PrivatePkcs8KeyDer<'static>,
+    peer_id: PeerId,
+}
+
+impl QuicIdentity {
+    /// Credentials for the Ed25519 key with this seed
+    pub fn from_seed(seed: &[u8; 32]) -> Result<Self, String> {
+        let pkcs8 = [&ED25519_PKCS8_PREFIX[..], seed].concat();
+        let key_pair = rcgen::KeyPair::try_from(pkcs8.as_slice())
+            .map_err(|e| format!("Invalid identity key: {}", e))?;
+        let cert = rcgen::CertificateParams::new(vec![SERVER_NAME.to_string()])
+            .and_then(|params| params.self_signed(&key_pair))
+            .map_err(|e| format!("Failed to create certificate: {}", e))?;
+
+        Ok(QuicIdentity {
+            cert: cert.der().clone(),
+            key: PrivatePkcs8KeyDer::from(pkcs8),
+            peer_id: peer_id_for(&SigningKey::from_bytes(seed).verifying_key()),
+        })
+    }
+
+    /// The PeerId other nodes verify us as
+    pub fn peer_id(&self) -> &PeerId {
+        &self.peer_id
+    }
+}
+
+/// PeerId of the holder of an Ed25519 key: its X25519 form, the node's Noise static key
+pub(super) fn peer_id_for(key: &VerifyingKey) -> PeerId {
+    PeerId::from_bytes(key.to_montgomery().to_bytes().to_vec())
+}
+
+/// PeerId of the key a certificate is for
+///
+/// The TLS handshake signature then proves the peer holds that key.
+fn cert_peer_id(cert: &CertificateDer<'_>) -> Result<PeerId, rustls::Error> {
+    let bad_encoding = || rustls::Error::InvalidCertificate(CertificateError::BadEncoding);
+    let cert = webpki::EndEntityCert::try_from(cert).map_err(|_| bad_encoding())?;
+    let spki = cert.subject_public_key_info();
+    let key: [u8; 32] = spki
+        .as_ref()
+        .strip_prefix(&ED25519_SPKI_PREFIX[..])
+        .and_then(|key| key.try_into().ok())
+        .ok_or_else(bad_encoding)?;
+    let key = VerifyingKey::from_bytes(&key).map_err(|_| bad_encoding())?;
+    Ok(peer_id_for(&key))
+}
+
+/// Accepts exactly the self-signed certificate of `expected` (any peer if None)
+#[derive(Debug)]
+struct PeerVerifier {
+    expected: Option<PeerId>,
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl PeerVerifier {
+    fn verify(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+    ) -> Result<(), rustls::Error> {
+        if !intermediates.is_empty() {
+            return Err(rustls::Error::InvalidCertificate(CertificateError::BadEncoding));
+        }
+        let peer_id = cert_peer_id(end_entity)?;
+        match &self.expected {
+            Some(expected) if *expected != peer_id => Err(rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl ServerCertVerifier for PeerVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.verify(end_entity, intermediates).map(|_| ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Err(rustls::Error::PeerIncompatible(rustls::PeerIncompatible::Tls13RequiredForQuic))
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        vec![SignatureScheme::ED25519]
+    }
+}
+
+impl ClientCertVerifier for PeerVerifier {
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        _now: UnixTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        self.verify(end_entity, intermediates).map(|_| ClientCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Err(rustls::Error::PeerIncompatible(rustls::PeerIncompatible::Tls13RequiredForQuic))
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        vec![SignatureScheme::ED25519]
+    }
+}
+
+/// QUIC endpoint and the connections it carries
+pub struct QuicTransport {
+    endpoint: quinn::Endpoint,
+    identity: Arc<QuicIdentity>,
+    provider: Arc<CryptoProvider>,
+    connections: Arc<Mutex<HashMap<u64, quinn::Connection>>>,
+    next_conn_id: Arc<AtomicU64>,
+    event_tx: mpsc::Sender<TransportEvent>,
+    bandwidth: Arc<BandwidthMeter>,
+}
+
+impl QuicTransport {
+    /// Bind an endpoint at addr and start accepting connections
+    ///
+    /// conn_ids come from next_conn_id so they never collide with the TCP transport's.
+    pub fn bind(
+        addr: &str,
+        identity: Arc<QuicIdentity>,
+        next_conn_id: Arc<AtomicU64>,
+        event_tx: mpsc::Sender<TransportEvent>,
+        bandwidth: Arc<BandwidthMeter>,
+    ) -> Result<Self, String> {
+        let addr: SocketAddr =
+            addr.parse().map_err(|e| format!("Invalid QUIC address {}: {}", addr, e))?;
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+        let verifier =
+            PeerVerifier { expected: None, algorithms: provider.signature_verification_algorithms };
+        let mut crypto = rustls::ServerConfig::builder_with_provider(provider.clone())
+            .with_protocol_versions(&[&rustls::version::TLS13])
+            .map_err(|e| format!("TLS setup failed: {}", e))?
+            .with_client_cert_verifier(Arc::new(verifier))
+            .with_single_cert(vec![identity.cert.clone()], identity.key.clone_key().into())
+            .map_err(|e| format!("TLS setup failed: {}", e))?;
+        crypto.alpn_protocols = vec![ALPN.to_vec()];
+        let crypto =
+            QuicServerConfig::try_from(crypto).map_err(|e| format!("TLS setup failed: {}", e))?;
+
+        let endpoint =
+            quinn::Endpoint::server(quinn::ServerConfig::with_crypto(Arc::new(crypto)), addr)
+                .map_err(|e| format!("Failed to bind QUIC {}: {}", addr, e))?;
+
+        let transport = QuicTransport {
+            endpoint,
+            identity,
+            provider,
+            connections: Default::default(),
+            next_conn_id,
+            event_tx,
+            bandwidth,
+        };
+        transport.spawn_accept_loop();
+        Ok(transport)
+    }
+
+    /// Address the endpoint is bound to
+    pub fn local_addr(&self) -> Result<SocketAddr, String> {
+        self.endpoint.local_addr().map_err(|e| format!("No QUIC local address: {}", e))
+    }
+
+    fn spawn_accept_loop(&self) {
+        let endpoint = self.endpoint.clone();
+        let connections = self.connections.clone();
+        let next_conn_id = self.next_conn_id.clone();
+        let event_tx = self.event_tx.clone();
+        let bandwidth = self.bandwidth.clone();
+
+        tokio::spawn(async move {
+            while let Some(incoming) = endpoint.accept().await {
+                let connections = connections.clone();
+                let next_conn_id = next_conn_id.clone();
+                let event_tx = event_tx.clone();
+                let bandwidth = bandwidth.clone();
+                tokio::spawn(async move {
+                    let connection = match incoming.await {
+                        Ok(connection) => connection,
+                        Err(e) => {
+                            eprintln!("QUIC handshake failed: {}", e);
+                            return;
+                        }
+                    };
+                    let conn_id = next_conn_id.fetch_add(1, Ordering::SeqCst);
+                    if let Err(e) =
+                        Self::register(conn_id, connection, &connections, &event_tx, &bandwidth)
+                            .await
+                    {
+                        eprintln!("{}", e);
+                    }
+                });
+            }
+        });
+    }
+
+    /// Dial addr, insisting that the node there is `expected`
+    pub async fn dial(&self, addr: &str, expected: PeerId) -> Result<u64, String> {
+        let remote: SocketAddr =
+            addr.parse().map_err(|e| format!("Invalid QUIC address {}: {}", addr, e))?;
+
+        let verifier = PeerVerifier {
+            expected: Some(expected),
+            algorithms: self.provider.signature_verification_algorithms,
+        };
+        let mut crypto = rustls::ClientConfig::builder_with_provider(self.provider.clone())
+            .with_protocol_versions(&[&rustls::version::TLS13])
+            .map_err(|e| format!("TLS setup failed: {}", e))?
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(verifier))
+            .with_client_auth_cert(
+                vec![self.identity.cert.clone()],
+                self.identity.key.clone_key().into(),
+            )
+            .map_err(|e| format!("TLS setup failed: {}", e))?;
+        crypto.alpn_protocols = vec![ALPN.to_vec()];
+        let crypto =
+            QuicClientConfig::try_from(crypto).map_err(|e| format!("TLS setup failed: {}", e))?;
+
+        let connection = self
+            .endpoint
+            .connect_with(quinn::ClientConfig::new(Arc::new(crypto)), remote, SERVER_NAME)
+            .map_err(|e| format!("Failed to connect to {}: {}", addr, e))?
+            .await
+            .map_err(|e| format!("Failed to connect to {}: {}", addr, e))?;
+
+        let conn_id = self.next_conn_id.fetch_add(1, Ordering::SeqCst);
+        Self::register(conn_id, connection, &self.connections, &self.event_tx, &self.bandwidth)
+            .await?;
+        Ok(conn_id)
+    }
+
+    /// Track a handshaken connection, announce it and start reading its streams
+    async fn register(
+        conn_id: u64,
+        connection: quinn::Connection,
+        connections: &Arc<Mutex<HashMap<u64, quinn::Connection>>>,
+        event_tx: &mpsc::Sender<TransportEvent>,
+        bandwidth: &Arc<BandwidthMeter>,
+    ) -> Result<(), String> {
+        let peer_id = connection
+            .peer_identity()
+            .and_then(|certs| certs.downcast::<Vec<CertificateDer<'static>>>().ok())
+            .and_then(|certs| cert_peer_id(certs.first()?).ok())
+            .ok_or_else(|| "QUIC peer presented no identity".to_string())?;
+        let remote_addr = connection.remote_address().to_string();
+
+        connections.lock().await.insert(conn_id, connection.clone());
+        if let Err(e) = event_tx
+            .send(TransportEvent::Authenticated(conn_id, remote_addr, peer_id.0))
+            .await
+        {
+            eprintln!("Failed to send Authenticated event: {}", e);
+        }
+
+        let connections = connections.clone();
+        let event_tx = event_tx.clone();
+        let bandwidth = bandwidth.clone();
+        tokio::spawn(async move {
+            while let Ok(mut stream) = connection.accept_uni().await {
+                let event_tx = event_tx.clone();
+                let bandwidth = bandwidth.clone();
+                let connection = connection.clone();
+                tokio::spawn(async move {
+                    match stream.read_to_end(MAX_STREAM_MESSAGE).await {
+                        Ok(data) => {
+                            let passed = bandwidth.pass(conn_id, Direction::Received, data.len());
+                            if let Err(e) = passed.await {
+                                // Ends accept_uni, which reports the disconnect below
+                                eprintln!("{}", e);
+                                connection.close(0u32.into(), b"bandwidth limit");
+                                return;
+                            }
+                            let _ = event_tx.send(TransportEvent::Data(conn_id, data)).await;
+                        }
+                        Err(e) => eprintln!("Connection {} stream error: {}", conn_id, e),
+                    }
+                });
+            }
+            // Closed by either side; a local Close has already reported it
+            if connections.lock().await.remove(&conn_id).is_some() {
+                bandwidth.unbind(conn_id).await;
+                let _ = event_tx.send(TransportEvent::Disconnected(conn_id)).await;
+            }
+        });
+        Ok(())
+    }
+
+    /// Whether conn_id is one of ours
+    pub async fn owns(&self, conn_id: u64) -> bool {
+        self.connections.lock().await.contains_key(&conn_id)
+    }
+
+    /// Send bytes as one message on a fresh stream
+    pub async fn send(&self, conn_id: u64, bytes: &[u8]) -> Result<(), String> {
+        let connection = self
+            .connections
+            .lock()
+            .await
+            .get(&conn_id)
+            .cloned()
+            .ok_or_else(|| format!("Connection {} not found", conn_id))?;
+
+        let mut stream = connection
+            .open_uni()
+            .await
+            .map_err(|e| format!("Failed to open stream: {}", e))?;
+        stream
+            .write_all(bytes)
+            .await
+            .map_err(|e| format!("Failed to write data: {}", e))?;
+        stream.finish().map_err(|e| format!("Failed to finish stream: {}", e))
+    }
+
+    /// Close conn_id; false if it isn't ours
+    pub async fn close(&self, conn_id: u64) -> bool {
+        match self.connections.lock().await.remove(&conn_id) {
+            Some(connection) => {
+                connection.close(0u32.into(), b"closed");
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Move the endpoint to a new local address, migrating open connections
+    pub fn rebind(&self, addr: &str) -> Result<(), String> {
+        let socket = std::net::UdpSocket::bind(addr)
+            .map_err(|e| format!("Failed to bind QUIC {}: {}", addr, e))?;
+        self.endpoint
+            .rebind(socket)
+            .map_err(|e| format!("Failed to rebind QUIC: {}", e))
+    }
+}
@@ -3,6 +3,8 @@
 
     Prevents DoS attacks from malicious peers flooding the system with requests.
     Uses token bucket for smooth rate limiting and circuit breaker for failing peers.
+    In byte mode (check_bytes/reserve_bytes) a second bucket per peer counts bytes instead
+    of requests, which the transport uses to throttle peers over their bandwidth limit.
 */
 
 use std::collections::HashMap;
@@ -25,6 +27,10 @@ pub struct RateLimiterConfig {
     pub circuit_breaker_threshold: u32,
     /// Circuit breaker: time to wait before attempting recovery (half-open state)
     pub circuit_breaker_timeout: Duration,
+    /// Byte mode: maximum bytes per second per peer (byte token refill rate)
+    pub max_bytes_per_sec: u64,
+    /// Byte mode: maximum burst in bytes (byte bucket capacity)
+    pub byte_burst_size: u64,
 }
 
 impl Default for RateLimiterConfig {
@@ -34,6 +40,8 @@ impl Default for RateLimiterConfig {
             burst_size: 200,               // Allow bursts up to 200
             circuit_breaker_threshold: 10, // 10 consecutive failures
             circuit_breaker_timeout: Duration::from_secs(30),
+            max_bytes_per_sec: 1024 * 1024, // 1 MiB/s sustained
+            byte_burst_size: 2 * 1024 * 1024,
         }
     }
 }
@@ -52,11 +60,11 @@ struct TokenBucket {
 }
 
 impl TokenBucket {
-    fn new(capacity: u32, refill_rate: u32) -> Self {
+    fn new(capacity: f64, refill_rate: f64) -> Self {
         TokenBucket {
-            tokens: capacity as f64, // Start with full bucket
-            capacity: capacity as f64,
-            refill_rate: refill_rate as f64,
+            tokens: capacity, // Start with full bucket
+            capacity,
+            refill_rate,
             last_refill: Instant::now(),
         }
     }
@@ -84,6 +92,19 @@ impl TokenBucket {
         }
     }
 
+    /// Consume tokens even if the bucket runs short, going into debt
+    /// Returns how long the refill takes to pay the debt back
+    fn reserve(&mut self, tokens: f64) -> Duration {
+        self.refill();
+        self.tokens -= tokens;
+
+        if self.tokens >= 0.0 || self.refill_rate <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.refill_rate)
+        }
+    }
+
     /// Get current token count (after refill)
     fn available_tokens(&mut self) -> f64 {
         self.refill();
@@ -245,13 +266,21 @@ impl CircuitBreaker {
 #[derive(Debug)]
 struct PeerLimiter {
     token_bucket: TokenBucket,
+    byte_bucket: TokenBucket,
     circuit_breaker: CircuitBreaker,
 }
 
 impl PeerLimiter {
     fn new(config: &RateLimiterConfig) -> Self {
         PeerLimiter {
-            token_bucket: TokenBucket::new(config.burst_size, config.max_requests_per_sec),
+            token_bucket: TokenBucket::new(
+                config.burst_size as f64,
+                config.max_requests_per_sec as f64,
+            ),
+            byte_bucket: TokenBucket::new(
+                config.byte_burst_size as f64,
+                config.max_bytes_per_sec as f64,
+            ),
             circuit_breaker: CircuitBreaker::new(
                 config.circuit_breaker_threshold,
                 config.circuit_breaker_timeout,
@@ -329,6 +358,40 @@ impl RateLimiter {
         }
     }
 
+    /// Check if `bytes` may be transferred with a peer, consuming them from its byte bucket
+    ///
+    /// A transfer larger than byte_burst_size never fits; use reserve_bytes to pace those.
+    pub async fn check_bytes(&self, peer_id: &PeerId, bytes: u64) -> RateLimitResult {
+        let mut limiters = self.limiters.lock().await;
+        let limiter = limiters
+            .entry(peer_id.clone())
+            .or_insert_with(|| PeerLimiter::new(&self.config));
+
+        if limiter.byte_bucket.try_consume(bytes as f64) {
+            RateLimitResult::Allowed
+        } else {
+            debug!(
+                peer_id = ?peer_id,
+                bytes,
+                bytes_per_sec = self.config.max_bytes_per_sec,
+                "Transfer blocked: byte rate exceeded"
+            );
+            RateLimitResult::RateLimitExceeded
+        }
+    }
+
+    /// Take `bytes` from a peer's byte bucket even if it holds fewer
+    ///
+    /// Returns how long the caller should wait before transferring them so that the peer
+    /// stays within max_bytes_per_sec; zero while the bucket still covers them.
+    pub async fn reserve_bytes(&self, peer_id: &PeerId, bytes: u64) -> Duration {
+        let mut limiters = self.limiters.lock().await;
+        let limiter = limiters
+            .entry(peer_id.clone())
+            .or_insert_with(|| PeerLimiter::new(&self.config));
+        limiter.byte_bucket.reserve(bytes as f64)
+    }
+
     /// Record a successful request (for circuit breaker)
     pub async fn record_success(&self, peer_id: &PeerId) {
         let mut limiters = self.limiters.lock().await;
@@ -396,6 +459,7 @@ mod tests {
             burst_size: 10,
             circuit_breaker_threshold: 5,
             circuit_breaker_timeout: Duration::from_secs(1),
+            ..RateLimiterConfig::default()
         };
         let limiter = RateLimiter::new_with_config(config);
         let peer = test_peer_id(1);
@@ -416,6 +480,7 @@ mod tests {
             burst_size: 5,
             circuit_breaker_threshold: 5,
             circuit_breaker_timeout: Duration::from_secs(1),
+            ..RateLimiterConfig::default()
         };
         let limiter = RateLimiter::new_with_config(config);
         let peer = test_peer_id(1);
@@ -440,6 +505,7 @@ mod tests {
             burst_size: 100,
             circuit_breaker_threshold: 3, // Open after 3 failures
             circuit_breaker_timeout: Duration::from_secs(10),
+            ..RateLimiterConfig::default()
         };
         let limiter = RateLimiter::new_with_config(config);
         let peer = test_peer_id(1);
@@ -464,6 +530,7 @@ mod tests {
             burst_size: 100,
             circuit_breaker_threshold: 2,
             circuit_breaker_timeout: Duration::from_millis(100), // Short timeout
+            ..RateLimiterConfig::default()
         };
         let limiter = RateLimiter::new_with_config(config);
         let peer = test_peer_id(1);
@@ -492,6 +559,7 @@ mod tests {
             burst_size: 100,
             circuit_breaker_threshold: 2,
             circuit_breaker_timeout: Duration::from_millis(100),
+            ..RateLimiterConfig::default()
         };
         let limiter = RateLimiter::new_with_config(config);
         let peer = test_peer_id(1);
@@ -516,6 +584,7 @@ mod tests {
             burst_size: 5,
             circuit_breaker_threshold: 10,
             circuit_breaker_timeout: Duration::from_secs(1),
+            ..RateLimiterConfig::default()
         };
         let limiter = RateLimiter::new_with_config(config);
         let peer1 = test_peer_id(1);
@@ -554,6 +623,7 @@ mod tests {
             burst_size: 100,
             circuit_breaker_threshold: 3,
             circuit_breaker_timeout: Duration::from_secs(10),
+            ..RateLimiterConfig::default()
         };
         let limiter = RateLimiter::new_with_config(config);
         let peer = test_peer_id(1);
@@ -579,6 +649,7 @@ mod tests {
             burst_size: 10,
             circuit_breaker_threshold: 10,
             circuit_breaker_timeout: Duration::from_secs(1),
+            ..RateLimiterConfig::default()
         };
         let limiter = RateLimiter::new_with_config(config);
         let peer = test_peer_id(1);
@@ -594,4 +665,31 @@ mod tests {
         assert!(tokens <= 10.0);
         assert!(tokens >= 9.0); // Allow some timing variance (consumed 1 initially)
     }
+
+    #[tokio::test]
+    async fn test_byte_bucket_limits_bytes() {
+        let config = RateLimiterConfig {
+            max_bytes_per_sec: 1000,
+            byte_burst_size: 4000,
+            ..RateLimiterConfig::default()
+        };
+        let limiter = RateLimiter::new_with_config(config);
+        let peer = test_peer_id(1);
+
+        assert_eq!(limiter.check_bytes(&peer, 3000).await, RateLimitResult::Allowed);
+        assert_eq!(limiter.check_bytes(&peer, 3000).await, RateLimitResult::RateLimitExceeded);
+        // Byte mode doesn't use up request tokens
+        assert_eq!(limiter.check_request(&peer).await, RateLimitResult::Allowed);
+
+        // 2000 bytes over the remaining 1000 take 2s to refill at 1000 bytes/s
+        let wait = limiter.reserve_bytes(&peer, 3000).await;
+        assert!(
+            wait > Duration::from_millis(1900) && wait <= Duration::from_secs(2),
+            "{:?}",
+            wait
+        );
+        // Debt carries over to the next reservation
+        let wait = limiter.reserve_bytes(&peer, 1000).await;
+        assert!(wait > Duration::from_millis(2900), "{:?}", wait);
+    }
 }
@@ -0,0 +1,261 @@
+/*
+    Reputation - how well a peer has behaved towards us lately
+
+    Each peer's record counts the RPCs it answered and failed, the invalid messages it sent
+    (undecodable frames, responses that don't fit the request, bad signatures) and the rate
+    limit violations it caused, plus a smoothed latency. Counts decay with a half-life, so
+    old misbehaviour fades and a peer can't bank goodwill forever.
+
+    Workflow:
+    1. Whoever sees a peer behave records it: record(Behaviour::..)
+    2. score() folds the decayed counts into 0.0 (worst) ..= 1.0 (best); a peer with no
+       record scores 0.5
+    3. A peer whose score falls below the quarantine threshold is quarantined for the
+       cooldown: no new sessions with it, and it is left out of lookups and relay selection
+
+    Inputs:
+      - Behaviour::RpcSucceeded { latency } / RpcFailed / InvalidMessage / RateLimited
+
+    Outputs:
+      - score(), is_quarantined()
+      - Change, when the score moves or the peer enters or leaves quarantine
+*/
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Weight of a failed RPC against one answered
+const FAILURE_WEIGHT: f64 = 1.0;
+/// Weight of an invalid message against one answered RPC
+const INVALID_WEIGHT: f64 = 3.0;
+/// Weight of a rate limit violation against one answered RPC
+const RATE_LIMIT_WEIGHT: f64 = 2.0;
+/// The latest sample counts for 1/LATENCY_SMOOTHING of the smoothed latency
+const LATENCY_SMOOTHING: f64 = 8.0;
+/// Largest share of the score slow answers can cost
+const MAX_LATENCY_PENALTY: f64 = 0.25;
+
+/// Something a peer did
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Behaviour {
+    /// Answered an RPC, in `latency` when it was timed
+    RpcSucceeded { latency: Option<Duration> },
+    /// Didn't answer an RPC, or answered with an error
+    RpcFailed,
+    /// Sent something malformed, unexpected or wrongly signed
+    InvalidMessage,
+    /// Sent faster than we allow
+    RateLimited,
+}
+
+/// Reputation tuning
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReputationConfig {
+    /// Time for a recorded behaviour to count half as much
+    pub half_life: Duration,
+    /// Score below which a peer is quarantined
+    pub quarantine_threshold: f64,
+    /// How long a quarantine lasts
+    pub quarantine_cooldown: Duration,
+    /// Latency that costs half of the latency penalty
+    pub latency_target: Duration,
+}
+
+impl Default for ReputationConfig {
+    fn default() -> Self {
+        ReputationConfig {
+            half_life: Duration::from_secs(10 * 60),
+            quarantine_threshold: 0.2,
+            quarantine_cooldown: Duration::from_secs(30 * 60),
+            latency_target: Duration::from_millis(500),
+        }
+    }
+}
+
+/// What recording a behaviour changed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Change {
+    /// Score after the behaviour
+    pub score: f64,
+    /// Whether this behaviour put the peer in quarantine
+    pub quarantined: bool,
+}
+
+/// A peer's decaying behaviour record
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Reputation {
+    successes: f64,
+    failures: f64,
+    invalid_messages: f64,
+    rate_limited: f64,
+    /// Smoothed latency of answered RPCs, in milliseconds
+    latency_ms: Option<f64>,
+    /// Unix time in milliseconds the counts were last decayed to
+    updated_at: u64,
+    /// Unix time in milliseconds the quarantine ends
+    quarantined_until: Option<u64>,
+}
+
+impl Reputation {
+    /// Record a behaviour, quarantining the peer if its score drops below the threshold
+    pub fn record(&mut self, behaviour: Behaviour, config: &ReputationConfig) -> Change {
+        self.record_at(behaviour, config, now_ms())
+    }
+
+    fn record_at(&mut self, behaviour: Behaviour, config: &ReputationConfig, now: u64) -> Change {
+        self.decay(config, now);
+        match behaviour {
+            Behaviour::RpcSucceeded { latency } => {
+                self.successes += 1.0;
+                if let Some(latency) = latency {
+                    let sample = latency.as_secs_f64() * 1000.0;
+                    self.latency_ms = Some(match self.latency_ms {
+                        Some(ms) => ms + (sample - ms) / LATENCY_SMOOTHING,
+                        None => sample,
+                    });
+                }
+            }
+            Behaviour::RpcFailed => self.failures += 1.0,
+            Behaviour::InvalidMessage => self.invalid_messages += 1.0,
+            Behaviour::RateLimited => self.rate_limited += 1.0,
+        }
+
+        let score = self.raw_score(config);
+        let quarantined = score < config.quarantine_threshold && !self.is_quarantined_at(now);
+        if quarantined {
+            self.quarantined_until = Some(now + config.quarantine_cooldown.as_millis() as u64);
+        }
+        Change { score, quarantined }
+    }
+
+    /// Score from 0.0 (worst) to 1.0 (best), 0.5 for a peer with no record
+    pub fn score(&self, config: &ReputationConfig) -> f64 {
+        let mut decayed = self.clone();
+        decayed.decay(config, now_ms());
+        decayed.raw_score(config)
+    }
+
+    /// Whether the peer is in quarantine
+    pub fn is_quarantined(&self) -> bool {
+        self.is_quarantined_at(now_ms())
+    }
+
+    /// Time left in quarantine, if the peer is in it
+    pub fn quarantine_remaining(&self) -> Option<Duration> {
+        let now = now_ms();
+        self.quarantined_until
+            .filter(|until| *until > now)
+            .map(|until| Duration::from_millis(until - now))
+    }
+
+    fn is_quarantined_at(&self, now: u64) -> bool {
+        self.quarantined_until.is_some_and(|until| until > now)
+    }
+
+    /// Successes and misbehaviour weighed against each other, with one of each assumed so
+    /// that a thin record stays near the middle, then discounted for slow answers
+    fn raw_score(&self, config: &ReputationConfig) -> f64 {
+        let bad = self.failures * FAILURE_WEIGHT
+            + self.invalid_messages * INVALID_WEIGHT
+            + self.rate_limited * RATE_LIMIT_WEIGHT;
+        let ratio = (self.successes + 1.0) / (self.successes + bad + 2.0);
+        let penalty = match self.latency_ms {
+            Some(ms) => {
+                let target = config.latency_target.as_secs_f64() * 1000.0;
+                MAX_LATENCY_PENALTY * ms / (ms + target.max(1.0))
+            }
+            None => 0.0,
+        };
+        ratio * (1.0 - penalty)
+    }
+
+    fn decay(&mut self, config: &ReputationConfig, now: u64) {
+        let elapsed = now.saturating_sub(self.updated_at) as f64 / 1000.0;
+        self.updated_at = now;
+        let half_life = config.half_life.as_secs_f64();
+        if elapsed <= 0.0 || half_life <= 0.0 {
+            return;
+        }
+        let factor = 0.5f64.powf(elapsed / half_life);
+        self.successes *= factor;
+        self.failures *= factor;
+        self.invalid_messages *= factor;
+        self.rate_limited *= factor;
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: u64 = 1_000_000;
+
+    #[test]
+    fn test_new_peer_is_neutral() {
+        let config = ReputationConfig::default();
+        assert_eq!(Reputation::default().score(&config), 0.5);
+        assert!(!Reputation::default().is_quarantined());
+    }
+
+    #[test]
+    fn test_misbehaviour_quarantines_once() {
+        let config = ReputationConfig::default();
+        let mut reputation = Reputation::default();
+        let first = reputation.record_at(Behaviour::InvalidMessage, &config, START);
+        assert!(!first.quarantined);
+        let second = reputation.record_at(Behaviour::InvalidMessage, &config, START);
+        assert!(second.quarantined);
+        assert!(second.score < config.quarantine_threshold);
+        // Already in quarantine: further misbehaviour doesn't start it again
+        let third = reputation.record_at(Behaviour::InvalidMessage, &config, START);
+        assert!(!third.quarantined);
+        assert!(reputation.is_quarantined_at(START + 1000));
+        let cooldown = config.quarantine_cooldown.as_millis() as u64;
+        assert!(!reputation.is_quarantined_at(START + cooldown));
+    }
+
+    #[test]
+    fn test_honest_peer_survives_an_occasional_failure() {
+        let config = ReputationConfig::default();
+        let mut reputation = Reputation::default();
+        for _ in 0..20 {
+            let latency = Some(Duration::from_millis(50));
+            reputation.record_at(Behaviour::RpcSucceeded { latency }, &config, START);
+        }
+        let change = reputation.record_at(Behaviour::RpcFailed, &config, START);
+        assert!(!change.quarantined);
+        assert!(change.score > 0.8, "{}", change.score);
+    }
+
+    #[test]
+    fn test_slow_answers_cost_score() {
+        let config = ReputationConfig::default();
+        let (mut fast, mut slow) = (Reputation::default(), Reputation::default());
+        for _ in 0..10 {
+            let latency = Some(Duration::from_millis(20));
+            fast.record_at(Behaviour::RpcSucceeded { latency }, &config, START);
+            let latency = Some(Duration::from_secs(2));
+            slow.record_at(Behaviour::RpcSucceeded { latency }, &config, START);
+        }
+        assert!(slow.raw_score(&config) < fast.raw_score(&config));
+        assert!(slow.raw_score(&config) >= 0.9 * (1.0 - MAX_LATENCY_PENALTY));
+    }
+
+    #[test]
+    fn test_misbehaviour_decays() {
+        let config = ReputationConfig::default();
+        let mut reputation = Reputation::default();
+        for _ in 0..5 {
+            reputation.record_at(Behaviour::RateLimited, &config, START);
+        }
+        let before = reputation.raw_score(&config);
+        let later = START + 5 * config.half_life.as_millis() as u64;
+        let change =
+            reputation.record_at(Behaviour::RpcSucceeded { latency: None }, &config, later);
+        assert!(change.score > 0.5, "{} should recover well past {}", change.score, before);
+    }
+}
@@ -13,9 +13,13 @@
       - RouteTableCommand::InsertPeer(peer_info)
       - RouteTableCommand::UpdatePeerStats(peer_id, stats)
       - RouteTableCommand::PickDiverseRelays(k)
+      - RouteTableCommand::PickBrowserRelays(k) (prefers relays browsers can reach over WebSocket)
+      - RouteTableCommand::SetReachability(peer_id, reachability)
+      - RouteTableCommand::RecordBehaviour(peer_id, behaviour) (feeds the peer's reputation)
 
     Outputs:
       - Queries like "get_best_route_for(peer_id)" or "pick_diverse_relays(k)"
+      - RouterEvent::PeerReputationChanged / PeerQuarantined, once given an event channel
 
     Notes:
 
@@ -29,15 +33,31 @@
     - failure_count: u32
     - asn: Option<u32>
     - geo_location: Option<GeoLocation>
+    - observed_addr: Option<String> (external address its peers see)
+    - reachability: Reachability (public, nat-punchable or relay-only)
+    - ws_addr: Option<String> (WebSocket listener, for browser/WASM peers)
+    - reputation: f64, quarantined: bool (filled in from the table's reputation records
+      whenever a PeerInfo is read out, so re-inserting a peer doesn't reset them)
+
+    Reputation is kept per PeerId apart from PeerInfo. Quarantined peers count as
+    unhealthy, so relay selection skips them, and the router refuses new sessions with
+    them until the cooldown ends.
 */
 
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
-use tokio::sync::{oneshot, Mutex};
+use tokio::sync::{broadcast, oneshot, Mutex};
+use tracing::warn;
 
+use super::metrics;
+use super::reputation::{Behaviour, Reputation, ReputationConfig};
+use super::router_handle::RouterEvent;
 use super::session_manager::PeerId;
 
+/// Smallest score change reported as a RouterEvent, so routine successes stay quiet
+const REPUTATION_EVENT_STEP: f64 = 0.05;
+
 /// Peer capabilities (e.g., relay, DHT node, storage)
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Capability {
@@ -45,6 +65,8 @@ pub enum Capability {
     DhtNode,
     Storage,
     LongLived, // Stable peer with good uptime
+    Quic,      // Accepts connections over QUIC
+    WebSocket, // Accepts connections over WebSocket (reachable from browsers)
 }
 
 /// Geographic location for diversity
@@ -55,6 +77,20 @@ pub struct GeoLocation {
     pub country_code: Option<String>,
 }
 
+/// How a peer can be reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Reachability {
+    /// Not known yet
+    #[default]
+    Unknown,
+    /// Accepts inbound connections on its advertised addresses
+    Public,
+    /// Behind a NAT that a coordinated hole punch gets through
+    NatPunchable,
+    /// Behind a NAT that defeats punching; only reachable through a relay
+    RelayOnly,
+}
+
 /// Comprehensive peer information
 #[derive(Debug, Clone)]
 pub struct PeerInfo {
@@ -67,6 +103,13 @@ pub struct PeerInfo {
     pub failure_count: u32,
     pub asn: Option<u32>,
     pub geo_location: Option<GeoLocation>,
+    pub observed_addr: Option<String>,
+    pub reachability: Reachability,
+    pub ws_addr: Option<String>,
+    /// Reputation score, from 0.0 (worst) to 1.0 (best)
+    pub reputation: f64,
+    /// In quarantine for misbehaving: no new sessions, not picked as a relay
+    pub quarantined: bool,
 }
 
 impl PeerInfo {
@@ -82,12 +125,17 @@ impl PeerInfo {
             failure_count: 0,
             asn: None,
             geo_location: None,
+            observed_addr: None,
+            reachability: Reachability::Unknown,
+            ws_addr: None,
+            reputation: 0.5,
+            quarantined: false,
         }
     }
 
-    /// Check if peer is healthy (low failure count, recently seen)
+    /// Check if peer is healthy (low failure count, recently seen, not quarantined)
     pub fn is_healthy(&self, max_failures: u32, max_age: Duration) -> bool {
-        if self.failure_count > max_failures {
+        if self.quarantined || self.failure_count > max_failures {
             return false;
         }
 
@@ -117,6 +165,9 @@ impl PeerInfo {
             score += elapsed.as_secs();
         }
 
+        // Penalize a poor reputation, up to two failures' worth
+        score += ((1.0 - self.reputation.clamp(0.0, 1.0)) * 2000.0) as u64;
+
         score
     }
 }
@@ -138,6 +189,8 @@ pub enum RouteTableCommand {
     UpdatePeerStats { peer_id: PeerId, stats: PeerStats },
     /// Pick k diverse relays for onion routing
     PickDiverseRelays { k: usize, response_tx: oneshot::Sender<Vec<PeerInfo>> },
+    /// Pick k diverse relays, WebSocket-capable first, for peers needing browser compatibility
+    PickBrowserRelays { k: usize, response_tx: oneshot::Sender<Vec<PeerInfo>> },
     /// Get a specific peer's info
     GetPeer { peer_id: PeerId, response_tx: oneshot::Sender<Option<PeerInfo>> },
     /// List all peers with a specific capability
@@ -146,17 +199,42 @@ pub enum RouteTableCommand {
     RemovePeer(PeerId),
     /// Get all known peers
     GetAllPeers { response_tx: oneshot::Sender<Vec<PeerInfo>> },
+    /// Record how a peer can be reached
+    SetReachability { peer_id: PeerId, reachability: Reachability },
+    /// Record something a peer did, good or bad, towards its reputation
+    RecordBehaviour { peer_id: PeerId, behaviour: Behaviour },
 }
 
 /// RouteTable managing peer metadata
 pub struct RouteTable {
     peers: Arc<Mutex<HashMap<PeerId, PeerInfo>>>,
+    /// Kept apart from PeerInfo so rediscovering a peer doesn't clear its record
+    reputations: Arc<Mutex<HashMap<PeerId, Reputation>>>,
+    reputation_config: ReputationConfig,
+    event_tx: Option<broadcast::Sender<RouterEvent>>,
 }
 
 impl RouteTable {
     /// Create a new RouteTable
     pub fn new() -> Self {
-        RouteTable { peers: Arc::new(Mutex::new(HashMap::new())) }
+        RouteTable {
+            peers: Arc::new(Mutex::new(HashMap::new())),
+            reputations: Arc::new(Mutex::new(HashMap::new())),
+            reputation_config: ReputationConfig::default(),
+            event_tx: None,
+        }
+    }
+
+    /// Builder: tune how reputation is scored and when peers are quarantined
+    pub fn with_reputation_config(mut self, config: ReputationConfig) -> Self {
+        self.reputation_config = config;
+        self
+    }
+
+    /// Builder: report reputation changes as RouterEvents on `event_tx`
+    pub fn with_events(mut self, event_tx: broadcast::Sender<RouterEvent>) -> Self {
+        self.event_tx = Some(event_tx);
+        self
     }
 
     /// Handle a command
@@ -172,6 +250,10 @@ impl RouteTable {
                 let relays = self.pick_diverse_relays(k).await;
                 let _ = response_tx.send(relays);
             }
+            RouteTableCommand::PickBrowserRelays { k, response_tx } => {
+                let relays = self.pick_relays(k, true).await;
+                let _ = response_tx.send(relays);
+            }
             RouteTableCommand::GetPeer { peer_id, response_tx } => {
                 let peer = self.get_peer(&peer_id).await;
                 let _ = response_tx.send(peer);
@@ -187,10 +269,77 @@ impl RouteTable {
                 let peers = self.get_all_peers().await;
                 let _ = response_tx.send(peers);
             }
+            RouteTableCommand::SetReachability { peer_id, reachability } => {
+                self.set_reachability(peer_id, reachability).await?;
+            }
+            RouteTableCommand::RecordBehaviour { peer_id, behaviour } => {
+                self.record_behaviour(peer_id, behaviour).await;
+            }
         }
         Ok(())
     }
 
+    /// Fold a behaviour into a peer's reputation, reporting notable changes
+    pub async fn record_behaviour(&self, peer_id: PeerId, behaviour: Behaviour) {
+        let config = &self.reputation_config;
+        let (before, change) = {
+            let mut reputations = self.reputations.lock().await;
+            let reputation = reputations.entry(peer_id.clone()).or_default();
+            let before = reputation.score(config);
+            (before, reputation.record(behaviour, config))
+        };
+
+        if change.quarantined {
+            warn!(peer_id = ?peer_id, score = change.score, "Peer quarantined for misbehaving");
+            metrics::peer_quarantined();
+            self.emit(RouterEvent::PeerQuarantined(peer_id.clone(), config.quarantine_cooldown));
+        }
+        if change.quarantined || (change.score - before).abs() >= REPUTATION_EVENT_STEP {
+            self.emit(RouterEvent::PeerReputationChanged(peer_id, change.score));
+        }
+    }
+
+    /// Reputation score of a peer, 0.5 if nothing has been recorded about it
+    pub async fn reputation(&self, peer_id: &PeerId) -> f64 {
+        let reputations = self.reputations.lock().await;
+        reputations.get(peer_id).map_or(0.5, |r| r.score(&self.reputation_config))
+    }
+
+    /// Whether a peer is in quarantine, and so mustn't get new sessions
+    pub async fn is_quarantined(&self, peer_id: &PeerId) -> bool {
+        let reputations = self.reputations.lock().await;
+        reputations.get(peer_id).is_some_and(Reputation::is_quarantined)
+    }
+
+    fn emit(&self, event: RouterEvent) {
+        if let Some(event_tx) = &self.event_tx {
+            let _ = event_tx.send(event);
+        }
+    }
+
+    /// Fill in a PeerInfo's reputation from the table's records
+    fn with_reputation(
+        &self,
+        mut peer: PeerInfo,
+        reputations: &HashMap<PeerId, Reputation>,
+    ) -> PeerInfo {
+        let reputation = reputations.get(&peer.peer_id);
+        peer.reputation = reputation.map_or(0.5, |r| r.score(&self.reputation_config));
+        peer.quarantined = reputation.is_some_and(Reputation::is_quarantined);
+        peer
+    }
+
+    /// Snapshot of peers matching `filter`, with their reputation filled in
+    async fn peers_where(&self, filter: impl Fn(&PeerInfo) -> bool) -> Vec<PeerInfo> {
+        let peers = self.peers.lock().await;
+        let reputations = self.reputations.lock().await;
+        peers
+            .values()
+            .filter(|p| filter(p))
+            .map(|p| self.with_reputation(p.clone(), &reputations))
+            .collect()
+    }
+
     /// Insert or update a peer
     async fn insert_peer(&self, peer_info: PeerInfo) {
         let mut peers = self.peers.lock().await;
@@ -211,17 +360,41 @@ impl RouteTable {
             peer.failure_count = new_count.max(0) as u32;
 
             peer.last_seen = stats.last_seen;
+            drop(peers);
 
+            // Failures count against the peer, recoveries and timed answers for it
+            if stats.failure_count_delta > 0 {
+                for _ in 0..stats.failure_count_delta {
+                    self.record_behaviour(peer_id.clone(), Behaviour::RpcFailed).await;
+                }
+            } else if stats.failure_count_delta < 0 || stats.latency.is_some() {
+                let behaviour = Behaviour::RpcSucceeded { latency: stats.latency };
+                self.record_behaviour(peer_id, behaviour).await;
+            }
             Ok(())
         } else {
             Err(format!("Peer {:?} not found", peer_id))
         }
     }
 
+    /// Record how a peer can be reached
+    async fn set_reachability(
+        &self,
+        peer_id: PeerId,
+        reachability: Reachability,
+    ) -> Result<(), String> {
+        let mut peers = self.peers.lock().await;
+        let peer =
+            peers.get_mut(&peer_id).ok_or_else(|| format!("Peer {:?} not found", peer_id))?;
+        peer.reachability = reachability;
+        Ok(())
+    }
+
     /// Get a specific peer
     async fn get_peer(&self, peer_id: &PeerId) -> Option<PeerInfo> {
         let peers = self.peers.lock().await;
-        peers.get(peer_id).cloned()
+        let reputations = self.reputations.lock().await;
+        peers.get(peer_id).map(|p| self.with_reputation(p.clone(), &reputations))
     }
 
     /// Remove a peer
@@ -232,33 +405,28 @@ impl RouteTable {
 
     /// Get all peers
     async fn get_all_peers(&self) -> Vec<PeerInfo> {
-        let peers = self.peers.lock().await;
-        peers.values().cloned().collect()
+        self.peers_where(|_| true).await
     }
 
     /// List peers by capability
     async fn list_peers_by_capability(&self, capability: &Capability) -> Vec<PeerInfo> {
-        let peers = self.peers.lock().await;
-        peers
-            .values()
-            .filter(|p| p.capabilities.contains(capability))
-            .cloned()
-            .collect()
+        self.peers_where(|p| p.capabilities.contains(capability)).await
     }
 
     /// Pick k diverse relays for onion routing
     /// Prioritizes diversity in ASN, geo location, and low latency
     async fn pick_diverse_relays(&self, k: usize) -> Vec<PeerInfo> {
-        let peers = self.peers.lock().await;
+        self.pick_relays(k, false).await
+    }
 
+    /// Pick k diverse relays, optionally ranking WebSocket-capable relays ahead of the rest
+    async fn pick_relays(&self, k: usize, prefer_ws: bool) -> Vec<PeerInfo> {
         // Filter to healthy relay-capable peers
-        let mut candidates: Vec<PeerInfo> = peers
-            .values()
-            .filter(|p| {
-                p.capabilities.contains(&Capability::Relay)
-                    && p.is_healthy(3, Duration::from_secs(3600)) // max 3 failures, seen in last hour
-            })
-            .cloned()
+        let mut candidates: Vec<PeerInfo> = self
+            .peers_where(|p| p.capabilities.contains(&Capability::Relay))
+            .await
+            .into_iter()
+            .filter(|p| p.is_healthy(3, Duration::from_secs(3600))) // max 3 failures, seen in last hour
             .collect();
 
         if candidates.is_empty() {
@@ -267,6 +435,10 @@ impl RouteTable {
 
         // Sort by relay score (best first)
         candidates.sort_by_key(|p| p.relay_score());
+        if prefer_ws {
+            // Stable sort keeps the score order within each group
+            candidates.sort_by_key(|p| !p.capabilities.contains(&Capability::WebSocket));
+        }
 
         // Pick diverse relays
         let mut selected = Vec::new();
@@ -457,6 +629,33 @@ mod tests {
         assert!(relays.iter().all(|p| p.failure_count <= 3));
     }
 
+    #[tokio::test]
+    async fn test_pick_browser_relays_prefers_websocket() {
+        let route_table = RouteTable::new();
+
+        let fast = create_test_peer(1, Some(1111), Some(10));
+        let mut ws = create_test_peer(2, Some(2222), Some(200));
+        ws.capabilities.push(Capability::WebSocket);
+        route_table.insert_peer(fast).await;
+        route_table.insert_peer(ws).await;
+
+        let (tx, rx) = oneshot::channel();
+        route_table
+            .handle_command(RouteTableCommand::PickBrowserRelays { k: 1, response_tx: tx })
+            .await
+            .unwrap();
+        let relays = rx.await.unwrap();
+        assert_eq!(relays[0].peer_id, PeerId::from_bytes(vec![2]));
+
+        // Without the browser requirement the faster relay wins
+        let (tx, rx) = oneshot::channel();
+        route_table
+            .handle_command(RouteTableCommand::PickDiverseRelays { k: 1, response_tx: tx })
+            .await
+            .unwrap();
+        assert_eq!(rx.await.unwrap()[0].peer_id, PeerId::from_bytes(vec![1]));
+    }
+
     #[tokio::test]
     async fn test_peer_is_healthy() {
         let peer = create_test_peer(1, Some(1234), Some(50));
@@ -494,4 +693,45 @@ mod tests {
         let all_peers = rx.await.unwrap();
         assert_eq!(all_peers.len(), 3);
     }
+
+    #[tokio::test]
+    async fn test_misbehaving_peer_is_quarantined() {
+        let (event_tx, mut events) = broadcast::channel(16);
+        let route_table = RouteTable::new().with_events(event_tx);
+        for id in 1..=3 {
+            route_table.insert_peer(create_test_peer(id, Some(id as u32), Some(50))).await;
+        }
+        let bad = PeerId::from_bytes(vec![1]);
+
+        for _ in 0..2 {
+            let command = RouteTableCommand::RecordBehaviour {
+                peer_id: bad.clone(),
+                behaviour: Behaviour::InvalidMessage,
+            };
+            route_table.handle_command(command).await.unwrap();
+        }
+        let honest = PeerId::from_bytes(vec![2]);
+        let latency = Some(Duration::from_millis(40));
+        route_table
+            .record_behaviour(honest.clone(), Behaviour::RpcSucceeded { latency })
+            .await;
+
+        assert!(route_table.is_quarantined(&bad).await);
+        assert!(!route_table.is_quarantined(&honest).await);
+        assert!(route_table.reputation(&honest).await > 0.5);
+        let peer = route_table.get_peer(&bad).await.unwrap();
+        assert!(peer.quarantined && !peer.is_healthy(3, Duration::from_secs(3600)));
+
+        let relays = route_table.pick_diverse_relays(3).await;
+        assert_eq!(relays.len(), 2);
+        assert!(relays.iter().all(|relay| relay.peer_id != bad));
+
+        let mut quarantined = false;
+        while let Ok(event) = events.try_recv() {
+            if let RouterEvent::PeerQuarantined(peer_id, _) = event {
+                quarantined |= peer_id == bad;
+            }
+        }
+        assert!(quarantined);
+    }
 }
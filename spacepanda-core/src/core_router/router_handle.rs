@@ -32,6 +32,7 @@
                       │ • rpc_call(peer, method, params)
                       │ • listen(addr)
                       │ • dial(addr)
+                      │ • dial_peer(peer) (direct, else hole punch via a relay)
                       │
     ┌─────────────────▼────────────────────────────────┐
     │              RouterHandle                        │
@@ -52,13 +53,18 @@
 */
 
 use serde_json::Value as JsonValue;
+use std::collections::HashSet;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::{broadcast, mpsc, oneshot, Mutex};
 use tokio::task::JoinHandle;
+use x25519_dalek::StaticSecret;
 
+use super::bandwidth::PeerClass;
+use super::nat_traversal::{self, serve_connect_requests, Connection, CONNECT_REQUEST_METHOD};
 use super::onion_router::{OnionCommand, OnionConfig, OnionEvent, OnionRouter};
-use super::route_table::RouteTable;
-use super::rpc_protocol::{RpcCommand, RpcError, RpcProtocol};
+use super::route_table::{RouteTable, RouteTableCommand};
+use super::rpc_protocol::{self, RpcCommand, RpcError, RpcProtocol, FETCH_BLOB_METHOD};
 use super::session_manager::{PeerId, SessionCommand, SessionEvent, SessionManager};
 use super::transport_manager::{TransportCommand, TransportManager};
 
@@ -69,6 +75,8 @@ pub enum RouterCommand {
     Listen(String),
     /// Dial a peer at an address
     Dial(String),
+    /// Open a connection to a known peer, punching through a relay if it is behind a NAT
+    DialPeer { peer_id: PeerId, response_tx: oneshot::Sender<Result<Connection, RpcError>> },
     /// Send data directly to a peer (encrypted)
     SendDirect(PeerId, Vec<u8>),
     /// Send data anonymously via onion routing
@@ -101,6 +109,10 @@ pub enum RouterEvent {
     DataReceived(PeerId, Vec<u8>),
     /// Peer disconnected
     PeerDisconnected(PeerId),
+    /// A peer's reputation score moved noticeably
+    PeerReputationChanged(PeerId, f64),
+    /// A peer misbehaved and gets no new sessions for this long
+    PeerQuarantined(PeerId, Duration),
 }
 
 /// Handle to interact with the router
@@ -113,10 +125,23 @@ pub struct RouterHandle {
 impl RouterHandle {
     /// Create a new router and spawn its event loop
     pub fn new() -> (Self, JoinHandle<()>) {
+        Self::spawn(None)
+    }
+
+    /// Create a router that relays onion circuits with the given onion key
+    ///
+    /// The key's public half must be what peers see as our PeerInfo::overlay_pubkey.
+    pub fn with_onion_key(onion_key: StaticSecret) -> (Self, JoinHandle<()>) {
+        Self::spawn(Some(onion_key))
+    }
+
+    fn spawn(onion_key: Option<StaticSecret>) -> (Self, JoinHandle<()>) {
         let (command_tx, command_rx) = mpsc::channel(100);
         let (event_tx, _event_rx) = broadcast::channel(100);
 
-        let router = Router::new(command_rx, event_tx.clone());
+        let mut router = Router::new(command_rx, event_tx.clone());
+        router.onion_key = onion_key;
+        router.command_tx = Some(command_tx.downgrade());
         let handle = router.spawn();
 
         (RouterHandle { command_tx, event_tx }, handle)
@@ -138,6 +163,25 @@ impl RouterHandle {
             .map_err(|e| format!("Failed to send dial command: {}", e))
     }
 
+    /// Open a connection to a peer in the route table
+    ///
+    /// Dials its advertised addresses, or has a connected peer coordinate a hole punch when
+    /// it is behind a NAT or no address answers.
+    pub async fn dial_peer(&self, peer_id: PeerId) -> Result<Connection, RpcError> {
+        let (response_tx, response_rx) = oneshot::channel();
+
+        self.command_tx
+            .send(RouterCommand::DialPeer { peer_id, response_tx })
+            .await
+            .map_err(|e| {
+                RpcError::internal_error(&format!("Failed to send dial command: {}", e))
+            })?;
+
+        response_rx.await.map_err(|e| {
+            RpcError::internal_error(&format!("Dial response channel closed: {}", e))
+        })?
+    }
+
     /// Send data directly to a peer
     pub async fn send_direct(&self, peer_id: PeerId, data: Vec<u8>) -> Result<(), String> {
         self.command_tx
@@ -183,6 +227,19 @@ impl RouterHandle {
             .map_err(|e| RpcError::internal_error(&format!("RPC response channel closed: {}", e)))?
     }
 
+    /// Fetch a content-addressed blob directly from a peer that provides it
+    pub async fn fetch_blob(
+        &self,
+        peer_id: PeerId,
+        hash: [u8; 32],
+        max_len: u64,
+    ) -> Result<Vec<u8>, RpcError> {
+        rpc_protocol::fetch_blob(hash, max_len, |params| {
+            self.rpc_call(peer_id.clone(), FETCH_BLOB_METHOD.to_string(), params)
+        })
+        .await
+    }
+
     /// Register an RPC method handler
     pub async fn register_rpc_handler(
         &self,
@@ -195,6 +252,14 @@ impl RouterHandle {
             .map_err(|e| format!("Failed to register RPC handler: {}", e))
     }
 
+    /// Subscribe to router events
+    ///
+    /// Unlike repeated [`next_event`](Self::next_event) calls, the receiver
+    /// keeps events that arrive while its owner is busy.
+    pub fn subscribe(&self) -> broadcast::Receiver<RouterEvent> {
+        self.event_tx.subscribe()
+    }
+
     /// Receive the next router event
     /// 
     /// Each call to this method subscribes to the broadcast channel,
@@ -226,11 +291,22 @@ impl RouterHandle {
 /// Internal router orchestrating all components
 struct Router {
     command_rx: mpsc::Receiver<RouterCommand>,
+    /// For tasks the router spawns to call back into it, without keeping it alive
+    command_tx: Option<mpsc::WeakSender<RouterCommand>>,
     event_tx: broadcast::Sender<RouterEvent>,
     rpc_protocol: RpcProtocol,
     transport_tx: mpsc::Sender<TransportCommand>,
     session_tx: mpsc::Sender<SessionCommand>,
     onion_tx: Option<mpsc::Sender<OnionCommand>>,
+    /// Without one the onion router can send but not relay circuits
+    onion_key: Option<StaticSecret>,
+    route_table: Arc<RouteTable>,
+    /// Set once run() has started the transport
+    transport: Option<Arc<TransportManager>>,
+    /// Our PeerId as connected peers see it
+    local_peer_id: Option<PeerId>,
+    /// Peers with an open session, the candidate relays for hole punching
+    connected: HashSet<PeerId>,
     /// In-memory peer registry for local (same-process) message delivery
     in_memory_mode: bool,
 }
@@ -241,15 +317,23 @@ impl Router {
         let (session_tx, _session_rx) = mpsc::channel(100);
 
         // Create dummy event channels for now - we'll recreate managers in spawn()
-        let rpc_protocol = RpcProtocol::new(session_tx.clone());
+        let route_table = Arc::new(RouteTable::new().with_events(event_tx.clone()));
+        let rpc_protocol =
+            RpcProtocol::new(session_tx.clone()).with_route_table(route_table.clone());
 
         Router { 
             command_rx, 
+            command_tx: None,
             event_tx, 
             rpc_protocol, 
             transport_tx, 
             session_tx, 
             onion_tx: None,
+            onion_key: None,
+            route_table,
+            transport: None,
+            local_peer_id: None,
+            connected: HashSet::new(),
             in_memory_mode: true, // Enable in-memory delivery for same-process peers
         }
     }
@@ -266,13 +350,15 @@ impl Router {
 
         // Create new transport manager with proper event channel
         let (transport_cmd_tx, mut transport_cmd_rx) = mpsc::channel(100);
-        let transport_manager = TransportManager::new(transport_event_tx.clone());
+        let transport_manager = Arc::new(TransportManager::new(transport_event_tx.clone()));
         self.transport_tx = transport_cmd_tx;
+        self.transport = Some(transport_manager.clone());
 
         // Spawn transport manager task
+        let transport_manager_cmd = transport_manager.clone();
         tokio::spawn(async move {
             while let Some(cmd) = transport_cmd_rx.recv().await {
-                if let Err(e) = transport_manager.handle_command(cmd).await {
+                if let Err(e) = transport_manager_cmd.handle_command(cmd).await {
                     eprintln!("Transport manager error: {}", e);
                 }
             }
@@ -287,6 +373,26 @@ impl Router {
             session_event_tx.clone(),
         ));
         self.session_tx = session_cmd_tx;
+        let local_peer_id = session_manager.local_peer_id();
+        self.local_peer_id = Some(local_peer_id.clone());
+
+        // Take part in hole punching, as relay and as target
+        let command_tx = self.command_tx.clone();
+        let (connect_tx, _connect_task) = serve_connect_requests(
+            local_peer_id,
+            transport_manager,
+            self.route_table.clone(),
+            move |target, params| {
+                rpc_via(command_tx.clone(), target, CONNECT_REQUEST_METHOD, params)
+            },
+        );
+        let _ = self
+            .rpc_protocol
+            .handle_command(RpcCommand::RegisterHandler {
+                method: CONNECT_REQUEST_METHOD.to_string(),
+                handler_tx: connect_tx,
+            })
+            .await;
 
         // Spawn session manager task
         let session_mgr = session_manager.clone();
@@ -309,10 +415,14 @@ impl Router {
         });
 
         // Create and spawn onion router
-        let route_table = Arc::new(RouteTable::new());
+        let route_table = self.route_table.clone();
         let (onion_event_tx, mut onion_event_rx) = mpsc::channel(100);
         let onion_config = OnionConfig::default();
-        let onion_router = Arc::new(OnionRouter::new(onion_config, route_table, onion_event_tx));
+        let mut onion_router = OnionRouter::new(onion_config, route_table, onion_event_tx);
+        if let Some(onion_key) = self.onion_key.take() {
+            onion_router = onion_router.with_onion_key(onion_key);
+        }
+        let onion_router = Arc::new(onion_router);
 
         let (onion_cmd_tx, onion_cmd_rx) = mpsc::channel(100);
         self.onion_tx = Some(onion_cmd_tx);
@@ -330,12 +440,21 @@ impl Router {
                     OnionEvent::PacketForward { next_peer, blob } => {
                         // Forward the onion packet to the next hop
                         let _ = session_tx_onion
-                            .send(SessionCommand::SendPlaintext(next_peer, blob))
+                            .send(SessionCommand::SendOnionPacket(next_peer, blob))
                             .await;
                     }
+                    OnionEvent::CellForward { next_peer, cell } => {
+                        let _ =
+                            session_tx_onion.send(SessionCommand::SendCell(next_peer, cell)).await;
+                    }
                     OnionEvent::DeliverLocal { envelope } => {
-                        // TODO: Deliver to application layer
-                        eprintln!("Onion packet delivered locally: {:?}", envelope);
+                        // We are the exit: hand the payload to its destination as a plain frame
+                        let _ = session_tx_onion
+                            .send(SessionCommand::SendPlaintext(
+                                PeerId::from_bytes(envelope.destination),
+                                envelope.payload,
+                            ))
+                            .await;
                     }
                     OnionEvent::CircuitBuilt { path_length } => {
                         eprintln!("Onion circuit built with {} hops", path_length);
@@ -385,6 +504,44 @@ impl Router {
                     .await
                     .map_err(|e| format!("Failed to send dial command: {}", e))?;
             }
+            RouterCommand::DialPeer { peer_id, response_tx } => {
+                let (Some(transport), Some(local)) =
+                    (self.transport.clone(), self.local_peer_id.clone())
+                else {
+                    let _ = response_tx.send(Err(RpcError::internal_error("router not running")));
+                    return Ok(());
+                };
+                let (peer_tx, peer_rx) = oneshot::channel();
+                self.route_table
+                    .handle_command(RouteTableCommand::GetPeer {
+                        peer_id: peer_id.clone(),
+                        response_tx: peer_tx,
+                    })
+                    .await?;
+                let Some(peer) = peer_rx.await.ok().flatten() else {
+                    let _ = response_tx.send(Err(RpcError::internal_error("unknown peer")));
+                    return Ok(());
+                };
+
+                // Dialing and punching wait on the network, so keep them off the command loop
+                let relays: Vec<PeerId> = self.connected.iter().cloned().collect();
+                let route_table = self.route_table.clone();
+                let command_tx = self.command_tx.clone();
+                tokio::spawn(async move {
+                    let result = nat_traversal::dial_peer(
+                        &transport,
+                        &route_table,
+                        &local,
+                        &peer,
+                        &relays,
+                        |relay, params| {
+                            rpc_via(command_tx.clone(), relay, CONNECT_REQUEST_METHOD, params)
+                        },
+                    )
+                    .await;
+                    let _ = response_tx.send(result);
+                });
+            }
             RouterCommand::SendDirect(peer_id, data) => {
                 if self.in_memory_mode {
                     // In-memory mode: directly emit DataReceived event
@@ -431,7 +588,28 @@ impl Router {
 
     async fn handle_session_event(&mut self, event: SessionEvent) -> Result<(), String> {
         match event.clone() {
-            SessionEvent::Established(peer_id, _conn_id) => {
+            SessionEvent::Established(peer_id, conn_id)
+            | SessionEvent::Resumed(peer_id, conn_id) => {
+                // Quarantined peers get no new sessions until their cooldown ends
+                if self.route_table.is_quarantined(&peer_id).await {
+                    let _ = self.session_tx.send(SessionCommand::CloseSession(peer_id)).await;
+                    return Ok(());
+                }
+
+                // Charge the connection's traffic to the peer for bandwidth limits
+                let (response_tx, response_rx) = oneshot::channel();
+                self.route_table
+                    .handle_command(RouteTableCommand::GetPeer { peer_id: peer_id.clone(), response_tx })
+                    .await?;
+                let class = match response_rx.await {
+                    Ok(Some(peer)) => PeerClass::from_capabilities(&peer.capabilities),
+                    _ => PeerClass::Direct,
+                };
+                let _ = self
+                    .transport_tx
+                    .send(TransportCommand::BindPeer(conn_id, peer_id.0.clone(), class))
+                    .await;
+                self.connected.insert(peer_id.clone());
                 let _ = self.event_tx.send(RouterEvent::PeerConnected(peer_id));
             }
             SessionEvent::PlaintextFrame(peer_id, data) => {
@@ -441,7 +619,24 @@ impl Router {
                     let _ = self.event_tx.send(RouterEvent::DataReceived(peer_id, data));
                 }
             }
+            SessionEvent::CellFrame(from, cell) => {
+                if let Some(ref onion_tx) = self.onion_tx {
+                    let _ = onion_tx.send(OnionCommand::HandleCell { from, cell }).await;
+                }
+            }
+            SessionEvent::OnionPacketFrame(_, encrypted_blob) => {
+                if let Some(ref onion_tx) = self.onion_tx {
+                    let _ = onion_tx.send(OnionCommand::RelayPacket { encrypted_blob }).await;
+                }
+            }
             SessionEvent::Closed(peer_id) => {
+                self.connected.remove(&peer_id);
+                // Circuits through the peer are rebuilt or torn down
+                if let Some(ref onion_tx) = self.onion_tx {
+                    let _ = onion_tx
+                        .send(OnionCommand::PeerDisconnected { peer_id: peer_id.clone() })
+                        .await;
+                }
                 let _ = self.event_tx.send(RouterEvent::PeerDisconnected(peer_id));
             }
         }
@@ -449,6 +644,26 @@ impl Router {
     }
 }
 
+/// RPC call through the router's command loop, from a task the router spawned
+async fn rpc_via(
+    command_tx: Option<mpsc::WeakSender<RouterCommand>>,
+    peer_id: PeerId,
+    method: &str,
+    params: JsonValue,
+) -> Result<JsonValue, RpcError> {
+    let command_tx = command_tx
+        .and_then(|tx| tx.upgrade())
+        .ok_or_else(|| RpcError::internal_error("router stopped"))?;
+    let (response_tx, response_rx) = oneshot::channel();
+    command_tx
+        .send(RouterCommand::RpcCall { peer_id, method: method.to_string(), params, response_tx })
+        .await
+        .map_err(|e| RpcError::internal_error(&format!("Failed to send RPC command: {}", e)))?;
+    response_rx
+        .await
+        .map_err(|e| RpcError::internal_error(&format!("RPC response channel closed: {}", e)))?
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -565,6 +780,17 @@ mod tests {
         handle.shutdown().await.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_router_dial_unknown_peer() {
+        let (handle, _router_task) = RouterHandle::new();
+
+        // Only peers in the route table have addresses to dial or punch towards
+        let result = handle.dial_peer(PeerId::from_bytes(vec![42])).await;
+        assert!(result.unwrap_err().message.contains("unknown peer"));
+
+        handle.shutdown().await.unwrap();
+    }
+
     #[tokio::test]
     async fn test_router_multiple_commands() {
         let (handle, _router_task) = RouterHandle::new();
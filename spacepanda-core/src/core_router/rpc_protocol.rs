@@ -38,19 +38,38 @@
         }
     }
     ```
+
+    Blob transfer:
+
+    Content-addressed blobs (attachment chunks) are not stored in the DHT; the DHT only
+    says who provides them. `fetch_blob` pulls a blob straight from a provider with the
+    `fetch_blob` method, one BLOB_SLICE_SIZE slice per request so every frame stays under
+    MAX_FRAME_SIZE, and checks the result against its BLAKE3 hash. Providers answer with
+    `serve_blobs` registered as the method's handler.
+
+    Reputation:
+
+    Given the route table, every call and frame feeds the peer's reputation: answered calls
+    and their latency count for the peer, timeouts, rate limit violations and oversized
+    frames against it. Frames that aren't RPC messages at all are left alone, since the
+    router passes those on as application data.
 */
 
 use hashlink::LruCache;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::future::Future;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::{mpsc, oneshot, Mutex};
+use tokio::task::JoinHandle;
 use tracing::{debug, instrument, trace, warn};
 use uuid::Uuid;
 
 use super::metrics;
 use super::rate_limiter::{RateLimitResult, RateLimiter, RateLimiterConfig};
+use super::reputation::Behaviour;
+use super::route_table::RouteTable;
 use super::session_manager::{PeerId, SessionCommand, SessionEvent};
 
 /// RPC message types
@@ -94,6 +113,14 @@ impl RpcError {
     pub fn circuit_breaker_open() -> Self {
         RpcError::new(ERR_CIRCUIT_BREAKER, "Circuit breaker open".to_string())
     }
+
+    pub fn invalid_params(msg: &str) -> Self {
+        RpcError::new(ERR_INVALID_PARAMS, format!("Invalid params: {}", msg))
+    }
+
+    pub fn blob_not_found() -> Self {
+        RpcError::new(ERR_BLOB_NOT_FOUND, "Blob not found".to_string())
+    }
 }
 
 /// Commands sent to RpcProtocol
@@ -125,6 +152,9 @@ struct PendingRequest {
     response_tx: oneshot::Sender<Result<serde_json::Value, RpcError>>,
     /// Handle to abort the timeout task if response arrives
     timeout_handle: tokio::task::AbortHandle,
+    /// Peer the request went to, and when
+    peer_id: PeerId,
+    sent_at: Instant,
 }
 
 pub struct RpcProtocol {
@@ -140,6 +170,8 @@ pub struct RpcProtocol {
     session_tx: mpsc::Sender<SessionCommand>,
     /// Default timeout for RPC calls
     default_timeout: Duration,
+    /// Where peer behaviour is recorded, if anywhere
+    route_table: Option<Arc<RouteTable>>,
 }
 
 /// Maximum frame size to prevent memory exhaustion DoS (64 KiB)
@@ -152,6 +184,130 @@ const ERR_TIMEOUT: i32 = -32000;
 const ERR_DUPLICATE_REQUEST: i32 = -32600;
 const ERR_RATE_LIMITED: i32 = -32001;
 const ERR_CIRCUIT_BREAKER: i32 = -32002;
+const ERR_INVALID_PARAMS: i32 = -32602;
+const ERR_BLOB_NOT_FOUND: i32 = -32003;
+
+/// Method name of the blob transfer RPC
+pub const FETCH_BLOB_METHOD: &str = "fetch_blob";
+
+/// Blob bytes per `fetch_blob` response (hex-encoded, so the frame stays under 64 KiB)
+pub const BLOB_SLICE_SIZE: usize = 16 * 1024;
+
+/// Params of `fetch_blob`: the slice of blob `hash` starting at `offset`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FetchBlob {
+    /// BLAKE3 hash of the blob
+    #[serde(with = "hex_bytes")]
+    pub hash: [u8; 32],
+    /// Byte offset of the slice
+    pub offset: u64,
+}
+
+/// Result of `fetch_blob`: up to BLOB_SLICE_SIZE bytes and the blob's full length
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlobSlice {
+    #[serde(with = "hex_bytes")]
+    pub data: Vec<u8>,
+    pub total_len: u64,
+}
+
+/// Hex encoding for byte fields, far denser than serde_json's number arrays
+mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer, T: AsRef<[u8]>>(bytes: &T, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, T: hex::FromHex>(d: D) -> Result<T, D::Error> {
+        let s = String::deserialize(d)?;
+        T::from_hex(s).map_err(|_| serde::de::Error::custom("invalid hex"))
+    }
+}
+
+/// Blobs a node can serve over `fetch_blob`
+pub trait BlobSource: Send + Sync {
+    /// The blob whose BLAKE3 hash is `hash`, if held
+    fn blob(&self, hash: &[u8; 32]) -> Option<Arc<[u8]>>;
+}
+
+/// Answer `fetch_blob` requests from `source`
+///
+/// Register the returned sender as the handler for FETCH_BLOB_METHOD. The task ends
+/// when the handler is unregistered and every sender dropped.
+pub fn serve_blobs(source: Arc<dyn BlobSource>) -> (mpsc::Sender<RpcRequest>, JoinHandle<()>) {
+    let (handler_tx, mut handler_rx) = mpsc::channel::<RpcRequest>(100);
+    let task = tokio::spawn(async move {
+        while let Some(request) = handler_rx.recv().await {
+            let result = serde_json::from_value::<FetchBlob>(request.params)
+                .map_err(|e| RpcError::invalid_params(&e.to_string()))
+                .and_then(|params| {
+                    let blob = source.blob(&params.hash).ok_or_else(RpcError::blob_not_found)?;
+                    let start = usize::try_from(params.offset)
+                        .ok()
+                        .filter(|start| *start <= blob.len())
+                        .ok_or_else(|| RpcError::invalid_params("offset past end of blob"))?;
+                    let end = blob.len().min(start + BLOB_SLICE_SIZE);
+                    let slice =
+                        BlobSlice { data: blob[start..end].to_vec(), total_len: blob.len() as u64 };
+                    serde_json::to_value(slice)
+                        .map_err(|e| RpcError::internal_error(&e.to_string()))
+                });
+            let _ = request.response_tx.send(result);
+        }
+    });
+    (handler_tx, task)
+}
+
+/// Fetch the blob hashed to `hash` slice by slice through `call`
+///
+/// `call` sends `fetch_blob` params to the provider and returns its result. Blobs
+/// longer than `max_len` are refused, and the assembled blob must match `hash`.
+pub async fn fetch_blob<F, Fut>(
+    hash: [u8; 32],
+    max_len: u64,
+    mut call: F,
+) -> Result<Vec<u8>, RpcError>
+where
+    F: FnMut(serde_json::Value) -> Fut,
+    Fut: Future<Output = Result<serde_json::Value, RpcError>>,
+{
+    let mut blob: Vec<u8> = Vec::new();
+    let mut total_len = None;
+
+    loop {
+        let params = serde_json::to_value(FetchBlob { hash, offset: blob.len() as u64 })
+            .map_err(|e| RpcError::internal_error(&e.to_string()))?;
+        let slice: BlobSlice = serde_json::from_value(call(params).await?)
+            .map_err(|e| RpcError::internal_error(&format!("bad blob slice: {}", e)))?;
+
+        let expected = *total_len.get_or_insert(slice.total_len);
+        if expected != slice.total_len || expected > max_len {
+            return Err(RpcError::internal_error("provider sent an inconsistent blob length"));
+        }
+        if blob.is_empty() {
+            blob.reserve_exact(expected as usize);
+        }
+        if slice.data.len() > BLOB_SLICE_SIZE
+            || blob.len() as u64 + slice.data.len() as u64 > expected
+        {
+            return Err(RpcError::internal_error("provider sent an oversized blob slice"));
+        }
+        if slice.data.is_empty() && (blob.len() as u64) < expected {
+            return Err(RpcError::internal_error("provider sent an empty blob slice"));
+        }
+        blob.extend_from_slice(&slice.data);
+
+        if blob.len() as u64 == expected {
+            break;
+        }
+    }
+
+    if blake3::hash(&blob).as_bytes() != &hash {
+        return Err(RpcError::internal_error("blob doesn't match its hash"));
+    }
+    Ok(blob)
+}
 
 impl RpcProtocol {
     /// Create a new RPC protocol handler with default settings
@@ -205,6 +361,7 @@ impl RpcProtocol {
             rate_limiter: Arc::new(RateLimiter::new_with_config(rate_limiter_config)),
             session_tx,
             default_timeout,
+            route_table: None,
         }
     }
 
@@ -214,6 +371,18 @@ impl RpcProtocol {
         self
     }
 
+    /// Record how peers behave in `route_table`, towards their reputation
+    pub fn with_route_table(mut self, route_table: Arc<RouteTable>) -> Self {
+        self.route_table = Some(route_table);
+        self
+    }
+
+    async fn record(&self, peer_id: &PeerId, behaviour: Behaviour) {
+        if let Some(route_table) = &self.route_table {
+            route_table.record_behaviour(peer_id.clone(), behaviour).await;
+        }
+    }
+
     /// Get the number of seen requests (for testing)
     #[cfg(test)]
     pub async fn seen_requests_count(&self) -> usize {
@@ -226,7 +395,11 @@ impl RpcProtocol {
             SessionEvent::PlaintextFrame(peer_id, bytes) => {
                 self.handle_frame(peer_id, bytes).await?;
             }
-            SessionEvent::Established(_, _) | SessionEvent::Closed(_) => {
+            SessionEvent::Established(_, _)
+            | SessionEvent::Resumed(_, _)
+            | SessionEvent::CellFrame(_, _)
+            | SessionEvent::OnionPacketFrame(_, _)
+            | SessionEvent::Closed(_) => {
                 // These events are informational for RPC layer
             }
         }
@@ -269,12 +442,16 @@ impl RpcProtocol {
         let pending_requests = self.pending_requests.clone();
         let timeout = self.default_timeout;
         let request_id_for_timeout = request_id.clone();
+        let route_table = self.route_table.clone();
 
         let timeout_task = tokio::spawn(async move {
             tokio::time::sleep(timeout).await;
             // Only send timeout error if request is still pending
             if let Some(pending) = pending_requests.lock().await.remove(&request_id_for_timeout) {
                 warn!(request_id = %request_id_for_timeout, method = %method_clone, timeout_ms = timeout.as_millis(), "Request timeout");
+                if let Some(route_table) = route_table {
+                    route_table.record_behaviour(pending.peer_id, Behaviour::RpcFailed).await;
+                }
                 let _ = pending.response_tx.send(Err(RpcError::timeout()));
             }
         });
@@ -282,7 +459,12 @@ impl RpcProtocol {
         let timeout_handle = timeout_task.abort_handle();
 
         // Store pending request with timeout handle
-        let pending = PendingRequest { response_tx, timeout_handle };
+        let pending = PendingRequest {
+            response_tx,
+            timeout_handle,
+            peer_id: peer_id.clone(),
+            sent_at: Instant::now(),
+        };
         self.pending_requests.lock().await.insert(request_id.clone(), pending);
 
         // Send via session manager
@@ -308,6 +490,7 @@ impl RpcProtocol {
                 // Rate limit exceeded, reject request
                 warn!("Request rejected: rate limit exceeded");
                 metrics::rpc_request_rate_limited();
+                self.record(&peer_id, Behaviour::RateLimited).await;
                 return Err(format!("Rate limit exceeded for peer {:?}", peer_id));
             }
             RateLimitResult::CircuitBreakerOpen => {
@@ -322,6 +505,7 @@ impl RpcProtocol {
         if bytes.len() > MAX_FRAME_SIZE {
             warn!(frame_size = bytes.len(), max_size = MAX_FRAME_SIZE, "Oversized frame rejected");
             metrics::oversized_frame_rejected(bytes.len());
+            self.record(&peer_id, Behaviour::InvalidMessage).await;
             return Err(format!("Frame too large: {} bytes (max {})", bytes.len(), MAX_FRAME_SIZE));
         }
 
@@ -459,6 +643,8 @@ impl RpcProtocol {
             // Abort the timeout task since response arrived
             pending.timeout_handle.abort();
             trace!("Response received, timeout cancelled");
+            let latency = Some(pending.sent_at.elapsed());
+            self.record(&pending.peer_id, Behaviour::RpcSucceeded { latency }).await;
 
             // Send response to caller
             let _ = pending.response_tx.send(result);
@@ -1110,6 +1296,7 @@ mod tests {
             burst_size: 5000,
             circuit_breaker_threshold: 10000,
             circuit_breaker_timeout: Duration::from_secs(60),
+            ..RateLimiterConfig::default()
         };
 
         let rpc = Arc::new(RpcProtocol::new_with_rate_limiting(
@@ -1211,6 +1398,7 @@ mod tests {
             burst_size: 5,                  // Only allow 5 requests initially
             circuit_breaker_threshold: 100, // High threshold to not interfere
             circuit_breaker_timeout: Duration::from_secs(60),
+            ..RateLimiterConfig::default()
         };
 
         let rpc = RpcProtocol::new_with_rate_limiting(
@@ -1262,6 +1450,7 @@ mod tests {
             burst_size: 1000,
             circuit_breaker_threshold: 3, // Open after 3 failures
             circuit_breaker_timeout: Duration::from_secs(60),
+            ..RateLimiterConfig::default()
         };
 
         let rpc = Arc::new(RpcProtocol::new_with_rate_limiting(
@@ -1333,6 +1522,7 @@ mod tests {
             burst_size: 3,
             circuit_breaker_threshold: 100,
             circuit_breaker_timeout: Duration::from_secs(60),
+            ..RateLimiterConfig::default()
         };
 
         let rpc = RpcProtocol::new_with_rate_limiting(
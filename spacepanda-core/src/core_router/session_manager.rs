@@ -9,16 +9,40 @@
   2. When handshake succedes: derive AEAD keys and create Session object.
   3. For sending: encrypt plaintext into AEAD ciphertext => hand to transport_manager.rs as Send.
   4. For receiving: decrypt AEAD ciphertext and emit PlaintextFrame(peer_id, bytes) to routing core.
+  5. On Authenticated(conn_id, peer_id): the transport (QUIC) already authenticated and encrypts
+     the connection, so the session is established at once and frames pass through as-is.
+
+  Resumption:
+  Every completed Noise handshake leaves both sides holding the same session ticket, keyed by
+  the other's PeerId: a secret derived from the handshake hash and both static keys, and an
+  ID derived from the secret. Dialing an address we hold a ticket for sends the ticket ID and
+  the first message of a Noise_KKpsk0 handshake, which mixes in the secret as a PSK. It takes
+  one round trip instead of three messages and no static keys cross the wire. KK also does DH
+  with both static keys, so a stolen ticket is useless without the identity key it was issued
+  to. The listener rejects unknown, expired or forged tickets, and the dialer falls back to a
+  full handshake on the same connection. Each resumption rotates the ticket (so each ID works
+  once), but the rotated ticket keeps the expiry of the full handshake that first issued it.
+
+  Framing:
+  Every session frame starts with a one-byte FrameType header, inside the AEAD payload (or the
+  QUIC stream) so it is authenticated with the rest of the frame: Data for application bytes,
+  Cell for onion circuit cells and OnionPacket for onion packets, so a relay can hand onion
+  traffic to its onion router instead of the application. Frames of an unknown type are dropped.
 
   Inputs:
     - TransportEvent::Data(conn_id, bytes) (handshake frames/incoming AEAD frames)
     - TransportEvent::Connected(conn_id, remote_addr)
     - TransportEvent::Disconnected(conn_id)
-    - Commands: SessionSend(peer_id, plaintext)
+    - TransportEvent::Authenticated(conn_id, remote_addr, peer_id)
+    - Commands: SendPlaintext(peer_id, plaintext), SendCell(peer_id, cell),
+      SendOnionPacket(peer_id, blob)
 
   Outputs:
     - SessionEvent::PlaintextFrame(peer_id, bytes) when a full decrypting and routing.
+    - SessionEvent::CellFrame(peer_id, cell) and SessionEvent::OnionPacketFrame(peer_id, blob)
+      for onion traffic.
     - SessionEvent::Established(peer_id, conn_id) for routing table.
+    - SessionEvent::Resumed(peer_id, conn_id) instead, when a session ticket was used.
     - SessionEvent::Closed(peer_id) when session is closed.
 
   Notes:
@@ -65,8 +89,10 @@
 */
 
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 use snow::{Builder, HandshakeState, TransportState};
 use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::{mpsc, Mutex};
@@ -93,6 +119,49 @@ const NONCE_WINDOW_SECS: u64 = 60;
 /// Maximum number of nonces to track per connection
 const MAX_NONCES_PER_CONN: usize = 100;
 
+/// Noise pattern for resuming from a session ticket: both static keys are already known and
+/// the ticket's secret is mixed in as a PSK
+const RESUME_PATTERN: &str = "Noise_KKpsk0_25519_ChaChaPoly_BLAKE2s";
+
+/// Prefix of resumption requests (followed by the ticket ID) and rejections
+const RESUME_MAGIC: &[u8; 4] = b"SPRS";
+
+/// The listener's answer to a ticket it won't accept
+const RESUME_REJECTED: &[u8; 5] = b"SPRS\0";
+
+/// Length of the ticket ID a dialer presents in the clear
+const TICKET_ID_LEN: usize = 16;
+
+/// Shortest resumption request: magic, ticket ID, ephemeral key, nonce and AEAD tag
+/// A full handshake's first message is 40 bytes, so the two can't be confused.
+const RESUME_REQUEST_MIN_LEN: usize = RESUME_MAGIC.len() + TICKET_ID_LEN + 32 + 8 + 16;
+
+/// How long a ticket stays usable after the full handshake that issued it (24 hours)
+const DEFAULT_TICKET_LIFETIME: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Type of a session frame, sent as its first byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum FrameType {
+    /// Application data
+    Data = 0,
+    /// Onion circuit cell
+    Cell = 1,
+    /// Onion packet to relay
+    OnionPacket = 2,
+}
+
+impl FrameType {
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(FrameType::Data),
+            1 => Some(FrameType::Cell),
+            2 => Some(FrameType::OnionPacket),
+            _ => None,
+        }
+    }
+}
+
 /// Peer identity derived from Noise static public key
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct PeerId(pub Vec<u8>);
@@ -107,11 +176,31 @@ impl PeerId {
     }
 }
 
+/// Event for a frame received from a peer, by its type header
+fn frame_event(peer_id: PeerId, mut frame: Vec<u8>) -> Result<SessionEvent, String> {
+    let frame_type = match frame.first() {
+        Some(&byte) => FrameType::from_byte(byte)
+            .ok_or_else(|| format!("Unknown frame type {} from peer", byte))?,
+        None => return Err("Empty session frame".to_string()),
+    };
+    let payload = frame.split_off(1);
+
+    Ok(match frame_type {
+        FrameType::Data => SessionEvent::PlaintextFrame(peer_id, payload),
+        FrameType::Cell => SessionEvent::CellFrame(peer_id, payload),
+        FrameType::OnionPacket => SessionEvent::OnionPacketFrame(peer_id, payload),
+    })
+}
+
 /// Commands sent to SessionManager
 #[derive(Debug)]
 pub enum SessionCommand {
     /// Send plaintext data to a peer
     SendPlaintext(PeerId, Vec<u8>),
+    /// Send an onion circuit cell to a neighbour
+    SendCell(PeerId, Vec<u8>),
+    /// Send an onion packet to the next hop
+    SendOnionPacket(PeerId, Vec<u8>),
     /// Close a session with a peer
     CloseSession(PeerId),
 }
@@ -121,8 +210,14 @@ pub enum SessionCommand {
 pub enum SessionEvent {
     /// Session successfully established with a peer
     Established(PeerId, u64), // peer_id, conn_id
+    /// Session established from a session ticket, without a full handshake
+    Resumed(PeerId, u64), // peer_id, conn_id
     /// Received plaintext data from a peer
     PlaintextFrame(PeerId, Vec<u8>),
+    /// Received an onion circuit cell from a neighbour
+    CellFrame(PeerId, Vec<u8>),
+    /// Received an onion packet to relay
+    OnionPacketFrame(PeerId, Vec<u8>),
     /// Session closed
     Closed(PeerId),
 }
@@ -171,8 +266,55 @@ impl HandshakeMetadata {
 enum SessionState {
     /// Handshake in progress
     Handshaking(HandshakeState, HandshakeMetadata),
+    /// Abbreviated handshake from a session ticket, with the ticket's expiry
+    Resuming(HandshakeState, HandshakeMetadata, u64),
     /// Handshake complete, ready for encrypted communication
     Established(TransportState, PeerId),
+    /// Authenticated and encrypted by the transport itself; frames pass through
+    Secured(PeerId),
+}
+
+/// Resumption ticket shared with a peer after a handshake
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SessionTicket {
+    /// Presented in the clear by the dialer so the listener can find the ticket
+    id: Vec<u8>,
+    secret: [u8; 32],
+    /// Unix time after which the ticket is no longer accepted
+    expires_at: u64,
+    /// Where we dialed the peer, so that dialing it there again resumes
+    addr: Option<String>,
+}
+
+impl SessionTicket {
+    /// Ticket issued by a completed handshake, bound to both sides' static keys
+    fn derive(
+        handshake_hash: &[u8],
+        initiator_static: &[u8],
+        responder_static: &[u8],
+        expires_at: u64,
+        addr: Option<String>,
+    ) -> Self {
+        let mut material = handshake_hash.to_vec();
+        material.extend_from_slice(initiator_static);
+        material.extend_from_slice(responder_static);
+        let secret = blake3::derive_key("spacepanda session ticket secret v1", &material);
+        let id = blake3::derive_key("spacepanda session ticket id v1", &secret);
+        SessionTicket { id: id[..TICKET_ID_LEN].to_vec(), secret, expires_at, addr }
+    }
+
+    fn is_expired(&self) -> bool {
+        self.expires_at <= current_timestamp()
+    }
+}
+
+/// A ticket as saved in the ticket store
+#[derive(Serialize, Deserialize)]
+struct SavedTicket {
+    /// Hex-encoded PeerId
+    peer_id: String,
+    #[serde(flatten)]
+    ticket: SessionTicket,
 }
 
 /// A session with encryption and authentication
@@ -188,6 +330,10 @@ pub struct SessionManager {
     static_keypair: Vec<u8>,                     // Our long-term identity key
     transport_tx: mpsc::Sender<TransportCommand>,
     event_tx: mpsc::Sender<SessionEvent>,
+    tickets: Arc<Mutex<HashMap<PeerId, SessionTicket>>>,
+    ticket_store: Option<PathBuf>,
+    ticket_lifetime: Duration,
+    dialed: Arc<Mutex<HashMap<u64, String>>>, // conn_id -> address, for connections we dialed
 }
 
 impl SessionManager {
@@ -203,9 +349,54 @@ impl SessionManager {
             static_keypair,
             transport_tx,
             event_tx,
+            tickets: Arc::new(Mutex::new(HashMap::new())),
+            ticket_store: None,
+            ticket_lifetime: DEFAULT_TICKET_LIFETIME,
+            dialed: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
+    /// Keep session tickets in a file, so reconnects after a restart can resume
+    ///
+    /// Loads the tickets already in the file, skipping expired ones.
+    pub fn with_ticket_store(mut self, path: PathBuf) -> Result<Self, String> {
+        let mut tickets = HashMap::new();
+        match std::fs::read(&path) {
+            Ok(bytes) => {
+                let saved: Vec<SavedTicket> = serde_json::from_slice(&bytes)
+                    .map_err(|e| format!("Invalid ticket store {}: {}", path.display(), e))?;
+                for entry in saved {
+                    let peer_id = hex::decode(&entry.peer_id).map_err(|e| {
+                        format!("Invalid peer in ticket store {}: {}", path.display(), e)
+                    })?;
+                    if !entry.ticket.is_expired() {
+                        tickets.insert(PeerId::from_bytes(peer_id), entry.ticket);
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        }
+        self.tickets = Arc::new(Mutex::new(tickets));
+        self.ticket_store = Some(path);
+        Ok(self)
+    }
+
+    /// Set how long after a full handshake its session ticket can be used
+    pub fn with_ticket_lifetime(mut self, lifetime: Duration) -> Self {
+        self.ticket_lifetime = lifetime;
+        self
+    }
+
+    /// Our PeerId as the other side of a Noise session sees it: the static public key
+    pub fn local_peer_id(&self) -> PeerId {
+        let mut private = [0u8; 32];
+        let len = self.static_keypair.len().min(32);
+        private[..len].copy_from_slice(&self.static_keypair[..len]);
+        let public = x25519_dalek::PublicKey::from(&x25519_dalek::StaticSecret::from(private));
+        PeerId::from_bytes(public.as_bytes().to_vec())
+    }
+
     /// Generate a new static keypair for testing
     pub fn generate_keypair() -> Vec<u8> {
         let builder = Builder::new(
@@ -220,11 +411,14 @@ impl SessionManager {
     /// Handle incoming transport events
     pub async fn handle_transport_event(&self, event: TransportEvent) -> Result<(), String> {
         match event {
-            TransportEvent::Connected(conn_id, _addr, is_outgoing) => {
+            TransportEvent::Connected(conn_id, addr, is_outgoing) => {
                 // Only initiate handshake if we dialed (outgoing connection)
                 // For incoming connections, wait for first data to create responder
                 if is_outgoing {
-                    self.initiate_handshake(conn_id).await?;
+                    self.dialed.lock().await.insert(conn_id, addr.clone());
+                    if !self.initiate_resumption(conn_id, &addr).await? {
+                        self.initiate_handshake(conn_id).await?;
+                    }
                 }
             }
             TransportEvent::Data(conn_id, bytes) => {
@@ -233,6 +427,9 @@ impl SessionManager {
             TransportEvent::Disconnected(conn_id) => {
                 self.handle_disconnect(conn_id).await?;
             }
+            TransportEvent::Authenticated(conn_id, _addr, peer_id) => {
+                self.accept_secured(conn_id, PeerId::from_bytes(peer_id)).await?;
+            }
         }
         Ok(())
     }
@@ -241,7 +438,13 @@ impl SessionManager {
     pub async fn handle_command(&self, command: SessionCommand) -> Result<(), String> {
         match command {
             SessionCommand::SendPlaintext(peer_id, plaintext) => {
-                self.send_plaintext(peer_id, plaintext).await?;
+                self.send_frame(peer_id, FrameType::Data, plaintext).await?;
+            }
+            SessionCommand::SendCell(peer_id, cell) => {
+                self.send_frame(peer_id, FrameType::Cell, cell).await?;
+            }
+            SessionCommand::SendOnionPacket(peer_id, blob) => {
+                self.send_frame(peer_id, FrameType::OnionPacket, blob).await?;
             }
             SessionCommand::CloseSession(peer_id) => {
                 self.close_session(peer_id).await?;
@@ -250,37 +453,288 @@ impl SessionManager {
         Ok(())
     }
 
-    /// Initiate Noise handshake for a new connection
-    async fn initiate_handshake(&self, conn_id: u64) -> Result<(), String> {
-        eprintln!("[INITIATOR] Initiating handshake for conn_id={}", conn_id);
+    /// Establish a session on a connection the transport already authenticated
+    async fn accept_secured(&self, conn_id: u64, peer_id: PeerId) -> Result<(), String> {
+        let session = Session { conn_id, state: SessionState::Secured(peer_id.clone()) };
+        self.sessions.lock().await.insert(conn_id, session);
+        self.peer_to_conn.lock().await.insert(peer_id.clone(), conn_id);
+
+        self.event_tx
+            .send(SessionEvent::Established(peer_id, conn_id))
+            .await
+            .map_err(|e| format!("Failed to send event: {}", e))
+    }
+
+    /// Switch a finished handshake to transport mode and emit Established, or Resumed
+    /// when the handshake came from a session ticket
+    async fn complete_handshake(&self, conn_id: u64) -> Result<(), String> {
+        eprintln!("[HANDSHAKING] conn_id={} COMPLETE! Transitioning to Established", conn_id);
+
+        // Remove the session from the map to take ownership
+        let mut sessions = self.sessions.lock().await;
+        let mut session = sessions.remove(&conn_id).ok_or("Session disappeared")?;
+
+        // A resumption keeps the expiry of the ticket it used
+        let (hs, ticket_expiry) = match session.state {
+            SessionState::Handshaking(hs, _) => (hs, None),
+            SessionState::Resuming(hs, _, expires_at) => (hs, Some(expires_at)),
+            state => {
+                session.state = state;
+                sessions.insert(conn_id, session);
+                return Err("Session is not handshaking".to_string());
+            }
+        };
+
+        // Extract peer's static public key
+        let Some(remote_static) = hs.get_remote_static().map(|key| key.to_vec()) else {
+            eprintln!("[ERROR] conn_id={} No remote static key!", conn_id);
+            return Err("No remote static key".to_string());
+        };
+        eprintln!("[HANDSHAKING] conn_id={} got remote static key", conn_id);
+        let peer_id = PeerId::from_bytes(remote_static);
+        let handshake_hash = hs.get_handshake_hash().to_vec();
+        let is_initiator = hs.is_initiator();
+
+        eprintln!("[HANDSHAKING] conn_id={} converting to transport mode", conn_id);
+
+        // Take the handshake and convert to transport mode
+        let transport = hs.into_transport_mode().map_err(|e| {
+            eprintln!("[ERROR] conn_id={} failed to enter transport mode: {}", conn_id, e);
+            format!("Failed to enter transport mode: {}", e)
+        })?;
+        session.state = SessionState::Established(transport, peer_id.clone());
+        eprintln!("[HANDSHAKING] conn_id={} state set to Established", conn_id);
+
+        // Put the session back in the map
+        sessions.insert(conn_id, session);
+
+        // Update peer mapping
+        drop(sessions);
+        self.peer_to_conn.lock().await.insert(peer_id.clone(), conn_id);
+        self.issue_ticket(conn_id, &peer_id, &handshake_hash, is_initiator, ticket_expiry)
+            .await;
+
+        eprintln!("[ESTABLISHED] conn_id={} -> peer_id={:?}", conn_id, peer_id);
+
+        // Emit Established (or Resumed) event
+        let event = if ticket_expiry.is_some() {
+            metrics::session_established("resumed");
+            SessionEvent::Resumed(peer_id, conn_id)
+        } else {
+            metrics::session_established("full");
+            SessionEvent::Established(peer_id, conn_id)
+        };
+        self.event_tx
+            .send(event)
+            .await
+            .map_err(|e| format!("Failed to send event: {}", e))?;
+        Ok(())
+    }
+
+    /// Our static public key, which peers know us by
+    fn local_static(&self) -> Vec<u8> {
+        match <[u8; 32]>::try_from(self.static_keypair.as_slice()) {
+            Ok(private) => {
+                let secret = x25519_dalek::StaticSecret::from(private);
+                x25519_dalek::PublicKey::from(&secret).as_bytes().to_vec()
+            }
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Replace the peer's session ticket with one derived from the handshake just completed
+    ///
+    /// `expires_at` is the expiry of the ticket a resumption used; a full handshake starts a
+    /// new lifetime.
+    async fn issue_ticket(
+        &self,
+        conn_id: u64,
+        peer_id: &PeerId,
+        handshake_hash: &[u8],
+        is_initiator: bool,
+        expires_at: Option<u64>,
+    ) {
+        let local_static = self.local_static();
+        let (initiator_static, responder_static) = if is_initiator {
+            (local_static.as_slice(), peer_id.as_bytes())
+        } else {
+            (peer_id.as_bytes(), local_static.as_slice())
+        };
+        let expires_at =
+            expires_at.unwrap_or_else(|| current_timestamp() + self.ticket_lifetime.as_secs());
+        let dialed = self.dialed.lock().await.remove(&conn_id);
+
+        let mut tickets = self.tickets.lock().await;
+        let addr = dialed.or_else(|| tickets.get(peer_id).and_then(|t| t.addr.clone()));
+        let ticket = SessionTicket::derive(
+            handshake_hash,
+            initiator_static,
+            responder_static,
+            expires_at,
+            addr,
+        );
+        tickets.insert(peer_id.clone(), ticket);
+        self.save_tickets(&tickets);
+    }
+
+    /// Write tickets to the ticket store, if there is one
+    fn save_tickets(&self, tickets: &HashMap<PeerId, SessionTicket>) {
+        let Some(path) = &self.ticket_store else {
+            return;
+        };
+        let saved: Vec<SavedTicket> = tickets
+            .iter()
+            .filter(|(_, ticket)| !ticket.is_expired())
+            .map(|(peer_id, ticket)| SavedTicket {
+                peer_id: hex::encode(peer_id.as_bytes()),
+                ticket: ticket.clone(),
+            })
+            .collect();
+        let result = serde_json::to_vec(&saved)
+            .map_err(|e| format!("Failed to encode session tickets: {}", e))
+            .and_then(|bytes| {
+                // Write then rename, so a crash mid-write leaves the previous tickets intact
+                let tmp = path.with_extension("tmp");
+                std::fs::write(&tmp, bytes)
+                    .and_then(|_| std::fs::rename(&tmp, path))
+                    .map_err(|e| format!("Failed to save tickets to {}: {}", path.display(), e))
+            });
+        if let Err(e) = result {
+            eprintln!("{}", e);
+        }
+    }
+
+    /// Drop the ticket for a peer, so the next connection does a full handshake
+    async fn forget_ticket(&self, peer_id: &PeerId) {
+        let mut tickets = self.tickets.lock().await;
+        if tickets.remove(peer_id).is_some() {
+            self.save_tickets(&tickets);
+        }
+    }
+
+    /// Start an abbreviated handshake on conn_id if we hold a ticket for the peer at addr
+    ///
+    /// Returns false, having sent nothing, when there is no usable ticket.
+    async fn initiate_resumption(&self, conn_id: u64, addr: &str) -> Result<bool, String> {
+        let found = self
+            .tickets
+            .lock()
+            .await
+            .iter()
+            .find(|(_, ticket)| ticket.addr.as_deref() == Some(addr) && !ticket.is_expired())
+            .map(|(peer_id, ticket)| (peer_id.clone(), ticket.clone()));
+        let Some((peer_id, ticket)) = found else {
+            return Ok(false);
+        };
 
-        // Build Noise handshake state (initiator role)
         let builder = Builder::new(
-            NOISE_PATTERN
+            RESUME_PATTERN
                 .parse()
                 .expect("Invalid noise pattern - this is a programming error"),
         );
-        let builder = builder.local_private_key(&self.static_keypair);
-
         let mut handshake = builder
+            .local_private_key(&self.static_keypair)
+            .remote_public_key(peer_id.as_bytes())
+            .psk(0, &ticket.secret)
             .build_initiator()
-            .map_err(|e| format!("Failed to build initiator: {}", e))?;
+            .map_err(|e| format!("Failed to build resuming initiator: {}", e))?;
 
-        // Create handshake metadata with nonce and timestamp
         let metadata = HandshakeMetadata::new();
-
-        // Send first handshake message with nonce
         let mut buffer = vec![0u8; 1024];
-        let nonce_bytes = metadata.nonce.to_le_bytes();
         let len = handshake
-            .write_message(&nonce_bytes, &mut buffer)
-            .map_err(|e| format!("Failed to write handshake: {}", e))?;
+            .write_message(&metadata.nonce.to_le_bytes(), &mut buffer)
+            .map_err(|e| format!("Failed to write resumption request: {}", e))?;
+        let mut request = RESUME_MAGIC.to_vec();
+        request.extend_from_slice(&ticket.id);
+        request.extend_from_slice(&buffer[..len]);
 
-        // Store handshake state with metadata
-        let session = Session { conn_id, state: SessionState::Handshaking(handshake, metadata) };
-        self.sessions.lock().await.insert(conn_id, session);
+        let state = SessionState::Resuming(handshake, metadata, ticket.expires_at);
+        self.sessions.lock().await.insert(conn_id, Session { conn_id, state });
+        self.spawn_handshake_timeout(conn_id);
 
-        // Spawn timeout task to cleanup stalled handshakes
+        self.transport_tx
+            .send(TransportCommand::Send(conn_id, request))
+            .await
+            .map_err(|e| format!("Failed to send resumption request: {}", e))?;
+        Ok(true)
+    }
+
+    /// Answer a dialer resuming from a ticket, or tell it to fall back to a full handshake
+    async fn accept_resumption(&self, conn_id: u64, request: Vec<u8>) -> Result<(), String> {
+        let (id, message) = request[RESUME_MAGIC.len()..].split_at(TICKET_ID_LEN);
+
+        match self.read_resumption(id, message).await {
+            Ok((handshake, metadata, expires_at, response)) => {
+                let state = SessionState::Resuming(handshake, metadata, expires_at);
+                self.sessions.lock().await.insert(conn_id, Session { conn_id, state });
+                self.transport_tx
+                    .send(TransportCommand::Send(conn_id, response))
+                    .await
+                    .map_err(|e| format!("Failed to send resumption response: {}", e))?;
+                self.complete_handshake(conn_id).await
+            }
+            Err(e) => {
+                // Without a session, the dialer's full handshake starts a responder as usual
+                eprintln!("[RESPONDER] conn_id={} resumption rejected: {}", conn_id, e);
+                metrics::session_resumption_rejected();
+                self.transport_tx
+                    .send(TransportCommand::Send(conn_id, RESUME_REJECTED.to_vec()))
+                    .await
+                    .map_err(|e| format!("Failed to reject resumption: {}", e))
+            }
+        }
+    }
+
+    /// Check a resumption request against the ticket it names and write the response
+    ///
+    /// The ticket is consumed only once the request proves both the ticket secret and the
+    /// dialer's static key, so a forged request can't burn someone else's ticket.
+    async fn read_resumption(
+        &self,
+        id: &[u8],
+        message: &[u8],
+    ) -> Result<(HandshakeState, HandshakeMetadata, u64, Vec<u8>), String> {
+        let found = self
+            .tickets
+            .lock()
+            .await
+            .iter()
+            .find(|(_, ticket)| ticket.id == id)
+            .map(|(peer_id, ticket)| (peer_id.clone(), ticket.clone()));
+        let (peer_id, ticket) = found.ok_or("Unknown session ticket")?;
+        if ticket.is_expired() {
+            return Err("Session ticket expired".to_string());
+        }
+
+        let builder = Builder::new(
+            RESUME_PATTERN
+                .parse()
+                .expect("Invalid noise pattern - this is a programming error"),
+        );
+        let mut handshake = builder
+            .local_private_key(&self.static_keypair)
+            .remote_public_key(peer_id.as_bytes())
+            .psk(0, &ticket.secret)
+            .build_responder()
+            .map_err(|e| format!("Failed to build resuming responder: {}", e))?;
+        let mut buffer = vec![0u8; 1024];
+        handshake
+            .read_message(message, &mut buffer)
+            .map_err(|e| format!("Resumption request read failed: {}", e))?;
+
+        // Each ticket resumes one session; the session issues the next
+        if self.tickets.lock().await.remove_entry(&peer_id).is_none() {
+            return Err("Session ticket already used".to_string());
+        }
+
+        let len = handshake
+            .write_message(&[], &mut buffer)
+            .map_err(|e| format!("Resumption response write failed: {}", e))?;
+        Ok((handshake, HandshakeMetadata::new(), ticket.expires_at, buffer[..len].to_vec()))
+    }
+
+    /// Close conn_id if its handshake hasn't finished within HANDSHAKE_TIMEOUT_SECS
+    fn spawn_handshake_timeout(&self, conn_id: u64) {
         let sessions = self.sessions.clone();
         let transport_tx = self.transport_tx.clone();
         tokio::spawn(async move {
@@ -289,7 +743,9 @@ impl SessionManager {
             let mut sessions = sessions.lock().await;
             if let Some(session) = sessions.get(&conn_id) {
                 // Check if still in handshaking state
-                if let SessionState::Handshaking(_, metadata) = &session.state {
+                if let SessionState::Handshaking(_, metadata)
+                | SessionState::Resuming(_, metadata, _) = &session.state
+                {
                     if metadata.is_expired() {
                         // Record timeout metric
                         metrics::handshake_timeout();
@@ -304,6 +760,40 @@ impl SessionManager {
                 }
             }
         });
+    }
+
+    /// Initiate Noise handshake for a new connection
+    async fn initiate_handshake(&self, conn_id: u64) -> Result<(), String> {
+        eprintln!("[INITIATOR] Initiating handshake for conn_id={}", conn_id);
+
+        // Build Noise handshake state (initiator role)
+        let builder = Builder::new(
+            NOISE_PATTERN
+                .parse()
+                .expect("Invalid noise pattern - this is a programming error"),
+        );
+        let builder = builder.local_private_key(&self.static_keypair);
+
+        let mut handshake = builder
+            .build_initiator()
+            .map_err(|e| format!("Failed to build initiator: {}", e))?;
+
+        // Create handshake metadata with nonce and timestamp
+        let metadata = HandshakeMetadata::new();
+
+        // Send first handshake message with nonce
+        let mut buffer = vec![0u8; 1024];
+        let nonce_bytes = metadata.nonce.to_le_bytes();
+        let len = handshake
+            .write_message(&nonce_bytes, &mut buffer)
+            .map_err(|e| format!("Failed to write handshake: {}", e))?;
+
+        // Store handshake state with metadata
+        let session = Session { conn_id, state: SessionState::Handshaking(handshake, metadata) };
+        self.sessions.lock().await.insert(conn_id, session);
+
+        // Spawn timeout task to cleanup stalled handshakes
+        self.spawn_handshake_timeout(conn_id);
 
         // Send handshake message via transport
         self.transport_tx
@@ -385,9 +875,12 @@ impl SessionManager {
         let mut sessions = self.sessions.lock().await;
 
         // If no session exists, this is the first message from an incoming connection
-        // Create a responder handshake
+        // Create a responder handshake, or try resuming when the dialer presents a ticket
         if !sessions.contains_key(&conn_id) {
             drop(sessions);
+            if bytes.len() >= RESUME_REQUEST_MIN_LEN && bytes.starts_with(RESUME_MAGIC) {
+                return self.accept_resumption(conn_id, bytes).await;
+            }
             self.create_responder_handshake(conn_id, bytes).await?;
             return Ok(());
         }
@@ -438,58 +931,8 @@ impl SessionManager {
 
                 // Check if handshake is complete
                 if handshake.is_handshake_finished() {
-                    eprintln!(
-                        "[HANDSHAKING] conn_id={} COMPLETE! Transitioning to Established",
-                        conn_id
-                    );
-
-                    // Extract peer's static public key
-                    let remote_static = match handshake.get_remote_static() {
-                        Some(key) => {
-                            eprintln!("[HANDSHAKING] conn_id={} got remote static key", conn_id);
-                            key.to_vec()
-                        }
-                        None => {
-                            eprintln!("[ERROR] conn_id={} No remote static key!", conn_id);
-                            return Err("No remote static key".to_string());
-                        }
-                    };
-                    let peer_id = PeerId::from_bytes(remote_static);
-
-                    // Remove the session from the map to take ownership
                     drop(sessions);
-                    let mut sessions = self.sessions.lock().await;
-                    let mut session = sessions.remove(&conn_id).expect("Session disappeared");
-
-                    eprintln!("[HANDSHAKING] conn_id={} converting to transport mode", conn_id);
-
-                    // Take the handshake and convert to transport mode
-                    if let SessionState::Handshaking(hs, _) = session.state {
-                        let transport = hs.into_transport_mode().map_err(|e| {
-                            eprintln!(
-                                "[ERROR] conn_id={} failed to enter transport mode: {}",
-                                conn_id, e
-                            );
-                            format!("Failed to enter transport mode: {}", e)
-                        })?;
-                        session.state = SessionState::Established(transport, peer_id.clone());
-                        eprintln!("[HANDSHAKING] conn_id={} state set to Established", conn_id);
-                    }
-
-                    // Put the session back in the map
-                    sessions.insert(conn_id, session);
-
-                    // Update peer mapping
-                    drop(sessions);
-                    self.peer_to_conn.lock().await.insert(peer_id.clone(), conn_id);
-
-                    eprintln!("[ESTABLISHED] conn_id={} -> peer_id={:?}", conn_id, peer_id);
-
-                    // Emit Established event
-                    self.event_tx
-                        .send(SessionEvent::Established(peer_id, conn_id))
-                        .await
-                        .map_err(|e| format!("Failed to send event: {}", e))?;
+                    self.complete_handshake(conn_id).await?;
                 } else {
                     // Continue handshake - send next message
                     eprintln!("[HANDSHAKING] conn_id={} continuing, payload_len={}", conn_id, len);
@@ -499,13 +942,45 @@ impl SessionManager {
                         .map_err(|e| format!("Handshake write failed: {}", e))?;
 
                     eprintln!("[HANDSHAKING] conn_id={} sending {} bytes", conn_id, len);
+                    // The initiator's last message completes the handshake on its side too
+                    let finished = handshake.is_handshake_finished();
                     drop(sessions);
                     self.transport_tx
                         .send(TransportCommand::Send(conn_id, buffer[..len].to_vec()))
                         .await
                         .map_err(|e| format!("Failed to send handshake: {}", e))?;
+                    if finished {
+                        self.complete_handshake(conn_id).await?;
+                    }
                 }
             }
+            SessionState::Resuming(handshake, _, _) => {
+                let peer_id =
+                    handshake.get_remote_static().map(|key| PeerId::from_bytes(key.to_vec()));
+                if bytes == RESUME_REJECTED {
+                    // The responder no longer honours the ticket: start over with a full handshake
+                    sessions.remove(&conn_id);
+                    drop(sessions);
+                    if let Some(peer_id) = peer_id {
+                        self.forget_ticket(&peer_id).await;
+                    }
+                    return self.initiate_handshake(conn_id).await;
+                }
+
+                let mut buffer = vec![0u8; 1024];
+                if let Err(e) = handshake.read_message(&bytes, &mut buffer) {
+                    // Whoever answered doesn't hold the peer's static key or the ticket secret
+                    sessions.remove(&conn_id);
+                    drop(sessions);
+                    if let Some(peer_id) = peer_id {
+                        self.forget_ticket(&peer_id).await;
+                    }
+                    let _ = self.transport_tx.send(TransportCommand::Close(conn_id)).await;
+                    return Err(format!("Resumption response read failed: {}", e));
+                }
+                drop(sessions);
+                self.complete_handshake(conn_id).await?;
+            }
             SessionState::Established(transport, peer_id) => {
                 // Decrypt message
                 let mut buffer = vec![0u8; bytes.len()];
@@ -518,9 +993,18 @@ impl SessionManager {
 
                 drop(sessions);
 
-                // Emit plaintext frame
+                // Emit the frame by its type
                 self.event_tx
-                    .send(SessionEvent::PlaintextFrame(peer_id, plaintext))
+                    .send(frame_event(peer_id, plaintext)?)
+                    .await
+                    .map_err(|e| format!("Failed to send event: {}", e))?;
+            }
+            SessionState::Secured(peer_id) => {
+                let peer_id = peer_id.clone();
+                drop(sessions);
+
+                self.event_tx
+                    .send(frame_event(peer_id, bytes)?)
                     .await
                     .map_err(|e| format!("Failed to send event: {}", e))?;
             }
@@ -529,8 +1013,17 @@ impl SessionManager {
         Ok(())
     }
 
-    /// Send plaintext to a peer (encrypts and sends)
-    async fn send_plaintext(&self, peer_id: PeerId, plaintext: Vec<u8>) -> Result<(), String> {
+    /// Send a frame to a peer (prepends its type header, encrypts and sends)
+    async fn send_frame(
+        &self,
+        peer_id: PeerId,
+        frame_type: FrameType,
+        payload: Vec<u8>,
+    ) -> Result<(), String> {
+        let mut plaintext = Vec::with_capacity(payload.len() + 1);
+        plaintext.push(frame_type as u8);
+        plaintext.extend_from_slice(&payload);
+
         let peer_to_conn = self.peer_to_conn.lock().await;
         let conn_id = peer_to_conn.get(&peer_id).ok_or_else(|| format!("No session for peer"))?;
         let conn_id = *conn_id;
@@ -557,17 +1050,30 @@ impl SessionManager {
 
                 Ok(())
             }
-            SessionState::Handshaking(_, _) => Err("Session not yet established".to_string()),
+            SessionState::Secured(_) => {
+                drop(sessions);
+
+                self.transport_tx
+                    .send(TransportCommand::Send(conn_id, plaintext))
+                    .await
+                    .map_err(|e| format!("Failed to send data: {}", e))
+            }
+            SessionState::Handshaking(_, _) | SessionState::Resuming(_, _, _) => {
+                Err("Session not yet established".to_string())
+            }
         }
     }
 
     /// Handle connection disconnect
     async fn handle_disconnect(&self, conn_id: u64) -> Result<(), String> {
+        self.dialed.lock().await.remove(&conn_id);
         let mut sessions = self.sessions.lock().await;
         let session = sessions.remove(&conn_id);
 
         if let Some(session) = session {
-            if let SessionState::Established(_, peer_id) = session.state {
+            if let SessionState::Established(_, peer_id) | SessionState::Secured(peer_id) =
+                session.state
+            {
                 drop(sessions);
 
                 self.peer_to_conn.lock().await.remove(&peer_id);
@@ -733,6 +1239,74 @@ mod tests {
         assert!(manager.peer_to_conn.lock().await.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_onion_frames_are_told_apart_from_plaintext() {
+        let (transport_tx, mut transport_rx) = mpsc::channel(100);
+        let (event_tx, mut event_rx) = mpsc::channel(100);
+
+        let keypair = SessionManager::generate_keypair();
+        let manager = SessionManager::new(keypair, transport_tx, event_tx);
+
+        let peer_id = PeerId::from_bytes(vec![7, 7, 7]);
+        let conn_id = 9;
+        manager
+            .handle_transport_event(TransportEvent::Authenticated(
+                conn_id,
+                "peer".to_string(),
+                peer_id.0.clone(),
+            ))
+            .await
+            .expect("Failed to accept secured connection");
+        assert!(matches!(event_rx.recv().await, Some(SessionEvent::Established(_, 9))));
+
+        // Outbound cells carry the cell type header on the wire
+        manager
+            .handle_command(SessionCommand::SendCell(peer_id.clone(), b"cell".to_vec()))
+            .await
+            .expect("Failed to send cell");
+        let wire = match transport_rx.recv().await {
+            Some(TransportCommand::Send(id, bytes)) if id == conn_id => bytes,
+            other => panic!("Expected Send command, got {:?}", other),
+        };
+        assert_eq!(wire, [&[FrameType::Cell as u8], b"cell".as_slice()].concat());
+
+        // Inbound frames are dispatched by their type header, not by sniffing the payload
+        manager
+            .handle_transport_event(TransportEvent::Data(conn_id, wire))
+            .await
+            .unwrap();
+        let packet = [&[FrameType::OnionPacket as u8], b"blob".as_slice()].concat();
+        manager
+            .handle_transport_event(TransportEvent::Data(conn_id, packet))
+            .await
+            .unwrap();
+        let data = [&[FrameType::Data as u8], b"SPOChello".as_slice()].concat();
+        manager
+            .handle_transport_event(TransportEvent::Data(conn_id, data))
+            .await
+            .unwrap();
+        assert!(manager
+            .handle_transport_event(TransportEvent::Data(conn_id, vec![0xff, 1, 2]))
+            .await
+            .is_err());
+
+        match event_rx.recv().await {
+            Some(SessionEvent::CellFrame(from, cell)) => {
+                assert_eq!(from, peer_id);
+                assert_eq!(cell, b"cell");
+            }
+            other => panic!("Expected CellFrame, got {:?}", other),
+        }
+        match event_rx.recv().await {
+            Some(SessionEvent::OnionPacketFrame(_, blob)) => assert_eq!(blob, b"blob"),
+            other => panic!("Expected OnionPacketFrame, got {:?}", other),
+        }
+        match event_rx.recv().await {
+            Some(SessionEvent::PlaintextFrame(_, data)) => assert_eq!(data, b"SPOChello"),
+            other => panic!("Expected PlaintextFrame, got {:?}", other),
+        }
+    }
+
     #[tokio::test]
     async fn test_handshake_replay_detection() {
         let (transport_tx, mut transport_rx) = mpsc::channel(100);
@@ -0,0 +1,195 @@
+/*
+ * BANDWIDTH TESTS
+ *
+ * A TransportManager whose peer is limited to 1MB an hour, with a soft limit at half that,
+ * exchanges 10MB with a plain TCP peer:
+ * 1. Sends are delayed past the soft limit, then refused and the connection closed
+ * 2. Reads stall past the soft limit, then the connection is closed under the sender
+ * 3. The meter survives both reconnecting and restarting
+ */
+
+#![cfg(test)]
+
+use super::super::{
+    Admission, BandwidthConfig, BandwidthMeter, Direction, PeerClass, PeerId, TransportCommand,
+    TransportEvent, TransportManager,
+};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::time::{timeout, Duration};
+
+const MB: u64 = 1024 * 1024;
+const CHUNK: usize = 64 * 1024;
+const TOTAL: usize = 10 * MB as usize;
+
+fn limits() -> BandwidthConfig {
+    BandwidthConfig {
+        soft_limit: Some(MB / 2),
+        hard_limit: Some(MB),
+        throttle_rate: MB,
+        ..BandwidthConfig::default()
+    }
+}
+
+fn peer() -> PeerId {
+    PeerId::from_bytes(vec![7; 32])
+}
+
+/// A limited manager dialed out to a plain TCP peer; returns the manager, its events, the
+/// conn_id bound to peer() and the peer's end of the socket
+async fn connected(
+    meter: Arc<BandwidthMeter>,
+) -> (TransportManager, mpsc::Receiver<TransportEvent>, u64, TcpStream) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let (event_tx, mut event_rx) = mpsc::channel(1000);
+    let manager = TransportManager::new(event_tx).with_bandwidth_meter(meter);
+
+    manager.handle_command(TransportCommand::Dial(addr)).await.unwrap();
+    let (remote, _) = listener.accept().await.unwrap();
+    let conn_id = match event_rx.recv().await {
+        Some(TransportEvent::Connected(conn_id, _, true)) => conn_id,
+        other => panic!("Expected Connected, got {:?}", other),
+    };
+    manager
+        .handle_command(TransportCommand::BindPeer(conn_id, peer().0, PeerClass::Relay))
+        .await
+        .unwrap();
+    (manager, event_rx, conn_id, remote)
+}
+
+async fn next_disconnect(event_rx: &mut mpsc::Receiver<TransportEvent>, conn_id: u64) {
+    timeout(Duration::from_secs(5), async {
+        loop {
+            match event_rx.recv().await {
+                Some(TransportEvent::Disconnected(id)) if id == conn_id => return,
+                Some(_) => continue,
+                None => panic!("Event channel closed"),
+            }
+        }
+    })
+    .await
+    .expect("Connection should be closed at the hard limit")
+}
+
+#[tokio::test]
+async fn test_sends_throttled_then_disconnected() {
+    let meter = Arc::new(BandwidthMeter::new(limits()).unwrap());
+    let (manager, mut event_rx, conn_id, mut remote) = connected(meter.clone()).await;
+    tokio::spawn(async move {
+        let mut sink = Vec::new();
+        let _ = remote.read_to_end(&mut sink).await;
+    });
+
+    let mut sent = 0;
+    let started = Instant::now();
+    let mut reached_soft = None;
+    let mut refused = false;
+    while sent < TOTAL {
+        let result = manager.handle_command(TransportCommand::Send(conn_id, vec![0; CHUNK])).await;
+        if result.is_err() {
+            refused = true;
+            break;
+        }
+        sent += CHUNK;
+        if sent as u64 == MB / 2 {
+            reached_soft = Some(started.elapsed());
+        }
+    }
+
+    assert!(refused, "Sends past the hard limit must fail");
+    assert_eq!(sent as u64, MB, "Everything up to the hard limit goes out");
+    // The first half MB goes out at socket speed, the second at the 1MB/s throttle rate
+    let reached_soft = reached_soft.unwrap();
+    let over_soft = started.elapsed() - reached_soft;
+    assert!(over_soft >= Duration::from_millis(300), "{:?}", over_soft);
+    assert!(reached_soft < over_soft, "{:?} vs {:?}", reached_soft, over_soft);
+    next_disconnect(&mut event_rx, conn_id).await;
+
+    let usage = meter.usage(&peer()).await;
+    assert_eq!(usage.received, 0);
+    assert!(usage.sent > MB);
+}
+
+#[tokio::test]
+async fn test_reads_throttled_then_disconnected() {
+    let meter = Arc::new(BandwidthMeter::new(limits()).unwrap());
+    let (_manager, mut event_rx, conn_id, mut remote) = connected(meter.clone()).await;
+
+    // The peer pushes 10MB as fast as the socket lets it
+    let pusher = tokio::spawn(async move {
+        let mut pushed = 0;
+        let frame = [&(CHUNK as u32).to_be_bytes()[..], &[1u8; CHUNK]].concat();
+        while pushed < TOTAL {
+            if remote.write_all(&frame).await.is_err() {
+                break;
+            }
+            pushed += CHUNK;
+        }
+        pushed
+    });
+
+    let started = Instant::now();
+    let mut received = 0;
+    let mut reached_soft = None;
+    timeout(Duration::from_secs(5), async {
+        loop {
+            match event_rx.recv().await {
+                Some(TransportEvent::Data(id, data)) if id == conn_id => {
+                    received += data.len() as u64;
+                    if received == MB / 2 {
+                        reached_soft = Some(started.elapsed());
+                    }
+                }
+                Some(TransportEvent::Disconnected(id)) if id == conn_id => break,
+                other => panic!("Unexpected event {:?}", other),
+            }
+        }
+    })
+    .await
+    .expect("Connection should be closed at the hard limit");
+
+    assert_eq!(received, MB, "Only data up to the hard limit is delivered");
+    // The second half MB arrives at the throttle rate, well behind the first
+    let reached_soft = reached_soft.unwrap();
+    assert!(started.elapsed() - reached_soft >= Duration::from_millis(300));
+    let pushed = timeout(Duration::from_secs(5), pusher).await.unwrap().unwrap();
+    assert!(pushed < TOTAL, "The pushing peer should be cut off");
+}
+
+#[tokio::test]
+async fn test_meter_survives_reconnect_and_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let config =
+        BandwidthConfig { state_path: Some(dir.path().join("bandwidth.json")), ..limits() };
+    let meter = BandwidthMeter::new(config.clone()).unwrap();
+
+    meter.bind(1, peer(), PeerClass::Relay).await;
+    assert_eq!(meter.admit(1, Direction::Received, 600 * 1024).await, Admission::Pass);
+    meter.unbind(1).await;
+
+    // Reconnecting on a new connection picks up where the old one stopped
+    meter.bind(2, peer(), PeerClass::Relay).await;
+    assert!(matches!(
+        meter.admit(2, Direction::Received, 200 * 1024).await,
+        Admission::Delay(_)
+    ));
+    meter.unbind(2).await;
+    drop(meter);
+
+    // So does restarting
+    let meter = BandwidthMeter::new(config).unwrap();
+    assert_eq!(meter.usage(&peer()).await.received, 800 * 1024);
+    meter.bind(3, peer(), PeerClass::Relay).await;
+    assert_eq!(meter.admit(3, Direction::Received, 300 * 1024).await, Admission::Close);
+    // Sent has its own budget
+    assert_eq!(meter.admit(3, Direction::Sent, 300 * 1024).await, Admission::Pass);
+
+    // Other peers and unbound connections are unaffected
+    meter.bind(4, PeerId::from_bytes(vec![8; 32]), PeerClass::Dht).await;
+    assert_eq!(meter.admit(4, Direction::Received, 300 * 1024).await, Admission::Pass);
+    assert_eq!(meter.admit(5, Direction::Received, 10 * MB as usize).await, Admission::Pass);
+}
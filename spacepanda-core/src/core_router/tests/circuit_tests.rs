@@ -0,0 +1,284 @@
+/*
+ * ONION CIRCUIT TESTS
+ *
+ * An origin and a set of relays run their own OnionRouter; cells are carried between them
+ * in-process and logged as they cross the wire:
+ * 1. No relay learns both the origin and the destination key
+ * 2. A circuit is rebuilt around a relay that dies mid-stream, keeping its CircuitId
+ * 3. Idle circuits are torn down at the origin and at every relay
+ */
+
+#![cfg(test)]
+
+use super::super::{
+    Capability, CircuitId, InnerEnvelope, OnionCommand, OnionConfig, OnionEvent, OnionRouter,
+    PeerId, PeerInfo, RouteTable, RouteTableCommand,
+};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout, Duration};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+/// A cell seen on the wire: sender, receiver, bytes
+type WireCell = (PeerId, PeerId, Vec<u8>);
+
+struct Network {
+    origin: Arc<OnionRouter>,
+    origin_id: PeerId,
+    routers: HashMap<PeerId, Arc<OnionRouter>>,
+    commands: Arc<HashMap<PeerId, mpsc::Sender<OnionCommand>>>,
+    wire: Arc<Mutex<Vec<WireCell>>>,
+    /// Nodes that stopped; cells to and from them are lost
+    down: Arc<Mutex<HashSet<PeerId>>>,
+    /// Envelopes delivered by exits, with the exit's ID
+    deliveries: mpsc::Receiver<(PeerId, InnerEnvelope)>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Drop for Network {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+impl Network {
+    /// Stop a relay; its neighbours see their sessions with it close
+    async fn kill(&self, peer_id: &PeerId) {
+        self.down.lock().unwrap().insert(peer_id.clone());
+        for (id, commands) in self.commands.iter() {
+            if id != peer_id {
+                let _ = commands
+                    .send(OnionCommand::PeerDisconnected { peer_id: peer_id.clone() })
+                    .await;
+            }
+        }
+    }
+
+    /// Wait for the origin to move a circuit off `old_path`
+    async fn rebuilt_path(&self, circuit: CircuitId, old_path: &[PeerId]) -> Vec<PeerId> {
+        timeout(Duration::from_secs(5), async {
+            loop {
+                let path = self.origin.circuit_path(circuit).await.unwrap();
+                if path != old_path {
+                    break path;
+                }
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Circuit should be rebuilt without waiting for the next send")
+    }
+
+    async fn next_delivery(&mut self) -> (PeerId, InnerEnvelope) {
+        timeout(Duration::from_secs(5), self.deliveries.recv())
+            .await
+            .expect("Timeout waiting for the exit to deliver")
+            .expect("Delivery channel closed")
+    }
+}
+
+fn relay_id(i: u8) -> PeerId {
+    PeerId::from_bytes(vec![i; 32])
+}
+
+/// An origin that knows `relay_count` relays, each in its own /16
+async fn network(relay_count: u8, config: OnionConfig) -> Network {
+    let origin_id = PeerId::from_bytes(vec![200; 32]);
+    let route_table = Arc::new(RouteTable::new());
+    let mut nodes = vec![(origin_id.clone(), route_table.clone(), None)];
+    for i in 1..=relay_count {
+        let onion_key = StaticSecret::from([i; 32]);
+        let mut relay = PeerInfo::new(relay_id(i), vec![format!("10.{}.0.1:9000", i)]);
+        relay.capabilities.push(Capability::Relay);
+        relay.overlay_pubkey = *PublicKey::from(&onion_key).as_bytes();
+        route_table.handle_command(RouteTableCommand::InsertPeer(relay)).await.unwrap();
+        nodes.push((relay_id(i), Arc::new(RouteTable::new()), Some(onion_key)));
+    }
+
+    let mut routers = HashMap::new();
+    let mut commands = HashMap::new();
+    let mut event_streams = Vec::new();
+    let mut tasks = Vec::new();
+    for (id, table, onion_key) in nodes {
+        let (event_tx, event_rx) = mpsc::channel(100);
+        let mut router =
+            OnionRouter::new(config.clone(), table, event_tx).with_local_id(id.clone());
+        if let Some(onion_key) = onion_key {
+            router = router.with_onion_key(onion_key);
+        }
+        let router = Arc::new(router);
+        let (cmd_tx, cmd_rx) = mpsc::channel(100);
+        tasks.push(tokio::spawn(router.clone().run(cmd_rx)));
+        routers.insert(id.clone(), router);
+        commands.insert(id.clone(), cmd_tx);
+        event_streams.push((id, event_rx));
+    }
+
+    let commands = Arc::new(commands);
+    let wire = Arc::new(Mutex::new(Vec::new()));
+    let down = Arc::new(Mutex::new(HashSet::new()));
+    let (delivered_tx, deliveries) = mpsc::channel(100);
+    for (id, mut event_rx) in event_streams {
+        let (commands, wire, down) = (commands.clone(), wire.clone(), down.clone());
+        let delivered_tx = delivered_tx.clone();
+        tasks.push(tokio::spawn(async move {
+            while let Some(event) = event_rx.recv().await {
+                match event {
+                    OnionEvent::CellForward { next_peer, cell } => {
+                        {
+                            let down = down.lock().unwrap();
+                            if down.contains(&id) || down.contains(&next_peer) {
+                                continue;
+                            }
+                        }
+                        wire.lock().unwrap().push((id.clone(), next_peer.clone(), cell.clone()));
+                        if let Some(commands) = commands.get(&next_peer) {
+                            let _ = commands
+                                .send(OnionCommand::HandleCell { from: id.clone(), cell })
+                                .await;
+                        }
+                    }
+                    OnionEvent::DeliverLocal { envelope } => {
+                        let _ = delivered_tx.send((id.clone(), envelope)).await;
+                    }
+                    _ => {}
+                }
+            }
+        }));
+    }
+
+    let origin = routers[&origin_id].clone();
+    Network { origin, origin_id, routers, commands, wire, down, deliveries, tasks }
+}
+
+#[tokio::test]
+async fn test_no_relay_learns_origin_and_destination() {
+    let mut net = network(3, OnionConfig::default()).await;
+    let circuit = net.origin.build_circuit(3).await.unwrap();
+    let path = net.origin.circuit_path(circuit).await.unwrap();
+    assert_eq!(path.len(), 3);
+
+    let destination_key = blake3::hash(b"channel_feed-head:some group").as_bytes().to_vec();
+    net.origin
+        .send_through_circuit(circuit, destination_key.clone(), b"sealed entry".to_vec())
+        .await
+        .unwrap();
+    let (exit, envelope) = net.next_delivery().await;
+    assert_eq!(exit, path[2]);
+    assert_eq!(envelope.destination, destination_key);
+    assert_eq!(envelope.payload, b"sealed entry");
+
+    // On the wire the key is always under at least one layer, so only the relay that
+    // peeled the last one, the exit, reads it
+    let wire = net.wire.lock().unwrap().clone();
+    assert!(wire.iter().all(|(_, _, cell)| !cell
+        .windows(destination_key.len())
+        .any(|window| window == destination_key.as_slice())));
+
+    for relay in &path {
+        let neighbours: HashSet<&PeerId> = wire
+            .iter()
+            .filter_map(|(from, to, _)| {
+                if from == relay {
+                    Some(to)
+                } else if to == relay {
+                    Some(from)
+                } else {
+                    None
+                }
+            })
+            .collect();
+        let knows_origin = neighbours.contains(&net.origin_id);
+        let knows_destination = *relay == exit;
+        assert!(
+            !(knows_origin && knows_destination),
+            "Relay {:?} saw both ends of the circuit",
+            relay
+        );
+        assert_eq!(knows_origin, *relay == path[0]);
+    }
+    // The origin only ever talks to its first hop
+    assert!(wire.iter().all(|(from, to, _)| {
+        (from != &net.origin_id || to == &path[0]) && (to != &net.origin_id || from == &path[0])
+    }));
+}
+
+#[tokio::test]
+async fn test_circuit_rebuilt_when_relay_dies() {
+    let mut net = network(6, OnionConfig::default()).await;
+    let circuit = net.origin.build_circuit(3).await.unwrap();
+    let first_path = net.origin.circuit_path(circuit).await.unwrap();
+    net.origin
+        .send_through_circuit(circuit, b"key".to_vec(), b"before".to_vec())
+        .await
+        .unwrap();
+    assert_eq!(net.next_delivery().await.1.payload, b"before");
+
+    // The middle relay dies; its predecessor reports the circuit truncated
+    let dead_middle = first_path[1].clone();
+    net.kill(&dead_middle).await;
+    let second_path = net.rebuilt_path(circuit, &first_path).await;
+    assert_eq!(second_path.len(), 3);
+    assert!(!second_path.contains(&dead_middle));
+
+    net.origin
+        .send_through_circuit(circuit, b"key".to_vec(), b"after middle".to_vec())
+        .await
+        .unwrap();
+    let (exit, envelope) = net.next_delivery().await;
+    assert_eq!(envelope.payload, b"after middle");
+    assert_eq!(exit, second_path[2]);
+
+    // Now the first hop: our own session with it closes
+    let dead_first = second_path[0].clone();
+    net.kill(&dead_first).await;
+    let third_path = net.rebuilt_path(circuit, &second_path).await;
+    assert!(!third_path.contains(&dead_first) && !third_path.contains(&dead_middle));
+    net.origin
+        .send_through_circuit(circuit, b"key".to_vec(), b"after first".to_vec())
+        .await
+        .unwrap();
+    let (exit, envelope) = net.next_delivery().await;
+    assert_eq!(envelope.payload, b"after first");
+    assert_eq!(exit, third_path[2]);
+    assert_eq!(net.origin.circuit_count().await, 1);
+}
+
+#[tokio::test]
+async fn test_idle_circuits_torn_down() {
+    let config =
+        OnionConfig { circuit_idle_timeout: Duration::from_millis(400), ..OnionConfig::default() };
+    let mut net = network(3, config).await;
+    assert!(net.origin.build_circuit(4).await.is_err(), "Only 3 relays satisfy the policy");
+
+    let circuit = net.origin.build_circuit(2).await.unwrap();
+    let path = net.origin.circuit_path(circuit).await.unwrap();
+    for relay in &path {
+        assert_eq!(net.routers[relay].relayed_circuit_count().await, 1);
+    }
+
+    // Use keeps it open past the timeout
+    sleep(Duration::from_millis(200)).await;
+    net.origin
+        .send_through_circuit(circuit, b"key".to_vec(), b"still here".to_vec())
+        .await
+        .unwrap();
+    net.next_delivery().await;
+    sleep(Duration::from_millis(250)).await;
+    assert_eq!(net.origin.circuit_count().await, 1);
+
+    sleep(Duration::from_millis(800)).await;
+    assert_eq!(net.origin.circuit_count().await, 0);
+    for relay in &path {
+        assert_eq!(net.routers[relay].relayed_circuit_count().await, 0);
+    }
+    assert!(net
+        .origin
+        .send_through_circuit(circuit, b"key".to_vec(), b"gone".to_vec())
+        .await
+        .is_err());
+}
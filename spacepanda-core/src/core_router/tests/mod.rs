@@ -1 +1,8 @@
+pub mod bandwidth_tests;
+pub mod circuit_tests;
+pub mod nat_traversal_tests;
+pub mod peer_exchange_tests;
+pub mod quic_tests;
 pub mod security_tests;
+pub mod session_resumption_tests;
+pub mod ws_tests;
@@ -0,0 +1,367 @@
+/*
+ * NAT TRAVERSAL TESTS
+ *
+ * Two peers behind separate NATs connect through a public relay, over an in-process
+ * network shim. The shim's NATs keep one external port per internal socket and only let
+ * in packets from addresses that socket has sent to (port-restricted cone NAT), which is
+ * what hole punching gets through. A symmetric NAT, which picks a fresh external port per
+ * destination, defeats it and forces the relay fallback.
+ */
+
+#![cfg(test)]
+
+use super::super::{
+    connect_via_relay, dial_peer, serve_connect_requests, Connection, DatagramSocket, PeerId,
+    PeerInfo, Reachability, RouteTable, RouteTableCommand, RpcError, RpcRequest, TransportCommand,
+    TransportEvent, TransportManager, CONNECT_REQUEST_METHOD,
+};
+use async_trait::async_trait;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{timeout, Duration};
+
+const RELAY_ADDR: &str = "198.51.100.1:7000";
+
+#[derive(Clone, Copy, PartialEq)]
+enum NatKind {
+    PortRestricted,
+    Symmetric,
+}
+
+struct Nat {
+    kind: NatKind,
+    next_port: u16,
+    /// (internal addr, destination for symmetric NATs) -> external port
+    mappings: HashMap<(String, Option<String>), u16>,
+    /// external port -> internal addr and the remotes it has sent to
+    ports: HashMap<u16, (String, HashSet<String>)>,
+}
+
+#[derive(Default)]
+struct NetState {
+    inboxes: HashMap<String, mpsc::UnboundedSender<(Vec<u8>, String)>>,
+    /// external IP -> NAT
+    nats: HashMap<String, Nat>,
+}
+
+/// In-process network of hosts, some of them behind NATs
+#[derive(Clone, Default)]
+struct SimNet {
+    state: Arc<Mutex<NetState>>,
+}
+
+impl SimNet {
+    fn add_nat(&self, external_ip: &str, kind: NatKind) {
+        let nat = Nat { kind, next_port: 40000, mappings: HashMap::new(), ports: HashMap::new() };
+        self.state.lock().unwrap().nats.insert(external_ip.to_string(), nat);
+    }
+
+    /// A socket at addr, behind the NAT owning nat_ip if any
+    fn socket(&self, addr: &str, nat_ip: Option<&str>) -> Arc<SimSocket> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.state.lock().unwrap().inboxes.insert(addr.to_string(), tx);
+        Arc::new(SimSocket {
+            net: self.clone(),
+            addr: addr.to_string(),
+            nat_ip: nat_ip.map(String::from),
+            inbox: tokio::sync::Mutex::new(rx),
+        })
+    }
+
+    fn send(&self, from: &str, nat_ip: Option<&str>, to: &str, bytes: &[u8]) {
+        let mut state = self.state.lock().unwrap();
+
+        let source = match nat_ip {
+            Some(ip) => {
+                let nat = state.nats.get_mut(ip).unwrap();
+                let key =
+                    (from.to_string(), (nat.kind == NatKind::Symmetric).then(|| to.to_string()));
+                let port = match nat.mappings.get(&key) {
+                    Some(port) => *port,
+                    None => {
+                        nat.next_port += 1;
+                        nat.mappings.insert(key, nat.next_port);
+                        nat.next_port
+                    }
+                };
+                let (_, remotes) =
+                    nat.ports.entry(port).or_insert_with(|| (from.to_string(), HashSet::new()));
+                remotes.insert(to.to_string());
+                format!("{}:{}", ip, port)
+            }
+            None => from.to_string(),
+        };
+
+        let (ip, port) = to.rsplit_once(':').unwrap();
+        let destination = match state.nats.get(ip) {
+            Some(nat) => match nat.ports.get(&port.parse().unwrap()) {
+                Some((internal, remotes)) if remotes.contains(&source) => internal.clone(),
+                // Unsolicited: the NAT drops it
+                _ => return,
+            },
+            None => to.to_string(),
+        };
+        if let Some(inbox) = state.inboxes.get(&destination) {
+            let _ = inbox.send((bytes.to_vec(), source));
+        }
+    }
+}
+
+struct SimSocket {
+    net: SimNet,
+    addr: String,
+    nat_ip: Option<String>,
+    inbox: tokio::sync::Mutex<mpsc::UnboundedReceiver<(Vec<u8>, String)>>,
+}
+
+#[async_trait]
+impl DatagramSocket for SimSocket {
+    async fn send_to(&self, bytes: &[u8], addr: &str) -> Result<(), String> {
+        self.net.send(&self.addr, self.nat_ip.as_deref(), addr, bytes);
+        Ok(())
+    }
+
+    async fn recv_from(&self) -> Result<(Vec<u8>, String), String> {
+        self.inbox.lock().await.recv().await.ok_or_else(|| "network gone".to_string())
+    }
+}
+
+struct SimNode {
+    id: PeerId,
+    transport: Arc<TransportManager>,
+    route_table: Arc<RouteTable>,
+    events: mpsc::Receiver<TransportEvent>,
+}
+
+async fn sim_node(net: &SimNet, id: u8, addr: &str, nat_ip: Option<&str>) -> SimNode {
+    let (event_tx, events) = mpsc::channel(100);
+    let transport =
+        Arc::new(TransportManager::new(event_tx).with_punch_timeout(Duration::from_millis(600)));
+    transport.attach_datagram_socket(net.socket(addr, nat_ip)).await;
+    SimNode {
+        id: PeerId::from_bytes(vec![id]),
+        transport,
+        route_table: Arc::new(RouteTable::new()),
+        events,
+    }
+}
+
+/// Deliver an RPC to a handler as if it arrived from peer_id
+async fn rpc(
+    handler: &mpsc::Sender<RpcRequest>,
+    peer_id: PeerId,
+    params: serde_json::Value,
+) -> Result<serde_json::Value, RpcError> {
+    let (response_tx, response_rx) = oneshot::channel();
+    let request = RpcRequest {
+        peer_id,
+        id: uuid::Uuid::new_v4().to_string(),
+        method: CONNECT_REQUEST_METHOD.to_string(),
+        params,
+        response_tx,
+    };
+    handler
+        .send(request)
+        .await
+        .map_err(|_| RpcError::internal_error("handler gone"))?;
+    response_rx.await.map_err(|_| RpcError::internal_error("no response"))?
+}
+
+async fn next_connected(node: &mut SimNode) -> (u64, String, bool) {
+    loop {
+        let event = timeout(Duration::from_secs(2), node.events.recv())
+            .await
+            .expect("Timeout waiting for Connected")
+            .expect("Event channel closed");
+        if let TransportEvent::Connected(conn_id, addr, is_outgoing) = event {
+            return (conn_id, addr, is_outgoing);
+        }
+    }
+}
+
+/// Public relay R, A behind a port-restricted NAT and B behind a NAT of kind nat_b
+async fn two_nats(nat_b: NatKind) -> (SimNode, SimNode, SimNode, mpsc::Sender<RpcRequest>) {
+    let net = SimNet::default();
+    net.add_nat("203.0.113.1", NatKind::PortRestricted);
+    net.add_nat("203.0.113.2", nat_b);
+
+    let relay = sim_node(&net, 1, RELAY_ADDR, None).await;
+    let a = sim_node(&net, 2, "10.0.0.2:7000", Some("203.0.113.1")).await;
+    let b = sim_node(&net, 3, "192.168.1.2:7000", Some("203.0.113.2")).await;
+
+    let a_external = a.transport.observe(RELAY_ADDR).await.unwrap();
+    let b_external = b.transport.observe(RELAY_ADDR).await.unwrap();
+    assert!(a_external.starts_with("203.0.113.1:"));
+    assert!(b_external.starts_with("203.0.113.2:"));
+
+    let mut peer_b = PeerInfo::new(b.id.clone(), vec!["192.168.1.2:7000".to_string()]);
+    peer_b.observed_addr = Some(b_external);
+    a.route_table
+        .handle_command(RouteTableCommand::InsertPeer(peer_b))
+        .await
+        .unwrap();
+    let mut peer_a = PeerInfo::new(a.id.clone(), vec!["10.0.0.2:7000".to_string()]);
+    peer_a.observed_addr = Some(a_external);
+    b.route_table
+        .handle_command(RouteTableCommand::InsertPeer(peer_a))
+        .await
+        .unwrap();
+
+    // B is connected to the relay, which forwards requests to it over that session
+    let (b_handler, _) = serve_connect_requests(
+        b.id.clone(),
+        b.transport.clone(),
+        b.route_table.clone(),
+        |_, _| async { Err(RpcError::internal_error("B relays nothing")) },
+    );
+    let relay_id = relay.id.clone();
+    let (relay_handler, _) = serve_connect_requests(
+        relay.id.clone(),
+        relay.transport.clone(),
+        relay.route_table.clone(),
+        move |target, params| {
+            let b_handler = b_handler.clone();
+            let relay_id = relay_id.clone();
+            async move {
+                assert_eq!(target, PeerId::from_bytes(vec![3]));
+                rpc(&b_handler, relay_id, params).await
+            }
+        },
+    );
+
+    (relay, a, b, relay_handler)
+}
+
+async fn peer_info(node: &SimNode, peer: u8) -> PeerInfo {
+    let (response_tx, response_rx) = oneshot::channel();
+    node.route_table
+        .handle_command(RouteTableCommand::GetPeer {
+            peer_id: PeerId::from_bytes(vec![peer]),
+            response_tx,
+        })
+        .await
+        .unwrap();
+    response_rx.await.unwrap().unwrap()
+}
+
+async fn reachability(node: &SimNode, peer: u8) -> Reachability {
+    peer_info(node, peer).await.reachability
+}
+
+#[tokio::test]
+async fn test_hole_punch_between_two_nats() {
+    let (relay, mut a, mut b, relay_handler) = two_nats(NatKind::PortRestricted).await;
+    let b_external = b.transport.observed_addr().await.unwrap();
+
+    // Without coordination B's NAT drops A's probes
+    assert!(a.transport.punch(&b_external, true).await.is_err());
+
+    let connection =
+        connect_via_relay(&a.transport, &a.route_table, &a.id, &b.id, &relay.id, |params| {
+            rpc(&relay_handler, PeerId::from_bytes(vec![2]), params)
+        })
+        .await
+        .unwrap();
+    let Connection::Direct(a_conn) = connection else {
+        panic!("Expected a punched connection, got {:?}", connection);
+    };
+
+    let (conn_id, addr, is_outgoing) = next_connected(&mut a).await;
+    assert_eq!((conn_id, addr.as_str(), is_outgoing), (a_conn, b_external.as_str(), true));
+    let (b_conn, addr, is_outgoing) = next_connected(&mut b).await;
+    assert_eq!(Some(addr), a.transport.observed_addr().await);
+    assert!(!is_outgoing, "Only the initiator should start the handshake");
+
+    // The punched path carries data both ways
+    a.transport
+        .handle_command(TransportCommand::Send(a_conn, b"hello".to_vec()))
+        .await
+        .unwrap();
+    b.transport
+        .handle_command(TransportCommand::Send(b_conn, b"hi".to_vec()))
+        .await
+        .unwrap();
+    let data = timeout(Duration::from_secs(2), b.events.recv()).await.unwrap().unwrap();
+    assert!(matches!(data, TransportEvent::Data(id, bytes) if id == b_conn && bytes == b"hello"));
+    let data = timeout(Duration::from_secs(2), a.events.recv()).await.unwrap().unwrap();
+    assert!(matches!(data, TransportEvent::Data(id, bytes) if id == a_conn && bytes == b"hi"));
+
+    assert_eq!(reachability(&a, 3).await, Reachability::NatPunchable);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(reachability(&b, 2).await, Reachability::NatPunchable);
+}
+
+#[tokio::test]
+async fn test_symmetric_nat_falls_back_to_relay() {
+    let (relay, a, b, relay_handler) = two_nats(NatKind::Symmetric).await;
+
+    let connection =
+        connect_via_relay(&a.transport, &a.route_table, &a.id, &b.id, &relay.id, |params| {
+            rpc(&relay_handler, PeerId::from_bytes(vec![2]), params)
+        })
+        .await
+        .unwrap();
+    assert_eq!(connection, Connection::Relayed(relay.id.clone()));
+    assert_eq!(reachability(&a, 3).await, Reachability::RelayOnly);
+
+    tokio::time::sleep(Duration::from_millis(700)).await;
+    assert_eq!(reachability(&b, 2).await, Reachability::RelayOnly);
+}
+
+#[tokio::test]
+async fn test_relay_refuses_spoofed_connect_request() {
+    let (relay, a, b, relay_handler) = two_nats(NatKind::PortRestricted).await;
+
+    // The relay only forwards on behalf of the peer that sent the request
+    let result =
+        connect_via_relay(&a.transport, &a.route_table, &a.id, &b.id, &relay.id, |params| {
+            rpc(&relay_handler, PeerId::from_bytes(vec![9]), params)
+        })
+        .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_dial_peer_punches_when_no_address_answers() {
+    let (relay, a, _b, relay_handler) = two_nats(NatKind::PortRestricted).await;
+    let mut peer_b = peer_info(&a, 3).await;
+    peer_b.addresses = vec!["127.0.0.1:1".to_string()];
+
+    // The peer itself is skipped as a relay
+    let relays = [PeerId::from_bytes(vec![3]), relay.id.clone()];
+    let connection =
+        dial_peer(&a.transport, &a.route_table, &a.id, &peer_b, &relays, |via, params| {
+            assert_eq!(via, relay.id);
+            rpc(&relay_handler, PeerId::from_bytes(vec![2]), params)
+        })
+        .await
+        .unwrap();
+    assert!(matches!(connection, Connection::Direct(_)), "got {:?}", connection);
+    assert_eq!(reachability(&a, 3).await, Reachability::NatPunchable);
+}
+
+#[tokio::test]
+async fn test_dial_peer_connects_directly_to_public_peer() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let (event_tx, mut events) = mpsc::channel(100);
+    let transport = TransportManager::new(event_tx);
+    let peer = PeerInfo::new(PeerId::from_bytes(vec![4]), vec![addr.clone()]);
+
+    let connection = dial_peer(
+        &transport,
+        &RouteTable::new(),
+        &PeerId::from_bytes(vec![2]),
+        &peer,
+        &[],
+        |_, _| async { Err(RpcError::internal_error("no relay needed")) },
+    )
+    .await
+    .unwrap();
+    let Connection::Direct(conn_id) = connection else {
+        panic!("Expected a direct connection, got {:?}", connection);
+    };
+    let event = timeout(Duration::from_secs(2), events.recv()).await.unwrap().unwrap();
+    assert!(matches!(event, TransportEvent::Connected(id, a, true) if id == conn_id && a == addr));
+}
@@ -0,0 +1,246 @@
+/*
+ * PEER EXCHANGE TESTS
+ *
+ * Overlay discovery nodes gossiping signed peer descriptors over an in-process transport
+ * that hands each request to the target node's exchange handler, JSON round trip included.
+ * Covers a three-node chain where A learns about C only through B, the limits that keep
+ * exchange from amplifying traffic, capability filtering, and responders passing on
+ * forged descriptors.
+ */
+
+#![cfg(test)]
+
+use super::super::{
+    Capability, DiscoveryCommand, DiscoveryConfig, DiscoveryEvent, ExchangeTransport,
+    OverlayDiscovery, PeerDescriptor, PeerExchangeRequest, PeerExchangeResponse, PeerId, PeerInfo,
+    QuicIdentity, RouteTable, RouteTableCommand, RpcError,
+};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{timeout, Duration};
+
+/// Discovery nodes by PeerId
+#[derive(Default)]
+struct Net {
+    nodes: Mutex<HashMap<PeerId, Arc<OverlayDiscovery>>>,
+}
+
+/// One node's view of the network
+struct Link {
+    from: PeerId,
+    net: Arc<Net>,
+}
+
+#[async_trait]
+impl ExchangeTransport for Link {
+    async fn exchange(
+        &self,
+        peer: PeerId,
+        request: PeerExchangeRequest,
+    ) -> Result<PeerExchangeResponse, RpcError> {
+        let node = self.net.nodes.lock().unwrap().get(&peer).cloned();
+        let node = node.ok_or_else(|| RpcError::internal_error("unknown peer"))?;
+        let request = serde_json::from_value(serde_json::to_value(request).unwrap()).unwrap();
+        let response = node.answer_exchange(&self.from, request).await?;
+        Ok(serde_json::from_value(serde_json::to_value(response).unwrap()).unwrap())
+    }
+}
+
+struct Node {
+    info: PeerInfo,
+    route_table: Arc<RouteTable>,
+    discovery: Arc<OverlayDiscovery>,
+    command_tx: mpsc::Sender<DiscoveryCommand>,
+    event_rx: mpsc::Receiver<DiscoveryEvent>,
+}
+
+impl Node {
+    /// Run one discovery round and wait for it to finish
+    async fn discover(&mut self) {
+        self.command_tx.send(DiscoveryCommand::TriggerDiscovery).await.unwrap();
+        loop {
+            let event = timeout(Duration::from_secs(2), self.event_rx.recv()).await.unwrap();
+            if let Some(DiscoveryEvent::RelayPoolUpdated { .. }) = event {
+                return;
+            }
+        }
+    }
+
+    async fn knows(&self, peer: &PeerInfo) -> Option<PeerInfo> {
+        let (tx, rx) = oneshot::channel();
+        let command = RouteTableCommand::GetPeer { peer_id: peer.peer_id.clone(), response_tx: tx };
+        self.route_table.handle_command(command).await.unwrap();
+        rx.await.unwrap()
+    }
+
+    async fn add_neighbour(&self, peer: &PeerInfo) {
+        let command = RouteTableCommand::InsertPeer(peer.clone());
+        self.route_table.handle_command(command).await.unwrap();
+    }
+}
+
+/// Local PeerInfo of the node with identity `seed`
+fn local_info(seed: u8, capabilities: &[Capability]) -> PeerInfo {
+    let peer_id = QuicIdentity::from_seed(&[seed; 32]).unwrap().peer_id().clone();
+    let mut info = PeerInfo::new(peer_id, vec![format!("10.0.0.{}:7000", seed)]);
+    info.capabilities = capabilities.to_vec();
+    info
+}
+
+async fn spawn_node(net: &Arc<Net>, seed: u8, capabilities: &[Capability]) -> Node {
+    let info = local_info(seed, capabilities);
+    let config = DiscoveryConfig {
+        discovery_interval: Duration::from_secs(3600),
+        min_exchange_interval: Duration::ZERO,
+        ..DiscoveryConfig::default()
+    };
+    let route_table = Arc::new(RouteTable::new());
+    let (event_tx, mut event_rx) = mpsc::channel(100);
+    let link = Link { from: info.peer_id.clone(), net: net.clone() };
+    let discovery = Arc::new(
+        OverlayDiscovery::new(config, route_table.clone(), event_tx)
+            .with_identity(&[seed; 32], info.clone())
+            .with_transport(Arc::new(link)),
+    );
+    net.nodes.lock().unwrap().insert(info.peer_id.clone(), discovery.clone());
+
+    let (command_tx, command_rx) = mpsc::channel(100);
+    tokio::spawn(discovery.clone().run(command_rx));
+    // The first tick runs a round straight away; let it finish on an empty table
+    while !matches!(
+        timeout(Duration::from_secs(2), event_rx.recv()).await.unwrap(),
+        Some(DiscoveryEvent::RelayPoolUpdated { .. })
+    ) {}
+
+    Node { info, route_table, discovery, command_tx, event_rx }
+}
+
+#[tokio::test]
+async fn test_chain_learns_far_peer_through_gossip() {
+    let net = Arc::new(Net::default());
+    let mut a = spawn_node(&net, 1, &[Capability::Relay]).await;
+    let mut b = spawn_node(&net, 2, &[Capability::Relay]).await;
+    let c = spawn_node(&net, 3, &[Capability::Relay, Capability::Storage]).await;
+
+    // A - B - C: A and C only know B
+    a.add_neighbour(&b.info).await;
+    b.add_neighbour(&a.info).await;
+    b.add_neighbour(&c.info).await;
+    c.add_neighbour(&b.info).await;
+    assert!(a.knows(&c.info).await.is_none());
+
+    for _ in 0..5 {
+        if a.knows(&c.info).await.is_some() {
+            break;
+        }
+        b.discover().await;
+        a.discover().await;
+    }
+
+    let learned = a.knows(&c.info).await.expect("A should learn about C through B");
+    assert_eq!(learned.addresses, c.info.addresses);
+    assert!(learned.capabilities.contains(&Capability::Storage));
+    assert!(a.knows(&a.info).await.is_none(), "nodes don't add themselves");
+    assert!(!a.route_table.is_quarantined(&b.info.peer_id).await);
+}
+
+#[tokio::test]
+async fn test_exchange_answers_are_capped_and_rate_limited() {
+    let net = Arc::new(Net::default());
+    let a = spawn_node(&net, 1, &[Capability::Relay]).await;
+
+    let route_table = Arc::new(RouteTable::new());
+    let (event_tx, _event_rx) = mpsc::channel(100);
+    let config = DiscoveryConfig { peer_exchange_count: 2, ..DiscoveryConfig::default() };
+    let b = OverlayDiscovery::new(config, route_table.clone(), event_tx)
+        .with_identity(&[2; 32], local_info(2, &[Capability::Relay]));
+
+    let ask = |count| PeerExchangeRequest { count, capabilities: Vec::new() };
+    let answer = b.answer_exchange(&a.info.peer_id, ask(0)).await.unwrap();
+    assert!(answer.peers.is_empty(), "answers never hold more than was asked for");
+
+    // Asking again straight away is refused, and held against the asker
+    let refused = b.answer_exchange(&a.info.peer_id, ask(1)).await.unwrap_err();
+    assert_eq!(refused.code, RpcError::rate_limited().code);
+    assert!(route_table.reputation(&a.info.peer_id).await < 0.5);
+
+    // Another peer asking for far more than the limit gets at most peer_exchange_count
+    let other = PeerId::from_bytes(vec![9]);
+    let answer = b.answer_exchange(&other, ask(1000)).await.unwrap();
+    assert!(answer.peers.len() <= 2);
+    assert!(answer.peers.iter().all(|peer| peer.verify(Duration::from_secs(60)).is_ok()));
+}
+
+#[tokio::test]
+async fn test_exchange_filters_by_capability() {
+    let net = Arc::new(Net::default());
+    let a = spawn_node(&net, 1, &[]).await;
+    let mut b = spawn_node(&net, 2, &[Capability::Relay]).await;
+    let relay = spawn_node(&net, 3, &[Capability::Relay]).await;
+    let ws = spawn_node(&net, 4, &[Capability::Relay, Capability::WebSocket]).await;
+
+    for peer in [&relay.info, &ws.info] {
+        b.add_neighbour(peer).await;
+    }
+    b.discover().await;
+
+    let request = PeerExchangeRequest { count: 10, capabilities: vec!["ws".to_string()] };
+    let answer = b.discovery.answer_exchange(&a.info.peer_id, request).await.unwrap();
+    let ids: Vec<&Vec<u8>> = answer.peers.iter().map(|peer| &peer.peer_id_bytes).collect();
+    assert_eq!(ids, vec![&ws.info.peer_id.0]);
+}
+
+/// Answers every exchange with a tampered descriptor for `victim`
+struct Forger {
+    victim: PeerDescriptor,
+}
+
+#[async_trait]
+impl ExchangeTransport for Forger {
+    async fn exchange(
+        &self,
+        _peer: PeerId,
+        _request: PeerExchangeRequest,
+    ) -> Result<PeerExchangeResponse, RpcError> {
+        let mut forged = self.victim.clone();
+        forged.addresses = vec!["203.0.113.66:7000".to_string()];
+        Ok(PeerExchangeResponse { peers: vec![forged] })
+    }
+}
+
+#[tokio::test]
+async fn test_forged_descriptors_are_rejected() {
+    let victim = local_info(3, &[Capability::Relay]);
+    let signing_key = ed25519_dalek::SigningKey::from_bytes(&[3; 32]);
+    let forger = Forger { victim: PeerDescriptor::from_peer_info(&victim).sign(&signing_key) };
+
+    let route_table = Arc::new(RouteTable::new());
+    let liar = local_info(2, &[Capability::Relay]);
+    route_table
+        .handle_command(RouteTableCommand::InsertPeer(liar.clone()))
+        .await
+        .unwrap();
+    let (event_tx, mut event_rx) = mpsc::channel(100);
+    let (command_tx, command_rx) = mpsc::channel(100);
+    let discovery = Arc::new(
+        OverlayDiscovery::new(DiscoveryConfig::default(), route_table.clone(), event_tx)
+            .with_transport(Arc::new(forger)),
+    );
+    tokio::spawn(discovery.run(command_rx));
+
+    for _ in 0..2 {
+        command_tx.send(DiscoveryCommand::TriggerDiscovery).await.unwrap();
+        while !matches!(
+            timeout(Duration::from_secs(2), event_rx.recv()).await.unwrap(),
+            Some(DiscoveryEvent::RelayPoolUpdated { .. })
+        ) {}
+    }
+
+    let (tx, rx) = oneshot::channel();
+    let command = RouteTableCommand::GetPeer { peer_id: victim.peer_id.clone(), response_tx: tx };
+    route_table.handle_command(command).await.unwrap();
+    assert!(rx.await.unwrap().is_none(), "forged descriptor merged");
+    assert!(route_table.reputation(&liar.peer_id).await < 0.5);
+}
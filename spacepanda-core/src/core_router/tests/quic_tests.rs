@@ -0,0 +1,248 @@
+/*
+ * QUIC TRANSPORT INTEROP TESTS
+ *
+ * Two in-process nodes talk over real QUIC on localhost:
+ * 1. DhtMessages through the session layer, which trusts QUIC's authentication
+ * 2. MlsEnvelopes straight over the transport, one stream each
+ * 3. Dialing with the wrong expected PeerId fails the handshake
+ * 4. Connections survive the dialing side moving to a new address
+ */
+
+#![cfg(test)]
+
+use super::super::{
+    negotiate_transport, Capability, PeerId, PeerInfo, QuicIdentity, SessionCommand, SessionEvent,
+    SessionManager, TransportCommand, TransportEvent, TransportKind, TransportManager,
+};
+use crate::core_dht::message::DhtMessage;
+use crate::core_dht::DhtKey;
+use crate::core_mls::types::GroupId;
+use crate::core_mls::{MlsEnvelope, MlsMessageType};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::time::{timeout, Duration};
+
+struct QuicNode {
+    peer_id: PeerId,
+    addr: String,
+    transport: Arc<TransportManager>,
+    events: mpsc::Receiver<TransportEvent>,
+}
+
+async fn quic_node(seed: u8) -> QuicNode {
+    let identity = QuicIdentity::from_seed(&[seed; 32]).unwrap();
+    let peer_id = identity.peer_id().clone();
+    let (event_tx, events) = mpsc::channel(100);
+    let transport = TransportManager::new(event_tx).with_quic_identity(identity);
+    let addr = transport.listen_quic("127.0.0.1:0").await.unwrap();
+    QuicNode { peer_id, addr, transport: Arc::new(transport), events }
+}
+
+async fn next_event(events: &mut mpsc::Receiver<TransportEvent>) -> TransportEvent {
+    timeout(Duration::from_secs(5), events.recv())
+        .await
+        .expect("Timeout waiting for transport event")
+        .expect("Event channel closed")
+}
+
+/// Dial b from a and return both sides' conn_ids after checking who they authenticated
+async fn connect(a: &mut QuicNode, b: &mut QuicNode) -> (u64, u64) {
+    a.transport
+        .handle_command(TransportCommand::DialQuic(b.addr.clone(), b.peer_id.0.clone()))
+        .await
+        .unwrap();
+
+    let TransportEvent::Authenticated(a_conn, _, peer) = next_event(&mut a.events).await else {
+        panic!("Expected Authenticated on the dialing side");
+    };
+    assert_eq!(peer, b.peer_id.0);
+    let TransportEvent::Authenticated(b_conn, _, peer) = next_event(&mut b.events).await else {
+        panic!("Expected Authenticated on the accepting side");
+    };
+    assert_eq!(peer, a.peer_id.0);
+    (a_conn, b_conn)
+}
+
+/// Run a SessionManager on top of node's transport
+fn with_session(node: QuicNode) -> (Arc<SessionManager>, mpsc::Receiver<SessionEvent>) {
+    let (command_tx, mut command_rx) = mpsc::channel(100);
+    let (session_tx, session_rx) = mpsc::channel(100);
+    let session =
+        Arc::new(SessionManager::new(SessionManager::generate_keypair(), command_tx, session_tx));
+
+    let transport = node.transport.clone();
+    tokio::spawn(async move {
+        while let Some(command) = command_rx.recv().await {
+            transport.handle_command(command).await.unwrap();
+        }
+    });
+    let mut events = node.events;
+    let session_events = session.clone();
+    tokio::spawn(async move {
+        while let Some(event) = events.recv().await {
+            session_events.handle_transport_event(event).await.unwrap();
+        }
+    });
+    (session, session_rx)
+}
+
+async fn next_session_event(events: &mut mpsc::Receiver<SessionEvent>) -> SessionEvent {
+    timeout(Duration::from_secs(5), events.recv())
+        .await
+        .expect("Timeout waiting for session event")
+        .expect("Session channel closed")
+}
+
+#[tokio::test]
+async fn test_dht_messages_over_quic_sessions() {
+    let a = quic_node(1).await;
+    let b = quic_node(2).await;
+    let (a_id, b_id, b_addr) = (a.peer_id.clone(), b.peer_id.clone(), b.addr.clone());
+    let a_transport = a.transport.clone();
+    let (a_session, mut a_events) = with_session(a);
+    let (_b_session, mut b_events) = with_session(b);
+
+    a_transport
+        .handle_command(TransportCommand::DialQuic(b_addr, b_id.0.clone()))
+        .await
+        .unwrap();
+
+    // No Noise handshake: QUIC already proved both identities
+    let SessionEvent::Established(peer, _) = next_session_event(&mut a_events).await else {
+        panic!("Expected Established");
+    };
+    assert_eq!(peer, b_id);
+    let SessionEvent::Established(peer, _) = next_session_event(&mut b_events).await else {
+        panic!("Expected Established");
+    };
+    assert_eq!(peer, a_id);
+
+    let sender_id = DhtKey::hash(b"node-a");
+    for request_id in 0..20u64 {
+        let message = DhtMessage::FindNode {
+            sender_id,
+            target: DhtKey::hash(&request_id.to_be_bytes()),
+            request_id,
+        };
+        let bytes = serde_json::to_vec(&message).unwrap();
+        a_session
+            .handle_command(SessionCommand::SendPlaintext(b_id.clone(), bytes))
+            .await
+            .unwrap();
+    }
+
+    // Each message has its own stream, so arrival order isn't fixed
+    let mut received = HashSet::new();
+    while received.len() < 20 {
+        let SessionEvent::PlaintextFrame(peer, bytes) = next_session_event(&mut b_events).await
+        else {
+            panic!("Expected PlaintextFrame");
+        };
+        assert_eq!(peer, a_id);
+        match serde_json::from_slice(&bytes).unwrap() {
+            DhtMessage::FindNode { sender_id: from, target, request_id } => {
+                assert_eq!(from, sender_id);
+                assert_eq!(target, DhtKey::hash(&request_id.to_be_bytes()));
+                received.insert(request_id);
+            }
+            other => panic!("Unexpected message {:?}", other),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_mls_envelopes_over_quic() {
+    let mut a = quic_node(3).await;
+    let mut b = quic_node(4).await;
+    let (a_conn, b_conn) = connect(&mut a, &mut b).await;
+
+    let group_id = GroupId::random();
+    let envelopes: Vec<MlsEnvelope> = (0..4u8)
+        .map(|i| MlsEnvelope {
+            version: MlsEnvelope::VERSION,
+            message_type: MlsMessageType::Application,
+            group_id: group_id.clone(),
+            sender: Some(i as u32),
+            // Well past one QUIC packet
+            payload: vec![i; 200_000],
+        })
+        .collect();
+    for envelope in &envelopes {
+        let bytes = envelope.to_bytes().unwrap();
+        a.transport.handle_command(TransportCommand::Send(a_conn, bytes)).await.unwrap();
+    }
+
+    let mut received = Vec::new();
+    while received.len() < envelopes.len() {
+        let TransportEvent::Data(conn_id, bytes) = next_event(&mut b.events).await else {
+            panic!("Expected Data");
+        };
+        assert_eq!(conn_id, b_conn);
+        received.push(MlsEnvelope::from_bytes(&bytes).unwrap());
+    }
+    received.sort_by_key(|envelope| envelope.sender);
+    assert_eq!(received, envelopes);
+
+    // And back the other way
+    let reply = envelopes[0].to_bytes().unwrap();
+    b.transport.handle_command(TransportCommand::Send(b_conn, reply)).await.unwrap();
+    let TransportEvent::Data(conn_id, bytes) = next_event(&mut a.events).await else {
+        panic!("Expected Data");
+    };
+    assert_eq!(conn_id, a_conn);
+    assert_eq!(MlsEnvelope::from_bytes(&bytes).unwrap(), envelopes[0]);
+}
+
+#[tokio::test]
+async fn test_quic_rejects_unexpected_peer() {
+    let a = quic_node(5).await;
+    let b = quic_node(6).await;
+    let impostor = QuicIdentity::from_seed(&[7; 32]).unwrap();
+
+    let result = a
+        .transport
+        .handle_command(TransportCommand::DialQuic(b.addr.clone(), impostor.peer_id().0.clone()))
+        .await;
+    assert!(result.is_err(), "Dial must fail when the certificate proves another PeerId");
+}
+
+#[tokio::test]
+async fn test_quic_connection_survives_rebind() {
+    let mut a = quic_node(8).await;
+    let mut b = quic_node(9).await;
+    let (a_conn, b_conn) = connect(&mut a, &mut b).await;
+
+    // A roams to a new address; the connection follows it
+    a.transport
+        .handle_command(TransportCommand::RebindQuic("127.0.0.1:0".to_string()))
+        .await
+        .unwrap();
+    a.transport
+        .handle_command(TransportCommand::Send(a_conn, b"after".to_vec()))
+        .await
+        .unwrap();
+
+    let event = next_event(&mut b.events).await;
+    assert!(
+        matches!(&event, TransportEvent::Data(id, bytes) if *id == b_conn && bytes == b"after"),
+        "Expected data on the migrated connection, got {:?}",
+        event
+    );
+    b.transport
+        .handle_command(TransportCommand::Send(b_conn, b"back".to_vec()))
+        .await
+        .unwrap();
+    let event = next_event(&mut a.events).await;
+    assert!(matches!(&event, TransportEvent::Data(id, bytes) if *id == a_conn && bytes == b"back"));
+}
+
+#[test]
+fn test_transport_negotiation() {
+    let mut peer = PeerInfo::new(PeerId::from_bytes(vec![1]), vec!["10.0.0.1:7000".to_string()]);
+    assert_eq!(negotiate_transport(&[Capability::Quic], &peer), TransportKind::Tcp);
+
+    peer.capabilities.push(Capability::Quic);
+    assert_eq!(negotiate_transport(&[Capability::Quic], &peer), TransportKind::Quic);
+    assert_eq!(negotiate_transport(&[Capability::Relay], &peer), TransportKind::Tcp);
+}
@@ -419,6 +419,7 @@ async fn test_onion_relay_privacy() {
         circuit_hops: 3,
         mixing_enabled: false,
         mixing_window: Duration::from_millis(100),
+        ..OnionConfig::default()
     };
 
     let route_table = Arc::new(RouteTable::new());
@@ -498,6 +499,9 @@ async fn test_onion_relay_privacy() {
             OnionEvent::DeliverLocal { .. } => {
                 // This would be at the destination, not observed by relay
             }
+            OnionEvent::CellForward { .. } => {
+                // Circuit cells only flow once a circuit is built
+            }
             OnionEvent::RelayError { error } => {
                 // Errors are acceptable in test scenarios
                 println!("Relay error (expected in test): {}", error);
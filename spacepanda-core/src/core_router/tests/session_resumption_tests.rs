@@ -0,0 +1,308 @@
+/*
+ * SESSION RESUMPTION TESTS
+ *
+ * Two SessionManagers handshake over an in-process wire that counts messages:
+ * 1. Reconnecting resumes the session in two messages instead of three, and keeps resuming
+ * 2. A listener that lost the ticket, or one past its expiry, falls back to a full handshake
+ * 3. A stolen ticket doesn't let another static key pass as the peer
+ * 4. A resumption request can't be replayed, since every resumption rotates the ticket
+ * 5. Tickets in the ticket store survive restarting both sides
+ */
+
+#![cfg(test)]
+
+use super::super::{
+    PeerId, SessionCommand, SessionEvent, SessionManager, TransportCommand, TransportEvent,
+};
+use std::path::PathBuf;
+use tokio::sync::mpsc;
+use tokio::time::Duration;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+const LISTENER_ADDR: &str = "127.0.0.1:9000";
+
+struct Node {
+    keypair: Vec<u8>,
+    manager: SessionManager,
+    transport_rx: mpsc::Receiver<TransportCommand>,
+    event_rx: mpsc::Receiver<SessionEvent>,
+}
+
+impl Node {
+    fn new(keypair: Vec<u8>) -> Self {
+        Self::with(keypair, |manager| manager)
+    }
+
+    fn with(keypair: Vec<u8>, configure: impl FnOnce(SessionManager) -> SessionManager) -> Self {
+        let (transport_tx, transport_rx) = mpsc::channel(100);
+        let (event_tx, event_rx) = mpsc::channel(100);
+        let manager = configure(SessionManager::new(keypair.clone(), transport_tx, event_tx));
+        Node { keypair, manager, transport_rx, event_rx }
+    }
+
+    fn stored(keypair: Vec<u8>, store: PathBuf) -> Self {
+        Self::with(keypair, |manager| manager.with_ticket_store(store).unwrap())
+    }
+
+    fn peer_id(&self) -> PeerId {
+        let id = self.manager.local_peer_id();
+        let private: [u8; 32] = self.keypair.as_slice().try_into().unwrap();
+        assert_eq!(id.0, PublicKey::from(&StaticSecret::from(private)).as_bytes());
+        id
+    }
+
+    fn events(&mut self) -> Vec<SessionEvent> {
+        let mut events = Vec::new();
+        while let Ok(event) = self.event_rx.try_recv() {
+            events.push(event);
+        }
+        events
+    }
+}
+
+/// What crossed the wire while connecting
+#[derive(Default)]
+struct Wire {
+    /// Messages from the dialer, in order
+    dialed: Vec<Vec<u8>>,
+    /// Messages from the listener, in order
+    answered: Vec<Vec<u8>>,
+}
+
+impl Wire {
+    fn messages(&self) -> usize {
+        self.dialed.len() + self.answered.len()
+    }
+}
+
+/// Deliver whatever either side sends until both go quiet
+async fn pump(dialer: &mut Node, listener: &mut Node, conn_id: u64) -> Wire {
+    let mut wire = Wire::default();
+    loop {
+        let mut moved = false;
+        while let Ok(command) = dialer.transport_rx.try_recv() {
+            if let TransportCommand::Send(_, bytes) = command {
+                moved = true;
+                wire.dialed.push(bytes.clone());
+                let _ = listener
+                    .manager
+                    .handle_transport_event(TransportEvent::Data(conn_id, bytes))
+                    .await;
+            }
+        }
+        while let Ok(command) = listener.transport_rx.try_recv() {
+            if let TransportCommand::Send(_, bytes) = command {
+                moved = true;
+                wire.answered.push(bytes.clone());
+                let _ = dialer
+                    .manager
+                    .handle_transport_event(TransportEvent::Data(conn_id, bytes))
+                    .await;
+            }
+        }
+        if !moved {
+            return wire;
+        }
+    }
+}
+
+/// Dial the listener on a fresh conn_id and run the handshake to the end
+async fn connect(dialer: &mut Node, listener: &mut Node, conn_id: u64) -> Wire {
+    let dialed = TransportEvent::Connected(conn_id, LISTENER_ADDR.to_string(), true);
+    dialer.manager.handle_transport_event(dialed).await.unwrap();
+    let accepted = TransportEvent::Connected(conn_id, "127.0.0.1:50000".to_string(), false);
+    listener.manager.handle_transport_event(accepted).await.unwrap();
+    pump(dialer, listener, conn_id).await
+}
+
+async fn disconnect(dialer: &mut Node, listener: &mut Node, conn_id: u64) {
+    for node in [dialer, listener] {
+        node.manager
+            .handle_transport_event(TransportEvent::Disconnected(conn_id))
+            .await
+            .unwrap();
+        node.events();
+    }
+}
+
+fn assert_established(events: &[SessionEvent], peer: &PeerId, conn_id: u64) {
+    assert!(
+        matches!(events, [SessionEvent::Established(p, c)] if p == peer && *c == conn_id),
+        "Expected a full handshake with {:?}, got {:?}",
+        peer,
+        events
+    );
+}
+
+fn assert_resumed(events: &[SessionEvent], peer: &PeerId, conn_id: u64) {
+    assert!(
+        matches!(events, [SessionEvent::Resumed(p, c)] if p == peer && *c == conn_id),
+        "Expected a resumed session with {:?}, got {:?}",
+        peer,
+        events
+    );
+}
+
+/// Send a frame from the dialer and check the listener decrypts it
+async fn exchange(dialer: &mut Node, listener: &mut Node, conn_id: u64) {
+    let send = SessionCommand::SendPlaintext(listener.peer_id(), b"hello again".to_vec());
+    dialer.manager.handle_command(send).await.unwrap();
+    pump(dialer, listener, conn_id).await;
+    let events = listener.events();
+    assert!(
+        matches!(&events[..], [SessionEvent::PlaintextFrame(p, data)]
+            if *p == dialer.peer_id() && data == b"hello again"),
+        "Expected the frame to arrive, got {:?}",
+        events
+    );
+}
+
+#[tokio::test]
+async fn test_reconnect_resumes_session() {
+    let mut alice = Node::new(SessionManager::generate_keypair());
+    let mut bob = Node::new(SessionManager::generate_keypair());
+
+    let wire = connect(&mut alice, &mut bob, 1).await;
+    assert_eq!(wire.messages(), 3);
+    assert_established(&alice.events(), &bob.peer_id(), 1);
+    assert_established(&bob.events(), &alice.peer_id(), 1);
+    disconnect(&mut alice, &mut bob, 1).await;
+
+    // Each resumption rotates the ticket, and the next one resumes from the new ticket
+    for conn_id in 2..5 {
+        let wire = connect(&mut alice, &mut bob, conn_id).await;
+        assert_eq!(wire.messages(), 2, "Resuming takes one round trip");
+        assert_resumed(&alice.events(), &bob.peer_id(), conn_id);
+        assert_resumed(&bob.events(), &alice.peer_id(), conn_id);
+        exchange(&mut alice, &mut bob, conn_id).await;
+        disconnect(&mut alice, &mut bob, conn_id).await;
+    }
+}
+
+#[tokio::test]
+async fn test_unknown_ticket_falls_back_to_full_handshake() {
+    let bob_keypair = SessionManager::generate_keypair();
+    let mut alice = Node::new(SessionManager::generate_keypair());
+    let mut bob = Node::new(bob_keypair.clone());
+    connect(&mut alice, &mut bob, 1).await;
+    disconnect(&mut alice, &mut bob, 1).await;
+    alice.events();
+
+    // Bob restarts without a ticket store and has forgotten the ticket
+    let mut bob = Node::new(bob_keypair);
+    let wire = connect(&mut alice, &mut bob, 2).await;
+    assert_eq!(wire.messages(), 5, "Rejected resumption, then a full handshake");
+    assert_established(&alice.events(), &bob.peer_id(), 2);
+    assert_established(&bob.events(), &alice.peer_id(), 2);
+    exchange(&mut alice, &mut bob, 2).await;
+    disconnect(&mut alice, &mut bob, 2).await;
+
+    // The full handshake issued a fresh ticket
+    let wire = connect(&mut alice, &mut bob, 3).await;
+    assert_eq!(wire.messages(), 2);
+    assert_resumed(&bob.events(), &alice.peer_id(), 3);
+}
+
+#[tokio::test]
+async fn test_expired_ticket_forces_full_handshake() {
+    let mut alice =
+        Node::with(SessionManager::generate_keypair(), |m| m.with_ticket_lifetime(Duration::ZERO));
+    let mut bob = Node::new(SessionManager::generate_keypair());
+    connect(&mut alice, &mut bob, 1).await;
+    disconnect(&mut alice, &mut bob, 1).await;
+    alice.events();
+
+    // Alice doesn't even offer her expired ticket
+    let wire = connect(&mut alice, &mut bob, 2).await;
+    assert_eq!(wire.messages(), 3);
+    assert_established(&alice.events(), &bob.peer_id(), 2);
+
+    // Bob, whose tickets last, refuses one past his own copy's expiry
+    let mut alice = Node::new(SessionManager::generate_keypair());
+    let mut bob =
+        Node::with(SessionManager::generate_keypair(), |m| m.with_ticket_lifetime(Duration::ZERO));
+    connect(&mut alice, &mut bob, 1).await;
+    disconnect(&mut alice, &mut bob, 1).await;
+    alice.events();
+    let wire = connect(&mut alice, &mut bob, 2).await;
+    assert_eq!(wire.messages(), 5);
+    assert_established(&alice.events(), &bob.peer_id(), 2);
+    assert_established(&bob.events(), &alice.peer_id(), 2);
+}
+
+#[tokio::test]
+async fn test_stolen_ticket_cannot_impersonate() {
+    let dir = tempfile::tempdir().unwrap();
+    let alice_store = dir.path().join("alice-tickets.json");
+    let alice_keypair = SessionManager::generate_keypair();
+    let mut alice = Node::stored(alice_keypair.clone(), alice_store.clone());
+    let mut bob = Node::new(SessionManager::generate_keypair());
+    connect(&mut alice, &mut bob, 1).await;
+    disconnect(&mut alice, &mut bob, 1).await;
+    alice.events();
+
+    // Mallory copies Alice's ticket store, but not her static key
+    let mallory_store = dir.path().join("mallory-tickets.json");
+    std::fs::copy(&alice_store, &mallory_store).unwrap();
+    let mut mallory = Node::stored(SessionManager::generate_keypair(), mallory_store);
+    let wire = connect(&mut mallory, &mut bob, 2).await;
+    assert_eq!(wire.messages(), 5, "The stolen ticket is refused");
+    assert_established(&bob.events(), &mallory.peer_id(), 2);
+    assert_ne!(mallory.peer_id(), alice.peer_id());
+    disconnect(&mut mallory, &mut bob, 2).await;
+
+    // The failed attempt didn't burn Alice's ticket
+    let wire = connect(&mut alice, &mut bob, 3).await;
+    assert_eq!(wire.messages(), 2);
+    assert_resumed(&bob.events(), &alice.peer_id(), 3);
+}
+
+#[tokio::test]
+async fn test_replayed_resumption_rejected() {
+    let mut alice = Node::new(SessionManager::generate_keypair());
+    let mut bob = Node::new(SessionManager::generate_keypair());
+    connect(&mut alice, &mut bob, 1).await;
+    disconnect(&mut alice, &mut bob, 1).await;
+
+    let wire = connect(&mut alice, &mut bob, 2).await;
+    assert_resumed(&bob.events(), &alice.peer_id(), 2);
+
+    // Someone who recorded the request plays it back on a new connection
+    let request = wire.dialed[0].clone();
+    let accepted = TransportEvent::Connected(3, "10.0.0.66:4000".to_string(), false);
+    bob.manager.handle_transport_event(accepted).await.unwrap();
+    bob.manager
+        .handle_transport_event(TransportEvent::Data(3, request))
+        .await
+        .unwrap();
+    match bob.transport_rx.try_recv() {
+        Ok(TransportCommand::Send(3, bytes)) => assert_eq!(bytes, b"SPRS\0"),
+        other => panic!("Expected the replay to be refused, got {:?}", other),
+    }
+    assert!(bob.events().is_empty());
+}
+
+#[tokio::test]
+async fn test_tickets_survive_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let (alice_keypair, bob_keypair) =
+        (SessionManager::generate_keypair(), SessionManager::generate_keypair());
+    let alice_store = dir.path().join("alice.json");
+    let bob_store = dir.path().join("bob.json");
+    let mut alice = Node::stored(alice_keypair.clone(), alice_store.clone());
+    let mut bob = Node::stored(bob_keypair.clone(), bob_store.clone());
+    connect(&mut alice, &mut bob, 1).await;
+    disconnect(&mut alice, &mut bob, 1).await;
+    connect(&mut alice, &mut bob, 2).await;
+    disconnect(&mut alice, &mut bob, 2).await;
+    drop((alice, bob));
+
+    // Both restart from their stores, holding the ticket the last resumption rotated to
+    let mut alice = Node::stored(alice_keypair, alice_store);
+    let mut bob = Node::stored(bob_keypair, bob_store);
+    let wire = connect(&mut alice, &mut bob, 1).await;
+    assert_eq!(wire.messages(), 2);
+    assert_resumed(&alice.events(), &bob.peer_id(), 1);
+    assert_resumed(&bob.events(), &alice.peer_id(), 1);
+    exchange(&mut alice, &mut bob, 1).await;
+}
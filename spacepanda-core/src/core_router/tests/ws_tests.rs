@@ -0,0 +1,294 @@
+/*
+ * WEBSOCKET TRANSPORT TESTS
+ *
+ * Nodes run the full TransportManager -> SessionManager -> RpcProtocol stack:
+ * 1. RPC echo both ways over a WebSocket connection (Noise runs on top as over TCP)
+ * 2. A two-hop route: browser-style client -> relay over WS, relay -> target over TCP
+ * 3. Keepalive pings hold an idle connection open; a closing peer is noticed
+ * 4. wss:// with the listener's cert and key loaded from ServerConfig paths
+ */
+
+#![cfg(test)]
+
+use super::super::{
+    ws_tls_config, PeerId, RpcCommand, RpcError, RpcProtocol, RpcRequest, SessionEvent,
+    SessionManager, TransportCommand, TransportEvent, TransportManager,
+};
+use crate::config::ServerConfig;
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{sleep, timeout, Duration};
+
+struct Node {
+    transport: Arc<TransportManager>,
+    rpc: Arc<RpcProtocol>,
+    established: mpsc::Receiver<PeerId>,
+}
+
+/// Wire transport, session and RPC layers together like the router does
+fn node(transport: TransportManager, mut transport_events: mpsc::Receiver<TransportEvent>) -> Node {
+    let transport = Arc::new(transport);
+    let (transport_tx, mut transport_rx) = mpsc::channel(100);
+    let (session_event_tx, mut session_events) = mpsc::channel(100);
+    let session = Arc::new(SessionManager::new(
+        SessionManager::generate_keypair(),
+        transport_tx,
+        session_event_tx,
+    ));
+    let (session_tx, mut session_rx) = mpsc::channel(100);
+    let rpc = Arc::new(RpcProtocol::new(session_tx));
+    let (established_tx, established) = mpsc::channel(10);
+
+    let commands = transport.clone();
+    tokio::spawn(async move {
+        while let Some(command) = transport_rx.recv().await {
+            let _ = commands.handle_command(command).await;
+        }
+    });
+    let session_transport = session.clone();
+    tokio::spawn(async move {
+        while let Some(event) = transport_events.recv().await {
+            let _ = session_transport.handle_transport_event(event).await;
+        }
+    });
+    let session_commands = session.clone();
+    tokio::spawn(async move {
+        while let Some(command) = session_rx.recv().await {
+            let _ = session_commands.handle_command(command).await;
+        }
+    });
+    let rpc_events = rpc.clone();
+    tokio::spawn(async move {
+        while let Some(event) = session_events.recv().await {
+            if let SessionEvent::Established(peer_id, _) = &event {
+                let _ = established_tx.send(peer_id.clone()).await;
+            }
+            let _ = rpc_events.handle_session_event(event).await;
+        }
+    });
+
+    Node { transport, rpc, established }
+}
+
+fn plain_node() -> Node {
+    let (event_tx, event_rx) = mpsc::channel(100);
+    node(TransportManager::new(event_tx), event_rx)
+}
+
+async fn next_established(node: &mut Node) -> PeerId {
+    timeout(Duration::from_secs(5), node.established.recv())
+        .await
+        .expect("Timeout waiting for session")
+        .expect("Session channel closed")
+}
+
+/// Answer `echo` with its params
+async fn serve_echo(rpc: &RpcProtocol) {
+    let (handler_tx, mut handler_rx) = mpsc::channel::<RpcRequest>(10);
+    rpc.handle_command(RpcCommand::RegisterHandler { method: "echo".to_string(), handler_tx })
+        .await
+        .unwrap();
+    tokio::spawn(async move {
+        while let Some(request) = handler_rx.recv().await {
+            let _ = request.response_tx.send(Ok(request.params));
+        }
+    });
+}
+
+async fn call(
+    rpc: &RpcProtocol,
+    peer_id: PeerId,
+    method: &str,
+    params: serde_json::Value,
+) -> Result<serde_json::Value, RpcError> {
+    let (response_tx, response_rx) = oneshot::channel();
+    rpc.handle_command(RpcCommand::Call {
+        peer_id,
+        method: method.to_string(),
+        params,
+        response_tx,
+    })
+    .await
+    .unwrap();
+    timeout(Duration::from_secs(5), response_rx)
+        .await
+        .expect("RPC timed out")
+        .unwrap()
+}
+
+/// Dial `server`'s WebSocket listener from `client`; returns (server's id, client's id)
+async fn connect_ws(client: &mut Node, server: &mut Node) -> (PeerId, PeerId) {
+    let addr = server.transport.listen_ws("127.0.0.1:0").await.unwrap();
+    client
+        .transport
+        .handle_command(TransportCommand::DialWs(format!("ws://{}", addr)))
+        .await
+        .unwrap();
+    (next_established(client).await, next_established(server).await)
+}
+
+#[tokio::test]
+async fn test_rpc_echo_over_websocket() {
+    let mut browser = plain_node();
+    let mut relay = plain_node();
+    serve_echo(&browser.rpc).await;
+    serve_echo(&relay.rpc).await;
+    let (relay_id, browser_id) = connect_ws(&mut browser, &mut relay).await;
+
+    let reply = call(&browser.rpc, relay_id, "echo", json!({"hello": "relay"})).await.unwrap();
+    assert_eq!(reply, json!({"hello": "relay"}));
+
+    // Frames well past one TCP segment stay whole
+    let big = "x".repeat(40_000);
+    let reply = call(&relay.rpc, browser_id, "echo", json!({ "big": big })).await.unwrap();
+    assert_eq!(reply["big"].as_str().unwrap().len(), 40_000);
+}
+
+#[tokio::test]
+async fn test_mixed_transport_route() {
+    let mut browser = plain_node();
+    let mut relay = plain_node();
+    let mut target = plain_node();
+    serve_echo(&target.rpc).await;
+
+    // First hop: WebSocket
+    let (relay_id, _) = connect_ws(&mut browser, &mut relay).await;
+
+    // Second hop: the native TCP transport
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let target_addr = listener.local_addr().unwrap().to_string();
+    drop(listener);
+    target
+        .transport
+        .handle_command(TransportCommand::Listen(target_addr.clone()))
+        .await
+        .unwrap();
+    relay
+        .transport
+        .handle_command(TransportCommand::Dial(target_addr))
+        .await
+        .unwrap();
+    let target_id = next_established(&mut relay).await;
+    next_established(&mut target).await;
+
+    // The relay passes `forward` calls on to their target over whatever transport reaches it
+    let (handler_tx, mut handler_rx) = mpsc::channel::<RpcRequest>(10);
+    relay
+        .rpc
+        .handle_command(RpcCommand::RegisterHandler { method: "forward".to_string(), handler_tx })
+        .await
+        .unwrap();
+    let relay_rpc = relay.rpc.clone();
+    tokio::spawn(async move {
+        while let Some(request) = handler_rx.recv().await {
+            let target: Vec<u8> = serde_json::from_value(request.params["target"].clone()).unwrap();
+            let result = call(
+                &relay_rpc,
+                PeerId::from_bytes(target),
+                "echo",
+                request.params["payload"].clone(),
+            )
+            .await;
+            let _ = request.response_tx.send(result);
+        }
+    });
+
+    let reply = call(
+        &browser.rpc,
+        relay_id,
+        "forward",
+        json!({"target": target_id.0, "payload": {"via": ["ws", "tcp"]}}),
+    )
+    .await
+    .unwrap();
+    assert_eq!(reply, json!({"via": ["ws", "tcp"]}));
+}
+
+#[tokio::test]
+async fn test_keepalive_and_close() {
+    let (a_tx, mut a_events) = mpsc::channel(100);
+    let a = TransportManager::new(a_tx).with_ws_keepalive(Duration::from_millis(50));
+    let (b_tx, mut b_events) = mpsc::channel(100);
+    let b = TransportManager::new(b_tx).with_ws_keepalive(Duration::from_millis(50));
+
+    let addr = b.listen_ws("127.0.0.1:0").await.unwrap();
+    a.handle_command(TransportCommand::DialWs(format!("ws://{}", addr)))
+        .await
+        .unwrap();
+    let Some(TransportEvent::Connected(a_conn, _, true)) = a_events.recv().await else {
+        panic!("Expected outgoing Connected");
+    };
+    let Some(TransportEvent::Connected(b_conn, _, false)) = b_events.recv().await else {
+        panic!("Expected incoming Connected");
+    };
+
+    // Many keepalive intervals of silence: pings and pongs keep both sides up
+    sleep(Duration::from_millis(400)).await;
+    a.handle_command(TransportCommand::Send(a_conn, b"still here".to_vec()))
+        .await
+        .unwrap();
+    let event = timeout(Duration::from_secs(5), b_events.recv()).await.unwrap();
+    assert!(
+        matches!(&event, Some(TransportEvent::Data(id, bytes)) if *id == b_conn && bytes == b"still here")
+    );
+
+    // A closes; B sees the closing handshake and drops the connection
+    a.handle_command(TransportCommand::Close(a_conn)).await.unwrap();
+    assert!(
+        matches!(a_events.recv().await, Some(TransportEvent::Disconnected(id)) if id == a_conn)
+    );
+    let event = timeout(Duration::from_secs(5), b_events.recv()).await.unwrap();
+    assert!(matches!(event, Some(TransportEvent::Disconnected(id)) if id == b_conn));
+    assert!(b
+        .handle_command(TransportCommand::Send(b_conn, b"gone".to_vec()))
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_websocket_over_tls() {
+    let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let cert_path = dir.path().join("cert.pem");
+    let key_path = dir.path().join("key.pem");
+    std::fs::write(&cert_path, certified.cert.pem()).unwrap();
+    std::fs::write(&key_path, certified.key_pair.serialize_pem()).unwrap();
+
+    let server = ServerConfig {
+        enable_tls: true,
+        tls_cert_path: Some(cert_path),
+        tls_key_path: Some(key_path),
+        ..ServerConfig::default()
+    };
+    let tls = ws_tls_config(&server).unwrap().expect("TLS is enabled");
+    assert!(ws_tls_config(&ServerConfig::default()).unwrap().is_none());
+
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(certified.cert.der().clone()).unwrap();
+    let client_tls = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .unwrap()
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+
+    let (a_tx, a_rx) = mpsc::channel(100);
+    let mut a = node(TransportManager::new(a_tx).with_ws_client_tls(Arc::new(client_tls)), a_rx);
+    let (b_tx, b_rx) = mpsc::channel(100);
+    let mut b = node(TransportManager::new(b_tx).with_ws_tls(tls), b_rx);
+    serve_echo(&b.rpc).await;
+
+    let addr = b.transport.listen_ws("127.0.0.1:0").await.unwrap();
+    let port = addr.rsplit(':').next().unwrap();
+    a.transport
+        .handle_command(TransportCommand::DialWs(format!("wss://localhost:{}", port)))
+        .await
+        .unwrap();
+    let b_id = next_established(&mut a).await;
+    next_established(&mut b).await;
+
+    let reply = call(&a.rpc, b_id, "echo", json!("over tls")).await.unwrap();
+    assert_eq!(reply, json!("over tls"));
+}
@@ -9,7 +9,11 @@
   When dialing, it establishes a socket and returns conn_id.
 
 
-  Handles NAT traversal helpers (STUN/TURN) if configured.
+  NAT traversal runs over an optional datagram socket (real UDP, or an in-process shim in
+  tests). Observe(addr) asks a peer which external address our packets arrive from, and
+  Punch(addr) has both sides probe each other's external address at the same time so that
+  each NAT opens a mapping for the other. A punched path gets a conn_id like any TCP
+  connection; when punching times out the caller falls back to a relay.
 
   Inputs:
   are the following commands:
@@ -17,15 +21,34 @@
     - Listen(addr) -> starts listening on addr for incoming connections
     - Send(conn_id, bytes) -> sends bytes on the specified connection
     - Close(conn_id) -> closes the specified connection
+    - ListenUdp(addr) -> binds the datagram socket used for observation and hole punching
+    - Observe(addr) -> learns our external address as seen by addr
+    - Punch(addr, is_outgoing) -> opens a direct UDP path to a NATed peer
+    - ListenQuic(addr) -> binds the QUIC endpoint (needs a QUIC identity)
+    - DialQuic(addr, peer_id) -> connects over QUIC to the node proving it is peer_id
+    - RebindQuic(addr) -> moves the QUIC endpoint to a new address without dropping connections
+    - ListenWs(addr) -> accepts WebSocket connections (wss:// when a TLS config is set)
+    - DialWs(url) -> connects to a ws:// or wss:// URL, emits Connected like Dial
+    - BindPeer(conn_id, peer_id, class) -> charges the connection's traffic to peer_id
 
   Outputs:
     Emits `TransportEvent::Connected(conn_id, remote_addr)` when a new connection is established.
     Emits `TransportEvent::Data(conn_id, bytes)` when data is received on a connection.
     Emits `TransportEvent::Disconnected(conn_id)` when a connection is closed.
+    Emits `TransportEvent::Authenticated(conn_id, remote_addr, peer_id)` instead of Connected
+    for QUIC connections, whose handshake already proved the remote PeerId.
 
   Important:
   Always perform basic framing (prefix length) on the bytes sent/received to avoid message boundary issues.
-  Keep this module ignorant of identities; its only bytes and addresses.
+  Keep this module ignorant of identities; its only bytes and addresses. The exception is
+  QUIC, whose TLS handshake authenticates peers (see quic_transport.rs).
+  WebSocket connections (ws_transport.rs) skip the length prefix: one binary message is one frame.
+
+  Bandwidth:
+  Every message sent or received on any transport passes the BandwidthMeter (bandwidth.rs).
+  Once a connection is bound to a peer, its traffic counts against that peer's soft limit
+  (sends wait and reads stall, which pushes back on the sender) and hard limit (the
+  connection is closed and Disconnected is emitted).
 
 ┌─────────────────────────────────────────────────────────┐
 │                   TransportManager                       │
@@ -56,13 +79,20 @@ Internal Tasks:
                                   Events sent here
 
 */
+use async_trait::async_trait;
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
-use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{mpsc, Mutex};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::{mpsc, oneshot, Mutex};
+
+use super::bandwidth::{BandwidthMeter, Direction, PeerClass};
+use super::quic_transport::{QuicIdentity, QuicTransport};
+use super::session_manager::PeerId;
+use super::ws_transport::WsTransport;
 
 #[derive(Debug)]
 pub enum TransportCommand {
@@ -70,6 +100,19 @@ pub enum TransportCommand {
     Listen(String),
     Send(u64, Vec<u8>),
     Close(u64),
+    ListenUdp(String),
+    Observe(String),
+    // remote external addr, is_outgoing (true on the side that asked for the connection)
+    Punch(String, bool),
+    ListenQuic(String),
+    // addr, PeerId bytes the remote certificate must prove
+    DialQuic(String, Vec<u8>),
+    RebindQuic(String),
+    ListenWs(String),
+    // ws:// or wss:// URL
+    DialWs(String),
+    // conn_id, PeerId bytes of the session on it, class its traffic is labeled with
+    BindPeer(u64, Vec<u8>, PeerClass),
 }
 
 #[derive(Debug, Clone)]
@@ -78,6 +121,59 @@ pub enum TransportEvent {
     Connected(u64, String, bool),
     Data(u64, Vec<u8>),
     Disconnected(u64),
+    // conn_id, remote_addr, PeerId bytes proven by the transport's own handshake (QUIC)
+    Authenticated(u64, String, Vec<u8>),
+}
+
+/// Unreliable datagram socket carrying observation, hole punching and punched traffic
+#[async_trait]
+pub trait DatagramSocket: Send + Sync {
+    /// Send one datagram to addr
+    async fn send_to(&self, bytes: &[u8], addr: &str) -> Result<(), String>;
+    /// Receive one datagram and the address it came from
+    async fn recv_from(&self) -> Result<(Vec<u8>, String), String>;
+}
+
+#[async_trait]
+impl DatagramSocket for UdpSocket {
+    async fn send_to(&self, bytes: &[u8], addr: &str) -> Result<(), String> {
+        UdpSocket::send_to(self, bytes, addr)
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("Failed to send datagram to {}: {}", addr, e))
+    }
+
+    async fn recv_from(&self) -> Result<(Vec<u8>, String), String> {
+        let mut buf = vec![0u8; 65_536];
+        let (len, from) = UdpSocket::recv_from(self, &mut buf)
+            .await
+            .map_err(|e| format!("Failed to receive datagram: {}", e))?;
+        buf.truncate(len);
+        Ok((buf, from.to_string()))
+    }
+}
+
+// Datagram packet tags (first byte of every datagram)
+const PACKET_PROBE: u8 = 1;
+const PACKET_ACK: u8 = 2;
+const PACKET_OBSERVE: u8 = 3;
+const PACKET_OBSERVED: u8 = 4;
+const PACKET_DATA: u8 = 5;
+
+/// Largest payload carried in one punched datagram
+const MAX_DATAGRAM_PAYLOAD: usize = 60_000;
+
+/// Time between probes while punching
+const PROBE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// State of the datagram socket: punched paths and in-flight punches/observations
+struct Datagrams {
+    socket: Arc<dyn DatagramSocket>,
+    remotes: HashMap<u64, String>,
+    conn_ids: HashMap<String, u64>,
+    punching: HashMap<String, oneshot::Sender<()>>,
+    observing: HashMap<String, oneshot::Sender<String>>,
+    observed_addr: Option<String>,
 }
 
 pub struct TransportManager {
@@ -85,21 +181,72 @@ pub struct TransportManager {
     connections: Arc<Mutex<HashMap<u64, OwnedWriteHalf>>>,
     next_conn_id: Arc<AtomicU64>,
     event_tx: mpsc::Sender<TransportEvent>,
+    datagrams: Arc<Mutex<Option<Datagrams>>>,
+    punch_timeout: Duration,
+    quic: Arc<Mutex<Option<Arc<QuicTransport>>>>,
+    quic_identity: Option<Arc<QuicIdentity>>,
+    ws: WsTransport,
+    bandwidth: Arc<BandwidthMeter>,
 }
 
 impl TransportManager {
     pub fn new(event_tx: mpsc::Sender<TransportEvent>) -> Self {
+        let next_conn_id = Arc::new(AtomicU64::new(1));
+        let bandwidth = Arc::new(BandwidthMeter::default());
         TransportManager {
             connections: Arc::new(Mutex::new(HashMap::new())),
-            next_conn_id: Arc::new(AtomicU64::new(1)),
+            ws: WsTransport::new(next_conn_id.clone(), event_tx.clone(), bandwidth.clone()),
+            bandwidth,
+            next_conn_id,
             event_tx,
+            datagrams: Arc::new(Mutex::new(None)),
+            punch_timeout: Duration::from_secs(3),
+            quic: Arc::new(Mutex::new(None)),
+            quic_identity: None,
         }
     }
 
+    /// Credentials presented by the QUIC endpoint
+    pub fn with_quic_identity(mut self, identity: QuicIdentity) -> Self {
+        self.quic_identity = Some(Arc::new(identity));
+        self
+    }
+
+    /// Serve WebSocket listeners over TLS (see ws_tls_config)
+    pub fn with_ws_tls(mut self, config: Arc<rustls::ServerConfig>) -> Self {
+        self.ws.set_tls(config);
+        self
+    }
+
+    /// Trusted roots for dialing wss:// URLs
+    pub fn with_ws_client_tls(mut self, config: Arc<rustls::ClientConfig>) -> Self {
+        self.ws.set_client_tls(config);
+        self
+    }
+
+    /// Set how often WebSocket connections are pinged
+    pub fn with_ws_keepalive(mut self, interval: Duration) -> Self {
+        self.ws.set_keepalive(interval);
+        self
+    }
+
+    /// Account and limit every connection's traffic with meter
+    pub fn with_bandwidth_meter(mut self, meter: Arc<BandwidthMeter>) -> Self {
+        self.ws.set_bandwidth_meter(meter.clone());
+        self.bandwidth = meter;
+        self
+    }
+
+    /// Set how long punching and observation wait for the remote side
+    pub fn with_punch_timeout(mut self, timeout: Duration) -> Self {
+        self.punch_timeout = timeout;
+        self
+    }
+
     pub async fn handle_command(&self, command: TransportCommand) -> Result<(), String> {
         match command {
             TransportCommand::Dial(addr) => {
-                self.handle_dial(addr).await?;
+                self.dial(&addr).await?;
             }
             TransportCommand::Listen(addr) => {
                 self.handle_listen(addr).await?;
@@ -110,10 +257,264 @@ impl TransportManager {
             TransportCommand::Close(conn_id) => {
                 self.handle_close(conn_id).await?;
             }
+            TransportCommand::ListenUdp(addr) => {
+                let socket = UdpSocket::bind(&addr)
+                    .await
+                    .map_err(|e| format!("Failed to bind UDP {}: {}", addr, e))?;
+                self.attach_datagram_socket(Arc::new(socket)).await;
+            }
+            TransportCommand::Observe(addr) => {
+                self.observe(&addr).await?;
+            }
+            TransportCommand::Punch(addr, is_outgoing) => {
+                // Punching waits on the remote side, so keep it off the command loop
+                let manager = self.handle();
+                tokio::spawn(async move {
+                    if let Err(e) = manager.punch(&addr, is_outgoing).await {
+                        eprintln!("{}", e);
+                    }
+                });
+            }
+            TransportCommand::ListenQuic(addr) => {
+                self.listen_quic(&addr).await?;
+            }
+            TransportCommand::DialQuic(addr, peer_id) => {
+                self.quic().await?.dial(&addr, PeerId::from_bytes(peer_id)).await?;
+            }
+            TransportCommand::RebindQuic(addr) => {
+                self.quic().await?.rebind(&addr)?;
+            }
+            TransportCommand::ListenWs(addr) => {
+                self.ws.listen(&addr).await?;
+            }
+            TransportCommand::DialWs(url) => {
+                self.ws.dial(&url).await?;
+            }
+            TransportCommand::BindPeer(conn_id, peer_id, class) => {
+                self.bandwidth.bind(conn_id, PeerId::from_bytes(peer_id), class).await;
+            }
         }
         Ok(())
     }
 
+    /// Another handle on the same sockets and connections
+    fn handle(&self) -> TransportManager {
+        TransportManager {
+            connections: self.connections.clone(),
+            next_conn_id: self.next_conn_id.clone(),
+            event_tx: self.event_tx.clone(),
+            datagrams: self.datagrams.clone(),
+            punch_timeout: self.punch_timeout,
+            quic: self.quic.clone(),
+            quic_identity: self.quic_identity.clone(),
+            ws: self.ws.clone(),
+            bandwidth: self.bandwidth.clone(),
+        }
+    }
+
+    /// Bind the QUIC endpoint at addr; returns the bound address
+    pub async fn listen_quic(&self, addr: &str) -> Result<String, String> {
+        let identity = self.quic_identity.clone().ok_or("No QUIC identity configured")?;
+        let quic = QuicTransport::bind(
+            addr,
+            identity,
+            self.next_conn_id.clone(),
+            self.event_tx.clone(),
+            self.bandwidth.clone(),
+        )?;
+        let local_addr = quic.local_addr()?.to_string();
+        *self.quic.lock().await = Some(Arc::new(quic));
+        Ok(local_addr)
+    }
+
+    /// Accept WebSocket connections on addr; returns the bound address
+    pub async fn listen_ws(&self, addr: &str) -> Result<String, String> {
+        self.ws.listen(addr).await
+    }
+
+    /// The bound QUIC endpoint
+    async fn quic(&self) -> Result<Arc<QuicTransport>, String> {
+        self.quic
+            .lock()
+            .await
+            .clone()
+            .ok_or_else(|| "QUIC endpoint not bound".to_string())
+    }
+
+    /// Use socket for observation, hole punching and punched connections
+    pub async fn attach_datagram_socket(&self, socket: Arc<dyn DatagramSocket>) {
+        *self.datagrams.lock().await = Some(Datagrams {
+            socket: socket.clone(),
+            remotes: HashMap::new(),
+            conn_ids: HashMap::new(),
+            punching: HashMap::new(),
+            observing: HashMap::new(),
+            observed_addr: None,
+        });
+
+        let datagrams = self.datagrams.clone();
+        let event_tx = self.event_tx.clone();
+        let bandwidth = self.bandwidth.clone();
+        tokio::spawn(async move {
+            loop {
+                match socket.recv_from().await {
+                    Ok((packet, from)) => {
+                        Self::handle_datagram(&datagrams, &event_tx, &bandwidth, packet, from)
+                            .await;
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        return;
+                    }
+                }
+            }
+        });
+    }
+
+    /// Our external address as last reported by a peer
+    pub async fn observed_addr(&self) -> Option<String> {
+        self.datagrams.lock().await.as_ref().and_then(|d| d.observed_addr.clone())
+    }
+
+    /// Ask the peer at addr which address our datagrams arrive from
+    pub async fn observe(&self, addr: &str) -> Result<String, String> {
+        let (tx, rx) = oneshot::channel();
+        let socket = {
+            let mut datagrams = self.datagrams.lock().await;
+            let datagrams = datagrams.as_mut().ok_or("No datagram socket attached")?;
+            datagrams.observing.insert(addr.to_string(), tx);
+            datagrams.socket.clone()
+        };
+        socket.send_to(&[PACKET_OBSERVE], addr).await?;
+
+        let result = tokio::time::timeout(self.punch_timeout, rx).await;
+        if let Some(datagrams) = self.datagrams.lock().await.as_mut() {
+            datagrams.observing.remove(addr);
+        }
+        match result {
+            Ok(Ok(observed)) => Ok(observed),
+            _ => Err(format!("No observed address from {}", addr)),
+        }
+    }
+
+    /// Open a direct path to the NATed peer whose external address is addr
+    ///
+    /// Both sides must punch at about the same time: each side's probes open a mapping in
+    /// its own NAT that lets the other side's probes through. Emits Connected and returns
+    /// the conn_id once a probe or ack arrives from addr.
+    pub async fn punch(&self, addr: &str, is_outgoing: bool) -> Result<u64, String> {
+        let (tx, mut rx) = oneshot::channel();
+        let socket = {
+            let mut datagrams = self.datagrams.lock().await;
+            let datagrams = datagrams.as_mut().ok_or("No datagram socket attached")?;
+            if let Some(conn_id) = datagrams.conn_ids.get(addr) {
+                return Ok(*conn_id);
+            }
+            datagrams.punching.insert(addr.to_string(), tx);
+            datagrams.socket.clone()
+        };
+
+        let mut probes = tokio::time::interval(PROBE_INTERVAL);
+        let deadline = tokio::time::sleep(self.punch_timeout);
+        tokio::pin!(deadline);
+        let punched = loop {
+            tokio::select! {
+                _ = probes.tick() => {
+                    // Lost probes are expected until both NATs have opened
+                    let _ = socket.send_to(&[PACKET_PROBE], addr).await;
+                }
+                result = &mut rx => break result.is_ok(),
+                _ = &mut deadline => break false,
+            }
+        };
+
+        let conn_id = {
+            let mut datagrams = self.datagrams.lock().await;
+            let datagrams = datagrams.as_mut().ok_or("No datagram socket attached")?;
+            datagrams.punching.remove(addr);
+            if !punched {
+                return Err(format!("Hole punch to {} timed out", addr));
+            }
+            let conn_id = self.next_conn_id.fetch_add(1, Ordering::SeqCst);
+            datagrams.remotes.insert(conn_id, addr.to_string());
+            datagrams.conn_ids.insert(addr.to_string(), conn_id);
+            conn_id
+        };
+        // Our last probe may have been dropped before the remote NAT opened
+        let _ = socket.send_to(&[PACKET_ACK], addr).await;
+
+        if let Err(e) = self
+            .event_tx
+            .send(TransportEvent::Connected(conn_id, addr.to_string(), is_outgoing))
+            .await
+        {
+            eprintln!("Failed to send Connected event: {}", e);
+        }
+        Ok(conn_id)
+    }
+
+    /// Handle one datagram from the attached socket
+    async fn handle_datagram(
+        state: &Mutex<Option<Datagrams>>,
+        event_tx: &mpsc::Sender<TransportEvent>,
+        bandwidth: &BandwidthMeter,
+        packet: Vec<u8>,
+        from: String,
+    ) {
+        let mut guard = state.lock().await;
+        let Some(datagrams) = guard.as_mut() else {
+            return;
+        };
+        let Some((&tag, payload)) = packet.split_first() else {
+            return;
+        };
+
+        match tag {
+            PACKET_PROBE | PACKET_ACK => {
+                if let Some(tx) = datagrams.punching.remove(&from) {
+                    let _ = tx.send(());
+                }
+                // Only answer peers we are punching to, so probes can't be reflected
+                if tag == PACKET_PROBE && datagrams.conn_ids.contains_key(&from) {
+                    let _ = datagrams.socket.send_to(&[PACKET_ACK], &from).await;
+                }
+            }
+            PACKET_OBSERVE => {
+                let mut reply = vec![PACKET_OBSERVED];
+                reply.extend_from_slice(from.as_bytes());
+                let _ = datagrams.socket.send_to(&reply, &from).await;
+            }
+            PACKET_OBSERVED => {
+                let Ok(observed) = String::from_utf8(payload.to_vec()) else {
+                    return;
+                };
+                if let Some(tx) = datagrams.observing.remove(&from) {
+                    datagrams.observed_addr = Some(observed.clone());
+                    let _ = tx.send(observed);
+                }
+            }
+            PACKET_DATA => {
+                if let Some(&conn_id) = datagrams.conn_ids.get(&from) {
+                    drop(guard);
+                    if let Err(e) =
+                        bandwidth.pass(conn_id, Direction::Received, payload.len()).await
+                    {
+                        eprintln!("{}", e);
+                        if let Some(datagrams) = state.lock().await.as_mut() {
+                            datagrams.remotes.remove(&conn_id);
+                            datagrams.conn_ids.remove(&from);
+                        }
+                        bandwidth.unbind(conn_id).await;
+                        let _ = event_tx.send(TransportEvent::Disconnected(conn_id)).await;
+                        return;
+                    }
+                    let _ = event_tx.send(TransportEvent::Data(conn_id, payload.to_vec())).await;
+                }
+            }
+            _ => {}
+        }
+    }
+
     async fn handle_listen(&self, addr: String) -> Result<(), String> {
         let listener = TcpListener::bind(&addr)
             .await
@@ -122,6 +523,7 @@ impl TransportManager {
         let connections = self.connections.clone();
         let next_conn_id = self.next_conn_id.clone();
         let event_tx = self.event_tx.clone();
+        let bandwidth = self.bandwidth.clone();
 
         tokio::spawn(async move {
             loop {
@@ -145,9 +547,17 @@ impl TransportManager {
 
                         // Spawn reader task with the read half
                         let event_tx_clone = event_tx.clone();
+                        let connections = connections.clone();
+                        let bandwidth = bandwidth.clone();
                         tokio::spawn(async move {
-                            if let Err(e) =
-                                Self::read_connection(conn_id, read_half, event_tx_clone).await
+                            if let Err(e) = Self::read_connection(
+                                conn_id,
+                                read_half,
+                                event_tx_clone,
+                                connections,
+                                bandwidth,
+                            )
+                            .await
                             {
                                 eprintln!("Connection {} read error: {}", conn_id, e);
                             }
@@ -163,8 +573,9 @@ impl TransportManager {
         Ok(())
     }
 
-    async fn handle_dial(&self, addr: String) -> Result<(), String> {
-        let socket = TcpStream::connect(&addr)
+    /// Open a TCP connection to addr; returns its conn_id
+    pub async fn dial(&self, addr: &str) -> Result<u64, String> {
+        let socket = TcpStream::connect(addr)
             .await
             .map_err(|e| format!("Failed to connect to {}: {}", addr, e))?;
 
@@ -177,26 +588,49 @@ impl TransportManager {
         self.connections.lock().await.insert(conn_id, write_half);
 
         // Emit Connected event (is_outgoing = true for dialed connections)
-        if let Err(e) = self.event_tx.send(TransportEvent::Connected(conn_id, addr, true)).await {
+        if let Err(e) = self
+            .event_tx
+            .send(TransportEvent::Connected(conn_id, addr.to_string(), true))
+            .await
+        {
             eprintln!("Failed to send Connected event: {}", e);
         }
 
         // Spawn reader task with the read half
         let event_tx_clone = self.event_tx.clone();
+        let connections = self.connections.clone();
+        let bandwidth = self.bandwidth.clone();
         tokio::spawn(async move {
-            if let Err(e) = Self::read_connection(conn_id, read_half, event_tx_clone).await {
+            if let Err(e) =
+                Self::read_connection(conn_id, read_half, event_tx_clone, connections, bandwidth)
+                    .await
+            {
                 eprintln!("Connection {} read error: {}", conn_id, e);
             }
         });
 
-        Ok(())
+        Ok(conn_id)
     }
 
     async fn handle_send(&self, conn_id: u64, bytes: Vec<u8>) -> Result<(), String> {
+        if let Err(e) = self.bandwidth.pass(conn_id, Direction::Sent, bytes.len()).await {
+            self.handle_close(conn_id).await?;
+            return Err(e);
+        }
+
         let mut connections = self.connections.lock().await;
-        let socket = connections
-            .get_mut(&conn_id)
-            .ok_or_else(|| format!("Connection {} not found", conn_id))?;
+        let Some(socket) = connections.get_mut(&conn_id) else {
+            drop(connections);
+            if let Ok(quic) = self.quic().await {
+                if quic.owns(conn_id).await {
+                    return quic.send(conn_id, &bytes).await;
+                }
+            }
+            if self.ws.owns(conn_id).await {
+                return self.ws.send(conn_id, bytes).await;
+            }
+            return self.send_datagram(conn_id, bytes).await;
+        };
 
         // Frame the message: 4-byte length prefix + data
         let len = bytes.len() as u32;
@@ -212,12 +646,33 @@ impl TransportManager {
         Ok(())
     }
 
+    /// Send bytes over a punched path; each datagram carries one whole message
+    async fn send_datagram(&self, conn_id: u64, bytes: Vec<u8>) -> Result<(), String> {
+        if bytes.len() > MAX_DATAGRAM_PAYLOAD {
+            return Err(format!("Message of {} bytes too large for a datagram", bytes.len()));
+        }
+        let (socket, addr) = {
+            let datagrams = self.datagrams.lock().await;
+            let remote = datagrams
+                .as_ref()
+                .and_then(|d| d.remotes.get(&conn_id).map(|addr| (d.socket.clone(), addr.clone())));
+            remote.ok_or_else(|| format!("Connection {} not found", conn_id))?
+        };
+
+        let mut packet = Vec::with_capacity(bytes.len() + 1);
+        packet.push(PACKET_DATA);
+        packet.extend_from_slice(&bytes);
+        socket.send_to(&packet, &addr).await
+    }
+
     /// Read from a connection and emit Data events
     /// This is spawned as a separate task for each connection
     async fn read_connection(
         conn_id: u64,
         mut read_half: OwnedReadHalf,
         event_tx: mpsc::Sender<TransportEvent>,
+        connections: Arc<Mutex<HashMap<u64, OwnedWriteHalf>>>,
+        bandwidth: Arc<BandwidthMeter>,
     ) -> Result<(), String> {
         loop {
             // Read length prefix (4 bytes)
@@ -226,6 +681,7 @@ impl TransportManager {
                 Ok(_) => {}
                 Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
                     // Connection closed gracefully
+                    bandwidth.unbind(conn_id).await;
                     let _ = event_tx.send(TransportEvent::Disconnected(conn_id)).await;
                     return Ok(());
                 }
@@ -248,6 +704,16 @@ impl TransportManager {
                 .await
                 .map_err(|e| format!("Failed to read data: {}", e))?;
 
+            // Waiting here stops reading, which pushes back on a peer over its soft limit
+            if let Err(e) = bandwidth.pass(conn_id, Direction::Received, len).await {
+                // Dropping the write half closes the socket, unless Close got there first
+                if connections.lock().await.remove(&conn_id).is_some() {
+                    bandwidth.unbind(conn_id).await;
+                    let _ = event_tx.send(TransportEvent::Disconnected(conn_id)).await;
+                }
+                return Err(e);
+            }
+
             // Emit Data event
             if let Err(e) = event_tx.send(TransportEvent::Data(conn_id, data)).await {
                 return Err(format!("Failed to send Data event: {}", e));
@@ -256,10 +722,25 @@ impl TransportManager {
     }
 
     async fn handle_close(&self, conn_id: u64) -> Result<(), String> {
-        let mut connections = self.connections.lock().await;
-        connections
-            .remove(&conn_id)
-            .ok_or_else(|| format!("Connection {} not found", conn_id))?;
+        let closed_tcp = self.connections.lock().await.remove(&conn_id).is_some();
+        let closed_quic = !closed_tcp
+            && match self.quic().await {
+                Ok(quic) => quic.close(conn_id).await,
+                Err(_) => false,
+            };
+        let closed_ws = !closed_tcp && !closed_quic && self.ws.close(conn_id).await;
+        let closed_udp = !closed_tcp && !closed_quic && !closed_ws && {
+            let mut datagrams = self.datagrams.lock().await;
+            let remote = datagrams.as_mut().and_then(|d| {
+                let addr = d.remotes.remove(&conn_id)?;
+                d.conn_ids.remove(&addr)
+            });
+            remote.is_some()
+        };
+        if !closed_tcp && !closed_quic && !closed_ws && !closed_udp {
+            return Err(format!("Connection {} not found", conn_id));
+        }
+        self.bandwidth.unbind(conn_id).await;
 
         // Emit Disconnected event
         if let Err(e) = self.event_tx.send(TransportEvent::Disconnected(conn_id)).await {
@@ -0,0 +1,374 @@
+/*
+    WsTransport - WebSocket backend for TransportManager
+
+    Lets browser/WASM peers, which can't open raw TCP or UDP sockets, reach relays. Carries
+    exactly what a TCP connection carries (Noise handshake frames, then encrypted RpcMessage
+    frames) but one frame per WebSocket binary message instead of behind a length prefix,
+    since WebSocket already keeps message boundaries.
+
+    Workflow:
+    1. listen(addr) -> accepts `ws://`, or `wss://` when a TLS config is set
+       (ws_tls_config builds one from the ServerConfig cert/key paths)
+    2. dial(url) -> connects to `ws://host:port` or `wss://host:port`
+    3. Every connection gets a writer task (outgoing messages, pings) and a reader task
+       (incoming messages, keepalive deadline)
+    4. The reader pings every keepalive interval and drops the connection if nothing,
+       not even a pong, arrived since the previous ping
+
+    Inputs:
+      - Listen/Dial/Send/Close for connections it owns
+
+    Outputs:
+      - TransportEvent::Connected(conn_id, remote_addr, is_outgoing) like TCP
+      - TransportEvent::Data(conn_id, bytes) per binary message
+      - TransportEvent::Disconnected(conn_id) on a Close frame, a half-closed socket
+        (EOF without a closing handshake) or a missed keepalive
+
+    Text messages are ignored; the wire format is binary only.
+*/
+
+use futures::stream::SplitStream;
+use futures::{SinkExt, StreamExt};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use std::collections::HashMap;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, Mutex};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_tungstenite::tungstenite::{Bytes, Message};
+use tokio_tungstenite::WebSocketStream;
+
+use super::bandwidth::{BandwidthMeter, Direction};
+use super::transport_manager::TransportEvent;
+use crate::config::ServerConfig;
+
+/// Largest message accepted (same cap as TCP frames)
+const MAX_WS_MESSAGE: usize = 10_000_000;
+
+/// Default time between keepalive pings
+const DEFAULT_KEEPALIVE: Duration = Duration::from_secs(20);
+
+/// TLS config for WebSocket listeners from the server's cert/key paths
+///
+/// Returns None when TLS is disabled, so listeners serve plain `ws://`.
+pub fn ws_tls_config(server: &ServerConfig) -> Result<Option<Arc<rustls::ServerConfig>>, String> {
+    if !server.enable_tls {
+        return Ok(None);
+    }
+    let (Some(cert_path), Some(key_path)) = (&server.tls_cert_path, &server.tls_key_path) else {
+        return Err("TLS enabled but cert/key paths not provided".to_string());
+    };
+
+    let certs = load_certs(cert_path)?;
+    let key = load_key(key_path)?;
+    let config = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .map_err(|e| format!("TLS setup failed: {}", e))?
+    .with_no_client_auth()
+    .with_single_cert(certs, key)
+    .map_err(|e| format!("Invalid TLS certificate/key: {}", e))?;
+    Ok(Some(Arc::new(config)))
+}
+
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, String> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read certificates from {}: {}", path.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("No certificates in {}", path.display()));
+    }
+    Ok(certs)
+}
+
+fn load_key(path: &Path) -> Result<PrivateKeyDer<'static>, String> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .map_err(|e| format!("Failed to read private key from {}: {}", path.display(), e))?
+        .ok_or_else(|| format!("No private key in {}", path.display()))
+}
+
+/// WebSocket listener/dialer sharing conn_ids and events with the other transports
+#[derive(Clone)]
+pub struct WsTransport {
+    // Writer task of each connection; removing the entry ends it
+    connections: Arc<Mutex<HashMap<u64, mpsc::Sender<Message>>>>,
+    next_conn_id: Arc<AtomicU64>,
+    event_tx: mpsc::Sender<TransportEvent>,
+    keepalive: Duration,
+    tls_acceptor: Option<TlsAcceptor>,
+    tls_connector: Option<TlsConnector>,
+    bandwidth: Arc<BandwidthMeter>,
+}
+
+impl WsTransport {
+    pub fn new(
+        next_conn_id: Arc<AtomicU64>,
+        event_tx: mpsc::Sender<TransportEvent>,
+        bandwidth: Arc<BandwidthMeter>,
+    ) -> Self {
+        WsTransport {
+            connections: Arc::new(Mutex::new(HashMap::new())),
+            next_conn_id,
+            event_tx,
+            keepalive: DEFAULT_KEEPALIVE,
+            tls_acceptor: None,
+            tls_connector: None,
+            bandwidth,
+        }
+    }
+
+    /// Meter received messages with `meter` (TransportManager meters sends)
+    pub fn set_bandwidth_meter(&mut self, meter: Arc<BandwidthMeter>) {
+        self.bandwidth = meter;
+    }
+
+    /// Serve `wss://` instead of `ws://`
+    pub fn set_tls(&mut self, config: Arc<rustls::ServerConfig>) {
+        self.tls_acceptor = Some(TlsAcceptor::from(config));
+    }
+
+    /// Client config (trusted roots) used to dial `wss://` URLs
+    pub fn set_client_tls(&mut self, config: Arc<rustls::ClientConfig>) {
+        self.tls_connector = Some(TlsConnector::from(config));
+    }
+
+    pub fn set_keepalive(&mut self, interval: Duration) {
+        self.keepalive = interval;
+    }
+
+    fn config() -> WebSocketConfig {
+        WebSocketConfig::default()
+            .max_message_size(Some(MAX_WS_MESSAGE))
+            .max_frame_size(Some(MAX_WS_MESSAGE))
+    }
+
+    /// Accept WebSocket connections on addr; returns the bound address
+    pub async fn listen(&self, addr: &str) -> Result<String, String> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| format!("Failed to bind WebSocket listener {}: {}", addr, e))?;
+        let local_addr = listener
+            .local_addr()
+            .map_err(|e| format!("Failed to read WebSocket listener address: {}", e))?;
+
+        let transport = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let (socket, peer_addr) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        eprintln!("Failed to accept WebSocket connection: {}", e);
+                        continue;
+                    }
+                };
+                // Handshakes run off the accept loop so a slow client can't stall it
+                let transport = transport.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = transport.accept(socket, peer_addr.to_string()).await {
+                        eprintln!("WebSocket handshake with {} failed: {}", peer_addr, e);
+                    }
+                });
+            }
+        });
+        Ok(local_addr.to_string())
+    }
+
+    async fn accept(&self, socket: TcpStream, peer_addr: String) -> Result<(), String> {
+        match &self.tls_acceptor {
+            Some(acceptor) => {
+                let tls = acceptor.accept(socket).await.map_err(|e| e.to_string())?;
+                let ws = tokio_tungstenite::accept_async_with_config(tls, Some(Self::config()))
+                    .await
+                    .map_err(|e| e.to_string())?;
+                self.register(ws, peer_addr, false).await;
+            }
+            None => {
+                let ws = tokio_tungstenite::accept_async_with_config(socket, Some(Self::config()))
+                    .await
+                    .map_err(|e| e.to_string())?;
+                self.register(ws, peer_addr, false).await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Connect to a `ws://` or `wss://` URL; returns the conn_id
+    pub async fn dial(&self, url: &str) -> Result<u64, String> {
+        let request = url
+            .into_client_request()
+            .map_err(|e| format!("Invalid WebSocket URL {}: {}", url, e))?;
+        let secure = match request.uri().scheme_str() {
+            Some("ws") => false,
+            Some("wss") => true,
+            _ => return Err(format!("Not a WebSocket URL: {}", url)),
+        };
+        let host = request.uri().host().ok_or_else(|| format!("No host in {}", url))?.to_string();
+        let port = request.uri().port_u16().unwrap_or(if secure { 443 } else { 80 });
+
+        let socket = TcpStream::connect((host.as_str(), port))
+            .await
+            .map_err(|e| format!("Failed to connect to {}: {}", url, e))?;
+        let map_err = |e| format!("WebSocket handshake with {} failed: {}", url, e);
+        if secure {
+            let connector =
+                self.tls_connector.as_ref().ok_or("No TLS client config for wss:// URLs")?;
+            let name = ServerName::try_from(host)
+                .map_err(|e| format!("Invalid TLS server name in {}: {}", url, e))?;
+            let tls = connector
+                .connect(name, socket)
+                .await
+                .map_err(|e| format!("TLS handshake with {} failed: {}", url, e))?;
+            let (ws, _) =
+                tokio_tungstenite::client_async_with_config(request, tls, Some(Self::config()))
+                    .await
+                    .map_err(map_err)?;
+            Ok(self.register(ws, url.to_string(), true).await)
+        } else {
+            let (ws, _) =
+                tokio_tungstenite::client_async_with_config(request, socket, Some(Self::config()))
+                    .await
+                    .map_err(map_err)?;
+            Ok(self.register(ws, url.to_string(), true).await)
+        }
+    }
+
+    /// Start the writer and reader tasks of a new connection and emit Connected
+    async fn register<S>(
+        &self,
+        ws: WebSocketStream<S>,
+        remote_addr: String,
+        is_outgoing: bool,
+    ) -> u64
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let conn_id = self.next_conn_id.fetch_add(1, Ordering::SeqCst);
+        let (mut sink, stream) = ws.split();
+        let (writer_tx, mut writer_rx) = mpsc::channel::<Message>(100);
+        self.connections.lock().await.insert(conn_id, writer_tx.clone());
+
+        if let Err(e) = self
+            .event_tx
+            .send(TransportEvent::Connected(conn_id, remote_addr, is_outgoing))
+            .await
+        {
+            eprintln!("Failed to send Connected event: {}", e);
+        }
+
+        tokio::spawn(async move {
+            while let Some(message) = writer_rx.recv().await {
+                let closing = matches!(message, Message::Close(_));
+                if sink.send(message).await.is_err() || closing {
+                    break;
+                }
+            }
+            let _ = sink.close().await;
+        });
+
+        let transport = self.clone();
+        tokio::spawn(async move {
+            transport.read_connection(conn_id, stream, writer_tx).await;
+        });
+        conn_id
+    }
+
+    /// Emit Data for every binary message and keep the connection alive
+    /// Ends on Close, EOF, a read error or a missed keepalive, emitting Disconnected unless
+    /// the connection was closed locally
+    async fn read_connection<S>(
+        &self,
+        conn_id: u64,
+        mut stream: SplitStream<WebSocketStream<S>>,
+        writer_tx: mpsc::Sender<Message>,
+    ) where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut keepalive = tokio::time::interval(self.keepalive);
+        // The first tick fires immediately
+        keepalive.tick().await;
+        let mut awaiting_pong = false;
+
+        loop {
+            tokio::select! {
+                message = stream.next() => match message {
+                    Some(Ok(Message::Binary(bytes))) => {
+                        awaiting_pong = false;
+                        if let Err(e) =
+                            self.bandwidth.pass(conn_id, Direction::Received, bytes.len()).await
+                        {
+                            eprintln!("{}", e);
+                            let _ = writer_tx.send(Message::Close(None)).await;
+                            break;
+                        }
+                        if self.event_tx.send(TransportEvent::Data(conn_id, bytes.to_vec())).await.is_err() {
+                            break;
+                        }
+                    }
+                    // tungstenite answers pings and Close frames itself
+                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Ok(_)) => awaiting_pong = false,
+                    Some(Err(e)) => {
+                        eprintln!("WebSocket connection {} read error: {}", conn_id, e);
+                        break;
+                    }
+                },
+                _ = keepalive.tick() => {
+                    if awaiting_pong {
+                        eprintln!("WebSocket connection {} missed keepalive", conn_id);
+                        break;
+                    }
+                    awaiting_pong = true;
+                    if writer_tx.send(Message::Ping(Bytes::new())).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        if self.connections.lock().await.remove(&conn_id).is_some() {
+            self.bandwidth.unbind(conn_id).await;
+            let _ = self.event_tx.send(TransportEvent::Disconnected(conn_id)).await;
+        }
+    }
+
+    /// Whether conn_id is one of our connections
+    pub async fn owns(&self, conn_id: u64) -> bool {
+        self.connections.lock().await.contains_key(&conn_id)
+    }
+
+    /// Send bytes as one binary message
+    pub async fn send(&self, conn_id: u64, bytes: Vec<u8>) -> Result<(), String> {
+        let writer = self
+            .connections
+            .lock()
+            .await
+            .get(&conn_id)
+            .cloned()
+            .ok_or_else(|| format!("Connection {} not found", conn_id))?;
+        writer
+            .send(Message::Binary(bytes.into()))
+            .await
+            .map_err(|_| format!("WebSocket connection {} closed", conn_id))
+    }
+
+    /// Start the closing handshake; returns false if conn_id isn't ours
+    pub async fn close(&self, conn_id: u64) -> bool {
+        let Some(writer) = self.connections.lock().await.remove(&conn_id) else {
+            return false;
+        };
+        let _ = writer.send(Message::Close(None)).await;
+        true
+    }
+}
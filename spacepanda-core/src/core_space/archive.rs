@@ -0,0 +1,902 @@
+//! Point-in-time Space archives for cold storage
+//!
+//! An archive is a self-contained, versioned snapshot of a whole Space: its
+//! metadata and roster (with roles as of export time), every channel's
+//! descriptor, visibility and member list, the full stored message history
+//! of each channel, and the manifests of attachments sent in it, optionally
+//! with their encrypted chunks.
+//!
+//! ## Format
+//!
+//! Archives are JSON lines, one [`ArchiveRecord`] per line, in this order:
+//!
+//! 1. `header` - format, version, Space ID, export time and the cut
+//! 2. `space` - Space metadata and roster
+//! 3. `channel` followed by that channel's `message` records, per channel;
+//!    a message carrying an attachment is followed by an `attachment` record,
+//!    and that by a `blob` record per chunk if blobs were included
+//! 4. `manifest` - record count, per-channel digests, overall digest
+//!
+//! The manifest holds SHA-256 digests over the exact bytes of the preceding
+//! lines, so [`verify_archive`] can check integrity offline without any keys.
+//! The cut (a vector clock of channel ID to message count) records how many
+//! messages each channel had when the export started; all channels are
+//! exported at that same cut so cross-channel references line up.
+//!
+//! Attachment chunks live in the DHT and by default are not copied into the
+//! archive: the manifest records their keys and hashes and the file key,
+//! which is enough to fetch and decrypt them while they are still stored.
+//! With [`ArchiveOptions::include_blobs`] each chunk is copied in as it was
+//! stored, still encrypted, and the verifier checks every blob against the
+//! hash its attachment manifest lists.
+
+use super::channel::{Channel, ChannelError};
+use super::space::{Space, SpaceError};
+use super::types::{ChannelId, SpaceId};
+use crate::core_mvp::attachments::AttachmentManifest;
+use crate::core_store::crdt::VectorClock;
+use crate::core_store::model::types::Timestamp;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+
+/// Format identifier written into every archive header
+pub const ARCHIVE_FORMAT: &str = "spacepanda-space-archive";
+
+/// Current archive format version; version 1 archives, which predate blob
+/// records, are still read
+pub const ARCHIVE_VERSION: u32 = 2;
+
+/// What goes into an exported archive besides the records always written
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveOptions {
+    /// Copy the encrypted chunks of every attachment into the archive
+    pub include_blobs: bool,
+}
+
+/// First record of an archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveHeader {
+    pub format: String,
+    pub version: u32,
+    pub space_id: SpaceId,
+    pub exported_at: Timestamp,
+    /// Messages per channel (keyed by hex channel ID) at the time of export
+    pub cut: VectorClock,
+}
+
+/// A stored channel message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedMessage {
+    pub channel_id: ChannelId,
+    /// Hex-encoded message ID
+    pub message_id: String,
+    pub sequence: i64,
+    /// Hex-encoded MLS ciphertext
+    pub encrypted_content: String,
+    /// Hex-encoded sealed sender
+    pub sealed_sender: String,
+    /// Hex-encoded plaintext, when it was stored locally
+    pub plaintext: Option<String>,
+}
+
+/// An attachment sent in a channel message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedAttachment {
+    pub channel_id: ChannelId,
+    /// Hex-encoded ID of the message that carried the manifest
+    pub message_id: String,
+    pub manifest: AttachmentManifest,
+}
+
+/// An encrypted attachment chunk, following its attachment record
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedBlob {
+    pub channel_id: ChannelId,
+    /// ID of the attachment the chunk belongs to
+    pub attachment_id: String,
+    /// Index of the chunk in the attachment
+    pub index: u32,
+    /// Hex-encoded BLAKE3 hash of the chunk, as listed in the manifest
+    pub hash: String,
+    /// Hex-encoded chunk ciphertext
+    pub data: String,
+}
+
+/// Digest of one channel's records
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelDigest {
+    pub message_count: u64,
+    pub attachment_count: u64,
+    #[serde(default)]
+    pub blob_count: u64,
+    /// SHA-256 over the channel record and its message, attachment and blob
+    /// records
+    pub sha256: String,
+}
+
+/// Last record of an archive
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    /// Number of records before the manifest
+    pub record_count: u64,
+    /// Per-channel digests keyed by hex channel ID
+    pub channels: BTreeMap<String, ChannelDigest>,
+    /// SHA-256 over every line before the manifest
+    pub sha256: String,
+}
+
+/// One line of an archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ArchiveRecord {
+    Header(ArchiveHeader),
+    Space(Space),
+    Channel(Channel),
+    Message(ArchivedMessage),
+    Attachment(ArchivedAttachment),
+    Blob(ArchivedBlob),
+    Manifest(ArchiveManifest),
+}
+
+/// Summary of a verified (or freshly written) archive
+#[derive(Debug, Clone)]
+pub struct ArchiveSummary {
+    pub space_id: SpaceId,
+    pub exported_at: Timestamp,
+    pub channel_count: usize,
+    pub message_count: u64,
+    pub attachment_count: u64,
+    pub blob_count: u64,
+    pub manifest: ArchiveManifest,
+}
+
+/// Archive errors
+#[derive(Debug, thiserror::Error)]
+pub enum ArchiveError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Serialization error: {0}")]
+    Serialization(String),
+
+    #[error("Invalid archive: {0}")]
+    InvalidFormat(String),
+
+    #[error("Unsupported archive version: {0}")]
+    UnsupportedVersion(u32),
+
+    #[error("Integrity check failed: {0}")]
+    IntegrityMismatch(String),
+
+    #[error("Channel not found in archive: {0}")]
+    ChannelNotFound(String),
+
+    #[error("Attachment blob unavailable: {0}")]
+    BlobUnavailable(String),
+
+    #[error("Space error: {0}")]
+    Space(#[from] SpaceError),
+
+    #[error("Channel error: {0}")]
+    Channel(#[from] ChannelError),
+}
+
+/// Running counts and digest of one channel's records
+struct ChannelState {
+    messages: u64,
+    attachments: u64,
+    blobs: u64,
+    hasher: Sha256,
+}
+
+/// Chunk hashes of the last attachment written, and how many of its blobs
+/// followed
+struct BlobCursor {
+    attachment_id: String,
+    hashes: Vec<[u8; 32]>,
+    next: usize,
+}
+
+impl BlobCursor {
+    /// An attachment's blobs are all there or all left out
+    fn check_complete(&self) -> Result<(), ArchiveError> {
+        if self.next == 0 || self.next == self.hashes.len() {
+            return Ok(());
+        }
+        Err(ArchiveError::IntegrityMismatch(format!(
+            "Attachment {} has {} of {} blobs",
+            self.attachment_id,
+            self.next,
+            self.hashes.len()
+        )))
+    }
+}
+
+/// Running digests over archive lines, shared by writer and verifier
+struct DigestState {
+    overall: Sha256,
+    channels: BTreeMap<String, ChannelState>,
+    record_count: u64,
+    blobs: Option<BlobCursor>,
+}
+
+impl DigestState {
+    fn new() -> Self {
+        Self { overall: Sha256::new(), channels: BTreeMap::new(), record_count: 0, blobs: None }
+    }
+
+    /// Check a blob against the chunk hashes of the attachment before it
+    fn check_blob(&mut self, blob: &ArchivedBlob) -> Result<(), ArchiveError> {
+        let cursor = self
+            .blobs
+            .as_mut()
+            .filter(|cursor| cursor.attachment_id == blob.attachment_id)
+            .ok_or_else(|| {
+                ArchiveError::InvalidFormat(format!(
+                    "Blob of {} does not follow its attachment",
+                    blob.attachment_id
+                ))
+            })?;
+        if blob.index as usize != cursor.next || cursor.next >= cursor.hashes.len() {
+            return Err(ArchiveError::InvalidFormat(format!(
+                "Unexpected blob {} of {}",
+                blob.index, blob.attachment_id
+            )));
+        }
+
+        let data = hex::decode(&blob.data)
+            .map_err(|_| ArchiveError::InvalidFormat("Blob data is not hex".into()))?;
+        let hash = *blake3::hash(&data).as_bytes();
+        if hex::encode(hash) != blob.hash || hash != cursor.hashes[cursor.next] {
+            return Err(ArchiveError::IntegrityMismatch(format!(
+                "Blob {} of {} does not match its hash",
+                blob.index, blob.attachment_id
+            )));
+        }
+        cursor.next += 1;
+        Ok(())
+    }
+
+    fn update(&mut self, line: &[u8], record: &ArchiveRecord) -> Result<(), ArchiveError> {
+        match record {
+            ArchiveRecord::Blob(blob) => self.check_blob(blob)?,
+            _ => {
+                if let Some(cursor) = self.blobs.take() {
+                    cursor.check_complete()?;
+                }
+            }
+        }
+        if let ArchiveRecord::Attachment(attachment) = record {
+            self.blobs = Some(BlobCursor {
+                attachment_id: attachment.manifest.id.clone(),
+                hashes: attachment.manifest.chunks.iter().map(|chunk| chunk.hash).collect(),
+                next: 0,
+            });
+        }
+
+        self.overall.update(line);
+        self.overall.update(b"\n");
+        self.record_count += 1;
+
+        match record {
+            ArchiveRecord::Channel(channel) => {
+                let key = hex::encode(channel.id.as_bytes());
+                if self.channels.contains_key(&key) {
+                    return Err(ArchiveError::InvalidFormat(format!("Duplicate channel {}", key)));
+                }
+                let mut hasher = Sha256::new();
+                hasher.update(line);
+                hasher.update(b"\n");
+                let state = ChannelState { messages: 0, attachments: 0, blobs: 0, hasher };
+                self.channels.insert(key, state);
+            }
+            ArchiveRecord::Message(message) => {
+                let key = hex::encode(message.channel_id.as_bytes());
+                let state = self.channels.get_mut(&key).ok_or_else(|| {
+                    ArchiveError::InvalidFormat(format!("Message for undeclared channel {}", key))
+                })?;
+                state.messages += 1;
+                state.hasher.update(line);
+                state.hasher.update(b"\n");
+            }
+            ArchiveRecord::Attachment(attachment) => {
+                let key = hex::encode(attachment.channel_id.as_bytes());
+                let state = self.channels.get_mut(&key).ok_or_else(|| {
+                    ArchiveError::InvalidFormat(format!(
+                        "Attachment for undeclared channel {}",
+                        key
+                    ))
+                })?;
+                state.attachments += 1;
+                state.hasher.update(line);
+                state.hasher.update(b"\n");
+            }
+            ArchiveRecord::Blob(blob) => {
+                let key = hex::encode(blob.channel_id.as_bytes());
+                let state = self.channels.get_mut(&key).ok_or_else(|| {
+                    ArchiveError::InvalidFormat(format!("Blob for undeclared channel {}", key))
+                })?;
+                state.blobs += 1;
+                state.hasher.update(line);
+                state.hasher.update(b"\n");
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn manifest(self) -> Result<ArchiveManifest, ArchiveError> {
+        if let Some(cursor) = &self.blobs {
+            cursor.check_complete()?;
+        }
+        Ok(ArchiveManifest {
+            record_count: self.record_count,
+            channels: self
+                .channels
+                .into_iter()
+                .map(|(key, state)| {
+                    let digest = ChannelDigest {
+                        message_count: state.messages,
+                        attachment_count: state.attachments,
+                        blob_count: state.blobs,
+                        sha256: hex::encode(state.hasher.finalize()),
+                    };
+                    (key, digest)
+                })
+                .collect(),
+            sha256: hex::encode(self.overall.finalize()),
+        })
+    }
+}
+
+/// Streaming archive writer
+///
+/// Records are written as they arrive; [`ArchiveWriter::finish`] appends the
+/// manifest.
+pub struct ArchiveWriter<W: Write> {
+    writer: W,
+    digests: DigestState,
+    header: ArchiveHeader,
+}
+
+impl<W: Write> ArchiveWriter<W> {
+    /// Start an archive by writing its header and Space record
+    pub fn new(mut writer: W, space: &Space, cut: VectorClock) -> Result<Self, ArchiveError> {
+        let header = ArchiveHeader {
+            format: ARCHIVE_FORMAT.to_string(),
+            version: ARCHIVE_VERSION,
+            space_id: space.id,
+            exported_at: Timestamp::now(),
+            cut,
+        };
+
+        let mut digests = DigestState::new();
+        for record in [ArchiveRecord::Header(header.clone()), ArchiveRecord::Space(space.clone())] {
+            Self::write_line(&mut writer, &mut digests, &record)?;
+        }
+
+        Ok(Self { writer, digests, header })
+    }
+
+    /// Write a channel descriptor; its messages must follow
+    pub fn write_channel(&mut self, channel: &Channel) -> Result<(), ArchiveError> {
+        Self::write_line(
+            &mut self.writer,
+            &mut self.digests,
+            &ArchiveRecord::Channel(channel.clone()),
+        )
+    }
+
+    /// Write a message of a previously written channel
+    pub fn write_message(&mut self, message: ArchivedMessage) -> Result<(), ArchiveError> {
+        Self::write_line(&mut self.writer, &mut self.digests, &ArchiveRecord::Message(message))
+    }
+
+    /// Write the manifest of an attachment sent in a previously written message
+    pub fn write_attachment(&mut self, attachment: ArchivedAttachment) -> Result<(), ArchiveError> {
+        Self::write_line(
+            &mut self.writer,
+            &mut self.digests,
+            &ArchiveRecord::Attachment(attachment),
+        )
+    }
+
+    /// Write an encrypted chunk of the attachment written last; they must
+    /// all follow it, in order
+    pub fn write_blob(&mut self, blob: ArchivedBlob) -> Result<(), ArchiveError> {
+        Self::write_line(&mut self.writer, &mut self.digests, &ArchiveRecord::Blob(blob))
+    }
+
+    /// Append the manifest and flush
+    pub fn finish(mut self) -> Result<ArchiveSummary, ArchiveError> {
+        let manifest = self.digests.manifest()?;
+
+        let line = serde_json::to_vec(&ArchiveRecord::Manifest(manifest.clone()))
+            .map_err(|e| ArchiveError::Serialization(e.to_string()))?;
+        self.writer.write_all(&line)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+
+        Ok(ArchiveSummary {
+            space_id: self.header.space_id,
+            exported_at: self.header.exported_at,
+            channel_count: manifest.channels.len(),
+            message_count: manifest.channels.values().map(|c| c.message_count).sum(),
+            attachment_count: manifest.channels.values().map(|c| c.attachment_count).sum(),
+            blob_count: manifest.channels.values().map(|c| c.blob_count).sum(),
+            manifest,
+        })
+    }
+
+    fn write_line(
+        writer: &mut W,
+        digests: &mut DigestState,
+        record: &ArchiveRecord,
+    ) -> Result<(), ArchiveError> {
+        let line =
+            serde_json::to_vec(record).map_err(|e| ArchiveError::Serialization(e.to_string()))?;
+        digests.update(&line, record)?;
+        writer.write_all(&line)?;
+        writer.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+/// Verify an archive's structure and digests without any keys
+pub fn verify_archive<R: BufRead>(reader: R) -> Result<ArchiveSummary, ArchiveError> {
+    read_archive(reader, |_| Ok(()))
+}
+
+/// Copy a single channel out of an archive into a new, self-contained archive
+///
+/// The source archive is verified while it is read. If verification fails the
+/// error is returned and the output must be discarded.
+pub fn extract_channel<R: BufRead, W: Write>(
+    reader: R,
+    channel_id: &ChannelId,
+    writer: W,
+) -> Result<ArchiveSummary, ArchiveError> {
+    let key = hex::encode(channel_id.as_bytes());
+    let mut writer = Some(writer);
+    let mut output: Option<ArchiveWriter<W>> = None;
+    let mut header: Option<ArchiveHeader> = None;
+
+    read_archive(reader, |record| {
+        match record {
+            ArchiveRecord::Header(h) => header = Some(h.clone()),
+            ArchiveRecord::Space(space) => {
+                let mut cut = VectorClock::new();
+                if let Some(h) = &header {
+                    cut.set(&key, h.cut.get(&key));
+                }
+                let writer = writer.take().expect("space record appears once");
+                output = Some(ArchiveWriter::new(writer, space, cut)?);
+            }
+            ArchiveRecord::Channel(channel) if channel.id == *channel_id => {
+                if let Some(out) = output.as_mut() {
+                    out.write_channel(channel)?;
+                }
+            }
+            ArchiveRecord::Message(message) if message.channel_id == *channel_id => {
+                if let Some(out) = output.as_mut() {
+                    out.write_message(message.clone())?;
+                }
+            }
+            ArchiveRecord::Attachment(attachment) if attachment.channel_id == *channel_id => {
+                if let Some(out) = output.as_mut() {
+                    out.write_attachment(attachment.clone())?;
+                }
+            }
+            ArchiveRecord::Blob(blob) if blob.channel_id == *channel_id => {
+                if let Some(out) = output.as_mut() {
+                    out.write_blob(blob.clone())?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    })?;
+
+    let output =
+        output.ok_or_else(|| ArchiveError::InvalidFormat("Missing space record".into()))?;
+    if !output.digests.channels.contains_key(&key) {
+        return Err(ArchiveError::ChannelNotFound(key));
+    }
+    output.finish()
+}
+
+/// Read and verify an archive, passing every non-manifest record to `visit`
+fn read_archive<R: BufRead>(
+    reader: R,
+    mut visit: impl FnMut(&ArchiveRecord) -> Result<(), ArchiveError>,
+) -> Result<ArchiveSummary, ArchiveError> {
+    let mut digests = DigestState::new();
+    let mut header: Option<ArchiveHeader> = None;
+    let mut manifest: Option<ArchiveManifest> = None;
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if manifest.is_some() {
+            return Err(ArchiveError::InvalidFormat("Data after manifest".into()));
+        }
+
+        let record: ArchiveRecord = serde_json::from_str(&line)
+            .map_err(|e| ArchiveError::InvalidFormat(format!("Line {}: {}", index + 1, e)))?;
+
+        match (&record, index) {
+            (ArchiveRecord::Header(h), 0) => {
+                if h.format != ARCHIVE_FORMAT {
+                    return Err(ArchiveError::InvalidFormat(format!(
+                        "Unknown format {}",
+                        h.format
+                    )));
+                }
+                if h.version == 0 || h.version > ARCHIVE_VERSION {
+                    return Err(ArchiveError::UnsupportedVersion(h.version));
+                }
+                header = Some(h.clone());
+            }
+            (_, 0) => return Err(ArchiveError::InvalidFormat("Missing header".into())),
+            (ArchiveRecord::Space(space), 1)
+                if header.as_ref().map(|h| h.space_id) != Some(space.id) =>
+            {
+                return Err(ArchiveError::InvalidFormat("Space does not match header".into()));
+            }
+            (ArchiveRecord::Space(_), 1) => {}
+            (_, 1) => return Err(ArchiveError::InvalidFormat("Missing space record".into())),
+            (ArchiveRecord::Header(_), _) | (ArchiveRecord::Space(_), _) => {
+                return Err(ArchiveError::InvalidFormat(format!(
+                    "Unexpected record at line {}",
+                    index + 1
+                )));
+            }
+            (ArchiveRecord::Manifest(m), _) => {
+                manifest = Some(m.clone());
+                continue;
+            }
+            _ => {}
+        }
+
+        digests.update(line.as_bytes(), &record)?;
+        visit(&record)?;
+    }
+
+    let header = header.ok_or_else(|| ArchiveError::InvalidFormat("Empty archive".into()))?;
+    let manifest =
+        manifest.ok_or_else(|| ArchiveError::InvalidFormat("Missing manifest".into()))?;
+
+    let computed = digests.manifest()?;
+    if computed.record_count != manifest.record_count {
+        return Err(ArchiveError::IntegrityMismatch(format!(
+            "Expected {} records, found {}",
+            manifest.record_count, computed.record_count
+        )));
+    }
+    if computed.channels != manifest.channels {
+        return Err(ArchiveError::IntegrityMismatch("Channel digests do not match".into()));
+    }
+    if computed.sha256 != manifest.sha256 {
+        return Err(ArchiveError::IntegrityMismatch("Archive digest does not match".into()));
+    }
+
+    // Every channel must be exported exactly at the recorded cut
+    for (key, digest) in &manifest.channels {
+        if header.cut.get(key) != digest.message_count {
+            return Err(ArchiveError::IntegrityMismatch(format!(
+                "Channel {} has {} messages, cut records {}",
+                key,
+                digest.message_count,
+                header.cut.get(key)
+            )));
+        }
+    }
+
+    Ok(ArchiveSummary {
+        space_id: header.space_id,
+        exported_at: header.exported_at,
+        channel_count: manifest.channels.len(),
+        message_count: manifest.channels.values().map(|c| c.message_count).sum(),
+        attachment_count: manifest.channels.values().map(|c| c.attachment_count).sum(),
+        blob_count: manifest.channels.values().map(|c| c.blob_count).sum(),
+        manifest,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::core_dht::DhtStorage;
+    use crate::core_mls::service::MlsService;
+    use crate::core_mvp::attachments::AttachmentStore;
+    use crate::core_space::async_manager::AsyncSpaceManager;
+    use crate::core_space::channel::ChannelVisibility;
+    use crate::core_space::space::{SpaceRole, SpaceVisibility};
+    use crate::core_space::storage::SpaceSqlStore;
+    use crate::core_store::model::types::UserId;
+    use crate::shutdown::ShutdownCoordinator;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    /// Contents of the fixture's attachment, in three chunks
+    const PHOTO: &[u8] = b"not really a png";
+
+    /// Space with two channels: "general" (3 messages) and "random" (a message
+    /// and an attachment), exported with `options`
+    async fn fixture_archive(
+        options: ArchiveOptions,
+    ) -> (TempDir, Vec<u8>, Space, Channel, Channel) {
+        let dir = tempfile::tempdir().unwrap();
+        let shutdown = Arc::new(ShutdownCoordinator::new(std::time::Duration::from_secs(5)));
+        let mls_service = Arc::new(
+            MlsService::with_storage(&Config::default(), shutdown, dir.path().join("mls")).unwrap(),
+        );
+        let attachments = Arc::new(AttachmentStore::new(DhtStorage::new()));
+        let manager = AsyncSpaceManager::new(SpaceSqlStore::memory().unwrap(), mls_service)
+            .with_attachment_store(attachments.clone());
+
+        let alice = UserId::new("alice".to_string());
+        let space = manager
+            .create_space("Archive Space".to_string(), alice.clone(), SpaceVisibility::Private)
+            .await
+            .unwrap();
+        let general = manager
+            .create_channel(
+                space.id,
+                "general".to_string(),
+                alice.clone(),
+                ChannelVisibility::Public,
+            )
+            .await
+            .unwrap();
+        let random = manager
+            .create_channel(
+                space.id,
+                "random".to_string(),
+                alice.clone(),
+                ChannelVisibility::Private,
+            )
+            .await
+            .unwrap();
+
+        for text in ["one", "two", "three"] {
+            manager
+                .send_channel_message(&general.id, &alice, text.as_bytes())
+                .await
+                .unwrap();
+        }
+        manager.send_channel_message(&random.id, &alice, b"hello").await.unwrap();
+        let mut attachment = AttachmentManifest::new(
+            "a1".to_string(),
+            "photo.png".to_string(),
+            "image/png".to_string(),
+            [7; 32],
+        );
+        attachment.chunk_size = 6;
+        attachments.upload(&mut attachment, PHOTO, u64::MAX).await.unwrap();
+        manager
+            .send_channel_message(&random.id, &alice, &attachment.encode().unwrap())
+            .await
+            .unwrap();
+
+        let mut archive = Vec::new();
+        let summary = manager.export_archive(&space.id, &mut archive, &options).await.unwrap();
+        assert_eq!(summary.channel_count, 2);
+        assert_eq!(summary.message_count, 5);
+
+        let space = manager.get_space(&space.id).await.unwrap();
+        (dir, archive, space, general, random)
+    }
+
+    fn records(archive: &[u8]) -> Vec<ArchiveRecord> {
+        archive
+            .split(|b| *b == b'\n')
+            .filter(|l| !l.is_empty())
+            .map(|l| serde_json::from_slice(l).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_export_and_verify_archive() {
+        let (_dir, archive, space, general, random) =
+            fixture_archive(ArchiveOptions::default()).await;
+
+        let summary = verify_archive(archive.as_slice()).unwrap();
+        assert_eq!(summary.space_id, space.id);
+        assert_eq!(summary.channel_count, 2);
+        assert_eq!(summary.message_count, 5);
+        assert_eq!(summary.attachment_count, 1);
+
+        let general_key = hex::encode(general.id.as_bytes());
+        let random_key = hex::encode(random.id.as_bytes());
+        assert_eq!(summary.manifest.channels[&general_key].message_count, 3);
+        assert_eq!(summary.manifest.channels[&general_key].attachment_count, 0);
+        assert_eq!(summary.manifest.channels[&random_key].message_count, 2);
+        assert_eq!(summary.manifest.channels[&random_key].attachment_count, 1);
+
+        let records = records(&archive);
+        match &records[0] {
+            ArchiveRecord::Header(header) => {
+                assert_eq!(header.version, ARCHIVE_VERSION);
+                assert_eq!(header.cut.get(&general_key), 3);
+                assert_eq!(header.cut.get(&random_key), 2);
+            }
+            other => panic!("Expected header, got {:?}", other),
+        }
+        match &records[1] {
+            ArchiveRecord::Space(archived) => {
+                assert_eq!(archived.name, "Archive Space");
+                let owner = &archived.members[&UserId::new("alice".to_string())];
+                assert_eq!(owner.role, SpaceRole::Owner);
+            }
+            other => panic!("Expected space, got {:?}", other),
+        }
+
+        // Plaintext of locally sent messages is preserved
+        let texts: Vec<Vec<u8>> = records
+            .iter()
+            .filter_map(|r| match r {
+                ArchiveRecord::Message(m) if m.channel_id == general.id => {
+                    m.plaintext.as_ref().map(|p| hex::decode(p).unwrap())
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(texts.len(), 3);
+        assert!(texts.contains(&b"two".to_vec()));
+
+        // The attachment manifest follows the message that carried it
+        let index = records
+            .iter()
+            .position(|r| matches!(r, ArchiveRecord::Attachment(_)))
+            .expect("attachment record");
+        match (&records[index - 1], &records[index]) {
+            (ArchiveRecord::Message(message), ArchiveRecord::Attachment(attachment)) => {
+                assert_eq!(attachment.channel_id, random.id);
+                assert_eq!(attachment.message_id, message.message_id);
+                assert_eq!(attachment.manifest.filename, "photo.png");
+                assert_eq!(attachment.manifest.key, [7; 32]);
+            }
+            other => panic!("Expected message then attachment, got {:?}", other),
+        }
+        assert!(!records.iter().any(|r| matches!(r, ArchiveRecord::Blob(_))));
+    }
+
+    #[tokio::test]
+    async fn test_archive_with_blobs() {
+        let options = ArchiveOptions { include_blobs: true };
+        let (_dir, archive, _, _, random) = fixture_archive(options).await;
+
+        let summary = verify_archive(archive.as_slice()).unwrap();
+        assert_eq!(summary.attachment_count, 1);
+        assert_eq!(summary.blob_count, 3);
+
+        // The chunks follow their attachment and decrypt with its key
+        let records = records(&archive);
+        let index = records
+            .iter()
+            .position(|r| matches!(r, ArchiveRecord::Attachment(_)))
+            .expect("attachment record");
+        let ArchiveRecord::Attachment(attachment) = &records[index] else {
+            unreachable!()
+        };
+        let mut photo = Vec::new();
+        for (i, record) in records[index + 1..index + 4].iter().enumerate() {
+            let ArchiveRecord::Blob(blob) = record else {
+                panic!("Expected blob, got {:?}", record)
+            };
+            assert_eq!(blob.index as usize, i);
+            let ciphertext = hex::decode(&blob.data).unwrap();
+            photo.extend(attachment.manifest.decrypt_chunk(i, &ciphertext).unwrap());
+        }
+        assert_eq!(photo, PHOTO);
+
+        let mut extracted = Vec::new();
+        let summary = extract_channel(archive.as_slice(), &random.id, &mut extracted).unwrap();
+        assert_eq!(summary.blob_count, 3);
+        assert_eq!(verify_archive(extracted.as_slice()).unwrap().blob_count, 3);
+
+        // An altered chunk no longer matches the manifest
+        let text = String::from_utf8(archive).unwrap();
+        let mut lines: Vec<String> = text.lines().map(String::from).collect();
+        let mut record: ArchiveRecord = serde_json::from_str(&lines[index + 1]).unwrap();
+        if let ArchiveRecord::Blob(blob) = &mut record {
+            let mut data = hex::decode(&blob.data).unwrap();
+            data[0] ^= 1;
+            blob.data = hex::encode(data);
+        }
+        lines[index + 1] = serde_json::to_string(&record).unwrap();
+        assert!(matches!(
+            verify_archive(lines.join("\n").as_bytes()),
+            Err(ArchiveError::IntegrityMismatch(_))
+        ));
+
+        // So does an attachment missing its last chunk
+        let mut truncated: Vec<&str> = text.lines().collect();
+        truncated.remove(index + 3);
+        assert!(matches!(
+            verify_archive(truncated.join("\n").as_bytes()),
+            Err(ArchiveError::IntegrityMismatch(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_verify_detects_tampering() {
+        let (_dir, archive, ..) = fixture_archive(ArchiveOptions::default()).await;
+        let text = String::from_utf8(archive).unwrap();
+
+        // Alter a message body
+        let mut lines: Vec<String> = text.lines().map(String::from).collect();
+        let index = lines.iter().position(|l| l.contains("\"type\":\"message\"")).unwrap();
+        let mut record: ArchiveRecord = serde_json::from_str(&lines[index]).unwrap();
+        if let ArchiveRecord::Message(m) = &mut record {
+            m.sequence += 1;
+        }
+        lines[index] = serde_json::to_string(&record).unwrap();
+        let tampered = lines.join("\n");
+        assert!(matches!(
+            verify_archive(tampered.as_bytes()),
+            Err(ArchiveError::IntegrityMismatch(_))
+        ));
+
+        // Drop a message line
+        let mut truncated: Vec<&str> = text.lines().collect();
+        truncated.remove(index);
+        assert!(matches!(
+            verify_archive(truncated.join("\n").as_bytes()),
+            Err(ArchiveError::IntegrityMismatch(_))
+        ));
+
+        // Missing manifest
+        let without_manifest: Vec<&str> = text.lines().take(text.lines().count() - 1).collect();
+        assert!(matches!(
+            verify_archive(without_manifest.join("\n").as_bytes()),
+            Err(ArchiveError::InvalidFormat(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_verify_rejects_unknown_version() {
+        let (_dir, archive, ..) = fixture_archive(ArchiveOptions::default()).await;
+        let mut records = records(&archive);
+        if let ArchiveRecord::Header(header) = &mut records[0] {
+            header.version = ARCHIVE_VERSION + 1;
+        }
+        let rewritten: Vec<String> =
+            records.iter().map(|r| serde_json::to_string(r).unwrap()).collect();
+
+        assert!(matches!(
+            verify_archive(rewritten.join("\n").as_bytes()),
+            Err(ArchiveError::UnsupportedVersion(v)) if v == ARCHIVE_VERSION + 1
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_extract_single_channel() {
+        let (_dir, archive, space, general, random) =
+            fixture_archive(ArchiveOptions::default()).await;
+
+        let mut extracted = Vec::new();
+        let summary = extract_channel(archive.as_slice(), &random.id, &mut extracted).unwrap();
+        assert_eq!(summary.space_id, space.id);
+        assert_eq!(summary.channel_count, 1);
+        assert_eq!(summary.message_count, 2);
+        assert_eq!(summary.attachment_count, 1);
+
+        // The extracted archive stands on its own
+        let verified = verify_archive(extracted.as_slice()).unwrap();
+        assert_eq!(verified.channel_count, 1);
+        assert_eq!(verified.attachment_count, 1);
+        assert!(verified.manifest.channels.contains_key(&hex::encode(random.id.as_bytes())));
+        assert!(!verified.manifest.channels.contains_key(&hex::encode(general.id.as_bytes())));
+
+        let missing = ChannelId::generate();
+        assert!(matches!(
+            extract_channel(archive.as_slice(), &missing, Vec::new()),
+            Err(ArchiveError::ChannelNotFound(_))
+        ));
+    }
+}
@@ -3,6 +3,10 @@
 //! This module provides async versions of the manager traits that integrate
 //! with the MLS service to create actual MLS groups for channels.
 
+use super::archive::{
+    ArchiveError, ArchiveOptions, ArchiveSummary, ArchiveWriter, ArchivedAttachment, ArchivedBlob,
+    ArchivedMessage,
+};
 use super::channel::{Channel, ChannelError, ChannelVisibility};
 use super::invite::{InviteError, SpaceInvite};
 use super::manager::{ChannelManager, MembershipError, MembershipManager, SpaceManager};
@@ -14,10 +18,21 @@ use crate::core_mls::sealed_sender;
 use crate::core_mls::service::MlsService;
 use crate::core_mls::timing_obfuscation;
 use crate::core_mls::types::GroupId;
+use crate::core_mvp::attachments::{AttachmentManifest, AttachmentStore};
+use crate::core_mvp::events::{ChannelEvent, ChannelEventKind, EventQueue};
 use crate::core_mvp::network::NetworkLayer;
+use crate::core_mvp::privacy::{MemberListVisibility, PrivacyReport, PrivacySettings};
+use crate::core_mvp::types::ChatMessage;
+use crate::core_store::crdt::VectorClock;
 use crate::core_store::model::types::{Timestamp, UserId};
+use crate::core_store::model::{Permission, PermissionSet};
+use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::sync::{broadcast, Mutex, RwLock};
+
+/// Events a subscriber may fall behind by before it starts missing them
+const EVENT_BROADCAST_CAPACITY: usize = 256;
 
 /// Async manager with MLS integration
 ///
@@ -32,6 +47,19 @@ pub struct AsyncSpaceManager {
 
     /// Optional network layer for P2P message distribution
     network_layer: Option<Arc<NetworkLayer>>,
+
+    /// Events for subscribers (see [`subscribe_events`](Self::subscribe_events))
+    events: broadcast::Sender<ChannelEvent>,
+
+    /// Ordering of each channel's events
+    event_queues: Mutex<HashMap<ChannelId, EventQueue>>,
+
+    /// Held shared while a message is stored, and exclusively by
+    /// [`export_archive`](Self::export_archive) so history stands still
+    history_lock: RwLock<()>,
+
+    /// Where attachment chunks are fetched from for archives that include them
+    attachment_store: Option<Arc<AttachmentStore>>,
 }
 
 impl AsyncSpaceManager {
@@ -41,6 +69,10 @@ impl AsyncSpaceManager {
             manager: Arc::new(RwLock::new(SpaceManagerImpl::new(store))),
             mls_service,
             network_layer: None,
+            events: broadcast::channel(EVENT_BROADCAST_CAPACITY).0,
+            event_queues: Mutex::new(HashMap::new()),
+            history_lock: RwLock::new(()),
+            attachment_store: None,
         }
     }
 
@@ -54,14 +86,35 @@ impl AsyncSpaceManager {
             manager: Arc::new(RwLock::new(SpaceManagerImpl::new(store))),
             mls_service,
             network_layer: Some(network_layer),
+            events: broadcast::channel(EVENT_BROADCAST_CAPACITY).0,
+            event_queues: Mutex::new(HashMap::new()),
+            history_lock: RwLock::new(()),
+            attachment_store: None,
         }
     }
 
+    /// Fetch attachment chunks from `store` when archiving with blobs
+    pub fn with_attachment_store(mut self, store: Arc<AttachmentStore>) -> Self {
+        self.attachment_store = Some(store);
+        self
+    }
+
     /// Get the network layer (if available)
     pub fn network_layer(&self) -> Option<Arc<NetworkLayer>> {
         self.network_layer.clone()
     }
 
+    /// Subscribe to messages received from peers and commits processed
+    ///
+    /// Events use the same types as
+    /// [`ChannelManager::subscribe_events`](crate::core_mvp::ChannelManager::subscribe_events),
+    /// with the channel ID hex-encoded as on the network. A subscriber that
+    /// falls too far behind gets `RecvError::Lagged`; processing never waits
+    /// for it.
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ChannelEvent> {
+        self.events.subscribe()
+    }
+
     /// Register a user as a channel member for P2P routing
     pub async fn register_channel_member(
         &self,
@@ -218,6 +271,65 @@ impl AsyncSpaceManager {
         manager.update_member_role(space_id, admin_id, target_user_id, new_role)
     }
 
+    /// Define or redefine a custom role (requires ManageRoles)
+    pub async fn define_role(
+        &self,
+        space_id: &SpaceId,
+        admin_id: &UserId,
+        name: String,
+        permissions: PermissionSet,
+    ) -> Result<(), MembershipError> {
+        let mut manager = self.manager.write().await;
+        manager.define_role(space_id, admin_id, name, permissions)
+    }
+
+    /// Delete a custom role (requires ManageRoles)
+    pub async fn delete_role(
+        &self,
+        space_id: &SpaceId,
+        admin_id: &UserId,
+        name: &str,
+    ) -> Result<(), MembershipError> {
+        let mut manager = self.manager.write().await;
+        manager.delete_role(space_id, admin_id, name)
+    }
+
+    /// Assign a custom role to a member, or clear it (requires ManageRoles)
+    pub async fn assign_custom_role(
+        &self,
+        space_id: &SpaceId,
+        admin_id: &UserId,
+        target_user_id: &UserId,
+        role: Option<String>,
+    ) -> Result<(), MembershipError> {
+        let mut manager = self.manager.write().await;
+        manager.assign_custom_role(space_id, admin_id, target_user_id, role)
+    }
+
+    /// Capabilities a user holds in the Space owning a channel
+    pub async fn channel_permissions(
+        &self,
+        channel_id: &ChannelId,
+        user_id: &UserId,
+    ) -> Result<PermissionSet, ChannelError> {
+        let manager = self.manager.read().await;
+        manager.channel_permissions(channel_id, user_id)
+    }
+
+    /// Fail with `PermissionDenied` unless the user holds a capability in the
+    /// Space owning a channel
+    pub async fn require_channel_permission(
+        &self,
+        channel_id: &ChannelId,
+        user_id: &UserId,
+        permission: Permission,
+    ) -> Result<(), ChannelError> {
+        if !self.channel_permissions(channel_id, user_id).await?.contains(permission) {
+            return Err(ChannelError::PermissionDenied);
+        }
+        Ok(())
+    }
+
     /// Revoke an invite
     pub async fn revoke_invite(
         &self,
@@ -260,6 +372,22 @@ impl AsyncSpaceManager {
         manager.create_channel(space_id, name, creator_id, visibility, Some(group_id))
     }
 
+    /// Record a Channel in a Space without creating an MLS group for it
+    ///
+    /// For channels encrypted by a [`core_mvp::ChannelManager`](crate::core_mvp::ChannelManager)
+    /// under the returned channel's ID; the record only lists the channel in
+    /// the Space and keeps its settings.
+    pub async fn add_channel_record(
+        &self,
+        space_id: SpaceId,
+        name: String,
+        creator_id: UserId,
+        visibility: ChannelVisibility,
+    ) -> Result<Channel, ChannelError> {
+        let mut manager = self.manager.write().await;
+        manager.create_channel(space_id, name, creator_id, visibility, None)
+    }
+
     /// Add a user to a Channel (creates MLS group membership)
     pub async fn add_channel_member(
         &self,
@@ -267,6 +395,10 @@ impl AsyncSpaceManager {
         user_id: &UserId,
         admin_id: &UserId,
     ) -> Result<(), ChannelError> {
+        // Check authorization before touching MLS state
+        self.require_channel_permission(channel_id, admin_id, Permission::Invite)
+            .await?;
+
         // Get channel to find MLS group ID
         let manager = self.manager.read().await;
         let channel = manager.get_channel(channel_id)?;
@@ -303,6 +435,9 @@ impl AsyncSpaceManager {
         user_id: &UserId,
         admin_id: &UserId,
     ) -> Result<(), ChannelError> {
+        // Check authorization before touching MLS state
+        self.require_channel_permission(channel_id, admin_id, Permission::Kick).await?;
+
         // Get channel to find MLS group ID
         let manager = self.manager.read().await;
         let channel = manager.get_channel(channel_id)?;
@@ -488,9 +623,14 @@ impl AsyncSpaceManager {
         drop(manager);
 
         // Decrypt the message
-        let plaintext = self
-            .receive_channel_message(channel_id, encrypted_message)
-            .await?;
+        let plaintext = match self.receive_channel_message(channel_id, encrypted_message).await {
+            Ok(plaintext) => plaintext,
+            Err(e) => {
+                let kind = ChannelEventKind::DecryptionFailed { error: e.to_string() };
+                self.emit_event(channel_id, group_id, Some(sender_id.clone()), kind).await;
+                return Err(e);
+            }
+        };
 
         // PRIVACY: Seal sender identity to prevent metadata leakage
         // Get group secret for sealing
@@ -545,6 +685,12 @@ impl AsyncSpaceManager {
             "Received and saved incoming message from peer (with sealed sender)"
         );
 
+        let mut message =
+            ChatMessage::new(event_channel_id(channel_id), sender_id.clone(), plaintext.clone());
+        message.message_id = crate::core_store::model::types::MessageId(message_id);
+        let kind = ChannelEventKind::MessageReceived(message);
+        self.emit_event(channel_id, group_id, Some(sender_id.clone()), kind).await;
+
         Ok(plaintext)
     }
 
@@ -564,9 +710,14 @@ impl AsyncSpaceManager {
         let group_id = &channel.mls_group_id;
         drop(manager);
 
+        // Take the roster before the commit as the starting point, if this
+        // is the channel's first event since startup
+        self.emit_commit_events(channel_id, group_id).await;
+
         // Process the commit message (updates epoch and group state)
         match self.mls_service.process_message(group_id, commit_data).await {
             Ok(_) => {
+                self.emit_commit_events(channel_id, group_id).await;
                 eprintln!("[P2P] ✓ Successfully processed commit for channel {}", hex::encode(channel_id.as_bytes()));
                 tracing::info!(
                     channel_id = %channel_id,
@@ -581,12 +732,80 @@ impl AsyncSpaceManager {
         }
     }
 
+    /// Emit an event, after the membership changes that came before it
+    async fn emit_event(
+        &self,
+        channel_id: &ChannelId,
+        group_id: &GroupId,
+        actor: Option<UserId>,
+        kind: ChannelEventKind,
+    ) {
+        let mut queues = self.event_queues.lock().await;
+        let queue = queues.entry(*channel_id).or_default();
+        self.emit_roster(channel_id, group_id, queue).await;
+        let _ = self.events.send(queue.event(&event_channel_id(channel_id), actor, kind));
+    }
+
+    /// Emit the epoch change and membership changes of commits since the last event
+    async fn emit_commit_events(&self, channel_id: &ChannelId, group_id: &GroupId) {
+        let mut queues = self.event_queues.lock().await;
+        let queue = queues.entry(*channel_id).or_default();
+        self.emit_roster(channel_id, group_id, queue).await;
+    }
+
+    /// Emit joins and leaves from the current MLS roster (caller holds the queue)
+    async fn emit_roster(
+        &self,
+        channel_id: &ChannelId,
+        group_id: &GroupId,
+        queue: &mut EventQueue,
+    ) {
+        let metadata = match self.mls_service.get_metadata(group_id).await {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                tracing::debug!(
+                    channel_id = %channel_id,
+                    error = ?e,
+                    "No roster to emit events from"
+                );
+                return;
+            }
+        };
+
+        let roster = metadata
+            .members
+            .iter()
+            .map(|member| UserId(String::from_utf8_lossy(&member.identity).into_owned()))
+            .collect();
+        let events = queue.observe_roster(&event_channel_id(channel_id), metadata.epoch, roster);
+        for event in events {
+            let _ = self.events.send(event);
+        }
+    }
+
     /// Get a Channel by ID
     pub async fn get_channel(&self, channel_id: &ChannelId) -> Result<Channel, ChannelError> {
         let manager = self.manager.read().await;
         manager.get_channel(channel_id)
     }
 
+    /// Report what metadata a Channel exposes
+    pub async fn channel_privacy_report(
+        &self,
+        channel_id: &ChannelId,
+    ) -> Result<PrivacyReport, ChannelError> {
+        let manager = self.manager.read().await;
+        let channel = manager.get_channel(channel_id)?;
+        let space = manager.get_space(&channel.space_id).map_err(|_| ChannelError::NotFound)?;
+        drop(manager);
+
+        let settings = SpaceChannelPrivacy {
+            space_visibility: space.visibility,
+            channel_visibility: channel.visibility,
+        };
+        Ok(PrivacyReport::evaluate(hex::encode(channel_id.as_bytes()), &settings))
+    }
+
     /// Update Channel metadata
     pub async fn update_channel(
         &self,
@@ -627,6 +846,175 @@ impl AsyncSpaceManager {
         let manager = self.manager.read().await;
         manager.list_user_channels(space_id, user_id)
     }
+
+    /// Export a point-in-time archive of a whole Space
+    ///
+    /// Messages stored through this manager wait until the export is done,
+    /// so no channel gains a message between recording the cut and streaming
+    /// the history. The manager's read lock is held too, so the Space and its
+    /// channels can't change under the export. Message history is paged from
+    /// storage rather than loaded at once, and with `include_blobs` each
+    /// attachment's chunks are fetched from the attachment store one at a
+    /// time; a chunk that can't be fetched fails the export.
+    pub async fn export_archive<W: std::io::Write>(
+        &self,
+        space_id: &SpaceId,
+        writer: W,
+        options: &ArchiveOptions,
+    ) -> Result<ArchiveSummary, ArchiveError> {
+        const PAGE_SIZE: i64 = 500;
+
+        let _history = self.history_lock.write().await;
+        let manager = self.manager.read().await;
+        let space = manager.get_space(space_id)?;
+        let channels = manager.list_space_channels(space_id)?;
+
+        // Record the cut: how many messages each channel has right now
+        let mut cut = VectorClock::new();
+        for channel in &channels {
+            let mut count = 0u64;
+            loop {
+                let page = self.load_archive_page(channel, PAGE_SIZE, count as i64).await?;
+                count += page.len() as u64;
+                if (page.len() as i64) < PAGE_SIZE {
+                    break;
+                }
+            }
+            cut.set(&hex::encode(channel.id.as_bytes()), count);
+        }
+
+        let mut archive = ArchiveWriter::new(writer, &space, cut.clone())?;
+        for channel in &channels {
+            archive.write_channel(channel)?;
+
+            let total = cut.get(&hex::encode(channel.id.as_bytes()));
+            let mut offset = 0u64;
+            while offset < total {
+                let limit = PAGE_SIZE.min((total - offset) as i64);
+                let page = self.load_archive_page(channel, limit, offset as i64).await?;
+                if page.is_empty() {
+                    break;
+                }
+                offset += page.len() as u64;
+
+                for (message_id, encrypted, sealed_sender, sequence, _, plaintext) in page {
+                    let message_id = hex::encode(message_id);
+                    let attachment = plaintext.as_deref().and_then(AttachmentManifest::decode);
+                    archive.write_message(ArchivedMessage {
+                        channel_id: channel.id,
+                        message_id: message_id.clone(),
+                        sequence,
+                        encrypted_content: hex::encode(encrypted),
+                        sealed_sender: hex::encode(sealed_sender),
+                        plaintext: plaintext.map(hex::encode),
+                    })?;
+                    if let Some(manifest) = attachment {
+                        archive.write_attachment(ArchivedAttachment {
+                            channel_id: channel.id,
+                            message_id,
+                            manifest: manifest.clone(),
+                        })?;
+                        if options.include_blobs {
+                            self.write_blobs(&mut archive, channel, &manifest).await?;
+                        }
+                    }
+                }
+            }
+        }
+        drop(manager);
+
+        archive.finish()
+    }
+
+    /// Copy an attachment's encrypted chunks into an archive
+    async fn write_blobs<W: std::io::Write>(
+        &self,
+        archive: &mut ArchiveWriter<W>,
+        channel: &Channel,
+        manifest: &AttachmentManifest,
+    ) -> Result<(), ArchiveError> {
+        let store = self.attachment_store.as_ref().ok_or_else(|| {
+            ArchiveError::BlobUnavailable("No attachment store configured".to_string())
+        })?;
+        for (index, chunk) in manifest.chunks.iter().enumerate() {
+            let ciphertext = store.fetch_chunk(index, chunk).await.map_err(|e| {
+                ArchiveError::BlobUnavailable(format!("{} chunk {}: {}", manifest.id, index, e))
+            })?;
+            archive.write_blob(ArchivedBlob {
+                channel_id: channel.id,
+                attachment_id: manifest.id.clone(),
+                index: index as u32,
+                hash: hex::encode(chunk.hash),
+                data: hex::encode(ciphertext),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Load one page of a channel's stored messages for archiving
+    async fn load_archive_page(
+        &self,
+        channel: &Channel,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>, Vec<u8>, i64, bool, Option<Vec<u8>>)>, ArchiveError> {
+        self.mls_service
+            .load_messages_from_storage(&channel.mls_group_id, limit, offset)
+            .await
+            .map_err(|e| {
+                ArchiveError::Channel(ChannelError::MlsError(format!(
+                    "Failed to load messages: {:?}",
+                    e
+                )))
+            })
+    }
+}
+
+/// Privacy settings of a Space channel
+struct SpaceChannelPrivacy {
+    space_visibility: SpaceVisibility,
+    channel_visibility: ChannelVisibility,
+}
+
+impl PrivacySettings for SpaceChannelPrivacy {
+    fn discoverable(&self) -> bool {
+        // Public Spaces are listed, and their public channels auto-joined
+        self.space_visibility == SpaceVisibility::Public
+            && self.channel_visibility == ChannelVisibility::Public
+    }
+
+    fn sealed_sender(&self) -> bool {
+        // Senders are sealed in local storage, but the network envelope names them
+        false
+    }
+
+    fn padding_bucket(&self) -> Option<usize> {
+        None
+    }
+
+    fn presence(&self) -> bool {
+        false
+    }
+
+    fn retention(&self) -> Option<Duration> {
+        None
+    }
+
+    fn member_list(&self) -> MemberListVisibility {
+        match self.channel_visibility {
+            ChannelVisibility::Public => MemberListVisibility::SpaceMembers,
+            ChannelVisibility::Private => MemberListVisibility::Members,
+        }
+    }
+
+    fn membership_batch_interval(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// A channel's ID as it appears on the network and in events
+fn event_channel_id(channel_id: &ChannelId) -> crate::core_store::model::types::ChannelId {
+    crate::core_store::model::types::ChannelId(hex::encode(channel_id.as_bytes()))
 }
 
 #[cfg(test)]
@@ -692,6 +1080,45 @@ mod tests {
         assert!(!channel.mls_group_id.as_bytes().is_empty(), "MLS group should be created");
     }
 
+    #[tokio::test]
+    async fn test_channel_privacy_report() {
+        let manager = setup_async_manager().await;
+        let alice = UserId::new("alice".to_string());
+
+        let space = manager
+            .create_space("Test Space".to_string(), alice.clone(), SpaceVisibility::Public)
+            .await
+            .unwrap();
+        let public = manager
+            .create_channel(
+                space.id.clone(),
+                "general".to_string(),
+                alice.clone(),
+                ChannelVisibility::Public,
+            )
+            .await
+            .unwrap();
+        let private = manager
+            .create_channel(
+                space.id.clone(),
+                "staff".to_string(),
+                alice,
+                ChannelVisibility::Private,
+            )
+            .await
+            .unwrap();
+
+        let public_report = manager.channel_privacy_report(&public.id).await.unwrap();
+        assert!(public_report.discoverable);
+        assert_eq!(public_report.member_list, MemberListVisibility::SpaceMembers);
+        assert_eq!(public_report.padding_bucket, None);
+
+        let private_report = manager.channel_privacy_report(&private.id).await.unwrap();
+        assert!(!private_report.discoverable);
+        assert_eq!(private_report.member_list, MemberListVisibility::Members);
+        assert!(private_report.score > public_report.score);
+    }
+
     #[tokio::test]
     async fn test_add_channel_member_with_mls() {
         let manager = setup_async_manager().await;
@@ -790,6 +1217,9 @@ impl AsyncSpaceManager {
         sequence: i64,
         plaintext_content: Option<&[u8]>,
     ) -> Result<(), ChannelError> {
+        // Wait out a running archive export
+        let _history = self.history_lock.read().await;
+
         // Get channel to find MLS group ID
         let manager = self.manager.read().await;
         let channel = manager.get_channel(channel_id)?;
@@ -845,11 +1275,13 @@ impl AsyncSpaceManager {
             .map_err(|e| ChannelError::MlsError(format!("Failed to generate key package: {:?}", e)))?;
 
         // Add member to MLS group
+        self.emit_commit_events(channel_id, &group_id).await;
         let (commit, _welcome, _ratchet_tree) = self
             .mls_service
             .add_members(&group_id, vec![key_package])
             .await
             .map_err(|e| ChannelError::MlsError(format!("Failed to add member: {:?}", e)))?;
+        self.emit_commit_events(channel_id, &group_id).await;
 
         // Broadcast commit to all channel members for MLS state synchronization
         if let Some(ref network_layer) = self.network_layer {
@@ -969,6 +1401,23 @@ impl AsyncSpaceManager {
                 ChannelError::MlsError(format!("Failed to save channel metadata during join: {:?}", e))
             })?;
 
+        self.record_joined_channel(space_id, &channel_id, channel_name, user_id, group_id)
+            .await?;
+
+        Ok(channel_id)
+    }
+
+    /// Record a channel joined by invite, and its Space if it's new here
+    ///
+    /// The Space record is a placeholder until the Space itself is synced.
+    pub async fn record_joined_channel(
+        &self,
+        space_id: &SpaceId,
+        channel_id: &ChannelId,
+        channel_name: &str,
+        user_id: &UserId,
+        group_id: GroupId,
+    ) -> Result<(), ChannelError> {
         // Create minimal space and channel records for messaging
         // This allows send/receive to work without full space sync
         use crate::core_space::space::{Space, SpaceVisibility};
@@ -986,6 +1435,7 @@ impl AsyncSpaceManager {
             SpaceMember {
                 user_id: user_id.clone(),
                 role: crate::core_space::space::SpaceRole::Member,
+                custom_role: None,
                 joined_at: now.clone(),
                 invited_by: None,
             }
@@ -999,6 +1449,7 @@ impl AsyncSpaceManager {
             visibility: SpaceVisibility::Private,
             owner_id: user_id.clone(),  // Temporary - not the real owner
             members: space_members,
+            roles: Default::default(),
             channels: vec![channel_id.clone()],
             created_at: now.clone(),
             updated_at: now.clone(),
@@ -1022,12 +1473,14 @@ impl AsyncSpaceManager {
 
         // Save space and channel to local database
         let mut manager = self.manager.write().await;
-        manager.create_space_direct(&space)
-            .map_err(|e| ChannelError::MlsError(format!("Failed to create space during join: {:?}", e)))?;
+        if manager.get_space(space_id).is_err() {
+            manager.create_space_direct(&space)
+                .map_err(|e| ChannelError::MlsError(format!("Failed to create space during join: {:?}", e)))?;
+        }
         manager.create_channel_direct(&channel)
             .map_err(|e| ChannelError::MlsError(format!("Failed to create channel during join: {:?}", e)))?;
         drop(manager);
 
-        Ok(channel_id)
+        Ok(())
     }
-}
\ No newline at end of file
+}
@@ -8,6 +8,7 @@ use super::storage::SpaceSqlStore;
 use super::types::{ChannelId, SpaceId};
 use crate::core_mls::types::GroupId;
 use crate::core_store::model::types::{Timestamp, UserId};
+use crate::core_store::model::{Permission, PermissionSet};
 
 /// Manager implementation with business logic
 ///
@@ -45,19 +46,116 @@ impl SpaceManagerImpl {
         Ok(())
     }
 
-    /// Check if user has admin privileges in a Space
-    fn check_admin_permission(
+    /// Check if user holds a capability in a Space
+    fn check_permission(
         &self,
         space_id: &SpaceId,
         user_id: &UserId,
+        permission: Permission,
     ) -> Result<(), SpaceError> {
         let space = self.store.get_space(space_id)?;
-        if !space.is_admin(user_id) {
+        if !space.has_permission(user_id, permission) {
             return Err(SpaceError::PermissionDenied);
         }
         Ok(())
     }
 
+    /// Capabilities a user holds in the Space owning a channel
+    pub fn channel_permissions(
+        &self,
+        channel_id: &ChannelId,
+        user_id: &UserId,
+    ) -> Result<PermissionSet, ChannelError> {
+        let channel = self.store.get_channel(channel_id)?;
+        let space = self.store.get_space(&channel.space_id).map_err(|_| ChannelError::PermissionDenied)?;
+        Ok(space.permissions(user_id))
+    }
+
+    /// Check if user holds a capability in the Space owning a channel
+    fn check_channel_permission(
+        &self,
+        channel: &Channel,
+        user_id: &UserId,
+        permission: Permission,
+    ) -> Result<Space, ChannelError> {
+        let space = self.store.get_space(&channel.space_id).map_err(|_| ChannelError::PermissionDenied)?;
+        if !space.has_permission(user_id, permission) {
+            return Err(ChannelError::PermissionDenied);
+        }
+        Ok(space)
+    }
+
+    /// Define or redefine a custom role (requires ManageRoles)
+    ///
+    /// A role can only grant capabilities the caller holds, so nobody can
+    /// escalate their own privileges through a role they defined.
+    pub fn define_role(
+        &mut self,
+        space_id: &SpaceId,
+        admin_id: &UserId,
+        name: String,
+        permissions: PermissionSet,
+    ) -> Result<(), MembershipError> {
+        let mut space = self.store.get_space(space_id)?;
+
+        let granted = space.permissions(admin_id);
+        if !granted.contains(Permission::ManageRoles) || !granted.contains_all(permissions) {
+            return Err(MembershipError::PermissionDenied);
+        }
+
+        space.define_role(name, permissions)?;
+        self.store.update_space(&space)?;
+
+        Ok(())
+    }
+
+    /// Delete a custom role (requires ManageRoles)
+    pub fn delete_role(
+        &mut self,
+        space_id: &SpaceId,
+        admin_id: &UserId,
+        name: &str,
+    ) -> Result<(), MembershipError> {
+        self.check_permission(space_id, admin_id, Permission::ManageRoles)?;
+
+        let mut space = self.store.get_space(space_id)?;
+        space.delete_role(name)?;
+        self.store.update_space(&space)?;
+
+        Ok(())
+    }
+
+    /// Assign a custom role to a member, or clear it (requires ManageRoles)
+    ///
+    /// Like built-in role changes, the caller must hold every capability the
+    /// member currently has and every capability the new role grants.
+    pub fn assign_custom_role(
+        &mut self,
+        space_id: &SpaceId,
+        admin_id: &UserId,
+        target_user_id: &UserId,
+        role: Option<String>,
+    ) -> Result<(), MembershipError> {
+        let mut space = self.store.get_space(space_id)?;
+
+        let granted = space.permissions(admin_id);
+        let role_permissions = match &role {
+            Some(name) => *space.roles.get(name).ok_or(SpaceError::RoleNotFound)?,
+            None => PermissionSet::empty(),
+        };
+        if !granted.contains(Permission::ManageRoles)
+            || !granted.contains_all(space.permissions(target_user_id))
+            || !granted.contains_all(role_permissions)
+        {
+            return Err(MembershipError::PermissionDenied);
+        }
+
+        space.assign_custom_role(target_user_id, role)?;
+        self.store.update_space(&space)?;
+
+        Ok(())
+    }
+
     /// Check if user is a member of a Space
     fn check_space_membership(
         &self,
@@ -181,9 +279,9 @@ impl MembershipManager for SpaceManagerImpl {
         max_uses: Option<u32>,
         expires_at: Option<Timestamp>,
     ) -> Result<SpaceInvite, InviteError> {
-        // Verify creator is a member
+        // Verify creator may invite
         let space = self.store.get_space(&space_id).map_err(|_| InviteError::InvalidInviteCode)?;
-        if !space.is_member(&created_by) {
+        if !space.has_permission(&created_by, Permission::Invite) {
             return Err(InviteError::UnauthorizedUser);
         }
 
@@ -203,9 +301,9 @@ impl MembershipManager for SpaceManagerImpl {
         target_user: UserId,
         expires_at: Option<Timestamp>,
     ) -> Result<SpaceInvite, InviteError> {
-        // Verify creator is a member
+        // Verify creator may invite
         let space = self.store.get_space(&space_id).map_err(|_| InviteError::InvalidInviteCode)?;
-        if !space.is_member(&created_by) {
+        if !space.has_permission(&created_by, Permission::Invite) {
             return Err(InviteError::UnauthorizedUser);
         }
 
@@ -288,7 +386,7 @@ impl MembershipManager for SpaceManagerImpl {
         let mut space = self.store.get_space(space_id)?;
 
         // Verify admin has permission
-        if !space.is_admin(admin_id) {
+        if !space.has_permission(admin_id, Permission::Kick) {
             return Err(MembershipError::PermissionDenied);
         }
 
@@ -318,8 +416,12 @@ impl MembershipManager for SpaceManagerImpl {
     ) -> Result<(), MembershipError> {
         let mut space = self.store.get_space(space_id)?;
 
-        // Verify admin has permission
-        if !space.is_admin(admin_id) {
+        // Verify admin may manage roles and outranks both the old and new role
+        let granted = space.permissions(admin_id);
+        if !granted.contains(Permission::ManageRoles)
+            || !granted.contains_all(space.permissions(target_user_id))
+            || !granted.contains_all(new_role.default_permissions())
+        {
             return Err(MembershipError::PermissionDenied);
         }
 
@@ -335,9 +437,9 @@ impl MembershipManager for SpaceManagerImpl {
     fn revoke_invite(&mut self, invite_id: &str, user_id: &UserId) -> Result<(), InviteError> {
         let mut invite = self.store.get_invite(invite_id)?;
 
-        // Verify user has permission (must be creator or Space admin)
+        // Verify user has permission (must be creator or able to kick)
         let space = self.store.get_space(&invite.space_id).map_err(|_| InviteError::InviteNotFound)?;
-        if &invite.created_by != user_id && !space.is_admin(user_id) {
+        if &invite.created_by != user_id && !space.has_permission(user_id, Permission::Kick) {
             return Err(InviteError::UnauthorizedUser);
         }
 
@@ -415,11 +517,8 @@ impl ChannelManager for SpaceManagerImpl {
     ) -> Result<(), ChannelError> {
         let channel = self.store.get_channel(channel_id)?;
 
-        // Verify admin is a Space admin
-        let space = self.store.get_space(&channel.space_id).map_err(|_| ChannelError::PermissionDenied)?;
-        if !space.is_admin(admin_id) {
-            return Err(ChannelError::PermissionDenied);
-        }
+        // Verify admin may edit channels
+        self.check_channel_permission(&channel, admin_id, Permission::EditChannel)?;
 
         let mut channel = channel;
 
@@ -457,11 +556,8 @@ impl ChannelManager for SpaceManagerImpl {
     ) -> Result<(), ChannelError> {
         let channel = self.store.get_channel(channel_id)?;
 
-        // Verify user is Space admin
-        let space = self.store.get_space(&channel.space_id).map_err(|_| ChannelError::PermissionDenied)?;
-        if !space.is_admin(admin_id) {
-            return Err(ChannelError::PermissionDenied);
-        }
+        // Verify user may edit channels
+        self.check_channel_permission(&channel, admin_id, Permission::EditChannel)?;
 
         // Delete channel (cascades to members)
         self.store.delete_channel(channel_id)?;
@@ -480,11 +576,8 @@ impl ChannelManager for SpaceManagerImpl {
     ) -> Result<(), ChannelError> {
         let mut channel = self.store.get_channel(channel_id)?;
 
-        // Verify admin is a Space admin
-        let space = self.store.get_space(&channel.space_id).map_err(|_| ChannelError::PermissionDenied)?;
-        if !space.is_admin(admin_id) {
-            return Err(ChannelError::PermissionDenied);
-        }
+        // Verify admin may invite
+        let space = self.check_channel_permission(&channel, admin_id, Permission::Invite)?;
 
         // Verify user is a Space member
         if !space.is_member(user_id) {
@@ -511,11 +604,8 @@ impl ChannelManager for SpaceManagerImpl {
     ) -> Result<(), ChannelError> {
         let mut channel = self.store.get_channel(channel_id)?;
 
-        // Verify admin is a Space admin
-        let space = self.store.get_space(&channel.space_id).map_err(|_| ChannelError::PermissionDenied)?;
-        if !space.is_admin(admin_id) {
-            return Err(ChannelError::PermissionDenied);
-        }
+        // Verify admin may kick
+        self.check_channel_permission(&channel, admin_id, Permission::Kick)?;
 
         // Remove member from channel
         channel.remove_member(user_id)?;
@@ -748,4 +838,76 @@ mod tests {
         assert_eq!(channels.len(), 1);
         assert_eq!(channels[0].name, "general");
     }
+
+    #[test]
+    fn test_custom_moderator_role() {
+        let mut manager = setup_manager();
+        let owner = UserId::new("alice".to_string());
+        let moderator = UserId::new("bob".to_string());
+        let member = UserId::new("carol".to_string());
+
+        let space = manager
+            .create_space("Test Space".to_string(), owner.clone(), SpaceVisibility::Public)
+            .unwrap();
+        manager.join_public_space(moderator.clone(), space.id).unwrap();
+        manager.join_public_space(member.clone(), space.id).unwrap();
+        let channel = manager
+            .create_channel(
+                space.id,
+                "general".to_string(),
+                owner.clone(),
+                ChannelVisibility::Private,
+                None,
+            )
+            .unwrap();
+
+        // Members cannot define or assign roles
+        let greeter: PermissionSet =
+            [Permission::Post, Permission::Invite].into_iter().collect();
+        assert!(manager
+            .define_role(&space.id, &moderator, "greeter".to_string(), greeter)
+            .is_err());
+
+        manager.define_role(&space.id, &owner, "greeter".to_string(), greeter).unwrap();
+        manager
+            .assign_custom_role(&space.id, &owner, &moderator, Some("greeter".to_string()))
+            .unwrap();
+
+        // The greeter can invite and add people to channels, but not kick
+        manager.create_invite(space.id, moderator.clone(), None, None).unwrap();
+        manager.add_channel_member(&channel.id, &member, &moderator).unwrap();
+        assert!(matches!(
+            manager.remove_channel_member(&channel.id, &member, &moderator),
+            Err(ChannelError::PermissionDenied)
+        ));
+        assert!(matches!(
+            manager.kick_member(&space.id, &moderator, &member),
+            Err(MembershipError::PermissionDenied)
+        ));
+        assert!(matches!(
+            manager.update_member_role(&space.id, &moderator, &member, SpaceRole::Admin),
+            Err(MembershipError::PermissionDenied)
+        ));
+
+        // A role manager cannot grant capabilities they lack, nor touch the owner
+        let role_manager: PermissionSet =
+            [Permission::Post, Permission::ManageRoles].into_iter().collect();
+        manager.define_role(&space.id, &owner, "steward".to_string(), role_manager).unwrap();
+        manager
+            .assign_custom_role(&space.id, &owner, &member, Some("steward".to_string()))
+            .unwrap();
+        assert!(matches!(
+            manager.update_member_role(&space.id, &member, &moderator, SpaceRole::Admin),
+            Err(MembershipError::PermissionDenied)
+        ));
+        assert!(manager
+            .define_role(&space.id, &member, "kicker".to_string(), Permission::Kick.into())
+            .is_err());
+        assert!(manager.assign_custom_role(&space.id, &member, &owner, None).is_err());
+
+        // The owner keeps every capability and can still kick
+        let space = manager.get_space(&space.id).unwrap();
+        assert_eq!(space.permissions(&owner), PermissionSet::all());
+        manager.kick_member(&space.id, &owner, &moderator).unwrap();
+    }
 }
@@ -13,9 +13,10 @@
 //!
 //! 1. Channel-scoped MLS groups (not Space-scoped)
 //! 2. Auto-join public channels on Space membership
-//! 3. Simple role model (Owner, Admin, Member)
+//! 3. Simple role model (Owner, Admin, Member) with capability-based checks and custom roles
 //! 4. Scalable to 1000+ members per Space
 
+pub mod archive;
 pub mod channel;
 pub mod invite;
 pub mod manager;
@@ -25,6 +26,10 @@ pub mod space;
 pub mod storage;
 pub mod types;
 
+pub use archive::{
+    extract_channel, verify_archive, ArchiveError, ArchiveManifest, ArchiveOptions, ArchiveRecord,
+    ArchiveSummary, ArchiveWriter, ArchivedBlob, ArchivedMessage, ARCHIVE_VERSION,
+};
 pub use channel::{Channel, ChannelError, ChannelVisibility};
 pub use invite::{InviteError, InviteType, SpaceInvite};
 pub use manager::{ChannelManager, MembershipError, MembershipManager, SpaceManager};
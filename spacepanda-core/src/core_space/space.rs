@@ -2,8 +2,12 @@
 
 use super::types::{ChannelId, SpaceId};
 use crate::core_store::model::types::{Timestamp, UserId};
+use crate::core_store::model::{Permission, PermissionSet};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+
+/// Maximum length of a custom role name
+pub const MAX_ROLE_NAME_LEN: usize = 32;
 
 /// A Space is a container for channels (like Discord servers)
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,6 +33,10 @@ pub struct Space {
     /// Space members with their roles
     pub members: HashMap<UserId, SpaceMember>,
 
+    /// Custom roles defined in this Space, by name
+    #[serde(default)]
+    pub roles: BTreeMap<String, PermissionSet>,
+
     /// List of channels in this Space
     pub channels: Vec<ChannelId>,
 
@@ -51,6 +59,7 @@ impl Space {
             SpaceMember {
                 user_id: owner_id.clone(),
                 role: SpaceRole::Owner,
+                custom_role: None,
                 joined_at: now,
                 invited_by: None,
             },
@@ -64,6 +73,7 @@ impl Space {
             visibility,
             owner_id,
             members,
+            roles: BTreeMap::new(),
             channels: Vec::new(),
             created_at: now,
             updated_at: now,
@@ -81,6 +91,7 @@ impl Space {
             SpaceMember {
                 user_id,
                 role: SpaceRole::Member,
+                custom_role: None,
                 joined_at: Timestamp::now(),
                 invited_by: Some(invited_by),
             },
@@ -105,6 +116,8 @@ impl Space {
     }
 
     /// Update a member's role
+    ///
+    /// Clears any custom role, so the member gets the new role's defaults.
     pub fn update_member_role(
         &mut self,
         user_id: &UserId,
@@ -120,6 +133,7 @@ impl Space {
             .ok_or(SpaceError::MemberNotFound)?;
 
         member.role = new_role;
+        member.custom_role = None;
         self.updated_at = Timestamp::now();
         Ok(())
     }
@@ -154,12 +168,95 @@ impl Space {
     }
 
     /// Check if a user has admin privileges (Owner or Admin)
+    ///
+    /// This checks role identity; authorization should use
+    /// [`has_permission`](Self::has_permission) instead.
     pub fn is_admin(&self, user_id: &UserId) -> bool {
         matches!(
             self.get_member_role(user_id),
             Some(SpaceRole::Owner) | Some(SpaceRole::Admin)
         )
     }
+
+    /// Capabilities a user holds in this Space
+    ///
+    /// The owner always holds every capability. Members with a custom role get
+    /// that role's set, everyone else gets their built-in role's defaults.
+    pub fn permissions(&self, user_id: &UserId) -> PermissionSet {
+        if user_id == &self.owner_id {
+            return PermissionSet::all();
+        }
+
+        let Some(member) = self.members.get(user_id) else {
+            return PermissionSet::empty();
+        };
+
+        member
+            .custom_role
+            .as_ref()
+            .and_then(|name| self.roles.get(name))
+            .copied()
+            .unwrap_or_else(|| member.role.default_permissions())
+    }
+
+    /// Check if a user holds a capability in this Space
+    pub fn has_permission(&self, user_id: &UserId, permission: Permission) -> bool {
+        self.permissions(user_id).contains(permission)
+    }
+
+    /// Define or redefine a custom role
+    pub fn define_role(
+        &mut self,
+        name: String,
+        permissions: PermissionSet,
+    ) -> Result<(), SpaceError> {
+        if name.is_empty() || name.len() > MAX_ROLE_NAME_LEN {
+            return Err(SpaceError::InvalidRoleName);
+        }
+
+        self.roles.insert(name, permissions);
+        self.updated_at = Timestamp::now();
+        Ok(())
+    }
+
+    /// Delete a custom role; members holding it fall back to their built-in role
+    pub fn delete_role(&mut self, name: &str) -> Result<(), SpaceError> {
+        if self.roles.remove(name).is_none() {
+            return Err(SpaceError::RoleNotFound);
+        }
+
+        for member in self.members.values_mut() {
+            if member.custom_role.as_deref() == Some(name) {
+                member.custom_role = None;
+            }
+        }
+
+        self.updated_at = Timestamp::now();
+        Ok(())
+    }
+
+    /// Assign a custom role to a member, or clear it with `None`
+    pub fn assign_custom_role(
+        &mut self,
+        user_id: &UserId,
+        role: Option<String>,
+    ) -> Result<(), SpaceError> {
+        if user_id == &self.owner_id {
+            return Err(SpaceError::CannotChangeOwnerRole);
+        }
+
+        if let Some(name) = &role {
+            if !self.roles.contains_key(name) {
+                return Err(SpaceError::RoleNotFound);
+            }
+        }
+
+        let member = self.members.get_mut(user_id).ok_or(SpaceError::MemberNotFound)?;
+
+        member.custom_role = role;
+        self.updated_at = Timestamp::now();
+        Ok(())
+    }
 }
 
 /// Space visibility modes
@@ -180,6 +277,10 @@ pub struct SpaceMember {
     /// Role in the Space
     pub role: SpaceRole,
 
+    /// Custom role overriding `role`'s default capabilities
+    #[serde(default)]
+    pub custom_role: Option<String>,
+
     /// When the member joined
     pub joined_at: Timestamp,
 
@@ -198,6 +299,19 @@ pub enum SpaceRole {
     Member,
 }
 
+impl SpaceRole {
+    /// Capabilities granted by this role when no custom role is assigned
+    ///
+    /// Matches what each role could do before capability sets existed:
+    /// members post and create invites, admins and owners do everything.
+    pub fn default_permissions(self) -> PermissionSet {
+        match self {
+            SpaceRole::Owner | SpaceRole::Admin => PermissionSet::all(),
+            SpaceRole::Member => [Permission::Post, Permission::Invite].into_iter().collect(),
+        }
+    }
+}
+
 /// Space operation errors
 #[derive(Debug, thiserror::Error)]
 pub enum SpaceError {
@@ -216,6 +330,12 @@ pub enum SpaceError {
     #[error("Channel not found in Space")]
     ChannelNotFound,
 
+    #[error("Role not found in Space")]
+    RoleNotFound,
+
+    #[error("Invalid role name")]
+    InvalidRoleName,
+
     #[error("Permission denied")]
     PermissionDenied,
 }
@@ -337,4 +457,58 @@ mod tests {
         space.remove_channel(&channel_id).unwrap();
         assert_eq!(space.channels.len(), 0);
     }
+
+    #[test]
+    fn test_builtin_role_permissions() {
+        let owner = UserId::new("alice".to_string());
+        let mut space =
+            Space::new("Test Space".to_string(), owner.clone(), SpaceVisibility::Public);
+
+        let member = UserId::new("bob".to_string());
+        space.add_member(member.clone(), owner.clone()).unwrap();
+
+        assert_eq!(space.permissions(&owner), PermissionSet::all());
+        assert!(space.has_permission(&member, Permission::Post));
+        assert!(space.has_permission(&member, Permission::Invite));
+        assert!(!space.has_permission(&member, Permission::Kick));
+        assert!(!space.has_permission(&member, Permission::ManageRoles));
+
+        space.update_member_role(&member, SpaceRole::Admin).unwrap();
+        assert_eq!(space.permissions(&member), PermissionSet::all());
+
+        // Non-members hold nothing
+        assert!(space.permissions(&UserId::new("carol".to_string())).is_empty());
+    }
+
+    #[test]
+    fn test_custom_role() {
+        let owner = UserId::new("alice".to_string());
+        let mut space =
+            Space::new("Test Space".to_string(), owner.clone(), SpaceVisibility::Public);
+
+        let member = UserId::new("bob".to_string());
+        space.add_member(member.clone(), owner.clone()).unwrap();
+
+        let greeter: PermissionSet =
+            [Permission::Post, Permission::Invite, Permission::PinMessages]
+                .into_iter()
+                .collect();
+        space.define_role("greeter".to_string(), greeter).unwrap();
+        space.assign_custom_role(&member, Some("greeter".to_string())).unwrap();
+        assert_eq!(space.permissions(&member), greeter);
+
+        // Unknown roles and the owner cannot be assigned
+        assert!(matches!(
+            space.assign_custom_role(&member, Some("nope".to_string())),
+            Err(SpaceError::RoleNotFound)
+        ));
+        assert!(matches!(
+            space.assign_custom_role(&owner, Some("greeter".to_string())),
+            Err(SpaceError::CannotChangeOwnerRole)
+        ));
+
+        // Deleting the role falls back to the built-in defaults
+        space.delete_role("greeter").unwrap();
+        assert_eq!(space.permissions(&member), SpaceRole::Member.default_permissions());
+    }
 }
@@ -9,7 +9,7 @@ use rusqlite::params;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Current schema version for core_space
-pub const CURRENT_SPACE_SCHEMA_VERSION: i32 = 1;
+pub const CURRENT_SPACE_SCHEMA_VERSION: i32 = 2;
 
 /// Migration descriptor
 pub struct Migration {
@@ -139,6 +139,31 @@ pub fn get_migrations() -> Vec<Migration> {
             "#,
             ),
         },
+        Migration {
+            version: 2,
+            description: "Capability-based permissions and custom roles",
+            // Existing members keep their Owner/Admin/Member role, which maps
+            // to that role's default capability set; no custom role is set.
+            up_sql: r#"
+                -- Custom roles defined per Space (permissions is a PermissionSet bit mask)
+                CREATE TABLE IF NOT EXISTS space_roles (
+                    space_id BLOB NOT NULL,                 -- SpaceId
+                    name TEXT NOT NULL,
+                    permissions INTEGER NOT NULL,
+                    PRIMARY KEY (space_id, name),
+                    FOREIGN KEY (space_id) REFERENCES spaces(id) ON DELETE CASCADE
+                );
+
+                -- Custom role overriding the built-in role's defaults
+                ALTER TABLE space_members ADD COLUMN custom_role TEXT;
+            "#,
+            down_sql: Some(
+                r#"
+                ALTER TABLE space_members DROP COLUMN custom_role;
+                DROP TABLE IF EXISTS space_roles;
+            "#,
+            ),
+        },
     ]
 }
 
@@ -253,6 +278,7 @@ mod tests {
         assert!(tables.contains(&"channels".to_string()));
         assert!(tables.contains(&"channel_members".to_string()));
         assert!(tables.contains(&"space_invites".to_string()));
+        assert!(tables.contains(&"space_roles".to_string()));
     }
 
     #[test]
@@ -6,10 +6,11 @@ use super::super::space::{Space, SpaceError, SpaceMember, SpaceRole, SpaceVisibi
 use super::super::types::{ChannelId, SpaceId};
 use crate::core_mls::types::GroupId;
 use crate::core_store::model::types::{Timestamp, UserId};
+use crate::core_store::model::PermissionSet;
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
 use rusqlite::{params, OptionalExtension, Row};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 /// SQL-based storage for Spaces and Channels
 pub struct SpaceSqlStore {
@@ -69,11 +70,36 @@ impl SpaceSqlStore {
         )
         .map_err(|_| SpaceError::PermissionDenied)?;
 
-        // Insert members
+        // Insert members and custom roles
+        Self::write_members_and_roles(&tx, space)?;
+
+        tx.commit().map_err(|_| SpaceError::PermissionDenied)?;
+
+        Ok(())
+    }
+
+    /// Replace a Space's member and custom role rows
+    fn write_members_and_roles(
+        tx: &rusqlite::Transaction<'_>,
+        space: &Space,
+    ) -> Result<(), SpaceError> {
+        tx.execute("DELETE FROM space_members WHERE space_id = ?", params![space.id.as_bytes()])
+            .map_err(|_| SpaceError::PermissionDenied)?;
+        tx.execute("DELETE FROM space_roles WHERE space_id = ?", params![space.id.as_bytes()])
+            .map_err(|_| SpaceError::PermissionDenied)?;
+
+        for (name, permissions) in &space.roles {
+            tx.execute(
+                "INSERT INTO space_roles (space_id, name, permissions) VALUES (?, ?, ?)",
+                params![space.id.as_bytes(), name, permissions.bits()],
+            )
+            .map_err(|_| SpaceError::PermissionDenied)?;
+        }
+
         for (user_id, member) in &space.members {
             tx.execute(
-                "INSERT INTO space_members (space_id, user_id, role, joined_at, invited_by)
-                 VALUES (?, ?, ?, ?, ?)",
+                "INSERT INTO space_members (space_id, user_id, role, custom_role, joined_at, invited_by)
+                 VALUES (?, ?, ?, ?, ?, ?)",
                 params![
                     space.id.as_bytes(),
                     user_id.to_string(),
@@ -82,6 +108,7 @@ impl SpaceSqlStore {
                         SpaceRole::Admin => "Admin",
                         SpaceRole::Member => "Member",
                     },
+                    &member.custom_role,
                     member.joined_at.as_millis() as i64,
                     member.invited_by.as_ref().map(|id| id.to_string()),
                 ],
@@ -89,8 +116,6 @@ impl SpaceSqlStore {
             .map_err(|_| SpaceError::PermissionDenied)?;
         }
 
-        tx.commit().map_err(|_| SpaceError::PermissionDenied)?;
-
         Ok(())
     }
 
@@ -120,6 +145,7 @@ impl SpaceSqlStore {
                         visibility,
                         owner_id: UserId::new(row.get(5)?),
                         members: HashMap::new(),
+                        roles: BTreeMap::new(),
                         channels: Vec::new(),
                         created_at: Timestamp::from_millis(row.get::<_, i64>(6)?.max(0) as u64),
                         updated_at: Timestamp::from_millis(row.get::<_, i64>(7)?.max(0) as u64),
@@ -133,7 +159,7 @@ impl SpaceSqlStore {
         // Get members
         let mut stmt = conn
             .prepare(
-                "SELECT user_id, role, joined_at, invited_by
+                "SELECT user_id, role, joined_at, invited_by, custom_role
                  FROM space_members WHERE space_id = ?",
             )
             .map_err(|_| SpaceError::PermissionDenied)?;
@@ -152,6 +178,7 @@ impl SpaceSqlStore {
                 let member = SpaceMember {
                     user_id: user_id.clone(),
                     role,
+                    custom_role: row.get(4)?,
                     joined_at: Timestamp::from_millis(row.get::<_, i64>(2)?.max(0) as u64),
                     invited_by: row.get::<_, Option<String>>(3)?.map(UserId::new),
                 };
@@ -164,6 +191,19 @@ impl SpaceSqlStore {
 
         space.members = members;
 
+        // Get custom roles
+        let mut stmt = conn
+            .prepare("SELECT name, permissions FROM space_roles WHERE space_id = ?")
+            .map_err(|_| SpaceError::PermissionDenied)?;
+
+        space.roles = stmt
+            .query_map(params![space_id.as_bytes()], |row| {
+                Ok((row.get(0)?, PermissionSet::from_bits_truncate(row.get(1)?)))
+            })
+            .map_err(|_| SpaceError::PermissionDenied)?
+            .collect::<Result<BTreeMap<_, _>, _>>()
+            .map_err(|_| SpaceError::PermissionDenied)?;
+
         // Get channel IDs
         let mut stmt = conn
             .prepare("SELECT id FROM channels WHERE space_id = ? ORDER BY created_at")
@@ -185,11 +225,12 @@ impl SpaceSqlStore {
         Ok(space)
     }
 
-    /// Update a Space's metadata
+    /// Update a Space's metadata, members and custom roles
     pub fn update_space(&self, space: &Space) -> Result<(), SpaceError> {
         let conn = self.pool.get().map_err(|_| SpaceError::PermissionDenied)?;
+        let tx = conn.unchecked_transaction().map_err(|_| SpaceError::PermissionDenied)?;
 
-        conn.execute(
+        tx.execute(
             "UPDATE spaces SET name = ?, description = ?, icon_url = ?, visibility = ?, updated_at = ?
              WHERE id = ?",
             params![
@@ -206,6 +247,10 @@ impl SpaceSqlStore {
         )
         .map_err(|_| SpaceError::PermissionDenied)?;
 
+        Self::write_members_and_roles(&tx, space)?;
+
+        tx.commit().map_err(|_| SpaceError::PermissionDenied)?;
+
         Ok(())
     }
 
@@ -605,6 +650,7 @@ impl SpaceSqlStore {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core_store::model::Permission;
 
     #[test]
     fn test_create_and_get_space() {
@@ -703,4 +749,71 @@ mod tests {
         // Channel should be gone
         assert!(store.get_channel(&channel.id).is_err());
     }
+
+    #[test]
+    fn test_custom_roles_persist() {
+        let store = SpaceSqlStore::memory().unwrap();
+        let owner = UserId::new("alice".to_string());
+        let member = UserId::new("bob".to_string());
+
+        let mut space =
+            Space::new("Test Space".to_string(), owner.clone(), SpaceVisibility::Public);
+        store.create_space(&space).unwrap();
+
+        let greeter: PermissionSet = [Permission::Post, Permission::Invite].into_iter().collect();
+        space.add_member(member.clone(), owner).unwrap();
+        space.define_role("greeter".to_string(), greeter).unwrap();
+        space.assign_custom_role(&member, Some("greeter".to_string())).unwrap();
+        store.update_space(&space).unwrap();
+
+        let retrieved = store.get_space(&space.id).unwrap();
+        assert_eq!(retrieved.roles.get("greeter"), Some(&greeter));
+        assert_eq!(retrieved.members[&member].custom_role.as_deref(), Some("greeter"));
+        assert_eq!(retrieved.permissions(&member), greeter);
+    }
+
+    #[test]
+    fn test_legacy_roles_map_to_default_permissions() {
+        let pool = Pool::builder().max_size(1).build(SqliteConnectionManager::memory()).unwrap();
+
+        // Database written before capability sets existed
+        {
+            let conn = pool.get().unwrap();
+            let v1 = &super::super::migrations::get_migrations()[0];
+            conn.execute_batch(v1.up_sql).unwrap();
+            conn.execute(
+                "INSERT INTO space_schema_version (version, applied_at) VALUES (1, 0)",
+                [],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO spaces (id, name, visibility, owner_id, created_at, updated_at)
+                 VALUES (?, 'Legacy', 'Public', 'alice', 0, 0)",
+                params![[7u8; 32]],
+            )
+            .unwrap();
+            for (user, role) in [("alice", "Owner"), ("bob", "Admin"), ("carol", "Member")] {
+                conn.execute(
+                    "INSERT INTO space_members (space_id, user_id, role, joined_at)
+                     VALUES (?, ?, ?, 0)",
+                    params![[7u8; 32], user, role],
+                )
+                .unwrap();
+            }
+        }
+
+        let store = SpaceSqlStore::new(pool).unwrap();
+        let space = store.get_space(&SpaceId::from_bytes([7u8; 32])).unwrap();
+
+        for (user, role) in [
+            ("alice", SpaceRole::Owner),
+            ("bob", SpaceRole::Admin),
+            ("carol", SpaceRole::Member),
+        ] {
+            let user = UserId::new(user.to_string());
+            assert_eq!(space.get_member_role(&user), Some(role));
+            assert_eq!(space.members[&user].custom_role, None);
+            assert_eq!(space.permissions(&user), role.default_permissions());
+        }
+    }
 }
@@ -0,0 +1,222 @@
+/*
+    archive.rs - Encrypted, content-addressed backup format
+
+    A backup is a set of encrypted chunks plus one encrypted manifest per
+    snapshot listing which chunks make up which file. Files are split into
+    fixed-size chunks, so a file that did not change since the last push maps
+    to the same chunk IDs and nothing needs to be uploaded for it; an
+    appended log only re-uploads its tail.
+
+    Everything leaving the device is encrypted with a key derived from the
+    backup passphrase (Argon2id). Chunk IDs are keyed hashes, so the remote
+    cannot confirm guesses about plaintext content, and chunk encryption uses
+    a nonce derived from the content so identical chunks produce identical
+    objects (which is what makes deduplication across pushes possible).
+*/
+
+use super::errors::{BackupError, BackupResult};
+use crate::core_store::model::Timestamp;
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Size of a backup chunk
+pub const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Current on-remote format version
+pub const FORMAT_VERSION: u32 = 1;
+
+const NONCE_LEN: usize = 12;
+
+/// Known plaintext sealed into the remote parameters to check the passphrase
+const KEY_CHECK: &[u8] = b"spacepanda-backup-key-check";
+
+/// Unencrypted parameters stored once per remote
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteParams {
+    /// Backup format version
+    pub version: u32,
+
+    /// Argon2id salt for the passphrase
+    pub salt: [u8; 16],
+
+    /// `KEY_CHECK` sealed with the derived key
+    pub key_check: Vec<u8>,
+}
+
+impl RemoteParams {
+    /// Generate parameters for a new remote
+    pub fn generate(passphrase: &str) -> BackupResult<(Self, BackupKey)> {
+        let mut salt = [0u8; 16];
+        rand::rng().fill_bytes(&mut salt);
+        let key = BackupKey::derive(passphrase, &salt)?;
+        let params = Self { version: FORMAT_VERSION, salt, key_check: key.seal(KEY_CHECK)? };
+        Ok((params, key))
+    }
+
+    /// Derive the key for these parameters, checking the passphrase
+    pub fn unlock(&self, passphrase: &str) -> BackupResult<BackupKey> {
+        if self.version != FORMAT_VERSION {
+            return Err(BackupError::InvalidConfig(format!(
+                "Unsupported backup format version {}",
+                self.version
+            )));
+        }
+
+        let key = BackupKey::derive(passphrase, &self.salt)?;
+        match key.open(&self.key_check) {
+            Ok(check) if check == KEY_CHECK => Ok(key),
+            _ => Err(BackupError::Decryption("Wrong backup passphrase".to_string())),
+        }
+    }
+}
+
+/// Keys derived from the backup passphrase
+#[derive(Zeroize, ZeroizeOnDrop)]
+pub struct BackupKey {
+    cipher_key: [u8; 32],
+    id_key: [u8; 32],
+    nonce_key: [u8; 32],
+}
+
+impl BackupKey {
+    /// Derive backup keys from a passphrase and salt
+    pub fn derive(passphrase: &str, salt: &[u8]) -> BackupResult<Self> {
+        let mut master = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut master)
+            .map_err(|e| BackupError::InvalidConfig(format!("Key derivation failed: {}", e)))?;
+
+        let key = Self {
+            cipher_key: blake3::derive_key("spacepanda backup v1 cipher", &master),
+            id_key: blake3::derive_key("spacepanda backup v1 chunk id", &master),
+            nonce_key: blake3::derive_key("spacepanda backup v1 chunk nonce", &master),
+        };
+        master.zeroize();
+        Ok(key)
+    }
+
+    /// Content address of a chunk
+    pub fn chunk_id(&self, plaintext: &[u8]) -> String {
+        blake3::keyed_hash(&self.id_key, plaintext).to_hex().to_string()
+    }
+
+    /// Encrypt a chunk deterministically, so identical chunks seal identically
+    pub fn seal_chunk(&self, plaintext: &[u8]) -> BackupResult<Vec<u8>> {
+        let digest = blake3::keyed_hash(&self.nonce_key, plaintext);
+        let mut nonce = [0u8; NONCE_LEN];
+        nonce.copy_from_slice(&digest.as_bytes()[..NONCE_LEN]);
+        self.seal_with_nonce(nonce, plaintext)
+    }
+
+    /// Encrypt with a random nonce
+    pub fn seal(&self, plaintext: &[u8]) -> BackupResult<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::rng().fill_bytes(&mut nonce);
+        self.seal_with_nonce(nonce, plaintext)
+    }
+
+    /// Decrypt output of [`seal`](Self::seal) or [`seal_chunk`](Self::seal_chunk)
+    pub fn open(&self, sealed: &[u8]) -> BackupResult<Vec<u8>> {
+        if sealed.len() < NONCE_LEN {
+            return Err(BackupError::Decryption("Sealed object too short".to_string()));
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        self.cipher()
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| BackupError::Decryption("Authentication failed".to_string()))
+    }
+
+    fn seal_with_nonce(&self, nonce: [u8; NONCE_LEN], plaintext: &[u8]) -> BackupResult<Vec<u8>> {
+        let ciphertext = self
+            .cipher()
+            .encrypt(Nonce::from_slice(&nonce), plaintext)
+            .map_err(|e| BackupError::Serialization(format!("Encryption failed: {}", e)))?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(Key::from_slice(&self.cipher_key))
+    }
+}
+
+/// A file recorded in a snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestFile {
+    /// Path relative to the backed-up directory, `/`-separated
+    pub path: String,
+
+    /// File size in bytes
+    pub size: u64,
+
+    /// Chunk IDs in file order
+    pub chunks: Vec<String>,
+}
+
+/// Contents of one snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Snapshot identifier (sorts chronologically)
+    pub snapshot_id: String,
+
+    /// When the snapshot was taken
+    pub created_at: Timestamp,
+
+    /// Files in the snapshot, sorted by path
+    pub files: Vec<ManifestFile>,
+}
+
+impl Manifest {
+    /// Start an empty manifest with a fresh snapshot ID
+    pub fn new() -> Self {
+        let created_at = Timestamp::now();
+        let mut suffix = [0u8; 4];
+        rand::rng().fill_bytes(&mut suffix);
+        let snapshot_id = format!("{:013}-{}", created_at.as_millis(), hex::encode(suffix));
+        Self { snapshot_id, created_at, files: Vec::new() }
+    }
+
+    /// Total size of all files
+    pub fn total_size(&self) -> u64 {
+        self.files.iter().map(|f| f.size).sum()
+    }
+
+    /// All chunk IDs referenced by the snapshot (may repeat)
+    pub fn chunk_ids(&self) -> impl Iterator<Item = &str> {
+        self.files.iter().flat_map(|f| f.chunks.iter().map(String::as_str))
+    }
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunks_seal_deterministically() {
+        let (params, key) = RemoteParams::generate("correct horse").unwrap();
+
+        let a = key.seal_chunk(b"chunk data").unwrap();
+        assert_eq!(a, key.seal_chunk(b"chunk data").unwrap());
+        assert_ne!(key.seal(b"chunk data").unwrap(), key.seal(b"chunk data").unwrap());
+        assert_eq!(key.open(&a).unwrap(), b"chunk data");
+        assert_eq!(key.chunk_id(b"chunk data").len(), 64);
+
+        // The same passphrase unlocks the remote, another one does not
+        let unlocked = params.unlock("correct horse").unwrap();
+        assert_eq!(unlocked.open(&a).unwrap(), b"chunk data");
+        assert!(matches!(params.unlock("battery staple"), Err(BackupError::Decryption(_))));
+    }
+}
@@ -0,0 +1,72 @@
+/*
+    errors.rs - Error types for remote backups and account exports
+*/
+
+use crate::core_store::store::preflight::InsufficientStorage;
+use thiserror::Error;
+
+/// Errors that can occur while pushing to or restoring from a backup remote,
+/// or while writing or importing an account export
+#[derive(Debug, Error)]
+pub enum BackupError {
+    /// Talking to the remote failed (connection reset, timeout, 5xx, ...)
+    ///
+    /// Transient: retrying the operation picks up where it stopped.
+    #[error("Remote unavailable: {0}")]
+    Remote(String),
+
+    /// The remote rejected the request (bad credentials, missing bucket, ...)
+    #[error("Remote rejected request: {0}")]
+    Rejected(String),
+
+    /// Requested snapshot does not exist on the remote
+    #[error("Snapshot not found: {0}")]
+    SnapshotNotFound(String),
+
+    /// An object referenced by a manifest is missing from the remote
+    #[error("Missing chunk on remote: {0}")]
+    MissingChunk(String),
+
+    /// Decryption failed (wrong passphrase or tampered object)
+    #[error("Decryption failed: {0}")]
+    Decryption(String),
+
+    /// Remote layout or settings are not usable
+    #[error("Invalid backup configuration: {0}")]
+    InvalidConfig(String),
+
+    /// Serialization error
+    #[error("Serialization error: {0}")]
+    Serialization(String),
+
+    /// Not enough free disk space for the restore
+    #[error("Insufficient storage: {required} bytes required, {available} available")]
+    InsufficientStorage { required: u64, available: u64 },
+
+    /// The destination already holds an identity
+    #[error("An identity already exists at {0}")]
+    IdentityExists(String),
+
+    /// Restoring would replace a file that already exists
+    #[error("Refusing to overwrite existing file: {0}")]
+    WouldOverwrite(String),
+
+    /// Local I/O error
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl From<bincode::Error> for BackupError {
+    fn from(err: bincode::Error) -> Self {
+        BackupError::Serialization(err.to_string())
+    }
+}
+
+impl From<InsufficientStorage> for BackupError {
+    fn from(err: InsufficientStorage) -> Self {
+        BackupError::InsufficientStorage { required: err.required, available: err.available }
+    }
+}
+
+/// Result type for backup operations
+pub type BackupResult<T> = Result<T, BackupError>;
@@ -0,0 +1,241 @@
+/*
+    mock_s3.rs - In-process S3-compatible server for tests
+
+    Implements the subset of the S3 API used by `S3Target` (path-style
+    PutObject, GetObject, DeleteObject and paginated ListObjectsV2), checks
+    SigV4 signatures like MinIO does, and can be told to start failing
+    uploads to simulate a connection dropping mid-push.
+*/
+
+use super::s3::{canonical_query, sign_v4, S3Target, SigningRequest};
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Router,
+};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+
+pub const BUCKET: &str = "backups";
+pub const ACCESS_KEY_ID: &str = "minioadmin";
+pub const SECRET_ACCESS_KEY: &str = "minioadmin-secret";
+pub const REGION: &str = "us-east-1";
+
+/// Keys per ListObjectsV2 page (small, so pagination is exercised)
+const MAX_KEYS: usize = 3;
+
+#[derive(Default)]
+struct MockState {
+    objects: Mutex<BTreeMap<String, Vec<u8>>>,
+    /// Remaining uploads before PUTs start failing (`None` = unlimited)
+    put_budget: Mutex<Option<usize>>,
+    puts: Mutex<usize>,
+}
+
+/// A running mock server; shut down on drop
+pub struct MockS3Server {
+    addr: SocketAddr,
+    state: Arc<MockState>,
+    task: JoinHandle<()>,
+}
+
+impl MockS3Server {
+    /// Start a server on an ephemeral localhost port
+    pub async fn start() -> Self {
+        let state = Arc::new(MockState::default());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new().fallback(handle).with_state(state.clone());
+        let task = tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        Self { addr, state, task }
+    }
+
+    /// Base URL of the server
+    pub fn endpoint(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// A target configured with the server's credentials
+    pub fn target(&self) -> S3Target {
+        S3Target::new(&self.endpoint(), REGION, BUCKET, ACCESS_KEY_ID, SECRET_ACCESS_KEY).unwrap()
+    }
+
+    /// All object keys currently stored in the bucket
+    pub fn object_keys(&self) -> Vec<String> {
+        self.state.objects.lock().unwrap().keys().cloned().collect()
+    }
+
+    /// Number of successful uploads so far
+    pub fn put_count(&self) -> usize {
+        *self.state.puts.lock().unwrap()
+    }
+
+    /// Accept `n` more uploads, then answer every PUT with 503
+    pub fn fail_puts_after(&self, n: usize) {
+        *self.state.put_budget.lock().unwrap() = Some(n);
+    }
+
+    /// Stop failing uploads
+    pub fn heal(&self) {
+        *self.state.put_budget.lock().unwrap() = None;
+    }
+}
+
+impl Drop for MockS3Server {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+fn s3_error(status: StatusCode, code: &str) -> Response {
+    let body = format!("<?xml version=\"1.0\"?><Error><Code>{}</Code></Error>", code);
+    (status, body).into_response()
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+            if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn query_pairs(uri: &Uri) -> Vec<(String, String)> {
+    uri.query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            let (k, v) = p.split_once('=').unwrap_or((p, ""));
+            (percent_decode(k), percent_decode(v))
+        })
+        .collect()
+}
+
+/// Verify the request's SigV4 signature
+fn authenticate(method: &Method, uri: &Uri, headers: &HeaderMap, body: &[u8]) -> Option<Response> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).unwrap_or_default();
+
+    let authorization = header("authorization");
+    if !authorization.contains(&format!("Credential={}/", ACCESS_KEY_ID)) {
+        return Some(s3_error(StatusCode::FORBIDDEN, "InvalidAccessKeyId"));
+    }
+
+    let payload_hash = hex::encode(Sha256::digest(body));
+    if header("x-amz-content-sha256") != payload_hash {
+        return Some(s3_error(StatusCode::BAD_REQUEST, "XAmzContentSHA256Mismatch"));
+    }
+
+    let pairs = query_pairs(uri);
+    let query: Vec<(&str, &str)> = pairs.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+    let expected = sign_v4(&SigningRequest {
+        method: method.as_str(),
+        path: uri.path(),
+        canonical_query: &canonical_query(&query),
+        headers: &[
+            ("host", header("host")),
+            ("x-amz-content-sha256", header("x-amz-content-sha256")),
+            ("x-amz-date", header("x-amz-date")),
+        ],
+        payload_hash: &payload_hash,
+        amz_date: header("x-amz-date"),
+        region: REGION,
+        access_key_id: ACCESS_KEY_ID,
+        secret_access_key: SECRET_ACCESS_KEY,
+    });
+    if expected != authorization {
+        return Some(s3_error(StatusCode::FORBIDDEN, "SignatureDoesNotMatch"));
+    }
+
+    None
+}
+
+async fn handle(
+    State(state): State<Arc<MockState>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if let Some(rejection) = authenticate(&method, &uri, &headers, &body) {
+        return rejection;
+    }
+
+    let path = percent_decode(uri.path().trim_start_matches('/'));
+    let (bucket, key) = path.split_once('/').unwrap_or((path.as_str(), ""));
+    if bucket != BUCKET {
+        return s3_error(StatusCode::NOT_FOUND, "NoSuchBucket");
+    }
+
+    match (method, key.is_empty()) {
+        (Method::GET, true) => list_objects(&state, &query_pairs(&uri)),
+        (Method::PUT, false) => {
+            let mut budget = state.put_budget.lock().unwrap();
+            match budget.as_mut() {
+                Some(0) => return s3_error(StatusCode::SERVICE_UNAVAILABLE, "SlowDown"),
+                Some(n) => *n -= 1,
+                None => {}
+            }
+            state.objects.lock().unwrap().insert(key.to_string(), body.to_vec());
+            *state.puts.lock().unwrap() += 1;
+            StatusCode::OK.into_response()
+        }
+        (Method::GET, false) => match state.objects.lock().unwrap().get(key) {
+            Some(data) => (StatusCode::OK, data.clone()).into_response(),
+            None => s3_error(StatusCode::NOT_FOUND, "NoSuchKey"),
+        },
+        (Method::DELETE, false) => {
+            state.objects.lock().unwrap().remove(key);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        _ => s3_error(StatusCode::METHOD_NOT_ALLOWED, "MethodNotAllowed"),
+    }
+}
+
+fn list_objects(state: &MockState, query: &[(String, String)]) -> Response {
+    let param = |name: &str| query.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str());
+    if param("list-type") != Some("2") {
+        return s3_error(StatusCode::NOT_IMPLEMENTED, "NotImplemented");
+    }
+    let prefix = param("prefix").unwrap_or_default();
+    let after = param("continuation-token").unwrap_or_default();
+
+    let objects = state.objects.lock().unwrap();
+    let mut matching = objects
+        .keys()
+        .filter(|k| k.starts_with(prefix) && k.as_str() > after)
+        .peekable();
+
+    let mut xml = String::from("<?xml version=\"1.0\"?><ListBucketResult>");
+    let mut last = None;
+    for key in matching.by_ref().take(MAX_KEYS) {
+        xml.push_str(&format!("<Contents><Key>{}</Key></Contents>", key.replace('&', "&amp;")));
+        last = Some(key.clone());
+    }
+    let truncated = matching.peek().is_some();
+    xml.push_str(&format!("<IsTruncated>{}</IsTruncated>", truncated));
+    if let (true, Some(last)) = (truncated, last) {
+        xml.push_str(&format!("<NextContinuationToken>{}</NextContinuationToken>", last));
+    }
+    xml.push_str("</ListBucketResult>");
+
+    (StatusCode::OK, xml).into_response()
+}
@@ -0,0 +1,23 @@
+/*
+    backup - End-to-end encrypted backups to a remote object store
+
+    Snapshots of the data directory are split into content-addressed chunks,
+    encrypted with a key derived from the backup passphrase and uploaded to a
+    `RemoteBackupTarget`. Pushes are incremental and resumable; old snapshots
+    are removed by a keep-last-N retention policy.
+*/
+
+pub mod archive;
+pub mod errors;
+pub mod remote;
+pub mod repository;
+pub mod s3;
+
+#[cfg(test)]
+mod mock_s3;
+
+pub use archive::{BackupKey, Manifest, ManifestFile, RemoteParams};
+pub use errors::{BackupError, BackupResult};
+pub use remote::RemoteBackupTarget;
+pub use repository::{PruneReport, PushReport, RemoteBackup, SnapshotInfo};
+pub use s3::S3Target;
@@ -0,0 +1,31 @@
+/*
+    remote.rs - Storage backends for remote backups
+
+    A backup remote is a flat object store: opaque keys mapping to opaque
+    bytes. Everything written through this trait is already encrypted, so
+    implementations never see plaintext and need no knowledge of the backup
+    format. S3-compatible storage is the first implementation; anything that
+    can put, get, list and delete objects (WebDAV, SFTP, ...) can follow.
+*/
+
+use super::errors::BackupResult;
+use async_trait::async_trait;
+
+/// An object store that backups can be pushed to
+#[async_trait]
+pub trait RemoteBackupTarget: Send + Sync {
+    /// Short human-readable description of the remote (for CLI output)
+    fn describe(&self) -> String;
+
+    /// Store an object, replacing any existing object with the same key
+    async fn put(&self, key: &str, data: &[u8]) -> BackupResult<()>;
+
+    /// Fetch an object, or `None` if it does not exist
+    async fn get(&self, key: &str) -> BackupResult<Option<Vec<u8>>>;
+
+    /// List all keys starting with `prefix`
+    async fn list(&self, prefix: &str) -> BackupResult<Vec<String>>;
+
+    /// Delete an object; deleting a missing key is not an error
+    async fn delete(&self, key: &str) -> BackupResult<()>;
+}
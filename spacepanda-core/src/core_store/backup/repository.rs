@@ -0,0 +1,557 @@
+/*
+    repository.rs - Push, restore and prune snapshots on a backup remote
+
+    Remote layout (all keys relative to the target):
+
+        params              RemoteParams (unencrypted: salt, format version)
+        chunks/<id>         sealed file chunk, addressed by keyed hash
+        snapshots/<id>      sealed Manifest
+
+    A push uploads only the chunks the remote does not already have and
+    writes the manifest last, so a snapshot is visible only once all of its
+    chunks are in place. If the connection drops mid-push, the chunks that
+    made it are kept and the next push skips them: re-running the push is
+    how an interrupted upload resumes.
+
+    A restore with a storage preflight attached first checks that the
+    destination's filesystem can hold the whole snapshot, so a full disk
+    doesn't leave a half-restored directory behind.
+
+    Pruning assumes a single writer per remote (one device pushing its own
+    data directory); a push running concurrently with a prune may lose
+    chunks that were uploaded but not yet referenced by a manifest.
+*/
+
+use super::archive::{BackupKey, Manifest, ManifestFile, RemoteParams, CHUNK_SIZE};
+use super::errors::{BackupError, BackupResult};
+use super::remote::RemoteBackupTarget;
+use crate::core_store::model::Timestamp;
+use crate::core_store::store::preflight::StoragePreflight;
+use std::collections::HashSet;
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+const PARAMS_KEY: &str = "params";
+const CHUNKS_PREFIX: &str = "chunks/";
+const SNAPSHOTS_PREFIX: &str = "snapshots/";
+
+/// Attempts per remote operation before giving up on a transient failure
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry (doubled on each further retry)
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(100);
+
+/// Directory entries never included in a backup
+///
+/// `attachments` is a cache that refills from peers; `*.tmp` files are
+/// half-written atomic replacements.
+const EXCLUDED_DIRS: &[&str] = &["attachments"];
+const EXCLUDED_EXTENSIONS: &[&str] = &["tmp"];
+
+/// Outcome of a push
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PushReport {
+    /// ID of the new snapshot
+    pub snapshot_id: String,
+
+    /// Files in the snapshot
+    pub files: usize,
+
+    /// Chunks uploaded by this push
+    pub chunks_uploaded: usize,
+
+    /// Chunks already on the remote
+    pub chunks_reused: usize,
+
+    /// Bytes sent for chunks (after encryption)
+    pub bytes_uploaded: u64,
+}
+
+/// Summary of a snapshot on the remote
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotInfo {
+    /// Snapshot ID
+    pub snapshot_id: String,
+
+    /// When the snapshot was taken
+    pub created_at: Timestamp,
+
+    /// Number of files
+    pub files: usize,
+
+    /// Total plaintext size
+    pub total_size: u64,
+}
+
+/// Outcome of applying a retention policy
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    /// Snapshots deleted
+    pub snapshots_removed: Vec<String>,
+
+    /// Chunks no longer referenced by any snapshot and deleted
+    pub chunks_removed: usize,
+}
+
+/// An encrypted backup repository on a remote target
+pub struct RemoteBackup {
+    target: Arc<dyn RemoteBackupTarget>,
+    key: BackupKey,
+    /// Parameters not yet written (fresh remote)
+    pending_params: Option<RemoteParams>,
+    /// Free space check before restores
+    preflight: Option<StoragePreflight>,
+}
+
+impl RemoteBackup {
+    /// Open the repository on `target`, unlocking it with `passphrase`
+    ///
+    /// A remote without a repository is initialised on the first push.
+    pub async fn open(target: Arc<dyn RemoteBackupTarget>, passphrase: &str) -> BackupResult<Self> {
+        match retry(|| target.get(PARAMS_KEY)).await? {
+            Some(bytes) => {
+                let params: RemoteParams = bincode::deserialize(&bytes)?;
+                let key = params.unlock(passphrase)?;
+                Ok(Self { target, key, pending_params: None, preflight: None })
+            }
+            None => {
+                let (params, key) = RemoteParams::generate(passphrase)?;
+                Ok(Self { target, key, pending_params: Some(params), preflight: None })
+            }
+        }
+    }
+
+    /// Check free disk space before restoring a snapshot
+    pub fn with_storage_preflight(mut self, preflight: StoragePreflight) -> Self {
+        self.preflight = Some(preflight);
+        self
+    }
+
+    /// The underlying target
+    pub fn target(&self) -> &Arc<dyn RemoteBackupTarget> {
+        &self.target
+    }
+
+    /// Upload a snapshot of every file under `source_dir`
+    pub async fn push(&mut self, source_dir: &Path) -> BackupResult<PushReport> {
+        if let Some(params) = &self.pending_params {
+            let bytes = bincode::serialize(params)?;
+            retry(|| self.target.put(PARAMS_KEY, &bytes)).await?;
+            self.pending_params = None;
+        }
+
+        let mut present: HashSet<String> = retry(|| self.target.list(CHUNKS_PREFIX))
+            .await?
+            .into_iter()
+            .filter_map(|k| k.strip_prefix(CHUNKS_PREFIX).map(str::to_string))
+            .collect();
+
+        let mut manifest = Manifest::new();
+        let mut report = PushReport {
+            snapshot_id: manifest.snapshot_id.clone(),
+            files: 0,
+            chunks_uploaded: 0,
+            chunks_reused: 0,
+            bytes_uploaded: 0,
+        };
+
+        for (relative, path) in collect_files(source_dir)? {
+            let mut file = fs::File::open(&path)?;
+            let mut entry = ManifestFile { path: relative, size: 0, chunks: Vec::new() };
+            let mut buf = vec![0u8; CHUNK_SIZE];
+
+            loop {
+                let n = read_full(&mut file, &mut buf)?;
+                if n == 0 {
+                    break;
+                }
+                let chunk = &buf[..n];
+                let id = self.key.chunk_id(chunk);
+
+                if present.contains(&id) {
+                    report.chunks_reused += 1;
+                } else {
+                    let sealed = self.key.seal_chunk(chunk)?;
+                    let key = format!("{}{}", CHUNKS_PREFIX, id);
+                    retry(|| self.target.put(&key, &sealed)).await?;
+                    report.chunks_uploaded += 1;
+                    report.bytes_uploaded += sealed.len() as u64;
+                    present.insert(id.clone());
+                }
+
+                entry.size += n as u64;
+                entry.chunks.push(id);
+                if n < CHUNK_SIZE {
+                    break;
+                }
+            }
+
+            manifest.files.push(entry);
+        }
+
+        // The manifest goes last: a snapshot only exists once all its chunks do
+        report.files = manifest.files.len();
+        let sealed = self.key.seal(&bincode::serialize(&manifest)?)?;
+        let key = format!("{}{}", SNAPSHOTS_PREFIX, manifest.snapshot_id);
+        retry(|| self.target.put(&key, &sealed)).await?;
+
+        info!(
+            snapshot = %report.snapshot_id,
+            files = report.files,
+            uploaded = report.chunks_uploaded,
+            reused = report.chunks_reused,
+            "Pushed backup snapshot"
+        );
+        Ok(report)
+    }
+
+    /// Snapshot IDs on the remote, oldest first
+    async fn snapshot_ids(&self) -> BackupResult<Vec<String>> {
+        let mut ids: Vec<String> = retry(|| self.target.list(SNAPSHOTS_PREFIX))
+            .await?
+            .into_iter()
+            .filter_map(|k| k.strip_prefix(SNAPSHOTS_PREFIX).map(str::to_string))
+            .collect();
+        ids.sort();
+        Ok(ids)
+    }
+
+    /// Fetch and decrypt a snapshot's manifest
+    pub async fn manifest(&self, snapshot_id: &str) -> BackupResult<Manifest> {
+        let key = format!("{}{}", SNAPSHOTS_PREFIX, snapshot_id);
+        let sealed = retry(|| self.target.get(&key))
+            .await?
+            .ok_or_else(|| BackupError::SnapshotNotFound(snapshot_id.to_string()))?;
+        Ok(bincode::deserialize(&self.key.open(&sealed)?)?)
+    }
+
+    /// All snapshots on the remote, oldest first
+    pub async fn list(&self) -> BackupResult<Vec<SnapshotInfo>> {
+        let mut snapshots = Vec::new();
+        for id in self.snapshot_ids().await? {
+            let manifest = self.manifest(&id).await?;
+            snapshots.push(SnapshotInfo {
+                snapshot_id: manifest.snapshot_id.clone(),
+                created_at: manifest.created_at,
+                files: manifest.files.len(),
+                total_size: manifest.total_size(),
+            });
+        }
+        Ok(snapshots)
+    }
+
+    /// Restore a snapshot (the latest if `snapshot_id` is `None`) into `dest_dir`
+    pub async fn pull(&self, snapshot_id: Option<&str>, dest_dir: &Path) -> BackupResult<Manifest> {
+        let snapshot_id = match snapshot_id {
+            Some(id) => id.to_string(),
+            None => self.snapshot_ids().await?.pop().ok_or_else(|| {
+                BackupError::SnapshotNotFound("no snapshots on remote".to_string())
+            })?,
+        };
+        let manifest = self.manifest(&snapshot_id).await?;
+        if let Some(preflight) = &self.preflight {
+            preflight.check(dest_dir, manifest.total_size())?;
+        }
+
+        for file in &manifest.files {
+            let path = dest_dir.join(safe_relative_path(&file.path)?);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            let mut contents = Vec::with_capacity(file.size as usize);
+            for id in &file.chunks {
+                let key = format!("{}{}", CHUNKS_PREFIX, id);
+                let sealed = retry(|| self.target.get(&key))
+                    .await?
+                    .ok_or_else(|| BackupError::MissingChunk(id.clone()))?;
+                let chunk = self.key.open(&sealed)?;
+                if &self.key.chunk_id(&chunk) != id {
+                    return Err(BackupError::Decryption(format!("Chunk {} content mismatch", id)));
+                }
+                contents.extend_from_slice(&chunk);
+            }
+
+            let tmp = path.with_extension("restore-tmp");
+            fs::write(&tmp, &contents)?;
+            fs::rename(&tmp, &path)?;
+            debug!(path = %file.path, size = file.size, "Restored file");
+        }
+
+        info!(snapshot = %snapshot_id, files = manifest.files.len(), "Restored backup snapshot");
+        Ok(manifest)
+    }
+
+    /// Keep the newest `keep_last` snapshots and delete everything else
+    pub async fn prune(&self, keep_last: usize) -> BackupResult<PruneReport> {
+        if keep_last == 0 {
+            return Err(BackupError::InvalidConfig("keep_last must be at least 1".to_string()));
+        }
+
+        let ids = self.snapshot_ids().await?;
+        let cutoff = ids.len().saturating_sub(keep_last);
+        let (expired, kept) = ids.split_at(cutoff);
+
+        let mut referenced = HashSet::new();
+        for id in kept {
+            referenced.extend(self.manifest(id).await?.chunk_ids().map(str::to_string));
+        }
+
+        let mut report = PruneReport::default();
+        for id in expired {
+            let key = format!("{}{}", SNAPSHOTS_PREFIX, id);
+            retry(|| self.target.delete(&key)).await?;
+            report.snapshots_removed.push(id.clone());
+        }
+
+        // Also collects chunks orphaned by interrupted pushes
+        for key in retry(|| self.target.list(CHUNKS_PREFIX)).await? {
+            let id = key.strip_prefix(CHUNKS_PREFIX).unwrap_or(&key);
+            if !referenced.contains(id) {
+                retry(|| self.target.delete(&key)).await?;
+                report.chunks_removed += 1;
+            }
+        }
+
+        info!(
+            snapshots = report.snapshots_removed.len(),
+            chunks = report.chunks_removed,
+            "Pruned backup remote"
+        );
+        Ok(report)
+    }
+}
+
+/// Run a remote operation, retrying transient failures with backoff
+async fn retry<T, F, Fut>(mut op: F) -> BackupResult<T>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = BackupResult<T>>,
+{
+    let mut delay = RETRY_BASE_DELAY;
+    let mut attempt = 1;
+    loop {
+        match op().await {
+            Err(BackupError::Remote(e)) if attempt < MAX_ATTEMPTS => {
+                warn!(attempt, error = %e, "Remote operation failed, retrying");
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Fill `buf` as far as possible, returning the number of bytes read
+fn read_full(file: &mut fs::File, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+/// All files under `root` as `(relative path, absolute path)`, sorted
+fn collect_files(root: &Path) -> BackupResult<Vec<(String, PathBuf)>> {
+    fn walk(root: &Path, dir: &Path, out: &mut Vec<(String, PathBuf)>) -> std::io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+
+            if file_type.is_dir() {
+                if dir == root && EXCLUDED_DIRS.contains(&name.as_str()) {
+                    continue;
+                }
+                walk(root, &path, out)?;
+            } else if file_type.is_file() {
+                let excluded = path
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .is_some_and(|e| EXCLUDED_EXTENSIONS.contains(&e));
+                if excluded {
+                    continue;
+                }
+                let relative = path
+                    .strip_prefix(root)
+                    .unwrap_or(&path)
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                out.push((relative, path));
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    walk(root, root, &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+/// Reject manifest paths that would escape the restore directory
+fn safe_relative_path(path: &str) -> BackupResult<PathBuf> {
+    let relative = PathBuf::from(path);
+    let safe = !path.is_empty() && relative.components().all(|c| matches!(c, Component::Normal(_)));
+    if !safe {
+        return Err(BackupError::Decryption(format!("Unsafe path in manifest: {}", path)));
+    }
+    Ok(relative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core_store::backup::mock_s3::MockS3Server;
+    use crate::core_store::store::preflight::FsStats;
+    use tempfile::TempDir;
+
+    fn write(dir: &Path, path: &str, contents: &[u8]) {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    fn data_dir() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        write(dir.path(), "keystore/identity.key", b"identity");
+        write(dir.path(), "snapshots/latest.bin", &vec![7u8; CHUNK_SIZE + 10]);
+        write(dir.path(), "commit.log", b"entry 1\n");
+        write(dir.path(), "attachments/blobs/abc", b"cached blob");
+        write(dir.path(), "inbox.tmp", b"half written");
+        dir
+    }
+
+    async fn open(server: &MockS3Server, passphrase: &str) -> BackupResult<RemoteBackup> {
+        RemoteBackup::open(Arc::new(server.target()), passphrase).await
+    }
+
+    #[tokio::test]
+    async fn test_push_is_incremental_and_restores() {
+        let server = MockS3Server::start().await;
+        let source = data_dir();
+        let mut backup = open(&server, "passphrase").await.unwrap();
+
+        let first = backup.push(source.path()).await.unwrap();
+        assert_eq!(first.files, 3, "cache and temp files are skipped");
+        assert_eq!(first.chunks_uploaded, 4);
+
+        // Nothing changed: only the manifest is uploaded
+        let puts = server.put_count();
+        let second = backup.push(source.path()).await.unwrap();
+        assert_eq!((second.chunks_uploaded, second.chunks_reused), (0, 4));
+        assert_eq!(server.put_count(), puts + 1);
+
+        // Appending to one file uploads just that file's new chunk
+        write(source.path(), "commit.log", b"entry 1\nentry 2\n");
+        let third = backup.push(source.path()).await.unwrap();
+        assert_eq!((third.chunks_uploaded, third.chunks_reused), (1, 3));
+
+        // Restore the latest snapshot on a "new device"
+        let restored = TempDir::new().unwrap();
+        let backup = open(&server, "passphrase").await.unwrap();
+        assert_eq!(backup.list().await.unwrap().len(), 3);
+        backup.pull(None, restored.path()).await.unwrap();
+
+        for path in ["keystore/identity.key", "snapshots/latest.bin", "commit.log"] {
+            assert_eq!(
+                fs::read(restored.path().join(path)).unwrap(),
+                fs::read(source.path().join(path)).unwrap()
+            );
+        }
+        assert!(!restored.path().join("attachments").exists());
+
+        // Nothing readable is stored on the remote
+        assert!(matches!(open(&server, "guess").await, Err(BackupError::Decryption(_))));
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_push_resumes() {
+        let server = MockS3Server::start().await;
+        let source = data_dir();
+        let mut backup = open(&server, "passphrase").await.unwrap();
+
+        // The connection drops after the params and two chunks
+        server.fail_puts_after(3);
+        let err = backup.push(source.path()).await.unwrap_err();
+        assert!(matches!(err, BackupError::Remote(_)));
+        assert!(backup.list().await.unwrap().is_empty(), "no partial snapshot is visible");
+
+        server.heal();
+        let resumed = backup.push(source.path()).await.unwrap();
+        assert_eq!((resumed.chunks_uploaded, resumed.chunks_reused), (2, 2));
+
+        let restored = TempDir::new().unwrap();
+        backup.pull(Some(&resumed.snapshot_id), restored.path()).await.unwrap();
+        assert_eq!(fs::read(restored.path().join("commit.log")).unwrap(), b"entry 1\n");
+    }
+
+    #[tokio::test]
+    async fn test_restore_refused_without_free_space() {
+        struct FreeSpace(u64);
+        impl FsStats for FreeSpace {
+            fn available_space(&self, _path: &Path) -> std::io::Result<u64> {
+                Ok(self.0)
+            }
+        }
+
+        let server = MockS3Server::start().await;
+        let source = data_dir();
+        let mut backup = open(&server, "passphrase").await.unwrap();
+        backup.push(source.path()).await.unwrap();
+        let size = backup.list().await.unwrap()[0].total_size;
+
+        let restored = TempDir::new().unwrap();
+        let full = StoragePreflight::new(10).with_fs_stats(Arc::new(FreeSpace(size + 9)));
+        let backup = backup.with_storage_preflight(full);
+        let err = backup.pull(None, restored.path()).await.unwrap_err();
+        assert!(matches!(
+            err,
+            BackupError::InsufficientStorage { required, available }
+                if required == size + 10 && available == size + 9
+        ));
+        assert_eq!(fs::read_dir(restored.path()).unwrap().count(), 0, "nothing was written");
+
+        let roomy = StoragePreflight::new(10).with_fs_stats(Arc::new(FreeSpace(size + 10)));
+        let backup = backup.with_storage_preflight(roomy);
+        backup.pull(None, restored.path()).await.unwrap();
+        assert_eq!(fs::read(restored.path().join("commit.log")).unwrap(), b"entry 1\n");
+    }
+
+    #[tokio::test]
+    async fn test_prune_keeps_latest_snapshots() {
+        let server = MockS3Server::start().await;
+        let source = data_dir();
+        let mut backup = open(&server, "passphrase").await.unwrap();
+
+        let mut pushed = Vec::new();
+        for i in 0..3 {
+            write(source.path(), "commit.log", format!("generation {}", i).as_bytes());
+            pushed.push(backup.push(source.path()).await.unwrap().snapshot_id);
+        }
+
+        let report = backup.prune(2).await.unwrap();
+        assert_eq!(report.snapshots_removed, vec![pushed[0].clone()]);
+        assert_eq!(report.chunks_removed, 1, "only the first commit.log chunk is unreferenced");
+
+        let remaining: Vec<_> =
+            backup.list().await.unwrap().into_iter().map(|s| s.snapshot_id).collect();
+        assert_eq!(remaining, pushed[1..]);
+
+        let restored = TempDir::new().unwrap();
+        backup.pull(Some(&pushed[1]), restored.path()).await.unwrap();
+        assert_eq!(fs::read(restored.path().join("commit.log")).unwrap(), b"generation 1");
+        assert!(backup.prune(0).await.is_err());
+    }
+}
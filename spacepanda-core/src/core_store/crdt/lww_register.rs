@@ -85,6 +85,26 @@ impl<T: Clone> LWWRegister<T> {
         }
     }
 
+    /// Set a new value, returning the delta: a register holding just this write
+    ///
+    /// Merging the delta into a replica has the same effect as the write.
+    pub fn set_delta(
+        &mut self,
+        value: T,
+        timestamp: u64,
+        node_id: String,
+        vector_clock: VectorClock,
+    ) -> Self {
+        let delta = LWWRegister {
+            value: Some(value.clone()),
+            timestamp,
+            node_id: node_id.clone(),
+            vector_clock: vector_clock.clone(),
+        };
+        self.set(value, timestamp, node_id, vector_clock);
+        delta
+    }
+
     /// Check if we should update based on timestamp and node ID
     fn should_update(&self, new_timestamp: u64, new_node_id: &str) -> bool {
         if new_timestamp > self.timestamp {
@@ -113,6 +133,22 @@ impl<T: Clone> LWWRegister<T> {
         &self.node_id
     }
 
+    /// The same register with its value converted, keeping the write's
+    /// timestamp, writer and clock
+    pub fn map<U: Clone>(&self, f: impl FnOnce(&T) -> U) -> LWWRegister<U> {
+        LWWRegister {
+            value: self.value.as_ref().map(f),
+            timestamp: self.timestamp,
+            node_id: self.node_id.clone(),
+            vector_clock: self.vector_clock.clone(),
+        }
+    }
+
+    /// Merge a vector clock learned elsewhere, leaving the value as it is
+    pub fn merge_clock(&mut self, vector_clock: &VectorClock) {
+        self.vector_clock.merge(vector_clock);
+    }
+
     /// Merge another LWW register into this one
     pub fn merge(&mut self, other: &LWWRegister<T>) {
         if let Some(ref other_value) = other.value {
@@ -269,6 +305,23 @@ mod tests {
         assert_eq!(reg.vector_clock().get("node1"), 5);
         assert_eq!(reg.vector_clock().get("node2"), 3);
     }
+
+    #[test]
+    fn test_lww_set_delta() {
+        let mut reg: LWWRegister<i32> = LWWRegister::new();
+        let mut replica = reg.clone();
+
+        let mut vc = VectorClock::new();
+        vc.set("node1", 1);
+        let delta = reg.set_delta(42, 100, "node1".to_string(), vc);
+        replica.merge(&delta);
+        // A stale write produces a delta that changes nothing either
+        let stale = reg.set_delta(7, 50, "node2".to_string(), VectorClock::new());
+        replica.merge(&stale);
+
+        assert_eq!(replica, reg);
+        assert_eq!(reg.get(), Some(&42));
+    }
 }
 
 #[cfg(test)]
@@ -12,11 +12,11 @@
 */
 
 use super::or_set::{AddId, ORSet};
-use super::traits::{Crdt, OperationMetadata};
+use super::traits::{Crdt, OperationMetadata, TombstoneCrdt};
 use super::vector_clock::VectorClock;
 use crate::core_store::store::errors::StoreResult;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 /// Observed-Remove Map CRDT
 /// Each key maps to a value tracked by OR-Set semantics
@@ -69,6 +69,22 @@ impl<K: Clone + Eq + std::hash::Hash, V: Clone> ORMap<K, V> {
         self.vector_clock.merge(&vector_clock);
     }
 
+    /// Put a key-value pair, returning the delta: a map holding just this put
+    ///
+    /// Merging the delta into a replica has the same effect as the put.
+    pub fn put_delta(
+        &mut self,
+        key: K,
+        value: V,
+        add_id: AddId,
+        vector_clock: VectorClock,
+    ) -> Self {
+        let mut delta = ORMap::new();
+        delta.put(key.clone(), value.clone(), add_id.clone(), vector_clock.clone());
+        self.put(key, value, add_id, vector_clock);
+        delta
+    }
+
     /// Get a value by key
     pub fn get(&self, key: &K) -> Option<&V> {
         self.map.get(key).map(|(v, _)| v)
@@ -80,6 +96,21 @@ impl<K: Clone + Eq + std::hash::Hash, V: Clone> ORMap<K, V> {
         self.vector_clock.merge(&vector_clock);
     }
 
+    /// Remove a key, returning the delta
+    ///
+    /// Merges don't carry removals, so the delta only holds the clock.
+    pub fn remove_delta(&mut self, key: &K, vector_clock: VectorClock) -> Self {
+        let mut delta = ORMap::new();
+        delta.merge_clock(&vector_clock);
+        self.remove(key, vector_clock);
+        delta
+    }
+
+    /// Merge a vector clock learned elsewhere, leaving the entries as they are
+    pub fn merge_clock(&mut self, vector_clock: &VectorClock) {
+        self.vector_clock.merge(vector_clock);
+    }
+
     /// Check if a key exists
     pub fn contains_key(&self, key: &K) -> bool {
         self.map.contains_key(key)
@@ -95,6 +126,11 @@ impl<K: Clone + Eq + std::hash::Hash, V: Clone> ORMap<K, V> {
         self.map.iter().map(|(k, (v, _))| (k.clone(), v.clone())).collect()
     }
 
+    /// Get the add IDs of a key
+    pub fn get_add_ids(&self, key: &K) -> Option<&HashSet<AddId>> {
+        self.map.get(key).and_then(|(_, set)| set.get_add_ids(key))
+    }
+
     /// Get the number of entries
     pub fn len(&self) -> usize {
         self.map.len()
@@ -149,6 +185,22 @@ impl<K: Clone + Eq + std::hash::Hash + Send + Sync, V: Clone + Send + Sync> Crdt
     }
 }
 
+impl<K, V> TombstoneCrdt for ORMap<K, V>
+where
+    K: Clone + Eq + std::hash::Hash + Send + Sync,
+    V: Clone + Send + Sync,
+{
+    fn is_tombstoned(&self, _key: &str) -> bool {
+        // Removed keys leave no tombstone in the map itself
+        false
+    }
+
+    /// Garbage collect the tombstones of each key's adds
+    fn gc_tombstones(&mut self, stable: &VectorClock) -> usize {
+        self.map.values_mut().map(|(_, key_set)| key_set.gc_tombstones(stable)).sum()
+    }
+}
+
 impl<K: Clone + Eq + std::hash::Hash, V: Clone> Default for ORMap<K, V> {
     fn default() -> Self {
         Self::new()
@@ -318,4 +370,21 @@ mod tests {
         assert_eq!(value.get(&"a".to_string()), Some(&1));
         assert_eq!(value.get(&"b".to_string()), Some(&2));
     }
+
+    #[test]
+    fn test_or_map_put_delta() {
+        let mut map: ORMap<String, i32> = ORMap::new();
+        let mut vc = VectorClock::new();
+        vc.increment("node1");
+        map.put("a".to_string(), 1, AddId::new("node1".to_string(), 1), vc.clone());
+        let mut replica = map.clone();
+
+        vc.increment("node1");
+        let delta = map.put_delta("b".to_string(), 2, AddId::new("node1".to_string(), 2), vc);
+        assert_eq!(delta.len(), 1);
+
+        replica.merge(&delta).unwrap();
+        assert_eq!(replica.value(), map.value());
+        assert_eq!(replica.vector_clock(), map.vector_clock());
+    }
 }
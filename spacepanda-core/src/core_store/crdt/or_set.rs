@@ -40,9 +40,10 @@ pub struct ORSet<T: Clone + Eq + std::hash::Hash> {
     /// An element exists if it has at least one add ID
     elements: HashMap<T, HashSet<AddId>>,
 
-    /// Tombstones: removed (element, add_id) pairs
-    /// Kept for synchronization to know what's been removed
-    tombstones: HashSet<(T, AddId)>,
+    /// Tombstones: removed (element, add_id) pairs and the clock of the
+    /// removal. Kept for synchronization to know what's been removed, until
+    /// every replica has seen the removal (see `gc_tombstones`)
+    tombstones: HashMap<(T, AddId), VectorClock>,
 
     /// Vector clock for causal ordering
     vector_clock: VectorClock,
@@ -71,7 +72,7 @@ impl<T: Clone + Eq + std::hash::Hash> ORSet<T> {
     pub fn new() -> Self {
         ORSet {
             elements: HashMap::new(),
-            tombstones: HashSet::new(),
+            tombstones: HashMap::new(),
             vector_clock: VectorClock::new(),
         }
     }
@@ -92,13 +93,59 @@ impl<T: Clone + Eq + std::hash::Hash> ORSet<T> {
 
         // Add to tombstones
         for add_id in &add_ids {
-            self.tombstones.insert((element.clone(), add_id.clone()));
+            self.tombstone(element.clone(), add_id.clone(), &vector_clock);
         }
 
         self.vector_clock.merge(&vector_clock);
         add_ids
     }
 
+    /// Add an element, returning the delta: a set holding just this add
+    ///
+    /// Merging the delta into a replica has the same effect as the add.
+    pub fn add_delta(&mut self, element: T, add_id: AddId, vector_clock: VectorClock) -> Self {
+        let mut delta = ORSet::new();
+        delta.add(element.clone(), add_id.clone(), vector_clock.clone());
+        self.add(element, add_id, vector_clock);
+        delta
+    }
+
+    /// Remove an element, returning the delta: a set holding just the
+    /// tombstones of its adds
+    pub fn remove_delta(&mut self, element: &T, vector_clock: VectorClock) -> Self {
+        let add_ids = self.remove(element, vector_clock.clone());
+        let mut delta = ORSet::new();
+        delta.remove_adds(element, &add_ids, vector_clock);
+        delta
+    }
+
+    /// Remove the given adds of an element, including adds not seen yet
+    ///
+    /// Unlike applying a `Remove` operation, adds that arrive later stay
+    /// removed, as they do when tombstones come in through `merge`.
+    pub fn remove_adds(
+        &mut self,
+        element: &T,
+        add_ids: &HashSet<AddId>,
+        vector_clock: VectorClock,
+    ) {
+        for add_id in add_ids {
+            self.tombstone(element.clone(), add_id.clone(), &vector_clock);
+        }
+        if let Some(adds) = self.elements.get_mut(element) {
+            adds.retain(|add_id| !add_ids.contains(add_id));
+            if adds.is_empty() {
+                self.elements.remove(element);
+            }
+        }
+        self.vector_clock.merge(&vector_clock);
+    }
+
+    /// Merge a vector clock learned elsewhere, leaving the elements as they are
+    pub fn merge_clock(&mut self, vector_clock: &VectorClock) {
+        self.vector_clock.merge(vector_clock);
+    }
+
     /// Check if an element is in the set
     pub fn contains(&self, element: &T) -> bool {
         self.elements.contains_key(element)
@@ -123,6 +170,28 @@ impl<T: Clone + Eq + std::hash::Hash> ORSet<T> {
     pub fn get_add_ids(&self, element: &T) -> Option<&HashSet<AddId>> {
         self.elements.get(element)
     }
+
+    /// Iterate over live (element, add ID) pairs
+    pub fn adds(&self) -> impl Iterator<Item = (&T, &AddId)> {
+        self.elements
+            .iter()
+            .flat_map(|(element, ids)| ids.iter().map(move |id| (element, id)))
+    }
+
+    /// Iterate over removed (element, add ID) pairs
+    pub fn tombstones(&self) -> impl Iterator<Item = &(T, AddId)> {
+        self.tombstones.keys()
+    }
+
+    /// Clock of the removal of an add, while its tombstone is kept
+    pub fn removed_at(&self, element: &T, add_id: &AddId) -> Option<&VectorClock> {
+        self.tombstones.get(&(element.clone(), add_id.clone()))
+    }
+
+    /// Record a tombstone, keeping the first clock it was recorded with
+    fn tombstone(&mut self, element: T, add_id: AddId, vector_clock: &VectorClock) {
+        self.tombstones.entry((element, add_id)).or_insert_with(|| vector_clock.clone());
+    }
 }
 
 impl<T: Clone + Eq + std::hash::Hash + Send + Sync> Crdt for ORSet<T> {
@@ -133,7 +202,7 @@ impl<T: Clone + Eq + std::hash::Hash + Send + Sync> Crdt for ORSet<T> {
         match op {
             ORSetOperation::Add { element, add_id, metadata } => {
                 // Don't add if this (element, add_id) is tombstoned
-                if !self.tombstones.contains(&(element.clone(), add_id.clone())) {
+                if !self.tombstones.contains_key(&(element.clone(), add_id.clone())) {
                     self.add(element, add_id, metadata.vector_clock);
                 }
             }
@@ -142,7 +211,9 @@ impl<T: Clone + Eq + std::hash::Hash + Send + Sync> Crdt for ORSet<T> {
                 if let Some(current_adds) = self.elements.get_mut(&element) {
                     for add_id in &add_ids {
                         current_adds.remove(add_id);
-                        self.tombstones.insert((element.clone(), add_id.clone()));
+                        self.tombstones
+                            .entry((element.clone(), add_id.clone()))
+                            .or_insert_with(|| metadata.vector_clock.clone());
                     }
 
                     // If no add_ids left, remove the element
@@ -166,15 +237,15 @@ impl<T: Clone + Eq + std::hash::Hash + Send + Sync> Crdt for ORSet<T> {
 
             for add_id in other_adds {
                 // Only add if not tombstoned
-                if !self.tombstones.contains(&(element.clone(), add_id.clone())) {
+                if !self.tombstones.contains_key(&(element.clone(), add_id.clone())) {
                     entry.insert(add_id.clone());
                 }
             }
         }
 
         // Merge tombstones and apply them
-        for tombstone in &other.tombstones {
-            self.tombstones.insert(tombstone.clone());
+        for (tombstone, removed_at) in &other.tombstones {
+            self.tombstones.entry(tombstone.clone()).or_insert_with(|| removed_at.clone());
 
             // Remove from elements if tombstoned
             if let Some(adds) = self.elements.get_mut(&tombstone.0) {
@@ -207,11 +278,10 @@ impl<T: Clone + Eq + std::hash::Hash + Send + Sync> TombstoneCrdt for ORSet<T> {
         false
     }
 
-    fn gc_tombstones(&mut self, _threshold_ms: u64) -> usize {
-        // Clear all tombstones (in production, would filter by age)
+    fn gc_tombstones(&mut self, stable: &VectorClock) -> usize {
         let count = self.tombstones.len();
-        self.tombstones.clear();
-        count
+        self.tombstones.retain(|_, removed_at| !removed_at.is_covered_by(stable));
+        count - self.tombstones.len()
     }
 }
 
@@ -382,6 +452,24 @@ mod tests {
         assert!(!set.contains(&"test".to_string()));
     }
 
+    #[test]
+    fn test_or_set_remove_adds_before_add() {
+        let mut set: ORSet<String> = ORSet::new();
+        let seen = AddId::new("node1".to_string(), 1);
+        let unseen = AddId::new("node2".to_string(), 2);
+        set.add("test".to_string(), seen.clone(), VectorClock::new());
+
+        // The remove also covers an add that hasn't arrived yet
+        let add_ids = HashSet::from([seen, unseen.clone()]);
+        set.remove_adds(&"test".to_string(), &add_ids, VectorClock::new());
+        assert!(!set.contains(&"test".to_string()));
+
+        let metadata = OperationMetadata::new("node2".to_string(), VectorClock::new());
+        let op = ORSetOperation::Add { element: "test".to_string(), add_id: unseen, metadata };
+        set.apply(op).unwrap();
+        assert!(!set.contains(&"test".to_string()));
+    }
+
     #[test]
     fn test_or_set_gc_tombstones() {
         let mut set: ORSet<String> = ORSet::new();
@@ -393,7 +481,7 @@ mod tests {
 
         assert_eq!(set.tombstones.len(), 1);
 
-        let removed = set.gc_tombstones(0);
+        let removed = set.gc_tombstones(&VectorClock::new());
         assert_eq!(removed, 1);
         assert_eq!(set.tombstones.len(), 0);
     }
@@ -411,6 +499,53 @@ mod tests {
         assert!(value.contains(&1));
         assert!(value.contains(&2));
     }
+
+    #[test]
+    fn test_or_set_deltas_replay_mutations() {
+        let mut set: ORSet<i32> = ORSet::new();
+        let mut replica = set.clone();
+        let mut vc = VectorClock::new();
+
+        let mut deltas = Vec::new();
+        for n in 1..=2 {
+            vc.increment("node1");
+            deltas.push(set.add_delta(n, AddId::new("node1".to_string(), n as u64), vc.clone()));
+        }
+        vc.increment("node1");
+        let remove = set.remove_delta(&2, vc);
+        assert!(remove.is_empty());
+        deltas.push(remove);
+
+        // Deltas merge in any order: the tombstone wins over its late add
+        for delta in deltas.iter().rev() {
+            assert!(delta.len() + delta.tombstones().count() == 1);
+            replica.merge(delta).unwrap();
+        }
+        assert_eq!(replica.elements(), vec![1]);
+        assert_eq!(replica.vector_clock(), set.vector_clock());
+    }
+
+    #[test]
+    fn test_or_set_gc_drops_only_stable_tombstones() {
+        let mut set: ORSet<i32> = ORSet::new();
+        let mut vc = VectorClock::new();
+        for n in 1..=2 {
+            vc.increment("node1");
+            set.add(n, AddId::new("node1".to_string(), n as u64), vc.clone());
+        }
+        vc.increment("node1");
+        set.remove(&1, vc.clone());
+        let stable = vc.clone();
+        vc.increment("node1");
+        set.remove(&2, vc.clone());
+        assert_eq!(set.removed_at(&1, &AddId::new("node1".to_string(), 1)), Some(&stable));
+
+        // Only the removal every replica has seen goes
+        assert_eq!(set.gc_tombstones(&stable), 1);
+        assert_eq!(set.tombstones().count(), 1);
+        assert_eq!(set.gc_tombstones(&vc), 1);
+        assert!(set.is_empty());
+    }
 }
 
 #[cfg(test)]
@@ -45,8 +45,9 @@ pub trait TombstoneCrdt: Crdt {
     /// Check if an element is tombstoned (deleted but retained for sync)
     fn is_tombstoned(&self, key: &str) -> bool;
 
-    /// Garbage collect tombstones older than the given threshold
-    fn gc_tombstones(&mut self, threshold_ms: u64) -> usize;
+    /// Garbage collect tombstones of removals covered by `stable`, a clock
+    /// every replica has reached; returns how many were dropped
+    fn gc_tombstones(&mut self, stable: &VectorClock) -> usize;
 }
 
 /// Metadata attached to every CRDT operation
@@ -54,6 +54,21 @@ impl VectorClock {
         }
     }
 
+    /// Keep the minimum of each entry; a node missing from either clock
+    /// counts as 0 and is left out
+    pub fn meet(&mut self, other: &VectorClock) {
+        self.clock.retain(|node_id, timestamp| {
+            *timestamp = (*timestamp).min(other.get(node_id));
+            *timestamp > 0
+        });
+    }
+
+    /// Check if every entry is at most the other clock's (equal clocks
+    /// included)
+    pub fn is_covered_by(&self, other: &VectorClock) -> bool {
+        self.clock.iter().all(|(node_id, &timestamp)| timestamp <= other.get(node_id))
+    }
+
     /// Check if this clock happened before another
     /// Returns true if all entries in self <= other and at least one is strictly less
     pub fn happened_before(&self, other: &VectorClock) -> bool {
@@ -255,6 +270,26 @@ mod tests {
         assert!(vc1.happened_before(&vc2));
         assert!(!vc2.happened_before(&vc1));
     }
+
+    #[test]
+    fn test_meet_and_covered_by() {
+        let mut vc1 = VectorClock::new();
+        vc1.set("node1", 3);
+        vc1.set("node2", 1);
+
+        let mut vc2 = VectorClock::new();
+        vc2.set("node1", 2);
+        vc2.set("node3", 5);
+
+        let mut floor = vc1.clone();
+        floor.meet(&vc2);
+        assert_eq!(floor.get("node1"), 2);
+        assert_eq!(floor.len(), 1);
+        assert!(floor.is_covered_by(&vc1));
+        assert!(floor.is_covered_by(&vc2));
+        assert!(!vc1.is_covered_by(&vc2));
+        assert!(vc1.is_covered_by(&vc1));
+    }
 }
 
 #[cfg(test)]
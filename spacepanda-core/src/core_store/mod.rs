@@ -8,8 +8,10 @@
     - Local persistence
     - DHT synchronization
     - Search and query
+    - Encrypted remote backups
 */
 
+pub mod backup;
 pub mod crdt;
 pub mod model;
 pub mod query;
@@ -5,8 +5,23 @@
     Uses CRDTs for all mutable fields to enable conflict-free replication.
 
     CRDT Design:
-    - name, topic: LWWRegister for single-value fields
+    - name, topic, avatar_hash, public, psk_id, padding, retention:
+      LWWRegister for single-value fields
     - members, pinned_messages: OR-Set for membership
+    - member_devices: OR-Set of (user, device) pairs mirroring the MLS roster
+    - replica_nodes: OR-Map from a member device to the node it syncs the
+      store from, with LWW values each device writes for itself
+    - invites: OR-Map of issued invites with LWW records; revocations are
+      an OR-Set that is only ever added to, so a revoke can't be undone by
+      a concurrent use
+    - webhooks: OR-Map of inbound webhooks with LWW records (token rotation
+      is an LWW update); revocations are an add-only OR-Set like invites
+    - linked_devices: OR-Map from a linked device's leaf to the device key
+      that leaf was added for; revocations are an add-only OR-Set of device
+      IDs like invites
+    - read_markers: OR-Map from user to an LWW read marker whose timestamp
+      is the position read up to, so the furthest marker of any device wins;
+      read_receipts: LWWRegister flag for sharing markers with other members
     - permissions: OR-Map with LWW values for deterministic permission changes
     - mls_identity: OR-Map tracking MLS leaf indices and credentials
     - messages: GList for causally-ordered message timeline (TODO: implement GList)
@@ -15,9 +30,143 @@
 use super::types::{
     ChannelId, ChannelType, IdentityMeta, MessageId, PermissionLevel, Timestamp, UserId,
 };
-use crate::core_store::crdt::{LWWRegister, ORMap, ORSet};
+use crate::core_mls::channel_metadata::RetentionPolicy;
+use crate::core_mls::padding::{PaddingStrategy, StoredPadding};
+use crate::core_store::crdt::{AddId, Crdt, LWWRegister, ORMap, ORSet};
 use serde::{Deserialize, Serialize};
 
+/// Serde adapter storing the channel's padding register as `StoredPadding`
+mod padding_register {
+    use super::{PaddingStrategy, StoredPadding};
+    use crate::core_store::crdt::LWWRegister;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        register: &LWWRegister<PaddingStrategy>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        register.map(|padding| StoredPadding::from(padding)).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<LWWRegister<PaddingStrategy>, D::Error> {
+        Ok(LWWRegister::<StoredPadding>::deserialize(deserializer)?.map(|padding| padding.into()))
+    }
+}
+
+/// One device of a channel member (one MLS leaf)
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MemberDevice {
+    /// Member's user ID
+    pub user_id: UserId,
+    /// Device identifier (derived from the leaf's signature key)
+    pub device_id: String,
+}
+
+/// An invite issued for a channel, as tracked by the members who issue them
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InviteRecord {
+    /// Invite identifier (matches the token's `invite_id`)
+    pub invite_id: String,
+
+    /// MLS identity of the member the invite's Welcome was built for
+    pub invitee: Vec<u8>,
+
+    /// Who issued the invite
+    pub issued_by: UserId,
+
+    /// When the invite was issued
+    pub created_at: Timestamp,
+
+    /// When the invite stops being valid
+    pub expires_at: Option<Timestamp>,
+
+    /// Joins allowed (`None` = unlimited)
+    pub max_uses: Option<u32>,
+
+    /// Joins seen so far
+    pub uses: u32,
+}
+
+impl InviteRecord {
+    /// Whether the invite is past its expiry time at `now`
+    pub fn is_expired_at(&self, now: Timestamp) -> bool {
+        self.expires_at.is_some_and(|expires_at| now > expires_at)
+    }
+
+    /// Whether every allowed join has been used
+    pub fn is_used_up(&self) -> bool {
+        self.max_uses.is_some_and(|max_uses| self.uses >= max_uses)
+    }
+}
+
+/// A leaf added for one of a member's linked devices
+///
+/// Ties the MLS leaf to the device the identity key authorized, so every
+/// leaf of the device can be found again when it is revoked.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkedDeviceRecord {
+    /// Leaf the device occupies (its `MemberDevice::device_id`)
+    pub leaf: String,
+
+    /// User the device belongs to
+    pub user_id: UserId,
+
+    /// Hex ID of the device in its key binding
+    pub device_id: String,
+
+    /// Device public key authorized by the identity key
+    pub device_public_key: Vec<u8>,
+
+    /// When the device was added to the channel
+    pub linked_at: Timestamp,
+}
+
+/// An inbound webhook that posts into a channel
+///
+/// Only a hash of the secret token is replicated, so any admin can see and
+/// manage webhooks without learning the token.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebhookRecord {
+    /// Webhook identifier (the `<id>` in `POST /webhooks/<id>`)
+    pub webhook_id: String,
+
+    /// Human-readable name (e.g. "CI")
+    pub name: String,
+
+    /// Pseudo-member the webhook posts as (its MLS identity)
+    pub user_id: UserId,
+
+    /// Hex-encoded BLAKE3 hash of the current secret token
+    pub token_hash: String,
+
+    /// Who created the webhook
+    pub created_by: UserId,
+
+    /// When the webhook was created
+    pub created_at: Timestamp,
+
+    /// When the token was last rotated
+    pub rotated_at: Option<Timestamp>,
+
+    /// Largest message body accepted, in bytes
+    pub max_content_bytes: usize,
+
+    /// Posts accepted per minute
+    pub max_posts_per_minute: u32,
+}
+
+/// How far a user has read in a channel
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadMarker {
+    /// Last message read
+    pub message_id: MessageId,
+
+    /// Timestamp of that message; everything up to it counts as read
+    pub position: Timestamp,
+}
+
 /// Channel metadata and state
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Channel {
@@ -42,6 +191,10 @@ pub struct Channel {
     /// Set of channel members (replicated via OR-Set)
     pub members: ORSet<UserId>,
 
+    /// Member devices (replicated via OR-Set)
+    /// Add IDs carry the join time, so concurrent add/remove is add-wins
+    pub member_devices: ORSet<MemberDevice>,
+
     /// Set of pinned message IDs (replicated via OR-Set)
     pub pinned_messages: ORSet<MessageId>,
 
@@ -52,6 +205,63 @@ pub struct Channel {
     /// MLS identity metadata (replicated via OR-Map)
     /// Maps user_id -> IdentityMeta for MLS tree reconciliation
     pub mls_identity: ORMap<UserId, IdentityMeta>,
+
+    /// Issued invites (replicated via OR-Map with LWW values)
+    #[serde(default)]
+    pub invites: ORMap<String, LWWRegister<InviteRecord>>,
+
+    /// IDs of revoked invites (replicated via OR-Set, never removed from)
+    #[serde(default)]
+    pub revoked_invites: ORSet<String>,
+
+    /// Inbound webhooks (replicated via OR-Map with LWW values)
+    #[serde(default)]
+    pub webhooks: ORMap<String, LWWRegister<WebhookRecord>>,
+
+    /// IDs of revoked webhooks (replicated via OR-Set, never removed from)
+    #[serde(default)]
+    pub revoked_webhooks: ORSet<String>,
+
+    /// Linked device leaves (replicated via OR-Map with LWW values)
+    #[serde(default)]
+    pub linked_devices: ORMap<String, LWWRegister<LinkedDeviceRecord>>,
+
+    /// IDs of revoked devices (replicated via OR-Set, never removed from)
+    #[serde(default)]
+    pub revoked_devices: ORSet<String>,
+
+    /// Node each member device syncs from, by device ID (replicated via
+    /// OR-Map with LWW values)
+    #[serde(default)]
+    pub replica_nodes: ORMap<String, LWWRegister<String>>,
+
+    /// Whether anyone may join without an invite (replicated via LWW, unset means private)
+    #[serde(default)]
+    pub public: LWWRegister<bool>,
+
+    /// ID of the external PSK invitees must know (replicated via LWW, unset or empty means none)
+    #[serde(default)]
+    pub psk_id: LWWRegister<Vec<u8>>,
+
+    /// How members pad messages (replicated via LWW, unset means each member's default)
+    #[serde(default, with = "padding_register")]
+    pub padding: LWWRegister<PaddingStrategy>,
+
+    /// SHA-256 of the channel avatar (replicated via LWW, unset or empty means none)
+    #[serde(default)]
+    pub avatar_hash: LWWRegister<Vec<u8>>,
+
+    /// Per-user read markers (replicated via OR-Map with LWW values)
+    #[serde(default)]
+    pub read_markers: ORMap<UserId, LWWRegister<ReadMarker>>,
+
+    /// Whether members share read markers (replicated via LWW, unset means no)
+    #[serde(default)]
+    pub read_receipts: LWWRegister<bool>,
+
+    /// How long members keep messages (replicated via LWW, unset means forever)
+    #[serde(default)]
+    pub retention: LWWRegister<RetentionPolicy>,
     // TODO: Add when GList is implemented
     // /// Message timeline (replicated via GList/RGA for causal ordering)
     // pub messages: GList<MessageId>,
@@ -71,9 +281,17 @@ impl Channel {
         let topic_register = LWWRegister::with_value(String::new(), node_id);
 
         let members = ORSet::new();
+        let member_devices = ORSet::new();
         let pinned_messages = ORSet::new();
         let permissions = ORMap::new();
         let mls_identity = ORMap::new();
+        let invites = ORMap::new();
+        let revoked_invites = ORSet::new();
+        let webhooks = ORMap::new();
+        let revoked_webhooks = ORSet::new();
+        let linked_devices = ORMap::new();
+        let revoked_devices = ORSet::new();
+        let replica_nodes = ORMap::new();
 
         Channel {
             id,
@@ -83,9 +301,24 @@ impl Channel {
             created_at,
             created_by,
             members,
+            member_devices,
             pinned_messages,
             permissions,
             mls_identity,
+            invites,
+            revoked_invites,
+            webhooks,
+            revoked_webhooks,
+            linked_devices,
+            revoked_devices,
+            replica_nodes,
+            public: LWWRegister::new(),
+            psk_id: LWWRegister::new(),
+            padding: LWWRegister::new(),
+            avatar_hash: LWWRegister::new(),
+            read_markers: ORMap::new(),
+            read_receipts: LWWRegister::new(),
+            retention: LWWRegister::new(),
         }
     }
 
@@ -99,6 +332,108 @@ impl Channel {
         self.topic.get()
     }
 
+    /// Rename the channel
+    pub fn set_name(&mut self, name: String, node_id: &str) {
+        let mut vector_clock = self.name.vector_clock().clone();
+        vector_clock.increment(node_id);
+
+        let timestamp = Timestamp::now().as_millis().max(self.name.timestamp() + 1);
+        self.name.set(name, timestamp, node_id.to_string(), vector_clock);
+    }
+
+    /// Set (or, with an empty string, clear) the topic
+    pub fn set_topic(&mut self, topic: String, node_id: &str) {
+        let mut vector_clock = self.topic.vector_clock().clone();
+        vector_clock.increment(node_id);
+
+        let timestamp = Timestamp::now().as_millis().max(self.topic.timestamp() + 1);
+        self.topic.set(topic, timestamp, node_id.to_string(), vector_clock);
+    }
+
+    /// Hash of the channel avatar, if one was set
+    pub fn avatar_hash(&self) -> Option<&[u8]> {
+        self.avatar_hash.get().map(Vec::as_slice).filter(|hash| !hash.is_empty())
+    }
+
+    /// Set (or, with an empty hash, clear) the channel avatar
+    pub fn set_avatar_hash(&mut self, hash: Vec<u8>, node_id: &str) {
+        let mut vector_clock = self.avatar_hash.vector_clock().clone();
+        vector_clock.increment(node_id);
+
+        let timestamp = Timestamp::now().as_millis().max(self.avatar_hash.timestamp() + 1);
+        self.avatar_hash.set(hash, timestamp, node_id.to_string(), vector_clock);
+    }
+
+    /// Whether the channel is public
+    pub fn is_public(&self) -> bool {
+        self.public.get().copied().unwrap_or(false)
+    }
+
+    /// Make the channel public or private
+    pub fn set_public(&mut self, public: bool, node_id: &str) {
+        let mut vector_clock = self.public.vector_clock().clone();
+        vector_clock.increment(node_id);
+
+        let timestamp = Timestamp::now().as_millis().max(self.public.timestamp() + 1);
+        self.public.set(public, timestamp, node_id.to_string(), vector_clock);
+    }
+
+    /// ID of the external PSK that joining requires, if any
+    pub fn required_psk(&self) -> Option<&[u8]> {
+        self.psk_id.get().map(Vec::as_slice).filter(|psk_id| !psk_id.is_empty())
+    }
+
+    /// Require (or, with an empty ID, stop requiring) an external PSK to join
+    pub fn set_required_psk(&mut self, psk_id: Vec<u8>, node_id: &str) {
+        let mut vector_clock = self.psk_id.vector_clock().clone();
+        vector_clock.increment(node_id);
+
+        let timestamp = Timestamp::now().as_millis().max(self.psk_id.timestamp() + 1);
+        self.psk_id.set(psk_id, timestamp, node_id.to_string(), vector_clock);
+    }
+
+    /// Padding strategy the members agreed on, if one was set
+    pub fn padding(&self) -> Option<&PaddingStrategy> {
+        self.padding.get()
+    }
+
+    /// Set the padding strategy all members use
+    pub fn set_padding(&mut self, padding: PaddingStrategy, node_id: &str) {
+        let mut vector_clock = self.padding.vector_clock().clone();
+        vector_clock.increment(node_id);
+
+        let timestamp = Timestamp::now().as_millis().max(self.padding.timestamp() + 1);
+        self.padding.set(padding, timestamp, node_id.to_string(), vector_clock);
+    }
+
+    /// Whether members share their read markers with each other
+    pub fn shares_read_receipts(&self) -> bool {
+        self.read_receipts.get().copied().unwrap_or(false)
+    }
+
+    /// Start or stop sharing read markers with other members
+    pub fn set_read_receipts(&mut self, enabled: bool, node_id: &str) {
+        let mut vector_clock = self.read_receipts.vector_clock().clone();
+        vector_clock.increment(node_id);
+
+        let timestamp = Timestamp::now().as_millis().max(self.read_receipts.timestamp() + 1);
+        self.read_receipts.set(enabled, timestamp, node_id.to_string(), vector_clock);
+    }
+
+    /// How long members keep the channel's messages
+    pub fn retention(&self) -> RetentionPolicy {
+        self.retention.get().copied().unwrap_or_default()
+    }
+
+    /// Set how long members keep the channel's messages
+    pub fn set_retention(&mut self, policy: RetentionPolicy, node_id: &str) {
+        let mut vector_clock = self.retention.vector_clock().clone();
+        vector_clock.increment(node_id);
+
+        let timestamp = Timestamp::now().as_millis().max(self.retention.timestamp() + 1);
+        self.retention.set(policy, timestamp, node_id.to_string(), vector_clock);
+    }
+
     /// Check if a user is a member
     pub fn has_member(&self, user_id: &UserId) -> bool {
         self.members.contains(user_id)
@@ -109,6 +444,75 @@ impl Channel {
         self.members.elements()
     }
 
+    /// Record a member device joining
+    ///
+    /// Also adds the user to `members`.
+    pub fn add_member_device(&mut self, device: MemberDevice, joined_at: Timestamp, node_id: &str) {
+        let mut vector_clock = self.member_devices.vector_clock().clone();
+        vector_clock.increment(node_id);
+
+        let add_id = AddId::new(node_id.to_string(), joined_at.as_millis());
+        self.members.add(device.user_id.clone(), add_id.clone(), vector_clock.clone());
+        self.member_devices.add(device, add_id, vector_clock);
+    }
+
+    /// Record a member device leaving
+    ///
+    /// The user is removed from `members` once none of their devices remain.
+    pub fn remove_member_device(&mut self, device: &MemberDevice, node_id: &str) {
+        let mut vector_clock = self.member_devices.vector_clock().clone();
+        vector_clock.increment(node_id);
+
+        self.member_devices.remove(device, vector_clock.clone());
+        if !self.member_devices.elements().iter().any(|d| d.user_id == device.user_id) {
+            self.members.remove(&device.user_id, vector_clock);
+        }
+    }
+
+    /// Record that a member device syncs from the node `node_id`
+    ///
+    /// Written by the device itself, so its node is the writer too.
+    pub fn set_replica_node(&mut self, device_id: &str, node_id: &str) {
+        let mut vector_clock = self.replica_nodes.vector_clock().clone();
+        vector_clock.increment(node_id);
+
+        let key = device_id.to_string();
+        let mut register = self.replica_nodes.get(&key).cloned().unwrap_or_default();
+        let timestamp = Timestamp::now().as_millis().max(register.timestamp() + 1);
+        register.set(node_id.to_string(), timestamp, node_id.to_string(), vector_clock.clone());
+        self.replica_nodes.put(
+            key,
+            register,
+            AddId::new(node_id.to_string(), timestamp),
+            vector_clock,
+        );
+    }
+
+    /// Node a member device syncs from, if it recorded one
+    pub fn replica_node(&self, device_id: &str) -> Option<&String> {
+        self.replica_nodes.get(&device_id.to_string())?.get()
+    }
+
+    /// Get all current member devices with their join times
+    ///
+    /// A device's join time is its earliest add that hasn't been removed,
+    /// so replicas that have merged the same operations agree on it.
+    pub fn get_member_devices(&self) -> Vec<(MemberDevice, Timestamp)> {
+        self.member_devices
+            .elements()
+            .into_iter()
+            .filter_map(|device| {
+                let joined_at = self
+                    .member_devices
+                    .get_add_ids(&device)?
+                    .iter()
+                    .map(|id| id.timestamp)
+                    .min()?;
+                Some((device, Timestamp::from_millis(joined_at)))
+            })
+            .collect()
+    }
+
     /// Check if a message is pinned
     pub fn is_pinned(&self, message_id: &MessageId) -> bool {
         self.pinned_messages.contains(message_id)
@@ -138,6 +542,174 @@ impl Channel {
     pub fn get_mls_identity(&self, user_id: &UserId) -> Option<&IdentityMeta> {
         self.mls_identity.get(user_id)
     }
+
+    /// Record a new invite or an update to one (e.g. its use count)
+    pub fn put_invite(&mut self, record: InviteRecord, node_id: &str) {
+        let mut vector_clock = self.invites.vector_clock().clone();
+        vector_clock.increment(node_id);
+
+        // Updates within the same millisecond must still win
+        let key = record.invite_id.clone();
+        let mut register = self.invites.get(&key).cloned().unwrap_or_default();
+        let timestamp = Timestamp::now().as_millis().max(register.timestamp() + 1);
+        register.set(record, timestamp, node_id.to_string(), vector_clock.clone());
+        self.invites
+            .put(key, register, AddId::new(node_id.to_string(), timestamp), vector_clock);
+    }
+
+    /// Get an invite record by ID
+    pub fn get_invite(&self, invite_id: &str) -> Option<&InviteRecord> {
+        self.invites.get(&invite_id.to_string())?.get()
+    }
+
+    /// Get all invite records
+    pub fn get_invites(&self) -> Vec<InviteRecord> {
+        self.invites
+            .entries()
+            .into_iter()
+            .filter_map(|(_, register)| register.get().cloned())
+            .collect()
+    }
+
+    /// Mark an invite as revoked
+    pub fn revoke_invite(&mut self, invite_id: &str, node_id: &str) {
+        let mut vector_clock = self.revoked_invites.vector_clock().clone();
+        vector_clock.increment(node_id);
+
+        let add_id = AddId::new(node_id.to_string(), Timestamp::now().as_millis());
+        self.revoked_invites.add(invite_id.to_string(), add_id, vector_clock);
+    }
+
+    /// Check if an invite has been revoked
+    pub fn is_invite_revoked(&self, invite_id: &str) -> bool {
+        self.revoked_invites.contains(&invite_id.to_string())
+    }
+
+    /// Record a new webhook or an update to one (e.g. a rotated token)
+    pub fn put_webhook(&mut self, record: WebhookRecord, node_id: &str) {
+        let mut vector_clock = self.webhooks.vector_clock().clone();
+        vector_clock.increment(node_id);
+
+        // Updates within the same millisecond must still win
+        let key = record.webhook_id.clone();
+        let mut register = self.webhooks.get(&key).cloned().unwrap_or_default();
+        let timestamp = Timestamp::now().as_millis().max(register.timestamp() + 1);
+        register.set(record, timestamp, node_id.to_string(), vector_clock.clone());
+        self.webhooks
+            .put(key, register, AddId::new(node_id.to_string(), timestamp), vector_clock);
+    }
+
+    /// Get a webhook record by ID
+    pub fn get_webhook(&self, webhook_id: &str) -> Option<&WebhookRecord> {
+        self.webhooks.get(&webhook_id.to_string())?.get()
+    }
+
+    /// Get all webhook records, including revoked ones
+    pub fn get_webhooks(&self) -> Vec<WebhookRecord> {
+        self.webhooks
+            .entries()
+            .into_iter()
+            .filter_map(|(_, register)| register.get().cloned())
+            .collect()
+    }
+
+    /// Mark a webhook as revoked
+    pub fn revoke_webhook(&mut self, webhook_id: &str, node_id: &str) {
+        let mut vector_clock = self.revoked_webhooks.vector_clock().clone();
+        vector_clock.increment(node_id);
+
+        let add_id = AddId::new(node_id.to_string(), Timestamp::now().as_millis());
+        self.revoked_webhooks.add(webhook_id.to_string(), add_id, vector_clock);
+    }
+
+    /// Check if a webhook has been revoked
+    pub fn is_webhook_revoked(&self, webhook_id: &str) -> bool {
+        self.revoked_webhooks.contains(&webhook_id.to_string())
+    }
+
+    /// Record the leaf a linked device was added with
+    pub fn put_linked_device(&mut self, record: LinkedDeviceRecord, node_id: &str) {
+        let mut vector_clock = self.linked_devices.vector_clock().clone();
+        vector_clock.increment(node_id);
+
+        let key = record.leaf.clone();
+        let mut register = self.linked_devices.get(&key).cloned().unwrap_or_default();
+        let timestamp = Timestamp::now().as_millis().max(register.timestamp() + 1);
+        register.set(record, timestamp, node_id.to_string(), vector_clock.clone());
+        self.linked_devices.put(
+            key,
+            register,
+            AddId::new(node_id.to_string(), timestamp),
+            vector_clock,
+        );
+    }
+
+    /// Get all linked device leaves, including those of revoked devices
+    pub fn get_linked_devices(&self) -> Vec<LinkedDeviceRecord> {
+        self.linked_devices
+            .entries()
+            .into_iter()
+            .filter_map(|(_, register)| register.get().cloned())
+            .collect()
+    }
+
+    /// Mark a device as revoked
+    pub fn revoke_device(&mut self, device_id: &str, node_id: &str) {
+        let mut vector_clock = self.revoked_devices.vector_clock().clone();
+        vector_clock.increment(node_id);
+
+        let add_id = AddId::new(node_id.to_string(), Timestamp::now().as_millis());
+        self.revoked_devices.add(device_id.to_string(), add_id, vector_clock);
+    }
+
+    /// Check if a device has been revoked
+    pub fn is_device_revoked(&self, device_id: &str) -> bool {
+        self.revoked_devices.contains(&device_id.to_string())
+    }
+
+    /// Move a user's read marker forward
+    ///
+    /// The marker's position is its LWW timestamp, so a marker behind the
+    /// current one is ignored and concurrent markers from several devices
+    /// converge on the furthest one.
+    ///
+    /// # Returns
+    ///
+    /// Whether the marker moved
+    pub fn set_read_marker(&mut self, user_id: UserId, marker: ReadMarker, node_id: &str) -> bool {
+        let mut vector_clock = self.read_markers.vector_clock().clone();
+        vector_clock.increment(node_id);
+
+        let mut register = self.read_markers.get(&user_id).cloned().unwrap_or_default();
+        let previous = register.get().cloned();
+        let timestamp = marker.position.as_millis();
+        register.set(marker, timestamp, node_id.to_string(), vector_clock.clone());
+        if register.get() == previous.as_ref() {
+            return false;
+        }
+
+        self.read_markers.put(
+            user_id,
+            register,
+            AddId::new(node_id.to_string(), timestamp),
+            vector_clock,
+        );
+        true
+    }
+
+    /// Get how far a user has read
+    pub fn get_read_marker(&self, user_id: &UserId) -> Option<&ReadMarker> {
+        self.read_markers.get(user_id)?.get()
+    }
+
+    /// Get the read markers of all users
+    pub fn get_read_markers(&self) -> Vec<(UserId, ReadMarker)> {
+        self.read_markers
+            .entries()
+            .into_iter()
+            .filter_map(|(user_id, register)| Some((user_id, register.get().cloned()?)))
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -184,5 +756,126 @@ mod tests {
         assert_eq!(channel.get_members().len(), 0);
         assert_eq!(channel.get_pinned_messages().len(), 0);
         assert_eq!(channel.get_all_permissions().len(), 0);
+        assert!(!channel.is_public());
+        assert_eq!(channel.required_psk(), None);
+    }
+
+    #[test]
+    fn test_set_public() {
+        let mut channel = Channel::new(
+            ChannelId::generate(),
+            "general".to_string(),
+            ChannelType::Text,
+            UserId::generate(),
+            Timestamp::now(),
+            "node1".to_string(),
+        );
+
+        channel.set_public(true, "node1");
+        assert!(channel.is_public());
+
+        // A change in the same millisecond still wins
+        channel.set_public(false, "node1");
+        assert!(!channel.is_public());
+    }
+
+    #[test]
+    fn test_set_required_psk() {
+        let mut channel = Channel::new(
+            ChannelId::generate(),
+            "ops".to_string(),
+            ChannelType::Text,
+            UserId::generate(),
+            Timestamp::now(),
+            "node1".to_string(),
+        );
+
+        channel.set_required_psk(b"ops-psk".to_vec(), "node1");
+        assert_eq!(channel.required_psk(), Some(&b"ops-psk"[..]));
+
+        channel.set_required_psk(Vec::new(), "node1");
+        assert_eq!(channel.required_psk(), None);
+    }
+
+    #[test]
+    fn test_set_padding() {
+        let mut channel = Channel::new(
+            ChannelId::generate(),
+            "ops".to_string(),
+            ChannelType::Text,
+            UserId::generate(),
+            Timestamp::now(),
+            "node1".to_string(),
+        );
+        assert_eq!(channel.padding(), None);
+
+        channel.set_padding(PaddingStrategy::Padme, "node1");
+        assert_eq!(channel.padding(), Some(&PaddingStrategy::Padme));
+    }
+
+    #[test]
+    fn test_padding_survives_bincode() {
+        let mut channel = Channel::new(
+            ChannelId::generate(),
+            "ops".to_string(),
+            ChannelType::Text,
+            UserId::generate(),
+            Timestamp::now(),
+            "node1".to_string(),
+        );
+        let padding = PaddingStrategy::Buckets { sizes: vec![256, 1024] };
+        channel.set_padding(padding.clone(), "node1");
+
+        let decoded: Channel =
+            bincode::deserialize(&bincode::serialize(&channel).unwrap()).unwrap();
+        assert_eq!(decoded.padding(), Some(&padding));
+        assert_eq!(decoded.padding.timestamp(), channel.padding.timestamp());
+    }
+
+    #[test]
+    fn test_set_metadata() {
+        let mut channel = Channel::new(
+            ChannelId::generate(),
+            "ops".to_string(),
+            ChannelType::Text,
+            UserId::generate(),
+            Timestamp::now(),
+            "node1".to_string(),
+        );
+        assert_eq!(channel.avatar_hash(), None);
+
+        channel.set_name("on-call".to_string(), "node1");
+        channel.set_topic("pager rotation".to_string(), "node1");
+        channel.set_avatar_hash(vec![7; 32], "node1");
+        assert_eq!(channel.get_name().map(String::as_str), Some("on-call"));
+        assert_eq!(channel.get_topic().map(String::as_str), Some("pager rotation"));
+        assert_eq!(channel.avatar_hash(), Some(&[7; 32][..]));
+
+        channel.set_avatar_hash(Vec::new(), "node1");
+        assert_eq!(channel.avatar_hash(), None);
+    }
+
+    #[test]
+    fn test_read_marker_only_moves_forward() {
+        let user_id = UserId::generate();
+        let mut channel = Channel::new(
+            ChannelId::generate(),
+            "general".to_string(),
+            ChannelType::Text,
+            user_id.clone(),
+            Timestamp::now(),
+            "node1".to_string(),
+        );
+        assert_eq!(channel.get_read_marker(&user_id), None);
+
+        let marker = |millis| ReadMarker {
+            message_id: MessageId::generate(),
+            position: Timestamp::from_millis(millis),
+        };
+        let read = marker(2_000);
+        assert!(channel.set_read_marker(user_id.clone(), read.clone(), "node1"));
+        assert!(!channel.set_read_marker(user_id.clone(), marker(1_000), "node1"));
+        assert_eq!(channel.get_read_marker(&user_id), Some(&read));
+        assert_eq!(channel.get_read_markers(), vec![(user_id, read)]);
     }
 }
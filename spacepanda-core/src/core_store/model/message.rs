@@ -11,12 +11,14 @@
     - timestamp: when message was created
     - reply_to: optional thread/reply reference
     - attachments: metadata about files (actual files stored separately)
-    - reactions: emoji reactions (CRDT OR-Map)
+    - reactions: emoji reactions (CRDT OR-Set of (user, emoji))
 */
 
 use super::types::{ChannelId, MessageId, Timestamp, UserId};
-use crate::core_store::crdt::ORMap;
+use crate::core_store::crdt::{AddId, Crdt, ORSet, ORSetOperation, OperationMetadata, VectorClock};
+use crate::core_store::store::errors::StoreResult;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 
 /// Attachment metadata (actual file stored in blob store)
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -58,9 +60,9 @@ pub struct Message {
     /// Optional attachments (encrypted separately)
     pub attachments: Vec<Attachment>,
 
-    /// Emoji reactions: emoji -> set of user IDs who reacted
-    /// Uses OR-Map to handle concurrent reactions
-    pub reactions: ORMap<String, ORSet<UserId>>,
+    /// Emoji reactions as (user, emoji) pairs
+    /// Uses OR-Set so concurrent reacts and unreacts from different devices converge
+    pub reactions: ORSet<(UserId, String)>,
 
     /// Edit history: tracks if message was edited
     /// Maps timestamp -> (editor_id, new_encrypted_content)
@@ -70,9 +72,6 @@ pub struct Message {
     pub deleted: bool,
 }
 
-/// For OR-Set of user IDs in reactions
-use crate::core_store::crdt::ORSet;
-
 impl Message {
     /// Create a new message
     pub fn new(
@@ -90,7 +89,7 @@ impl Message {
             timestamp,
             reply_to: None,
             attachments: Vec::new(),
-            reactions: ORMap::new(),
+            reactions: ORSet::new(),
             edits: Vec::new(),
             deleted: false,
         }
@@ -142,6 +141,34 @@ impl Message {
     pub fn last_editor(&self) -> &UserId {
         self.edits.last().map(|(_, editor, _)| editor).unwrap_or(&self.sender)
     }
+
+    /// Add a reaction; `add_id` tells apart adds of the same reaction
+    pub fn add_reaction(&mut self, user: UserId, emoji: String, add_id: AddId) -> StoreResult<()> {
+        let metadata = OperationMetadata::new(add_id.node_id.clone(), VectorClock::new());
+        self.reactions
+            .apply(ORSetOperation::Add { element: (user, emoji), add_id, metadata })
+    }
+
+    /// Remove the given adds of a reaction
+    ///
+    /// Adds not seen yet stay removed when they arrive; adds made
+    /// concurrently elsewhere are kept.
+    pub fn remove_reaction(&mut self, user: UserId, emoji: String, add_ids: &HashSet<AddId>) {
+        self.reactions.remove_adds(&(user, emoji), add_ids, VectorClock::new());
+    }
+
+    /// Adds of a reaction currently in effect
+    pub fn reaction_add_ids(&self, user: &UserId, emoji: &str) -> HashSet<AddId> {
+        self.reactions
+            .get_add_ids(&(user.clone(), emoji.to_string()))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Current reactions as (user, emoji) pairs
+    pub fn reactions(&self) -> Vec<(UserId, String)> {
+        self.reactions.elements()
+    }
 }
 
 #[cfg(test)]
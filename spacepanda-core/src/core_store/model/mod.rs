@@ -8,6 +8,7 @@ pub mod channel;
 pub mod identity_meta;
 pub mod message;
 pub mod mls_state;
+pub mod permission;
 pub mod space;
 pub mod types;
 
@@ -15,5 +16,6 @@ pub use channel::*;
 pub use identity_meta::*;
 pub use message::*;
 pub use mls_state::*;
+pub use permission::*;
 pub use space::*;
 pub use types::*;
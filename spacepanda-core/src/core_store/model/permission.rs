@@ -0,0 +1,238 @@
+/*
+    permission.rs - Capability-based permissions
+
+    Authorization checks ask "does this user hold capability X?" rather than
+    "is this user an admin?". A role is just a named `PermissionSet`; the
+    built-in roles (space Owner/Admin/Member, channel Admin/Member/ReadOnly,
+    legacy `PermissionLevel`s) map to default sets, and spaces can define
+    additional named roles with arbitrary sets.
+
+    Sets are stored as a bit mask so they replicate and persist compactly.
+    Bits not known to this version are dropped when decoding.
+*/
+
+use super::types::PermissionLevel;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A single capability
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[repr(u32)]
+pub enum Permission {
+    /// Invite users and add them to channels
+    Invite = 1 << 0,
+    /// Remove members
+    Kick = 1 << 1,
+    /// Create, rename and delete channels
+    EditChannel = 1 << 2,
+    /// Delete other members' messages
+    DeleteMessages = 1 << 3,
+    /// Define roles and change members' roles
+    ManageRoles = 1 << 4,
+    /// Send messages
+    Post = 1 << 5,
+    /// Pin and unpin messages
+    PinMessages = 1 << 6,
+}
+
+impl Permission {
+    /// Every capability, in bit order
+    pub const ALL: [Permission; 7] = [
+        Permission::Invite,
+        Permission::Kick,
+        Permission::EditChannel,
+        Permission::DeleteMessages,
+        Permission::ManageRoles,
+        Permission::Post,
+        Permission::PinMessages,
+    ];
+
+    /// Bit representing this capability
+    pub fn bit(self) -> u32 {
+        self as u32
+    }
+
+    /// Stable lower-case name (used in CLI output and config)
+    pub fn name(self) -> &'static str {
+        match self {
+            Permission::Invite => "invite",
+            Permission::Kick => "kick",
+            Permission::EditChannel => "edit_channel",
+            Permission::DeleteMessages => "delete_messages",
+            Permission::ManageRoles => "manage_roles",
+            Permission::Post => "post",
+            Permission::PinMessages => "pin_messages",
+        }
+    }
+
+    /// Parse a name produced by [`Permission::name`]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.name() == name)
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A set of capabilities
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct PermissionSet(u32);
+
+impl PermissionSet {
+    const MASK: u32 = (1 << Permission::ALL.len()) - 1;
+
+    /// No capabilities
+    pub const fn empty() -> Self {
+        PermissionSet(0)
+    }
+
+    /// Every capability
+    pub const fn all() -> Self {
+        PermissionSet(Self::MASK)
+    }
+
+    /// Decode a bit mask, ignoring unknown bits
+    pub const fn from_bits_truncate(bits: u32) -> Self {
+        PermissionSet(bits & Self::MASK)
+    }
+
+    /// Bit mask for storage
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Whether the set grants `permission`
+    pub fn contains(self, permission: Permission) -> bool {
+        self.0 & permission.bit() != 0
+    }
+
+    /// Whether every capability in `other` is granted
+    pub fn contains_all(self, other: PermissionSet) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Whether the set is empty
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Add a capability
+    pub fn insert(&mut self, permission: Permission) {
+        self.0 |= permission.bit();
+    }
+
+    /// Remove a capability
+    pub fn remove(&mut self, permission: Permission) {
+        self.0 &= !permission.bit();
+    }
+
+    /// This set plus `permission`
+    pub fn with(mut self, permission: Permission) -> Self {
+        self.insert(permission);
+        self
+    }
+
+    /// Capabilities in either set
+    pub fn union(self, other: PermissionSet) -> Self {
+        PermissionSet(self.0 | other.0)
+    }
+
+    /// Capabilities in both sets
+    pub fn intersection(self, other: PermissionSet) -> Self {
+        PermissionSet(self.0 & other.0)
+    }
+
+    /// Granted capabilities, in bit order
+    pub fn iter(self) -> impl Iterator<Item = Permission> {
+        Permission::ALL.into_iter().filter(move |p| self.contains(*p))
+    }
+}
+
+impl FromIterator<Permission> for PermissionSet {
+    fn from_iter<I: IntoIterator<Item = Permission>>(iter: I) -> Self {
+        iter.into_iter().fold(PermissionSet::empty(), PermissionSet::with)
+    }
+}
+
+impl From<Permission> for PermissionSet {
+    fn from(permission: Permission) -> Self {
+        PermissionSet(permission.bit())
+    }
+}
+
+impl fmt::Debug for PermissionSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl fmt::Display for PermissionSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<_> = self.iter().map(Permission::name).collect();
+        f.write_str(&names.join(","))
+    }
+}
+
+/// Map a legacy permission level onto capabilities
+///
+/// `read` has no capability of its own: membership implies read access.
+impl From<&PermissionLevel> for PermissionSet {
+    fn from(level: &PermissionLevel) -> Self {
+        if level.admin {
+            return PermissionSet::all();
+        }
+
+        let mut set = PermissionSet::empty();
+        if level.write {
+            set.insert(Permission::Post);
+        }
+        if level.ban_members {
+            set = set.with(Permission::Kick).with(Permission::DeleteMessages);
+        }
+        if level.manage_roles {
+            set.insert(Permission::ManageRoles);
+        }
+        if level.manage_channels {
+            set = set.with(Permission::EditChannel).with(Permission::PinMessages);
+        }
+        set
+    }
+}
+
+impl From<PermissionLevel> for PermissionSet {
+    fn from(level: PermissionLevel) -> Self {
+        PermissionSet::from(&level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permission_level_mapping() {
+        assert_eq!(PermissionSet::from(&PermissionLevel::admin()), PermissionSet::all());
+        assert_eq!(PermissionSet::from(&PermissionLevel::member()), Permission::Post.into());
+        assert!(PermissionSet::from(&PermissionLevel::read_only()).is_empty());
+
+        let moderator = PermissionSet::from(&PermissionLevel::moderator());
+        assert!(moderator.contains(Permission::Kick));
+        assert!(!moderator.contains(Permission::ManageRoles));
+    }
+
+    #[test]
+    fn test_set_operations() {
+        let set: PermissionSet = [Permission::Invite, Permission::Post].into_iter().collect();
+        assert!(set.contains(Permission::Invite));
+        assert!(!set.contains(Permission::Kick));
+        assert!(PermissionSet::all().contains_all(set));
+        assert_eq!(set.to_string(), "invite,post");
+        assert_eq!(Permission::from_name("pin_messages"), Some(Permission::PinMessages));
+
+        // Unknown bits from a newer version are dropped
+        assert_eq!(PermissionSet::from_bits_truncate(u32::MAX), PermissionSet::all());
+    }
+}
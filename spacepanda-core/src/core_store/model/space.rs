@@ -7,11 +7,13 @@
     CRDT Design:
     - name, description: LWWRegister for single-value fields
     - channels, members: OR-Set for membership
-    - roles: OR-Map with CRDT-wrapped Role fields
+    - roles: OR-Map with CRDT-wrapped Role fields; each role carries a
+      capability set (see permission.rs), so spaces can define custom roles
     - member_roles: OR-Map with LWW values for deterministic role assignment
     - mls_identity: OR-Map tracking MLS leaf indices and credentials
 */
 
+use super::permission::{Permission, PermissionSet};
 use super::types::{ChannelId, IdentityMeta, PermissionLevel, SpaceId, Timestamp, UserId};
 use crate::core_store::crdt::traits::Crdt;
 use crate::core_store::crdt::{LWWRegister, ORMap, ORSet, VectorClock};
@@ -30,6 +32,13 @@ pub struct Role {
 
     /// Role color (LWW for concurrent color changes)
     pub color: LWWRegister<Option<String>>,
+
+    /// Capability set (LWW for concurrent permission changes)
+    ///
+    /// Unset for roles created before capability sets existed; those fall
+    /// back to the set implied by `permission_level`.
+    #[serde(default)]
+    pub permissions: LWWRegister<PermissionSet>,
 }
 
 impl Role {
@@ -38,6 +47,17 @@ impl Role {
             name: LWWRegister::with_value(name, node_id.clone()),
             permission_level: LWWRegister::with_value(permission_level, node_id.clone()),
             color: LWWRegister::with_value(None, node_id),
+            permissions: LWWRegister::new(),
+        }
+    }
+
+    /// Create a custom role with an explicit capability set
+    pub fn with_permissions(name: String, permissions: PermissionSet, node_id: String) -> Self {
+        Role {
+            name: LWWRegister::with_value(name, node_id.clone()),
+            permission_level: LWWRegister::new(),
+            color: LWWRegister::with_value(None, node_id.clone()),
+            permissions: LWWRegister::with_value(permissions, node_id),
         }
     }
 
@@ -52,6 +72,14 @@ impl Role {
     pub fn get_color(&self) -> Option<&Option<String>> {
         self.color.get()
     }
+
+    /// Capabilities granted by this role
+    pub fn get_permissions(&self) -> PermissionSet {
+        match self.permissions.get() {
+            Some(set) => *set,
+            None => self.get_permission_level().map(PermissionSet::from).unwrap_or_default(),
+        }
+    }
 }
 
 /// Implement Crdt for Role to enable proper merging in ORMap
@@ -69,6 +97,7 @@ impl Crdt for Role {
         self.name.merge(&other.name);
         self.permission_level.merge(&other.permission_level);
         self.color.merge(&other.color);
+        self.permissions.merge(&other.permissions);
         Ok(())
     }
 
@@ -215,6 +244,25 @@ impl Space {
             .cloned()
     }
 
+    /// Get the capabilities granted to a user
+    ///
+    /// The owner always holds every capability; users without a role hold none.
+    pub fn get_user_permissions(&self, user_id: &UserId) -> PermissionSet {
+        if user_id == &self.owner {
+            return PermissionSet::all();
+        }
+
+        self.get_user_role_id(user_id)
+            .and_then(|role_id| self.get_role(role_id))
+            .map(Role::get_permissions)
+            .unwrap_or_default()
+    }
+
+    /// Check whether a user holds a capability
+    pub fn user_has_permission(&self, user_id: &UserId, permission: Permission) -> bool {
+        self.get_user_permissions(user_id).contains(permission)
+    }
+
     /// Get MLS identity for a user
     pub fn get_mls_identity(&self, user_id: &UserId) -> Option<&IdentityMeta> {
         self.mls_identity.get(user_id)
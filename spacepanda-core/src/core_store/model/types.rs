@@ -103,8 +103,36 @@ impl ChannelId {
         let id = Uuid::new_v4().to_string();
         ChannelId(id)
     }
+
+    /// Derive a channel ID from its creator and a caller-chosen key
+    ///
+    /// The same inputs always give the same ID, so provisioning can be
+    /// re-run without creating duplicates. Derived IDs start with
+    /// [`DERIVED_CHANNEL_ID_PREFIX`], which never appears in a UUID, so they
+    /// can't collide with generated ones.
+    pub fn derive(creator: &UserId, namespace_key: &str) -> Self {
+        // Length-prefix the creator so ("a", "bc") and ("ab", "c") differ
+        let mut material = Vec::with_capacity(8 + creator.0.len() + namespace_key.len());
+        material.extend_from_slice(&(creator.0.len() as u64).to_le_bytes());
+        material.extend_from_slice(creator.0.as_bytes());
+        material.extend_from_slice(namespace_key.as_bytes());
+
+        let hash = blake3::derive_key(DERIVED_CHANNEL_ID_CONTEXT, &material);
+        ChannelId(format!("{}{}", DERIVED_CHANNEL_ID_PREFIX, hex::encode(hash)))
+    }
+
+    /// Whether this ID was produced by [`ChannelId::derive`]
+    pub fn is_derived(&self) -> bool {
+        self.0.starts_with(DERIVED_CHANNEL_ID_PREFIX)
+    }
 }
 
+/// Prefix of every derived channel ID
+pub const DERIVED_CHANNEL_ID_PREFIX: &str = "key:";
+
+/// BLAKE3 key-derivation context for derived channel IDs
+const DERIVED_CHANNEL_ID_CONTEXT: &str = "spacepanda 2025 derived channel id v1";
+
 impl fmt::Display for ChannelId {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.0)
@@ -125,6 +153,12 @@ impl MessageId {
         let id = Uuid::new_v4().to_string();
         MessageId(id)
     }
+
+    /// ID every member derives for a sent message from its ciphertext
+    pub fn from_ciphertext(ciphertext: &[u8]) -> Self {
+        let hash = blake3::hash(ciphertext);
+        MessageId(hex::encode(&hash.as_bytes()[..16]))
+    }
 }
 
 impl fmt::Display for MessageId {
@@ -293,6 +327,25 @@ mod tests {
         assert!(id1.0.len() > 0);
     }
 
+    #[test]
+    fn test_derived_channel_ids() {
+        let alice = UserId("alice".to_string());
+        let bob = UserId("bob".to_string());
+
+        let id = ChannelId::derive(&alice, "project-x");
+        assert_eq!(id, ChannelId::derive(&alice, "project-x"));
+        assert_ne!(id, ChannelId::derive(&alice, "project-y"));
+        assert_ne!(id, ChannelId::derive(&bob, "project-x"));
+        assert_ne!(
+            ChannelId::derive(&UserId("a".to_string()), "bc"),
+            ChannelId::derive(&UserId("ab".to_string()), "c")
+        );
+
+        assert!(id.is_derived());
+        assert!(!ChannelId::generate().is_derived());
+        assert!(uuid::Uuid::parse_str(&id.0).is_err());
+    }
+
     #[test]
     fn test_message_id_generation() {
         let id1 = MessageId::generate();
@@ -1,5 +1,12 @@
+pub mod observers;
+pub mod persistent_index;
 pub mod query_engine;
 pub mod search_index;
 
-pub use query_engine::{ChannelInfo, MessageInfo, QueryEngine, SortOrder, SpaceInfo};
+pub use observers::{SearchIndexObserver, UnreadCounters};
+pub use persistent_index::PersistentSearchIndex;
+pub use query_engine::{
+    ChannelInfo, Cursor, MessageFilter, MessageInfo, MessageKind, Page, PageRequest, QueryEngine,
+    SortOrder, SpaceInfo, TotalHint,
+};
 pub use search_index::{IndexStats, SearchIndex, SearchResult};
@@ -0,0 +1,265 @@
+/*
+    observers.rs - Store observers that maintain query-side state
+
+    These keep derived read models up to date from LocalStore commits
+    instead of being fed by hand:
+    - SearchIndexObserver: full-text index over message plaintext
+    - UnreadCounters: per-channel unread message counts
+
+    Both are idempotent, so replaying the commit log with
+    LocalStore::observe_from after a rebuild produces the same state.
+*/
+
+use super::search_index::SearchIndex;
+use crate::core_store::model::{ChannelId, Message, MessageId, Timestamp, UserId};
+use crate::core_store::store::observer::{
+    EntityKind, ObserverFilter, StoreChange, StoreEvent, StoreObserver,
+};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+
+/// Keeps a SearchIndex in sync with committed messages
+///
+/// Message content is indexed when it is valid UTF-8 (i.e. already
+/// decrypted); ciphertext is skipped.
+pub struct SearchIndexObserver {
+    index: Arc<RwLock<SearchIndex>>,
+}
+
+impl SearchIndexObserver {
+    pub fn new(index: Arc<RwLock<SearchIndex>>) -> Self {
+        SearchIndexObserver { index }
+    }
+
+    /// Filter selecting the events this observer needs
+    pub fn filter() -> ObserverFilter {
+        ObserverFilter::all().entity(EntityKind::Message)
+    }
+
+    /// Shared handle to the underlying index
+    pub fn index(&self) -> Arc<RwLock<SearchIndex>> {
+        self.index.clone()
+    }
+}
+
+impl StoreObserver for SearchIndexObserver {
+    fn on_commit(&self, event: &StoreEvent) {
+        let mut index = self.index.write().unwrap_or_else(|e| e.into_inner());
+        let message = match &event.change {
+            StoreChange::Message(message) => message,
+            StoreChange::MessagesPruned { message_ids, .. } => {
+                for message_id in message_ids {
+                    index.remove_message(message_id);
+                }
+                return;
+            }
+            _ => return,
+        };
+
+        // Re-indexing replaces any earlier version of the message
+        index.remove_message(&message.id);
+        if let Some(plaintext) = indexable_text(message) {
+            index.index_message(
+                message.id.clone(),
+                message.channel_id.clone(),
+                message.sender.clone(),
+                message.timestamp,
+                plaintext.to_string(),
+            );
+        }
+    }
+}
+
+/// Current searchable text of a message: the latest edit, if valid UTF-8
+///
+/// Deleted messages and ciphertext have none.
+pub(crate) fn indexable_text(message: &Message) -> Option<&str> {
+    if message.deleted {
+        return None;
+    }
+
+    let content = message.edits.last().map(|(_, _, content)| content).unwrap_or(&message.content);
+    std::str::from_utf8(content).ok()
+}
+
+#[derive(Default)]
+struct UnreadState {
+    /// Unread message IDs per channel
+    unread: HashMap<ChannelId, HashSet<MessageId>>,
+
+    /// Newest timestamp the local user has read up to, per channel
+    read_up_to: HashMap<ChannelId, Timestamp>,
+}
+
+/// Per-channel unread message counts for the local user
+///
+/// Messages sent by the local user never count as unread.
+pub struct UnreadCounters {
+    local_user: UserId,
+    state: RwLock<UnreadState>,
+}
+
+impl UnreadCounters {
+    pub fn new(local_user: UserId) -> Self {
+        UnreadCounters { local_user, state: RwLock::new(UnreadState::default()) }
+    }
+
+    /// Filter selecting the events this observer needs
+    pub fn filter() -> ObserverFilter {
+        ObserverFilter::all().entity(EntityKind::Message)
+    }
+
+    /// Number of unread messages in a channel
+    pub fn unread(&self, channel_id: &ChannelId) -> usize {
+        let state = self.state.read().unwrap_or_else(|e| e.into_inner());
+        state.unread.get(channel_id).map_or(0, HashSet::len)
+    }
+
+    /// Mark everything up to and including `up_to` in a channel as read
+    pub fn mark_read(&self, channel_id: &ChannelId, up_to: Timestamp) {
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+        let read_up_to = state.read_up_to.entry(channel_id.clone()).or_insert(up_to);
+        if up_to > *read_up_to {
+            *read_up_to = up_to;
+        }
+        state.unread.remove(channel_id);
+    }
+
+    fn record(&self, message: &Message) {
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+
+        if message.deleted {
+            if let Some(unread) = state.unread.get_mut(&message.channel_id) {
+                unread.remove(&message.id);
+            }
+            return;
+        }
+
+        if message.sender == self.local_user {
+            return;
+        }
+
+        if let Some(read_up_to) = state.read_up_to.get(&message.channel_id) {
+            if message.timestamp <= *read_up_to {
+                return;
+            }
+        }
+
+        state
+            .unread
+            .entry(message.channel_id.clone())
+            .or_default()
+            .insert(message.id.clone());
+    }
+
+    /// Stop counting messages that were pruned
+    fn forget(&self, channel_id: &ChannelId, message_ids: &[MessageId]) {
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(unread) = state.unread.get_mut(channel_id) {
+            for message_id in message_ids {
+                unread.remove(message_id);
+            }
+        }
+    }
+}
+
+impl StoreObserver for UnreadCounters {
+    fn on_commit(&self, event: &StoreEvent) {
+        match &event.change {
+            StoreChange::Message(message) => self.record(message),
+            StoreChange::MessagesPruned { channel_id, message_ids } => {
+                self.forget(channel_id, message_ids)
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core_store::store::observer::ChangeOrigin;
+
+    fn event(message: Message) -> StoreEvent {
+        StoreEvent { seq: 0, origin: ChangeOrigin::Local, change: StoreChange::Message(message) }
+    }
+
+    fn message(channel: &ChannelId, sender: &str, body: &str, at: u64) -> Message {
+        Message::new(
+            MessageId::generate(),
+            channel.clone(),
+            UserId(sender.to_string()),
+            body.as_bytes().to_vec(),
+            Timestamp::from_millis(at),
+        )
+    }
+
+    #[test]
+    fn test_unread_counters() {
+        let channel = ChannelId::generate();
+        let counters = UnreadCounters::new(UserId("alice".to_string()));
+
+        let first = message(&channel, "bob", "one", 1);
+        counters.on_commit(&event(first.clone()));
+        counters.on_commit(&event(first)); // Redelivery is idempotent
+        counters.on_commit(&event(message(&channel, "alice", "mine", 2)));
+        counters.on_commit(&event(message(&channel, "bob", "two", 3)));
+        assert_eq!(counters.unread(&channel), 2);
+
+        counters.mark_read(&channel, Timestamp::from_millis(3));
+        assert_eq!(counters.unread(&channel), 0);
+
+        // Replayed history stays read
+        counters.on_commit(&event(message(&channel, "bob", "old", 2)));
+        assert_eq!(counters.unread(&channel), 0);
+    }
+
+    #[test]
+    fn test_search_index_observer_reindexes_edits() {
+        let channel = ChannelId::generate();
+        let observer = SearchIndexObserver::new(Arc::new(RwLock::new(SearchIndex::new())));
+
+        let mut msg = message(&channel, "bob", "hello world", 1);
+        observer.on_commit(&event(msg.clone()));
+
+        msg.edits
+            .push((Timestamp::from_millis(2), msg.sender.clone(), b"goodbye world".to_vec()));
+        observer.on_commit(&event(msg.clone()));
+
+        let index = observer.index();
+        let index = index.read().unwrap();
+        assert!(index.search("hello", 10).is_empty());
+        assert_eq!(index.search("goodbye", 10).len(), 1);
+    }
+
+    #[test]
+    fn test_pruned_messages_leave_index_and_counters() {
+        let channel = ChannelId::generate();
+        let observer = SearchIndexObserver::new(Arc::new(RwLock::new(SearchIndex::new())));
+        let counters = UnreadCounters::new(UserId("alice".to_string()));
+
+        let old = message(&channel, "bob", "expired secret", 1);
+        let new = message(&channel, "bob", "fresh news", 2);
+        for msg in [&old, &new] {
+            observer.on_commit(&event(msg.clone()));
+            counters.on_commit(&event(msg.clone()));
+        }
+
+        let pruned = StoreEvent {
+            seq: 1,
+            origin: ChangeOrigin::Local,
+            change: StoreChange::MessagesPruned {
+                channel_id: channel.clone(),
+                message_ids: vec![old.id.clone()],
+            },
+        };
+        observer.on_commit(&pruned);
+        counters.on_commit(&pruned);
+
+        let index = observer.index();
+        let index = index.read().unwrap();
+        assert!(index.search("secret", 10).is_empty());
+        assert_eq!(index.search("fresh", 10).len(), 1);
+        assert_eq!(counters.unread(&channel), 1);
+    }
+}
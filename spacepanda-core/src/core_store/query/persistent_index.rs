@@ -0,0 +1,705 @@
+/*
+    persistent_index.rs - On-disk, incrementally updated search index
+
+    Keeps a SearchIndex in sync with the store like SearchIndexObserver, and
+    also persists it so startup doesn't need to rescan the whole history.
+
+    On-disk layout (all files encrypted with the store key when the store
+    has at-rest encryption enabled):
+    - seg-<id>.idx: a segment, the index changes of a batch of commits
+    - MANIFEST: live segments and the checkpoint they cover
+
+    Write path:
+    1. Commits are applied to the in-memory index and buffered
+    2. Every `segment_size` changes (or on flush) the buffer is written as a
+       new segment
+    3. The manifest is rewritten to include it and advance the checkpoint
+
+    A crash between 2 and 3 leaves a segment the manifest doesn't know
+    about. It is deleted on open, and replaying the commit log from the
+    manifest checkpoint (LocalStore::observe_from) re-creates its changes.
+
+    Segments are merged in the background (see spawn_maintenance) so their
+    number stays small; merging drops removed and superseded messages.
+    Messages pruned by a retention policy are merged out right away, so
+    their plaintext doesn't stay on disk until the next maintenance run.
+*/
+
+use super::observers::indexable_text;
+use super::search_index::SearchIndex;
+use crate::core_store::model::{ChannelId, Message, MessageId, Timestamp, UserId};
+use crate::core_store::store::encryption::EncryptionManager;
+use crate::core_store::store::errors::{StoreError, StoreResult};
+use crate::core_store::store::local_store::LocalStore;
+use crate::core_store::store::observer::{
+    ObserverFilter, ObserverId, StoreChange, StoreEvent, StoreObserver,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+const MANIFEST_FILE: &str = "MANIFEST";
+const SEGMENT_PREFIX: &str = "seg-";
+const SEGMENT_EXTENSION: &str = "idx";
+
+/// Default number of buffered changes per segment
+pub const DEFAULT_SEGMENT_SIZE: usize = 256;
+
+/// A message as stored in a segment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexedDocument {
+    message_id: MessageId,
+    channel_id: ChannelId,
+    sender: UserId,
+    timestamp: Timestamp,
+    plaintext: String,
+}
+
+/// One index change
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum SegmentEntry {
+    /// Index (or re-index) a message
+    Indexed(IndexedDocument),
+    /// Drop a message from the index
+    Removed(MessageId),
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Segment {
+    entries: Vec<SegmentEntry>,
+}
+
+/// Which segments are live and how far they cover the commit log
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Manifest {
+    /// Commit log sequence number to resume replay from
+    checkpoint: u64,
+    /// Live segment IDs, oldest first
+    segments: Vec<u64>,
+    /// ID for the next segment written
+    next_segment: u64,
+}
+
+#[derive(Debug, Default)]
+struct WriterState {
+    manifest: Manifest,
+    /// Changes not yet written to a segment
+    pending: Vec<SegmentEntry>,
+    /// Checkpoint to record once `pending` is written
+    pending_checkpoint: u64,
+}
+
+/// Search index persisted as segments under a directory
+pub struct PersistentSearchIndex {
+    dir: PathBuf,
+    encryption: Option<Arc<EncryptionManager>>,
+    index: Arc<RwLock<SearchIndex>>,
+    state: Mutex<WriterState>,
+    segment_size: usize,
+}
+
+impl PersistentSearchIndex {
+    /// Open the index stored in `dir`, loading its segments
+    ///
+    /// An index that cannot be read (missing or corrupt segment, wrong key)
+    /// is discarded and starts empty at checkpoint 0, so the next
+    /// `attach` rebuilds it from the commit log.
+    pub fn open(
+        dir: impl Into<PathBuf>,
+        encryption: Option<Arc<EncryptionManager>>,
+        segment_size: usize,
+    ) -> StoreResult<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        let index = Self {
+            dir,
+            encryption,
+            index: Arc::new(RwLock::new(SearchIndex::new())),
+            state: Mutex::new(WriterState::default()),
+            segment_size: segment_size.max(1),
+        };
+
+        let manifest = match index.load_segments() {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                tracing::warn!(error = %e, "Search index unreadable, rebuilding from scratch");
+                index.write().clear();
+                Manifest::default()
+            }
+        };
+        index.remove_stray_segments(&manifest.segments)?;
+
+        let mut state = index.lock();
+        state.pending_checkpoint = manifest.checkpoint;
+        state.manifest = manifest;
+        drop(state);
+
+        Ok(index)
+    }
+
+    /// Filter selecting the events this index needs
+    pub fn filter() -> ObserverFilter {
+        super::observers::SearchIndexObserver::filter()
+    }
+
+    /// Shared handle to the in-memory index
+    pub fn index(&self) -> Arc<RwLock<SearchIndex>> {
+        self.index.clone()
+    }
+
+    /// Commit log sequence number covered by the segments on disk
+    pub fn checkpoint(&self) -> u64 {
+        self.lock().manifest.checkpoint
+    }
+
+    /// Number of live segments
+    pub fn segment_count(&self) -> usize {
+        self.lock().manifest.segments.len()
+    }
+
+    /// Register with the store and catch up on commits since the checkpoint
+    ///
+    /// Returns the observer ID for unregistering.
+    pub fn attach(self: &Arc<Self>, store: &LocalStore) -> StoreResult<ObserverId> {
+        // Register first so nothing committed during the replay is missed;
+        // indexing is idempotent, so overlap is harmless
+        let id = store.register_observer(Self::filter(), self.clone());
+        let next = store.observe_from(self.checkpoint(), &Self::filter(), self.as_ref())?;
+        {
+            let mut state = self.lock();
+            state.pending_checkpoint = state.pending_checkpoint.max(next);
+        }
+        self.flush()?;
+        Ok(id)
+    }
+
+    /// Discard the index and rebuild it from the whole commit log
+    ///
+    /// `progress` is called with (entries replayed, total entries).
+    pub fn rebuild(
+        &self,
+        store: &LocalStore,
+        progress: &(dyn Fn(u64, u64) + Sync),
+    ) -> StoreResult<()> {
+        {
+            let mut state = self.lock();
+            let old_segments = std::mem::take(&mut state.manifest.segments);
+            state.manifest.checkpoint = 0;
+            state.pending.clear();
+            state.pending_checkpoint = 0;
+            self.write().clear();
+            self.write_manifest(&state.manifest)?;
+            for id in old_segments {
+                self.delete_segment(id)?;
+            }
+        }
+
+        let total = store.checkpoint()?;
+        let replay = RebuildReplay { index: self, total, progress };
+        store.observe_from(0, &ObserverFilter::all(), &replay)?;
+        progress(total, total);
+
+        self.flush()
+    }
+
+    /// Write buffered changes as a segment and advance the checkpoint
+    pub fn flush(&self) -> StoreResult<()> {
+        let mut state = self.lock();
+        self.flush_locked(&mut state)
+    }
+
+    /// Merge all live segments into one
+    ///
+    /// Removed and superseded messages are dropped. Returns false if there
+    /// was nothing to merge.
+    pub fn merge_segments(&self) -> StoreResult<bool> {
+        let mut state = self.lock();
+        if state.manifest.segments.len() < 2 {
+            return Ok(false);
+        }
+
+        // Latest version of every message, in first-seen order
+        let mut order: Vec<MessageId> = Vec::new();
+        let mut live: HashMap<MessageId, Option<IndexedDocument>> = HashMap::new();
+        for id in &state.manifest.segments {
+            for entry in self.read_segment(*id)?.entries {
+                let (message_id, document) = match entry {
+                    SegmentEntry::Indexed(document) => {
+                        (document.message_id.clone(), Some(document))
+                    }
+                    SegmentEntry::Removed(message_id) => (message_id, None),
+                };
+                if !live.contains_key(&message_id) {
+                    order.push(message_id.clone());
+                }
+                live.insert(message_id, document);
+            }
+        }
+
+        let merged = Segment {
+            entries: order
+                .into_iter()
+                .filter_map(|id| live.remove(&id).flatten())
+                .map(SegmentEntry::Indexed)
+                .collect(),
+        };
+
+        let merged_id = state.manifest.next_segment;
+        self.write_segment(merged_id, &merged)?;
+
+        let old_segments = std::mem::replace(&mut state.manifest.segments, vec![merged_id]);
+        state.manifest.next_segment += 1;
+        self.write_manifest(&state.manifest)?;
+
+        for id in old_segments {
+            self.delete_segment(id)?;
+        }
+
+        tracing::debug!(
+            segment = merged_id,
+            documents = merged.entries.len(),
+            "Merged search index segments"
+        );
+        Ok(true)
+    }
+
+    /// Periodically flush buffered changes and merge segments
+    ///
+    /// Segments are merged once there are more than `max_segments`.
+    pub fn spawn_maintenance(
+        self: Arc<Self>,
+        interval: Duration,
+        max_segments: usize,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+
+                let index = self.clone();
+                let result = tokio::task::spawn_blocking(move || {
+                    index.flush()?;
+                    if index.segment_count() > max_segments {
+                        index.merge_segments()?;
+                    }
+                    Ok::<_, StoreError>(())
+                })
+                .await;
+
+                match result {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => tracing::warn!(error = %e, "Search index maintenance failed"),
+                    Err(e) => tracing::error!(error = %e, "Search index maintenance panicked"),
+                }
+            }
+        })
+    }
+
+    /// Apply a committed message to the in-memory index and buffer it
+    fn apply(&self, seq: u64, message: &Message) {
+        let mut state = self.lock();
+
+        // Re-indexing replaces any earlier version of the message
+        let mut index = self.write();
+        index.remove_message(&message.id);
+        let entry = match indexable_text(message) {
+            Some(plaintext) => {
+                index.index_message(
+                    message.id.clone(),
+                    message.channel_id.clone(),
+                    message.sender.clone(),
+                    message.timestamp,
+                    plaintext.to_string(),
+                );
+                SegmentEntry::Indexed(IndexedDocument {
+                    message_id: message.id.clone(),
+                    channel_id: message.channel_id.clone(),
+                    sender: message.sender.clone(),
+                    timestamp: message.timestamp,
+                    plaintext: plaintext.to_string(),
+                })
+            }
+            None => SegmentEntry::Removed(message.id.clone()),
+        };
+        drop(index);
+
+        state.pending.push(entry);
+        state.pending_checkpoint = state.pending_checkpoint.max(seq + 1);
+
+        if state.pending.len() >= self.segment_size {
+            if let Err(e) = self.flush_locked(&mut state) {
+                // Changes stay buffered; the next flush retries
+                tracing::warn!(error = %e, "Failed to write search index segment");
+            }
+        }
+    }
+
+    /// Drop pruned messages from the index and from the segments on disk
+    fn prune(&self, seq: u64, message_ids: &[MessageId]) {
+        let mut state = self.lock();
+        let mut index = self.write();
+        for message_id in message_ids {
+            index.remove_message(message_id);
+            state.pending.push(SegmentEntry::Removed(message_id.clone()));
+        }
+        drop(index);
+        state.pending_checkpoint = state.pending_checkpoint.max(seq + 1);
+
+        let result = self.flush_locked(&mut state);
+        drop(state);
+        if let Err(e) = result.and_then(|()| self.merge_segments().map(drop)) {
+            // The next flush or maintenance run retries
+            tracing::warn!(error = %e, "Failed to merge pruned messages out of the search index");
+        }
+    }
+
+    fn flush_locked(&self, state: &mut WriterState) -> StoreResult<()> {
+        if state.pending.is_empty() {
+            if state.pending_checkpoint > state.manifest.checkpoint {
+                // Replayed commits that didn't touch the index still advance it
+                state.manifest.checkpoint = state.pending_checkpoint;
+                self.write_manifest(&state.manifest)?;
+            }
+            return Ok(());
+        }
+
+        let id = state.manifest.next_segment;
+        let segment = Segment { entries: std::mem::take(&mut state.pending) };
+        if let Err(e) = self.write_segment(id, &segment) {
+            state.pending = segment.entries;
+            return Err(e);
+        }
+
+        // The segment only becomes live once the manifest lists it
+        let mut manifest = state.manifest.clone();
+        manifest.segments.push(id);
+        manifest.next_segment += 1;
+        manifest.checkpoint = state.pending_checkpoint;
+        self.write_manifest(&manifest)?;
+        state.manifest = manifest;
+
+        Ok(())
+    }
+
+    /// Load the manifest and apply its segments to the in-memory index
+    fn load_segments(&self) -> StoreResult<Manifest> {
+        let path = self.dir.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(Manifest::default());
+        }
+        let manifest: Manifest = bincode::deserialize(&self.read_file(&path)?)?;
+
+        let mut index = self.write();
+        for id in &manifest.segments {
+            for entry in self.read_segment(*id)?.entries {
+                match entry {
+                    SegmentEntry::Indexed(document) => {
+                        index.remove_message(&document.message_id);
+                        index.index_message(
+                            document.message_id,
+                            document.channel_id,
+                            document.sender,
+                            document.timestamp,
+                            document.plaintext,
+                        );
+                    }
+                    SegmentEntry::Removed(message_id) => index.remove_message(&message_id),
+                }
+            }
+        }
+
+        Ok(manifest)
+    }
+
+    /// Delete segment files not listed in the manifest
+    fn remove_stray_segments(&self, live: &[u64]) -> StoreResult<()> {
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if let Some(id) = segment_id(&path) {
+                if !live.contains(&id) {
+                    tracing::debug!(segment = id, "Removing unreferenced search index segment");
+                    fs::remove_file(&path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn segment_path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{}{:08}.{}", SEGMENT_PREFIX, id, SEGMENT_EXTENSION))
+    }
+
+    fn read_segment(&self, id: u64) -> StoreResult<Segment> {
+        Ok(bincode::deserialize(&self.read_file(&self.segment_path(id))?)?)
+    }
+
+    fn write_segment(&self, id: u64, segment: &Segment) -> StoreResult<()> {
+        self.write_file(&self.segment_path(id), &bincode::serialize(segment)?)
+    }
+
+    fn delete_segment(&self, id: u64) -> StoreResult<()> {
+        match fs::remove_file(self.segment_path(id)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn write_manifest(&self, manifest: &Manifest) -> StoreResult<()> {
+        self.write_file(&self.dir.join(MANIFEST_FILE), &bincode::serialize(manifest)?)
+    }
+
+    fn read_file(&self, path: &Path) -> StoreResult<Vec<u8>> {
+        let data = fs::read(path)?;
+        match &self.encryption {
+            Some(encryption) => encryption.decrypt(&data),
+            None => Ok(data),
+        }
+    }
+
+    fn write_file(&self, path: &Path, data: &[u8]) -> StoreResult<()> {
+        let data = match &self.encryption {
+            Some(encryption) => encryption.encrypt(data)?,
+            None => data.to_vec(),
+        };
+
+        // Atomic write: temp file then rename
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, WriterState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, SearchIndex> {
+        self.index.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl StoreObserver for PersistentSearchIndex {
+    fn on_commit(&self, event: &StoreEvent) {
+        match &event.change {
+            StoreChange::Message(message) => self.apply(event.seq, message),
+            StoreChange::MessagesPruned { message_ids, .. } => self.prune(event.seq, message_ids),
+            _ => {
+                let mut state = self.lock();
+                state.pending_checkpoint = state.pending_checkpoint.max(event.seq + 1);
+            }
+        }
+    }
+}
+
+/// Replays every commit into the index, reporting progress
+struct RebuildReplay<'a> {
+    index: &'a PersistentSearchIndex,
+    total: u64,
+    progress: &'a (dyn Fn(u64, u64) + Sync),
+}
+
+impl StoreObserver for RebuildReplay<'_> {
+    fn on_commit(&self, event: &StoreEvent) {
+        self.index.on_commit(event);
+        (self.progress)(event.seq + 1, self.total);
+    }
+}
+
+/// Segment ID encoded in a segment file name
+fn segment_id(path: &Path) -> Option<u64> {
+    if path.extension()? != SEGMENT_EXTENSION {
+        return None;
+    }
+    path.file_stem()?.to_str()?.strip_prefix(SEGMENT_PREFIX)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core_store::store::local_store::LocalStoreConfig;
+    use tempfile::tempdir;
+
+    fn test_store(dir: &Path) -> LocalStore {
+        LocalStore::new(LocalStoreConfig {
+            data_dir: dir.to_path_buf(),
+            enable_encryption: false,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    fn store_messages(store: &LocalStore, channel_id: &ChannelId, bodies: &[&str]) -> Vec<Message> {
+        let sender = UserId("alice".to_string());
+        bodies
+            .iter()
+            .map(|body| {
+                let message = Message::new(
+                    MessageId::generate(),
+                    channel_id.clone(),
+                    sender.clone(),
+                    body.as_bytes().to_vec(),
+                    Timestamp::now(),
+                );
+                store.store_message(&message).unwrap();
+                message
+            })
+            .collect()
+    }
+
+    /// Sorted message IDs matching a query
+    fn hits(index: &PersistentSearchIndex, query: &str) -> Vec<MessageId> {
+        let mut ids: Vec<MessageId> = index
+            .index()
+            .read()
+            .unwrap()
+            .search(query, 100)
+            .into_iter()
+            .map(|r| r.message_id)
+            .collect();
+        ids.sort_by(|a, b| a.0.cmp(&b.0));
+        ids
+    }
+
+    #[test]
+    fn test_reopen_loads_segments_without_replay() {
+        let dir = tempdir().unwrap();
+        let store = test_store(dir.path());
+        let channel_id = ChannelId::generate();
+
+        let index =
+            Arc::new(PersistentSearchIndex::open(dir.path().join("search"), None, 2).unwrap());
+        index.attach(&store).unwrap();
+        store_messages(&store, &channel_id, &["pancake one", "pancake two", "waffle"]);
+        index.flush().unwrap();
+        assert_eq!(index.segment_count(), 2);
+        assert_eq!(index.checkpoint(), 3);
+
+        let reopened = PersistentSearchIndex::open(dir.path().join("search"), None, 2).unwrap();
+        assert_eq!(reopened.checkpoint(), 3);
+        assert_eq!(hits(&reopened, "pancake"), hits(&index, "pancake"));
+        assert_eq!(hits(&reopened, "pancake").len(), 2);
+    }
+
+    #[test]
+    fn test_crash_between_segment_write_and_checkpoint() {
+        let dir = tempdir().unwrap();
+        let store = test_store(dir.path());
+        let channel_id = ChannelId::generate();
+        let index_dir = dir.path().join("search");
+
+        let index = Arc::new(PersistentSearchIndex::open(&index_dir, None, 100).unwrap());
+        let observer_id = index.attach(&store).unwrap();
+        store_messages(&store, &channel_id, &["before the crash"]);
+        index.flush().unwrap();
+
+        // Simulate a crash after writing the next segment but before the
+        // manifest update: write the segment, then restore the old manifest
+        let manifest = fs::read(index_dir.join(MANIFEST_FILE)).unwrap();
+        store_messages(&store, &channel_id, &["during the crash"]);
+        index.flush().unwrap();
+        fs::write(index_dir.join(MANIFEST_FILE), manifest).unwrap();
+        store.unregister_observer(observer_id);
+        drop(index);
+        assert!(index_dir.join("seg-00000001.idx").exists());
+
+        let recovered = Arc::new(PersistentSearchIndex::open(&index_dir, None, 100).unwrap());
+        assert!(!index_dir.join("seg-00000001.idx").exists(), "stray segment removed");
+        assert_eq!(recovered.checkpoint(), 1);
+        assert!(hits(&recovered, "during").is_empty());
+
+        recovered.attach(&store).unwrap();
+        assert_eq!(recovered.checkpoint(), 2);
+        assert_eq!(hits(&recovered, "crash").len(), 2);
+    }
+
+    #[test]
+    fn test_merge_keeps_search_results() {
+        let dir = tempdir().unwrap();
+        let store = test_store(dir.path());
+        let channel_id = ChannelId::generate();
+        let index_dir = dir.path().join("search");
+
+        let index = Arc::new(PersistentSearchIndex::open(&index_dir, None, 2).unwrap());
+        index.attach(&store).unwrap();
+        let messages =
+            store_messages(&store, &channel_id, &["alpha beta", "beta gamma", "gamma delta"]);
+
+        // Edit one message and delete another so merging has to resolve them
+        let mut edited = messages[0].clone();
+        edited
+            .edits
+            .push((Timestamp::now(), edited.sender.clone(), b"alpha omega".to_vec()));
+        store.store_message(&edited).unwrap();
+        let mut deleted = messages[1].clone();
+        deleted.deleted = true;
+        store.store_message(&deleted).unwrap();
+        index.flush().unwrap();
+
+        let queries = ["alpha", "beta", "gamma", "delta", "omega"];
+        let before: Vec<_> = queries.iter().map(|q| hits(&index, q)).collect();
+        assert!(index.segment_count() > 1);
+
+        assert!(index.merge_segments().unwrap());
+        assert_eq!(index.segment_count(), 1);
+        assert!(!index.merge_segments().unwrap());
+
+        let reopened = PersistentSearchIndex::open(&index_dir, None, 2).unwrap();
+        let after: Vec<_> = queries.iter().map(|q| hits(&reopened, q)).collect();
+        assert_eq!(before, after);
+        assert_eq!(reopened.checkpoint(), index.checkpoint());
+    }
+
+    #[test]
+    fn test_rebuild_matches_incremental_index() {
+        let dir = tempdir().unwrap();
+        let store = test_store(dir.path());
+        let channel_id = ChannelId::generate();
+
+        let incremental =
+            Arc::new(PersistentSearchIndex::open(dir.path().join("a"), None, 3).unwrap());
+        incremental.attach(&store).unwrap();
+        store_messages(&store, &channel_id, &["one fish", "two fish", "red fish", "blue fish"]);
+        incremental.flush().unwrap();
+
+        let rebuilt = PersistentSearchIndex::open(dir.path().join("b"), None, 3).unwrap();
+        let reports = Mutex::new(Vec::new());
+        rebuilt
+            .rebuild(&store, &|done, total| reports.lock().unwrap().push((done, total)))
+            .unwrap();
+
+        for query in ["fish", "red", "blue", "one"] {
+            assert_eq!(hits(&rebuilt, query), hits(&incremental, query));
+        }
+        assert_eq!(rebuilt.checkpoint(), incremental.checkpoint());
+        assert_eq!(reports.lock().unwrap().last(), Some(&(4, 4)));
+    }
+
+    #[test]
+    fn test_encrypted_index_unreadable_with_other_key() {
+        let dir = tempdir().unwrap();
+        let store = test_store(dir.path());
+        let index_dir = dir.path().join("search");
+
+        let key = Arc::new(EncryptionManager::new().unwrap());
+        let index =
+            Arc::new(PersistentSearchIndex::open(&index_dir, Some(key.clone()), 1).unwrap());
+        index.attach(&store).unwrap();
+        store_messages(&store, &ChannelId::generate(), &["secret plans"]);
+
+        let segment = fs::read(index_dir.join("seg-00000000.idx")).unwrap();
+        assert!(!segment.windows(6).any(|w| w == b"secret"));
+
+        let same_key = PersistentSearchIndex::open(&index_dir, Some(key), 1).unwrap();
+        assert_eq!(hits(&same_key, "secret").len(), 1);
+
+        // A different key can't read it and starts over from the log
+        let other_key = Arc::new(EncryptionManager::new().unwrap());
+        let other = PersistentSearchIndex::open(&index_dir, Some(other_key), 1).unwrap();
+        assert_eq!(other.checkpoint(), 0);
+        assert!(hits(&other, "secret").is_empty());
+    }
+}
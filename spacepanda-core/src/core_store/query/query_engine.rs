@@ -10,13 +10,29 @@
     - Search messages
     - Filter by user/time/role
     - Thread reconstruction
+    - Cursor pagination of spaces, channels and messages
+
+    Messages of a channel are kept ordered by (timestamp, message ID). A
+    cursor is the key of the last item a page returned and the next page
+    starts right after it, so messages inserted while a client pages
+    through never shift it into duplicates or gaps; one inserted behind the
+    cursor is just not part of that pass. Filters on the time range narrow
+    the walk over the index instead of scanning it. Pages carry a hint of
+    the total number of matches: exact where an index has the count (no
+    filter, or only a sender), otherwise a lower bound. A cursor taken from
+    another kind of listing is refused with `StoreError::InvalidCursor`
+    rather than restarting from the first page.
 */
 
+use super::observers::UnreadCounters;
 use crate::core_store::model::{
     Channel, ChannelId, Message, MessageId, Space, SpaceId, Timestamp, UserId,
 };
+use crate::core_store::store::errors::{StoreError, StoreResult};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+use std::sync::Arc;
 
 /// Query results for channels
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -53,12 +69,315 @@ pub struct SpaceInfo {
 }
 
 /// Sorting options for queries
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+///
+/// Spaces and channels sort by name, messages by timestamp; ties are
+/// broken by ID.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SortOrder {
+    #[default]
     Ascending,
     Descending,
 }
 
+/// Position of a message in its channel's index
+type MessageKey = (Timestamp, String);
+
+/// Sort key of a listed item
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+enum SortKey {
+    /// Messages: timestamp, then message ID
+    Time(Timestamp, String),
+    /// Spaces and channels: name, then ID
+    Name(String, String),
+}
+
+/// Where the next page of a listing starts
+///
+/// Opaque to clients: pass back the `next` cursor of the previous page.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Cursor(SortKey);
+
+/// Which page of a listing to return
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageRequest {
+    /// Items per page
+    pub limit: usize,
+
+    /// Continue after this cursor (`None` for the first page)
+    pub after: Option<Cursor>,
+
+    /// Order of the items
+    pub order: SortOrder,
+}
+
+impl PageRequest {
+    /// First page of `limit` items in ascending order
+    pub fn first(limit: usize) -> Self {
+        PageRequest { limit, after: None, order: SortOrder::Ascending }
+    }
+
+    /// The same listing in the given order
+    pub fn order(mut self, order: SortOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// The page after `page` of the same listing
+    pub fn next<T>(&self, page: &Page<T>) -> Option<Self> {
+        let after = page.next.clone()?;
+        Some(PageRequest { after: Some(after), ..self.clone() })
+    }
+}
+
+/// How many items match a listing in total
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TotalHint {
+    /// Known from an index
+    Exact(usize),
+
+    /// Counting would take a full scan; at least this many match
+    AtLeast(usize),
+}
+
+/// One page of a listing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+
+    /// Cursor for the next page, `None` on the last one
+    pub next: Option<Cursor>,
+
+    pub total: TotalHint,
+}
+
+/// What a message is, for filtering
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MessageKind {
+    /// A message that isn't a reply
+    Post,
+    /// A reply to another message
+    Reply,
+    /// A message deleted by its sender
+    Deleted,
+}
+
+impl MessageKind {
+    fn of(message: &Message) -> Self {
+        if message.deleted {
+            MessageKind::Deleted
+        } else if message.reply_to.is_some() {
+            MessageKind::Reply
+        } else {
+            MessageKind::Post
+        }
+    }
+}
+
+/// Conditions a listed message must meet; unset fields match everything
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MessageFilter {
+    pub sender: Option<UserId>,
+
+    /// Sent at or after
+    pub since: Option<Timestamp>,
+
+    /// Sent at or before
+    pub until: Option<Timestamp>,
+
+    pub kind: Option<MessageKind>,
+
+    pub has_attachment: Option<bool>,
+}
+
+impl MessageFilter {
+    fn matches(&self, message: &Message) -> bool {
+        self.sender.as_ref().is_none_or(|sender| &message.sender == sender)
+            && self.since.is_none_or(|since| message.timestamp >= since)
+            && self.until.is_none_or(|until| message.timestamp <= until)
+            && self.kind.is_none_or(|kind| MessageKind::of(message) == kind)
+            && self
+                .has_attachment
+                .is_none_or(|has_attachment| message.attachments.is_empty() != has_attachment)
+    }
+
+    /// Only the sender is set, so the per-sender counts have the total
+    fn sender_only(&self) -> bool {
+        self.since.is_none()
+            && self.until.is_none()
+            && self.kind.is_none()
+            && self.has_attachment.is_none()
+    }
+}
+
+/// Messages of one channel, ordered for pagination
+#[derive(Default)]
+struct ChannelMessages {
+    ordered: BTreeMap<MessageKey, Message>,
+
+    /// Messages per sender, for exact totals of sender-filtered listings
+    per_sender: HashMap<UserId, usize>,
+}
+
+impl ChannelMessages {
+    /// Add a message, replacing the one with the same timestamp and ID
+    fn upsert(&mut self, message: Message) {
+        let key = (message.timestamp, message.id.0.clone());
+        let sender = message.sender.clone();
+        if let Some(old) = self.ordered.insert(key, message) {
+            self.forget_sender(&old.sender);
+        }
+        *self.per_sender.entry(sender).or_default() += 1;
+    }
+
+    fn forget_sender(&mut self, sender: &UserId) {
+        if let Some(count) = self.per_sender.get_mut(sender) {
+            *count -= 1;
+            if *count == 0 {
+                self.per_sender.remove(sender);
+            }
+        }
+    }
+
+    fn iter(&self) -> impl DoubleEndedIterator<Item = &Message> {
+        self.ordered.values()
+    }
+
+    fn last(&self) -> Option<&Message> {
+        self.ordered.values().next_back()
+    }
+
+    /// Messages in `order`, after `after`, within the filter's time range
+    fn walk<'a>(
+        &'a self,
+        filter: &MessageFilter,
+        order: SortOrder,
+        after: Option<&MessageKey>,
+    ) -> Box<dyn Iterator<Item = &'a Message> + 'a> {
+        // The time range bounds the walk; keys of a timestamp start at ""
+        let mut lower = filter
+            .since
+            .map_or(Bound::Unbounded, |since| Bound::Included((since, String::new())));
+        let mut upper = Bound::Unbounded;
+        let until = filter.until;
+
+        if let Some(after) = after {
+            match order {
+                SortOrder::Ascending => {
+                    if !matches!(&lower, Bound::Included(since) if since > after) {
+                        lower = Bound::Excluded(after.clone());
+                    }
+                }
+                SortOrder::Descending => upper = Bound::Excluded(after.clone()),
+            }
+        }
+        if let (Bound::Included(low) | Bound::Excluded(low), Bound::Excluded(high)) =
+            (&lower, &upper)
+        {
+            if low >= high {
+                return Box::new(std::iter::empty());
+            }
+        }
+
+        let range = self.ordered.range((lower, upper)).map(|(_, message)| message);
+        match order {
+            SortOrder::Ascending => {
+                Box::new(range.take_while(move |message| {
+                    until.is_none_or(|until| message.timestamp <= until)
+                }))
+            }
+            SortOrder::Descending => {
+                Box::new(range.rev().skip_while(move |message| {
+                    until.is_some_and(|until| message.timestamp > until)
+                }))
+            }
+        }
+    }
+}
+
+/// One page of `items`, already in listing order and past the cursor
+///
+/// Takes one item more than the page to know whether another page follows.
+fn paginate<'a, I, T: 'a, R>(
+    items: I,
+    request: &PageRequest,
+    key: impl Fn(&T) -> SortKey,
+    info: impl Fn(&T) -> R,
+) -> (Vec<R>, Option<Cursor>)
+where
+    I: Iterator<Item = &'a T>,
+{
+    let mut items = items.peekable();
+    let mut page = Vec::new();
+    let mut last = None;
+    while page.len() < request.limit {
+        let Some(item) = items.next() else {
+            break;
+        };
+        page.push(info(item));
+        last = Some(key(item));
+    }
+
+    let next = if items.peek().is_some() {
+        last.map(Cursor)
+    } else {
+        None
+    };
+    (page, next)
+}
+
+fn message_info(msg: &Message) -> MessageInfo {
+    MessageInfo {
+        id: msg.id.clone(),
+        sender: msg.sender.clone(),
+        content: msg.current_content().to_vec(),
+        timestamp: msg.timestamp,
+        is_edited: msg.is_edited(),
+        reply_to: msg.reply_to.clone(),
+        reaction_count: msg.reactions.len(),
+    }
+}
+
+/// Items sorted by name and ID in `order`, after the cursor
+fn sorted_by_name<'a, T>(
+    items: impl Iterator<Item = &'a T>,
+    key: impl Fn(&T) -> SortKey,
+    request: &PageRequest,
+) -> StoreResult<Vec<&'a T>> {
+    let after = match &request.after {
+        None => None,
+        Some(Cursor(key @ SortKey::Name(..))) => Some(key),
+        Some(_) => return Err(invalid_cursor("a name-ordered listing")),
+    };
+
+    let mut items: Vec<(SortKey, &T)> = items.map(|item| (key(item), item)).collect();
+    items.sort_by(|a, b| a.0.cmp(&b.0));
+    if request.order == SortOrder::Descending {
+        items.reverse();
+    }
+
+    Ok(items
+        .into_iter()
+        .filter(|(key, _)| match (after, request.order) {
+            (None, _) => true,
+            (Some(after), SortOrder::Ascending) => key > after,
+            (Some(after), SortOrder::Descending) => key < after,
+        })
+        .map(|(_, item)| item)
+        .collect())
+}
+
+fn invalid_cursor(listing: &str) -> StoreError {
+    StoreError::InvalidCursor(format!("cursor is not from {}", listing))
+}
+
+fn space_key(space: &Space) -> SortKey {
+    SortKey::Name(space.get_name().cloned().unwrap_or_default(), space.id.0.clone())
+}
+
+fn channel_key(channel: &Channel) -> SortKey {
+    SortKey::Name(channel.get_name().cloned().unwrap_or_default(), channel.id.0.clone())
+}
+
 /// Query engine for read-only access
 pub struct QueryEngine {
     /// Cache of spaces
@@ -68,12 +387,25 @@ pub struct QueryEngine {
     channels: HashMap<ChannelId, Channel>,
 
     /// Cache of messages by channel
-    messages: HashMap<ChannelId, Vec<Message>>,
+    messages: HashMap<ChannelId, ChannelMessages>,
+
+    /// Unread counts, maintained by a store observer
+    unread: Option<Arc<UnreadCounters>>,
 }
 
 impl QueryEngine {
     pub fn new() -> Self {
-        QueryEngine { spaces: HashMap::new(), channels: HashMap::new(), messages: HashMap::new() }
+        QueryEngine {
+            spaces: HashMap::new(),
+            channels: HashMap::new(),
+            messages: HashMap::new(),
+            unread: None,
+        }
+    }
+
+    /// Report unread counts from the given counters
+    pub fn set_unread_counters(&mut self, unread: Arc<UnreadCounters>) {
+        self.unread = Some(unread);
     }
 
     /// Add a space to the query cache
@@ -88,22 +420,23 @@ impl QueryEngine {
 
     /// Add messages to the query cache
     pub fn add_messages(&mut self, channel_id: ChannelId, messages: Vec<Message>) {
-        self.messages.insert(channel_id, messages);
+        let mut channel_messages = ChannelMessages::default();
+        for message in messages {
+            channel_messages.upsert(message);
+        }
+        self.messages.insert(channel_id, channel_messages);
+    }
+
+    /// Add one message to the query cache, or update it (e.g. after an edit)
+    ///
+    /// Listings being paged through stay consistent.
+    pub fn add_message(&mut self, message: Message) {
+        self.messages.entry(message.channel_id.clone()).or_default().upsert(message);
     }
 
     /// List all spaces
     pub fn list_spaces(&self) -> Vec<SpaceInfo> {
-        self.spaces
-            .values()
-            .map(|space| SpaceInfo {
-                id: space.id.clone(),
-                name: space.get_name().unwrap_or(&String::new()).clone(),
-                description: space.get_description().cloned(),
-                member_count: space.get_members().len(),
-                channel_count: space.get_channels().len(),
-                role_count: space.roles.len(),
-            })
-            .collect()
+        self.spaces.values().map(Self::space_info).collect()
     }
 
     /// Get a specific space by ID
@@ -122,25 +455,103 @@ impl QueryEngine {
 
         channel_ids
             .iter()
-            .filter_map(|channel_id| {
-                self.channels.get(channel_id).map(|channel| {
-                    let empty_vec = Vec::new();
-                    let messages = self.messages.get(&channel.id).unwrap_or(&empty_vec);
-                    let last_message_time = messages.last().map(|m| m.timestamp);
-
-                    ChannelInfo {
-                        id: channel.id.clone(),
-                        name: channel.get_name().unwrap_or(&String::new()).clone(),
-                        topic: channel.get_topic().cloned(),
-                        member_count: channel.get_members().len(),
-                        unread_count: 0, // TODO: Track read positions
-                        last_message_time,
-                    }
-                })
-            })
+            .filter_map(|channel_id| self.channels.get(channel_id))
+            .map(|channel| self.channel_info(channel))
             .collect()
     }
 
+    fn channel_info(&self, channel: &Channel) -> ChannelInfo {
+        let last_message_time = self
+            .messages
+            .get(&channel.id)
+            .and_then(|messages| messages.last())
+            .map(|m| m.timestamp);
+
+        ChannelInfo {
+            id: channel.id.clone(),
+            name: channel.get_name().unwrap_or(&String::new()).clone(),
+            topic: channel.get_topic().cloned(),
+            member_count: channel.get_members().len(),
+            unread_count: self.unread.as_ref().map_or(0, |unread| unread.unread(&channel.id)),
+            last_message_time,
+        }
+    }
+
+    fn space_info(space: &Space) -> SpaceInfo {
+        SpaceInfo {
+            id: space.id.clone(),
+            name: space.get_name().unwrap_or(&String::new()).clone(),
+            description: space.get_description().cloned(),
+            member_count: space.get_members().len(),
+            channel_count: space.get_channels().len(),
+            role_count: space.roles.len(),
+        }
+    }
+
+    /// Page through all spaces, by name
+    pub fn list_spaces_page(&self, request: &PageRequest) -> StoreResult<Page<SpaceInfo>> {
+        let spaces = sorted_by_name(self.spaces.values(), space_key, request)?;
+        let (items, next) = paginate(spaces.into_iter(), request, space_key, Self::space_info);
+        Ok(Page { items, next, total: TotalHint::Exact(self.spaces.len()) })
+    }
+
+    /// Page through the channels of a space, by name
+    pub fn list_channels_page(
+        &self,
+        space_id: &SpaceId,
+        request: &PageRequest,
+    ) -> StoreResult<Page<ChannelInfo>> {
+        let Some(space) = self.spaces.get(space_id) else {
+            return Ok(Page { items: Vec::new(), next: None, total: TotalHint::Exact(0) });
+        };
+
+        let ids = space.get_channels();
+        let channels = ids.iter().filter_map(|id| self.channels.get(id));
+        let channels = sorted_by_name(channels, channel_key, request)?;
+        let (items, next) = paginate(channels.into_iter(), request, channel_key, |channel| {
+            self.channel_info(channel)
+        });
+        Ok(Page { items, next, total: TotalHint::Exact(ids.len()) })
+    }
+
+    /// Page through the messages of a channel that match `filter`, by time
+    ///
+    /// Stable while messages are added: see the module docs.
+    pub fn list_messages_page(
+        &self,
+        channel_id: &ChannelId,
+        filter: &MessageFilter,
+        request: &PageRequest,
+    ) -> StoreResult<Page<MessageInfo>> {
+        let after = match &request.after {
+            None => None,
+            Some(Cursor(SortKey::Time(timestamp, id))) => Some((*timestamp, id.clone())),
+            Some(_) => return Err(invalid_cursor("a message listing")),
+        };
+        let Some(messages) = self.messages.get(channel_id) else {
+            return Ok(Page { items: Vec::new(), next: None, total: TotalHint::Exact(0) });
+        };
+
+        let matching = messages
+            .walk(filter, request.order, after.as_ref())
+            .filter(|message| filter.matches(message));
+        let (items, next) = paginate(
+            matching,
+            request,
+            |message| SortKey::Time(message.timestamp, message.id.0.clone()),
+            message_info,
+        );
+
+        let total = match &filter.sender {
+            _ if !filter.sender_only() => {
+                TotalHint::AtLeast(items.len() + usize::from(next.is_some()))
+            }
+            None => TotalHint::Exact(messages.ordered.len()),
+            Some(sender) => TotalHint::Exact(messages.per_sender.get(sender).copied().unwrap_or(0)),
+        };
+        Ok(Page { items, next, total })
+    }
+
     /// Get a specific channel by ID
     pub fn get_channel(&self, channel_id: &ChannelId) -> Option<&Channel> {
         self.channels.get(channel_id)
@@ -162,7 +573,7 @@ impl QueryEngine {
         let result: Vec<MessageInfo> = messages
             .iter()
             .skip(offset)
-            .take(limit.unwrap_or(messages.len()))
+            .take(limit.unwrap_or(messages.ordered.len()))
             .map(|msg| MessageInfo {
                 id: msg.id.clone(),
                 sender: msg.sender.clone(),
@@ -298,6 +709,8 @@ impl Default for QueryEngine {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core_store::crdt::{AddId, VectorClock};
+    use crate::core_store::model::message::Attachment;
     use crate::core_store::model::ChannelType;
 
     #[test]
@@ -620,4 +1033,304 @@ mod tests {
         assert_eq!(msgs.len(), 1);
         assert_eq!(msgs[0].timestamp, now);
     }
+
+    fn message_at(channel_id: &ChannelId, sender: &UserId, id: &str, at: u64) -> Message {
+        Message::new(
+            MessageId(id.to_string()),
+            channel_id.clone(),
+            sender.clone(),
+            id.as_bytes().to_vec(),
+            Timestamp::from_millis(at),
+        )
+    }
+
+    /// Every page of a listing, in order
+    fn all_pages(
+        engine: &QueryEngine,
+        channel_id: &ChannelId,
+        filter: &MessageFilter,
+        request: PageRequest,
+    ) -> Vec<Page<MessageInfo>> {
+        let mut pages = Vec::new();
+        let mut request = Some(request);
+        while let Some(current) = request {
+            let page = engine.list_messages_page(channel_id, filter, &current).unwrap();
+            request = current.next(&page);
+            pages.push(page);
+        }
+        pages
+    }
+
+    #[test]
+    fn test_message_pages_honor_sort_order() {
+        let mut engine = QueryEngine::new();
+        let channel_id = ChannelId::generate();
+        let alice = UserId::generate();
+        // Inserted out of order, with a timestamp tie
+        for (id, at) in [("c", 30), ("a", 10), ("d", 30), ("b", 20), ("e", 50)] {
+            engine.add_message(message_at(&channel_id, &alice, id, at));
+        }
+
+        let ids = |order| {
+            all_pages(
+                &engine,
+                &channel_id,
+                &MessageFilter::default(),
+                PageRequest::first(2).order(order),
+            )
+            .iter()
+            .flat_map(|page| page.items.iter().map(|m| m.id.0.clone()))
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(SortOrder::Ascending), ["a", "b", "c", "d", "e"]);
+        assert_eq!(ids(SortOrder::Descending), ["e", "d", "c", "b", "a"]);
+
+        let pages =
+            all_pages(&engine, &channel_id, &MessageFilter::default(), PageRequest::first(2));
+        assert_eq!(pages.len(), 3);
+        assert!(pages.iter().all(|page| page.total == TotalHint::Exact(5)));
+        assert!(pages[2].next.is_none());
+    }
+
+    #[test]
+    fn test_message_filters() {
+        let mut engine = QueryEngine::new();
+        let channel_id = ChannelId::generate();
+        let alice = UserId::generate();
+        let bob = UserId::generate();
+
+        engine.add_message(message_at(&channel_id, &alice, "post", 10));
+        let mut reply = message_at(&channel_id, &bob, "reply", 20);
+        reply.reply_to = Some(MessageId("post".to_string()));
+        engine.add_message(reply);
+        let mut file = message_at(&channel_id, &alice, "file", 30);
+        file.add_attachment(Attachment {
+            id: "att".to_string(),
+            filename: "notes.txt".to_string(),
+            mime_type: "text/plain".to_string(),
+            size_bytes: 5,
+            content_hash: "hash".to_string(),
+        });
+        engine.add_message(file);
+        let mut gone = message_at(&channel_id, &bob, "gone", 40);
+        gone.delete();
+        engine.add_message(gone);
+
+        let list = |filter: MessageFilter, order| {
+            let page = engine
+                .list_messages_page(&channel_id, &filter, &PageRequest::first(10).order(order))
+                .unwrap();
+            let ids: Vec<String> = page.items.iter().map(|m| m.id.0.clone()).collect();
+            (ids, page.total)
+        };
+
+        let by_alice = MessageFilter { sender: Some(alice.clone()), ..Default::default() };
+        assert_eq!(
+            list(by_alice, SortOrder::Ascending),
+            (vec!["post".to_string(), "file".to_string()], TotalHint::Exact(2))
+        );
+
+        let range = MessageFilter {
+            since: Some(Timestamp::from_millis(20)),
+            until: Some(Timestamp::from_millis(30)),
+            ..Default::default()
+        };
+        assert_eq!(list(range.clone(), SortOrder::Ascending).0, ["reply", "file"]);
+        assert_eq!(list(range, SortOrder::Descending).0, ["file", "reply"]);
+
+        let replies = MessageFilter { kind: Some(MessageKind::Reply), ..Default::default() };
+        assert_eq!(
+            list(replies, SortOrder::Ascending),
+            (vec!["reply".to_string()], TotalHint::AtLeast(1))
+        );
+
+        let deleted = MessageFilter { kind: Some(MessageKind::Deleted), ..Default::default() };
+        assert_eq!(list(deleted, SortOrder::Ascending).0, ["gone"]);
+
+        let files = MessageFilter { has_attachment: Some(true), ..Default::default() };
+        assert_eq!(list(files, SortOrder::Descending).0, ["file"]);
+    }
+
+    #[test]
+    fn test_channel_and_space_pages_sort_by_name() {
+        let mut engine = QueryEngine::new();
+        let creator = UserId::generate();
+        let mut space = Space::new(
+            SpaceId::generate(),
+            "Space".to_string(),
+            creator.clone(),
+            Timestamp::now(),
+            "node1".to_string(),
+        );
+        for name in ["ops", "general", "random"] {
+            let channel = Channel::new(
+                ChannelId::generate(),
+                name.to_string(),
+                ChannelType::Text,
+                creator.clone(),
+                Timestamp::now(),
+                "node1".to_string(),
+            );
+            let add_id = AddId::new("node1".to_string(), space.get_channels().len() as u64);
+            space.channels.add(channel.id.clone(), add_id, VectorClock::new());
+            engine.add_channel(channel);
+        }
+        let space_id = space.id.clone();
+        engine.add_space(space);
+
+        let first = engine.list_channels_page(&space_id, &PageRequest::first(2)).unwrap();
+        assert_eq!(first.total, TotalHint::Exact(3));
+        let second_request = PageRequest::first(2).next(&first).unwrap();
+        let second = engine.list_channels_page(&space_id, &second_request).unwrap();
+        let names: Vec<String> =
+            first.items.iter().chain(&second.items).map(|c| c.name.clone()).collect();
+        assert_eq!(names, ["general", "ops", "random"]);
+        assert!(second.next.is_none());
+
+        let descending = engine
+            .list_channels_page(&space_id, &PageRequest::first(1).order(SortOrder::Descending))
+            .unwrap();
+        assert_eq!(descending.items[0].name, "random");
+
+        let spaces = engine.list_spaces_page(&PageRequest::first(10)).unwrap();
+        assert_eq!((spaces.items.len(), spaces.total), (1, TotalHint::Exact(1)));
+
+        // A cursor from the channel listing is refused by a message listing
+        let filter = MessageFilter::default();
+        let result = engine.list_messages_page(&ChannelId::generate(), &filter, &second_request);
+        assert!(matches!(result, Err(StoreError::InvalidCursor(_))));
+    }
+
+    #[test]
+    fn test_paging_while_another_thread_inserts() {
+        let engine = Arc::new(std::sync::RwLock::new(QueryEngine::new()));
+        let channel_id = ChannelId::generate();
+        let alice = UserId::generate();
+        {
+            let mut engine = engine.write().unwrap();
+            for i in 0..10_000u64 {
+                engine.add_message(message_at(
+                    &channel_id,
+                    &alice,
+                    &format!("m{}", i),
+                    i * 3 % 10_007,
+                ));
+            }
+        }
+
+        let writer = {
+            let (engine, channel_id, alice) = (engine.clone(), channel_id.clone(), alice.clone());
+            std::thread::spawn(move || {
+                for i in 0..2_000u64 {
+                    let message =
+                        message_at(&channel_id, &alice, &format!("new{}", i), i * 7 % 10_007);
+                    engine.write().unwrap().add_message(message);
+                }
+            })
+        };
+
+        let mut seen = std::collections::HashSet::new();
+        let mut request = Some(PageRequest::first(37));
+        while let Some(current) = request {
+            let page = engine
+                .read()
+                .unwrap()
+                .list_messages_page(&channel_id, &MessageFilter::default(), &current)
+                .unwrap();
+            for message in &page.items {
+                assert!(seen.insert(message.id.0.clone()), "{} listed twice", message.id);
+            }
+            request = current.next(&page);
+        }
+        writer.join().unwrap();
+
+        assert!((0..10_000).all(|i| seen.contains(&format!("m{}", i))));
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::collections::HashSet;
+
+    const MESSAGES: usize = 10_000;
+
+    fn message(channel_id: &ChannelId, sender: &UserId, id: String, at: u64) -> Message {
+        Message::new(
+            MessageId(id),
+            channel_id.clone(),
+            sender.clone(),
+            Vec::new(),
+            Timestamp::from_millis(at),
+        )
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(8))]
+
+        // Property: paging returns each message present when it started
+        // exactly once, in order, whatever is inserted between pages
+        #[test]
+        fn prop_pagination_has_no_duplicates_or_gaps(
+            timestamps in prop::collection::vec(0..5_000u64, MESSAGES),
+            page_sizes in prop::collection::vec(1..400usize, 1..40),
+            inserts in prop::collection::vec(prop::collection::vec(0..5_000u64, 0..20), 1..40),
+            descending in any::<bool>(),
+            by_sender in any::<bool>(),
+        ) {
+            let channel_id = ChannelId::generate();
+            let senders = [UserId("alice".to_string()), UserId("bob".to_string())];
+            let mut engine = QueryEngine::new();
+            for (i, at) in timestamps.iter().enumerate() {
+                engine.add_message(message(&channel_id, &senders[i % 2], format!("m{}", i), *at));
+            }
+
+            let filter = MessageFilter {
+                sender: by_sender.then(|| senders[0].clone()),
+                ..Default::default()
+            };
+            let order = if descending { SortOrder::Descending } else { SortOrder::Ascending };
+            let expected = if by_sender { MESSAGES / 2 } else { MESSAGES };
+
+            let mut seen = HashSet::new();
+            let mut last: Option<(Timestamp, String)> = None;
+            let mut request = Some(PageRequest::first(page_sizes[0]).order(order));
+            let mut page_index = 0;
+            while let Some(current) = request {
+                let page = engine.list_messages_page(&channel_id, &filter, &current).unwrap();
+                for item in &page.items {
+                    prop_assert!(seen.insert(item.id.0.clone()), "{} listed twice", item.id);
+                    let key = (item.timestamp, item.id.0.clone());
+                    if let Some(last) = &last {
+                        match order {
+                            SortOrder::Ascending => prop_assert!(&key > last),
+                            SortOrder::Descending => prop_assert!(&key < last),
+                        }
+                    }
+                    last = Some(key);
+                    if by_sender {
+                        prop_assert_eq!(&item.sender, &senders[0]);
+                    }
+                }
+
+                // Concurrent writers add messages before the next page
+                for (j, at) in inserts[page_index % inserts.len()].iter().enumerate() {
+                    let id = format!("new{}-{}", page_index, j);
+                    engine.add_message(message(&channel_id, &senders[j % 2], id, *at));
+                }
+
+                page_index += 1;
+                request = current
+                    .next(&page)
+                    .map(|next| PageRequest { limit: page_sizes[page_index % page_sizes.len()], ..next });
+            }
+
+            let original = (0..MESSAGES)
+                .filter(|i| !by_sender || i % 2 == 0)
+                .filter(|i| !seen.contains(&format!("m{}", i)))
+                .count();
+            prop_assert_eq!(original, 0, "messages missing from {} expected", expected);
+        }
+    }
 }
@@ -0,0 +1,722 @@
+/*
+    attachment_cache.rs - Quota-aware cache for attachment blobs
+
+    Attachment blobs live outside the CRDT store (messages only carry the
+    Attachment manifest), so without bookkeeping they accumulate forever.
+    The cache tracks every blob on disk and keeps the total under a byte
+    quota by evicting the least recently used downloaded blobs.
+
+    Rules:
+    - Downloaded blobs are evictable; they are re-fetched transparently on
+      the next access since the manifest stays in message history
+    - Authored blobs are kept until delivery is confirmed to the
+      replication factor, then treated like downloaded ones
+    - Attachments of pinned messages are never evicted
+    - Evicting a blob also purges its derived artifacts (thumbnails, ...)
+
+    Layout under the cache directory:
+    - blobs/<key>          blob contents
+    - derived/<key>/<name> derived artifacts
+    - index.bin            entry metadata and LRU clock
+
+    <key> is the BLAKE3 hash of the attachment ID, so IDs received from
+    peers never become file paths.
+
+    With a storage preflight attached, a download that would not fit on
+    disk (manifest size plus safety margin) is refused before fetching.
+*/
+
+use crate::core_store::model::{Attachment, MessageId};
+use crate::core_store::store::errors::{StoreError, StoreResult};
+use crate::core_store::store::preflight::StoragePreflight;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const INDEX_FILE: &str = "index.bin";
+
+/// Where a cached blob came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlobOrigin {
+    /// Downloaded from a peer
+    Downloaded,
+    /// Authored locally, with the number of peers that confirmed delivery
+    Authored { confirmed_replicas: usize },
+}
+
+/// Metadata for a cached attachment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedAttachment {
+    /// Message the attachment belongs to
+    pub message_id: MessageId,
+
+    /// Attachment manifest
+    pub attachment: Attachment,
+
+    /// Where the blob came from
+    pub origin: BlobOrigin,
+
+    /// Bytes on disk (blob plus derived artifacts)
+    pub size_bytes: u64,
+
+    /// Names of derived artifacts
+    pub derived: Vec<String>,
+
+    /// Logical access time, for LRU ordering
+    last_access: u64,
+}
+
+/// Source used to re-download evicted blobs
+pub trait BlobFetcher {
+    /// Download the blob for an attachment
+    fn fetch(&self, attachment: &Attachment) -> StoreResult<Vec<u8>>;
+}
+
+/// Cache usage summary
+#[derive(Debug, Clone)]
+pub struct AttachmentUsage {
+    /// Bytes currently on disk
+    pub used_bytes: u64,
+
+    /// Configured quota
+    pub quota_bytes: u64,
+
+    /// Number of cached attachments
+    pub entries: usize,
+
+    /// Cached attachments exempt from eviction because they are pinned
+    pub pinned: Vec<CachedAttachment>,
+
+    /// Authored attachments still awaiting delivery confirmation
+    pub pending_delivery: usize,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheIndex {
+    /// Attachment ID -> cached entry
+    entries: HashMap<String, CachedAttachment>,
+
+    /// Messages whose attachments are pinned
+    pinned: HashSet<MessageId>,
+
+    /// Logical clock for access times
+    clock: u64,
+}
+
+impl CacheIndex {
+    fn used_bytes(&self) -> u64 {
+        self.entries.values().map(|e| e.size_bytes).sum()
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+}
+
+/// Quota-aware attachment blob cache
+pub struct AttachmentCache {
+    dir: PathBuf,
+    quota_bytes: u64,
+    replication_factor: usize,
+    preflight: Option<StoragePreflight>,
+    index: Mutex<CacheIndex>,
+}
+
+impl AttachmentCache {
+    /// Open (or create) a cache in `dir`
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - Cache directory
+    /// * `quota_bytes` - Byte quota for evictable blobs
+    /// * `replication_factor` - Confirmed deliveries required before an
+    ///   authored blob becomes evictable
+    pub fn open(dir: &Path, quota_bytes: u64, replication_factor: usize) -> StoreResult<Self> {
+        fs::create_dir_all(dir.join("blobs"))?;
+        fs::create_dir_all(dir.join("derived"))?;
+
+        let index_path = dir.join(INDEX_FILE);
+        let index = if index_path.exists() {
+            bincode::deserialize(&fs::read(&index_path)?)?
+        } else {
+            CacheIndex::default()
+        };
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            quota_bytes,
+            replication_factor,
+            preflight: None,
+            index: Mutex::new(index),
+        })
+    }
+
+    /// Check free disk space before downloading a blob
+    pub fn with_storage_preflight(mut self, preflight: StoragePreflight) -> Self {
+        self.preflight = Some(preflight);
+        self
+    }
+
+    /// Configured quota in bytes
+    pub fn quota_bytes(&self) -> u64 {
+        self.quota_bytes
+    }
+
+    /// Cache a blob downloaded from a peer
+    ///
+    /// Returns the IDs of attachments evicted to stay under the quota.
+    pub fn insert_downloaded(
+        &self,
+        message_id: &MessageId,
+        attachment: &Attachment,
+        data: &[u8],
+    ) -> StoreResult<Vec<String>> {
+        self.insert(message_id, attachment, data, BlobOrigin::Downloaded)
+    }
+
+    /// Cache a locally authored blob
+    ///
+    /// The blob is kept regardless of quota until [`confirm_delivery`]
+    /// reports enough replicas.
+    ///
+    /// [`confirm_delivery`]: AttachmentCache::confirm_delivery
+    pub fn insert_authored(
+        &self,
+        message_id: &MessageId,
+        attachment: &Attachment,
+        data: &[u8],
+    ) -> StoreResult<Vec<String>> {
+        self.insert(message_id, attachment, data, BlobOrigin::Authored { confirmed_replicas: 0 })
+    }
+
+    /// Record how many peers have confirmed delivery of an authored blob
+    ///
+    /// Returns the IDs of attachments evicted once the blob became evictable.
+    pub fn confirm_delivery(
+        &self,
+        attachment_id: &str,
+        replicas: usize,
+    ) -> StoreResult<Vec<String>> {
+        let mut index = self.lock();
+        let entry = index
+            .entries
+            .get_mut(attachment_id)
+            .ok_or_else(|| StoreError::NotFound(format!("Attachment {}", attachment_id)))?;
+
+        if let BlobOrigin::Authored { confirmed_replicas } = &mut entry.origin {
+            *confirmed_replicas = (*confirmed_replicas).max(replicas);
+        }
+
+        let evicted = self.evict_to_quota(&mut index, None)?;
+        self.persist(&index)?;
+        Ok(evicted)
+    }
+
+    /// Store a derived artifact (e.g. a thumbnail) for a cached attachment
+    pub fn put_derived(&self, attachment_id: &str, name: &str, data: &[u8]) -> StoreResult<()> {
+        if name.is_empty() || name.contains(['/', '\\']) || name == "." || name == ".." {
+            return Err(StoreError::InvalidOperation(format!(
+                "Invalid derived artifact name: {}",
+                name
+            )));
+        }
+
+        let mut index = self.lock();
+        let dir = self.derived_dir(attachment_id);
+        let entry = index
+            .entries
+            .get_mut(attachment_id)
+            .ok_or_else(|| StoreError::NotFound(format!("Attachment {}", attachment_id)))?;
+
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(name);
+        if let Ok(existing) = fs::metadata(&path) {
+            entry.size_bytes -= existing.len();
+        } else {
+            entry.derived.push(name.to_string());
+        }
+        fs::write(&path, data)?;
+        entry.size_bytes += data.len() as u64;
+
+        self.evict_to_quota(&mut index, Some(attachment_id))?;
+        self.persist(&index)
+    }
+
+    /// Read a derived artifact, if present
+    pub fn get_derived(&self, attachment_id: &str, name: &str) -> StoreResult<Option<Vec<u8>>> {
+        let index = self.lock();
+        match index.entries.get(attachment_id) {
+            Some(entry) if entry.derived.iter().any(|d| d == name) => {
+                Ok(Some(fs::read(self.derived_dir(attachment_id).join(name))?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Read an attachment blob, re-downloading it if it was evicted
+    ///
+    /// A re-downloaded blob must match the manifest's `content_hash`
+    /// (hex BLAKE3) when one is set. With a storage preflight attached,
+    /// fails with `InsufficientStorage` before fetching if the manifest's
+    /// `size_bytes` doesn't fit on disk.
+    pub fn get(
+        &self,
+        message_id: &MessageId,
+        attachment: &Attachment,
+        fetcher: &dyn BlobFetcher,
+    ) -> StoreResult<Vec<u8>> {
+        if let Some(data) = self.read(&attachment.id)? {
+            return Ok(data);
+        }
+
+        if let Some(preflight) = &self.preflight {
+            preflight.check(&self.dir, attachment.size_bytes)?;
+        }
+
+        let data = fetcher.fetch(attachment)?;
+        if !attachment.content_hash.is_empty()
+            && blake3::hash(&data).to_hex().as_str() != attachment.content_hash
+        {
+            return Err(StoreError::CorruptedData(format!(
+                "Attachment {} does not match its content hash",
+                attachment.id
+            )));
+        }
+
+        self.insert_downloaded(message_id, attachment, &data)?;
+        Ok(data)
+    }
+
+    /// Read a cached blob, counting it as used
+    ///
+    /// Returns `None` if it isn't cached, e.g. because it was evicted.
+    pub fn read(&self, attachment_id: &str) -> StoreResult<Option<Vec<u8>>> {
+        let mut index = self.lock();
+        let now = index.tick();
+        let Some(entry) = index.entries.get_mut(attachment_id) else {
+            return Ok(None);
+        };
+        entry.last_access = now;
+        let data = fs::read(self.blob_path(attachment_id))?;
+        self.persist(&index)?;
+        Ok(Some(data))
+    }
+
+    /// Whether an attachment blob is currently on disk
+    pub fn contains(&self, attachment_id: &str) -> bool {
+        self.lock().entries.contains_key(attachment_id)
+    }
+
+    /// Exempt a message's attachments from eviction
+    pub fn pin(&self, message_id: &MessageId) -> StoreResult<()> {
+        let mut index = self.lock();
+        index.pinned.insert(message_id.clone());
+        self.persist(&index)
+    }
+
+    /// Make a message's attachments evictable again
+    ///
+    /// Returns false if the message was not pinned.
+    pub fn unpin(&self, message_id: &MessageId) -> StoreResult<bool> {
+        let mut index = self.lock();
+        if !index.pinned.remove(message_id) {
+            return Ok(false);
+        }
+        self.evict_to_quota(&mut index, None)?;
+        self.persist(&index)?;
+        Ok(true)
+    }
+
+    /// Whether a message's attachments are pinned
+    pub fn is_pinned(&self, message_id: &MessageId) -> bool {
+        self.lock().pinned.contains(message_id)
+    }
+
+    /// Current usage
+    pub fn usage(&self) -> AttachmentUsage {
+        let index = self.lock();
+        let mut pinned: Vec<CachedAttachment> = index
+            .entries
+            .values()
+            .filter(|e| index.pinned.contains(&e.message_id))
+            .cloned()
+            .collect();
+        pinned.sort_by(|a, b| a.attachment.id.cmp(&b.attachment.id));
+
+        AttachmentUsage {
+            used_bytes: index.used_bytes(),
+            quota_bytes: self.quota_bytes,
+            entries: index.entries.len(),
+            pinned,
+            pending_delivery: index.entries.values().filter(|e| self.awaiting_delivery(e)).count(),
+        }
+    }
+
+    /// Evict every evictable blob, regardless of quota
+    ///
+    /// Returns the IDs of the evicted attachments.
+    pub fn evict_all(&self) -> StoreResult<Vec<String>> {
+        let mut index = self.lock();
+        let candidates = self.eviction_order(&index, None);
+        for id in &candidates {
+            self.remove_entry(&mut index, id)?;
+        }
+        self.persist(&index)?;
+        Ok(candidates)
+    }
+
+    /// Drop every blob of a message, pinned or not
+    ///
+    /// For messages deleted for good, e.g. pruned by retention. Returns the
+    /// IDs of the removed attachments.
+    pub fn remove_message(&self, message_id: &MessageId) -> StoreResult<Vec<String>> {
+        let mut index = self.lock();
+        let mut removed: Vec<String> = index
+            .entries
+            .values()
+            .filter(|e| &e.message_id == message_id)
+            .map(|e| e.attachment.id.clone())
+            .collect();
+        removed.sort();
+        for id in &removed {
+            self.remove_entry(&mut index, id)?;
+        }
+        let unpinned = index.pinned.remove(message_id);
+        if !removed.is_empty() || unpinned {
+            self.persist(&index)?;
+        }
+        Ok(removed)
+    }
+
+    fn insert(
+        &self,
+        message_id: &MessageId,
+        attachment: &Attachment,
+        data: &[u8],
+        origin: BlobOrigin,
+    ) -> StoreResult<Vec<String>> {
+        let mut index = self.lock();
+
+        // Replacing an entry drops its old blob and derived artifacts
+        if index.entries.contains_key(&attachment.id) {
+            self.remove_entry(&mut index, &attachment.id)?;
+        }
+
+        fs::write(self.blob_path(&attachment.id), data)?;
+        let last_access = index.tick();
+        index.entries.insert(
+            attachment.id.clone(),
+            CachedAttachment {
+                message_id: message_id.clone(),
+                attachment: attachment.clone(),
+                origin,
+                size_bytes: data.len() as u64,
+                derived: Vec::new(),
+                last_access,
+            },
+        );
+
+        let evicted = self.evict_to_quota(&mut index, Some(&attachment.id))?;
+        self.persist(&index)?;
+        Ok(evicted)
+    }
+
+    fn awaiting_delivery(&self, entry: &CachedAttachment) -> bool {
+        matches!(
+            entry.origin,
+            BlobOrigin::Authored { confirmed_replicas } if confirmed_replicas < self.replication_factor
+        )
+    }
+
+    /// Evictable attachment IDs, least recently used first
+    fn eviction_order(&self, index: &CacheIndex, protect: Option<&str>) -> Vec<String> {
+        let mut candidates: Vec<&CachedAttachment> = index
+            .entries
+            .values()
+            .filter(|e| Some(e.attachment.id.as_str()) != protect)
+            .filter(|e| !index.pinned.contains(&e.message_id) && !self.awaiting_delivery(e))
+            .collect();
+        candidates.sort_by_key(|e| e.last_access);
+        candidates.into_iter().map(|e| e.attachment.id.clone()).collect()
+    }
+
+    /// Evict LRU blobs until usage fits the quota
+    ///
+    /// `protect` (the entry just written) is never evicted, so a single
+    /// oversized blob is still readable until something else displaces it.
+    fn evict_to_quota(
+        &self,
+        index: &mut CacheIndex,
+        protect: Option<&str>,
+    ) -> StoreResult<Vec<String>> {
+        let mut evicted = Vec::new();
+        if index.used_bytes() <= self.quota_bytes {
+            return Ok(evicted);
+        }
+
+        for id in self.eviction_order(index, protect) {
+            self.remove_entry(index, &id)?;
+            evicted.push(id);
+            if index.used_bytes() <= self.quota_bytes {
+                break;
+            }
+        }
+
+        if !evicted.is_empty() {
+            tracing::debug!(count = evicted.len(), "Evicted attachments to stay under quota");
+        }
+        Ok(evicted)
+    }
+
+    fn remove_entry(&self, index: &mut CacheIndex, attachment_id: &str) -> StoreResult<()> {
+        index.entries.remove(attachment_id);
+
+        let blob = self.blob_path(attachment_id);
+        if blob.exists() {
+            fs::remove_file(blob)?;
+        }
+        let derived = self.derived_dir(attachment_id);
+        if derived.exists() {
+            fs::remove_dir_all(derived)?;
+        }
+        Ok(())
+    }
+
+    fn persist(&self, index: &CacheIndex) -> StoreResult<()> {
+        // Atomic write: temp file then rename
+        let tmp = self.dir.join(format!("{}.tmp", INDEX_FILE));
+        fs::write(&tmp, bincode::serialize(index)?)?;
+        fs::rename(tmp, self.dir.join(INDEX_FILE))?;
+        Ok(())
+    }
+
+    fn key(attachment_id: &str) -> String {
+        blake3::hash(attachment_id.as_bytes()).to_hex().to_string()
+    }
+
+    fn blob_path(&self, attachment_id: &str) -> PathBuf {
+        self.dir.join("blobs").join(Self::key(attachment_id))
+    }
+
+    fn derived_dir(&self, attachment_id: &str) -> PathBuf {
+        self.dir.join("derived").join(Self::key(attachment_id))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheIndex> {
+        self.index.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core_store::store::preflight::FsStats;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    /// Serves blobs from memory and counts downloads
+    struct MemoryFetcher {
+        blobs: HashMap<String, Vec<u8>>,
+        fetches: AtomicUsize,
+    }
+
+    impl BlobFetcher for MemoryFetcher {
+        fn fetch(&self, attachment: &Attachment) -> StoreResult<Vec<u8>> {
+            self.fetches.fetch_add(1, Ordering::SeqCst);
+            self.blobs
+                .get(&attachment.id)
+                .cloned()
+                .ok_or_else(|| StoreError::NotFound(attachment.id.clone()))
+        }
+    }
+
+    fn attachment(id: &str, data: &[u8]) -> Attachment {
+        Attachment {
+            id: id.to_string(),
+            filename: format!("{}.bin", id),
+            mime_type: "application/octet-stream".to_string(),
+            size_bytes: data.len() as u64,
+            content_hash: blake3::hash(data).to_hex().to_string(),
+        }
+    }
+
+    fn open(dir: &TempDir, quota: u64) -> AttachmentCache {
+        AttachmentCache::open(dir.path(), quota, 2).unwrap()
+    }
+
+    #[test]
+    fn test_lru_eviction_order() {
+        let dir = TempDir::new().unwrap();
+        let cache = open(&dir, 300);
+        let msg = MessageId::generate();
+        let blob = [0u8; 100];
+
+        for id in ["a", "b", "c"] {
+            cache.insert_downloaded(&msg, &attachment(id, &blob), &blob).unwrap();
+        }
+
+        // Touch "a" so "b" becomes least recently used
+        let fetcher = MemoryFetcher { blobs: HashMap::new(), fetches: AtomicUsize::new(0) };
+        cache.get(&msg, &attachment("a", &blob), &fetcher).unwrap();
+
+        let evicted = cache.insert_downloaded(&msg, &attachment("d", &blob), &blob).unwrap();
+        assert_eq!(evicted, vec!["b"]);
+        let evicted = cache.insert_downloaded(&msg, &attachment("e", &blob), &blob).unwrap();
+        assert_eq!(evicted, vec!["c"]);
+
+        assert!(cache.contains("a") && cache.contains("d") && cache.contains("e"));
+        assert_eq!(cache.usage().used_bytes, 300);
+        assert_eq!(fs::read_dir(dir.path().join("blobs")).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn test_pinned_and_authored_are_exempt() {
+        let dir = TempDir::new().unwrap();
+        let cache = open(&dir, 200);
+        let pinned_msg = MessageId::generate();
+        let other_msg = MessageId::generate();
+        let blob = [1u8; 100];
+
+        cache.pin(&pinned_msg).unwrap();
+        cache
+            .insert_downloaded(&pinned_msg, &attachment("pinned", &blob), &blob)
+            .unwrap();
+        cache.insert_authored(&other_msg, &attachment("mine", &blob), &blob).unwrap();
+        assert!(cache.confirm_delivery("mine", 1).unwrap().is_empty());
+        assert_eq!(cache.usage().pending_delivery, 1);
+
+        // Exempt blobs fill the quota, so only downloads compete for space
+        assert!(cache
+            .insert_downloaded(&other_msg, &attachment("x", &blob), &blob)
+            .unwrap()
+            .is_empty());
+        let evicted = cache.insert_downloaded(&other_msg, &attachment("y", &blob), &blob).unwrap();
+        assert_eq!(evicted, vec!["x"]);
+        assert_eq!(cache.usage().pinned.len(), 1);
+
+        // Once delivered to the replication factor, the authored blob is evictable
+        assert_eq!(cache.confirm_delivery("mine", 2).unwrap(), vec!["mine"]);
+        assert_eq!(cache.usage().pending_delivery, 0);
+
+        // Unpinning makes the pinned blob evictable again
+        assert!(cache.unpin(&pinned_msg).unwrap());
+        let evicted = cache.insert_downloaded(&other_msg, &attachment("z", &blob), &blob).unwrap();
+        assert_eq!(evicted, vec!["pinned"]);
+        assert!(cache.contains("y") && cache.contains("z"));
+    }
+
+    #[test]
+    fn test_transparent_refetch_after_eviction() {
+        let dir = TempDir::new().unwrap();
+        let cache = open(&dir, 150);
+        let msg = MessageId::generate();
+        let first = b"first blob contents".repeat(5);
+        let second = b"second blob contents".repeat(5);
+
+        let fetcher = MemoryFetcher {
+            blobs: HashMap::from([
+                ("first".to_string(), first.clone()),
+                ("second".to_string(), second.clone()),
+            ]),
+            fetches: AtomicUsize::new(0),
+        };
+
+        assert_eq!(cache.get(&msg, &attachment("first", &first), &fetcher).unwrap(), first);
+        assert_eq!(cache.get(&msg, &attachment("first", &first), &fetcher).unwrap(), first);
+        assert_eq!(fetcher.fetches.load(Ordering::SeqCst), 1);
+
+        // Fetching the second blob evicts the first
+        assert_eq!(cache.get(&msg, &attachment("second", &second), &fetcher).unwrap(), second);
+        assert!(!cache.contains("first"));
+
+        assert_eq!(cache.get(&msg, &attachment("first", &first), &fetcher).unwrap(), first);
+        assert_eq!(fetcher.fetches.load(Ordering::SeqCst), 3);
+
+        // A blob that doesn't match the manifest is rejected
+        let forged = attachment("second", b"something else");
+        cache.evict_all().unwrap();
+        assert!(matches!(cache.get(&msg, &forged, &fetcher), Err(StoreError::CorruptedData(_))));
+    }
+
+    #[test]
+    fn test_download_refused_when_disk_is_full() {
+        struct NearlyFull;
+        impl FsStats for NearlyFull {
+            fn available_space(&self, _path: &Path) -> std::io::Result<u64> {
+                Ok(150)
+            }
+        }
+
+        let dir = TempDir::new().unwrap();
+        let preflight = StoragePreflight::new(100).with_fs_stats(Arc::new(NearlyFull));
+        let cache = open(&dir, 1000).with_storage_preflight(preflight);
+        let msg = MessageId::generate();
+        let (small, large) = ([3u8; 50], [4u8; 51]);
+        let fetcher = MemoryFetcher {
+            blobs: HashMap::from([
+                ("small".to_string(), small.to_vec()),
+                ("large".to_string(), large.to_vec()),
+            ]),
+            fetches: AtomicUsize::new(0),
+        };
+
+        assert_eq!(cache.get(&msg, &attachment("small", &small), &fetcher).unwrap(), small);
+        assert!(matches!(
+            cache.get(&msg, &attachment("large", &large), &fetcher),
+            Err(StoreError::InsufficientStorage { required: 151, available: 150 })
+        ));
+        assert_eq!(fetcher.fetches.load(Ordering::SeqCst), 1);
+
+        // Cached blobs are still served
+        assert_eq!(cache.get(&msg, &attachment("small", &small), &fetcher).unwrap(), small);
+    }
+
+    #[test]
+    fn test_eviction_purges_derived_and_survives_reopen() {
+        let dir = TempDir::new().unwrap();
+        let msg = MessageId::generate();
+        let blob = [2u8; 50];
+
+        {
+            let cache = open(&dir, 1000);
+            cache.insert_downloaded(&msg, &attachment("photo", &blob), &blob).unwrap();
+            cache.put_derived("photo", "thumb_64.png", &[3u8; 20]).unwrap();
+            assert!(cache.put_derived("photo", "../escape", b"x").is_err());
+            assert_eq!(cache.usage().used_bytes, 70);
+        }
+
+        let cache = open(&dir, 1000);
+        assert_eq!(cache.get_derived("photo", "thumb_64.png").unwrap(), Some(vec![3u8; 20]));
+
+        assert_eq!(cache.evict_all().unwrap(), vec!["photo"]);
+        assert_eq!(cache.usage().used_bytes, 0);
+        assert_eq!(fs::read_dir(dir.path().join("derived")).unwrap().count(), 0);
+        assert_eq!(cache.get_derived("photo", "thumb_64.png").unwrap(), None);
+    }
+
+    #[test]
+    fn test_remove_message_drops_pinned_blobs() {
+        let dir = TempDir::new().unwrap();
+        let cache = open(&dir, 1000);
+        let pruned = MessageId::generate();
+        let kept = MessageId::generate();
+        let blob = [2u8; 100];
+
+        cache.pin(&pruned).unwrap();
+        cache.insert_downloaded(&pruned, &attachment("a", &blob), &blob).unwrap();
+        cache.insert_authored(&pruned, &attachment("b", &blob), &blob).unwrap();
+        cache.put_derived("a", "thumb_64.png", b"thumb").unwrap();
+        cache.insert_downloaded(&kept, &attachment("c", &blob), &blob).unwrap();
+
+        assert_eq!(cache.remove_message(&pruned).unwrap(), vec!["a", "b"]);
+        assert!(!cache.is_pinned(&pruned));
+        assert!(cache.contains("c"));
+        assert_eq!(cache.usage().used_bytes, 100);
+        assert_eq!(fs::read_dir(dir.path().join("derived")).unwrap().count(), 0);
+        assert!(cache.remove_message(&pruned).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,408 @@
+/*
+    attestation.rs - Startup integrity attestation of binary and data directory
+
+    Every startup writes a signed record pairing the running binary with the
+    data directory: the binary version, the storage schema versions, a random
+    data directory ID and a startup counter that only ever goes up. Records
+    are appended to an audit log (one JSON record per line, each chained to
+    the previous one by hash) and the latest one is also kept in a sidecar
+    file next to it.
+
+    On the next startup both copies are compared before anything else
+    touches the directory. A sidecar behind the audit log means it was
+    restored from an older copy of the directory; an audit log behind the
+    sidecar means the log was truncated; an older binary or schema than last
+    time means a downgrade. Any of these refuses to start unless the
+    operator explicitly accepts the rollback, and an accepted rollback is
+    recorded in the audit log.
+
+    Restoring both files together from the same backup is not detectable
+    from the directory alone.
+*/
+
+use crate::core_identity::keypair::Keypair;
+use crate::core_store::model::Timestamp;
+use crate::core_store::store::errors::{StoreError, StoreResult};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// Sidecar holding the latest sealed attestation
+pub const ATTESTATION_FILE: &str = "attestation.json";
+
+/// Append-only log of every sealed attestation
+pub const AUDIT_LOG_FILE: &str = "audit.log";
+
+/// Domain separator for attestation signatures
+const SIGNATURE_CONTEXT: &[u8] = b"SPACEPANDA_ATTESTATION_V1:";
+
+/// What one startup attested to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttestationRecord {
+    /// Random ID given to the data directory on its first attestation
+    pub data_dir_id: String,
+
+    /// Startup counter, strictly increasing
+    pub counter: u64,
+
+    /// Version of the binary that started
+    pub binary_version: String,
+
+    /// Storage schema versions, by schema
+    pub schema_versions: BTreeMap<String, i32>,
+
+    /// When the startup happened
+    pub attested_at: Timestamp,
+
+    /// Digest of the previous sealed attestation
+    pub previous: Option<String>,
+
+    /// Anomalies the operator accepted at this startup
+    #[serde(default)]
+    pub accepted_anomalies: Vec<String>,
+}
+
+/// An attestation record signed with the identity (or device) key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SealedAttestation {
+    pub record: AttestationRecord,
+    pub public_key: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+impl SealedAttestation {
+    /// Sign a record
+    pub fn seal(record: AttestationRecord, signer: &Keypair) -> StoreResult<Self> {
+        let signature = signer.sign(&Self::body(&record)?);
+        Ok(Self { record, public_key: signer.public_key().to_vec(), signature })
+    }
+
+    fn body(record: &AttestationRecord) -> StoreResult<Vec<u8>> {
+        let json = serde_json::to_vec(record)
+            .map_err(|e| StoreError::Serialization(format!("Attestation: {}", e)))?;
+        Ok([SIGNATURE_CONTEXT, &json].concat())
+    }
+
+    /// Whether the signature matches the record and public key
+    pub fn verify(&self) -> bool {
+        Self::body(&self.record)
+            .map(|body| Keypair::verify(&self.public_key, &body, &self.signature))
+            .unwrap_or(false)
+    }
+
+    /// Hex digest chaining the next record to this one
+    pub fn digest(&self) -> StoreResult<String> {
+        let json = serde_json::to_vec(self)
+            .map_err(|e| StoreError::Serialization(format!("Attestation: {}", e)))?;
+        Ok(hex::encode(Sha256::digest(json)))
+    }
+}
+
+/// Something about the data directory that doesn't match its history
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttestationAnomaly {
+    /// The sidecar is gone although startups were logged
+    SidecarMissing { logged: u64 },
+
+    /// The sidecar is older than the audit log (restored from a backup)
+    CounterRegressed { sidecar: u64, logged: u64 },
+
+    /// The audit log is older than the sidecar (truncated or replaced)
+    AuditLogBehind { sidecar: u64, logged: u64 },
+
+    /// Sidecar and audit log disagree about the same startup
+    Diverged { counter: u64 },
+
+    /// Sidecar and audit log belong to different data directories
+    DataDirMismatch { sidecar: String, logged: String },
+
+    /// A record's signature doesn't verify
+    InvalidSignature { file: &'static str },
+
+    /// The record was signed by a different key than the one starting now
+    SignerChanged,
+
+    /// The binary is older than the one that last started
+    BinaryDowngrade { previous: String, current: String },
+
+    /// A storage schema is older than the one last seen
+    SchemaDowngrade { schema: String, previous: i32, current: i32 },
+}
+
+impl fmt::Display for AttestationAnomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttestationAnomaly::SidecarMissing { logged } => {
+                write!(f, "{} is missing but {} startups are logged", ATTESTATION_FILE, logged)
+            }
+            AttestationAnomaly::CounterRegressed { sidecar, logged } => write!(
+                f,
+                "startup counter went back from {} to {} (data directory restored?)",
+                logged, sidecar
+            ),
+            AttestationAnomaly::AuditLogBehind { sidecar, logged } => write!(
+                f,
+                "{} ends at startup {} but {} is at {}",
+                AUDIT_LOG_FILE, logged, ATTESTATION_FILE, sidecar
+            ),
+            AttestationAnomaly::Diverged { counter } => {
+                write!(
+                    f,
+                    "{} and {} disagree about startup {}",
+                    ATTESTATION_FILE, AUDIT_LOG_FILE, counter
+                )
+            }
+            AttestationAnomaly::DataDirMismatch { sidecar, logged } => write!(
+                f,
+                "{} is from data directory {}, {} from {}",
+                ATTESTATION_FILE, sidecar, AUDIT_LOG_FILE, logged
+            ),
+            AttestationAnomaly::InvalidSignature { file } => {
+                write!(f, "{} has an invalid signature", file)
+            }
+            AttestationAnomaly::SignerChanged => {
+                write!(f, "last startup was attested by a different key")
+            }
+            AttestationAnomaly::BinaryDowngrade { previous, current } => {
+                write!(f, "binary downgraded from {} to {}", previous, current)
+            }
+            AttestationAnomaly::SchemaDowngrade { schema, previous, current } => {
+                write!(f, "{} schema downgraded from {} to {}", schema, previous, current)
+            }
+        }
+    }
+}
+
+/// Result of a successful startup attestation
+#[derive(Debug, Clone)]
+pub struct AttestationOutcome {
+    /// The record written for this startup
+    pub attestation: SealedAttestation,
+
+    /// Anomalies found (and accepted) before writing it
+    pub anomalies: Vec<AttestationAnomaly>,
+}
+
+/// Attests a startup against a data directory's history
+pub struct StartupAttestation {
+    data_dir: PathBuf,
+    binary_version: String,
+    schema_versions: BTreeMap<String, i32>,
+}
+
+impl StartupAttestation {
+    /// Attest `binary_version` with the storage schemas built into this crate
+    pub fn new(data_dir: impl Into<PathBuf>, binary_version: impl Into<String>) -> Self {
+        let schema_versions = BTreeMap::from([
+            ("mls".to_string(), crate::core_mls::storage::migrations::CURRENT_SCHEMA_VERSION),
+            (
+                "space".to_string(),
+                crate::core_space::storage::migrations::CURRENT_SPACE_SCHEMA_VERSION,
+            ),
+        ]);
+        Self {
+            data_dir: data_dir.into(),
+            binary_version: binary_version.into(),
+            schema_versions,
+        }
+    }
+
+    /// Override the version attested for a schema
+    pub fn with_schema_version(mut self, schema: impl Into<String>, version: i32) -> Self {
+        self.schema_versions.insert(schema.into(), version);
+        self
+    }
+
+    /// Compare this startup against the directory's history
+    pub fn check(&self, signer: &Keypair) -> StoreResult<Vec<AttestationAnomaly>> {
+        Ok(self.inspect(signer)?.anomalies)
+    }
+
+    /// Check, then record this startup
+    ///
+    /// Fails with [`StoreError::PermissionDenied`] if anything is amiss and
+    /// `accept_rollback` isn't set; nothing is written in that case.
+    pub fn attest(
+        &self,
+        signer: &Keypair,
+        accept_rollback: bool,
+    ) -> StoreResult<AttestationOutcome> {
+        let history = self.inspect(signer)?;
+        if !history.anomalies.is_empty() && !accept_rollback {
+            let anomalies: Vec<String> = history.anomalies.iter().map(|a| a.to_string()).collect();
+            return Err(StoreError::PermissionDenied(format!(
+                "integrity attestation failed: {}",
+                anomalies.join("; ")
+            )));
+        }
+
+        let record = AttestationRecord {
+            data_dir_id: history.data_dir_id,
+            counter: history.counter + 1,
+            binary_version: self.binary_version.clone(),
+            schema_versions: self.schema_versions.clone(),
+            attested_at: Timestamp::now(),
+            previous: history.previous,
+            accepted_anomalies: history.anomalies.iter().map(|a| a.to_string()).collect(),
+        };
+        let attestation = SealedAttestation::seal(record, signer)?;
+        let line = serde_json::to_string(&attestation)
+            .map_err(|e| StoreError::Serialization(format!("Attestation: {}", e)))?;
+
+        // The log goes first: a crash in between leaves the sidecar behind,
+        // which the next startup reports rather than silently accepting
+        let mut log = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.data_dir.join(AUDIT_LOG_FILE))?;
+        writeln!(log, "{}", line)?;
+        log.sync_all()?;
+
+        // Atomic write: temp file then rename
+        let tmp = self.data_dir.join(format!("{}.tmp", ATTESTATION_FILE));
+        fs::write(&tmp, &line)?;
+        fs::rename(tmp, self.data_dir.join(ATTESTATION_FILE))?;
+
+        Ok(AttestationOutcome { attestation, anomalies: history.anomalies })
+    }
+
+    fn inspect(&self, signer: &Keypair) -> StoreResult<History> {
+        let sidecar = read_sidecar(&self.data_dir.join(ATTESTATION_FILE))?;
+        let logged = read_last_logged(&self.data_dir.join(AUDIT_LOG_FILE))?;
+        let mut anomalies = Vec::new();
+
+        for (attestation, file) in [(&sidecar, ATTESTATION_FILE), (&logged, AUDIT_LOG_FILE)] {
+            if attestation.as_ref().is_some_and(|a| !a.verify()) {
+                anomalies.push(AttestationAnomaly::InvalidSignature { file });
+            }
+        }
+
+        match (&sidecar, &logged) {
+            (None, Some(logged)) => {
+                anomalies.push(AttestationAnomaly::SidecarMissing { logged: logged.record.counter })
+            }
+            (Some(sidecar), None) => anomalies.push(AttestationAnomaly::AuditLogBehind {
+                sidecar: sidecar.record.counter,
+                logged: 0,
+            }),
+            (Some(sidecar), Some(logged)) => {
+                let (s, l) = (&sidecar.record, &logged.record);
+                if s.data_dir_id != l.data_dir_id {
+                    anomalies.push(AttestationAnomaly::DataDirMismatch {
+                        sidecar: s.data_dir_id.clone(),
+                        logged: l.data_dir_id.clone(),
+                    });
+                }
+                if s.counter < l.counter {
+                    anomalies.push(AttestationAnomaly::CounterRegressed {
+                        sidecar: s.counter,
+                        logged: l.counter,
+                    });
+                } else if s.counter > l.counter {
+                    anomalies.push(AttestationAnomaly::AuditLogBehind {
+                        sidecar: s.counter,
+                        logged: l.counter,
+                    });
+                } else if sidecar != logged {
+                    anomalies.push(AttestationAnomaly::Diverged { counter: s.counter });
+                }
+            }
+            (None, None) => {}
+        }
+
+        // The most recent startup either file knows about is the baseline
+        let latest = match (&sidecar, &logged) {
+            (Some(s), Some(l)) if s.record.counter > l.record.counter => Some(s),
+            (_, Some(l)) => Some(l),
+            (s, None) => s.as_ref(),
+        };
+        if let Some(latest) = latest {
+            if latest.public_key != signer.public_key() {
+                anomalies.push(AttestationAnomaly::SignerChanged);
+            }
+            self.check_versions(&latest.record, &mut anomalies);
+        }
+
+        Ok(History {
+            data_dir_id: latest
+                .map(|a| a.record.data_dir_id.clone())
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+            counter: latest.map(|a| a.record.counter).unwrap_or(0),
+            previous: latest.map(|a| a.digest()).transpose()?,
+            anomalies,
+        })
+    }
+
+    fn check_versions(
+        &self,
+        previous: &AttestationRecord,
+        anomalies: &mut Vec<AttestationAnomaly>,
+    ) {
+        if version_key(&self.binary_version) < version_key(&previous.binary_version) {
+            anomalies.push(AttestationAnomaly::BinaryDowngrade {
+                previous: previous.binary_version.clone(),
+                current: self.binary_version.clone(),
+            });
+        }
+        for (schema, &before) in &previous.schema_versions {
+            let now = self.schema_versions.get(schema).copied().unwrap_or(0);
+            if now < before {
+                anomalies.push(AttestationAnomaly::SchemaDowngrade {
+                    schema: schema.clone(),
+                    previous: before,
+                    current: now,
+                });
+            }
+        }
+    }
+}
+
+/// What the directory's history says before this startup
+struct History {
+    data_dir_id: String,
+    counter: u64,
+    previous: Option<String>,
+    anomalies: Vec<AttestationAnomaly>,
+}
+
+/// Numeric components of a `major.minor.patch[-pre]` version
+fn version_key(version: &str) -> Vec<u64> {
+    version
+        .split(['-', '+'])
+        .next()
+        .unwrap_or_default()
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
+
+fn read_sidecar(path: &Path) -> StoreResult<Option<SealedAttestation>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let attestation = serde_json::from_slice(&fs::read(path)?)
+        .map_err(|e| StoreError::CorruptedData(format!("{:?}: {}", path, e)))?;
+    Ok(Some(attestation))
+}
+
+fn read_last_logged(path: &Path) -> StoreResult<Option<SealedAttestation>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let mut last = None;
+    for line in BufReader::new(fs::File::open(path)?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        last = Some(
+            serde_json::from_str(&line)
+                .map_err(|e| StoreError::CorruptedData(format!("{:?}: {}", path, e)))?,
+        );
+    }
+    Ok(last)
+}
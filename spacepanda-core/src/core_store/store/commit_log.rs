@@ -9,12 +9,55 @@
     - Sequential read for replay
     - Log rotation and compaction
     - CRC32 checksums for corruption detection
+
+    Compaction drops the entries a snapshot already covers. Numbering
+    continues across it: the first sequence number kept is recorded in a
+    `.base` file next to the log before the entries go, so a log emptied
+    by compaction still resumes where it left off.
+
+    With a write-ahead log in front (see wal.rs), entries are appended with
+    `append_deferred`: they get their sequence number and are visible to
+    readers right away, but reach the file only with the next
+    `write_pending`, many at a time.
 */
 
 use crate::core_store::store::errors::{StoreError, StoreResult};
 use std::fs::{File, OpenOptions};
-use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
-use std::path::PathBuf;
+use std::io::{BufReader, BufWriter, Chain, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Suffix of the file recording the first sequence number kept by compaction
+const BASE_SUFFIX: &str = ".base";
+
+/// Suffix of the rewritten log while compaction builds it
+const COMPACTING_SUFFIX: &str = ".compacting";
+
+/// Flush a directory so that renames into it survive a crash
+pub(crate) fn sync_dir(dir: &Path) -> StoreResult<()> {
+    // Directories can't be opened for syncing on every platform; there the
+    // rename is as durable as it gets
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+/// Path next to `path` with `suffix` appended to its file name
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Write one entry: [seq:8][timestamp:8][len:4][data:len][checksum:4]
+fn write_entry(out: &mut impl Write, entry: &LogEntry) -> StoreResult<usize> {
+    out.write_all(&entry.seq.to_le_bytes())?;
+    out.write_all(&entry.timestamp.to_le_bytes())?;
+    out.write_all(&(entry.data.len() as u32).to_le_bytes())?;
+    out.write_all(&entry.data)?;
+    out.write_all(&entry.checksum.to_le_bytes())?;
+    Ok(8 + 8 + 4 + entry.data.len() + 4)
+}
 
 /// Entry in the commit log
 #[derive(Debug, Clone)]
@@ -53,6 +96,12 @@ pub struct CommitLog {
     file: BufWriter<File>,
     seq: u64,
     size: usize,
+
+    /// Sequence number of the oldest entry kept; earlier ones were compacted
+    first_seq: u64,
+
+    /// Entries appended with `append_deferred` that aren't in the file yet
+    pending: Vec<u8>,
 }
 
 impl CommitLog {
@@ -63,13 +112,40 @@ impl CommitLog {
             std::fs::create_dir_all(parent)?;
         }
 
+        // A rewrite interrupted before it replaced the log is discarded
+        let _ = std::fs::remove_file(sibling(&path, COMPACTING_SUFFIX));
+        let base = match std::fs::read(sibling(&path, BASE_SUFFIX)) {
+            Ok(bytes) => bytes.try_into().map(u64::from_le_bytes).map_err(|_| {
+                StoreError::CorruptedData("Invalid commit log base file".to_string())
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e.into()),
+        };
+
         // Open or create file in append mode
         let file = OpenOptions::new().create(true).append(true).read(true).open(&path)?;
 
         let size = file.metadata()?.len() as usize;
         let file = BufWriter::new(file);
 
-        Ok(CommitLog { path, file, seq: 0, size })
+        let mut log =
+            CommitLog { path, file, seq: base, size, first_seq: base, pending: Vec::new() };
+
+        // Resume numbering after existing entries so sequence numbers stay
+        // usable as replay checkpoints across restarts. A corrupted log is
+        // still opened; the error surfaces on the next read_all().
+        if size > 0 {
+            if let Ok(entries) = log.read_all() {
+                if let (Some(first), Some(last)) = (entries.first(), entries.last()) {
+                    // Entries below the base are left when a crash cut
+                    // compaction short; they are still there to read
+                    log.first_seq = first.seq.min(base);
+                    log.seq = (last.seq + 1).max(base);
+                }
+            }
+        }
+
+        Ok(log)
     }
 
     /// Append an entry to the log
@@ -81,65 +157,115 @@ impl CommitLog {
 
         let entry = LogEntry::new(self.seq, timestamp, data.to_vec());
 
-        // Write entry: [seq:8][timestamp:8][len:4][data:len][checksum:4]
-        self.file.write_all(&entry.seq.to_le_bytes())?;
-        self.file.write_all(&entry.timestamp.to_le_bytes())?;
-        self.file.write_all(&(entry.data.len() as u32).to_le_bytes())?;
-        self.file.write_all(&entry.data)?;
-        self.file.write_all(&entry.checksum.to_le_bytes())?;
-
+        // Deferred entries come first
+        self.write_pending()?;
+        let written = write_entry(&mut self.file, &entry)?;
         self.file.flush()?;
 
-        self.size += 8 + 8 + 4 + entry.data.len() + 4;
+        self.size += written;
         self.seq += 1;
 
         Ok(entry.seq)
     }
 
-    /// Read all entries from the log
-    pub fn read_all(&self) -> StoreResult<Vec<LogEntry>> {
-        let file = File::open(&self.path)?;
-        let mut reader = BufReader::new(file);
-        let mut entries = Vec::new();
-
-        loop {
-            // Read sequence number
-            let mut seq_buf = [0u8; 8];
-            if reader.read_exact(&mut seq_buf).is_err() {
-                break; // EOF
-            }
-            let seq = u64::from_le_bytes(seq_buf);
+    /// Append an entry without writing it to the file yet
+    ///
+    /// Readers see it right away; it is written with the next
+    /// `write_pending`, `append` or `truncate_before`. Until then it is only
+    /// in memory, so the caller must have it logged elsewhere.
+    pub fn append_deferred(&mut self, data: &[u8]) -> StoreResult<u64> {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
 
-            // Read timestamp
-            let mut ts_buf = [0u8; 8];
-            reader.read_exact(&mut ts_buf)?;
-            let timestamp = u64::from_le_bytes(ts_buf);
+        let entry = LogEntry::new(self.seq, timestamp, data.to_vec());
+        self.size += write_entry(&mut self.pending, &entry)?;
+        self.seq += 1;
 
-            // Read length
-            let mut len_buf = [0u8; 4];
-            reader.read_exact(&mut len_buf)?;
-            let len = u32::from_le_bytes(len_buf) as usize;
+        Ok(entry.seq)
+    }
 
-            // Read data
-            let mut data = vec![0u8; len];
-            reader.read_exact(&mut data)?;
+    /// Bytes of deferred entries not written to the file yet
+    pub fn pending_size(&self) -> usize {
+        self.pending.len()
+    }
 
-            // Read checksum
-            let mut checksum_buf = [0u8; 4];
-            reader.read_exact(&mut checksum_buf)?;
-            let checksum = u32::from_le_bytes(checksum_buf);
+    /// Write deferred entries to the file and sync it
+    pub fn write_pending(&mut self) -> StoreResult<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        self.file.write_all(&self.pending)?;
+        self.file.flush()?;
+        self.file.get_ref().sync_data()?;
+        self.pending.clear();
+        Ok(())
+    }
 
-            let entry = LogEntry { seq, timestamp, data, checksum };
+    /// Force appended entries to disk, deferred ones included
+    pub fn sync(&mut self) -> StoreResult<()> {
+        self.write_pending()?;
+        self.file.flush()?;
+        self.file.get_ref().sync_data()?;
+        Ok(())
+    }
 
-            // Verify checksum
-            if !entry.verify_checksum() {
-                return Err(StoreError::CorruptedData(format!("Invalid checksum at seq {}", seq)));
+    /// Cut off an entry left half written at the end of the log at `path`
+    ///
+    /// Only the last entry is considered torn; damage followed by more
+    /// entries is left for readers to report. Returns the bytes removed.
+    pub fn discard_torn_tail(path: &Path) -> StoreResult<u64> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let Some(header) = bytes.get(offset..offset + 20) else {
+                break;
+            };
+            let len = u32::from_le_bytes(header[16..20].try_into().unwrap()) as usize;
+            let end = offset + 20 + len + 4;
+            let Some(checksum) = bytes.get(end - 4..end) else {
+                break;
+            };
+            let data = &bytes[offset + 20..end - 4];
+            if crc32fast::hash(data) != u32::from_le_bytes(checksum.try_into().unwrap()) {
+                if end < bytes.len() {
+                    return Ok(0);
+                }
+                break;
             }
+            offset = end;
+        }
 
-            entries.push(entry);
+        let torn = (bytes.len() - offset) as u64;
+        if torn > 0 {
+            let file = OpenOptions::new().write(true).open(path)?;
+            file.set_len(offset as u64)?;
+            file.sync_all()?;
         }
+        Ok(torn)
+    }
 
-        Ok(entries)
+    /// Read all entries from the log
+    pub fn read_all(&self) -> StoreResult<Vec<LogEntry>> {
+        self.reader()?.collect()
+    }
+
+    /// Stream entries from the log, oldest first
+    ///
+    /// The reader has its own file handle, so the log can be appended to
+    /// while it is read; entries appended after it reaches the end are not
+    /// returned. Deferred entries are read after the file's.
+    pub fn reader(&self) -> StoreResult<CommitLogReader> {
+        let file = File::open(&self.path)?;
+        let reader = BufReader::new(file.chain(Cursor::new(self.pending.clone())));
+        Ok(CommitLogReader { reader, failed: false })
     }
 
     /// Truncate the log (remove all entries)
@@ -149,6 +275,54 @@ impl CommitLog {
         self.file.get_mut().seek(SeekFrom::Start(0))?;
         self.seq = 0;
         self.size = 0;
+        self.first_seq = 0;
+        self.pending.clear();
+        match std::fs::remove_file(sibling(&self.path, BASE_SUFFIX)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Drop the entries with `seq < first_seq`, keeping the rest and the
+    /// numbering
+    ///
+    /// The kept entries are written to a new file, synced and swapped in
+    /// by rename; a crash at any point leaves either the old log or the
+    /// new one.
+    pub fn truncate_before(&mut self, first_seq: u64) -> StoreResult<()> {
+        self.file.flush()?;
+        let first_seq = first_seq.clamp(self.first_seq, self.seq);
+
+        let temp = sibling(&self.path, COMPACTING_SUFFIX);
+        let mut out = BufWriter::new(File::create(&temp)?);
+        let mut size = 0;
+        for entry in self.reader()? {
+            let entry = entry?;
+            if entry.seq >= first_seq {
+                size += write_entry(&mut out, &entry)?;
+            }
+        }
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+
+        // The base goes first: if the log were swapped without it, an
+        // emptied log would restart numbering at 0
+        let base = sibling(&self.path, BASE_SUFFIX);
+        let base_temp = sibling(&base, ".tmp");
+        let mut file = File::create(&base_temp)?;
+        file.write_all(&first_seq.to_le_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&base_temp, &base)?;
+
+        std::fs::rename(&temp, &self.path)?;
+        if let Some(parent) = self.path.parent() {
+            sync_dir(parent)?;
+        }
+
+        let file = OpenOptions::new().append(true).read(true).open(&self.path)?;
+        self.file = BufWriter::new(file);
+        self.pending.clear();
+        self.size = size;
+        self.first_seq = first_seq;
         Ok(())
     }
 
@@ -161,6 +335,72 @@ impl CommitLog {
     pub fn current_seq(&self) -> u64 {
         self.seq
     }
+
+    /// Sequence number of the oldest entry still in the log
+    ///
+    /// Entries before it were dropped by `truncate_before`.
+    pub fn first_seq(&self) -> u64 {
+        self.first_seq
+    }
+}
+
+/// Streaming reader over a commit log's entries
+pub struct CommitLogReader {
+    reader: BufReader<Chain<File, Cursor<Vec<u8>>>>,
+    failed: bool,
+}
+
+impl CommitLogReader {
+    fn read_entry(&mut self) -> StoreResult<Option<LogEntry>> {
+        // Read sequence number
+        let mut seq_buf = [0u8; 8];
+        if self.reader.read_exact(&mut seq_buf).is_err() {
+            return Ok(None); // EOF
+        }
+        let seq = u64::from_le_bytes(seq_buf);
+
+        // Read timestamp
+        let mut ts_buf = [0u8; 8];
+        self.reader.read_exact(&mut ts_buf)?;
+        let timestamp = u64::from_le_bytes(ts_buf);
+
+        // Read length
+        let mut len_buf = [0u8; 4];
+        self.reader.read_exact(&mut len_buf)?;
+        let len = u32::from_le_bytes(len_buf) as usize;
+
+        // Read data
+        let mut data = vec![0u8; len];
+        self.reader.read_exact(&mut data)?;
+
+        // Read checksum
+        let mut checksum_buf = [0u8; 4];
+        self.reader.read_exact(&mut checksum_buf)?;
+        let checksum = u32::from_le_bytes(checksum_buf);
+
+        let entry = LogEntry { seq, timestamp, data, checksum };
+
+        // Verify checksum
+        if !entry.verify_checksum() {
+            return Err(StoreError::CorruptedData(format!("Invalid checksum at seq {}", seq)));
+        }
+
+        Ok(Some(entry))
+    }
+}
+
+impl Iterator for CommitLogReader {
+    type Item = StoreResult<LogEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Nothing after a corrupted entry can be trusted
+        if self.failed {
+            return None;
+        }
+        let entry = self.read_entry().transpose();
+        self.failed = matches!(entry, Some(Err(_)));
+        entry
+    }
 }
 
 #[cfg(test)]
@@ -234,4 +474,110 @@ mod tests {
         assert_eq!(log.size(), 0);
         assert_eq!(log.current_seq(), 0);
     }
+
+    #[test]
+    fn test_reopen_resumes_sequence() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.log");
+
+        {
+            let mut log = CommitLog::new(path.clone()).unwrap();
+            log.append(b"first").unwrap();
+            log.append(b"second").unwrap();
+        }
+
+        let mut log = CommitLog::new(path).unwrap();
+        assert_eq!(log.current_seq(), 2);
+        assert_eq!(log.append(b"third").unwrap(), 2);
+    }
+
+    #[test]
+    fn test_truncate_before_keeps_numbering() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.log");
+
+        let mut log = CommitLog::new(path.clone()).unwrap();
+        for data in [b"a", b"b", b"c"] {
+            log.append(data).unwrap();
+        }
+        log.truncate_before(2).unwrap();
+        assert_eq!(log.first_seq(), 2);
+        assert_eq!(log.size(), 25);
+        assert_eq!(log.append(b"d").unwrap(), 3);
+        let seqs: Vec<u64> = log.read_all().unwrap().iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, vec![2, 3]);
+
+        // Emptied entirely, the log still resumes after the dropped entries
+        log.truncate_before(4).unwrap();
+        drop(log);
+        let mut log = CommitLog::new(path).unwrap();
+        assert!(log.read_all().unwrap().is_empty());
+        assert_eq!((log.first_seq(), log.current_seq()), (4, 4));
+        assert_eq!(log.append(b"e").unwrap(), 4);
+    }
+
+    #[test]
+    fn test_interrupted_truncation_keeps_old_log() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.log");
+
+        {
+            let mut log = CommitLog::new(path.clone()).unwrap();
+            log.append(b"a").unwrap();
+            log.append(b"b").unwrap();
+        }
+
+        // Crash after the rewrite and the base were written, before the swap
+        std::fs::write(sibling(&path, COMPACTING_SUFFIX), b"partial").unwrap();
+        std::fs::write(sibling(&path, BASE_SUFFIX), 1u64.to_le_bytes()).unwrap();
+
+        let log = CommitLog::new(path.clone()).unwrap();
+        assert!(!sibling(&path, COMPACTING_SUFFIX).exists());
+        assert_eq!(log.read_all().unwrap().len(), 2);
+        assert_eq!((log.first_seq(), log.current_seq()), (0, 2));
+    }
+
+    #[test]
+    fn test_deferred_entries_are_read_before_written() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.log");
+
+        let mut log = CommitLog::new(path.clone()).unwrap();
+        log.append(b"a").unwrap();
+        assert_eq!(log.append_deferred(b"b").unwrap(), 1);
+        assert_eq!(log.append_deferred(b"c").unwrap(), 2);
+        assert_eq!(log.read_all().unwrap().len(), 3);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 25);
+
+        log.write_pending().unwrap();
+        assert_eq!(log.pending_size(), 0);
+        drop(log);
+        let log = CommitLog::new(path).unwrap();
+        let data: Vec<Vec<u8>> = log.read_all().unwrap().into_iter().map(|e| e.data).collect();
+        assert_eq!(data, vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]);
+    }
+
+    #[test]
+    fn test_torn_last_entry_is_discarded() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.log");
+
+        {
+            let mut log = CommitLog::new(path.clone()).unwrap();
+            log.append(b"a").unwrap();
+            log.append(b"b").unwrap();
+        }
+        let len = std::fs::metadata(&path).unwrap().len();
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 3)
+            .unwrap();
+
+        assert_eq!(CommitLog::discard_torn_tail(&path).unwrap(), 22);
+        let mut log = CommitLog::new(path).unwrap();
+        assert_eq!(log.read_all().unwrap().len(), 1);
+        assert_eq!(log.append(b"c").unwrap(), 1);
+    }
 }
@@ -8,6 +8,11 @@
     - Authenticated encryption (AEAD)
     - Unique nonce per encryption
     - Key derivation with Argon2id
+
+    An encrypted store has an `encryption.bin` header in its data directory
+    recording where the key comes from (identity master key or passphrase
+    salt) and a key check value, so a wrong key is rejected when the store
+    is opened instead of producing garbage on the first read.
 */
 
 use crate::core_store::store::errors::{StoreError, StoreResult};
@@ -20,6 +25,122 @@ use argon2::{
     password_hash::{PasswordHasher, SaltString},
     Argon2,
 };
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// Header file marking a data directory as encrypted
+pub const ENCRYPTION_HEADER_FILE: &str = "encryption.bin";
+
+/// Header format version
+const HEADER_VERSION: u32 = 1;
+
+/// Plaintext of the key check value stored in the header
+const KEY_CHECK_PLAINTEXT: &[u8] = b"spacepanda store key check v1";
+
+/// Salt length for passphrase-derived keys
+const PASSPHRASE_SALT_LEN: usize = 16;
+
+/// Key used to encrypt a store at rest
+#[derive(Clone)]
+pub enum StoreKey {
+    /// 32-byte key derived from the identity master key
+    Derived([u8; 32]),
+
+    /// Passphrase, stretched with Argon2id and the salt in the header
+    Passphrase(Zeroizing<String>),
+}
+
+impl StoreKey {
+    /// Key from a passphrase
+    pub fn passphrase(passphrase: &str) -> Self {
+        StoreKey::Passphrase(Zeroizing::new(passphrase.to_string()))
+    }
+}
+
+impl std::fmt::Debug for StoreKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StoreKey::Derived(_) => f.write_str("StoreKey::Derived(..)"),
+            StoreKey::Passphrase(_) => f.write_str("StoreKey::Passphrase(..)"),
+        }
+    }
+}
+
+/// Where an encrypted store's key comes from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeySource {
+    Derived,
+    Passphrase { salt: [u8; PASSPHRASE_SALT_LEN] },
+}
+
+/// Header of an encrypted store
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptionHeader {
+    pub version: u32,
+    pub source: KeySource,
+
+    /// `KEY_CHECK_PLAINTEXT` encrypted with the store key
+    key_check: Vec<u8>,
+}
+
+impl EncryptionHeader {
+    /// Create a header for a new encrypted store, returning it with the cipher
+    pub fn create(key: &StoreKey) -> StoreResult<(Self, EncryptionManager)> {
+        let source = match key {
+            StoreKey::Derived(_) => KeySource::Derived,
+            StoreKey::Passphrase(_) => {
+                use aes_gcm::aead::rand_core::RngCore;
+                let mut salt = [0u8; PASSPHRASE_SALT_LEN];
+                OsRng.fill_bytes(&mut salt);
+                KeySource::Passphrase { salt }
+            }
+        };
+
+        let manager = EncryptionManager::for_source(&source, key)?;
+        let key_check = manager.encrypt(KEY_CHECK_PLAINTEXT)?;
+
+        Ok((EncryptionHeader { version: HEADER_VERSION, source, key_check }, manager))
+    }
+
+    /// Check `key` against this header and return the store's cipher
+    ///
+    /// Fails with `DecryptionFailed` if the key is wrong or of the wrong kind.
+    pub fn unlock(&self, key: &StoreKey) -> StoreResult<EncryptionManager> {
+        let manager = EncryptionManager::for_source(&self.source, key)?;
+        match manager.decrypt(&self.key_check) {
+            Ok(check) if check == KEY_CHECK_PLAINTEXT => Ok(manager),
+            _ => Err(StoreError::DecryptionFailed("wrong store key".to_string())),
+        }
+    }
+
+    /// Read the header from a data directory, if the store is encrypted
+    pub fn load(data_dir: &Path) -> StoreResult<Option<Self>> {
+        let path = data_dir.join(ENCRYPTION_HEADER_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let header: EncryptionHeader = bincode::deserialize(&std::fs::read(&path)?)
+            .map_err(|e| StoreError::CorruptedData(format!("Encryption header: {}", e)))?;
+        if header.version != HEADER_VERSION {
+            return Err(StoreError::CorruptedData(format!(
+                "Unsupported encryption header version {}",
+                header.version
+            )));
+        }
+
+        Ok(Some(header))
+    }
+
+    /// Write the header into a data directory
+    pub fn save(&self, data_dir: &Path) -> StoreResult<()> {
+        let temp_path = data_dir.join(format!("{}.tmp", ENCRYPTION_HEADER_FILE));
+        std::fs::write(&temp_path, bincode::serialize(self)?)?;
+        std::fs::rename(temp_path, data_dir.join(ENCRYPTION_HEADER_FILE))?;
+        Ok(())
+    }
+}
 
 /// Manages encryption/decryption of data at rest
 pub struct EncryptionManager {
@@ -55,6 +176,42 @@ impl EncryptionManager {
         Ok(EncryptionManager { cipher })
     }
 
+    /// Create from a 32-byte key
+    pub fn from_key(key: &[u8; 32]) -> Self {
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+        EncryptionManager { cipher }
+    }
+
+    /// Create from a passphrase and a stored salt
+    ///
+    /// Unlike `from_passphrase`, the same inputs always give the same key.
+    pub fn from_passphrase_with_salt(passphrase: &str, salt: &[u8]) -> StoreResult<Self> {
+        let mut key = Zeroizing::new([0u8; 32]);
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key[..])
+            .map_err(|e| StoreError::EncryptionError(e.to_string()))?;
+
+        Ok(Self::from_key(&key))
+    }
+
+    /// Cipher for a store key, given where the store expects it to come from
+    fn for_source(source: &KeySource, key: &StoreKey) -> StoreResult<Self> {
+        match (source, key) {
+            (KeySource::Derived, StoreKey::Derived(key)) => Ok(Self::from_key(key)),
+            (KeySource::Passphrase { salt }, StoreKey::Passphrase(passphrase)) => {
+                Self::from_passphrase_with_salt(passphrase, salt)
+            }
+            (KeySource::Derived, StoreKey::Passphrase(_)) => Err(StoreError::DecryptionFailed(
+                "store is encrypted with the identity key, not a passphrase".to_string(),
+            )),
+            (KeySource::Passphrase { .. }, StoreKey::Derived(_)) => {
+                Err(StoreError::DecryptionFailed(
+                    "store is encrypted with a passphrase, not the identity key".to_string(),
+                ))
+            }
+        }
+    }
+
     /// Encrypt data
     pub fn encrypt(&self, plaintext: &[u8]) -> StoreResult<Vec<u8>> {
         // Generate random nonce
@@ -79,17 +236,18 @@ impl EncryptionManager {
     /// Decrypt data
     pub fn decrypt(&self, ciphertext: &[u8]) -> StoreResult<Vec<u8>> {
         if ciphertext.len() < 12 {
-            return Err(StoreError::EncryptionError("Invalid ciphertext length".to_string()));
+            return Err(StoreError::DecryptionFailed("ciphertext too short".to_string()));
         }
 
         // Extract nonce
         let nonce = Nonce::from_slice(&ciphertext[..12]);
 
         // Decrypt
+        // Authentication fails for a wrong key as well as for tampered data
         let plaintext = self
             .cipher
             .decrypt(nonce, &ciphertext[12..])
-            .map_err(|e| StoreError::EncryptionError(e.to_string()))?;
+            .map_err(|_| StoreError::DecryptionFailed("wrong key or corrupted data".to_string()))?;
 
         Ok(plaintext)
     }
@@ -153,4 +311,29 @@ mod tests {
         let result = manager.decrypt(b"invalid");
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_wrong_key_fails_clearly() {
+        let ciphertext = EncryptionManager::from_key(&[1; 32]).encrypt(b"secret").unwrap();
+        let result = EncryptionManager::from_key(&[2; 32]).decrypt(&ciphertext);
+        assert!(matches!(result, Err(StoreError::DecryptionFailed(_))));
+    }
+
+    #[test]
+    fn test_header_unlock() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let key = StoreKey::passphrase("correct horse");
+        let (header, manager) = EncryptionHeader::create(&key).unwrap();
+        header.save(dir.path()).unwrap();
+        let ciphertext = manager.encrypt(b"channel state").unwrap();
+
+        let header = EncryptionHeader::load(dir.path()).unwrap().unwrap();
+        let reopened = header.unlock(&key).unwrap();
+        assert_eq!(reopened.decrypt(&ciphertext).unwrap(), b"channel state");
+
+        for wrong in [StoreKey::passphrase("battery staple"), StoreKey::Derived([0; 32])] {
+            assert!(matches!(header.unlock(&wrong), Err(StoreError::DecryptionFailed(_))));
+        }
+    }
 }
@@ -61,6 +61,10 @@ pub enum StoreError {
     #[error("Decryption error: {0}")]
     Decryption(String),
 
+    /// Encrypted data could not be authenticated (wrong key or tampering)
+    #[error("Decryption failed: {0}")]
+    DecryptionFailed(String),
+
     /// Corrupted data detected
     #[error("Corrupted data: {0}")]
     CorruptedData(String),
@@ -85,6 +89,18 @@ pub enum StoreError {
     #[error("DHT error: {0}")]
     Dht(String),
 
+    /// Sync session with a peer failed
+    #[error("Sync error: {0}")]
+    Sync(String),
+
+    /// Not enough free disk space to start an operation
+    #[error("Insufficient storage: {required} bytes required, {available} available")]
+    InsufficientStorage { required: u64, available: u64 },
+
+    /// A page cursor that doesn't belong to the listing it was passed to
+    #[error("Invalid cursor: {0}")]
+    InvalidCursor(String),
+
     /// Concurrent modification conflict
     #[error("Concurrent modification: {0}")]
     Conflict(String),
@@ -149,6 +165,12 @@ impl From<CrdtError> for StoreError {
     }
 }
 
+impl From<super::preflight::InsufficientStorage> for StoreError {
+    fn from(err: super::preflight::InsufficientStorage) -> Self {
+        StoreError::InsufficientStorage { required: err.required, available: err.available }
+    }
+}
+
 impl From<std::io::Error> for StoreError {
     fn from(err: std::io::Error) -> Self {
         StoreError::Storage(err.to_string())
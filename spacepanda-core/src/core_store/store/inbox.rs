@@ -0,0 +1,185 @@
+/*
+    inbox.rs - Durable queue of received but unprocessed envelopes
+
+    Incoming ciphertext is recorded here before decryption is attempted and
+    removed once it has been processed. Anything left over (the client
+    exited mid-processing, or the message belongs to an epoch whose commit
+    has not arrived yet) is retried later instead of being lost.
+
+    The queue is small and short-lived, so it is persisted as a single
+    bincode file rewritten atomically on every change.
+*/
+
+use crate::core_store::model::{ChannelId, Timestamp, UserId};
+use crate::core_store::store::errors::StoreResult;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// A received envelope awaiting processing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingEnvelope {
+    /// Queue-local identifier
+    pub id: u64,
+
+    /// Channel the envelope was addressed to
+    pub channel_id: ChannelId,
+
+    /// Sender as announced by the delivering peer
+    pub sender: UserId,
+
+    /// MLS ciphertext
+    pub ciphertext: Vec<u8>,
+
+    /// When the envelope was received
+    pub received_at: Timestamp,
+
+    /// Failed processing attempts so far
+    pub attempts: u32,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct InboxState {
+    next_id: u64,
+    envelopes: Vec<PendingEnvelope>,
+}
+
+/// Persistent queue of pending envelopes, in arrival order
+pub struct PendingInbox {
+    path: PathBuf,
+    state: Mutex<InboxState>,
+}
+
+impl PendingInbox {
+    /// Open (or create) the inbox stored at `path`
+    pub fn open(path: PathBuf) -> StoreResult<Self> {
+        let state = if path.exists() {
+            bincode::deserialize(&fs::read(&path)?)?
+        } else {
+            InboxState::default()
+        };
+
+        Ok(Self { path, state: Mutex::new(state) })
+    }
+
+    /// Queue an envelope, returning its ID
+    pub fn enqueue(
+        &self,
+        channel_id: ChannelId,
+        sender: UserId,
+        ciphertext: Vec<u8>,
+    ) -> StoreResult<u64> {
+        let mut state = self.lock();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.envelopes.push(PendingEnvelope {
+            id,
+            channel_id,
+            sender,
+            ciphertext,
+            received_at: Timestamp::now(),
+            attempts: 0,
+        });
+        self.persist(&state)?;
+        Ok(id)
+    }
+
+    /// Pending envelopes for a channel, oldest first
+    pub fn pending(&self, channel_id: &ChannelId) -> Vec<PendingEnvelope> {
+        self.lock()
+            .envelopes
+            .iter()
+            .filter(|e| &e.channel_id == channel_id)
+            .cloned()
+            .collect()
+    }
+
+    /// Get a pending envelope by ID
+    pub fn get(&self, id: u64) -> Option<PendingEnvelope> {
+        self.lock().envelopes.iter().find(|e| e.id == id).cloned()
+    }
+
+    /// Remove a processed envelope; returns false if it was not queued
+    pub fn remove(&self, id: u64) -> StoreResult<bool> {
+        let mut state = self.lock();
+        let before = state.envelopes.len();
+        state.envelopes.retain(|e| e.id != id);
+        if state.envelopes.len() == before {
+            return Ok(false);
+        }
+        self.persist(&state)?;
+        Ok(true)
+    }
+
+    /// Record a failed processing attempt, returning the new attempt count
+    pub fn record_attempt(&self, id: u64) -> StoreResult<u32> {
+        let mut state = self.lock();
+        let Some(envelope) = state.envelopes.iter_mut().find(|e| e.id == id) else {
+            return Ok(0);
+        };
+        envelope.attempts += 1;
+        let attempts = envelope.attempts;
+        self.persist(&state)?;
+        Ok(attempts)
+    }
+
+    /// Total number of pending envelopes
+    pub fn len(&self) -> usize {
+        self.lock().envelopes.len()
+    }
+
+    /// Whether the inbox is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn persist(&self, state: &InboxState) -> StoreResult<()> {
+        // Atomic write: temp file then rename
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, bincode::serialize(state)?)?;
+        fs::rename(tmp, &self.path)?;
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, InboxState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_inbox_survives_reopen() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("inbox.bin");
+        let general = ChannelId("general".to_string());
+        let random = ChannelId("random".to_string());
+        let alice = UserId("alice".to_string());
+
+        let first = {
+            let inbox = PendingInbox::open(path.clone()).unwrap();
+            let first = inbox.enqueue(general.clone(), alice.clone(), vec![1]).unwrap();
+            inbox.enqueue(random.clone(), alice.clone(), vec![2]).unwrap();
+            inbox.enqueue(general.clone(), alice.clone(), vec![3]).unwrap();
+            assert_eq!(inbox.record_attempt(first).unwrap(), 1);
+            first
+        };
+
+        let inbox = PendingInbox::open(path).unwrap();
+        let pending = inbox.pending(&general);
+        assert_eq!(pending.iter().map(|e| e.ciphertext[0]).collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(pending[0].attempts, 1);
+
+        assert!(inbox.remove(first).unwrap());
+        assert!(!inbox.remove(first).unwrap());
+        assert_eq!(inbox.len(), 2);
+
+        // IDs are never reused after reopening
+        let next = inbox.enqueue(general, alice, vec![4]).unwrap();
+        assert!(next > pending[1].id);
+    }
+}
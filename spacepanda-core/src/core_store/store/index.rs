@@ -63,6 +63,15 @@ impl IndexManager {
         Ok(())
     }
 
+    /// Remove a channel and its user mappings
+    pub fn remove_channel(&self, channel_id: &ChannelId) -> StoreResult<()> {
+        self.channel_index.write().map_err(handle_poison)?.remove(channel_id);
+        for channels in self.user_channels.write().map_err(handle_poison)?.values_mut() {
+            channels.remove(channel_id);
+        }
+        Ok(())
+    }
+
     /// Add user to space mapping
     pub fn add_user_to_space(&self, user_id: &UserId, space_id: &SpaceId) -> StoreResult<()> {
         self.user_spaces
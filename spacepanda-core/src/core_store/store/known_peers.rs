@@ -0,0 +1,133 @@
+/*
+    known_peers.rs - DHT peers remembered across restarts
+
+    The DHT routing table lives in memory. Its known-good peers are copied
+    here periodically and on shutdown, so the next start can rejoin the
+    network through them instead of depending on the configured bootstrap
+    peers alone.
+
+    Like the inbox and outbox, the list is persisted as a single bincode
+    file rewritten atomically on every change.
+*/
+
+use crate::core_store::store::errors::StoreResult;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// A DHT peer seen in an earlier run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KnownPeer {
+    /// Peer's DHT node ID
+    pub node_id: [u8; 32],
+
+    /// Network address it was reached at
+    pub address: String,
+
+    /// When it last answered (Unix seconds)
+    pub last_seen: u64,
+
+    /// How reliably it has answered, from 0.0 to 1.0
+    pub reliability: f64,
+}
+
+/// Persistent list of DHT peers
+pub struct KnownPeers {
+    path: PathBuf,
+    peers: Mutex<Vec<KnownPeer>>,
+}
+
+impl KnownPeers {
+    /// Open (or create) the peer list stored at `path`
+    pub fn open(path: PathBuf) -> StoreResult<Self> {
+        let peers = if path.exists() {
+            bincode::deserialize(&fs::read(&path)?)?
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self { path, peers: Mutex::new(peers) })
+    }
+
+    /// Replace the list with a fresh copy of the routing table's peers
+    pub fn replace(&self, mut peers: Vec<KnownPeer>) -> StoreResult<()> {
+        peers.sort_by(|a, b| b.reliability.total_cmp(&a.reliability));
+        let mut current = self.lock();
+        *current = peers;
+        self.persist(&current)
+    }
+
+    /// All remembered peers, most reliable first
+    pub fn all(&self) -> Vec<KnownPeer> {
+        self.lock().clone()
+    }
+
+    /// Forget a peer; returns false if it wasn't known
+    pub fn remove(&self, node_id: &[u8; 32]) -> StoreResult<bool> {
+        let mut peers = self.lock();
+        let before = peers.len();
+        peers.retain(|p| &p.node_id != node_id);
+        if peers.len() == before {
+            return Ok(false);
+        }
+        self.persist(&peers)?;
+        Ok(true)
+    }
+
+    /// Number of remembered peers
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Whether no peers are remembered
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn persist(&self, peers: &[KnownPeer]) -> StoreResult<()> {
+        // Atomic write: temp file then rename
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, bincode::serialize(peers)?)?;
+        fs::rename(tmp, &self.path)?;
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<KnownPeer>> {
+        self.peers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn peer(byte: u8, reliability: f64) -> KnownPeer {
+        KnownPeer {
+            node_id: [byte; 32],
+            address: format!("192.0.2.{}:7000", byte),
+            last_seen: 1_700_000_000,
+            reliability,
+        }
+    }
+
+    #[test]
+    fn test_peers_survive_reopen() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("dht_peers.bin");
+
+        let peers = KnownPeers::open(path.clone()).unwrap();
+        assert!(peers.is_empty());
+        peers.replace(vec![peer(1, 0.2), peer(2, 0.9)]).unwrap();
+        drop(peers);
+
+        let peers = KnownPeers::open(path).unwrap();
+        let all = peers.all();
+        assert_eq!(all, vec![peer(2, 0.9), peer(1, 0.2)]);
+
+        assert!(peers.remove(&[1; 32]).unwrap());
+        assert!(!peers.remove(&[1; 32]).unwrap());
+        assert_eq!(peers.len(), 1);
+    }
+}
@@ -7,27 +7,290 @@
     Architecture:
     - Append-only commit log for all operations
     - Periodic snapshots for fast rehydration
+    - Compaction: snapshot, then drop the log entries it covers
+    - Optional write-ahead log with group commit (see wal.rs)
+    - Pruning of messages past their channel's retention policy
     - Indices for efficient queries
     - At-rest encryption for all data
+    - Observers notified after each durable commit (see observer.rs)
+    - Inbox of received envelopes awaiting processing (see inbox.rs)
+    - Outbox of work waiting for connectivity (see outbox.rs)
+    - Delivery state of sent messages (see receipts.rs)
+    - DHT peers remembered across restarts (see known_peers.rs)
+
+    Compaction runs under the commit log's write lock, in stages that each
+    leave a recoverable store behind: the snapshot is written and synced
+    under a temporary name, moved in place, read back, and only then is the
+    log truncated up to the snapshot's `log_seq`. The previous snapshot is
+    kept as a fallback. `load` replays only the entries after the snapshot
+    it starts from, so nothing is lost or applied twice whichever stage a
+    crash interrupts.
+
+    With `enable_wal`, a change is queued in the WAL and held in memory by
+    the commit log, then the writer releases the commit log and waits until
+    the batch holding its record is fsynced; writers that arrive together
+    share one fsync. The commit log file is written in bulk once enough has
+    piled up, and on drop, after which the WAL is emptied. Opening the
+    store moves whatever only the WAL holds into the commit log. Without
+    the WAL every write is appended to the commit log and synced on its
+    own, under the commit log lock.
+
+    Either way a write returns, and observers hear of it, only once it is
+    durable. Observers are notified in commit order (see `CommitOrder`).
+
+    Pruning records the removed message IDs in the commit log, so `load`
+    and observers replaying the log drop them too, then compacts so their
+    content leaves the log. A remote copy of a message the channel's
+    retention policy would already have pruned is ignored, so a replica
+    that hasn't pruned yet can't bring it back.
 */
 
+use crate::core_mls::channel_metadata::RetentionPolicy;
 use crate::core_store::crdt::{Crdt, OperationMetadata};
-use crate::core_store::model::{Channel, ChannelId, Message, MessageId, Space, SpaceId};
+use crate::core_store::model::{
+    Channel, ChannelId, Message, MessageId, Space, SpaceId, Timestamp,
+};
 use crate::core_store::store::commit_log::CommitLog;
-use crate::core_store::store::encryption::EncryptionManager;
+use crate::core_store::store::encryption::{
+    EncryptionHeader, EncryptionManager, StoreKey, ENCRYPTION_HEADER_FILE,
+};
 use crate::core_store::store::errors::{StoreError, StoreResult};
+use crate::core_store::store::inbox::PendingInbox;
 use crate::core_store::store::index::IndexManager;
-use crate::core_store::store::snapshot::SnapshotManager;
+use crate::core_store::store::known_peers::KnownPeers;
+use crate::core_store::store::observer::{
+    deliver, ChangeOrigin, CommitOrder, ObserverFilter, ObserverId, ObserverRegistry, StoreChange,
+    StoreEvent, StoreObserver,
+};
+use crate::core_store::store::oplog_export::OplogExporter;
+use crate::core_store::store::outbox::PendingOutbox;
+use crate::core_store::store::receipts::DeliveryReceipts;
+use crate::core_store::store::snapshot::{PendingSnapshot, SnapshotManager};
+use crate::core_store::store::wal::{WalConfig, WriteAheadLog};
+use crate::core_store::sync::{
+    apply_remote_to_channel, apply_remote_to_message, apply_remote_to_space,
+};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, PoisonError, RwLock};
+use tracing::{debug, warn};
 
 /// Helper to convert poison errors into StoreError
 fn handle_poison<T>(_err: PoisonError<T>) -> StoreError {
     StoreError::Storage("Lock poisoned: a thread panicked while holding the lock".to_string())
 }
 
+/// Suffix of files written by `encrypt_in_place` before they replace the originals
+const ENCRYPTING_SUFFIX: &str = "encrypting";
+
+/// Marker at the start of a space, channel or message write in the commit log
+const ENTITY_ENTRY_MAGIC: [u8; 8] = *b"SPENTITY";
+
+/// Commit log entry recording a space, channel or message write
+///
+/// Tagged with its type, so it's never mistaken for another entity whose
+/// encoding happens to fit the same bytes. Stores written before the tag
+/// was introduced hold bare entities, which `decode_change` still reads.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EntityEntry {
+    magic: [u8; 8],
+    entity: StoredEntity,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum StoredEntity {
+    Space(Space),
+    Channel(Channel),
+    Message(Message),
+}
+
+impl EntityEntry {
+    fn new(entity: StoredEntity) -> Self {
+        Self { magic: ENTITY_ENTRY_MAGIC, entity }
+    }
+
+    fn decode(data: &[u8]) -> Option<Self> {
+        let entry: EntityEntry = bincode::deserialize(data).ok()?;
+        let size = bincode::serialized_size(&entry).ok()?;
+        (entry.magic == ENTITY_ENTRY_MAGIC && size == data.len() as u64).then_some(entry)
+    }
+}
+
+/// Marker at the start of a channel removal entry in the commit log
+const CHANNEL_TOMBSTONE_MAGIC: [u8; 8] = *b"SPCHTOMB";
+
+/// Commit log entry recording that a channel was removed locally
+///
+/// Replayed by `load` after the channel's own entries, so the channel
+/// stays gone across restarts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ChannelTombstone {
+    magic: [u8; 8],
+    channel_id: ChannelId,
+}
+
+impl ChannelTombstone {
+    fn decode(data: &[u8]) -> Option<Self> {
+        let tombstone: ChannelTombstone = bincode::deserialize(data).ok()?;
+        let size = bincode::serialized_size(&tombstone).ok()?;
+        (tombstone.magic == CHANNEL_TOMBSTONE_MAGIC && size == data.len() as u64)
+            .then_some(tombstone)
+    }
+}
+
+/// Marker at the start of a message pruning entry in the commit log
+const MESSAGE_PRUNE_MAGIC: [u8; 8] = *b"SPMSPRUN";
+
+/// Commit log entry recording messages removed by retention
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MessagePrune {
+    magic: [u8; 8],
+    channel_id: ChannelId,
+    message_ids: Vec<MessageId>,
+}
+
+impl MessagePrune {
+    fn decode(data: &[u8]) -> Option<Self> {
+        let prune: MessagePrune = bincode::deserialize(data).ok()?;
+        let size = bincode::serialized_size(&prune).ok()?;
+        (prune.magic == MESSAGE_PRUNE_MAGIC && size == data.len() as u64).then_some(prune)
+    }
+}
+
+/// Order of messages for retention: by timestamp, then ID
+fn retention_key(message: &Message) -> (Timestamp, &str) {
+    (message.timestamp, message.id.0.as_str())
+}
+
+/// Messages a retention policy no longer keeps, oldest first
+fn expired(policy: RetentionPolicy, messages: &[Message], now: Timestamp) -> Vec<&Message> {
+    let mut sorted: Vec<&Message> = messages.iter().collect();
+    sorted.sort_by(|a, b| retention_key(a).cmp(&retention_key(b)));
+
+    match policy {
+        RetentionPolicy::KeepForever => Vec::new(),
+        RetentionPolicy::KeepDays(_) => {
+            let cutoff = policy.cutoff(now).unwrap_or(Timestamp::from_millis(0));
+            sorted.into_iter().take_while(|message| message.timestamp < cutoff).collect()
+        }
+        RetentionPolicy::KeepMessages(keep) => {
+            sorted.truncate(sorted.len().saturating_sub(keep as usize));
+            sorted
+        }
+    }
+}
+
+/// Whether a message not in `messages` would be expired on arrival
+fn past_horizon(
+    policy: RetentionPolicy,
+    messages: &[Message],
+    message: &Message,
+    now: Timestamp,
+) -> bool {
+    match policy {
+        RetentionPolicy::KeepForever => false,
+        RetentionPolicy::KeepDays(_) => {
+            policy.cutoff(now).is_some_and(|cutoff| message.timestamp < cutoff)
+        }
+        RetentionPolicy::KeepMessages(keep) => {
+            let key = retention_key(message);
+            messages.iter().filter(|m| retention_key(m) > key).count() >= keep as usize
+        }
+    }
+}
+
+/// File name of the write-ahead log in the data directory
+const WAL_FILE: &str = "wal";
+
+/// Deferred commit log entries that trigger writing them out and emptying
+/// the WAL
+const WAL_CHECKPOINT_BYTES: usize = 4 * 1024 * 1024;
+
+/// Snapshots kept by compaction: the new one and the one before it
+const SNAPSHOTS_KEPT: usize = 2;
+
+/// Stages of `LocalStore::compact`, in order
+///
+/// Tests stop compaction after a stage to simulate a crash there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(test), allow(dead_code))]
+pub(crate) enum CompactionStage {
+    /// The snapshot is written and synced under a temporary name
+    SnapshotWritten,
+
+    /// The snapshot is in place but not read back yet
+    SnapshotPublished,
+
+    /// The snapshot was read back; the log still holds what it covers
+    SnapshotVerified,
+
+    /// The log was truncated; older snapshots are still on disk
+    LogTruncated,
+}
+
+/// Add a message to a channel's messages, replacing an earlier version
+///
+/// A new version of a stored message (e.g. edited) keeps its place.
+fn upsert_message(messages: &mut HashMap<ChannelId, Vec<Message>>, message: Message) {
+    let messages = messages.entry(message.channel_id.clone()).or_default();
+    match messages.iter_mut().find(|m| m.id == message.id) {
+        Some(stored) => *stored = message,
+        None => messages.push(message),
+    }
+}
+
+/// Remove messages from a channel's messages
+fn remove_messages(
+    messages: &mut HashMap<ChannelId, Vec<Message>>,
+    channel_id: &ChannelId,
+    message_ids: &[MessageId],
+) {
+    if let Some(messages) = messages.get_mut(channel_id) {
+        messages.retain(|message| !message_ids.contains(&message.id));
+    }
+}
+
+/// Whether a data directory already holds store data
+fn has_data(data_dir: &Path) -> StoreResult<bool> {
+    for file in ["commit_log", WAL_FILE] {
+        if std::fs::metadata(data_dir.join(file)).map(|m| m.len()).unwrap_or(0) > 0 {
+            return Ok(true);
+        }
+    }
+
+    let snapshots = data_dir.join("snapshots");
+    Ok(snapshots.exists() && std::fs::read_dir(snapshots)?.next().is_some())
+}
+
+/// Swap in files left by `encrypt_in_place`, once the store is marked encrypted
+fn finish_encryption(data_dir: &Path) -> StoreResult<()> {
+    if !data_dir.join(ENCRYPTION_HEADER_FILE).exists() {
+        return Ok(());
+    }
+
+    let log = data_dir.join(format!("commit_log.{}", ENCRYPTING_SUFFIX));
+    if log.exists() {
+        std::fs::rename(log, data_dir.join("commit_log"))?;
+    }
+
+    let snapshots = data_dir.join(format!("snapshots.{}", ENCRYPTING_SUFFIX));
+    if snapshots.exists() {
+        let old = data_dir.join("snapshots");
+        if old.exists() {
+            std::fs::remove_dir_all(&old)?;
+        }
+        std::fs::rename(snapshots, old)?;
+    }
+
+    Ok(())
+}
+
+fn default_enable_wal() -> bool {
+    true
+}
+
 /// Configuration for local storage
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LocalStoreConfig {
@@ -46,6 +309,11 @@ pub struct LocalStoreConfig {
     /// Enable automatic compaction
     pub enable_compaction: bool,
 
+    /// Write through a write-ahead log with group commit instead of syncing
+    /// the commit log on every write
+    #[serde(default = "default_enable_wal")]
+    pub enable_wal: bool,
+
     /// Require signatures on all operations (production mode)
     pub require_signatures: bool,
 
@@ -61,6 +329,7 @@ impl Default for LocalStoreConfig {
             snapshot_interval: 1000,
             max_log_size: 10_000_000, // 10MB
             enable_compaction: true,
+            enable_wal: true,
             require_signatures: false, // Disabled by default for backwards compatibility
             authorized_keys: Vec::new(),
         }
@@ -86,25 +355,174 @@ pub struct LocalStore {
 
     /// Operation counter for snapshots
     operation_count: Arc<RwLock<usize>>,
+
+    /// Whether the caches hold all state on disk, i.e. `load` has run or
+    /// the store started empty; snapshots are only taken once they do
+    loaded: AtomicBool,
+
+    /// Observers notified after each commit
+    observers: Arc<ObserverRegistry>,
+
+    /// Order observers are notified in
+    commit_order: Arc<CommitOrder>,
+
+    /// Received envelopes awaiting processing
+    inbox: Arc<PendingInbox>,
+
+    /// Undelivered messages and deferred joins
+    outbox: Arc<PendingOutbox>,
+
+    /// Delivery state of sent messages
+    receipts: Arc<DeliveryReceipts>,
+
+    known_peers: Arc<KnownPeers>,
+
+    /// Write-ahead log in front of the commit log, if enabled
+    wal: Option<WriteAheadLog>,
 }
 
 impl LocalStore {
     /// Create a new local store with the given configuration
+    ///
+    /// With `enable_encryption`, data is encrypted under a random key that
+    /// lasts for this instance only; use `open_encrypted` for a store that
+    /// must be reopened.
     pub fn new(config: LocalStoreConfig) -> StoreResult<Self> {
+        let encryption = if config.enable_encryption {
+            Some(Arc::new(EncryptionManager::new()?))
+        } else {
+            None
+        };
+
+        Self::open_with(config, encryption)
+    }
+
+    /// Open a store encrypted at rest under `key`
+    ///
+    /// A new, empty store is marked encrypted. An existing encrypted store
+    /// is only opened if `key` matches, otherwise this fails with
+    /// `StoreError::DecryptionFailed`. An existing unencrypted store is
+    /// refused; migrate it first with `encrypt_in_place`.
+    pub fn open_encrypted(config: LocalStoreConfig, key: &StoreKey) -> StoreResult<Self> {
+        let data_dir = config.data_dir.clone();
+        std::fs::create_dir_all(&data_dir)?;
+        finish_encryption(&data_dir)?;
+
+        let encryption = match EncryptionHeader::load(&data_dir)? {
+            Some(header) => header.unlock(key)?,
+            None if has_data(&data_dir)? => {
+                return Err(StoreError::InvalidOperation(format!(
+                    "Store at {:?} is not encrypted; migrate it first",
+                    data_dir
+                )));
+            }
+            None => {
+                let (header, encryption) = EncryptionHeader::create(key)?;
+                header.save(&data_dir)?;
+                encryption
+            }
+        };
+
+        Self::open_with(
+            LocalStoreConfig { enable_encryption: true, ..config },
+            Some(Arc::new(encryption)),
+        )
+    }
+
+    /// Whether the store in `data_dir` is encrypted at rest
+    pub fn is_encrypted(data_dir: &Path) -> bool {
+        data_dir.join(ENCRYPTION_HEADER_FILE).exists()
+    }
+
+    /// Encrypt an existing unencrypted store in place
+    ///
+    /// Every commit log entry is re-encrypted under `key`, in order, and the
+    /// current state is written as a new encrypted snapshot. Both are built next to the originals and swapped
+    /// in once complete; if the swap is interrupted, the next
+    /// `open_encrypted` finishes it.
+    ///
+    /// # Returns
+    ///
+    /// Number of commit log entries migrated
+    pub fn encrypt_in_place(config: LocalStoreConfig, key: &StoreKey) -> StoreResult<usize> {
+        let data_dir = config.data_dir.clone();
+        if Self::is_encrypted(&data_dir) {
+            return Err(StoreError::InvalidOperation(format!(
+                "Store at {:?} is already encrypted",
+                data_dir
+            )));
+        }
+
+        let plain = Self::open_with(LocalStoreConfig { enable_encryption: false, ..config }, None)?;
+        plain.load()?;
+        let entries = plain.commit_log.read().map_err(handle_poison)?.read_all()?;
+        let spaces = plain.spaces_cache.read().map_err(handle_poison)?.clone();
+        let channels = plain.channels_cache.read().map_err(handle_poison)?.clone();
+        let messages = plain.messages_cache.read().map_err(handle_poison)?.clone();
+        drop(plain);
+
+        let (header, encryption) = EncryptionHeader::create(key)?;
+        let encryption = Arc::new(encryption);
+
+        let log_path = data_dir.join(format!("commit_log.{}", ENCRYPTING_SUFFIX));
+        let _ = std::fs::remove_file(&log_path);
+        let mut log = CommitLog::new(log_path)?;
+        for entry in &entries {
+            log.append(&encryption.encrypt(&entry.data)?)?;
+        }
+        drop(log);
+
+        let snapshots_path = data_dir.join(format!("snapshots.{}", ENCRYPTING_SUFFIX));
+        if snapshots_path.exists() {
+            std::fs::remove_dir_all(&snapshots_path)?;
+        }
+        SnapshotManager::new(snapshots_path)?
+            .with_encryption(Some(encryption))
+            .create_snapshot_at(spaces, channels, messages, 0)?;
+
+        // From here on the store counts as encrypted
+        header.save(&data_dir)?;
+        finish_encryption(&data_dir)?;
+
+        Ok(entries.len())
+    }
+
+    /// Open the store's files with the given cipher
+    fn open_with(
+        config: LocalStoreConfig,
+        encryption: Option<Arc<EncryptionManager>>,
+    ) -> StoreResult<Self> {
         // Create data directory if it doesn't exist
         std::fs::create_dir_all(&config.data_dir)?;
 
-        let commit_log = Arc::new(RwLock::new(CommitLog::new(config.data_dir.join("commit_log"))?));
+        // A WAL left by an earlier run is recovered even with the WAL off
+        let wal_path = config.data_dir.join(WAL_FILE);
+        let (commit_log, wal) = if config.enable_wal || wal_path.exists() {
+            let (log, wal) = Self::recover_wal(&config.data_dir)?;
+            if config.enable_wal {
+                (log, Some(wal))
+            } else {
+                drop(wal);
+                std::fs::remove_file(&wal_path)?;
+                (log, None)
+            }
+        } else {
+            (CommitLog::new(config.data_dir.join("commit_log"))?, None)
+        };
+        let commit_log = Arc::new(RwLock::new(commit_log));
+        let loaded = AtomicBool::new(!has_data(&config.data_dir)?);
 
-        let snapshot_manager = Arc::new(SnapshotManager::new(config.data_dir.join("snapshots"))?);
+        let snapshot_manager = Arc::new(
+            SnapshotManager::new(config.data_dir.join("snapshots"))?
+                .with_encryption(encryption.clone()),
+        );
 
         let index_manager = Arc::new(IndexManager::new(config.data_dir.join("indices"))?);
 
-        let encryption = if config.enable_encryption {
-            Some(Arc::new(EncryptionManager::new()?))
-        } else {
-            None
-        };
+        let inbox = Arc::new(PendingInbox::open(config.data_dir.join("inbox.bin"))?);
+        let outbox = Arc::new(PendingOutbox::open(config.data_dir.join("outbox.bin"))?);
+        let receipts = Arc::new(DeliveryReceipts::open(config.data_dir.join("receipts.bin"))?);
+        let known_peers = Arc::new(KnownPeers::open(config.data_dir.join("dht_peers.bin"))?);
 
         Ok(LocalStore {
             config,
@@ -116,37 +534,405 @@ impl LocalStore {
             channels_cache: Arc::new(RwLock::new(HashMap::new())),
             messages_cache: Arc::new(RwLock::new(HashMap::new())),
             operation_count: Arc::new(RwLock::new(0)),
+            loaded,
+            observers: Arc::new(ObserverRegistry::new()),
+            commit_order: Arc::new(CommitOrder::default()),
+            inbox,
+            outbox,
+            receipts,
+            known_peers,
+            wal,
         })
     }
 
-    /// Store a space
-    pub fn store_space(&self, space: &Space) -> StoreResult<()> {
-        // Serialize space
-        let data = bincode::serialize(space)?;
+    /// Open the commit log and the WAL, moving the entries only the WAL
+    /// holds into the commit log
+    fn recover_wal(data_dir: &Path) -> StoreResult<(CommitLog, WriteAheadLog)> {
+        let log_path = data_dir.join("commit_log");
+        let torn = CommitLog::discard_torn_tail(&log_path)?;
+        if torn > 0 {
+            warn!("Discarded {} bytes of a torn entry at the end of the commit log", torn);
+        }
+
+        let mut log = CommitLog::new(log_path)?;
+        let (wal, records) = WriteAheadLog::open(data_dir.join(WAL_FILE), WalConfig::default())?;
+        for record in records {
+            // Already written to the commit log
+            if record.seq < log.current_seq() {
+                continue;
+            }
+            if record.seq > log.current_seq() {
+                return Err(StoreError::CorruptedData(format!(
+                    "WAL continues at entry {} but the commit log ends before {}",
+                    record.seq,
+                    log.current_seq()
+                )));
+            }
+            log.append_deferred(&record.data)?;
+        }
+
+        log.write_pending()?;
+        wal.reset(log.current_seq())?;
+        Ok((log, wal))
+    }
+
+    /// Append an entry to the commit log, through the WAL if enabled
+    ///
+    /// Without the WAL the entry is synced before this returns. With it
+    /// the entry is only queued; release the commit log and call
+    /// `wait_durable`.
+    fn append_entry(&self, log: &mut CommitLog, data: &[u8]) -> StoreResult<u64> {
+        let Some(wal) = &self.wal else {
+            let seq = log.append(data)?;
+            log.sync()?;
+            return Ok(seq);
+        };
+
+        wal.append(log.current_seq(), data)?;
+        let seq = log.append_deferred(data)?;
+        if log.pending_size() >= WAL_CHECKPOINT_BYTES {
+            self.checkpoint_wal(log)?;
+        }
+        Ok(seq)
+    }
+
+    /// Wait until entry `seq` is durable
+    ///
+    /// Writers waiting together share one WAL fsync.
+    fn wait_durable(&self, seq: u64) -> StoreResult<()> {
+        match &self.wal {
+            Some(wal) => wal.wait_durable(seq + 1),
+            None => Ok(()),
+        }
+    }
+
+    /// Write the entries only the WAL holds to the commit log, then empty it
+    fn checkpoint_wal(&self, log: &mut CommitLog) -> StoreResult<()> {
+        if let Some(wal) = &self.wal {
+            log.write_pending()?;
+            wal.reset(log.current_seq())?;
+        }
+        Ok(())
+    }
+
+    /// Wait until every change committed so far is durable
+    ///
+    /// Each write already waits for its own entry; this also covers writes
+    /// other threads still have in flight. Concurrent callers share one
+    /// fsync.
+    pub fn sync(&self) -> StoreResult<()> {
+        match &self.wal {
+            Some(wal) => wal.sync(),
+            None => Ok(()),
+        }
+    }
+
+    /// Number of fsyncs the WAL has made, if enabled
+    #[cfg(test)]
+    pub(crate) fn wal_fsyncs(&self) -> Option<u64> {
+        self.wal.as_ref().map(WriteAheadLog::fsyncs)
+    }
+
+    /// Stop as if the process died: what the WAL hasn't made durable yet,
+    /// and what is only in memory, is lost
+    #[cfg(test)]
+    pub(crate) fn crash(mut self) {
+        if let Some(wal) = self.wal.take() {
+            wal.abandon();
+        }
+    }
+
+    /// Directory the store keeps its files in
+    pub fn data_dir(&self) -> &Path {
+        &self.config.data_dir
+    }
+
+    /// Inbox of received envelopes that have not been processed yet
+    pub fn inbox(&self) -> &PendingInbox {
+        &self.inbox
+    }
+
+    /// Outbox of sent messages and joins waiting for connectivity
+    pub fn outbox(&self) -> &PendingOutbox {
+        &self.outbox
+    }
+
+    /// Delivery state of the messages we sent
+    pub fn receipts(&self) -> &DeliveryReceipts {
+        &self.receipts
+    }
+
+    /// DHT peers remembered from earlier runs
+    pub fn known_peers(&self) -> &KnownPeers {
+        &self.known_peers
+    }
+
+    /// Register an observer for committed changes
+    ///
+    /// The observer sees every change committed after registration. To catch
+    /// up on earlier changes, follow with `observe_from`.
+    pub fn register_observer(
+        &self,
+        filter: ObserverFilter,
+        observer: Arc<dyn StoreObserver>,
+    ) -> ObserverId {
+        self.observers.register(filter, observer)
+    }
+
+    /// Remove a previously registered observer
+    pub fn unregister_observer(&self, id: ObserverId) -> bool {
+        self.observers.unregister(id)
+    }
+
+    /// Sequence number the next commit will receive
+    ///
+    /// Observers persist this alongside their own state and pass it to
+    /// `observe_from` to resume.
+    pub fn checkpoint(&self) -> StoreResult<u64> {
+        Ok(self.commit_log.read().map_err(handle_poison)?.current_seq())
+    }
+
+    /// At-rest encryption key, if enabled
+    ///
+    /// Derived state kept outside the store (e.g. the search index) uses it
+    /// to encrypt its own files.
+    pub fn encryption(&self) -> Option<Arc<EncryptionManager>> {
+        self.encryption.clone()
+    }
+
+    /// Replay committed changes with `seq >= checkpoint` to an observer
+    ///
+    /// Events are delivered in log order with origin `Replay`. Returns the
+    /// checkpoint to resume from next time. Entries removed by `compact` are
+    /// no longer replayable; rebuild from the snapshot state instead.
+    pub fn observe_from(
+        &self,
+        checkpoint: u64,
+        filter: &ObserverFilter,
+        observer: &dyn StoreObserver,
+    ) -> StoreResult<u64> {
+        // Hold the read lock so no commit interleaves with the replay, and
+        // replay in turn so commits made before it are notified first
+        let log = self.commit_log.read().map_err(handle_poison)?;
+        let turn = self.commit_order.take();
+
+        self.commit_order.run(turn, || {
+            for entry in log.read_all()? {
+                if entry.seq < checkpoint {
+                    continue;
+                }
+
+                let Some(change) = self.decode_entry(&entry.data)? else {
+                    continue;
+                };
+
+                let event = StoreEvent { seq: entry.seq, origin: ChangeOrigin::Replay, change };
+                if filter.matches(&event) {
+                    deliver(observer, &event);
+                }
+            }
+
+            Ok(log.current_seq())
+        })
+    }
+
+    /// Apply a space received from a remote peer, merging with local state
+    pub fn apply_remote_space(&self, remote: &Space) -> StoreResult<()> {
+        let merged = match self.get_space(&remote.id)? {
+            Some(mut local) => {
+                apply_remote_to_space(&mut local, remote)?;
+                local
+            }
+            None => remote.clone(),
+        };
+
+        self.commit(StoreChange::Space(merged), ChangeOrigin::Remote)
+    }
+
+    /// Apply a channel received from a remote peer, merging with local state
+    pub fn apply_remote_channel(&self, remote: &Channel) -> StoreResult<()> {
+        let merged = match self.get_channel(&remote.id)? {
+            Some(mut local) => {
+                apply_remote_to_channel(&mut local, remote)?;
+                local
+            }
+            None => remote.clone(),
+        };
+
+        self.commit(StoreChange::Channel(merged), ChangeOrigin::Remote)
+    }
+
+    /// Apply a message received from a remote peer
+    ///
+    /// A known message only takes the remote's reactions, so redelivery of
+    /// an unchanged message is a no-op. An unknown message the channel's
+    /// retention policy would already have pruned is ignored.
+    pub fn apply_remote_message(&self, message: &Message) -> StoreResult<()> {
+        let merged = match self.get_message(&message.id)? {
+            Some(mut local) => {
+                let before = (local.reactions.adds().count(), local.reactions.tombstones().count());
+                apply_remote_to_message(&mut local, message)?;
+                let after = (local.reactions.adds().count(), local.reactions.tombstones().count());
+                if before == after {
+                    return Ok(());
+                }
+                local
+            }
+            None if self.is_past_horizon(message)? => {
+                debug!(
+                    channel_id = %message.channel_id,
+                    message_id = %message.id,
+                    "Ignoring remote message past the retention horizon"
+                );
+                return Ok(());
+            }
+            None => message.clone(),
+        };
+
+        self.commit(StoreChange::Message(merged), ChangeOrigin::Remote)
+    }
+
+    /// Whether the retention policy of a message's channel has passed it
+    fn is_past_horizon(&self, message: &Message) -> StoreResult<bool> {
+        let Some(channel) = self.get_channel(&message.channel_id)? else {
+            return Ok(false);
+        };
+        let cache = self.messages_cache.read().map_err(handle_poison)?;
+        let messages = cache.get(&message.channel_id).map(Vec::as_slice).unwrap_or_default();
+        Ok(past_horizon(channel.retention(), messages, message, Timestamp::now()))
+    }
+
+    /// Persist a change, update caches and indices, then notify observers
+    ///
+    /// The commit log is released while the change waits for its fsync, so
+    /// concurrent writers share it. Observers are only told once the change
+    /// is durable, in `seq` order even under concurrent writers.
+    fn commit(&self, change: StoreChange, origin: ChangeOrigin) -> StoreResult<()> {
+        let data = match &change {
+            StoreChange::Space(space) => {
+                bincode::serialize(&EntityEntry::new(StoredEntity::Space(space.clone())))?
+            }
+            StoreChange::Channel(channel) => {
+                bincode::serialize(&EntityEntry::new(StoredEntity::Channel(channel.clone())))?
+            }
+            StoreChange::Message(message) => {
+                bincode::serialize(&EntityEntry::new(StoredEntity::Message(message.clone())))?
+            }
+            StoreChange::MessagesPruned { channel_id, message_ids } => {
+                bincode::serialize(&MessagePrune {
+                    magic: MESSAGE_PRUNE_MAGIC,
+                    channel_id: channel_id.clone(),
+                    message_ids: message_ids.clone(),
+                })?
+            }
+        };
 
-        // Encrypt if enabled
         let data = if let Some(enc) = &self.encryption {
             enc.encrypt(&data)?
         } else {
             data
         };
 
-        // Write to commit log
-        self.commit_log.write().map_err(handle_poison)?.append(&data)?;
+        let mut log = self.commit_log.write().map_err(handle_poison)?;
+        let seq = self.append_entry(&mut log, &data)?;
+
+        match &change {
+            StoreChange::Space(space) => {
+                self.spaces_cache
+                    .write()
+                    .map_err(handle_poison)?
+                    .insert(space.id.clone(), space.clone());
+                self.index_manager.index_space(&space.id)?;
+            }
+            StoreChange::Channel(channel) => {
+                self.channels_cache
+                    .write()
+                    .map_err(handle_poison)?
+                    .insert(channel.id.clone(), channel.clone());
+                self.index_manager.index_channel(&channel.id)?;
+            }
+            StoreChange::Message(message) => {
+                let mut cache = self.messages_cache.write().map_err(handle_poison)?;
+                upsert_message(&mut cache, message.clone());
+            }
+            StoreChange::MessagesPruned { channel_id, message_ids } => {
+                let mut cache = self.messages_cache.write().map_err(handle_poison)?;
+                remove_messages(&mut cache, channel_id, message_ids);
+            }
+        }
 
-        // Update cache
-        self.spaces_cache
-            .write()
-            .map_err(handle_poison)?
-            .insert(space.id.clone(), space.clone());
+        let turn = self.commit_order.take();
+        drop(log);
 
-        // Update indices
-        self.index_manager.index_space(&space.id)?;
+        let durable = self.wait_durable(seq);
+        let event = StoreEvent { seq, origin, change };
+        self.commit_order.run(turn, || {
+            if durable.is_ok() {
+                self.observers.notify(&event);
+            }
+        });
+        durable?;
 
-        // Check if we need to snapshot
         self.maybe_snapshot()?;
+        self.maybe_compact()
+    }
 
-        Ok(())
+    /// Decode a commit log entry into a store change
+    ///
+    /// Entity writes carry their type (see `EntityEntry`). Returns None for
+    /// entries that are not entity writes (e.g. raw CRDT operations).
+    fn decode_entry(&self, data: &[u8]) -> StoreResult<Option<StoreChange>> {
+        Ok(Self::decode_change(&self.decrypt_entry(data)?))
+    }
+
+    /// Decrypt a commit log entry if the store is encrypted
+    fn decrypt_entry(&self, data: &[u8]) -> StoreResult<Vec<u8>> {
+        match &self.encryption {
+            Some(enc) => enc.decrypt(data),
+            None => Ok(data.to_vec()),
+        }
+    }
+
+    /// Decode a decrypted commit log entry, see `decode_entry`
+    fn decode_change(data: &[u8]) -> Option<StoreChange> {
+        if let Some(entry) = EntityEntry::decode(data) {
+            return Some(match entry.entity {
+                StoredEntity::Space(space) => StoreChange::Space(space),
+                StoredEntity::Channel(channel) => StoreChange::Channel(channel),
+                StoredEntity::Message(message) => StoreChange::Message(message),
+            });
+        }
+        if let Some(prune) = MessagePrune::decode(data) {
+            return Some(StoreChange::MessagesPruned {
+                channel_id: prune.channel_id,
+                message_ids: prune.message_ids,
+            });
+        }
+
+        // Untagged entries from older stores: each type is tried in turn and
+        // only accepted if it consumes the whole entry
+        fn exact<T: serde::de::DeserializeOwned + Serialize>(data: &[u8]) -> Option<T> {
+            let value: T = bincode::deserialize(data).ok()?;
+            let size = bincode::serialized_size(&value).ok()?;
+            (size == data.len() as u64).then_some(value)
+        }
+
+        if let Some(channel) = exact::<Channel>(data) {
+            return Some(StoreChange::Channel(channel));
+        }
+        if let Some(space) = exact::<Space>(data) {
+            return Some(StoreChange::Space(space));
+        }
+        if let Some(message) = exact::<Message>(data) {
+            return Some(StoreChange::Message(message));
+        }
+
+        None
+    }
+
+    /// Store a space
+    pub fn store_space(&self, space: &Space) -> StoreResult<()> {
+        self.commit(StoreChange::Space(space.clone()), ChangeOrigin::Local)
     }
 
     /// Retrieve a space by ID
@@ -172,23 +958,7 @@ impl LocalStore {
 
     /// Store a channel
     pub fn store_channel(&self, channel: &Channel) -> StoreResult<()> {
-        let data = bincode::serialize(channel)?;
-
-        let data = if let Some(enc) = &self.encryption {
-            enc.encrypt(&data)?
-        } else {
-            data
-        };
-
-        self.commit_log.write().map_err(handle_poison)?.append(&data)?;
-        self.channels_cache
-            .write()
-            .map_err(handle_poison)?
-            .insert(channel.id.clone(), channel.clone());
-        self.index_manager.index_channel(&channel.id)?;
-        self.maybe_snapshot()?;
-
-        Ok(())
+        self.commit(StoreChange::Channel(channel.clone()), ChangeOrigin::Local)
     }
 
     /// Retrieve a channel by ID
@@ -205,7 +975,42 @@ impl LocalStore {
             return Ok(Some(channel));
         }
 
-        Ok(None)
+        Ok(None)
+    }
+
+    /// Remove a channel and its cached messages
+    ///
+    /// The removal is recorded in the commit log and a fresh snapshot is
+    /// taken, so the channel does not come back on the next `load`.
+    ///
+    /// # Returns
+    ///
+    /// `false` if the channel was not in the store
+    pub fn remove_channel(&self, channel_id: &ChannelId) -> StoreResult<bool> {
+        if self.get_channel(channel_id)?.is_none() {
+            return Ok(false);
+        }
+
+        let tombstone =
+            ChannelTombstone { magic: CHANNEL_TOMBSTONE_MAGIC, channel_id: channel_id.clone() };
+        let data = bincode::serialize(&tombstone)?;
+        let data = if let Some(enc) = &self.encryption {
+            enc.encrypt(&data)?
+        } else {
+            data
+        };
+
+        let mut log = self.commit_log.write().map_err(handle_poison)?;
+        let seq = self.append_entry(&mut log, &data)?;
+
+        self.channels_cache.write().map_err(handle_poison)?.remove(channel_id);
+        self.messages_cache.write().map_err(handle_poison)?.remove(channel_id);
+        self.index_manager.remove_channel(channel_id)?;
+        drop(log);
+        self.wait_durable(seq)?;
+
+        self.create_snapshot()?;
+        Ok(true)
     }
 
     /// List all spaces
@@ -218,33 +1023,9 @@ impl LocalStore {
         Ok(self.channels_cache.read().map_err(handle_poison)?.keys().cloned().collect())
     }
 
-    /// Store a message
+    /// Store a message, replacing an earlier version with the same ID
     pub fn store_message(&self, message: &Message) -> StoreResult<()> {
-        // Serialize message
-        let data = bincode::serialize(message)?;
-
-        // Encrypt if enabled
-        let data = if let Some(enc) = &self.encryption {
-            enc.encrypt(&data)?
-        } else {
-            data
-        };
-
-        // Write to commit log
-        self.commit_log.write().map_err(handle_poison)?.append(&data)?;
-
-        // Update cache
-        self.messages_cache
-            .write()
-            .map_err(handle_poison)?
-            .entry(message.channel_id.clone())
-            .or_insert_with(Vec::new)
-            .push(message.clone());
-
-        // Check if we need to snapshot
-        self.maybe_snapshot()?;
-
-        Ok(())
+        self.commit(StoreChange::Message(message.clone()), ChangeOrigin::Local)
     }
 
     /// Get a single message by ID
@@ -283,6 +1064,123 @@ impl LocalStore {
         Ok(sorted.into_iter().skip(offset).take(limit).collect())
     }
 
+    /// Get a page of channel history, newest first
+    ///
+    /// Returns up to `limit` messages older than `before`, or the newest
+    /// messages if `before` is `None`. Messages with equal timestamps are
+    /// ordered by arrival, so the cursor is stable. Returns `Ok(None)` if
+    /// `before` is not a message in this channel.
+    pub fn get_channel_history(
+        &self,
+        channel_id: &ChannelId,
+        limit: usize,
+        before: Option<&MessageId>,
+    ) -> StoreResult<Option<Vec<Message>>> {
+        let cache = self.messages_cache.read().map_err(handle_poison)?;
+        let mut history = cache.get(channel_id).cloned().unwrap_or_default();
+
+        // Newest arrival first, then a stable sort keeps that order among equal timestamps
+        history.reverse();
+        history.sort_by_key(|m| std::cmp::Reverse(m.timestamp));
+
+        let start = match before {
+            Some(cursor) => match history.iter().position(|m| &m.id == cursor) {
+                Some(pos) => pos + 1,
+                None => return Ok(None),
+            },
+            None => 0,
+        };
+
+        Ok(Some(history.into_iter().skip(start).take(limit).collect()))
+    }
+
+    /// Page through a channel's history oldest first
+    ///
+    /// Returns up to `limit` messages newer than `after`, or the oldest
+    /// messages if `after` is `None`. Messages with equal timestamps are
+    /// ordered by ID, so every replica pages in the same order. Returns
+    /// `Ok(None)` if `after` is not a message in this channel.
+    pub fn get_channel_history_after(
+        &self,
+        channel_id: &ChannelId,
+        limit: usize,
+        after: Option<&MessageId>,
+    ) -> StoreResult<Option<Vec<Message>>> {
+        let cache = self.messages_cache.read().map_err(handle_poison)?;
+        let messages = cache.get(channel_id).map(Vec::as_slice).unwrap_or_default();
+
+        let start = match after {
+            Some(cursor) => match messages.iter().find(|m| &m.id == cursor) {
+                Some(m) => Some((m.timestamp, m.id.0.as_str())),
+                None => return Ok(None),
+            },
+            None => None,
+        };
+
+        let mut page: Vec<&Message> = messages
+            .iter()
+            .filter(|m| start.is_none_or(|start| (m.timestamp, m.id.0.as_str()) > start))
+            .collect();
+        page.sort_unstable_by(|a, b| {
+            a.timestamp.cmp(&b.timestamp).then_with(|| a.id.0.cmp(&b.id.0))
+        });
+        page.truncate(limit);
+        Ok(Some(page.into_iter().cloned().collect()))
+    }
+
+    /// Messages of a channel its retention policy no longer keeps
+    ///
+    /// Oldest first; empty if the channel keeps everything or isn't in the
+    /// store.
+    pub fn expired_messages(
+        &self,
+        channel_id: &ChannelId,
+        now: Timestamp,
+    ) -> StoreResult<Vec<Message>> {
+        let Some(channel) = self.get_channel(channel_id)? else {
+            return Ok(Vec::new());
+        };
+        let cache = self.messages_cache.read().map_err(handle_poison)?;
+        let messages = cache.get(channel_id).map(Vec::as_slice).unwrap_or_default();
+        Ok(expired(channel.retention(), messages, now).into_iter().cloned().collect())
+    }
+
+    /// Remove messages of a channel for good
+    ///
+    /// The removal is committed like any change, so observers drop the
+    /// messages too, and the store is then compacted so their content
+    /// leaves the commit log. IDs not in the channel are skipped.
+    ///
+    /// # Returns
+    ///
+    /// Number of messages removed
+    pub fn prune_messages(
+        &self,
+        channel_id: &ChannelId,
+        message_ids: &[MessageId],
+    ) -> StoreResult<usize> {
+        let message_ids: Vec<MessageId> = {
+            let cache = self.messages_cache.read().map_err(handle_poison)?;
+            let stored = cache.get(channel_id).map(Vec::as_slice).unwrap_or_default();
+            message_ids
+                .iter()
+                .filter(|id| stored.iter().any(|message| &message.id == *id))
+                .cloned()
+                .collect()
+        };
+        if message_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let count = message_ids.len();
+        self.commit(
+            StoreChange::MessagesPruned { channel_id: channel_id.clone(), message_ids },
+            ChangeOrigin::Local,
+        )?;
+        self.compact()?;
+        Ok(count)
+    }
+
     /// Get thread replies (messages that reply to a specific message)
     pub fn get_thread_replies(&self, parent_id: &MessageId) -> StoreResult<Vec<Message>> {
         let cache = self.messages_cache.read().map_err(handle_poison)?;
@@ -304,6 +1202,41 @@ impl LocalStore {
         Ok(replies)
     }
 
+    /// Write a channel's operation log for debugging
+    ///
+    /// Streams the commit log into `out` in the format described in
+    /// [`oplog_export`](crate::core_store::store::oplog_export); payloads are
+    /// redacted to hashes unless `payloads` is set. Returns the number of
+    /// operations written.
+    pub fn export_oplog<W: std::io::Write>(
+        &self,
+        channel_id: &ChannelId,
+        payloads: bool,
+        out: W,
+    ) -> StoreResult<u64> {
+        let channel = self
+            .get_channel(channel_id)?
+            .ok_or_else(|| StoreError::NotFound(format!("Channel {}", channel_id)))?;
+
+        let mut exporter = OplogExporter::new(out, channel_id, payloads)?;
+        let reader = self.commit_log.read().map_err(handle_poison)?.reader()?;
+        for entry in reader {
+            match self.decode_entry(&entry?.data)? {
+                Some(StoreChange::Channel(channel)) => exporter.channel(&channel)?,
+                Some(StoreChange::Message(message)) => exporter.message(&message)?,
+                _ => {}
+            }
+        }
+
+        // Operations compacted out of the commit log survive only in current state
+        exporter.channel(&channel)?;
+        for message in self.get_channel_messages(channel_id)? {
+            exporter.message(&message)?;
+        }
+
+        exporter.finish()
+    }
+
     /// Apply a CRDT operation and persist it
     ///
     /// Note: For cryptographic signature verification, wrap CRDTs with ValidatedCrdt.
@@ -325,13 +1258,17 @@ impl LocalStore {
         };
 
         // Append to commit log
-        self.commit_log.write().map_err(handle_poison)?.append(&op_data)?;
+        let mut log = self.commit_log.write().map_err(handle_poison)?;
+        let seq = self.append_entry(&mut log, &op_data)?;
+        drop(log);
+        self.wait_durable(seq)?;
 
         // Increment operation counter
         *self.operation_count.write().map_err(handle_poison)? += 1;
 
         // Maybe snapshot
         self.maybe_snapshot()?;
+        self.maybe_compact()?;
 
         Ok(())
     }
@@ -348,70 +1285,150 @@ impl LocalStore {
         Ok(())
     }
 
+    /// Compact once the commit log outgrows `max_log_size`
+    fn maybe_compact(&self) -> StoreResult<()> {
+        if !self.config.enable_compaction {
+            return Ok(());
+        }
+
+        let size = self.commit_log.read().map_err(handle_poison)?.size();
+        if size > self.config.max_log_size {
+            self.compact()?;
+        }
+
+        Ok(())
+    }
+
     /// Force create a snapshot
     pub fn create_snapshot(&self) -> StoreResult<()> {
+        // Hold the log so the caches and the log position agree
+        let log = self.commit_log.read().map_err(handle_poison)?;
+        let pending = self.write_snapshot(&log)?;
+        self.snapshot_manager.publish(pending)?;
+
+        Ok(())
+    }
+
+    /// Write a snapshot of the caches covering the log up to now
+    fn write_snapshot(&self, log: &CommitLog) -> StoreResult<PendingSnapshot> {
+        // Caches of a store that was never loaded miss what is on disk
+        if !self.loaded.load(Ordering::SeqCst) {
+            self.load_from(log)?;
+        }
+
         let spaces = self.spaces_cache.read().map_err(handle_poison)?.clone();
         let channels = self.channels_cache.read().map_err(handle_poison)?.clone();
+        let messages = self.messages_cache.read().map_err(handle_poison)?.clone();
 
-        self.snapshot_manager.create_snapshot(spaces, channels)?;
-
-        Ok(())
+        self.snapshot_manager
+            .write_snapshot(spaces, channels, messages, log.current_seq())
     }
 
-    /// Load all state from snapshots and replay commit log
+    /// Load all state from the newest snapshot and replay the commit log
+    /// entries after it
     pub fn load(&self) -> StoreResult<()> {
-        // Load latest snapshot
-        let (spaces, channels) = self.snapshot_manager.load_latest()?;
-
-        *self.spaces_cache.write().map_err(handle_poison)? = spaces;
-        *self.channels_cache.write().map_err(handle_poison)? = channels;
+        let log = self.commit_log.read().map_err(handle_poison)?;
+        self.load_from(&log)
+    }
 
-        // Replay commit log entries after snapshot
-        // For now, we replay ALL entries since we don't track snapshot positions
-        let entries = self.commit_log.read().map_err(handle_poison)?.read_all()?;
+    fn load_from(&self, log: &CommitLog) -> StoreResult<()> {
+        let snapshot = self.snapshot_manager.load_newest()?;
+        let from = snapshot.as_ref().map_or(0, |snapshot| snapshot.log_seq);
+        if from < log.first_seq() {
+            return Err(StoreError::CorruptedData(format!(
+                "Snapshot covers the commit log up to {}, but entries before {} were compacted",
+                from,
+                log.first_seq()
+            )));
+        }
 
-        for entry in entries {
-            // Try to decrypt if encryption is enabled
-            let data = if let Some(enc) = &self.encryption {
-                enc.decrypt(&entry.data)?
-            } else {
-                entry.data.clone()
-            };
+        let (mut spaces, mut channels, mut messages) = match snapshot {
+            Some(snapshot) => (snapshot.spaces, snapshot.channels, snapshot.messages),
+            None => Default::default(),
+        };
 
-            // Try to deserialize as Channel first (most common in our case)
-            if let Ok(channel) = bincode::deserialize::<Channel>(&data) {
-                self.channels_cache
-                    .write()
-                    .map_err(handle_poison)?
-                    .insert(channel.id.clone(), channel);
+        for entry in log.reader()? {
+            let entry = entry?;
+            // Already in the snapshot
+            if entry.seq < from {
                 continue;
             }
 
-            // Try to deserialize as Space
-            if let Ok(space) = bincode::deserialize::<Space>(&data) {
-                self.spaces_cache
-                    .write()
-                    .map_err(handle_poison)?
-                    .insert(space.id.clone(), space);
-                continue;
+            let data = self.decrypt_entry(&entry.data)?;
+            match Self::decode_change(&data) {
+                Some(StoreChange::Channel(channel)) => {
+                    channels.insert(channel.id.clone(), channel);
+                }
+                Some(StoreChange::Space(space)) => {
+                    spaces.insert(space.id.clone(), space);
+                }
+                Some(StoreChange::Message(message)) => upsert_message(&mut messages, message),
+                Some(StoreChange::MessagesPruned { channel_id, message_ids }) => {
+                    remove_messages(&mut messages, &channel_id, &message_ids);
+                }
+                None => {
+                    // Channel removed after the entries above
+                    if let Some(tombstone) = ChannelTombstone::decode(&data) {
+                        channels.remove(&tombstone.channel_id);
+                        messages.remove(&tombstone.channel_id);
+                    }
+                    // Anything else (e.g. raw CRDT operations) isn't loaded
+                }
             }
-
-            // If we can't deserialize as either, skip this entry
-            // (could be a message or other data type we're not loading yet)
         }
 
+        *self.spaces_cache.write().map_err(handle_poison)? = spaces;
+        *self.channels_cache.write().map_err(handle_poison)? = channels;
+        *self.messages_cache.write().map_err(handle_poison)? = messages;
+        self.loaded.store(true, Ordering::SeqCst);
+
         Ok(())
     }
 
     /// Compact the commit log
+    ///
+    /// Writes a snapshot of the current state, reads it back, then drops
+    /// the log entries it covers. The previous snapshot is kept; older ones
+    /// are removed. Runs on its own once the log outgrows `max_log_size`
+    /// if `enable_compaction` is set.
     pub fn compact(&self) -> StoreResult<()> {
-        // Create snapshot
-        self.create_snapshot()?;
+        self.compact_until(None)
+    }
 
-        // Truncate commit log
-        self.commit_log.write().map_err(handle_poison)?.truncate()?;
+    /// Compact, stopping after `stop` if given
+    pub(crate) fn compact_until(&self, stop: Option<CompactionStage>) -> StoreResult<()> {
+        // No commit may land between the snapshot and the truncation
+        let mut log = self.commit_log.write().map_err(handle_poison)?;
+        let log_seq = log.current_seq();
 
-        Ok(())
+        let pending = self.write_snapshot(&log)?;
+        if stop == Some(CompactionStage::SnapshotWritten) {
+            return Ok(());
+        }
+
+        let version = self.snapshot_manager.publish(pending)?;
+        if stop == Some(CompactionStage::SnapshotPublished) {
+            return Ok(());
+        }
+
+        let written = self.snapshot_manager.load_version(version)?;
+        if written.log_seq != log_seq {
+            return Err(StoreError::CorruptedData(format!(
+                "Snapshot {} read back with log position {}, expected {}",
+                version, written.log_seq, log_seq
+            )));
+        }
+        if stop == Some(CompactionStage::SnapshotVerified) {
+            return Ok(());
+        }
+
+        log.truncate_before(log_seq)?;
+        self.checkpoint_wal(&mut log)?;
+        if stop == Some(CompactionStage::LogTruncated) {
+            return Ok(());
+        }
+
+        self.snapshot_manager.cleanup_old_snapshots(SNAPSHOTS_KEPT)
     }
 
     /// Get storage statistics
@@ -425,6 +1442,19 @@ impl LocalStore {
     }
 }
 
+impl Drop for LocalStore {
+    fn drop(&mut self) {
+        // Leave the commit log complete, so the next open finds the WAL empty
+        let result = match self.commit_log.write() {
+            Ok(mut log) => self.checkpoint_wal(&mut log),
+            Err(e) => Err(handle_poison(e)),
+        };
+        if let Err(e) = result {
+            warn!("Failed to write the WAL into the commit log: {}", e);
+        }
+    }
+}
+
 /// Storage statistics
 #[derive(Debug, Clone)]
 pub struct StoreStats {
@@ -534,6 +1564,415 @@ mod tests {
         assert_eq!(stats.channels_count, 0);
     }
 
+    fn test_store(dir: &std::path::Path) -> LocalStore {
+        LocalStore::new(LocalStoreConfig {
+            data_dir: dir.to_path_buf(),
+            enable_encryption: false,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    fn test_message(channel_id: &ChannelId, sender: &UserId, body: &str) -> Message {
+        Message::new(
+            MessageId::generate(),
+            channel_id.clone(),
+            sender.clone(),
+            body.as_bytes().to_vec(),
+            Timestamp::now(),
+        )
+    }
+
+    struct Recorder(std::sync::Mutex<Vec<(u64, ChangeOrigin)>>);
+
+    impl StoreObserver for Recorder {
+        fn on_commit(&self, event: &StoreEvent) {
+            self.0.lock().unwrap().push((event.seq, event.origin));
+        }
+    }
+
+    #[test]
+    fn test_observer_sees_local_and_remote_writes() {
+        use crate::core_store::store::observer::EntityKind;
+
+        let dir = tempdir().unwrap();
+        let store = test_store(dir.path());
+        let recorder = Arc::new(Recorder(std::sync::Mutex::new(Vec::new())));
+        store
+            .register_observer(ObserverFilter::all().entity(EntityKind::Message), recorder.clone());
+
+        let channel = Channel::new(
+            ChannelId::generate(),
+            "general".to_string(),
+            ChannelType::Text,
+            UserId::generate(),
+            Timestamp::now(),
+            "node1".to_string(),
+        );
+        store.store_channel(&channel).unwrap();
+
+        let alice = UserId::generate();
+        let local = test_message(&channel.id, &alice, "local");
+        let remote = test_message(&channel.id, &UserId::generate(), "remote");
+        store.store_message(&local).unwrap();
+        store.apply_remote_message(&remote).unwrap();
+        store.apply_remote_message(&remote).unwrap(); // Redelivery is ignored
+
+        let seen = recorder.0.lock().unwrap().clone();
+        assert_eq!(seen, vec![(1, ChangeOrigin::Local), (2, ChangeOrigin::Remote)]);
+        assert_eq!(store.get_channel_messages(&channel.id).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_replay_rebuilds_search_index() {
+        use crate::core_store::query::{SearchIndex, SearchIndexObserver};
+
+        let dir = tempdir().unwrap();
+        let channel_id = ChannelId::generate();
+        let sender = UserId::generate();
+
+        let checkpoint = {
+            let store = test_store(dir.path());
+            store
+                .store_message(&test_message(&channel_id, &sender, "first pancake"))
+                .unwrap();
+            store
+                .store_message(&test_message(&channel_id, &sender, "second pancake"))
+                .unwrap();
+            store.checkpoint().unwrap()
+        };
+        assert_eq!(checkpoint, 2);
+
+        // Simulate losing the index: reopen and rebuild from scratch
+        let store = test_store(dir.path());
+        let observer =
+            Arc::new(SearchIndexObserver::new(Arc::new(RwLock::new(SearchIndex::new()))));
+        let filter = SearchIndexObserver::filter();
+        store.register_observer(filter.clone(), observer.clone());
+        store
+            .store_message(&test_message(&channel_id, &sender, "third pancake"))
+            .unwrap();
+
+        let resume = store.observe_from(0, &filter, observer.as_ref()).unwrap();
+        assert_eq!(resume, 3);
+
+        let index = observer.index();
+        assert_eq!(index.read().unwrap().search("pancake", 10).len(), 3);
+    }
+
+    #[test]
+    fn test_panicking_observer_does_not_abort_write() {
+        struct Panicker;
+
+        impl StoreObserver for Panicker {
+            fn on_commit(&self, _event: &StoreEvent) {
+                panic!("observer failure");
+            }
+        }
+
+        let dir = tempdir().unwrap();
+        let store = test_store(dir.path());
+        let recorder = Arc::new(Recorder(std::sync::Mutex::new(Vec::new())));
+        store.register_observer(ObserverFilter::all(), Arc::new(Panicker));
+        store.register_observer(ObserverFilter::all(), recorder.clone());
+
+        let space = Space::new(
+            SpaceId::generate(),
+            "Test Space".to_string(),
+            UserId::generate(),
+            Timestamp::now(),
+            "node1".to_string(),
+        );
+        store.store_space(&space).unwrap();
+
+        assert!(store.get_space(&space.id).unwrap().is_some());
+        assert_eq!(recorder.0.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_removed_channel_stays_removed_after_reload() {
+        let dir = tempdir().unwrap();
+        let store = test_store(dir.path());
+        let creator = UserId::generate();
+
+        let channel = Channel::new(
+            ChannelId::generate(),
+            "general".to_string(),
+            ChannelType::Text,
+            creator.clone(),
+            Timestamp::now(),
+            "node1".to_string(),
+        );
+        store.store_channel(&channel).unwrap();
+        store.store_message(&test_message(&channel.id, &creator, "hello")).unwrap();
+
+        assert!(store.remove_channel(&channel.id).unwrap());
+        assert!(!store.remove_channel(&channel.id).unwrap());
+        assert!(store.get_channel(&channel.id).unwrap().is_none());
+        assert!(store.get_channel_messages(&channel.id).unwrap().is_empty());
+
+        let reopened = test_store(dir.path());
+        reopened.load().unwrap();
+        assert!(reopened.get_channel(&channel.id).unwrap().is_none());
+        assert!(reopened.list_channels().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_channel_history_pages_newest_first() {
+        let dir = tempdir().unwrap();
+        let store = test_store(dir.path());
+        let channel_id = ChannelId::generate();
+        let sender = UserId::generate();
+
+        // Same timestamp for all: arrival order decides
+        let timestamp = Timestamp::now();
+        let ids: Vec<MessageId> = (0..5)
+            .map(|i| {
+                let mut message = test_message(&channel_id, &sender, &format!("m{}", i));
+                message.timestamp = timestamp;
+                store.store_message(&message).unwrap();
+                message.id
+            })
+            .collect();
+
+        let page = store.get_channel_history(&channel_id, 2, None).unwrap().unwrap();
+        assert_eq!(
+            page.iter().map(|m| m.id.clone()).collect::<Vec<_>>(),
+            vec![ids[4].clone(), ids[3].clone()]
+        );
+
+        let page = store.get_channel_history(&channel_id, 2, Some(&ids[3])).unwrap().unwrap();
+        assert_eq!(
+            page.iter().map(|m| m.id.clone()).collect::<Vec<_>>(),
+            vec![ids[2].clone(), ids[1].clone()]
+        );
+
+        let page = store.get_channel_history(&channel_id, 2, Some(&ids[1])).unwrap().unwrap();
+        assert_eq!(page.len(), 1);
+        assert!(store
+            .get_channel_history(&channel_id, 2, Some(&ids[0]))
+            .unwrap()
+            .unwrap()
+            .is_empty());
+
+        assert!(store
+            .get_channel_history(&channel_id, 2, Some(&MessageId::generate()))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_channel_history_pages_oldest_first() {
+        let dir = tempdir().unwrap();
+        let store = test_store(dir.path());
+        let channel_id = ChannelId::generate();
+        let sender = UserId::generate();
+
+        // Stored out of order; equal timestamps are ordered by ID
+        for (id, millis) in [("c", 2_000), ("b", 1_000), ("e", 3_000), ("a", 2_000), ("d", 1_000)] {
+            let mut message = test_message(&channel_id, &sender, id);
+            message.id = MessageId(id.to_string());
+            message.timestamp = Timestamp::from_millis(millis);
+            store.store_message(&message).unwrap();
+        }
+
+        let mut order = Vec::new();
+        let mut after = None;
+        loop {
+            let page = store
+                .get_channel_history_after(&channel_id, 2, after.as_ref())
+                .unwrap()
+                .unwrap();
+            let Some(last) = page.last() else { break };
+            after = Some(last.id.clone());
+            order.extend(page.into_iter().map(|m| m.id.0));
+        }
+        assert_eq!(order, ["b", "d", "a", "c", "e"]);
+
+        assert!(store
+            .get_channel_history_after(&channel_id, 2, Some(&MessageId::generate()))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_storing_message_again_replaces_it() {
+        let dir = tempdir().unwrap();
+        let store = test_store(dir.path());
+        let channel_id = ChannelId::generate();
+        let sender = UserId::generate();
+
+        let mut message = test_message(&channel_id, &sender, "tpyo");
+        store.store_message(&message).unwrap();
+        store.store_message(&test_message(&channel_id, &sender, "later")).unwrap();
+
+        message.add_edit(Timestamp::now(), sender, b"typo".to_vec());
+        store.store_message(&message).unwrap();
+
+        let messages = store.get_channel_messages(&channel_id).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].id, message.id);
+        assert_eq!(messages[0].current_content(), b"typo");
+    }
+
+    #[test]
+    fn test_pruned_messages_stay_gone() {
+        let dir = tempdir().unwrap();
+        let store = test_store(dir.path());
+        let sender = UserId::generate();
+        let mut channel = test_channel("ephemeral");
+        channel.set_retention(RetentionPolicy::KeepMessages(2), "node1");
+        store.store_channel(&channel).unwrap();
+
+        let messages: Vec<Message> = ["oldest secret", "middle", "newest"]
+            .iter()
+            .enumerate()
+            .map(|(i, body)| {
+                let mut message = test_message(&channel.id, &sender, body);
+                message.timestamp = Timestamp::from_millis(1_000 + i as u64);
+                store.store_message(&message).unwrap();
+                message
+            })
+            .collect();
+
+        let expired = store.expired_messages(&channel.id, Timestamp::now()).unwrap();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].id, messages[0].id);
+
+        let ids: Vec<MessageId> = expired.into_iter().map(|m| m.id).collect();
+        assert_eq!(store.prune_messages(&channel.id, &ids).unwrap(), 1);
+        assert_eq!(store.prune_messages(&channel.id, &ids).unwrap(), 0);
+        assert!(!on_disk(dir.path(), b"oldest secret"));
+
+        // A replica that hasn't pruned yet can't bring it back
+        store.apply_remote_message(&messages[0]).unwrap();
+        assert_eq!(store.get_channel_messages(&channel.id).unwrap().len(), 2);
+
+        drop(store);
+        let reopened = test_store(dir.path());
+        reopened.load().unwrap();
+        assert!(reopened.get_message(&messages[0].id).unwrap().is_none());
+        assert_eq!(reopened.get_channel_messages(&channel.id).unwrap().len(), 2);
+    }
+
+    fn encrypted_config(dir: &std::path::Path) -> LocalStoreConfig {
+        LocalStoreConfig { data_dir: dir.to_path_buf(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_entity_entries_decode_as_their_own_type() {
+        let space = Space::new(
+            SpaceId::generate(),
+            "Test Space".to_string(),
+            UserId::generate(),
+            Timestamp::now(),
+            "node1".to_string(),
+        );
+        let channel = test_channel("general");
+        let message = test_message(&channel.id, &UserId::generate(), "hello");
+
+        let tagged = |entity| bincode::serialize(&EntityEntry::new(entity)).unwrap();
+        assert!(matches!(
+            LocalStore::decode_change(&tagged(StoredEntity::Space(space.clone()))),
+            Some(StoreChange::Space(s)) if s.id == space.id
+        ));
+        assert!(matches!(
+            LocalStore::decode_change(&tagged(StoredEntity::Channel(channel.clone()))),
+            Some(StoreChange::Channel(c)) if c.id == channel.id
+        ));
+        assert!(matches!(
+            LocalStore::decode_change(&tagged(StoredEntity::Message(message.clone()))),
+            Some(StoreChange::Message(m)) if m.id == message.id
+        ));
+
+        // Entries written before the tag still load
+        assert!(matches!(
+            LocalStore::decode_change(&bincode::serialize(&space).unwrap()),
+            Some(StoreChange::Space(s)) if s.id == space.id
+        ));
+        assert!(matches!(
+            LocalStore::decode_change(&bincode::serialize(&message).unwrap()),
+            Some(StoreChange::Message(m)) if m.id == message.id
+        ));
+    }
+
+    fn test_channel(name: &str) -> Channel {
+        Channel::new(
+            ChannelId::generate(),
+            name.to_string(),
+            ChannelType::Text,
+            UserId::generate(),
+            Timestamp::now(),
+            "node1".to_string(),
+        )
+    }
+
+    /// Whether any file under `dir` contains `needle`
+    fn on_disk(dir: &std::path::Path, needle: &[u8]) -> bool {
+        std::fs::read_dir(dir).unwrap().flatten().any(|entry| {
+            let path = entry.path();
+            if path.is_dir() {
+                on_disk(&path, needle)
+            } else {
+                std::fs::read(path).unwrap().windows(needle.len()).any(|w| w == needle)
+            }
+        })
+    }
+
+    #[test]
+    fn test_encrypted_store_reopens_with_key_only() {
+        let dir = tempdir().unwrap();
+        let key = StoreKey::Derived([3; 32]);
+        let channel = test_channel("quarterly-plans");
+
+        {
+            let store = LocalStore::open_encrypted(encrypted_config(dir.path()), &key).unwrap();
+            store.store_channel(&channel).unwrap();
+            store.create_snapshot().unwrap();
+        }
+        assert!(LocalStore::is_encrypted(dir.path()));
+        assert!(!on_disk(dir.path(), b"quarterly-plans"));
+
+        let store = LocalStore::open_encrypted(encrypted_config(dir.path()), &key).unwrap();
+        store.load().unwrap();
+        assert!(store.get_channel(&channel.id).unwrap().is_some());
+
+        for wrong in [StoreKey::Derived([4; 32]), StoreKey::passphrase("guess")] {
+            let result = LocalStore::open_encrypted(encrypted_config(dir.path()), &wrong);
+            assert!(matches!(result, Err(StoreError::DecryptionFailed(_))));
+        }
+    }
+
+    #[test]
+    fn test_encrypt_in_place_migrates_plaintext_store() {
+        let dir = tempdir().unwrap();
+        let key = StoreKey::passphrase("correct horse");
+        let kept = test_channel("kept-channel");
+        let removed = test_channel("removed-channel");
+
+        {
+            let store = test_store(dir.path());
+            store.store_channel(&kept).unwrap();
+            store.store_channel(&removed).unwrap();
+            store.remove_channel(&removed.id).unwrap();
+        }
+        assert!(on_disk(dir.path(), b"kept-channel"));
+
+        // A plaintext store must be migrated explicitly
+        let result = LocalStore::open_encrypted(encrypted_config(dir.path()), &key);
+        assert!(matches!(result, Err(StoreError::InvalidOperation(_))));
+
+        let migrated = LocalStore::encrypt_in_place(encrypted_config(dir.path()), &key).unwrap();
+        assert_eq!(migrated, 3);
+        assert!(!on_disk(dir.path(), b"kept-channel"));
+        assert!(LocalStore::encrypt_in_place(encrypted_config(dir.path()), &key).is_err());
+
+        let store = LocalStore::open_encrypted(encrypted_config(dir.path()), &key).unwrap();
+        store.load().unwrap();
+        assert!(store.get_channel(&kept.id).unwrap().is_some());
+        assert!(store.get_channel(&removed.id).unwrap().is_none());
+    }
+
     /// Example: Using ValidatedCrdt for signature enforcement
     ///
     /// This test demonstrates the recommended pattern for enforcing signatures on CRDT operations.
@@ -2,20 +2,50 @@
     Store subsystem - Persistence layer
 */
 
+pub mod attachment_cache;
+pub mod attestation;
 pub mod commit_log;
 pub mod dht_adapter;
 pub mod encryption;
 pub mod errors;
+pub mod inbox;
 pub mod index;
+pub mod known_peers;
 pub mod local_store;
+pub mod observer;
+pub mod oplog_export;
+pub mod outbox;
+pub mod preflight;
+pub mod receipts;
 pub mod snapshot;
 pub mod validator;
+pub mod wal;
 
-pub use commit_log::{CommitLog, LogEntry};
+pub use attachment_cache::{
+    AttachmentCache, AttachmentUsage, BlobFetcher, BlobOrigin, CachedAttachment,
+};
+pub use attestation::{
+    AttestationAnomaly, AttestationOutcome, AttestationRecord, SealedAttestation,
+    StartupAttestation,
+};
+pub use commit_log::{CommitLog, CommitLogReader, LogEntry};
 pub use dht_adapter::{DhtAdapter, DhtDelta, DhtObjectKey};
-pub use encryption::EncryptionManager;
+pub use encryption::{EncryptionHeader, EncryptionManager, StoreKey};
 pub use errors::*;
+pub use inbox::{PendingEnvelope, PendingInbox};
 pub use index::IndexManager;
+pub use known_peers::{KnownPeer, KnownPeers};
 pub use local_store::{LocalStore, LocalStoreConfig, StoreStats};
+pub use observer::{
+    ChangeOrigin, EntityKind, ObserverFilter, ObserverId, ObserverRegistry, StoreChange,
+    StoreEvent, StoreObserver,
+};
+pub use oplog_export::{
+    OplogDiffSummary, OplogDiffer, OplogExporter, OplogFinding, OplogHeader, OplogOp, OplogSide,
+};
+pub use outbox::{DeferredJoin, OutgoingEnvelope, PendingOutbox};
+pub use preflight::{FsStats, InsufficientStorage, StoragePreflight, SystemFsStats};
+pub use receipts::{DeliveryReceipts, DeliveryState, SentReceipts};
 pub use snapshot::{Snapshot, SnapshotManager, SnapshotMetadata};
 pub use validator::{OperationValidator, ValidationRules};
+pub use wal::{WalConfig, WalRecord, WriteAheadLog};
@@ -0,0 +1,314 @@
+/*
+    observer.rs - Store observers for reactive indexes
+
+    Components that derive state from the store (search index, unread
+    counters, caches) register a StoreObserver instead of hooking each write
+    path themselves. LocalStore notifies observers after an entry is durably
+    appended to the commit log, for local writes and for remote operations
+    applied via sync alike.
+
+    Guarantees:
+    - Events are delivered in commit log order (ascending `seq`)
+    - Observers are called in registration order
+    - A panicking observer is isolated; the write still succeeds
+
+    Observers are called once the change is durable, one commit at a time
+    in the order the commits were made, so they must not write back into
+    the store synchronously.
+
+    An observer that was offline (e.g. an index being rebuilt) catches up with
+    LocalStore::observe_from(checkpoint, ...), which replays the commit log
+    from a sequence number onward.
+*/
+
+use crate::core_store::model::{Channel, ChannelId, Message, MessageId, Space};
+use std::collections::HashSet;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+
+/// Kind of entity a store change touches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EntityKind {
+    Space,
+    Channel,
+    Message,
+}
+
+/// Where a change came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeOrigin {
+    /// Written by this node
+    Local,
+    /// Applied from a remote peer via sync
+    Remote,
+    /// Replayed from the commit log by observe_from
+    Replay,
+}
+
+/// Entity written to the store
+#[derive(Debug, Clone)]
+pub enum StoreChange {
+    Space(Space),
+    Channel(Channel),
+    Message(Message),
+
+    /// Messages of a channel removed by its retention policy
+    MessagesPruned { channel_id: ChannelId, message_ids: Vec<MessageId> },
+}
+
+impl StoreChange {
+    /// Kind of entity this change touches
+    pub fn entity(&self) -> EntityKind {
+        match self {
+            StoreChange::Space(_) => EntityKind::Space,
+            StoreChange::Channel(_) => EntityKind::Channel,
+            StoreChange::Message(_) | StoreChange::MessagesPruned { .. } => EntityKind::Message,
+        }
+    }
+
+    /// Channel this change belongs to, if any
+    pub fn channel_id(&self) -> Option<&ChannelId> {
+        match self {
+            StoreChange::Space(_) => None,
+            StoreChange::Channel(channel) => Some(&channel.id),
+            StoreChange::Message(message) => Some(&message.channel_id),
+            StoreChange::MessagesPruned { channel_id, .. } => Some(channel_id),
+        }
+    }
+}
+
+/// A committed store change delivered to observers
+#[derive(Debug, Clone)]
+pub struct StoreEvent {
+    /// Commit log sequence number (usable as a replay checkpoint)
+    pub seq: u64,
+    pub origin: ChangeOrigin,
+    pub change: StoreChange,
+}
+
+/// Selects which events an observer receives
+#[derive(Debug, Clone, Default)]
+pub struct ObserverFilter {
+    entities: Option<HashSet<EntityKind>>,
+    channels: Option<HashSet<ChannelId>>,
+}
+
+impl ObserverFilter {
+    /// Receive every event
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// Only receive events for this entity kind (may be called repeatedly)
+    pub fn entity(mut self, kind: EntityKind) -> Self {
+        self.entities.get_or_insert_with(HashSet::new).insert(kind);
+        self
+    }
+
+    /// Only receive events in this channel (may be called repeatedly)
+    ///
+    /// Space events carry no channel and are excluded by a channel filter.
+    pub fn channel(mut self, channel_id: ChannelId) -> Self {
+        self.channels.get_or_insert_with(HashSet::new).insert(channel_id);
+        self
+    }
+
+    /// Whether an event passes this filter
+    pub fn matches(&self, event: &StoreEvent) -> bool {
+        if let Some(entities) = &self.entities {
+            if !entities.contains(&event.change.entity()) {
+                return false;
+            }
+        }
+
+        if let Some(channels) = &self.channels {
+            match event.change.channel_id() {
+                Some(channel_id) => channels.contains(channel_id),
+                None => false,
+            }
+        } else {
+            true
+        }
+    }
+}
+
+/// Callback invoked for committed store changes
+pub trait StoreObserver: Send + Sync {
+    /// Handle a committed change
+    fn on_commit(&self, event: &StoreEvent);
+}
+
+/// Handle returned when registering an observer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObserverId(u64);
+
+struct Registration {
+    id: ObserverId,
+    filter: ObserverFilter,
+    observer: Arc<dyn StoreObserver>,
+}
+
+/// Registered observers, in registration order
+#[derive(Default)]
+pub struct ObserverRegistry {
+    registrations: RwLock<Vec<Registration>>,
+    next_id: RwLock<u64>,
+}
+
+impl ObserverRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register an observer
+    pub fn register(&self, filter: ObserverFilter, observer: Arc<dyn StoreObserver>) -> ObserverId {
+        let mut next_id = self.next_id.write().unwrap_or_else(|e| e.into_inner());
+        let id = ObserverId(*next_id);
+        *next_id += 1;
+
+        self.registrations
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(Registration { id, filter, observer });
+        id
+    }
+
+    /// Remove an observer; returns false if it was not registered
+    pub fn unregister(&self, id: ObserverId) -> bool {
+        let mut registrations = self.registrations.write().unwrap_or_else(|e| e.into_inner());
+        let before = registrations.len();
+        registrations.retain(|r| r.id != id);
+        registrations.len() != before
+    }
+
+    /// Number of registered observers
+    pub fn len(&self) -> usize {
+        self.registrations.read().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Whether no observers are registered
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Deliver an event to every matching observer
+    pub fn notify(&self, event: &StoreEvent) {
+        let registrations = self.registrations.read().unwrap_or_else(|e| e.into_inner());
+        for registration in registrations.iter() {
+            if registration.filter.matches(event) {
+                deliver(registration.observer.as_ref(), event);
+            }
+        }
+    }
+}
+
+/// Turns to notify observers in, handed out in commit log order
+///
+/// A writer takes its turn while it holds the commit log lock and runs it
+/// once its entry is durable, so events go out in `seq` order even though
+/// writers wait for their fsyncs concurrently.
+#[derive(Default)]
+pub(crate) struct CommitOrder {
+    issued: AtomicU64,
+    next: Mutex<u64>,
+    turn: Condvar,
+}
+
+impl CommitOrder {
+    /// Take the next turn
+    pub(crate) fn take(&self) -> u64 {
+        self.issued.fetch_add(1, Ordering::SeqCst)
+    }
+
+    /// Wait for `turn`, run `f`, then hand over to the next turn
+    ///
+    /// Every turn taken must be run, or later ones wait forever.
+    pub(crate) fn run<T>(&self, turn: u64, f: impl FnOnce() -> T) -> T {
+        let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+        while *next != turn {
+            next = self.turn.wait(next).unwrap_or_else(|e| e.into_inner());
+        }
+        let result = f();
+        *next += 1;
+        self.turn.notify_all();
+        result
+    }
+}
+
+/// Invoke an observer, containing any panic it raises
+pub(crate) fn deliver(observer: &dyn StoreObserver, event: &StoreEvent) {
+    if catch_unwind(AssertUnwindSafe(|| observer.on_commit(event))).is_err() {
+        tracing::error!(seq = event.seq, entity = ?event.change.entity(), "Store observer panicked");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core_store::model::{ChannelType, MessageId, Timestamp, UserId};
+    use std::sync::Mutex;
+
+    struct Recorder(Mutex<Vec<u64>>);
+
+    impl StoreObserver for Recorder {
+        fn on_commit(&self, event: &StoreEvent) {
+            self.0.lock().unwrap().push(event.seq);
+        }
+    }
+
+    fn message_event(seq: u64, channel: &str) -> StoreEvent {
+        StoreEvent {
+            seq,
+            origin: ChangeOrigin::Local,
+            change: StoreChange::Message(Message::new(
+                MessageId::generate(),
+                ChannelId(channel.to_string()),
+                UserId("alice".to_string()),
+                b"hi".to_vec(),
+                Timestamp::now(),
+            )),
+        }
+    }
+
+    fn channel_event(seq: u64, channel: &str) -> StoreEvent {
+        StoreEvent {
+            seq,
+            origin: ChangeOrigin::Local,
+            change: StoreChange::Channel(Channel::new(
+                ChannelId(channel.to_string()),
+                "general".to_string(),
+                ChannelType::Text,
+                UserId("alice".to_string()),
+                Timestamp::now(),
+                "node1".to_string(),
+            )),
+        }
+    }
+
+    #[test]
+    fn test_filter_by_entity_and_channel() {
+        let filter =
+            ObserverFilter::all().entity(EntityKind::Message).channel(ChannelId("a".into()));
+
+        assert!(filter.matches(&message_event(0, "a")));
+        assert!(!filter.matches(&message_event(1, "b")));
+        assert!(!filter.matches(&channel_event(2, "a")));
+        assert!(ObserverFilter::all().matches(&channel_event(3, "b")));
+    }
+
+    #[test]
+    fn test_unregister() {
+        let registry = ObserverRegistry::new();
+        let recorder = Arc::new(Recorder(Mutex::new(Vec::new())));
+        let id = registry.register(ObserverFilter::all(), recorder.clone());
+
+        registry.notify(&message_event(0, "a"));
+        assert!(registry.unregister(id));
+        assert!(!registry.unregister(id));
+        registry.notify(&message_event(1, "a"));
+
+        assert_eq!(*recorder.0.lock().unwrap(), vec![0]);
+        assert!(registry.is_empty());
+    }
+}
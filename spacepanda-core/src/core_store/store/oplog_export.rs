@@ -0,0 +1,806 @@
+/*
+    oplog_export.rs - Debug export and diff of a channel's operation log
+
+    When two replicas of a channel disagree, comparing their operation logs
+    shows which operations one of them never received. The export format is
+    JSON Lines, deterministic for a given store, so it can also be compared
+    with ordinary text tools.
+
+    Line 1 is a header:
+
+        {"format":"spacepanda-oplog","version":1,"channel_id":"...","payloads":false}
+
+    Every other line is one operation, in the order this replica committed
+    them:
+
+        {"op_id":"...","op_type":"member.add","target":"alice","author":"node-a",
+         "timestamp":1700000000000,"clock":{"node-a":2},"payload_hash":"..."}
+
+    - op_id: hex of the first 16 bytes of a SHA-256 over the op type,
+      target, author, timestamp and payload hash. The same operation has the
+      same ID on every replica.
+    - op_type: `<field>.<action>`, e.g. `member.add`, `invite.put`,
+      `name.set`, `message.post`, `message.react`
+    - target: element, key or message the operation applies to, as JSON
+    - author: node ID for channel operations, sender for message operations
+    - timestamp: milliseconds, as recorded by the author
+    - clock: the channel's vector clock once this replica had committed the
+      operation. Replica-local, so not part of op_id.
+    - payload_hash: hex SHA-256 of the payload (the bincode-encoded element
+      or value, or the message content)
+    - payload: hex payload bytes; only present when exported with payloads
+
+    The store keeps channel state rather than individual operations, so
+    operations are recovered by replaying the commit log: each add ID,
+    tombstone, register write, message, edit and reaction seen for the first
+    time becomes an operation. A register write overwritten before this
+    replica committed it is not visible. Operations from history compacted
+    out of the commit log are listed after it. Within one commit, operations
+    are ordered by timestamp, then op_id.
+
+    Diffing uses an external merge sort over op IDs, so memory stays bounded
+    by the sort run size however long the logs are.
+*/
+
+use crate::core_store::crdt::{AddId, Crdt, LWWRegister, ORMap, ORSet, VectorClock};
+use crate::core_store::model::{Channel, ChannelId, Message};
+use crate::core_store::store::errors::{StoreError, StoreResult};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::{Path, PathBuf};
+
+/// Format name in the header line
+pub const OPLOG_FORMAT: &str = "spacepanda-oplog";
+
+/// Current export format version
+pub const OPLOG_FORMAT_VERSION: u32 = 1;
+
+/// Operations sorted in memory at once while diffing
+pub const DEFAULT_RUN_SIZE: usize = 65_536;
+
+/// First line of an exported oplog
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OplogHeader {
+    /// Always [`OPLOG_FORMAT`]
+    pub format: String,
+
+    /// Format version
+    pub version: u32,
+
+    /// Channel the operations belong to
+    pub channel_id: ChannelId,
+
+    /// Whether operation lines carry their payloads
+    pub payloads: bool,
+}
+
+/// One exported operation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OplogOp {
+    /// Replica-independent operation ID
+    pub op_id: String,
+
+    /// `<field>.<action>`
+    pub op_type: String,
+
+    /// What the operation applies to
+    pub target: serde_json::Value,
+
+    /// Node or user that performed the operation
+    pub author: String,
+
+    /// Author's timestamp in milliseconds
+    pub timestamp: u64,
+
+    /// Channel clock once this replica had committed the operation
+    pub clock: BTreeMap<String, u64>,
+
+    /// Hex SHA-256 of the payload
+    pub payload_hash: String,
+
+    /// Hex payload, if exported with payloads
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload: Option<String>,
+}
+
+/// An operation recovered from channel or message state
+struct RawOp {
+    op_type: String,
+    target: serde_json::Value,
+    author: String,
+    timestamp: u64,
+    payload: Vec<u8>,
+}
+
+impl RawOp {
+    fn new(
+        op_type: String,
+        target: serde_json::Value,
+        author: &str,
+        timestamp: u64,
+        payload: Vec<u8>,
+    ) -> Self {
+        RawOp { op_type, target, author: author.to_string(), timestamp, payload }
+    }
+
+    fn into_op(self, clock: &BTreeMap<String, u64>, payloads: bool) -> OplogOp {
+        let payload_hash = hex::encode(Sha256::digest(&self.payload));
+        let target = self.target.to_string();
+
+        let mut hasher = Sha256::new();
+        for part in [
+            self.op_type.as_bytes(),
+            target.as_bytes(),
+            self.author.as_bytes(),
+            &self.timestamp.to_le_bytes(),
+            payload_hash.as_bytes(),
+        ] {
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        }
+
+        OplogOp {
+            op_id: hex::encode(&hasher.finalize()[..16]),
+            op_type: self.op_type,
+            target: self.target,
+            author: self.author,
+            timestamp: self.timestamp,
+            clock: clock.clone(),
+            payload_hash,
+            payload: payloads.then(|| hex::encode(&self.payload)),
+        }
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> StoreResult<serde_json::Value> {
+    serde_json::to_value(value).map_err(|e| StoreError::Serialization(e.to_string()))
+}
+
+fn add_op<T: Serialize>(
+    op_type: String,
+    element: &T,
+    add_id: &AddId,
+    ops: &mut Vec<RawOp>,
+) -> StoreResult<()> {
+    let payload = bincode::serialize(element)?;
+    ops.push(RawOp::new(
+        op_type,
+        to_json(element)?,
+        &add_id.node_id,
+        add_id.timestamp,
+        payload,
+    ));
+    Ok(())
+}
+
+fn set_ops<T>(field: &str, set: &ORSet<T>, ops: &mut Vec<RawOp>) -> StoreResult<()>
+where
+    T: Clone + Eq + std::hash::Hash + Serialize,
+{
+    for (element, add_id) in set.adds() {
+        add_op(format!("{}.add", field), element, add_id, ops)?;
+    }
+    for (element, add_id) in set.tombstones() {
+        add_op(format!("{}.remove", field), element, add_id, ops)?;
+    }
+    Ok(())
+}
+
+/// Puts of an OR-Map, with `payload` giving the bytes of a value
+fn map_ops<K, V>(
+    field: &str,
+    map: &ORMap<K, V>,
+    payload: impl Fn(&V) -> StoreResult<Vec<u8>>,
+    ops: &mut Vec<RawOp>,
+) -> StoreResult<()>
+where
+    K: Clone + Eq + std::hash::Hash + Serialize,
+    V: Clone,
+{
+    for key in map.keys() {
+        let Some(value) = map.get(&key) else { continue };
+        let payload = payload(value)?;
+        for add_id in map.get_add_ids(&key).into_iter().flatten() {
+            let op_type = format!("{}.put", field);
+            ops.push(RawOp::new(
+                op_type,
+                to_json(&key)?,
+                &add_id.node_id,
+                add_id.timestamp,
+                payload.clone(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Payload of a register value, leaving out its replica-local clock
+fn register_payload<T: Clone + Serialize>(register: &LWWRegister<T>) -> StoreResult<Vec<u8>> {
+    Ok(bincode::serialize(&register.get())?)
+}
+
+fn register_op<T>(field: &str, register: &LWWRegister<T>, ops: &mut Vec<RawOp>) -> StoreResult<()>
+where
+    T: Clone + Serialize,
+{
+    if let Some(value) = register.get() {
+        ops.push(RawOp::new(
+            format!("{}.set", field),
+            serde_json::Value::Null,
+            register.writer(),
+            register.timestamp(),
+            bincode::serialize(value)?,
+        ));
+    }
+    Ok(())
+}
+
+/// Operations visible in one version of a channel
+fn channel_ops(channel: &Channel) -> StoreResult<Vec<RawOp>> {
+    let mut ops = Vec::new();
+    register_op("name", &channel.name, &mut ops)?;
+    register_op("topic", &channel.topic, &mut ops)?;
+    set_ops("member", &channel.members, &mut ops)?;
+    set_ops("device", &channel.member_devices, &mut ops)?;
+    set_ops("pin", &channel.pinned_messages, &mut ops)?;
+    map_ops("permission", &channel.permissions, register_payload, &mut ops)?;
+    map_ops(
+        "mls_identity",
+        &channel.mls_identity,
+        |meta| Ok(bincode::serialize(meta)?),
+        &mut ops,
+    )?;
+    map_ops("invite", &channel.invites, register_payload, &mut ops)?;
+    set_ops("invite_revocation", &channel.revoked_invites, &mut ops)?;
+    map_ops("webhook", &channel.webhooks, register_payload, &mut ops)?;
+    set_ops("webhook_revocation", &channel.revoked_webhooks, &mut ops)?;
+    map_ops("linked_device", &channel.linked_devices, register_payload, &mut ops)?;
+    set_ops("device_revocation", &channel.revoked_devices, &mut ops)?;
+    map_ops("replica_node", &channel.replica_nodes, register_payload, &mut ops)?;
+    Ok(ops)
+}
+
+/// Vector clock of a whole channel: the merge of its fields' clocks
+fn channel_clock(channel: &Channel) -> VectorClock {
+    let mut clock = channel.name.vector_clock().clone();
+    for field in [
+        channel.topic.vector_clock(),
+        channel.members.vector_clock(),
+        channel.member_devices.vector_clock(),
+        channel.pinned_messages.vector_clock(),
+        channel.permissions.vector_clock(),
+        channel.mls_identity.vector_clock(),
+        channel.invites.vector_clock(),
+        channel.revoked_invites.vector_clock(),
+        channel.webhooks.vector_clock(),
+        channel.revoked_webhooks.vector_clock(),
+        channel.linked_devices.vector_clock(),
+        channel.revoked_devices.vector_clock(),
+        channel.replica_nodes.vector_clock(),
+    ] {
+        clock.merge(field);
+    }
+    clock
+}
+
+/// Operations visible in one version of a message
+///
+/// The store does not record who deleted a message or when, so a delete
+/// carries the message's own sender and timestamp.
+fn message_ops(message: &Message) -> StoreResult<Vec<RawOp>> {
+    let id = to_json(&message.id)?;
+    let sender = message.sender.0.as_str();
+    let timestamp = message.timestamp.as_millis();
+
+    let mut ops = vec![RawOp::new(
+        "message.post".to_string(),
+        id.clone(),
+        sender,
+        timestamp,
+        message.content.clone(),
+    )];
+    for (edited_at, editor, content) in &message.edits {
+        ops.push(RawOp::new(
+            "message.edit".to_string(),
+            id.clone(),
+            &editor.0,
+            edited_at.as_millis(),
+            content.clone(),
+        ));
+    }
+    for ((user, emoji), add_id) in message.reactions.adds() {
+        let target = serde_json::json!([id, emoji, user]);
+        ops.push(RawOp::new(
+            "message.react".to_string(),
+            target,
+            &add_id.node_id,
+            add_id.timestamp,
+            Vec::new(),
+        ));
+    }
+    for ((user, emoji), add_id) in message.reactions.tombstones() {
+        let target = serde_json::json!([id, emoji, user]);
+        ops.push(RawOp::new(
+            "message.unreact".to_string(),
+            target,
+            &add_id.node_id,
+            add_id.timestamp,
+            Vec::new(),
+        ));
+    }
+    if message.deleted {
+        ops.push(RawOp::new("message.delete".to_string(), id, sender, timestamp, Vec::new()));
+    }
+    Ok(ops)
+}
+
+/// Writes a channel's operations as they are first seen
+///
+/// Feed it every version of the channel and its messages in commit order;
+/// operations already written are skipped.
+pub struct OplogExporter<W: Write> {
+    out: W,
+    channel_id: ChannelId,
+    payloads: bool,
+    clock: VectorClock,
+    seen: HashSet<String>,
+    written: u64,
+}
+
+impl<W: Write> OplogExporter<W> {
+    /// Start an export, writing the header line
+    pub fn new(mut out: W, channel_id: &ChannelId, payloads: bool) -> StoreResult<Self> {
+        let header = OplogHeader {
+            format: OPLOG_FORMAT.to_string(),
+            version: OPLOG_FORMAT_VERSION,
+            channel_id: channel_id.clone(),
+            payloads,
+        };
+        write_line(&mut out, &header)?;
+
+        Ok(OplogExporter {
+            out,
+            channel_id: channel_id.clone(),
+            payloads,
+            clock: VectorClock::new(),
+            seen: HashSet::new(),
+            written: 0,
+        })
+    }
+
+    /// Record a version of the channel
+    pub fn channel(&mut self, channel: &Channel) -> StoreResult<()> {
+        if channel.id != self.channel_id {
+            return Ok(());
+        }
+        self.clock.merge(&channel_clock(channel));
+        self.emit(channel_ops(channel)?)
+    }
+
+    /// Record a version of a message
+    pub fn message(&mut self, message: &Message) -> StoreResult<()> {
+        if message.channel_id != self.channel_id {
+            return Ok(());
+        }
+        self.emit(message_ops(message)?)
+    }
+
+    fn emit(&mut self, raw: Vec<RawOp>) -> StoreResult<()> {
+        let clock: BTreeMap<String, u64> = self
+            .clock
+            .node_ids()
+            .into_iter()
+            .map(|node| (node.clone(), self.clock.get(&node)))
+            .collect();
+
+        let mut ops: Vec<OplogOp> = raw
+            .into_iter()
+            .map(|op| op.into_op(&clock, self.payloads))
+            .filter(|op| !self.seen.contains(&op.op_id))
+            .collect();
+        ops.sort_by(|a, b| (a.timestamp, &a.op_id).cmp(&(b.timestamp, &b.op_id)));
+        ops.dedup_by(|a, b| a.op_id == b.op_id);
+
+        for op in ops {
+            write_line(&mut self.out, &op)?;
+            self.seen.insert(op.op_id);
+            self.written += 1;
+        }
+        Ok(())
+    }
+
+    /// Flush the output and return the number of operations written
+    pub fn finish(mut self) -> StoreResult<u64> {
+        self.out.flush()?;
+        Ok(self.written)
+    }
+}
+
+fn write_line<W: Write, T: Serialize>(out: &mut W, value: &T) -> StoreResult<()> {
+    serde_json::to_writer(&mut *out, value)
+        .map_err(|e| StoreError::Serialization(e.to_string()))?;
+    out.write_all(b"\n")?;
+    Ok(())
+}
+
+/// Which of the two diffed logs something refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OplogSide {
+    A,
+    B,
+}
+
+/// Something the differ found, reported as it is found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OplogFinding {
+    /// An operation in `side`'s log that the other log lacks
+    Missing { side: OplogSide, index: u64, op: OplogOp },
+
+    /// The same operation listed more than once in one log
+    Duplicate { side: OplogSide, index: u64, op_id: String },
+
+    /// An operation committed under an older clock than the one before it
+    ClockRegression {
+        side: OplogSide,
+        index: u64,
+        op_id: String,
+        node: String,
+        previous: u64,
+        current: u64,
+    },
+}
+
+/// Totals of a diff
+///
+/// Indexes are 1-based positions among a log's operations (the header
+/// line is not counted).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OplogDiffSummary {
+    /// Operations in log A
+    pub ops_a: u64,
+
+    /// Operations in log B
+    pub ops_b: u64,
+
+    /// Operations only log A has
+    pub only_in_a: u64,
+
+    /// Operations only log B has
+    pub only_in_b: u64,
+
+    /// Duplicates and clock regressions in either log
+    pub anomalies: u64,
+
+    /// Leading operations both logs list in the same order
+    pub common_prefix: u64,
+
+    /// Index in A of the earliest operation B lacks
+    pub first_missing_from_b: Option<u64>,
+
+    /// Index in B of the earliest operation A lacks
+    pub first_missing_from_a: Option<u64>,
+}
+
+impl OplogDiffSummary {
+    /// Whether both logs hold the same operations
+    pub fn is_converged(&self) -> bool {
+        self.only_in_a == 0 && self.only_in_b == 0
+    }
+}
+
+/// Compares two exported oplogs
+pub struct OplogDiffer {
+    work_dir: PathBuf,
+    run_size: usize,
+}
+
+impl OplogDiffer {
+    /// Create a differ that keeps its sort runs under `work_dir`
+    pub fn new(work_dir: impl Into<PathBuf>) -> Self {
+        OplogDiffer { work_dir: work_dir.into(), run_size: DEFAULT_RUN_SIZE }
+    }
+
+    /// Sort at most this many operations in memory at once
+    pub fn with_run_size(mut self, run_size: usize) -> Self {
+        self.run_size = run_size.max(1);
+        self
+    }
+
+    /// Diff two exports of the same channel
+    ///
+    /// Findings are passed to `on_finding` as they are found rather than
+    /// collected, so a large divergence does not have to fit in memory.
+    pub fn diff(
+        &self,
+        a: &Path,
+        b: &Path,
+        mut on_finding: impl FnMut(OplogFinding),
+    ) -> StoreResult<OplogDiffSummary> {
+        let header_a = read_header(a)?;
+        let header_b = read_header(b)?;
+        if header_a.channel_id != header_b.channel_id {
+            return Err(StoreError::InvalidOperation(format!(
+                "Oplogs are of different channels ({} and {})",
+                header_a.channel_id, header_b.channel_id
+            )));
+        }
+
+        let mut summary =
+            OplogDiffSummary { common_prefix: common_prefix(a, b)?, ..Default::default() };
+        let mut scratch = ScratchDir::create(&self.work_dir)?;
+
+        let runs_a =
+            self.write_runs(a, OplogSide::A, &mut scratch, &mut summary, &mut on_finding)?;
+        let runs_b =
+            self.write_runs(b, OplogSide::B, &mut scratch, &mut summary, &mut on_finding)?;
+        let mut sorted_a = SortedOps::new(runs_a, OplogSide::A)?;
+        let mut sorted_b = SortedOps::new(runs_b, OplogSide::B)?;
+
+        let mut next_a = sorted_a.next(&mut summary, &mut on_finding)?;
+        let mut next_b = sorted_b.next(&mut summary, &mut on_finding)?;
+        loop {
+            let a_first = match (&next_a, &next_b) {
+                (None, None) => break,
+                (Some(a), Some(b)) if a.op_id == b.op_id => {
+                    next_a = sorted_a.next(&mut summary, &mut on_finding)?;
+                    next_b = sorted_b.next(&mut summary, &mut on_finding)?;
+                    continue;
+                }
+                (Some(a), Some(b)) => a.op_id < b.op_id,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+            };
+
+            // The smaller op ID is one the other log does not have
+            let (side, record, first_missing) = if a_first {
+                summary.only_in_a += 1;
+                let record = next_a.take().expect("checked above");
+                next_a = sorted_a.next(&mut summary, &mut on_finding)?;
+                (OplogSide::A, record, &mut summary.first_missing_from_b)
+            } else {
+                summary.only_in_b += 1;
+                let record = next_b.take().expect("checked above");
+                next_b = sorted_b.next(&mut summary, &mut on_finding)?;
+                (OplogSide::B, record, &mut summary.first_missing_from_a)
+            };
+            *first_missing = Some(first_missing.map_or(record.index, |i| i.min(record.index)));
+            on_finding(OplogFinding::Missing { side, index: record.index, op: record.op()? });
+        }
+
+        Ok(summary)
+    }
+
+    /// Check one log's ordering and split it into sorted runs on disk
+    fn write_runs(
+        &self,
+        path: &Path,
+        side: OplogSide,
+        scratch: &mut ScratchDir,
+        summary: &mut OplogDiffSummary,
+        on_finding: &mut impl FnMut(OplogFinding),
+    ) -> StoreResult<Vec<PathBuf>> {
+        let mut runs = Vec::new();
+        let mut run: Vec<SortRecord> = Vec::with_capacity(self.run_size.min(DEFAULT_RUN_SIZE));
+        let mut previous_clock: Option<BTreeMap<String, u64>> = None;
+        let mut count = 0;
+
+        for op in OpReader::open(path)? {
+            let (index, op) = op?;
+            count = index;
+
+            if let Some(previous) = &previous_clock {
+                let regressed = previous
+                    .iter()
+                    .find(|&(node, &time)| op.clock.get(node).copied().unwrap_or(0) < time);
+                if let Some((node, &time)) = regressed {
+                    summary.anomalies += 1;
+                    on_finding(OplogFinding::ClockRegression {
+                        side,
+                        index,
+                        op_id: op.op_id.clone(),
+                        node: node.clone(),
+                        previous: time,
+                        current: op.clock.get(node).copied().unwrap_or(0),
+                    });
+                }
+            }
+
+            let json =
+                serde_json::to_string(&op).map_err(|e| StoreError::Serialization(e.to_string()))?;
+            previous_clock = Some(op.clock);
+            run.push(SortRecord { op_id: op.op_id, index, json });
+
+            if run.len() >= self.run_size {
+                runs.push(scratch.write_run(&mut run)?);
+            }
+        }
+        if !run.is_empty() {
+            runs.push(scratch.write_run(&mut run)?);
+        }
+
+        match side {
+            OplogSide::A => summary.ops_a = count,
+            OplogSide::B => summary.ops_b = count,
+        }
+        Ok(runs)
+    }
+}
+
+/// Read and check the header line of an export
+pub fn read_header(path: &Path) -> StoreResult<OplogHeader> {
+    let file = File::open(path)?;
+    let mut line = String::new();
+    BufReader::new(file).read_line(&mut line)?;
+    parse_header(path, &line)
+}
+
+fn parse_header(path: &Path, line: &str) -> StoreResult<OplogHeader> {
+    let header: OplogHeader = serde_json::from_str(line).map_err(|e| {
+        StoreError::Deserialization(format!("{:?} has no oplog header: {}", path, e))
+    })?;
+    if header.format != OPLOG_FORMAT || header.version != OPLOG_FORMAT_VERSION {
+        return Err(StoreError::InvalidOperation(format!(
+            "{:?} is {} version {}, expected {} version {}",
+            path, header.format, header.version, OPLOG_FORMAT, OPLOG_FORMAT_VERSION
+        )));
+    }
+    Ok(header)
+}
+
+/// Number of leading operations two logs list in the same order
+fn common_prefix(a: &Path, b: &Path) -> StoreResult<u64> {
+    let mut prefix = 0;
+    for (op_a, op_b) in OpReader::open(a)?.zip(OpReader::open(b)?) {
+        if op_a?.1.op_id != op_b?.1.op_id {
+            break;
+        }
+        prefix += 1;
+    }
+    Ok(prefix)
+}
+
+/// Streams the operations of an export with their indexes
+struct OpReader {
+    path: PathBuf,
+    lines: Lines<BufReader<File>>,
+    index: u64,
+}
+
+impl OpReader {
+    fn open(path: &Path) -> StoreResult<Self> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        let header = lines.next().transpose()?.unwrap_or_default();
+        parse_header(path, &header)?;
+        Ok(OpReader { path: path.to_path_buf(), lines, index: 0 })
+    }
+}
+
+impl Iterator for OpReader {
+    type Item = StoreResult<(u64, OplogOp)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = match self.lines.next()? {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e.into())),
+        };
+        self.index += 1;
+        let op = serde_json::from_str(&line).map_err(|e| {
+            StoreError::Deserialization(format!("{:?} operation {}: {}", self.path, self.index, e))
+        });
+        Some(op.map(|op| (self.index, op)))
+    }
+}
+
+/// An operation as held while sorting
+struct SortRecord {
+    op_id: String,
+    index: u64,
+    json: String,
+}
+
+impl SortRecord {
+    fn op(&self) -> StoreResult<OplogOp> {
+        serde_json::from_str(&self.json).map_err(|e| StoreError::Deserialization(e.to_string()))
+    }
+
+    fn parse(line: &str) -> StoreResult<Self> {
+        let mut parts = line.splitn(3, '\t');
+        let (Some(op_id), Some(index), Some(json)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(StoreError::CorruptedData("Malformed oplog sort run".to_string()));
+        };
+        let index = index
+            .parse()
+            .map_err(|_| StoreError::CorruptedData("Malformed oplog sort run".to_string()))?;
+        Ok(SortRecord { op_id: op_id.to_string(), index, json: json.to_string() })
+    }
+}
+
+/// Scratch directory for sort runs, removed when dropped
+struct ScratchDir {
+    path: PathBuf,
+    runs: usize,
+}
+
+impl ScratchDir {
+    fn create(parent: &Path) -> StoreResult<Self> {
+        let path = parent.join(format!("oplog-diff-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&path)?;
+        Ok(ScratchDir { path, runs: 0 })
+    }
+
+    /// Sort a run by op ID and write it out, leaving `run` empty
+    ///
+    /// Lines are `<op_id>\t<index>\t<json>`; compact JSON has no raw tabs
+    /// or newlines.
+    fn write_run(&mut self, run: &mut Vec<SortRecord>) -> StoreResult<PathBuf> {
+        run.sort_by(|a, b| (&a.op_id, a.index).cmp(&(&b.op_id, b.index)));
+
+        let path = self.path.join(format!("run-{}", self.runs));
+        self.runs += 1;
+        let mut out = BufWriter::new(File::create(&path)?);
+        for record in run.drain(..) {
+            writeln!(out, "{}\t{}\t{}", record.op_id, record.index, record.json)?;
+        }
+        out.flush()?;
+        Ok(path)
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// One log's operations in op ID order, merged from its sorted runs
+struct SortedOps {
+    side: OplogSide,
+    runs: Vec<Lines<BufReader<File>>>,
+    heap: BinaryHeap<Reverse<(String, u64, usize, String)>>,
+    last_op_id: Option<String>,
+}
+
+impl SortedOps {
+    fn new(paths: Vec<PathBuf>, side: OplogSide) -> StoreResult<Self> {
+        let mut sorted =
+            SortedOps { side, runs: Vec::new(), heap: BinaryHeap::new(), last_op_id: None };
+        for path in paths {
+            sorted.runs.push(BufReader::new(File::open(path)?).lines());
+            sorted.refill(sorted.runs.len() - 1)?;
+        }
+        Ok(sorted)
+    }
+
+    /// Queue the next record of a run, if it has one
+    fn refill(&mut self, run: usize) -> StoreResult<()> {
+        if let Some(line) = self.runs[run].next().transpose()? {
+            let record = SortRecord::parse(&line)?;
+            self.heap.push(Reverse((record.op_id, record.index, run, record.json)));
+        }
+        Ok(())
+    }
+
+    /// Next distinct operation, reporting repeats as duplicates
+    fn next(
+        &mut self,
+        summary: &mut OplogDiffSummary,
+        on_finding: &mut impl FnMut(OplogFinding),
+    ) -> StoreResult<Option<SortRecord>> {
+        while let Some(Reverse((op_id, index, run, json))) = self.heap.pop() {
+            self.refill(run)?;
+            if self.last_op_id.as_deref() == Some(op_id.as_str()) {
+                summary.anomalies += 1;
+                on_finding(OplogFinding::Duplicate { side: self.side, index, op_id });
+                continue;
+            }
+            self.last_op_id = Some(op_id.clone());
+            return Ok(Some(SortRecord { op_id, index, json }));
+        }
+        Ok(None)
+    }
+}
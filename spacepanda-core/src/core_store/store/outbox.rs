@@ -0,0 +1,319 @@
+/*
+    outbox.rs - Durable queue of work waiting for connectivity
+
+    Two kinds of entries:
+    - Sent messages whose delivery failed (or that were sent before any
+      peer was reachable). The ciphertext is kept and handed to the
+      network again once it's back.
+    - Channels joined from an offline invite, whose network steps
+      (directory listing refresh, mailbox drain, key package top-up,
+      acceptance to the inviter) haven't run yet.
+
+    Like the inbox, the queue is persisted as a single bincode file
+    rewritten atomically on every change, so work queued by one run of the
+    client is picked up by the next. Messages keep the order they were
+    queued in, and the queue can be capped in total ciphertext size by
+    evicting the oldest messages.
+*/
+
+use crate::core_store::model::{ChannelId, Timestamp, UserId};
+use crate::core_store::store::errors::StoreResult;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// A sent message awaiting delivery
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutgoingEnvelope {
+    /// Queue-local identifier
+    pub id: u64,
+
+    /// Channel the message was sent to
+    pub channel_id: ChannelId,
+
+    /// Sending user
+    pub sender: UserId,
+
+    /// MLS ciphertext
+    pub ciphertext: Vec<u8>,
+
+    /// Whether the message goes through the broadcast mailbox
+    pub broadcast: bool,
+
+    /// When the message was queued
+    pub queued_at: Timestamp,
+
+    /// Failed delivery attempts so far
+    pub attempts: u32,
+}
+
+/// Network steps of a join that ran without a network
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeferredJoin {
+    /// Joined channel
+    pub channel_id: ChannelId,
+
+    /// Invite the channel was joined with
+    pub invite_id: String,
+
+    /// User who issued the invite
+    pub inviter: UserId,
+
+    /// Inviter's peer ID, if the invite carried one
+    pub inviter_peer_id: Option<Vec<u8>>,
+
+    /// Addresses to try first when reconnecting
+    pub bootstrap_peers: Vec<String>,
+
+    /// When the channel was joined
+    pub joined_at: Timestamp,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct OutboxState {
+    next_id: u64,
+    envelopes: Vec<OutgoingEnvelope>,
+    deferred_joins: Vec<DeferredJoin>,
+}
+
+/// Persistent queue of undelivered messages and deferred joins
+pub struct PendingOutbox {
+    path: PathBuf,
+    state: Mutex<OutboxState>,
+}
+
+impl PendingOutbox {
+    /// Open (or create) the outbox stored at `path`
+    pub fn open(path: PathBuf) -> StoreResult<Self> {
+        let state = if path.exists() {
+            bincode::deserialize(&fs::read(&path)?)?
+        } else {
+            OutboxState::default()
+        };
+
+        Ok(Self { path, state: Mutex::new(state) })
+    }
+
+    /// Queue a message for delivery, returning its ID
+    pub fn enqueue(
+        &self,
+        channel_id: ChannelId,
+        sender: UserId,
+        ciphertext: Vec<u8>,
+        broadcast: bool,
+    ) -> StoreResult<u64> {
+        let mut state = self.lock();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.envelopes.push(OutgoingEnvelope {
+            id,
+            channel_id,
+            sender,
+            ciphertext,
+            broadcast,
+            queued_at: Timestamp::now(),
+            attempts: 0,
+        });
+        self.persist(&state)?;
+        Ok(id)
+    }
+
+    /// Queued messages for a channel, oldest first
+    pub fn pending(&self, channel_id: &ChannelId) -> Vec<OutgoingEnvelope> {
+        self.lock()
+            .envelopes
+            .iter()
+            .filter(|e| &e.channel_id == channel_id)
+            .cloned()
+            .collect()
+    }
+
+    /// All queued messages, oldest first
+    pub fn all(&self) -> Vec<OutgoingEnvelope> {
+        self.lock().envelopes.clone()
+    }
+
+    /// Whether any message for a channel is queued
+    pub fn has_pending(&self, channel_id: &ChannelId) -> bool {
+        self.lock().envelopes.iter().any(|e| &e.channel_id == channel_id)
+    }
+
+    /// Total ciphertext size of the queued messages
+    pub fn size_bytes(&self) -> usize {
+        self.lock().envelopes.iter().map(|e| e.ciphertext.len()).sum()
+    }
+
+    /// Drop the oldest messages until the queue fits in `max_bytes`
+    ///
+    /// Message `keep` (usually the one just queued) is never dropped, so
+    /// it alone may exceed the cap. Returns the dropped messages, oldest
+    /// first.
+    pub fn evict_oldest(&self, max_bytes: usize, keep: u64) -> StoreResult<Vec<OutgoingEnvelope>> {
+        let mut state = self.lock();
+        let mut size: usize = state.envelopes.iter().map(|e| e.ciphertext.len()).sum();
+        let mut evicted = Vec::new();
+        while size > max_bytes {
+            let Some(index) = state.envelopes.iter().position(|e| e.id != keep) else {
+                break;
+            };
+            let envelope = state.envelopes.remove(index);
+            size -= envelope.ciphertext.len();
+            evicted.push(envelope);
+        }
+        if !evicted.is_empty() {
+            self.persist(&state)?;
+        }
+        Ok(evicted)
+    }
+
+    /// Remove a delivered message; returns false if it was not queued
+    pub fn remove(&self, id: u64) -> StoreResult<bool> {
+        let mut state = self.lock();
+        let before = state.envelopes.len();
+        state.envelopes.retain(|e| e.id != id);
+        if state.envelopes.len() == before {
+            return Ok(false);
+        }
+        self.persist(&state)?;
+        Ok(true)
+    }
+
+    /// Record a failed delivery attempt, returning the new attempt count
+    pub fn record_attempt(&self, id: u64) -> StoreResult<u32> {
+        let mut state = self.lock();
+        let Some(envelope) = state.envelopes.iter_mut().find(|e| e.id == id) else {
+            return Ok(0);
+        };
+        envelope.attempts += 1;
+        let attempts = envelope.attempts;
+        self.persist(&state)?;
+        Ok(attempts)
+    }
+
+    /// Number of queued messages
+    pub fn len(&self) -> usize {
+        self.lock().envelopes.len()
+    }
+
+    /// Whether no messages are queued
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Record a join whose network steps still have to run
+    ///
+    /// Replaces an earlier record for the same channel.
+    pub fn defer_join(&self, join: DeferredJoin) -> StoreResult<()> {
+        let mut state = self.lock();
+        state.deferred_joins.retain(|j| j.channel_id != join.channel_id);
+        state.deferred_joins.push(join);
+        self.persist(&state)
+    }
+
+    /// Joins whose network steps still have to run, oldest first
+    pub fn deferred_joins(&self) -> Vec<DeferredJoin> {
+        self.lock().deferred_joins.clone()
+    }
+
+    /// Whether a channel's join still has network steps to run
+    pub fn is_deferred(&self, channel_id: &ChannelId) -> bool {
+        self.lock().deferred_joins.iter().any(|j| &j.channel_id == channel_id)
+    }
+
+    /// Drop a channel's deferred join once its steps have run
+    pub fn complete_join(&self, channel_id: &ChannelId) -> StoreResult<bool> {
+        let mut state = self.lock();
+        let before = state.deferred_joins.len();
+        state.deferred_joins.retain(|j| &j.channel_id != channel_id);
+        if state.deferred_joins.len() == before {
+            return Ok(false);
+        }
+        self.persist(&state)?;
+        Ok(true)
+    }
+
+    fn persist(&self, state: &OutboxState) -> StoreResult<()> {
+        // Atomic write: temp file then rename
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, bincode::serialize(state)?)?;
+        fs::rename(tmp, &self.path)?;
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, OutboxState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_outbox_survives_reopen() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("outbox.bin");
+        let general = ChannelId("general".to_string());
+        let alice = UserId("alice".to_string());
+        let join = DeferredJoin {
+            channel_id: general.clone(),
+            invite_id: "invite-1".to_string(),
+            inviter: UserId("bob".to_string()),
+            inviter_peer_id: Some(vec![7]),
+            bootstrap_peers: vec!["192.0.2.1:7000".to_string()],
+            joined_at: Timestamp::now(),
+        };
+
+        let first = {
+            let outbox = PendingOutbox::open(path.clone()).unwrap();
+            let first = outbox.enqueue(general.clone(), alice.clone(), vec![1], false).unwrap();
+            outbox.enqueue(general.clone(), alice.clone(), vec![2], true).unwrap();
+            assert_eq!(outbox.record_attempt(first).unwrap(), 1);
+            outbox.defer_join(join.clone()).unwrap();
+            first
+        };
+
+        let outbox = PendingOutbox::open(path).unwrap();
+        let pending = outbox.pending(&general);
+        assert_eq!(pending.iter().map(|e| e.ciphertext[0]).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(pending[0].attempts, 1);
+        assert!(pending[1].broadcast);
+        assert_eq!(outbox.deferred_joins(), vec![join]);
+
+        assert!(outbox.remove(first).unwrap());
+        assert_eq!(outbox.len(), 1);
+        assert!(outbox.complete_join(&general).unwrap());
+        assert!(!outbox.is_deferred(&general));
+        assert!(!outbox.complete_join(&general).unwrap());
+    }
+
+    #[test]
+    fn test_outbox_evicts_oldest_over_cap() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("outbox.bin");
+        let general = ChannelId("general".to_string());
+        let random = ChannelId("random".to_string());
+        let alice = UserId("alice".to_string());
+
+        let outbox = PendingOutbox::open(path.clone()).unwrap();
+        let first = outbox.enqueue(general.clone(), alice.clone(), vec![1; 40], false).unwrap();
+        outbox.enqueue(random.clone(), alice.clone(), vec![2; 40], false).unwrap();
+        let third = outbox.enqueue(general.clone(), alice.clone(), vec![3; 40], false).unwrap();
+        assert_eq!(outbox.size_bytes(), 120);
+        assert!(outbox.evict_oldest(120, third).unwrap().is_empty());
+
+        let evicted = outbox.evict_oldest(100, third).unwrap();
+        assert_eq!(evicted.iter().map(|e| e.id).collect::<Vec<_>>(), vec![first]);
+        assert!(outbox.has_pending(&random));
+
+        // The kept message stays even when it alone is over the cap
+        let evicted = outbox.evict_oldest(10, third).unwrap();
+        assert_eq!(evicted.len(), 1);
+        assert!(!outbox.has_pending(&random));
+
+        let outbox = PendingOutbox::open(path).unwrap();
+        assert_eq!(outbox.all().iter().map(|e| e.id).collect::<Vec<_>>(), vec![third]);
+    }
+}
@@ -0,0 +1,181 @@
+/*
+    preflight.rs - Free disk space checks before large operations
+
+    Joining a large channel, importing history, downloading attachments or
+    restoring a backup can fill the disk halfway through and leave partial
+    state behind for recovery to clean up. A preflight estimates how much
+    the operation will write and refuses to start unless the filesystem has
+    that much free plus a safety margin.
+
+    Estimates:
+    - join: Welcome and ratchet tree size, times JOIN_EXPANSION (group
+      state, key material and store records built from them)
+    - history import: import file size, times IMPORT_EXPANSION (decoded
+      messages plus indexes)
+    - attachment download / backup restore: size from the manifest
+
+    Free space comes from an `FsStats` provider, so tests can pretend the
+    disk is full. If the provider fails, the check passes with a warning:
+    a preflight must never be the reason an operation can't run at all.
+*/
+
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::warn;
+
+/// Bytes written per byte of Welcome and ratchet tree when joining
+pub const JOIN_EXPANSION: u64 = 4;
+
+/// Bytes written per byte of history import file
+pub const IMPORT_EXPANSION: u64 = 3;
+
+/// Default free space to leave on top of an estimate (64 MB)
+pub const DEFAULT_SAFETY_MARGIN: u64 = 64 * 1024 * 1024;
+
+/// Not enough free space for an operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("Insufficient storage: {required} bytes required, {available} available")]
+pub struct InsufficientStorage {
+    /// Estimated size plus safety margin
+    pub required: u64,
+
+    /// Free space on the target filesystem
+    pub available: u64,
+}
+
+/// Source of free space figures for a filesystem
+pub trait FsStats: Send + Sync {
+    /// Bytes available to this process on the filesystem holding `path`
+    fn available_space(&self, path: &Path) -> io::Result<u64>;
+}
+
+/// Free space as reported by the operating system
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemFsStats;
+
+impl FsStats for SystemFsStats {
+    fn available_space(&self, path: &Path) -> io::Result<u64> {
+        // The target (e.g. a restore directory) may not exist yet
+        let existing = path.ancestors().find(|p| p.exists()).unwrap_or(path);
+        fs2::available_space(existing)
+    }
+}
+
+/// Estimated bytes written by joining a channel
+pub fn estimate_join(welcome_len: usize, ratchet_tree_len: usize) -> u64 {
+    (welcome_len as u64)
+        .saturating_add(ratchet_tree_len as u64)
+        .saturating_mul(JOIN_EXPANSION)
+}
+
+/// Estimated bytes written by importing a history file
+pub fn estimate_history_import(file_size: u64) -> u64 {
+    file_size.saturating_mul(IMPORT_EXPANSION)
+}
+
+/// Free space check run before large operations
+#[derive(Clone)]
+pub struct StoragePreflight {
+    stats: Arc<dyn FsStats>,
+    safety_margin: u64,
+}
+
+impl StoragePreflight {
+    /// Check against the operating system's free space figures
+    ///
+    /// # Arguments
+    ///
+    /// * `safety_margin` - Bytes that must stay free on top of an estimate
+    pub fn new(safety_margin: u64) -> Self {
+        Self { stats: Arc::new(SystemFsStats), safety_margin }
+    }
+
+    /// Use another free space provider
+    pub fn with_fs_stats(mut self, stats: Arc<dyn FsStats>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    /// Configured safety margin in bytes
+    pub fn safety_margin(&self) -> u64 {
+        self.safety_margin
+    }
+
+    /// Check that `dir`'s filesystem can take `estimate` more bytes
+    pub fn check(&self, dir: &Path, estimate: u64) -> Result<(), InsufficientStorage> {
+        let required = estimate.saturating_add(self.safety_margin);
+        let available = match self.stats.available_space(dir) {
+            Ok(available) => available,
+            Err(e) => {
+                warn!(dir = ?dir, error = %e, "Could not read free space, skipping preflight");
+                return Ok(());
+            }
+        };
+
+        if available < required {
+            warn!(dir = ?dir, required, available, "Not enough free space");
+            return Err(InsufficientStorage { required, available });
+        }
+        Ok(())
+    }
+}
+
+impl Default for StoragePreflight {
+    fn default() -> Self {
+        Self::new(DEFAULT_SAFETY_MARGIN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedSpace(io::Result<u64>);
+
+    impl FsStats for FixedSpace {
+        fn available_space(&self, _path: &Path) -> io::Result<u64> {
+            match &self.0 {
+                Ok(space) => Ok(*space),
+                Err(e) => Err(io::Error::new(e.kind(), e.to_string())),
+            }
+        }
+    }
+
+    fn preflight(margin: u64, available: u64) -> StoragePreflight {
+        StoragePreflight::new(margin).with_fs_stats(Arc::new(FixedSpace(Ok(available))))
+    }
+
+    #[test]
+    fn test_estimate_plus_margin_must_fit() {
+        let dir = Path::new("/data");
+        assert!(preflight(100, 1_100).check(dir, 1_000).is_ok());
+        assert_eq!(
+            preflight(100, 1_099).check(dir, 1_000),
+            Err(InsufficientStorage { required: 1_100, available: 1_099 })
+        );
+        assert!(preflight(0, 0).check(dir, 0).is_ok());
+    }
+
+    #[test]
+    fn test_estimates() {
+        assert_eq!(estimate_join(100, 50), 150 * JOIN_EXPANSION);
+        assert_eq!(estimate_history_import(1_000), 1_000 * IMPORT_EXPANSION);
+        assert_eq!(estimate_join(usize::MAX, 1), u64::MAX);
+    }
+
+    #[test]
+    fn test_unreadable_stats_pass() {
+        let stats = FixedSpace(Err(io::Error::new(io::ErrorKind::Unsupported, "no statvfs")));
+        let preflight = StoragePreflight::new(100).with_fs_stats(Arc::new(stats));
+        assert!(preflight.check(Path::new("/data"), u64::MAX).is_ok());
+    }
+
+    #[test]
+    fn test_system_stats_on_missing_directory() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let available = SystemFsStats.available_space(&dir.path().join("not/yet/created"));
+        assert!(available.is_ok());
+    }
+}
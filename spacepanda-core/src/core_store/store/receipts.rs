@@ -0,0 +1,248 @@
+/*
+    receipts.rs - Delivery state of the messages we sent
+
+    Each sent message is tracked against the members it was encrypted for,
+    i.e. the MLS roster at the epoch it was sent in. Every recipient starts
+    out pending; a delivery acknowledgement moves them to delivered and a
+    read receipt to read. States only move forward, so acknowledgements
+    arriving after a read receipt change nothing. Members who joined after
+    the message was sent were never recipients and aren't tracked for it.
+
+    Like the outbox, the receipts are persisted as a single bincode file
+    rewritten atomically on every change.
+*/
+
+use crate::core_store::model::{ChannelId, MessageId, Timestamp, UserId};
+use crate::core_store::store::errors::StoreResult;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// How far a sent message has got with one member, in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum DeliveryState {
+    /// Not acknowledged yet
+    Pending,
+    /// The member's client decrypted it
+    Delivered,
+    /// The member read it
+    Read,
+}
+
+/// A sent message and how far it got with each recipient
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SentReceipts {
+    /// Channel the message was sent to
+    pub channel_id: ChannelId,
+
+    /// When we sent it, by our clock
+    pub sent_at: Timestamp,
+
+    /// MLS epoch it was encrypted in
+    pub epoch: u64,
+
+    /// Recipients and their state
+    pub recipients: BTreeMap<UserId, DeliveryState>,
+}
+
+impl SentReceipts {
+    /// State of the recipient least far along
+    ///
+    /// `Read` if there are no recipients, as nobody is left to wait for.
+    pub fn state(&self) -> DeliveryState {
+        self.recipients.values().copied().min().unwrap_or(DeliveryState::Read)
+    }
+
+    /// Number of recipients at `state` or further
+    pub fn count(&self, state: DeliveryState) -> usize {
+        self.recipients.values().filter(|s| **s >= state).count()
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ReceiptsState {
+    messages: HashMap<MessageId, SentReceipts>,
+}
+
+/// Persistent delivery state of sent messages
+pub struct DeliveryReceipts {
+    path: PathBuf,
+    state: Mutex<ReceiptsState>,
+}
+
+impl DeliveryReceipts {
+    /// Open (or create) the receipts stored at `path`
+    pub fn open(path: PathBuf) -> StoreResult<Self> {
+        let state = if path.exists() {
+            bincode::deserialize(&fs::read(&path)?)?
+        } else {
+            ReceiptsState::default()
+        };
+
+        Ok(Self { path, state: Mutex::new(state) })
+    }
+
+    /// Start tracking a sent message, with every recipient pending
+    pub fn track(
+        &self,
+        message_id: MessageId,
+        channel_id: ChannelId,
+        epoch: u64,
+        sent_at: Timestamp,
+        recipients: impl IntoIterator<Item = UserId>,
+    ) -> StoreResult<()> {
+        let receipts = SentReceipts {
+            channel_id,
+            sent_at,
+            epoch,
+            recipients: recipients.into_iter().map(|user| (user, DeliveryState::Pending)).collect(),
+        };
+        let mut state = self.lock();
+        state.messages.insert(message_id, receipts);
+        self.persist(&state)
+    }
+
+    /// Delivery state of a sent message, if it is tracked
+    pub fn get(&self, message_id: &MessageId) -> Option<SentReceipts> {
+        self.lock().messages.get(message_id).cloned()
+    }
+
+    /// Move a recipient of a message on to `to`
+    ///
+    /// Returns the message's receipts if the recipient moved. Nothing
+    /// happens if the message isn't tracked in `channel_id`, `member` wasn't
+    /// one of its recipients, or they are already at `to` or further.
+    pub fn advance(
+        &self,
+        channel_id: &ChannelId,
+        message_id: &MessageId,
+        member: &UserId,
+        to: DeliveryState,
+    ) -> StoreResult<Option<SentReceipts>> {
+        let mut state = self.lock();
+        let Some(receipts) = state.messages.get_mut(message_id) else {
+            return Ok(None);
+        };
+        if &receipts.channel_id != channel_id || !advance_member(receipts, member, to) {
+            return Ok(None);
+        }
+        let receipts = receipts.clone();
+        self.persist(&state)?;
+        Ok(Some(receipts))
+    }
+
+    /// Move a recipient on to `to` for every message in a channel sent up
+    /// to `position`
+    ///
+    /// Returns the messages whose recipient moved.
+    pub fn advance_up_to(
+        &self,
+        channel_id: &ChannelId,
+        member: &UserId,
+        position: Timestamp,
+        to: DeliveryState,
+    ) -> StoreResult<Vec<(MessageId, SentReceipts)>> {
+        let mut state = self.lock();
+        let mut moved = Vec::new();
+        for (message_id, receipts) in state.messages.iter_mut() {
+            if &receipts.channel_id == channel_id
+                && receipts.sent_at <= position
+                && advance_member(receipts, member, to)
+            {
+                moved.push((message_id.clone(), receipts.clone()));
+            }
+        }
+        if !moved.is_empty() {
+            self.persist(&state)?;
+        }
+        Ok(moved)
+    }
+
+    /// Number of tracked messages
+    pub fn len(&self) -> usize {
+        self.lock().messages.len()
+    }
+
+    /// Whether no messages are tracked
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn persist(&self, state: &ReceiptsState) -> StoreResult<()> {
+        // Atomic write: temp file then rename
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, bincode::serialize(state)?)?;
+        fs::rename(tmp, &self.path)?;
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ReceiptsState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Move `member` on to `to`, returning whether they moved
+fn advance_member(receipts: &mut SentReceipts, member: &UserId, to: DeliveryState) -> bool {
+    match receipts.recipients.get_mut(member) {
+        Some(state) if *state < to => {
+            *state = to;
+            true
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_receipts_only_move_forward_and_survive_reopen() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("receipts.bin");
+        let general = ChannelId("general".to_string());
+        let (bob, carol, dave) = (
+            UserId("bob".to_string()),
+            UserId("carol".to_string()),
+            UserId("dave".to_string()),
+        );
+        let message = MessageId("m1".to_string());
+
+        let receipts = DeliveryReceipts::open(path.clone()).unwrap();
+        let sent_at = Timestamp::now();
+        receipts
+            .track(message.clone(), general.clone(), 3, sent_at, [bob.clone(), carol.clone()])
+            .unwrap();
+        assert_eq!(receipts.get(&message).unwrap().state(), DeliveryState::Pending);
+
+        let read = receipts.advance(&general, &message, &bob, DeliveryState::Read).unwrap();
+        assert_eq!(read.unwrap().count(DeliveryState::Read), 1);
+        // A late acknowledgement doesn't undo the read
+        let ack = receipts.advance(&general, &message, &bob, DeliveryState::Delivered).unwrap();
+        assert!(ack.is_none());
+        // Dave joined later, so wasn't a recipient
+        let ack = receipts.advance(&general, &message, &dave, DeliveryState::Delivered).unwrap();
+        assert!(ack.is_none());
+        let other = ChannelId("other".to_string());
+        assert!(receipts
+            .advance(&other, &message, &carol, DeliveryState::Read)
+            .unwrap()
+            .is_none());
+        drop(receipts);
+
+        let receipts = DeliveryReceipts::open(path).unwrap();
+        let tracked = receipts.get(&message).unwrap();
+        assert_eq!(tracked.recipients.len(), 2);
+        assert_eq!(tracked.state(), DeliveryState::Pending);
+        assert_eq!(tracked.count(DeliveryState::Delivered), 1);
+
+        let moved = receipts
+            .advance_up_to(&general, &carol, sent_at, DeliveryState::Delivered)
+            .unwrap();
+        assert_eq!(moved.len(), 1);
+        assert_eq!(receipts.get(&message).unwrap().state(), DeliveryState::Delivered);
+    }
+}
@@ -8,16 +8,26 @@
     - Atomic snapshot creation (write to temp, then rename)
     - Versioned snapshots with metadata
     - Automatic cleanup of old snapshots
+    - Encrypted with the store key when the store is encrypted
+
+    Each snapshot records the commit log sequence number it covers
+    (`log_seq`), so recovery replays only the entries after it and the log
+    can be truncated up to it. Loading prefers the newest snapshot that
+    decodes; an unreadable one falls back to the one before.
 */
 
-use crate::core_store::model::{Channel, ChannelId, Space, SpaceId};
-use crate::core_store::store::errors::StoreResult;
+use crate::core_store::model::{Channel, ChannelId, Message, Space, SpaceId};
+use crate::core_store::store::commit_log::sync_dir;
+use crate::core_store::store::encryption::EncryptionManager;
+use crate::core_store::store::errors::{StoreError, StoreResult};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs::{create_dir_all, File};
 use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use tracing::warn;
 
 /// Snapshot metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,27 +51,107 @@ pub struct Snapshot {
     pub metadata: SnapshotMetadata,
     pub spaces: HashMap<SpaceId, Space>,
     pub channels: HashMap<ChannelId, Channel>,
+
+    /// Messages of each channel, in arrival order
+    pub messages: HashMap<ChannelId, Vec<Message>>,
+
+    /// Commit log sequence number the snapshot covers: every entry with a
+    /// lower `seq` is reflected in it
+    pub log_seq: u64,
+}
+
+/// Snapshot written before messages and the log position were recorded
+///
+/// Decoded as a fallback; its `log_seq` is 0, so the whole log is replayed
+/// on top of it as before.
+#[derive(Deserialize)]
+struct LegacySnapshot {
+    metadata: SnapshotMetadata,
+    spaces: HashMap<SpaceId, Space>,
+    channels: HashMap<ChannelId, Channel>,
+}
+
+/// A snapshot written to a temporary file but not yet in place
+#[derive(Debug)]
+pub struct PendingSnapshot {
+    version: u32,
+    temp_path: PathBuf,
+}
+
+/// Version of a snapshot file, from its `snapshot_{version}.bin` name
+fn snapshot_version(path: &Path) -> Option<u32> {
+    if path.extension()? != "bin" {
+        return None;
+    }
+    path.file_stem()?.to_str()?.strip_prefix("snapshot_")?.parse().ok()
 }
 
 /// Manages snapshots
 pub struct SnapshotManager {
     snapshots_dir: PathBuf,
     current_version: AtomicU32,
+    encryption: Option<Arc<EncryptionManager>>,
 }
 
 impl SnapshotManager {
     pub fn new(snapshots_dir: PathBuf) -> StoreResult<Self> {
         create_dir_all(&snapshots_dir)?;
 
-        Ok(SnapshotManager { snapshots_dir, current_version: AtomicU32::new(0) })
+        let manager =
+            SnapshotManager { snapshots_dir, current_version: AtomicU32::new(0), encryption: None };
+
+        // Continue after the existing snapshots, and drop any a crash left
+        // half written
+        let latest = manager.versions()?.last().copied().unwrap_or(0);
+        manager.current_version.store(latest, Ordering::SeqCst);
+        for entry in std::fs::read_dir(&manager.snapshots_dir)?.flatten() {
+            if entry.path().extension().is_some_and(|ext| ext == "tmp") {
+                std::fs::remove_file(entry.path())?;
+            }
+        }
+
+        Ok(manager)
     }
 
-    /// Create a new snapshot
+    /// Encrypt snapshots written and decrypt snapshots read
+    pub fn with_encryption(mut self, encryption: Option<Arc<EncryptionManager>>) -> Self {
+        self.encryption = encryption;
+        self
+    }
+
+    /// Create a new snapshot of spaces and channels
     pub fn create_snapshot(
         &self,
         spaces: HashMap<SpaceId, Space>,
         channels: HashMap<ChannelId, Channel>,
     ) -> StoreResult<()> {
+        self.create_snapshot_at(spaces, channels, HashMap::new(), 0).map(|_| ())
+    }
+
+    /// Create a new snapshot covering the commit log up to `log_seq`
+    ///
+    /// Returns the snapshot's version.
+    pub fn create_snapshot_at(
+        &self,
+        spaces: HashMap<SpaceId, Space>,
+        channels: HashMap<ChannelId, Channel>,
+        messages: HashMap<ChannelId, Vec<Message>>,
+        log_seq: u64,
+    ) -> StoreResult<u32> {
+        let pending = self.write_snapshot(spaces, channels, messages, log_seq)?;
+        self.publish(pending)
+    }
+
+    /// Write a snapshot to a temporary file and sync it
+    ///
+    /// It is not loaded until `publish` moves it in place.
+    pub fn write_snapshot(
+        &self,
+        spaces: HashMap<SpaceId, Space>,
+        channels: HashMap<ChannelId, Channel>,
+        messages: HashMap<ChannelId, Vec<Message>>,
+        log_seq: u64,
+    ) -> StoreResult<PendingSnapshot> {
         let version = self.current_version.fetch_add(1, Ordering::SeqCst) + 1;
         let metadata = SnapshotMetadata {
             version,
@@ -73,61 +163,103 @@ impl SnapshotManager {
             channels_count: channels.len(),
         };
 
-        let snapshot = Snapshot { metadata, spaces, channels };
+        let snapshot = Snapshot { metadata, spaces, channels, messages, log_seq };
 
         // Serialize snapshot
         let data = bincode::serialize(&snapshot)?;
+        let data = match &self.encryption {
+            Some(enc) => enc.encrypt(&data)?,
+            None => data,
+        };
 
         // Write to temporary file first
-        let temp_path =
-            self.snapshots_dir.join(format!("snapshot_{}.tmp", snapshot.metadata.version));
+        let temp_path = self.snapshots_dir.join(format!("snapshot_{}.tmp", version));
         let mut file = File::create(&temp_path)?;
         file.write_all(&data)?;
         file.sync_all()?;
-        drop(file);
 
-        // Atomically rename to final name
-        let final_path =
-            self.snapshots_dir.join(format!("snapshot_{}.bin", snapshot.metadata.version));
-        std::fs::rename(temp_path, final_path)?;
+        Ok(PendingSnapshot { version, temp_path })
+    }
+
+    /// Atomically move a written snapshot in place
+    ///
+    /// Returns its version.
+    pub fn publish(&self, pending: PendingSnapshot) -> StoreResult<u32> {
+        let final_path = self.snapshots_dir.join(format!("snapshot_{}.bin", pending.version));
+        std::fs::rename(&pending.temp_path, final_path)?;
+        sync_dir(&self.snapshots_dir)?;
 
-        Ok(())
+        Ok(pending.version)
     }
 
-    /// Load the latest snapshot
-    pub fn load_latest(
-        &self,
-    ) -> StoreResult<(HashMap<SpaceId, Space>, HashMap<ChannelId, Channel>)> {
-        // Find latest snapshot file
-        let mut snapshots: Vec<_> = std::fs::read_dir(&self.snapshots_dir)?
+    /// Versions of the snapshots on disk, oldest first
+    fn versions(&self) -> StoreResult<Vec<u32>> {
+        let mut versions: Vec<u32> = std::fs::read_dir(&self.snapshots_dir)?
             .filter_map(|entry| entry.ok())
-            .filter(|entry| {
-                entry
-                    .path()
-                    .extension()
-                    .and_then(|ext| ext.to_str())
-                    .map(|ext| ext == "bin")
-                    .unwrap_or(false)
-            })
+            .filter_map(|entry| snapshot_version(&entry.path()))
             .collect();
+        versions.sort_unstable();
+        Ok(versions)
+    }
 
-        if snapshots.is_empty() {
-            return Ok((HashMap::new(), HashMap::new()));
+    /// Read and decode one snapshot
+    pub fn load_version(&self, version: u32) -> StoreResult<Snapshot> {
+        let path = self.snapshots_dir.join(format!("snapshot_{}.bin", version));
+        let mut file = File::open(path)?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        if let Some(enc) = &self.encryption {
+            data = enc.decrypt(&data)?;
         }
 
-        // Sort by filename (version)
-        snapshots.sort_by_key(|entry| entry.file_name());
-
-        let latest = snapshots.last().unwrap();
+        if let Ok(snapshot) = bincode::deserialize::<Snapshot>(&data) {
+            return Ok(snapshot);
+        }
+        let legacy: LegacySnapshot = bincode::deserialize(&data)?;
+        Ok(Snapshot {
+            metadata: legacy.metadata,
+            spaces: legacy.spaces,
+            channels: legacy.channels,
+            messages: HashMap::new(),
+            log_seq: 0,
+        })
+    }
 
-        // Read and deserialize
-        let mut file = File::open(latest.path())?;
-        let mut data = Vec::new();
-        file.read_to_end(&mut data)?;
+    /// Load the newest snapshot that can be read
+    ///
+    /// Unreadable snapshots are skipped in favour of older ones. Returns
+    /// `None` without snapshots, and the newest snapshot's error if none
+    /// can be read.
+    pub fn load_newest(&self) -> StoreResult<Option<Snapshot>> {
+        let mut newest_error: Option<StoreError> = None;
+        for version in self.versions()?.into_iter().rev() {
+            match self.load_version(version) {
+                Ok(snapshot) => {
+                    if let Some(e) = newest_error {
+                        warn!("Skipped unreadable snapshots, loading version {}: {}", version, e);
+                    }
+                    return Ok(Some(snapshot));
+                }
+                Err(e) => {
+                    newest_error.get_or_insert(e);
+                }
+            }
+        }
 
-        let snapshot: Snapshot = bincode::deserialize(&data)?;
+        match newest_error {
+            Some(e) => Err(e),
+            None => Ok(None),
+        }
+    }
 
-        Ok((snapshot.spaces, snapshot.channels))
+    /// Load the latest snapshot
+    pub fn load_latest(
+        &self,
+    ) -> StoreResult<(HashMap<SpaceId, Space>, HashMap<ChannelId, Channel>)> {
+        Ok(self
+            .load_newest()?
+            .map(|snapshot| (snapshot.spaces, snapshot.channels))
+            .unwrap_or_default())
     }
 
     /// Load a specific space from snapshot
@@ -144,27 +276,14 @@ impl SnapshotManager {
 
     /// Clean up old snapshots, keeping only the N most recent
     pub fn cleanup_old_snapshots(&self, keep_count: usize) -> StoreResult<()> {
-        let mut snapshots: Vec<_> = std::fs::read_dir(&self.snapshots_dir)?
-            .filter_map(|entry| entry.ok())
-            .filter(|entry| {
-                entry
-                    .path()
-                    .extension()
-                    .and_then(|ext| ext.to_str())
-                    .map(|ext| ext == "bin")
-                    .unwrap_or(false)
-            })
-            .collect();
-
-        if snapshots.len() <= keep_count {
+        let versions = self.versions()?;
+        if versions.len() <= keep_count {
             return Ok(());
         }
 
-        snapshots.sort_by_key(|entry| entry.file_name());
-
         // Remove oldest snapshots
-        for entry in snapshots.iter().take(snapshots.len() - keep_count) {
-            std::fs::remove_file(entry.path())?;
+        for version in &versions[..versions.len() - keep_count] {
+            std::fs::remove_file(self.snapshots_dir.join(format!("snapshot_{}.bin", version)))?;
         }
 
         Ok(())
@@ -244,6 +363,40 @@ mod tests {
         assert!(loaded_channels.contains_key(&channel_id));
     }
 
+    #[test]
+    fn test_encrypted_snapshot() {
+        let dir = tempdir().unwrap();
+        let key = Arc::new(EncryptionManager::from_key(&[7; 32]));
+        let manager = SnapshotManager::new(dir.path().to_path_buf())
+            .unwrap()
+            .with_encryption(Some(key));
+
+        let mut spaces = HashMap::new();
+        let space = Space::new(
+            SpaceId::generate(),
+            "Secret Space".to_string(),
+            UserId::generate(),
+            Timestamp::now(),
+            "node1".to_string(),
+        );
+        spaces.insert(space.id.clone(), space);
+        manager.create_snapshot(spaces, HashMap::new()).unwrap();
+
+        let (loaded, _) = manager.load_latest().unwrap();
+        assert_eq!(loaded.len(), 1);
+
+        // Nothing readable on disk, and another key is refused
+        let file = std::fs::read(dir.path().join("snapshot_1.bin")).unwrap();
+        assert!(!file.windows(12).any(|w| w == b"Secret Space"));
+        let other = SnapshotManager::new(dir.path().to_path_buf())
+            .unwrap()
+            .with_encryption(Some(Arc::new(EncryptionManager::from_key(&[8; 32]))));
+        assert!(matches!(
+            other.load_latest(),
+            Err(crate::core_store::store::errors::StoreError::DecryptionFailed(_))
+        ));
+    }
+
     #[test]
     fn test_cleanup_old_snapshots() {
         let dir = tempdir().unwrap();
@@ -262,4 +415,30 @@ mod tests {
 
         assert_eq!(count, 2);
     }
+
+    #[test]
+    fn test_newest_readable_snapshot_wins() {
+        let dir = tempdir().unwrap();
+        let manager = SnapshotManager::new(dir.path().to_path_buf()).unwrap();
+        for log_seq in 1..=11 {
+            manager
+                .create_snapshot_at(HashMap::new(), HashMap::new(), HashMap::new(), log_seq)
+                .unwrap();
+        }
+
+        // Versions compare as numbers: 11 is newer than 9
+        assert_eq!(manager.load_newest().unwrap().unwrap().log_seq, 11);
+
+        std::fs::write(dir.path().join("snapshot_11.bin"), b"torn").unwrap();
+        assert_eq!(manager.load_newest().unwrap().unwrap().log_seq, 10);
+
+        // Reopened, numbering continues and half-written files are dropped
+        std::fs::write(dir.path().join("snapshot_12.tmp"), b"half").unwrap();
+        let manager = SnapshotManager::new(dir.path().to_path_buf()).unwrap();
+        assert!(!dir.path().join("snapshot_12.tmp").exists());
+        let version = manager
+            .create_snapshot_at(HashMap::new(), HashMap::new(), HashMap::new(), 12)
+            .unwrap();
+        assert_eq!(version, 12);
+    }
 }
@@ -6,11 +6,11 @@
     - Causality (vector clock ordering)
     - Signatures (if present)
     - Schema conformance
-    - Authorization (role-based)
+    - Authorization (capability-based)
 */
 
 use crate::core_store::crdt::{OperationMetadata, VectorClock};
-use crate::core_store::model::{PermissionLevel, UserId};
+use crate::core_store::model::{Permission, PermissionLevel, PermissionSet, UserId};
 use crate::core_store::store::errors::{StoreError, StoreResult};
 use std::collections::HashMap;
 
@@ -43,8 +43,8 @@ pub struct OperationValidator {
     /// Current vector clock state
     current_clock: VectorClock,
 
-    /// User permissions cache
-    permissions: HashMap<UserId, PermissionLevel>,
+    /// User capability cache
+    permissions: HashMap<UserId, PermissionSet>,
 }
 
 impl OperationValidator {
@@ -75,54 +75,51 @@ impl OperationValidator {
     }
 
     /// Validate user permissions
+    ///
+    /// Legacy levels are checked through the capabilities they imply.
     pub fn validate_permission(
         &self,
         user_id: &UserId,
         required_level: &PermissionLevel,
+    ) -> StoreResult<()> {
+        self.validate_permissions(user_id, PermissionSet::from(required_level))
+    }
+
+    /// Validate that a user holds a single capability
+    pub fn validate_capability(&self, user_id: &UserId, permission: Permission) -> StoreResult<()> {
+        self.validate_permissions(user_id, permission.into())
+    }
+
+    /// Validate that a user holds every capability in `required`
+    pub fn validate_permissions(
+        &self,
+        user_id: &UserId,
+        required: PermissionSet,
     ) -> StoreResult<()> {
         if !self.rules.check_permissions {
             return Ok(());
         }
 
-        let user_level = self
+        let granted = self
             .permissions
             .get(user_id)
             .ok_or_else(|| StoreError::ValidationError("User not found".to_string()))?;
 
-        if !Self::has_permission(user_level, required_level) {
-            return Err(StoreError::ValidationError(format!("Insufficient permissions")));
+        if let Some(missing) = required.iter().find(|p| !granted.contains(*p)) {
+            return Err(StoreError::ValidationError(format!(
+                "Insufficient permissions: missing {}",
+                missing
+            )));
         }
 
         Ok(())
     }
 
-    /// Check if a permission level is sufficient
-    fn has_permission(user_level: &PermissionLevel, required_level: &PermissionLevel) -> bool {
-        // Check each required permission
-        if required_level.read && !user_level.read {
-            return false;
-        }
-        if required_level.write && !user_level.write {
-            return false;
-        }
-        if required_level.admin && !user_level.admin {
-            return false;
-        }
-        if required_level.ban_members && !user_level.ban_members {
-            return false;
-        }
-        if required_level.manage_roles && !user_level.manage_roles {
-            return false;
-        }
-        if required_level.manage_channels && !user_level.manage_channels {
-            return false;
-        }
-        true
-    }
-
     /// Add user permission
-    pub fn add_user_permission(&mut self, user_id: UserId, level: PermissionLevel) {
-        self.permissions.insert(user_id, level);
+    ///
+    /// Accepts a legacy `PermissionLevel` or an explicit `PermissionSet`.
+    pub fn add_user_permission(&mut self, user_id: UserId, permissions: impl Into<PermissionSet>) {
+        self.permissions.insert(user_id, permissions.into());
     }
 
     /// Update vector clock after accepting an operation
@@ -198,4 +195,19 @@ mod tests {
         assert!(validator.validate_permission(&user_id, &PermissionLevel::member()).is_ok());
         assert!(validator.validate_permission(&user_id, &PermissionLevel::admin()).is_ok());
     }
+
+    #[test]
+    fn test_custom_capability_set() {
+        let mut validator = OperationValidator::new(ValidationRules::default());
+
+        // Moderator that may invite but not kick
+        let user_id = UserId::generate();
+        let set: PermissionSet = [Permission::Post, Permission::Invite].into_iter().collect();
+        validator.add_user_permission(user_id.clone(), set);
+
+        assert!(validator.validate_capability(&user_id, Permission::Invite).is_ok());
+        assert!(validator.validate_capability(&user_id, Permission::Kick).is_err());
+        assert!(validator.validate_permission(&user_id, &PermissionLevel::member()).is_ok());
+        assert!(validator.validate_permission(&user_id, &PermissionLevel::moderator()).is_err());
+    }
 }
@@ -0,0 +1,437 @@
+/*
+    wal.rs - Write-ahead log with group commit
+
+    In WAL mode the store doesn't write each change to the commit log as it
+    happens. The change is queued here and the writer blocks in
+    `wait_durable`; a committer thread writes everything queued in one go
+    and makes it durable with a single fsync. While one batch is being
+    synced, the writers arriving meanwhile queue up behind it and go out
+    together in the next, so one fsync covers all of them. Records nobody
+    waits for are written at most `max_latency` after the first of them
+    was queued, or once `max_batch_bytes` are queued.
+
+    Record format: [len:4][crc:4][seq:8][data:len], the CRC covering seq
+    and data. Each record carries the commit log sequence number of its
+    entry; the store later writes the entries to the commit log in bulk and
+    empties the WAL (`reset`).
+
+    On open, records are read up to the first one that is cut short or
+    fails its CRC - the tail of a write torn by a crash - and the file is
+    truncated there.
+*/
+
+use crate::core_store::store::errors::{StoreError, StoreResult};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tracing::{error, warn};
+
+/// Bytes before a record's data: length, CRC and sequence number
+const HEADER_LEN: usize = 4 + 4 + 8;
+
+/// Group commit settings
+#[derive(Debug, Clone)]
+pub struct WalConfig {
+    /// Longest a queued record waits for its fsync
+    pub max_latency: Duration,
+
+    /// Queued bytes that trigger a write without waiting out the window
+    pub max_batch_bytes: usize,
+}
+
+impl Default for WalConfig {
+    fn default() -> Self {
+        WalConfig { max_latency: Duration::from_millis(2), max_batch_bytes: 1024 * 1024 }
+    }
+}
+
+/// A record read back from the WAL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalRecord {
+    /// Commit log sequence number of the entry
+    pub seq: u64,
+
+    /// Entry data, as it goes into the commit log
+    pub data: Vec<u8>,
+}
+
+/// What the committer thread and writers share
+#[derive(Default)]
+struct WalState {
+    /// Framed records waiting for the next write
+    queue: Vec<u8>,
+
+    /// When the oldest queued record was queued
+    queued_since: Option<Instant>,
+
+    /// Sequence number after the last queued record
+    queued_through: u64,
+
+    /// Records with a lower sequence number are being written, or durable
+    writing_through: u64,
+
+    /// Records with a lower sequence number are durable
+    durable_through: u64,
+
+    /// Someone is waiting; write without waiting out the window
+    flush_now: bool,
+
+    /// Write error, after which nothing more is accepted
+    failed: Option<String>,
+
+    /// Batches written (one fsync each)
+    batches: u64,
+
+    /// Drop queued records instead of writing them on shutdown
+    abandoned: bool,
+
+    shutdown: bool,
+}
+
+struct Shared {
+    config: WalConfig,
+    state: Mutex<WalState>,
+    changed: Condvar,
+
+    /// Held while a batch is written, so `reset` never cuts one in half
+    file: Mutex<File>,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, WalState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Write-ahead log with a group commit thread
+pub struct WriteAheadLog {
+    shared: Arc<Shared>,
+    committer: Option<JoinHandle<()>>,
+}
+
+fn frame(seq: u64, data: &[u8], out: &mut Vec<u8>) {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&seq.to_le_bytes());
+    hasher.update(data);
+
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(&hasher.finalize().to_le_bytes());
+    out.extend_from_slice(&seq.to_le_bytes());
+    out.extend_from_slice(data);
+}
+
+/// Records in `bytes` up to the first damaged one, and the length they span
+fn parse(bytes: &[u8]) -> (Vec<WalRecord>, usize) {
+    let mut records = Vec::new();
+    let mut offset = 0;
+    while let Some(header) = bytes.get(offset..offset + HEADER_LEN) {
+        let len = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+        let crc = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        let Some(data) = bytes.get(offset + HEADER_LEN..offset + HEADER_LEN + len) else {
+            break;
+        };
+        if crc32fast::hash(&bytes[offset + 8..offset + HEADER_LEN + len]) != crc {
+            break;
+        }
+
+        let seq = u64::from_le_bytes(header[8..16].try_into().unwrap());
+        records.push(WalRecord { seq, data: data.to_vec() });
+        offset += HEADER_LEN + len;
+    }
+    (records, offset)
+}
+
+impl WriteAheadLog {
+    /// Open or create a WAL and start its committer thread
+    ///
+    /// Returns the records it already held, oldest first; a torn tail is
+    /// cut off.
+    pub fn open(path: PathBuf, config: WalConfig) -> StoreResult<(Self, Vec<WalRecord>)> {
+        let mut file = OpenOptions::new().create(true).read(true).append(true).open(&path)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+
+        let (records, valid) = parse(&bytes);
+        if valid < bytes.len() {
+            warn!(
+                "Discarding {} bytes of torn records at the end of {:?}",
+                bytes.len() - valid,
+                path
+            );
+            file.set_len(valid as u64)?;
+            file.sync_all()?;
+        }
+
+        let through = records.last().map_or(0, |record| record.seq + 1);
+        let state = WalState {
+            queued_through: through,
+            writing_through: through,
+            durable_through: through,
+            ..Default::default()
+        };
+        let shared = Arc::new(Shared {
+            config,
+            state: Mutex::new(state),
+            changed: Condvar::new(),
+            file: Mutex::new(file),
+        });
+
+        let committer = {
+            let shared = shared.clone();
+            std::thread::Builder::new()
+                .name("store-wal".to_string())
+                .spawn(move || run_committer(&shared))?
+        };
+
+        Ok((WriteAheadLog { shared, committer: Some(committer) }, records))
+    }
+
+    /// Queue the record of commit log entry `seq`
+    ///
+    /// Returns once it is queued; it is durable within `max_latency`, or
+    /// right away with `wait_durable`.
+    pub fn append(&self, seq: u64, data: &[u8]) -> StoreResult<()> {
+        let mut state = self.shared.lock();
+        if let Some(e) = &state.failed {
+            return Err(StoreError::Storage(format!("WAL write failed earlier: {}", e)));
+        }
+
+        frame(seq, data, &mut state.queue);
+        state.queued_through = seq + 1;
+
+        // The committer only needs waking to start a window or cut it short
+        let starts_window = state.queued_since.is_none();
+        state.queued_since.get_or_insert_with(Instant::now);
+        if starts_window || state.queue.len() >= self.shared.config.max_batch_bytes {
+            self.shared.changed.notify_all();
+        }
+        Ok(())
+    }
+
+    /// Wait until every record with a sequence number below `seq` is durable
+    ///
+    /// Writers waiting together share one fsync.
+    pub fn wait_durable(&self, seq: u64) -> StoreResult<()> {
+        let mut state = self.shared.lock();
+        loop {
+            if state.durable_through >= seq.min(state.queued_through) {
+                return Ok(());
+            }
+            if let Some(e) = &state.failed {
+                return Err(StoreError::Storage(format!("WAL write failed: {}", e)));
+            }
+            // Records already being written need no flush of their own
+            if state.writing_through < seq.min(state.queued_through) {
+                state.flush_now = true;
+                self.shared.changed.notify_all();
+            }
+            state = self.shared.changed.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Number of fsyncs so far
+    pub fn fsyncs(&self) -> u64 {
+        self.shared.lock().batches
+    }
+
+    /// Wait until everything queued is durable
+    pub fn sync(&self) -> StoreResult<()> {
+        self.wait_durable(u64::MAX)
+    }
+
+    /// Empty the WAL once the entries below `through` are durable elsewhere
+    ///
+    /// Every record queued so far must have a lower sequence number.
+    pub fn reset(&self, through: u64) -> StoreResult<()> {
+        let mut state = self.shared.lock();
+        state.queue.clear();
+        state.queued_since = None;
+        state.queued_through = state.queued_through.max(through);
+        state.writing_through = state.queued_through;
+        state.durable_through = state.queued_through;
+
+        // Waits for a batch being written; records it holds are stale now,
+        // but would only be skipped on recovery anyway
+        let file = self.shared.file.lock().unwrap_or_else(|e| e.into_inner());
+        file.set_len(0)?;
+        file.sync_all()?;
+        drop(file);
+
+        self.shared.changed.notify_all();
+        Ok(())
+    }
+
+    /// Stop without writing what is queued, as if the process died
+    #[cfg(test)]
+    pub(crate) fn abandon(&self) {
+        self.shared.lock().abandoned = true;
+    }
+}
+
+impl Drop for WriteAheadLog {
+    fn drop(&mut self) {
+        self.shared.lock().shutdown = true;
+        self.shared.changed.notify_all();
+        if let Some(committer) = self.committer.take() {
+            let _ = committer.join();
+        }
+    }
+}
+
+/// Write queued records in batches until shut down
+fn run_committer(shared: &Shared) {
+    loop {
+        let mut state = shared.lock();
+        while state.queue.is_empty() && !state.shutdown {
+            state = shared.changed.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+        if state.shutdown && (state.queue.is_empty() || state.abandoned) {
+            return;
+        }
+
+        // Let the window fill up with records from other writers
+        while !state.shutdown
+            && !state.flush_now
+            && state.queue.len() < shared.config.max_batch_bytes
+        {
+            let waited = state.queued_since.map_or(Duration::ZERO, |since| since.elapsed());
+            let Some(left) = shared.config.max_latency.checked_sub(waited) else {
+                break;
+            };
+            state = shared.changed.wait_timeout(state, left).unwrap_or_else(|e| e.into_inner()).0;
+        }
+        if state.abandoned {
+            return;
+        }
+
+        let batch = std::mem::take(&mut state.queue);
+        let through = state.queued_through;
+        state.writing_through = through;
+        state.queued_since = None;
+        state.flush_now = false;
+        let mut file = shared.file.lock().unwrap_or_else(|e| e.into_inner());
+        drop(state);
+
+        let written = file.write_all(&batch).and_then(|_| file.sync_data());
+        drop(file);
+
+        let mut state = shared.lock();
+        match written {
+            Ok(()) => {
+                state.durable_through = state.durable_through.max(through);
+                state.batches += 1;
+            }
+            Err(e) => {
+                error!("WAL write failed: {}", e);
+                state.failed = Some(e.to_string());
+            }
+        }
+        shared.changed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_records_survive_reopen() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("wal");
+
+        {
+            let (wal, records) = WriteAheadLog::open(path.clone(), WalConfig::default()).unwrap();
+            assert!(records.is_empty());
+            for seq in 0..100 {
+                wal.append(seq, format!("entry {}", seq).as_bytes()).unwrap();
+            }
+            wal.sync().unwrap();
+        }
+
+        let (_wal, records) = WriteAheadLog::open(path, WalConfig::default()).unwrap();
+        assert_eq!(records.len(), 100);
+        assert_eq!(records[42], WalRecord { seq: 42, data: b"entry 42".to_vec() });
+    }
+
+    #[test]
+    fn test_torn_tail_is_discarded() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("wal");
+
+        {
+            let (wal, _) = WriteAheadLog::open(path.clone(), WalConfig::default()).unwrap();
+            wal.append(0, b"first").unwrap();
+            wal.append(1, b"second").unwrap();
+        }
+        let intact = std::fs::metadata(&path).unwrap().len();
+
+        // A record cut short, then one whose bytes didn't all reach the disk
+        let mut torn = Vec::new();
+        frame(2, b"third", &mut torn);
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes.extend_from_slice(&torn[..torn.len() - 2]);
+        std::fs::write(&path, &bytes).unwrap();
+        let (_wal, records) = WriteAheadLog::open(path.clone(), WalConfig::default()).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), intact);
+        drop(_wal);
+
+        let last = torn.len() - 1;
+        torn[last] ^= 0xff;
+        bytes.truncate(intact as usize);
+        bytes.extend_from_slice(&torn);
+        std::fs::write(&path, &bytes).unwrap();
+        let (_wal, records) = WriteAheadLog::open(path.clone(), WalConfig::default()).unwrap();
+        assert_eq!(records.iter().map(|r| r.seq).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), intact);
+    }
+
+    #[test]
+    fn test_concurrent_writers_share_fsyncs() {
+        const WRITERS: usize = 8;
+        const ROUNDS: u64 = 25;
+
+        // A window long enough that only waiting writers trigger a write
+        let dir = tempdir().unwrap();
+        let config = WalConfig { max_latency: Duration::from_secs(60), ..Default::default() };
+        let (wal, _) = WriteAheadLog::open(dir.path().join("wal"), config).unwrap();
+        let wal = Arc::new(wal);
+        let next = Arc::new(Mutex::new(0u64));
+        let appended = Arc::new(std::sync::Barrier::new(WRITERS));
+
+        let writers: Vec<_> = (0..WRITERS)
+            .map(|_| {
+                let (wal, next, appended) = (wal.clone(), next.clone(), appended.clone());
+                std::thread::spawn(move || {
+                    for _ in 0..ROUNDS {
+                        let seq = {
+                            let mut next = next.lock().unwrap();
+                            wal.append(*next, b"record").unwrap();
+                            *next += 1;
+                            *next
+                        };
+                        // Everyone has queued a record before anyone waits
+                        appended.wait();
+                        wal.wait_durable(seq).unwrap();
+                        appended.wait();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        // The first writer to wait makes the whole round durable
+        assert_eq!(wal.fsyncs(), ROUNDS);
+        drop(wal);
+        let bytes = std::fs::read(dir.path().join("wal")).unwrap();
+        let (records, _) = parse(&bytes);
+        assert_eq!(records.len(), WRITERS * ROUNDS as usize);
+        assert!(records.windows(2).all(|pair| pair[1].seq == pair[0].seq + 1));
+    }
+}
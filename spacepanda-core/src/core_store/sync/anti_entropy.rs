@@ -13,12 +13,18 @@
     - Direct messages are lost
     - Nodes are temporarily offline
     - Network partitions heal
+
+    Each peer is synced on its own schedule: every `sync_interval` plus a
+    random part of `sync_jitter`, so peers that connected together don't
+    all sync at the same moment. The sessions themselves run in session.rs.
 */
 
 use crate::core_store::crdt::VectorClock;
 use crate::core_store::store::errors::StoreResult;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 /// Configuration for anti-entropy sync
 #[derive(Debug, Clone)]
@@ -37,6 +43,12 @@ pub struct AntiEntropyConfig {
 
     /// Batch size for delta requests
     pub batch_size: usize,
+
+    /// Most random extra delay added to each peer's `sync_interval`
+    pub sync_jitter: Duration,
+
+    /// Most deltas a session sends or requests per second
+    pub max_deltas_per_sec: u32,
 }
 
 impl Default for AntiEntropyConfig {
@@ -47,6 +59,8 @@ impl Default for AntiEntropyConfig {
             max_delta_age: 3600, // 1 hour
             use_dht_fallback: true,
             batch_size: 10,
+            sync_jitter: Duration::from_secs(6),
+            max_deltas_per_sec: 20,
         }
     }
 }
@@ -65,10 +79,13 @@ pub struct PeerSyncState {
 
     /// Number of consecutive failures
     pub failure_count: u32,
+
+    /// When the peer is next due a sync session
+    pub next_sync: Instant,
 }
 
 /// Sync request to send to a peer
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SyncRequest {
     /// Target channel or space
     pub target_id: String,
@@ -127,6 +144,11 @@ impl AntiEntropyManager {
         }
     }
 
+    /// Configuration the manager runs with
+    pub fn config(&self) -> &AntiEntropyConfig {
+        &self.config
+    }
+
     /// Add a target to track
     pub fn add_target(&mut self, target_id: String, clock: VectorClock) {
         self.targets.insert(target_id.clone());
@@ -153,9 +175,11 @@ impl AntiEntropyManager {
             .expect("System clock is before UNIX epoch")
             .as_secs();
 
+        // A new peer is synced soon, at a random point within the jitter
+        let next_sync = Instant::now() + self.jitter();
         self.peers.insert(
             peer_id.clone(),
-            PeerSyncState { peer_id, vector_clock, last_sync: now, failure_count: 0 },
+            PeerSyncState { peer_id, vector_clock, last_sync: now, failure_count: 0, next_sync },
         );
     }
 
@@ -197,6 +221,38 @@ impl AntiEntropyManager {
         peers
     }
 
+    /// Peers due a sync session at `now`, longest overdue first
+    pub fn due_peers(&self, now: Instant) -> Vec<String> {
+        let mut due: Vec<_> = self.peers.values().filter(|p| p.next_sync <= now).collect();
+        due.sort_by_key(|p| p.next_sync);
+        due.truncate(self.config.max_peers_per_round);
+        due.into_iter().map(|p| p.peer_id.clone()).collect()
+    }
+
+    /// Targets being tracked
+    pub fn targets(&self) -> Vec<String> {
+        self.targets.iter().cloned().collect()
+    }
+
+    /// Record a completed sync session with a peer and schedule the next
+    pub fn record_success(&mut self, peer_id: &str) {
+        let next_sync = Instant::now() + self.config.sync_interval + self.jitter();
+        if let Some(peer) = self.peers.get_mut(peer_id) {
+            peer.last_sync = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("System clock is before UNIX epoch")
+                .as_secs();
+            peer.failure_count = 0;
+            peer.next_sync = next_sync;
+        }
+    }
+
+    /// Random delay of up to `sync_jitter`
+    fn jitter(&self) -> Duration {
+        let max = self.config.sync_jitter.as_millis() as u64;
+        Duration::from_millis(rand::rng().random_range(0..=max))
+    }
+
     /// Detect missing operations by comparing clocks
     pub fn detect_missing(&self, our_clock: &VectorClock, peer_clock: &VectorClock) -> bool {
         // If peer has operations we don't have, we're missing data
@@ -254,9 +310,13 @@ impl AntiEntropyManager {
     }
 
     /// Record a peer sync failure
+    ///
+    /// The peer is tried again after the usual interval.
     pub fn record_failure(&mut self, peer_id: &str) {
+        let next_sync = Instant::now() + self.config.sync_interval + self.jitter();
         if let Some(peer) = self.peers.get_mut(peer_id) {
             peer.failure_count += 1;
+            peer.next_sync = next_sync;
 
             // Remove peer if too many failures
             if peer.failure_count > 5 {
@@ -351,6 +411,27 @@ mod tests {
         assert_eq!(requests[0].target_id, "channel_123");
     }
 
+    #[test]
+    fn test_peers_are_scheduled_with_jitter() {
+        let config = AntiEntropyConfig {
+            sync_interval: Duration::from_secs(30),
+            sync_jitter: Duration::from_secs(5),
+            ..Default::default()
+        };
+        let mut manager = AntiEntropyManager::new(config);
+        let start = Instant::now();
+
+        manager.add_peer("peer1".to_string(), VectorClock::new());
+        // Due within the jitter of being added
+        assert_eq!(manager.due_peers(start + Duration::from_secs(5)), vec!["peer1".to_string()]);
+
+        manager.record_success("peer1");
+        let next = manager.peers["peer1"].next_sync;
+        assert!(next >= start + Duration::from_secs(30));
+        assert!(next <= Instant::now() + Duration::from_secs(35));
+        assert!(manager.due_peers(start + Duration::from_secs(29)).is_empty());
+    }
+
     #[test]
     fn test_record_failure() {
         let config = AntiEntropyConfig::default();
@@ -15,7 +15,7 @@
 */
 
 use crate::core_store::crdt::{Crdt, OperationMetadata};
-use crate::core_store::model::{Channel, Space};
+use crate::core_store::model::{Channel, Message, Space};
 use crate::core_store::store::errors::StoreResult;
 use serde::{Deserialize, Serialize};
 
@@ -54,9 +54,29 @@ pub fn apply_remote_to_channel(channel: &mut Channel, remote_channel: &Channel)
     channel.name.merge(&remote_channel.name);
     channel.topic.merge(&remote_channel.topic);
     channel.members.merge(&remote_channel.members)?;
+    channel.member_devices.merge(&remote_channel.member_devices)?;
     channel.pinned_messages.merge(&remote_channel.pinned_messages)?;
     channel.permissions.merge_nested(&remote_channel.permissions)?;
     channel.mls_identity.merge(&remote_channel.mls_identity)?;
+    channel.invites.merge_nested(&remote_channel.invites)?;
+    channel.revoked_invites.merge(&remote_channel.revoked_invites)?;
+    channel.webhooks.merge_nested(&remote_channel.webhooks)?;
+    channel.revoked_webhooks.merge(&remote_channel.revoked_webhooks)?;
+    channel.linked_devices.merge_nested(&remote_channel.linked_devices)?;
+    channel.revoked_devices.merge(&remote_channel.revoked_devices)?;
+    channel.replica_nodes.merge_nested(&remote_channel.replica_nodes)?;
+    channel.read_markers.merge_nested(&remote_channel.read_markers)?;
+    channel.read_receipts.merge(&remote_channel.read_receipts);
+
+    Ok(())
+}
+
+/// Apply a remote copy of a message
+///
+/// Only reactions change after a message is sent by another device; the
+/// rest of the message comes with edits and deletions.
+pub fn apply_remote_to_message(message: &mut Message, remote_message: &Message) -> StoreResult<()> {
+    message.reactions.merge(&remote_message.reactions)?;
 
     Ok(())
 }
@@ -105,7 +125,9 @@ impl RemoteContext {
 mod tests {
     use super::*;
     use crate::core_store::crdt::{AddId, VectorClock};
-    use crate::core_store::model::{ChannelId, ChannelType, Timestamp, UserId};
+    use crate::core_store::model::{
+        ChannelId, ChannelType, MessageId, ReadMarker, Timestamp, UserId,
+    };
 
     #[test]
     fn test_apply_remote_to_channel() {
@@ -192,4 +214,40 @@ mod tests {
         assert!(local_channel.has_member(&alice));
         assert!(local_channel.has_member(&bob));
     }
+
+    #[test]
+    fn test_concurrent_read_markers_converge() {
+        let channel_id = ChannelId::generate();
+        let creator = UserId::generate();
+        let now = Timestamp::now();
+        let mut phone = Channel::new(
+            channel_id.clone(),
+            "Test".to_string(),
+            ChannelType::Text,
+            creator.clone(),
+            now,
+            "phone".to_string(),
+        );
+        let mut laptop = phone.clone();
+
+        // Two devices of the same user mark different messages read
+        let earlier = ReadMarker {
+            message_id: MessageId::generate(),
+            position: Timestamp::from_millis(1_000),
+        };
+        let later = ReadMarker {
+            message_id: MessageId::generate(),
+            position: Timestamp::from_millis(2_000),
+        };
+        assert!(phone.set_read_marker(creator.clone(), later.clone(), "phone"));
+        assert!(laptop.set_read_marker(creator.clone(), earlier, "laptop"));
+
+        let phone_state = phone.clone();
+        apply_remote_to_channel(&mut phone, &laptop).unwrap();
+        apply_remote_to_channel(&mut laptop, &phone_state).unwrap();
+
+        // Both keep the furthest position
+        assert_eq!(phone.get_read_marker(&creator), Some(&later));
+        assert_eq!(laptop.get_read_marker(&creator), Some(&later));
+    }
 }
@@ -10,11 +10,14 @@
     - Extract individual CRDT operations
 */
 
-use super::delta_encoder::{Delta, DeltaOperation};
+use super::delta_encoder::{Delta, DeltaOperation, FRAME_COMPRESSED, FRAME_MAGIC, FRAME_VERSION};
 use crate::core_store::crdt::VectorClock;
 use crate::core_store::store::errors::{StoreError, StoreResult};
 use serde::de::DeserializeOwned;
 
+/// Largest body a compact frame may decode to
+pub const MAX_FRAME_BODY: usize = 64 * 1024 * 1024;
+
 /// Delta decoder for parsing compressed CRDT operation bundles
 pub struct DeltaDecoder {
     /// The decoded delta
@@ -30,6 +33,11 @@ impl DeltaDecoder {
         Ok(DeltaDecoder { delta })
     }
 
+    /// Decode a delta from bytes in the compact framing
+    pub fn decode_compact(bytes: &[u8]) -> StoreResult<Self> {
+        Self::decode(&unframe(bytes)?)
+    }
+
     /// Get the delta version
     pub fn version(&self) -> u8 {
         self.delta.version
@@ -71,18 +79,19 @@ impl DeltaDecoder {
     }
 
     /// Reconstruct full vector clock from base + delta
+    ///
+    /// Entries in the delta replace the base's; a 0 drops the node.
     pub fn reconstruct_clock(
         &self,
         clock_delta: &std::collections::HashMap<String, u64>,
     ) -> VectorClock {
-        let mut clock = self.delta.base_clock.clone();
-
-        // Apply deltas
-        for (node, count) in clock_delta {
-            // This is simplified - actual implementation needs VectorClock::set()
-            // For now we just merge a temporary clock
-            for _ in 0..*count {
-                clock.increment(node);
+        let base = &self.delta.base_clock;
+        let mut clock = VectorClock::new();
+
+        for node in base.node_ids().into_iter().chain(clock_delta.keys().cloned()) {
+            let time = clock_delta.get(&node).copied().unwrap_or_else(|| base.get(&node));
+            if time > 0 {
+                clock.set(&node, time);
             }
         }
 
@@ -174,6 +183,7 @@ impl DeltaApplier {
                 DeltaOperation::ORSetRemove { path, .. } => path,
                 DeltaOperation::ORMapPut { path, .. } => path,
                 DeltaOperation::ORMapRemove { path, .. } => path,
+                DeltaOperation::Clock { path, .. } => path,
             };
 
             if !modified_paths.contains(path) {
@@ -190,6 +200,51 @@ impl DeltaApplier {
     }
 }
 
+/// Unwrap a payload from the compact framing (see `frame`)
+pub fn unframe(bytes: &[u8]) -> StoreResult<Vec<u8>> {
+    let corrupted = |what: &str| StoreError::CorruptedData(format!("Delta frame: {}", what));
+
+    let rest = bytes
+        .strip_prefix(FRAME_MAGIC.as_slice())
+        .ok_or_else(|| corrupted("bad magic"))?;
+    let (&[version, flags], rest) =
+        rest.split_first_chunk().ok_or_else(|| corrupted("truncated"))?;
+    if version != FRAME_VERSION {
+        return Err(corrupted(&format!("unsupported version {}", version)));
+    }
+    if flags & !FRAME_COMPRESSED != 0 {
+        return Err(corrupted(&format!("unknown flags {:#04x}", flags)));
+    }
+
+    let (mut len, mut shift, mut read) = (0u64, 0, 0);
+    loop {
+        let byte = *rest.get(read).ok_or_else(|| corrupted("truncated"))?;
+        if shift > 56 {
+            return Err(corrupted("length overflows"));
+        }
+        len |= u64::from(byte & 0x7f) << shift;
+        shift += 7;
+        read += 1;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    if len > MAX_FRAME_BODY as u64 {
+        return Err(corrupted(&format!("body of {} bytes", len)));
+    }
+    let (len, body) = (len as usize, &rest[read..]);
+
+    let payload = if flags & FRAME_COMPRESSED != 0 {
+        lz4_flex::block::decompress(body, len).map_err(|e| corrupted(&e.to_string()))?
+    } else {
+        body.to_vec()
+    };
+    if payload.len() != len {
+        return Err(corrupted("length mismatch"));
+    }
+    Ok(payload)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -223,6 +278,30 @@ mod tests {
         assert_eq!(decoder.operation_count(), 1);
     }
 
+    #[test]
+    fn test_decode_compact() {
+        let vc = VectorClock::new();
+        let mut encoder =
+            DeltaEncoder::new("channel_123".to_string(), "node1".to_string(), vc.clone());
+        for i in 0..50 {
+            encoder
+                .add_orset_add("channel.pinned".to_string(), &i, format!("n:{}", i), &vc)
+                .unwrap();
+        }
+        let delta = encoder.finalize();
+
+        for compress in [false, true] {
+            let bytes = DeltaEncoder::encode_compact(&delta, compress).unwrap();
+            let decoder = DeltaDecoder::decode_compact(&bytes).unwrap();
+            assert_eq!(decoder.operation_count(), 50);
+
+            // Truncated or unframed input is rejected, not misread
+            assert!(DeltaDecoder::decode_compact(&bytes[..bytes.len() - 1]).is_err());
+        }
+        assert!(unframe(&DeltaEncoder::encode(&delta).unwrap()).is_err());
+        assert!(unframe(b"SD\x01\x00\xff\xff\xff\xff\xff\xff\xff\xff\xff\x01").is_err());
+    }
+
     #[test]
     fn test_validate_delta() {
         let vc = VectorClock::new();
@@ -271,6 +350,28 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_reconstruct_clock() {
+        let mut base = VectorClock::new();
+        base.set("node1", 5);
+        base.set("node2", 3);
+        let mut field = VectorClock::new();
+        field.set("node1", 5);
+        field.set("node3", 1);
+
+        let mut encoder = DeltaEncoder::new("channel_123".to_string(), "node1".to_string(), base);
+        encoder.add_clock("channel.members".to_string(), &field);
+        let bytes = DeltaEncoder::encode(&encoder.finalize()).unwrap();
+        let decoder = DeltaDecoder::decode(&bytes).unwrap();
+
+        let DeltaOperation::Clock { clock_delta, .. } = &decoder.operations()[0] else {
+            panic!("Expected Clock operation");
+        };
+        // Only the entries that differ from the base are sent
+        assert_eq!(clock_delta.len(), 2);
+        assert_eq!(decoder.reconstruct_clock(clock_delta), field);
+    }
+
     #[test]
     fn test_delta_applier() {
         let vc = VectorClock::new();
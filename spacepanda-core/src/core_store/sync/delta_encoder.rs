@@ -13,6 +13,11 @@
     - Header: version, delta_id, base_clock
     - Operations: array of CRDT ops with shared context
     - Signature: delta-level signature for integrity
+
+    Compact framing (for deltas and delta-state payloads on the wire):
+    - 2-byte magic, a version byte and a flags byte
+    - Length of the decoded body as a LEB128 varint
+    - Body, LZ4-compressed if the flags say so
 */
 
 use crate::core_store::crdt::VectorClock;
@@ -23,6 +28,18 @@ use std::collections::HashMap;
 /// Version of the delta encoding format
 const DELTA_VERSION: u8 = 1;
 
+/// Magic at the start of a framed payload
+pub(super) const FRAME_MAGIC: &[u8; 2] = b"SD";
+
+/// Version of the compact framing
+pub(super) const FRAME_VERSION: u8 = 1;
+
+/// Frame flag: the body is LZ4-compressed
+pub(super) const FRAME_COMPRESSED: u8 = 0x01;
+
+/// Bodies shorter than this aren't worth compressing
+const MIN_COMPRESS_LEN: usize = 128;
+
 /// A compressed delta containing multiple CRDT operations
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Delta {
@@ -87,6 +104,10 @@ pub enum DeltaOperation {
 
     /// OR-Map remove operation
     ORMapRemove { path: String, key: Vec<u8>, clock_delta: HashMap<String, u64> },
+
+    /// Vector clock of a whole field, which may have moved on through
+    /// operations no longer visible in its state
+    Clock { path: String, clock_delta: HashMap<String, u64> },
 }
 
 /// Delta encoder for compressing CRDT operations
@@ -181,16 +202,62 @@ impl DeltaEncoder {
         Ok(())
     }
 
+    /// Add an OR-Map put operation
+    pub fn add_ormap_put<K: Serialize, V: Serialize>(
+        &mut self,
+        path: String,
+        key: &K,
+        value: &V,
+        add_id: String,
+        vector_clock: &VectorClock,
+    ) -> StoreResult<()> {
+        let key_bytes =
+            bincode::serialize(key).map_err(|e| StoreError::Serialization(e.to_string()))?;
+        let value_bytes =
+            bincode::serialize(value).map_err(|e| StoreError::Serialization(e.to_string()))?;
+
+        let clock_delta = self.compute_clock_delta(vector_clock);
+
+        self.operations.push(DeltaOperation::ORMapPut {
+            path,
+            key: key_bytes,
+            value: value_bytes,
+            add_id,
+            clock_delta,
+        });
+
+        Ok(())
+    }
+
+    /// Add the vector clock of a whole field
+    pub fn add_clock(&mut self, path: String, vector_clock: &VectorClock) {
+        let clock_delta = self.compute_clock_delta(vector_clock);
+        self.operations.push(DeltaOperation::Clock { path, clock_delta });
+    }
+
+    /// Add an already encoded operation, e.g. one taken from another delta
+    /// with the same base clock
+    pub fn add_operation(&mut self, operation: DeltaOperation) {
+        self.operations.push(operation);
+    }
+
     /// Compute vector clock delta (only differences from base)
+    ///
+    /// Entries equal to the base are left out; entries the clock lacks are
+    /// recorded as 0.
     fn compute_clock_delta(&self, clock: &VectorClock) -> HashMap<String, u64> {
-        let delta = HashMap::new();
+        let mut delta = HashMap::new();
 
-        // For each node in the new clock, compute the difference
-        // This is a simplified version - actual implementation would need
-        // access to VectorClock internals or a delta method
-
-        // For now, we'll include the full clock
-        // TODO: Add VectorClock::delta() method for proper compression
+        for node in self.base_clock.node_ids() {
+            if clock.get(&node) != self.base_clock.get(&node) {
+                delta.insert(node.clone(), clock.get(&node));
+            }
+        }
+        for node in clock.node_ids() {
+            if self.base_clock.get(&node) == 0 {
+                delta.insert(node.clone(), clock.get(&node));
+            }
+        }
 
         delta
     }
@@ -219,6 +286,13 @@ impl DeltaEncoder {
         bincode::serialize(delta).map_err(|e| StoreError::Serialization(e.to_string()))
     }
 
+    /// Encode delta to bytes in the compact framing, compressed if `compress`
+    ///
+    /// `compress` normally comes from `StoreConfig::enable_compression`.
+    pub fn encode_compact(delta: &Delta, compress: bool) -> StoreResult<Vec<u8>> {
+        Ok(frame(&Self::encode(delta)?, compress))
+    }
+
     /// Get the number of operations buffered
     pub fn operation_count(&self) -> usize {
         self.operations.len()
@@ -230,6 +304,33 @@ impl DeltaEncoder {
     }
 }
 
+/// Wrap an encoded payload in the compact framing
+///
+/// With `compress` the body is LZ4-compressed, unless it is short or
+/// doesn't shrink.
+pub fn frame(payload: &[u8], compress: bool) -> Vec<u8> {
+    let compressed = (compress && payload.len() >= MIN_COMPRESS_LEN)
+        .then(|| lz4_flex::block::compress(payload))
+        .filter(|compressed| compressed.len() < payload.len());
+
+    let mut framed = Vec::with_capacity(payload.len() + 14);
+    framed.extend_from_slice(FRAME_MAGIC);
+    framed.push(FRAME_VERSION);
+    framed.push(if compressed.is_some() {
+        FRAME_COMPRESSED
+    } else {
+        0
+    });
+    let mut len = payload.len() as u64;
+    while len >= 0x80 {
+        framed.push((len as u8) | 0x80);
+        len >>= 7;
+    }
+    framed.push(len as u8);
+    framed.extend_from_slice(compressed.as_deref().unwrap_or(payload));
+    framed
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -295,4 +396,23 @@ mod tests {
 
         assert!(!bytes.is_empty());
     }
+
+    #[test]
+    fn test_compact_framing_compresses_when_asked() {
+        let vc = VectorClock::new();
+        let mut encoder =
+            DeltaEncoder::new("channel_123".to_string(), "node1".to_string(), vc.clone());
+        for i in 0..100 {
+            encoder
+                .add_orset_add("channel.members".to_string(), &i, format!("node1:{}", i), &vc)
+                .unwrap();
+        }
+        let delta = encoder.finalize();
+
+        let plain = DeltaEncoder::encode(&delta).unwrap();
+        let framed = DeltaEncoder::encode_compact(&delta, false).unwrap();
+        let compressed = DeltaEncoder::encode_compact(&delta, true).unwrap();
+        assert_eq!(&framed[framed.len() - plain.len()..], &plain[..]);
+        assert!(compressed.len() < plain.len() / 2);
+    }
 }
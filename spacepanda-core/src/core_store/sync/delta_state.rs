@@ -0,0 +1,262 @@
+/*
+    delta_state.rs - Delta-state sync of a single CRDT
+
+    Shipping operation logs makes every sync O(history). Instead, each
+    mutation also yields a delta (ORSet::add_delta, ORMap::put_delta,
+    LWWRegister::set_delta, ...): a small state of the same CRDT that has
+    the mutation's effect when merged. A DeltaBuffer keeps the recent
+    deltas of one CRDT along with the clock each peer last acknowledged,
+    and syncing with a peer sends the join of the deltas that clock
+    doesn't cover, so the payload grows with what changed rather than with
+    the size of the state.
+
+    The full state goes instead when:
+    - the peer never acknowledged anything
+    - deltas it still needs were already dropped from the buffer
+    - it is missing more than `max_gap` deltas, when the state is about as
+      cheap to send
+
+    Payloads use the delta codec's compact framing, compressed if
+    StoreConfig::enable_compression is set.
+*/
+
+use super::delta_decoder::unframe;
+use super::delta_encoder::frame;
+use crate::config::StoreConfig;
+use crate::core_store::crdt::{Crdt, VectorClock};
+use crate::core_store::store::errors::StoreResult;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// Limits of a delta buffer
+#[derive(Debug, Clone)]
+pub struct DeltaStateConfig {
+    /// Deltas kept; older ones are dropped
+    pub max_deltas: usize,
+
+    /// Most deltas sent to a peer before falling back to the full state
+    pub max_gap: usize,
+
+    /// Compress payloads
+    pub compress: bool,
+}
+
+impl Default for DeltaStateConfig {
+    fn default() -> Self {
+        DeltaStateConfig { max_deltas: 1024, max_gap: 256, compress: true }
+    }
+}
+
+impl DeltaStateConfig {
+    /// Default limits, compressing as the store config says
+    pub fn from_store_config(config: &StoreConfig) -> Self {
+        DeltaStateConfig { compress: config.enable_compression, ..Default::default() }
+    }
+}
+
+/// What one sync sends a peer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DeltaPayload<C> {
+    /// Join of the deltas the peer hasn't acknowledged
+    Delta(C),
+
+    /// The whole state
+    Full(C),
+}
+
+impl<C> DeltaPayload<C> {
+    /// Whether this is the full state
+    pub fn is_full(&self) -> bool {
+        matches!(self, DeltaPayload::Full(_))
+    }
+
+    /// The state to merge
+    pub fn state(&self) -> &C {
+        match self {
+            DeltaPayload::Delta(state) | DeltaPayload::Full(state) => state,
+        }
+    }
+}
+
+/// Whether everything in `clock` is covered by `acked`
+fn covers(acked: &VectorClock, clock: &VectorClock) -> bool {
+    clock.node_ids().iter().all(|node| clock.get(node) <= acked.get(node))
+}
+
+/// Recent deltas of one CRDT and how far each peer has acknowledged them
+pub struct DeltaBuffer<C> {
+    config: DeltaStateConfig,
+
+    /// Deltas, oldest first
+    deltas: VecDeque<C>,
+
+    /// Merged clocks of the deltas dropped so far
+    dropped: Option<VectorClock>,
+
+    /// Last clock acknowledged by each peer
+    acked: HashMap<String, VectorClock>,
+}
+
+impl<C> DeltaBuffer<C>
+where
+    C: Crdt + Clone + Default + Serialize + DeserializeOwned,
+{
+    /// Create an empty buffer
+    pub fn new(config: DeltaStateConfig) -> Self {
+        DeltaBuffer { config, deltas: VecDeque::new(), dropped: None, acked: HashMap::new() }
+    }
+
+    /// Record the delta of a mutation
+    ///
+    /// Record deltas merged from other peers too if they should be passed on.
+    pub fn record(&mut self, delta: C) {
+        self.deltas.push_back(delta);
+        while self.deltas.len() > self.config.max_deltas {
+            self.drop_oldest();
+        }
+    }
+
+    /// Record that `peer_id`'s replica has reached `clock`
+    ///
+    /// Deltas every known peer has acknowledged are dropped.
+    pub fn ack(&mut self, peer_id: &str, clock: &VectorClock) {
+        self.acked.entry(peer_id.to_string()).or_default().merge(clock);
+        while self.deltas.front().is_some_and(|delta| {
+            self.acked.values().all(|acked| covers(acked, delta.vector_clock()))
+        }) {
+            self.drop_oldest();
+        }
+    }
+
+    /// Forget a peer; it gets the full state next time
+    pub fn remove_peer(&mut self, peer_id: &str) {
+        self.acked.remove(peer_id);
+    }
+
+    /// What to send `peer_id` for it to catch up with `state`
+    pub fn payload_for(&self, peer_id: &str, state: &C) -> StoreResult<DeltaPayload<C>> {
+        let Some(acked) = self.acked.get(peer_id) else {
+            return Ok(DeltaPayload::Full(state.clone()));
+        };
+        if self.dropped.as_ref().is_some_and(|dropped| !covers(acked, dropped)) {
+            return Ok(DeltaPayload::Full(state.clone()));
+        }
+
+        let pending: Vec<&C> = self
+            .deltas
+            .iter()
+            .filter(|delta| !covers(acked, delta.vector_clock()))
+            .collect();
+        if pending.len() > self.config.max_gap {
+            return Ok(DeltaPayload::Full(state.clone()));
+        }
+        let mut joined = C::default();
+        for delta in pending {
+            joined.merge(delta)?;
+        }
+        Ok(DeltaPayload::Delta(joined))
+    }
+
+    /// Encoded payload for `peer_id`, to be merged with `apply_payload`
+    pub fn encode_for(&self, peer_id: &str, state: &C) -> StoreResult<Vec<u8>> {
+        let payload = bincode::serialize(&self.payload_for(peer_id, state)?)?;
+        Ok(frame(&payload, self.config.compress))
+    }
+
+    /// Number of deltas held
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    /// Whether no deltas are held
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    fn drop_oldest(&mut self) {
+        if let Some(delta) = self.deltas.pop_front() {
+            self.dropped.get_or_insert_with(VectorClock::new).merge(delta.vector_clock());
+        }
+    }
+}
+
+/// Merge a payload from `DeltaBuffer::encode_for` into `state`
+///
+/// Returns the clock to acknowledge to the sender, and whether the payload
+/// was the full state.
+pub fn apply_payload<C>(state: &mut C, bytes: &[u8]) -> StoreResult<(VectorClock, bool)>
+where
+    C: Crdt + DeserializeOwned,
+{
+    let payload: DeltaPayload<C> = bincode::deserialize(&unframe(bytes)?)?;
+    state.merge(payload.state())?;
+    Ok((state.vector_clock().clone(), payload.is_full()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core_store::crdt::{AddId, ORSet};
+
+    fn add(set: &mut ORSet<u32>, clock: &mut VectorClock, n: u32) -> ORSet<u32> {
+        clock.increment("node1");
+        set.add_delta(n, AddId::new("node1".to_string(), n as u64), clock.clone())
+    }
+
+    #[test]
+    fn test_falls_back_to_full_state() {
+        let config = DeltaStateConfig { max_deltas: 4, max_gap: 2, compress: false };
+        let mut buffer = DeltaBuffer::new(config);
+        let (mut set, mut clock) = (ORSet::new(), VectorClock::new());
+
+        // A peer that never acknowledged anything gets everything
+        buffer.record(add(&mut set, &mut clock, 1));
+        assert!(buffer.payload_for("peer", &set).unwrap().is_full());
+        buffer.ack("peer", &clock);
+        assert!(buffer.is_empty());
+
+        buffer.record(add(&mut set, &mut clock, 2));
+        buffer.record(add(&mut set, &mut clock, 3));
+        let payload = buffer.payload_for("peer", &set).unwrap();
+        assert!(!payload.is_full());
+        assert_eq!(payload.state().len(), 2);
+
+        // Too far behind
+        buffer.record(add(&mut set, &mut clock, 4));
+        assert!(buffer.payload_for("peer", &set).unwrap().is_full());
+
+        // Deltas it needs were dropped from the buffer
+        buffer.ack("peer", &set.vector_clock().clone());
+        buffer.ack("other", &VectorClock::new());
+        for n in 5..10 {
+            buffer.record(add(&mut set, &mut clock, n));
+        }
+        assert_eq!(buffer.len(), 4);
+        assert!(buffer.payload_for("peer", &set).unwrap().is_full());
+    }
+
+    #[test]
+    fn test_payloads_roundtrip() {
+        let mut buffer = DeltaBuffer::new(DeltaStateConfig::default());
+        let (mut set, mut clock) = (ORSet::new(), VectorClock::new());
+        let mut replica: ORSet<u32> = ORSet::new();
+        for n in 0..3 {
+            buffer.record(add(&mut set, &mut clock, n));
+        }
+
+        let (acked, full) =
+            apply_payload(&mut replica, &buffer.encode_for("peer", &set).unwrap()).unwrap();
+        assert!(full);
+        buffer.ack("peer", &acked);
+
+        buffer.record(add(&mut set, &mut clock, 3));
+        let (acked, full) =
+            apply_payload(&mut replica, &buffer.encode_for("peer", &set).unwrap()).unwrap();
+        assert!(!full);
+        assert_eq!(&acked, set.vector_clock());
+        let mut elements = replica.elements();
+        elements.sort();
+        assert_eq!(elements, vec![0, 1, 2, 3]);
+    }
+}
@@ -9,13 +9,25 @@ pub mod apply_local;
 pub mod apply_remote;
 pub mod delta_decoder;
 pub mod delta_encoder;
+pub mod delta_state;
+pub mod session;
+pub mod stability;
 
 pub use anti_entropy::{
     AntiEntropyConfig, AntiEntropyManager, PeerSyncState, SyncRequest, SyncResponse,
 };
 pub use apply_local::{apply_local_to_channel, apply_local_to_space, LocalContext, LocalOperation};
 pub use apply_remote::{
-    apply_remote_to_channel, apply_remote_to_space, RemoteContext, RemoteOperation,
+    apply_remote_to_channel, apply_remote_to_message, apply_remote_to_space, RemoteContext,
+    RemoteOperation,
+};
+pub use delta_decoder::{unframe, DeltaApplier, DeltaDecoder};
+pub use delta_encoder::{frame, Delta, DeltaEncoder, DeltaOperation};
+pub use delta_state::{apply_payload, DeltaBuffer, DeltaPayload, DeltaStateConfig};
+pub use session::{
+    serve_sync, spawn_anti_entropy, summary_clock, sync_channel, SyncDeltas, SyncReplicas,
+    SyncReply, SyncReport, SyncServer, SyncStep, SYNC_METHOD,
+};
+pub use stability::{
+    collect_garbage, CausalStability, GcReport, StabilityConfig, StabilityEvent, STABILITY_FILE,
 };
-pub use delta_decoder::{DeltaApplier, DeltaDecoder};
-pub use delta_encoder::{Delta, DeltaEncoder, DeltaOperation};
@@ -0,0 +1,1384 @@
+/*
+    session.rs - Anti-entropy sync sessions between peers
+
+    A session reconciles one channel's store state with one peer over the
+    `store_sync` RPC. The initiator drives it:
+
+    1. Summary: send our vector clock for the channel (a SyncRequest); the
+       peer answers with its own, a digest of its operation IDs and how far
+       its delta buffer got. Equal clocks and digests end the session.
+    2. Deltas: each side keeps a delta buffer (delta_state.rs) of the
+       operations it came to hold since each peer last acknowledged it. The
+       peer sends the IDs of those we haven't acknowledged; we pull the ones
+       we lack and push ours it hasn't acknowledged, then compare summaries
+       again. If they agree the session only moved what changed.
+    3. Ids: otherwise, as when either side never had an acknowledgement, its
+       buffer dropped deltas still needed or the gap is over `max_gap`, the
+       full state is reconciled: page through the IDs of every operation in
+       the peer's replica. Operations are recovered from the channel's CRDT
+       fields (each add ID, tombstone, map put and register write); the
+       same operation has the same ID on every replica.
+    4. Pull: ask for the operations we lack, a batch at a time. They come
+       back as deltas (DeltaEncoder) and each one is merged in with
+       apply_remote_to_channel as soon as it arrives.
+    5. Push: send the peer the operations it lacks the same way.
+    6. Ack: tell the peer we now hold everything it held, so its next
+       deltas for us start from there.
+
+    Every delta also carries the sender's clock of each field, so after one
+    session both replicas hold the same state and the same vector clocks,
+    even where a field's clock moved on through writes no longer visible.
+
+    A session that gets through leaves the peer holding every operation we
+    hold; SyncReport::acked reports our field clocks at that point, which
+    feed tombstone garbage collection (stability.rs). The acks are kept per
+    member device of the channel's roster, found through the node each
+    device syncs from (Channel::replica_nodes).
+
+    Batches are committed as they arrive, so a session cut off mid-stream
+    loses at most the batch in flight: the next one finds a smaller
+    difference and carries on from there. Deltas are paced to
+    `max_deltas_per_sec` and kept under the RPC frame size.
+
+    Only the fields merged by apply_remote_to_channel are synced; messages
+    reach members through MLS rather than through these sessions.
+*/
+
+use super::anti_entropy::{AntiEntropyConfig, AntiEntropyManager, SyncRequest};
+use super::delta_decoder::DeltaDecoder;
+use super::delta_encoder::{DeltaEncoder, DeltaOperation};
+use super::delta_state::{DeltaBuffer, DeltaPayload, DeltaStateConfig};
+use super::stability::{collect_garbage, CausalStability};
+use crate::core_router::{PeerId, RouterHandle, RpcError, RpcRequest};
+use crate::core_store::crdt::{AddId, Crdt, LWWRegister, ORMap, ORSet, TombstoneCrdt, VectorClock};
+use crate::core_store::model::{Channel, ChannelId};
+use crate::core_store::store::errors::{StoreError, StoreResult};
+use crate::core_store::store::LocalStore;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+use tokio::time::{interval, sleep_until, Instant};
+use tracing::{debug, warn};
+
+/// Method name of the sync session RPC
+pub const SYNC_METHOD: &str = "store_sync";
+
+/// Operation IDs per `Ids` reply
+pub const SYNC_ID_PAGE: usize = 512;
+
+/// Most operation IDs one `Pull` may ask for
+pub const MAX_PULL_IDS: usize = 512;
+
+/// Most operation bytes per delta (hex-encoded, so the frame stays under 64 KiB)
+pub const MAX_DELTA_BYTES: usize = 16 * 1024;
+
+const NAME: &str = "channel.name";
+const TOPIC: &str = "channel.topic";
+const MEMBERS: &str = "channel.members";
+const MEMBER_DEVICES: &str = "channel.member_devices";
+const PINNED_MESSAGES: &str = "channel.pinned_messages";
+const PERMISSIONS: &str = "channel.permissions";
+const MLS_IDENTITY: &str = "channel.mls_identity";
+const INVITES: &str = "channel.invites";
+const REVOKED_INVITES: &str = "channel.revoked_invites";
+const WEBHOOKS: &str = "channel.webhooks";
+const REVOKED_WEBHOOKS: &str = "channel.revoked_webhooks";
+const LINKED_DEVICES: &str = "channel.linked_devices";
+const REVOKED_DEVICES: &str = "channel.revoked_devices";
+const REPLICA_NODES: &str = "channel.replica_nodes";
+const READ_MARKERS: &str = "channel.read_markers";
+const READ_RECEIPTS: &str = "channel.read_receipts";
+
+/// Params of `store_sync`: one step of a session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum SyncStep {
+    /// Our clock for a channel
+    Summary(SyncRequest),
+
+    /// IDs of the operations the peer buffered since we last acknowledged it
+    Deltas { target_id: String },
+
+    /// Up to SYNC_ID_PAGE operation IDs, in order, following `after`
+    Ids { target_id: String, after: Option<String> },
+
+    /// The operations with these IDs
+    Pull { target_id: String, op_ids: Vec<String> },
+
+    /// Operations the peer lacks, to merge in
+    Push {
+        #[serde(with = "hex_bytes")]
+        delta: Vec<u8>,
+    },
+
+    /// We hold every operation the peer held when its delta buffer was at `seq`
+    Ack { target_id: String, seq: u64 },
+}
+
+/// Result of `store_sync`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "reply", rename_all = "snake_case")]
+pub enum SyncReply {
+    /// The peer's clock for the channel, and a digest of its operation IDs
+    Summary {
+        clock: VectorClock,
+        #[serde(default)]
+        digest: Option<String>,
+        /// Sequence number of the peer's delta buffer; absent if it keeps none
+        #[serde(default)]
+        seq: Option<u64>,
+    },
+
+    /// Buffered operation IDs we haven't acknowledged, or None if the full
+    /// state has to be reconciled instead
+    Pending { op_ids: Option<Vec<String>> },
+
+    /// A page of the peer's operation IDs
+    Ids { op_ids: Vec<String>, done: bool },
+
+    /// Requested operations; `served` is how many of the requested IDs
+    /// (from the start) the delta covers, the rest have to be asked again
+    Delta {
+        #[serde(with = "hex_bytes")]
+        delta: Vec<u8>,
+        served: usize,
+    },
+
+    /// Number of pushed operations merged
+    Applied { operations: usize },
+
+    /// The acknowledgement was recorded
+    Acked,
+}
+
+/// Hex encoding for byte fields, far denser than serde_json's number arrays
+mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(d)?;
+        hex::decode(s).map_err(|_| serde::de::Error::custom("invalid hex"))
+    }
+}
+
+/// What a session moved
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    /// Operations merged from the peer
+    pub pulled: usize,
+
+    /// Operations the peer merged from us
+    pub pushed: usize,
+
+    /// Deltas exchanged either way
+    pub deltas: usize,
+
+    /// Field clocks the peer is known to hold every operation of, once the
+    /// session got through (see stability.rs)
+    pub acked: Option<BTreeMap<String, VectorClock>>,
+}
+
+impl SyncReport {
+    /// Whether both replicas already agreed
+    pub fn is_empty(&self) -> bool {
+        self.deltas == 0
+    }
+}
+
+/// Vector clocks of the synced fields of a channel
+fn field_clocks(channel: &Channel) -> [(&'static str, &VectorClock); 16] {
+    [
+        (NAME, channel.name.vector_clock()),
+        (TOPIC, channel.topic.vector_clock()),
+        (MEMBERS, channel.members.vector_clock()),
+        (MEMBER_DEVICES, channel.member_devices.vector_clock()),
+        (PINNED_MESSAGES, channel.pinned_messages.vector_clock()),
+        (PERMISSIONS, channel.permissions.vector_clock()),
+        (MLS_IDENTITY, channel.mls_identity.vector_clock()),
+        (INVITES, channel.invites.vector_clock()),
+        (REVOKED_INVITES, channel.revoked_invites.vector_clock()),
+        (WEBHOOKS, channel.webhooks.vector_clock()),
+        (REVOKED_WEBHOOKS, channel.revoked_webhooks.vector_clock()),
+        (LINKED_DEVICES, channel.linked_devices.vector_clock()),
+        (REVOKED_DEVICES, channel.revoked_devices.vector_clock()),
+        (REPLICA_NODES, channel.replica_nodes.vector_clock()),
+        (READ_MARKERS, channel.read_markers.vector_clock()),
+        (READ_RECEIPTS, channel.read_receipts.vector_clock()),
+    ]
+}
+
+/// Field clocks of a channel by sync path
+pub(super) fn field_clock_map(channel: &Channel) -> BTreeMap<String, VectorClock> {
+    field_clocks(channel)
+        .into_iter()
+        .map(|(path, clock)| (path.to_string(), clock.clone()))
+        .collect()
+}
+
+/// Drop the tombstones of each OR-Set and OR-Map field whose removal is
+/// covered by the field's entry in `stable`; returns how many went
+pub(super) fn gc_fields(channel: &mut Channel, stable: &HashMap<String, VectorClock>) -> usize {
+    fn gc(
+        field: &mut impl TombstoneCrdt,
+        stable: &HashMap<String, VectorClock>,
+        path: &str,
+    ) -> usize {
+        stable.get(path).map_or(0, |stable| field.gc_tombstones(stable))
+    }
+
+    gc(&mut channel.members, stable, MEMBERS)
+        + gc(&mut channel.member_devices, stable, MEMBER_DEVICES)
+        + gc(&mut channel.pinned_messages, stable, PINNED_MESSAGES)
+        + gc(&mut channel.permissions, stable, PERMISSIONS)
+        + gc(&mut channel.mls_identity, stable, MLS_IDENTITY)
+        + gc(&mut channel.invites, stable, INVITES)
+        + gc(&mut channel.revoked_invites, stable, REVOKED_INVITES)
+        + gc(&mut channel.webhooks, stable, WEBHOOKS)
+        + gc(&mut channel.revoked_webhooks, stable, REVOKED_WEBHOOKS)
+        + gc(&mut channel.linked_devices, stable, LINKED_DEVICES)
+        + gc(&mut channel.revoked_devices, stable, REVOKED_DEVICES)
+        + gc(&mut channel.replica_nodes, stable, REPLICA_NODES)
+        + gc(&mut channel.read_markers, stable, READ_MARKERS)
+}
+
+/// Vector clock summarising a channel: the merge of its synced fields' clocks
+pub fn summary_clock(channel: &Channel) -> VectorClock {
+    let mut clock = VectorClock::new();
+    for (_, field) in field_clocks(channel) {
+        clock.merge(field);
+    }
+    clock
+}
+
+/// Replica-independent ID of an operation on `path`
+fn op_id(path: &str, parts: &[&[u8]]) -> String {
+    let mut hasher = Sha256::new();
+    for part in std::iter::once(path.as_bytes()).chain(parts.iter().copied()) {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    }
+    hex::encode(&hasher.finalize()[..16])
+}
+
+fn encode_add_id(add_id: &AddId) -> String {
+    format!("{}:{}", add_id.node_id, add_id.timestamp)
+}
+
+fn decode_add_id(encoded: &str) -> StoreResult<AddId> {
+    let (node_id, timestamp) = encoded
+        .rsplit_once(':')
+        .and_then(|(node_id, timestamp)| Some((node_id, timestamp.parse().ok()?)))
+        .ok_or_else(|| StoreError::Deserialization(format!("Invalid add ID {}", encoded)))?;
+    Ok(AddId::new(node_id.to_string(), timestamp))
+}
+
+/// Operations recovered from a channel, in one delta encoder
+struct OpCollector {
+    encoder: DeltaEncoder,
+    ids: Vec<String>,
+}
+
+impl OpCollector {
+    fn register<T>(&mut self, path: &str, register: &LWWRegister<T>) -> StoreResult<()>
+    where
+        T: Clone + Send + Sync + Serialize,
+    {
+        let Some(value) = register.get() else {
+            return Ok(());
+        };
+        let (writer, timestamp) = (register.writer(), register.timestamp());
+        let bytes = bincode::serialize(value)?;
+        self.ids
+            .push(op_id(path, &[b"set", &bytes, writer.as_bytes(), &timestamp.to_le_bytes()]));
+        self.encoder.add_lww_operation(
+            path.to_string(),
+            value,
+            timestamp,
+            writer.to_string(),
+            register.vector_clock(),
+        )
+    }
+
+    fn set<T>(&mut self, path: &str, set: &ORSet<T>) -> StoreResult<()>
+    where
+        T: Clone + Eq + Hash + Send + Sync + Serialize,
+    {
+        for (element, add_id) in set.adds() {
+            let add_id = encode_add_id(add_id);
+            let bytes = bincode::serialize(element)?;
+            self.ids.push(op_id(path, &[b"add", &bytes, add_id.as_bytes()]));
+            self.encoder
+                .add_orset_add(path.to_string(), element, add_id, set.vector_clock())?;
+        }
+        for (element, add_id) in set.tombstones() {
+            let removed_at = set.removed_at(element, add_id).unwrap_or(set.vector_clock());
+            let add_id = encode_add_id(add_id);
+            let bytes = bincode::serialize(element)?;
+            self.ids.push(op_id(path, &[b"remove", &bytes, add_id.as_bytes()]));
+            self.encoder
+                .add_orset_remove(path.to_string(), element, vec![add_id], removed_at)?;
+        }
+        Ok(())
+    }
+
+    /// Puts of an OR-Map, with `identity` giving the bytes that identify a
+    /// value (leaving out replica-local clocks)
+    fn map<K, V>(
+        &mut self,
+        path: &str,
+        map: &ORMap<K, V>,
+        identity: impl Fn(&V) -> StoreResult<Vec<u8>>,
+    ) -> StoreResult<()>
+    where
+        K: Clone + Eq + Hash + Send + Sync + Serialize,
+        V: Clone + Send + Sync + Serialize,
+    {
+        for key in map.keys() {
+            let Some(value) = map.get(&key) else { continue };
+            let key_bytes = bincode::serialize(&key)?;
+            let value_bytes = identity(value)?;
+            for add_id in map.get_add_ids(&key).into_iter().flatten() {
+                let add_id = encode_add_id(add_id);
+                self.ids
+                    .push(op_id(path, &[b"put", &key_bytes, add_id.as_bytes(), &value_bytes]));
+                self.encoder.add_ormap_put(
+                    path.to_string(),
+                    &key,
+                    value,
+                    add_id,
+                    map.vector_clock(),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Identity of a register value: the value and its write, not its clock
+fn register_identity<T: Clone + Serialize>(register: &LWWRegister<T>) -> StoreResult<Vec<u8>> {
+    Ok(bincode::serialize(&(register.get(), register.writer(), register.timestamp()))?)
+}
+
+fn plain_identity<T: Serialize>(value: &T) -> StoreResult<Vec<u8>> {
+    Ok(bincode::serialize(value)?)
+}
+
+/// A channel's operations keyed by ID, ready to be put into deltas
+struct ChannelOps {
+    target_id: String,
+    base_clock: VectorClock,
+    ops: BTreeMap<String, DeltaOperation>,
+    clocks: Vec<DeltaOperation>,
+}
+
+impl ChannelOps {
+    fn new(channel: &Channel, author: &str) -> StoreResult<Self> {
+        let target_id = channel.id.0.clone();
+        let base_clock = summary_clock(channel);
+        let encoder = DeltaEncoder::new(target_id.clone(), author.to_string(), base_clock.clone());
+        let mut ops = OpCollector { encoder, ids: Vec::new() };
+
+        ops.register(NAME, &channel.name)?;
+        ops.register(TOPIC, &channel.topic)?;
+        ops.set(MEMBERS, &channel.members)?;
+        ops.set(MEMBER_DEVICES, &channel.member_devices)?;
+        ops.set(PINNED_MESSAGES, &channel.pinned_messages)?;
+        ops.map(PERMISSIONS, &channel.permissions, register_identity)?;
+        ops.map(MLS_IDENTITY, &channel.mls_identity, plain_identity)?;
+        ops.map(INVITES, &channel.invites, register_identity)?;
+        ops.set(REVOKED_INVITES, &channel.revoked_invites)?;
+        ops.map(WEBHOOKS, &channel.webhooks, register_identity)?;
+        ops.set(REVOKED_WEBHOOKS, &channel.revoked_webhooks)?;
+        ops.map(LINKED_DEVICES, &channel.linked_devices, register_identity)?;
+        ops.set(REVOKED_DEVICES, &channel.revoked_devices)?;
+        ops.map(REPLICA_NODES, &channel.replica_nodes, register_identity)?;
+        ops.map(READ_MARKERS, &channel.read_markers, register_identity)?;
+        ops.register(READ_RECEIPTS, &channel.read_receipts)?;
+        for (path, clock) in field_clocks(channel) {
+            ops.encoder.add_clock(path.to_string(), clock);
+        }
+
+        let OpCollector { encoder, ids } = ops;
+        let mut operations = encoder.finalize().operations.into_iter();
+        let ops = ids.into_iter().zip(&mut operations).collect();
+        let clocks = operations.collect();
+        Ok(ChannelOps { target_id, base_clock, ops, clocks })
+    }
+
+    /// Digest of every operation ID; replicas holding the same operations
+    /// have the same digest
+    fn digest(&self) -> String {
+        let ids: Vec<&[u8]> = self.ops.keys().map(|id| id.as_bytes()).collect();
+        op_id("digest", &ids)
+    }
+
+    /// Encode the operations in `op_ids`, in order, plus every field clock
+    ///
+    /// Stops after `max_ops` operations or MAX_DELTA_BYTES (but always
+    /// takes at least one). Returns the delta and how many of `op_ids` it
+    /// covers; IDs of operations this replica doesn't hold are skipped.
+    fn delta(
+        &self,
+        author: &str,
+        op_ids: &[String],
+        max_ops: usize,
+    ) -> StoreResult<(Vec<u8>, usize)> {
+        let mut encoder =
+            DeltaEncoder::new(self.target_id.clone(), author.to_string(), self.base_clock.clone());
+        let (mut count, mut bytes, mut covered) = (0, 0, 0);
+        for id in op_ids {
+            if let Some(op) = self.ops.get(id) {
+                let size = bincode::serialized_size(op)? as usize;
+                if count == max_ops.max(1) || (count > 0 && bytes + size > MAX_DELTA_BYTES) {
+                    break;
+                }
+                encoder.add_operation(op.clone());
+                count += 1;
+                bytes += size;
+            }
+            covered += 1;
+        }
+        for clock in &self.clocks {
+            encoder.add_operation(clock.clone());
+        }
+        Ok((DeltaEncoder::encode(&encoder.finalize())?, covered))
+    }
+}
+
+/// Clock entry delta buffers count their own deltas under
+const DELTA_SEQ: &str = "seq";
+
+/// Operation IDs a channel came to hold, as a CRDT for DeltaBuffer
+///
+/// Merging is set union. The clock only counts this replica's deltas, so
+/// that an acknowledgement covers exactly the deltas recorded before it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct OpIds {
+    ids: BTreeSet<String>,
+    clock: VectorClock,
+}
+
+impl Crdt for OpIds {
+    type Operation = String;
+    type Value = BTreeSet<String>;
+
+    fn apply(&mut self, op: String) -> StoreResult<()> {
+        self.ids.insert(op);
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &Self) -> StoreResult<()> {
+        self.ids.extend(other.ids.iter().cloned());
+        self.clock.merge(&other.clock);
+        Ok(())
+    }
+
+    fn value(&self) -> BTreeSet<String> {
+        self.ids.clone()
+    }
+
+    fn vector_clock(&self) -> &VectorClock {
+        &self.clock
+    }
+}
+
+/// Delta buffer of one channel
+struct ChannelDeltas {
+    seen: HashSet<String>,
+    seq: u64,
+    buffer: DeltaBuffer<OpIds>,
+}
+
+/// Delta-state of the synced channels: the operations each replica came to
+/// hold since every peer last acknowledged it
+///
+/// Share one between the SyncServer and the sessions this node runs.
+pub struct SyncDeltas {
+    config: DeltaStateConfig,
+    channels: HashMap<ChannelId, ChannelDeltas>,
+}
+
+impl SyncDeltas {
+    /// Create empty buffers with `config`'s limits
+    pub fn new(config: DeltaStateConfig) -> Self {
+        SyncDeltas { config, channels: HashMap::new() }
+    }
+
+    /// Record the operations of `ops` not seen before as one delta, and
+    /// return the buffer's sequence number
+    ///
+    /// What the channel holds when first observed is never buffered; peers
+    /// get it through the full state.
+    fn observe(&mut self, ops: &ChannelOps) -> u64 {
+        let channel_id = ChannelId(ops.target_id.clone());
+        let Some(deltas) = self.channels.get_mut(&channel_id) else {
+            let deltas = ChannelDeltas {
+                seen: ops.ops.keys().cloned().collect(),
+                seq: 0,
+                buffer: DeltaBuffer::new(self.config.clone()),
+            };
+            self.channels.insert(channel_id, deltas);
+            return 0;
+        };
+
+        let ids: BTreeSet<String> =
+            ops.ops.keys().filter(|id| !deltas.seen.contains(*id)).cloned().collect();
+        if !ids.is_empty() {
+            deltas.seq += 1;
+            deltas.seen.extend(ids.iter().cloned());
+            let mut clock = VectorClock::new();
+            clock.set(DELTA_SEQ, deltas.seq);
+            deltas.buffer.record(OpIds { ids, clock });
+        }
+        deltas.seq
+    }
+
+    /// Record that `peer` holds every operation this replica held at `seq`
+    fn ack(&mut self, channel_id: &ChannelId, peer: &str, seq: u64) {
+        if let Some(deltas) = self.channels.get_mut(channel_id) {
+            let mut clock = VectorClock::new();
+            clock.set(DELTA_SEQ, seq.min(deltas.seq));
+            deltas.buffer.ack(peer, &clock);
+        }
+    }
+
+    /// IDs of the buffered operations `peer` hasn't acknowledged, or None
+    /// if it has to get the full state
+    fn pending(&self, channel_id: &ChannelId, peer: &str) -> StoreResult<Option<Vec<String>>> {
+        let Some(deltas) = self.channels.get(channel_id) else {
+            return Ok(None);
+        };
+        // The full state itself is reconciled by the session, not sent here
+        match deltas.buffer.payload_for(peer, &OpIds::default())? {
+            DeltaPayload::Delta(joined) if joined.ids.len() <= SYNC_ID_PAGE => {
+                Ok(Some(joined.ids.into_iter().collect()))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// A channel with the same identity as `channel` and empty CRDT fields
+fn empty_like(channel: &Channel) -> Channel {
+    Channel {
+        id: channel.id.clone(),
+        name: LWWRegister::new(),
+        topic: LWWRegister::new(),
+        channel_type: channel.channel_type,
+        created_at: channel.created_at,
+        created_by: channel.created_by.clone(),
+        members: ORSet::new(),
+        member_devices: ORSet::new(),
+        pinned_messages: ORSet::new(),
+        permissions: ORMap::new(),
+        mls_identity: ORMap::new(),
+        invites: ORMap::new(),
+        revoked_invites: ORSet::new(),
+        webhooks: ORMap::new(),
+        revoked_webhooks: ORSet::new(),
+        linked_devices: ORMap::new(),
+        revoked_devices: ORSet::new(),
+        replica_nodes: ORMap::new(),
+        public: LWWRegister::new(),
+        psk_id: LWWRegister::new(),
+        padding: LWWRegister::new(),
+        avatar_hash: LWWRegister::new(),
+        read_markers: ORMap::new(),
+        read_receipts: LWWRegister::new(),
+        retention: LWWRegister::new(),
+    }
+}
+
+fn unexpected(op: &DeltaOperation) -> StoreError {
+    StoreError::ValidationError(format!("Unexpected operation in delta: {:?}", op))
+}
+
+fn apply_register<T>(
+    register: &mut LWWRegister<T>,
+    op: &DeltaOperation,
+    decoder: &DeltaDecoder,
+) -> StoreResult<()>
+where
+    T: Clone + DeserializeOwned,
+{
+    match op {
+        DeltaOperation::LWWUpdate { value, timestamp, node_id, clock_delta, .. } => {
+            let value = decoder.deserialize_lww_value(value)?;
+            let clock = decoder.reconstruct_clock(clock_delta);
+            register.set(value, *timestamp, node_id.clone(), clock);
+        }
+        DeltaOperation::Clock { clock_delta, .. } => {
+            register.merge_clock(&decoder.reconstruct_clock(clock_delta));
+        }
+        _ => return Err(unexpected(op)),
+    }
+    Ok(())
+}
+
+fn apply_set<T>(set: &mut ORSet<T>, op: &DeltaOperation, decoder: &DeltaDecoder) -> StoreResult<()>
+where
+    T: Clone + Eq + Hash + DeserializeOwned,
+{
+    match op {
+        DeltaOperation::ORSetAdd { element, add_id, clock_delta, .. } => {
+            let element = decoder.deserialize_orset_element(element)?;
+            set.add(element, decode_add_id(add_id)?, decoder.reconstruct_clock(clock_delta));
+        }
+        DeltaOperation::ORSetRemove { element, add_ids, clock_delta, .. } => {
+            let element: T = decoder.deserialize_orset_element(element)?;
+            let add_ids = add_ids
+                .iter()
+                .map(|id| decode_add_id(id))
+                .collect::<StoreResult<HashSet<_>>>()?;
+            set.remove_adds(&element, &add_ids, decoder.reconstruct_clock(clock_delta));
+        }
+        DeltaOperation::Clock { clock_delta, .. } => {
+            set.merge_clock(&decoder.reconstruct_clock(clock_delta));
+        }
+        _ => return Err(unexpected(op)),
+    }
+    Ok(())
+}
+
+fn apply_map<K, V>(
+    map: &mut ORMap<K, V>,
+    op: &DeltaOperation,
+    decoder: &DeltaDecoder,
+) -> StoreResult<()>
+where
+    K: Clone + Eq + Hash + DeserializeOwned,
+    V: Clone + DeserializeOwned,
+{
+    match op {
+        DeltaOperation::ORMapPut { key, value, add_id, clock_delta, .. } => {
+            let key = decoder.deserialize_ormap_key(key)?;
+            let value = decoder.deserialize_ormap_value(value)?;
+            map.put(key, value, decode_add_id(add_id)?, decoder.reconstruct_clock(clock_delta));
+        }
+        DeltaOperation::Clock { clock_delta, .. } => {
+            map.merge_clock(&decoder.reconstruct_clock(clock_delta));
+        }
+        _ => return Err(unexpected(op)),
+    }
+    Ok(())
+}
+
+fn apply_op(channel: &mut Channel, op: &DeltaOperation, decoder: &DeltaDecoder) -> StoreResult<()> {
+    let path = match op {
+        DeltaOperation::LWWUpdate { path, .. }
+        | DeltaOperation::ORSetAdd { path, .. }
+        | DeltaOperation::ORSetRemove { path, .. }
+        | DeltaOperation::ORMapPut { path, .. }
+        | DeltaOperation::ORMapRemove { path, .. }
+        | DeltaOperation::Clock { path, .. } => path.as_str(),
+    };
+
+    match path {
+        NAME => apply_register(&mut channel.name, op, decoder),
+        TOPIC => apply_register(&mut channel.topic, op, decoder),
+        MEMBERS => apply_set(&mut channel.members, op, decoder),
+        MEMBER_DEVICES => apply_set(&mut channel.member_devices, op, decoder),
+        PINNED_MESSAGES => apply_set(&mut channel.pinned_messages, op, decoder),
+        PERMISSIONS => apply_map(&mut channel.permissions, op, decoder),
+        MLS_IDENTITY => apply_map(&mut channel.mls_identity, op, decoder),
+        INVITES => apply_map(&mut channel.invites, op, decoder),
+        REVOKED_INVITES => apply_set(&mut channel.revoked_invites, op, decoder),
+        WEBHOOKS => apply_map(&mut channel.webhooks, op, decoder),
+        REVOKED_WEBHOOKS => apply_set(&mut channel.revoked_webhooks, op, decoder),
+        LINKED_DEVICES => apply_map(&mut channel.linked_devices, op, decoder),
+        REVOKED_DEVICES => apply_set(&mut channel.revoked_devices, op, decoder),
+        REPLICA_NODES => apply_map(&mut channel.replica_nodes, op, decoder),
+        READ_MARKERS => apply_map(&mut channel.read_markers, op, decoder),
+        READ_RECEIPTS => apply_register(&mut channel.read_receipts, op, decoder),
+        _ => Err(StoreError::ValidationError(format!("Unknown sync path {}", path))),
+    }
+}
+
+/// Merge a received delta into its channel, returning the channel and the
+/// number of operations (not counting field clocks) it carried
+fn apply_delta(store: &LocalStore, bytes: &[u8]) -> StoreResult<(ChannelId, usize)> {
+    let decoder = DeltaDecoder::decode(bytes)?;
+    decoder.validate()?;
+
+    let channel_id = ChannelId(decoder.target_id().to_string());
+    let local = store
+        .get_channel(&channel_id)?
+        .ok_or_else(|| StoreError::NotFound(format!("Channel {}", channel_id.0)))?;
+
+    let mut remote = empty_like(&local);
+    let mut operations = 0;
+    for op in decoder.operations() {
+        apply_op(&mut remote, op, &decoder)?;
+        if !matches!(op, DeltaOperation::Clock { .. }) {
+            operations += 1;
+        }
+    }
+    store.apply_remote_channel(&remote)?;
+    Ok((channel_id, operations))
+}
+
+fn channel(store: &LocalStore, target_id: &str) -> StoreResult<Channel> {
+    store
+        .get_channel(&ChannelId(target_id.to_string()))?
+        .ok_or_else(|| StoreError::NotFound(format!("Channel {}", target_id)))
+}
+
+/// A channel, its operations and, with `deltas`, the buffer's sequence
+/// number once they are recorded
+///
+/// The channel is read under the buffer's lock, so the operations recorded
+/// up to a sequence number are all in the state it came with.
+fn snapshot(
+    store: &LocalStore,
+    target_id: &str,
+    node_id: &str,
+    deltas: Option<&Mutex<SyncDeltas>>,
+) -> StoreResult<(Channel, ChannelOps, Option<u64>)> {
+    let mut deltas = deltas.map(|deltas| deltas.lock().unwrap_or_else(|e| e.into_inner()));
+    let channel = channel(store, target_id)?;
+    let ops = ChannelOps::new(&channel, node_id)?;
+    let seq = deltas.as_mut().map(|deltas| deltas.observe(&ops));
+    Ok((channel, ops, seq))
+}
+
+/// Channel a step is about
+fn step_target(step: &SyncStep) -> StoreResult<String> {
+    match step {
+        SyncStep::Summary(request) => Ok(request.target_id.clone()),
+        SyncStep::Deltas { target_id }
+        | SyncStep::Ids { target_id, .. }
+        | SyncStep::Pull { target_id, .. }
+        | SyncStep::Ack { target_id, .. } => Ok(target_id.clone()),
+        SyncStep::Push { delta } => Ok(DeltaDecoder::decode(delta)?.target_id().to_string()),
+    }
+}
+
+/// Answers the steps of sessions other peers run with this store
+pub struct SyncServer {
+    store: Arc<LocalStore>,
+    node_id: String,
+    stability: Option<Arc<Mutex<CausalStability>>>,
+    deltas: Option<Arc<Mutex<SyncDeltas>>>,
+}
+
+impl SyncServer {
+    /// Serve `store`, signing deltas as `node_id`
+    pub fn new(store: Arc<LocalStore>, node_id: String) -> Self {
+        SyncServer { store, node_id, stability: None, deltas: None }
+    }
+
+    /// Refuse sessions from replicas `stability` has quarantined
+    pub fn with_stability(mut self, stability: Arc<Mutex<CausalStability>>) -> Self {
+        self.stability = Some(stability);
+        self
+    }
+
+    /// Serve the operations buffered in `deltas` to replicas that
+    /// acknowledged us before
+    pub fn with_deltas(mut self, deltas: Arc<Mutex<SyncDeltas>>) -> Self {
+        self.deltas = Some(deltas);
+        self
+    }
+
+    /// Answer one step of a session run by the node `replica`
+    pub fn handle_from(&self, replica: &str, step: SyncStep) -> StoreResult<SyncReply> {
+        if let Some(stability) = &self.stability {
+            let target_id = step_target(&step)?;
+            let quarantined = match self.store.get_channel(&ChannelId(target_id.clone()))? {
+                Some(channel) => {
+                    let stability = stability.lock().unwrap_or_else(|e| e.into_inner());
+                    stability.is_quarantined(&channel, replica)
+                }
+                None => false,
+            };
+            if quarantined {
+                return Err(StoreError::Sync(format!(
+                    "Replica {} is quarantined for {}",
+                    replica, target_id
+                )));
+            }
+        }
+        self.respond(Some(replica), step)
+    }
+
+    /// Answer one step of a session run by an unknown replica, which never
+    /// gets buffered deltas
+    pub fn handle(&self, step: SyncStep) -> StoreResult<SyncReply> {
+        self.respond(None, step)
+    }
+
+    fn deltas(&self) -> Option<std::sync::MutexGuard<'_, SyncDeltas>> {
+        let deltas = self.deltas.as_ref()?;
+        Some(deltas.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    fn respond(&self, replica: Option<&str>, step: SyncStep) -> StoreResult<SyncReply> {
+        match step {
+            SyncStep::Summary(request) => {
+                let (channel, ops, seq) = snapshot(
+                    &self.store,
+                    &request.target_id,
+                    &self.node_id,
+                    self.deltas.as_deref(),
+                )?;
+                Ok(SyncReply::Summary {
+                    clock: summary_clock(&channel),
+                    digest: Some(ops.digest()),
+                    seq,
+                })
+            }
+            SyncStep::Deltas { target_id } => {
+                let channel_id = ChannelId(target_id);
+                let op_ids = match (self.deltas(), replica) {
+                    (Some(deltas), Some(replica)) => deltas.pending(&channel_id, replica)?,
+                    _ => None,
+                };
+                Ok(SyncReply::Pending { op_ids })
+            }
+            SyncStep::Ids { target_id, after } => {
+                let ops = ChannelOps::new(&channel(&self.store, &target_id)?, &self.node_id)?;
+                let mut op_ids: Vec<String> = ops
+                    .ops
+                    .into_keys()
+                    .filter(|id| after.as_ref().is_none_or(|after| id > after))
+                    .take(SYNC_ID_PAGE + 1)
+                    .collect();
+                let done = op_ids.len() <= SYNC_ID_PAGE;
+                op_ids.truncate(SYNC_ID_PAGE);
+                Ok(SyncReply::Ids { op_ids, done })
+            }
+            SyncStep::Pull { target_id, op_ids } => {
+                if op_ids.len() > MAX_PULL_IDS {
+                    return Err(StoreError::ValidationError(format!(
+                        "Pull of {} operations, at most {} allowed",
+                        op_ids.len(),
+                        MAX_PULL_IDS
+                    )));
+                }
+                let ops = ChannelOps::new(&channel(&self.store, &target_id)?, &self.node_id)?;
+                let (delta, served) = ops.delta(&self.node_id, &op_ids, MAX_PULL_IDS)?;
+                Ok(SyncReply::Delta { delta, served })
+            }
+            SyncStep::Push { delta } => {
+                let (_, operations) = apply_delta(&self.store, &delta)?;
+                Ok(SyncReply::Applied { operations })
+            }
+            SyncStep::Ack { target_id, seq } => {
+                if let (Some(mut deltas), Some(replica)) = (self.deltas(), replica) {
+                    deltas.ack(&ChannelId(target_id), replica, seq);
+                }
+                Ok(SyncReply::Acked)
+            }
+        }
+    }
+}
+
+/// Spaces deltas out to at most `per_sec` a second
+struct Pacer {
+    gap: Duration,
+    last: Option<Instant>,
+}
+
+impl Pacer {
+    fn new(per_sec: u32) -> Self {
+        Pacer { gap: Duration::from_secs(1) / per_sec.max(1), last: None }
+    }
+
+    async fn wait(&mut self) {
+        if let Some(last) = self.last {
+            sleep_until(last + self.gap).await;
+        }
+        self.last = Some(Instant::now());
+    }
+}
+
+fn unexpected_reply(reply: SyncReply) -> StoreError {
+    StoreError::Sync(format!("Unexpected reply: {:?}", reply))
+}
+
+/// Ask the peer for its summary of `local`'s channel: its clock, digest and
+/// delta buffer sequence number
+async fn summary<F, Fut>(
+    call: &mut F,
+    local: &Channel,
+    node_id: &str,
+) -> StoreResult<(VectorClock, Option<String>, Option<u64>)>
+where
+    F: FnMut(SyncStep) -> Fut,
+    Fut: Future<Output = StoreResult<SyncReply>>,
+{
+    let target_id = local.id.0.clone();
+    let sent_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+    let request = SyncRequest {
+        request_id: format!("{}:{}:{}", target_id, node_id, sent_at),
+        target_id,
+        our_clock: summary_clock(local),
+    };
+    match call(SyncStep::Summary(request)).await? {
+        SyncReply::Summary { clock, digest, seq } => Ok((clock, digest, seq)),
+        reply => Err(unexpected_reply(reply)),
+    }
+}
+
+/// Pull the operations in `missing`, a batch at a time
+///
+/// Always takes at least one delta, which brings the peer's field clocks.
+async fn pull<F, Fut>(
+    store: &LocalStore,
+    channel_id: &ChannelId,
+    mut missing: Vec<String>,
+    config: &AntiEntropyConfig,
+    pacer: &mut Pacer,
+    call: &mut F,
+    report: &mut SyncReport,
+) -> StoreResult<()>
+where
+    F: FnMut(SyncStep) -> Fut,
+    Fut: Future<Output = StoreResult<SyncReply>>,
+{
+    loop {
+        pacer.wait().await;
+        let op_ids: Vec<String> = missing.iter().take(config.batch_size.max(1)).cloned().collect();
+        let requested = op_ids.len();
+        let target_id = channel_id.0.clone();
+        let (delta, served) = match call(SyncStep::Pull { target_id, op_ids }).await? {
+            SyncReply::Delta { delta, served } => (delta, served),
+            reply => return Err(unexpected_reply(reply)),
+        };
+        if served > requested || (served == 0 && requested > 0) {
+            return Err(StoreError::Sync(format!("Peer served {} of {}", served, requested)));
+        }
+        let (target, operations) = apply_delta(store, &delta)?;
+        if target != *channel_id {
+            return Err(StoreError::Sync(format!("Delta for {} instead", target.0)));
+        }
+        missing.drain(..served);
+        report.pulled += operations;
+        report.deltas += 1;
+        if missing.is_empty() {
+            return Ok(());
+        }
+    }
+}
+
+/// Push the operations of `ours` in `missing`, a batch at a time
+///
+/// Always sends at least one delta, which brings our field clocks.
+async fn push<F, Fut>(
+    ours: &ChannelOps,
+    node_id: &str,
+    mut missing: Vec<String>,
+    config: &AntiEntropyConfig,
+    pacer: &mut Pacer,
+    call: &mut F,
+    report: &mut SyncReport,
+) -> StoreResult<()>
+where
+    F: FnMut(SyncStep) -> Fut,
+    Fut: Future<Output = StoreResult<SyncReply>>,
+{
+    loop {
+        pacer.wait().await;
+        let (delta, covered) = ours.delta(node_id, &missing, config.batch_size)?;
+        match call(SyncStep::Push { delta }).await? {
+            SyncReply::Applied { operations } => report.pushed += operations,
+            reply => return Err(unexpected_reply(reply)),
+        }
+        missing.drain(..covered);
+        report.deltas += 1;
+        if missing.is_empty() {
+            return Ok(());
+        }
+    }
+}
+
+/// Reconcile a channel with a peer
+///
+/// `call` sends a step to the peer and returns its reply. `deltas` is this
+/// node's delta buffer, in which the peer is known as `peer`. Fails if
+/// either side doesn't hold the channel; whatever was merged before a
+/// failure stays merged.
+pub async fn sync_channel<F, Fut>(
+    store: &LocalStore,
+    channel_id: &ChannelId,
+    node_id: &str,
+    config: &AntiEntropyConfig,
+    deltas: &Mutex<SyncDeltas>,
+    peer: &str,
+    mut call: F,
+) -> StoreResult<SyncReport>
+where
+    F: FnMut(SyncStep) -> Fut,
+    Fut: Future<Output = StoreResult<SyncReply>>,
+{
+    let target_id = channel_id.0.clone();
+    let (local, ours, seq) = snapshot(store, &target_id, node_id, Some(deltas))?;
+    let mut our_seq = seq.unwrap_or_default();
+    let (their_clock, their_digest, mut their_seq) = summary(&mut call, &local, node_id).await?;
+    let mut report = SyncReport::default();
+    if their_clock == summary_clock(&local) {
+        // Peers that send a digest can confirm they hold the same operations
+        match their_digest {
+            Some(digest) if digest == ours.digest() => {
+                let session = Acks { channel_id, deltas, peer, our_seq, their_seq };
+                return session.finish(&local, &mut call, report).await;
+            }
+            Some(_) => {}
+            None => return Ok(report),
+        }
+    }
+    let mut pacer = Pacer::new(config.max_deltas_per_sec);
+
+    // Only what changed since each side last acknowledged the other
+    if their_seq.is_some() {
+        let our_deltas = {
+            let deltas = deltas.lock().unwrap_or_else(|e| e.into_inner());
+            deltas.pending(channel_id, peer)?
+        };
+        let their_deltas = match call(SyncStep::Deltas { target_id: target_id.clone() }).await? {
+            SyncReply::Pending { op_ids } => op_ids,
+            reply => return Err(unexpected_reply(reply)),
+        };
+        if let (Some(our_deltas), Some(their_deltas)) = (our_deltas, their_deltas) {
+            let missing: Vec<String> =
+                their_deltas.iter().filter(|id| !ours.ops.contains_key(*id)).cloned().collect();
+            pull(store, channel_id, missing, config, &mut pacer, &mut call, &mut report).await?;
+
+            let (merged, ours, seq) = snapshot(store, &target_id, node_id, Some(deltas))?;
+            our_seq = seq.unwrap_or_default();
+            let theirs: HashSet<&String> = their_deltas.iter().collect();
+            let missing: Vec<String> = our_deltas
+                .into_iter()
+                .filter(|id| ours.ops.contains_key(id) && !theirs.contains(id))
+                .collect();
+            push(&ours, node_id, missing, config, &mut pacer, &mut call, &mut report).await?;
+
+            let (clock, digest, seq) = summary(&mut call, &merged, node_id).await?;
+            their_seq = seq;
+            if clock == summary_clock(&merged) && digest == Some(ours.digest()) {
+                let session = Acks { channel_id, deltas, peer, our_seq, their_seq };
+                return session.finish(&merged, &mut call, report).await;
+            }
+        }
+    }
+
+    // Otherwise the full state: everything they hold
+    let mut theirs = BTreeSet::new();
+    let mut after = None;
+    loop {
+        match call(SyncStep::Ids { target_id: target_id.clone(), after }).await? {
+            SyncReply::Ids { op_ids, done } => {
+                after = op_ids.last().cloned();
+                theirs.extend(op_ids);
+                if done || after.is_none() {
+                    break;
+                }
+            }
+            reply => return Err(unexpected_reply(reply)),
+        }
+    }
+
+    // Pull what we lack
+    let local = channel(store, &target_id)?;
+    let ours = ChannelOps::new(&local, node_id)?;
+    let missing: Vec<String> =
+        theirs.iter().filter(|id| !ours.ops.contains_key(*id)).cloned().collect();
+    pull(store, channel_id, missing, config, &mut pacer, &mut call, &mut report).await?;
+
+    // Push what they lack, from the merged state so they get our clocks too
+    let (merged, ours, seq) = snapshot(store, &target_id, node_id, Some(deltas))?;
+    our_seq = seq.unwrap_or(our_seq);
+    let missing: Vec<String> =
+        ours.ops.keys().filter(|id| !theirs.contains(*id)).cloned().collect();
+    push(&ours, node_id, missing, config, &mut pacer, &mut call, &mut report).await?;
+
+    // They now hold everything we held
+    let session = Acks { channel_id, deltas, peer, our_seq, their_seq };
+    session.finish(&merged, &mut call, report).await
+}
+
+/// Delta buffer positions both sides of a session reached
+struct Acks<'a> {
+    channel_id: &'a ChannelId,
+    deltas: &'a Mutex<SyncDeltas>,
+    peer: &'a str,
+    our_seq: u64,
+    their_seq: Option<u64>,
+}
+
+impl Acks<'_> {
+    /// End a session that left the peer holding every operation of
+    /// `merged` and us holding every one it had: acknowledge each other's
+    /// delta buffers
+    async fn finish<F, Fut>(
+        self,
+        merged: &Channel,
+        call: &mut F,
+        mut report: SyncReport,
+    ) -> StoreResult<SyncReport>
+    where
+        F: FnMut(SyncStep) -> Fut,
+        Fut: Future<Output = StoreResult<SyncReply>>,
+    {
+        {
+            let mut deltas = self.deltas.lock().unwrap_or_else(|e| e.into_inner());
+            deltas.ack(self.channel_id, self.peer, self.our_seq);
+        }
+        if let Some(seq) = self.their_seq {
+            let target_id = self.channel_id.0.clone();
+            match call(SyncStep::Ack { target_id, seq }).await? {
+                SyncReply::Acked => {}
+                reply => return Err(unexpected_reply(reply)),
+            }
+        }
+        report.acked = Some(field_clock_map(merged));
+        Ok(report)
+    }
+}
+
+/// What a node tracks about the replicas it syncs with, shared by
+/// serve_sync and spawn_anti_entropy
+#[derive(Clone)]
+pub struct SyncReplicas {
+    /// Acknowledgements and quarantine, for tombstone collection
+    pub stability: Arc<Mutex<CausalStability>>,
+
+    /// Operations each replica has yet to acknowledge
+    pub deltas: Arc<Mutex<SyncDeltas>>,
+}
+
+/// Answer `store_sync` requests from `store`
+///
+/// Register the returned sender as the handler for SYNC_METHOD. The task ends
+/// when the handler is unregistered and every sender dropped. Callers are
+/// known to `replicas` by their node ID (see [`peer_node`]).
+pub fn serve_sync(
+    store: Arc<LocalStore>,
+    node_id: String,
+    replicas: SyncReplicas,
+) -> (mpsc::Sender<RpcRequest>, JoinHandle<()>) {
+    let server = SyncServer::new(store, node_id)
+        .with_stability(replicas.stability)
+        .with_deltas(replicas.deltas);
+    let (handler_tx, mut handler_rx) = mpsc::channel::<RpcRequest>(100);
+    let task = tokio::spawn(async move {
+        while let Some(request) = handler_rx.recv().await {
+            let replica = peer_node(&request.peer_id);
+            let result = serde_json::from_value::<SyncStep>(request.params)
+                .map_err(|e| RpcError::invalid_params(&e.to_string()))
+                .and_then(|step| {
+                    server
+                        .handle_from(&replica, step)
+                        .map_err(|e| RpcError::internal_error(&e.to_string()))
+                })
+                .and_then(|reply| {
+                    serde_json::to_value(reply)
+                        .map_err(|e| RpcError::internal_error(&e.to_string()))
+                });
+            let _ = request.response_tx.send(result);
+        }
+    });
+    (handler_tx, task)
+}
+
+/// Node ID of a peer: a node's router peer ID is its node ID's bytes
+pub fn peer_node(peer_id: &PeerId) -> String {
+    String::from_utf8_lossy(peer_id.as_bytes()).into_owned()
+}
+
+/// Send one session step to `peer_id` over the router
+pub async fn call_peer(
+    router: &RouterHandle,
+    peer_id: PeerId,
+    step: SyncStep,
+) -> StoreResult<SyncReply> {
+    let params =
+        serde_json::to_value(step).map_err(|e| StoreError::Serialization(e.to_string()))?;
+    let reply = router
+        .rpc_call(peer_id, SYNC_METHOD.to_string(), params)
+        .await
+        .map_err(|e| StoreError::Sync(e.message))?;
+    serde_json::from_value(reply).map_err(|e| StoreError::Deserialization(e.to_string()))
+}
+
+/// Sync every target of `manager` with each peer as it falls due
+///
+/// Peers are added to `manager` as they connect, under their hex-encoded
+/// peer ID; `replicas` knows them by node ID. Due peers are looked for
+/// every `tick`; sessions with quarantined replicas are skipped. Tombstones
+/// are collected every `cleanup_interval` of the stability config, with
+/// each target's replicas taken from its roster.
+pub fn spawn_anti_entropy(
+    router: RouterHandle,
+    store: Arc<LocalStore>,
+    manager: Arc<Mutex<AntiEntropyManager>>,
+    replicas: SyncReplicas,
+    node_id: String,
+    mut connections: broadcast::Receiver<PeerId>,
+    tick: Duration,
+) -> JoinHandle<()> {
+    let SyncReplicas { stability, deltas } = replicas;
+    tokio::spawn(async move {
+        let mut tick = interval(tick);
+        let cleanup_interval = {
+            let stability = stability.lock().unwrap_or_else(|e| e.into_inner());
+            stability.config().cleanup_interval
+        };
+        let mut next_cleanup = Instant::now() + cleanup_interval;
+        loop {
+            tokio::select! {
+                connected = connections.recv() => match connected {
+                    Ok(peer_id) => {
+                        let peer = hex::encode(peer_id.as_bytes());
+                        let mut manager = manager.lock().unwrap_or_else(|e| e.into_inner());
+                        manager.add_peer(peer, VectorClock::new());
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return,
+                },
+                _ = tick.tick() => {}
+            }
+
+            let (due, targets, config) = {
+                let manager = manager.lock().unwrap_or_else(|e| e.into_inner());
+                let due = manager.due_peers(Instant::now().into_std());
+                (due, manager.targets(), manager.config().clone())
+            };
+            for peer in due {
+                let Ok(peer_bytes) = hex::decode(&peer) else {
+                    continue;
+                };
+                let peer_id = PeerId::from_bytes(peer_bytes);
+                let node = peer_node(&peer_id);
+                let mut failed = false;
+                for target in &targets {
+                    let channel_id = ChannelId(target.clone());
+                    let quarantined = channel(&store, target).is_ok_and(|channel| {
+                        let stability = stability.lock().unwrap_or_else(|e| e.into_inner());
+                        stability.is_quarantined(&channel, &node)
+                    });
+                    if quarantined {
+                        debug!(peer = %peer, channel = %target, "Skipping quarantined replica");
+                        continue;
+                    }
+                    let result = sync_channel(
+                        &store,
+                        &channel_id,
+                        &node_id,
+                        &config,
+                        &deltas,
+                        &node,
+                        |step| call_peer(&router, peer_id.clone(), step),
+                    )
+                    .await;
+                    match result {
+                        Ok(report) => {
+                            debug!(peer = %peer, channel = %target, ?report, "Sync session done");
+                            if let Ok(channel) = channel(&store, target) {
+                                if let Some(acked) = &report.acked {
+                                    let mut stability =
+                                        stability.lock().unwrap_or_else(|e| e.into_inner());
+                                    let now = SystemTime::now();
+                                    let acked = stability
+                                        .update_roster(&channel, &node_id, now)
+                                        .and_then(|_| stability.ack(&channel, &node, acked, now));
+                                    if let Err(e) = acked {
+                                        warn!(channel = %target, error = %e, "Failed to record acknowledgement");
+                                    }
+                                }
+                                let mut manager = manager.lock().unwrap_or_else(|e| e.into_inner());
+                                manager.update_clock(target, summary_clock(&channel));
+                            }
+                        }
+                        Err(e) => {
+                            warn!(peer = %peer, channel = %target, error = %e, "Sync session failed");
+                            failed = true;
+                        }
+                    }
+                }
+
+                let mut manager = manager.lock().unwrap_or_else(|e| e.into_inner());
+                if failed {
+                    manager.record_failure(&peer);
+                } else {
+                    manager.record_success(&peer);
+                }
+            }
+
+            if Instant::now() >= next_cleanup {
+                next_cleanup = Instant::now() + cleanup_interval;
+                for target in &targets {
+                    let channel_id = ChannelId(target.clone());
+                    let now = SystemTime::now();
+                    let mut stability = stability.lock().unwrap_or_else(|e| e.into_inner());
+                    match collect_garbage(&store, &mut stability, &channel_id, &node_id, now) {
+                        Ok(report) => {
+                            for event in &report.events {
+                                warn!(channel = %target, ?event, "Replica left out of cleanup");
+                            }
+                            debug!(channel = %target, purged = report.purged, "Tombstones purged");
+                        }
+                        Err(e) => warn!(channel = %target, error = %e, "Tombstone cleanup failed"),
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core_store::model::{ChannelType, Timestamp, UserId};
+
+    #[test]
+    fn test_operations_survive_a_delta() {
+        let mut channel = Channel::new(
+            ChannelId("general".to_string()),
+            "general".to_string(),
+            ChannelType::Text,
+            UserId("alice".to_string()),
+            Timestamp::from_millis(1_000),
+            "node-a".to_string(),
+        );
+        let bob = UserId("bob".to_string());
+        let mut clock = VectorClock::new();
+        clock.increment("node-a");
+        channel
+            .members
+            .add(bob.clone(), AddId::new("node-a:1".to_string(), 7), clock.clone());
+        channel.members.remove(&bob, clock);
+        channel.set_topic("plans".to_string(), "node-b");
+
+        let ops = ChannelOps::new(&channel, "node-a").unwrap();
+        let ids: Vec<String> = ops.ops.keys().cloned().collect();
+        // name, topic and the tombstone
+        assert_eq!(ids.len(), 3);
+        assert_eq!(ChannelOps::new(&channel, "node-b").unwrap().ops.keys().count(), 3);
+
+        let (delta, covered) = ops.delta("node-a", &ids, 10).unwrap();
+        assert_eq!(covered, 3);
+        let decoder = DeltaDecoder::decode(&delta).unwrap();
+        let mut copy = empty_like(&channel);
+        for op in decoder.operations() {
+            apply_op(&mut copy, op, &decoder).unwrap();
+        }
+
+        let copied = ChannelOps::new(&copy, "node-a").unwrap();
+        assert_eq!(copied.ops.keys().collect::<Vec<_>>(), ops.ops.keys().collect::<Vec<_>>());
+        assert_eq!(summary_clock(&copy), summary_clock(&channel));
+        assert_eq!(copy.get_topic(), Some(&"plans".to_string()));
+        assert!(!copy.has_member(&bob));
+    }
+}
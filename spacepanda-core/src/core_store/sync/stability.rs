@@ -0,0 +1,470 @@
+/*
+    stability.rs - Causal stability tracking and tombstone garbage collection
+
+    An OR-Set keeps a tombstone for every removed add, so that a replica
+    still holding the add can't bring it back on merge. Once every replica
+    of a channel has seen a removal nobody can, and its tombstone can go.
+
+    The replicas of a channel are its member devices (Channel::member_devices,
+    which mirrors the MLS roster), whether or not they are online. Each
+    device records the node it syncs from in Channel::replica_nodes, which
+    is how a sync peer is told apart; a device that hasn't recorded one yet
+    can't acknowledge anything and holds cleanup back like any other
+    replica that hasn't caught up.
+
+    CausalStability records, per channel, the field clocks each device is
+    known to hold every operation of: a sync session that gets through
+    leaves the peer with everything we had (SyncReport::acked). The stable
+    clock of a field is the meet of those clocks over the channel's
+    devices, and `collect_garbage` drops the tombstones whose removal
+    clock it covers.
+
+    A device that goes silent would hold cleanup back forever, so once it
+    hasn't acknowledged anything for `silence_horizon` it is left out of
+    the stable clock, with a StabilityEvent::ReplicaExcluded. Its replica
+    may still hold adds whose tombstones are gone here, so it is
+    quarantined from then on: sync sessions with its node are refused.
+    Readmit it only once it has discarded its copy of the channel.
+
+    Acknowledgements and quarantine are kept in a file next to the store,
+    so neither a silent device's clock nor its quarantine is lost on
+    restart.
+*/
+
+use super::session::gc_fields;
+use crate::config::StoreConfig;
+use crate::core_store::crdt::VectorClock;
+use crate::core_store::model::{Channel, ChannelId};
+use crate::core_store::store::errors::{StoreError, StoreResult};
+use crate::core_store::store::LocalStore;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+/// File name of the tracker's state in the store directory
+pub const STABILITY_FILE: &str = "replicas.bin";
+
+/// Configuration for tombstone garbage collection
+#[derive(Debug, Clone)]
+pub struct StabilityConfig {
+    /// How often tombstones are collected
+    pub cleanup_interval: Duration,
+
+    /// How long a replica may go without acknowledging before it is left
+    /// out of the stable clock and quarantined
+    pub silence_horizon: Duration,
+}
+
+impl Default for StabilityConfig {
+    fn default() -> Self {
+        StabilityConfig::from_store_config(&StoreConfig::default())
+    }
+}
+
+impl StabilityConfig {
+    /// Intervals from the store config
+    pub fn from_store_config(config: &StoreConfig) -> Self {
+        StabilityConfig {
+            cleanup_interval: config.tombstone_cleanup_interval,
+            silence_horizon: config.replica_silence_horizon,
+        }
+    }
+}
+
+/// Events raised while computing stable clocks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StabilityEvent {
+    /// A member device was silent for longer than the horizon; it no
+    /// longer holds back cleanup and sessions with its node are refused
+    ReplicaExcluded { channel_id: ChannelId, replica: String, silent_for: Duration },
+}
+
+/// What one collection did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GcReport {
+    /// Tombstones dropped
+    pub purged: usize,
+
+    /// Replicas excluded on the way
+    pub events: Vec<StabilityEvent>,
+}
+
+/// What a member device has acknowledged
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ReplicaAcks {
+    /// Field clocks it holds every operation of
+    clocks: BTreeMap<String, VectorClock>,
+
+    /// When it last acknowledged (or joined the roster)
+    last_ack: SystemTime,
+}
+
+/// Acknowledgements and quarantine of every channel, as persisted
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ReplicaState {
+    /// Member devices of each channel, by device ID
+    replicas: HashMap<ChannelId, HashMap<String, ReplicaAcks>>,
+
+    /// Devices excluded for silence, per channel
+    quarantined: HashMap<ChannelId, HashSet<String>>,
+}
+
+/// Tracks how far every replica of each channel has caught up
+pub struct CausalStability {
+    config: StabilityConfig,
+
+    /// Where the state is kept, if anywhere
+    path: Option<PathBuf>,
+
+    state: ReplicaState,
+}
+
+impl CausalStability {
+    /// Create a tracker that knows no replicas and keeps nothing on disk
+    pub fn new(config: StabilityConfig) -> Self {
+        CausalStability { config, path: None, state: ReplicaState::default() }
+    }
+
+    /// Open (or create) a tracker whose state is stored at `path`
+    ///
+    /// Use [`STABILITY_FILE`] in the store's data directory.
+    pub fn open(config: StabilityConfig, path: PathBuf) -> StoreResult<Self> {
+        let state = if path.exists() {
+            bincode::deserialize(&fs::read(&path)?)?
+        } else {
+            ReplicaState::default()
+        };
+        Ok(CausalStability { config, path: Some(path), state })
+    }
+
+    /// Configuration the tracker runs with
+    pub fn config(&self) -> &StabilityConfig {
+        &self.config
+    }
+
+    /// Take the channel's member devices, other than those syncing from
+    /// `own_node`, as its replicas
+    ///
+    /// Devices new to the roster start out silent at `now` and hold
+    /// nothing; devices that left are forgotten. Quarantined devices stay
+    /// quarantined.
+    pub fn update_roster(
+        &mut self,
+        channel: &Channel,
+        own_node: &str,
+        now: SystemTime,
+    ) -> StoreResult<()> {
+        let roster: HashSet<String> = channel
+            .member_devices
+            .elements()
+            .into_iter()
+            .map(|device| device.device_id)
+            .filter(|device_id| {
+                channel.replica_node(device_id).map(String::as_str) != Some(own_node)
+            })
+            .collect();
+        let quarantined = self.state.quarantined.get(&channel.id);
+        let replicas = self.state.replicas.entry(channel.id.clone()).or_default();
+
+        let before = replicas.len();
+        replicas.retain(|device_id, _| roster.contains(device_id));
+        let mut changed = replicas.len() != before;
+        for device_id in roster {
+            if quarantined.is_some_and(|devices| devices.contains(&device_id)) {
+                continue;
+            }
+            replicas.entry(device_id).or_insert_with(|| {
+                changed = true;
+                ReplicaAcks { clocks: BTreeMap::new(), last_ack: now }
+            });
+        }
+
+        if changed {
+            self.persist()?;
+        }
+        Ok(())
+    }
+
+    /// Record that the devices syncing from `node` hold every operation of
+    /// a channel's fields up to `clocks`
+    ///
+    /// Only devices on the roster (see [`update_roster`](Self::update_roster))
+    /// count.
+    pub fn ack(
+        &mut self,
+        channel: &Channel,
+        node: &str,
+        clocks: &BTreeMap<String, VectorClock>,
+        now: SystemTime,
+    ) -> StoreResult<()> {
+        let Some(replicas) = self.state.replicas.get_mut(&channel.id) else {
+            return Ok(());
+        };
+        let mut acked = false;
+        for device_id in devices_of(channel, node) {
+            if let Some(acks) = replicas.get_mut(&device_id) {
+                for (path, clock) in clocks {
+                    acks.clocks.entry(path.clone()).or_default().merge(clock);
+                }
+                acks.last_ack = now;
+                acked = true;
+            }
+        }
+
+        if acked {
+            self.persist()?;
+        }
+        Ok(())
+    }
+
+    /// Whether a device syncing from `node` was excluded from a channel for
+    /// silence
+    pub fn is_quarantined(&self, channel: &Channel, node: &str) -> bool {
+        self.state.quarantined.get(&channel.id).is_some_and(|quarantined| {
+            devices_of(channel, node)
+                .iter()
+                .any(|device_id| quarantined.contains(device_id))
+        })
+    }
+
+    /// Let a quarantined device sync a channel again, as a new replica
+    ///
+    /// It must have discarded its copy of the channel first, or adds whose
+    /// tombstones were collected come back.
+    pub fn readmit(&mut self, channel_id: &ChannelId, device_id: &str) -> StoreResult<()> {
+        let readmitted = self
+            .state
+            .quarantined
+            .get_mut(channel_id)
+            .is_some_and(|devices| devices.remove(device_id));
+        if readmitted {
+            self.persist()?;
+        }
+        Ok(())
+    }
+
+    /// Stable clock of each field of a channel: the meet of what every
+    /// replica acknowledged
+    ///
+    /// Replicas silent past the horizon are quarantined first. Without any
+    /// replica nothing is stable; a field some replica hasn't acknowledged
+    /// is left out.
+    pub fn stable_clocks(
+        &mut self,
+        channel_id: &ChannelId,
+        now: SystemTime,
+    ) -> StoreResult<(Option<HashMap<String, VectorClock>>, Vec<StabilityEvent>)> {
+        let horizon = self.config.silence_horizon;
+        let replicas = self.state.replicas.entry(channel_id.clone()).or_default();
+
+        let silent: Vec<(String, Duration)> = replicas
+            .iter()
+            .map(|(replica, acks)| (replica, now.duration_since(acks.last_ack).unwrap_or_default()))
+            .filter(|(_, silent_for)| *silent_for > horizon)
+            .map(|(replica, silent_for)| (replica.clone(), silent_for))
+            .collect();
+        let mut events = Vec::new();
+        for (replica, silent_for) in silent {
+            replicas.remove(&replica);
+            self.state
+                .quarantined
+                .entry(channel_id.clone())
+                .or_default()
+                .insert(replica.clone());
+            events.push(StabilityEvent::ReplicaExcluded {
+                channel_id: channel_id.clone(),
+                replica,
+                silent_for,
+            });
+        }
+        if !events.is_empty() {
+            self.persist()?;
+        }
+
+        let mut acks = self.state.replicas[channel_id].values();
+        let Some(first) = acks.next() else {
+            return Ok((None, events));
+        };
+        let mut stable: HashMap<String, VectorClock> =
+            first.clocks.iter().map(|(path, clock)| (path.clone(), clock.clone())).collect();
+        for replica in acks {
+            stable.retain(|path, clock| match replica.clocks.get(path) {
+                Some(acked) => {
+                    clock.meet(acked);
+                    true
+                }
+                None => false,
+            });
+        }
+        Ok((Some(stable), events))
+    }
+
+    fn persist(&self) -> StoreResult<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        // Atomic write: temp file then rename
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, bincode::serialize(&self.state)?)?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+}
+
+/// Member devices of a channel that sync from `node`
+fn devices_of(channel: &Channel, node: &str) -> Vec<String> {
+    channel
+        .member_devices
+        .elements()
+        .into_iter()
+        .map(|device| device.device_id)
+        .filter(|device_id| channel.replica_node(device_id).map(String::as_str) == Some(node))
+        .collect()
+}
+
+/// Drop the tombstones of a channel that every replica has seen removed
+///
+/// The channel's roster is refreshed first; `own_node` is the node this
+/// replica syncs as.
+pub fn collect_garbage(
+    store: &LocalStore,
+    stability: &mut CausalStability,
+    channel_id: &ChannelId,
+    own_node: &str,
+    now: SystemTime,
+) -> StoreResult<GcReport> {
+    let mut channel = store
+        .get_channel(channel_id)?
+        .ok_or_else(|| StoreError::NotFound(format!("Channel {}", channel_id.0)))?;
+    stability.update_roster(&channel, own_node, now)?;
+
+    let (stable, events) = stability.stable_clocks(channel_id, now)?;
+    let mut report = GcReport { purged: 0, events };
+    let Some(stable) = stable else {
+        return Ok(report);
+    };
+
+    report.purged = gc_fields(&mut channel, &stable);
+    if report.purged > 0 {
+        store.store_channel(&channel)?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core_store::model::{ChannelType, MemberDevice, Timestamp, UserId};
+    use tempfile::TempDir;
+
+    fn clocks(path: &str, counter: u64) -> BTreeMap<String, VectorClock> {
+        let mut clock = VectorClock::new();
+        clock.set("node-a", counter);
+        BTreeMap::from([(path.to_string(), clock)])
+    }
+
+    /// A channel whose member device `device-x` syncs from `node-x`, for
+    /// each x in `names`
+    fn channel(names: &[&str]) -> Channel {
+        let mut channel = Channel::new(
+            ChannelId("general".to_string()),
+            "general".to_string(),
+            ChannelType::Text,
+            UserId("alice".to_string()),
+            Timestamp::from_millis(1_000),
+            "node-a".to_string(),
+        );
+        for name in names {
+            let device = MemberDevice {
+                user_id: UserId(name.to_string()),
+                device_id: format!("device-{}", name),
+            };
+            channel.add_member_device(device, Timestamp::from_millis(1_000), "node-a");
+            channel.set_replica_node(&format!("device-{}", name), &format!("node-{}", name));
+        }
+        channel
+    }
+
+    #[test]
+    fn test_stable_clock_is_the_slowest_replica() {
+        let mut stability = CausalStability::new(StabilityConfig::default());
+        let channel = channel(&["a", "b", "c", "d"]);
+        let now = SystemTime::now();
+
+        assert!(stability.stable_clocks(&channel.id, now).unwrap().0.is_none());
+        stability.update_roster(&channel, "node-a", now).unwrap();
+        stability.ack(&channel, "node-b", &clocks("channel.members", 5), now).unwrap();
+        stability.ack(&channel, "node-c", &clocks("channel.members", 3), now).unwrap();
+
+        // D hasn't acknowledged anything yet, even without ever syncing
+        let (stable, events) = stability.stable_clocks(&channel.id, now).unwrap();
+        assert!(stable.unwrap().is_empty());
+        assert!(events.is_empty());
+
+        stability.ack(&channel, "node-d", &clocks("channel.members", 4), now).unwrap();
+        let (stable, _) = stability.stable_clocks(&channel.id, now).unwrap();
+        assert_eq!(stable.unwrap()["channel.members"].get("node-a"), 3);
+
+        // Nodes that hold no member device don't count
+        stability.ack(&channel, "node-e", &clocks("channel.members", 1), now).unwrap();
+        let (stable, _) = stability.stable_clocks(&channel.id, now).unwrap();
+        assert_eq!(stable.unwrap()["channel.members"].get("node-a"), 3);
+    }
+
+    #[test]
+    fn test_silent_replica_is_excluded_and_quarantined() {
+        let config =
+            StabilityConfig { silence_horizon: Duration::from_secs(60), ..Default::default() };
+        let mut stability = CausalStability::new(config);
+        let channel = channel(&["a", "b", "c"]);
+        let start = SystemTime::now();
+        stability.update_roster(&channel, "node-a", start).unwrap();
+        stability.ack(&channel, "node-b", &clocks("channel.members", 1), start).unwrap();
+
+        let later = start + Duration::from_secs(61);
+        stability.ack(&channel, "node-c", &clocks("channel.members", 7), later).unwrap();
+        let (stable, events) = stability.stable_clocks(&channel.id, later).unwrap();
+        assert_eq!(stable.unwrap()["channel.members"].get("node-a"), 7);
+        let [StabilityEvent::ReplicaExcluded { replica, .. }] = &events[..] else {
+            panic!("expected one exclusion, got {:?}", events);
+        };
+        assert_eq!(replica, "device-b");
+        assert!(stability.is_quarantined(&channel, "node-b"));
+
+        // Its acknowledgements no longer count, nor does the roster bring it back
+        stability.update_roster(&channel, "node-a", later).unwrap();
+        stability.ack(&channel, "node-b", &clocks("channel.members", 1), later).unwrap();
+        let (stable, _) = stability.stable_clocks(&channel.id, later).unwrap();
+        assert_eq!(stable.unwrap()["channel.members"].get("node-a"), 7);
+
+        stability.readmit(&channel.id, "device-b").unwrap();
+        assert!(!stability.is_quarantined(&channel, "node-b"));
+        stability.update_roster(&channel, "node-a", later).unwrap();
+        assert!(stability.stable_clocks(&channel.id, later).unwrap().0.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_state_survives_restart() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(STABILITY_FILE);
+        let config =
+            StabilityConfig { silence_horizon: Duration::from_secs(60), ..Default::default() };
+        let channel = channel(&["a", "b", "c"]);
+        let start = SystemTime::now();
+
+        let mut stability = CausalStability::open(config.clone(), path.clone()).unwrap();
+        stability.update_roster(&channel, "node-a", start).unwrap();
+        stability.ack(&channel, "node-b", &clocks("channel.members", 4), start).unwrap();
+        let later = start + Duration::from_secs(61);
+        stability.ack(&channel, "node-c", &clocks("channel.members", 6), later).unwrap();
+        stability.stable_clocks(&channel.id, later).unwrap();
+        drop(stability);
+
+        let mut stability = CausalStability::open(config, path).unwrap();
+        assert!(stability.is_quarantined(&channel, "node-b"));
+        let (stable, _) = stability.stable_clocks(&channel.id, later).unwrap();
+        assert_eq!(stable.unwrap()["channel.members"].get("node-a"), 6);
+    }
+}
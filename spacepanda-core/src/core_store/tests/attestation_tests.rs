@@ -0,0 +1,128 @@
+/*
+    attestation_tests.rs - Startup integrity attestation
+
+    A data directory is started a few times, then its sidecar is swapped for
+    an older copy as a restore from backup would. The next startup must be
+    refused, and only start when the rollback is explicitly accepted.
+*/
+
+use crate::core_identity::keypair::{KeyType, Keypair};
+use crate::core_store::store::attestation::{ATTESTATION_FILE, AUDIT_LOG_FILE};
+use crate::core_store::store::{
+    AttestationAnomaly, SealedAttestation, StartupAttestation, StoreError,
+};
+use std::path::Path;
+use tempfile::tempdir;
+
+fn startup(dir: &Path, version: &str) -> StartupAttestation {
+    StartupAttestation::new(dir, version)
+}
+
+fn logged(dir: &Path) -> Vec<SealedAttestation> {
+    std::fs::read_to_string(dir.join(AUDIT_LOG_FILE))
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_startups_are_counted_and_chained() {
+    let dir = tempdir().unwrap();
+    let key = Keypair::generate(KeyType::Ed25519);
+
+    for expected in 1..=3 {
+        let outcome = startup(dir.path(), "0.1.0").attest(&key, false).unwrap();
+        assert!(outcome.anomalies.is_empty());
+        assert_eq!(outcome.attestation.record.counter, expected);
+        assert!(outcome.attestation.verify());
+    }
+
+    let log = logged(dir.path());
+    assert_eq!(log.len(), 3);
+    assert!(log.iter().all(|a| a.record.data_dir_id == log[0].record.data_dir_id));
+    assert_eq!(log[0].record.previous, None);
+    assert_eq!(log[2].record.previous, Some(log[1].digest().unwrap()));
+
+    // A newer binary is an upgrade, not an anomaly
+    let outcome = startup(dir.path(), "0.2.0").attest(&key, false).unwrap();
+    assert!(outcome.anomalies.is_empty());
+}
+
+#[test]
+fn test_restored_sidecar_requires_accept_rollback() {
+    let dir = tempdir().unwrap();
+    let key = Keypair::generate(KeyType::Ed25519);
+    let sidecar = dir.path().join(ATTESTATION_FILE);
+
+    startup(dir.path(), "0.1.0").attest(&key, false).unwrap();
+    let backup = std::fs::read(&sidecar).unwrap();
+    startup(dir.path(), "0.1.0").attest(&key, false).unwrap();
+    startup(dir.path(), "0.1.0").attest(&key, false).unwrap();
+
+    // Put back the sidecar from after the first startup
+    std::fs::write(&sidecar, &backup).unwrap();
+    let expected = AttestationAnomaly::CounterRegressed { sidecar: 1, logged: 3 };
+    assert_eq!(startup(dir.path(), "0.1.0").check(&key).unwrap(), vec![expected.clone()]);
+
+    let refused = startup(dir.path(), "0.1.0").attest(&key, false);
+    assert!(matches!(refused, Err(StoreError::PermissionDenied(_))));
+    assert_eq!(logged(dir.path()).len(), 3);
+    assert_eq!(std::fs::read(&sidecar).unwrap(), backup);
+
+    // Accepting the rollback starts and records what was accepted
+    let outcome = startup(dir.path(), "0.1.0").attest(&key, true).unwrap();
+    assert_eq!(outcome.anomalies, vec![expected.clone()]);
+    assert_eq!(outcome.attestation.record.counter, 4);
+    let last = logged(dir.path()).pop().unwrap();
+    assert_eq!(last.record.accepted_anomalies, vec![expected.to_string()]);
+
+    // Both copies agree again afterwards
+    assert!(startup(dir.path(), "0.1.0").attest(&key, false).unwrap().anomalies.is_empty());
+}
+
+#[test]
+fn test_downgrades_and_tampering_are_refused() {
+    let dir = tempdir().unwrap();
+    let key = Keypair::generate(KeyType::Ed25519);
+    startup(dir.path(), "0.10.0").attest(&key, false).unwrap();
+
+    // Versions compare numerically, so 0.9 is older than 0.10
+    let anomalies = startup(dir.path(), "0.9.5").check(&key).unwrap();
+    assert_eq!(
+        anomalies,
+        vec![AttestationAnomaly::BinaryDowngrade {
+            previous: "0.10.0".to_string(),
+            current: "0.9.5".to_string(),
+        }]
+    );
+
+    let older_schema = startup(dir.path(), "0.10.0").with_schema_version("mls", 1);
+    assert!(matches!(
+        older_schema.check(&key).unwrap().as_slice(),
+        [AttestationAnomaly::SchemaDowngrade { schema, previous: _, current: 1 }] if schema == "mls"
+    ));
+
+    let other_key = Keypair::generate(KeyType::Ed25519);
+    assert_eq!(
+        startup(dir.path(), "0.10.0").check(&other_key).unwrap(),
+        vec![AttestationAnomaly::SignerChanged]
+    );
+
+    // A forged sidecar no longer verifies, and no longer matches the log
+    let sidecar = dir.path().join(ATTESTATION_FILE);
+    let mut forged: SealedAttestation =
+        serde_json::from_slice(&std::fs::read(&sidecar).unwrap()).unwrap();
+    forged.record.binary_version = "0.1.0".to_string();
+    std::fs::write(&sidecar, serde_json::to_vec(&forged).unwrap()).unwrap();
+    let anomalies = startup(dir.path(), "0.10.0").check(&key).unwrap();
+    assert!(anomalies.contains(&AttestationAnomaly::InvalidSignature { file: ATTESTATION_FILE }));
+    assert!(anomalies.contains(&AttestationAnomaly::Diverged { counter: 1 }));
+
+    // Losing the sidecar is an anomaly too
+    std::fs::remove_file(&sidecar).unwrap();
+    assert_eq!(
+        startup(dir.path(), "0.10.0").check(&key).unwrap(),
+        vec![AttestationAnomaly::SidecarMissing { logged: 1 }]
+    );
+}
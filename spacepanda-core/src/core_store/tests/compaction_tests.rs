@@ -0,0 +1,241 @@
+/*
+    compaction_tests.rs - Store compaction and crash recovery
+
+    Compaction is cut short after each of its stages, as if the process had
+    been killed there, and the store is reopened. Whatever the stage, the
+    reloaded state must match what was committed, with every message once,
+    and the commit log must keep numbering where it left off.
+*/
+
+use crate::core_store::model::{
+    Channel, ChannelId, ChannelType, Message, MessageId, Space, SpaceId, Timestamp, UserId,
+};
+use crate::core_store::store::local_store::CompactionStage;
+use crate::core_store::store::observer::{ObserverFilter, StoreEvent, StoreObserver};
+use crate::core_store::store::{LocalStore, LocalStoreConfig};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use tempfile::TempDir;
+
+fn open_store(dir: &TempDir) -> LocalStore {
+    LocalStore::new(LocalStoreConfig {
+        data_dir: dir.path().to_path_buf(),
+        enable_encryption: false,
+        enable_compaction: false,
+        ..Default::default()
+    })
+    .unwrap()
+}
+
+fn channel(name: &str) -> Channel {
+    Channel::new(
+        ChannelId(name.to_string()),
+        name.to_string(),
+        ChannelType::Text,
+        UserId("alice".to_string()),
+        Timestamp::from_millis(1_000),
+        "node-a".to_string(),
+    )
+}
+
+fn message(channel_id: &ChannelId, id: &str, at: u64) -> Message {
+    Message::new(
+        MessageId(id.to_string()),
+        channel_id.clone(),
+        UserId("alice".to_string()),
+        format!("body of {}", id).into_bytes(),
+        Timestamp::from_millis(at),
+    )
+}
+
+/// Everything `load` restores, in a comparable form
+#[derive(Debug, PartialEq, Eq)]
+struct State {
+    spaces: Vec<String>,
+    channels: Vec<String>,
+    messages: BTreeMap<String, Vec<(String, Vec<u8>)>>,
+}
+
+fn state(store: &LocalStore) -> State {
+    let mut spaces: Vec<String> = store.list_spaces().unwrap().into_iter().map(|s| s.0).collect();
+    spaces.sort();
+    let mut channels: Vec<String> =
+        store.list_channels().unwrap().into_iter().map(|c| c.0).collect();
+    channels.sort();
+    let messages = channels
+        .iter()
+        .map(|name| {
+            let messages = store.get_channel_messages(&ChannelId(name.clone())).unwrap();
+            let messages = messages
+                .iter()
+                .map(|m| (m.id.0.clone(), m.current_content().to_vec()))
+                .collect();
+            (name.clone(), messages)
+        })
+        .collect();
+    State { spaces, channels, messages }
+}
+
+/// Spaces, channels, messages, an edit and a removed channel
+fn populate(store: &LocalStore, round: u64) {
+    let space = Space::new(
+        SpaceId(format!("space-{}", round)),
+        format!("Space {}", round),
+        UserId("alice".to_string()),
+        Timestamp::from_millis(1_000),
+        "node-a".to_string(),
+    );
+    store.store_space(&space).unwrap();
+
+    let general = channel(&format!("general-{}", round));
+    let scratch = channel(&format!("scratch-{}", round));
+    store.store_channel(&general).unwrap();
+    store.store_channel(&scratch).unwrap();
+
+    let mut first = message(&general.id, &format!("m{}-0", round), 10);
+    store.store_message(&first).unwrap();
+    for i in 1..5 {
+        store
+            .store_message(&message(&general.id, &format!("m{}-{}", round, i), 10 + i))
+            .unwrap();
+    }
+    store.store_message(&message(&scratch.id, &format!("s{}", round), 20)).unwrap();
+
+    first.add_edit(Timestamp::from_millis(30), UserId("alice".to_string()), b"edited".to_vec());
+    store.store_message(&first).unwrap();
+    store.remove_channel(&scratch.id).unwrap();
+}
+
+/// Records the sequence numbers a replay delivers
+struct Seqs(Mutex<Vec<u64>>);
+
+impl StoreObserver for Seqs {
+    fn on_commit(&self, event: &StoreEvent) {
+        self.0.lock().unwrap().push(event.seq);
+    }
+}
+
+/// Reopen after a crash and check nothing was lost or applied twice
+fn assert_recovers(dir: &TempDir, expected: &State, checkpoint: u64) {
+    let store = open_store(dir);
+    store.load().unwrap();
+    assert_eq!(&state(&store), expected);
+    assert_eq!(store.checkpoint().unwrap(), checkpoint);
+
+    // The log replays each remaining entry once, in order
+    let seqs = Seqs(Mutex::new(Vec::new()));
+    store.observe_from(0, &ObserverFilter::all(), &seqs).unwrap();
+    let seqs = seqs.0.into_inner().unwrap();
+    assert!(seqs.windows(2).all(|pair| pair[0] < pair[1]));
+
+    // And the store carries on: write, compact fully, reload
+    store
+        .store_message(&message(&ChannelId("general-1".to_string()), "after", 99))
+        .unwrap();
+    store.compact().unwrap();
+    let expected_after = state(&store);
+    drop(store);
+
+    let store = open_store(dir);
+    store.load().unwrap();
+    assert_eq!(state(&store), expected_after);
+    assert_eq!(store.checkpoint().unwrap(), checkpoint + 1);
+    assert_eq!(std::fs::read_dir(dir.path().join("snapshots")).unwrap().count(), 2);
+}
+
+#[test]
+fn test_crash_at_each_compaction_stage() {
+    let stages = [
+        CompactionStage::SnapshotWritten,
+        CompactionStage::SnapshotPublished,
+        CompactionStage::SnapshotVerified,
+        CompactionStage::LogTruncated,
+    ];
+
+    for stage in stages {
+        let dir = TempDir::new().unwrap();
+        let (expected, checkpoint) = {
+            let store = open_store(&dir);
+            populate(&store, 1);
+            store.compact().unwrap();
+            populate(&store, 2);
+
+            let expected = state(&store);
+            let checkpoint = store.checkpoint().unwrap();
+            store.compact_until(Some(stage)).unwrap();
+            (expected, checkpoint)
+        };
+        assert_eq!(expected.channels, vec!["general-1", "general-2"], "{:?}", stage);
+        assert_eq!(expected.messages["general-2"].len(), 5);
+        assert_eq!(expected.messages["general-2"][0].1, b"edited");
+
+        assert_recovers(&dir, &expected, checkpoint);
+    }
+}
+
+#[test]
+fn test_compaction_truncates_log_and_survives_reopen() {
+    let dir = TempDir::new().unwrap();
+    let (expected, checkpoint) = {
+        let store = open_store(&dir);
+        populate(&store, 1);
+        populate(&store, 2);
+        assert!(store.stats().unwrap().log_size > 0);
+
+        store.compact().unwrap();
+        assert_eq!(store.stats().unwrap().log_size, 0);
+        (state(&store), store.checkpoint().unwrap())
+    };
+
+    assert_recovers(&dir, &expected, checkpoint);
+}
+
+#[test]
+fn test_compaction_runs_when_log_outgrows_limit() {
+    let dir = TempDir::new().unwrap();
+    let config = LocalStoreConfig {
+        data_dir: dir.path().to_path_buf(),
+        enable_encryption: false,
+        enable_compaction: true,
+        max_log_size: 2_000,
+        ..Default::default()
+    };
+
+    let expected = {
+        let store = LocalStore::new(config.clone()).unwrap();
+        let general = channel("general");
+        store.store_channel(&general).unwrap();
+        for i in 0..100 {
+            store.store_message(&message(&general.id, &format!("m{}", i), i)).unwrap();
+            assert!(store.stats().unwrap().log_size <= 2_000);
+        }
+        state(&store)
+    };
+    assert_eq!(expected.messages["general"].len(), 100);
+
+    let store = LocalStore::new(config).unwrap();
+    store.load().unwrap();
+    assert_eq!(state(&store), expected);
+    assert_eq!(store.checkpoint().unwrap(), 101);
+}
+
+#[test]
+fn test_compaction_on_store_opened_without_load() {
+    let dir = TempDir::new().unwrap();
+    {
+        let store = open_store(&dir);
+        populate(&store, 1);
+    }
+
+    // The caches of this instance only hold what it wrote itself
+    let store = open_store(&dir);
+    populate(&store, 2);
+    store.compact().unwrap();
+    let expected = state(&store);
+    assert_eq!(expected.channels, vec!["general-1", "general-2"]);
+    drop(store);
+
+    let store = open_store(&dir);
+    store.load().unwrap();
+    assert_eq!(state(&store), expected);
+}
@@ -6,13 +6,19 @@
     2. Full space replica convergence
     3. Commutative and associative merge properties
     4. Idempotent merge operations
+    5. Concurrent member device add/remove
+    6. Concurrent invite use and revocation
+    7. Concurrent reactions and unreactions
 */
 
 use crate::core_store::crdt::{AddId, Crdt, LWWRegister, VectorClock};
 use crate::core_store::model::types::{
-    ChannelId, ChannelType, PermissionLevel, SpaceId, Timestamp, UserId,
+    ChannelId, ChannelType, MessageId, PermissionLevel, SpaceId, Timestamp, UserId,
 };
-use crate::core_store::model::{Channel, Role, Space};
+use crate::core_store::model::{
+    Channel, InviteRecord, MemberDevice, Message, Role, Space, WebhookRecord,
+};
+use crate::core_store::sync::apply_remote::{apply_remote_to_channel, apply_remote_to_message};
 
 #[test]
 fn test_full_channel_replica_convergence() {
@@ -306,3 +312,188 @@ fn test_three_way_merge_convergence() {
     assert!(replica1.has_member(&charlie));
     assert_eq!(replica1.get_members().len(), 3);
 }
+
+#[test]
+fn test_concurrent_member_device_add_remove_converges() {
+    let channel_id = ChannelId::generate();
+    let creator = UserId::generate();
+    let now = Timestamp::now();
+    let bob = MemberDevice { user_id: UserId::generate(), device_id: "phone".to_string() };
+
+    let mut replica1 = Channel::new(
+        channel_id.clone(),
+        "General".to_string(),
+        ChannelType::Text,
+        creator.clone(),
+        now,
+        "node1".to_string(),
+    );
+    replica1.add_member_device(bob.clone(), Timestamp::from_millis(1_000), "node1");
+
+    // Both replicas start from the same state
+    let mut replica2 = replica1.clone();
+
+    // Concurrently: replica1 removes Bob, replica2 re-adds him (e.g. rejoin)
+    replica1.remove_member_device(&bob, "node1");
+    replica2.add_member_device(bob.clone(), Timestamp::from_millis(2_000), "node2");
+    assert!(!replica1.has_member(&bob.user_id));
+
+    let snapshot1 = replica1.clone();
+    apply_remote_to_channel(&mut replica1, &replica2).unwrap();
+    apply_remote_to_channel(&mut replica2, &snapshot1).unwrap();
+
+    // The unobserved add survives the remove on both replicas
+    for replica in [&replica1, &replica2] {
+        assert_eq!(
+            replica.get_member_devices(),
+            vec![(bob.clone(), Timestamp::from_millis(2_000))]
+        );
+        assert!(replica.has_member(&bob.user_id));
+    }
+
+    // A remove that has observed every add wins everywhere
+    replica2.remove_member_device(&bob, "node2");
+    apply_remote_to_channel(&mut replica1, &replica2).unwrap();
+    assert!(replica1.get_member_devices().is_empty());
+    assert!(!replica1.has_member(&bob.user_id));
+}
+
+#[test]
+fn test_concurrent_invite_use_and_revoke_converges() {
+    let creator = UserId::generate();
+    let mut replica1 = Channel::new(
+        ChannelId::generate(),
+        "General".to_string(),
+        ChannelType::Text,
+        creator.clone(),
+        Timestamp::now(),
+        "node1".to_string(),
+    );
+    let record = InviteRecord {
+        invite_id: "invite-1".to_string(),
+        invitee: b"bob".to_vec(),
+        issued_by: creator,
+        created_at: Timestamp::now(),
+        expires_at: None,
+        max_uses: Some(1),
+        uses: 0,
+    };
+    replica1.put_invite(record.clone(), "node1");
+
+    let mut replica2 = replica1.clone();
+
+    // Concurrently: replica1 revokes, replica2 records a use
+    replica1.revoke_invite("invite-1", "node1");
+    replica2.put_invite(InviteRecord { uses: 1, ..record }, "node2");
+
+    let snapshot1 = replica1.clone();
+    apply_remote_to_channel(&mut replica1, &replica2).unwrap();
+    apply_remote_to_channel(&mut replica2, &snapshot1).unwrap();
+
+    // The use is kept, and so is the revocation
+    for replica in [&replica1, &replica2] {
+        assert_eq!(replica.get_invite("invite-1").unwrap().uses, 1);
+        assert!(replica.is_invite_revoked("invite-1"));
+        assert_eq!(replica.get_invites().len(), 1);
+    }
+}
+
+#[test]
+fn test_concurrent_webhook_rotation_and_revoke_converges() {
+    let creator = UserId::generate();
+    let mut replica1 = Channel::new(
+        ChannelId::generate(),
+        "Alerts".to_string(),
+        ChannelType::Text,
+        creator.clone(),
+        Timestamp::now(),
+        "node1".to_string(),
+    );
+    let record = WebhookRecord {
+        webhook_id: "webhook-1".to_string(),
+        name: "CI".to_string(),
+        user_id: UserId("webhook:webhook-1".to_string()),
+        token_hash: "old".to_string(),
+        created_by: creator,
+        created_at: Timestamp::now(),
+        rotated_at: None,
+        max_content_bytes: 4096,
+        max_posts_per_minute: 30,
+    };
+    replica1.put_webhook(record.clone(), "node1");
+
+    let mut replica2 = replica1.clone();
+
+    // Concurrently: replica1 revokes, replica2 rotates the token
+    replica1.revoke_webhook("webhook-1", "node1");
+    replica2.put_webhook(
+        WebhookRecord {
+            token_hash: "new".to_string(),
+            rotated_at: Some(Timestamp::now()),
+            ..record
+        },
+        "node2",
+    );
+
+    let snapshot1 = replica1.clone();
+    apply_remote_to_channel(&mut replica1, &replica2).unwrap();
+    apply_remote_to_channel(&mut replica2, &snapshot1).unwrap();
+
+    // The rotation is kept, and the revocation still wins
+    for replica in [&replica1, &replica2] {
+        assert_eq!(replica.get_webhook("webhook-1").unwrap().token_hash, "new");
+        assert!(replica.is_webhook_revoked("webhook-1"));
+        assert_eq!(replica.get_webhooks().len(), 1);
+    }
+}
+
+#[test]
+fn test_concurrent_reactions_converge() {
+    let alice = UserId::generate();
+    let bob = UserId::generate();
+    let thumbs_up = "👍".to_string();
+    let mut replica1 = Message::new(
+        MessageId::generate(),
+        ChannelId::generate(),
+        alice.clone(),
+        b"lunch?".to_vec(),
+        Timestamp::now(),
+    );
+    replica1
+        .add_reaction(alice.clone(), thumbs_up.clone(), AddId::new("alice-phone".to_string(), 1))
+        .unwrap();
+
+    // Both replicas start from the same state
+    let mut replica2 = replica1.clone();
+
+    // Concurrently: Alice unreacts on her phone and reacts again on her
+    // laptop, and Bob reacts
+    let observed = replica1.reaction_add_ids(&alice, &thumbs_up);
+    replica1.remove_reaction(alice.clone(), thumbs_up.clone(), &observed);
+    replica2
+        .add_reaction(alice.clone(), thumbs_up.clone(), AddId::new("alice-laptop".to_string(), 2))
+        .unwrap();
+    replica2
+        .add_reaction(bob.clone(), "🎉".to_string(), AddId::new("bob-phone".to_string(), 3))
+        .unwrap();
+
+    let snapshot1 = replica1.clone();
+    apply_remote_to_message(&mut replica1, &replica2).unwrap();
+    apply_remote_to_message(&mut replica2, &snapshot1).unwrap();
+
+    // The laptop's add wasn't observed by the remove, so it survives
+    let mut expected = vec![(alice.clone(), thumbs_up.clone()), (bob.clone(), "🎉".to_string())];
+    expected.sort();
+    for replica in [&replica1, &replica2] {
+        let mut reactions = replica.reactions();
+        reactions.sort();
+        assert_eq!(reactions, expected);
+    }
+
+    // A remove that has observed every add wins everywhere
+    let observed = replica2.reaction_add_ids(&alice, &thumbs_up);
+    assert_eq!(observed.len(), 1);
+    replica2.remove_reaction(alice.clone(), thumbs_up, &observed);
+    apply_remote_to_message(&mut replica1, &replica2).unwrap();
+    assert_eq!(replica1.reactions(), vec![(bob, "🎉".to_string())]);
+}
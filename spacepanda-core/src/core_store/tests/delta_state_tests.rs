@@ -0,0 +1,92 @@
+/*
+    delta_state_tests.rs - Payload size of delta-state sync
+
+    A replica of a 10,000-element OR-Set that is in sync with its peer
+    takes 10 more adds. The next sync has to cost what 10 adds cost, not
+    what 10,000 elements do, compressed or not.
+*/
+
+use crate::config::StoreConfig;
+use crate::core_store::crdt::{AddId, Crdt, ORSet};
+use crate::core_store::sync::{apply_payload, DeltaBuffer, DeltaStateConfig};
+
+const ELEMENTS: u64 = 10_000;
+
+/// A 10,000-element set, its buffer, and a peer replica that has caught up
+fn synced(config: DeltaStateConfig) -> (ORSet<String>, DeltaBuffer<ORSet<String>>, ORSet<String>) {
+    let mut set = ORSet::new();
+    let mut buffer = DeltaBuffer::new(config);
+    for n in 0..ELEMENTS {
+        add(&mut set, &mut buffer, n);
+    }
+
+    let mut replica = ORSet::new();
+    let payload = buffer.encode_for("peer", &set).unwrap();
+    let (acked, full) = apply_payload(&mut replica, &payload).unwrap();
+    assert!(full, "a new peer gets the full state");
+    buffer.ack("peer", &acked);
+    (set, buffer, replica)
+}
+
+fn add(set: &mut ORSet<String>, buffer: &mut DeltaBuffer<ORSet<String>>, n: u64) {
+    let mut clock = set.vector_clock().clone();
+    clock.increment("node-a");
+    let add_id = AddId::new("node-a".to_string(), n);
+    buffer.record(set.add_delta(format!("member-{:05}", n), add_id, clock));
+}
+
+/// Size of the payload that catches the peer up after `adds` more adds
+///
+/// Also returns the size of the full state, for comparison.
+fn payload_after(config: &DeltaStateConfig, adds: u64) -> (usize, usize) {
+    let (mut set, mut buffer, mut replica) = synced(config.clone());
+    for n in ELEMENTS..ELEMENTS + adds {
+        add(&mut set, &mut buffer, n);
+    }
+
+    let payload = buffer.encode_for("peer", &set).unwrap();
+    let (acked, full) = apply_payload(&mut replica, &payload).unwrap();
+    assert!(!full);
+    assert_eq!(&acked, set.vector_clock());
+    assert_eq!(replica.len(), set.len());
+
+    let full_state = buffer.encode_for("new peer", &set).unwrap();
+    (payload.len(), full_state.len())
+}
+
+#[test]
+fn test_ten_adds_to_ten_thousand_elements_cost_ten_adds() {
+    for enable_compression in [false, true] {
+        let store_config = StoreConfig { enable_compression, ..Default::default() };
+        let config = DeltaStateConfig::from_store_config(&store_config);
+
+        let (one, _) = payload_after(&config, 1);
+        let (ten, full) = payload_after(&config, 10);
+        let (hundred, _) = payload_after(&config, 100);
+        println!(
+            "compression {}: 1 add {} B, 10 adds {} B, 100 adds {} B, full state {} B",
+            enable_compression, one, ten, hundred, full
+        );
+
+        // Proportional to the adds, nowhere near the full state
+        assert!(ten <= 10 * one, "10 adds took {} bytes, 1 add {}", ten, one);
+        assert!(hundred >= 5 * ten, "100 adds took {} bytes, 10 adds {}", hundred, ten);
+        assert!(ten * 100 < full, "10 adds took {} bytes, the full state {}", ten, full);
+    }
+}
+
+#[test]
+fn test_full_state_after_buffer_truncation() {
+    let config = DeltaStateConfig { max_deltas: 50, ..Default::default() };
+    let (mut set, mut buffer, mut replica) = synced(config);
+
+    // The peer falls behind by more than the buffer holds
+    for n in ELEMENTS..ELEMENTS + 60 {
+        add(&mut set, &mut buffer, n);
+    }
+    let payload = buffer.encode_for("peer", &set).unwrap();
+    let (acked, full) = apply_payload(&mut replica, &payload).unwrap();
+    assert!(full);
+    assert_eq!(&acked, set.vector_clock());
+    assert_eq!(replica.len(), set.len());
+}
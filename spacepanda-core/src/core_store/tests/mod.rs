@@ -11,10 +11,17 @@
     - Edge cases and tricky scenarios
 */
 
+pub mod attestation_tests;
+pub mod compaction_tests;
 pub mod convergence_tests;
 pub mod crdt_tests;
+pub mod delta_state_tests;
 pub mod model_tests;
+pub mod oplog_export_tests;
 pub mod persistence_tests;
+pub mod sync_session_tests;
+pub mod tombstone_gc_tests;
+pub mod wal_tests;
 
 // Edge case tests
 pub mod lww_edge_cases;
@@ -7,13 +7,14 @@
     3. Member role assignments converge deterministically
     4. Identity metadata is preserved across merges
     5. Permission checks work correctly
+    6. Custom role capability sets converge
 */
 
 use crate::core_store::crdt::{AddId, Crdt, LWWRegister, VectorClock};
 use crate::core_store::model::types::{
     ChannelId, ChannelType, IdentityMeta, MessageId, PermissionLevel, SpaceId, Timestamp, UserId,
 };
-use crate::core_store::model::{Channel, Role, Space};
+use crate::core_store::model::{Channel, Permission, PermissionSet, Role, Space};
 
 #[test]
 fn test_channel_metadata_converges() {
@@ -241,6 +242,52 @@ fn test_non_owner_uses_role_permissions() {
     assert_eq!(space.get_user_permission_level(&alice), Some(PermissionLevel::member()));
 }
 
+#[test]
+fn test_custom_role_permissions_converge() {
+    let space_id = SpaceId::generate();
+    let owner = UserId::generate();
+    let now = Timestamp::now();
+
+    let mut space1 =
+        Space::new(space_id.clone(), "Space".to_string(), owner.clone(), now, "node1".to_string());
+    let mut space2 = space1.clone();
+
+    // Both replicas define the same custom role; the later write wins
+    let invite_only = PermissionSet::from(Permission::Invite);
+    let invite_and_pin = invite_only.with(Permission::PinMessages);
+    let role1 = Role::with_permissions("greeter".to_string(), invite_only, "node1".to_string());
+    std::thread::sleep(std::time::Duration::from_millis(2));
+    let role2 = Role::with_permissions("greeter".to_string(), invite_and_pin, "node2".to_string());
+
+    let mut vc = VectorClock::new();
+    vc.increment("node1");
+    space1
+        .roles
+        .put("greeter".to_string(), role1, AddId::new("node1".to_string(), 1), vc.clone());
+    space2
+        .roles
+        .put("greeter".to_string(), role2, AddId::new("node2".to_string(), 1), vc.clone());
+
+    let alice = UserId::generate();
+    let mut role_reg = LWWRegister::new();
+    role_reg.set("greeter".to_string(), now.as_millis(), "node1".to_string(), vc.clone());
+    space1
+        .member_roles
+        .put(alice.clone(), role_reg, AddId::new(alice.0.clone(), 1), vc);
+
+    space1.roles.merge_nested(&space2.roles).unwrap();
+
+    assert_eq!(space1.get_user_permissions(&alice), invite_and_pin);
+    assert!(space1.user_has_permission(&alice, Permission::Invite));
+    assert!(!space1.user_has_permission(&alice, Permission::Kick));
+    assert_eq!(space1.get_user_permissions(&owner), PermissionSet::all());
+
+    // Roles created before capability sets fall back to their permission level
+    let legacy = Role::new("Mod".to_string(), PermissionLevel::moderator(), "node1".to_string());
+    assert!(legacy.get_permissions().contains(Permission::Kick));
+    assert!(!legacy.get_permissions().contains(Permission::ManageRoles));
+}
+
 #[test]
 fn test_channel_pinned_messages_merge() {
     let channel_id = ChannelId::generate();
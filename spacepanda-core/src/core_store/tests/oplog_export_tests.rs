@@ -0,0 +1,221 @@
+/*
+    oplog_export_tests.rs - Oplog export and diff
+
+    Two replicas of a channel are driven apart on purpose; exporting both
+    and diffing the exports must pinpoint the operation one of them lacks.
+*/
+
+use crate::core_store::model::{
+    Channel, ChannelId, ChannelType, MemberDevice, Message, MessageId, Timestamp, UserId,
+};
+use crate::core_store::store::oplog_export::read_header;
+use crate::core_store::store::{
+    LocalStore, LocalStoreConfig, OplogDiffSummary, OplogDiffer, OplogFinding, OplogOp, OplogSide,
+};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tempfile::{tempdir, TempDir};
+
+fn open_store(dir: &TempDir) -> LocalStore {
+    LocalStore::new(LocalStoreConfig {
+        data_dir: dir.path().to_path_buf(),
+        enable_encryption: false,
+        ..Default::default()
+    })
+    .unwrap()
+}
+
+fn device(user: &str) -> MemberDevice {
+    MemberDevice { user_id: UserId(user.to_string()), device_id: format!("{}-phone", user) }
+}
+
+fn message(channel_id: &ChannelId, id: &str, sender: &str, at: u64) -> Message {
+    Message::new(
+        MessageId(id.to_string()),
+        channel_id.clone(),
+        UserId(sender.to_string()),
+        format!("body of {}", id).into_bytes(),
+        Timestamp::from_millis(at),
+    )
+}
+
+fn export(store: &LocalStore, channel_id: &ChannelId, dir: &TempDir, name: &str) -> PathBuf {
+    let path = dir.path().join(name);
+    let file = std::fs::File::create(&path).unwrap();
+    store.export_oplog(channel_id, false, std::io::BufWriter::new(file)).unwrap();
+    path
+}
+
+fn diff(a: &Path, b: &Path, work: &TempDir) -> (OplogDiffSummary, Vec<OplogFinding>) {
+    let mut findings = Vec::new();
+    // A tiny run size forces the external sort to merge many runs
+    let summary = OplogDiffer::new(work.path())
+        .with_run_size(2)
+        .diff(a, b, |finding| findings.push(finding))
+        .unwrap();
+    (summary, findings)
+}
+
+/// Two replicas of a channel where only A received message m4
+fn diverged_replicas() -> (TempDir, TempDir, ChannelId, LocalStore, LocalStore) {
+    let (dir_a, dir_b) = (tempdir().unwrap(), tempdir().unwrap());
+    let (a, b) = (open_store(&dir_a), open_store(&dir_b));
+
+    let channel_id = ChannelId("general".to_string());
+    let mut channel = Channel::new(
+        channel_id.clone(),
+        "general".to_string(),
+        ChannelType::Text,
+        UserId("alice".to_string()),
+        Timestamp::from_millis(1_000),
+        "node-a".to_string(),
+    );
+    channel.add_member_device(device("alice"), Timestamp::from_millis(1_000), "node-a");
+    a.store_channel(&channel).unwrap();
+    b.apply_remote_channel(&channel).unwrap();
+
+    channel.add_member_device(device("bob"), Timestamp::from_millis(2_000), "node-a");
+    a.store_channel(&channel).unwrap();
+    b.apply_remote_channel(&channel).unwrap();
+
+    for (id, sender, at) in [("m1", "alice", 3_000), ("m2", "bob", 4_000), ("m3", "alice", 5_000)] {
+        let message = message(&channel_id, id, sender, at);
+        a.store_message(&message).unwrap();
+        b.apply_remote_message(&message).unwrap();
+    }
+
+    // Only A ever sees m4; both see m5
+    a.store_message(&message(&channel_id, "m4", "alice", 6_000)).unwrap();
+    let m5 = message(&channel_id, "m5", "bob", 7_000);
+    a.store_message(&m5).unwrap();
+    b.apply_remote_message(&m5).unwrap();
+
+    (dir_a, dir_b, channel_id, a, b)
+}
+
+#[test]
+fn test_export_is_deterministic_and_redacted() {
+    let (_dir_a, _dir_b, channel_id, a, _b) = diverged_replicas();
+    let out = tempdir().unwrap();
+
+    let first = std::fs::read_to_string(export(&a, &channel_id, &out, "1.jsonl")).unwrap();
+    let second = std::fs::read_to_string(export(&a, &channel_id, &out, "2.jsonl")).unwrap();
+    assert_eq!(first, second);
+
+    let header = read_header(&out.path().join("1.jsonl")).unwrap();
+    assert_eq!(header.channel_id, channel_id);
+    assert!(!header.payloads);
+
+    let ops: Vec<OplogOp> =
+        first.lines().skip(1).map(|line| serde_json::from_str(line).unwrap()).collect();
+    let types: Vec<&str> = ops.iter().map(|op| op.op_type.as_str()).collect();
+    assert_eq!(types.iter().filter(|t| **t == "message.post").count(), 5);
+    assert_eq!(types.iter().filter(|t| **t == "device.add").count(), 2);
+    assert!(ops.iter().all(|op| op.payload.is_none()));
+    assert!(!first.contains(&hex::encode(b"body of m1")));
+
+    // Payloads are only included on request
+    let mut with_payloads = Vec::new();
+    a.export_oplog(&channel_id, true, &mut with_payloads).unwrap();
+    assert!(String::from_utf8(with_payloads).unwrap().contains(&hex::encode(b"body of m1")));
+}
+
+#[test]
+fn test_diff_pinpoints_missing_op() {
+    let (_dir_a, _dir_b, channel_id, a, b) = diverged_replicas();
+    let out = tempdir().unwrap();
+    let path_a = export(&a, &channel_id, &out, "a.jsonl");
+    let path_b = export(&b, &channel_id, &out, "b.jsonl");
+
+    let (summary, findings) = diff(&path_a, &path_b, &out);
+    assert_eq!(summary.ops_a, summary.ops_b + 1);
+    assert_eq!((summary.only_in_a, summary.only_in_b, summary.anomalies), (1, 0, 0));
+    assert!(!summary.is_converged());
+
+    let [OplogFinding::Missing { side, index, op }] = findings.as_slice() else {
+        panic!("expected exactly one missing op, got {:?}", findings);
+    };
+    assert_eq!(*side, OplogSide::A);
+    assert_eq!(op.op_type, "message.post");
+    assert_eq!(op.target, serde_json::json!("m4"));
+
+    // The logs agree up to the missing op, which is where they diverge
+    assert_eq!(summary.first_missing_from_b, Some(*index));
+    assert_eq!(summary.common_prefix, index - 1);
+    assert_eq!(summary.first_missing_from_a, None);
+
+    // Sort runs are cleaned up
+    assert_eq!(std::fs::read_dir(out.path()).unwrap().count(), 2);
+
+    // Once B catches up the replicas converge
+    b.apply_remote_message(&message(&channel_id, "m4", "alice", 6_000)).unwrap();
+    let path_b = export(&b, &channel_id, &out, "b2.jsonl");
+    let (summary, findings) = diff(&path_a, &path_b, &out);
+    assert!(summary.is_converged(), "{:?}", findings);
+    assert!(findings.is_empty());
+}
+
+#[test]
+fn test_diff_reports_causal_anomalies() {
+    let out = tempdir().unwrap();
+    let op = |id: &str, clock: &[(&str, u64)]| OplogOp {
+        op_id: id.to_string(),
+        op_type: "member.add".to_string(),
+        target: serde_json::json!("alice"),
+        author: "node-a".to_string(),
+        timestamp: 1,
+        clock: clock
+            .iter()
+            .map(|(node, time)| (node.to_string(), *time))
+            .collect::<BTreeMap<_, _>>(),
+        payload_hash: String::new(),
+        payload: None,
+    };
+    let write = |name: &str, ops: &[OplogOp]| {
+        let mut lines = vec![
+            r#"{"format":"spacepanda-oplog","version":1,"channel_id":"general","payloads":false}"#
+                .to_string(),
+        ];
+        lines.extend(ops.iter().map(|op| serde_json::to_string(op).unwrap()));
+        let path = out.path().join(name);
+        std::fs::write(&path, lines.join("\n") + "\n").unwrap();
+        path
+    };
+
+    // B lists one op twice and then goes back in time
+    let a = write("a.jsonl", &[op("01", &[("node-a", 1)]), op("02", &[("node-a", 2)])]);
+    let b = write(
+        "b.jsonl",
+        &[
+            op("01", &[("node-a", 1)]),
+            op("02", &[("node-a", 2)]),
+            op("01", &[("node-a", 1)]),
+        ],
+    );
+
+    let (summary, findings) = diff(&a, &b, &out);
+    assert!(summary.is_converged());
+    assert_eq!(summary.anomalies, 2);
+    assert!(findings.contains(&OplogFinding::Duplicate {
+        side: OplogSide::B,
+        index: 3,
+        op_id: "01".to_string()
+    }));
+    assert!(findings.contains(&OplogFinding::ClockRegression {
+        side: OplogSide::B,
+        index: 3,
+        op_id: "01".to_string(),
+        node: "node-a".to_string(),
+        previous: 2,
+        current: 1,
+    }));
+
+    // Exports of different channels are refused
+    let other = out.path().join("other.jsonl");
+    std::fs::write(
+        &other,
+        r#"{"format":"spacepanda-oplog","version":1,"channel_id":"random","payloads":false}"#,
+    )
+    .unwrap();
+    assert!(OplogDiffer::new(out.path()).diff(&a, &other, |_| {}).is_err());
+}
@@ -26,6 +26,7 @@ async fn test_store_snapshot_replay() {
         snapshot_interval: 200,   // Snapshot after 200 ops
         max_log_size: 10_000_000,
         enable_compaction: false,
+        enable_wal: true,
         require_signatures: false,
         authorized_keys: Vec::new(),
     };
@@ -223,6 +224,7 @@ async fn test_store_concurrent_write_safety() {
         snapshot_interval: 10000, // High to avoid snapshots during test
         max_log_size: 10_000_000,
         enable_compaction: false,
+        enable_wal: true,
         require_signatures: false,
         authorized_keys: Vec::new(),
     };
@@ -0,0 +1,297 @@
+/*
+    sync_session_tests.rs - Anti-entropy sync sessions between two replicas
+
+    Two stores share a channel and are partitioned: node-a records 500
+    member devices joining (1000 operations, as each also adds its user)
+    and node-b pins 1000 messages, unpinning some of them again. One sync
+    session over a pair of wired-up RpcProtocols has to leave both with
+    identical state and identical vector clocks. A session cut off
+    mid-stream resumes where it stopped. Once two replicas of a channel
+    with 10000 pins have acknowledged each other, ten new pins sync
+    through the delta buffer in a payload sized by the ten.
+*/
+
+use crate::core_router::{PeerId, RpcCommand, RpcProtocol, SessionCommand, SessionEvent};
+use crate::core_store::crdt::{AddId, Crdt, VectorClock};
+use crate::core_store::model::{
+    Channel, ChannelId, ChannelType, MemberDevice, MessageId, Timestamp, UserId,
+};
+use crate::core_store::store::errors::{StoreError, StoreResult};
+use crate::core_store::store::{LocalStore, LocalStoreConfig};
+use crate::core_store::sync::{
+    serve_sync, summary_clock, sync_channel, AntiEntropyConfig, CausalStability, DeltaStateConfig,
+    StabilityConfig, SyncDeltas, SyncReplicas, SyncReply, SyncReport, SyncServer, SyncStep,
+    SYNC_METHOD,
+};
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+use tokio::sync::{mpsc, oneshot};
+
+fn store(dir: &TempDir) -> Arc<LocalStore> {
+    let config = LocalStoreConfig {
+        data_dir: dir.path().to_path_buf(),
+        enable_encryption: false,
+        require_signatures: false,
+        authorized_keys: Vec::new(),
+        ..Default::default()
+    };
+    Arc::new(LocalStore::new(config).unwrap())
+}
+
+fn config() -> AntiEntropyConfig {
+    AntiEntropyConfig { batch_size: 100, max_deltas_per_sec: 1000, ..Default::default() }
+}
+
+fn deltas() -> Arc<Mutex<SyncDeltas>> {
+    Arc::new(Mutex::new(SyncDeltas::new(DeltaStateConfig::default())))
+}
+
+/// Two replicas of one channel, partitioned and diverged by 1000 ops each
+fn partitioned(dir_a: &TempDir, dir_b: &TempDir) -> (Arc<LocalStore>, Arc<LocalStore>, ChannelId) {
+    let channel_id = ChannelId("general".to_string());
+    let channel = Channel::new(
+        channel_id.clone(),
+        "general".to_string(),
+        ChannelType::Text,
+        UserId("alice".to_string()),
+        Timestamp::from_millis(1_000),
+        "node-a".to_string(),
+    );
+    let (a, b) = (store(dir_a), store(dir_b));
+    a.store_channel(&channel).unwrap();
+    b.store_channel(&channel).unwrap();
+
+    let mut on_a = channel.clone();
+    for i in 0..500 {
+        let device = MemberDevice {
+            user_id: UserId(format!("user-{}", i)),
+            device_id: format!("device-{}", i),
+        };
+        on_a.add_member_device(device, Timestamp::from_millis(2_000 + i), "node-a");
+    }
+    on_a.set_topic("from a".to_string(), "node-a");
+    a.store_channel(&on_a).unwrap();
+
+    let mut on_b = channel;
+    let mut clock = VectorClock::new();
+    for i in 0..1000 {
+        clock.increment("node-b");
+        let message_id = MessageId(format!("message-{}", i));
+        let add_id = AddId::new("node-b".to_string(), 3_000 + i);
+        on_b.pinned_messages.add(message_id, add_id, clock.clone());
+    }
+    for i in (0..1000).step_by(10) {
+        clock.increment("node-b");
+        on_b.pinned_messages.remove(&MessageId(format!("message-{}", i)), clock.clone());
+    }
+    on_b.set_topic("from b".to_string(), "node-b");
+    b.store_channel(&on_b).unwrap();
+
+    (a, b, channel_id)
+}
+
+/// Assert both replicas hold the same channel state and clocks
+fn assert_converged(a: &LocalStore, b: &LocalStore, channel_id: &ChannelId) {
+    let on_a = a.get_channel(channel_id).unwrap().unwrap();
+    let on_b = b.get_channel(channel_id).unwrap().unwrap();
+
+    let sorted = |mut v: Vec<String>| {
+        v.sort();
+        v
+    };
+    let members = |c: &Channel| sorted(c.get_members().into_iter().map(|u| u.0).collect());
+    let pins = |c: &Channel| sorted(c.get_pinned_messages().into_iter().map(|m| m.0).collect());
+    assert_eq!(members(&on_a).len(), 500);
+    assert_eq!(members(&on_a), members(&on_b));
+    assert_eq!(pins(&on_a).len(), 900);
+    assert_eq!(pins(&on_a), pins(&on_b));
+    assert_eq!(on_a.get_member_devices().len(), on_b.get_member_devices().len());
+    assert_eq!(on_a.get_topic(), on_b.get_topic());
+
+    assert_eq!(summary_clock(&on_a), summary_clock(&on_b));
+    assert_eq!(on_a.members.vector_clock(), on_b.members.vector_clock());
+    assert_eq!(on_a.member_devices.vector_clock(), on_b.member_devices.vector_clock());
+    assert_eq!(on_a.pinned_messages.vector_clock(), on_b.pinned_messages.vector_clock());
+    assert_eq!(on_a.topic.vector_clock(), on_b.topic.vector_clock());
+}
+
+/// Deliver every frame `from_id` sends to `to`
+fn wire(from_id: PeerId, mut outbox: mpsc::Receiver<SessionCommand>, to: Arc<RpcProtocol>) {
+    tokio::spawn(async move {
+        while let Some(command) = outbox.recv().await {
+            if let SessionCommand::SendPlaintext(_, bytes) = command {
+                let frame = SessionEvent::PlaintextFrame(from_id.clone(), bytes);
+                let _ = to.handle_session_event(frame).await;
+            }
+        }
+    });
+}
+
+/// Send a session step through `rpc`
+async fn call(rpc: Arc<RpcProtocol>, peer_id: PeerId, step: SyncStep) -> StoreResult<SyncReply> {
+    let params = serde_json::to_value(step).unwrap();
+    let (response_tx, response_rx) = oneshot::channel();
+    let method = SYNC_METHOD.to_string();
+    rpc.handle_command(RpcCommand::Call { peer_id, method, params, response_tx })
+        .await
+        .map_err(StoreError::Sync)?;
+    let reply = response_rx
+        .await
+        .map_err(|_| StoreError::Sync("call dropped".to_string()))?
+        .map_err(|e| StoreError::Sync(e.message))?;
+    Ok(serde_json::from_value(reply).unwrap())
+}
+
+#[tokio::test]
+async fn test_partitioned_replicas_converge_in_one_round() {
+    let (dir_a, dir_b) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let (a, b, channel_id) = partitioned(&dir_a, &dir_b);
+
+    let (a_tx, a_outbox) = mpsc::channel(100);
+    let (b_tx, b_outbox) = mpsc::channel(100);
+    let a_rpc = Arc::new(RpcProtocol::new(a_tx));
+    let b_rpc = Arc::new(RpcProtocol::new(b_tx));
+    let (a_peer, b_peer) = (PeerId::from_bytes(vec![0xa]), PeerId::from_bytes(vec![0xb]));
+    wire(a_peer, a_outbox, b_rpc.clone());
+    wire(b_peer.clone(), b_outbox, a_rpc.clone());
+
+    // B answers sessions; A runs one
+    let replicas = SyncReplicas {
+        stability: Arc::new(Mutex::new(CausalStability::new(StabilityConfig::default()))),
+        deltas: deltas(),
+    };
+    let (handler_tx, _server) = serve_sync(b.clone(), "node-b".to_string(), replicas);
+    let method = SYNC_METHOD.to_string();
+    b_rpc
+        .handle_command(RpcCommand::RegisterHandler { method, handler_tx })
+        .await
+        .unwrap();
+
+    let a_deltas = deltas();
+    let report = sync_channel(&a, &channel_id, "node-a", &config(), &a_deltas, "node-b", |step| {
+        call(a_rpc.clone(), b_peer.clone(), step)
+    })
+    .await
+    .unwrap();
+    // 900 pins and 100 unpins plus the winning topic one way, 500 users
+    // and their devices the other
+    assert_eq!(report.pulled, 1001);
+    assert_eq!(report.pushed, 1000);
+    assert_converged(&a, &b, &channel_id);
+
+    // Nothing is left for a second round
+    let again = sync_channel(&a, &channel_id, "node-a", &config(), &a_deltas, "node-b", |step| {
+        call(a_rpc.clone(), b_peer.clone(), step)
+    })
+    .await
+    .unwrap();
+    assert!(again.is_empty());
+}
+
+#[tokio::test]
+async fn test_interrupted_session_resumes() {
+    let (dir_a, dir_b) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let (a, b, channel_id) = partitioned(&dir_a, &dir_b);
+    let server = SyncServer::new(b.clone(), "node-b".to_string());
+
+    // The connection drops after the summary, the ID pages and five pulls
+    let a_deltas = deltas();
+    let mut steps = 0;
+    let cut_off = sync_channel(&a, &channel_id, "node-a", &config(), &a_deltas, "node-b", |step| {
+        steps += 1;
+        let reply = match steps {
+            ..=8 => server.handle(step),
+            _ => Err(StoreError::Sync("connection lost".to_string())),
+        };
+        async move { reply }
+    })
+    .await;
+    assert!(matches!(cut_off, Err(StoreError::Sync(_))));
+
+    // The next session only moves what is left
+    let report = sync_channel(&a, &channel_id, "node-a", &config(), &a_deltas, "node-b", |step| {
+        let reply = server.handle(step);
+        async move { reply }
+    })
+    .await
+    .unwrap();
+    assert_eq!(report.pulled, 1001 - 500);
+    assert_eq!(report.pushed, 1000);
+    assert_converged(&a, &b, &channel_id);
+}
+
+/// Run a session from `a` with `server`; returns the report, the steps sent
+/// and the bytes moved both ways
+async fn metered_session(
+    a: &LocalStore,
+    channel_id: &ChannelId,
+    deltas: &Mutex<SyncDeltas>,
+    server: &SyncServer,
+) -> (SyncReport, Vec<String>, usize) {
+    let (mut steps, mut bytes) = (Vec::new(), 0);
+    let report = sync_channel(a, channel_id, "node-a", &config(), deltas, "node-b", |step| {
+        let sent = serde_json::to_value(&step).unwrap();
+        steps.push(sent["step"].as_str().unwrap().to_string());
+        bytes += sent.to_string().len();
+        let reply = server.handle_from("node-a", step);
+        if let Ok(reply) = &reply {
+            bytes += serde_json::to_string(reply).unwrap().len();
+        }
+        async move { reply }
+    })
+    .await
+    .unwrap();
+    (report, steps, bytes)
+}
+
+#[tokio::test]
+async fn test_acknowledged_replicas_exchange_only_deltas() {
+    let (dir_a, dir_b) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let channel_id = ChannelId("general".to_string());
+    let mut channel = Channel::new(
+        channel_id.clone(),
+        "general".to_string(),
+        ChannelType::Text,
+        UserId("alice".to_string()),
+        Timestamp::from_millis(1_000),
+        "node-a".to_string(),
+    );
+    let mut clock = VectorClock::new();
+    let mut pin = |channel: &mut Channel, i: u64| {
+        clock.increment("node-a");
+        let add_id = AddId::new("node-a".to_string(), i);
+        channel
+            .pinned_messages
+            .add(MessageId(format!("message-{}", i)), add_id, clock.clone());
+    };
+    for i in 0..10_000 {
+        pin(&mut channel, i);
+    }
+    let (a, b) = (store(&dir_a), store(&dir_b));
+    a.store_channel(&channel).unwrap();
+    b.store_channel(&channel).unwrap();
+
+    let (a_deltas, b_deltas) = (deltas(), deltas());
+    let server = SyncServer::new(b.clone(), "node-b".to_string()).with_deltas(b_deltas);
+
+    // Matching replicas acknowledge each other
+    let (report, _, _) = metered_session(&a, &channel_id, &a_deltas, &server).await;
+    assert!(report.is_empty());
+
+    for i in 10_000..10_010 {
+        pin(&mut channel, i);
+    }
+    a.store_channel(&channel).unwrap();
+    let (report, steps, bytes) = metered_session(&a, &channel_id, &a_deltas, &server).await;
+    assert_eq!((report.pulled, report.pushed), (0, 10));
+    assert!(!steps.contains(&"ids".to_string()), "fell back to the full state: {:?}", steps);
+    // A page of the 10010 operation IDs alone would be larger
+    assert!(bytes < 16 * 1024, "{} bytes for 10 operations", bytes);
+
+    let pins = |store: &LocalStore| {
+        let channel = store.get_channel(&channel_id).unwrap().unwrap();
+        (channel.get_pinned_messages().len(), summary_clock(&channel))
+    };
+    assert_eq!(pins(&a), pins(&b));
+    assert_eq!(pins(&b).0, 10_010);
+}
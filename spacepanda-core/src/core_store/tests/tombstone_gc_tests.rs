@@ -0,0 +1,263 @@
+/*
+    tombstone_gc_tests.rs - Tombstone garbage collection across replicas
+
+    Three replicas of a channel pin ten messages and one of them unpins
+    five. Once sync sessions have shown every replica holds the removals,
+    their tombstones are collected, and further sessions must not bring
+    the unpinned messages back. The replicas are the channel's member
+    devices: one that never syncs holds collection back until it has been
+    silent past the horizon. Then it is left out of the stable clock, and
+    when it comes back with a stale add its sessions are refused.
+*/
+
+use crate::core_store::crdt::{AddId, Crdt, VectorClock};
+use crate::core_store::model::{
+    Channel, ChannelId, ChannelType, MemberDevice, MessageId, Timestamp, UserId,
+};
+use crate::core_store::store::errors::{StoreError, StoreResult};
+use crate::core_store::store::{LocalStore, LocalStoreConfig};
+use crate::core_store::sync::{
+    collect_garbage, sync_channel, AntiEntropyConfig, CausalStability, DeltaStateConfig, GcReport,
+    StabilityConfig, StabilityEvent, SyncDeltas, SyncReport, SyncServer,
+};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tempfile::TempDir;
+
+const HORIZON: Duration = Duration::from_secs(60);
+
+/// One replica: its store, the server answering its peers, its tracker and
+/// its delta buffer
+struct Replica {
+    name: &'static str,
+    store: Arc<LocalStore>,
+    server: SyncServer,
+    stability: Arc<Mutex<CausalStability>>,
+    deltas: Arc<Mutex<SyncDeltas>>,
+    _dir: TempDir,
+}
+
+fn channel_id() -> ChannelId {
+    ChannelId("general".to_string())
+}
+
+/// The channel with ten pinned messages, whose members are `device-x`
+/// syncing from `node-x` for each of `names`
+fn general(names: &[&str]) -> Channel {
+    let mut channel = Channel::new(
+        channel_id(),
+        "general".to_string(),
+        ChannelType::Text,
+        UserId("alice".to_string()),
+        Timestamp::from_millis(1_000),
+        "node-a".to_string(),
+    );
+    let mut clock = VectorClock::new();
+    for i in 0..10 {
+        clock.increment("node-a");
+        let add_id = AddId::new("node-a".to_string(), 100 + i);
+        channel
+            .pinned_messages
+            .add(MessageId(format!("message-{}", i)), add_id, clock.clone());
+    }
+    for name in names {
+        let device = MemberDevice {
+            user_id: UserId(name.to_string()),
+            device_id: format!("device-{}", name),
+        };
+        channel.add_member_device(device, Timestamp::from_millis(1_000), "node-a");
+        channel.set_replica_node(&format!("device-{}", name), &format!("node-{}", name));
+    }
+    channel
+}
+
+/// A replica holding `channel`
+fn replica(name: &'static str, channel: &Channel) -> Replica {
+    let dir = TempDir::new().unwrap();
+    let config = LocalStoreConfig {
+        data_dir: dir.path().to_path_buf(),
+        enable_encryption: false,
+        require_signatures: false,
+        authorized_keys: Vec::new(),
+        ..Default::default()
+    };
+    let store = Arc::new(LocalStore::new(config).unwrap());
+    store.store_channel(channel).unwrap();
+
+    let config = StabilityConfig { silence_horizon: HORIZON, ..Default::default() };
+    let stability = Arc::new(Mutex::new(CausalStability::new(config)));
+    let deltas = Arc::new(Mutex::new(SyncDeltas::new(DeltaStateConfig::default())));
+    let server = SyncServer::new(store.clone(), name.to_string())
+        .with_stability(stability.clone())
+        .with_deltas(deltas.clone());
+    Replica { name, store, server, stability, deltas, _dir: dir }
+}
+
+/// Unpin messages on a replica
+fn unpin(replica: &Replica, messages: std::ops::Range<usize>) {
+    let mut channel = replica.store.get_channel(&channel_id()).unwrap().unwrap();
+    for i in messages {
+        let mut clock = channel.pinned_messages.vector_clock().clone();
+        clock.increment(replica.name);
+        channel.pinned_messages.remove(&MessageId(format!("message-{}", i)), clock);
+    }
+    replica.store.store_channel(&channel).unwrap();
+}
+
+/// Run a session from `from` with `to`, recording what `to` acknowledged
+async fn session(from: &Replica, to: &Replica, now: SystemTime) -> StoreResult<SyncReport> {
+    let config =
+        AntiEntropyConfig { batch_size: 100, max_deltas_per_sec: 1000, ..Default::default() };
+    let report = sync_channel(
+        &from.store,
+        &channel_id(),
+        from.name,
+        &config,
+        &from.deltas,
+        to.name,
+        |step| {
+            let reply = to.server.handle_from(from.name, step);
+            async move { reply }
+        },
+    )
+    .await?;
+    if let Some(acked) = &report.acked {
+        let channel = from.store.get_channel(&channel_id())?.unwrap();
+        let mut stability = from.stability.lock().unwrap();
+        stability.update_roster(&channel, from.name, now)?;
+        stability.ack(&channel, to.name, acked, now)?;
+    }
+    Ok(report)
+}
+
+/// A session from every replica with every other
+async fn round(replicas: &[&Replica], now: SystemTime) -> Vec<SyncReport> {
+    let mut reports = Vec::new();
+    for from in replicas {
+        for to in replicas.iter().filter(|to| to.name != from.name) {
+            reports.push(session(from, to, now).await.unwrap());
+        }
+    }
+    reports
+}
+
+fn gc(replica: &Replica, now: SystemTime) -> GcReport {
+    let mut stability = replica.stability.lock().unwrap();
+    collect_garbage(&replica.store, &mut stability, &channel_id(), replica.name, now).unwrap()
+}
+
+fn pins(replica: &Replica) -> Vec<String> {
+    let channel = replica.store.get_channel(&channel_id()).unwrap().unwrap();
+    let mut pins: Vec<String> = channel.get_pinned_messages().into_iter().map(|m| m.0).collect();
+    pins.sort();
+    pins
+}
+
+fn tombstones(replica: &Replica) -> usize {
+    let channel = replica.store.get_channel(&channel_id()).unwrap().unwrap();
+    channel.pinned_messages.tombstones().count()
+}
+
+#[tokio::test]
+async fn test_no_resurrection_after_collection() {
+    let channel = general(&["a", "b", "c"]);
+    let (a, b, c) = (
+        replica("node-a", &channel),
+        replica("node-b", &channel),
+        replica("node-c", &channel),
+    );
+    let now = SystemTime::now();
+    unpin(&b, 0..5);
+
+    // Nothing is collected before the other replicas have been heard from
+    assert_eq!(gc(&b, now).purged, 0);
+
+    round(&[&a, &b, &c], now).await;
+    let expected = pins(&a);
+    assert_eq!(expected.len(), 5);
+    for replica in [&a, &b, &c] {
+        assert_eq!(pins(replica), expected);
+        assert_eq!(tombstones(replica), 5);
+    }
+
+    // A collects first; B and C still hold the tombstones
+    assert_eq!(gc(&a, now), GcReport { purged: 5, events: Vec::new() });
+    assert_eq!(tombstones(&a), 0);
+    round(&[&a, &b, &c], now).await;
+    for replica in [&a, &b, &c] {
+        assert_eq!(pins(replica), expected);
+        gc(replica, now);
+        assert_eq!(tombstones(replica), 0);
+    }
+
+    // Without tombstones anywhere the replicas still agree, and stay put
+    for report in round(&[&a, &b, &c], now).await {
+        assert!(report.is_empty());
+    }
+    for replica in [&a, &b, &c] {
+        assert_eq!(pins(replica), expected);
+    }
+}
+
+#[tokio::test]
+async fn test_offline_member_holds_collection_back() {
+    let channel = general(&["a", "b", "c", "d"]);
+    let (a, b, c) = (
+        replica("node-a", &channel),
+        replica("node-b", &channel),
+        replica("node-c", &channel),
+    );
+    let start = SystemTime::now();
+    unpin(&b, 0..5);
+
+    // D is on the roster but never syncs, so it may still hold the adds
+    round(&[&a, &b, &c], start).await;
+    assert_eq!(gc(&a, start).purged, 0);
+    assert_eq!(tombstones(&a), 5);
+
+    // Until it has been silent past the horizon
+    let later = start + HORIZON + Duration::from_secs(1);
+    round(&[&a, &b, &c], later).await;
+    let report = gc(&a, later);
+    assert_eq!(report.purged, 5);
+    assert!(matches!(
+        &report.events[..],
+        [StabilityEvent::ReplicaExcluded { replica, .. }] if replica == "device-d"
+    ));
+}
+
+#[tokio::test]
+async fn test_late_replica_is_refused_after_collection() {
+    let channel = general(&["a", "b", "c"]);
+    let (a, b, c) = (
+        replica("node-a", &channel),
+        replica("node-b", &channel),
+        replica("node-c", &channel),
+    );
+    let start = SystemTime::now();
+    round(&[&a, &b, &c], start).await;
+
+    // C goes silent; B unpins a message C still has pinned
+    let later = start + HORIZON + Duration::from_secs(1);
+    unpin(&b, 0..1);
+    round(&[&a, &b], later).await;
+    assert!(!pins(&a).contains(&"message-0".to_string()));
+    assert!(pins(&c).contains(&"message-0".to_string()));
+
+    // A stops waiting for C and collects the tombstone
+    let report = gc(&a, later);
+    assert_eq!(report.purged, 1);
+    assert!(matches!(
+        &report.events[..],
+        [StabilityEvent::ReplicaExcluded { replica, .. }] if replica == "device-c"
+    ));
+    assert_eq!(tombstones(&a), 0);
+
+    // C comes back with its stale add, and is turned away
+    let refused = session(&c, &a, later).await;
+    assert!(matches!(refused, Err(StoreError::Sync(_))));
+    let channel = a.store.get_channel(&channel_id()).unwrap().unwrap();
+    assert!(a.stability.lock().unwrap().is_quarantined(&channel, "node-c"));
+    assert_eq!(pins(&a).len(), 9);
+    assert!(!pins(&a).contains(&"message-0".to_string()));
+}
@@ -0,0 +1,191 @@
+/*
+    wal_tests.rs - Write-ahead log mode of the local store
+
+    Each write returns once it is durable. Without the WAL every write is
+    synced on its own under the commit log lock; with it, writers that
+    arrive together share one fsync, so concurrent writers get through
+    more writes per second. Observers hear of a write only once it is
+    durable. After a crash, what was synced must come back from the WAL,
+    and a record torn by the crash must be dropped without losing anything
+    before it.
+*/
+
+use crate::core_store::model::{
+    Channel, ChannelId, ChannelType, Message, MessageId, Timestamp, UserId,
+};
+use crate::core_store::store::{
+    LocalStore, LocalStoreConfig, ObserverFilter, StoreEvent, StoreObserver,
+};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+fn open_store(dir: &TempDir, enable_wal: bool) -> LocalStore {
+    LocalStore::new(LocalStoreConfig {
+        data_dir: dir.path().to_path_buf(),
+        enable_encryption: false,
+        enable_compaction: false,
+        snapshot_interval: usize::MAX,
+        enable_wal,
+        ..Default::default()
+    })
+    .unwrap()
+}
+
+fn channel() -> Channel {
+    Channel::new(
+        ChannelId("general".to_string()),
+        "general".to_string(),
+        ChannelType::Text,
+        UserId("alice".to_string()),
+        Timestamp::from_millis(1_000),
+        "node-a".to_string(),
+    )
+}
+
+fn message(i: u64) -> Message {
+    Message::new(
+        MessageId(format!("m{}", i)),
+        ChannelId("general".to_string()),
+        UserId("alice".to_string()),
+        format!("message number {}", i).into_bytes(),
+        Timestamp::from_millis(2_000 + i),
+    )
+}
+
+/// Write `count` messages from each of `writers` threads, numbered from
+/// `first`, each write returning once durable
+fn concurrent_writes(store: &LocalStore, first: u64, writers: u64, count: u64) -> Duration {
+    let started = Instant::now();
+    std::thread::scope(|scope| {
+        for writer in 0..writers {
+            scope.spawn(move || {
+                for i in 0..count {
+                    store.store_message(&message(first + writer * count + i)).unwrap();
+                }
+            });
+        }
+    });
+    started.elapsed()
+}
+
+#[test]
+fn test_wal_speeds_up_concurrent_writes() {
+    const ROUNDS: u64 = 4;
+    const WRITERS: u64 = 8;
+    const WRITES: u64 = 50;
+    const TOTAL: u64 = ROUNDS * WRITERS * WRITES;
+
+    let wal_dir = TempDir::new().unwrap();
+    let wal_store = open_store(&wal_dir, true);
+    wal_store.store_channel(&channel()).unwrap();
+    let plain_dir = TempDir::new().unwrap();
+    let plain_store = open_store(&plain_dir, false);
+    plain_store.store_channel(&channel()).unwrap();
+
+    // Alternate between the stores so load from elsewhere hits both alike
+    let (mut wal_time, mut plain_time) = (Duration::ZERO, Duration::ZERO);
+    for round in 0..ROUNDS {
+        let first = round * WRITERS * WRITES;
+        wal_time += concurrent_writes(&wal_store, first, WRITERS, WRITES);
+        plain_time += concurrent_writes(&plain_store, first, WRITERS, WRITES);
+    }
+    let wal_rate = TOTAL as f64 / wal_time.as_secs_f64();
+    let plain_rate = TOTAL as f64 / plain_time.as_secs_f64();
+    assert!(
+        wal_rate > plain_rate,
+        "{:.0} writes/s with the WAL, {:.0} without",
+        wal_rate,
+        plain_rate
+    );
+
+    // Fewer fsyncs than writes is where the difference comes from
+    assert_eq!(plain_store.wal_fsyncs(), None);
+    let fsyncs = wal_store.wal_fsyncs().unwrap();
+    assert!(fsyncs < TOTAL / 2, "{} fsyncs for {} writes with the WAL", fsyncs, TOTAL);
+
+    // Both stores hold the same data after a reopen
+    drop(wal_store);
+    drop(plain_store);
+    for dir in [&wal_dir, &plain_dir] {
+        let store = open_store(dir, true);
+        store.load().unwrap();
+        let messages = store.get_channel_messages(&ChannelId("general".to_string())).unwrap();
+        assert_eq!(messages.len(), TOTAL as usize);
+    }
+}
+
+/// Records how much of the WAL file was on disk when each event arrived
+struct WalSizes {
+    path: PathBuf,
+    sizes: Mutex<Vec<u64>>,
+}
+
+impl StoreObserver for WalSizes {
+    fn on_commit(&self, _event: &StoreEvent) {
+        let size = std::fs::metadata(&self.path).unwrap().len();
+        self.sizes.lock().unwrap().push(size);
+    }
+}
+
+#[test]
+fn test_observers_hear_of_writes_once_durable() {
+    let dir = TempDir::new().unwrap();
+    let store = open_store(&dir, true);
+    let observer = Arc::new(WalSizes { path: dir.path().join("wal"), sizes: Mutex::default() });
+    store.register_observer(ObserverFilter::all(), observer.clone());
+
+    store.store_channel(&channel()).unwrap();
+    for i in 0..20 {
+        store.store_message(&message(i)).unwrap();
+    }
+
+    // Every event found its record already written to the WAL
+    let sizes = observer.sizes.lock().unwrap().clone();
+    assert_eq!(sizes.len(), 21);
+    assert!(sizes[0] > 0);
+    assert!(sizes.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", sizes);
+}
+
+#[test]
+fn test_torn_wal_record_is_discarded_on_recovery() {
+    let dir = TempDir::new().unwrap();
+    let store = open_store(&dir, true);
+    store.store_channel(&channel()).unwrap();
+    for i in 0..50 {
+        store.store_message(&message(i)).unwrap();
+    }
+    store.sync().unwrap();
+    store.crash();
+
+    // Only the WAL holds the messages
+    let wal_path = dir.path().join("wal");
+    assert_eq!(std::fs::metadata(dir.path().join("commit_log")).unwrap().len(), 0);
+    let intact = std::fs::metadata(&wal_path).unwrap().len();
+    assert!(intact > 0);
+
+    // A record cut short by the crash: a header promising more than follows
+    let mut wal = std::fs::OpenOptions::new().append(true).open(&wal_path).unwrap();
+    wal.write_all(&100u32.to_le_bytes()).unwrap();
+    wal.write_all(&[0xAB; 20]).unwrap();
+    drop(wal);
+
+    let store = open_store(&dir, true);
+    store.load().unwrap();
+    let channel_id = ChannelId("general".to_string());
+    assert_eq!(store.get_channel_messages(&channel_id).unwrap().len(), 50);
+    assert_eq!(store.checkpoint().unwrap(), 51);
+
+    // The torn record is gone and the store carries on after the last good one
+    store.store_message(&message(50)).unwrap();
+    store.sync().unwrap();
+    drop(store);
+    assert_eq!(std::fs::metadata(&wal_path).unwrap().len(), 0);
+
+    let store = open_store(&dir, false);
+    store.load().unwrap();
+    assert_eq!(store.get_channel_messages(&channel_id).unwrap().len(), 51);
+    assert!(!wal_path.exists());
+}
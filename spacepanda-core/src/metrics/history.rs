@@ -0,0 +1,526 @@
+//! Local time-series history for a curated set of metrics
+//!
+//! Desktop installs have no Prometheus to scrape, so the metrics service
+//! also keeps a small history on disk. Each tier is a fixed-size ring
+//! file with one record per (time bucket, series); a sample updates the
+//! current bucket of every tier, so coarser tiers are the downsampled
+//! view of finer ones and outlive them.
+//!
+//! File layout: `[header:32][record:32]*`, records addressed by
+//! `(bucket % slots) * series + series_index`. A record is
+//! `[name_hash:8][bucket:8][value:8][count:4][crc32:4]`. Records whose
+//! checksum, series or bucket doesn't match what their position should
+//! hold are skipped, so torn writes, truncation and stale ring slots all
+//! read as gaps.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MAGIC: &[u8; 4] = b"SPMH";
+const VERSION: u32 = 1;
+const HEADER_SIZE: u64 = 32;
+const RECORD_SIZE: usize = 32;
+
+/// How a series is downsampled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeriesKind {
+    /// Point-in-time value; buckets hold the mean
+    Gauge,
+    /// Running total; buckets hold the latest value
+    Counter,
+}
+
+/// Series sampled by the metrics service
+pub const HISTORY_SERIES: &[(&str, SeriesKind)] = &[
+    ("system.uptime_seconds", SeriesKind::Gauge),
+    ("system.threads.count", SeriesKind::Gauge),
+    ("dht.peers.active", SeriesKind::Gauge),
+    ("store.size.bytes", SeriesKind::Gauge),
+    ("crdt.operations", SeriesKind::Counter),
+    ("dht.requests", SeriesKind::Counter),
+    ("store.operations", SeriesKind::Counter),
+    ("network.messages.sent", SeriesKind::Counter),
+    ("network.messages.received", SeriesKind::Counter),
+];
+
+/// Resolution and retention of one ring
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tier {
+    /// Width of one bucket
+    pub resolution: Duration,
+    /// How far back the ring reaches
+    pub retention: Duration,
+}
+
+impl Tier {
+    fn resolution_secs(&self) -> u64 {
+        self.resolution.as_secs().max(1)
+    }
+
+    fn slots(&self) -> u64 {
+        (self.retention.as_secs() / self.resolution_secs()).max(1)
+    }
+}
+
+/// 7 days at 1 minute, then 90 days at 1 hour
+pub const DEFAULT_TIERS: &[Tier] = &[
+    Tier {
+        resolution: Duration::from_secs(60),
+        retention: Duration::from_secs(7 * 24 * 3600),
+    },
+    Tier {
+        resolution: Duration::from_secs(3600),
+        retention: Duration::from_secs(90 * 24 * 3600),
+    },
+];
+
+/// One point of a history query
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HistoryPoint {
+    /// Start of the step, in seconds since the Unix epoch
+    pub timestamp: u64,
+    /// Mean (gauges) or latest value (counters) over the step
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Record {
+    name_hash: u64,
+    bucket: u64,
+    value: f64,
+    count: u32,
+}
+
+impl Record {
+    fn encode(&self) -> [u8; RECORD_SIZE] {
+        let mut buf = [0u8; RECORD_SIZE];
+        buf[0..8].copy_from_slice(&self.name_hash.to_le_bytes());
+        buf[8..16].copy_from_slice(&self.bucket.to_le_bytes());
+        buf[16..24].copy_from_slice(&self.value.to_le_bytes());
+        buf[24..28].copy_from_slice(&self.count.to_le_bytes());
+        let checksum = crc32fast::hash(&buf[0..28]);
+        buf[28..32].copy_from_slice(&checksum.to_le_bytes());
+        buf
+    }
+
+    /// Decode a record, `None` if empty or corrupt
+    fn decode(buf: &[u8]) -> Option<Self> {
+        let buf: &[u8; RECORD_SIZE] = buf.try_into().ok()?;
+        let checksum = u32::from_le_bytes(buf[28..32].try_into().ok()?);
+        if crc32fast::hash(&buf[0..28]) != checksum {
+            return None;
+        }
+        let record = Record {
+            name_hash: u64::from_le_bytes(buf[0..8].try_into().ok()?),
+            bucket: u64::from_le_bytes(buf[8..16].try_into().ok()?),
+            value: f64::from_le_bytes(buf[16..24].try_into().ok()?),
+            count: u32::from_le_bytes(buf[24..28].try_into().ok()?),
+        };
+        (record.count > 0).then_some(record)
+    }
+
+    /// Fold one more sample into the bucket
+    fn add_sample(&mut self, kind: SeriesKind, value: f64) {
+        self.count += 1;
+        self.value = match kind {
+            SeriesKind::Gauge => self.value + (value - self.value) / self.count as f64,
+            SeriesKind::Counter => value,
+        };
+    }
+}
+
+/// One tier's ring file
+struct Ring {
+    tier: Tier,
+    slots: u64,
+    series: u64,
+    file: File,
+}
+
+impl Ring {
+    fn open(path: &Path, tier: Tier, series: usize) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let slots = tier.slots();
+        let series = series as u64;
+
+        let mut header = [0u8; HEADER_SIZE as usize];
+        header[0..4].copy_from_slice(MAGIC);
+        header[4..8].copy_from_slice(&VERSION.to_le_bytes());
+        header[8..16].copy_from_slice(&tier.resolution_secs().to_le_bytes());
+        header[16..24].copy_from_slice(&slots.to_le_bytes());
+        header[24..32].copy_from_slice(&series.to_le_bytes());
+
+        // A different layout can't be reinterpreted; start over
+        let mut existing = [0u8; HEADER_SIZE as usize];
+        let matches = file.read_exact(&mut existing).is_ok() && existing == header;
+        if !matches {
+            file.set_len(0)?;
+            file.seek(SeekFrom::Start(0))?;
+            file.write_all(&header)?;
+        }
+
+        // Restore the full size after truncation; the lost tail reads as gaps
+        let size = HEADER_SIZE + slots * series * RECORD_SIZE as u64;
+        if file.metadata()?.len() != size {
+            file.set_len(size)?;
+        }
+
+        Ok(Self { tier, slots, series, file })
+    }
+
+    fn offset(&self, bucket: u64, series_index: usize) -> u64 {
+        HEADER_SIZE
+            + ((bucket % self.slots) * self.series + series_index as u64) * RECORD_SIZE as u64
+    }
+
+    fn read(&mut self, bucket: u64, series_index: usize) -> io::Result<[u8; RECORD_SIZE]> {
+        let mut buf = [0u8; RECORD_SIZE];
+        self.file.seek(SeekFrom::Start(self.offset(bucket, series_index)))?;
+        self.file.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn write(&mut self, series_index: usize, record: &Record) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(self.offset(record.bucket, series_index)))?;
+        self.file.write_all(&record.encode())
+    }
+
+    fn read_all(&mut self) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.read_to_end(&mut data)?;
+        Ok(data)
+    }
+}
+
+/// Bounded on-disk metrics history
+pub struct MetricsHistory {
+    dir: PathBuf,
+    series: Vec<(String, SeriesKind)>,
+    rings: Vec<Mutex<Ring>>,
+}
+
+impl MetricsHistory {
+    /// Open the history in `dir` with [`HISTORY_SERIES`] and [`DEFAULT_TIERS`]
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        Self::open_with(dir, HISTORY_SERIES, DEFAULT_TIERS)
+    }
+
+    /// Open the history with a custom series set and tiers (finest first)
+    ///
+    /// Changing either for an existing directory discards the affected
+    /// rings' contents.
+    pub fn open_with(
+        dir: impl Into<PathBuf>,
+        series: &[(&str, SeriesKind)],
+        tiers: &[Tier],
+    ) -> io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+
+        let rings = tiers
+            .iter()
+            .map(|tier| {
+                let path = dir.join(format!("history-{}s.ring", tier.resolution_secs()));
+                Ring::open(&path, *tier, series.len()).map(Mutex::new)
+            })
+            .collect::<io::Result<_>>()?;
+
+        Ok(Self {
+            dir,
+            series: series.iter().map(|(name, kind)| (name.to_string(), *kind)).collect(),
+            rings,
+        })
+    }
+
+    /// Directory holding the ring files
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Names of the recorded series
+    pub fn series(&self) -> impl Iterator<Item = &str> {
+        self.series.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Record a sample taken now
+    pub fn record(&self, name: &str, value: f64) -> io::Result<()> {
+        self.record_at(name, value, unix_now())
+    }
+
+    /// Record a sample taken at `timestamp` (seconds since the Unix epoch)
+    pub fn record_at(&self, name: &str, value: f64, timestamp: u64) -> io::Result<()> {
+        let (index, kind) = self.lookup(name)?;
+        let name_hash = name_hash(name);
+
+        for ring in &self.rings {
+            let mut ring = ring.lock().unwrap_or_else(|e| e.into_inner());
+            let bucket = timestamp / ring.tier.resolution_secs();
+
+            let mut record = Record::decode(&ring.read(bucket, index)?)
+                .filter(|r| r.name_hash == name_hash && r.bucket == bucket)
+                .unwrap_or(Record { name_hash, bucket, value: 0.0, count: 0 });
+            record.add_sample(kind, value);
+            ring.write(index, &record)?;
+        }
+
+        Ok(())
+    }
+
+    /// Flush written samples to disk
+    pub fn sync(&self) -> io::Result<()> {
+        for ring in &self.rings {
+            ring.lock().unwrap_or_else(|e| e.into_inner()).file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    /// Query the last `range` of a series in `step`-wide points
+    ///
+    /// Uses the finest tier that retains the whole range. `step` is rounded
+    /// up to a multiple of that tier's resolution; steps without samples
+    /// are left out.
+    pub fn query(
+        &self,
+        name: &str,
+        range: Duration,
+        step: Duration,
+    ) -> io::Result<Vec<HistoryPoint>> {
+        self.query_at(name, range, step, unix_now())
+    }
+
+    /// Like [`query`](Self::query), with the range ending at `now`
+    pub fn query_at(
+        &self,
+        name: &str,
+        range: Duration,
+        step: Duration,
+        now: u64,
+    ) -> io::Result<Vec<HistoryPoint>> {
+        let (index, kind) = self.lookup(name)?;
+        let name_hash = name_hash(name);
+
+        let Some(ring) = self
+            .rings
+            .iter()
+            .find(|ring| ring.lock().unwrap_or_else(|e| e.into_inner()).tier.retention >= range)
+            .or(self.rings.last())
+        else {
+            return Ok(Vec::new());
+        };
+        let mut ring = ring.lock().unwrap_or_else(|e| e.into_inner());
+
+        let resolution = ring.tier.resolution_secs();
+        let last_bucket = now / resolution;
+        // Never reach past retention, even for ranges longer than it
+        let span = (range.as_secs() / resolution).min(ring.slots - 1);
+        let first_bucket = last_bucket.saturating_sub(span);
+        let step_buckets = step.as_secs().div_ceil(resolution).max(1);
+
+        let data = ring.read_all()?;
+        let mut points: Vec<HistoryPoint> = Vec::new();
+        let mut window: Option<(u64, f64, u64)> = None; // (window, sum or last, weight)
+
+        for bucket in first_bucket..=last_bucket {
+            let offset = ring.offset(bucket, index) as usize;
+            let Some(record) = data
+                .get(offset..offset + RECORD_SIZE)
+                .and_then(Record::decode)
+                .filter(|r| r.name_hash == name_hash && r.bucket == bucket)
+            else {
+                continue;
+            };
+
+            let this_window = bucket / step_buckets;
+            if let Some((w, acc, weight)) = window {
+                if w != this_window {
+                    points.push(finish_window(kind, w, acc, weight, step_buckets, resolution));
+                    window = None;
+                }
+            }
+            let (w, acc, weight) = window.unwrap_or((this_window, 0.0, 0));
+            window = Some(match kind {
+                SeriesKind::Gauge => {
+                    (w, acc + record.value * record.count as f64, weight + record.count as u64)
+                }
+                SeriesKind::Counter => (w, record.value, weight + record.count as u64),
+            });
+        }
+        if let Some((w, acc, weight)) = window {
+            points.push(finish_window(kind, w, acc, weight, step_buckets, resolution));
+        }
+
+        Ok(points)
+    }
+
+    fn lookup(&self, name: &str) -> io::Result<(usize, SeriesKind)> {
+        self.series
+            .iter()
+            .position(|(series, _)| series == name)
+            .map(|index| (index, self.series[index].1))
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown series: {}", name))
+            })
+    }
+}
+
+fn finish_window(
+    kind: SeriesKind,
+    window: u64,
+    acc: f64,
+    weight: u64,
+    step_buckets: u64,
+    resolution: u64,
+) -> HistoryPoint {
+    let value = match kind {
+        SeriesKind::Gauge => acc / weight as f64,
+        SeriesKind::Counter => acc,
+    };
+    HistoryPoint { timestamp: window * step_buckets * resolution, value }
+}
+
+fn name_hash(name: &str) -> u64 {
+    let hash = blake3::hash(name.as_bytes());
+    u64::from_le_bytes(hash.as_bytes()[0..8].try_into().expect("8 bytes"))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const MINUTE: u64 = 60;
+    const HOUR: u64 = 3600;
+
+    /// 1 hour at 1 minute, then 1 day at 1 hour
+    const TEST_TIERS: &[Tier] = &[
+        Tier { resolution: Duration::from_secs(MINUTE), retention: Duration::from_secs(HOUR) },
+        Tier { resolution: Duration::from_secs(HOUR), retention: Duration::from_secs(24 * HOUR) },
+    ];
+
+    const TEST_SERIES: &[(&str, SeriesKind)] =
+        &[("cpu", SeriesKind::Gauge), ("sent", SeriesKind::Counter)];
+
+    fn open(dir: &TempDir) -> MetricsHistory {
+        MetricsHistory::open_with(dir.path(), TEST_SERIES, TEST_TIERS).unwrap()
+    }
+
+    #[test]
+    fn test_sampling_and_step_aggregation() {
+        let dir = TempDir::new().unwrap();
+        let history = open(&dir);
+        let start = 1_000 * HOUR;
+
+        // Two samples per minute for ten minutes
+        for minute in 0..10 {
+            let at = start + minute * MINUTE;
+            history.record_at("cpu", minute as f64, at).unwrap();
+            history.record_at("cpu", minute as f64 + 1.0, at + 30).unwrap();
+            history.record_at("sent", (minute * 10) as f64, at).unwrap();
+        }
+        let now = start + 9 * MINUTE + 59;
+
+        let points = history
+            .query_at("cpu", Duration::from_secs(10 * MINUTE), Duration::from_secs(MINUTE), now)
+            .unwrap();
+        assert_eq!(points.len(), 10);
+        assert_eq!(points[0], HistoryPoint { timestamp: start, value: 0.5 });
+        assert_eq!(points[9].value, 9.5);
+
+        // Five-minute steps: mean for gauges, latest for counters
+        let step = Duration::from_secs(5 * MINUTE);
+        let cpu = history.query_at("cpu", Duration::from_secs(10 * MINUTE), step, now).unwrap();
+        assert_eq!(cpu.iter().map(|p| p.value).collect::<Vec<_>>(), vec![2.5, 7.5]);
+        let sent = history.query_at("sent", Duration::from_secs(10 * MINUTE), step, now).unwrap();
+        assert_eq!(sent.iter().map(|p| p.value).collect::<Vec<_>>(), vec![40.0, 90.0]);
+
+        assert!(history.record_at("unknown", 1.0, now).is_err());
+    }
+
+    #[test]
+    fn test_downsampled_tier_holds_hourly_mean() {
+        let dir = TempDir::new().unwrap();
+        let history = open(&dir);
+        let start = 1_000 * HOUR;
+
+        for minute in 0..60 {
+            history.record_at("cpu", (minute + 1) as f64, start + minute * MINUTE).unwrap();
+        }
+
+        // Longer than the minute tier keeps, so the hourly tier answers
+        let now = start + 3 * HOUR;
+        let points = history
+            .query_at("cpu", Duration::from_secs(6 * HOUR), Duration::from_secs(HOUR), now)
+            .unwrap();
+        assert_eq!(points, vec![HistoryPoint { timestamp: start, value: 30.5 }]);
+    }
+
+    #[test]
+    fn test_retention_is_enforced() {
+        let dir = TempDir::new().unwrap();
+        let history = open(&dir);
+        let start = 1_000 * HOUR;
+
+        // Two hours of minute samples into a one-hour minute ring
+        for minute in 0..120 {
+            history.record_at("cpu", minute as f64, start + minute * MINUTE).unwrap();
+        }
+        let now = start + 119 * MINUTE;
+
+        let points = history
+            .query_at("cpu", Duration::from_secs(HOUR), Duration::from_secs(MINUTE), now)
+            .unwrap();
+        assert_eq!(points.len(), 60);
+        assert_eq!(points[0].value, 60.0);
+        assert_eq!(points[59].value, 119.0);
+
+        // Files never grow past their fixed size
+        let ring_size = HEADER_SIZE + 60 * 2 * RECORD_SIZE as u64;
+        let path = dir.path().join("history-60s.ring");
+        assert_eq!(std::fs::metadata(path).unwrap().len(), ring_size);
+    }
+
+    #[test]
+    fn test_recovers_from_truncated_and_corrupt_file() {
+        let dir = TempDir::new().unwrap();
+        let start = 1_000 * HOUR;
+        {
+            let history = open(&dir);
+            for minute in 0..10 {
+                history.record_at("cpu", minute as f64, start + minute * MINUTE).unwrap();
+            }
+            history.sync().unwrap();
+        }
+
+        // Cut off the records for minutes 5.. and flip a bit in minute 1
+        let path = dir.path().join("history-60s.ring");
+        let data = std::fs::read(&path).unwrap();
+        let minute_offset = |m: u64| (HEADER_SIZE + ((start / MINUTE + m) % 60) * 2 * 32) as usize;
+        let mut data = data[..minute_offset(5)].to_vec();
+        data[minute_offset(1) + 16] ^= 0xff;
+        std::fs::write(&path, data).unwrap();
+
+        let history = open(&dir);
+        let now = start + 9 * MINUTE;
+        let range = Duration::from_secs(10 * MINUTE);
+        let points = history.query_at("cpu", range, Duration::from_secs(MINUTE), now).unwrap();
+        assert_eq!(points.iter().map(|p| p.value).collect::<Vec<_>>(), vec![0.0, 2.0, 3.0, 4.0]);
+
+        // The ring is usable again
+        history.record_at("cpu", 42.0, now).unwrap();
+        let points = history.query_at("cpu", range, Duration::from_secs(MINUTE), now).unwrap();
+        assert_eq!(points.last().unwrap().value, 42.0);
+    }
+}
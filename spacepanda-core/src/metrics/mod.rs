@@ -7,9 +7,11 @@ use tokio::sync::RwLock;
 
 mod collector;
 mod exporter;
+pub mod history;
 
 pub use collector::MetricsCollector;
 pub use exporter::{MetricsExporter, PrometheusExporter};
+pub use history::{HistoryPoint, MetricsHistory};
 
 /// Initialize metrics with descriptions
 pub fn init_metrics() {
@@ -31,6 +33,12 @@ pub fn init_metrics() {
     describe_gauge!("dht.peers.active", "Number of active DHT peers");
     describe_gauge!("dht.peers.total", "Total number of known DHT peers");
     describe_gauge!("dht.bucket.entries", "Number of entries in DHT buckets");
+    describe_counter!("dht.read_repairs", "Stale DHT replicas rewritten after a read");
+    describe_counter!("dht.bucket_evictions", "DHT peers evicted after failing a liveness ping");
+    describe_counter!("dht.bucket_refreshes", "Idle DHT buckets refreshed with a lookup");
+    describe_counter!("dht.storage_evictions", "DHT records evicted to make room in a full store");
+    describe_counter!("dht.storage_rejections", "DHT puts refused for being over quota");
+    describe_counter!("dht.store_throttled", "STOREs refused from peers throttled for flooding");
 
     // Store metrics
     describe_counter!("store.operations.total", "Total store operations");
@@ -44,8 +52,14 @@ pub fn init_metrics() {
     // Network metrics
     describe_counter!("network.messages.sent", "Number of network messages sent");
     describe_counter!("network.messages.received", "Number of network messages received");
-    describe_counter!("network.bytes.sent", "Number of bytes sent over network");
-    describe_counter!("network.bytes.received", "Number of bytes received over network");
+    describe_counter!(
+        "network.bytes.sent",
+        "Number of bytes sent over network, by peer class (relay, direct, dht)"
+    );
+    describe_counter!(
+        "network.bytes.received",
+        "Number of bytes received over network, by peer class (relay, direct, dht)"
+    );
     describe_histogram!("network.latency_ms", "Network latency in milliseconds");
 
     // MLS metrics
@@ -56,6 +70,40 @@ pub fn init_metrics() {
     describe_histogram!("mls.encryption.duration_ms", "MLS encryption duration in milliseconds");
     describe_histogram!("mls.decryption.duration_ms", "MLS decryption duration in milliseconds");
 
+    // Channel metrics
+    describe_counter!("mvp.commits.created", "Number of MLS commits created by channel operations");
+    describe_counter!(
+        "mvp.broadcast.changes_queued",
+        "Membership changes queued for a broadcast channel batch commit"
+    );
+    describe_counter!(
+        "mvp.broadcast.mailbox_published",
+        "Broadcast channel messages published to the mailbox"
+    );
+    describe_histogram!(
+        "mvp.delivery.latency_ms",
+        "Send-to-receipt delivery latency in milliseconds, by channel"
+    );
+    describe_gauge!("mvp.delivery.p50_ms", "Median delivery latency in the SLO window, by channel");
+    describe_gauge!("mvp.delivery.p95_ms", "p95 delivery latency in the SLO window, by channel");
+    describe_gauge!("mvp.delivery.p99_ms", "p99 delivery latency in the SLO window, by channel");
+    describe_gauge!(
+        "mvp.delivery.compliance",
+        "Share of deliveries within the SLO target latency, by channel"
+    );
+    describe_counter!(
+        "mvp.channel_worker.panics",
+        "Panics caught while handling a channel's inbound work, by channel"
+    );
+    describe_counter!(
+        "mvp.channel_worker.quarantined",
+        "Channels quarantined after repeated worker panics, by channel"
+    );
+    describe_counter!(
+        "mvp.channel_worker.overflow",
+        "Inbound work parked because a channel's mailbox was full, by channel"
+    );
+
     // System metrics
     describe_gauge!("system.memory.used_bytes", "System memory used in bytes");
     describe_gauge!("system.cpu.usage_percent", "CPU usage percentage");
@@ -114,12 +162,25 @@ pub struct MetricsSnapshot {
 pub struct MetricsService {
     collector: Arc<RwLock<MetricsCollector>>,
     collection_interval: Duration,
+    history: Option<Arc<MetricsHistory>>,
+    started: Instant,
 }
 
 impl MetricsService {
     /// Create a new metrics service
     pub fn new(collection_interval: Duration) -> Self {
-        Self { collector: Arc::new(RwLock::new(MetricsCollector::new())), collection_interval }
+        Self {
+            collector: Arc::new(RwLock::new(MetricsCollector::new())),
+            collection_interval,
+            history: None,
+            started: Instant::now(),
+        }
+    }
+
+    /// Record the curated series into a local history on every collection
+    pub fn with_history(mut self, history: Arc<MetricsHistory>) -> Self {
+        self.history = Some(history);
+        self
     }
 
     /// Start the metrics collection service
@@ -131,6 +192,50 @@ impl MetricsService {
 
             // Collect system metrics
             self.collect_system_metrics().await;
+            self.sample_history().await;
+        }
+    }
+
+    /// Append the curated series to the local history, if enabled
+    async fn sample_history(&self) {
+        let Some(history) = &self.history else {
+            return;
+        };
+
+        let snapshot = self.snapshot().await;
+        let samples = [
+            ("system.uptime_seconds", self.started.elapsed().as_secs_f64()),
+            ("system.threads.count", num_cpus::get() as f64),
+            ("dht.peers.active", snapshot.active_peers as f64),
+            ("store.size.bytes", snapshot.store_size_bytes as f64),
+            ("crdt.operations", snapshot.crdt_operations as f64),
+            ("dht.requests", snapshot.dht_requests as f64),
+            ("store.operations", snapshot.store_operations as f64),
+            ("network.messages.sent", snapshot.network_messages_sent as f64),
+            ("network.messages.received", snapshot.network_messages_received as f64),
+        ];
+
+        // History is best-effort; a failing disk must not stop collection
+        for (name, value) in samples {
+            if let Err(e) = history.record(name, value) {
+                tracing::warn!(metric = name, error = %e, "Failed to record metrics history");
+                return;
+            }
+        }
+    }
+
+    /// Query the local history of a metric
+    ///
+    /// Returns an empty series if no history is configured.
+    pub fn metrics_history(
+        &self,
+        name: &str,
+        range: Duration,
+        step: Duration,
+    ) -> std::io::Result<Vec<HistoryPoint>> {
+        match &self.history {
+            Some(history) => history.query(name, range, step),
+            None => Ok(Vec::new()),
         }
     }
 
@@ -171,4 +276,21 @@ mod tests {
         let snapshot = service.snapshot().await;
         assert!(snapshot.timestamp <= std::time::SystemTime::now());
     }
+
+    #[tokio::test]
+    async fn test_metrics_service_samples_history() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let history = Arc::new(MetricsHistory::open(dir.path()).unwrap());
+        let service = MetricsService::new(Duration::from_millis(100)).with_history(history);
+
+        service.sample_history().await;
+
+        let hour = Duration::from_secs(3600);
+        for name in history::HISTORY_SERIES.iter().map(|(name, _)| *name) {
+            let points = service.metrics_history(name, hour, Duration::from_secs(60)).unwrap();
+            assert_eq!(points.len(), 1, "{}", name);
+        }
+        let threads = service.metrics_history("system.threads.count", hour, hour).unwrap();
+        assert_eq!(threads[0].value, num_cpus::get() as f64);
+    }
 }
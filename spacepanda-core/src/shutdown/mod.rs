@@ -1,8 +1,15 @@
 //! Graceful shutdown coordinator
-
+//!
+//! Components that have work to finish when shutting down register with the
+//! coordinator and hold the returned [`ShutdownGuard`] until they're done.
+//! Shutdown completes as soon as every guard is dropped, or at the timeout
+//! if some component is still busy.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::{broadcast, RwLock};
+use tokio::sync::{broadcast, watch, RwLock};
 use tracing::{error, info, warn};
 
 /// Shutdown signal
@@ -25,6 +32,9 @@ pub struct ShutdownCoordinator {
     state: Arc<RwLock<ShutdownState>>,
     shutdown_tx: broadcast::Sender<ShutdownSignal>,
     timeout: Duration,
+    /// Registered components that haven't finished, by registration number
+    pending: Arc<watch::Sender<BTreeMap<u64, String>>>,
+    next_component: AtomicU64,
 }
 
 impl ShutdownCoordinator {
@@ -32,7 +42,30 @@ impl ShutdownCoordinator {
     pub fn new(timeout: Duration) -> Self {
         let (shutdown_tx, _) = broadcast::channel(16);
 
-        Self { state: Arc::new(RwLock::new(ShutdownState::Running)), shutdown_tx, timeout }
+        Self {
+            state: Arc::new(RwLock::new(ShutdownState::Running)),
+            shutdown_tx,
+            timeout,
+            pending: Arc::new(watch::Sender::new(BTreeMap::new())),
+            next_component: AtomicU64::new(0),
+        }
+    }
+
+    /// Register a component that shutdown should wait for
+    ///
+    /// Shutdown waits, up to its timeout, until the returned guard is dropped
+    /// or marked done.
+    pub fn register(&self, component_name: impl Into<String>) -> ShutdownGuard {
+        let id = self.next_component.fetch_add(1, Ordering::Relaxed);
+        self.pending.send_modify(|pending| {
+            pending.insert(id, component_name.into());
+        });
+        ShutdownGuard { id, pending: self.pending.clone() }
+    }
+
+    /// Names of registered components that haven't finished
+    pub fn pending_components(&self) -> Vec<String> {
+        self.pending.borrow().values().cloned().collect()
     }
 
     /// Subscribe to shutdown notifications
@@ -58,8 +91,16 @@ impl ShutdownCoordinator {
             error!("Failed to send shutdown signal: {}", e);
         }
 
-        // Wait for timeout
-        tokio::time::sleep(self.timeout).await;
+        // Wait for registered components, but no longer than the timeout
+        let mut pending = self.pending.subscribe();
+        let finished = pending.wait_for(|pending| pending.is_empty());
+        if tokio::time::timeout(self.timeout, finished).await.is_err() {
+            warn!(
+                "Shutdown timed out after {:?} waiting for: {}",
+                self.timeout,
+                self.pending_components().join(", ")
+            );
+        }
 
         let mut state = self.state.write().await;
         *state = ShutdownState::Shutdown;
@@ -97,6 +138,24 @@ impl ShutdownCoordinator {
     }
 }
 
+/// Held by a registered component until it has finished shutting down
+#[must_use = "shutdown stops waiting for the component once its guard is dropped"]
+pub struct ShutdownGuard {
+    id: u64,
+    pending: Arc<watch::Sender<BTreeMap<u64, String>>>,
+}
+
+impl ShutdownGuard {
+    /// Report the component as finished
+    pub fn done(self) {}
+}
+
+impl Drop for ShutdownGuard {
+    fn drop(&mut self) {
+        self.pending.send_if_modified(|pending| pending.remove(&self.id).is_some());
+    }
+}
+
 /// Shutdown handler for managing component lifecycle
 pub struct ShutdownHandler {
     coordinator: Arc<ShutdownCoordinator>,
@@ -118,6 +177,7 @@ impl ShutdownHandler {
         info!("Starting component: {}", self.component_name);
 
         let mut shutdown_rx = self.coordinator.subscribe();
+        let _guard = self.coordinator.register(self.component_name.clone());
 
         tokio::select! {
             _ = f() => {
@@ -208,4 +268,41 @@ mod tests {
 
         task.await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_shutdown_completes_when_components_finish() {
+        let coordinator = Arc::new(ShutdownCoordinator::new(Duration::from_secs(30)));
+        for name in ["fast", "slower"] {
+            let guard = coordinator.register(name);
+            let coordinator = coordinator.clone();
+            tokio::spawn(async move {
+                coordinator.wait_for_shutdown().await;
+                if name == "slower" {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }
+                guard.done();
+            });
+        }
+        tokio::task::yield_now().await;
+
+        let started = tokio::time::Instant::now();
+        coordinator.shutdown().await;
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(coordinator.state().await, ShutdownState::Shutdown);
+        assert!(coordinator.pending_components().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_gives_up_at_the_timeout() {
+        let coordinator = ShutdownCoordinator::new(Duration::from_millis(100));
+        let _stuck = coordinator.register("stuck");
+        let finished = coordinator.register("finished");
+        drop(finished);
+
+        let started = tokio::time::Instant::now();
+        coordinator.shutdown().await;
+        assert!(started.elapsed() >= Duration::from_millis(100));
+        assert_eq!(coordinator.state().await, ShutdownState::Shutdown);
+        assert_eq!(coordinator.pending_components(), ["stuck"]);
+    }
 }
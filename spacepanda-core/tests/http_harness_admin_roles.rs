@@ -30,6 +30,7 @@ async fn create_test_manager(name: &str) -> (Arc<ChannelManager>, TempDir) {
         snapshot_interval: 1000,
         max_log_size: 10_000_000,
         enable_compaction: false,
+        enable_wal: true,
         require_signatures: false,
         authorized_keys: Vec::new(),
     };
@@ -33,6 +33,7 @@ async fn test_integration_identity_store_roundtrip() {
         snapshot_interval: 1000,
         max_log_size: 10_000_000,
         enable_compaction: false,
+        enable_wal: true,
         require_signatures: false,
         authorized_keys: Vec::new(),
     };
@@ -125,6 +126,7 @@ async fn test_integration_crdt_store_persistence() {
         snapshot_interval: 100,
         max_log_size: 1_000_000,
         enable_compaction: false,
+        enable_wal: true,
         require_signatures: false,
         authorized_keys: Vec::new(),
     };
@@ -287,6 +289,7 @@ async fn test_integration_store_snapshot_dht_versioning() {
         snapshot_interval: 50,
         max_log_size: 1_000_000,
         enable_compaction: false,
+        enable_wal: true,
         require_signatures: false,
         authorized_keys: Vec::new(),
     };
@@ -355,6 +358,7 @@ async fn test_integration_full_stack_availability() {
         snapshot_interval: 100,
         max_log_size: 1_000_000,
         enable_compaction: false,
+        enable_wal: true,
         require_signatures: false,
         authorized_keys: Vec::new(),
     };
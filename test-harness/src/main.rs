@@ -1,12 +1,21 @@
 //! HTTP Test Harness binary
 //!
-//! Simple placeholder until the HTTP API module dependency issues are resolved.
+//! Serves the `core_mvp::test_harness` HTTP API. Each simulated user added
+//! with `POST /users/:user/init` gets a channel manager of their own, and
+//! the harness relays commits and messages between them, so multi-party
+//! flows like member removal can be driven with curl; see
+//! `spacepanda-core/src/core_mvp/test_harness/tests/test_member_removal.sh`.
 //!
-//! For now, you can test the member removal feature using the integration tests:
-//!   nix develop --command cargo test four_party
+//! With `--scenario <file.yaml>` it runs a scripted scenario instead of
+//! serving, prints a pass/fail report and writes a JSON transcript of every
+//! envelope the actors exchanged; see `scenarios/` for examples.
 
-use anyhow::Result;
+mod scenario;
+
+use anyhow::{Context, Result};
 use clap::Parser;
+use spacepanda_core::core_mvp::test_harness;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser, Debug)]
 #[command(name = "test-harness")]
@@ -19,27 +28,69 @@ struct Args {
     /// Host to bind to
     #[arg(long, default_value = "127.0.0.1")]
     host: String,
+
+    /// Run a scenario file instead of serving
+    #[arg(long)]
+    scenario: Option<PathBuf>,
+
+    /// Where to write the scenario transcript, `-` for stdout
+    /// (default: next to the scenario, as `<name>.transcript.json`)
+    #[arg(long, requires = "scenario")]
+    transcript: Option<PathBuf>,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
+    tracing_subscriber::fmt().with_env_filter("info").init();
+
+    if let Some(path) = &args.scenario {
+        return run_scenario(path, args.transcript.as_deref()).await;
+    }
 
     println!("🐼 SpacePanda HTTP Test Harness");
-    println!("Address: {}:{}", args.host, args.port);
-    println!();
-    println!("NOTE: The HTTP server is not yet fully wired up due to module visibility issues.");
-    println!();
-    println!("To test the member removal feature, use the integration tests instead:");
-    println!("  cd spacepanda-core");
-    println!("  nix develop --command cargo test --lib four_party -- --nocapture");
-    println!();
-    println!("This will run the test_four_party_member_removal() test which demonstrates:");
-    println!("  - Creating a 4-person MLS group");
-    println!("  - All members sending/receiving messages");
-    println!("  - Removing a member from the group");
-    println!("  - Verifying the removed member can't decrypt new messages");
-    println!("  - Verifying remaining members continue to communicate");
+    println!("Address: http://{}:{}", args.host, args.port);
+
+    test_harness::start_server(&args.host, args.port).await
+}
+
+async fn run_scenario(path: &Path, transcript: Option<&Path>) -> Result<()> {
+    let report = scenario::run_file(path).await?;
+
+    for envelope in &report.envelopes {
+        let mark = match envelope.outcome {
+            scenario::Outcome::Rejected => '✗',
+            scenario::Outcome::Pending => '·',
+            _ => '✓',
+        };
+        println!(
+            "  {} #{} step {}: {:?} {} → {} in {} ({:?})",
+            mark,
+            envelope.seq,
+            envelope.step,
+            envelope.kind,
+            envelope.from,
+            envelope.to,
+            envelope.channel,
+            envelope.outcome
+        );
+    }
+    println!("{}", report);
+
+    let json = serde_json::to_string_pretty(&report)?;
+    let transcript = transcript
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| path.with_extension("transcript.json"));
+    if transcript == Path::new("-") {
+        println!("{}", json);
+    } else {
+        std::fs::write(&transcript, json)
+            .with_context(|| format!("Failed to write transcript {}", transcript.display()))?;
+        println!("Transcript: {}", transcript.display());
+    }
 
+    if !report.succeeded() {
+        std::process::exit(1);
+    }
     Ok(())
 }
@@ -0,0 +1,713 @@
+//! Scripted protocol scenarios
+//!
+//! A scenario is a YAML file naming some actors and a list of steps for
+//! them: creating channels, inviting, joining, sending, removing members,
+//! and assertions about who could read what. Each actor gets an in-process
+//! `ChannelManager`, and the runner stands in for the network between them.
+//! Everything one actor sends another is an envelope; the transcript of
+//! envelopes is kept for the report.
+//!
+//! With `delivery: auto` (the default) envelopes are handed over after each
+//! step, oldest first, to every actor who has joined the channel and isn't
+//! cut off by a partition. With `delivery: manual` nothing moves until a
+//! `deliver` step, which makes orderings like a commit overtaking a message
+//! easy to script. Welcomes are only ever taken by `join`, so a welcome can
+//! be left waiting while the channel moves on.
+//!
+//! ```yaml
+//! name: Two friends
+//! actors: [alice, bob]
+//! steps:
+//!   - { action: create_channel, actor: alice, channel: room }
+//!   - { action: invite, actor: alice, channel: room, invitee: bob }
+//!   - { action: join, actor: bob, channel: room }
+//!   - { action: send, actor: alice, channel: room, text: hello }
+//!   - { action: assert_received, actor: bob, channel: room, text: hello }
+//! ```
+//!
+//! Any step can carry `expect_error: true` when it's supposed to fail.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use spacepanda_core::core_mvp::channel_manager::ChannelManager;
+use spacepanda_core::core_mvp::network::IncomingMessage;
+use spacepanda_core::core_mvp::test_harness::state::new_channel_manager;
+use spacepanda_core::core_mvp::types::InviteToken;
+use spacepanda_core::core_router::session_manager::PeerId;
+use spacepanda_core::core_store::model::types::{ChannelId, MessageId};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A scripted scenario
+#[derive(Debug, Clone, Deserialize)]
+pub struct Scenario {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub actors: Vec<String>,
+    #[serde(default)]
+    pub delivery: Delivery,
+    pub steps: Vec<Step>,
+}
+
+impl Scenario {
+    /// Read a scenario from a YAML file
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read scenario {}", path.display()))?;
+        serde_yaml::from_str(&contents)
+            .with_context(|| format!("Failed to parse scenario {}", path.display()))
+    }
+}
+
+/// When envelopes are handed to their recipients
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Delivery {
+    /// After every step
+    #[default]
+    Auto,
+    /// Only in `deliver` steps
+    Manual,
+}
+
+/// One step of a scenario
+#[derive(Debug, Clone, Deserialize)]
+pub struct Step {
+    #[serde(flatten)]
+    pub action: Action,
+    /// The step passes only if the action fails
+    #[serde(default)]
+    pub expect_error: bool,
+}
+
+/// What a step does
+///
+/// Channels are named by labels local to the scenario.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    CreateChannel {
+        actor: String,
+        channel: String,
+        #[serde(default)]
+        public: bool,
+    },
+    /// Add `invitee` to the channel; they get a welcome to `join` with
+    Invite {
+        actor: String,
+        channel: String,
+        invitee: String,
+    },
+    /// Join with the oldest welcome waiting for the actor
+    Join {
+        actor: String,
+        channel: String,
+    },
+    Send {
+        actor: String,
+        channel: String,
+        text: String,
+    },
+    Remove {
+        actor: String,
+        channel: String,
+        member: String,
+    },
+    /// Hand over waiting envelopes, optionally only those to one actor or
+    /// of one kind
+    Deliver {
+        #[serde(default)]
+        to: Option<String>,
+        #[serde(default)]
+        kind: Option<EnvelopeKind>,
+    },
+    /// Hold envelopes between actors in different groups until `heal`
+    ///
+    /// Actors not listed are cut off from everyone.
+    Partition {
+        groups: Vec<Vec<String>>,
+    },
+    Heal,
+    Delay {
+        ms: u64,
+    },
+    AssertReceived {
+        actor: String,
+        channel: String,
+        text: String,
+    },
+    AssertNotReceived {
+        actor: String,
+        channel: String,
+        text: String,
+    },
+    /// The actor can't decrypt the message with this text
+    AssertDecryptFails {
+        actor: String,
+        channel: String,
+        text: String,
+    },
+}
+
+impl Action {
+    /// The actor the step is about, if any
+    pub fn actor(&self) -> Option<&str> {
+        match self {
+            Action::CreateChannel { actor, .. }
+            | Action::Invite { actor, .. }
+            | Action::Join { actor, .. }
+            | Action::Send { actor, .. }
+            | Action::Remove { actor, .. }
+            | Action::AssertReceived { actor, .. }
+            | Action::AssertNotReceived { actor, .. }
+            | Action::AssertDecryptFails { actor, .. } => Some(actor),
+            Action::Deliver { to, .. } => to.as_deref(),
+            Action::Partition { .. } | Action::Heal | Action::Delay { .. } => None,
+        }
+    }
+
+    /// The action's name as written in scenarios
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::CreateChannel { .. } => "create_channel",
+            Action::Invite { .. } => "invite",
+            Action::Join { .. } => "join",
+            Action::Send { .. } => "send",
+            Action::Remove { .. } => "remove",
+            Action::Deliver { .. } => "deliver",
+            Action::Partition { .. } => "partition",
+            Action::Heal => "heal",
+            Action::Delay { .. } => "delay",
+            Action::AssertReceived { .. } => "assert_received",
+            Action::AssertNotReceived { .. } => "assert_not_received",
+            Action::AssertDecryptFails { .. } => "assert_decrypt_fails",
+        }
+    }
+}
+
+/// What an envelope carries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnvelopeKind {
+    Welcome,
+    Commit,
+    Message,
+}
+
+/// What became of an envelope
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// Not handed over yet
+    Pending,
+    /// A welcome used to join
+    Joined,
+    /// A commit the recipient applied
+    Applied,
+    Decrypted,
+    /// A message the recipient couldn't decrypt yet and kept for later
+    Queued,
+    Rejected,
+}
+
+/// Something one actor sent another
+#[derive(Debug, Clone, Serialize)]
+pub struct Envelope {
+    pub seq: usize,
+    /// The step that sent it, counting from 1
+    pub step: usize,
+    pub kind: EnvelopeKind,
+    pub channel: String,
+    pub from: String,
+    pub to: String,
+    pub size: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    pub outcome: Outcome,
+    /// The step that handed it over
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delivered_at: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip)]
+    channel_id: ChannelId,
+    #[serde(skip)]
+    payload: Vec<u8>,
+}
+
+/// The step a scenario failed at
+#[derive(Debug, Clone, Serialize)]
+pub struct StepFailure {
+    /// Counting from 1
+    pub step: usize,
+    pub action: String,
+    pub actor: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for StepFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "step {} ({}", self.step, self.action)?;
+        if let Some(actor) = &self.actor {
+            write!(f, " by {}", actor)?;
+        }
+        write!(f, "): {}", self.message)
+    }
+}
+
+/// The result of running a scenario
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub scenario: String,
+    /// Steps that passed, before any failure
+    pub passed: usize,
+    pub total: usize,
+    pub failure: Option<StepFailure>,
+    pub envelopes: Vec<Envelope>,
+}
+
+impl Report {
+    pub fn succeeded(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.failure {
+            None => write!(f, "PASS {} ({} steps", self.scenario, self.total)?,
+            Some(failure) => write!(
+                f,
+                "FAIL {} at {} ({} of {} steps passed",
+                self.scenario, failure, self.passed, self.total
+            )?,
+        }
+        write!(f, ", {} envelopes)", self.envelopes.len())
+    }
+}
+
+/// Runs a scenario against fresh channel managers
+pub struct Runner {
+    actors: BTreeMap<String, Arc<ChannelManager>>,
+    delivery: Delivery,
+    /// Channel labels to IDs
+    channels: HashMap<String, ChannelId>,
+    /// Everyone in each channel's group, including invitees yet to join
+    rosters: HashMap<String, BTreeSet<String>>,
+    /// (actor, channel label) pairs able to take envelopes
+    joined: HashSet<(String, String)>,
+    partition: Option<Vec<BTreeSet<String>>>,
+    envelopes: Vec<Envelope>,
+    data_dir: PathBuf,
+}
+
+impl Runner {
+    /// Set up a channel manager for each of the scenario's actors
+    pub fn new(scenario: &Scenario) -> Result<Self> {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let data_dir = std::env::temp_dir().join(format!(
+            "spacepanda_scenario_{}_{}",
+            std::process::id(),
+            nanos
+        ));
+
+        let mut actors = BTreeMap::new();
+        for name in &scenario.actors {
+            let manager = new_channel_manager(&data_dir.join(name), name)?;
+            if actors.insert(name.clone(), manager).is_some() {
+                bail!("Actor {} is listed twice", name);
+            }
+        }
+
+        Ok(Self {
+            actors,
+            delivery: scenario.delivery,
+            channels: HashMap::new(),
+            rosters: HashMap::new(),
+            joined: HashSet::new(),
+            partition: None,
+            envelopes: Vec::new(),
+            data_dir,
+        })
+    }
+
+    /// Run every step, stopping at the first that fails
+    pub async fn run(mut self, scenario: &Scenario) -> Report {
+        let mut failure = None;
+        let mut passed = 0;
+        for (index, step) in scenario.steps.iter().enumerate() {
+            let number = index + 1;
+            let result = self.execute(number, &step.action).await;
+            let result = match (result, step.expect_error) {
+                (Ok(()), false) | (Err(_), true) => Ok(()),
+                (Ok(()), true) => Err(anyhow!("expected an error, but the step succeeded")),
+                (Err(e), false) => Err(e),
+            };
+            if let Err(e) = result {
+                failure = Some(StepFailure {
+                    step: number,
+                    action: step.action.name().to_string(),
+                    actor: step.action.actor().map(str::to_string),
+                    message: format!("{:#}", e),
+                });
+                break;
+            }
+            if self.delivery == Delivery::Auto {
+                self.deliver(number, None, None).await;
+            }
+            passed += 1;
+        }
+
+        Report {
+            scenario: scenario.name.clone(),
+            passed,
+            total: scenario.steps.len(),
+            failure,
+            envelopes: std::mem::take(&mut self.envelopes),
+        }
+    }
+
+    async fn execute(&mut self, step: usize, action: &Action) -> Result<()> {
+        match action {
+            Action::CreateChannel { actor, channel, public } => {
+                if self.channels.contains_key(channel) {
+                    bail!("Channel {} already exists", channel);
+                }
+                let id = self.actor(actor)?.create_channel(channel.clone(), *public).await?;
+                self.channels.insert(channel.clone(), id);
+                self.rosters.insert(channel.clone(), BTreeSet::from([actor.clone()]));
+                self.joined.insert((actor.clone(), channel.clone()));
+            }
+            Action::Invite { actor, channel, invitee } => {
+                let id = self.channel(channel)?;
+                let key_package = self.actor(invitee)?.generate_key_package().await?;
+                let (invite, commit) = self.actor(actor)?.create_invite(&id, key_package).await?;
+                if let Some(commit) = commit {
+                    self.broadcast(step, EnvelopeKind::Commit, channel, actor, commit, None);
+                }
+                let welcome = serde_json::to_vec(&invite)?;
+                self.send(step, EnvelopeKind::Welcome, channel, actor, invitee, welcome, None);
+                self.rosters.entry(channel.clone()).or_default().insert(invitee.clone());
+            }
+            Action::Join { actor, channel } => {
+                let manager = self.actor(actor)?;
+                let index = self
+                    .envelopes
+                    .iter()
+                    .position(|e| {
+                        e.kind == EnvelopeKind::Welcome
+                            && e.to == *actor
+                            && e.channel == *channel
+                            && e.outcome == Outcome::Pending
+                    })
+                    .ok_or_else(|| anyhow!("No welcome to {} is waiting for {}", channel, actor))?;
+                let invite: InviteToken = serde_json::from_slice(&self.envelopes[index].payload)?;
+
+                let joined = manager.join_channel(&invite).await;
+                let envelope = &mut self.envelopes[index];
+                envelope.delivered_at = Some(step);
+                match joined {
+                    Ok(_) => envelope.outcome = Outcome::Joined,
+                    Err(e) => {
+                        envelope.outcome = Outcome::Rejected;
+                        envelope.error = Some(e.to_string());
+                        return Err(e.into());
+                    }
+                }
+                self.joined.insert((actor.clone(), channel.clone()));
+            }
+            Action::Send { actor, channel, text } => {
+                let id = self.channel(channel)?;
+                let ciphertext = self.actor(actor)?.send_message(&id, text.as_bytes()).await?;
+                let text = Some(text.clone());
+                self.broadcast(step, EnvelopeKind::Message, channel, actor, ciphertext, text);
+            }
+            Action::Remove { actor, channel, member } => {
+                let id = self.channel(channel)?;
+                let identity = self.actor(member)?.identity().user_id.0.clone();
+                let commit = self.actor(actor)?.remove_member(&id, identity.as_bytes()).await?;
+                self.rosters.entry(channel.clone()).or_default().remove(member);
+                self.broadcast(step, EnvelopeKind::Commit, channel, actor, commit, None);
+            }
+            Action::Deliver { to, kind } => {
+                if let Some(to) = to {
+                    self.actor(to)?;
+                }
+                if self.deliver(step, to.as_deref(), *kind).await == 0 {
+                    bail!("Nothing could be delivered");
+                }
+            }
+            Action::Partition { groups } => {
+                for name in groups.iter().flatten() {
+                    self.actor(name)?;
+                }
+                let groups = groups.iter().map(|group| group.iter().cloned().collect()).collect();
+                self.partition = Some(groups);
+            }
+            Action::Heal => self.partition = None,
+            Action::Delay { ms } => tokio::time::sleep(Duration::from_millis(*ms)).await,
+            Action::AssertReceived { actor, channel, text } => {
+                if !self.has_received(actor, channel, text).await? {
+                    bail!("{} hasn't received {:?}", actor, text);
+                }
+            }
+            Action::AssertNotReceived { actor, channel, text } => {
+                if self.has_received(actor, channel, text).await? {
+                    bail!("{} received {:?}", actor, text);
+                }
+            }
+            Action::AssertDecryptFails { actor, channel, text } => {
+                let manager = self.actor(actor)?;
+                let sent = self
+                    .envelopes
+                    .iter()
+                    .rposition(|e| e.channel == *channel && e.text.as_ref() == Some(text))
+                    .ok_or_else(|| anyhow!("No message {:?} was sent in {}", text, channel))?;
+                let seq = self.envelopes[sent].seq;
+                let payload = self.envelopes[sent].payload.clone();
+
+                // Messages handed over already say how that went; others
+                // are tried now
+                let delivered = self
+                    .envelopes
+                    .iter()
+                    .find(|e| e.seq == seq && e.to == *actor && e.outcome != Outcome::Pending);
+                match delivered {
+                    Some(e) if e.outcome == Outcome::Decrypted => {
+                        bail!("{} decrypted {:?}", actor, text)
+                    }
+                    Some(_) => {}
+                    None => {
+                        if manager.receive_message(&payload).await.is_ok() {
+                            bail!("{} decrypted {:?}", actor, text);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Hand over waiting envelopes, oldest first; returns how many
+    async fn deliver(
+        &mut self,
+        step: usize,
+        to: Option<&str>,
+        kind: Option<EnvelopeKind>,
+    ) -> usize {
+        let mut delivered = 0;
+        for index in 0..self.envelopes.len() {
+            let envelope = &self.envelopes[index];
+            let wanted = to.map_or(true, |to| envelope.to == to)
+                && kind.map_or(true, |kind| envelope.kind == kind);
+            if !wanted || !self.deliverable(envelope) {
+                continue;
+            }
+            self.hand_over(step, index).await;
+            delivered += 1;
+        }
+        delivered
+    }
+
+    fn deliverable(&self, envelope: &Envelope) -> bool {
+        envelope.outcome == Outcome::Pending
+            && envelope.kind != EnvelopeKind::Welcome
+            && self.joined.contains(&(envelope.to.clone(), envelope.channel.clone()))
+            && self.connected(&envelope.from, &envelope.to)
+    }
+
+    fn connected(&self, from: &str, to: &str) -> bool {
+        match &self.partition {
+            None => true,
+            Some(groups) => groups.iter().any(|group| group.contains(from) && group.contains(to)),
+        }
+    }
+
+    async fn hand_over(&mut self, step: usize, index: usize) {
+        let envelope = &self.envelopes[index];
+        let manager = self.actors[&envelope.to].clone();
+        let channel_id = envelope.channel_id.clone();
+        let (outcome, error) = match envelope.kind {
+            EnvelopeKind::Commit => match manager.process_commit(&envelope.payload).await {
+                Ok(()) => (Outcome::Applied, None),
+                Err(e) => (Outcome::Rejected, Some(e.to_string())),
+            },
+            EnvelopeKind::Message => {
+                let sender_id = self.actors[&envelope.from].identity().user_id.clone();
+                let incoming = IncomingMessage {
+                    channel_id: channel_id.clone(),
+                    ciphertext: envelope.payload.clone(),
+                    sender_peer_id: PeerId::from_bytes(sender_id.0.as_bytes().to_vec()),
+                    sender_id,
+                };
+                match manager.handle_incoming_message(incoming).await {
+                    Ok(Some(_)) => (Outcome::Decrypted, None),
+                    Ok(None) => (Outcome::Queued, None),
+                    Err(e) => (Outcome::Rejected, Some(e.to_string())),
+                }
+            }
+            EnvelopeKind::Welcome => return,
+        };
+
+        let to = envelope.to.clone();
+        let envelope = &mut self.envelopes[index];
+        envelope.outcome = outcome;
+        envelope.error = error;
+        envelope.delivered_at = Some(step);
+
+        // A new epoch may unlock messages that arrived ahead of its commit
+        if outcome == Outcome::Applied {
+            if let Ok(messages) = manager.process_pending(&channel_id).await {
+                for message in messages {
+                    let unlocked = self.envelopes.iter_mut().find(|e| {
+                        e.to == to
+                            && e.outcome == Outcome::Queued
+                            && e.message_id.as_ref() == Some(&message.message_id.0)
+                    });
+                    if let Some(envelope) = unlocked {
+                        envelope.outcome = Outcome::Decrypted;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn has_received(&self, actor: &str, channel: &str, text: &str) -> Result<bool> {
+        let id = self.channel(channel)?;
+        let messages = self.actor(actor)?.get_messages(&id, 1000, None).await?;
+        Ok(messages.iter().any(|m| m.body_as_string().as_deref() == Some(text)))
+    }
+
+    /// Queue an envelope to every other member of the channel
+    fn broadcast(
+        &mut self,
+        step: usize,
+        kind: EnvelopeKind,
+        channel: &str,
+        from: &str,
+        payload: Vec<u8>,
+        text: Option<String>,
+    ) {
+        let recipients: Vec<_> =
+            self.rosters[channel].iter().filter(|m| *m != from).cloned().collect();
+        for to in recipients {
+            self.send(step, kind, channel, from, &to, payload.clone(), text.clone());
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn send(
+        &mut self,
+        step: usize,
+        kind: EnvelopeKind,
+        channel: &str,
+        from: &str,
+        to: &str,
+        payload: Vec<u8>,
+        text: Option<String>,
+    ) {
+        let message_id =
+            (kind == EnvelopeKind::Message).then(|| MessageId::from_ciphertext(&payload).0);
+        self.envelopes.push(Envelope {
+            seq: self.envelopes.len() + 1,
+            step,
+            kind,
+            channel: channel.to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+            size: payload.len(),
+            message_id,
+            text,
+            outcome: Outcome::Pending,
+            delivered_at: None,
+            error: None,
+            channel_id: self.channels[channel].clone(),
+            payload,
+        });
+    }
+
+    fn actor(&self, name: &str) -> Result<Arc<ChannelManager>> {
+        self.actors.get(name).cloned().ok_or_else(|| anyhow!("Unknown actor {}", name))
+    }
+
+    fn channel(&self, label: &str) -> Result<ChannelId> {
+        self.channels
+            .get(label)
+            .cloned()
+            .ok_or_else(|| anyhow!("Unknown channel {}", label))
+    }
+}
+
+impl Drop for Runner {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.data_dir);
+    }
+}
+
+/// Run the scenario in `path`
+pub async fn run_file(path: &Path) -> Result<Report> {
+    let scenario = Scenario::load(path)?;
+    let runner = Runner::new(&scenario)?;
+    Ok(runner.run(&scenario).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scenarios_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("scenarios")
+    }
+
+    #[tokio::test]
+    async fn test_example_scenarios_pass() {
+        let mut paths: Vec<_> = std::fs::read_dir(scenarios_dir())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "yaml"))
+            .collect();
+        paths.sort();
+        assert!(paths.len() >= 3);
+
+        for path in paths {
+            let report = run_file(&path).await.unwrap();
+            assert!(report.succeeded(), "{}: {}", path.display(), report);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failures_name_the_step_and_actor() {
+        let scenario: Scenario = serde_yaml::from_str(
+            r#"
+name: Wrong expectation
+actors: [alice, bob]
+steps:
+  - { action: create_channel, actor: alice, channel: room }
+  - { action: send, actor: alice, channel: room, text: early }
+  - { action: invite, actor: alice, channel: room, invitee: bob }
+  - { action: join, actor: bob, channel: room }
+  - { action: assert_received, actor: bob, channel: room, text: early }
+"#,
+        )
+        .unwrap();
+        let report = Runner::new(&scenario).unwrap().run(&scenario).await;
+
+        // Bob was invited after the message was sent, so never got it
+        let failure = report.failure.as_ref().unwrap();
+        assert_eq!(failure.step, 5);
+        assert_eq!(failure.action, "assert_received");
+        assert_eq!(failure.actor.as_deref(), Some("bob"));
+        assert_eq!(report.passed, 4);
+
+        assert!(report.envelopes.iter().all(|e| e.kind != EnvelopeKind::Message));
+    }
+}